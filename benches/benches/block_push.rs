@@ -0,0 +1,48 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+
+use nimiq_block::Block;
+use nimiq_block_production::test_utils::TemporaryBlockProducer;
+use nimiq_blockchain::PushResult;
+use nimiq_primitives::policy;
+
+/// Pushes a single micro block on top of the genesis block.
+fn push_micro_block(c: &mut Criterion) {
+    c.bench_function("block_push/micro", |b| {
+        b.iter_batched(
+            || {
+                let producer = TemporaryBlockProducer::new();
+                let block = producer.next_block_no_push(0, vec![]);
+                (producer, block)
+            },
+            |(producer, block)| assert_eq!(producer.push(block), Ok(PushResult::Extended)),
+            BatchSize::PerIteration,
+        )
+    });
+}
+
+/// Pushes a full batch, i.e. its micro blocks and the closing macro block.
+fn push_batch(c: &mut Criterion) {
+    let producer = TemporaryBlockProducer::new();
+    let blocks: Vec<Block> = (0..policy::BATCH_LENGTH)
+        .map(|_| producer.next_block(0, vec![]))
+        .collect();
+
+    c.bench_function("block_push/batch", |b| {
+        b.iter_batched(
+            TemporaryBlockProducer::new,
+            |producer| {
+                for block in blocks.iter().cloned() {
+                    assert_eq!(producer.push(block), Ok(PushResult::Extended));
+                }
+            },
+            BatchSize::PerIteration,
+        )
+    });
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(10);
+    targets = push_micro_block, push_batch
+}
+criterion_main!(benches);
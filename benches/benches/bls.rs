@@ -0,0 +1,51 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use nimiq_bls::{AggregatePublicKey, AggregateSignature, KeyPair, PublicKey, Signature};
+use nimiq_keys::SecureGenerate;
+use nimiq_primitives::policy;
+
+/// The message signed by all validators, like a vote on a macro block.
+const MESSAGE: &str = "Albatross macro block proposal";
+
+/// Signs the message with as many keys as are needed for a macro block justification.
+fn setup() -> (Vec<PublicKey>, Vec<Signature>) {
+    let mut rng = StdRng::seed_from_u64(0);
+    (0..policy::TWO_F_PLUS_ONE)
+        .map(|_| {
+            let key_pair = KeyPair::generate(&mut rng);
+            (key_pair.public_key, key_pair.sign(&MESSAGE))
+        })
+        .unzip()
+}
+
+/// Aggregates the signatures and the public keys of the signers.
+fn aggregate(c: &mut Criterion) {
+    let (public_keys, signatures) = setup();
+
+    c.bench_function("bls/aggregate_signatures", |b| {
+        b.iter(|| AggregateSignature::from_signatures(&signatures))
+    });
+    c.bench_function("bls/aggregate_public_keys", |b| {
+        b.iter(|| AggregatePublicKey::from_public_keys(&public_keys))
+    });
+}
+
+/// Verifies an aggregate signature against the aggregate public key.
+fn verify_aggregate(c: &mut Criterion) {
+    let (public_keys, signatures) = setup();
+    let signature = AggregateSignature::from_signatures(&signatures);
+    let public_key = AggregatePublicKey::from_public_keys(&public_keys);
+
+    c.bench_function("bls/verify_aggregate", |b| {
+        b.iter(|| assert!(public_key.verify(&MESSAGE, &signature)))
+    });
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(20);
+    targets = aggregate, verify_aggregate
+}
+criterion_main!(benches);
@@ -0,0 +1,147 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use beserial::{Deserialize, Serialize};
+use nimiq_blockchain::{
+    ExtTxData, ExtendedTransaction, HistoryStore, HistoryTreeChunk, CHUNK_SIZE,
+};
+use nimiq_consensus::messages::{HistoryChunk, ResponseStatus};
+use nimiq_database::volatile::VolatileEnvironment;
+use nimiq_database::{Environment, ReadTransaction, WriteTransaction};
+use nimiq_keys::Address;
+use nimiq_network_interface::message::Message;
+use nimiq_primitives::coin::Coin;
+use nimiq_primitives::networks::NetworkId;
+use nimiq_primitives::policy;
+use nimiq_transaction::Transaction;
+
+/// The number of chunks in the history of the epoch.
+const NUM_CHUNKS: usize = 4;
+
+fn ext_tx(value: u64) -> ExtendedTransaction {
+    ExtendedTransaction {
+        network_id: NetworkId::UnitAlbatross,
+        block_number: 1,
+        block_time: 0,
+        data: ExtTxData::Basic(Transaction::new_basic(
+            Address::from([1u8; 20]),
+            Address::from([2u8; 20]),
+            Coin::from_u64_unchecked(value),
+            Coin::ZERO,
+            0,
+            NetworkId::UnitAlbatross,
+        )),
+    }
+}
+
+/// Creates a history store with the given number of chunks in the first epoch.
+fn setup() -> (Environment, HistoryStore) {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let history_store = HistoryStore::new(env.clone());
+
+    let ext_txs: Vec<_> = (0..(NUM_CHUNKS * CHUNK_SIZE) as u64).map(ext_tx).collect();
+    let mut txn = WriteTransaction::new(&env);
+    history_store.add_to_history(&mut txn, 1, &ext_txs);
+    txn.commit();
+
+    (env, history_store)
+}
+
+/// Proves a chunk of the history, as done when serving a syncing peer.
+fn prove_chunk(c: &mut Criterion) {
+    let (env, history_store) = setup();
+    let verifier_block_number = policy::first_block_of(1);
+
+    c.bench_function("history/prove_chunk", |b| {
+        b.iter(|| {
+            let txn = ReadTransaction::new(&env);
+            history_store
+                .prove_chunk(1, verifier_block_number, CHUNK_SIZE, 1, Some(&txn))
+                .unwrap()
+        })
+    });
+}
+
+/// Verifies a chunk of the history against the history root, as done when syncing.
+fn verify_chunk(c: &mut Criterion) {
+    let (env, history_store) = setup();
+    let verifier_block_number = policy::first_block_of(1);
+
+    let txn = ReadTransaction::new(&env);
+    let root = history_store.get_history_tree_root(1, Some(&txn)).unwrap();
+    let chunk = history_store
+        .prove_chunk(1, verifier_block_number, CHUNK_SIZE, 1, Some(&txn))
+        .unwrap();
+
+    c.bench_function("history/verify_chunk", |b| {
+        b.iter(|| assert_eq!(chunk.verify(root.clone(), CHUNK_SIZE), Some(true)))
+    });
+}
+
+/// Builds the response to a request for a cached chunk and encodes it for the wire, once by
+/// deserializing the cached chunk and once by sending it in serialized form.
+fn serve_cached_chunk(c: &mut Criterion) {
+    let (env, history_store) = setup();
+    let verifier_block_number = policy::first_block_of(1);
+
+    let txn = ReadTransaction::new(&env);
+    let cached = history_store
+        .prove_chunk(1, verifier_block_number, CHUNK_SIZE, 1, Some(&txn))
+        .unwrap()
+        .serialize_to_vec();
+
+    fn encode<M: Message>(response: M) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(response.serialized_message_size());
+        response.serialize_message(&mut buf).unwrap();
+        buf
+    }
+
+    c.bench_function("history/serve_cached_chunk/deserialized", |b| {
+        b.iter(|| {
+            encode(HistoryChunk {
+                chunk: Some(HistoryTreeChunk::deserialize_from_vec(&cached).unwrap()),
+                status: ResponseStatus::Ok,
+                request_identifier: 1,
+            })
+        })
+    });
+
+    c.bench_function("history/serve_cached_chunk/serialized", |b| {
+        b.iter(|| {
+            encode(HistoryChunk::serialized(
+                Some(&cached),
+                ResponseStatus::Ok,
+                1,
+            ))
+        })
+    });
+}
+
+/// Deserializes a received history chunk response, as done when syncing.
+fn receive_chunk(c: &mut Criterion) {
+    let (env, history_store) = setup();
+    let verifier_block_number = policy::first_block_of(1);
+
+    let txn = ReadTransaction::new(&env);
+    let chunk = history_store
+        .prove_chunk(1, verifier_block_number, CHUNK_SIZE, 1, Some(&txn))
+        .unwrap();
+    let data = HistoryChunk {
+        chunk: Some(chunk),
+        status: ResponseStatus::Ok,
+        request_identifier: 1,
+    }
+    .serialize_to_bytes();
+
+    c.bench_function("history/receive_chunk", |b| {
+        b.iter(|| HistoryChunk::deserialize_from_bytes(data.clone()).unwrap())
+    });
+}
+
+criterion_group!(
+    benches,
+    prove_chunk,
+    verify_chunk,
+    serve_cached_chunk,
+    receive_chunk
+);
+criterion_main!(benches);
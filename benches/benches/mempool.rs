@@ -0,0 +1,155 @@
+use std::sync::Arc;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use tokio::runtime::Runtime;
+
+use nimiq_block::{Block, MicroBlock, MicroBody, MicroHeader};
+use nimiq_blockchain::{Blockchain, BlockchainLock};
+use nimiq_bls::KeyPair as BlsKeyPair;
+use nimiq_build_tools::genesis::GenesisBuilder;
+use nimiq_database::volatile::VolatileEnvironment;
+use nimiq_hash::Blake2bHash;
+use nimiq_keys::{
+    Address, KeyPair as SchnorrKeyPair, PublicKey as SchnorrPublicKey, SecureGenerate,
+};
+use nimiq_mempool::config::MempoolConfig;
+use nimiq_mempool::mempool::Mempool;
+use nimiq_primitives::networks::NetworkId;
+use nimiq_test_utils::test_transaction::{
+    generate_accounts, generate_transactions, TestTransaction,
+};
+use nimiq_transaction::Transaction;
+use nimiq_utils::time::OffsetTime;
+use nimiq_vrf::VrfSeed;
+
+/// The number of transactions in the mempool, each one from a different sender.
+const NUM_TXNS: usize = 1_000;
+
+/// Creates a blockchain whose genesis funds the senders of the returned transactions.
+fn setup() -> (Arc<BlockchainLock>, Vec<Transaction>) {
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut genesis_builder = GenesisBuilder::default();
+
+    let recipients = generate_accounts(vec![0; 1], &mut genesis_builder, false);
+    let senders = generate_accounts(vec![10_000; NUM_TXNS], &mut genesis_builder, true);
+
+    let mempool_transactions = senders
+        .into_iter()
+        .map(|sender| TestTransaction {
+            fee: 0,
+            value: 10,
+            sender,
+            recipient: recipients[0].clone(),
+        })
+        .collect();
+    let (txns, _) = generate_transactions(mempool_transactions, true);
+
+    genesis_builder.with_genesis_validator(
+        Address::from(&SchnorrKeyPair::generate(&mut rng)),
+        SchnorrPublicKey::from([0u8; 32]),
+        BlsKeyPair::generate(&mut rng).public_key,
+        Address::default(),
+    );
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let genesis_info = genesis_builder.generate(env.clone()).unwrap();
+    let blockchain = Blockchain::with_genesis(
+        env,
+        Arc::new(OffsetTime::new()),
+        NetworkId::UnitAlbatross,
+        genesis_info.block,
+        genesis_info.accounts,
+    )
+    .unwrap();
+
+    (Arc::new(BlockchainLock::new(blockchain)), txns)
+}
+
+/// A block including the given transactions. The mempool only looks at the transactions of
+/// adopted blocks, so the rest of the block doesn't need to be valid.
+fn block_with(transactions: Vec<Transaction>) -> Block {
+    Block::Micro(MicroBlock {
+        header: MicroHeader {
+            version: 0,
+            block_number: 1,
+            view_number: 0,
+            timestamp: 0,
+            parent_hash: Blake2bHash::default(),
+            seed: VrfSeed::default(),
+            extra_data: vec![],
+            state_root: Blake2bHash::default(),
+            body_root: Blake2bHash::default(),
+            history_root: Blake2bHash::default(),
+        },
+        body: Some(MicroBody {
+            fork_proofs: vec![],
+            transactions,
+        }),
+        justification: None,
+    })
+}
+
+fn filled_mempool(
+    runtime: &Runtime,
+    blockchain: &Arc<BlockchainLock>,
+    txns: &[Transaction],
+) -> Mempool {
+    let mempool = Mempool::new(Arc::clone(blockchain), MempoolConfig::default());
+    runtime.block_on(async {
+        for txn in txns {
+            mempool.add_transaction(txn.clone()).await.unwrap();
+        }
+    });
+    mempool
+}
+
+/// Verifies and adds the transactions to an empty mempool.
+fn put(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let (blockchain, txns) = setup();
+
+    c.bench_function("mempool/put", |b| {
+        b.iter_batched(
+            || Mempool::new(Arc::clone(&blockchain), MempoolConfig::default()),
+            |mempool| {
+                runtime.block_on(async {
+                    for txn in &txns {
+                        mempool.add_transaction(txn.clone()).await.unwrap();
+                    }
+                });
+                mempool
+            },
+            BatchSize::PerIteration,
+        )
+    });
+}
+
+/// Removes the transactions from a full mempool because a block included them.
+fn remove(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let (blockchain, txns) = setup();
+
+    let block = block_with(txns.clone());
+    let adopted_blocks = vec![(block.hash(), block)];
+
+    c.bench_function("mempool/remove", |b| {
+        b.iter_batched(
+            || filled_mempool(&runtime, &blockchain, &txns),
+            |mempool| {
+                mempool.mempool_update(&adopted_blocks, &[]);
+                assert_eq!(mempool.num_transactions(), 0);
+                mempool
+            },
+            BatchSize::PerIteration,
+        )
+    });
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(10);
+    targets = put, remove
+}
+criterion_main!(benches);
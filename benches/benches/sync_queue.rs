@@ -0,0 +1,71 @@
+use std::sync::{Arc, Weak};
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use futures::future::{self, BoxFuture, FutureExt};
+use futures::StreamExt;
+use tokio::runtime::Runtime;
+
+use nimiq_consensus::consensus_agent::ConsensusAgent;
+use nimiq_consensus::sync::sync_queue::SyncQueue;
+use nimiq_network_interface::network::Network;
+use nimiq_network_interface::peer::Peer;
+use nimiq_network_mock::{MockHub, MockPeer};
+
+/// The number of items requested through the queue.
+const NUM_IDS: u32 = 10_000;
+/// The number of peers the items are requested from.
+const NUM_PEERS: usize = 8;
+/// The number of requests that are pending at the same time.
+const DESIRED_PENDING_SIZE: usize = 100;
+
+/// Answers requests instantly, so only the overhead of the queue is measured.
+fn request(id: u32, _agent: Weak<ConsensusAgent<MockPeer>>) -> BoxFuture<'static, Option<u32>> {
+    future::ready(Some(id)).boxed()
+}
+
+/// Requests items from several peers and yields them in order.
+fn throughput(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+
+    let mut hub = MockHub::new();
+    let network = hub.new_network();
+    let peer_networks: Vec<_> = (0..NUM_PEERS).map(|_| hub.new_network()).collect();
+    for peer_network in &peer_networks {
+        network.dial_mock(peer_network);
+    }
+
+    // The agents listen for responses, which needs a runtime.
+    let agents: Vec<Arc<ConsensusAgent<MockPeer>>> = {
+        let _guard = runtime.enter();
+        network
+            .get_peers()
+            .into_iter()
+            .map(|peer| Arc::new(ConsensusAgent::new(peer)))
+            .collect()
+    };
+
+    c.bench_function("sync_queue/throughput", |b| {
+        b.iter_batched(
+            || {
+                let mut queue = SyncQueue::new(
+                    (0..NUM_IDS).collect(),
+                    vec![],
+                    DESIRED_PENDING_SIZE,
+                    request,
+                );
+                for agent in &agents {
+                    queue.add_peer(agent.peer.id(), Arc::downgrade(agent));
+                }
+                queue
+            },
+            |queue| {
+                let items = runtime.block_on(queue.collect::<Vec<_>>());
+                assert_eq!(items.len(), NUM_IDS as usize);
+            },
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+criterion_group!(benches, throughput);
+criterion_main!(benches);
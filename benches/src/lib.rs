@@ -0,0 +1,15 @@
+//! Benchmarks of the performance critical paths of the node.
+//!
+//! The benchmarks are only built with the `benchmarks` feature:
+//!
+//! ```text
+//! cargo bench -p nimiq-benches --features benchmarks
+//! ```
+//!
+//! Criterion writes its estimates to `target/criterion`. The `nimiq-bench-baseline` tool turns them
+//! into a machine-readable baseline and compares later runs against it:
+//!
+//! ```text
+//! nimiq-bench-baseline save baseline.json
+//! nimiq-bench-baseline compare baseline.json --threshold 10
+//! ```
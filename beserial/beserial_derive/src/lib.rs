@@ -18,6 +18,9 @@
 //!   implementing `(De-)SerializeWithLength`
 //! - `#[beserial(len_type(X, limit = Y))]` same as `len_type` but allows to specify a custom
 //!   limit on the length during deserialization
+//! - `#[beserial(trailing)]` marks a field that was appended in a later version of the struct.
+//!   If the input ends before the field, `Default` is used instead. Trailing fields must come
+//!   after all other serialized fields.
 //!
 //! ## (De-)serializing enums
 //! Enums are a special case as they require a discriminant for each enum case.
@@ -76,6 +79,13 @@
 //! }
 //! # }
 //! ```
+//!
+//! ## Describing the serialization
+//! `#[derive(Schema)]` implements `beserial::Schema`, which describes the fields, length types,
+//! limits and discriminants of a type as they are serialized by the derived `Serialize`. It takes
+//! the same attributes as `Serialize` and `Deserialize`. The types of all serialized fields, and
+//! the type parameters of generic types, need to implement `Schema` as well. The type is named
+//! after its identifier, generic types include the names of their type parameters.
 
 extern crate proc_macro;
 
@@ -88,6 +98,7 @@ enum FieldAttribute {
     Skip(Option<syn::Lit>),
     LenType(syn::Ident, Option<usize>),
     Discriminant(u64),
+    Trailing,
 }
 
 #[inline]
@@ -178,6 +189,8 @@ fn parse_field_attribs(attrs: &[syn::Attribute]) -> Option<FieldAttribute> {
                                     return Some(FieldAttribute::Skip(None));
                                 } else if cmp_ident(path, "uvar") {
                                     return Some(FieldAttribute::Uvar);
+                                } else if cmp_ident(path, "trailing") {
+                                    return Some(FieldAttribute::Trailing);
                                 } else {
                                     panic!("unknown flag for beserial: {:?}", path)
                                 }
@@ -546,6 +559,15 @@ fn impl_deserialize_field(field: &syn::Field) -> TokenStream {
         (Some(ident), None) => {
             quote! { #ident: ::beserial::Deserialize::deserialize(reader)?, }
         }
+
+        // tuple field that might be missing at the end of the input
+        (None, Some(FieldAttribute::Trailing)) => {
+            quote! { ::beserial::deserialize_trailing(reader)?, }
+        }
+        // struct field that might be missing at the end of the input
+        (Some(ident), Some(FieldAttribute::Trailing)) => {
+            quote! { #ident: ::beserial::deserialize_trailing(reader)?, }
+        }
         (_, Some(FieldAttribute::Uvar)) => {
             panic!("beserial(uvar) attribute not allowed for struct fields")
         }
@@ -557,6 +579,23 @@ fn impl_deserialize_field(field: &syn::Field) -> TokenStream {
     }
 }
 
+/// Ensures that no regular field follows a `#[beserial(trailing)]` field.
+/// Otherwise a missing trailing field would shift all following fields.
+fn check_trailing_fields<'a>(fields: impl IntoIterator<Item = &'a syn::Field>) {
+    let mut seen_trailing = false;
+    for field in fields {
+        match parse_field_attribs(&field.attrs) {
+            Some(FieldAttribute::Trailing) => seen_trailing = true,
+            Some(FieldAttribute::Skip(_)) => {}
+            _ => {
+                if seen_trailing {
+                    panic!("beserial(trailing) fields must come after all other serialized fields")
+                }
+            }
+        }
+    }
+}
+
 fn impl_deserialize(ast: &syn::DeriveInput) -> TokenStream {
     let name = &ast.ident;
 
@@ -642,6 +681,7 @@ fn impl_deserialize(ast: &syn::DeriveInput) -> TokenStream {
                         match_cases.push(quote! { #discriminant => Ok(#name::#ident), });
                     }
                     syn::Fields::Named(ref fields) => {
+                        check_trailing_fields(fields.named.iter());
                         let mut field_cases = Vec::<TokenStream>::new();
                         for field in fields.named.iter() {
                             field_cases.push(impl_deserialize_field(field));
@@ -651,6 +691,7 @@ fn impl_deserialize(ast: &syn::DeriveInput) -> TokenStream {
                         );
                     }
                     syn::Fields::Unnamed(ref fields) => {
+                        check_trailing_fields(fields.unnamed.iter());
                         let mut field_cases = Vec::<TokenStream>::new();
                         for field in fields.unnamed.iter() {
                             field_cases.push(impl_deserialize_field(field));
@@ -682,6 +723,7 @@ fn impl_deserialize(ast: &syn::DeriveInput) -> TokenStream {
             }
         }
         Data::Struct(ref data_struct) => {
+            check_trailing_fields(data_struct.fields.iter());
             let mut tuple = false;
             let mut field_cases = Vec::<TokenStream>::new();
             for field in data_struct.fields.iter() {
@@ -718,3 +760,174 @@ fn impl_deserialize(ast: &syn::DeriveInput) -> TokenStream {
     };
     gen
 }
+
+#[proc_macro_derive(Schema, attributes(beserial))]
+pub fn derive_schema(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let ast = parse_macro_input!(input as DeriveInput);
+    proc_macro::TokenStream::from(impl_schema(&ast))
+}
+
+/// None means skip, otherwise returns the expression building the `FieldSchema`.
+fn impl_schema_field(field: &syn::Field) -> Option<TokenStream> {
+    let ty = &field.ty;
+    let name = match field.ident {
+        Some(ref ident) => {
+            let name = ident.to_string();
+            quote! { Some(#name) }
+        }
+        None => quote! { None },
+    };
+    let schema = quote! { ::beserial::FieldSchema::new::<#ty>(#name) };
+
+    match parse_field_attribs(&field.attrs) {
+        Some(FieldAttribute::Skip(_)) => None,
+        Some(FieldAttribute::LenType(len_type, limit)) => {
+            let len_type = len_type.to_string();
+            let limit = expr_from_limit(&limit);
+            Some(quote! { #schema.with_len_type(#len_type, #limit) })
+        }
+        Some(FieldAttribute::Trailing) => Some(quote! { #schema.trailing() }),
+        _ => Some(schema),
+    }
+}
+
+/// Returns the types of the fields that are serialized.
+fn schema_field_types<'a>(
+    fields: impl IntoIterator<Item = &'a syn::Field>,
+) -> impl Iterator<Item = &'a syn::Type> {
+    fields
+        .into_iter()
+        .filter(|field| {
+            !matches!(
+                parse_field_attribs(&field.attrs),
+                Some(FieldAttribute::Skip(_))
+            )
+        })
+        .map(|field| &field.ty)
+}
+
+fn impl_schema(ast: &syn::DeriveInput) -> TokenStream {
+    let name = &ast.ident;
+
+    // The type parameters need to implement `Schema` as well.
+    let mut generics = ast.generics.clone();
+    let type_params: Vec<Ident> = generics
+        .type_params()
+        .map(|param| param.ident.clone())
+        .collect();
+    {
+        let where_clause = generics.make_where_clause();
+        for param in &type_params {
+            where_clause
+                .predicates
+                .push(syn::parse_quote! { #param: ::beserial::Schema });
+        }
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    // Generic types are named after their type parameters, e.g. `SignedMessage<ViewChange>`.
+    let type_name = if type_params.is_empty() {
+        let type_name = name.to_string();
+        quote! { #type_name.to_string() }
+    } else {
+        let format = format!("{}<{}>", name, vec!["{}"; type_params.len()].join(", "));
+        quote! { format!(#format, #(<#type_params as ::beserial::Schema>::name()),*) }
+    };
+
+    let mut field_types = Vec::<&syn::Type>::new();
+    let body = match ast.data {
+        Data::Enum(ref enum_def) => {
+            let (enum_type, uvar) = parse_enum_attribs(ast);
+
+            let discriminant_type = if uvar {
+                "uvar".to_string()
+            } else {
+                enum_type
+                    .unwrap_or_else(|| {
+                        panic!(
+                            "Schema can not be derived for enum {} without repr(u*) or repr(i*)",
+                            name
+                        )
+                    })
+                    .to_string()
+            };
+
+            let mut variants = Vec::<TokenStream>::new();
+            if enum_has_data_attached(enum_def) {
+                // Same discriminants as in the serialization.
+                let mut discriminant = 0;
+                let mut first = true;
+                for variant in enum_def.variants.iter() {
+                    match parse_field_attribs(&variant.attrs) {
+                        Some(FieldAttribute::Discriminant(d)) => discriminant = d,
+                        _ => {
+                            if !first {
+                                discriminant += 1;
+                            }
+                        }
+                    };
+                    first = false;
+
+                    let variant_name = variant.ident.to_string();
+                    let fields = variant.fields.iter().filter_map(impl_schema_field);
+                    field_types.extend(schema_field_types(&variant.fields));
+                    variants.push(quote! {
+                        ::beserial::VariantSchema {
+                            name: #variant_name,
+                            discriminant: #discriminant,
+                            fields: vec![#(#fields),*],
+                        }
+                    });
+                }
+            } else {
+                // Enums that do not carry any data are serialized by casting them to an integer.
+                for variant in enum_def.variants.iter() {
+                    let variant_ident = &variant.ident;
+                    let variant_name = variant_ident.to_string();
+                    variants.push(quote! {
+                        ::beserial::VariantSchema {
+                            name: #variant_name,
+                            discriminant: #name::#variant_ident as u64,
+                            fields: vec![],
+                        }
+                    });
+                }
+            }
+
+            quote! {
+                ::beserial::TypeSchema::Enum {
+                    discriminant: #discriminant_type,
+                    variants: vec![#(#variants),*],
+                }
+            }
+        }
+        Data::Struct(ref data_struct) => {
+            let fields = data_struct.fields.iter().filter_map(impl_schema_field);
+            field_types.extend(schema_field_types(&data_struct.fields));
+            quote! {
+                ::beserial::TypeSchema::Struct {
+                    fields: vec![#(#fields),*],
+                }
+            }
+        }
+        Data::Union(_) => panic!("Schema can not be derived for Union {}", name),
+    };
+
+    quote! {
+        impl #impl_generics ::beserial::Schema for #name #ty_generics #where_clause {
+            fn name() -> String {
+                #type_name
+            }
+
+            fn schema() -> ::beserial::TypeSchema {
+                #body
+            }
+
+            fn collect(definitions: &mut ::beserial::schema::Definitions) {
+                if ::beserial::schema::define::<Self>(definitions) {
+                    #(<#field_types as ::beserial::Schema>::collect(definitions);)*
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,161 @@
+#[macro_use]
+extern crate beserial_derive;
+
+use beserial::schema::{skip, Definitions};
+use beserial::{Deserialize, FieldSchema, Schema, Serialize, TypeSchema, VariantSchema};
+
+#[allow(dead_code)]
+#[derive(Serialize, Deserialize, Schema)]
+struct TestStruct {
+    a: u32,
+    #[beserial(len_type(u16, limit = 8))]
+    b: Vec<u8>,
+    #[beserial(skip)]
+    c: bool,
+    #[beserial(trailing)]
+    d: u8,
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, Serialize, Deserialize, Schema)]
+#[repr(u8)]
+enum TestUnitEnum {
+    A = 1,
+    B = 5,
+}
+
+#[allow(dead_code)]
+#[derive(Serialize, Deserialize, Schema)]
+#[repr(u16)]
+enum TestDataEnum {
+    #[beserial(discriminant = 3)]
+    A(u8),
+    B {
+        #[beserial(len_type(u8))]
+        b: Vec<u64>,
+    },
+}
+
+#[test]
+fn it_describes_structs() {
+    assert_eq!(
+        TestStruct::schema(),
+        TypeSchema::Struct {
+            fields: vec![
+                FieldSchema::new::<u32>(Some("a")),
+                FieldSchema::new::<Vec<u8>>(Some("b")).with_len_type("u16", Some(8)),
+                FieldSchema::new::<u8>(Some("d")).trailing(),
+            ],
+        }
+    );
+}
+
+#[test]
+fn it_describes_enums() {
+    assert_eq!(
+        TestUnitEnum::schema(),
+        TypeSchema::Enum {
+            discriminant: "u8",
+            variants: vec![
+                VariantSchema {
+                    name: "A",
+                    discriminant: 1,
+                    fields: vec![],
+                },
+                VariantSchema {
+                    name: "B",
+                    discriminant: 5,
+                    fields: vec![],
+                },
+            ],
+        }
+    );
+
+    assert_eq!(
+        TestDataEnum::schema(),
+        TypeSchema::Enum {
+            discriminant: "u16",
+            variants: vec![
+                VariantSchema {
+                    name: "A",
+                    discriminant: 3,
+                    fields: vec![FieldSchema::new::<u8>(None)],
+                },
+                VariantSchema {
+                    name: "B",
+                    discriminant: 4,
+                    fields: vec![FieldSchema::new::<Vec<u64>>(Some("b")).with_len_type("u8", None)],
+                },
+            ],
+        }
+    );
+}
+
+#[allow(dead_code)]
+#[derive(Serialize, Deserialize, Schema)]
+struct TestGeneric<T: Serialize + Deserialize> {
+    item: T,
+    #[beserial(len_type(u8))]
+    items: Option<Vec<TestDataEnum>>,
+}
+
+fn collect<T: Schema>() -> Definitions {
+    let mut definitions = Definitions::new();
+    T::collect(&mut definitions);
+    definitions
+}
+
+#[test]
+fn it_expands_nested_types() {
+    assert_eq!(
+        TestGeneric::<TestUnitEnum>::name(),
+        "TestGeneric<TestUnitEnum>"
+    );
+
+    let definitions = collect::<TestGeneric<TestUnitEnum>>();
+    assert_eq!(
+        definitions.keys().collect::<Vec<_>>(),
+        vec![
+            "Option<Vec<TestDataEnum>>",
+            "TestDataEnum",
+            "TestGeneric<TestUnitEnum>",
+            "TestUnitEnum",
+            "Vec<TestDataEnum>",
+            "Vec<u64>",
+            "u64",
+            "u8",
+        ]
+    );
+    assert_eq!(definitions["TestUnitEnum"], TestUnitEnum::schema());
+}
+
+#[test]
+fn schemas_match_the_serialization() {
+    let definitions = collect::<TestGeneric<TestUnitEnum>>();
+    let value = TestGeneric {
+        item: TestUnitEnum::B,
+        items: Some(vec![TestDataEnum::A(1), TestDataEnum::B { b: vec![2, 3] }]),
+    };
+    let data = value.serialize_to_vec();
+
+    let reader = &mut &data[..];
+    skip(&definitions, "TestGeneric<TestUnitEnum>", reader).unwrap();
+    assert!(reader.is_empty());
+
+    // A missing trailing field is fine, a truncated one isn't.
+    let definitions = collect::<TestStruct>();
+    let value = TestStruct {
+        a: 1,
+        b: vec![2],
+        c: false,
+        d: 4,
+    };
+    let data = value.serialize_to_vec();
+
+    let reader = &mut &data[..data.len() - 1];
+    skip(&definitions, "TestStruct", reader).unwrap();
+    assert!(reader.is_empty());
+
+    let reader = &mut &data[..data.len() - 2];
+    assert!(skip(&definitions, "TestStruct", reader).is_err());
+}
@@ -0,0 +1,82 @@
+#[macro_use]
+extern crate beserial_derive;
+
+use beserial::{Deserialize, Serialize, SerializingError, Version};
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+struct RequestV1 {
+    locator: u32,
+    max_items: u16,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+struct RequestV2 {
+    version: Version<2>,
+    locator: u32,
+    max_items: u16,
+    #[beserial(trailing)]
+    include_micro: bool,
+    #[beserial(trailing)]
+    epoch: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+struct RequestV1Versioned {
+    version: Version<1>,
+    locator: u32,
+    max_items: u16,
+}
+
+#[test]
+fn it_uses_defaults_for_missing_trailing_fields() {
+    let old = RequestV1Versioned {
+        version: Version::CURRENT,
+        locator: 42,
+        max_items: 1000,
+    };
+    let new = RequestV2::deserialize_from_vec(&old.serialize_to_vec()).unwrap();
+    assert_eq!(new.version.get(), 1);
+    assert!(!new.version.supports(2));
+    assert_eq!(new.locator, 42);
+    assert_eq!(new.max_items, 1000);
+    assert!(!new.include_micro);
+    assert_eq!(new.epoch, None);
+}
+
+#[test]
+fn it_ignores_unknown_trailing_fields() {
+    let new = RequestV2 {
+        version: Version::CURRENT,
+        locator: 42,
+        max_items: 1000,
+        include_micro: true,
+        epoch: Some(7),
+    };
+    let bin = new.serialize_to_vec();
+    assert_eq!(bin.len(), new.serialized_size());
+    assert_eq!(RequestV2::deserialize_from_vec(&bin).unwrap(), new);
+
+    let old = RequestV1Versioned::deserialize_from_vec(&bin).unwrap();
+    assert_eq!(old.version.get(), 2);
+    assert_eq!(old.locator, 42);
+    assert_eq!(old.max_items, 1000);
+}
+
+#[test]
+fn it_rejects_truncated_trailing_fields() {
+    let new = RequestV2 {
+        version: Version::CURRENT,
+        locator: 42,
+        max_items: 1000,
+        include_micro: true,
+        epoch: Some(7),
+    };
+    let bin = new.serialize_to_vec();
+    // Cut the input in the middle of the epoch.
+    assert!(matches!(
+        RequestV2::deserialize_from_vec(&bin[..bin.len() - 2]),
+        Err(SerializingError::IoError(_))
+    ));
+    // Missing fields must still be present.
+    assert!(RequestV1::deserialize_from_vec(&bin[..5]).is_err());
+}
@@ -0,0 +1,92 @@
+//! Helpers for evolving serialized types without breaking compatibility with older versions.
+//!
+//! New fields can be appended to the end of a struct and marked with `#[beserial(trailing)]`.
+//! Older peers ignore the additional bytes, newer peers fall back to `Default` if the bytes
+//! are missing. A [`Version`] field can be used to find out which fields the sender knew about.
+
+use std::io::{self, Read};
+
+use crate::{Deserialize, ReadBytesExt, Serialize, SerializingError, WriteBytesExt};
+
+/// Deserializes a field that might be missing at the end of the input.
+///
+/// If the input ends right before the field, `T::default()` is returned. If the input ends
+/// somewhere within the field, the usual `UnexpectedEof` error is returned.
+pub fn deserialize_trailing<T: Deserialize + Default, R: ReadBytesExt>(
+    reader: &mut R,
+) -> Result<T, SerializingError> {
+    let mut counting_reader = CountingReader {
+        inner: reader,
+        bytes_read: 0,
+    };
+    match T::deserialize(&mut counting_reader) {
+        Err(SerializingError::IoError(e))
+            if e.kind() == io::ErrorKind::UnexpectedEof && counting_reader.bytes_read == 0 =>
+        {
+            Ok(T::default())
+        }
+        result => result,
+    }
+}
+
+struct CountingReader<'a, R> {
+    inner: &'a mut R,
+    bytes_read: usize,
+}
+
+impl<'a, R: Read> Read for CountingReader<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.bytes_read += n;
+        Ok(n)
+    }
+}
+
+/// A version byte for types that evolve over time.
+///
+/// `CURRENT` is the version the local code knows about and is used when constructing new values.
+/// Deserialization accepts any version, since newer senders only append trailing fields. Use
+/// [`Version::supports`] to check whether the sender knew about a field.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version<const CURRENT: u8>(u8);
+
+impl<const CURRENT: u8> Version<CURRENT> {
+    pub const CURRENT: Self = Version(CURRENT);
+
+    pub fn new(version: u8) -> Self {
+        Version(version)
+    }
+
+    #[inline]
+    pub fn get(&self) -> u8 {
+        self.0
+    }
+
+    /// Returns true if the sender knew about the fields introduced in `version`.
+    #[inline]
+    pub fn supports(&self, version: u8) -> bool {
+        self.0 >= version
+    }
+}
+
+impl<const CURRENT: u8> Default for Version<CURRENT> {
+    fn default() -> Self {
+        Self::CURRENT
+    }
+}
+
+impl<const CURRENT: u8> Serialize for Version<CURRENT> {
+    fn serialize<W: WriteBytesExt>(&self, writer: &mut W) -> Result<usize, SerializingError> {
+        Serialize::serialize(&self.0, writer)
+    }
+
+    fn serialized_size(&self) -> usize {
+        1
+    }
+}
+
+impl<const CURRENT: u8> Deserialize for Version<CURRENT> {
+    fn deserialize<R: ReadBytesExt>(reader: &mut R) -> Result<Self, SerializingError> {
+        Ok(Version(Deserialize::deserialize(reader)?))
+    }
+}
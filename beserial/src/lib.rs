@@ -7,14 +7,18 @@ pub use byteorder::{BigEndian, ByteOrder, ReadBytesExt, WriteBytesExt};
 pub use num::{FromPrimitive, ToPrimitive};
 use thiserror::Error;
 
+pub use crate::evolution::{deserialize_trailing, Version};
+pub use crate::schema::{FieldSchema, Schema, TypeSchema, VariantSchema};
 pub use crate::types::uvar;
 
 #[cfg(feature = "bitvec")]
 mod bitvec;
+mod evolution;
 #[cfg(feature = "libp2p")]
 mod libp2p;
 #[cfg(feature = "net")]
 mod net;
+pub mod schema;
 mod types;
 
 // Base traits
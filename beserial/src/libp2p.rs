@@ -9,7 +9,9 @@ use libp2p::{
 };
 
 use crate::{
-    uvar, Deserialize, DeserializeWithLength, Serialize, SerializeWithLength, SerializingError,
+    schema::{define, Definitions},
+    uvar, Deserialize, DeserializeWithLength, FieldSchema, Schema, Serialize, SerializeWithLength,
+    SerializingError, TypeSchema,
 };
 
 impl Serialize for Multiaddr {
@@ -31,6 +33,24 @@ impl Deserialize for Multiaddr {
     }
 }
 
+impl Schema for Multiaddr {
+    fn name() -> String {
+        "Multiaddr".to_string()
+    }
+
+    fn schema() -> TypeSchema {
+        TypeSchema::Struct {
+            fields: vec![FieldSchema::new::<Vec<u8>>(Some("bytes")).with_len_type("uvar", None)],
+        }
+    }
+
+    fn collect(definitions: &mut Definitions) {
+        if define::<Self>(definitions) {
+            Vec::<u8>::collect(definitions);
+        }
+    }
+}
+
 impl Serialize for PublicKey {
     fn serialize<W: WriteBytesExt>(&self, writer: &mut W) -> Result<usize, SerializingError> {
         match self {
@@ -74,6 +94,24 @@ impl Deserialize for PeerId {
     }
 }
 
+impl Schema for PeerId {
+    fn name() -> String {
+        "PeerId".to_string()
+    }
+
+    fn schema() -> TypeSchema {
+        TypeSchema::Struct {
+            fields: vec![FieldSchema::new::<Vec<u8>>(Some("bytes")).with_len_type("u8", None)],
+        }
+    }
+
+    fn collect(definitions: &mut Definitions) {
+        if define::<Self>(definitions) {
+            Vec::<u8>::collect(definitions);
+        }
+    }
+}
+
 impl Serialize for Keypair {
     fn serialize<W: WriteBytesExt>(&self, writer: &mut W) -> Result<usize, SerializingError> {
         match self {
@@ -0,0 +1,442 @@
+//! Machine-readable descriptions of serialized types.
+//!
+//! A [`TypeSchema`] describes how a type is serialized: the fields of structs and enum variants in
+//! the order in which they are serialized, together with the length types and limits of variable
+//! length fields, the discriminants of enums and the layout of containers and fixed size values.
+//! It can be derived with `#[derive(Schema)]` for every type that derives `Serialize`. Types that
+//! are serialized by hand implement [`Schema`] by hand.
+//!
+//! Schemas refer to other types by their [`Schema::name`], which is chosen by the type and doesn't
+//! change between compiler versions. [`Schema::collect`] gathers the schemas of a type and of all
+//! types it refers to, so the resulting [`Definitions`] fully describe its serialization.
+//! [`skip`] reads a value as described by the definitions, which checks that a schema matches the
+//! actual serialization.
+
+use std::collections::BTreeMap;
+
+use crate::{uvar, Deserialize, SerializingError, Version};
+
+/// The schemas of a set of types by their name.
+pub type Definitions = BTreeMap<String, TypeSchema>;
+
+/// Describes the serialization of a type.
+pub trait Schema {
+    /// The name by which other schemas refer to the type. Generic types include the names of
+    /// their type parameters, e.g. `Vec<u8>`.
+    fn name() -> String;
+
+    /// Describes the serialization of the type.
+    fn schema() -> TypeSchema;
+
+    /// Adds the schema of the type and of all types it refers to. Types that refer to other types
+    /// need to override this, the default only adds the type itself.
+    fn collect(definitions: &mut Definitions) {
+        define::<Self>(definitions);
+    }
+}
+
+/// Adds the schema of `T` to the definitions. Returns `false` if `T` was already defined, in which
+/// case the types it refers to don't need to be collected again.
+///
+/// Panics if another type with the same name but a different schema was defined.
+pub fn define<T: Schema + ?Sized>(definitions: &mut Definitions) -> bool {
+    let name = T::name();
+    let schema = T::schema();
+    match definitions.get(&name) {
+        Some(defined) => {
+            assert_eq!(defined, &schema, "Conflicting schemas for {}", name);
+            false
+        }
+        None => {
+            definitions.insert(name, schema);
+            true
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(
+    feature = "serde-derive",
+    derive(serde::Serialize),
+    serde(tag = "kind", rename_all = "camelCase")
+)]
+pub enum TypeSchema {
+    /// A fixed number of bytes, e.g. an integer in big endian byte order, a hash or a compressed
+    /// key.
+    Bytes { len: usize },
+    /// An unsigned integer of variable length, see [`uvar`].
+    Uvar,
+    /// A sequence of items, prefixed by its length. The type of the length is given by the field
+    /// that contains the sequence.
+    List { item: String },
+    /// A byte that is `0` for `None` and `1` for `Some`, followed by the item in the latter case.
+    /// The length type of the field that contains the option applies to the item.
+    Option { item: String },
+    /// The items are serialized one after the other.
+    Tuple { items: Vec<String> },
+    /// The fields are serialized one after the other.
+    Struct { fields: Vec<FieldSchema> },
+    /// The discriminant of the variant is serialized first, followed by the fields of the variant.
+    Enum {
+        /// The type of the discriminant, e.g. `u8`, or `uvar` for a variable length integer.
+        discriminant: &'static str,
+        variants: Vec<VariantSchema>,
+    },
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(
+    feature = "serde-derive",
+    derive(serde::Serialize),
+    serde(rename_all = "camelCase")
+)]
+pub struct FieldSchema {
+    /// The name of the field, `None` for the fields of tuple structs and tuple variants.
+    pub name: Option<&'static str>,
+    /// The [`Schema::name`] of the type of the field.
+    #[cfg_attr(feature = "serde-derive", serde(rename = "type"))]
+    pub ty: String,
+    /// The type of the length prefix of a variable length field.
+    pub len_type: Option<&'static str>,
+    /// The maximum length of a variable length field.
+    pub limit: Option<usize>,
+    /// Whether the field was appended in a later version and might be missing at the end of the
+    /// input.
+    pub trailing: bool,
+}
+
+impl FieldSchema {
+    pub fn new<T: Schema + ?Sized>(name: Option<&'static str>) -> Self {
+        FieldSchema {
+            name,
+            ty: T::name(),
+            len_type: None,
+            limit: None,
+            trailing: false,
+        }
+    }
+
+    #[must_use]
+    pub fn with_len_type(mut self, len_type: &'static str, limit: Option<usize>) -> Self {
+        self.len_type = Some(len_type);
+        self.limit = limit;
+        self
+    }
+
+    #[must_use]
+    pub fn trailing(mut self) -> Self {
+        self.trailing = true;
+        self
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde-derive", derive(serde::Serialize))]
+pub struct VariantSchema {
+    pub name: &'static str,
+    pub discriminant: u64,
+    pub fields: Vec<FieldSchema>,
+}
+
+/// Reads a value of the type with the given name from `reader` as described by the definitions,
+/// without constructing it.
+pub fn skip(
+    definitions: &Definitions,
+    ty: &str,
+    reader: &mut &[u8],
+) -> Result<(), SerializingError> {
+    skip_value(definitions, ty, None, reader)
+}
+
+fn skip_value(
+    definitions: &Definitions,
+    ty: &str,
+    len: Option<(&str, Option<usize>)>,
+    reader: &mut &[u8],
+) -> Result<(), SerializingError> {
+    let schema = definitions.get(ty).ok_or(SerializingError::InvalidValue)?;
+
+    match schema {
+        TypeSchema::Bytes { len } => {
+            if reader.len() < *len {
+                return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+            }
+            *reader = &reader[*len..];
+        }
+        TypeSchema::Uvar => {
+            uvar::deserialize(reader)?;
+        }
+        TypeSchema::List { item } => {
+            let (len_type, limit) = len.ok_or(SerializingError::InvalidValue)?;
+            let num_items = read_integer(len_type, reader)?;
+            if limit.map_or(false, |limit| num_items > limit as u64) {
+                return Err(SerializingError::LimitExceeded);
+            }
+            for _ in 0..num_items {
+                skip_value(definitions, item, None, reader)?;
+            }
+        }
+        TypeSchema::Option { item } => match u8::deserialize(reader)? {
+            0 => {}
+            1 => skip_value(definitions, item, len, reader)?,
+            _ => return Err(SerializingError::InvalidValue),
+        },
+        TypeSchema::Tuple { items } => {
+            for item in items {
+                skip_value(definitions, item, None, reader)?;
+            }
+        }
+        TypeSchema::Struct { fields } => skip_fields(definitions, fields, reader)?,
+        TypeSchema::Enum {
+            discriminant,
+            variants,
+        } => {
+            let discriminant = read_integer(discriminant, reader)?;
+            let variant = variants
+                .iter()
+                .find(|variant| variant.discriminant == discriminant)
+                .ok_or(SerializingError::InvalidValue)?;
+            skip_fields(definitions, &variant.fields, reader)?;
+        }
+    }
+    Ok(())
+}
+
+fn skip_fields(
+    definitions: &Definitions,
+    fields: &[FieldSchema],
+    reader: &mut &[u8],
+) -> Result<(), SerializingError> {
+    for field in fields {
+        // Trailing fields might be missing at the end of the input.
+        if field.trailing && reader.is_empty() {
+            break;
+        }
+        let len = field.len_type.map(|len_type| (len_type, field.limit));
+        skip_value(definitions, &field.ty, len, reader)?;
+    }
+    Ok(())
+}
+
+/// Reads a length or discriminant of the given type.
+fn read_integer(ty: &str, reader: &mut &[u8]) -> Result<u64, SerializingError> {
+    Ok(match ty {
+        "u8" => u8::deserialize(reader)?.into(),
+        "u16" => u16::deserialize(reader)?.into(),
+        "u32" => u32::deserialize(reader)?.into(),
+        "u64" => u64::deserialize(reader)?,
+        "uvar" => uvar::deserialize(reader)?.into(),
+        "i8" => i8::deserialize(reader)? as u64,
+        "i16" => i16::deserialize(reader)? as u64,
+        "i32" => i32::deserialize(reader)? as u64,
+        "i64" => i64::deserialize(reader)? as u64,
+        _ => return Err(SerializingError::InvalidValue),
+    })
+}
+
+macro_rules! fixed_size_schema {
+    ($($ty:ty => $len:expr),* $(,)?) => {
+        $(
+            impl Schema for $ty {
+                fn name() -> String {
+                    stringify!($ty).to_string()
+                }
+
+                fn schema() -> TypeSchema {
+                    TypeSchema::Bytes { len: $len }
+                }
+            }
+        )*
+    };
+}
+
+fixed_size_schema! {
+    u8 => 1,
+    u16 => 2,
+    u32 => 4,
+    u64 => 8,
+    i8 => 1,
+    i16 => 2,
+    i32 => 4,
+    i64 => 8,
+    bool => 1,
+    () => 0,
+}
+
+impl Schema for uvar {
+    fn name() -> String {
+        "uvar".to_string()
+    }
+
+    fn schema() -> TypeSchema {
+        TypeSchema::Uvar
+    }
+}
+
+impl<const CURRENT: u8> Schema for Version<CURRENT> {
+    fn name() -> String {
+        "Version".to_string()
+    }
+
+    fn schema() -> TypeSchema {
+        TypeSchema::Bytes { len: 1 }
+    }
+}
+
+impl Schema for String {
+    fn name() -> String {
+        "String".to_string()
+    }
+
+    fn schema() -> TypeSchema {
+        TypeSchema::List { item: u8::name() }
+    }
+
+    fn collect(definitions: &mut Definitions) {
+        if define::<Self>(definitions) {
+            u8::collect(definitions);
+        }
+    }
+}
+
+impl<T: Schema> Schema for Vec<T> {
+    fn name() -> String {
+        format!("Vec<{}>", T::name())
+    }
+
+    fn schema() -> TypeSchema {
+        TypeSchema::List { item: T::name() }
+    }
+
+    fn collect(definitions: &mut Definitions) {
+        if define::<Self>(definitions) {
+            T::collect(definitions);
+        }
+    }
+}
+
+impl<T: Schema> Schema for Option<T> {
+    fn name() -> String {
+        format!("Option<{}>", T::name())
+    }
+
+    fn schema() -> TypeSchema {
+        TypeSchema::Option { item: T::name() }
+    }
+
+    fn collect(definitions: &mut Definitions) {
+        if define::<Self>(definitions) {
+            T::collect(definitions);
+        }
+    }
+}
+
+impl<T: Schema, V: Schema> Schema for (T, V) {
+    fn name() -> String {
+        format!("({}, {})", T::name(), V::name())
+    }
+
+    fn schema() -> TypeSchema {
+        TypeSchema::Tuple {
+            items: vec![T::name(), V::name()],
+        }
+    }
+
+    fn collect(definitions: &mut Definitions) {
+        if define::<Self>(definitions) {
+            T::collect(definitions);
+            V::collect(definitions);
+        }
+    }
+}
+
+/// Maps are serialized as a sequence of key value pairs.
+impl<K: Schema, V: Schema> Schema for BTreeMap<K, V> {
+    fn name() -> String {
+        format!("BTreeMap<{}, {}>", K::name(), V::name())
+    }
+
+    fn schema() -> TypeSchema {
+        TypeSchema::List {
+            item: <(K, V)>::name(),
+        }
+    }
+
+    fn collect(definitions: &mut Definitions) {
+        if define::<Self>(definitions) {
+            <(K, V)>::collect(definitions);
+        }
+    }
+}
+
+/// Boxes are serialized like their content.
+impl<T: Schema> Schema for Box<T> {
+    fn name() -> String {
+        T::name()
+    }
+
+    fn schema() -> TypeSchema {
+        T::schema()
+    }
+
+    fn collect(definitions: &mut Definitions) {
+        T::collect(definitions);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SerializeWithLength;
+
+    const TY: &str = "Option<Vec<(u8, u16)>>";
+
+    fn definitions<T: Schema>() -> Definitions {
+        let mut definitions = Definitions::new();
+        T::collect(&mut definitions);
+        definitions
+    }
+
+    #[test]
+    fn it_collects_nested_types() {
+        let definitions = definitions::<Option<Vec<(u8, u16)>>>();
+        assert_eq!(
+            definitions.keys().collect::<Vec<_>>(),
+            vec!["(u8, u16)", TY, "Vec<(u8, u16)>", "u16", "u8"]
+        );
+        assert_eq!(
+            definitions["Vec<(u8, u16)>"],
+            TypeSchema::List {
+                item: "(u8, u16)".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn it_skips_values_as_they_are_serialized() {
+        let definitions = definitions::<Option<Vec<(u8, u16)>>>();
+        let value = Some(vec![(1u8, 2u16), (3, 4)]);
+        let data = SerializeWithLength::serialize_to_vec::<u16>(&value);
+
+        // The length type of the field applies to the vector inside the option.
+        let reader = &mut &data[..];
+        skip_value(&definitions, TY, Some(("u16", None)), reader).unwrap();
+        assert!(reader.is_empty());
+
+        let reader = &mut &data[..];
+        assert_eq!(
+            skip_value(&definitions, TY, Some(("u16", Some(1))), reader),
+            Err(SerializingError::LimitExceeded)
+        );
+
+        let reader = &mut &data[..data.len() - 1];
+        assert!(skip_value(&definitions, TY, Some(("u16", None)), reader).is_err());
+
+        // A sequence can't be read without knowing the type of its length.
+        let reader = &mut &data[..];
+        assert_eq!(
+            skip(&definitions, TY, reader),
+            Err(SerializingError::InvalidValue)
+        );
+    }
+}
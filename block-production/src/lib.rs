@@ -1,4 +1,6 @@
-use nimiq_account::Inherent;
+use thiserror::Error;
+
+use nimiq_account::{AccountError, Inherent};
 use nimiq_block::{
     ForkProof, MacroBlock, MacroBody, MacroHeader, MicroBlock, MicroBody, MicroHeader,
     MicroJustification, ViewChangeProof, ViewChanges,
@@ -10,6 +12,15 @@ use nimiq_keys::KeyPair as SchnorrKeyPair;
 use nimiq_primitives::policy;
 use nimiq_transaction::Transaction;
 
+/// An error that prevented the production of a block.
+#[derive(Debug, Error)]
+pub enum BlockProductionError {
+    #[error("Failed to compute accounts hash: {0}")]
+    AccountsHash(#[from] AccountError),
+    #[error("Failed to compute history root")]
+    HistoryRoot,
+}
+
 /// Struct that contains all necessary information to actually produce blocks.
 /// It has the validator keys for this validator.
 #[derive(Clone)]
@@ -43,10 +54,36 @@ impl BlockProducer {
         // the batch when it happened or in the next one, but not after that.
         fork_proofs: Vec<ForkProof>,
         // The transactions to be included in the block body.
-        mut transactions: Vec<Transaction>,
+        transactions: Vec<Transaction>,
         // Extra data for this block. It has no a priori use.
         extra_data: Vec<u8>,
     ) -> MicroBlock {
+        self.try_next_micro_block(
+            blockchain,
+            timestamp,
+            view_number,
+            view_change_proof,
+            fork_proofs,
+            transactions,
+            extra_data,
+        )
+        .expect("Failed to produce micro block")
+    }
+
+    /// Creates the next micro block, like `next_micro_block`. Returns an error instead of
+    /// panicking if the transactions or fork proofs can't be applied to the accounts.
+    // Ignoring clippy warning because the arguments are the same as for `next_micro_block`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn try_next_micro_block(
+        &self,
+        blockchain: &Blockchain,
+        timestamp: u64,
+        view_number: u32,
+        view_change_proof: Option<ViewChangeProof>,
+        fork_proofs: Vec<ForkProof>,
+        mut transactions: Vec<Transaction>,
+        extra_data: Vec<u8>,
+    ) -> Result<MicroBlock, BlockProductionError> {
         // Calculate the block number. It is simply the previous block number incremented by one.
         let block_number = blockchain.block_number() + 1;
 
@@ -77,11 +114,12 @@ impl BlockProducer {
         let inherents = blockchain.create_slash_inherents(&fork_proofs, &view_changes, None);
 
         // Update the state and calculate the state root.
-        let state_root = blockchain
-            .state()
-            .accounts
-            .get_root_with(&transactions, &inherents, block_number, timestamp)
-            .expect("Failed to compute accounts hash during block production");
+        let state_root = blockchain.state().accounts.get_root_with(
+            &transactions,
+            &inherents,
+            block_number,
+            timestamp,
+        )?;
 
         // Calculate the extended transactions from the transactions and the inherents.
         let ext_txs = ExtendedTransaction::from(
@@ -95,15 +133,17 @@ impl BlockProducer {
         // Store the extended transactions into the history tree and calculate the history root.
         let mut txn = blockchain.write_transaction();
 
-        let history_root = blockchain
-            .history_store
-            .add_to_history(&mut txn, policy::epoch_at(block_number), &ext_txs)
-            .expect("Failed to compute history root during block production.");
+        let history_root = blockchain.history_store.add_to_history(
+            &mut txn,
+            policy::epoch_at(block_number),
+            &ext_txs,
+        );
 
         // Not strictly necessary to drop the lock here, but sign as well as compress might be somewhat expensive
         // and there is no need to hold the lock after this point.
         // Abort txn so that blockchain is no longer borrowed.
         txn.abort();
+        let history_root = history_root.ok_or(BlockProductionError::HistoryRoot)?;
 
         // Create the micro block body.
         let body = MicroBody {
@@ -130,14 +170,14 @@ impl BlockProducer {
         let signature = self.signing_key.sign(hash.as_slice());
 
         // Returns the micro block.
-        MicroBlock {
+        Ok(MicroBlock {
             header,
             body: Some(body),
             justification: Some(MicroJustification {
                 signature,
                 view_change_proof,
             }),
-        }
+        })
     }
 
     /// Creates a proposal for the next macro block (checkpoint or election). It is just a proposal,
@@ -1,14 +1,12 @@
 use std::sync::Arc;
 
-use parking_lot::RwLock;
-
 use beserial::Deserialize;
 use nimiq_block::{
     Block, MacroBlock, MacroBody, MultiSignature, SignedViewChange, TendermintIdentifier,
     TendermintProof, TendermintProposal, TendermintStep, TendermintVote, ViewChange,
     ViewChangeProof,
 };
-use nimiq_blockchain::{AbstractBlockchain, Blockchain, PushError, PushResult};
+use nimiq_blockchain::{AbstractBlockchain, Blockchain, BlockchainLock, PushError, PushResult};
 use nimiq_bls::{AggregateSignature, KeyPair as BlsKeyPair, SecretKey as BlsSecretKey};
 use nimiq_collections::BitSet;
 use nimiq_database::volatile::VolatileEnvironment;
@@ -25,7 +23,7 @@ const SIGNING_KEY: &str = "041580cc67e66e9e08b68fd9e4c9deb68737168fbe7488de2638c
 const VOTING_KEY: &str = "196ffdb1a8acc7cbd76a251aeac0600a1d68b3aba1eba823b5e4dc5dbdcdc730afa752c05ab4f6ef8518384ad514f403c5a088a22b17bf1bc14f8ff8decc2a512c0a200f68d7bdf5a319b30356fe8d1d75ef510aed7a8660968c216c328a0000";
 
 pub struct TemporaryBlockProducer {
-    pub blockchain: Arc<RwLock<Blockchain>>,
+    pub blockchain: Arc<BlockchainLock>,
     pub producer: BlockProducer,
 }
 
@@ -39,7 +37,7 @@ impl TemporaryBlockProducer {
     pub fn new() -> Self {
         let time = Arc::new(OffsetTime::new());
         let env = VolatileEnvironment::new(10).unwrap();
-        let blockchain = Arc::new(RwLock::new(
+        let blockchain = Arc::new(BlockchainLock::new(
             Blockchain::new(env, NetworkId::UnitAlbatross, time).unwrap(),
         ));
 
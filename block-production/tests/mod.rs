@@ -1,4 +1,3 @@
-use parking_lot::RwLock;
 use std::convert::TryInto;
 use std::sync::Arc;
 use tempfile::tempdir;
@@ -6,7 +5,7 @@ use tempfile::tempdir;
 use beserial::Deserialize;
 use nimiq_block::{Block, BlockError, ForkProof};
 use nimiq_block_production::BlockProducer;
-use nimiq_blockchain::{AbstractBlockchain, Blockchain, PushError, PushResult};
+use nimiq_blockchain::{AbstractBlockchain, Blockchain, BlockchainLock, PushError, PushResult};
 use nimiq_database::{
     lmdb::{open as LmdbFlags, LmdbEnvironment},
     volatile::VolatileEnvironment,
@@ -37,7 +36,7 @@ const VOLATILE_ENV: bool = true;
 fn it_can_produce_micro_blocks() {
     let time = Arc::new(OffsetTime::new());
     let env = VolatileEnvironment::new(10).unwrap();
-    let blockchain = Arc::new(RwLock::new(
+    let blockchain = Arc::new(BlockchainLock::new(
         Blockchain::new(env, NetworkId::UnitAlbatross, time).unwrap(),
     ));
     let producer = BlockProducer::new(signing_key(), voting_key());
@@ -136,7 +135,7 @@ fn it_can_produce_micro_blocks() {
 fn it_can_produce_macro_blocks() {
     let time = Arc::new(OffsetTime::new());
     let env = VolatileEnvironment::new(10).unwrap();
-    let blockchain = Arc::new(RwLock::new(
+    let blockchain = Arc::new(BlockchainLock::new(
         Blockchain::new(env, NetworkId::UnitAlbatross, time).unwrap(),
     ));
     let producer = BlockProducer::new(signing_key(), voting_key());
@@ -164,7 +163,7 @@ fn it_can_produce_macro_blocks() {
 fn it_can_produce_election_blocks() {
     let time = Arc::new(OffsetTime::new());
     let env = VolatileEnvironment::new(10).unwrap();
-    let blockchain = Arc::new(RwLock::new(
+    let blockchain = Arc::new(BlockchainLock::new(
         Blockchain::new(env, NetworkId::UnitAlbatross, time).unwrap(),
     ));
     let producer = BlockProducer::new(signing_key(), voting_key());
@@ -192,6 +191,45 @@ fn it_can_produce_election_blocks() {
     }
 }
 
+#[test]
+fn it_prunes_micro_bodies_of_finalized_batches() {
+    let time = Arc::new(OffsetTime::new());
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Arc::new(BlockchainLock::new(
+        Blockchain::new(env, NetworkId::UnitAlbatross, time).unwrap(),
+    ));
+    blockchain.write().prune_micro_bodies = true;
+    let producer = BlockProducer::new(signing_key(), voting_key());
+
+    // The bodies are kept until the macro block of the batch is pushed.
+    fill_micro_blocks(&producer, &blockchain);
+    let micro_block = blockchain.read().get_block_at(1, true, None).unwrap();
+    assert!(micro_block.unwrap_micro().body.is_some());
+
+    let bc = blockchain.upgradable_read();
+    let macro_block = producer.next_macro_block_proposal(
+        &bc,
+        bc.time.now() + bc.block_number() as u64 * 1000,
+        0u32,
+        vec![],
+    );
+    let block = sign_macro_block(&voting_key(), macro_block.header, macro_block.body);
+    assert_eq!(
+        Blockchain::push(bc, Block::Macro(block)),
+        Ok(PushResult::Extended)
+    );
+
+    let micro_block = blockchain.read().get_block_at(1, true, None).unwrap();
+    assert!(micro_block.unwrap_micro().body.is_none());
+
+    // Macro blocks keep their body.
+    let macro_block = blockchain
+        .read()
+        .get_block_at(policy::macro_block_of(1), true, None)
+        .unwrap();
+    assert!(macro_block.unwrap_macro().body.is_some());
+}
+
 #[test]
 fn it_can_produce_a_chain_with_txns() {
     let time = Arc::new(OffsetTime::new());
@@ -208,7 +246,7 @@ fn it_can_produce_a_chain_with_txns() {
         )
         .unwrap()
     };
-    let blockchain = Arc::new(RwLock::new(
+    let blockchain = Arc::new(BlockchainLock::new(
         Blockchain::new(env, NetworkId::UnitAlbatross, time).unwrap(),
     ));
     let producer = BlockProducer::new(signing_key(), voting_key());
@@ -245,7 +283,7 @@ fn it_can_produce_a_chain_with_txns() {
 fn it_can_revert_unpark_transactions() {
     let time = Arc::new(OffsetTime::new());
     let env = VolatileEnvironment::new(10).unwrap();
-    let blockchain = Arc::new(RwLock::new(
+    let blockchain = Arc::new(BlockchainLock::new(
         Blockchain::new(env, NetworkId::UnitAlbatross, time).unwrap(),
     ));
     let producer = BlockProducer::new(signing_key(), voting_key());
@@ -344,7 +382,7 @@ fn it_can_revert_unpark_transactions() {
 fn it_can_revert_create_stacker_transaction() {
     let time = Arc::new(OffsetTime::new());
     let env = VolatileEnvironment::new(10).unwrap();
-    let blockchain = Arc::new(RwLock::new(
+    let blockchain = Arc::new(BlockchainLock::new(
         Blockchain::new(env, NetworkId::UnitAlbatross, time).unwrap(),
     ));
     let producer = BlockProducer::new(signing_key(), voting_key());
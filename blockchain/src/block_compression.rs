@@ -0,0 +1,241 @@
+//! Compression of the block bodies stored in the chain store.
+//!
+//! Micro blocks with a body are stored compressed with zstd. Once an epoch with enough
+//! transactions has been stored, a dictionary is trained on its micro blocks. Small and similar
+//! transactions compress a lot better with a dictionary than every block on its own.
+//! Compressed entries reference the dictionary they were compressed with. Entries stored without
+//! compression, e.g. by older versions, are read as they are.
+
+use std::collections::HashMap;
+use std::io;
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use beserial::{Deserialize, Serialize};
+use nimiq_block::Block;
+
+/// Marks compressed entries. Uncompressed entries start with the block type, which is 1 or 2.
+const COMPRESSED_MARKER: u8 = 0x80;
+
+/// Size of the header of compressed entries: the marker, the dictionary ID and the uncompressed
+/// size.
+const HEADER_SIZE: usize = 9;
+
+const COMPRESSION_LEVEL: i32 = 3;
+
+/// Dictionary ID of entries that were compressed without a dictionary.
+pub(crate) const NO_DICTIONARY: u32 = 0;
+
+/// Maximum size of a trained dictionary.
+pub(crate) const MAX_DICTIONARY_SIZE: usize = 64 * 1024;
+
+/// Minimum number of micro blocks with transactions needed to train a dictionary.
+pub(crate) const MIN_DICTIONARY_SAMPLES: usize = 256;
+
+#[derive(Debug, Default)]
+struct Dictionaries {
+    by_id: HashMap<u32, Arc<Vec<u8>>>,
+    /// The dictionary new entries are compressed with.
+    current: u32,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct BlockCompressor {
+    dictionaries: RwLock<Dictionaries>,
+}
+
+impl BlockCompressor {
+    /// Adds a dictionary and compresses new entries with it.
+    pub fn add_dictionary(&self, id: u32, dictionary: Vec<u8>) {
+        let mut dictionaries = self.dictionaries.write();
+        dictionaries.by_id.insert(id, Arc::new(dictionary));
+        dictionaries.current = dictionaries.current.max(id);
+    }
+
+    /// Returns the ID of the dictionary new entries are compressed with.
+    pub fn current_dictionary(&self) -> u32 {
+        self.dictionaries.read().current
+    }
+
+    /// Serializes the block, compressing micro blocks with a body.
+    pub fn encode(&self, block: &Block) -> Vec<u8> {
+        let serialized = block.serialize_to_vec();
+        let has_body = matches!(block, Block::Micro(micro_block) if micro_block.body.is_some());
+        if !has_body {
+            return serialized;
+        }
+
+        let (id, dictionary) = {
+            let dictionaries = self.dictionaries.read();
+            let id = dictionaries.current;
+            (id, dictionaries.by_id.get(&id).cloned())
+        };
+
+        let compressed = match dictionary {
+            Some(dictionary) => {
+                zstd::bulk::Compressor::with_dictionary(COMPRESSION_LEVEL, &dictionary)
+                    .and_then(|mut compressor| compressor.compress(&serialized))
+            }
+            None => zstd::bulk::compress(&serialized, COMPRESSION_LEVEL),
+        };
+
+        match compressed {
+            Ok(compressed) if compressed.len() + HEADER_SIZE < serialized.len() => {
+                let mut bytes = Vec::with_capacity(HEADER_SIZE + compressed.len());
+                bytes.push(COMPRESSED_MARKER);
+                bytes.extend_from_slice(&id.to_be_bytes());
+                bytes.extend_from_slice(&(serialized.len() as u32).to_be_bytes());
+                bytes.extend_from_slice(&compressed);
+                bytes
+            }
+            Ok(_) => serialized,
+            Err(e) => {
+                warn!("Failed to compress block: {}", e);
+                serialized
+            }
+        }
+    }
+
+    /// Deserializes a block stored by `encode`, or without compression.
+    pub fn decode(&self, bytes: &[u8]) -> io::Result<Block> {
+        if bytes.first() != Some(&COMPRESSED_MARKER) {
+            return Ok(Block::deserialize_from_vec(bytes)?);
+        }
+        if bytes.len() < HEADER_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Truncated compressed block",
+            ));
+        }
+
+        let mut id = [0u8; 4];
+        id.copy_from_slice(&bytes[1..5]);
+        let id = u32::from_be_bytes(id);
+        let mut size = [0u8; 4];
+        size.copy_from_slice(&bytes[5..9]);
+        let size = u32::from_be_bytes(size) as usize;
+        let compressed = &bytes[HEADER_SIZE..];
+
+        let serialized = if id == NO_DICTIONARY {
+            zstd::bulk::decompress(compressed, size)?
+        } else {
+            let dictionary = self
+                .dictionaries
+                .read()
+                .by_id
+                .get(&id)
+                .cloned()
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("Unknown compression dictionary {}", id),
+                    )
+                })?;
+            zstd::bulk::Decompressor::with_dictionary(&dictionary)?.decompress(compressed, size)?
+        };
+
+        Ok(Block::deserialize_from_vec(&serialized)?)
+    }
+}
+
+/// Trains a compression dictionary on the given serialized micro blocks.
+pub(crate) fn train_dictionary(samples: &[Vec<u8>]) -> io::Result<Vec<u8>> {
+    zstd::dict::from_samples(samples, MAX_DICTIONARY_SIZE)
+}
+
+#[cfg(test)]
+mod tests {
+    use nimiq_block::{MacroBlock, MicroBlock, MicroBody, MicroHeader};
+    use nimiq_keys::Address;
+    use nimiq_primitives::coin::Coin;
+    use nimiq_primitives::networks::NetworkId;
+    use nimiq_transaction::Transaction;
+
+    use super::*;
+
+    fn micro_block(num_transactions: u8) -> Block {
+        let transactions = (0..num_transactions)
+            .map(|i| {
+                Transaction::new_basic(
+                    Address::from([i; Address::SIZE]),
+                    Address::from([0xff; Address::SIZE]),
+                    Coin::from_u64_unchecked(1000),
+                    Coin::from_u64_unchecked(10),
+                    1,
+                    NetworkId::UnitAlbatross,
+                )
+            })
+            .collect();
+
+        Block::Micro(MicroBlock {
+            header: MicroHeader {
+                version: 1,
+                block_number: 1,
+                view_number: 0,
+                timestamp: 0,
+                parent_hash: Default::default(),
+                seed: Default::default(),
+                extra_data: vec![],
+                state_root: Default::default(),
+                body_root: Default::default(),
+                history_root: Default::default(),
+            },
+            body: Some(MicroBody {
+                fork_proofs: vec![],
+                transactions,
+            }),
+            justification: None,
+        })
+    }
+
+    #[test]
+    fn blocks_round_trip_without_a_dictionary() {
+        let compressor = BlockCompressor::default();
+        let block = micro_block(32);
+
+        let encoded = compressor.encode(&block);
+        assert_eq!(encoded[0], COMPRESSED_MARKER);
+        assert!(encoded.len() < block.serialized_size());
+        assert_eq!(compressor.decode(&encoded).unwrap(), block);
+    }
+
+    #[test]
+    fn blocks_round_trip_with_a_dictionary() {
+        let compressor = BlockCompressor::default();
+        let block = micro_block(32);
+        // Any content can be used as a dictionary.
+        compressor.add_dictionary(1, micro_block(16).serialize_to_vec());
+        assert_eq!(compressor.current_dictionary(), 1);
+
+        let encoded = compressor.encode(&block);
+        assert_eq!(encoded[1..5], 1u32.to_be_bytes());
+        assert_eq!(compressor.decode(&encoded).unwrap(), block);
+
+        // Entries compressed with a dictionary can't be read without it.
+        let without_dictionary = BlockCompressor::default();
+        assert_eq!(
+            without_dictionary.decode(&encoded).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn uncompressed_blocks_are_read_as_they_are() {
+        let compressor = BlockCompressor::default();
+
+        // Blocks without a body aren't compressed.
+        let block = Block::Macro(MacroBlock::default());
+        let encoded = compressor.encode(&block);
+        assert_eq!(encoded, block.serialize_to_vec());
+        assert_eq!(compressor.decode(&encoded).unwrap(), block);
+
+        // Entries stored by older versions are uncompressed.
+        let block = micro_block(4);
+        assert_eq!(compressor.decode(&block.serialize_to_vec()).unwrap(), block);
+
+        // Truncated entries are rejected.
+        let encoded = compressor.encode(&micro_block(32));
+        assert!(compressor.decode(&encoded[..HEADER_SIZE - 1]).is_err());
+    }
+}
@@ -6,7 +6,7 @@ use nimiq_vrf::VrfEntropy;
 
 use crate::blockchain_state::BlockchainState;
 use crate::history_store::ExtendedTransaction;
-use crate::{Blockchain, PushError};
+use crate::{Blockchain, BlockchainError, PushError};
 
 /// Implements methods to handle the accounts.
 impl Blockchain {
@@ -126,11 +126,12 @@ impl Blockchain {
         prev_entropy: VrfEntropy,
         prev_view_number: u32,
     ) -> Result<(), PushError> {
-        assert_eq!(
-            micro_block.header.state_root,
-            accounts.get_root(Some(txn)),
-            "Failed to revert - inconsistent state"
-        );
+        if micro_block.header.state_root != accounts.get_root(Some(txn)) {
+            error!("Failed to revert - inconsistent state");
+            return Err(PushError::BlockchainError(
+                BlockchainError::InconsistentState,
+            ));
+        }
 
         debug!(
             "Reverting block #{}.{}",
@@ -138,7 +139,10 @@ impl Blockchain {
         );
 
         // Get the body of the block.
-        let body = micro_block.body.as_ref().unwrap();
+        let body = micro_block.body.as_ref().ok_or_else(|| {
+            error!("Failed to revert - missing body");
+            PushError::BlockchainError(BlockchainError::InconsistentState)
+        })?;
 
         // Get the view changes.
         let view_changes = ViewChanges::new(
@@ -155,7 +159,10 @@ impl Blockchain {
         let receipts = self
             .chain_store
             .get_receipts(micro_block.header.block_number, Some(txn))
-            .expect("Failed to revert - missing receipts");
+            .ok_or_else(|| {
+                error!("Failed to revert - missing receipts");
+                PushError::BlockchainError(BlockchainError::InconsistentState)
+            })?;
 
         // Revert the block from AccountsTree.
         if let Err(e) = accounts.revert(
@@ -166,7 +173,8 @@ impl Blockchain {
             micro_block.header.timestamp,
             &receipts,
         ) {
-            panic!("Failed to revert - {:?}", e);
+            error!("Failed to revert - {:?}", e);
+            return Err(PushError::AccountsError(e));
         }
 
         // Remove the transactions from the History tree. For this you only need to calculate the
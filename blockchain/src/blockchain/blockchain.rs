@@ -2,13 +2,14 @@ use std::sync::Arc;
 
 use nimiq_account::{Account, Accounts};
 use nimiq_block::Block;
-use nimiq_database::{Environment, ReadTransaction, WriteTransaction};
+use nimiq_database::{DatabaseMetricsSnapshot, Environment, ReadTransaction, WriteTransaction};
 use nimiq_genesis::NetworkInfo;
 use nimiq_hash::Blake2bHash;
 use nimiq_primitives::coin::Coin;
 use nimiq_primitives::networks::NetworkId;
 use nimiq_primitives::policy;
 use nimiq_primitives::slots::Validators;
+use nimiq_utils::lock_metrics::InstrumentedRwLock;
 use nimiq_utils::observer::Notifier;
 use nimiq_utils::time::OffsetTime;
 
@@ -18,6 +19,7 @@ use crate::chain_info::ChainInfo;
 use crate::chain_metrics::BlockchainMetrics;
 use crate::chain_store::ChainStore;
 use crate::history_store::HistoryStore;
+use crate::reader::BlockchainReader;
 use crate::reward::genesis_parameters;
 use crate::{BlockchainError, BlockchainEvent, ForkEvent};
 use nimiq_trie::key_nibbles::KeyNibbles;
@@ -38,11 +40,16 @@ pub struct Blockchain {
     // The chain store is a database containing all of the chain infos, blocks and receipts.
     pub chain_store: ChainStore,
     // The history store is a database containing all of the history trees and transactions.
-    pub history_store: HistoryStore,
+    pub history_store: Arc<HistoryStore>,
     // The current state of the blockchain.
     pub state: BlockchainState,
     // A reference to a "function" to test whether a given transaction is known and valid.
     pub tx_verification_cache: Arc<dyn TransactionVerificationCache>,
+    // If set, the bodies of the micro blocks of a batch are pruned once its macro block is pushed.
+    // The transactions are still available from the history store.
+    pub prune_micro_bodies: bool,
+    // The handle to read the head and the accounts without taking the blockchain lock.
+    pub(crate) reader: BlockchainReader,
     // The metrics for the blockchain. Needed for analysis.
     #[cfg(feature = "metrics")]
     pub(crate) metrics: BlockchainMetrics,
@@ -52,6 +59,10 @@ pub struct Blockchain {
     pub(crate) genesis_timestamp: u64,
 }
 
+/// The lock that shares the blockchain between the subsystems. With the `metrics` feature, it
+/// records how long its call sites wait for and hold it, see `nimiq_utils::lock_metrics`.
+pub type BlockchainLock = InstrumentedRwLock<Blockchain>;
+
 /// Implements methods to start a Blockchain.
 impl Blockchain {
     /// Creates a new blockchain from a given environment and network ID.
@@ -123,21 +134,37 @@ impl Blockchain {
             genesis_parameters(&genesis_block.unwrap_macro().header);
 
         // Load main chain from store.
-        let main_chain = chain_store
-            .get_chain_info(&head_hash, true, None)
-            .ok_or(BlockchainError::FailedLoadingMainChain)?;
+        let main_chain = chain_store.get_chain_info(&head_hash, true, None);
 
         // Check that chain/accounts state is consistent.
-        let accounts = Accounts::new(env.clone());
-
-        if main_chain.head.state_root() != &accounts.get_root(None) {
-            log::error!(
-                "Main chain's head state root: {:?}, Account state root: {:?}",
-                main_chain.head.state_root(),
-                &accounts.get_root(None)
-            );
-            return Err(BlockchainError::InconsistentState);
-        }
+        let accounts = Arc::new(Accounts::new(env.clone()));
+
+        let (head_hash, main_chain, recovered) = match main_chain {
+            Some(main_chain) if main_chain.head.state_root() == &accounts.get_root(None) => {
+                (head_hash, main_chain, false)
+            }
+            main_chain => {
+                // The last commit was only partially written. Roll back to the block that matches
+                // the accounts.
+                match &main_chain {
+                    Some(main_chain) => log::error!(
+                        "Main chain's head state root: {:?}, Account state root: {:?}",
+                        main_chain.head.state_root(),
+                        &accounts.get_root(None)
+                    ),
+                    None => log::error!("Main chain's head {} not found", head_hash),
+                }
+
+                let (head_hash, main_chain) = Self::recover_tail(
+                    &env,
+                    &chain_store,
+                    &history_store,
+                    &accounts,
+                    main_chain.map(|main_chain| main_chain.head.block_number()),
+                )?;
+                (head_hash, main_chain, true)
+            }
+        };
 
         // Load macro chain from store.
         let macro_chain_info = chain_store
@@ -188,7 +215,17 @@ impl Blockchain {
             _ => return Err(BlockchainError::InconsistentState),
         };
 
-        Ok(Blockchain {
+        let history_store = Arc::new(history_store);
+        let reader = BlockchainReader::new(
+            network_id,
+            env.clone(),
+            Arc::clone(&accounts),
+            Arc::clone(&history_store),
+            head_hash.clone(),
+            main_chain.head.clone(),
+        );
+
+        let mut blockchain = Blockchain {
             env,
             network_id,
             time,
@@ -208,11 +245,20 @@ impl Blockchain {
                 previous_slots: last_slots,
             },
             tx_verification_cache: Arc::new(DEFAULT_TX_VERIFICATION_CACHE),
+            prune_micro_bodies: false,
+            reader,
             #[cfg(feature = "metrics")]
             metrics: BlockchainMetrics::default(),
             genesis_supply,
             genesis_timestamp,
-        })
+        };
+
+        // Only the blocks up to the last macro block are final, the others are synced again.
+        if recovered {
+            blockchain.revert_to_macro_head()?;
+        }
+
+        Ok(blockchain)
     }
 
     /// Initializes a blockchain.
@@ -235,7 +281,7 @@ impl Blockchain {
         let main_chain = ChainInfo::new(genesis_block, true);
 
         // Initialize accounts.
-        let accounts = Accounts::new(env.clone());
+        let accounts = Arc::new(Accounts::new(env.clone()));
         let mut txn = WriteTransaction::new(&env);
         accounts.init(&mut txn, genesis_accounts);
 
@@ -244,6 +290,16 @@ impl Blockchain {
         chain_store.set_head(&mut txn, &head_hash);
         txn.commit();
 
+        let history_store = Arc::new(history_store);
+        let reader = BlockchainReader::new(
+            network_id,
+            env.clone(),
+            Arc::clone(&accounts),
+            Arc::clone(&history_store),
+            head_hash.clone(),
+            main_chain.head.clone(),
+        );
+
         Ok(Blockchain {
             env,
             network_id,
@@ -264,6 +320,8 @@ impl Blockchain {
                 previous_slots: Some(Validators::default()),
             },
             tx_verification_cache: Arc::new(DEFAULT_TX_VERIFICATION_CACHE),
+            prune_micro_bodies: false,
+            reader,
             #[cfg(feature = "metrics")]
             metrics: BlockchainMetrics::default(),
             genesis_supply,
@@ -271,6 +329,11 @@ impl Blockchain {
         })
     }
 
+    /// Returns a handle to read the head and the accounts without taking the blockchain lock.
+    pub fn reader(&self) -> BlockchainReader {
+        self.reader.clone()
+    }
+
     pub fn read_transaction(&self) -> ReadTransaction {
         ReadTransaction::new(&self.env)
     }
@@ -278,6 +341,11 @@ impl Blockchain {
     pub fn write_transaction(&self) -> WriteTransaction {
         WriteTransaction::new(&self.env)
     }
+
+    /// Returns the transaction metrics and the map usage of the database.
+    pub fn database_metrics(&self) -> DatabaseMetricsSnapshot {
+        self.env.metrics()
+    }
 }
 
 pub trait TransactionVerificationCache: Send + Sync {
@@ -1,16 +1,35 @@
-use parking_lot::{RwLockUpgradableReadGuard, RwLockWriteGuard};
-
+use beserial::SerializingError;
 use nimiq_block::{Block, BlockError, TendermintProof};
 use nimiq_database::WriteTransaction;
 use nimiq_hash::{Blake2bHash, Hash};
 use nimiq_primitives::coin::Coin;
 use nimiq_primitives::policy;
+use nimiq_utils::lock_metrics::{RwLockUpgradableReadGuard, RwLockWriteGuard};
 
 use crate::chain_info::ChainInfo;
 use crate::history_store::{ExtTxData, ExtendedTransaction, HistoryStore};
 use crate::{AbstractBlockchain, Blockchain, BlockchainEvent, PushError, PushResult};
 use nimiq_account::{Inherent, InherentType};
 
+/// The history of an epoch that is pushed with history sync.
+///
+/// The history is iterated several times while it is pushed, so a source can read it from disk
+/// each time instead of holding the whole epoch in memory.
+pub trait HistorySource {
+    /// Iterates over the extended transactions of the epoch, in the order of the history tree.
+    fn iter(
+        &mut self,
+    ) -> Box<dyn Iterator<Item = Result<ExtendedTransaction, SerializingError>> + '_>;
+}
+
+impl HistorySource for &[ExtendedTransaction] {
+    fn iter(
+        &mut self,
+    ) -> Box<dyn Iterator<Item = Result<ExtendedTransaction, SerializingError>> + '_> {
+        Box::new((**self).iter().cloned().map(Ok))
+    }
+}
+
 /// Implements methods to push macro blocks into the chain when an history node is syncing. This
 /// type of syncing is called history syncing. It works by having the node get all the election
 /// macro blocks since genesis plus the last macro block (most likely it will be a checkpoint block,
@@ -30,7 +49,17 @@ impl Blockchain {
     pub fn push_history_sync(
         this: RwLockUpgradableReadGuard<Self>,
         block: Block,
-        history: &[ExtendedTransaction],
+        mut history: &[ExtendedTransaction],
+    ) -> Result<PushResult, PushError> {
+        Self::push_history_sync_from(this, block, &mut history)
+    }
+
+    /// Like [`Blockchain::push_history_sync`], but iterates over the history instead of holding it
+    /// in memory. Only the transactions of a single block are held in memory at a time.
+    pub fn push_history_sync_from(
+        this: RwLockUpgradableReadGuard<Self>,
+        block: Block,
+        history: &mut dyn HistorySource,
     ) -> Result<PushResult, PushError> {
         // Check that it is a macro block. We can't push micro blocks with this function.
         assert!(
@@ -105,7 +134,7 @@ impl Blockchain {
         }
 
         // Check the history root.
-        let history_root = HistoryStore::root_from_ext_txs(history)
+        let history_root = HistoryStore::root_from_ext_tx_iter(history.iter())?
             .ok_or(PushError::InvalidBlock(BlockError::InvalidHistoryRoot))?;
 
         if *block.history_root() != history_root {
@@ -151,31 +180,36 @@ impl Blockchain {
     fn extend_history_sync(
         this: RwLockUpgradableReadGuard<Blockchain>,
         block: Block,
-        history: &[ExtendedTransaction],
+        history: &mut dyn HistorySource,
         mut prev_macro_info: ChainInfo,
     ) -> Result<PushResult, PushError> {
-        // Create a new database write transaction.
-        let mut txn = this.write_transaction();
-
         // Get the block hash.
         let block_hash = block.hash();
 
         // Calculate the cumulative transaction fees for the current batch. This is necessary to
-        // create the chain info for the block.
+        // create the chain info for the block. The history is sorted by block number, so the
+        // transactions of the current batch are the last ones.
         let mut cum_tx_fees = Coin::ZERO;
+        let mut history_len = 0;
 
         let current_batch = policy::batch_at(block.block_number());
 
-        for i in (0..history.len()).rev() {
-            if policy::batch_at(history[i].block_number) != current_batch {
-                break;
+        for ext_tx in history.iter() {
+            let ext_tx = ext_tx?;
+            history_len += 1;
+
+            if policy::batch_at(ext_tx.block_number) != current_batch {
+                continue;
             }
 
-            if let ExtTxData::Basic(tx) = &history[i].data {
+            if let ExtTxData::Basic(tx) = &ext_tx.data {
                 cum_tx_fees += tx.fee;
             }
         }
 
+        // Create a new database write transaction.
+        let mut txn = this.write_transaction();
+
         // Create the chain info for the current block and store it.
         let chain_info = ChainInfo {
             on_main_chain: true,
@@ -203,87 +237,48 @@ impl Blockchain {
         // We might already know the given epoch partially.
         // Revert our chain to a common ancestor state in case we have adopted a different history.
         // Also skip over any transactions that we already know.
-        let first_new_ext_tx = this.revert_to_common_state(&block, history, &mut txn);
+        let first_new_ext_tx = match this.revert_to_common_state(&block, history, &mut txn) {
+            Ok(first_new_ext_tx) => first_new_ext_tx,
+            Err(e) => {
+                txn.abort();
+                return Err(e.into());
+            }
+        };
 
-        // Separate the extended transactions by block number and type.
-        // We know it comes sorted because we already checked it against the history root and
-        // extended transactions in the history tree come sorted by block number and type.
+        // Apply the new extended transactions one block at a time and add them to the history
+        // tree. We know they come sorted because we already checked them against the history root
+        // and extended transactions in the history tree come sorted by block number and type.
         // Ignore the extended transactions that were already added in past macro blocks.
-        let mut block_numbers = vec![];
-        let mut block_timestamps = vec![];
-        let mut block_transactions = vec![];
-        let mut block_inherents = vec![];
-        let mut prev = 0;
-
+        let mut pending = PendingBlock::default();
         for ext_tx in history.iter().skip(first_new_ext_tx) {
-            if ext_tx.block_number > prev {
-                block_numbers.push(ext_tx.block_number);
-                block_timestamps.push(ext_tx.block_time);
-                block_transactions.push(vec![]);
-                block_inherents.push(vec![]);
-                prev = ext_tx.block_number;
-            }
-
-            match &ext_tx.data {
-                ExtTxData::Basic(tx) => block_transactions.last_mut().unwrap().push(tx.clone()),
-                ExtTxData::Inherent(tx) => block_inherents.last_mut().unwrap().push(tx.clone()),
-            }
-        }
-
-        // We go over the blocks one more time and add the FinalizeBatch and FinalizeEpoch inherents
-        // to the macro blocks. This is necessary because the History Store doesn't store those inherents
-        // so we need to add them again in order to correctly sync.
-        for (i, block_number) in block_numbers.iter().enumerate() {
-            if policy::is_macro_block_at(*block_number) {
-                let finalize_batch = Inherent {
-                    ty: InherentType::FinalizeBatch,
-                    target: this.staking_contract_address(),
-                    value: Coin::ZERO,
-                    data: vec![],
-                };
-
-                block_inherents.get_mut(i).unwrap().push(finalize_batch);
-
-                if policy::is_election_block_at(*block_number) {
-                    let finalize_epoch = Inherent {
-                        ty: InherentType::FinalizeEpoch,
-                        target: this.staking_contract_address(),
-                        value: Coin::ZERO,
-                        data: vec![],
-                    };
+            let ext_tx = match ext_tx {
+                Ok(ext_tx) => ext_tx,
+                Err(e) => {
+                    txn.abort();
+                    return Err(e.into());
+                }
+            };
 
-                    block_inherents.get_mut(i).unwrap().push(finalize_epoch);
+            if ext_tx.block_number > pending.block_number {
+                if let Err(e) = this.apply_history_block(&block, &pending, &mut txn) {
+                    txn.abort();
+                    #[cfg(feature = "metrics")]
+                    this.metrics.note_invalid_block();
+                    return Err(e);
                 }
+                pending = PendingBlock {
+                    block_number: ext_tx.block_number,
+                    block_time: ext_tx.block_time,
+                    ..Default::default()
+                };
             }
+            pending.ext_txs.push(ext_tx);
         }
-
-        // Update the accounts tree, one block at a time.
-        for i in 0..block_numbers.len() {
-            // Commit block to AccountsTree and create the receipts.
-            let receipts = this.state.accounts.commit_batch(
-                &mut txn,
-                &block_transactions[i],
-                &block_inherents[i],
-                block_numbers[i],
-                block_timestamps[i],
-            );
-
-            // Check if the receipts contain an error.
-            if let Err(e) = receipts {
-                warn!(
-                    "Rejecting block {} - commit of block #{} ({} transactions, {} inherents) failed: {:?}",
-                    block,
-                    block_numbers[i],
-                    block_transactions[i].len(),
-                    block_inherents[i].len(),
-                    e
-                );
-
-                txn.abort();
-                #[cfg(feature = "metrics")]
-                this.metrics.note_invalid_block();
-                return Err(PushError::AccountsError(e));
-            }
+        if let Err(e) = this.apply_history_block(&block, &pending, &mut txn) {
+            txn.abort();
+            #[cfg(feature = "metrics")]
+            this.metrics.note_invalid_block();
+            return Err(e);
         }
         this.state.accounts.finalize_batch(&mut txn);
 
@@ -309,15 +304,9 @@ impl Blockchain {
         // as rebranching across this block is not possible.
         this.chain_store.clear_receipts(&mut txn);
 
-        // Store the new extended transactions into the History tree.
-        this.history_store.add_to_history(
-            &mut txn,
-            block.epoch_number(),
-            &history[first_new_ext_tx..],
-        );
-
         // Give up database transactions and push lock before creating notifications.
-        txn.commit();
+        this.reader
+            .commit(txn, block_hash.clone(), chain_info.head.clone());
 
         // Update the blockchain state.
         let mut this = RwLockUpgradableReadGuard::upgrade(this);
@@ -346,7 +335,7 @@ impl Blockchain {
         debug!(
             "Accepted epoch #{} with {} items (history_sync)",
             block.epoch_number(),
-            history.len()
+            history_len
         );
 
         if is_election_block {
@@ -360,18 +349,90 @@ impl Blockchain {
         Ok(PushResult::Extended)
     }
 
+    /// Commits the extended transactions of a single block to the accounts tree and adds them to
+    /// the history tree. Macro blocks get the FinalizeBatch and FinalizeEpoch inherents, which the
+    /// history store doesn't store.
+    fn apply_history_block(
+        &self,
+        block: &Block,
+        pending: &PendingBlock,
+        txn: &mut WriteTransaction,
+    ) -> Result<(), PushError> {
+        if pending.ext_txs.is_empty() {
+            return Ok(());
+        }
+
+        let mut transactions = vec![];
+        let mut inherents = vec![];
+        for ext_tx in &pending.ext_txs {
+            match &ext_tx.data {
+                ExtTxData::Basic(tx) => transactions.push(tx.clone()),
+                ExtTxData::Inherent(tx) => inherents.push(tx.clone()),
+            }
+        }
+
+        if policy::is_macro_block_at(pending.block_number) {
+            inherents.push(Inherent {
+                ty: InherentType::FinalizeBatch,
+                target: self.staking_contract_address(),
+                value: Coin::ZERO,
+                data: vec![],
+            });
+
+            if policy::is_election_block_at(pending.block_number) {
+                inherents.push(Inherent {
+                    ty: InherentType::FinalizeEpoch,
+                    target: self.staking_contract_address(),
+                    value: Coin::ZERO,
+                    data: vec![],
+                });
+            }
+        }
+
+        // Commit block to AccountsTree and create the receipts.
+        if let Err(e) = self.state.accounts.commit_batch(
+            txn,
+            &transactions,
+            &inherents,
+            pending.block_number,
+            pending.block_time,
+        ) {
+            warn!(
+                "Rejecting block {} - commit of block #{} ({} transactions, {} inherents) failed: {:?}",
+                block,
+                pending.block_number,
+                transactions.len(),
+                inherents.len(),
+                e
+            );
+            return Err(PushError::AccountsError(e));
+        }
+
+        // Store the new extended transactions into the History tree.
+        self.history_store
+            .add_to_history(txn, block.epoch_number(), &pending.ext_txs);
+
+        Ok(())
+    }
+
     fn revert_to_common_state(
         &self,
         block: &Block,
-        history: &[ExtendedTransaction],
+        history: &mut dyn HistorySource,
         txn: &mut WriteTransaction,
-    ) -> usize {
+    ) -> Result<usize, SerializingError> {
         // Find the index of the first extended transaction in the current batch.
         let last_macro_block = policy::last_macro_block(self.block_number());
-        let mut first_new_ext_tx = history
-            .iter()
-            .position(|ext_tx| ext_tx.block_number > last_macro_block)
-            .unwrap_or(history.len());
+        let mut history_len = 0;
+        let mut first_new_ext_tx = None;
+        for ext_tx in history.iter() {
+            let ext_tx = ext_tx?;
+            if first_new_ext_tx.is_none() && ext_tx.block_number > last_macro_block {
+                first_new_ext_tx = Some(history_len);
+            }
+            history_len += 1;
+        }
+        let mut first_new_ext_tx = first_new_ext_tx.unwrap_or(history_len);
 
         // Check if our adopted non-final history matches the given history.
         // Revert any blocks that don't match.
@@ -385,10 +446,10 @@ impl Blockchain {
             let mut given = history.iter().skip(first_new_ext_tx);
             let mut last_known_block = None;
             let diverging_block = loop {
-                match (known.next(), given.next()) {
+                match (known.next(), given.next().transpose()?) {
                     (Some(known_tx), Some(given_tx)) => {
                         last_known_block = Some(known_tx.block_number);
-                        if *known_tx != *given_tx {
+                        if *known_tx != given_tx {
                             break Some(known_tx.block_number);
                         }
                     }
@@ -404,6 +465,7 @@ impl Blockchain {
                     (None, None) => break None,
                 }
             };
+            drop(given);
 
             if let Some(diverging_block) = diverging_block {
                 // The histories diverge, so revert our state to the block before the divergence.
@@ -412,11 +474,14 @@ impl Blockchain {
                     .expect("Failed to revert chain");
 
                 // TODO We could incorporate this into the parallel iteration loop above.
-                first_new_ext_tx += history
-                    .iter()
-                    .skip(first_new_ext_tx)
-                    .position(|ext_tx| ext_tx.block_number >= diverging_block)
-                    .unwrap_or(history.len() - first_new_ext_tx);
+                let mut skipped = 0;
+                for ext_tx in history.iter().skip(first_new_ext_tx) {
+                    if ext_tx?.block_number >= diverging_block {
+                        break;
+                    }
+                    skipped += 1;
+                }
+                first_new_ext_tx += skipped;
             } else {
                 // The histories match, so we can skip over all known transactions.
                 first_new_ext_tx += known_history.len();
@@ -425,15 +490,16 @@ impl Blockchain {
             // We have micro blocks for the current batch but the history is empty.
             // Check if the given history contains any items before our current block; if so, we
             // need to revert.
-            let first_block_number = history[first_new_ext_tx].block_number;
-            if first_block_number <= self.block_number() {
-                let num_blocks_to_revert = self.block_number() - first_block_number + 1;
-                self.revert_blocks(num_blocks_to_revert, txn)
-                    .expect("Failed to revert chain");
+            if let Some(first_new) = history.iter().nth(first_new_ext_tx).transpose()? {
+                if first_new.block_number <= self.block_number() {
+                    let num_blocks_to_revert = self.block_number() - first_new.block_number + 1;
+                    self.revert_blocks(num_blocks_to_revert, txn)
+                        .expect("Failed to revert chain");
+                }
             }
         };
 
-        first_new_ext_tx
+        Ok(first_new_ext_tx)
     }
 
     /// Reverts a given number of micro blocks from the blockchain.
@@ -480,3 +546,11 @@ impl Blockchain {
         Ok(())
     }
 }
+
+/// The extended transactions of a single block while the history of an epoch is applied.
+#[derive(Default)]
+struct PendingBlock {
+    block_number: u32,
+    block_time: u64,
+    ext_txs: Vec<ExtendedTransaction>,
+}
@@ -4,6 +4,9 @@ pub mod blockchain;
 pub mod history_sync;
 pub mod inherents;
 pub mod push;
+pub mod recovery;
+pub mod reindex;
+pub mod schedule;
 pub mod slots;
 pub mod verify;
 pub mod wrappers;
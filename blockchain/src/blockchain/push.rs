@@ -1,11 +1,13 @@
 use std::ops::Deref;
-
-use parking_lot::{RwLockUpgradableReadGuard, RwLockWriteGuard};
+use std::time::Duration;
 
 use nimiq_block::{Block, ForkProof};
 use nimiq_database::WriteTransaction;
 use nimiq_hash::{Blake2bHash, Hash};
 use nimiq_primitives::policy;
+use nimiq_utils::lock_metrics::{RwLockUpgradableReadGuard, RwLockWriteGuard};
+use nimiq_utils::memory::{with_allocation_tag, AllocationTag};
+use nimiq_utils::stall;
 use nimiq_vrf::VrfEntropy;
 
 use crate::blockchain_state::BlockchainState;
@@ -16,6 +18,10 @@ use crate::{
     PushResult,
 };
 
+/// Pushing a block that takes longer than two seconds is reported as a stall. Pushes are
+/// serialized by the upgradable read lock of the blockchain, so they share one span.
+static PUSH_SPAN: stall::Span = stall::Span::new("Blockchain::push", Duration::from_secs(2));
+
 /// Implements methods to push blocks into the chain. This is used when the node has already synced
 /// and is just receiving newly produced blocks. It is also used for the final phase of syncing,
 /// when the node is just receiving micro blocks.
@@ -28,6 +34,8 @@ impl Blockchain {
         block: Block,
         trusted: bool,
     ) -> Result<PushResult, PushError> {
+        let _stall_span = PUSH_SPAN.enter();
+
         // Ignore all blocks that precede (or are at the same height) as the most recent accepted
         // macro block.
         let last_macro_block = policy::last_macro_block(this.block_number());
@@ -206,7 +214,9 @@ impl Blockchain {
         this: RwLockUpgradableReadGuard<Self>,
         block: Block,
     ) -> Result<PushResult, PushError> {
-        Self::do_push(this, block, false)
+        with_allocation_tag(AllocationTag::Blockchain, || {
+            Self::do_push(this, block, false)
+        })
     }
 
     // To retain the option of having already taken a lock before this call the self was exchanged.
@@ -221,7 +231,9 @@ impl Blockchain {
         this: RwLockUpgradableReadGuard<Self>,
         block: Block,
     ) -> Result<PushResult, PushError> {
-        Self::do_push(this, block, true)
+        with_allocation_tag(AllocationTag::Blockchain, || {
+            Self::do_push(this, block, true)
+        })
     }
 
     /// Extends the current main chain.
@@ -257,14 +269,27 @@ impl Blockchain {
             .put_chain_info(&mut txn, chain_info.head.parent_hash(), &prev_info, false);
         this.chain_store.set_head(&mut txn, &block_hash);
 
+        let mut compression_dictionary = None;
         if is_election_block {
             this.chain_store.prune_epoch(
                 policy::epoch_at(block_number).saturating_sub(MAX_EPOCHS_STORED),
                 &mut txn,
             );
+            compression_dictionary = this
+                .chain_store
+                .train_compression_dictionary(policy::epoch_at(block_number), &mut txn);
         }
 
-        txn.commit();
+        if is_macro_block && this.prune_micro_bodies {
+            this.chain_store
+                .prune_micro_bodies(policy::batch_at(block_number), &mut txn);
+        }
+
+        this.reader
+            .commit(txn, block_hash.clone(), chain_info.head.clone());
+        if let Some(dictionary) = compression_dictionary {
+            this.chain_store.add_compression_dictionary(dictionary);
+        }
 
         // Upgrade the lock as late as possible.
         let mut this = RwLockUpgradableReadGuard::upgrade(this);
@@ -476,7 +501,8 @@ impl Blockchain {
         let new_head_hash = &fork_chain[0].0;
         let new_head_info = &fork_chain[0].1;
         this.chain_store.set_head(&mut write_txn, new_head_hash);
-        write_txn.commit();
+        this.reader
+            .commit(write_txn, new_head_hash.clone(), new_head_info.head.clone());
 
         // Upgrade the lock as late as possible.
         let mut this = RwLockUpgradableReadGuard::upgrade(this);
@@ -0,0 +1,126 @@
+use nimiq_account::Accounts;
+use nimiq_block::Block;
+use nimiq_database::{Environment, ReadTransaction, WriteTransaction};
+use nimiq_hash::Blake2bHash;
+use nimiq_primitives::policy;
+
+use crate::chain_info::ChainInfo;
+use crate::chain_store::ChainStore;
+use crate::history_store::HistoryStore;
+use crate::{Blockchain, BlockchainError};
+
+/// Implements methods to recover from a chain tail that was only partially written, e.g. because
+/// the node crashed during a commit.
+impl Blockchain {
+    /// Rolls the chain store back to the main chain block whose state matches the accounts. The
+    /// block is searched for in the batch of the last stored macro block. All main chain blocks
+    /// after it and their history are removed. Returns the hash and chain info of the block, which
+    /// becomes the new head.
+    ///
+    /// `head_block_number` is the block number of the stored head, if its chain info could be
+    /// loaded. Otherwise, the end of the chain is taken from the history.
+    pub(crate) fn recover_tail(
+        env: &Environment,
+        chain_store: &ChainStore,
+        history_store: &HistoryStore,
+        accounts: &Accounts,
+        head_block_number: Option<u32>,
+    ) -> Result<(Blake2bHash, ChainInfo), BlockchainError> {
+        let read_txn = ReadTransaction::new(env);
+
+        let tail_block_number = head_block_number
+            .or_else(|| history_store.last_block_number(&read_txn))
+            .ok_or(BlockchainError::FailedLoadingMainChain)?;
+
+        let mut chain_info = chain_store
+            .get_chain_info_at(
+                policy::last_macro_block(tail_block_number),
+                true,
+                Some(&read_txn),
+            )
+            .ok_or(BlockchainError::FailedLoadingMainChain)?;
+        let mut hash = chain_info.head.hash();
+
+        // Follow the main chain from the macro block until we reach the block that the accounts
+        // were last committed for.
+        let accounts_root = accounts.get_root(Some(&read_txn));
+        while chain_info.head.state_root() != &accounts_root {
+            hash = chain_info
+                .main_chain_successor
+                .clone()
+                .ok_or(BlockchainError::InconsistentState)?;
+            chain_info = chain_store
+                .get_chain_info(&hash, true, Some(&read_txn))
+                .ok_or(BlockchainError::InconsistentState)?;
+        }
+        read_txn.close();
+
+        warn!(
+            "Corrupted chain tail, rolling back to block {} (stored head was at {})",
+            chain_info.head, tail_block_number
+        );
+
+        let block_number = chain_info.head.block_number();
+        let mut txn = WriteTransaction::new(env);
+        chain_store.truncate_main_chain(&mut txn, block_number);
+        history_store.truncate_history(&mut txn, block_number);
+
+        chain_info.main_chain_successor = None;
+        chain_store.put_chain_info(&mut txn, &hash, &chain_info, false);
+        chain_store.set_head(&mut txn, &hash);
+        txn.commit();
+
+        Ok((hash, chain_info))
+    }
+
+    /// Reverts the micro blocks after the last macro block, so that the chain ends with a block
+    /// that is final. The reverted blocks are removed from the store and are synced again from
+    /// the network.
+    pub(crate) fn revert_to_macro_head(&mut self) -> Result<(), BlockchainError> {
+        let mut txn = self.write_transaction();
+
+        let mut hash = self.state.head_hash.clone();
+        let mut chain_info = self.state.main_chain.clone();
+
+        while let Block::Micro(ref micro_block) = chain_info.head {
+            let prev_hash = micro_block.header.parent_hash.clone();
+            let prev_info = self
+                .chain_store
+                .get_chain_info(&prev_hash, true, Some(&txn))
+                .ok_or(BlockchainError::FailedLoadingMainChain)?;
+
+            self.revert_accounts(
+                &self.state.accounts,
+                &mut txn,
+                micro_block,
+                prev_info.head.seed().entropy(),
+                prev_info.head.next_view_number(),
+            )
+            .map_err(|_| BlockchainError::InconsistentState)?;
+
+            self.chain_store
+                .remove_chain_info(&mut txn, &hash, micro_block.header.block_number);
+
+            hash = prev_hash;
+            chain_info = prev_info;
+        }
+
+        chain_info.main_chain_successor = None;
+        self.chain_store
+            .put_chain_info(&mut txn, &hash, &chain_info, false);
+        self.chain_store.set_head(&mut txn, &hash);
+        self.chain_store.clear_receipts(&mut txn);
+        self.reader
+            .commit(txn, hash.clone(), chain_info.head.clone());
+
+        info!(
+            "Rolled back to macro block {}, the following blocks will be synced again",
+            chain_info.head
+        );
+
+        self.state.head_hash = hash;
+        self.state.main_chain = chain_info;
+
+        Ok(())
+    }
+}
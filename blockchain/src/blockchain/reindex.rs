@@ -0,0 +1,70 @@
+use crate::Blockchain;
+
+/// The progress of a reindex, reported after each epoch.
+#[derive(Clone, Copy, Debug)]
+pub struct ReindexProgress {
+    /// The epoch that was just reindexed.
+    pub epoch: u32,
+    /// The last epoch that will be reindexed.
+    pub last_epoch: u32,
+    /// The number of history entries that were reindexed in this epoch.
+    pub entries: usize,
+}
+
+/// Implements methods to rebuild the indices that are derived from the history.
+impl Blockchain {
+    /// Returns whether a reindex was started but not finished. The indices are incomplete until
+    /// [`Blockchain::reindex`] is called again to resume it.
+    pub fn is_reindexing(&self) -> bool {
+        self.chain_store.get_reindex_progress(None).is_some()
+    }
+
+    /// Rebuilds the address index from the history trees, which are the authoritative record of
+    /// the transactions and inherents. This is needed whenever the format of the index changes.
+    /// The address index is the only index derived from the history, there is no staking event
+    /// log or reward index yet.
+    ///
+    /// The index is rebuilt one epoch per database transaction, and the next epoch to be reindexed
+    /// is stored along with it. If the reindex is interrupted, calling this method again resumes
+    /// after the last epoch that was committed. Returns the number of epochs that were reindexed.
+    pub fn reindex<F: FnMut(ReindexProgress)>(&self, mut on_progress: F) -> u32 {
+        let last_epoch = match self.history_store.last_epoch(&self.read_transaction()) {
+            Some(epoch) => epoch,
+            None => return 0,
+        };
+
+        let first_epoch = match self.chain_store.get_reindex_progress(None) {
+            Some(epoch) => {
+                log::info!("Resuming reindex at epoch {}", epoch);
+                epoch
+            }
+            None => {
+                let mut txn = self.write_transaction();
+                self.history_store.clear_address_index(&mut txn);
+                self.chain_store.set_reindex_progress(&mut txn, Some(0));
+                txn.commit();
+                0
+            }
+        };
+
+        for epoch in first_epoch..=last_epoch {
+            let mut txn = self.write_transaction();
+            let entries = self.history_store.reindex_epoch(&mut txn, epoch);
+            let next_epoch = if epoch < last_epoch {
+                Some(epoch + 1)
+            } else {
+                None
+            };
+            self.chain_store.set_reindex_progress(&mut txn, next_epoch);
+            txn.commit();
+
+            on_progress(ReindexProgress {
+                epoch,
+                last_epoch,
+                entries,
+            });
+        }
+
+        (last_epoch + 1).saturating_sub(first_epoch)
+    }
+}
@@ -0,0 +1,110 @@
+use nimiq_primitives::policy;
+
+use crate::{AbstractBlockchain, Blockchain, ScheduleError};
+
+/// The expected block numbers and timestamps of the next batch and epoch boundaries.
+///
+/// The timestamps are extrapolated from the head block assuming that the upcoming blocks are
+/// produced at the same pace as the recent ones, so they are estimates only.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChainSchedule {
+    /// The block number of the head block.
+    pub block_number: u32,
+    /// The timestamp of the head block.
+    pub timestamp: u64,
+    /// The average time between the recent blocks in milliseconds.
+    pub average_block_time: u64,
+    /// The block number of the next macro block, which ends the current batch.
+    pub next_macro_block: u32,
+    /// The expected timestamp of the next macro block.
+    pub next_macro_block_eta: u64,
+    /// The block number of the next election block, which ends the current epoch.
+    pub next_election_block: u32,
+    /// The expected timestamp of the next election block.
+    pub next_election_block_eta: u64,
+}
+
+impl ChainSchedule {
+    /// Extrapolates the schedule from the head block at `block_number` with the given `timestamp`,
+    /// assuming that a block is produced every `average_block_time` milliseconds.
+    pub fn extrapolate(block_number: u32, timestamp: u64, average_block_time: u64) -> Self {
+        let eta = |target: u32| timestamp + u64::from(target - block_number) * average_block_time;
+
+        let next_macro_block = policy::macro_block_after(block_number);
+        let next_election_block = policy::election_block_after(block_number);
+
+        ChainSchedule {
+            block_number,
+            timestamp,
+            average_block_time,
+            next_macro_block,
+            next_macro_block_eta: eta(next_macro_block),
+            next_election_block,
+            next_election_block_eta: eta(next_election_block),
+        }
+    }
+}
+
+impl Blockchain {
+    /// Returns the expected times of the next batch and epoch boundaries, based on the average time
+    /// between the last `num_blocks` blocks. Fails if the head is the genesis block, or if the
+    /// first of these blocks isn't stored, e.g. because the node only synced the history.
+    pub fn chain_schedule(&self, num_blocks: u32) -> Result<ChainSchedule, ScheduleError> {
+        let head = self.head();
+        let block_number = head.block_number();
+        let first_block_number = block_number.saturating_sub(num_blocks.max(1));
+        if first_block_number == block_number {
+            return Err(ScheduleError::NoBlocks);
+        }
+
+        let first_block = self
+            .get_block_at(first_block_number, false, None)
+            .ok_or(ScheduleError::MissingBlock(first_block_number))?;
+        let average_block_time = head.timestamp().saturating_sub(first_block.timestamp())
+            / u64::from(block_number - first_block_number);
+
+        Ok(ChainSchedule::extrapolate(
+            block_number,
+            head.timestamp(),
+            average_block_time,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nimiq_primitives::policy;
+
+    use super::ChainSchedule;
+
+    #[test]
+    fn it_extrapolates_the_next_boundaries() {
+        let block_number = policy::EPOCH_LENGTH + 1;
+        let schedule = ChainSchedule::extrapolate(block_number, 1_000_000, 1_000);
+
+        assert_eq!(
+            schedule.next_macro_block,
+            policy::EPOCH_LENGTH + policy::BATCH_LENGTH
+        );
+        assert_eq!(
+            schedule.next_macro_block_eta,
+            1_000_000 + u64::from(policy::BATCH_LENGTH - 1) * 1_000
+        );
+        assert_eq!(schedule.next_election_block, 2 * policy::EPOCH_LENGTH);
+        assert_eq!(
+            schedule.next_election_block_eta,
+            1_000_000 + u64::from(policy::EPOCH_LENGTH - 1) * 1_000
+        );
+    }
+
+    #[test]
+    fn it_schedules_the_following_boundaries_at_a_macro_block() {
+        let schedule = ChainSchedule::extrapolate(policy::EPOCH_LENGTH, 0, 1_000);
+
+        assert_eq!(
+            schedule.next_macro_block,
+            policy::EPOCH_LENGTH + policy::BATCH_LENGTH
+        );
+        assert_eq!(schedule.next_election_block, 2 * policy::EPOCH_LENGTH);
+    }
+}
@@ -7,12 +7,55 @@ use nimiq_vrf::{Rng, VrfEntropy, VrfSeed, VrfUseCase};
 
 use crate::{AbstractBlockchain, Blockchain};
 
+#[derive(Clone, Debug)]
 pub struct Slot {
     pub number: u16,
     pub band: u16,
     pub validator: Validator,
 }
 
+/// The slot owner at a given block number and view number, together with all the inputs that went
+/// into the slot selection. This allows anyone to independently confirm which validator was
+/// supposed to produce a given block.
+#[derive(Clone, Debug)]
+pub struct SlotAssignment {
+    pub block_number: u32,
+    pub view_number: u32,
+    pub slot: Slot,
+    /// The seed of the preceding block. The slot selection is derived from its entropy.
+    pub prev_seed: VrfSeed,
+    /// The disabled slots of the preceding macro block, which are excluded from the selection.
+    pub disabled_slots: BitSet,
+}
+
+impl SlotAssignment {
+    /// Returns the VRF entropy that was used to select the slot.
+    pub fn vrf_entropy(&self) -> VrfEntropy {
+        self.prev_seed.entropy()
+    }
+
+    /// Recomputes the slot selection from the VRF entropy and the disabled slots and checks that
+    /// it results in the claimed slot, owned by the claimed validator in the given validator set.
+    pub fn verify(&self, validators: &Validators) -> bool {
+        let slot_number = Blockchain::compute_slot_number(
+            self.view_number,
+            self.vrf_entropy(),
+            self.disabled_slots.clone(),
+        );
+
+        slot_number == self.slot.number
+            && validators.get_validator_by_slot_number(slot_number).address
+                == self.slot.validator.address
+    }
+
+    /// Checks that the given seed was produced by the slot owner, i.e. that it is the valid
+    /// successor of the previous seed signed with the slot owner's signing key.
+    pub fn verify_seed(&self, seed: &VrfSeed) -> bool {
+        seed.verify(&self.prev_seed, &self.slot.validator.signing_key)
+            .is_ok()
+    }
+}
+
 /// Implements methods to handle slots and validators.
 impl Blockchain {
     /// Gets the active validators for a given epoch.
@@ -78,7 +121,36 @@ impl Blockchain {
         })
     }
 
-    fn compute_slot_number(
+    /// Returns the slot assignment at the given block number and view number, including the VRF
+    /// seed and disabled slots that were used to compute it.
+    pub fn get_slot_assignment_at(
+        &self,
+        block_number: u32,
+        view_number: u32,
+        txn: Option<&Transaction>,
+    ) -> Option<SlotAssignment> {
+        let prev_seed = self
+            .get_block_at(block_number.checked_sub(1)?, false, txn)?
+            .seed()
+            .clone();
+
+        let macro_block = self.get_block_at(policy::macro_block_before(block_number), true, txn)?;
+        let disabled_slots = macro_block.unwrap_macro().body?.disabled_set;
+
+        let slot = self.get_proposer_at(block_number, view_number, prev_seed.entropy(), txn)?;
+
+        Some(SlotAssignment {
+            block_number,
+            view_number,
+            slot,
+            prev_seed,
+            disabled_slots,
+        })
+    }
+
+    /// Computes the slot number of the proposer for the given view number from the VRF entropy of
+    /// the preceding block and the set of disabled slots.
+    pub fn compute_slot_number(
         view_number: u32,
         vrf_entropy: VrfEntropy,
         disabled_slots: BitSet,
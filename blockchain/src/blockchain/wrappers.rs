@@ -9,8 +9,8 @@ use nimiq_utils::observer::{Listener, ListenerHandle};
 use crate::blockchain_state::BlockchainState;
 #[cfg(feature = "metrics")]
 use crate::chain_metrics::BlockchainMetrics;
+use crate::reader::account_key;
 use crate::{AbstractBlockchain, Blockchain, BlockchainEvent, Direction};
-use nimiq_trie::key_nibbles::KeyNibbles;
 
 /// Implements several wrapper functions.
 impl Blockchain {
@@ -34,6 +34,13 @@ impl Blockchain {
     /// Fetches a given number of macro blocks, starting at a specific block (by its hash).
     /// It can fetch only election macro blocks if desired.
     /// Returns None if given start_block_hash is not a macro block.
+    /// Returns the block number up to which the bodies of micro blocks are pruned, or `None` if
+    /// they are kept. Macro blocks always keep their body.
+    pub fn micro_body_horizon(&self) -> Option<u32> {
+        self.prune_micro_bodies
+            .then(|| self.macro_head().header.block_number)
+    }
+
     pub fn get_macro_blocks(
         &self,
         start_block_hash: &Blake2bHash,
@@ -78,14 +85,7 @@ impl Blockchain {
     }
 
     pub fn get_account(&self, address: &Address) -> Option<Account> {
-        // TODO: Find a better place for this differentiation, it should be in a more general location.
-        let key = if *address == policy::STAKING_CONTRACT_ADDRESS {
-            StakingContract::get_key_staking_contract()
-        } else {
-            KeyNibbles::from(address)
-        };
-
-        self.state.accounts.get(&key, None)
+        self.state.accounts.get(&account_key(address), None)
     }
 
     /// Checks if we have seen some transaction with this hash inside the a validity window.
@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 use nimiq_account::Accounts;
 use nimiq_block::MacroBlock;
 use nimiq_hash::Blake2bHash;
@@ -8,8 +10,8 @@ use crate::chain_info::ChainInfo;
 /// A struct that keeps the current state of the blockchain. It summarizes the information known to
 /// a validator at the head of the blockchain.
 pub struct BlockchainState {
-    // The accounts tree.
-    pub accounts: Accounts,
+    // The accounts tree. Shared with the `BlockchainReader`.
+    pub accounts: Arc<Accounts>,
     // The chain info for the head of the main chain.
     pub main_chain: ChainInfo,
     // The hash of the head of the main chain.
@@ -1,5 +1,9 @@
 use std::sync::atomic::{AtomicUsize, Ordering};
 
+use nimiq_bls::cache::{PublicKeyCache, PublicKeyCacheStats};
+use nimiq_utils::lock_metrics::{self, LockStats};
+use nimiq_utils::memory::{self, AllocationCounters, AllocatorStats};
+
 use crate::{PushError, PushResult};
 
 #[derive(Default)]
@@ -96,4 +100,25 @@ impl BlockchainMetrics {
     pub fn block_forked_count(&self) -> usize {
         self.block_forked_count.load(Ordering::Acquire)
     }
+
+    /// The wait and hold times of the call sites of the blockchain lock.
+    pub fn lock_stats(&self) -> Vec<LockStats> {
+        lock_metrics::lock_stats()
+    }
+
+    /// The hits and misses of the cache of uncompressed BLS public keys, which is shared by all
+    /// components that verify signatures.
+    pub fn public_key_cache_stats(&self) -> PublicKeyCacheStats {
+        PublicKeyCache::global().stats()
+    }
+
+    /// The statistics of the allocator, or `None` if the client doesn't run on jemalloc.
+    pub fn allocator_stats(&self) -> Option<AllocatorStats> {
+        AllocatorStats::read()
+    }
+
+    /// The allocations of the subsystems, see [`memory::AllocationTag`].
+    pub fn allocation_counters(&self) -> Vec<AllocationCounters> {
+        memory::allocation_counters()
+    }
 }
@@ -1,3 +1,4 @@
+use beserial::Serialize;
 use nimiq_account::Receipts;
 use nimiq_block::Block;
 use nimiq_database::cursor::{ReadCursor, WriteCursor};
@@ -7,6 +8,7 @@ use nimiq_database::{
 use nimiq_hash::Blake2bHash;
 use nimiq_primitives::policy;
 
+use crate::block_compression::{self, BlockCompressor, MIN_DICTIONARY_SAMPLES};
 use crate::chain_info::ChainInfo;
 use crate::Direction;
 
@@ -25,6 +27,16 @@ pub struct ChainStore {
     height_idx: Database,
     // A database of the transaction receipts for a block, by their corresponding block hashes.
     receipt_db: Database,
+    // Compresses the block bodies, see `block_compression`.
+    compressor: BlockCompressor,
+}
+
+/// A compression dictionary that was stored in a transaction, but isn't used before the transaction
+/// is committed, see [`ChainStore::train_compression_dictionary`].
+#[derive(Debug)]
+pub struct CompressionDictionary {
+    id: u32,
+    dictionary: Vec<u8>,
 }
 
 impl ChainStore {
@@ -34,6 +46,9 @@ impl ChainStore {
     const RECEIPT_DB_NAME: &'static str = "Receipts";
 
     const HEAD_KEY: &'static str = "head";
+    const COMPRESSION_DICTIONARY_KEY: &'static str = "compressionDictionary";
+    const REINDEX_PROGRESS_KEY: &'static str = "reindexProgress";
+    const SCHEMA_VERSION_KEY: &'static str = "schemaVersion";
 
     pub fn new(env: Environment) -> Self {
         let chain_db = env.open_database(Self::CHAIN_DB_NAME.to_string());
@@ -44,12 +59,42 @@ impl ChainStore {
         );
         let receipt_db = env
             .open_database_with_flags(Self::RECEIPT_DB_NAME.to_string(), DatabaseFlags::UINT_KEYS);
+        let compressor = BlockCompressor::default();
+        {
+            let txn = ReadTransaction::new(&env);
+            let current: u32 = txn
+                .get(&chain_db, Self::COMPRESSION_DICTIONARY_KEY)
+                .unwrap_or(block_compression::NO_DICTIONARY);
+            for id in 1..=current {
+                if let Some(dictionary) = txn.get(&chain_db, Self::dictionary_key(id).as_str()) {
+                    compressor.add_dictionary(id, dictionary);
+                } else {
+                    error!("Corrupted store: Compression dictionary {} not found", id);
+                }
+            }
+        }
         ChainStore {
             env,
             chain_db,
             block_db,
             height_idx,
             receipt_db,
+            compressor,
+        }
+    }
+
+    fn dictionary_key(id: u32) -> String {
+        format!("{}/{}", Self::COMPRESSION_DICTIONARY_KEY, id)
+    }
+
+    fn get_block_body(&self, txn: &Transaction, hash: &Blake2bHash) -> Option<Block> {
+        let bytes: Vec<u8> = txn.get(&self.block_db, hash)?;
+        match self.compressor.decode(&bytes) {
+            Ok(block) => Some(block),
+            Err(e) => {
+                error!("Corrupted store: Failed to decode block {}: {}", hash, e);
+                None
+            }
         }
     }
 
@@ -85,7 +130,7 @@ impl ChainStore {
         };
 
         if include_body {
-            if let Some(block) = txn.get(&self.block_db, hash) {
+            if let Some(block) = self.get_block_body(txn, hash) {
                 chain_info.head = block;
             } else {
                 warn!("Block body requested but not present");
@@ -133,7 +178,7 @@ impl ChainStore {
         };
 
         if include_body {
-            if let Some(block) = txn.get(&self.block_db, &block_hash) {
+            if let Some(block) = self.get_block_body(txn, &block_hash) {
                 chain_info.head = block;
             } else {
                 warn!("Block body requested but not present");
@@ -156,7 +201,11 @@ impl ChainStore {
 
         // Store body if requested.
         if include_body {
-            txn.put_reserve(&self.block_db, hash, &chain_info.head);
+            txn.put(
+                &self.block_db,
+                hash,
+                &self.compressor.encode(&chain_info.head)[..],
+            );
         }
 
         // Add to height index.
@@ -170,6 +219,41 @@ impl ChainStore {
         txn.remove_item(&self.height_idx, &height, hash);
     }
 
+    /// Removes the main chain blocks above the given height, starting at the next height and going
+    /// up until there is no block at a height. Index entries that refer to a missing chain info are
+    /// removed as well. Blocks on forks are kept.
+    pub fn truncate_main_chain(&self, txn: &mut WriteTransaction, block_height: u32) {
+        let mut height = block_height + 1;
+        loop {
+            let hashes: Vec<Blake2bHash> = {
+                let mut cursor = txn.cursor(&self.height_idx);
+                let mut hashes = vec![];
+                let mut next = cursor.seek_key::<u32, Blake2bHash>(&height);
+                while let Some(hash) = next {
+                    hashes.push(hash);
+                    next = cursor
+                        .next_duplicate::<u32, Blake2bHash>()
+                        .map(|(_, hash)| hash);
+                }
+                hashes
+            };
+
+            if hashes.is_empty() {
+                break;
+            }
+
+            for hash in hashes {
+                match txn.get::<Blake2bHash, ChainInfo>(&self.chain_db, &hash) {
+                    Some(chain_info) if !chain_info.on_main_chain => {}
+                    Some(_) => self.remove_chain_info(txn, &hash, height),
+                    None => txn.remove_item(&self.height_idx, &height, &hash),
+                }
+            }
+
+            height += 1;
+        }
+    }
+
     pub fn get_block(
         &self,
         hash: &Blake2bHash,
@@ -186,7 +270,7 @@ impl ChainStore {
         };
 
         if include_body {
-            txn.get(&self.block_db, hash)
+            self.get_block_body(txn, hash)
         } else {
             txn.get(&self.chain_db, hash)
                 .map(|chain_info: ChainInfo| chain_info.head)
@@ -470,6 +554,89 @@ impl ChainStore {
         }
     }
 
+    /// Replaces the stored micro blocks of the given batch by their headers, dropping the bodies.
+    /// The blocks can still be looked up, but are returned without a body.
+    pub fn prune_micro_bodies(&self, batch_number: u32, txn: &mut WriteTransaction) {
+        for height in
+            policy::first_block_of_batch(batch_number)..policy::macro_block_of(batch_number)
+        {
+            if let Some(hash) = txn.get::<u32, Blake2bHash>(&self.height_idx, &height) {
+                // The serialization of ChainInfo ignores the block body.
+                if let Some(chain_info) = txn.get::<Blake2bHash, ChainInfo>(&self.chain_db, &hash) {
+                    txn.put(
+                        &self.block_db,
+                        &hash,
+                        &self.compressor.encode(&chain_info.head)[..],
+                    );
+                }
+            }
+        }
+    }
+
+    /// Trains a compression dictionary on the micro blocks of the given epoch and stores it in the
+    /// transaction, unless there already is one. The returned dictionary has to be passed to
+    /// `add_compression_dictionary` once the transaction is committed, so that blocks are only
+    /// compressed with dictionaries that are stored.
+    #[must_use]
+    pub fn train_compression_dictionary(
+        &self,
+        epoch_number: u32,
+        txn: &mut WriteTransaction,
+    ) -> Option<CompressionDictionary> {
+        if self.compressor.current_dictionary() != block_compression::NO_DICTIONARY {
+            return None;
+        }
+
+        let mut samples = vec![];
+        for height in policy::first_block_of(epoch_number)..policy::election_block_of(epoch_number)
+        {
+            let block = txn
+                .get::<u32, Blake2bHash>(&self.height_idx, &height)
+                .and_then(|hash| self.get_block_body(txn, &hash));
+            if let Some(Block::Micro(block)) = block {
+                if block
+                    .body
+                    .as_ref()
+                    .map_or(false, |body| !body.transactions.is_empty())
+                {
+                    samples.push(Block::Micro(block).serialize_to_vec());
+                }
+            }
+        }
+
+        if samples.len() < MIN_DICTIONARY_SAMPLES {
+            return None;
+        }
+
+        match block_compression::train_dictionary(&samples) {
+            Ok(dictionary) => {
+                let id = block_compression::NO_DICTIONARY + 1;
+                txn.put(
+                    &self.chain_db,
+                    Self::dictionary_key(id).as_str(),
+                    &dictionary[..],
+                );
+                txn.put(&self.chain_db, Self::COMPRESSION_DICTIONARY_KEY, &id);
+                info!(
+                    "Trained block compression dictionary on {} micro blocks",
+                    samples.len()
+                );
+                Some(CompressionDictionary { id, dictionary })
+            }
+            Err(e) => {
+                warn!("Failed to train block compression dictionary: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Compresses new blocks with a dictionary returned by `train_compression_dictionary`, after
+    /// the transaction that stored it was committed.
+    pub fn add_compression_dictionary(&self, dictionary: CompressionDictionary) {
+        self.compressor
+            .add_dictionary(dictionary.id, dictionary.dictionary);
+    }
+
     pub fn put_receipts(&self, txn: &mut WriteTransaction, block_height: u32, receipts: &Receipts) {
         txn.put_reserve(&self.receipt_db, &block_height, receipts);
     }
@@ -491,6 +658,35 @@ impl ChainStore {
         txn.get(&self.receipt_db, &block_height)
     }
 
+    /// Returns the schema version of the database, if it was recorded.
+    pub fn get_schema_version(&self, txn_option: Option<&Transaction>) -> Option<u32> {
+        match txn_option {
+            Some(txn) => txn.get(&self.chain_db, Self::SCHEMA_VERSION_KEY),
+            None => ReadTransaction::new(&self.env).get(&self.chain_db, Self::SCHEMA_VERSION_KEY),
+        }
+    }
+
+    pub fn set_schema_version(&self, txn: &mut WriteTransaction, version: u32) {
+        txn.put(&self.chain_db, Self::SCHEMA_VERSION_KEY, &version);
+    }
+
+    /// Returns the next epoch to be reindexed, if a reindex of the history was started and hasn't
+    /// finished yet.
+    pub fn get_reindex_progress(&self, txn_option: Option<&Transaction>) -> Option<u32> {
+        match txn_option {
+            Some(txn) => txn.get(&self.chain_db, Self::REINDEX_PROGRESS_KEY),
+            None => ReadTransaction::new(&self.env).get(&self.chain_db, Self::REINDEX_PROGRESS_KEY),
+        }
+    }
+
+    /// Stores the next epoch to be reindexed, or removes it once the reindex is finished.
+    pub fn set_reindex_progress(&self, txn: &mut WriteTransaction, next_epoch: Option<u32>) {
+        match next_epoch {
+            Some(epoch) => txn.put(&self.chain_db, Self::REINDEX_PROGRESS_KEY, &epoch),
+            None => txn.remove(&self.chain_db, Self::REINDEX_PROGRESS_KEY),
+        }
+    }
+
     pub fn clear_receipts(&self, txn: &mut WriteTransaction) {
         let mut cursor = txn.write_cursor(&self.receipt_db);
         let mut pos: Option<(u32, Receipts)> = cursor.first();
@@ -1,5 +1,7 @@
 use thiserror::Error;
 
+use beserial::SerializingError;
+
 use nimiq_account::AccountError;
 use nimiq_block::{Block, BlockError, ForkProof};
 use nimiq_hash::Blake2bHash;
@@ -31,6 +33,15 @@ pub enum BlockchainError {
     NoNetwork(NetworkId),
 }
 
+/// The reasons the chain schedule can't be estimated.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ScheduleError {
+    #[error("No blocks after the genesis block to estimate the block time from")]
+    NoBlocks,
+    #[error("Block {0} to estimate the block time from is not stored")]
+    MissingBlock(u32),
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum PushResult {
     Known,
@@ -60,6 +71,8 @@ pub enum PushError {
     InvalidFork,
     #[error("Blockchain error: {0}")]
     BlockchainError(#[from] BlockchainError),
+    #[error("Failed to read the history: {0}")]
+    HistoryUnavailable(#[from] SerializingError),
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
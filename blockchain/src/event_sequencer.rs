@@ -0,0 +1,234 @@
+use std::collections::VecDeque;
+
+use nimiq_hash::Blake2bHash;
+use nimiq_primitives::policy;
+
+use crate::BlockchainEvent;
+
+/// A change of the main chain with the cursor it was assigned.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SequencedEvent {
+    /// The block was added to the main chain.
+    Block {
+        cursor: u64,
+        block_number: u32,
+        hash: Blake2bHash,
+    },
+    /// The block was removed from the main chain by a rebranch. `reverted_cursor` is the cursor of
+    /// the `Block` event that added it, `None` if that event isn't known to the sequencer, e.g.
+    /// because the block was added before the sequencer was created.
+    Revert {
+        cursor: u64,
+        reverted_cursor: Option<u64>,
+        block_number: u32,
+        hash: Blake2bHash,
+    },
+}
+
+impl SequencedEvent {
+    pub fn cursor(&self) -> u64 {
+        match self {
+            SequencedEvent::Block { cursor, .. } | SequencedEvent::Revert { cursor, .. } => *cursor,
+        }
+    }
+}
+
+/// Assigns monotonically increasing cursors to the changes of the main chain.
+///
+/// Blockchain events only contain the new branch of a rebranch, so consumers that recorded the
+/// reverted blocks silently diverge from the chain. The sequencer turns every block added to the
+/// main chain into a `Block` event and every reverted block into a `Revert` event that references
+/// the cursor of the `Block` event it undoes. Reverts are emitted newest block first, before the
+/// blocks of the new branch.
+#[derive(Clone, Debug)]
+pub struct EventSequencer {
+    next_cursor: u64,
+    head_block_number: u32,
+    /// The most recently added blocks that are still on the main chain and their cursors, oldest
+    /// first. Rebranches can't revert macro blocks, so one batch of blocks is enough.
+    announced: VecDeque<(Blake2bHash, u64)>,
+}
+
+impl EventSequencer {
+    /// Creates a sequencer for a main chain whose head is at the given block number. The first
+    /// cursor is 1.
+    pub fn new(head_block_number: u32) -> Self {
+        Self::resume(1, head_block_number, vec![])
+    }
+
+    /// Creates a sequencer that continues where a previous one stopped.
+    pub fn resume(
+        next_cursor: u64,
+        head_block_number: u32,
+        announced: Vec<(Blake2bHash, u64)>,
+    ) -> Self {
+        EventSequencer {
+            next_cursor,
+            head_block_number,
+            announced: announced.into(),
+        }
+    }
+
+    /// The cursor the next event will be assigned.
+    pub fn next_cursor(&self) -> u64 {
+        self.next_cursor
+    }
+
+    /// The blocks that can still be reverted and the cursors they were added with, oldest first.
+    pub fn announced(&self) -> Vec<(Blake2bHash, u64)> {
+        self.announced.iter().cloned().collect()
+    }
+
+    /// Returns the sequenced events of a blockchain event.
+    pub fn sequence(&mut self, event: &BlockchainEvent) -> Vec<SequencedEvent> {
+        match event {
+            BlockchainEvent::Extended(hash)
+            | BlockchainEvent::Finalized(hash)
+            | BlockchainEvent::EpochFinalized(hash) => {
+                let block_number = self.head_block_number + 1;
+                vec![self.announce(hash.clone(), block_number)]
+            }
+            BlockchainEvent::Rebranched(reverted_blocks, adopted_blocks) => {
+                // The reverted blocks are ordered from the oldest to the newest one.
+                let reverted = reverted_blocks
+                    .iter()
+                    .rev()
+                    .map(|(hash, block)| self.revert(hash.clone(), block.block_number()));
+                let mut events: Vec<SequencedEvent> = reverted.collect();
+
+                for (hash, block) in adopted_blocks {
+                    events.push(self.announce(hash.clone(), block.block_number()));
+                }
+                events
+            }
+        }
+    }
+
+    /// Sequences a block that was added to the main chain.
+    pub fn announce(&mut self, hash: Blake2bHash, block_number: u32) -> SequencedEvent {
+        let cursor = self.take_cursor();
+
+        if self.announced.len() >= policy::BATCH_LENGTH as usize {
+            self.announced.pop_front();
+        }
+        self.announced.push_back((hash.clone(), cursor));
+        self.head_block_number = block_number;
+
+        SequencedEvent::Block {
+            cursor,
+            block_number,
+            hash,
+        }
+    }
+
+    /// Sequences a block that was removed from the main chain. Blocks must be reverted newest first.
+    pub fn revert(&mut self, hash: Blake2bHash, block_number: u32) -> SequencedEvent {
+        let cursor = self.take_cursor();
+
+        let reverted_cursor = match self.announced.back() {
+            Some((announced_hash, announced_cursor)) if *announced_hash == hash => {
+                let announced_cursor = *announced_cursor;
+                self.announced.pop_back();
+                Some(announced_cursor)
+            }
+            _ => None,
+        };
+        self.head_block_number = block_number.saturating_sub(1);
+
+        SequencedEvent::Revert {
+            cursor,
+            reverted_cursor,
+            block_number,
+            hash,
+        }
+    }
+
+    fn take_cursor(&mut self) -> u64 {
+        let cursor = self.next_cursor;
+        self.next_cursor += 1;
+        cursor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash(byte: u8) -> Blake2bHash {
+        Blake2bHash::from([byte; 32])
+    }
+
+    #[test]
+    fn reverts_reference_the_cursors_of_the_reverted_blocks() {
+        let mut sequencer = EventSequencer::new(10);
+
+        assert_eq!(
+            sequencer.sequence(&BlockchainEvent::Extended(hash(1))),
+            vec![SequencedEvent::Block {
+                cursor: 1,
+                block_number: 11,
+                hash: hash(1),
+            }]
+        );
+        sequencer.announce(hash(2), 12);
+
+        assert_eq!(
+            sequencer.revert(hash(2), 12),
+            SequencedEvent::Revert {
+                cursor: 3,
+                reverted_cursor: Some(2),
+                block_number: 12,
+                hash: hash(2),
+            }
+        );
+        assert_eq!(
+            sequencer.revert(hash(1), 11),
+            SequencedEvent::Revert {
+                cursor: 4,
+                reverted_cursor: Some(1),
+                block_number: 11,
+                hash: hash(1),
+            }
+        );
+
+        // Blocks added before the sequencer was created aren't known.
+        assert_eq!(
+            sequencer.revert(hash(3), 10),
+            SequencedEvent::Revert {
+                cursor: 5,
+                reverted_cursor: None,
+                block_number: 10,
+                hash: hash(3),
+            }
+        );
+
+        // The next block is added on top of the new head.
+        assert_eq!(
+            sequencer.sequence(&BlockchainEvent::Extended(hash(4))),
+            vec![SequencedEvent::Block {
+                cursor: 6,
+                block_number: 10,
+                hash: hash(4),
+            }]
+        );
+    }
+
+    #[test]
+    fn resumed_sequencer_continues_the_cursors() {
+        let mut sequencer = EventSequencer::new(0);
+        sequencer.announce(hash(1), 1);
+        sequencer.announce(hash(2), 2);
+
+        let mut resumed = EventSequencer::resume(sequencer.next_cursor(), 2, sequencer.announced());
+        assert_eq!(resumed.revert(hash(2), 2).cursor(), 3);
+        assert_eq!(
+            resumed.revert(hash(1), 1),
+            SequencedEvent::Revert {
+                cursor: 4,
+                reverted_cursor: Some(1),
+                block_number: 1,
+                hash: hash(1),
+            }
+        );
+    }
+}
@@ -1,6 +1,10 @@
 use std::io;
 
-use beserial::{Deserialize, ReadBytesExt, Serialize, SerializingError, WriteBytesExt};
+use beserial::schema::{define, Definitions};
+use beserial::{
+    Deserialize, FieldSchema, ReadBytesExt, Schema, Serialize, SerializingError, TypeSchema,
+    VariantSchema, WriteBytesExt,
+};
 use nimiq_account::{Inherent, InherentType};
 use nimiq_database::{FromDatabaseValue, IntoDatabaseValue};
 use nimiq_hash::{Blake2bHash, Hash};
@@ -187,6 +191,32 @@ impl Deserialize for ExtendedTransaction {
     }
 }
 
+impl Schema for ExtendedTransaction {
+    fn name() -> String {
+        "ExtendedTransaction".to_string()
+    }
+
+    fn schema() -> TypeSchema {
+        TypeSchema::Struct {
+            fields: vec![
+                FieldSchema::new::<NetworkId>(Some("network_id")),
+                FieldSchema::new::<u32>(Some("block_number")),
+                FieldSchema::new::<u64>(Some("block_time")),
+                FieldSchema::new::<ExtTxData>(Some("data")),
+            ],
+        }
+    }
+
+    fn collect(definitions: &mut Definitions) {
+        if define::<Self>(definitions) {
+            NetworkId::collect(definitions);
+            u32::collect(definitions);
+            u64::collect(definitions);
+            ExtTxData::collect(definitions);
+        }
+    }
+}
+
 impl IntoDatabaseValue for ExtendedTransaction {
     fn database_byte_size(&self) -> usize {
         self.serialized_size()
@@ -269,6 +299,37 @@ impl Deserialize for ExtTxData {
     }
 }
 
+impl Schema for ExtTxData {
+    fn name() -> String {
+        "ExtTxData".to_string()
+    }
+
+    fn schema() -> TypeSchema {
+        TypeSchema::Enum {
+            discriminant: "u8",
+            variants: vec![
+                VariantSchema {
+                    name: "Basic",
+                    discriminant: ExtendedTransactionDataType::Basic as u64,
+                    fields: vec![FieldSchema::new::<BlockchainTransaction>(None)],
+                },
+                VariantSchema {
+                    name: "Inherent",
+                    discriminant: ExtendedTransactionDataType::Inherent as u64,
+                    fields: vec![FieldSchema::new::<Inherent>(None)],
+                },
+            ],
+        }
+    }
+
+    fn collect(definitions: &mut Definitions) {
+        if define::<Self>(definitions) {
+            BlockchainTransaction::collect(definitions);
+            Inherent::collect(definitions);
+        }
+    }
+}
+
 /// Just a convenience enum to help with the serialization/deserialization functions.
 #[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 #[repr(u8)]
@@ -2,9 +2,9 @@ use std::cmp;
 use std::collections::VecDeque;
 
 use nimiq_account::InherentType;
-use nimiq_database::cursor::ReadCursor;
+use nimiq_database::cursor::{ReadCursor, WriteCursor};
 use nimiq_database::{
-    Database, DatabaseFlags, Environment, ReadTransaction, Transaction, WriteTransaction,
+    Cursor, Database, DatabaseFlags, Environment, ReadTransaction, Transaction, WriteTransaction,
 };
 use nimiq_hash::Blake2bHash;
 use nimiq_keys::Address;
@@ -19,7 +19,9 @@ use nimiq_primitives::policy;
 
 use crate::history_store::mmr_store::MMRStore;
 use crate::history_store::ordered_hash::OrderedHash;
-use crate::history_store::{ExtendedTransaction, HistoryTreeChunk, HistoryTreeProof};
+use crate::history_store::{
+    ExtendedTransaction, HistoryFilter, HistoryQuery, HistoryTreeChunk, HistoryTreeProof,
+};
 use crate::ExtTxData;
 
 /// A struct that contains databases to store history trees (which are Merkle Mountain Ranges
@@ -260,6 +262,25 @@ impl HistoryStore {
         tree.get_root().ok()
     }
 
+    /// Like [`HistoryStore::root_from_ext_txs`], but reads the extended transactions from an
+    /// iterator, so they don't need to be held in memory.
+    pub fn root_from_ext_tx_iter<E>(
+        ext_txs: impl Iterator<Item = Result<ExtendedTransaction, E>>,
+    ) -> Result<Option<Blake2bHash>, E> {
+        // Create a new history tree.
+        let mut tree = MerkleMountainRange::new(MemoryStore::new());
+
+        // Append the extended transactions to the history tree.
+        for tx in ext_txs {
+            if tree.push(&tx?).is_err() {
+                return Ok(None);
+            }
+        }
+
+        // Return the history root.
+        Ok(tree.get_root().ok())
+    }
+
     /// Gets an extended transaction given its transaction hash.
     pub fn get_ext_tx_by_hash(
         &self,
@@ -502,28 +523,23 @@ impl HistoryStore {
             }
         };
 
-        let mut tx_hashes = vec![];
-
-        // Seek to the first transaction hash at the given address. If there's none, stop here.
-        let mut cursor = txn.cursor(&self.address_db);
-
-        if cursor.seek_key::<Address, OrderedHash>(address).is_none() {
-            return tx_hashes;
-        }
-
-        // Then go to the last transaction hash at the given address and add it to the transaction
-        // hashes list.
-        tx_hashes.push(cursor.last_duplicate::<OrderedHash>().expect("This shouldn't panic since we already verified before that there is at least one transactions at this address!").hash);
-
-        while tx_hashes.len() < max as usize {
-            // Get previous transaction hash.
-            match cursor.prev_duplicate::<Address, OrderedHash>() {
-                Some((_, v)) => tx_hashes.push(v.hash),
-                None => break,
-            };
-        }
+        self.query(
+            HistoryFilter::new().address(address.clone()).newest_first(),
+            txn,
+        )
+        .map(|ext_tx| ext_tx.tx_hash())
+        .take(max as usize)
+        .collect()
+    }
 
-        tx_hashes
+    /// Returns the entries of the history that match the given filter. The entries are read
+    /// lazily while iterating over the result.
+    pub fn query<'txn, 'env>(
+        &'txn self,
+        filter: HistoryFilter,
+        txn: &'txn Transaction<'env>,
+    ) -> HistoryQuery<'txn, 'env> {
+        HistoryQuery::new(self, filter, txn)
     }
 
     /// Returns a proof for transactions with the given hashes. The proof also includes the extended
@@ -534,7 +550,35 @@ impl HistoryStore {
         hashes: Vec<&Blake2bHash>,
         txn_option: Option<&Transaction>,
     ) -> Option<HistoryTreeProof> {
-        // Get the leaf indexes.
+        let positions = self.get_positions(hashes, txn_option);
+        self.prove_with_position(epoch_number, positions, None, txn_option)
+    }
+
+    /// Returns a proof for transactions with the given hashes, which verifies against the history
+    /// root of the block with the given number instead of the current one. The block must be part
+    /// of the given epoch and the transactions must have been included up to this block.
+    pub fn prove_at_block(
+        &self,
+        epoch_number: u32,
+        hashes: Vec<&Blake2bHash>,
+        verifier_block_number: u32,
+        txn_option: Option<&Transaction>,
+    ) -> Option<HistoryTreeProof> {
+        let positions = self.get_positions(hashes, txn_option);
+        self.prove_with_position(
+            epoch_number,
+            positions,
+            Some(verifier_block_number),
+            txn_option,
+        )
+    }
+
+    /// Returns the leaf indexes of the transactions with the given hashes.
+    fn get_positions(
+        &self,
+        hashes: Vec<&Blake2bHash>,
+        txn_option: Option<&Transaction>,
+    ) -> Vec<usize> {
         let mut positions = vec![];
 
         for hash in hashes {
@@ -547,15 +591,17 @@ impl HistoryStore {
             positions.append(&mut indices)
         }
 
-        self.prove_with_position(epoch_number, positions, txn_option)
+        positions
     }
 
     /// Returns a proof for all the extended transactions at the given positions (leaf indexes). The
-    /// proof also includes the extended transactions.
+    /// proof also includes the extended transactions. If a verifier block number is given, the
+    /// proof verifies against the history tree as it was at that block.
     fn prove_with_position(
         &self,
         epoch_number: u32,
         positions: Vec<usize>,
+        verifier_block_number: Option<u32>,
         txn_option: Option<&Transaction>,
     ) -> Option<HistoryTreeProof> {
         let read_txn: ReadTransaction;
@@ -574,8 +620,13 @@ impl HistoryStore {
             epoch_number,
         ));
 
+        // Calculate number of nodes in the verifier's history tree.
+        let verifier_state = verifier_block_number.map(|block_number| {
+            leaf_number_to_index(self.length_at(block_number, Some(txn)) as usize)
+        });
+
         // Create Merkle proof.
-        let proof = tree.prove(&positions, None).ok()?;
+        let proof = tree.prove(&positions, verifier_state).ok()?;
 
         // Get each extended transaction from the tree.
         let mut ext_txs = vec![];
@@ -688,7 +739,7 @@ impl HistoryStore {
 
     /// Gets an extended transaction by its hash. Note that this hash is the leaf hash (see MMRHash)
     /// of the transaction, not a simple Blake2b hash of the transaction.
-    fn get_extended_tx(
+    pub(super) fn get_extended_tx(
         &self,
         leaf_hash: &Blake2bHash,
         txn_option: Option<&Transaction>,
@@ -734,6 +785,17 @@ impl HistoryStore {
             &leaf_index,
         );
 
+        self.put_address_entries(txn, ext_tx, tx_hash);
+    }
+
+    /// Inserts the hash of an extended transaction into the address database, for both the sender
+    /// and the recipient of a transaction and for the target of a reward inherent.
+    fn put_address_entries(
+        &self,
+        txn: &mut WriteTransaction,
+        ext_tx: &ExtendedTransaction,
+        tx_hash: Blake2bHash,
+    ) {
         match &ext_tx.data {
             ExtTxData::Basic(tx) => {
                 let index_tx_sender = self.get_last_tx_index_for_address(&tx.sender, Some(txn)) + 1;
@@ -778,6 +840,32 @@ impl HistoryStore {
         }
     }
 
+    /// Removes all entries from the address database.
+    pub fn clear_address_index(&self, txn: &mut WriteTransaction) {
+        let mut cursor = txn.write_cursor(&self.address_db);
+        let mut pos: Option<(Address, OrderedHash)> = cursor.first();
+
+        while pos.is_some() {
+            cursor.remove();
+            pos = cursor.next();
+        }
+    }
+
+    /// Adds the extended transactions of the given epoch to the address database, in the order
+    /// they appear in the history tree. Returns the number of extended transactions.
+    ///
+    /// This is used to rebuild the address database from the history trees. The epochs must be
+    /// reindexed in increasing order, starting with an empty address database.
+    pub fn reindex_epoch(&self, txn: &mut WriteTransaction, epoch_number: u32) -> usize {
+        let ext_txs = self.get_epoch_transactions(epoch_number, Some(txn));
+
+        for ext_tx in &ext_txs {
+            self.put_address_entries(txn, ext_tx, ext_tx.tx_hash());
+        }
+
+        ext_txs.len()
+    }
+
     /// Removes a extended transaction from the History Store's transaction databases.
     fn remove_extended_tx(
         &self,
@@ -920,7 +1008,7 @@ impl HistoryStore {
 
     /// Returns a vector containing all leaf hashes and indexes corresponding to the given
     /// transaction hash.
-    fn get_leaves_by_tx_hash(
+    pub(super) fn get_leaves_by_tx_hash(
         &self,
         tx_hash: &Blake2bHash,
         txn_option: Option<&Transaction>,
@@ -1000,6 +1088,58 @@ impl HistoryStore {
         (start, end)
     }
 
+    /// Returns a cursor over the address index.
+    pub(super) fn address_cursor<'txn>(&'txn self, txn: &'txn Transaction) -> Cursor<'txn, 'txn> {
+        txn.cursor(&self.address_db)
+    }
+
+    /// Returns the history tree of the given epoch.
+    pub(super) fn history_tree<'txn, 'env>(
+        &'txn self,
+        txn: &'txn Transaction<'env>,
+        epoch_number: u32,
+    ) -> MerkleMountainRange<Blake2bHash, MMRStore<'txn, 'env>> {
+        MerkleMountainRange::new(MMRStore::with_read_transaction(
+            &self.hist_tree_db,
+            txn,
+            epoch_number,
+        ))
+    }
+
+    /// Returns the most recent epoch with entries in the history, if there is any.
+    pub(crate) fn last_epoch(&self, txn: &Transaction) -> Option<u32> {
+        let mut cursor = txn.cursor(&self.last_leaf_db);
+        cursor
+            .last::<u32, u32>()
+            .map(|(block_number, _)| policy::epoch_at(block_number.to_be()))
+    }
+
+    /// Returns the number of the most recent block with entries in the history, if there is any.
+    pub(crate) fn last_block_number(&self, txn: &Transaction) -> Option<u32> {
+        let mut cursor = txn.cursor(&self.last_leaf_db);
+        cursor
+            .last::<u32, u32>()
+            .map(|(block_number, _)| block_number.to_be())
+    }
+
+    /// Removes the extended transactions of all blocks after the given block number, so that the
+    /// history ends with that block.
+    pub(crate) fn truncate_history(&self, txn: &mut WriteTransaction, block_number: u32) {
+        let epoch_number = policy::epoch_at(block_number);
+
+        if let Some(last_epoch) = self.last_epoch(txn) {
+            for epoch in epoch_number + 1..=last_epoch {
+                self.remove_history(txn, epoch);
+            }
+        }
+
+        let num_ext_txs = self.num_epoch_transactions(epoch_number, Some(txn))
+            - self.length_at(block_number, Some(txn)) as usize;
+        if num_ext_txs > 0 {
+            self.remove_partial_history(txn, epoch_number, num_ext_txs);
+        }
+    }
+
     /// Returns the index of the last transaction (or reward inherent) associated to the given address.
     fn get_last_tx_index_for_address(
         &self,
@@ -1038,6 +1178,7 @@ mod tests {
     use nimiq_primitives::networks::NetworkId;
     use nimiq_transaction::Transaction as BlockchainTransaction;
 
+    use crate::history_store::HistoryEntryType;
     use crate::ExtTxData;
 
     use super::*;
@@ -1407,6 +1548,205 @@ mod tests {
         assert_eq!(query_4.len(), 0);
     }
 
+    #[test]
+    fn reindex_epoch_works() {
+        // Initialize History Store.
+        let env = VolatileEnvironment::new(10).unwrap();
+        let history_store = HistoryStore::new(env.clone());
+
+        // Create extended transactions.
+        let ext_txs = gen_ext_txs();
+
+        // Add extended transactions to History Store.
+        let mut txn = WriteTransaction::new(&env);
+        history_store.add_to_history(&mut txn, 0, &ext_txs[..3]);
+        history_store.add_to_history(&mut txn, 1, &ext_txs[3..]);
+
+        let address =
+            Address::from_user_friendly_address("NQ09 VF5Y 1PKV MRM4 5LE1 55KV P6R2 GXYJ XYQF")
+                .unwrap();
+        let expected = history_store.get_tx_hashes_by_address(&address, 99, Some(&txn));
+
+        // Clear the address index.
+        history_store.clear_address_index(&mut txn);
+        assert!(history_store
+            .get_tx_hashes_by_address(&address, 99, Some(&txn))
+            .is_empty());
+
+        // Rebuild it.
+        assert_eq!(history_store.reindex_epoch(&mut txn, 0), 3);
+        assert_eq!(history_store.reindex_epoch(&mut txn, 1), 5);
+
+        assert_eq!(
+            history_store.get_tx_hashes_by_address(&address, 99, Some(&txn)),
+            expected
+        );
+    }
+
+    #[test]
+    fn query_works() {
+        // Initialize History Store.
+        let env = VolatileEnvironment::new(10).unwrap();
+        let history_store = HistoryStore::new(env.clone());
+
+        // Create extended transactions.
+        let ext_txs = gen_ext_txs();
+
+        // Add extended transactions to History Store.
+        let mut txn = WriteTransaction::new(&env);
+        history_store.add_to_history(&mut txn, 0, &ext_txs[..3]);
+        history_store.add_to_history(&mut txn, 1, &ext_txs[3..]);
+
+        // All entries, from the oldest to the most recent one.
+        let query: Vec<_> = history_store.query(HistoryFilter::new(), &txn).collect();
+        assert_eq!(query, ext_txs);
+
+        // All entries, from the most recent to the oldest one.
+        let query: Vec<_> = history_store
+            .query(HistoryFilter::new().newest_first(), &txn)
+            .collect();
+        assert_eq!(query, ext_txs.iter().rev().cloned().collect::<Vec<_>>());
+
+        // Entries of an epoch range.
+        let query: Vec<_> = history_store
+            .query(HistoryFilter::new().epochs(1..=1), &txn)
+            .collect();
+        assert_eq!(query, ext_txs[3..]);
+
+        // Entries of a type.
+        let query: Vec<_> = history_store
+            .query(
+                HistoryFilter::new().types(vec![HistoryEntryType::Inherent(InherentType::Reward)]),
+                &txn,
+            )
+            .collect();
+        assert_eq!(
+            query,
+            vec![ext_txs[2].clone(), ext_txs[4].clone(), ext_txs[7].clone()]
+        );
+
+        // Entries of an address in an epoch range, using the address index.
+        let address =
+            Address::from_user_friendly_address("NQ04 B79B R4FF 4NGU A9H0 2PT9 9ART 5A88 J73T")
+                .unwrap();
+        let query: Vec<_> = history_store
+            .query(
+                HistoryFilter::new()
+                    .address(address)
+                    .epochs(1..=1)
+                    .newest_first(),
+                &txn,
+            )
+            .collect();
+        assert_eq!(query, vec![ext_txs[7].clone(), ext_txs[4].clone()]);
+
+        // The results are streamed.
+        let mut query = history_store.query(HistoryFilter::new(), &txn);
+        assert_eq!(query.next(), Some(ext_txs[0].clone()));
+        assert_eq!(query.next(), Some(ext_txs[1].clone()));
+    }
+
+    #[test]
+    fn query_skips_duplicates() {
+        // Initialize History Store.
+        let env = VolatileEnvironment::new(10).unwrap();
+        let history_store = HistoryStore::new(env.clone());
+
+        // Create a transaction to oneself and two rewards with the same hash.
+        let address =
+            Address::from_user_friendly_address("NQ09 VF5Y 1PKV MRM4 5LE1 55KV P6R2 GXYJ XYQF")
+                .unwrap();
+        let ext_txs = vec![
+            ExtendedTransaction {
+                network_id: NetworkId::UnitAlbatross,
+                block_number: 1,
+                block_time: 0,
+                data: ExtTxData::Basic(BlockchainTransaction::new_basic(
+                    address.clone(),
+                    address.clone(),
+                    Coin::from_u64_unchecked(1),
+                    Coin::from_u64_unchecked(0),
+                    0,
+                    NetworkId::Dummy,
+                )),
+            },
+            create_inherent(1, 2),
+            create_inherent(1, 2),
+        ];
+
+        // Add extended transactions to History Store.
+        let mut txn = WriteTransaction::new(&env);
+        history_store.add_to_history(&mut txn, 1, &ext_txs);
+
+        // The transaction to oneself is returned once.
+        for filter in vec![
+            HistoryFilter::new().address(address.clone()),
+            HistoryFilter::new().address(address).newest_first(),
+        ] {
+            let query: Vec<_> = history_store.query(filter, &txn).collect();
+            assert_eq!(query, ext_txs[..1]);
+        }
+
+        // Each reward is returned once.
+        let address =
+            Address::from_user_friendly_address("NQ04 B79B R4FF 4NGU A9H0 2PT9 9ART 5A88 J73T")
+                .unwrap();
+        for filter in vec![
+            HistoryFilter::new().address(address.clone()),
+            HistoryFilter::new().address(address).newest_first(),
+        ] {
+            let query: Vec<_> = history_store.query(filter, &txn).collect();
+            assert_eq!(query, ext_txs[1..]);
+        }
+    }
+
+    #[test]
+    fn query_continues_after_cursor() {
+        // Initialize History Store.
+        let env = VolatileEnvironment::new(10).unwrap();
+        let history_store = HistoryStore::new(env.clone());
+
+        // Create extended transactions.
+        let ext_txs = gen_ext_txs();
+
+        // Add extended transactions to History Store.
+        let mut txn = WriteTransaction::new(&env);
+        history_store.add_to_history(&mut txn, 0, &ext_txs[..3]);
+        history_store.add_to_history(&mut txn, 1, &ext_txs[3..]);
+
+        let address =
+            Address::from_user_friendly_address("NQ09 VF5Y 1PKV MRM4 5LE1 55KV P6R2 GXYJ XYQF")
+                .unwrap();
+
+        for filter in vec![
+            HistoryFilter::new(),
+            HistoryFilter::new().newest_first(),
+            HistoryFilter::new().address(address.clone()),
+            HistoryFilter::new().address(address).newest_first(),
+        ] {
+            let expected: Vec<_> = history_store.query(filter.clone(), &txn).collect();
+
+            // Read the entries in pages of two.
+            let mut entries = vec![];
+            let mut cursor = None;
+            loop {
+                let filter = match cursor {
+                    Some(cursor) => filter.clone().after(cursor),
+                    None => filter.clone(),
+                };
+                let mut query = history_store.query(filter, &txn);
+                let page: Vec<_> = query.by_ref().take(2).collect();
+                if page.is_empty() {
+                    break;
+                }
+                cursor = query.cursor();
+                entries.extend(page);
+            }
+
+            assert_eq!(entries, expected);
+        }
+    }
+
     #[test]
     fn prove_works() {
         // Initialize History Store.
@@ -1469,6 +1809,37 @@ mod tests {
         assert!(proof.verify(root).unwrap());
     }
 
+    #[test]
+    fn prove_at_block_works() {
+        // Initialize History Store.
+        let env = VolatileEnvironment::new(10).unwrap();
+        let history_store = HistoryStore::new(env.clone());
+
+        // Create extended transactions.
+        let ext_txs = gen_ext_txs();
+
+        // Add extended transactions to History Store.
+        let mut txn = WriteTransaction::new(&env);
+        history_store.add_to_history(&mut txn, 0, &ext_txs[..3]);
+        history_store.add_to_history(&mut txn, 1, &ext_txs[3..]);
+
+        // The history root at block 1 only contains the transactions of block 1.
+        let root = HistoryStore::root_from_ext_txs(&ext_txs[3..5]).unwrap();
+
+        let proof = history_store
+            .prove_at_block(1, vec![&ext_txs[3].tx_hash()], 1, Some(&txn))
+            .unwrap();
+
+        assert_eq!(proof.history.len(), 1);
+        assert_eq!(proof.history[0].tx_hash(), ext_txs[3].tx_hash());
+        assert!(proof.verify(root).unwrap());
+
+        // Transactions of later blocks can't be proven.
+        assert!(history_store
+            .prove_at_block(1, vec![&ext_txs[5].tx_hash()], 1, Some(&txn))
+            .is_none());
+    }
+
     #[test]
     fn prove_empty_tree_works() {
         // Initialize History Store.
@@ -1,8 +1,9 @@
 use std::fmt::{self, Debug, Formatter};
 
+use beserial::schema::{define, Definitions};
 use beserial::{
-    Deserialize, DeserializeWithLength, ReadBytesExt, Serialize, SerializeWithLength,
-    SerializingError, WriteBytesExt,
+    Deserialize, DeserializeWithLength, FieldSchema, ReadBytesExt, Schema, Serialize,
+    SerializeWithLength, SerializingError, TypeSchema, WriteBytesExt,
 };
 use nimiq_hash::Blake2bHash;
 use nimiq_mmr::mmr::proof::{Proof, RangeProof};
@@ -82,3 +83,31 @@ impl Deserialize for HistoryTreeChunk {
         })
     }
 }
+
+/// The range proof is serialized field by field, followed by the history.
+impl Schema for HistoryTreeChunk {
+    fn name() -> String {
+        "HistoryTreeChunk".to_string()
+    }
+
+    fn schema() -> TypeSchema {
+        TypeSchema::Struct {
+            fields: vec![
+                FieldSchema::new::<bool>(Some("assume_previous")),
+                FieldSchema::new::<u64>(Some("mmr_size")),
+                FieldSchema::new::<Vec<Blake2bHash>>(Some("nodes")).with_len_type("u32", None),
+                FieldSchema::new::<Vec<ExtendedTransaction>>(Some("history"))
+                    .with_len_type("u16", None),
+            ],
+        }
+    }
+
+    fn collect(definitions: &mut Definitions) {
+        if define::<Self>(definitions) {
+            bool::collect(definitions);
+            u64::collect(definitions);
+            <Vec<Blake2bHash>>::collect(definitions);
+            <Vec<ExtendedTransaction>>::collect(definitions);
+        }
+    }
+}
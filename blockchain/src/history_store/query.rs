@@ -0,0 +1,406 @@
+use std::collections::VecDeque;
+use std::ops::{Range, RangeInclusive};
+
+use nimiq_account::InherentType;
+use nimiq_database::cursor::ReadCursor;
+use nimiq_database::{Cursor, Transaction};
+use nimiq_hash::Blake2bHash;
+use nimiq_keys::Address;
+use nimiq_mmr::mmr::MerkleMountainRange;
+use nimiq_primitives::policy;
+
+use crate::history_store::mmr_store::MMRStore;
+use crate::history_store::ordered_hash::OrderedHash;
+use crate::history_store::{ExtTxData, ExtendedTransaction, HistoryStore};
+
+/// The type of an entry of the history store.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HistoryEntryType {
+    /// A basic transaction.
+    Transaction,
+    /// An inherent of the given type.
+    Inherent(InherentType),
+}
+
+impl HistoryEntryType {
+    pub fn of(ext_tx: &ExtendedTransaction) -> Self {
+        match &ext_tx.data {
+            ExtTxData::Basic(_) => HistoryEntryType::Transaction,
+            ExtTxData::Inherent(inherent) => HistoryEntryType::Inherent(inherent.ty),
+        }
+    }
+}
+
+/// The position of an entry in the history, see [`HistoryQuery::cursor`]. Entries are ordered by
+/// their block number and then by their index in the history tree of the epoch.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HistoryCursor {
+    pub block_number: u32,
+    pub leaf_index: u32,
+    /// The position of the entry in the address index, if the query was filtered by address.
+    pub address_index: Option<u32>,
+}
+
+impl HistoryCursor {
+    /// Returns whether this position comes after `other` in the order of the query.
+    fn follows(&self, other: &HistoryCursor, newest_first: bool) -> bool {
+        let position = (self.block_number, self.leaf_index);
+        let other = (other.block_number, other.leaf_index);
+        if newest_first {
+            position < other
+        } else {
+            position > other
+        }
+    }
+}
+
+/// Selects the entries returned by [`HistoryStore::query`]. By default, all entries are returned
+/// from the oldest to the most recent one.
+#[derive(Clone, Debug, Default)]
+pub struct HistoryFilter {
+    first_epoch: u32,
+    last_epoch: Option<u32>,
+    address: Option<Address>,
+    types: Option<Vec<HistoryEntryType>>,
+    newest_first: bool,
+    after: Option<HistoryCursor>,
+}
+
+impl HistoryFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only returns entries of the given epochs.
+    pub fn epochs(mut self, epochs: RangeInclusive<u32>) -> Self {
+        self.first_epoch = *epochs.start();
+        self.last_epoch = Some(*epochs.end());
+        self
+    }
+
+    /// Only returns the transactions sent or received by the address and the rewards it received.
+    /// The entries are looked up in the address index instead of scanning the history trees.
+    pub fn address(mut self, address: Address) -> Self {
+        self.address = Some(address);
+        self
+    }
+
+    /// Only returns entries of the given types.
+    pub fn types(mut self, types: Vec<HistoryEntryType>) -> Self {
+        self.types = Some(types);
+        self
+    }
+
+    /// Returns the most recent entries first.
+    pub fn newest_first(mut self) -> Self {
+        self.newest_first = true;
+        self
+    }
+
+    /// Only returns the entries after the cursor, which continues a previous query with the same
+    /// filter.
+    pub fn after(mut self, cursor: HistoryCursor) -> Self {
+        self.after = Some(cursor);
+        self
+    }
+
+    fn contains_epoch(&self, epoch: u32) -> bool {
+        epoch >= self.first_epoch && self.last_epoch.map_or(true, |last| epoch <= last)
+    }
+
+    /// Returns whether the entry is selected by the filter.
+    pub fn matches(&self, ext_tx: &ExtendedTransaction) -> bool {
+        if !self.contains_epoch(policy::epoch_at(ext_tx.block_number)) {
+            return false;
+        }
+
+        if let Some(types) = &self.types {
+            if !types.contains(&HistoryEntryType::of(ext_tx)) {
+                return false;
+            }
+        }
+
+        match &self.address {
+            None => true,
+            Some(address) => match &ext_tx.data {
+                ExtTxData::Basic(tx) => &tx.sender == address || &tx.recipient == address,
+                // Like in the address index, only reward inherents belong to an address.
+                ExtTxData::Inherent(inherent) => {
+                    inherent.ty == InherentType::Reward && &inherent.target == address
+                }
+            },
+        }
+    }
+}
+
+enum Source<'txn, 'env> {
+    /// Walks the address index.
+    Address {
+        cursor: Cursor<'txn, 'txn>,
+        started: bool,
+    },
+    /// Walks the history trees of the epochs.
+    Epochs {
+        epochs: RangeInclusive<u32>,
+        tree: Option<MerkleMountainRange<Blake2bHash, MMRStore<'txn, 'env>>>,
+        leaves: Range<usize>,
+    },
+    Done,
+}
+
+/// The entries of the history store selected by a [`HistoryFilter`]. The entries are read from the
+/// database while iterating, so arbitrarily large ranges can be scanned without loading them
+/// into memory.
+pub struct HistoryQuery<'txn, 'env> {
+    store: &'txn HistoryStore,
+    txn: &'txn Transaction<'env>,
+    filter: HistoryFilter,
+    source: Source<'txn, 'env>,
+    pending: VecDeque<(HistoryCursor, ExtendedTransaction)>,
+    last: Option<HistoryCursor>,
+}
+
+impl<'txn, 'env> HistoryQuery<'txn, 'env> {
+    pub(super) fn new(
+        store: &'txn HistoryStore,
+        filter: HistoryFilter,
+        txn: &'txn Transaction<'env>,
+    ) -> Self {
+        let source = if filter.address.is_some() {
+            Source::Address {
+                cursor: store.address_cursor(txn),
+                started: false,
+            }
+        } else {
+            match store.last_epoch(txn) {
+                Some(last_epoch) => {
+                    let mut first_epoch = filter.first_epoch;
+                    let mut last_epoch = filter.last_epoch.unwrap_or(last_epoch);
+
+                    // Skip the epochs before the cursor.
+                    if let Some(after) = &filter.after {
+                        let epoch = policy::epoch_at(after.block_number);
+                        if filter.newest_first {
+                            last_epoch = last_epoch.min(epoch);
+                        } else {
+                            first_epoch = first_epoch.max(epoch);
+                        }
+                    }
+
+                    Source::Epochs {
+                        epochs: first_epoch..=last_epoch,
+                        tree: None,
+                        leaves: 0..0,
+                    }
+                }
+                None => Source::Done,
+            }
+        };
+
+        HistoryQuery {
+            store,
+            txn,
+            last: filter.after,
+            filter,
+            source,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Returns the position of the last entry that was read. A query with the same filter and
+    /// [`HistoryFilter::after`] set to it continues with the entries that were not returned yet.
+    pub fn cursor(&self) -> Option<HistoryCursor> {
+        self.last
+    }
+
+    /// Reads the next candidates into `pending`. Returns false if there are none left.
+    fn fill(&mut self) -> bool {
+        let newest_first = self.filter.newest_first;
+
+        match &mut self.source {
+            Source::Address { cursor, started } => {
+                let address = self.filter.address.as_ref().unwrap();
+                let next = if !*started {
+                    *started = true;
+                    match self.last.and_then(|last| last.address_index) {
+                        // Continue after the index entry of the cursor.
+                        Some(index) => {
+                            let value = OrderedHash {
+                                index,
+                                hash: Blake2bHash::default(),
+                            };
+                            match cursor
+                                .seek_key_nearest_value::<Address, OrderedHash>(address, &value)
+                            {
+                                Some(v) if v.index == index => step(cursor, newest_first),
+                                Some(v) => Some(v),
+                                // The entry of the cursor has been reverted.
+                                None if newest_first => cursor
+                                    .seek_key::<Address, OrderedHash>(address)
+                                    .and_then(|_| cursor.last_duplicate::<OrderedHash>()),
+                                None => None,
+                            }
+                        }
+                        None => {
+                            let first = cursor.seek_key::<Address, OrderedHash>(address);
+                            if newest_first {
+                                first.and_then(|_| cursor.last_duplicate::<OrderedHash>())
+                            } else {
+                                first
+                            }
+                        }
+                    }
+                } else {
+                    step(cursor, newest_first)
+                };
+
+                let entry = match next {
+                    Some(v) => v,
+                    None => {
+                        self.source = Source::Done;
+                        return false;
+                    }
+                };
+
+                // A transaction hash can belong to several entries of the history, so we take the
+                // entry closest to the previous one. Index entries whose history entries were
+                // all read already, like the second entry of a transaction to oneself, are
+                // skipped.
+                let last = self.last;
+                let next = self
+                    .store
+                    .get_leaves_by_tx_hash(&entry.hash, Some(self.txn))
+                    .into_iter()
+                    .map(|leaf| {
+                        let ext_tx = self
+                            .store
+                            .get_extended_tx(&leaf.hash, Some(self.txn))
+                            .unwrap();
+                        let position = HistoryCursor {
+                            block_number: ext_tx.block_number,
+                            leaf_index: leaf.index,
+                            address_index: Some(entry.index),
+                        };
+                        (position, ext_tx)
+                    })
+                    .filter(|(position, _)| {
+                        last.map_or(true, |last| position.follows(&last, newest_first))
+                    })
+                    .reduce(|closest, other| {
+                        if closest.0.follows(&other.0, newest_first) {
+                            other
+                        } else {
+                            closest
+                        }
+                    });
+
+                // The index is ordered by time, so we can stop once we passed the epoch range.
+                if let Some((_, ext_tx)) = &next {
+                    let epoch = policy::epoch_at(ext_tx.block_number);
+                    let passed = if newest_first {
+                        epoch < self.filter.first_epoch
+                    } else {
+                        self.filter.last_epoch.map_or(false, |last| epoch > last)
+                    };
+                    if passed {
+                        self.source = Source::Done;
+                        return false;
+                    }
+                }
+
+                self.pending.extend(next);
+                true
+            }
+            Source::Epochs {
+                epochs,
+                tree,
+                leaves,
+            } => loop {
+                let leaf = if newest_first {
+                    leaves.next_back()
+                } else {
+                    leaves.next()
+                };
+
+                if let (Some(leaf), Some(tree)) = (leaf, tree.as_ref()) {
+                    let leaf_hash = tree.get_leaf(leaf).unwrap();
+                    let ext_tx = self
+                        .store
+                        .get_extended_tx(&leaf_hash, Some(self.txn))
+                        .unwrap();
+                    let position = HistoryCursor {
+                        block_number: ext_tx.block_number,
+                        leaf_index: leaf as u32,
+                        address_index: None,
+                    };
+                    self.pending.push_back((position, ext_tx));
+                    return true;
+                }
+
+                // Continue with the next epoch.
+                let epoch = if newest_first {
+                    epochs.next_back()
+                } else {
+                    epochs.next()
+                };
+                match epoch {
+                    Some(epoch) => {
+                        let epoch_tree = self.store.history_tree(self.txn, epoch);
+                        *leaves = 0..epoch_tree.num_leaves();
+
+                        // Skip the leaves before the cursor.
+                        if let Some(last) = &self.last {
+                            if policy::epoch_at(last.block_number) == epoch {
+                                let leaf_index = last.leaf_index as usize;
+                                if newest_first {
+                                    leaves.end = leaves.end.min(leaf_index);
+                                } else {
+                                    leaves.start = leaf_index + 1;
+                                }
+                            }
+                        }
+
+                        *tree = Some(epoch_tree);
+                    }
+                    None => {
+                        self.source = Source::Done;
+                        return false;
+                    }
+                }
+            },
+            Source::Done => false,
+        }
+    }
+}
+
+impl<'txn, 'env> Iterator for HistoryQuery<'txn, 'env> {
+    type Item = ExtendedTransaction;
+
+    fn next(&mut self) -> Option<ExtendedTransaction> {
+        loop {
+            match self.pending.pop_front() {
+                Some((position, ext_tx)) => {
+                    self.last = Some(position);
+                    if self.filter.matches(&ext_tx) {
+                        return Some(ext_tx);
+                    }
+                }
+                None => {
+                    if !self.fill() {
+                        return None;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Moves the cursor to the next entry of the address index in the order of the query.
+fn step(cursor: &mut Cursor, newest_first: bool) -> Option<OrderedHash> {
+    if newest_first {
+        cursor.prev_duplicate::<Address, OrderedHash>()
+    } else {
+        cursor.next_duplicate::<Address, OrderedHash>()
+    }
+    .map(|(_, v)| v)
+}
@@ -4,13 +4,19 @@ extern crate beserial_derive;
 extern crate log;
 
 pub use abstract_blockchain::AbstractBlockchain;
-pub use blockchain::blockchain::{Blockchain, TransactionVerificationCache};
+pub use blockchain::blockchain::{Blockchain, BlockchainLock, TransactionVerificationCache};
+pub use blockchain::reindex::ReindexProgress;
+pub use blockchain::schedule::ChainSchedule;
+pub use blockchain::slots::{Slot, SlotAssignment};
 pub use chain_info::ChainInfo;
 pub use chain_ordering::ChainOrdering;
 pub use error::*;
+pub use event_sequencer::{EventSequencer, SequencedEvent};
 pub use history_store::*;
+pub use reader::{BlockchainReader, BlockchainSnapshot, StateView};
 
 pub(crate) mod abstract_blockchain;
+pub(crate) mod block_compression;
 pub(crate) mod blockchain;
 pub(crate) mod blockchain_state;
 pub(crate) mod chain_info;
@@ -19,5 +25,9 @@ pub mod chain_metrics;
 pub(crate) mod chain_ordering;
 pub(crate) mod chain_store;
 pub(crate) mod error;
+pub(crate) mod event_sequencer;
 pub(crate) mod history_store;
+pub mod migration;
+pub(crate) mod reader;
+pub mod receipt;
 pub mod reward;
@@ -0,0 +1,277 @@
+use std::io;
+
+use thiserror::Error;
+
+use nimiq_database::{Environment, WriteTransaction};
+
+use crate::chain_store::ChainStore;
+
+/// The version of the format of the chain store and the history store. It must be increased
+/// whenever the format changes, together with a migration to the new version in [`MIGRATIONS`].
+pub const SCHEMA_VERSION: u32 = 3;
+
+/// The migrations to the schema versions after the first one, in increasing order.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 2,
+        description: "Add the event cursors to the offset of the event sink",
+        run: add_event_sink_cursors,
+    },
+    Migration {
+        version: 3,
+        description: "Store micro blocks compressed",
+        run: compress_micro_blocks,
+    },
+];
+
+/// A step that converts the database from the previous schema version to `version`.
+pub struct Migration {
+    /// The schema version of the database after the migration.
+    pub version: u32,
+    /// What the migration changes, for the logs.
+    pub description: &'static str,
+    /// Runs the migration. It may use as many write transactions as it needs, but it must be safe
+    /// to run it again if it was interrupted, since the new schema version is only recorded after
+    /// it finished.
+    pub run: fn(&Environment) -> Result<(), String>,
+}
+
+/// The offset of the event sink stores the state of its event sequencer since schema version 2.
+/// Before, it only stored the number and hash of the last published block. The cursors of an
+/// existing offset start over at 1 and its published blocks can't be referenced by reverts.
+fn add_event_sink_cursors(env: &Environment) -> Result<(), String> {
+    // The database and key of the offset in `nimiq-lib`.
+    const DB_NAME: &str = "EventSink";
+    const OFFSET_KEY: &str = "offset";
+    // The block number and the hash of the block.
+    const LEGACY_OFFSET_SIZE: usize = 4 + 32;
+
+    let db = env.open_database(DB_NAME.to_string());
+    let mut txn = WriteTransaction::new(env);
+    let offset: Option<Vec<u8>> = txn.get(&db, OFFSET_KEY);
+    if let Some(mut offset) = offset.filter(|offset| offset.len() == LEGACY_OFFSET_SIZE) {
+        // The next cursor and the empty list of announced blocks.
+        offset.extend_from_slice(&1u64.to_be_bytes());
+        offset.extend_from_slice(&0u16.to_be_bytes());
+        txn.put(&db, OFFSET_KEY, &offset);
+    }
+    txn.commit();
+
+    Ok(())
+}
+
+/// Micro blocks are stored compressed since schema version 3. Blocks stored before are read as
+/// they are, so nothing needs to be converted. The new version keeps older releases, which can't
+/// read compressed blocks, from opening the database.
+fn compress_micro_blocks(_env: &Environment) -> Result<(), String> {
+    Ok(())
+}
+
+#[derive(Debug, Error)]
+pub enum MigrationError {
+    #[error("Database schema version {0} is newer than the supported version {1}")]
+    UnsupportedVersion(u32, u32),
+    #[error("No migration to schema version {0}")]
+    MissingMigration(u32),
+    #[error("Failed to back up the database: {0}")]
+    Backup(#[from] io::Error),
+    #[error("Migration to schema version {0} failed: {1}")]
+    Failed(u32, String),
+}
+
+/// Brings the database to the current [`SCHEMA_VERSION`]. See [`migrate_to`].
+pub fn migrate(env: &Environment, backup_path: Option<&str>) -> Result<u32, MigrationError> {
+    migrate_to(env, SCHEMA_VERSION, MIGRATIONS, backup_path)
+}
+
+/// Runs the migrations from the schema version of the database up to `target` in order, and
+/// returns the previous schema version.
+///
+/// New databases get the target version without running any migrations. Databases created before
+/// the schema version was recorded have the first version. If migrations need to run and a backup
+/// path is given, the database is copied there first.
+pub fn migrate_to(
+    env: &Environment,
+    target: u32,
+    migrations: &[Migration],
+    backup_path: Option<&str>,
+) -> Result<u32, MigrationError> {
+    let chain_store = ChainStore::new(env.clone());
+
+    let recorded = chain_store.get_schema_version(None);
+    let version = match recorded {
+        Some(version) => version,
+        None if chain_store.get_head(None).is_none() => target,
+        None => 1,
+    };
+
+    if version > target {
+        return Err(MigrationError::UnsupportedVersion(version, target));
+    }
+
+    let steps = (version + 1..=target)
+        .map(|next| {
+            migrations
+                .iter()
+                .find(|migration| migration.version == next)
+                .ok_or(MigrationError::MissingMigration(next))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if !steps.is_empty() {
+        if let Some(path) = backup_path {
+            info!("Backing up the database to {}", path);
+            env.backup(path)?;
+        }
+    }
+
+    for migration in steps {
+        info!(
+            "Migrating the database to schema version {}: {}",
+            migration.version, migration.description
+        );
+        (migration.run)(env).map_err(|e| MigrationError::Failed(migration.version, e))?;
+
+        let mut txn = WriteTransaction::new(env);
+        chain_store.set_schema_version(&mut txn, migration.version);
+        txn.commit();
+    }
+
+    if recorded.is_none() {
+        let mut txn = WriteTransaction::new(env);
+        chain_store.set_schema_version(&mut txn, target);
+        txn.commit();
+    }
+
+    Ok(version)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use nimiq_database::volatile::VolatileEnvironment;
+    use nimiq_hash::Blake2bHash;
+
+    use super::*;
+
+    static RUNS: AtomicU32 = AtomicU32::new(0);
+
+    fn count_run(_: &Environment) -> Result<(), String> {
+        RUNS.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn fail(_: &Environment) -> Result<(), String> {
+        Err("broken".to_string())
+    }
+
+    /// Creates an environment that looks like a database from before schema versions existed.
+    fn unversioned_env() -> Environment {
+        let env = VolatileEnvironment::new(10).unwrap();
+        let chain_store = ChainStore::new(env.clone());
+        let mut txn = WriteTransaction::new(&env);
+        chain_store.set_head(&mut txn, &Blake2bHash::default());
+        txn.commit();
+        env
+    }
+
+    #[test]
+    fn migrations_run_in_order() {
+        let migrations = [
+            Migration {
+                version: 2,
+                description: "two",
+                run: count_run,
+            },
+            Migration {
+                version: 3,
+                description: "three",
+                run: count_run,
+            },
+        ];
+
+        // New databases start at the target version.
+        let env = VolatileEnvironment::new(10).unwrap();
+        assert_eq!(migrate_to(&env, 3, &migrations, None).unwrap(), 3);
+        assert_eq!(RUNS.load(Ordering::SeqCst), 0);
+
+        // Unversioned databases have the first version.
+        let env = unversioned_env();
+        assert_eq!(migrate_to(&env, 3, &migrations, None).unwrap(), 1);
+        assert_eq!(RUNS.load(Ordering::SeqCst), 2);
+        assert_eq!(
+            ChainStore::new(env.clone()).get_schema_version(None),
+            Some(3)
+        );
+
+        // Nothing is left to do afterwards.
+        assert_eq!(migrate_to(&env, 3, &migrations, None).unwrap(), 3);
+        assert_eq!(RUNS.load(Ordering::SeqCst), 2);
+
+        // Newer databases are rejected.
+        assert!(matches!(
+            migrate_to(&env, 2, &migrations, None),
+            Err(MigrationError::UnsupportedVersion(3, 2))
+        ));
+    }
+
+    #[test]
+    fn all_schema_versions_have_a_migration() {
+        let versions: Vec<u32> = MIGRATIONS
+            .iter()
+            .map(|migration| migration.version)
+            .collect();
+        assert_eq!(versions, (2..=SCHEMA_VERSION).collect::<Vec<_>>());
+
+        let env = unversioned_env();
+        assert_eq!(migrate(&env, None).unwrap(), 1);
+        assert_eq!(
+            ChainStore::new(env.clone()).get_schema_version(None),
+            Some(SCHEMA_VERSION)
+        );
+    }
+
+    #[test]
+    fn event_sink_offsets_get_cursors() {
+        let env = VolatileEnvironment::new(10).unwrap();
+        let db = env.open_database("EventSink".to_string());
+
+        let mut legacy = 42u32.to_be_bytes().to_vec();
+        legacy.extend_from_slice(&[7u8; 32]);
+        let mut txn = WriteTransaction::new(&env);
+        txn.put::<str, Vec<u8>>(&db, "offset", &legacy);
+        txn.commit();
+
+        add_event_sink_cursors(&env).unwrap();
+        let offset: Vec<u8> = WriteTransaction::new(&env).get(&db, "offset").unwrap();
+        assert_eq!(&offset[..36], &legacy[..]);
+        assert_eq!(&offset[36..], &[0, 0, 0, 0, 0, 0, 0, 1, 0, 0]);
+
+        // Offsets in the new layout are left alone.
+        add_event_sink_cursors(&env).unwrap();
+        let migrated: Vec<u8> = WriteTransaction::new(&env).get(&db, "offset").unwrap();
+        assert_eq!(migrated, offset);
+    }
+
+    #[test]
+    fn failed_migrations_keep_the_version() {
+        let migrations = [Migration {
+            version: 2,
+            description: "two",
+            run: fail,
+        }];
+
+        let env = unversioned_env();
+        assert!(matches!(
+            migrate_to(&env, 2, &migrations, None),
+            Err(MigrationError::Failed(2, _))
+        ));
+        assert_eq!(ChainStore::new(env.clone()).get_schema_version(None), None);
+
+        assert!(matches!(
+            migrate_to(&env, 3, &migrations, None),
+            Err(MigrationError::MissingMigration(3))
+        ));
+    }
+}
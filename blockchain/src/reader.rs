@@ -0,0 +1,166 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use nimiq_account::{Account, Accounts, AccountsTrie, StakingContract};
+use nimiq_block::Block;
+use nimiq_database::{Environment, ReadTransaction, WriteTransaction};
+use nimiq_hash::Blake2bHash;
+use nimiq_keys::Address;
+use nimiq_primitives::networks::NetworkId;
+use nimiq_primitives::policy;
+use nimiq_trie::key_nibbles::KeyNibbles;
+
+use crate::HistoryStore;
+
+/// The head of the main chain at the time it was published.
+#[derive(Clone, Debug)]
+pub struct BlockchainSnapshot {
+    pub head_hash: Blake2bHash,
+    pub head: Block,
+}
+
+impl BlockchainSnapshot {
+    pub fn block_number(&self) -> u32 {
+        self.head.block_number()
+    }
+
+    pub fn timestamp(&self) -> u64 {
+        self.head.timestamp()
+    }
+
+    pub fn epoch_number(&self) -> u32 {
+        policy::epoch_at(self.block_number())
+    }
+}
+
+/// A cheap handle to read the head and the accounts of the blockchain without taking the
+/// blockchain lock, so that readers don't wait for blocks being pushed.
+///
+/// The blockchain commits a block and publishes its new head in one step. The accounts are read
+/// from a database transaction, which sees the committed state without blocking the writer.
+#[derive(Clone)]
+pub struct BlockchainReader {
+    network_id: NetworkId,
+    env: Environment,
+    accounts: Arc<Accounts>,
+    history_store: Arc<HistoryStore>,
+    // Only held to swap or clone the snapshot, to open a view and to commit a block, never for
+    // the rest of a push.
+    head: Arc<RwLock<Arc<BlockchainSnapshot>>>,
+}
+
+impl BlockchainReader {
+    pub(crate) fn new(
+        network_id: NetworkId,
+        env: Environment,
+        accounts: Arc<Accounts>,
+        history_store: Arc<HistoryStore>,
+        head_hash: Blake2bHash,
+        head: Block,
+    ) -> Self {
+        BlockchainReader {
+            network_id,
+            env,
+            accounts,
+            history_store,
+            head: Arc::new(RwLock::new(Arc::new(BlockchainSnapshot {
+                head_hash,
+                head,
+            }))),
+        }
+    }
+
+    /// Commits the transaction that stores the new head and publishes it. Views see either the
+    /// previous head and accounts or the new ones, never the new accounts with the previous head.
+    pub(crate) fn commit(&self, txn: WriteTransaction, head_hash: Blake2bHash, head: Block) {
+        let mut published = self.head.write();
+        txn.commit();
+        *published = Arc::new(BlockchainSnapshot { head_hash, head });
+    }
+
+    pub fn network_id(&self) -> NetworkId {
+        self.network_id
+    }
+
+    /// Returns the latest published head.
+    pub fn head(&self) -> Arc<BlockchainSnapshot> {
+        Arc::clone(&self.head.read())
+    }
+
+    pub fn head_hash(&self) -> Blake2bHash {
+        self.head().head_hash.clone()
+    }
+
+    pub fn block_number(&self) -> u32 {
+        self.head().block_number()
+    }
+
+    /// Opens a consistent view of the accounts at the latest published head.
+    pub fn view(&self) -> StateView {
+        // The head can't be replaced while the transaction is opened, see `commit`.
+        let head = self.head.read();
+        let txn = ReadTransaction::new(&self.env);
+        StateView {
+            reader: self,
+            head: Arc::clone(&head),
+            txn,
+        }
+    }
+}
+
+/// The head and the accounts of the blockchain at one block, see [`BlockchainReader::view`].
+pub struct StateView<'r> {
+    reader: &'r BlockchainReader,
+    head: Arc<BlockchainSnapshot>,
+    txn: ReadTransaction<'r>,
+}
+
+impl<'r> StateView<'r> {
+    pub fn head(&self) -> &BlockchainSnapshot {
+        &self.head
+    }
+
+    pub fn block_number(&self) -> u32 {
+        self.head.block_number()
+    }
+
+    pub fn get_account(&self, address: &Address) -> Option<Account> {
+        self.reader
+            .accounts
+            .get(&account_key(address), Some(&self.txn))
+    }
+
+    pub fn accounts_tree(&self) -> &AccountsTrie {
+        &self.reader.accounts.tree
+    }
+
+    /// The database transaction the view reads from.
+    pub fn read_transaction(&self) -> &ReadTransaction<'r> {
+        &self.txn
+    }
+
+    /// Checks if we have seen some transaction with this hash inside the validity window. This is
+    /// used to prevent replay attacks.
+    pub fn contains_tx_in_validity_window(&self, tx_hash: &Blake2bHash) -> bool {
+        let validity_window_start = self
+            .block_number()
+            .saturating_sub(policy::TRANSACTION_VALIDITY_WINDOW);
+
+        self.reader
+            .history_store
+            .get_ext_tx_by_hash(tx_hash, Some(&self.txn))
+            .iter()
+            .any(|ext_tx| ext_tx.block_number >= validity_window_start)
+    }
+}
+
+/// Returns the key of an account in the accounts tree.
+pub(crate) fn account_key(address: &Address) -> KeyNibbles {
+    // TODO: Find a better place for this differentiation, it should be in a more general location.
+    if *address == policy::STAKING_CONTRACT_ADDRESS {
+        StakingContract::get_key_staking_contract()
+    } else {
+        KeyNibbles::from(address)
+    }
+}
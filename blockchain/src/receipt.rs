@@ -0,0 +1,150 @@
+use thiserror::Error;
+
+use nimiq_block::{BlockHeader, MacroHeader};
+use nimiq_hash::{Blake2bHash, Hash};
+use nimiq_primitives::policy;
+
+use crate::{AbstractBlockchain, Blockchain, ExtendedTransaction, HistoryTreeProof};
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ReceiptError {
+    #[error("Transaction not found")]
+    TransactionNotFound,
+    #[error("Block not found")]
+    BlockNotFound,
+    #[error("Invalid history proof")]
+    InvalidProof,
+    #[error("Transaction is not part of the block")]
+    BlockMismatch,
+    #[error("Invalid election block reference")]
+    InvalidElectionReference,
+}
+
+/// A self-contained proof that a transaction was included in the chain.
+///
+/// The receipt contains the header of the block that included the transaction and a proof that
+/// the transaction is part of the history tree committed to in that header. It can be verified
+/// without access to the blockchain, which makes it suitable to archive proof of received
+/// payments.
+///
+/// The receipt doesn't prove that the block is part of the chain. Once the epoch of the
+/// transaction is finalized, the receipt also references the election block of the epoch. Its
+/// hash can be checked against a zero-knowledge proof of the chain, or any other trusted source.
+#[derive(Serialize, Deserialize)]
+pub struct PaymentReceipt {
+    pub header: BlockHeader,
+    pub proof: HistoryTreeProof,
+    pub election: Option<ElectionReference>,
+}
+
+/// Proof that a transaction is part of the history tree of an election block.
+#[derive(Serialize, Deserialize)]
+pub struct ElectionReference {
+    pub header: MacroHeader,
+    pub proof: HistoryTreeProof,
+}
+
+impl PaymentReceipt {
+    /// Verifies the receipt for the transaction with the given hash and returns the transaction.
+    pub fn verify(&self, tx_hash: &Blake2bHash) -> Result<&ExtendedTransaction, ReceiptError> {
+        let ext_tx = find_tx(&self.proof, tx_hash)?;
+
+        if ext_tx.block_number != self.header.block_number() {
+            return Err(ReceiptError::BlockMismatch);
+        }
+
+        if self.proof.verify(self.header.history_root().clone()) != Some(true) {
+            return Err(ReceiptError::InvalidProof);
+        }
+
+        if let Some(election) = &self.election {
+            let election_block_number = election.header.block_number;
+            if !policy::is_election_block_at(election_block_number)
+                || policy::epoch_at(election_block_number) != policy::epoch_at(ext_tx.block_number)
+            {
+                return Err(ReceiptError::InvalidElectionReference);
+            }
+
+            if find_tx(&election.proof, tx_hash)? != ext_tx
+                || election.proof.verify(election.header.history_root.clone()) != Some(true)
+            {
+                return Err(ReceiptError::InvalidElectionReference);
+            }
+        }
+
+        Ok(ext_tx)
+    }
+
+    /// Returns the hash of the block that included the transaction.
+    pub fn block_hash(&self) -> Blake2bHash {
+        self.header.hash()
+    }
+
+    /// Returns the hash of the referenced election block, if there is one.
+    pub fn election_block_hash(&self) -> Option<Blake2bHash> {
+        self.election
+            .as_ref()
+            .map(|election| election.header.hash::<Blake2bHash>())
+    }
+}
+
+fn find_tx<'a>(
+    proof: &'a HistoryTreeProof,
+    tx_hash: &Blake2bHash,
+) -> Result<&'a ExtendedTransaction, ReceiptError> {
+    proof
+        .history
+        .iter()
+        .find(|ext_tx| ext_tx.tx_hash() == *tx_hash)
+        .ok_or(ReceiptError::TransactionNotFound)
+}
+
+impl Blockchain {
+    /// Creates a payment receipt for the transaction with the given hash. The transaction must be
+    /// part of the main chain.
+    pub fn create_payment_receipt(
+        &self,
+        tx_hash: &Blake2bHash,
+    ) -> Result<PaymentReceipt, ReceiptError> {
+        let txn = self.read_transaction();
+
+        let ext_tx = self
+            .history_store
+            .get_ext_tx_by_hash(tx_hash, Some(&txn))
+            .into_iter()
+            .find(|ext_tx| !ext_tx.is_inherent())
+            .ok_or(ReceiptError::TransactionNotFound)?;
+        let epoch = policy::epoch_at(ext_tx.block_number);
+
+        let header = self
+            .get_block_at(ext_tx.block_number, false, Some(&txn))
+            .ok_or(ReceiptError::BlockNotFound)?
+            .header();
+        let proof = self
+            .history_store
+            .prove_at_block(epoch, vec![tx_hash], ext_tx.block_number, Some(&txn))
+            .ok_or(ReceiptError::InvalidProof)?;
+
+        let election_block_number = policy::election_block_of(epoch);
+        let election = if self.election_head().block_number() >= election_block_number {
+            let header = self
+                .get_block_at(election_block_number, false, Some(&txn))
+                .ok_or(ReceiptError::BlockNotFound)?
+                .unwrap_macro()
+                .header;
+            let proof = self
+                .history_store
+                .prove_at_block(epoch, vec![tx_hash], election_block_number, Some(&txn))
+                .ok_or(ReceiptError::InvalidProof)?;
+            Some(ElectionReference { header, proof })
+        } else {
+            None
+        };
+
+        Ok(PaymentReceipt {
+            header,
+            proof,
+            election,
+        })
+    }
+}
@@ -1,9 +1,7 @@
 use std::sync::Arc;
 
-use parking_lot::RwLock;
-
 use nimiq_block_production::BlockProducer;
-use nimiq_blockchain::{AbstractBlockchain, Blockchain, PushResult};
+use nimiq_blockchain::{AbstractBlockchain, Blockchain, BlockchainLock, PushResult};
 use nimiq_database::volatile::VolatileEnvironment;
 use nimiq_genesis::NetworkId;
 use nimiq_primitives::policy::{BATCHES_PER_EPOCH, BATCH_LENGTH, EPOCH_LENGTH};
@@ -27,7 +25,7 @@ fn history_sync_works() {
     // Create a blockchain to produce the macro blocks.
     let env = VolatileEnvironment::new(10).unwrap();
 
-    let blockchain = Arc::new(RwLock::new(
+    let blockchain = Arc::new(BlockchainLock::new(
         Blockchain::new(env, NetworkId::UnitAlbatross, time).unwrap(),
     ));
 
@@ -93,7 +91,7 @@ fn history_sync_works() {
     // Create a second blockchain to push these blocks.
     let env2 = VolatileEnvironment::new(10).unwrap();
 
-    let blockchain2 = Arc::new(RwLock::new(
+    let blockchain2 = Arc::new(BlockchainLock::new(
         Blockchain::new(env2, NetworkId::UnitAlbatross, time).unwrap(),
     ));
 
@@ -157,7 +155,7 @@ fn history_sync_works_with_micro_blocks() {
     // Create a blockchain to produce the macro blocks.
     let env = VolatileEnvironment::new(10).unwrap();
 
-    let blockchain = Arc::new(RwLock::new(
+    let blockchain = Arc::new(BlockchainLock::new(
         Blockchain::new(env, NetworkId::UnitAlbatross, time).unwrap(),
     ));
 
@@ -232,7 +230,7 @@ fn history_sync_works_with_micro_blocks() {
     // Create a second blockchain to push these blocks.
     let env2 = VolatileEnvironment::new(10).unwrap();
 
-    let blockchain2 = Arc::new(RwLock::new(
+    let blockchain2 = Arc::new(BlockchainLock::new(
         Blockchain::new(env2, NetworkId::UnitAlbatross, time).unwrap(),
     ));
 
@@ -299,7 +297,7 @@ fn history_sync_works_with_diverging_history() {
     // Produce macro blocks to complete one epoch in blockchain1.
     let env = VolatileEnvironment::new(10).unwrap();
     let time = Arc::new(OffsetTime::new());
-    let blockchain1 = Arc::new(RwLock::new(
+    let blockchain1 = Arc::new(BlockchainLock::new(
         Blockchain::new(env, NetworkId::UnitAlbatross, time).unwrap(),
     ));
 
@@ -311,7 +309,7 @@ fn history_sync_works_with_diverging_history() {
     // Produce some micro blocks (with a different history) in blockchain2.
     let env = VolatileEnvironment::new(10).unwrap();
     let time = Arc::new(OffsetTime::new());
-    let blockchain2 = Arc::new(RwLock::new(
+    let blockchain2 = Arc::new(BlockchainLock::new(
         Blockchain::new(env, NetworkId::UnitAlbatross, time).unwrap(),
     ));
     fill_micro_blocks_with_txns(&producer, &blockchain2, 3, 1);
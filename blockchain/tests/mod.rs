@@ -4,11 +4,12 @@ use std::sync::Arc;
 use beserial::Deserialize;
 use nimiq_block::Block;
 use nimiq_block_production::{test_utils::TemporaryBlockProducer, BlockProducer};
-use nimiq_blockchain::{AbstractBlockchain, Blockchain};
+use nimiq_blockchain::{AbstractBlockchain, Blockchain, BlockchainLock};
 use nimiq_blockchain::{ForkEvent, PushResult};
 use nimiq_bls::{KeyPair, SecretKey};
 use nimiq_database::volatile::VolatileEnvironment;
 use nimiq_genesis::NetworkId;
+use nimiq_hash::Blake2bHash;
 use nimiq_keys::{KeyPair as SchnorrKeyPair, PrivateKey as SchnorrPrivateKey};
 use nimiq_primitives::policy;
 use nimiq_test_utils::blockchain::{sign_view_change, SIGNING_KEY, VOTING_KEY};
@@ -61,11 +62,41 @@ fn it_can_rebranch_view_changes() {
     assert_eq!(temp_producer1.push(fork2), Ok(PushResult::Extended));
 }
 
+#[test]
+fn the_reader_follows_the_head() {
+    let temp_producer1 = TemporaryBlockProducer::new();
+    let temp_producer2 = TemporaryBlockProducer::new();
+    let reader = temp_producer1.blockchain.read().reader();
+    assert_eq!(reader.block_number(), 0);
+
+    let block = temp_producer1.next_block(0, vec![]);
+    temp_producer2.push(block.clone()).unwrap();
+    assert_eq!(reader.head_hash(), block.hash());
+    assert_eq!(reader.block_number(), 1);
+
+    // The reader also follows rebranches.
+    temp_producer1.next_block(0, vec![]);
+    let fork = temp_producer2.next_block(1, vec![]);
+    assert_eq!(
+        temp_producer1.push(fork.clone()),
+        Ok(PushResult::Rebranched)
+    );
+    assert_eq!(reader.head_hash(), fork.hash());
+
+    let view = reader.view();
+    let address = policy::STAKING_CONTRACT_ADDRESS;
+    assert_eq!(view.head().head_hash, fork.hash());
+    assert_eq!(
+        view.get_account(&address),
+        temp_producer1.blockchain.read().get_account(&address)
+    );
+}
+
 #[test]
 fn it_can_push_consecutive_view_changes() {
     let time = Arc::new(OffsetTime::new());
     let env = VolatileEnvironment::new(10).unwrap();
-    let blockchain = Arc::new(RwLock::new(
+    let blockchain = Arc::new(BlockchainLock::new(
         Blockchain::new(env, NetworkId::UnitAlbatross, time).unwrap(),
     ));
     let signing_key = SchnorrKeyPair::from(
@@ -211,6 +242,77 @@ fn micro_block_works_after_macro_block() {
     assert_eq!(temp_producer.blockchain.read().view_number(), 2);
 }
 
+#[test]
+fn it_recovers_from_a_corrupted_chain_tail() {
+    let time = Arc::new(OffsetTime::new());
+    let env = VolatileEnvironment::new(10).unwrap();
+    let temp_producer = TemporaryBlockProducer {
+        blockchain: Arc::new(BlockchainLock::new(
+            Blockchain::new(env.clone(), NetworkId::UnitAlbatross, Arc::clone(&time)).unwrap(),
+        )),
+        ..TemporaryBlockProducer::new()
+    };
+
+    for _ in 0..policy::BATCH_LENGTH {
+        temp_producer.next_block(0, vec![]);
+    }
+    temp_producer.next_block(1, vec![]);
+    temp_producer.next_block(1, vec![]);
+    assert_eq!(
+        temp_producer.blockchain.read().block_number(),
+        policy::BATCH_LENGTH + 2
+    );
+
+    // Simulate a crash during a commit that left the head pointing to a block that was never
+    // stored.
+    {
+        let blockchain = temp_producer.blockchain.read();
+        let mut txn = blockchain.write_transaction();
+        blockchain
+            .chain_store
+            .set_head(&mut txn, &Blake2bHash::default());
+        txn.commit();
+    }
+
+    // The blockchain rolls back to the last macro block instead of failing to load.
+    let blockchain = Blockchain::new(env, NetworkId::UnitAlbatross, time).unwrap();
+    assert_eq!(blockchain.block_number(), policy::BATCH_LENGTH);
+    assert_eq!(blockchain.head_hash(), blockchain.macro_head_hash());
+    assert!(blockchain
+        .chain_store
+        .get_block_at(policy::BATCH_LENGTH + 1, false, None)
+        .is_none());
+
+    // The reverted blocks can be pushed again.
+    *temp_producer.blockchain.write() = blockchain;
+    temp_producer.next_block(1, vec![]);
+    assert_eq!(
+        temp_producer.blockchain.read().block_number(),
+        policy::BATCH_LENGTH + 1
+    );
+}
+
+#[test]
+fn it_can_verify_slot_assignments() {
+    let temp_producer = TemporaryBlockProducer::new();
+
+    let block = temp_producer.next_block(2, vec![]);
+
+    let blockchain = temp_producer.blockchain.read();
+    let validators = blockchain.current_validators().unwrap();
+
+    let assignment = blockchain
+        .get_slot_assignment_at(block.block_number(), block.view_number(), None)
+        .unwrap();
+    assert!(assignment.verify(&validators));
+    assert!(assignment.verify_seed(block.seed()));
+
+    // A slot that doesn't follow from the VRF entropy must be rejected.
+    let mut forged = assignment;
+    forged.slot.number = (forged.slot.number + 1) % policy::SLOTS;
+    assert!(!forged.verify(&validators));
+}
+
 #[test]
 fn it_can_rebranch_forks() {
     // Build forks using two producers.
@@ -0,0 +1,99 @@
+use std::sync::Arc;
+
+use nimiq_block_production::BlockProducer;
+use nimiq_blockchain::receipt::ReceiptError;
+use nimiq_blockchain::{AbstractBlockchain, Blockchain, BlockchainLock};
+use nimiq_database::volatile::VolatileEnvironment;
+use nimiq_genesis::NetworkId;
+use nimiq_hash::{Blake2bHash, Hash};
+use nimiq_primitives::policy::{BATCHES_PER_EPOCH, BATCH_LENGTH, EPOCH_LENGTH};
+use nimiq_test_utils::blockchain::{
+    fill_micro_blocks_with_txns, produce_macro_blocks_with_txns, signing_key, voting_key,
+};
+use nimiq_utils::time::OffsetTime;
+
+/// Returns the hash of the first transaction of the block at the given block number.
+fn tx_hash_at(blockchain: &Blockchain, block_number: u32) -> Blake2bHash {
+    let block = blockchain.get_block_at(block_number, true, None).unwrap();
+    block.transactions().unwrap()[0].hash()
+}
+
+#[test]
+fn payment_receipts_can_be_verified() {
+    let time = Arc::new(OffsetTime::new());
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Arc::new(BlockchainLock::new(
+        Blockchain::new(env, NetworkId::UnitAlbatross, time).unwrap(),
+    ));
+
+    // Finalize the first epoch and produce a batch of the second one.
+    let producer = BlockProducer::new(signing_key(), voting_key());
+    produce_macro_blocks_with_txns(&producer, &blockchain, BATCHES_PER_EPOCH as usize, 1, 0);
+    fill_micro_blocks_with_txns(&producer, &blockchain, 1, 1);
+
+    let blockchain = blockchain.read();
+
+    // The receipt of a finalized transaction references the election block of its epoch.
+    let tx_hash = tx_hash_at(&blockchain, 1);
+    let receipt = blockchain.create_payment_receipt(&tx_hash).unwrap();
+    assert_eq!(receipt.verify(&tx_hash).unwrap().block_number, 1);
+    assert_eq!(
+        receipt.block_hash(),
+        blockchain.get_block_at(1, true, None).unwrap().hash()
+    );
+    assert_eq!(
+        receipt.election_block_hash(),
+        Some(blockchain.election_head_hash())
+    );
+
+    // The receipt doesn't prove any other transaction.
+    let other_hash = tx_hash_at(&blockchain, 2);
+    assert_eq!(
+        receipt.verify(&other_hash).err(),
+        Some(ReceiptError::TransactionNotFound)
+    );
+
+    // The receipt of a transaction of the current epoch doesn't reference an election block yet.
+    let tx_hash = tx_hash_at(&blockchain, EPOCH_LENGTH + 1);
+    let receipt = blockchain.create_payment_receipt(&tx_hash).unwrap();
+    assert_eq!(
+        receipt.verify(&tx_hash).unwrap().block_number,
+        EPOCH_LENGTH + 1
+    );
+    assert_eq!(receipt.election_block_hash(), None);
+}
+
+#[test]
+fn payment_receipts_with_swapped_headers_are_rejected() {
+    let time = Arc::new(OffsetTime::new());
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Arc::new(BlockchainLock::new(
+        Blockchain::new(env, NetworkId::UnitAlbatross, time).unwrap(),
+    ));
+
+    let producer = BlockProducer::new(signing_key(), voting_key());
+    produce_macro_blocks_with_txns(&producer, &blockchain, BATCHES_PER_EPOCH as usize, 1, 0);
+
+    let blockchain = blockchain.read();
+    let tx_hash = tx_hash_at(&blockchain, 1);
+
+    // The header of another block doesn't match the transaction.
+    let mut receipt = blockchain.create_payment_receipt(&tx_hash).unwrap();
+    receipt.header = blockchain.get_block_at(2, true, None).unwrap().header();
+    assert_eq!(
+        receipt.verify(&tx_hash).err(),
+        Some(ReceiptError::BlockMismatch)
+    );
+
+    // A checkpoint block isn't a valid election reference.
+    let mut receipt = blockchain.create_payment_receipt(&tx_hash).unwrap();
+    receipt.election.as_mut().unwrap().header = blockchain
+        .get_block_at(BATCH_LENGTH, true, None)
+        .unwrap()
+        .unwrap_macro()
+        .header;
+    assert_eq!(
+        receipt.verify(&tx_hash).err(),
+        Some(ReceiptError::InvalidElectionReference)
+    );
+}
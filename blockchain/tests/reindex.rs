@@ -0,0 +1,47 @@
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::Arc;
+
+use nimiq_block_production::BlockProducer;
+use nimiq_blockchain::{Blockchain, BlockchainLock};
+use nimiq_database::volatile::VolatileEnvironment;
+use nimiq_genesis::NetworkId;
+use nimiq_primitives::policy::BATCHES_PER_EPOCH;
+use nimiq_test_utils::blockchain::{produce_macro_blocks, signing_key, voting_key};
+use nimiq_utils::time::OffsetTime;
+
+#[test]
+fn an_interrupted_reindex_resumes() {
+    let time = Arc::new(OffsetTime::new());
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Arc::new(BlockchainLock::new(
+        Blockchain::new(env, NetworkId::UnitAlbatross, time).unwrap(),
+    ));
+
+    let producer = BlockProducer::new(signing_key(), voting_key());
+    produce_macro_blocks(&producer, &blockchain, (2 * BATCHES_PER_EPOCH + 1) as usize);
+
+    let blockchain = blockchain.read();
+    assert!(!blockchain.is_reindexing());
+
+    // Interrupt the reindex after the first epoch was committed.
+    let interrupted = catch_unwind(AssertUnwindSafe(|| {
+        blockchain.reindex(|progress| {
+            assert_eq!(progress.epoch, 0);
+            panic!("interrupted");
+        })
+    }));
+    assert!(interrupted.is_err());
+    assert!(blockchain.is_reindexing());
+
+    // Reindexing again continues with the next epoch.
+    let mut epochs = vec![];
+    let mut last_epoch = 0;
+    let reindexed = blockchain.reindex(|progress| {
+        epochs.push(progress.epoch);
+        last_epoch = progress.last_epoch;
+    });
+    assert!(last_epoch >= 2);
+    assert_eq!(epochs, (1..=last_epoch).collect::<Vec<_>>());
+    assert_eq!(reindexed, last_epoch);
+    assert!(!blockchain.is_reindexing());
+}
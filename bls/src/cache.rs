@@ -0,0 +1,111 @@
+use std::{
+    io::Error,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use lazy_static::lazy_static;
+use lru::LruCache;
+use parking_lot::Mutex;
+
+use crate::{CompressedPublicKey, PublicKey};
+
+lazy_static! {
+    static ref GLOBAL_CACHE: PublicKeyCache = PublicKeyCache::new(PublicKeyCache::DEFAULT_CAPACITY);
+}
+
+/// The usage of a [`PublicKeyCache`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PublicKeyCacheStats {
+    pub hits: usize,
+    pub misses: usize,
+    /// The number of cached public keys.
+    pub len: usize,
+    pub capacity: usize,
+}
+
+/// A bounded cache of uncompressed public keys, keyed by their compressed form.
+///
+/// Uncompressing a public key is expensive, and the same keys are uncompressed over and over again
+/// (e.g. when verifying DHT records, aggregates and macro block justifications). The least recently
+/// used entries are evicted once the capacity is reached.
+pub struct PublicKeyCache {
+    cache: Mutex<LruCache<CompressedPublicKey, PublicKey>>,
+    hit_count: AtomicUsize,
+    miss_count: AtomicUsize,
+}
+
+impl PublicKeyCache {
+    /// The capacity of the global cache. This comfortably fits the voting keys of two full
+    /// validator sets.
+    pub const DEFAULT_CAPACITY: usize = 1024;
+
+    pub fn new(capacity: usize) -> Self {
+        PublicKeyCache {
+            cache: Mutex::new(LruCache::new(capacity)),
+            hit_count: AtomicUsize::new(0),
+            miss_count: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the process-wide cache shared by all components.
+    pub fn global() -> &'static PublicKeyCache {
+        &GLOBAL_CACHE
+    }
+
+    /// Returns the uncompressed public key from the cache or uncompresses and caches it.
+    /// Keys that fail to uncompress are not cached.
+    pub fn get_or_uncompress(&self, compressed: &CompressedPublicKey) -> Result<PublicKey, Error> {
+        if let Some(public_key) = self.cache.lock().get(compressed) {
+            self.hit_count.fetch_add(1, Ordering::Release);
+            return Ok(*public_key);
+        }
+
+        self.miss_count.fetch_add(1, Ordering::Release);
+
+        // Uncompress without holding the lock, this is the expensive part.
+        let public_key = compressed.uncompress()?;
+        self.cache.lock().put(compressed.clone(), public_key);
+        Ok(public_key)
+    }
+
+    /// Inserts an already uncompressed public key into the cache.
+    pub fn insert(&self, compressed: CompressedPublicKey, public_key: PublicKey) {
+        self.cache.lock().put(compressed, public_key);
+    }
+
+    pub fn clear(&self) {
+        self.cache.lock().clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.cache.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cache.lock().is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.cache.lock().cap()
+    }
+
+    #[inline]
+    pub fn hit_count(&self) -> usize {
+        self.hit_count.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    pub fn miss_count(&self) -> usize {
+        self.miss_count.load(Ordering::Acquire)
+    }
+
+    pub fn stats(&self) -> PublicKeyCacheStats {
+        let cache = self.cache.lock();
+        PublicKeyCacheStats {
+            hits: self.hit_count(),
+            misses: self.miss_count(),
+            len: cache.len(),
+            capacity: cache.cap(),
+        }
+    }
+}
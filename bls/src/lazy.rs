@@ -78,7 +78,7 @@ impl LazyPublicKey {
         } else {
             // Slow path, upgrade, write, downgrade and return
             let mut upgraded = RwLockUpgradableReadGuard::upgrade(upgradable);
-            *upgraded = Some(match self.compressed.uncompress() {
+            *upgraded = Some(match self.compressed.uncompress_cached() {
                 Ok(p) => p,
                 _ => return None,
             });
@@ -138,7 +138,9 @@ impl From<LazyPublicKey> for CompressedPublicKey {
 
 #[cfg(feature = "beserial")]
 mod serialization {
-    use beserial::{Deserialize, ReadBytesExt, Serialize, SerializingError, WriteBytesExt};
+    use beserial::{
+        Deserialize, ReadBytesExt, Schema, Serialize, SerializingError, TypeSchema, WriteBytesExt,
+    };
 
     use super::*;
 
@@ -159,4 +161,14 @@ mod serialization {
             )?))
         }
     }
+
+    impl Schema for LazyPublicKey {
+        fn name() -> String {
+            CompressedPublicKey::name()
+        }
+
+        fn schema() -> TypeSchema {
+            CompressedPublicKey::schema()
+        }
+    }
 }
@@ -10,6 +10,10 @@ pub mod serialization;
 // Implements big-endian serialization of algebra types.
 pub mod compression;
 
+// Implements a global cache for uncompressed public keys.
+#[cfg(feature = "cache")]
+pub mod cache;
+
 // Implements the LazyPublicKey type. Which is a faster, cached version of PublicKey.
 #[cfg(feature = "lazy")]
 pub mod lazy;
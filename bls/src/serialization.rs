@@ -3,7 +3,9 @@ use std::io;
 use ark_ff::{FromBytes, ToBytes};
 use ark_mnt6_753::Fr;
 
-use beserial::{Deserialize, ReadBytesExt, Serialize, SerializingError, WriteBytesExt};
+use beserial::{
+    Deserialize, ReadBytesExt, Schema, Serialize, SerializingError, TypeSchema, WriteBytesExt,
+};
 use nimiq_hash::{Hash, SerializeContent};
 
 use crate::{
@@ -202,3 +204,59 @@ impl Deserialize for KeyPair {
         Ok(KeyPair::from(secret))
     }
 }
+
+impl Schema for CompressedPublicKey {
+    fn name() -> String {
+        "BlsPublicKey".to_string()
+    }
+
+    fn schema() -> TypeSchema {
+        TypeSchema::Bytes {
+            len: CompressedPublicKey::SIZE,
+        }
+    }
+}
+
+impl Schema for CompressedSignature {
+    fn name() -> String {
+        "BlsSignature".to_string()
+    }
+
+    fn schema() -> TypeSchema {
+        TypeSchema::Bytes {
+            len: CompressedSignature::SIZE,
+        }
+    }
+}
+
+/// Public keys are serialized in their compressed form.
+impl Schema for PublicKey {
+    fn name() -> String {
+        CompressedPublicKey::name()
+    }
+
+    fn schema() -> TypeSchema {
+        CompressedPublicKey::schema()
+    }
+}
+
+/// Signatures are serialized in their compressed form.
+impl Schema for Signature {
+    fn name() -> String {
+        CompressedSignature::name()
+    }
+
+    fn schema() -> TypeSchema {
+        CompressedSignature::schema()
+    }
+}
+
+impl Schema for AggregateSignature {
+    fn name() -> String {
+        Signature::name()
+    }
+
+    fn schema() -> TypeSchema {
+        Signature::schema()
+    }
+}
@@ -33,6 +33,13 @@ impl CompressedPublicKey {
         })
     }
 
+    /// Transforms the compressed form back into the projective form, using the global
+    /// public key cache to avoid repeatedly uncompressing the same key.
+    #[cfg(feature = "cache")]
+    pub fn uncompress_cached(&self) -> Result<PublicKey, Error> {
+        crate::cache::PublicKeyCache::global().get_or_uncompress(self)
+    }
+
     /// Formats the compressed form into a hexadecimal string.
     pub fn to_hex(&self) -> String {
         hex::encode(self.as_ref())
@@ -127,6 +127,47 @@ fn uncompress_compress() {
     );
 }
 
+#[test]
+fn public_key_cache() {
+    let rng = &mut thread_rng();
+
+    let cache = cache::PublicKeyCache::new(2);
+    let keypairs: Vec<KeyPair> = (0..3).map(|_| KeyPair::generate(rng)).collect();
+    let compressed: Vec<CompressedPublicKey> = keypairs
+        .iter()
+        .map(|keypair| keypair.public_key.compress())
+        .collect();
+
+    assert_eq!(
+        cache.get_or_uncompress(&compressed[0]).unwrap(),
+        keypairs[0].public_key
+    );
+    assert_eq!(
+        cache.get_or_uncompress(&compressed[0]).unwrap(),
+        keypairs[0].public_key
+    );
+    assert_eq!(cache.hit_count(), 1);
+    assert_eq!(cache.miss_count(), 1);
+
+    // Inserting two more keys evicts the first one.
+    cache.get_or_uncompress(&compressed[1]).unwrap();
+    cache.get_or_uncompress(&compressed[2]).unwrap();
+    assert_eq!(cache.len(), 2);
+
+    cache.get_or_uncompress(&compressed[0]).unwrap();
+    assert_eq!(cache.hit_count(), 1);
+    assert_eq!(cache.miss_count(), 4);
+    assert_eq!(
+        cache.stats(),
+        cache::PublicKeyCacheStats {
+            hits: 1,
+            misses: 4,
+            len: 2,
+            capacity: 2,
+        }
+    );
+}
+
 #[test]
 fn aggregate_signatures_same_message() {
     let rng = &mut thread_rng();
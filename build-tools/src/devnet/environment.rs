@@ -0,0 +1,373 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Error;
+use rand::thread_rng;
+
+use beserial::Serialize;
+use nimiq_bls::KeyPair as BlsKeyPair;
+use nimiq_keys::{Address, KeyPair, SecureGenerate};
+
+/// The name of the node that the validators connect to.
+pub const SEED_NODE: &str = "seed";
+
+/// The stake of every validator in Luna.
+const VALIDATOR_STAKE: u64 = 1_000_000;
+
+/// The balance of the reward account of every validator in Luna, so it can pay for transactions.
+const REWARD_BALANCE: u64 = 10_000_000_00000;
+
+/// The keys of a validator.
+pub struct ValidatorKeys {
+    pub validator_address: Address,
+    pub signing_key: KeyPair,
+    pub voting_key: BlsKeyPair,
+    /// Receives the rewards and pays the fees of the validator.
+    pub reward_key: KeyPair,
+}
+
+impl ValidatorKeys {
+    pub fn generate() -> Self {
+        let mut rng = thread_rng();
+        ValidatorKeys {
+            validator_address: Address::from(&KeyPair::generate(&mut rng)),
+            signing_key: KeyPair::generate(&mut rng),
+            voting_key: BlsKeyPair::generate(&mut rng),
+            reward_key: KeyPair::generate(&mut rng),
+        }
+    }
+}
+
+/// A devnet of validators that connect to a common seed node, stored in a directory:
+///
+/// * `dev-albatross.toml`: The genesis config, which the client needs to be built with.
+/// * `seed/client.toml`: The config of the seed node, which has RPC enabled.
+/// * `validator{n}/client.toml`: The configs of the validators, including their keys.
+/// * `docker-compose.yml`: Optionally, a compose file that runs the same network in containers.
+///
+/// The database and log file of every node are written to its directory.
+pub struct Environment {
+    pub directory: PathBuf,
+    pub validators: Vec<ValidatorKeys>,
+    /// The port of the seed node. The validators listen on the following ports.
+    pub base_port: u16,
+    pub rpc_port: u16,
+}
+
+impl Environment {
+    pub fn generate<P: AsRef<Path>>(
+        directory: P,
+        num_validators: usize,
+        base_port: u16,
+        rpc_port: u16,
+    ) -> Self {
+        Environment {
+            directory: directory.as_ref().to_path_buf(),
+            validators: (0..num_validators)
+                .map(|_| ValidatorKeys::generate())
+                .collect(),
+            base_port,
+            rpc_port,
+        }
+    }
+
+    /// Returns the names of the nodes, which are also the names of their directories.
+    pub fn node_names(&self) -> Vec<String> {
+        let mut names = vec![SEED_NODE.to_string()];
+        names.extend((1..=self.validators.len()).map(|i| format!("validator{}", i)));
+        names
+    }
+
+    pub fn write(&self, docker: bool) -> Result<(), Error> {
+        fs::create_dir_all(&self.directory)?;
+        // The configs reference the node directories, which must be absolute to not depend on
+        // the working directory of the client.
+        let directory = fs::canonicalize(&self.directory)?;
+
+        fs::write(directory.join("dev-albatross.toml"), self.genesis_config())?;
+
+        let seed_dir = directory.join(SEED_NODE);
+        fs::create_dir_all(&seed_dir)?;
+        fs::write(seed_dir.join("client.toml"), self.seed_config(&seed_dir))?;
+
+        for (i, validator) in self.validators.iter().enumerate() {
+            let validator_dir = directory.join(format!("validator{}", i + 1));
+            fs::create_dir_all(&validator_dir)?;
+            fs::write(
+                validator_dir.join("client.toml"),
+                self.validator_config(&validator_dir, i, validator),
+            )?;
+        }
+
+        if docker {
+            fs::write(directory.join("docker-compose.yml"), self.docker_compose())?;
+        }
+
+        info!(
+            "Wrote devnet with {} validators to {}",
+            self.validators.len(),
+            directory.display()
+        );
+        Ok(())
+    }
+
+    fn genesis_config(&self) -> String {
+        let mut config = String::from(
+            r#"name = "dev-albatross"
+seed_message = "Albatross DevNet"
+timestamp = "2021-07-15T00:00:00.000+00:00"
+vrf_seed = "e8c7f2f3935da9ca39419aa7d2cc90817245f75e58cc543f2b9478766308e8a50fffccb09e2df3546f5a0c0059d73a506c48fa2b546f15b511d0f7a63f0ee20cd510a87f520e26478bb687ca31a08db8b02921f9a22e32a790c07f16dbdf4501"
+"#,
+        );
+
+        for validator in &self.validators {
+            write!(
+                config,
+                r#"
+[[validators]]
+validator_address = "{}"
+signing_key = "{}"
+voting_key = "{}"
+reward_address = "{}"
+"#,
+                validator.validator_address.to_user_friendly_address(),
+                hex::encode(validator.signing_key.public.serialize_to_vec()),
+                hex::encode(validator.voting_key.public_key.serialize_to_vec()),
+                Address::from(&validator.reward_key).to_user_friendly_address(),
+            )
+            .unwrap();
+        }
+
+        for validator in &self.validators {
+            write!(
+                config,
+                r#"
+[[stakers]]
+staker_address = "{}"
+balance = {}
+delegation = "{}"
+"#,
+                Address::from(&validator.reward_key).to_user_friendly_address(),
+                VALIDATOR_STAKE,
+                validator.validator_address.to_user_friendly_address(),
+            )
+            .unwrap();
+        }
+
+        for validator in &self.validators {
+            write!(
+                config,
+                r#"
+[[accounts]]
+address = "{}"
+balance = {}
+"#,
+                Address::from(&validator.reward_key).to_user_friendly_address(),
+                REWARD_BALANCE,
+            )
+            .unwrap();
+        }
+
+        config
+    }
+
+    fn seed_config(&self, path: &Path) -> String {
+        format!(
+            r#"[network]
+peer_key_file = "{path}/peer_key.dat"
+listen_addresses = [
+    "/ip4/127.0.0.1/tcp/{port}/ws",
+]
+
+[consensus]
+network = "dev-albatross"
+min_peers = 1
+
+[database]
+path = "{path}"
+
+[rpc-server]
+bind = "127.0.0.1"
+port = {rpc_port}
+
+[log]
+level = "debug"
+timestamps = true
+statistics = 5
+file = "{path}/client.log"
+"#,
+            path = path.display(),
+            port = self.base_port,
+            rpc_port = self.rpc_port,
+        )
+    }
+
+    fn validator_config(&self, path: &Path, index: usize, validator: &ValidatorKeys) -> String {
+        format!(
+            r#"[network]
+peer_key_file = "{path}/peer_key.dat"
+listen_addresses = [
+    "/ip4/127.0.0.1/tcp/{port}/ws",
+]
+seed_nodes = [
+    {{ address = "/ip4/127.0.0.1/tcp/{seed_port}/ws" }},
+]
+
+[consensus]
+network = "dev-albatross"
+min_peers = 1
+
+[database]
+path = "{path}"
+
+[log]
+level = "debug"
+timestamps = true
+statistics = 5
+file = "{path}/client.log"
+
+[validator]
+validator_address = "{validator_address}"
+signing_key_file = "{path}/signing_key.dat"
+signing_key = "{signing_key}"
+voting_key_file = "{path}/voting_key.dat"
+voting_key = "{voting_key}"
+fee_key_file = "{path}/fee_key.dat"
+fee_key = "{fee_key}"
+"#,
+            path = path.display(),
+            port = self.base_port as usize + index + 1,
+            seed_port = self.base_port,
+            validator_address = validator.validator_address.to_user_friendly_address(),
+            signing_key = hex::encode(validator.signing_key.private.serialize_to_vec()),
+            voting_key = hex::encode(validator.voting_key.secret_key.serialize_to_vec()),
+            fee_key = hex::encode(validator.reward_key.private.serialize_to_vec()),
+        )
+    }
+
+    fn docker_compose(&self) -> String {
+        let mut compose = format!(
+            r#"version: "3.5"
+
+networks:
+  devnet:
+    name: ${{NETWORK_NAME:?err}}
+    driver: bridge
+    ipam:
+      driver: default
+      config:
+        - subnet: 7.0.0.0/24
+
+services:
+  seed:
+    image: core:latest
+    environment:
+      - LISTEN_ADDRESSES=/ip4/7.0.0.99/tcp/8443/ws
+      - NIMIQ_HOST=seed.${{NETWORK_NAME:?err}}
+      - NIMIQ_NETWORK=dev-albatross
+      - NIMIQ_PEER_KEY_FILE=/home/nimiq/.nimiq/peer_key.dat
+      - NIMIQ_INSTANT_INBOUND=true
+      - RPC_ENABLED=true
+      - RUST_BACKTRACE="1"
+      - NIMIQ_LOG_LEVEL=debug
+      - NIMIQ_LOG_TIMESTAMPS=true
+    ports:
+      - "{rpc_port}:8648"
+    networks:
+      devnet:
+        ipv4_address: 7.0.0.99
+    volumes:
+      - "seed:/home/nimiq/.nimiq:rw"
+"#,
+            rpc_port = self.rpc_port,
+        );
+
+        for (i, validator) in self.validators.iter().enumerate() {
+            write!(
+                compose,
+                r#"
+  validator{n}:
+    image: core:latest
+    depends_on:
+      - seed
+    environment:
+      - LISTEN_ADDRESSES=/ip4/{ip}/tcp/8443/ws
+      - NIMIQ_HOST=validator{n}.${{NETWORK_NAME:?err}}
+      - NIMIQ_NETWORK=dev-albatross
+      - NIMIQ_SEED_NODES=/ip4/7.0.0.99/tcp/8443/ws
+      - NIMIQ_PEER_KEY_FILE=/home/nimiq/.nimiq/peer_key.dat
+      - NIMIQ_INSTANT_INBOUND=true
+      - NIMIQ_VALIDATOR=validator
+      - VALIDATOR_ADDRESS={validator_address}
+      - SIGNING_KEY={signing_key}
+      - VOTING_KEY={voting_key}
+      - FEE_KEY={fee_key}
+      - RPC_ENABLED=false
+      - RUST_BACKTRACE="1"
+      - NIMIQ_LOG_LEVEL=debug
+      - NIMIQ_LOG_TIMESTAMPS=true
+    networks:
+      devnet:
+        ipv4_address: {ip}
+    volumes:
+      - "validator{n}:/home/nimiq/.nimiq:rw"
+"#,
+                n = i + 1,
+                ip = format!("7.0.0.{}", i + 2),
+                validator_address = validator
+                    .validator_address
+                    .to_user_friendly_address()
+                    .replace(' ', ""),
+                signing_key = hex::encode(validator.signing_key.private.serialize_to_vec()),
+                voting_key = hex::encode(validator.voting_key.secret_key.serialize_to_vec()),
+                fee_key = hex::encode(validator.reward_key.private.serialize_to_vec()),
+            )
+            .unwrap();
+        }
+
+        compose.push_str("\nvolumes:\n");
+        for name in self.node_names() {
+            writeln!(compose, "  {}:", name).unwrap();
+        }
+
+        compose
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nimiq_build_tools::genesis::GenesisBuilder;
+    use nimiq_database::volatile::VolatileEnvironment;
+
+    use super::*;
+
+    #[test]
+    fn it_writes_a_genesis_config_that_can_be_built() {
+        let directory = tempfile::tempdir().unwrap();
+        let environment = Environment::generate(directory.path(), 3, 9100, 8648);
+        environment.write(true).unwrap();
+
+        let env = VolatileEnvironment::new(10).unwrap();
+        let genesis = GenesisBuilder::new()
+            .with_config_file(directory.path().join("dev-albatross.toml"))
+            .unwrap()
+            .generate(env)
+            .unwrap();
+
+        let validators = genesis
+            .block
+            .unwrap_macro()
+            .body
+            .unwrap()
+            .validators
+            .unwrap();
+        assert_eq!(validators.num_validators(), 3);
+
+        // The node configs are valid TOML as well.
+        for name in environment.node_names() {
+            let config = fs::read_to_string(directory.path().join(name).join("client.toml"));
+            assert!(toml::from_str::<toml::Value>(&config.unwrap()).is_ok());
+        }
+    }
+}
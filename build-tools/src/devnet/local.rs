@@ -0,0 +1,132 @@
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Error};
+
+use crate::environment::SEED_NODE;
+
+/// How often the nodes are checked.
+const MONITOR_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Runs the nodes of a devnet as local processes.
+pub struct LocalNetwork {
+    nodes: Vec<(String, Child)>,
+    seed_log: LogFollower,
+}
+
+impl LocalNetwork {
+    /// Starts a client for every node. The seed node is started first.
+    pub fn start(client: &Path, env_dir: &Path, node_names: &[String]) -> Result<Self, Error> {
+        let mut network = LocalNetwork {
+            nodes: vec![],
+            seed_log: LogFollower::new(env_dir.join(SEED_NODE).join("client.log")),
+        };
+
+        for name in node_names {
+            let node_dir = env_dir.join(name);
+            info!("Starting {}", name);
+            let child = Command::new(client)
+                .arg("--config")
+                .arg(node_dir.join("client.toml"))
+                .stdout(Stdio::null())
+                .stderr(File::create(node_dir.join("stderr.log"))?)
+                .spawn()?;
+            network.nodes.push((name.clone(), child));
+
+            if name == SEED_NODE {
+                // Give the seed node time to listen before the validators connect to it.
+                sleep(Duration::from_secs(1));
+            }
+        }
+
+        Ok(network)
+    }
+
+    /// Follows the head of the seed node until interrupted. Fails if a node exits or if no block
+    /// is produced within the stall timeout.
+    pub fn monitor(
+        &mut self,
+        stall_timeout: Duration,
+        keyboard_interrupt: &AtomicBool,
+    ) -> Result<(), Error> {
+        let mut head = None;
+        let mut last_progress = Instant::now();
+
+        while !keyboard_interrupt.load(Ordering::SeqCst) {
+            for (name, child) in &mut self.nodes {
+                if let Some(status) = child.try_wait()? {
+                    bail!("{} exited with {}", name, status);
+                }
+            }
+
+            let seed_head = self.seed_log.latest_head().or(head);
+            if seed_head > head {
+                if let Some(block_number) = seed_head {
+                    info!("Devnet at block #{}", block_number);
+                }
+                head = seed_head;
+                last_progress = Instant::now();
+            } else if last_progress.elapsed() > stall_timeout {
+                bail!(
+                    "No block was produced for {}s, the devnet is stuck",
+                    stall_timeout.as_secs()
+                );
+            }
+
+            sleep(MONITOR_INTERVAL);
+        }
+
+        Ok(())
+    }
+
+    pub fn stop(&mut self) {
+        for (name, child) in &mut self.nodes {
+            info!("Stopping {}", name);
+            if let Err(e) = child.kill().and_then(|_| child.wait()) {
+                warn!("Failed to stop {}: {}", name, e);
+            }
+        }
+        self.nodes.clear();
+    }
+}
+
+impl Drop for LocalNetwork {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Reads the lines that are appended to the log file of a client.
+struct LogFollower {
+    path: PathBuf,
+    offset: u64,
+}
+
+impl LogFollower {
+    /// Follows the log file from its current end, so that the heads of earlier runs are skipped.
+    fn new(path: PathBuf) -> Self {
+        let offset = fs::metadata(&path)
+            .map(|metadata| metadata.len())
+            .unwrap_or(0);
+        LogFollower { path, offset }
+    }
+
+    /// Returns the block number of the last head that was logged in the statistics of the client
+    /// since the previous call.
+    fn latest_head(&mut self) -> Option<u32> {
+        let mut file = File::open(&self.path).ok()?;
+        file.seek(SeekFrom::Start(self.offset)).ok()?;
+        let mut appended = vec![];
+        self.offset += file.read_to_end(&mut appended).ok()? as u64;
+
+        let appended = String::from_utf8_lossy(&appended);
+        let (_, head) = appended.rsplit_once("Head: #")?;
+        let digits = head.split(|c: char| !c.is_ascii_digit()).next()?;
+        digits.parse().ok()
+    }
+}
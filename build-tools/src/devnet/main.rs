@@ -1,24 +1,82 @@
 #[macro_use]
 extern crate log;
 
-use std::fs::{canonicalize, remove_file};
-use std::path::PathBuf;
+use std::fs::{canonicalize, read_dir, remove_file};
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
-use anyhow::Error;
+use anyhow::{bail, Error};
 use structopt::StructOpt;
 
 use docker::Docker;
+use environment::{Environment, SEED_NODE};
+use local::LocalNetwork;
 
 mod docker;
+mod environment;
+mod local;
+
+const DEFAULT_BASE_PORT: u16 = 9100;
+const DEFAULT_RPC_PORT: u16 = 8648;
 
 #[derive(Debug, StructOpt)]
 #[structopt(about = "Run an Albatross DevNet locally")]
-struct Args {
+enum Args {
+    /// Generate the genesis config and the configs and keys of the nodes of a new devnet.
+    Create(CreateArgs),
+    /// Build the client with the genesis of a devnet and run its nodes.
+    Run(RunArgs),
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(rename_all = "kebab")]
+struct CreateArgs {
+    /// The number of validators.
+    #[structopt(short = "n", long, default_value = "4")]
+    validators: usize,
+
+    /// Path to the environment to create.
+    #[structopt(short, long, parse(from_os_str), default_value = "/tmp/nimiq-devnet")]
+    output: PathBuf,
+
+    /// The port of the seed node. The validators listen on the following ports.
+    #[structopt(long, default_value = "9100")]
+    base_port: u16,
+
+    /// The RPC port of the seed node.
+    #[structopt(long, default_value = "8648")]
+    rpc_port: u16,
+
+    /// Also write a docker-compose file for the devnet.
+    #[structopt(long)]
+    docker: bool,
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(rename_all = "kebab")]
+struct RunArgs {
     #[structopt(parse(from_os_str))]
     /// Path to the environment. Have a look at `devnet-environments`.
     env: PathBuf,
+
+    /// Create a new devnet with this many validators in the environment before running it.
+    #[structopt(short = "n", long)]
+    validators: Option<usize>,
+
+    /// Run the nodes with docker-compose instead of as local processes.
+    #[structopt(long)]
+    docker: bool,
+
+    /// Use this client binary instead of building one. It must have been built with
+    /// `NIMIQ_OVERRIDE_DEVNET_CONFIG` set to the genesis config of the environment.
+    #[structopt(long, parse(from_os_str))]
+    client: Option<PathBuf>,
+
+    /// Fail if no block is produced for this many seconds.
+    #[structopt(long, default_value = "60")]
+    stall_timeout: u64,
 }
 
 #[shellfn::shell]
@@ -33,13 +91,59 @@ fn build_client<S: ToString>(
 "#
 }
 
-fn run_devnet(args: Args, keyboard_interrupt: Arc<AtomicBool>) -> Result<(), Error> {
+/// Returns the names of the nodes of the environment, seed node first.
+fn node_names(env_dir: &Path) -> Result<Vec<String>, Error> {
+    let mut validators = vec![];
+    for entry in read_dir(env_dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if let Some(Ok(index)) = name.strip_prefix("validator").map(str::parse::<usize>) {
+            if entry.file_type()?.is_dir() {
+                validators.push((index, name));
+            }
+        }
+    }
+    validators.sort();
+
+    let mut names = vec![SEED_NODE.to_string()];
+    names.extend(validators.into_iter().map(|(_, name)| name));
+    Ok(names)
+}
+
+fn run_devnet(args: RunArgs, keyboard_interrupt: Arc<AtomicBool>) -> Result<(), Error> {
+    if let Some(num_validators) = args.validators {
+        Environment::generate(
+            &args.env,
+            num_validators,
+            DEFAULT_BASE_PORT,
+            DEFAULT_RPC_PORT,
+        )
+        .write(args.docker)?;
+    }
+
     let env_dir = canonicalize(&args.env).expect("Can't get absolute path");
 
-    // Build `nimiq-client` binary
-    info!("Building nimiq-client");
-    for line in build_client(env_dir.to_str().unwrap())? {
-        println!("{}", line?);
+    let client = match args.client {
+        Some(client) => client,
+        None => {
+            // Build `nimiq-client` binary
+            info!("Building nimiq-client");
+            for line in build_client(env_dir.to_str().unwrap())? {
+                println!("{}", line?);
+            }
+            env_dir.join("build").join("nimiq-client")
+        }
+    };
+
+    if !args.docker {
+        let mut network = LocalNetwork::start(&client, &env_dir, &node_names(&env_dir)?)?;
+        let result = network.monitor(Duration::from_secs(args.stall_timeout), &keyboard_interrupt);
+        network.stop();
+        return result;
+    }
+
+    if !env_dir.join("docker-compose.yml").exists() {
+        bail!("The environment has no docker-compose.yml, create it with --docker");
     }
 
     let docker = Docker::new(&env_dir);
@@ -63,8 +167,9 @@ fn run_devnet(args: Args, keyboard_interrupt: Arc<AtomicBool>) -> Result<(), Err
     // delete build directory
     //remove_dir_all(env_dir.join("build"))
     //    .expect("Failed to delete build directory");
-    remove_file(env_dir.join("build").join("nimiq-client"))
-        .expect("Failed to delete nimiq-client binary");
+    if args.client.is_none() {
+        remove_file(client).expect("Failed to delete nimiq-client binary");
+    }
 
     Ok(())
 }
@@ -81,12 +186,21 @@ fn main(args: Args) {
         let keyboard_interrupt = Arc::clone(&keyboard_interrupt);
         ctrlc::set_handler(move || {
             info!("Keyboard interrupt");
-            keyboard_interrupt.store(false, Ordering::SeqCst);
+            keyboard_interrupt.store(true, Ordering::SeqCst);
         })
         .expect("Failed to register handler for Ctrl-C");
     }
 
-    if let Err(e) = run_devnet(args, keyboard_interrupt) {
+    let result = match args {
+        Args::Create(args) => {
+            Environment::generate(&args.output, args.validators, args.base_port, args.rpc_port)
+                .write(args.docker)
+        }
+        Args::Run(args) => run_devnet(args, keyboard_interrupt),
+    };
+
+    if let Err(e) = result {
         error!("Error: {}", e);
+        std::process::exit(1);
     }
 }
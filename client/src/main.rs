@@ -2,25 +2,45 @@ use std::time::Duration;
 
 pub use nimiq::{
     client::{Client, Consensus},
-    config::command_line::CommandLine,
+    config::command_line::{Command, CommandLine, DbCommand, SniffCommand},
     config::config::ClientConfig,
     config::config_file::ConfigFile,
     error::Error,
     extras::{
-        deadlock::initialize_deadlock_detection,
         logging::{initialize_logging, log_error_cause_chain},
         panic::initialize_panic_reporting,
+        stall_detector::initialize_stall_detection,
     },
 };
 
+#[cfg(feature = "jemalloc")]
+use nimiq_utils::memory::TaggingAllocator;
+
+#[cfg(feature = "jemalloc")]
+#[global_allocator]
+static ALLOCATOR: TaggingAllocator<tikv_jemallocator::Jemalloc> =
+    TaggingAllocator(tikv_jemallocator::Jemalloc);
+
 async fn main_inner() -> Result<(), Error> {
-    // Initialize deadlock detection
-    initialize_deadlock_detection();
+    // Initialize stall detection
+    initialize_stall_detection();
 
     // Parse command line.
     let command_line = CommandLine::from_args();
     log::trace!("Command line: {:#?}", command_line);
 
+    // The schema and the decoding of captures don't depend on the configuration.
+    match &command_line.command {
+        Some(Command::NetworkSchema) => {
+            println!("{}", nimiq::registry::REGISTRY.schema_json());
+            return Ok(());
+        }
+        Some(Command::Sniff(SniffCommand::Decode { capture })) => {
+            return nimiq::sniff::decode_capture(capture, std::io::stdout().lock());
+        }
+        _ => {}
+    }
+
     // Parse config file - this will obey the `--config` command line option.
     let config_file = ConfigFile::find(Some(&command_line))?;
     log::trace!("Config file: {:#?}", config_file);
@@ -38,11 +58,29 @@ async fn main_inner() -> Result<(), Error> {
     builder.command_line(&command_line)?;
 
     // Finalize config.
-    let config = builder.build()?;
+    let mut config = builder.build()?;
+
+    // A capturing client records all inbound messages and doesn't take part in the validation.
+    let capture = match &command_line.command {
+        Some(Command::Sniff(SniffCommand::Record { output })) => {
+            config.network.record_messages = Some(output.clone());
+            config.validator = None;
+            true
+        }
+        _ => false,
+    };
+
     log::debug!("Final configuration: {:#?}", config);
 
+    // Run maintenance commands instead of the client.
+    if let Some(Command::Db(DbCommand::Reindex)) = command_line.command {
+        return nimiq::db::reindex_database(config);
+    }
+
     // Clone config for RPC and metrics server
     let rpc_config = config.rpc_server.clone();
+    let webhook_config = config.webhooks.clone();
+    let event_sink_config = config.event_sink.clone();
     // let _metrics_config = config.metrics_server.clone();
 
     // Create client from config.
@@ -50,12 +88,40 @@ async fn main_inner() -> Result<(), Error> {
     let mut client: Client = Client::from_config(config).await?;
     log::info!("Client initialized");
 
+    if capture {
+        nimiq::sniff::subscribe_validator_topics(&client.network()).await?;
+        log::info!("Capturing the network traffic");
+    }
+
+    let executors = client.executors();
+
     // Initialize RPC server
     if let Some(rpc_config) = rpc_config {
-        use nimiq::extras::rpc_server::initialize_rpc_server;
+        use nimiq::extras::rpc_server::{initialize_public_rpc_server, initialize_rpc_server};
+
+        if let Some(public_config) = rpc_config.public.clone() {
+            let public_server = initialize_public_rpc_server(&client, public_config).bind()?;
+            executors.rpc.spawn(public_server);
+        }
+
         let rpc_server = initialize_rpc_server(&client, rpc_config, client.wallet_store())
             .expect("Failed to initialize RPC server");
-        tokio::spawn(async move { rpc_server.run().await });
+        executors.rpc.spawn(async move { rpc_server.run().await });
+    }
+
+    // Initialize webhooks
+    if let Some(webhook_config) = webhook_config {
+        use nimiq::extras::webhooks::initialize_webhooks;
+        let webhooks =
+            initialize_webhooks(&client, webhook_config).expect("Failed to initialize webhooks");
+        tokio::spawn(webhooks.run());
+    }
+
+    // Initialize event sink
+    if let Some(event_sink_config) = event_sink_config {
+        use nimiq::extras::event_sink::initialize_event_sink;
+        let event_sink = initialize_event_sink(&client, event_sink_config).await?;
+        tokio::spawn(event_sink.run());
     }
 
     // Initialize metrics server
@@ -85,13 +151,13 @@ async fn main_inner() -> Result<(), Error> {
     let consensus = client.consensus().unwrap();
 
     log::info!("Spawning consensus");
-    tokio::spawn(consensus);
+    executors.consensus.spawn(consensus);
     let consensus = client.consensus_proxy();
 
     // Start validator
     if let Some(validator) = client.validator() {
         log::info!("Spawning validator");
-        tokio::spawn(validator);
+        executors.consensus.spawn(validator);
     }
 
     // Create the "monitor" future which never completes to keep the client alive.
@@ -4,9 +4,10 @@ use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign};
 
 use itertools::{EitherOrBoth, Itertools};
 
+use beserial::schema::{define, Definitions};
 use beserial::{
-    uvar, Deserialize, FromPrimitive, ReadBytesExt, Serialize, SerializingError, ToPrimitive,
-    WriteBytesExt,
+    uvar, Deserialize, FieldSchema, FromPrimitive, ReadBytesExt, Schema, Serialize,
+    SerializingError, ToPrimitive, TypeSchema, WriteBytesExt,
 };
 
 #[inline]
@@ -319,6 +320,24 @@ impl Deserialize for BitSet {
     }
 }
 
+impl Schema for BitSet {
+    fn name() -> String {
+        "BitSet".to_string()
+    }
+
+    fn schema() -> TypeSchema {
+        TypeSchema::Struct {
+            fields: vec![FieldSchema::new::<Vec<u64>>(Some("store")).with_len_type("uvar", None)],
+        }
+    }
+
+    fn collect(definitions: &mut Definitions) {
+        if define::<Self>(definitions) {
+            <Vec<u64>>::collect(definitions);
+        }
+    }
+}
+
 impl fmt::Display for BitSet {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         write!(f, "[")?;
@@ -7,10 +7,9 @@ use futures::future::BoxFuture;
 use futures::stream::FuturesUnordered;
 use futures::task::{Context, Poll};
 use futures::{Future, FutureExt, StreamExt};
-use parking_lot::RwLock;
 
 use nimiq_block::Block;
-use nimiq_blockchain::{AbstractBlockchain, Blockchain};
+use nimiq_blockchain::{AbstractBlockchain, BlockchainLock};
 use nimiq_hash::Blake2bHash;
 use nimiq_network_interface::{peer::Peer, request_response::RequestError};
 
@@ -24,7 +23,7 @@ pub struct HeadRequests<TPeer: Peer + 'static> {
     head_blocks:
         FuturesUnordered<BoxFuture<'static, (Result<Option<Block>, RequestError>, TPeer::Id)>>,
     requested_hashes: HashSet<Blake2bHash>,
-    blockchain: Arc<RwLock<Blockchain>>,
+    blockchain: Arc<BlockchainLock>,
     num_known_blocks: usize,
     num_unknown_blocks: usize,
     unknown_blocks: Vec<(Block, TPeer::Id)>,
@@ -37,10 +36,7 @@ pub struct HeadRequestsResult<TPeer: Peer + 'static> {
 }
 
 impl<TPeer: Peer + 'static> HeadRequests<TPeer> {
-    pub fn new(
-        peers: Vec<Weak<ConsensusAgent<TPeer>>>,
-        blockchain: Arc<RwLock<Blockchain>>,
-    ) -> Self {
+    pub fn new(peers: Vec<Weak<ConsensusAgent<TPeer>>>, blockchain: Arc<BlockchainLock>) -> Self {
         let peers: Vec<_> = peers
             .into_iter()
             .filter_map(|peer| peer.upgrade())
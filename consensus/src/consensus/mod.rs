@@ -1,5 +1,4 @@
 use std::pin::Pin;
-use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
@@ -10,23 +9,35 @@ use tokio::sync::broadcast::{channel as broadcast, Sender as BroadcastSender};
 use tokio::time::Sleep;
 use tokio_stream::wrappers::BroadcastStream;
 
-use nimiq_blockchain::{AbstractBlockchain, Blockchain};
+use nimiq_blockchain::{AbstractBlockchain, BlockchainLock};
 use nimiq_database::Environment;
-use nimiq_mempool::mempool::TransactionTopic;
-use nimiq_network_interface::network::Network;
+use nimiq_mempool::{mempool::TransactionTopic, sharding::publish_to_shard};
+use nimiq_network_interface::network::{Network, NetworkEvent};
 use nimiq_transaction::Transaction;
+use nimiq_utils::memory::MemoryGauge;
 
 use crate::consensus::head_requests::{HeadRequests, HeadRequestsResult};
+use crate::consensus::state::{ConsensusInputs, ConsensusStateMachine};
 use crate::sync::block_queue::{BlockQueue, BlockQueueConfig, BlockQueueEvent};
+use crate::sync::block_relay::BlockRelay;
 use crate::sync::request_component::{BlockRequestComponent, HistorySyncStream};
 
 mod head_requests;
 mod request_response;
+mod serving_limits;
+mod state;
+
+pub use self::request_response::RequestServing;
+pub use self::state::{ConsensusState, EstablishedPolicy};
+pub use nimiq_mempool::production_window::ProductionWindow;
 
 pub struct ConsensusProxy<N: Network> {
-    pub blockchain: Arc<RwLock<Blockchain>>,
+    pub blockchain: Arc<BlockchainLock>,
     pub network: Arc<N>,
-    established_flag: Arc<AtomicBool>,
+    state: Arc<RwLock<ConsensusState>>,
+    events: BroadcastSender<ConsensusEvent>,
+    sharded_transactions: bool,
+    production_window: Arc<ProductionWindow>,
 }
 
 impl<N: Network> Clone for ConsensusProxy<N> {
@@ -34,18 +45,41 @@ impl<N: Network> Clone for ConsensusProxy<N> {
         Self {
             blockchain: Arc::clone(&self.blockchain),
             network: Arc::clone(&self.network),
-            established_flag: Arc::clone(&self.established_flag),
+            state: Arc::clone(&self.state),
+            events: self.events.clone(),
+            sharded_transactions: self.sharded_transactions,
+            production_window: Arc::clone(&self.production_window),
         }
     }
 }
 
 impl<N: Network> ConsensusProxy<N> {
+    /// Publishes the transaction to the network. If sharded transaction topics are enabled, the
+    /// transaction is published to the shard topic of its sender.
     pub async fn send_transaction(&self, tx: Transaction) -> Result<(), N::Error> {
-        self.network.publish::<TransactionTopic>(tx).await
+        if self.sharded_transactions {
+            publish_to_shard(&*self.network, tx).await
+        } else {
+            self.network.publish::<TransactionTopic>(tx).await
+        }
     }
 
     pub fn is_established(&self) -> bool {
-        self.established_flag.load(Ordering::Acquire)
+        self.state() == ConsensusState::Established
+    }
+
+    pub fn state(&self) -> ConsensusState {
+        *self.state.read()
+    }
+
+    pub fn subscribe_events(&self) -> BroadcastStream<ConsensusEvent> {
+        BroadcastStream::new(self.events.subscribe())
+    }
+
+    /// The window in which our validator produces a block. Expensive sync requests of other peers
+    /// are not served while it is open.
+    pub fn production_window(&self) -> &ProductionWindow {
+        &self.production_window
     }
 }
 
@@ -56,7 +90,7 @@ pub enum ConsensusEvent {
 }
 
 pub struct Consensus<N: Network> {
-    pub blockchain: Arc<RwLock<Blockchain>>,
+    pub blockchain: Arc<BlockchainLock>,
     pub network: Arc<N>,
     pub env: Environment,
 
@@ -67,20 +101,24 @@ pub struct Consensus<N: Network> {
     next_execution_timer: Option<Pin<Box<Sleep>>>,
 
     events: BroadcastSender<ConsensusEvent>,
-    established_flag: Arc<AtomicBool>,
+    state: ConsensusStateMachine,
     head_requests: Option<HeadRequests<N::PeerType>>,
     head_requests_time: Option<Instant>,
 
-    min_peers: usize,
-}
+    /// Network events, used to resync once the network reconnected.
+    network_events: BroadcastStream<NetworkEvent<N::PeerType>>,
 
-impl<N: Network> Consensus<N> {
-    /// Minimum number of peers for consensus to be established.
-    const MIN_PEERS_ESTABLISHED: usize = 3;
+    /// Whether proxies publish transactions to the sharded transaction topics.
+    sharded_transactions: bool,
 
-    /// Minimum number of block announcements extending the chain for consensus to be established.
-    const MIN_BLOCKS_ESTABLISHED: usize = 5;
+    /// The size of the responses to sync requests that are cached.
+    cache_memory: MemoryGauge,
 
+    /// Shared with the request handlers and the proxies.
+    production_window: Arc<ProductionWindow>,
+}
+
+impl<N: Network> Consensus<N> {
     /// Timeout after which head requests will be performed (again) to determine consensus
     /// established state and to advance the chain.
     const HEAD_REQUESTS_TIMEOUT: Duration = Duration::from_secs(5);
@@ -93,56 +131,113 @@ impl<N: Network> Consensus<N> {
 
     pub async fn from_network(
         env: Environment,
-        blockchain: Arc<RwLock<Blockchain>>,
+        blockchain: Arc<BlockchainLock>,
         network: Arc<N>,
         sync_protocol: Pin<Box<dyn HistorySyncStream<N::PeerType>>>,
     ) -> Self {
-        Self::with_min_peers(
+        Self::with_policy(
             env,
             blockchain,
             network,
             sync_protocol,
-            Self::MIN_PEERS_ESTABLISHED,
+            EstablishedPolicy::default(),
+            RequestServing::Full,
         )
         .await
     }
 
+    /// Creates a consensus that requires `min_peers` peers to be established. `serving` decides
+    /// which requests of other peers are answered.
     pub async fn with_min_peers(
         env: Environment,
-        blockchain: Arc<RwLock<Blockchain>>,
+        blockchain: Arc<BlockchainLock>,
         network: Arc<N>,
         sync_protocol: Pin<Box<dyn HistorySyncStream<N::PeerType>>>,
         min_peers: usize,
+        serving: RequestServing,
     ) -> Self {
-        let request_component =
-            BlockRequestComponent::new(sync_protocol, network.subscribe_events());
+        Self::with_policy(
+            env,
+            blockchain,
+            network,
+            sync_protocol,
+            EstablishedPolicy {
+                min_peers,
+                ..Default::default()
+            },
+            serving,
+        )
+        .await
+    }
 
-        let block_queue = BlockQueue::new(
+    /// Creates a consensus that is established according to the given `policy`. `serving` decides
+    /// which requests of other peers are answered.
+    pub async fn with_policy(
+        env: Environment,
+        blockchain: Arc<BlockchainLock>,
+        network: Arc<N>,
+        sync_protocol: Pin<Box<dyn HistorySyncStream<N::PeerType>>>,
+        policy: EstablishedPolicy,
+        serving: RequestServing,
+    ) -> Self {
+        Self::with_block_queue_config(
+            env,
+            blockchain,
+            network,
+            sync_protocol,
+            policy,
             BlockQueueConfig::default(),
+            serving,
+        )
+        .await
+    }
+
+    /// Like [`Consensus::with_policy`], but blocks received via gossip are queued according to
+    /// `block_queue_config`.
+    pub async fn with_block_queue_config(
+        env: Environment,
+        blockchain: Arc<BlockchainLock>,
+        network: Arc<N>,
+        sync_protocol: Pin<Box<dyn HistorySyncStream<N::PeerType>>>,
+        policy: EstablishedPolicy,
+        block_queue_config: BlockQueueConfig,
+        serving: RequestServing,
+    ) -> Self {
+        let request_component = BlockRequestComponent::with_num_pending_blocks(
+            sync_protocol,
+            network.subscribe_events(),
+            block_queue_config.concurrent_block_requests,
+        );
+
+        let block_queue = BlockQueue::new(
+            block_queue_config,
             Arc::clone(&blockchain),
             Arc::clone(&network),
             request_component,
         )
         .await;
 
-        Self::new(env, blockchain, network, block_queue, min_peers)
+        Self::new(env, blockchain, network, block_queue, policy, serving)
     }
 
     pub fn new(
         env: Environment,
-        blockchain: Arc<RwLock<Blockchain>>,
+        blockchain: Arc<BlockchainLock>,
         network: Arc<N>,
         block_queue: BlockQueue<N, BlockRequestComponent<N::PeerType>>,
-        min_peers: usize,
+        policy: EstablishedPolicy,
+        serving: RequestServing,
     ) -> Self {
         let (tx, _rx) = broadcast(256);
 
-        Self::init_network_requests(&network, &blockchain);
-
-        let established_flag = Arc::new(AtomicBool::new(false));
+        let production_window = Arc::new(ProductionWindow::default());
+        let cache_memory =
+            Self::init_network_requests(&network, &blockchain, serving, &production_window);
 
         let timer = Box::pin(tokio::time::sleep(Self::CONSENSUS_POLL_TIMER));
 
+        let network_events = network.subscribe_events();
+
         Consensus {
             blockchain,
             network,
@@ -150,11 +245,13 @@ impl<N: Network> Consensus<N> {
             block_queue,
             events: tx,
             next_execution_timer: Some(timer),
-            established_flag,
+            state: ConsensusStateMachine::new(policy),
             head_requests: None,
             head_requests_time: None,
-
-            min_peers,
+            network_events,
+            sharded_transactions: false,
+            cache_memory,
+            production_window,
         }
     }
 
@@ -163,37 +260,70 @@ impl<N: Network> Consensus<N> {
     }
 
     pub fn is_established(&self) -> bool {
-        self.established_flag.load(Ordering::Acquire)
+        self.state() == ConsensusState::Established
+    }
+
+    pub fn state(&self) -> ConsensusState {
+        self.state.state()
     }
 
     pub fn num_agents(&self) -> usize {
         self.block_queue.num_peers()
     }
 
+    /// Returns the gauge counting the size of the cached responses to sync requests. If its cap is
+    /// reached, the least recently used responses are evicted.
+    pub fn cache_memory(&self) -> MemoryGauge {
+        self.cache_memory.clone()
+    }
+
+    /// Returns the gauge counting the size of the blocks that are buffered until their parent is
+    /// known.
+    pub fn block_buffer_memory(&self) -> MemoryGauge {
+        self.block_queue.memory()
+    }
+
     pub fn proxy(&self) -> ConsensusProxy<N> {
         ConsensusProxy {
             blockchain: Arc::clone(&self.blockchain),
             network: Arc::clone(&self.network),
-            established_flag: Arc::clone(&self.established_flag),
+            state: self.state.shared_state(),
+            events: self.events.clone(),
+            sharded_transactions: self.sharded_transactions,
+            production_window: Arc::clone(&self.production_window),
         }
     }
 
+    /// Sets an external block relay that blocks are exchanged with besides gossipsub. See
+    /// [`BlockRelay`].
+    pub fn set_block_relay(&mut self, relay: Arc<dyn BlockRelay>) {
+        self.block_queue.set_block_relay(relay);
+    }
+
+    /// Sets whether transactions sent via proxies are published to the sharded transaction topics.
+    /// This only affects proxies created afterwards.
+    pub fn set_sharded_transactions(&mut self, sharded_transactions: bool) {
+        self.sharded_transactions = sharded_transactions;
+    }
+
     /// Forcefully sets consensus established, should be used for tests only.
     pub fn force_established(&mut self) {
         trace!("Consensus forcefully established.");
-        self.established_flag.swap(true, Ordering::Release);
+        let event = self.state.force_established();
 
         // Also stop any other checks.
         self.head_requests = None;
         self.head_requests_time = None;
-        self.events.send(ConsensusEvent::Established).ok();
+        if let Some(event) = event {
+            self.events.send(event).ok();
+        }
     }
 
-    /// Calculates and sets established state, returns a ConsensusEvent if the state changed.
-    /// Once consensus is established, we can only loose it if we loose all our peers.
-    /// To reach consensus established state, we need at least `minPeers` peers and
-    /// one of the following conditions must be true:
-    /// - we accepted at least `MIN_BLOCKS_ESTABLISHED` block announcements
+    /// Updates the consensus state, returns a ConsensusEvent if consensus was established or lost.
+    /// The decision is made by the state machine according to the [`EstablishedPolicy`]:
+    /// To reach consensus established state, we need at least `min_peers` peers, our head must
+    /// not be older than `max_head_age` (if set) and one of the following conditions must be true:
+    /// - we accepted at least `min_block_announcements` block announcements
     /// - we know at least 2/3 of the head blocks of our peers
     ///
     /// The latter check is started immediately once we reach the minimum number of peers
@@ -205,54 +335,55 @@ impl<N: Network> Consensus<N> {
         &mut self,
         finished_head_request: Option<HeadRequestsResult<N::PeerType>>,
     ) -> Option<ConsensusEvent> {
-        // We can only lose established state right now if we drop below our minimum peer threshold.
-        if self.is_established() {
-            if self.num_agents() < self.min_peers {
-                warn!("Lost consensus!");
-                self.established_flag.swap(false, Ordering::Release);
-                return Some(ConsensusEvent::Lost);
+        let heads_known = match finished_head_request {
+            Some(head_request) => {
+                debug!(
+                    "Trying to establish consensus, checking head request ({} known, {} unknown).",
+                    head_request.num_known_blocks, head_request.num_unknown_blocks
+                );
+                // We would like that 2/3 of our peers have a known state.
+                head_request.num_known_blocks >= 2 * head_request.num_unknown_blocks
             }
-        } else {
-            // We have two conditions on whether we move to the established state.
-            // First, we always need a minimum number of peers connected.
-            // Then, we check that we either:
-            // - accepted a minimum number of block announcements, or
-            // - know the head state of a majority of our peers
-            if self.num_agents() >= self.min_peers {
-                if self.block_queue.accepted_block_announcements() >= Self::MIN_BLOCKS_ESTABLISHED {
-                    info!("Consensus established, number of accepted announcements satisfied.");
-                    self.established_flag.swap(true, Ordering::Release);
-
-                    // Also stop any other checks.
-                    self.head_requests = None;
-                    self.head_requests_time = None;
-                    return Some(ConsensusEvent::Established);
-                } else {
-                    // The head state check is carried out immediately after we reach the minimum
-                    // number of peers and then after certain time intervals until consensus is reached.
-                    // If we have a finished one, check its outcome.
-                    if let Some(head_request) = finished_head_request {
-                        debug!("Trying to establish consensus, checking head request ({} known, {} unknown).", head_request.num_known_blocks, head_request.num_unknown_blocks);
-                        // We would like that 2/3 of our peers have a known state.
-                        if head_request.num_known_blocks >= 2 * head_request.num_unknown_blocks {
-                            info!("Consensus established, 2/3 of heads known.");
-                            self.established_flag.swap(true, Ordering::Release);
-                            return Some(ConsensusEvent::Established);
-                        }
-                    }
-                    // If there's no ongoing head request, check whether we should start a new one.
-                    self.request_heads();
-                }
+            None => false,
+        };
+
+        let inputs = ConsensusInputs {
+            num_peers: self.num_agents(),
+            accepted_block_announcements: self.block_queue.accepted_block_announcements(),
+            heads_known,
+            head_age: self.head_age(),
+        };
+
+        let event = self.state.update(&inputs);
+        match event {
+            Some(ConsensusEvent::Established) => {
+                // Stop any other checks.
+                self.head_requests = None;
+                self.head_requests_time = None;
+            }
+            _ if self.state() == ConsensusState::Syncing => {
+                // If there's no ongoing head request, check whether we should start a new one.
+                self.request_heads();
             }
+            _ => {}
         }
-        None
+        event
+    }
+
+    /// Returns the age of our head block relative to the network time.
+    fn head_age(&self) -> Duration {
+        let blockchain = self.blockchain.read();
+        let now = blockchain.time.now();
+        Duration::from_millis(now.saturating_sub(blockchain.head().timestamp()))
     }
 
     /// Requests heads from connected peers in a predefined interval.
     fn request_heads(&mut self) {
         // If there's no ongoing head request and we have at least one peer, check whether we should
         // start a new one.
-        if self.head_requests.is_none() && (self.num_agents() > 0 || self.min_peers == 0) {
+        if self.head_requests.is_none()
+            && (self.num_agents() > 0 || self.state.policy().min_peers == 0)
+        {
             // This is the case if `head_requests_time` is unset or the timeout is hit.
             let should_start_request = self
                 .head_requests_time
@@ -274,6 +405,15 @@ impl<N: Network> Future for Consensus<N> {
     type Output = ();
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // 0. Resync once the network regained connectivity by immediately requesting heads again.
+        while let Poll::Ready(Some(event)) = self.network_events.poll_next_unpin(cx) {
+            if let Ok(NetworkEvent::Reconnected) = event {
+                info!("Network reconnected, resyncing");
+                self.head_requests = None;
+                self.head_requests_time = None;
+            }
+        }
+
         // 1. Poll and advance block queue
         while let Poll::Ready(Some(event)) = self.block_queue.poll_next_unpin(cx) {
             match event {
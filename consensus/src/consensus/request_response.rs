@@ -1,53 +1,222 @@
 use std::future::Future;
 use std::sync::Arc;
+use std::time::Instant;
 
 use futures::stream::BoxStream;
 use futures::StreamExt;
-use parking_lot::RwLock;
+use tracing::{Instrument, Span};
 
-use nimiq_blockchain::Blockchain;
-use nimiq_network_interface::prelude::{Message, Network, Peer, ResponseMessage};
+use nimiq_blockchain::BlockchainLock;
+use nimiq_mempool::production_window::ProductionWindow;
+use nimiq_network_interface::prelude::{Message, Network, Peer, ResponseMessage, TraceId};
+use nimiq_utils::memory::MemoryGauge;
 
-use crate::messages::handlers::Handle;
+use crate::consensus::serving_limits::ServingLimiter;
+use crate::messages::cache::ResponseCache;
+use crate::messages::handlers::{Handle, HandleBusy};
 use crate::messages::{
     RequestBatchSet, RequestBlock, RequestBlockHashes, RequestHead, RequestHistoryChunk,
     RequestMissingBlocks,
 };
 use crate::Consensus;
 
+/// Which requests of other peers a node answers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RequestServing {
+    /// All requests, including the ones for batch sets and history chunks.
+    Full,
+    /// All requests except the ones for batch sets and history chunks, which need the full
+    /// history.
+    WithoutHistory,
+    /// Only the cheap requests for the head and single blocks. Used by seed nodes, which don't
+    /// help peers to sync.
+    HeadOnly,
+}
+
+impl RequestServing {
+    pub fn serves_history(&self) -> bool {
+        matches!(self, Self::Full)
+    }
+
+    /// Whether requests for block hashes and missing blocks are answered.
+    pub fn serves_sync(&self) -> bool {
+        matches!(self, Self::Full | Self::WithoutHistory)
+    }
+}
+
 impl<N: Network> Consensus<N> {
     const MAX_CONCURRENT_HANDLERS: usize = 64;
 
-    pub(super) fn init_network_requests(network: &Arc<N>, blockchain: &Arc<RwLock<Blockchain>>) {
-        let stream = network.receive_from_all::<RequestBlockHashes>();
-        tokio::spawn(Self::request_handler(stream, blockchain));
+    /// Spawns the handlers of the requests of other peers. Returns the memory gauge of the cache
+    /// of their responses.
+    pub(super) fn init_network_requests(
+        network: &Arc<N>,
+        blockchain: &Arc<BlockchainLock>,
+        serving: RequestServing,
+        production_window: &Arc<ProductionWindow>,
+    ) -> MemoryGauge {
+        // Responses to sync requests are shared between all peers.
+        let cache = Arc::new(ResponseCache::default());
+        let memory = cache.memory();
 
-        let stream = network.receive_from_all::<RequestBatchSet>();
-        tokio::spawn(Self::request_handler(stream, blockchain));
+        // Expensive requests share the same serving limits.
+        let limiter = Arc::new(ServingLimiter::default());
 
-        let stream = network.receive_from_all::<RequestHistoryChunk>();
-        tokio::spawn(Self::request_handler(stream, blockchain));
+        if serving.serves_sync() {
+            let stream = network.receive_from_all::<RequestBlockHashes>();
+            tokio::spawn(Self::request_handler(stream, blockchain, &cache));
 
-        let stream = network.receive_from_all::<RequestBlock>();
-        tokio::spawn(Self::request_handler(stream, blockchain));
+            let stream = network.receive_from_all::<RequestMissingBlocks>();
+            tokio::spawn(Self::request_handler(stream, blockchain, &cache));
+        }
 
-        let stream = network.receive_from_all::<RequestMissingBlocks>();
-        tokio::spawn(Self::request_handler(stream, blockchain));
+        // Nodes that don't keep the full history don't serve it either.
+        if serving.serves_history() {
+            let stream = network.receive_from_all::<RequestBatchSet>();
+            tokio::spawn(Self::limited_request_handler(
+                stream,
+                blockchain,
+                &cache,
+                &limiter,
+                production_window,
+            ));
+
+            let stream = network.receive_from_all::<RequestHistoryChunk>();
+            tokio::spawn(Self::limited_request_handler(
+                stream,
+                blockchain,
+                &cache,
+                &limiter,
+                production_window,
+            ));
+        }
+
+        let stream = network.receive_from_all::<RequestBlock>();
+        tokio::spawn(Self::request_handler(stream, blockchain, &cache));
 
         let stream = network.receive_from_all::<RequestHead>();
-        tokio::spawn(Self::request_handler(stream, blockchain));
+        tokio::spawn(Self::request_handler(stream, blockchain, &cache));
+
+        memory
+    }
+
+    /// Returns the trace ID of the request and the span to handle it in, if the request is traced.
+    /// Whether a request is traced is up to the requesting node.
+    fn trace_request<Req: ResponseMessage>(
+        msg: &Req,
+        peer: &N::PeerType,
+    ) -> (Option<TraceId>, Span) {
+        match msg.get_trace_id() {
+            Some(trace_id) => {
+                let span = tracing::info_span!(
+                    "handle_request",
+                    trace_id = %trace_id,
+                    request = std::any::type_name::<Req>(),
+                    peer = ?peer.id(),
+                );
+                (Some(trace_id), span)
+            }
+            None => (None, Span::none()),
+        }
+    }
+
+    fn log_traced_request<Req: ResponseMessage>(
+        trace_id: Option<TraceId>,
+        msg: &Req,
+        peer: &N::PeerType,
+        started: Instant,
+    ) {
+        if let Some(trace_id) = trace_id {
+            debug!(
+                "Handled traced {} [{} {}] from {:?} in {:?}",
+                std::any::type_name::<Req>(),
+                msg.get_request_identifier(),
+                trace_id,
+                peer.id(),
+                started.elapsed()
+            );
+        }
     }
 
     fn request_handler<Req: Handle<Res> + ResponseMessage, Res: Message>(
         stream: BoxStream<'static, (Req, Arc<N::PeerType>)>,
-        blockchain: &Arc<RwLock<Blockchain>>,
+        blockchain: &Arc<BlockchainLock>,
+        cache: &Arc<ResponseCache<<N::PeerType as Peer>::Id>>,
+    ) -> impl Future<Output = ()> {
+        let blockchain = Arc::clone(blockchain);
+        let cache = Arc::clone(cache);
+        async move {
+            stream
+                .for_each_concurrent(Self::MAX_CONCURRENT_HANDLERS, |(msg, peer)| async {
+                    let blockchain = Arc::clone(&blockchain);
+                    let cache = Arc::clone(&cache);
+                    let (trace_id, span) = Self::trace_request(&msg, &peer);
+                    let handling = async move {
+                        trace!(
+                            "[{}] {:?} {:#?}",
+                            msg.get_request_identifier(),
+                            peer.id(),
+                            msg
+                        );
+
+                        // Retried requests are answered with the response that was sent before.
+                        let started = Instant::now();
+                        let request_identifier = msg.get_request_identifier();
+                        let response = match cache.get_served(peer.id(), request_identifier) {
+                            Some(response) => response,
+                            None => {
+                                let response = msg.handle(&blockchain, &cache);
+                                cache.put_served(peer.id(), request_identifier, &response);
+                                response
+                            }
+                        };
+                        Self::log_traced_request(trace_id, &msg, &peer, started);
+
+                        // Try to send the response, logging to debug if it fails
+                        if let Err(err) = peer.send(response).await {
+                            log::debug!(
+                                "[{}] Failed to send {} response: {:?}",
+                                msg.get_request_identifier(),
+                                std::any::type_name::<Req>(),
+                                err
+                            );
+                        };
+                    };
+                    tokio::spawn(handling.instrument(span))
+                        .await
+                        .expect("Request handler panicked")
+                })
+                .await
+        }
+    }
+
+    /// Like `request_handler`, but answers requests with a busy response if the serving limits
+    /// are exceeded or while our validator is producing a block.
+    fn limited_request_handler<Req: HandleBusy<Res> + ResponseMessage, Res: Message>(
+        stream: BoxStream<'static, (Req, Arc<N::PeerType>)>,
+        blockchain: &Arc<BlockchainLock>,
+        cache: &Arc<ResponseCache<<N::PeerType as Peer>::Id>>,
+        limiter: &Arc<ServingLimiter<<N::PeerType as Peer>::Id>>,
+        production_window: &Arc<ProductionWindow>,
     ) -> impl Future<Output = ()> {
         let blockchain = Arc::clone(blockchain);
+        let cache = Arc::clone(cache);
+        let limiter = Arc::clone(limiter);
+        let production_window = Arc::clone(production_window);
         async move {
             stream
                 .for_each_concurrent(Self::MAX_CONCURRENT_HANDLERS, |(msg, peer)| async {
                     let blockchain = Arc::clone(&blockchain);
-                    tokio::spawn(async move {
+                    let cache = Arc::clone(&cache);
+                    // Block production takes precedence over serving other peers' syncs.
+                    let producing = production_window.is_open();
+                    let permit = if producing {
+                        None
+                    } else {
+                        limiter.try_acquire(&peer.id())
+                    };
+                    let (trace_id, span) = Self::trace_request(&msg, &peer);
+                    let handling = async move {
                         trace!(
                             "[{}] {:?} {:#?}",
                             msg.get_request_identifier(),
@@ -55,8 +224,41 @@ impl<N: Network> Consensus<N> {
                             msg
                         );
 
+                        // Retried requests are answered with the response that was sent before,
+                        // busy responses aren't remembered.
+                        let started = Instant::now();
+                        let request_identifier = msg.get_request_identifier();
+                        let response = if let Some(response) =
+                            cache.get_served(peer.id(), request_identifier)
+                        {
+                            response
+                        } else if permit.is_some() {
+                            let response = msg.handle(&blockchain, &cache);
+                            cache.put_served(peer.id(), request_identifier, &response);
+                            response
+                        } else if producing {
+                            debug!(
+                                "[{}] Producing a block, peer {:?} should retry its {} request later",
+                                msg.get_request_identifier(),
+                                peer.id(),
+                                std::any::type_name::<Req>()
+                            );
+                            msg.busy()
+                        } else {
+                            debug!(
+                                "[{}] Too many concurrent {} requests, peer {:?} should retry later",
+                                msg.get_request_identifier(),
+                                std::any::type_name::<Req>(),
+                                peer.id()
+                            );
+                            msg.busy()
+                        };
+                        // Release the capacity before sending the response.
+                        drop(permit);
+                        Self::log_traced_request(trace_id, &msg, &peer, started);
+
                         // Try to send the response, logging to debug if it fails
-                        if let Err(err) = peer.send(msg.handle(&blockchain)).await {
+                        if let Err(err) = peer.send(response).await {
                             log::debug!(
                                 "[{}] Failed to send {} response: {:?}",
                                 msg.get_request_identifier(),
@@ -64,9 +266,10 @@ impl<N: Network> Consensus<N> {
                                 err
                             );
                         };
-                    })
-                    .await
-                    .expect("Request handler panicked")
+                    };
+                    tokio::spawn(handling.instrument(span))
+                        .await
+                        .expect("Request handler panicked")
                 })
                 .await
         }
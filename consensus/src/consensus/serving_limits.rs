@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+struct LimiterState<TPeerId> {
+    in_flight: HashMap<TPeerId, usize>,
+    total: usize,
+}
+
+/// Limits the number of expensive sync requests (batch sets, history chunks) that are served
+/// concurrently, both globally and per peer.
+///
+/// Once more than half of the global capacity is in use, a peer is only admitted while it is below
+/// its fair share of the capacity, so a single syncing peer cannot monopolize the node's disk and
+/// CPU. Rejected requests are answered with a busy response.
+pub(crate) struct ServingLimiter<TPeerId> {
+    state: Arc<Mutex<LimiterState<TPeerId>>>,
+}
+
+impl<TPeerId: Clone + Hash + Eq> ServingLimiter<TPeerId> {
+    /// Maximum number of expensive requests served concurrently.
+    const MAX_IN_FLIGHT: usize = 32;
+
+    /// Maximum number of expensive requests served concurrently for a single peer.
+    const MAX_IN_FLIGHT_PER_PEER: usize = 4;
+
+    /// Tries to reserve capacity to serve a request from the given peer. The capacity is released
+    /// once the returned permit is dropped.
+    pub fn try_acquire(&self, peer_id: &TPeerId) -> Option<ServingPermit<TPeerId>> {
+        let mut state = self.state.lock();
+
+        let peer_in_flight = state.in_flight.get(peer_id).copied().unwrap_or(0);
+        if peer_in_flight >= Self::MAX_IN_FLIGHT_PER_PEER || state.total >= Self::MAX_IN_FLIGHT {
+            return None;
+        }
+
+        if state.total >= Self::MAX_IN_FLIGHT / 2 {
+            let num_peers = state.in_flight.len() + usize::from(peer_in_flight == 0);
+            let fair_share = (Self::MAX_IN_FLIGHT / num_peers).max(1);
+            if peer_in_flight >= fair_share {
+                return None;
+            }
+        }
+
+        *state.in_flight.entry(peer_id.clone()).or_default() += 1;
+        state.total += 1;
+
+        Some(ServingPermit {
+            state: Arc::clone(&self.state),
+            peer_id: peer_id.clone(),
+        })
+    }
+}
+
+impl<TPeerId> Default for ServingLimiter<TPeerId> {
+    fn default() -> Self {
+        ServingLimiter {
+            state: Arc::new(Mutex::new(LimiterState {
+                in_flight: HashMap::new(),
+                total: 0,
+            })),
+        }
+    }
+}
+
+/// Capacity reserved for serving a single request.
+pub(crate) struct ServingPermit<TPeerId: Hash + Eq> {
+    state: Arc<Mutex<LimiterState<TPeerId>>>,
+    peer_id: TPeerId,
+}
+
+impl<TPeerId: Hash + Eq> Drop for ServingPermit<TPeerId> {
+    fn drop(&mut self) {
+        let mut state = self.state.lock();
+        state.total -= 1;
+        if let Some(in_flight) = state.in_flight.get_mut(&self.peer_id) {
+            *in_flight -= 1;
+            if *in_flight == 0 {
+                state.in_flight.remove(&self.peer_id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Limiter = ServingLimiter<u32>;
+
+    #[test]
+    fn it_limits_requests_per_peer() {
+        let limiter = Limiter::default();
+
+        let mut permits: Vec<_> = (0..Limiter::MAX_IN_FLIGHT_PER_PEER)
+            .map(|_| limiter.try_acquire(&1).unwrap())
+            .collect();
+        assert!(limiter.try_acquire(&1).is_none());
+
+        // Other peers are still served.
+        assert!(limiter.try_acquire(&2).is_some());
+
+        // Capacity is released once a request was served.
+        permits.pop();
+        assert!(limiter.try_acquire(&1).is_some());
+    }
+
+    #[test]
+    fn it_limits_requests_globally() {
+        let limiter = Limiter::default();
+
+        let num_peers = (Limiter::MAX_IN_FLIGHT / Limiter::MAX_IN_FLIGHT_PER_PEER) as u32;
+        let _permits: Vec<_> = (0..num_peers)
+            .flat_map(|peer_id| (0..Limiter::MAX_IN_FLIGHT_PER_PEER).map(move |_| peer_id))
+            .map(|peer_id| limiter.try_acquire(&peer_id).unwrap())
+            .collect();
+
+        assert!(limiter.try_acquire(&num_peers).is_none());
+    }
+
+    #[test]
+    fn it_limits_peers_to_their_fair_share_under_load() {
+        let limiter = Limiter::default();
+
+        // More than half of the capacity is used by many peers with one request each.
+        let num_peers = (Limiter::MAX_IN_FLIGHT / 2 + 1) as u32;
+        let _permits: Vec<_> = (0..num_peers)
+            .map(|peer_id| limiter.try_acquire(&peer_id).unwrap())
+            .collect();
+
+        // A peer that already got its share has to wait, a new peer is still served.
+        assert!(limiter.try_acquire(&0).is_none());
+        assert!(limiter.try_acquire(&num_peers).is_some());
+    }
+
+    #[test]
+    fn it_releases_all_capacity() {
+        let limiter = Limiter::default();
+
+        let permits: Vec<_> = (0..3)
+            .map(|peer_id| limiter.try_acquire(&peer_id).unwrap())
+            .collect();
+        drop(permits);
+
+        let state = limiter.state.lock();
+        assert_eq!(state.total, 0);
+        assert!(state.in_flight.is_empty());
+    }
+}
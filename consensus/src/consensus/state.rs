@@ -0,0 +1,218 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use parking_lot::RwLock;
+
+use crate::consensus::ConsensusEvent;
+
+/// The consensus state of a node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConsensusState {
+    /// Less than the minimum number of peers are connected.
+    WaitingForPeers,
+    /// Enough peers are connected, but we didn't catch up with them yet.
+    Syncing,
+    /// We are in sync with the network.
+    Established,
+}
+
+/// Determines when consensus is established and when it is lost.
+#[derive(Clone, Debug)]
+pub struct EstablishedPolicy {
+    /// Minimum number of peers for consensus to be established. Consensus is lost once the number
+    /// of peers drops below it.
+    pub min_peers: usize,
+
+    /// Number of accepted block announcements after which we consider ourselves in sync.
+    /// Alternatively, we are in sync once we know at least 2/3 of the head blocks of our peers.
+    pub min_block_announcements: usize,
+
+    /// If set, consensus is only established once our head block is at most this old, relative to
+    /// the network time.
+    pub max_head_age: Option<Duration>,
+
+    /// If set, consensus is lost once our head block is older than this, relative to the network
+    /// time. If the chain stalls for this long, all nodes lose consensus. Validators then stop
+    /// producing blocks, so this shouldn't be combined with `max_head_age` on validators.
+    pub lost_head_age: Option<Duration>,
+}
+
+impl Default for EstablishedPolicy {
+    fn default() -> Self {
+        Self {
+            min_peers: 3,
+            min_block_announcements: 5,
+            max_head_age: None,
+            lost_head_age: None,
+        }
+    }
+}
+
+/// The observations the consensus state is derived from.
+#[derive(Clone, Debug)]
+pub(crate) struct ConsensusInputs {
+    /// Number of connected peers.
+    pub num_peers: usize,
+    /// Number of block announcements accepted so far.
+    pub accepted_block_announcements: usize,
+    /// Whether a finished head request found that we know at least 2/3 of our peers' heads.
+    pub heads_known: bool,
+    /// Age of our head block relative to the network time.
+    pub head_age: Duration,
+}
+
+/// Tracks the consensus state. The current state is shared with all consensus proxies.
+pub(crate) struct ConsensusStateMachine {
+    policy: EstablishedPolicy,
+    state: Arc<RwLock<ConsensusState>>,
+}
+
+impl ConsensusStateMachine {
+    pub fn new(policy: EstablishedPolicy) -> Self {
+        Self {
+            policy,
+            state: Arc::new(RwLock::new(ConsensusState::WaitingForPeers)),
+        }
+    }
+
+    pub fn policy(&self) -> &EstablishedPolicy {
+        &self.policy
+    }
+
+    pub fn state(&self) -> ConsensusState {
+        *self.state.read()
+    }
+
+    pub fn shared_state(&self) -> Arc<RwLock<ConsensusState>> {
+        Arc::clone(&self.state)
+    }
+
+    /// Sets the state to established. Returns an event if the state changed.
+    pub fn force_established(&mut self) -> Option<ConsensusEvent> {
+        self.transition(ConsensusState::Established)
+    }
+
+    /// Updates the state from the given observations. Returns an event if consensus was
+    /// established or lost.
+    pub fn update(&mut self, inputs: &ConsensusInputs) -> Option<ConsensusEvent> {
+        let has_peers = inputs.num_peers >= self.policy.min_peers;
+
+        if self.state() == ConsensusState::Established {
+            if !has_peers {
+                warn!("Lost consensus, only {} peers connected", inputs.num_peers);
+                return self.transition(ConsensusState::WaitingForPeers);
+            }
+
+            if let Some(lost_head_age) = self.policy.lost_head_age {
+                if inputs.head_age > lost_head_age {
+                    warn!(
+                        "Lost consensus, head block is {}s old",
+                        inputs.head_age.as_secs()
+                    );
+                    return self.transition(ConsensusState::Syncing);
+                }
+            }
+
+            return None;
+        }
+
+        if !has_peers {
+            return self.transition(ConsensusState::WaitingForPeers);
+        }
+
+        let head_fresh = self
+            .policy
+            .max_head_age
+            .map_or(true, |max_head_age| inputs.head_age <= max_head_age);
+
+        if head_fresh && inputs.accepted_block_announcements >= self.policy.min_block_announcements
+        {
+            info!("Consensus established, number of accepted announcements satisfied.");
+            self.transition(ConsensusState::Established)
+        } else if head_fresh && inputs.heads_known {
+            info!("Consensus established, 2/3 of heads known.");
+            self.transition(ConsensusState::Established)
+        } else {
+            self.transition(ConsensusState::Syncing)
+        }
+    }
+
+    fn transition(&mut self, new_state: ConsensusState) -> Option<ConsensusEvent> {
+        let mut state = self.state.write();
+        let old_state = std::mem::replace(&mut *state, new_state);
+        if old_state == new_state {
+            return None;
+        }
+
+        debug!(
+            "Consensus state changed: {:?} -> {:?}",
+            old_state, new_state
+        );
+        match new_state {
+            ConsensusState::Established => Some(ConsensusEvent::Established),
+            _ if old_state == ConsensusState::Established => Some(ConsensusEvent::Lost),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inputs(num_peers: usize, heads_known: bool, head_age: u64) -> ConsensusInputs {
+        ConsensusInputs {
+            num_peers,
+            accepted_block_announcements: 0,
+            heads_known,
+            head_age: Duration::from_secs(head_age),
+        }
+    }
+
+    #[test]
+    fn it_establishes_and_loses_consensus_with_peers() {
+        let mut state = ConsensusStateMachine::new(EstablishedPolicy::default());
+
+        assert!(state.update(&inputs(2, true, 0)).is_none());
+        assert_eq!(state.state(), ConsensusState::WaitingForPeers);
+
+        assert!(state.update(&inputs(3, false, 0)).is_none());
+        assert_eq!(state.state(), ConsensusState::Syncing);
+
+        assert!(matches!(
+            state.update(&inputs(3, true, 0)),
+            Some(ConsensusEvent::Established)
+        ));
+        assert!(state.update(&inputs(3, false, 0)).is_none());
+
+        assert!(matches!(
+            state.update(&inputs(2, false, 0)),
+            Some(ConsensusEvent::Lost)
+        ));
+        assert_eq!(state.state(), ConsensusState::WaitingForPeers);
+    }
+
+    #[test]
+    fn it_respects_the_head_age() {
+        let mut state = ConsensusStateMachine::new(EstablishedPolicy {
+            max_head_age: Some(Duration::from_secs(60)),
+            lost_head_age: Some(Duration::from_secs(600)),
+            ..Default::default()
+        });
+
+        assert!(state.update(&inputs(3, true, 61)).is_none());
+        assert_eq!(state.state(), ConsensusState::Syncing);
+
+        assert!(matches!(
+            state.update(&inputs(3, true, 60)),
+            Some(ConsensusEvent::Established)
+        ));
+        assert!(state.update(&inputs(3, false, 600)).is_none());
+
+        assert!(matches!(
+            state.update(&inputs(3, false, 601)),
+            Some(ConsensusEvent::Lost)
+        ));
+        assert_eq!(state.state(), ConsensusState::Syncing);
+    }
+}
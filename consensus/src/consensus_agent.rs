@@ -1,8 +1,8 @@
 use std::fmt::{Debug, Formatter, Result as FmtResult};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 
 use nimiq_block::Block;
 use nimiq_hash::Blake2bHash;
@@ -17,6 +17,25 @@ pub struct ConsensusAgentState {
     remote_subscription: Subscription,
 }
 
+/// Tracks how fast a peer answers sync requests.
+#[derive(Default)]
+struct RequestLatency {
+    /// Exponentially weighted moving average of the round-trip times.
+    average: Option<Duration>,
+}
+
+impl RequestLatency {
+    /// The weight of a new round-trip time in the moving average.
+    const WEIGHT: f64 = 0.2;
+
+    fn record(&mut self, rtt: Duration) {
+        self.average = Some(match self.average {
+            Some(average) => average.mul_f64(1.0 - Self::WEIGHT) + rtt.mul_f64(Self::WEIGHT),
+            None => rtt,
+        });
+    }
+}
+
 #[derive(Ord, PartialOrd, PartialEq, Eq, Hash, Clone, Copy, Debug)]
 enum ConsensusAgentTimer {
     Mempool,
@@ -35,6 +54,8 @@ pub struct ConsensusAgent<P: Peer> {
     block_requests: RequestResponse<P, RequestBlock, ResponseBlock>,
     missing_block_requests: RequestResponse<P, RequestMissingBlocks, ResponseBlocks>,
     head_requests: RequestResponse<P, RequestHead, HeadResponse>,
+
+    latency: Mutex<RequestLatency>,
 }
 
 impl<P: Peer> Debug for ConsensusAgent<P> {
@@ -44,15 +65,40 @@ impl<P: Peer> Debug for ConsensusAgent<P> {
 }
 
 impl<P: Peer> ConsensusAgent<P> {
+    /// Number of times a request is retried if the peer responds that it is busy.
+    const MAX_BUSY_RETRIES: u32 = 3;
+
+    /// Delay before retrying a request the peer was too busy to serve. The delay grows linearly
+    /// with the number of retries.
+    const BUSY_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+    /// Number of times an expensive sync request is sent again if it times out. The peer answers
+    /// a retry from the responses it recently sent, so a response that got lost is cheap to get.
+    const MAX_TIMEOUT_RETRIES: usize = 1;
+
     pub fn new(peer: Arc<P>) -> Self {
+        Self::with_tracing(peer, false)
+    }
+
+    /// Creates an agent whose requests carry trace IDs if `trace_requests` is set, see
+    /// [`RequestResponse::with_tracing`].
+    pub fn with_tracing(peer: Arc<P>, trace_requests: bool) -> Self {
         // TODO: Timeout
         let timeout = Duration::from_secs(10);
-        let block_hashes_requests = RequestResponse::new(Arc::clone(&peer), timeout);
-        let epoch_requests = RequestResponse::new(Arc::clone(&peer), timeout);
-        let history_chunk_requests = RequestResponse::new(Arc::clone(&peer), timeout);
-        let block_requests = RequestResponse::new(Arc::clone(&peer), timeout);
-        let missing_block_requests = RequestResponse::new(Arc::clone(&peer), timeout);
-        let head_requests = RequestResponse::new(Arc::clone(&peer), timeout);
+        let block_hashes_requests =
+            RequestResponse::new(Arc::clone(&peer), timeout).with_tracing(trace_requests);
+        let epoch_requests = RequestResponse::new(Arc::clone(&peer), timeout)
+            .with_max_retries(Self::MAX_TIMEOUT_RETRIES)
+            .with_tracing(trace_requests);
+        let history_chunk_requests = RequestResponse::new(Arc::clone(&peer), timeout)
+            .with_max_retries(Self::MAX_TIMEOUT_RETRIES)
+            .with_tracing(trace_requests);
+        let block_requests =
+            RequestResponse::new(Arc::clone(&peer), timeout).with_tracing(trace_requests);
+        let missing_block_requests =
+            RequestResponse::new(Arc::clone(&peer), timeout).with_tracing(trace_requests);
+        let head_requests =
+            RequestResponse::new(Arc::clone(&peer), timeout).with_tracing(trace_requests);
 
         ConsensusAgent {
             peer,
@@ -66,6 +112,24 @@ impl<P: Peer> ConsensusAgent<P> {
             block_requests,
             missing_block_requests,
             head_requests,
+            latency: Mutex::new(RequestLatency::default()),
+        }
+    }
+
+    /// The average time the peer took to answer sync requests, or `None` if it wasn't asked yet.
+    /// Requests that timed out count with the time we waited for them.
+    pub fn latency(&self) -> Option<Duration> {
+        self.latency.lock().average
+    }
+
+    pub(crate) fn record_latency(&self, rtt: Duration) {
+        self.latency.lock().record(rtt);
+    }
+
+    /// Records the round-trip time of a request, unless it failed because the peer disconnected.
+    fn record_request<T>(&self, start: Instant, result: &Result<T, RequestError>) {
+        if matches!(result, Ok(_) | Err(RequestError::Timeout)) {
+            self.record_latency(start.elapsed());
         }
     }
 
@@ -75,6 +139,7 @@ impl<P: Peer> ConsensusAgent<P> {
             .request(RequestBlock {
                 hash,
                 request_identifier: 0, // will automatically be set at a later point
+                trace_id: Default::default(),
             })
             .await;
 
@@ -82,17 +147,35 @@ impl<P: Peer> ConsensusAgent<P> {
     }
 
     pub async fn request_epoch(&self, hash: Blake2bHash) -> Result<BatchSetInfo, RequestError> {
-        let result = self
-            .epoch_requests
-            .request(RequestBatchSet {
-                hash,
-                request_identifier: 0, // will automatically be set at a later point
-            })
-            .await;
+        let mut num_retries = 0;
+        loop {
+            let start = Instant::now();
+            let result = self
+                .epoch_requests
+                .request(RequestBatchSet {
+                    hash: hash.clone(),
+                    request_identifier: 0, // will automatically be set at a later point
+                    trace_id: Default::default(),
+                })
+                .await;
 
-        // TODO verify that hash of returned epoch matches the one we requested
+            // TODO verify that hash of returned epoch matches the one we requested
 
-        result
+            match result {
+                // A busy peer answers quickly without doing any work, so it isn't counted.
+                Ok(batch_set) if batch_set.status == ResponseStatus::Busy => {
+                    if num_retries >= Self::MAX_BUSY_RETRIES {
+                        return Ok(batch_set);
+                    }
+                    num_retries += 1;
+                    tokio::time::sleep(Self::BUSY_RETRY_DELAY * num_retries).await;
+                }
+                result => {
+                    self.record_request(start, &result);
+                    return result;
+                }
+            }
+        }
     }
 
     pub async fn request_block_hashes(
@@ -108,6 +191,7 @@ impl<P: Peer> ConsensusAgent<P> {
                 max_blocks,
                 filter,
                 request_identifier: 0, // will automatically be set at a later point
+                trace_id: Default::default(),
             })
             .await;
 
@@ -120,19 +204,36 @@ impl<P: Peer> ConsensusAgent<P> {
         block_number: u32,
         chunk_index: usize,
     ) -> Result<HistoryChunk, RequestError> {
-        let result = self
-            .history_chunk_requests
-            .request(RequestHistoryChunk {
-                epoch_number,
-                block_number,
-                chunk_index: chunk_index as u64,
-                request_identifier: 0, // will automatically be set at a later point
-            })
-            .await;
+        let mut num_retries = 0;
+        loop {
+            let start = Instant::now();
+            let result = self
+                .history_chunk_requests
+                .request(RequestHistoryChunk {
+                    epoch_number,
+                    block_number,
+                    chunk_index: chunk_index as u64,
+                    request_identifier: 0, // will automatically be set at a later point
+                    trace_id: Default::default(),
+                })
+                .await;
 
-        // TODO filter empty chunks here?
+            // TODO filter empty chunks here?
 
-        result
+            match result {
+                Ok(chunk) if chunk.status == ResponseStatus::Busy => {
+                    if num_retries >= Self::MAX_BUSY_RETRIES {
+                        return Ok(chunk);
+                    }
+                    num_retries += 1;
+                    tokio::time::sleep(Self::BUSY_RETRY_DELAY * num_retries).await;
+                }
+                result => {
+                    self.record_request(start, &result);
+                    return result;
+                }
+            }
+        }
     }
 
     pub async fn request_missing_blocks(
@@ -140,14 +241,17 @@ impl<P: Peer> ConsensusAgent<P> {
         target_block_hash: Blake2bHash,
         locators: Vec<Blake2bHash>,
     ) -> Result<Option<Vec<Block>>, RequestError> {
+        let start = Instant::now();
         let result = self
             .missing_block_requests
             .request(RequestMissingBlocks {
                 locators,
                 target_hash: target_block_hash,
                 request_identifier: 0, // will automatically be set at a later point
+                trace_id: Default::default(),
             })
             .await;
+        self.record_request(start, &result);
 
         result.map(|response_blocks| response_blocks.blocks)
     }
@@ -157,6 +261,7 @@ impl<P: Peer> ConsensusAgent<P> {
             .head_requests
             .request(RequestHead {
                 request_identifier: 0, // will automatically be set at a later point
+                trace_id: Default::default(),
             })
             .await;
 
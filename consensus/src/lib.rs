@@ -18,7 +18,10 @@ extern crate pin_project;
 #[macro_use]
 extern crate nimiq_macros;
 
-pub use consensus::{Consensus, ConsensusEvent, ConsensusProxy};
+pub use consensus::{
+    Consensus, ConsensusEvent, ConsensusProxy, ConsensusState, EstablishedPolicy, ProductionWindow,
+    RequestServing,
+};
 pub use error::Error;
 
 pub mod consensus;
@@ -26,3 +29,5 @@ pub mod consensus_agent;
 pub mod error;
 pub mod messages;
 pub mod sync;
+
+nimiq_network_interface::register_topics!(pub TOPICS = [sync::block_queue::BlockTopic]);
@@ -0,0 +1,242 @@
+use std::hash::Hash;
+
+use bytes::Bytes;
+use lru::LruCache;
+use parking_lot::Mutex;
+
+use beserial::Serialize;
+use nimiq_block::MacroBlock;
+use nimiq_hash::Blake2bHash;
+use nimiq_network_interface::message::Message;
+use nimiq_utils::memory::MemoryGauge;
+
+/// Identifies a history chunk by epoch, block number and chunk index.
+type HistoryChunkKey = (u32, u32, u64);
+
+/// Caches responses to expensive sync requests that were recently served to other peers.
+///
+/// When many peers sync at the same time, they request the same epochs and history chunks over
+/// and over again. Rebuilding those responses hits the database every time, so we keep the most
+/// recently served ones around. Only finalized data is cached, which never changes.
+///
+/// The cache also remembers the responses that were recently sent to each peer. Request
+/// identifiers are unique within the session of the requesting peer, so a request that arrives
+/// again with the same identifier is a retry, e.g. because the response was lost in a reconnect.
+/// Retries are answered with the remembered response instead of handling the request again.
+///
+/// The size of the cached responses is counted by a memory gauge. If the gauge has a cap, the
+/// least recently used responses are evicted until the cache fits into it again.
+pub(crate) struct ResponseCache<Id: Hash + Eq> {
+    /// Macro block and history length by macro block hash.
+    batch_sets: Mutex<LruCache<Blake2bHash, (MacroBlock, u32)>>,
+    /// History chunks are kept in serialized form, which is considerably more compact and can be
+    /// sent without serializing them again.
+    history_chunks: Mutex<LruCache<HistoryChunkKey, Bytes>>,
+    /// Serialized responses by peer and request identifier.
+    served: Mutex<ServedResponses<Id>>,
+    memory: MemoryGauge,
+}
+
+/// The responses sent to peers, bounded by their total size rather than their number since they
+/// range from a few bytes to whole history chunks.
+struct ServedResponses<Id: Hash + Eq> {
+    responses: LruCache<(Id, u32), Bytes>,
+    size: usize,
+}
+
+impl<Id: Hash + Eq> ResponseCache<Id> {
+    /// Number of batch set infos kept in the cache.
+    const BATCH_SET_CAPACITY: usize = 64;
+
+    /// Number of history chunks kept in the cache.
+    const HISTORY_CHUNK_CAPACITY: usize = 128;
+
+    /// Total size of the responses sent to peers that are remembered.
+    const SERVED_MAX_SIZE: usize = 16 * 1024 * 1024;
+
+    pub fn memory(&self) -> MemoryGauge {
+        self.memory.clone()
+    }
+
+    pub fn get_batch_set(&self, hash: &Blake2bHash) -> Option<(MacroBlock, u32)> {
+        self.batch_sets.lock().get(hash).cloned()
+    }
+
+    pub fn put_batch_set(&self, hash: Blake2bHash, block: MacroBlock, history_len: u32) {
+        Self::put(
+            &self.batch_sets,
+            &self.memory,
+            hash,
+            (block, history_len),
+            |(block, _)| block.serialized_size(),
+        );
+        self.shrink();
+    }
+
+    pub fn get_history_chunk(
+        &self,
+        epoch_number: u32,
+        block_number: u32,
+        chunk_index: u64,
+    ) -> Option<Bytes> {
+        self.history_chunks
+            .lock()
+            .get(&(epoch_number, block_number, chunk_index))
+            .cloned()
+    }
+
+    pub fn put_history_chunk(
+        &self,
+        epoch_number: u32,
+        block_number: u32,
+        chunk_index: u64,
+        chunk: Bytes,
+    ) {
+        Self::put(
+            &self.history_chunks,
+            &self.memory,
+            (epoch_number, block_number, chunk_index),
+            chunk,
+            Bytes::len,
+        );
+        self.shrink();
+    }
+
+    /// Returns the response that was sent to the peer for the request with the given identifier.
+    pub fn get_served<Res: Message>(&self, peer_id: Id, request_identifier: u32) -> Option<Res> {
+        let bytes = self
+            .served
+            .lock()
+            .responses
+            .get(&(peer_id, request_identifier))
+            .cloned()?;
+        Res::deserialize_from_bytes(bytes).ok()
+    }
+
+    /// Remembers the response that is sent to the peer for the request with the given identifier.
+    pub fn put_served<Res: Message>(&self, peer_id: Id, request_identifier: u32, response: &Res) {
+        let bytes = response.serialize_to_bytes();
+        let mut served = self.served.lock();
+
+        served.size += bytes.len();
+        self.memory.add(bytes.len());
+        if let Some(replaced) = served.responses.put((peer_id, request_identifier), bytes) {
+            served.size -= replaced.len();
+            self.memory.sub(replaced.len());
+        }
+
+        while served.size > Self::SERVED_MAX_SIZE {
+            match served.responses.pop_lru() {
+                Some((_, evicted)) => {
+                    served.size -= evicted.len();
+                    self.memory.sub(evicted.len());
+                }
+                None => break,
+            }
+        }
+        drop(served);
+
+        self.shrink();
+    }
+
+    /// Puts an entry into the cache and accounts for the entries it replaces.
+    fn put<K: Hash + Eq, V, F: Fn(&V) -> usize>(
+        cache: &Mutex<LruCache<K, V>>,
+        memory: &MemoryGauge,
+        key: K,
+        value: V,
+        size: F,
+    ) {
+        let mut cache = cache.lock();
+
+        // The cache evicts the least recently used entry when it is full.
+        if !cache.contains(&key) && cache.len() == cache.cap() {
+            if let Some((_, evicted)) = cache.pop_lru() {
+                memory.sub(size(&evicted));
+            }
+        }
+
+        memory.add(size(&value));
+        if let Some(replaced) = cache.put(key, value) {
+            memory.sub(size(&replaced));
+        }
+    }
+
+    /// Evicts the least recently used entries while the cache is over its cap. Responses sent to
+    /// peers are evicted first since they only help retries, then history chunks since they are
+    /// larger and cheaper to rebuild than batch sets.
+    fn shrink(&self) {
+        while self.memory.is_over_cap() {
+            let mut served = self.served.lock();
+            if let Some((_, response)) = served.responses.pop_lru() {
+                served.size -= response.len();
+                self.memory.sub(response.len());
+            } else if let Some((_, chunk)) = self.history_chunks.lock().pop_lru() {
+                self.memory.sub(chunk.len());
+            } else if let Some((_, (block, _))) = self.batch_sets.lock().pop_lru() {
+                self.memory.sub(block.serialized_size());
+            } else {
+                break;
+            }
+        }
+    }
+}
+
+impl<Id: Hash + Eq> Default for ResponseCache<Id> {
+    fn default() -> Self {
+        ResponseCache {
+            batch_sets: Mutex::new(LruCache::new(Self::BATCH_SET_CAPACITY)),
+            history_chunks: Mutex::new(LruCache::new(Self::HISTORY_CHUNK_CAPACITY)),
+            served: Mutex::new(ServedResponses {
+                responses: LruCache::unbounded(),
+                size: 0,
+            }),
+            memory: MemoryGauge::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::HeadResponse;
+
+    fn head_response(request_identifier: u32) -> HeadResponse {
+        HeadResponse {
+            hash: Blake2bHash::default(),
+            request_identifier,
+        }
+    }
+
+    #[test]
+    fn it_remembers_served_responses() {
+        let cache = ResponseCache::default();
+
+        cache.put_served(1u32, 7, &head_response(7));
+        assert_eq!(
+            cache
+                .get_served::<HeadResponse>(1, 7)
+                .map(|response| response.request_identifier),
+            Some(7)
+        );
+        // The identifier is only unique for the requesting peer.
+        assert!(cache.get_served::<HeadResponse>(2, 7).is_none());
+    }
+
+    #[test]
+    fn it_evicts_served_responses_by_size() {
+        let cache = ResponseCache::default();
+        let size = head_response(0).serialized_size();
+
+        // Leave room for two responses.
+        cache.memory().set_cap(Some(2 * size));
+        cache.put_served(1u32, 7, &head_response(7));
+        cache.put_served(1, 8, &head_response(8));
+        cache.put_served(1, 9, &head_response(9));
+
+        assert!(cache.get_served::<HeadResponse>(1, 7).is_none());
+        assert!(cache.get_served::<HeadResponse>(1, 8).is_some());
+        assert!(cache.get_served::<HeadResponse>(1, 9).is_some());
+        assert_eq!(cache.memory().used(), 2 * size);
+    }
+}
@@ -1,21 +1,38 @@
+use std::hash::Hash;
 use std::sync::Arc;
 
-use parking_lot::RwLock;
+use bytes::Bytes;
 
+use beserial::Serialize;
 use nimiq_block::Block;
-use nimiq_blockchain::{AbstractBlockchain, Blockchain, Direction, CHUNK_SIZE};
-use nimiq_network_interface::message::ResponseMessage;
+use nimiq_blockchain::{AbstractBlockchain, BlockchainLock, Direction, CHUNK_SIZE};
+use nimiq_network_interface::message::{ResponseMessage, SerializedMessage};
 use nimiq_primitives::policy;
 
+use crate::messages::cache::ResponseCache;
 use crate::messages::*;
 
 /// This trait defines the behaviour when receiving a message and how to generate the response.
 pub trait Handle<Response> {
-    fn handle(&self, blockchain: &Arc<RwLock<Blockchain>>) -> Response;
+    fn handle<Id: Hash + Eq>(
+        &self,
+        blockchain: &Arc<BlockchainLock>,
+        cache: &ResponseCache<Id>,
+    ) -> Response;
+}
+
+/// This trait is implemented by expensive requests that are subject to the serving limits.
+pub trait HandleBusy<Response>: Handle<Response> {
+    /// Generates the response telling the requester to retry later.
+    fn busy(&self) -> Response;
 }
 
 impl Handle<BlockHashes> for RequestBlockHashes {
-    fn handle(&self, blockchain: &Arc<RwLock<Blockchain>>) -> BlockHashes {
+    fn handle<Id: Hash + Eq>(
+        &self,
+        blockchain: &Arc<BlockchainLock>,
+        _cache: &ResponseCache<Id>,
+    ) -> BlockHashes {
         let blockchain = blockchain.read();
         // A peer has requested blocks. Check all requested block locator hashes
         // in the given order and pick the first hash that is found on our main
@@ -87,47 +104,130 @@ impl Handle<BlockHashes> for RequestBlockHashes {
 }
 
 impl Handle<BatchSetInfo> for RequestBatchSet {
-    fn handle(&self, blockchain: &Arc<RwLock<Blockchain>>) -> BatchSetInfo {
+    fn handle<Id: Hash + Eq>(
+        &self,
+        blockchain: &Arc<BlockchainLock>,
+        cache: &ResponseCache<Id>,
+    ) -> BatchSetInfo {
+        if let Some((block, history_len)) = cache.get_batch_set(&self.hash) {
+            return BatchSetInfo {
+                block: Some(block),
+                history_len,
+                status: ResponseStatus::Ok,
+                request_identifier: self.get_request_identifier(),
+            };
+        }
+
         let blockchain = blockchain.read();
         if let Some(Block::Macro(block)) = blockchain.get_block(&self.hash, true, None) {
             let history_len = blockchain
                 .history_store
                 .length_at(block.header.block_number, None);
 
+            // Macro blocks are final, so the response for this hash won't change anymore.
+            cache.put_batch_set(self.hash.clone(), block.clone(), history_len);
+
             BatchSetInfo {
                 block: Some(block),
                 history_len,
+                status: ResponseStatus::Ok,
                 request_identifier: self.get_request_identifier(),
             }
         } else {
             BatchSetInfo {
                 block: None,
                 history_len: 0,
+                status: ResponseStatus::Ok,
                 request_identifier: self.get_request_identifier(),
             }
         }
     }
 }
 
-impl Handle<HistoryChunk> for RequestHistoryChunk {
-    fn handle(&self, blockchain: &Arc<RwLock<Blockchain>>) -> HistoryChunk {
-        let chunk = blockchain.read().history_store.prove_chunk(
-            self.epoch_number,
-            self.block_number,
-            CHUNK_SIZE,
-            self.chunk_index as usize,
-            None,
-        );
-        HistoryChunk {
-            chunk,
+/// History chunks are large, so they are served in serialized form. Cached chunks are sent
+/// without deserializing them.
+impl Handle<SerializedMessage<HistoryChunk>> for RequestHistoryChunk {
+    fn handle<Id: Hash + Eq>(
+        &self,
+        blockchain: &Arc<BlockchainLock>,
+        cache: &ResponseCache<Id>,
+    ) -> SerializedMessage<HistoryChunk> {
+        if let Some(chunk) =
+            cache.get_history_chunk(self.epoch_number, self.block_number, self.chunk_index)
+        {
+            return HistoryChunk::serialized(
+                Some(&chunk),
+                ResponseStatus::Ok,
+                self.get_request_identifier(),
+            );
+        }
+
+        let blockchain = blockchain.read();
+        let chunk = blockchain
+            .history_store
+            .prove_chunk(
+                self.epoch_number,
+                self.block_number,
+                CHUNK_SIZE,
+                self.chunk_index as usize,
+                None,
+            )
+            .map(|chunk| Bytes::from(chunk.serialize_to_vec()));
+
+        // Only cache chunks up to a finalized macro block, the history after it might still
+        // be reverted.
+        if let Some(chunk) = &chunk {
+            if policy::is_macro_block_at(self.block_number)
+                && self.block_number <= blockchain.macro_head().block_number()
+            {
+                cache.put_history_chunk(
+                    self.epoch_number,
+                    self.block_number,
+                    self.chunk_index,
+                    chunk.clone(),
+                );
+            }
+        }
+
+        HistoryChunk::serialized(
+            chunk.as_deref(),
+            ResponseStatus::Ok,
+            self.get_request_identifier(),
+        )
+    }
+}
+
+impl HandleBusy<BatchSetInfo> for RequestBatchSet {
+    fn busy(&self) -> BatchSetInfo {
+        BatchSetInfo {
+            block: None,
+            history_len: 0,
+            status: ResponseStatus::Busy,
             request_identifier: self.get_request_identifier(),
         }
     }
 }
 
+impl HandleBusy<SerializedMessage<HistoryChunk>> for RequestHistoryChunk {
+    fn busy(&self) -> SerializedMessage<HistoryChunk> {
+        HistoryChunk::serialized(None, ResponseStatus::Busy, self.get_request_identifier())
+    }
+}
+
 impl Handle<ResponseBlock> for RequestBlock {
-    fn handle(&self, blockchain: &Arc<RwLock<Blockchain>>) -> ResponseBlock {
-        let block = blockchain.read().get_block(&self.hash, true, None);
+    fn handle<Id: Hash + Eq>(
+        &self,
+        blockchain: &Arc<BlockchainLock>,
+        _cache: &ResponseCache<Id>,
+    ) -> ResponseBlock {
+        let blockchain = blockchain.read();
+        // We can't serve micro blocks whose body was pruned.
+        let horizon = blockchain.micro_body_horizon();
+        let block = blockchain
+            .get_block(&self.hash, true, None)
+            .filter(|block| {
+                !block.is_micro() || horizon.map_or(true, |horizon| block.block_number() > horizon)
+            });
         ResponseBlock {
             block,
             request_identifier: self.get_request_identifier(),
@@ -136,7 +236,11 @@ impl Handle<ResponseBlock> for RequestBlock {
 }
 
 impl Handle<ResponseBlocks> for RequestMissingBlocks {
-    fn handle(&self, blockchain: &Arc<RwLock<Blockchain>>) -> ResponseBlocks {
+    fn handle<Id: Hash + Eq>(
+        &self,
+        blockchain: &Arc<BlockchainLock>,
+        _cache: &ResponseCache<Id>,
+    ) -> ResponseBlocks {
         let blockchain = blockchain.read();
         // Behaviour of our missing blocks request:
         // 1. Receives `target_block_hash: Blake2bHash, locators: Vec<Blake2bHash>`
@@ -201,6 +305,21 @@ impl Handle<ResponseBlocks> for RequestMissingBlocks {
             };
         }
 
+        // We can't serve micro blocks whose body was pruned. The first block up to the horizon is
+        // the macro block at the horizon, any earlier one is a pruned micro block.
+        if let Some(horizon) = blockchain.micro_body_horizon() {
+            if start_block.block_number() + 1 < horizon {
+                debug!(
+                    "ResponseBlocks [{}] - blocks below the prune horizon #{} requested",
+                    self.request_identifier, horizon
+                );
+                return ResponseBlocks {
+                    blocks: None,
+                    request_identifier: self.get_request_identifier(),
+                };
+            }
+        }
+
         // Collect the blocks starting right after the identified block on the main chain
         // up to our target hash.
         let blocks =
@@ -214,7 +333,11 @@ impl Handle<ResponseBlocks> for RequestMissingBlocks {
 }
 
 impl Handle<HeadResponse> for RequestHead {
-    fn handle(&self, blockchain: &Arc<RwLock<Blockchain>>) -> HeadResponse {
+    fn handle<Id: Hash + Eq>(
+        &self,
+        blockchain: &Arc<BlockchainLock>,
+        _cache: &ResponseCache<Id>,
+    ) -> HeadResponse {
         let hash = blockchain.read().head_hash();
         HeadResponse {
             hash,
@@ -222,3 +345,91 @@ impl Handle<HeadResponse> for RequestHead {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use nimiq_block_production::BlockProducer;
+    use nimiq_blockchain::Blockchain;
+    use nimiq_database::volatile::VolatileEnvironment;
+    use nimiq_primitives::networks::NetworkId;
+    use nimiq_test_utils::blockchain::{
+        fill_micro_blocks, produce_macro_blocks, signing_key, voting_key,
+    };
+    use nimiq_utils::time::OffsetTime;
+
+    use super::*;
+
+    /// Produces a finalized batch, whose micro bodies are pruned, followed by a batch of micro
+    /// blocks that keep their bodies.
+    fn pruned_blockchain() -> Arc<BlockchainLock> {
+        let time = Arc::new(OffsetTime::new());
+        let env = VolatileEnvironment::new(10).unwrap();
+        let blockchain = Arc::new(BlockchainLock::new(
+            Blockchain::new(env, NetworkId::UnitAlbatross, time).unwrap(),
+        ));
+        blockchain.write().prune_micro_bodies = true;
+
+        let producer = BlockProducer::new(signing_key(), voting_key());
+        produce_macro_blocks(&producer, &blockchain, 1);
+        fill_micro_blocks(&producer, &blockchain);
+        blockchain
+    }
+
+    fn request_block(blockchain: &Arc<BlockchainLock>, block_number: u32) -> Option<Block> {
+        let hash = blockchain
+            .read()
+            .get_block_at(block_number, false, None)
+            .unwrap()
+            .hash();
+        let request = RequestBlock {
+            hash,
+            request_identifier: 1,
+            trace_id: Default::default(),
+        };
+        request
+            .handle(blockchain, &ResponseCache::<u32>::default())
+            .block
+    }
+
+    #[test]
+    fn it_does_not_serve_pruned_blocks() {
+        let blockchain = pruned_blockchain();
+        let horizon = policy::macro_block_of(1);
+
+        assert!(request_block(&blockchain, 1).is_none());
+        assert!(request_block(&blockchain, horizon).is_some());
+
+        let block = request_block(&blockchain, horizon + 1).unwrap();
+        assert!(block.unwrap_micro().body.is_some());
+    }
+
+    #[test]
+    fn it_does_not_serve_missing_blocks_below_the_horizon() {
+        let blockchain = pruned_blockchain();
+        let (genesis_hash, macro_hash, head_hash) = {
+            let blockchain = blockchain.read();
+            (
+                blockchain.get_block_at(0, false, None).unwrap().hash(),
+                blockchain.macro_head_hash(),
+                blockchain.head_hash(),
+            )
+        };
+
+        let request = |locator| RequestMissingBlocks {
+            target_hash: head_hash.clone(),
+            locators: vec![locator],
+            request_identifier: 1,
+            trace_id: Default::default(),
+        };
+
+        let response = request(genesis_hash).handle(&blockchain, &ResponseCache::<u32>::default());
+        assert!(response.blocks.is_none());
+
+        let response = request(macro_hash).handle(&blockchain, &ResponseCache::<u32>::default());
+        let blocks = response.blocks.unwrap();
+        assert_eq!(blocks.len() as u32, policy::BATCH_LENGTH - 1);
+        assert!(blocks
+            .iter()
+            .all(|block| block.unwrap_micro_ref().body.is_some()));
+    }
+}
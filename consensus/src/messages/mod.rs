@@ -1,5 +1,7 @@
 use std::fmt::{Debug, Formatter};
 
+use bytes::Bytes;
+
 use beserial::{Deserialize, Serialize};
 use nimiq_block::{Block, MacroBlock};
 use nimiq_blockchain::HistoryTreeChunk;
@@ -8,6 +10,7 @@ use nimiq_network_interface::message::*;
 
 use crate::request_response;
 
+pub(crate) mod cache;
 pub(crate) mod handlers;
 mod request_response;
 
@@ -49,7 +52,7 @@ impl<T: Serialize + Deserialize> Objects<T> {
     }
 }
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize, Schema)]
 #[repr(u8)]
 pub enum BlockHashType {
     Micro = 1,
@@ -72,7 +75,7 @@ impl<'a> From<&'a Block> for BlockHashType {
     }
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize, Schema)]
 pub struct BlockHashes {
     #[beserial(len_type(u16))]
     pub hashes: Option<Vec<(BlockHashType, Blake2bHash)>>,
@@ -102,7 +105,23 @@ impl Debug for BlockHashes {
     }
 }
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
+/// The status of a response to an expensive sync request. Older peers don't send it, their
+/// responses are treated as `Ok`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize, Schema)]
+#[repr(u8)]
+pub enum ResponseStatus {
+    Ok = 0,
+    /// The peer is serving too many requests at the moment, the request should be retried later.
+    Busy = 1,
+}
+
+impl Default for ResponseStatus {
+    fn default() -> Self {
+        ResponseStatus::Ok
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize, Schema)]
 #[repr(u8)]
 pub enum RequestBlockHashesFilter {
     All = 1,
@@ -110,26 +129,30 @@ pub enum RequestBlockHashesFilter {
     ElectionAndLatestCheckpoint = 3,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, Schema)]
 pub struct RequestBlockHashes {
     #[beserial(len_type(u16, limit = 128))]
     pub locators: Vec<Blake2bHash>,
     pub max_blocks: u16,
     pub filter: RequestBlockHashesFilter,
     pub request_identifier: u32,
+    #[beserial(trailing)]
+    pub trace_id: Option<TraceId>,
 }
-request_response!(RequestBlockHashes);
+request_response!(RequestBlockHashes, request_identifier, trace_id);
 
 impl Message for RequestBlockHashes {
     const TYPE_ID: u64 = 200;
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, Schema)]
 pub struct RequestBatchSet {
     pub hash: Blake2bHash,
     pub request_identifier: u32,
+    #[beserial(trailing)]
+    pub trace_id: Option<TraceId>,
 }
-request_response!(RequestBatchSet);
+request_response!(RequestBatchSet, request_identifier, trace_id);
 
 impl Message for RequestBatchSet {
     const TYPE_ID: u64 = 202;
@@ -137,11 +160,13 @@ impl Message for RequestBatchSet {
 
 /// This message contains a macro block and the number of extended transactions (transitions)
 /// within this epoch.
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize, Schema)]
 pub struct BatchSetInfo {
     pub block: Option<MacroBlock>,
     pub history_len: u32,
     pub request_identifier: u32,
+    #[beserial(trailing)]
+    pub status: ResponseStatus,
 }
 request_response!(BatchSetInfo);
 
@@ -160,30 +185,35 @@ impl Debug for BatchSetInfo {
         }
         debug_struct
             .field("history_len", &self.history_len)
+            .field("status", &self.status)
             .field("request_identifier", &self.request_identifier);
         debug_struct.finish()
     }
 }
 
 /// This message contains a chunk of the history.
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, Schema)]
 pub struct RequestHistoryChunk {
     pub epoch_number: u32,
     pub block_number: u32,
     pub chunk_index: u64,
     pub request_identifier: u32,
+    #[beserial(trailing)]
+    pub trace_id: Option<TraceId>,
 }
-request_response!(RequestHistoryChunk);
+request_response!(RequestHistoryChunk, request_identifier, trace_id);
 
 impl Message for RequestHistoryChunk {
     const TYPE_ID: u64 = 204;
 }
 
 /// This message contains a chunk of the history.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Schema)]
 pub struct HistoryChunk {
     pub chunk: Option<HistoryTreeChunk>,
     pub request_identifier: u32,
+    #[beserial(trailing)]
+    pub status: ResponseStatus,
 }
 request_response!(HistoryChunk);
 
@@ -191,7 +221,35 @@ impl Message for HistoryChunk {
     const TYPE_ID: u64 = 205;
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+impl HistoryChunk {
+    /// Builds the response from a chunk that is already serialized, e.g. a cached one, without
+    /// deserializing it.
+    pub fn serialized(
+        chunk: Option<&[u8]>,
+        status: ResponseStatus,
+        request_identifier: u32,
+    ) -> SerializedMessage<HistoryChunk> {
+        let mut body = Vec::with_capacity(
+            1 + chunk.map_or(0, <[u8]>::len)
+                + request_identifier.serialized_size()
+                + status.serialized_size(),
+        );
+        // Encoded like `Option<HistoryTreeChunk>`.
+        match chunk {
+            Some(chunk) => {
+                body.push(1);
+                body.extend_from_slice(chunk);
+            }
+            None => body.push(0),
+        }
+        request_identifier.serialize(&mut body).unwrap();
+        status.serialize(&mut body).unwrap();
+
+        SerializedMessage::from_bytes(Bytes::from(body))
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Schema)]
 pub struct ResponseBlock {
     pub block: Option<Block>,
     pub request_identifier: u32,
@@ -213,18 +271,20 @@ impl Debug for ResponseBlock {
         dbg.finish()
     }
 }
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, Schema)]
 pub struct RequestBlock {
     pub hash: Blake2bHash,
     pub request_identifier: u32,
+    #[beserial(trailing)]
+    pub trace_id: Option<TraceId>,
 }
-request_response!(RequestBlock);
+request_response!(RequestBlock, request_identifier, trace_id);
 
 impl Message for RequestBlock {
     const TYPE_ID: u64 = 207;
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize, Schema)]
 pub struct ResponseBlocks {
     // TODO: Set to sensible limit (2 * BATCH_SIZE for example).
     #[beserial(len_type(u16, limit = 256))]
@@ -255,30 +315,34 @@ impl Debug for ResponseBlocks {
     }
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, Schema)]
 pub struct RequestMissingBlocks {
     pub target_hash: Blake2bHash,
     #[beserial(len_type(u16, limit = 128))]
     pub locators: Vec<Blake2bHash>,
     pub request_identifier: u32,
+    #[beserial(trailing)]
+    pub trace_id: Option<TraceId>,
 }
-request_response!(RequestMissingBlocks);
+request_response!(RequestMissingBlocks, request_identifier, trace_id);
 
 impl Message for RequestMissingBlocks {
     const TYPE_ID: u64 = 209;
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, Schema)]
 pub struct RequestHead {
     pub request_identifier: u32,
+    #[beserial(trailing)]
+    pub trace_id: Option<TraceId>,
 }
-request_response!(RequestHead);
+request_response!(RequestHead, request_identifier, trace_id);
 
 impl Message for RequestHead {
     const TYPE_ID: u64 = 210;
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, Schema)]
 pub struct HeadResponse {
     pub hash: Blake2bHash,
     pub request_identifier: u32,
@@ -288,3 +352,89 @@ request_response!(HeadResponse);
 impl Message for HeadResponse {
     const TYPE_ID: u64 = 211;
 }
+
+nimiq_network_interface::register_messages!(pub MESSAGES = [
+    RequestBlockHashes => RequestBlockHashes {
+        locators: vec![Blake2bHash::default()],
+        max_blocks: 1000,
+        filter: RequestBlockHashesFilter::All,
+        request_identifier: 1,
+        trace_id: None,
+    },
+    BlockHashes => BlockHashes {
+        hashes: Some(vec![(BlockHashType::Micro, Blake2bHash::default())]),
+        request_identifier: 1,
+    },
+    RequestBatchSet => RequestBatchSet {
+        hash: Blake2bHash::default(),
+        request_identifier: 1,
+        trace_id: None,
+    },
+    BatchSetInfo => BatchSetInfo {
+        block: None,
+        history_len: 0,
+        request_identifier: 1,
+        status: ResponseStatus::Ok,
+    },
+    RequestHistoryChunk => RequestHistoryChunk {
+        epoch_number: 1,
+        block_number: 2,
+        chunk_index: 3,
+        request_identifier: 4,
+        trace_id: None,
+    },
+    HistoryChunk => HistoryChunk {
+        chunk: None,
+        request_identifier: 1,
+        status: ResponseStatus::Busy,
+    },
+    ResponseBlock => ResponseBlock {
+        block: None,
+        request_identifier: 1,
+    },
+    RequestBlock => RequestBlock {
+        hash: Blake2bHash::default(),
+        request_identifier: 1,
+        trace_id: None,
+    },
+    ResponseBlocks => ResponseBlocks {
+        blocks: None,
+        request_identifier: 1,
+    },
+    RequestMissingBlocks => RequestMissingBlocks {
+        target_hash: Blake2bHash::default(),
+        locators: vec![],
+        request_identifier: 1,
+        trace_id: None,
+    },
+    RequestHead => RequestHead {
+        request_identifier: 1,
+        trace_id: None,
+    },
+    HeadResponse => HeadResponse {
+        hash: Blake2bHash::default(),
+        request_identifier: 1,
+    },
+]);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn history_chunks_of_older_peers_are_ok() {
+        // Older peers send the response without a status.
+        let mut bin = vec![0];
+        bin.extend_from_slice(&7u32.to_be_bytes());
+
+        let chunk = HistoryChunk::deserialize_from_vec(&bin).unwrap();
+        assert_eq!(chunk.request_identifier, 7);
+        assert_eq!(chunk.status, ResponseStatus::Ok);
+
+        // The serialized form matches the derived one, with the status at the end.
+        let busy = HistoryChunk::serialized(None, ResponseStatus::Busy, 7);
+        let chunk = HistoryChunk::deserialize_from_vec(busy.body()).unwrap();
+        assert_eq!(chunk.request_identifier, 7);
+        assert_eq!(chunk.status, ResponseStatus::Busy);
+    }
+}
@@ -15,4 +15,24 @@ macro_rules! request_response {
             }
         }
     };
+    ($msg:ty, $a:ident, $trace:ident) => {
+        impl nimiq_network_interface::message::RequestMessage for $msg {
+            fn set_request_identifier(&mut self, request_identifier: u32) {
+                self.$a = request_identifier;
+            }
+
+            fn set_trace_id(&mut self, trace_id: nimiq_network_interface::message::TraceId) {
+                self.$trace = Some(trace_id);
+            }
+        }
+        impl nimiq_network_interface::message::ResponseMessage for $msg {
+            fn get_request_identifier(&self) -> u32 {
+                self.$a
+            }
+
+            fn get_trace_id(&self) -> Option<nimiq_network_interface::message::TraceId> {
+                self.$trace
+            }
+        }
+    };
 }
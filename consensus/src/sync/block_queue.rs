@@ -8,21 +8,25 @@ use std::{
 use futures::future::BoxFuture;
 use futures::stream::{BoxStream, Stream, StreamExt};
 use futures::FutureExt;
-use parking_lot::RwLock;
 use pin_project::pin_project;
 use tokio::task::spawn_blocking;
 
+use beserial::Serialize;
 use nimiq_block::Block;
-use nimiq_blockchain::{AbstractBlockchain, Direction};
-use nimiq_blockchain::{Blockchain, PushError, PushResult};
+use nimiq_blockchain::{AbstractBlockchain, BlockchainEvent, Direction};
+use nimiq_blockchain::{Blockchain, BlockchainLock, PushError, PushResult};
 use nimiq_hash::Blake2bHash;
 use nimiq_network_interface::{
-    network::{MsgAcceptance, Network, PubsubId, Topic},
+    network::{MsgAcceptance, Network, OverflowPolicy, PubsubId, Topic},
     peer::Peer,
 };
 use nimiq_primitives::policy;
+use nimiq_utils::memory::MemoryGauge;
+use nimiq_utils::observer::NotifierStream;
 
 use crate::consensus_agent::ConsensusAgent;
+use crate::sync::block_relay::BlockRelay;
+use crate::sync::block_validation::{BlockValidation, ValidatedBlock};
 use crate::sync::request_component::RequestComponentEvent;
 
 use super::request_component::RequestComponent;
@@ -36,6 +40,9 @@ impl Topic for BlockTopic {
     const BUFFER_SIZE: usize = 16;
     const NAME: &'static str = "blocks";
     const VALIDATE: bool = true;
+    /// The newest blocks are the most relevant ones, older blocks are requested as missing blocks
+    /// if they are needed.
+    const OVERFLOW_POLICY: OverflowPolicy = OverflowPolicy::DropOld;
 }
 
 pub type BlockStream<N> = BoxStream<'static, (Block, <N as Network>::PubsubId)>;
@@ -56,6 +63,20 @@ pub struct BlockQueueConfig {
 
     /// How many blocks ahead we will buffer.
     pub window_max: u32,
+
+    /// How many headers of blocks received via gossipsub are checked in parallel before the
+    /// blocks are queued. If zero, blocks are only verified when they are pushed to the chain.
+    pub header_check_workers: usize,
+
+    /// Relay gossiped blocks as soon as their header check passed, instead of after they were
+    /// pushed to the chain. If the full verification fails afterwards, a follow-up reject is
+    /// reported, which stops gossipsub from serving the block to peers that ask for it. Has no
+    /// effect if `header_check_workers` is zero.
+    pub cut_through_relay: bool,
+
+    /// How many requests for the blocks missing between our head and a block received via
+    /// gossipsub are sent to peers at the same time.
+    pub concurrent_block_requests: usize,
 }
 
 impl Default for BlockQueueConfig {
@@ -63,6 +84,9 @@ impl Default for BlockQueueConfig {
         Self {
             buffer_max: 4 * policy::BATCH_LENGTH as usize,
             window_max: 2 * policy::BATCH_LENGTH,
+            header_check_workers: 4,
+            cut_through_relay: false,
+            concurrent_block_requests: 5,
         }
     }
 }
@@ -72,7 +96,7 @@ struct Inner<N: Network> {
     config: BlockQueueConfig,
 
     /// Reference to the block chain
-    blockchain: Arc<RwLock<Blockchain>>,
+    blockchain: Arc<BlockchainLock>,
 
     /// Reference to the network
     network: Arc<N>,
@@ -81,6 +105,9 @@ struct Inner<N: Network> {
     /// There can be multiple blocks at a height if there are forks.
     buffer: BTreeMap<u32, HashMap<Blake2bHash, BlockAndId<N>>>,
 
+    /// The serialized size of the buffered blocks.
+    memory: MemoryGauge,
+
     /// Vector of pending `blockchain.push()` operations.
     push_ops: VecDeque<BoxFuture<'static, PushOpResult>>,
 
@@ -91,6 +118,23 @@ struct Inner<N: Network> {
 
     /// The block number of the latest macro block. We prune the block buffer when it changes.
     current_macro_height: u32,
+
+    /// Blocks that were relayed before they were fully verified - `block_hash -> (block_number, id)`.
+    /// If their verification fails, a follow-up reject is reported.
+    relayed: HashMap<Blake2bHash, (u32, <N as Network>::PubsubId)>,
+
+    /// Gossiped blocks that were already being pushed when they arrived, e.g. because the block
+    /// relay was faster - `block_hash -> (block_number, id)`. Their acceptance is reported once the
+    /// push finished.
+    deferred: HashMap<Blake2bHash, (u32, <N as Network>::PubsubId)>,
+
+    /// Blocks from the block relay that are queued to be pushed - `block_hash -> block_number`.
+    /// Only blocks inside the buffer window are queued, so their block number is bounded.
+    relay_pending: HashMap<Blake2bHash, u32>,
+
+    /// Blocks on our chain that were received from or sent to the block relay -
+    /// `block_hash -> block_number`. Entries below the head are pruned.
+    relay_seen: HashMap<Blake2bHash, u32>,
 }
 
 enum PushOpResult {
@@ -109,7 +153,7 @@ impl<N: Network> Inner<N> {
         &mut self,
         block: Block,
         mut request_component: Pin<&mut TReq>,
-        peer_id: <N::PeerType as Peer>::Id,
+        peer_id: Option<<N::PeerType as Peer>::Id>,
         pubsub_id: Option<<N as Network>::PubsubId>,
     ) {
         let block_number = block.block_number();
@@ -140,7 +184,7 @@ impl<N: Network> Inner<N> {
             );
             self.report_validation_result(pubsub_id, MsgAcceptance::Ignore);
 
-            if let Some(peer) = self.network.get_peer(peer_id) {
+            if let Some(peer) = peer_id.and_then(|peer_id| self.network.get_peer(peer_id)) {
                 request_component.put_peer_into_sync_mode(peer);
             }
         } else if self.buffer.len() >= self.config.buffer_max {
@@ -166,6 +210,7 @@ impl<N: Network> Inner<N> {
             let parent_hash = block.parent_hash().clone();
 
             // Insert block into buffer. If we already know the block, we're done.
+            let block_size = block.serialized_size();
             let block_known = self
                 .buffer
                 .entry(block_number)
@@ -181,6 +226,7 @@ impl<N: Network> Inner<N> {
             if block_known {
                 return;
             }
+            self.memory.add(block_size);
 
             // If the parent of this block is already in the buffer, we're done.
             let parent_buffered = self
@@ -310,7 +356,11 @@ impl<N: Network> Inner<N> {
     {
         let block_hash = block.hash();
         if !self.pending_blocks.insert(block_hash.clone()) {
-            // The block is already pending, so no need to add another future to push it.
+            // The block is already pending, so no need to add another future to push it. The
+            // message it came with is validated once the pending push finished.
+            if let Some(id) = pubsub_id {
+                self.deferred.insert(block_hash, (block.block_number(), id));
+            }
             return;
         }
 
@@ -366,6 +416,7 @@ impl<N: Network> Inner<N> {
         }
 
         for (block, pubsub_id) in blocks_to_push {
+            self.memory.sub(block.serialized_size());
             self.push_block(block, pubsub_id, PushOpResult::Buffered);
         }
     }
@@ -382,6 +433,7 @@ impl<N: Network> Inner<N> {
                 if invalid_blocks.contains(block.parent_hash()) {
                     log::trace!("Removing block because parent is invalid: {}", hash);
                     invalid_blocks.insert(hash.clone());
+                    self.memory.sub(block.serialized_size());
 
                     if let Some(id) = pubsub_id {
                         self.network
@@ -398,18 +450,129 @@ impl<N: Network> Inner<N> {
     }
 
     fn prune_buffer(&mut self) {
+        let current_macro_height = self.current_macro_height;
+        self.relayed
+            .retain(|_, (block_number, _)| *block_number > current_macro_height);
+        for (_, (_, pubsub_id)) in self
+            .deferred
+            .drain_filter(|_, (block_number, _)| *block_number <= current_macro_height)
+        {
+            self.network
+                .validate_message::<BlockTopic>(pubsub_id, MsgAcceptance::Ignore);
+        }
+        self.relay_pending
+            .retain(|_, block_number| *block_number > current_macro_height);
+
         while let Some(entry) = self.buffer.first_entry() {
             // Remove all entries from the block buffer that precede `current_macro_height`.
             if *entry.key() > self.current_macro_height {
                 break;
             }
             // Tell gossipsub to ignore the removed blocks.
-            for (_, (_, pubsub_id)) in entry.remove().into_iter() {
+            for (_, (block, pubsub_id)) in entry.remove().into_iter() {
+                self.memory.sub(block.serialized_size());
                 self.report_validation_result(pubsub_id, MsgAcceptance::Ignore);
             }
         }
     }
 
+    /// Marks a block that was relayed before it was fully verified.
+    fn on_block_relayed(&mut self, block: &Block, pubsub_id: <N as Network>::PubsubId) {
+        self.relayed
+            .insert(block.hash(), (block.block_number(), pubsub_id));
+    }
+
+    /// Called when the full verification of a block finished. Reports a follow-up reject if the
+    /// block was already relayed but turned out to be invalid.
+    fn on_block_verified(&mut self, hash: &Blake2bHash, valid: bool) {
+        if !valid {
+            self.relay_pending.remove(hash);
+        }
+
+        if let Some((_, pubsub_id)) = self.relayed.remove(hash) {
+            if !valid {
+                log::warn!("Relayed block {} failed verification", hash);
+                self.network
+                    .validate_message::<BlockTopic>(pubsub_id, MsgAcceptance::Reject);
+            }
+        }
+
+        if let Some((_, pubsub_id)) = self.deferred.remove(hash) {
+            let acceptance = if valid {
+                MsgAcceptance::Accept
+            } else {
+                MsgAcceptance::Reject
+            };
+            self.network
+                .validate_message::<BlockTopic>(pubsub_id, acceptance);
+        }
+    }
+
+    /// Handles a block received from the block relay. Blocks that we already know, from either
+    /// path, are dropped.
+    ///
+    /// The block is only remembered as coming from the relay if it was queued, i.e. it is inside
+    /// the buffer window. Nothing is remembered for blocks that are discarded, so a relay can't
+    /// fill our state with blocks claiming arbitrary block numbers.
+    fn on_block_from_relay<TReq: RequestComponent<N::PeerType>>(
+        &mut self,
+        block: Block,
+        request_component: Pin<&mut TReq>,
+    ) {
+        let block_hash = block.hash();
+        if self.relay_pending.contains_key(&block_hash)
+            || self.relay_seen.contains_key(&block_hash)
+            || self.pending_blocks.contains(&block_hash)
+            || self.blockchain.read().contains(&block_hash, true)
+        {
+            return;
+        }
+
+        let block_number = block.block_number();
+        log::debug!(
+            "Received block #{}.{} from the block relay",
+            block_number,
+            block.view_number()
+        );
+        self.on_block_announced(block, request_component, None, None);
+
+        let queued = self.pending_blocks.contains(&block_hash)
+            || self
+                .buffer
+                .get(&block_number)
+                .map_or(false, |blocks| blocks.contains_key(&block_hash));
+        if queued {
+            self.relay_pending.insert(block_hash, block_number);
+        }
+    }
+
+    /// Hands a block that extended our chain to the block relay, unless it came from there.
+    fn relay_block(&mut self, relay: &dyn BlockRelay, hash: &Blake2bHash) {
+        let blockchain = self.blockchain.read();
+        let block = match blockchain.get_block(hash, true, None) {
+            Some(block) => block,
+            None => return,
+        };
+        let head_height = blockchain.block_number();
+        drop(blockchain);
+
+        // The block is on our chain, so it is valid. Blocks below the head aren't relayed again
+        // unless they are part of a rebranch, so there is no need to remember them.
+        self.relay_seen
+            .retain(|_, block_number| *block_number >= head_height);
+
+        let from_relay = self.relay_pending.remove(hash).is_some();
+        if self
+            .relay_seen
+            .insert(hash.clone(), block.block_number())
+            .is_some()
+            || from_relay
+        {
+            return;
+        }
+        relay.relay(&block);
+    }
+
     #[inline]
     fn report_validation_result(
         &self,
@@ -435,6 +598,7 @@ impl<N: Network> Stream for Inner<N> {
             match result {
                 PushOpResult::Head(Ok(result), hash) => {
                     self.pending_blocks.remove(&hash);
+                    self.on_block_verified(&hash, true);
                     self.push_buffered();
                     if result == PushResult::Extended || result == PushResult::Rebranched {
                         return Poll::Ready(Some(BlockQueueEvent::AcceptedAnnouncedBlock(hash)));
@@ -442,6 +606,7 @@ impl<N: Network> Stream for Inner<N> {
                 }
                 PushOpResult::Buffered(Ok(result), hash) => {
                     self.pending_blocks.remove(&hash);
+                    self.on_block_verified(&hash, true);
                     self.push_buffered();
                     if result == PushResult::Extended || result == PushResult::Rebranched {
                         return Poll::Ready(Some(BlockQueueEvent::AcceptedBufferedBlock(
@@ -453,9 +618,11 @@ impl<N: Network> Stream for Inner<N> {
                 PushOpResult::Missing(result, mut adopted_blocks, invalid_blocks) => {
                     for hash in &adopted_blocks {
                         self.pending_blocks.remove(hash);
+                        self.on_block_verified(hash, true);
                     }
                     for hash in &invalid_blocks {
                         self.pending_blocks.remove(hash);
+                        self.on_block_verified(hash, false);
                     }
 
                     self.remove_invalid_blocks(invalid_blocks);
@@ -474,6 +641,7 @@ impl<N: Network> Stream for Inner<N> {
                     //If there was a blockchain push error, we remove the block from the pending blocks
                     log::trace!("Head push operation failed because of {}", result);
                     self.pending_blocks.remove(&hash);
+                    self.on_block_verified(&hash, false);
                     return Poll::Ready(Some(BlockQueueEvent::RejectedBlock(hash)));
                 }
 
@@ -481,6 +649,7 @@ impl<N: Network> Stream for Inner<N> {
                     //If there was a blockchain push error, we remove the block from the pending blocks
                     log::trace!("Buffered push operation failed because of {}", result);
                     self.pending_blocks.remove(&hash);
+                    self.on_block_verified(&hash, false);
                     return Poll::Ready(Some(BlockQueueEvent::RejectedBlock(hash)));
                 }
             };
@@ -490,6 +659,16 @@ impl<N: Network> Stream for Inner<N> {
     }
 }
 
+struct RelayState {
+    relay: Arc<dyn BlockRelay>,
+
+    /// The blocks received from the relay.
+    blocks: BoxStream<'static, Block>,
+
+    /// Used to hand the blocks that extend our chain to the relay.
+    blockchain_events: NotifierStream<BlockchainEvent>,
+}
+
 #[pin_project]
 pub struct BlockQueue<N: Network, TReq: RequestComponent<N::PeerType>> {
     /// The Peer Tracking and Request Component.
@@ -498,19 +677,22 @@ pub struct BlockQueue<N: Network, TReq: RequestComponent<N::PeerType>> {
 
     /// The blocks received via gossipsub.
     #[pin]
-    block_stream: BlockStream<N>,
+    block_stream: BoxStream<'static, ValidatedBlock<N>>,
 
     /// The inner state of the block queue.
     inner: Inner<N>,
 
     /// The number of extended blocks through announcements.
     accepted_announcements: usize,
+
+    /// The external block relay, if any.
+    relay: Option<RelayState>,
 }
 
 impl<N: Network, TReq: RequestComponent<N::PeerType>> BlockQueue<N, TReq> {
     pub async fn new(
         config: BlockQueueConfig,
-        blockchain: Arc<RwLock<Blockchain>>,
+        blockchain: Arc<BlockchainLock>,
         network: Arc<N>,
         request_component: TReq,
     ) -> Self {
@@ -521,12 +703,34 @@ impl<N: Network, TReq: RequestComponent<N::PeerType>> BlockQueue<N, TReq> {
 
     pub fn with_block_stream(
         config: BlockQueueConfig,
-        blockchain: Arc<RwLock<Blockchain>>,
+        blockchain: Arc<BlockchainLock>,
         network: Arc<N>,
         request_component: TReq,
         block_stream: BlockStream<N>,
     ) -> Self {
         let current_macro_height = policy::last_macro_block(blockchain.read().block_number());
+
+        // Check the headers of gossiped blocks before they are queued, if enabled.
+        let block_stream = if config.header_check_workers > 0 {
+            BlockValidation::new(
+                Arc::clone(&blockchain),
+                Arc::clone(&network),
+                block_stream,
+                config.header_check_workers,
+                config.cut_through_relay,
+            )
+            .boxed()
+        } else {
+            block_stream
+                .map(|(block, pubsub_id)| ValidatedBlock {
+                    block,
+                    source: pubsub_id.propagation_source(),
+                    pubsub_id: Some(pubsub_id),
+                    relayed: false,
+                })
+                .boxed()
+        };
+
         Self {
             request_component,
             block_stream,
@@ -535,12 +739,18 @@ impl<N: Network, TReq: RequestComponent<N::PeerType>> BlockQueue<N, TReq> {
                 blockchain,
                 network,
                 buffer: BTreeMap::new(),
+                memory: MemoryGauge::new(),
                 push_ops: VecDeque::new(),
                 pending_blocks: BTreeSet::new(),
                 waker: None,
                 current_macro_height,
+                relayed: HashMap::new(),
+                deferred: HashMap::new(),
+                relay_pending: HashMap::new(),
+                relay_seen: HashMap::new(),
             },
             accepted_announcements: 0,
+            relay: None,
         }
     }
 
@@ -554,6 +764,11 @@ impl<N: Network, TReq: RequestComponent<N::PeerType>> BlockQueue<N, TReq> {
         })
     }
 
+    /// Returns the gauge counting the serialized size of the buffered blocks.
+    pub fn memory(&self) -> MemoryGauge {
+        self.inner.memory.clone()
+    }
+
     pub fn num_peers(&self) -> usize {
         self.request_component.num_peers()
     }
@@ -567,8 +782,23 @@ impl<N: Network, TReq: RequestComponent<N::PeerType>> BlockQueue<N, TReq> {
     }
 
     pub fn push_block(&mut self, block: Block, peer_id: <N::PeerType as Peer>::Id) {
-        self.inner
-            .on_block_announced(block, Pin::new(&mut self.request_component), peer_id, None);
+        self.inner.on_block_announced(
+            block,
+            Pin::new(&mut self.request_component),
+            Some(peer_id),
+            None,
+        );
+    }
+
+    /// Sets an external block relay that blocks are received from and sent to besides gossipsub.
+    pub fn set_block_relay(&mut self, relay: Arc<dyn BlockRelay>) {
+        let blocks = relay.receive();
+        let blockchain_events = self.inner.blockchain.write().notifier.as_stream();
+        self.relay = Some(RelayState {
+            relay,
+            blocks,
+            blockchain_events,
+        });
     }
 }
 
@@ -595,19 +825,29 @@ impl<N: Network, TReq: RequestComponent<N::PeerType>> Stream for BlockQueue<N, T
         // Then, try to get as many blocks from the gossipsub stream as possible.
         loop {
             match this.block_stream.as_mut().poll_next(cx) {
-                Poll::Ready(Some((block, pubsub_id))) => {
+                Poll::Ready(Some(validated)) => {
                     // Ignore all block announcements until there is at least one synced peer.
                     if num_peers > 0 {
                         log::debug!(
                             "Received block #{}.{} via gossipsub",
-                            block.block_number(),
-                            block.view_number()
+                            validated.block.block_number(),
+                            validated.block.view_number()
                         );
+
+                        // The acceptance of relayed blocks was already reported.
+                        let pubsub_id = match validated.pubsub_id {
+                            Some(pubsub_id) if validated.relayed => {
+                                this.inner.on_block_relayed(&validated.block, pubsub_id);
+                                None
+                            }
+                            pubsub_id => pubsub_id,
+                        };
+
                         this.inner.on_block_announced(
-                            block,
+                            validated.block,
                             this.request_component.as_mut(),
-                            pubsub_id.propagation_source(),
-                            Some(pubsub_id),
+                            Some(validated.source),
+                            pubsub_id,
                         );
                     }
                 }
@@ -617,6 +857,47 @@ impl<N: Network, TReq: RequestComponent<N::PeerType>> Stream for BlockQueue<N, T
             }
         }
 
+        // Then, exchange blocks with the external relay.
+        if let Some(relay) = this.relay.as_mut() {
+            while let Poll::Ready(Some(event)) = relay.blockchain_events.poll_next_unpin(cx) {
+                match event {
+                    BlockchainEvent::Extended(hash)
+                    | BlockchainEvent::Finalized(hash)
+                    | BlockchainEvent::EpochFinalized(hash) => {
+                        this.inner.relay_block(&*relay.relay, &hash)
+                    }
+                    BlockchainEvent::Rebranched(_, new_chain) => {
+                        for (hash, _) in new_chain {
+                            this.inner.relay_block(&*relay.relay, &hash);
+                        }
+                    }
+                }
+            }
+
+            let mut relay_closed = false;
+            loop {
+                match relay.blocks.poll_next_unpin(cx) {
+                    Poll::Ready(Some(block)) => {
+                        // Like gossiped blocks, they are ignored until there is a synced peer.
+                        if num_peers > 0 {
+                            this.inner
+                                .on_block_from_relay(block, this.request_component.as_mut());
+                        }
+                    }
+                    Poll::Ready(None) => {
+                        relay_closed = true;
+                        break;
+                    }
+                    Poll::Pending => break,
+                }
+            }
+
+            if relay_closed {
+                log::warn!("The block relay closed its stream of blocks, removing it");
+                *this.relay = None;
+            }
+        }
+
         // Then, read all the responses we got for our missing blocks requests.
         loop {
             match this.request_component.as_mut().poll_next(cx) {
@@ -0,0 +1,18 @@
+use futures::stream::BoxStream;
+
+use nimiq_block::Block;
+
+/// A transport for blocks besides gossipsub, e.g. a relay network between validators that forwards
+/// blocks with less latency.
+///
+/// Blocks received from the relay are queued like the ones received via gossipsub and are fully
+/// verified when they are pushed to the chain. A block that arrives on both paths is only pushed
+/// once. Every block that extends our chain is handed to the relay, except for the ones it sent
+/// to us.
+pub trait BlockRelay: Send + Sync + 'static {
+    /// Sends a block to the relay.
+    fn relay(&self, block: &Block);
+
+    /// Returns the blocks received from the relay. This is called once, when the relay is set.
+    fn receive(&self) -> BoxStream<'static, Block>;
+}
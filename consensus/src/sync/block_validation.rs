@@ -0,0 +1,279 @@
+use std::{
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use futures::future::{self, BoxFuture};
+use futures::stream::{FuturesOrdered, Stream, StreamExt};
+use futures::FutureExt;
+use tokio::task::spawn_blocking;
+
+use nimiq_block::{Block, BlockError};
+use nimiq_blockchain::{AbstractBlockchain, Blockchain, BlockchainLock, PushError};
+use nimiq_network_interface::{
+    network::{MsgAcceptance, Network, PubsubId},
+    peer::Peer,
+};
+use nimiq_primitives::policy;
+
+use crate::sync::block_queue::{BlockStream, BlockTopic};
+
+/// A block received via gossipsub that passed the validation pipeline.
+pub struct ValidatedBlock<N: Network> {
+    pub block: Block,
+    /// The peer that relayed the block to us.
+    pub source: <N::PeerType as Peer>::Id,
+    /// The gossipsub id of the block.
+    pub pubsub_id: Option<N::PubsubId>,
+    /// Whether the block was already accepted and relayed after its header check. If the full
+    /// verification fails, a follow-up reject must be reported for it.
+    pub relayed: bool,
+}
+
+/// The outcome of validating a gossiped block.
+enum Verdict {
+    /// The header of the block is valid.
+    Accept,
+    /// The block is invalid and dropped.
+    Reject,
+    /// The block can't be validated yet, e.g. because its parent is unknown. It is validated when
+    /// it is pushed to the chain.
+    Defer,
+}
+
+/// Validates the blocks received via gossipsub in stages before they are handed to the block
+/// queue:
+///
+///  1. Syntax: The version and the presence of the justification are checked.
+///  2. Known parent: Blocks whose parent we don't know are deferred to the block queue.
+///  3. Header: The VRF seed and the signature of the producer are verified.
+///
+/// The header checks run in parallel on blocking worker tasks, so they don't delay the blocks
+/// behind them. Invalid blocks are rejected and never reach the block queue. The body and the
+/// resulting state of a block are only verified when it is pushed, which is also when gossipsub is
+/// told to relay the block. With cut-through relay, blocks are relayed as soon as their header
+/// check passes instead. The blocks are yielded in the order they were received.
+pub struct BlockValidation<N: Network> {
+    blockchain: Arc<BlockchainLock>,
+    network: Arc<N>,
+    blocks: BlockStream<N>,
+    checks: FuturesOrdered<BoxFuture<'static, Option<ValidatedBlock<N>>>>,
+    max_checks: usize,
+    cut_through_relay: bool,
+    exhausted: bool,
+}
+
+impl<N: Network> BlockValidation<N> {
+    /// Creates a pipeline that checks up to `max_checks` block headers in parallel. If
+    /// `cut_through_relay` is set, blocks are relayed as soon as their header check passed.
+    pub fn new(
+        blockchain: Arc<BlockchainLock>,
+        network: Arc<N>,
+        blocks: BlockStream<N>,
+        max_checks: usize,
+        cut_through_relay: bool,
+    ) -> Self {
+        Self {
+            blockchain,
+            network,
+            blocks,
+            checks: FuturesOrdered::new(),
+            max_checks: max_checks.max(1),
+            cut_through_relay,
+            exhausted: false,
+        }
+    }
+
+    fn validate(
+        &self,
+        block: Block,
+        pubsub_id: N::PubsubId,
+    ) -> BoxFuture<'static, Option<ValidatedBlock<N>>> {
+        let source = pubsub_id.propagation_source();
+
+        let verdict = Self::check_syntax(&block)
+            .or_else(|| Self::check_parent(&self.blockchain.read(), &block));
+
+        let cut_through_relay = self.cut_through_relay;
+        if let Some(verdict) = verdict {
+            let validated = Self::report(
+                &self.network,
+                block,
+                source,
+                pubsub_id,
+                verdict,
+                cut_through_relay,
+            );
+            return future::ready(validated).boxed();
+        }
+
+        let blockchain = Arc::clone(&self.blockchain);
+        let network = Arc::clone(&self.network);
+        async move {
+            let (block, verdict) = spawn_blocking(move || {
+                let verdict = Self::check_header(&blockchain.read(), &block);
+                (block, verdict)
+            })
+            .await
+            .expect("Header check should not panic");
+
+            Self::report(
+                &network,
+                block,
+                source,
+                pubsub_id,
+                verdict,
+                cut_through_relay,
+            )
+        }
+        .boxed()
+    }
+
+    /// Checks the fields of the block that don't depend on the chain.
+    fn check_syntax(block: &Block) -> Option<Verdict> {
+        if block.version() != policy::VERSION {
+            log::debug!(
+                "Rejecting gossiped block {} - wrong version {}",
+                block,
+                block.version()
+            );
+            return Some(Verdict::Reject);
+        }
+
+        if block.justification().is_none() {
+            log::debug!("Rejecting gossiped block {} - no justification", block);
+            return Some(Verdict::Reject);
+        }
+
+        None
+    }
+
+    /// Defers blocks whose header can't be checked against the current chain.
+    fn check_parent(blockchain: &Blockchain, block: &Block) -> Option<Verdict> {
+        // Blocks of finalized batches are ignored by the block queue.
+        if block.block_number() <= policy::last_macro_block(blockchain.block_number()) {
+            return Some(Verdict::Defer);
+        }
+
+        if !blockchain.contains(block.parent_hash(), true) {
+            return Some(Verdict::Defer);
+        }
+
+        None
+    }
+
+    /// Verifies the header and the justification of a block whose parent is known.
+    fn check_header(blockchain: &Blockchain, block: &Block) -> Verdict {
+        let read_txn = blockchain.read_transaction();
+
+        let prev_info = match blockchain.get_chain_info(block.parent_hash(), false, Some(&read_txn))
+        {
+            Some(prev_info) => prev_info,
+            None => return Verdict::Defer,
+        };
+
+        let proposer_slot = match blockchain.get_proposer_at(
+            block.block_number(),
+            block.view_number(),
+            prev_info.head.seed().entropy(),
+            Some(&read_txn),
+        ) {
+            Some(slot) => slot,
+            None => return Verdict::Defer,
+        };
+        let signing_key = &proposer_slot.validator.signing_key;
+
+        let result = Blockchain::verify_block_header(
+            blockchain,
+            &block.header(),
+            signing_key,
+            Some(&read_txn),
+            true,
+        )
+        .and_then(|_| {
+            Blockchain::verify_block_justification(
+                blockchain,
+                block,
+                signing_key,
+                Some(&read_txn),
+                true,
+            )
+        });
+
+        match result {
+            Ok(()) => Verdict::Accept,
+            // The chain changed while the block was waiting for its check.
+            Err(PushError::Orphan) => Verdict::Defer,
+            Err(PushError::InvalidBlock(BlockError::FromTheFuture)) => Verdict::Defer,
+            Err(e) => {
+                log::debug!("Rejecting gossiped block {} - bad header: {}", block, e);
+                Verdict::Reject
+            }
+        }
+    }
+
+    /// Reports the verdict to the network. Returns the block if it should be handed to the block
+    /// queue.
+    fn report(
+        network: &Arc<N>,
+        block: Block,
+        source: <N::PeerType as Peer>::Id,
+        pubsub_id: N::PubsubId,
+        verdict: Verdict,
+        cut_through_relay: bool,
+    ) -> Option<ValidatedBlock<N>> {
+        match verdict {
+            Verdict::Accept if cut_through_relay => {
+                network.validate_message::<BlockTopic>(pubsub_id.clone(), MsgAcceptance::Accept);
+                Some(ValidatedBlock {
+                    block,
+                    source,
+                    pubsub_id: Some(pubsub_id),
+                    relayed: true,
+                })
+            }
+            Verdict::Reject => {
+                network.validate_message::<BlockTopic>(pubsub_id, MsgAcceptance::Reject);
+                None
+            }
+            Verdict::Accept | Verdict::Defer => Some(ValidatedBlock {
+                block,
+                source,
+                pubsub_id: Some(pubsub_id),
+                relayed: false,
+            }),
+        }
+    }
+}
+
+// The pipeline doesn't pin any of its fields.
+impl<N: Network> Unpin for BlockValidation<N> {}
+
+impl<N: Network> Stream for BlockValidation<N> {
+    type Item = ValidatedBlock<N>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            // Start checking new blocks while there is capacity.
+            while !self.exhausted && self.checks.len() < self.max_checks {
+                match self.blocks.poll_next_unpin(cx) {
+                    Poll::Ready(Some((block, pubsub_id))) => {
+                        let check = self.validate(block, pubsub_id);
+                        self.checks.push(check);
+                    }
+                    Poll::Ready(None) => self.exhausted = true,
+                    Poll::Pending => break,
+                }
+            }
+
+            match self.checks.poll_next_unpin(cx) {
+                Poll::Ready(Some(Some(validated))) => return Poll::Ready(Some(validated)),
+                // The block was rejected, continue with the next one.
+                Poll::Ready(Some(None)) => {}
+                Poll::Ready(None) if self.exhausted => return Poll::Ready(None),
+                Poll::Ready(None) | Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
@@ -6,23 +6,25 @@ use std::sync::{Arc, Weak};
 
 use futures::task::{Context, Poll};
 use futures::{FutureExt, Stream, StreamExt};
-use parking_lot::RwLock;
 
+use beserial::Serialize;
 use nimiq_block::MacroBlock;
-use nimiq_blockchain::{AbstractBlockchain, Blockchain, ExtendedTransaction, CHUNK_SIZE};
+use nimiq_blockchain::{AbstractBlockchain, BlockchainLock, CHUNK_SIZE};
 use nimiq_hash::Blake2bHash;
 use nimiq_network_interface::prelude::Peer;
 use nimiq_utils::math::CeilingDiv;
 
 use crate::consensus_agent::ConsensusAgent;
-use crate::messages::{BatchSetInfo, HistoryChunk};
+use crate::messages::{BatchSetInfo, HistoryChunk, ResponseStatus};
+use crate::sync::history::staging::StagedHistory;
+use crate::sync::history::sync::HistorySyncConfig;
 use crate::sync::sync_queue::{SyncQueue, SyncQueuePeer};
 
 struct PendingBatchSet {
     block: MacroBlock,
     history_len: usize,
     history_offset: usize,
-    history: Vec<ExtendedTransaction>,
+    history: StagedHistory,
 }
 impl PendingBatchSet {
     fn is_complete(&self) -> bool {
@@ -36,7 +38,7 @@ impl PendingBatchSet {
 
 pub(crate) struct BatchSet {
     pub block: MacroBlock,
-    pub history: Vec<ExtendedTransaction>,
+    pub history: StagedHistory,
 }
 
 impl std::fmt::Debug for BatchSet {
@@ -48,6 +50,14 @@ impl std::fmt::Debug for BatchSet {
     }
 }
 
+impl BatchSet {
+    /// The serialized size of the macro block and the history held in memory, as an estimate of
+    /// the memory the batch set holds.
+    pub fn size(&self) -> usize {
+        self.block.serialized_size() + self.history.memory_size()
+    }
+}
+
 impl From<PendingBatchSet> for BatchSet {
     fn from(batch_set: PendingBatchSet) -> Self {
         Self {
@@ -72,25 +82,27 @@ pub(crate) struct SyncCluster<TPeer: Peer> {
     pending_batch_sets: VecDeque<PendingBatchSet>,
     num_epochs_finished: usize,
 
-    blockchain: Arc<RwLock<Blockchain>>,
+    blockchain: Arc<BlockchainLock>,
+    config: HistorySyncConfig,
 }
 
 impl<TPeer: Peer + 'static> SyncCluster<TPeer> {
-    const NUM_PENDING_BATCH_SETS: usize = 5;
-    const NUM_PENDING_CHUNKS: usize = 12;
+    /// Histories with more items than this are staged on disk while they are downloaded.
+    const HISTORY_SPILL_THRESHOLD: usize = 16 * CHUNK_SIZE;
 
     pub(crate) fn new(
         epoch_ids: Vec<Blake2bHash>,
         first_epoch_number: usize,
         peers: Vec<SyncQueuePeer<TPeer>>,
-        blockchain: Arc<RwLock<Blockchain>>,
+        blockchain: Arc<BlockchainLock>,
+        config: HistorySyncConfig,
     ) -> Self {
         let id = SYNC_CLUSTER_ID.fetch_add(1, Ordering::SeqCst);
 
         let batch_set_queue = SyncQueue::new(
             epoch_ids.clone(),
             peers.clone(),
-            Self::NUM_PENDING_BATCH_SETS,
+            config.num_pending_epochs,
             |id, peer| {
                 async move {
                     if let Some(peer) = Weak::upgrade(&peer) {
@@ -108,7 +120,7 @@ impl<TPeer: Peer + 'static> SyncCluster<TPeer> {
         let history_queue = SyncQueue::new(
             Vec::<(u32, u32, usize)>::new(),
             peers,
-            Self::NUM_PENDING_CHUNKS,
+            config.num_pending_chunks,
             move |(epoch_number, block_number, chunk_index), peer| {
                 async move {
                     if let Some(peer) = Weak::upgrade(&peer) {
@@ -116,6 +128,9 @@ impl<TPeer: Peer + 'static> SyncCluster<TPeer> {
                             .request_history_chunk(epoch_number, block_number, chunk_index)
                             .await
                             .ok()
+                            // A peer that is still busy is treated like a failed request, so that
+                            // the chunk is requested from another peer.
+                            .filter(|chunk| chunk.status != ResponseStatus::Busy)
                             .map(|chunk| (epoch_number, chunk));
                     }
                     None
@@ -129,9 +144,10 @@ impl<TPeer: Peer + 'static> SyncCluster<TPeer> {
             first_epoch_number,
             batch_set_queue,
             history_queue,
-            pending_batch_sets: VecDeque::with_capacity(Self::NUM_PENDING_BATCH_SETS),
+            pending_batch_sets: VecDeque::with_capacity(config.num_pending_epochs),
             num_epochs_finished: 0,
             blockchain,
+            config,
         }
     }
 
@@ -160,7 +176,10 @@ impl<TPeer: Peer + 'static> SyncCluster<TPeer> {
             block,
             history_len: epoch.history_len as usize,
             history_offset: 0,
-            history: Vec::new(),
+            history: StagedHistory::new(
+                Self::HISTORY_SPILL_THRESHOLD,
+                self.config.staging_dir.clone(),
+            ),
         };
 
         // If the block is in the same epoch, add already known history.
@@ -217,8 +236,14 @@ impl<TPeer: Peer + 'static> SyncCluster<TPeer> {
         }
 
         // Add the received history chunk to the pending epoch.
-        let mut chunk = chunk.history;
-        epoch.history.append(&mut chunk);
+        if let Err(e) = epoch.history.append(chunk.history) {
+            log::error!(
+                "Failed to stage history for epoch #{}: {}",
+                epoch.epoch_number(),
+                e
+            );
+            return Err(SyncClusterResult::Error);
+        }
 
         if epoch.history_len > CHUNK_SIZE {
             log::info!(
@@ -282,6 +307,7 @@ impl<TPeer: Peer + 'static> SyncCluster<TPeer> {
             first_epoch_number,
             self.batch_set_queue.peers.clone(),
             Arc::clone(&self.blockchain),
+            self.config.clone(),
         )
     }
 
@@ -327,13 +353,20 @@ impl<TPeer: Peer + 'static> SyncCluster<TPeer> {
     pub(crate) fn num_epochs_finished(&self) -> usize {
         self.num_epochs_finished
     }
+
+    /// Returns the id of the given epoch, if the cluster contains it.
+    pub(crate) fn epoch_id(&self, epoch_number: usize) -> Option<&Blake2bHash> {
+        epoch_number
+            .checked_sub(self.first_epoch_number)
+            .and_then(|index| self.epoch_ids.get(index))
+    }
 }
 
 impl<TPeer: Peer + 'static> Stream for SyncCluster<TPeer> {
     type Item = Result<BatchSet, SyncClusterResult>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        while self.pending_batch_sets.len() < Self::NUM_PENDING_BATCH_SETS {
+        while self.pending_batch_sets.len() < self.config.num_pending_epochs {
             let result = match self.batch_set_queue.poll_next_unpin(cx) {
                 Poll::Ready(Some(result)) => result,
                 _ => break,
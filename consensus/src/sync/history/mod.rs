@@ -1,6 +1,8 @@
 mod cluster;
+mod staging;
 mod sync;
 mod sync_clustering;
+mod sync_forks;
 mod sync_stream;
 
-pub use sync::{HistorySync, HistorySyncReturn};
+pub use sync::{HistorySync, HistorySyncConfig, HistorySyncReturn};
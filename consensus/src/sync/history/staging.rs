@@ -0,0 +1,183 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+use beserial::{Deserialize, Serialize, SerializingError};
+use nimiq_blockchain::history_sync::HistorySource;
+use nimiq_blockchain::ExtendedTransaction;
+
+/// The history of an epoch while it is being downloaded.
+///
+/// Small histories are kept in memory. Once a history grows beyond the spill threshold, it is moved
+/// to an anonymous temporary file and all further chunks are appended to that file. When the epoch
+/// is pushed to the blockchain, the history is read back from the file as it is iterated, so epochs
+/// with millions of transactions never occupy memory at once.
+pub(crate) struct StagedHistory {
+    spill_threshold: usize,
+    dir: Option<PathBuf>,
+    len: usize,
+    memory: Vec<ExtendedTransaction>,
+    file: Option<BufWriter<File>>,
+}
+
+impl StagedHistory {
+    /// Creates an empty history that is moved to a temporary file in `dir` once it holds more than
+    /// `spill_threshold` items. Without a directory, the system's temporary directory is used.
+    pub fn new(spill_threshold: usize, dir: Option<PathBuf>) -> Self {
+        Self {
+            spill_threshold,
+            dir,
+            len: 0,
+            memory: Vec::new(),
+            file: None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether the history was moved to disk.
+    pub fn is_spilled(&self) -> bool {
+        self.file.is_some()
+    }
+
+    /// The serialized size of the items held in memory.
+    pub fn memory_size(&self) -> usize {
+        self.memory.iter().map(Serialize::serialized_size).sum()
+    }
+
+    /// Appends the items of a verified history chunk.
+    pub fn append(&mut self, mut items: Vec<ExtendedTransaction>) -> Result<(), SerializingError> {
+        if self.file.is_none() && self.memory.len() + items.len() <= self.spill_threshold {
+            self.len += items.len();
+            self.memory.append(&mut items);
+            return Ok(());
+        }
+
+        let file = match &mut self.file {
+            Some(file) => file,
+            None => {
+                let file = match &self.dir {
+                    Some(dir) => tempfile::tempfile_in(dir)?,
+                    None => tempfile::tempfile()?,
+                };
+                let mut file = BufWriter::new(file);
+                for item in &self.memory {
+                    item.serialize(&mut file)?;
+                }
+                self.memory = Vec::new();
+                self.file.insert(file)
+            }
+        };
+
+        // The history might have been read in the meantime.
+        file.seek(SeekFrom::End(0))?;
+        for item in &items {
+            item.serialize(file)?;
+        }
+        self.len += items.len();
+
+        Ok(())
+    }
+}
+
+impl HistorySource for StagedHistory {
+    fn iter(
+        &mut self,
+    ) -> Box<dyn Iterator<Item = Result<ExtendedTransaction, SerializingError>> + '_> {
+        let len = self.len;
+        let file = match &mut self.file {
+            Some(file) => file,
+            None => return Box::new(self.memory.iter().cloned().map(Ok)),
+        };
+
+        // Read the file from the start, after writing out the buffered items.
+        let rewind = file.flush().and_then(|_| {
+            let mut file: &File = file.get_ref();
+            file.seek(SeekFrom::Start(0))
+        });
+        if let Err(e) = rewind {
+            return Box::new(std::iter::once(Err(e.into())));
+        }
+
+        let mut reader = BufReader::new(file.get_ref());
+        Box::new((0..len).map(move |_| Deserialize::deserialize(&mut reader)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nimiq_blockchain::ExtendedTransaction;
+    use nimiq_keys::Address;
+    use nimiq_primitives::coin::Coin;
+    use nimiq_primitives::networks::NetworkId;
+    use nimiq_transaction::Transaction;
+
+    use super::*;
+
+    fn read(history: &mut StagedHistory) -> Vec<ExtendedTransaction> {
+        history.iter().collect::<Result<_, _>>().unwrap()
+    }
+
+    fn ext_txs(first_block: u32, num: u32) -> Vec<ExtendedTransaction> {
+        (first_block..first_block + num)
+            .flat_map(|block_number| {
+                let tx = Transaction::new_basic(
+                    Address::from([1u8; 20]),
+                    Address::from([2u8; 20]),
+                    Coin::from_u64_unchecked(block_number as u64),
+                    Coin::ZERO,
+                    block_number,
+                    NetworkId::UnitAlbatross,
+                );
+                ExtendedTransaction::from(
+                    NetworkId::UnitAlbatross,
+                    block_number,
+                    0,
+                    vec![tx],
+                    vec![],
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn it_keeps_small_histories_in_memory() {
+        let mut history = StagedHistory::new(10, None);
+        history.append(ext_txs(1, 4)).unwrap();
+        history.append(ext_txs(5, 6)).unwrap();
+
+        assert!(!history.is_spilled());
+        assert_eq!(history.len(), 10);
+        assert_eq!(read(&mut history), ext_txs(1, 10));
+    }
+
+    #[test]
+    fn it_spills_large_histories_to_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut history = StagedHistory::new(10, Some(dir.path().to_path_buf()));
+        history.append(ext_txs(1, 8)).unwrap();
+        history.append(ext_txs(9, 8)).unwrap();
+        history.append(ext_txs(17, 8)).unwrap();
+
+        assert!(history.is_spilled());
+        assert_eq!(history.len(), 24);
+        assert_eq!(history.memory_size(), 0);
+        assert_eq!(read(&mut history), ext_txs(1, 24));
+    }
+
+    #[test]
+    fn spilled_histories_can_be_read_repeatedly() {
+        let mut history = StagedHistory::new(4, None);
+        history.append(ext_txs(1, 8)).unwrap();
+
+        // The history is read several times while it is pushed.
+        assert_eq!(history.iter().count(), 8);
+        assert_eq!(read(&mut history), ext_txs(1, 8));
+
+        // Items appended after reading end up at the end.
+        history.append(ext_txs(9, 2)).unwrap();
+        assert_eq!(read(&mut history), ext_txs(1, 10));
+    }
+}
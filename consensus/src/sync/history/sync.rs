@@ -1,19 +1,21 @@
 use std::collections::{HashMap, VecDeque};
-use std::sync::Arc;
+use std::path::PathBuf;
+use std::sync::{Arc, Weak};
 use std::task::Waker;
 
 use futures::future::BoxFuture;
 use futures::stream::FuturesUnordered;
 use futures::FutureExt;
-use parking_lot::RwLock;
 use tokio_stream::wrappers::BroadcastStream;
 
-use nimiq_blockchain::Blockchain;
+use nimiq_blockchain::BlockchainLock;
 use nimiq_hash::Blake2bHash;
 use nimiq_network_interface::prelude::{Network, NetworkEvent, Peer};
+use nimiq_utils::memory::MemoryGauge;
 
 use crate::consensus_agent::ConsensusAgent;
 use crate::sync::history::cluster::{SyncCluster, SyncClusterResult};
+use crate::sync::history::sync_forks::{ForkCandidate, ForkCheck};
 use crate::sync::request_component::HistorySyncStream;
 
 pub(crate) struct EpochIds<TPeer: Peer> {
@@ -42,13 +44,46 @@ impl<TPeer: Peer> EpochIds<TPeer> {
     }
 }
 
+/// Tunes how much the history sync downloads in parallel.
+#[derive(Clone, Debug)]
+pub struct HistorySyncConfig {
+    /// How many epochs (macro blocks and history lengths) a cluster downloads at the same time.
+    pub num_pending_epochs: usize,
+
+    /// How many history chunks a cluster downloads at the same time.
+    pub num_pending_chunks: usize,
+
+    /// How many clusters of epoch ids are kept. Epoch ids of further peers are only processed
+    /// once there is room for their clusters.
+    pub max_clusters: usize,
+
+    /// The directory in which large histories are staged while they are downloaded, usually the
+    /// database directory. Without it, the system's temporary directory is used.
+    pub staging_dir: Option<PathBuf>,
+
+    /// Whether the requests to the peers carry trace IDs, see [`ConsensusAgent::with_tracing`].
+    pub trace_requests: bool,
+}
+
+impl Default for HistorySyncConfig {
+    fn default() -> Self {
+        Self {
+            num_pending_epochs: 5,
+            num_pending_chunks: 12,
+            max_clusters: 100,
+            staging_dir: None,
+            trace_requests: false,
+        }
+    }
+}
+
 pub(crate) enum Job<TPeer: Peer> {
     PushBatchSet(usize, Blake2bHash, BoxFuture<'static, SyncClusterResult>),
     FinishCluster(SyncCluster<TPeer>, SyncClusterResult),
 }
 
 pub struct HistorySync<TNetwork: Network> {
-    pub(crate) blockchain: Arc<RwLock<Blockchain>>,
+    pub(crate) blockchain: Arc<BlockchainLock>,
     pub(crate) network_event_rx: BroadcastStream<NetworkEvent<TNetwork::PeerType>>,
     pub(crate) agents:
         HashMap<Arc<TNetwork::PeerType>, (Arc<ConsensusAgent<TNetwork::PeerType>>, usize)>,
@@ -59,6 +94,17 @@ pub struct HistorySync<TNetwork: Network> {
     pub(crate) active_cluster: Option<SyncCluster<TNetwork::PeerType>>,
     pub(crate) job_queue: VecDeque<Job<TNetwork::PeerType>>,
     pub(crate) waker: Option<Waker>,
+    /// Counts the batch sets that were downloaded but not pushed yet. While it is over its cap,
+    /// no further batch sets are taken from the active cluster.
+    pub(crate) memory: MemoryGauge,
+    pub(crate) config: HistorySyncConfig,
+    /// All peers that sent us epoch ids. The election blocks of forks are requested from them.
+    pub(crate) known_agents:
+        HashMap<<TNetwork::PeerType as Peer>::Id, Weak<ConsensusAgent<TNetwork::PeerType>>>,
+    pub(crate) fork_checks: FuturesUnordered<BoxFuture<'static, ForkCheck<TNetwork::PeerType>>>,
+    /// The competing election block ids at a divergence point, by epoch number.
+    pub(crate) fork_candidates:
+        HashMap<usize, HashMap<Blake2bHash, ForkCandidate<<TNetwork::PeerType as Peer>::Id>>>,
 }
 
 pub enum HistorySyncReturn<TPeer: Peer> {
@@ -67,12 +113,19 @@ pub enum HistorySyncReturn<TPeer: Peer> {
 }
 
 impl<TNetwork: Network> HistorySync<TNetwork> {
-    pub(crate) const MAX_CLUSTERS: usize = 100;
     pub(crate) const MAX_QUEUED_JOBS: usize = 4;
 
     pub fn new(
-        blockchain: Arc<RwLock<Blockchain>>,
+        blockchain: Arc<BlockchainLock>,
         network_event_rx: BroadcastStream<NetworkEvent<TNetwork::PeerType>>,
+    ) -> Self {
+        Self::with_config(blockchain, network_event_rx, HistorySyncConfig::default())
+    }
+
+    pub fn with_config(
+        blockchain: Arc<BlockchainLock>,
+        network_event_rx: BroadcastStream<NetworkEvent<TNetwork::PeerType>>,
+        config: HistorySyncConfig,
     ) -> Self {
         Self {
             blockchain,
@@ -84,9 +137,18 @@ impl<TNetwork: Network> HistorySync<TNetwork> {
             active_cluster: None,
             job_queue: VecDeque::new(),
             waker: None,
+            memory: MemoryGauge::new(),
+            config,
+            known_agents: HashMap::new(),
+            fork_checks: FuturesUnordered::new(),
+            fork_candidates: HashMap::new(),
         }
     }
 
+    pub fn memory(&self) -> MemoryGauge {
+        self.memory.clone()
+    }
+
     pub fn agents(&self) -> impl Iterator<Item = &Arc<ConsensusAgent<TNetwork::PeerType>>> {
         self.agents.values().map(|(agent, _)| agent)
     }
@@ -122,55 +184,55 @@ impl<TNetwork: Network> HistorySyncStream<TNetwork::PeerType> for HistorySync<TN
 mod tests {
     use std::sync::Arc;
 
-    use parking_lot::RwLock;
-
-    use nimiq_blockchain::Blockchain;
+    use nimiq_block::Block;
+    use nimiq_blockchain::{AbstractBlockchain, Blockchain, BlockchainLock};
     use nimiq_database::volatile::VolatileEnvironment;
     use nimiq_hash::Blake2bHash;
-    use nimiq_network_interface::prelude::Network;
+    use nimiq_network_interface::prelude::{Network, Peer};
     use nimiq_network_mock::{MockHub, MockNetwork, MockPeer};
     use nimiq_primitives::networks::NetworkId;
     use nimiq_utils::time::OffsetTime;
 
     use crate::consensus_agent::ConsensusAgent;
     use crate::sync::history::sync::EpochIds;
+    use crate::sync::history::sync_forks::{verify_election_block, ForkCheck, ForkVerdict};
     use crate::sync::history::HistorySync;
 
-    #[tokio::test]
-    async fn it_can_cluster_epoch_ids() {
-        fn generate_epoch_ids(
-            agent: &Arc<ConsensusAgent<MockPeer>>,
-            len: usize,
-            first_epoch_number: usize,
-            diverge_at: Option<usize>,
-        ) -> EpochIds<MockPeer> {
-            let mut ids = vec![];
-            for i in first_epoch_number..first_epoch_number + len {
-                let mut epoch_id = [0u8; 32];
-                epoch_id[0..8].copy_from_slice(&i.to_le_bytes());
-
-                if diverge_at
-                    .map(|d| i >= d + first_epoch_number)
-                    .unwrap_or(false)
-                {
-                    epoch_id[9] = 1;
-                }
-
-                ids.push(Blake2bHash::from(epoch_id));
+    fn generate_epoch_ids(
+        agent: &Arc<ConsensusAgent<MockPeer>>,
+        len: usize,
+        first_epoch_number: usize,
+        diverge_at: Option<usize>,
+    ) -> EpochIds<MockPeer> {
+        let mut ids = vec![];
+        for i in first_epoch_number..first_epoch_number + len {
+            let mut epoch_id = [0u8; 32];
+            epoch_id[0..8].copy_from_slice(&i.to_le_bytes());
+
+            if diverge_at
+                .map(|d| i >= d + first_epoch_number)
+                .unwrap_or(false)
+            {
+                epoch_id[9] = 1;
             }
 
-            EpochIds {
-                locator_found: true,
-                ids,
-                checkpoint_id: None,
-                first_epoch_number,
-                sender: Arc::clone(agent),
-            }
+            ids.push(Blake2bHash::from(epoch_id));
         }
 
+        EpochIds {
+            locator_found: true,
+            ids,
+            checkpoint_id: None,
+            first_epoch_number,
+            sender: Arc::clone(agent),
+        }
+    }
+
+    #[tokio::test]
+    async fn it_can_cluster_epoch_ids() {
         let time = Arc::new(OffsetTime::new());
         let env1 = VolatileEnvironment::new(10).unwrap();
-        let blockchain = Arc::new(RwLock::new(
+        let blockchain = Arc::new(BlockchainLock::new(
             Blockchain::new(env1, NetworkId::UnitAlbatross, time).unwrap(),
         ));
 
@@ -189,7 +251,7 @@ mod tests {
             .collect();
 
         fn run_test<F>(
-            blockchain: &Arc<RwLock<Blockchain>>,
+            blockchain: &Arc<BlockchainLock>,
             net: &Arc<MockNetwork>,
             epoch_ids1: EpochIds<MockPeer>,
             epoch_ids2: EpochIds<MockPeer>,
@@ -349,4 +411,142 @@ mod tests {
             false,
         ); // TODO: for a symmetric check, blockchain state would need to change
     }
+
+    fn fork_setup() -> (
+        Arc<BlockchainLock>,
+        Arc<MockNetwork>,
+        Vec<Arc<ConsensusAgent<MockPeer>>>,
+    ) {
+        let time = Arc::new(OffsetTime::new());
+        let env = VolatileEnvironment::new(10).unwrap();
+        let blockchain = Arc::new(BlockchainLock::new(
+            Blockchain::new(env, NetworkId::UnitAlbatross, time).unwrap(),
+        ));
+
+        let mut hub = MockHub::default();
+        let net = Arc::new(hub.new_network());
+        let other_nets: Vec<_> = (0..4).map(|_| Arc::new(hub.new_network())).collect();
+        for other_net in &other_nets {
+            net.dial_mock(other_net);
+        }
+        let consensus_agents: Vec<_> = net
+            .get_peers()
+            .into_iter()
+            .map(ConsensusAgent::new)
+            .map(Arc::new)
+            .collect();
+
+        (blockchain, net, consensus_agents)
+    }
+
+    /// Clusters the epoch ids of one peer that follows the valid chain and of three peers that
+    /// follow a fork from the first epoch on.
+    fn cluster_fork(
+        blockchain: &Arc<BlockchainLock>,
+        net: &Arc<MockNetwork>,
+        consensus_agents: &[Arc<ConsensusAgent<MockPeer>>],
+    ) -> (
+        HistorySync<MockNetwork>,
+        EpochIds<MockPeer>,
+        EpochIds<MockPeer>,
+    ) {
+        let valid = generate_epoch_ids(&consensus_agents[0], 10, 1, None);
+        let forked: Vec<_> = consensus_agents[1..]
+            .iter()
+            .map(|agent| generate_epoch_ids(agent, 10, 1, Some(0)))
+            .collect();
+
+        let mut sync =
+            HistorySync::<MockNetwork>::new(Arc::clone(blockchain), net.subscribe_events());
+        sync.cluster_epoch_ids(valid.clone());
+        for epoch_ids in &forked {
+            sync.cluster_epoch_ids(epoch_ids.clone());
+        }
+
+        (sync, valid, forked[0].clone())
+    }
+
+    #[tokio::test]
+    async fn it_verifies_forks_instead_of_counting_peers() {
+        let (blockchain, net, consensus_agents) = fork_setup();
+        let (sync, _, _) = cluster_fork(&blockchain, &net, &consensus_agents);
+
+        // The majority of the peers doesn't decide the fork, the election block of each id is
+        // requested instead.
+        assert_eq!(sync.epoch_clusters.len(), 2);
+        assert_eq!(sync.fork_checks.len(), 2);
+        for agent in &consensus_agents {
+            assert!(sync.agents.contains_key(&agent.peer));
+        }
+    }
+
+    #[tokio::test]
+    async fn it_does_not_verify_forks_at_later_epochs() {
+        let (blockchain, net, consensus_agents) = fork_setup();
+
+        let mut sync =
+            HistorySync::<MockNetwork>::new(Arc::clone(&blockchain), net.subscribe_events());
+        sync.cluster_epoch_ids(generate_epoch_ids(&consensus_agents[0], 10, 1, None));
+        sync.cluster_epoch_ids(generate_epoch_ids(&consensus_agents[1], 10, 1, Some(4)));
+
+        assert_eq!(sync.epoch_clusters.len(), 3);
+        assert!(sync.fork_checks.is_empty());
+    }
+
+    #[tokio::test]
+    async fn it_discards_forks_once_an_election_block_is_valid() {
+        let (blockchain, net, consensus_agents) = fork_setup();
+        let (mut sync, valid, _) = cluster_fork(&blockchain, &net, &consensus_agents);
+
+        sync.on_fork_check(ForkCheck {
+            epoch_number: 1,
+            id: valid.ids[0].clone(),
+            peer_id: consensus_agents[0].peer.id(),
+            verdict: ForkVerdict::Valid,
+        });
+        sync.resolve_forks();
+
+        assert_eq!(sync.epoch_clusters.len(), 1);
+        assert_eq!(sync.epoch_clusters[0].epoch_ids, valid.ids);
+        // The peers on the fork might just not know better, so they are not punished.
+        for agent in &consensus_agents {
+            assert!(sync.agents.contains_key(&agent.peer));
+        }
+    }
+
+    #[tokio::test]
+    async fn it_closes_peers_serving_invalid_election_blocks() {
+        let (blockchain, net, consensus_agents) = fork_setup();
+        let (mut sync, valid, forked) = cluster_fork(&blockchain, &net, &consensus_agents);
+
+        sync.on_fork_check(ForkCheck {
+            epoch_number: 1,
+            id: forked.ids[0].clone(),
+            peer_id: consensus_agents[1].peer.id(),
+            verdict: ForkVerdict::Invalid,
+        });
+
+        assert_eq!(sync.epoch_clusters.len(), 1);
+        assert_eq!(sync.epoch_clusters[0].epoch_ids, valid.ids);
+        assert!(!sync.agents.contains_key(&consensus_agents[1].peer));
+        assert!(sync.agents.contains_key(&consensus_agents[0].peer));
+    }
+
+    #[tokio::test]
+    async fn it_rejects_blocks_that_are_not_the_next_election_block() {
+        let (blockchain, _, _) = fork_setup();
+        let genesis = blockchain.read().election_head();
+        let genesis_hash = genesis.hash();
+
+        assert!(!verify_election_block(
+            &blockchain,
+            &genesis_hash,
+            &Block::Macro(genesis)
+        ));
+        assert!(!verify_election_block(
+            &blockchain,
+            &Blake2bHash::default(),
+            &Block::Macro(blockchain.read().election_head())
+        ));
+    }
 }
@@ -1,9 +1,7 @@
 use std::collections::VecDeque;
 use std::sync::{Arc, Weak};
 
-use parking_lot::RwLock;
-
-use nimiq_blockchain::{AbstractBlockchain, Blockchain};
+use nimiq_blockchain::{AbstractBlockchain, BlockchainLock};
 use nimiq_hash::Blake2bHash;
 use nimiq_network_interface::prelude::{CloseReason, Network, Peer};
 
@@ -17,7 +15,7 @@ use crate::sync::sync_queue::SyncQueuePeer;
 
 impl<TNetwork: Network> HistorySync<TNetwork> {
     pub(crate) async fn request_epoch_ids(
-        blockchain: Arc<RwLock<Blockchain>>,
+        blockchain: Arc<BlockchainLock>,
         agent: Arc<ConsensusAgent<TNetwork::PeerType>>,
     ) -> Option<EpochIds<TNetwork::PeerType>> {
         let (locators, epoch_number) = {
@@ -104,6 +102,11 @@ impl<TNetwork: Network> HistorySync<TNetwork> {
         &mut self,
         mut epoch_ids: EpochIds<TNetwork::PeerType>,
     ) -> Option<Arc<ConsensusAgent<TNetwork::PeerType>>> {
+        self.known_agents.insert(
+            epoch_ids.sender.peer.id(),
+            Arc::downgrade(&epoch_ids.sender),
+        );
+
         // Read our current blockchain state.
         let (our_epoch_id, our_epoch_number) = {
             let blockchain = self.blockchain.read();
@@ -124,7 +127,12 @@ impl<TNetwork: Network> HistorySync<TNetwork> {
                 let peers_epoch_id =
                     &epoch_ids.ids[our_epoch_number - epoch_ids.first_epoch_number];
                 if our_epoch_id != *peers_epoch_id {
-                    // TODO Actually ban the peer.
+                    debug!(
+                        "Peer {:?} contradicts our election head at #{}, closing it",
+                        epoch_ids.sender.peer.id(),
+                        our_epoch_number
+                    );
+                    epoch_ids.sender.peer.close(CloseReason::MaliciousPeer);
                     return Some(epoch_ids.sender);
                 }
 
@@ -300,6 +308,7 @@ impl<TNetwork: Network> HistorySync<TNetwork> {
                     agent: Arc::downgrade(&agent),
                 }],
                 Arc::clone(&self.blockchain),
+                self.config.clone(),
             ));
             // Don't increment the num_clusters here, as this is done in the loop later on.
         }
@@ -341,6 +350,7 @@ impl<TNetwork: Network> HistorySync<TNetwork> {
                         agent: Arc::downgrade(&agent),
                     }],
                     Arc::clone(&self.blockchain),
+                    self.config.clone(),
                 );
                 self.checkpoint_clusters.push_back(cluster);
                 num_clusters += 1;
@@ -371,6 +381,9 @@ impl<TNetwork: Network> HistorySync<TNetwork> {
         // Add buffered clusters to sync_clusters.
         self.epoch_clusters.append(&mut new_clusters);
 
+        // The new ids might have created or decided a fork.
+        self.resolve_forks();
+
         None
     }
 
@@ -391,7 +404,7 @@ impl<TNetwork: Network> HistorySync<TNetwork> {
 
     fn find_best_cluster(
         clusters: &mut VecDeque<SyncCluster<TNetwork::PeerType>>,
-        blockchain: &Arc<RwLock<Blockchain>>,
+        blockchain: &Arc<BlockchainLock>,
     ) -> Option<SyncCluster<TNetwork::PeerType>> {
         if clusters.is_empty() {
             return None;
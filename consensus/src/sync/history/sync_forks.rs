@@ -0,0 +1,317 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Weak};
+
+use futures::task::{Context, Poll};
+use futures::{FutureExt, StreamExt};
+
+use nimiq_block::{Block, TendermintProof};
+use nimiq_blockchain::{AbstractBlockchain, BlockchainLock};
+use nimiq_hash::{Blake2bHash, Hash};
+use nimiq_network_interface::prelude::{CloseReason, Network, Peer};
+use nimiq_primitives::policy;
+
+use crate::consensus_agent::ConsensusAgent;
+use crate::sync::history::sync::Job;
+use crate::sync::history::HistorySync;
+
+type PeerId<TNetwork> = <<TNetwork as Network>::PeerType as Peer>::Id;
+
+/// The outcome of requesting the election block of a fork from a peer that claims it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ForkVerdict {
+    /// The peer served a valid election block with the claimed id.
+    Valid,
+    /// The peer served a block that isn't a valid election block with the claimed id.
+    Invalid,
+    /// The peer didn't serve the block.
+    Unavailable,
+}
+
+/// The result of checking the election block with the given id at a divergence point.
+pub(crate) struct ForkCheck<TPeer: Peer> {
+    pub epoch_number: usize,
+    pub id: Blake2bHash,
+    pub peer_id: TPeer::Id,
+    pub verdict: ForkVerdict,
+}
+
+/// The state of an election block id that competes with others at a divergence point.
+pub(crate) struct ForkCandidate<TPeerId> {
+    /// The peers that were asked for the election block.
+    asked: HashSet<TPeerId>,
+    /// Whether a request for the election block is in flight.
+    pending: bool,
+    /// Whether the election block is valid, once a peer served it.
+    valid: Option<bool>,
+}
+
+impl<TPeerId> Default for ForkCandidate<TPeerId> {
+    fn default() -> Self {
+        ForkCandidate {
+            asked: HashSet::new(),
+            pending: false,
+            valid: None,
+        }
+    }
+}
+
+impl<TNetwork: Network> HistorySync<TNetwork> {
+    /// Looks for queued clusters that compete at the epoch following our election head and tries
+    /// to resolve the fork between them.
+    ///
+    /// A fork is decided by the election blocks themselves, not by the number of peers that claim
+    /// them, since peers are cheap to create. For every competing id, the election block is
+    /// requested from a peer that claims it and its justification is verified against the
+    /// validators of our election head. Once one of them is valid, the clusters with another id
+    /// at that point are discarded. Only peers that served an invalid block are closed as
+    /// malicious.
+    ///
+    /// Forks at later epochs can't be verified yet, they are resolved once we are synced up to
+    /// them.
+    pub(crate) fn resolve_forks(&mut self) {
+        let our_epoch_number = self.blockchain.read().election_head().epoch_number() as usize;
+        self.fork_candidates
+            .retain(|epoch_number, _| *epoch_number > our_epoch_number);
+
+        let epoch_number = our_epoch_number + 1;
+        let votes = self.votes_at(epoch_number);
+        if votes.len() < 2 {
+            return;
+        }
+
+        let valid_id = self
+            .fork_candidates
+            .get(&epoch_number)
+            .and_then(|candidates| {
+                candidates
+                    .iter()
+                    .find(|(_, candidate)| candidate.valid == Some(true))
+                    .map(|(id, _)| id.clone())
+            });
+        if let Some(valid_id) = valid_id {
+            self.discard_fork(epoch_number, &valid_id);
+            return;
+        }
+
+        let candidates = self.fork_candidates.entry(epoch_number).or_default();
+        let mut checks = vec![];
+        for (id, voters) in votes {
+            let candidate = candidates.entry(id.clone()).or_default();
+            if candidate.pending || candidate.valid.is_some() {
+                continue;
+            }
+
+            // Ask one of the peers that claim the id and weren't asked yet.
+            let agent = voters
+                .iter()
+                .filter(|peer_id| !candidate.asked.contains(*peer_id))
+                .find_map(|peer_id| self.known_agents.get(peer_id).and_then(Weak::upgrade));
+            if let Some(agent) = agent {
+                candidate.asked.insert(agent.peer.id());
+                candidate.pending = true;
+                checks.push((id, agent));
+            }
+        }
+
+        if checks.is_empty() {
+            return;
+        }
+
+        debug!(
+            "Fork at epoch #{}, verifying the election blocks of {} ids",
+            epoch_number,
+            checks.len()
+        );
+
+        for (id, agent) in checks {
+            let future =
+                Self::check_fork(Arc::clone(&self.blockchain), agent, epoch_number, id).boxed();
+            self.fork_checks.push(future);
+        }
+
+        if let Some(waker) = &self.waker {
+            waker.wake_by_ref();
+        }
+    }
+
+    pub(crate) fn poll_fork_checks(&mut self, cx: &mut Context<'_>) {
+        let mut received_checks = false;
+        while let Poll::Ready(Some(check)) = self.fork_checks.poll_next_unpin(cx) {
+            self.on_fork_check(check);
+            received_checks = true;
+        }
+
+        if received_checks {
+            self.resolve_forks();
+        }
+    }
+
+    /// Records the result of a fork check. Clusters with an id whose election block is invalid
+    /// are discarded and the peer that served the block is closed as malicious.
+    pub(crate) fn on_fork_check(&mut self, check: ForkCheck<TNetwork::PeerType>) {
+        // The candidates are dropped once we are past the fork.
+        let candidate = match self
+            .fork_candidates
+            .get_mut(&check.epoch_number)
+            .and_then(|candidates| candidates.get_mut(&check.id))
+        {
+            Some(candidate) => candidate,
+            None => return,
+        };
+
+        candidate.pending = false;
+        match check.verdict {
+            ForkVerdict::Valid => candidate.valid = Some(true),
+            ForkVerdict::Invalid => {
+                candidate.valid = Some(false);
+                self.discard_clusters(check.epoch_number, |id| *id == check.id);
+                self.report_bogus_peer(check.peer_id);
+            }
+            // Another peer that claims the id is asked next time.
+            ForkVerdict::Unavailable => {}
+        }
+    }
+
+    /// Returns the peers that claim each id at the given epoch.
+    fn votes_at(&self, epoch_number: usize) -> HashMap<Blake2bHash, HashSet<PeerId<TNetwork>>> {
+        let mut votes: HashMap<Blake2bHash, HashSet<PeerId<TNetwork>>> = HashMap::new();
+
+        let clusters = self.epoch_clusters.iter().chain(self.active_cluster.iter());
+        for cluster in clusters {
+            if let Some(id) = cluster.epoch_id(epoch_number) {
+                votes
+                    .entry(id.clone())
+                    .or_default()
+                    .extend(cluster.peers().iter().map(|peer| peer.peer_id.clone()));
+            }
+        }
+
+        votes
+    }
+
+    /// Discards the queued clusters that diverge from the valid election block at the given epoch.
+    /// Their peers aren't punished, they might just not know the valid block yet.
+    fn discard_fork(&mut self, epoch_number: usize, valid_id: &Blake2bHash) {
+        // The epochs of the active cluster are verified as they are pushed, so we let the
+        // blockchain decide if the active cluster is on the losing side.
+        if let Some(cluster) = &self.active_cluster {
+            if cluster
+                .epoch_id(epoch_number)
+                .map_or(false, |id| id != valid_id)
+            {
+                return;
+            }
+        }
+
+        let discarded = self.discard_clusters(epoch_number, |id| id != valid_id);
+        if discarded > 0 {
+            debug!(
+                "Resolved fork at epoch #{} in favor of {}: discarded {} clusters",
+                epoch_number, valid_id, discarded
+            );
+        }
+    }
+
+    /// Discards the queued clusters that start at the given epoch with an id matching the filter.
+    /// Returns the number of discarded clusters.
+    fn discard_clusters<F: Fn(&Blake2bHash) -> bool>(
+        &mut self,
+        epoch_number: usize,
+        discard: F,
+    ) -> usize {
+        let num_clusters = self.epoch_clusters.len();
+        self.epoch_clusters.retain(|cluster| {
+            cluster.first_epoch_number != epoch_number
+                || !cluster.epoch_id(epoch_number).map_or(false, &discard)
+        });
+        num_clusters - self.epoch_clusters.len()
+    }
+
+    /// Removes a peer that served an invalid election block from all clusters and closes it as
+    /// malicious.
+    fn report_bogus_peer(&mut self, peer_id: PeerId<TNetwork>) {
+        self.remove_agent(peer_id.clone());
+        for job in self.job_queue.iter_mut() {
+            if let Job::FinishCluster(cluster, _) = job {
+                cluster.remove_peer(&peer_id);
+            }
+        }
+
+        let agent = self
+            .known_agents
+            .remove(&peer_id)
+            .and_then(|agent| Weak::upgrade(&agent));
+        if let Some(agent) = agent {
+            debug!(
+                "Closing connection to peer {:?}, it served an invalid election block",
+                peer_id
+            );
+            self.agents.remove(&agent.peer);
+            agent.peer.close(CloseReason::MaliciousPeer);
+        }
+    }
+
+    /// Requests the election block with the given id from the peer and verifies it.
+    async fn check_fork(
+        blockchain: Arc<BlockchainLock>,
+        agent: Arc<ConsensusAgent<TNetwork::PeerType>>,
+        epoch_number: usize,
+        id: Blake2bHash,
+    ) -> ForkCheck<TNetwork::PeerType> {
+        let verdict = match agent.request_block(id.clone()).await {
+            Ok(Some(block)) => {
+                if verify_election_block(&blockchain, &id, &block) {
+                    ForkVerdict::Valid
+                } else {
+                    ForkVerdict::Invalid
+                }
+            }
+            Ok(None) => ForkVerdict::Unavailable,
+            Err(e) => {
+                debug!(
+                    "Fork check at epoch #{} with peer {:?} failed: {}",
+                    epoch_number,
+                    agent.peer.id(),
+                    e
+                );
+                ForkVerdict::Unavailable
+            }
+        };
+
+        ForkCheck {
+            epoch_number,
+            id,
+            peer_id: agent.peer.id(),
+            verdict,
+        }
+    }
+}
+
+/// Checks that the block is the election block with the given id that follows our election head,
+/// and that it is justified by the validators of our election head. Such a block can't be forged
+/// by peers.
+pub(crate) fn verify_election_block(
+    blockchain: &BlockchainLock,
+    id: &Blake2bHash,
+    block: &Block,
+) -> bool {
+    let block = match block {
+        Block::Macro(block) if block.is_election_block() => block,
+        _ => return false,
+    };
+
+    let blockchain = blockchain.read();
+    let election_head = blockchain.election_head();
+    let validators = match blockchain.current_validators() {
+        Some(validators) => validators,
+        None => return false,
+    };
+
+    block.hash() == *id
+        && block.header.block_number == election_head.header.block_number + policy::EPOCH_LENGTH
+        && block.header.parent_election_hash == blockchain.election_head_hash()
+        && block.body.as_ref().map_or(false, |body| {
+            body.hash::<Blake2bHash>() == block.header.body_root
+        })
+        && TendermintProof::verify(block, &validators)
+}
@@ -29,12 +29,25 @@ impl<TNetwork: Network> HistorySync<TNetwork> {
                     // it didn't send any epoch ids).
                     self.remove_agent(peer.id());
                     self.agents.remove(&peer);
+                    self.known_agents.remove(&peer.id());
                 }
                 Ok(NetworkEvent::PeerJoined(peer)) => {
                     // Create a ConsensusAgent for the peer that joined and request epoch_ids from it.
-                    let agent = Arc::new(ConsensusAgent::new(peer));
+                    let agent = Arc::new(ConsensusAgent::with_tracing(
+                        peer,
+                        self.config.trace_requests,
+                    ));
                     self.add_agent(agent);
                 }
+                // Peers that are still connected after a reconnect are tracked already.
+                Ok(NetworkEvent::Reconnected) => {}
+                // Connection pool events don't affect peers we are syncing with.
+                Ok(
+                    NetworkEvent::DialFailed { .. }
+                    | NetworkEvent::PeerBanned(_)
+                    | NetworkEvent::BackoffStarted(_)
+                    | NetworkEvent::BackoffEnded(_),
+                ) => {}
                 Err(_) => return Poll::Ready(None),
             }
         }
@@ -48,7 +61,7 @@ impl<TNetwork: Network> HistorySync<TNetwork> {
     ) -> Poll<Option<HistorySyncReturn<TNetwork::PeerType>>> {
         // TODO We might want to not send an epoch_id request in the first place if we're at the
         //  cluster limit.
-        while self.epoch_clusters.len() < Self::MAX_CLUSTERS {
+        while self.epoch_clusters.len() < self.config.max_clusters {
             let epoch_ids = match self.epoch_ids_stream.poll_next_unpin(cx) {
                 Poll::Ready(Some(epoch_ids)) => epoch_ids,
                 _ => break,
@@ -92,7 +105,9 @@ impl<TNetwork: Network> HistorySync<TNetwork> {
 
         // Poll the active cluster.
         if let Some(cluster) = self.active_cluster.as_mut() {
-            while self.job_queue.len() < Self::MAX_QUEUED_JOBS {
+            // Pause the cluster while the pending batch sets hold too much memory. It continues
+            // once the queued jobs are done.
+            while self.job_queue.len() < Self::MAX_QUEUED_JOBS && !self.memory.is_over_cap() {
                 let result = match cluster.poll_next_unpin(cx) {
                     Poll::Ready(result) => result,
                     Poll::Pending => break,
@@ -102,22 +117,28 @@ impl<TNetwork: Network> HistorySync<TNetwork> {
                     Some(Ok(batch_set)) => {
                         let hash = batch_set.block.hash();
                         let blockchain = Arc::clone(&self.blockchain);
+                        let reservation = self.memory.reserve(batch_set.size());
                         let future = async move {
                             debug!(
                                 "Processing epoch #{} ({} history items)",
                                 batch_set.block.epoch_number(),
                                 batch_set.history.len()
                             );
-                            spawn_blocking(move || {
-                                Blockchain::push_history_sync(
+                            let result = spawn_blocking(move || {
+                                // Large histories were staged on disk while downloading, they are
+                                // read from there as they are pushed.
+                                let mut history = batch_set.history;
+                                Blockchain::push_history_sync_from(
                                     blockchain.upgradable_read(),
                                     Block::Macro(batch_set.block),
-                                    &batch_set.history,
+                                    &mut history,
                                 )
+                                .into()
                             })
                             .await
-                            .expect("blockchain.push_history_sync() should not panic")
-                            .into()
+                            .expect("blockchain.push_history_sync() should not panic");
+                            drop(reservation);
+                            result
                         }
                         .boxed();
 
@@ -231,6 +252,8 @@ impl<TNetwork: Network> Stream for HistorySync<TNetwork> {
             return Poll::Ready(o);
         }
 
+        self.poll_fork_checks(cx);
+
         self.poll_cluster(cx);
 
         self.poll_job_queue(cx);
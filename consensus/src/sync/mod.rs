@@ -1,4 +1,6 @@
 pub mod block_queue;
+pub mod block_relay;
+pub mod block_validation;
 pub mod history;
 pub mod request_component;
-mod sync_queue;
+pub mod sync_queue;
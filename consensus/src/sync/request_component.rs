@@ -66,13 +66,23 @@ impl<TPeer: Peer + 'static> BlockRequestComponent<TPeer> {
     pub fn new(
         sync_method: Pin<Box<dyn HistorySyncStream<TPeer>>>,
         network_event_rx: BroadcastStream<NetworkEvent<TPeer>>,
+    ) -> Self {
+        Self::with_num_pending_blocks(sync_method, network_event_rx, Self::NUM_PENDING_BLOCKS)
+    }
+
+    /// Creates the component, sending up to `num_pending_blocks` requests for missing blocks at
+    /// the same time.
+    pub fn with_num_pending_blocks(
+        sync_method: Pin<Box<dyn HistorySyncStream<TPeer>>>,
+        network_event_rx: BroadcastStream<NetworkEvent<TPeer>>,
+        num_pending_blocks: usize,
     ) -> Self {
         Self {
             sync_method,
             sync_queue: SyncQueue::new(
                 vec![],
                 vec![],
-                Self::NUM_PENDING_BLOCKS,
+                num_pending_blocks,
                 |(target_block_hash, locators), peer| {
                     async move {
                         if let Some(peer) = Weak::upgrade(&peer) {
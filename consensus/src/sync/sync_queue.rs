@@ -6,6 +6,7 @@ use std::fmt::Debug;
 use std::pin::Pin;
 use std::sync::{Arc, Weak};
 use std::task::Waker;
+use std::time::Duration;
 
 use futures::future::BoxFuture;
 use futures::stream::FuturesUnordered;
@@ -18,22 +19,22 @@ use crate::consensus_agent::ConsensusAgent;
 
 #[pin_project]
 #[derive(Debug)]
-struct OrderWrapper<TId, TOutput> {
+struct OrderWrapper<TId, TPeerId, TOutput> {
     id: TId,
     #[pin]
     data: TOutput, // A future or a future's output
     index: usize,
-    peer: usize,      // The peer the data is requested from
+    peer: TPeerId,    // The peer the data is requested from
     num_tries: usize, // The number of tries this id has been requested
 }
 
-impl<TId: Clone, TOutput: Future> Future for OrderWrapper<TId, TOutput> {
-    type Output = OrderWrapper<TId, TOutput::Output>;
+impl<TId: Clone, TPeerId: Clone, TOutput: Future> Future for OrderWrapper<TId, TPeerId, TOutput> {
+    type Output = OrderWrapper<TId, TPeerId, TOutput::Output>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let id = self.id.clone();
         let index = self.index;
-        let peer = self.peer;
+        let peer = self.peer.clone();
         let num_tries = self.num_tries;
         self.project().data.poll(cx).map(|output| OrderWrapper {
             id,
@@ -85,15 +86,21 @@ impl<TPeer: Peer> Clone for SyncQueuePeer<TPeer> {
 /// The SyncQueue will request a list of ids from a set of peers
 /// and implements an ordered stream over the resulting objects.
 /// The stream returns an error if an id could not be resolved.
+///
+/// Requests preferably go to the peers that answered previous requests the fastest, see
+/// `select_peer`. Failed requests are retried with the other peers in turn.
 pub struct SyncQueue<TPeer: Peer, TId, TOutput> {
     pub(crate) peers: Vec<SyncQueuePeer<TPeer>>,
     desired_pending_size: usize,
     ids_to_request: VecDeque<TId>,
-    pending_futures: FuturesUnordered<OrderWrapper<TId, BoxFuture<'static, Option<TOutput>>>>,
+    pending_futures:
+        FuturesUnordered<OrderWrapper<TId, TPeer::Id, BoxFuture<'static, Option<TOutput>>>>,
     queued_outputs: BinaryHeap<QueuedOutput<TOutput>>,
     next_incoming_index: usize,
     next_outgoing_index: usize,
     current_peer_index: usize,
+    // The index of the next demoted peer that is asked to measure its latency again.
+    current_demoted_index: usize,
     request_fn: fn(TId, Weak<ConsensusAgent<TPeer>>) -> BoxFuture<'static, Option<TOutput>>,
     waker: Option<Waker>,
 }
@@ -104,6 +111,18 @@ where
     TId: Clone + Debug,
     TOutput: Send + Unpin,
 {
+    /// Peers that answer this many times slower than the fastest peer are demoted: They are only
+    /// asked again if a request to another peer failed.
+    const SLOW_PEER_FACTOR: u32 = 4;
+
+    /// The latency assumed for peers if no peer answered a request yet.
+    const DEFAULT_LATENCY: Duration = Duration::from_millis(1);
+
+    /// Every this many requests, one goes to a demoted peer without pending requests instead, so
+    /// that its latency is measured again. Otherwise a peer that was slow once would never be asked
+    /// again.
+    const DEMOTED_PROBE_INTERVAL: usize = 32;
+
     pub fn new(
         ids: Vec<TId>,
         peers: Vec<SyncQueuePeer<TPeer>>,
@@ -126,17 +145,21 @@ where
             next_incoming_index: 0,
             next_outgoing_index: 0,
             current_peer_index: 0,
+            current_demoted_index: 0,
             request_fn,
             waker: None,
         }
     }
 
-    fn get_next_peer(&mut self, start_index: usize) -> Option<Weak<ConsensusAgent<TPeer>>> {
+    fn get_next_peer(
+        &mut self,
+        start_index: usize,
+    ) -> Option<(TPeer::Id, Weak<ConsensusAgent<TPeer>>)> {
         while !self.peers.is_empty() {
             let index = start_index % self.peers.len();
             match Weak::upgrade(&self.peers[index].agent) {
                 Some(peer) => {
-                    return Some(Arc::downgrade(&peer));
+                    return Some((self.peers[index].peer_id.clone(), Arc::downgrade(&peer)));
                 }
                 None => {
                     self.peers.remove(index);
@@ -146,6 +169,82 @@ where
         None
     }
 
+    /// Selects the peer for the next request.
+    ///
+    /// Peers are ranked by their latency multiplied by the number of requests they are already
+    /// serving, so that most requests go to fast peers without overloading them. Peers that didn't
+    /// answer a request yet are assumed to be as fast as the fastest peer, so they are tried early.
+    /// Ties are broken in round-robin order. Every [`Self::DEMOTED_PROBE_INTERVAL`] requests, an
+    /// idle demoted peer is selected instead.
+    fn select_peer(&mut self) -> Option<(TPeer::Id, Weak<ConsensusAgent<TPeer>>)> {
+        // Remove the peers that are gone.
+        let mut agents = Vec::with_capacity(self.peers.len());
+        self.peers.retain(|peer| match Weak::upgrade(&peer.agent) {
+            Some(agent) => {
+                agents.push(agent);
+                true
+            }
+            None => false,
+        });
+
+        let fastest = agents.iter().filter_map(|agent| agent.latency()).min();
+        let is_demoted = |agent: &ConsensusAgent<TPeer>| match (agent.latency(), fastest) {
+            (Some(latency), Some(fastest)) => latency > fastest * Self::SLOW_PEER_FACTOR,
+            _ => false,
+        };
+
+        if self.next_incoming_index % Self::DEMOTED_PROBE_INTERVAL
+            == Self::DEMOTED_PROBE_INTERVAL - 1
+        {
+            for offset in 0..agents.len() {
+                let index = (self.current_demoted_index + offset) % agents.len();
+                if is_demoted(&agents[index]) && self.num_pending(&self.peers[index].peer_id) == 0 {
+                    self.current_demoted_index = (index + 1) % agents.len();
+                    return Some((
+                        self.peers[index].peer_id.clone(),
+                        Arc::downgrade(&agents[index]),
+                    ));
+                }
+            }
+        }
+
+        let mut best: Option<(usize, Duration)> = None;
+        for offset in 0..agents.len() {
+            let index = (self.current_peer_index + offset) % agents.len();
+            if is_demoted(&agents[index]) {
+                continue;
+            }
+
+            let latency = agents[index]
+                .latency()
+                .or(fastest)
+                .unwrap_or(Self::DEFAULT_LATENCY)
+                .max(Self::DEFAULT_LATENCY);
+            let num_pending = self.num_pending(&self.peers[index].peer_id);
+            let score = latency * (num_pending as u32 + 1);
+
+            if best.map_or(true, |(_, best_score)| score < best_score) {
+                best = Some((index, score));
+            }
+        }
+
+        // The fastest peer is never demoted, so there is a peer unless all of them are gone.
+        let (index, _) = best?;
+        self.current_peer_index = (index + 1) % agents.len();
+        Some((
+            self.peers[index].peer_id.clone(),
+            Arc::downgrade(&agents[index]),
+        ))
+    }
+
+    /// Returns the number of pending requests to the given peer.
+    fn num_pending(&self, peer_id: &TPeer::Id) -> usize {
+        Pin::new(&self.pending_futures)
+            .iter_pin_ref()
+            .filter(|wrapper| wrapper.peer == *peer_id)
+            .count()
+    }
+
     fn try_push_futures(&mut self) {
         // Determine number of new futures required to maintain desired_pending_size.
         let num_ids_to_request = cmp::min(
@@ -158,8 +257,8 @@ where
 
         // Drain ids and produce futures.
         for _ in 0..num_ids_to_request {
-            // Get next peer in line. Abort if there are no more peers.
-            let peer = match self.get_next_peer(self.current_peer_index) {
+            // Get the best peer. Abort if there are no more peers.
+            let (peer_id, peer) = match self.select_peer() {
                 Some(peer) => peer,
                 None => return,
             };
@@ -167,22 +266,21 @@ where
             let id = self.ids_to_request.pop_front().unwrap();
 
             log::trace!(
-                "Requesting {:?} @ {} from peer {}",
+                "Requesting {:?} @ {} from peer {:?}",
                 id,
                 self.next_incoming_index,
-                self.current_peer_index
+                peer_id
             );
 
             let wrapper = OrderWrapper {
                 data: (self.request_fn)(id.clone(), peer),
                 id,
                 index: self.next_incoming_index,
-                peer: self.current_peer_index,
+                peer: peer_id,
                 num_tries: 1,
             };
 
             self.next_incoming_index += 1;
-            self.current_peer_index = (self.current_peer_index + 1) % self.peers.len();
 
             self.pending_futures.push(wrapper);
         }
@@ -304,25 +402,30 @@ where
                                 return Poll::Ready(Some(Err(result.id)));
                             }
 
-                            // Re-request from different peer. Return an error if there are no more peers.
-                            let next_peer = (result.peer + 1) % self.peers.len();
-                            let peer = match self.get_next_peer(next_peer) {
+                            // Re-request from the peer after the one that failed, or the first
+                            // one if it is gone. Return an error if there are no more peers.
+                            let next_peer = self
+                                .peers
+                                .iter()
+                                .position(|peer| peer.peer_id == result.peer)
+                                .map_or(0, |index| index + 1);
+                            let (peer_id, peer) = match self.get_next_peer(next_peer) {
                                 Some(peer) => peer,
                                 None => return Poll::Ready(Some(Err(result.id))),
                             };
 
                             log::debug!(
-                                "Re-requesting {:?} @ {} from peer {}",
+                                "Re-requesting {:?} @ {} from peer {:?}",
                                 result.id,
                                 result.index,
-                                next_peer
+                                peer_id
                             );
 
                             let wrapper = OrderWrapper {
                                 data: (self.request_fn)(result.id.clone(), peer),
                                 id: result.id,
                                 index: result.index,
-                                peer: next_peer,
+                                peer: peer_id,
                                 num_tries: result.num_tries + 1,
                             };
 
@@ -347,3 +450,96 @@ where
         (len, Some(len))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::{Arc, Weak};
+    use std::time::Duration;
+
+    use futures::future::{self, BoxFuture, FutureExt};
+    use futures::StreamExt;
+
+    use nimiq_network_interface::network::Network;
+    use nimiq_network_interface::peer::Peer;
+    use nimiq_network_mock::{MockHub, MockPeer, MockPeerId};
+
+    use crate::consensus_agent::ConsensusAgent;
+    use crate::sync::sync_queue::SyncQueue;
+
+    /// Answers with the id of the peer that was asked.
+    fn request(
+        _id: u32,
+        agent: Weak<ConsensusAgent<MockPeer>>,
+    ) -> BoxFuture<'static, Option<MockPeerId>> {
+        future::ready(agent.upgrade().map(|agent| agent.peer.id())).boxed()
+    }
+
+    #[tokio::test]
+    async fn it_prefers_fast_peers() {
+        let mut hub = MockHub::new();
+        let network = hub.new_network();
+        for _ in 0..3 {
+            network.dial_mock(&hub.new_network());
+        }
+
+        let agents: Vec<Arc<ConsensusAgent<MockPeer>>> = network
+            .get_peers()
+            .into_iter()
+            .map(|peer| Arc::new(ConsensusAgent::new(peer)))
+            .collect();
+
+        // The first peer is fast, the second one is a bit slower and the third one is so slow that
+        // it is demoted.
+        agents[0].record_latency(Duration::from_millis(10));
+        agents[1].record_latency(Duration::from_millis(20));
+        agents[2].record_latency(Duration::from_millis(100));
+
+        let mut queue = SyncQueue::new((0..30).collect(), vec![], 3, request);
+        for agent in &agents {
+            queue.add_peer(agent.peer.id(), Arc::downgrade(agent));
+        }
+
+        let mut num_requests = HashMap::new();
+        while let Some(result) = queue.next().await {
+            *num_requests.entry(result.unwrap()).or_insert(0) += 1;
+        }
+
+        let fast = num_requests.get(&agents[0].peer.id()).copied().unwrap_or(0);
+        let slower = num_requests.get(&agents[1].peer.id()).copied().unwrap_or(0);
+        assert!(fast > slower);
+        assert!(slower > 0);
+        assert_eq!(num_requests.get(&agents[2].peer.id()), None);
+    }
+
+    #[tokio::test]
+    async fn it_measures_demoted_peers_again() {
+        let mut hub = MockHub::new();
+        let network = hub.new_network();
+        for _ in 0..2 {
+            network.dial_mock(&hub.new_network());
+        }
+
+        let agents: Vec<Arc<ConsensusAgent<MockPeer>>> = network
+            .get_peers()
+            .into_iter()
+            .map(|peer| Arc::new(ConsensusAgent::new(peer)))
+            .collect();
+        agents[0].record_latency(Duration::from_millis(10));
+        agents[1].record_latency(Duration::from_millis(100));
+
+        let num_ids = 2 * SyncQueue::<MockPeer, u32, MockPeerId>::DEMOTED_PROBE_INTERVAL as u32;
+        let mut queue = SyncQueue::new((0..num_ids).collect(), vec![], 1, request);
+        for agent in &agents {
+            queue.add_peer(agent.peer.id(), Arc::downgrade(agent));
+        }
+
+        let mut num_requests = HashMap::new();
+        while let Some(result) = queue.next().await {
+            *num_requests.entry(result.unwrap()).or_insert(0) += 1;
+        }
+
+        // The demoted peer is asked once per interval.
+        assert_eq!(num_requests.get(&agents[1].peer.id()), Some(&2));
+    }
+}
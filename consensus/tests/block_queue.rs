@@ -3,27 +3,29 @@ use std::{
     pin::Pin,
     sync::{Arc, Weak},
     task::{Context, Poll},
+    time::Duration,
 };
 
 use futures::{
     channel::mpsc,
     sink::SinkExt,
-    stream::{Stream, StreamExt},
+    stream::{BoxStream, Stream, StreamExt},
     task::noop_waker_ref,
 };
-use parking_lot::RwLock;
+use parking_lot::Mutex;
 use pin_project::pin_project;
 use rand::Rng;
 
 use nimiq_block::Block;
 use nimiq_block_production::BlockProducer;
-use nimiq_blockchain::{AbstractBlockchain, Blockchain};
+use nimiq_blockchain::{AbstractBlockchain, Blockchain, BlockchainLock};
 use nimiq_consensus::consensus_agent::ConsensusAgent;
-use nimiq_consensus::sync::block_queue::{BlockQueue, BlockQueueConfig};
+use nimiq_consensus::sync::block_queue::{BlockQueue, BlockQueueConfig, BlockQueueEvent};
+use nimiq_consensus::sync::block_relay::BlockRelay;
 use nimiq_consensus::sync::request_component::{RequestComponent, RequestComponentEvent};
 use nimiq_database::volatile::VolatileEnvironment;
 use nimiq_hash::Blake2bHash;
-use nimiq_network_interface::network::Network;
+use nimiq_network_interface::network::{MsgAcceptance, Network};
 use nimiq_network_interface::peer::Peer;
 use nimiq_network_mock::{MockHub, MockId, MockPeer};
 use nimiq_primitives::networks::NetworkId;
@@ -103,11 +105,32 @@ impl<P: Peer> Stream for MockRequestComponent<P> {
     }
 }
 
+/// A block relay that receives blocks from a channel and records the hashes of the blocks that are
+/// sent to it.
+struct ChannelRelay {
+    blocks: Mutex<Option<mpsc::UnboundedReceiver<Block>>>,
+    relayed: Mutex<Vec<Blake2bHash>>,
+}
+
+impl BlockRelay for ChannelRelay {
+    fn relay(&self, block: &Block) {
+        self.relayed.lock().push(block.hash());
+    }
+
+    fn receive(&self) -> BoxStream<'static, Block> {
+        self.blocks
+            .lock()
+            .take()
+            .expect("Blocks are only received once")
+            .boxed()
+    }
+}
+
 #[tokio::test]
 async fn send_single_micro_block_to_block_queue() {
     let time = Arc::new(OffsetTime::new());
     let env = VolatileEnvironment::new(10).unwrap();
-    let blockchain = Arc::new(RwLock::new(
+    let blockchain = Arc::new(BlockchainLock::new(
         Blockchain::new(env, NetworkId::UnitAlbatross, time).unwrap(),
     ));
     let mut hub = MockHub::new();
@@ -151,16 +174,57 @@ async fn send_single_micro_block_to_block_queue() {
     assert!(block_queue.buffered_blocks().next().is_none());
 }
 
+#[tokio::test]
+async fn block_with_invalid_signature_is_rejected_before_queueing() {
+    let time = Arc::new(OffsetTime::new());
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Arc::new(BlockchainLock::new(
+        Blockchain::new(env, NetworkId::UnitAlbatross, time).unwrap(),
+    ));
+    let mut hub = MockHub::new();
+    let network = Arc::new(hub.new_network());
+    let producer = BlockProducer::new(signing_key(), voting_key());
+    let request_component = MockRequestComponent::<MockPeer>::default();
+    let (mut tx, rx) = mpsc::channel(32);
+
+    let mut block_queue = BlockQueue::with_block_stream(
+        Default::default(),
+        Arc::clone(&blockchain),
+        Arc::clone(&network),
+        request_component,
+        rx.boxed(),
+    );
+
+    // Changing the header after the block was signed invalidates the signature.
+    let block = {
+        let bc = blockchain.read();
+        let mut micro_block =
+            producer.next_micro_block(&bc, bc.time.now(), 0, None, vec![], vec![], vec![0x42]);
+        micro_block.header.extra_data = vec![0x43];
+        Block::Micro(micro_block)
+    };
+
+    let mock_id = MockId::new(hub.new_address().into());
+    tx.send((block, mock_id)).await.unwrap();
+
+    // The header check drops the block, so the block queue never sees it.
+    let event = tokio::time::timeout(Duration::from_millis(500), block_queue.next()).await;
+    assert!(event.is_err());
+
+    assert_eq!(blockchain.read().block_number(), 0);
+    assert!(block_queue.buffered_blocks().next().is_none());
+}
+
 #[tokio::test]
 async fn send_two_micro_blocks_out_of_order() {
     let env1 = VolatileEnvironment::new(10).unwrap();
     let time1 = Arc::new(OffsetTime::new());
     let env2 = VolatileEnvironment::new(10).unwrap();
     let time2 = Arc::new(OffsetTime::new());
-    let blockchain1 = Arc::new(RwLock::new(
+    let blockchain1 = Arc::new(BlockchainLock::new(
         Blockchain::new(env1, NetworkId::UnitAlbatross, time1).unwrap(),
     ));
-    let blockchain2 = Arc::new(RwLock::new(
+    let blockchain2 = Arc::new(BlockchainLock::new(
         Blockchain::new(env2, NetworkId::UnitAlbatross, time2).unwrap(),
     ));
     let mut hub = MockHub::new();
@@ -253,10 +317,10 @@ async fn send_micro_blocks_out_of_order() {
     let time1 = Arc::new(OffsetTime::new());
     let env2 = VolatileEnvironment::new(10).unwrap();
     let time2 = Arc::new(OffsetTime::new());
-    let blockchain1 = Arc::new(RwLock::new(
+    let blockchain1 = Arc::new(BlockchainLock::new(
         Blockchain::new(env1, NetworkId::UnitAlbatross, time1).unwrap(),
     ));
-    let blockchain2 = Arc::new(RwLock::new(
+    let blockchain2 = Arc::new(BlockchainLock::new(
         Blockchain::new(env2, NetworkId::UnitAlbatross, time2).unwrap(),
     ));
     let mut hub = MockHub::new();
@@ -347,10 +411,10 @@ async fn send_invalid_block() {
     let time1 = Arc::new(OffsetTime::new());
     let env2 = VolatileEnvironment::new(10).unwrap();
     let time2 = Arc::new(OffsetTime::new());
-    let blockchain1 = Arc::new(RwLock::new(
+    let blockchain1 = Arc::new(BlockchainLock::new(
         Blockchain::new(env1, NetworkId::UnitAlbatross, time1).unwrap(),
     ));
-    let blockchain2 = Arc::new(RwLock::new(
+    let blockchain2 = Arc::new(BlockchainLock::new(
         Blockchain::new(env2, NetworkId::UnitAlbatross, time2).unwrap(),
     ));
     let mut hub = MockHub::new();
@@ -433,16 +497,93 @@ async fn send_invalid_block() {
     );
 }
 
+#[tokio::test]
+async fn relayed_block_that_fails_verification_is_rejected() {
+    let env1 = VolatileEnvironment::new(10).unwrap();
+    let time1 = Arc::new(OffsetTime::new());
+    let env2 = VolatileEnvironment::new(10).unwrap();
+    let time2 = Arc::new(OffsetTime::new());
+    let blockchain1 = Arc::new(BlockchainLock::new(
+        Blockchain::new(env1, NetworkId::UnitAlbatross, time1).unwrap(),
+    ));
+    let blockchain2 = Arc::new(BlockchainLock::new(
+        Blockchain::new(env2, NetworkId::UnitAlbatross, time2).unwrap(),
+    ));
+    let mut hub = MockHub::new();
+    let network = Arc::new(hub.new_network());
+    let producer = BlockProducer::new(signing_key(), voting_key());
+    let request_component = MockRequestComponent::<MockPeer>::default();
+    let (mut tx, rx) = mpsc::channel(32);
+
+    let mut block_queue = BlockQueue::with_block_stream(
+        BlockQueueConfig {
+            cut_through_relay: true,
+            ..Default::default()
+        },
+        Arc::clone(&blockchain1),
+        Arc::clone(&network),
+        request_component,
+        rx.boxed(),
+    );
+
+    let bc = blockchain2.upgradable_read();
+    let block1 = Block::Micro(producer.next_micro_block(
+        &bc,
+        bc.time.now(),
+        0,
+        None,
+        vec![],
+        vec![],
+        vec![0x42],
+    ));
+    Blockchain::push(bc, block1.clone()).unwrap();
+
+    // Block2 has a valid header, but it lacks its body, which is only verified when it is pushed.
+    let block2 = {
+        let bc = blockchain2.read();
+        let mut micro_block =
+            producer.next_micro_block(&bc, bc.time.now(), 0, None, vec![], vec![], vec![0x42]);
+        micro_block.body = None;
+        Block::Micro(micro_block)
+    };
+
+    let mock_id = MockId::new(hub.new_address().into());
+
+    // Block1 passes the header check, is relayed right away and then pushed.
+    tx.send((block1.clone(), mock_id.clone())).await.unwrap();
+    match block_queue.next().await {
+        Some(BlockQueueEvent::AcceptedAnnouncedBlock(hash)) => assert_eq!(hash, block1.hash()),
+        event => panic!("Unexpected event: {:?}", event),
+    }
+    assert_eq!(blockchain1.read().block_number(), 1);
+
+    // Block2 passes the header check as well and is relayed, but the push fails, so a follow-up
+    // reject is reported for it.
+    tx.send((block2.clone(), mock_id)).await.unwrap();
+    match block_queue.next().await {
+        Some(BlockQueueEvent::RejectedBlock(hash)) => assert_eq!(hash, block2.hash()),
+        event => panic!("Unexpected event: {:?}", event),
+    }
+    assert_eq!(blockchain1.read().block_number(), 1);
+    assert!(block_queue.buffered_blocks().next().is_none());
+
+    let validations = network.validations();
+    assert_eq!(validations.len(), 3);
+    assert!(matches!(validations[0], (_, MsgAcceptance::Accept)));
+    assert!(matches!(validations[1], (_, MsgAcceptance::Accept)));
+    assert!(matches!(validations[2], (_, MsgAcceptance::Reject)));
+}
+
 #[tokio::test]
 async fn send_block_with_gap_and_respond_to_missing_request() {
     let env1 = VolatileEnvironment::new(10).unwrap();
     let time1 = Arc::new(OffsetTime::new());
     let env2 = VolatileEnvironment::new(10).unwrap();
     let time2 = Arc::new(OffsetTime::new());
-    let blockchain1 = Arc::new(RwLock::new(
+    let blockchain1 = Arc::new(BlockchainLock::new(
         Blockchain::new(env1, NetworkId::UnitAlbatross, time1).unwrap(),
     ));
-    let blockchain2 = Arc::new(RwLock::new(
+    let blockchain2 = Arc::new(BlockchainLock::new(
         Blockchain::new(env2, NetworkId::UnitAlbatross, time2).unwrap(),
     ));
     let mut hub = MockHub::new();
@@ -536,10 +677,10 @@ async fn put_peer_back_into_sync_mode() {
     let time1 = Arc::new(OffsetTime::new());
     let env2 = VolatileEnvironment::new(10).unwrap();
     let time2 = Arc::new(OffsetTime::new());
-    let blockchain1 = Arc::new(RwLock::new(
+    let blockchain1 = Arc::new(BlockchainLock::new(
         Blockchain::new(env1, NetworkId::UnitAlbatross, time1).unwrap(),
     ));
-    let blockchain2 = Arc::new(RwLock::new(
+    let blockchain2 = Arc::new(BlockchainLock::new(
         Blockchain::new(env2, NetworkId::UnitAlbatross, time2).unwrap(),
     ));
     let mut hub = MockHub::new();
@@ -556,6 +697,9 @@ async fn put_peer_back_into_sync_mode() {
         BlockQueueConfig {
             buffer_max: 10,
             window_max: 10,
+            header_check_workers: 4,
+            cut_through_relay: false,
+            ..Default::default()
         },
         Arc::clone(&blockchain1),
         network,
@@ -597,3 +741,134 @@ async fn put_peer_back_into_sync_mode() {
 
     assert!(block_queue.request_component.peer_put_into_sync);
 }
+
+#[tokio::test]
+async fn blocks_are_exchanged_with_the_block_relay() {
+    let time = Arc::new(OffsetTime::new());
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Arc::new(BlockchainLock::new(
+        Blockchain::new(env, NetworkId::UnitAlbatross, time).unwrap(),
+    ));
+    let mut hub = MockHub::new();
+    let network = Arc::new(hub.new_network());
+    let producer = BlockProducer::new(signing_key(), voting_key());
+    let request_component = MockRequestComponent::<MockPeer>::default();
+    let (mut tx, rx) = mpsc::channel(32);
+
+    let mut block_queue = BlockQueue::with_block_stream(
+        Default::default(),
+        Arc::clone(&blockchain),
+        Arc::clone(&network),
+        request_component,
+        rx.boxed(),
+    );
+
+    let (relay_tx, relay_rx) = mpsc::unbounded();
+    let relay = Arc::new(ChannelRelay {
+        blocks: Mutex::new(Some(relay_rx)),
+        relayed: Mutex::new(vec![]),
+    });
+    block_queue.set_block_relay(Arc::clone(&relay) as Arc<dyn BlockRelay>);
+
+    let next_block = || {
+        let bc = blockchain.read();
+        Block::Micro(producer.next_micro_block(
+            &bc,
+            bc.time.now(),
+            0,
+            None,
+            vec![],
+            vec![],
+            vec![0x42],
+        ))
+    };
+
+    // A block from the relay is pushed like a gossiped one.
+    relay_tx.unbounded_send(next_block()).unwrap();
+    block_queue.next().await;
+    assert_eq!(blockchain.read().block_number(), 1);
+
+    // A gossiped block is handed to the relay once it extended the chain.
+    let block = next_block();
+    let mock_id = MockId::new(hub.new_address().into());
+    tx.send((block.clone(), mock_id)).await.unwrap();
+    block_queue.next().await;
+    assert_eq!(blockchain.read().block_number(), 2);
+
+    // Let the block queue process the blockchain events.
+    let _ = tokio::time::timeout(Duration::from_millis(100), block_queue.next()).await;
+
+    // The block from the relay isn't sent back to it.
+    assert_eq!(*relay.relayed.lock(), vec![block.hash()]);
+}
+
+#[tokio::test]
+async fn discarded_blocks_from_the_block_relay_are_not_remembered() {
+    let time = Arc::new(OffsetTime::new());
+    let env1 = VolatileEnvironment::new(10).unwrap();
+    let env2 = VolatileEnvironment::new(10).unwrap();
+    let blockchain1 = Arc::new(BlockchainLock::new(
+        Blockchain::new(env1, NetworkId::UnitAlbatross, Arc::clone(&time)).unwrap(),
+    ));
+    let blockchain2 = Arc::new(BlockchainLock::new(
+        Blockchain::new(env2, NetworkId::UnitAlbatross, time).unwrap(),
+    ));
+    let mut hub = MockHub::new();
+    let network = Arc::new(hub.new_network());
+    let producer = BlockProducer::new(signing_key(), voting_key());
+    let request_component = MockRequestComponent::<MockPeer>::default();
+    let (mut tx, rx) = mpsc::channel(32);
+
+    let mut block_queue = BlockQueue::with_block_stream(
+        BlockQueueConfig {
+            window_max: 0,
+            ..Default::default()
+        },
+        Arc::clone(&blockchain1),
+        Arc::clone(&network),
+        request_component,
+        rx.boxed(),
+    );
+
+    let (relay_tx, relay_rx) = mpsc::unbounded();
+    let relay = Arc::new(ChannelRelay {
+        blocks: Mutex::new(Some(relay_rx)),
+        relayed: Mutex::new(vec![]),
+    });
+    block_queue.set_block_relay(Arc::clone(&relay) as Arc<dyn BlockRelay>);
+
+    let mut blocks = vec![];
+    for _ in 0..2 {
+        let bc = blockchain2.upgradable_read();
+        let block = Block::Micro(producer.next_micro_block(
+            &bc,
+            bc.time.now(),
+            0,
+            None,
+            vec![],
+            vec![],
+            vec![0x42],
+        ));
+        blocks.push(block.clone());
+        Blockchain::push(bc, block).unwrap();
+    }
+
+    // The second block is outside of the buffer window, so it is discarded.
+    relay_tx.unbounded_send(blocks[1].clone()).unwrap();
+    let _ = tokio::time::timeout(Duration::from_millis(100), block_queue.next()).await;
+    assert_eq!(blockchain1.read().block_number(), 0);
+
+    // Once both blocks arrive via gossipsub, both are handed to the relay.
+    for block in &blocks {
+        let mock_id = MockId::new(hub.new_address().into());
+        tx.send((block.clone(), mock_id)).await.unwrap();
+        block_queue.next().await;
+    }
+    assert_eq!(blockchain1.read().block_number(), 2);
+
+    let _ = tokio::time::timeout(Duration::from_millis(100), block_queue.next()).await;
+    assert_eq!(
+        *relay.relayed.lock(),
+        blocks.iter().map(Block::hash).collect::<Vec<_>>()
+    );
+}
@@ -4,10 +4,9 @@ use std::time::Duration;
 
 use futures::task::{Context, Poll};
 use futures::{Stream, StreamExt};
-use parking_lot::RwLock;
 
 use nimiq_block_production::BlockProducer;
-use nimiq_blockchain::{AbstractBlockchain, Blockchain};
+use nimiq_blockchain::{AbstractBlockchain, Blockchain, BlockchainLock};
 use nimiq_consensus::consensus::Consensus;
 use nimiq_consensus::consensus_agent::ConsensusAgent;
 use nimiq_consensus::messages::RequestBlockHashesFilter;
@@ -46,7 +45,7 @@ async fn peers_can_sync() {
     // Setup first peer.
     let env1 = VolatileEnvironment::new(10).unwrap();
     let time = Arc::new(OffsetTime::new());
-    let blockchain1 = Arc::new(RwLock::new(
+    let blockchain1 = Arc::new(BlockchainLock::new(
         Blockchain::new(env1.clone(), NetworkId::UnitAlbatross, time).unwrap(),
     ));
 
@@ -67,7 +66,7 @@ async fn peers_can_sync() {
     // Setup second peer (not synced yet).
     let time = Arc::new(OffsetTime::new());
     let env2 = VolatileEnvironment::new(10).unwrap();
-    let blockchain2 = Arc::new(RwLock::new(
+    let blockchain2 = Arc::new(BlockchainLock::new(
         Blockchain::new(env2.clone(), NetworkId::UnitAlbatross, time).unwrap(),
     ));
 
@@ -175,7 +174,7 @@ async fn sync_ingredients() {
     // Setup first peer.
     let time = Arc::new(OffsetTime::new());
     let env1 = VolatileEnvironment::new(10).unwrap();
-    let blockchain1 = Arc::new(RwLock::new(
+    let blockchain1 = Arc::new(BlockchainLock::new(
         Blockchain::new(env1.clone(), NetworkId::UnitAlbatross, time).unwrap(),
     ));
 
@@ -202,7 +201,7 @@ async fn sync_ingredients() {
     // Setup second peer (not synced yet).
     let env2 = VolatileEnvironment::new(10).unwrap();
     let time = Arc::new(OffsetTime::new());
-    let blockchain2 = Arc::new(RwLock::new(
+    let blockchain2 = Arc::new(BlockchainLock::new(
         Blockchain::new(env2.clone(), NetworkId::UnitAlbatross, time).unwrap(),
     ));
 
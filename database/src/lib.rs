@@ -4,15 +4,18 @@ extern crate log;
 use std::borrow::Cow;
 use std::io;
 use std::ops::Deref;
+use std::time::Duration;
 
 use bitflags::bitflags;
 
 use crate::cursor::{ReadCursor, WriteCursor as WriteCursorTrait};
+pub use crate::metrics::DatabaseMetricsSnapshot;
 pub use crate::traits::{AsDatabaseBytes, FromDatabaseValue, IntoDatabaseValue};
 
 #[macro_use]
 pub mod cursor;
 pub mod lmdb;
+pub mod metrics;
 pub mod traits;
 pub mod volatile;
 
@@ -59,6 +62,22 @@ impl Environment {
         }
     }
 
+    /// Returns the transaction metrics and the map usage of the environment.
+    pub fn metrics(&self) -> DatabaseMetricsSnapshot {
+        match *self {
+            Environment::Volatile(ref env) => env.metrics(),
+            Environment::Persistent(ref env) => env.metrics(),
+        }
+    }
+
+    /// Commits that take at least this long are logged as warnings. Zero disables the warnings.
+    pub fn set_slow_commit_threshold(&self, threshold: Duration) {
+        match *self {
+            Environment::Volatile(ref env) => env.set_slow_commit_threshold(threshold),
+            Environment::Persistent(ref env) => env.set_slow_commit_threshold(threshold),
+        }
+    }
+
     pub fn close(self) {}
 
     pub fn drop_database(self) -> io::Result<()> {
@@ -67,6 +86,15 @@ impl Environment {
             Environment::Persistent(env) => env.drop_database(),
         }
     }
+
+    /// Copies a persistent environment to the given directory. Volatile environments are not
+    /// backed up.
+    pub fn backup(&self, path: &str) -> io::Result<()> {
+        match *self {
+            Environment::Volatile(_) => Ok(()),
+            Environment::Persistent(ref env) => env.backup(path),
+        }
+    }
 }
 
 #[derive(Debug)]
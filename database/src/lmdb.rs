@@ -1,25 +1,52 @@
+use std::cell::RefCell;
 use std::fmt;
 use std::fs;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
 
 // re export the lmdb error
+use lmdb_zero::error::{MAP_FULL, MAP_RESIZED};
 pub use lmdb_zero::open;
 use lmdb_zero::traits::LmdbResultExt;
 pub use lmdb_zero::Error as LmdbError;
 
 use crate::cursor::{RawReadCursor, ReadCursor, WriteCursor as WriteCursorTrait};
+use crate::metrics::{
+    DatabaseMetrics, DatabaseMetricsSnapshot, ReadTransactionTimer, WriteTransactionTimer,
+};
 
 use super::*;
 
+/// The memory map is grown once more than this share of it is in use.
+const GROWTH_THRESHOLD: f64 = 0.8;
+
+/// Interval in which a write transaction that ran out of space reports that it is still waiting
+/// for the other transactions of this process to finish, so that the memory map can be grown.
+const GROWTH_WARNING_INTERVAL: Duration = Duration::from_secs(30);
+
 #[derive(Debug)]
 pub struct LmdbEnvironment {
     env: Arc<lmdb_zero::Environment>,
+    metrics: Arc<DatabaseMetrics>,
+    /// Number of transactions of this process that are currently open. The memory map can only be
+    /// resized while there are none.
+    active_transactions: Arc<AtomicUsize>,
+    /// Taken to begin a transaction or to resize the memory map.
+    resize_lock: Arc<Mutex<()>>,
+    /// Held by the write transaction of this process for its whole lifetime, so that no other
+    /// write is committed while a transaction that ran out of space is replayed.
+    write_lock: Arc<WriteLock>,
 }
 
 impl Clone for LmdbEnvironment {
     fn clone(&self) -> Self {
         Self {
             env: Arc::clone(&self.env),
+            metrics: Arc::clone(&self.metrics),
+            active_transactions: Arc::clone(&self.active_transactions),
+            resize_lock: Arc::clone(&self.resize_lock),
+            write_lock: Arc::clone(&self.write_lock),
         }
     }
 }
@@ -76,10 +103,14 @@ impl LmdbEnvironment {
             info!("LMDB memory map size: {}", cur_mapsize);
         }
 
-        let lmdb = LmdbEnvironment { env: Arc::new(env) };
-        if lmdb.need_resize(0) {
-            info!("LMDB memory needs to be resized.");
-        }
+        let lmdb = LmdbEnvironment {
+            env: Arc::new(env),
+            metrics: Arc::new(DatabaseMetrics::default()),
+            active_transactions: Arc::new(AtomicUsize::new(0)),
+            resize_lock: Arc::new(Mutex::new(())),
+            write_lock: Arc::new(WriteLock::default()),
+        };
+        lmdb.grow_if_needed();
 
         Ok(lmdb)
     }
@@ -105,12 +136,14 @@ impl LmdbEnvironment {
         }
 
         LmdbDatabase {
-            db: lmdb_zero::Database::open(
-                Arc::clone(&self.env),
-                Some(&name),
-                &lmdb_zero::DatabaseOptions::new(db_flags),
-            )
-            .unwrap(),
+            db: Arc::new(
+                lmdb_zero::Database::open(
+                    Arc::clone(&self.env),
+                    Some(&name),
+                    &lmdb_zero::DatabaseOptions::new(db_flags),
+                )
+                .unwrap(),
+            ),
         }
     }
 
@@ -118,59 +151,248 @@ impl LmdbEnvironment {
         fs::remove_dir_all(self.path().as_ref())
     }
 
+    /// Writes a compacted copy of the environment to the given directory, which must not contain
+    /// a database yet. The copy is consistent even while write transactions are running.
+    pub(super) fn backup(&self, path: &str) -> io::Result<()> {
+        fs::create_dir_all(path)?;
+        self.env
+            .copy(path, lmdb_zero::copy::COMPACT)
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+    }
+
     fn path(&self) -> Cow<str> {
         self.env.path().unwrap().to_string_lossy()
     }
 
-    pub fn need_resize(&self, threshold_size: usize) -> bool {
+    /// Returns the size of the memory map and the size of the pages that are in use.
+    fn map_usage(&self) -> (usize, usize) {
         let info = self.env.info().unwrap();
         let stat = self.env.stat().unwrap();
+        (info.mapsize, (stat.psize as usize) * (info.last_pgno + 1))
+    }
+
+    pub fn metrics(&self) -> DatabaseMetricsSnapshot {
+        let (map_size, map_used) = self.map_usage();
+        self.metrics.snapshot(map_size, map_used)
+    }
 
-        let size_used = (stat.psize as usize) * (info.last_pgno + 1);
+    /// Commits that take at least this long are logged as warnings. Zero disables the warnings.
+    pub fn set_slow_commit_threshold(&self, threshold: Duration) {
+        self.metrics.set_slow_commit_threshold(threshold);
+    }
 
-        if threshold_size > 0 && info.mapsize - size_used < threshold_size {
-            info!("DB resize (threshold-based)");
-            info!("DB map size: {}", info.mapsize);
-            info!("Space used: {}", size_used);
-            info!("Space remaining: {}", info.mapsize - size_used);
-            info!("Size threshold: {}", threshold_size);
+    pub fn need_resize(&self, threshold_size: usize) -> bool {
+        let (mapsize, size_used) = self.map_usage();
+
+        if threshold_size > 0 && mapsize - size_used < threshold_size {
+            debug!("DB resize (threshold-based)");
+            debug!("DB map size: {}", mapsize);
+            debug!("Space used: {}", size_used);
+            debug!("Space remaining: {}", mapsize - size_used);
+            debug!("Size threshold: {}", threshold_size);
             return true;
         }
 
-        // Resize is currently not supported. So don't let the resize happen
-        // if a specific percentage is reached.
-        let resize_percent: f64 = 1_f64;
-
-        if (size_used as f64) / (info.mapsize as f64) > resize_percent {
-            info!("DB resize (percent-based)");
-            info!("DB map size: {}", info.mapsize);
-            info!("Space used: {}", size_used);
-            info!("Space remaining: {}", info.mapsize - size_used);
-            info!(
-                "Percent used: {:.2}",
-                (size_used as f64) / (info.mapsize as f64)
-            );
+        if (size_used as f64) / (mapsize as f64) > GROWTH_THRESHOLD {
+            debug!("DB resize (percent-based)");
+            debug!("DB map size: {}", mapsize);
+            debug!("Space used: {}", size_used);
+            debug!("Space remaining: {}", mapsize - size_used);
+            debug!("Percent used: {:.2}", (size_used as f64) / (mapsize as f64));
             return true;
         }
 
         false
     }
+
+    /// Doubles the size of the memory map if it is running full.
+    ///
+    /// LMDB only allows to resize the map while no transaction of this process is open. If there
+    /// is one, the resize is skipped and tried again before the next write transaction.
+    fn grow_if_needed(&self) {
+        if !self.need_resize(0) {
+            return;
+        }
+
+        let _lock = self.resize_lock.lock().unwrap();
+        if self.active_transactions.load(Ordering::SeqCst) > 0 {
+            debug!("DB resize postponed, there are open transactions");
+            return;
+        }
+
+        let (mapsize, size_used) = self.map_usage();
+        let new_mapsize = mapsize.saturating_mul(2);
+        // Safe, since no transaction is open and new ones wait for the lock.
+        match unsafe { self.env.set_mapsize(new_mapsize) } {
+            Ok(()) => info!(
+                "Grew LMDB memory map from {} to {} bytes ({} bytes used)",
+                mapsize, new_mapsize, size_used
+            ),
+            Err(e) => error!("Failed to grow LMDB memory map: {}", e),
+        }
+    }
+
+    /// Doubles the size of the memory map for a write transaction that ran out of space and was
+    /// aborted. Waits until the other transactions of this process are finished, new ones wait for
+    /// the resize. The aborted transaction is still counted as open.
+    ///
+    /// Long running read transactions only delay the write. Since the wait never ends if the
+    /// thread of the write transaction holds a read transaction as well, a warning is logged in
+    /// every [`GROWTH_WARNING_INTERVAL`] while waiting.
+    fn grow_for_write(&self) {
+        let _lock = self.resize_lock.lock().unwrap();
+        let started = Instant::now();
+        let mut next_warning = GROWTH_WARNING_INTERVAL;
+        loop {
+            let open_transactions = self.active_transactions.load(Ordering::SeqCst) - 1;
+            if open_transactions == 0 {
+                break;
+            }
+            if started.elapsed() > next_warning {
+                warn!(
+                    "LMDB memory map is full, waiting for {} open transactions to finish for {:?}",
+                    open_transactions,
+                    started.elapsed()
+                );
+                next_warning += GROWTH_WARNING_INTERVAL;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        let (mapsize, size_used) = self.map_usage();
+        let new_mapsize = mapsize.saturating_mul(2);
+        // Safe, since no other transaction is open and new ones wait for the lock.
+        unsafe { self.env.set_mapsize(new_mapsize) }.unwrap();
+        info!(
+            "Grew full LMDB memory map from {} to {} bytes ({} bytes used)",
+            mapsize, new_mapsize, size_used
+        );
+    }
+
+    /// Begins a transaction and counts it as open until the returned guard is dropped.
+    fn begin<T, F>(&self, begin: F) -> (T, ActiveTransaction<'_>)
+    where
+        F: Fn() -> Result<T, LmdbError>,
+    {
+        // Don't hold the lock while beginning the transaction, beginning a write transaction
+        // waits for other write transactions to finish.
+        {
+            let _lock = self.resize_lock.lock().unwrap();
+            self.active_transactions.fetch_add(1, Ordering::SeqCst);
+        }
+        let guard = ActiveTransaction(&self.active_transactions);
+
+        let txn = match begin() {
+            Err(LmdbError::Code(MAP_RESIZED)) if self.adopt_map_size() => begin().unwrap(),
+            result => result.unwrap(),
+        };
+
+        (txn, guard)
+    }
+
+    /// Adopts the size of the memory map after another process grew it. This is only possible if
+    /// no other transaction of this process is open.
+    fn adopt_map_size(&self) -> bool {
+        let _lock = self.resize_lock.lock().unwrap();
+        // The transaction that failed to begin is counted as open.
+        if self.active_transactions.load(Ordering::SeqCst) > 1 {
+            return false;
+        }
+
+        info!("LMDB memory map was resized by another process");
+        unsafe { self.env.set_mapsize(0).is_ok() }
+    }
+}
+
+/// Serializes the write transactions of this process. Unlike a `MutexGuard`, its guard can be
+/// sent to other threads along with the transaction.
+#[derive(Debug, Default)]
+struct WriteLock {
+    locked: Mutex<bool>,
+    released: Condvar,
+}
+
+impl WriteLock {
+    fn acquire(&self) -> WriteLockGuard<'_> {
+        let mut locked = self.locked.lock().unwrap();
+        while *locked {
+            locked = self.released.wait(locked).unwrap();
+        }
+        *locked = true;
+        WriteLockGuard(self)
+    }
+}
+
+struct WriteLockGuard<'env>(&'env WriteLock);
+
+impl<'env> Drop for WriteLockGuard<'env> {
+    fn drop(&mut self) {
+        *self.0.locked.lock().unwrap() = false;
+        self.0.released.notify_one();
+    }
+}
+
+/// Marks a transaction as open while it exists.
+struct ActiveTransaction<'env>(&'env AtomicUsize);
+
+impl<'env> Drop for ActiveTransaction<'env> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
 }
 
 #[derive(Debug)]
 pub struct LmdbDatabase {
-    db: lmdb_zero::Database<'static>,
+    db: Arc<lmdb_zero::Database<'static>>,
+}
+
+/// A write of a transaction, kept to replay it in a new transaction if the memory map runs full.
+enum JournalEntry {
+    Put {
+        db: Arc<lmdb_zero::Database<'static>>,
+        key: Vec<u8>,
+        value: Vec<u8>,
+    },
+    Remove {
+        db: Arc<lmdb_zero::Database<'static>>,
+        key: Vec<u8>,
+    },
+    RemoveItem {
+        db: Arc<lmdb_zero::Database<'static>>,
+        key: Vec<u8>,
+        value: Vec<u8>,
+    },
+}
+
+impl JournalEntry {
+    fn apply(&self, txn: &lmdb_zero::WriteTransaction) -> Result<(), LmdbError> {
+        let mut access = txn.access();
+        match self {
+            JournalEntry::Put { db, key, value } => {
+                access.put(db, &key[..], &value[..], lmdb_zero::put::Flags::empty())
+            }
+            JournalEntry::Remove { db, key } => access.del_key(db, &key[..]).to_opt().map(|_| ()),
+            JournalEntry::RemoveItem { db, key, value } => access
+                .del_item(db, &key[..], &value[..])
+                .to_opt()
+                .map(|_| ()),
+        }
+    }
 }
 
 pub struct LmdbReadTransaction<'env> {
     txn: lmdb_zero::ReadTransaction<'env>,
+    _timer: ReadTransactionTimer<'env>,
+    _active: ActiveTransaction<'env>,
 }
 
 impl<'env> LmdbReadTransaction<'env> {
     pub(super) fn new(env: &'env LmdbEnvironment) -> Self {
-        // This is an implicit transaction, so take the lock first.
+        let (txn, active) = env.begin(|| lmdb_zero::ReadTransaction::new(Arc::clone(&env.env)));
         LmdbReadTransaction {
-            txn: lmdb_zero::ReadTransaction::new(Arc::clone(&env.env)).unwrap(),
+            txn,
+            _timer: ReadTransactionTimer::start(&env.metrics),
+            _active: active,
         }
     }
 
@@ -188,7 +410,7 @@ impl<'env> LmdbReadTransaction<'env> {
     }
 
     pub(super) fn cursor<'txn, 'db>(&'txn self, db: &'db Database) -> LmdbCursor<'txn, 'db> {
-        let cursor = self.txn.cursor(&db.persistent().unwrap().db).unwrap();
+        let cursor = self.txn.cursor(&*db.persistent().unwrap().db).unwrap();
         LmdbCursor {
             raw: RawLmdbCursor { cursor },
             txn: &self.txn,
@@ -202,18 +424,47 @@ impl<'env> fmt::Debug for LmdbReadTransaction<'env> {
     }
 }
 
+/// A write transaction.
+///
+/// Its writes are journaled. If the memory map runs full within the transaction, the transaction
+/// is aborted, the memory map is grown and the writes are replayed in a new transaction, which
+/// the caller continues with. No other write is committed meanwhile, so the replayed transaction
+/// starts from the same state. A removal through a write cursor that runs out of space can't be
+/// replayed while the cursor borrows the transaction and still panics.
 pub struct LmdbWriteTransaction<'env> {
-    txn: lmdb_zero::WriteTransaction<'env>,
+    env: &'env LmdbEnvironment,
+    txn: Option<lmdb_zero::WriteTransaction<'env>>,
+    journal: RefCell<Vec<JournalEntry>>,
+    timer: Option<WriteTransactionTimer<'env>>,
+    _active: ActiveTransaction<'env>,
+    _write_lock: WriteLockGuard<'env>,
 }
 
 impl<'env> LmdbWriteTransaction<'env> {
     pub(super) fn new(env: &'env LmdbEnvironment) -> Self {
+        // LMDB only allows one write transaction at a time anyway. Wait for the other one before
+        // this transaction is counted as open, it couldn't grow the memory map otherwise.
+        let write_lock = env.write_lock.acquire();
         // Check for enough space before every write transaction.
-        if env.need_resize(0) {
-            log::error!("DB needs resize, resize not supported");
-        }
+        env.grow_if_needed();
+        let (txn, active) = env.begin(|| lmdb_zero::WriteTransaction::new(Arc::clone(&env.env)));
         LmdbWriteTransaction {
-            txn: lmdb_zero::WriteTransaction::new(Arc::clone(&env.env)).unwrap(),
+            env,
+            txn: Some(txn),
+            journal: RefCell::new(vec![]),
+            timer: Some(WriteTransactionTimer::start(&env.metrics)),
+            _active: active,
+            _write_lock: write_lock,
+        }
+    }
+
+    fn txn(&self) -> &lmdb_zero::WriteTransaction<'env> {
+        self.txn.as_ref().unwrap()
+    }
+
+    fn add_bytes(&mut self, bytes: usize) {
+        if let Some(timer) = &mut self.timer {
+            timer.add_bytes(bytes);
         }
     }
 
@@ -222,7 +473,7 @@ impl<'env> LmdbWriteTransaction<'env> {
         K: AsDatabaseBytes + ?Sized,
         V: FromDatabaseValue,
     {
-        let access = self.txn.access();
+        let access = self.txn().access();
         let result: Option<&[u8]> = access
             .get(&db.db, AsDatabaseBytes::as_database_bytes(key).as_ref())
             .to_opt()
@@ -235,20 +486,17 @@ impl<'env> LmdbWriteTransaction<'env> {
         K: AsDatabaseBytes + ?Sized,
         V: IntoDatabaseValue + ?Sized,
     {
+        // The journal needs a copy of the value anyway, so it is serialized into the journal
+        // rather than into a reserved space of the database.
         let key = AsDatabaseBytes::as_database_bytes(key);
-        let value_size = IntoDatabaseValue::database_byte_size(value);
-        unsafe {
-            let mut access = self.txn.access();
-            let bytes: &mut [u8] = access
-                .put_reserve_unsized(
-                    &db.db,
-                    key.as_ref(),
-                    value_size,
-                    lmdb_zero::put::Flags::empty(),
-                )
-                .unwrap();
-            IntoDatabaseValue::copy_into_database(value, bytes);
-        }
+        let mut value_bytes = vec![0u8; IntoDatabaseValue::database_byte_size(value)];
+        IntoDatabaseValue::copy_into_database(value, &mut value_bytes);
+        self.add_bytes(key.as_ref().len() + value_bytes.len());
+        self.write(JournalEntry::Put {
+            db: Arc::clone(&db.db),
+            key: key.as_ref().to_vec(),
+            value: value_bytes,
+        });
     }
 
     pub(super) fn put<K, V>(&mut self, db: &LmdbDatabase, key: &K, value: &V)
@@ -258,26 +506,22 @@ impl<'env> LmdbWriteTransaction<'env> {
     {
         let key = AsDatabaseBytes::as_database_bytes(key);
         let value = AsDatabaseBytes::as_database_bytes(value);
-        let mut access = self.txn.access();
-        access
-            .put(
-                &db.db,
-                key.as_ref(),
-                value.as_ref(),
-                lmdb_zero::put::Flags::empty(),
-            )
-            .unwrap();
+        self.add_bytes(key.as_ref().len() + value.as_ref().len());
+        self.write(JournalEntry::Put {
+            db: Arc::clone(&db.db),
+            key: key.as_ref().to_vec(),
+            value: value.as_ref().to_vec(),
+        });
     }
 
     pub(super) fn remove<K>(&mut self, db: &LmdbDatabase, key: &K)
     where
         K: AsDatabaseBytes + ?Sized,
     {
-        let mut access = self.txn.access();
-        access
-            .del_key(&db.db, AsDatabaseBytes::as_database_bytes(key).as_ref())
-            .to_opt()
-            .unwrap();
+        self.write(JournalEntry::Remove {
+            db: Arc::clone(&db.db),
+            key: AsDatabaseBytes::as_database_bytes(key).as_ref().to_vec(),
+        });
     }
 
     pub(super) fn remove_item<K, V>(&mut self, db: &LmdbDatabase, key: &K, value: &V)
@@ -285,26 +529,66 @@ impl<'env> LmdbWriteTransaction<'env> {
         K: AsDatabaseBytes + ?Sized,
         V: AsDatabaseBytes + ?Sized,
     {
-        let mut access = self.txn.access();
-        access
-            .del_item(
-                &db.db,
-                AsDatabaseBytes::as_database_bytes(key).as_ref(),
-                AsDatabaseBytes::as_database_bytes(value).as_ref(),
-            )
-            .to_opt()
-            .unwrap();
+        self.write(JournalEntry::RemoveItem {
+            db: Arc::clone(&db.db),
+            key: AsDatabaseBytes::as_database_bytes(key).as_ref().to_vec(),
+            value: AsDatabaseBytes::as_database_bytes(value).as_ref().to_vec(),
+        });
     }
 
-    pub(super) fn commit(self) {
-        self.txn.commit().unwrap();
+    /// Applies the write and journals it. If the memory map is full, the transaction is replayed,
+    /// including this write.
+    fn write(&mut self, entry: JournalEntry) {
+        let result = entry.apply(self.txn());
+        self.journal.get_mut().push(entry);
+        match result {
+            Ok(()) => {}
+            Err(LmdbError::Code(MAP_FULL)) => self.replay(),
+            Err(e) => panic!("LMDB write failed: {}", e),
+        }
+    }
+
+    /// Aborts the transaction, grows the memory map and replays the journal in a new transaction,
+    /// until the journal fits.
+    fn replay(&mut self) {
+        loop {
+            // Abort the transaction that ran out of space.
+            self.txn = None;
+            self.env.grow_for_write();
+
+            let txn = lmdb_zero::WriteTransaction::new(Arc::clone(&self.env.env)).unwrap();
+            let result = self
+                .journal
+                .get_mut()
+                .iter()
+                .try_for_each(|entry| entry.apply(&txn));
+            self.txn = Some(txn);
+            match result {
+                Ok(()) => return,
+                Err(LmdbError::Code(MAP_FULL)) => {}
+                Err(e) => panic!("LMDB write failed: {}", e),
+            }
+        }
+    }
+
+    pub(super) fn commit(mut self) {
+        let timer = self.timer.take().unwrap();
+        timer.commit(|| loop {
+            // Committing needs space as well, e.g. for the list of free pages. A failed commit
+            // aborts the transaction.
+            match self.txn.take().unwrap().commit() {
+                Ok(()) => return,
+                Err(LmdbError::Code(MAP_FULL)) => self.replay(),
+                Err(e) => panic!("LMDB commit failed: {}", e),
+            }
+        });
     }
 
     pub(super) fn cursor<'txn, 'db>(&'txn self, db: &'db Database) -> LmdbCursor<'txn, 'db> {
-        let cursor = self.txn.cursor(&db.persistent().unwrap().db).unwrap();
+        let cursor = self.txn().cursor(&*db.persistent().unwrap().db).unwrap();
         LmdbCursor {
             raw: RawLmdbCursor { cursor },
-            txn: &self.txn,
+            txn: self.txn(),
         }
     }
 
@@ -312,17 +596,20 @@ impl<'env> LmdbWriteTransaction<'env> {
         &'txn self,
         db: &'db Database,
     ) -> LmdbWriteCursor<'txn, 'db> {
-        let cursor = self.txn.cursor(&db.persistent().unwrap().db).unwrap();
+        let db = db.persistent().unwrap();
+        let cursor = self.txn().cursor(&*db.db).unwrap();
         LmdbWriteCursor {
             raw: RawLmdbCursor { cursor },
-            txn: &self.txn,
+            txn: self.txn(),
+            db: Arc::clone(&db.db),
+            journal: &self.journal,
         }
     }
 }
 
 impl<'env> fmt::Debug for LmdbWriteTransaction<'env> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "LmdbWriteTransaction {{ txn: {:?} }}", self.txn)
+        write!(f, "LmdbWriteTransaction {{ txn: {:?} }}", self.txn())
     }
 }
 
@@ -556,6 +843,8 @@ impl_read_cursor_from_raw!(LmdbCursor<'txn, 'db>, raw, txn);
 pub struct LmdbWriteCursor<'txn, 'db> {
     raw: RawLmdbCursor<'txn, 'db>,
     txn: &'txn lmdb_zero::WriteTransaction<'txn>,
+    db: Arc<lmdb_zero::Database<'static>>,
+    journal: &'txn RefCell<Vec<JournalEntry>>,
 }
 
 impl_read_cursor_from_raw!(LmdbWriteCursor<'txn, 'db>, raw, txn);
@@ -563,10 +852,19 @@ impl_read_cursor_from_raw!(LmdbWriteCursor<'txn, 'db>, raw, txn);
 impl<'txn, 'db> WriteCursorTrait for LmdbWriteCursor<'txn, 'db> {
     fn remove(&mut self) {
         let mut access = self.txn.access();
+        // Journal the removed item, so the removal is replayed if a later write of the
+        // transaction runs out of space.
+        let (key, value): (&[u8], &[u8]) = self.raw.cursor.get_current(&access).unwrap();
+        let entry = JournalEntry::RemoveItem {
+            db: Arc::clone(&self.db),
+            key: key.to_vec(),
+            value: value.to_vec(),
+        };
         self.raw
             .cursor
             .del(&mut access, lmdb_zero::del::Flags::empty())
             .unwrap();
+        self.journal.borrow_mut().push(entry);
     }
 }
 
@@ -628,6 +926,45 @@ mod tests {
         env.drop_database().unwrap();
     }
 
+    #[test]
+    fn it_grows_a_full_map_once_read_transactions_finished() {
+        let env = LmdbEnvironment::new("./test5", 0, 1, open::NOTLS).unwrap();
+        {
+            let db = env.open_database("test".to_string());
+
+            // Hold a read transaction open in another thread while the map runs full.
+            let (opened_tx, opened_rx) = std::sync::mpsc::channel();
+            let reader_env = env.clone();
+            let reader = std::thread::spawn(move || {
+                let tx = ReadTransaction::new(&reader_env);
+                opened_tx.send(()).unwrap();
+                std::thread::sleep(Duration::from_millis(500));
+                tx.close();
+                Instant::now()
+            });
+            opened_rx.recv().unwrap();
+
+            // Write more than the initial map size of 10 MiB.
+            let value = vec![1u8; 100 * 1024];
+            let mut tx = WriteTransaction::new(&env);
+            for i in 0..200u32 {
+                tx.put(&db, &i, &value);
+            }
+            tx.commit();
+            let committed = Instant::now();
+
+            // The map could only be grown after the read transaction finished.
+            assert!(reader.join().unwrap() <= committed);
+
+            let tx = ReadTransaction::new(&env);
+            for i in 0..200u32 {
+                assert_eq!(tx.get::<u32, Vec<u8>>(&db, &i), Some(value.clone()));
+            }
+        }
+
+        env.drop_database().unwrap();
+    }
+
     #[test]
     fn isolation_test() {
         let env = LmdbEnvironment::new("./test2", 0, 1, open::NOTLS).unwrap();
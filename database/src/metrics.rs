@@ -0,0 +1,219 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Upper bounds of the buckets of the transaction duration histograms, in milliseconds. Durations
+/// above the last bound are counted in an additional bucket.
+pub const DURATION_BUCKETS_MS: [u64; 8] = [1, 5, 10, 50, 100, 500, 1000, 5000];
+
+/// Commits that take longer than this are logged, unless configured otherwise.
+pub const DEFAULT_SLOW_COMMIT_THRESHOLD: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Default)]
+struct DurationHistogram {
+    buckets: [AtomicU64; DURATION_BUCKETS_MS.len() + 1],
+    count: AtomicU64,
+    total_us: AtomicU64,
+    max_us: AtomicU64,
+}
+
+impl DurationHistogram {
+    fn record(&self, duration: Duration) {
+        let ms = duration.as_millis() as u64;
+        let bucket = DURATION_BUCKETS_MS
+            .iter()
+            .position(|&bound| ms <= bound)
+            .unwrap_or(DURATION_BUCKETS_MS.len());
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+
+        let us = duration.as_micros() as u64;
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.total_us.fetch_add(us, Ordering::Relaxed);
+        self.max_us.fetch_max(us, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> DurationHistogramSnapshot {
+        DurationHistogramSnapshot {
+            buckets: self
+                .buckets
+                .iter()
+                .map(|bucket| bucket.load(Ordering::Relaxed))
+                .collect(),
+            count: self.count.load(Ordering::Relaxed),
+            total: Duration::from_micros(self.total_us.load(Ordering::Relaxed)),
+            max: Duration::from_micros(self.max_us.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// The distribution of the durations of a kind of transaction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DurationHistogramSnapshot {
+    /// Number of transactions per bucket of `DURATION_BUCKETS_MS`, plus the number of transactions
+    /// that took longer than the last bucket.
+    pub buckets: Vec<u64>,
+    pub count: u64,
+    pub total: Duration,
+    pub max: Duration,
+}
+
+/// Counters of the transactions of a database environment.
+#[derive(Debug)]
+pub(crate) struct DatabaseMetrics {
+    read_transactions: DurationHistogram,
+    write_transactions: DurationHistogram,
+    commits: DurationHistogram,
+    bytes_committed: AtomicU64,
+    largest_commit: AtomicU64,
+    slow_commits: AtomicU64,
+    slow_commit_threshold_ms: AtomicU64,
+}
+
+impl Default for DatabaseMetrics {
+    fn default() -> Self {
+        DatabaseMetrics {
+            read_transactions: Default::default(),
+            write_transactions: Default::default(),
+            commits: Default::default(),
+            bytes_committed: Default::default(),
+            largest_commit: Default::default(),
+            slow_commits: Default::default(),
+            slow_commit_threshold_ms: AtomicU64::new(
+                DEFAULT_SLOW_COMMIT_THRESHOLD.as_millis() as u64
+            ),
+        }
+    }
+}
+
+impl DatabaseMetrics {
+    pub fn set_slow_commit_threshold(&self, threshold: Duration) {
+        self.slow_commit_threshold_ms
+            .store(threshold.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    pub fn read_finished(&self, started: Instant) {
+        self.read_transactions.record(started.elapsed());
+    }
+
+    /// Records a write transaction that was committed or aborted. `commit` is the duration of the
+    /// commit itself and the number of bytes that were written, `None` if the transaction was
+    /// aborted.
+    pub fn write_finished(&self, started: Instant, commit: Option<(Duration, usize)>) {
+        let duration = started.elapsed();
+        self.write_transactions.record(duration);
+
+        if let Some((commit_duration, size)) = commit {
+            self.commits.record(commit_duration);
+            self.bytes_committed
+                .fetch_add(size as u64, Ordering::Relaxed);
+            self.largest_commit
+                .fetch_max(size as u64, Ordering::Relaxed);
+
+            let threshold = self.slow_commit_threshold_ms.load(Ordering::Relaxed);
+            if threshold > 0 && commit_duration.as_millis() as u64 >= threshold {
+                self.slow_commits.fetch_add(1, Ordering::Relaxed);
+                warn!(
+                    "Slow database commit: {:?} to commit {} bytes, transaction was open for {:?}",
+                    commit_duration, size, duration
+                );
+            }
+        }
+    }
+
+    pub fn snapshot(&self, map_size: usize, map_used: usize) -> DatabaseMetricsSnapshot {
+        DatabaseMetricsSnapshot {
+            read_transactions: self.read_transactions.snapshot(),
+            write_transactions: self.write_transactions.snapshot(),
+            commits: self.commits.snapshot(),
+            bytes_committed: self.bytes_committed.load(Ordering::Relaxed),
+            largest_commit: self.largest_commit.load(Ordering::Relaxed),
+            slow_commits: self.slow_commits.load(Ordering::Relaxed),
+            map_size,
+            map_used,
+        }
+    }
+}
+
+/// The metrics of a database environment since it was opened.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DatabaseMetricsSnapshot {
+    /// Time read transactions were open.
+    pub read_transactions: DurationHistogramSnapshot,
+    /// Time write transactions were open, including the commit.
+    pub write_transactions: DurationHistogramSnapshot,
+    /// Time it took to commit write transactions.
+    pub commits: DurationHistogramSnapshot,
+    /// Total size of the keys and values written by committed transactions.
+    pub bytes_committed: u64,
+    /// Size of the keys and values written by the largest commit.
+    pub largest_commit: u64,
+    /// Number of commits that took longer than the slow commit threshold.
+    pub slow_commits: u64,
+    /// Size of the memory map.
+    pub map_size: usize,
+    /// Size of the pages of the memory map that are in use.
+    pub map_used: usize,
+}
+
+/// Records the duration of a read transaction when it is dropped.
+pub(crate) struct ReadTransactionTimer<'env> {
+    metrics: &'env DatabaseMetrics,
+    started: Instant,
+}
+
+impl<'env> ReadTransactionTimer<'env> {
+    pub fn start(metrics: &'env DatabaseMetrics) -> Self {
+        ReadTransactionTimer {
+            metrics,
+            started: Instant::now(),
+        }
+    }
+}
+
+impl<'env> Drop for ReadTransactionTimer<'env> {
+    fn drop(&mut self) {
+        self.metrics.read_finished(self.started);
+    }
+}
+
+/// Records the duration and size of a write transaction when it is committed, or its duration
+/// when it is dropped without committing.
+pub(crate) struct WriteTransactionTimer<'env> {
+    metrics: &'env DatabaseMetrics,
+    started: Instant,
+    bytes_written: usize,
+    committed: bool,
+}
+
+impl<'env> WriteTransactionTimer<'env> {
+    pub fn start(metrics: &'env DatabaseMetrics) -> Self {
+        WriteTransactionTimer {
+            metrics,
+            started: Instant::now(),
+            bytes_written: 0,
+            committed: false,
+        }
+    }
+
+    pub fn add_bytes(&mut self, bytes: usize) {
+        self.bytes_written += bytes;
+    }
+
+    /// Runs and times the commit of the transaction.
+    pub fn commit<F: FnOnce()>(mut self, commit: F) {
+        let commit_started = Instant::now();
+        commit();
+        self.committed = true;
+        self.metrics.write_finished(
+            self.started,
+            Some((commit_started.elapsed(), self.bytes_written)),
+        );
+    }
+}
+
+impl<'env> Drop for WriteTransactionTimer<'env> {
+    fn drop(&mut self) {
+        if !self.committed {
+            self.metrics.write_finished(self.started, None);
+        }
+    }
+}
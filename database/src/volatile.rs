@@ -111,6 +111,14 @@ impl VolatileEnvironment {
     pub(super) fn drop_database(self) -> io::Result<()> {
         Ok(())
     }
+
+    pub(super) fn metrics(&self) -> DatabaseMetricsSnapshot {
+        self.env.metrics()
+    }
+
+    pub(super) fn set_slow_commit_threshold(&self, threshold: Duration) {
+        self.env.set_slow_commit_threshold(threshold)
+    }
 }
 
 #[derive(Debug)]
@@ -701,4 +709,88 @@ mod tests {
 
         env.drop_database().unwrap();
     }
+
+    #[test]
+    fn it_records_transaction_metrics() {
+        let env = VolatileEnvironment::new(1).unwrap();
+        let db = env.open_database("test".to_string());
+
+        let mut tx = WriteTransaction::new(&env);
+        tx.put_reserve(&db, "test", "one");
+        tx.commit();
+
+        let mut tx = WriteTransaction::new(&env);
+        tx.put_reserve(&db, "test", "two");
+        tx.abort();
+
+        ReadTransaction::new(&env).close();
+
+        let metrics = env.metrics();
+        assert_eq!(metrics.read_transactions.count, 1);
+        assert_eq!(metrics.write_transactions.count, 2);
+        assert_eq!(metrics.commits.count, 1);
+        assert_eq!(metrics.commits.buckets.iter().sum::<u64>(), 1);
+        assert!(metrics.bytes_committed > 0);
+        assert_eq!(metrics.largest_commit, metrics.bytes_committed);
+        assert!(metrics.map_used <= metrics.map_size);
+    }
+
+    #[test]
+    fn it_grows_the_memory_map() {
+        let env = VolatileEnvironment::new(1).unwrap();
+        let db = env.open_database("test".to_string());
+        let initial_size = env.metrics().map_size;
+
+        // Write more than fits into the initial memory map.
+        let value = vec![0u8; 64 * 1024];
+        let num_values = 2 * initial_size / value.len();
+        for i in 0..num_values {
+            let mut tx = WriteTransaction::new(&env);
+            tx.put(&db, &i.to_be_bytes().to_vec(), &value);
+            tx.commit();
+        }
+
+        assert!(env.metrics().map_size > initial_size);
+        let tx = ReadTransaction::new(&env);
+        assert_eq!(
+            tx.get::<Vec<u8>, Vec<u8>>(&db, &(num_values - 1).to_be_bytes().to_vec()),
+            Some(value)
+        );
+    }
+
+    #[test]
+    fn it_grows_the_memory_map_within_a_transaction() {
+        let env = VolatileEnvironment::new(1).unwrap();
+        let db = env.open_database("test".to_string());
+        let initial_size = env.metrics().map_size;
+
+        let mut tx = WriteTransaction::new(&env);
+        tx.put(&db, "removed", "value");
+        tx.commit();
+
+        // Write more than fits into the initial memory map within one transaction. The writes
+        // before the memory map ran full, including the removal through the cursor, are replayed.
+        let mut tx = WriteTransaction::new(&env);
+        {
+            let mut cursor = tx.write_cursor(&db);
+            assert!(cursor.seek_key::<str, String>("removed").is_some());
+            cursor.remove();
+        }
+        let value = vec![0u8; 64 * 1024];
+        let num_values = 2 * initial_size / value.len();
+        for i in 0..num_values {
+            tx.put(&db, &i.to_be_bytes().to_vec(), &value);
+        }
+        tx.commit();
+
+        assert!(env.metrics().map_size > initial_size);
+        let tx = ReadTransaction::new(&env);
+        assert!(tx.get::<str, String>(&db, "removed").is_none());
+        for i in 0..num_values {
+            assert_eq!(
+                tx.get::<Vec<u8>, Vec<u8>>(&db, &i.to_be_bytes().to_vec()),
+                Some(value.clone())
+            );
+        }
+    }
 }
@@ -85,7 +85,7 @@ impl<
         sender: LevelUpdateSender<P, T>,
     ) -> Self {
         // Invoke the partitioner to create the level structure of peers.
-        let levels: Vec<Level> = Level::create_levels(protocol.partitioner());
+        let levels: Vec<Level> = Level::create_levels(protocol.partitioner(), config.rng.clone());
 
         // Create an empty todo list which can later be polled for the best available todo.
         let mut todos = TodoList::new(protocol.evaluator(), input_stream);
@@ -1,5 +1,7 @@
 use std::time::Duration;
 
+use utils::shared_rng::SharedRng;
+
 #[derive(Clone, Debug)]
 pub struct Config {
     /// Number of peers contacted during an update at each level
@@ -13,6 +15,9 @@ pub struct Config {
 
     /// How many peers are contacted at each level
     pub peer_count: usize,
+
+    /// Shuffles the peers of each level
+    pub rng: SharedRng,
 }
 
 impl Default for Config {
@@ -22,6 +27,7 @@ impl Default for Config {
             update_interval: Duration::from_millis(200),
             timeout: Duration::from_millis(400),
             peer_count: 16,
+            rng: SharedRng::os(),
         }
     }
 }
@@ -3,7 +3,8 @@ use std::sync::Arc;
 
 use parking_lot::RwLock;
 use rand::seq::SliceRandom;
-use rand::thread_rng;
+
+use utils::shared_rng::SharedRng;
 
 use crate::contribution::AggregatableContribution;
 use crate::partitioner::{Partitioner, PartitioningError};
@@ -45,11 +46,10 @@ impl Level {
         self.peer_ids.len()
     }
 
-    pub fn create_levels<P: Partitioner>(partitioner: Arc<P>) -> Vec<Level> {
+    pub fn create_levels<P: Partitioner>(partitioner: Arc<P>, mut rng: SharedRng) -> Vec<Level> {
         let mut levels: Vec<Level> = Vec::new();
         let mut first_active = false;
         let mut send_expected_full_size: usize = 1;
-        let mut rng = thread_rng();
 
         for i in 0..partitioner.levels() {
             match partitioner.range(i) {
@@ -5,7 +5,7 @@ use nimiq_network_interface::message::Message;
 
 use crate::contribution::AggregatableContribution;
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, Schema)]
 pub struct LevelUpdate<C: AggregatableContribution> {
     /// The updated multi-signature for this level
     pub aggregate: C,
@@ -58,7 +58,7 @@ impl<C: AggregatableContribution> LevelUpdate<C> {
     }
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, Schema)]
 pub struct LevelUpdateMessage<
     C: AggregatableContribution,
     T: Clone + Debug + Serialize + Deserialize + Send + Unpin,
@@ -30,6 +30,7 @@ use nimiq_handel::verifier;
 use nimiq_network_interface::message::Message;
 use nimiq_network_interface::network::Network;
 use nimiq_network_mock::{MockHub, MockNetwork};
+use nimiq_utils::shared_rng::SharedRng;
 
 /// Dump Aggregate adding numbers.
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -259,6 +260,7 @@ async fn it_can_aggregate() {
         update_interval: Duration::from_millis(500),
         timeout: Duration::from_millis(500),
         peer_count: 1,
+        rng: SharedRng::seeded(42),
     };
 
     let stopped = Arc::new(RwLock::new(false));
@@ -28,6 +28,8 @@ pub enum AddressParseError {
     WrongLength,
     #[error("Invalid checksum")]
     InvalidChecksum,
+    #[error("Invalid character")]
+    InvalidCharacter,
     // from Hash
     #[error("Invalid hash")]
     InvalidHash,
@@ -38,7 +40,9 @@ pub enum AddressParseError {
 
 impl Address {
     const CCODE: &'static str = "NQ";
-    const NIMIQ_ALPHABET: &'static str = "0123456789ABCDEFGHJKLMNPQRSTUVXY";
+    pub const NIMIQ_ALPHABET: &'static str = "0123456789ABCDEFGHJKLMNPQRSTUVXY";
+    /// Length of the user-friendly address without spaces.
+    pub const FRIENDLY_LEN: usize = 36;
 
     pub fn as_bytes(&self) -> &[u8] {
         &self.0
@@ -47,12 +51,27 @@ impl Address {
     pub fn from_user_friendly_address(friendly_addr: &str) -> Result<Address, AddressParseError> {
         let friendly_addr_wospace = str::replace(friendly_addr, " ", "");
 
-        if friendly_addr_wospace.len() != 36 {
+        if !friendly_addr_wospace.is_ascii() {
+            return Err(AddressParseError::InvalidCharacter);
+        }
+        if friendly_addr_wospace.len() != Address::FRIENDLY_LEN {
             return Err(AddressParseError::WrongLength);
         }
         if friendly_addr_wospace[0..2].to_uppercase() != Address::CCODE {
             return Err(AddressParseError::WrongCountryCode);
         }
+        // The checksum digits and the base32 encoded address. Checking them here also keeps
+        // invalid characters away from the checksum computation and the decoding below.
+        if !friendly_addr_wospace[2..4]
+            .chars()
+            .all(|c| c.is_ascii_digit())
+            || !friendly_addr_wospace[4..]
+                .to_uppercase()
+                .chars()
+                .all(|c| Address::NIMIQ_ALPHABET.contains(c))
+        {
+            return Err(AddressParseError::InvalidCharacter);
+        }
         let mut twisted_str = String::with_capacity(friendly_addr_wospace.len());
         twisted_str.push_str(&friendly_addr_wospace[4..]);
         twisted_str.push_str(&friendly_addr_wospace[..4]);
@@ -65,8 +84,8 @@ impl Address {
         let encoding = spec.encoding().unwrap();
 
         let b_vec = encoding
-            .decode(friendly_addr_wospace[4..].as_bytes())
-            .unwrap();
+            .decode(friendly_addr_wospace[4..].to_uppercase().as_bytes())
+            .map_err(|_| AddressParseError::InvalidCharacter)?;
         let mut b = [0; 20];
         b.copy_from_slice(&b_vec[..b_vec.len()]);
         Ok(Address(b))
@@ -58,6 +58,7 @@ macro_rules! implement_simple_add_sum_traits {
 }
 
 pub mod multisig;
+pub mod vanity;
 
 mod address;
 mod errors;
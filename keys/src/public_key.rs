@@ -7,7 +7,9 @@ use std::str::FromStr;
 
 use hex::FromHex;
 
-use beserial::{Deserialize, ReadBytesExt, Serialize, SerializingError, WriteBytesExt};
+use beserial::{
+    Deserialize, ReadBytesExt, Schema, Serialize, SerializingError, TypeSchema, WriteBytesExt,
+};
 use hash::{Hash, SerializeContent};
 
 use crate::errors::{KeysError, ParseError};
@@ -148,6 +150,18 @@ impl Serialize for PublicKey {
     }
 }
 
+impl Schema for PublicKey {
+    fn name() -> String {
+        "Ed25519PublicKey".to_string()
+    }
+
+    fn schema() -> TypeSchema {
+        TypeSchema::Bytes {
+            len: PublicKey::SIZE,
+        }
+    }
+}
+
 impl SerializeContent for PublicKey {
     fn serialize_content<W: io::Write>(&self, writer: &mut W) -> io::Result<usize> {
         Ok(self.serialize(writer)?)
@@ -3,7 +3,9 @@ use std::str::FromStr;
 
 use hex::FromHex;
 
-use beserial::{Deserialize, ReadBytesExt, Serialize, SerializingError, WriteBytesExt};
+use beserial::{
+    Deserialize, ReadBytesExt, Schema, Serialize, SerializingError, TypeSchema, WriteBytesExt,
+};
 
 use crate::errors::{KeysError, ParseError};
 
@@ -87,6 +89,18 @@ impl Serialize for Signature {
     }
 }
 
+impl Schema for Signature {
+    fn name() -> String {
+        "Ed25519Signature".to_string()
+    }
+
+    fn schema() -> TypeSchema {
+        TypeSchema::Bytes {
+            len: Signature::SIZE,
+        }
+    }
+}
+
 impl FromStr for Signature {
     type Err = ParseError;
 
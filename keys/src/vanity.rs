@@ -0,0 +1,137 @@
+//! Generation of key pairs whose user-friendly address matches a pattern.
+//!
+//! The checksum digits of a user-friendly address are derived from the rest of the address, so a
+//! pattern only applies to the 32 characters after them. Every additional character in the
+//! pattern makes the search 32 times harder.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+use thiserror::Error;
+
+use utils::key_rng::SecureGenerate;
+
+use crate::{Address, KeyPair};
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum VanityPatternError {
+    #[error("The pattern is empty")]
+    Empty,
+    #[error("The pattern is longer than an address")]
+    TooLong,
+    #[error("Invalid character '{0}', addresses don't contain it")]
+    InvalidCharacter(char),
+}
+
+/// Where in the address a vanity pattern has to appear.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VanityPosition {
+    /// Right after the checksum digits.
+    Prefix,
+    /// At the end of the address.
+    Suffix,
+    /// Anywhere after the checksum digits.
+    Anywhere,
+}
+
+/// A pattern for the user-friendly address of a vanity key pair.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VanityPattern {
+    pattern: String,
+    position: VanityPosition,
+}
+
+impl VanityPattern {
+    /// Number of characters of a user-friendly address a pattern can apply to.
+    pub const MAX_LEN: usize = Address::FRIENDLY_LEN - 4;
+
+    /// Creates a new pattern. Spaces are ignored and the pattern is case-insensitive.
+    pub fn new(pattern: &str, position: VanityPosition) -> Result<Self, VanityPatternError> {
+        let pattern = pattern.replace(' ', "").to_uppercase();
+
+        if pattern.is_empty() {
+            return Err(VanityPatternError::Empty);
+        }
+        if let Some(c) = pattern
+            .chars()
+            .find(|c| !Address::NIMIQ_ALPHABET.contains(*c))
+        {
+            return Err(VanityPatternError::InvalidCharacter(c));
+        }
+        if pattern.len() > Self::MAX_LEN {
+            return Err(VanityPatternError::TooLong);
+        }
+
+        Ok(Self { pattern, position })
+    }
+
+    /// Returns whether the address matches the pattern.
+    pub fn matches(&self, address: &Address) -> bool {
+        let friendly_address = address.to_user_friendly_address().replace(' ', "");
+        let encoded = &friendly_address[4..];
+
+        match self.position {
+            VanityPosition::Prefix => encoded.starts_with(&self.pattern),
+            VanityPosition::Suffix => encoded.ends_with(&self.pattern),
+            VanityPosition::Anywhere => encoded.contains(&self.pattern),
+        }
+    }
+
+    /// Expected number of key pairs that have to be generated to find a match.
+    pub fn difficulty(&self) -> f64 {
+        let difficulty = 32f64.powi(self.pattern.len() as i32);
+        match self.position {
+            VanityPosition::Anywhere => {
+                difficulty / (Self::MAX_LEN - self.pattern.len() + 1) as f64
+            }
+            _ => difficulty,
+        }
+    }
+}
+
+/// Searches for a key pair matching the pattern, using `num_threads` threads. Returns the key pair
+/// and the total number of key pairs that were generated.
+///
+/// `attempts` is updated while the search runs and can be used to report its progress.
+pub fn generate_vanity_key_pair(
+    pattern: &VanityPattern,
+    num_threads: usize,
+    attempts: Arc<AtomicU64>,
+) -> (KeyPair, u64) {
+    let found = Arc::new(AtomicBool::new(false));
+    let (sender, receiver) = mpsc::channel();
+
+    let workers: Vec<_> = (0..num_threads.max(1))
+        .map(|_| {
+            let pattern = pattern.clone();
+            let found = Arc::clone(&found);
+            let attempts = Arc::clone(&attempts);
+            let sender = sender.clone();
+
+            thread::spawn(move || {
+                while !found.load(Ordering::Relaxed) {
+                    let key_pair = KeyPair::generate_default_csprng();
+                    attempts.fetch_add(1, Ordering::Relaxed);
+
+                    if pattern.matches(&Address::from(&key_pair)) {
+                        found.store(true, Ordering::Relaxed);
+                        // Only the first match is received, the others are dropped.
+                        let _ = sender.send(key_pair);
+                    }
+                }
+            })
+        })
+        .collect();
+    drop(sender);
+
+    let key_pair = receiver
+        .recv()
+        .expect("Vanity workers stopped without a result");
+
+    for worker in workers {
+        worker.join().expect("Vanity worker panicked");
+    }
+
+    (key_pair, attempts.load(Ordering::Relaxed))
+}
@@ -1,4 +1,9 @@
-use nimiq_keys::{Address, KeyPair, PrivateKey, PublicKey, SecureGenerate, Signature};
+use nimiq_keys::vanity::{
+    generate_vanity_key_pair, VanityPattern, VanityPatternError, VanityPosition,
+};
+use nimiq_keys::{
+    Address, AddressParseError, KeyPair, PrivateKey, PublicKey, SecureGenerate, Signature,
+};
 
 mod multisig;
 
@@ -126,3 +131,62 @@ fn it_computes_friendly_addresses() {
         addr2.to_user_friendly_address()
     );
 }
+
+#[test]
+fn it_rejects_invalid_friendly_addresses() {
+    assert!(
+        Address::from_user_friendly_address("NQ05 563U 530Y XDRT L7GQ M6HE YRNU 20FE 4PNR").is_ok()
+    );
+    assert!(
+        Address::from_user_friendly_address("nq05 563u 530y xdrt l7gq m6he yrnu 20fe 4pnr").is_ok()
+    );
+    assert!(matches!(
+        Address::from_user_friendly_address("NQ05 563U 530Y XDRT L7GQ M6HE YRNU 20FE 4PNS"),
+        Err(AddressParseError::InvalidChecksum)
+    ));
+    assert!(matches!(
+        Address::from_user_friendly_address("NQ05 563U 530Y XDRT L7GQ M6HE YRNU 20FE 4PN!"),
+        Err(AddressParseError::InvalidCharacter)
+    ));
+    assert!(matches!(
+        Address::from_user_friendly_address("NQ05 563U 530Y XDRT L7GQ M6HE YRNU 20FE 4PNÜ"),
+        Err(AddressParseError::InvalidCharacter)
+    ));
+    assert!(matches!(
+        Address::from_user_friendly_address("NQ05 563U 530Y XDRT L7GQ M6HE YRNU 20FE"),
+        Err(AddressParseError::WrongLength)
+    ));
+}
+
+#[test]
+fn it_generates_vanity_addresses() {
+    assert_eq!(
+        VanityPattern::new("NQ!", VanityPosition::Prefix),
+        Err(VanityPatternError::InvalidCharacter('!'))
+    );
+    assert_eq!(
+        VanityPattern::new("", VanityPosition::Prefix),
+        Err(VanityPatternError::Empty)
+    );
+
+    let address =
+        Address::from_user_friendly_address("NQ05 563U 530Y XDRT L7GQ M6HE YRNU 20FE 4PNR")
+            .unwrap();
+    assert!(VanityPattern::new("563u", VanityPosition::Prefix)
+        .unwrap()
+        .matches(&address));
+    assert!(VanityPattern::new("4PNR", VanityPosition::Suffix)
+        .unwrap()
+        .matches(&address));
+    assert!(VanityPattern::new("XDRT L7", VanityPosition::Anywhere)
+        .unwrap()
+        .matches(&address));
+    assert!(!VanityPattern::new("NQ05", VanityPosition::Anywhere)
+        .unwrap()
+        .matches(&address));
+
+    let pattern = VanityPattern::new("A", VanityPosition::Prefix).unwrap();
+    let (key_pair, attempts) = generate_vanity_key_pair(&pattern, 2, Default::default());
+    assert!(pattern.matches(&Address::from(&key_pair)));
+    assert!(attempts >= 1);
+}
@@ -1,9 +1,8 @@
+use std::path::PathBuf;
 use std::sync::Arc;
 
-use parking_lot::RwLock;
-
 use nimiq_block::Block;
-use nimiq_blockchain::{AbstractBlockchain, Blockchain};
+use nimiq_blockchain::{AbstractBlockchain, Blockchain, BlockchainLock};
 use nimiq_consensus::{
     sync::history::HistorySync, Consensus as AbstractConsensus,
     ConsensusProxy as AbstractConsensusProxy,
@@ -11,11 +10,15 @@ use nimiq_consensus::{
 use nimiq_database::Environment;
 use nimiq_genesis::NetworkInfo;
 use nimiq_mempool::mempool::Mempool;
-use nimiq_network_interface::network::Network as NetworkInterface;
+use nimiq_network_interface::{network::Network as NetworkInterface, recording::MessageRecorder};
+#[cfg(feature = "validator")]
+use nimiq_network_libp2p::libp2p::multiaddr::Protocol;
 use nimiq_network_libp2p::{
     discovery::peer_contacts::{PeerContact, Services},
-    Config as NetworkConfig, Multiaddr, Network,
+    Config as NetworkConfig, Multiaddr, Network, TlsCertificates,
 };
+use nimiq_utils::memory::MemoryAccounting;
+use nimiq_utils::shared_rng::SharedRng;
 use nimiq_utils::time::OffsetTime;
 #[cfg(feature = "validator")]
 use nimiq_validator::validator::Validator as AbstractValidator;
@@ -23,11 +26,15 @@ use nimiq_validator::validator::Validator as AbstractValidator;
 use nimiq_validator::validator::ValidatorProxy as AbstractValidatorProxy;
 #[cfg(feature = "validator")]
 use nimiq_validator_network::network_impl::ValidatorNetworkImpl;
+#[cfg(feature = "validator")]
+use nimiq_validator_network::validator_record::{TransportHint, ValidatorAddress};
 #[cfg(feature = "wallet")]
 use nimiq_wallet::WalletStore;
 
-use crate::config::config::ClientConfig;
+use crate::config::config::{ClientConfig, NodeRole};
+use crate::dial_priority::StakeDialPriority;
 use crate::error::Error;
+use crate::executor::Executors;
 
 /// Alias for the Consensus and Validator specialized over libp2p network
 pub type Consensus = AbstractConsensus<Network>;
@@ -59,6 +66,19 @@ pub(crate) struct ClientInner {
     #[cfg(feature = "validator")]
     validator: Option<ValidatorProxy>,
 
+    /// The mempool of nodes that keep one without running a validator. Validators use the
+    /// validator's mempool instead.
+    mempool: Option<Arc<Mempool>>,
+
+    /// The memory gauges of the subsystems.
+    memory: MemoryAccounting,
+
+    /// The directory heap profiles are written to, if the storage is persistent.
+    heap_profile_dir: Option<PathBuf>,
+
+    /// The executors that the tasks of the subsystems are spawned on.
+    executors: Executors,
+
     /// Wallet that stores keypairs for transaction signing
     #[cfg(feature = "wallet")]
     wallet_store: Arc<WalletStore>,
@@ -75,6 +95,23 @@ impl ClientInner {
         }
         let network_info = NetworkInfo::from_network_id(config.network_id);
 
+        // Make sure the role can be fulfilled with the given configuration.
+        #[cfg(feature = "validator")]
+        let has_validator_config = config.validator.is_some();
+        #[cfg(not(feature = "validator"))]
+        let has_validator_config = false;
+        if config.role.runs_validator() != has_validator_config {
+            return Err(Error::config_error(&format!(
+                "Role {} requires {} validator configuration",
+                config.role,
+                if has_validator_config { "no" } else { "a" }
+            )));
+        }
+
+        // Start the dedicated runtimes of the subsystems
+        log::debug!("Runtime configuration: {:?}", config.runtime);
+        let executors = Executors::new(&config.runtime)?;
+
         // Initialize clock
         let time = Arc::new(OffsetTime::new());
 
@@ -86,11 +123,17 @@ impl ClientInner {
             identity_keypair.public().to_peer_id().to_base58()
         );
 
+        // Followers don't keep the bodies of old micro blocks.
+        let mut services = config.role.services();
+        if config.consensus.sync_mode.prunes_micro_bodies() {
+            services.remove(Services::BLOCK_HISTORY);
+        }
+
         // Generate peer contact from identity keypair and services/protocols
         let mut peer_contact = PeerContact::new(
             config.network.listen_addresses.clone(),
             identity_keypair.public(),
-            Services::all(), // TODO
+            services,
             None,
         );
         peer_contact.set_current_time();
@@ -103,45 +146,131 @@ impl ClientInner {
             .map(|seed| seed.address)
             .collect();
 
+        // Open database
+        let environment = config.storage.database(
+            config.network_id,
+            config.consensus.sync_mode,
+            config.database,
+        )?;
+        let mut blockchain =
+            Blockchain::new(environment.clone(), config.network_id, Arc::clone(&time)).unwrap();
+        if blockchain.is_reindexing() {
+            return Err(Error::config_error(
+                "An interrupted reindex is incomplete, run `nimiq-client db reindex` to finish it",
+            ));
+        }
+        blockchain.prune_micro_bodies = config.consensus.sync_mode.prunes_micro_bodies();
+        let blockchain = Arc::new(BlockchainLock::new(blockchain));
+
+        // Only tests seed the randomness, see `ClientConfig::rng_seed`.
+        let rng = config
+            .rng_seed
+            .map_or_else(SharedRng::os, SharedRng::seeded);
+
         // Setup libp2p network
-        let network_config = NetworkConfig::new(
+        let mut network_config = NetworkConfig::new(
             identity_keypair,
             peer_contact,
             seeds,
             network_info.genesis_hash().clone(),
         );
+        if let Some(path) = &config.network.record_messages {
+            log::info!("Recording inbound network messages to {}", path.display());
+            network_config.recorder = Some(Arc::new(MessageRecorder::create(path)?));
+        }
+        network_config.trusted_proxies = config.network.trusted_proxies.clone();
+        network_config.plain_tcp = config.network.plain_tcp;
+        network_config.relay_server = config.network.relay_server;
+        network_config.seed = config.role == NodeRole::Seed;
+        network_config.port_mapping = config.network.port_mapping;
+        if let Some(tls) = &config.network.tls {
+            network_config.tls = Some(Arc::new(TlsCertificates::load(
+                &tls.certificate_file,
+                &tls.private_key_file,
+            )?));
+        }
+        // Validators that restart quickly shouldn't process the messages they have already seen.
+        #[cfg(feature = "validator")]
+        if config.validator.is_some() {
+            network_config.seen_messages_file = config.storage.seen_messages_path();
+        }
+        // Prefer the peers of the current validators when dialing.
+        let dial_priority = Arc::new(StakeDialPriority::new());
+        network_config.dial_priority = Some(Arc::clone(&dial_priority) as _);
+        network_config.runtime = executors.network.handle();
+        network_config.rng = rng.fork();
 
         log::debug!("listen_addresses = {:?}", config.network.listen_addresses);
+        log::debug!(
+            "Network registry: {} message types, {} topics",
+            crate::registry::REGISTRY.messages().count(),
+            crate::registry::REGISTRY.topics().count()
+        );
 
         let network = Arc::new(Network::new(Arc::clone(&time), network_config).await);
+        executors
+            .network
+            .spawn(dial_priority.run(Arc::clone(&blockchain), Arc::clone(&network)));
+
+        // Count the memory of the subsystems and apply the configured caps.
+        let memory = MemoryAccounting::new();
+        memory.register("peer_buffers", network.peer_buffer_memory());
 
         // Start buffering network events as early as possible
         let network_events = network.subscribe_events();
 
-        // Open database
-        let environment = config.storage.database(
-            config.network_id,
-            config.consensus.sync_mode,
-            config.database,
-        )?;
-        let blockchain = Arc::new(RwLock::new(
-            Blockchain::new(environment.clone(), config.network_id, time).unwrap(),
-        ));
-
         // Open wallet
         #[cfg(feature = "wallet")]
         let wallet_store = Arc::new(WalletStore::new(environment.clone()));
 
         // Initialize consensus
-        let sync = HistorySync::<Network>::new(Arc::clone(&blockchain), network_events);
-        let consensus = Consensus::with_min_peers(
+        // Large histories are staged next to the database rather than in a possibly small tmpfs.
+        let mut history_sync_config = config.consensus.history_sync_config();
+        history_sync_config.staging_dir = config.storage.staging_dir();
+        let sync = HistorySync::<Network>::with_config(
+            Arc::clone(&blockchain),
+            network_events,
+            history_sync_config,
+        );
+        let sync_memory = sync.memory();
+        sync_memory.set_cap(config.memory.sync);
+        memory.register("sync", sync_memory);
+        let mut consensus = Consensus::with_block_queue_config(
             environment.clone(),
             blockchain,
             Arc::clone(&network),
             Box::pin(sync),
-            config.consensus.min_peers,
+            config.consensus.established_policy(),
+            config.consensus.block_queue_config(),
+            config.role.request_serving(),
         )
         .await;
+        consensus.set_sharded_transactions(config.mempool.sharded_topics);
+
+        let cache_memory = consensus.cache_memory();
+        cache_memory.set_cap(config.memory.response_cache);
+        memory.register("response_cache", cache_memory);
+        memory.register("block_buffer", consensus.block_buffer_memory());
+
+        // Nodes that keep a mempool without running a validator start it right away.
+        let mempool = if config.role.runs_mempool() && !config.role.runs_validator() {
+            let mut mempool_config = config.mempool.clone();
+            mempool_config.rng = rng.fork();
+            let mempool = Arc::new(Mempool::new(
+                Arc::clone(&consensus.blockchain),
+                mempool_config,
+            ));
+            consensus.blockchain.write().tx_verification_cache = Arc::<Mempool>::clone(&mempool);
+            mempool.start_executor(Arc::clone(&network)).await;
+
+            let mempool_memory = mempool.memory();
+            mempool_memory.set_cap(config.memory.mempool);
+            memory.register("mempool", mempool_memory);
+
+            Some(mempool)
+        } else {
+            None
+        };
 
         #[cfg(feature = "validator")]
         let (validator, validator_proxy) = match config.validator {
@@ -158,22 +287,43 @@ impl ClientInner {
                 // Load fee key (before we give away ownership of the storage config)
                 let fee_key = config.storage.fee_keypair()?;
 
-                let validator_network = Arc::new(ValidatorNetworkImpl::new(Arc::clone(&network)));
+                // Other validators dial the listen addresses in the order they are configured.
+                let validator_network = Arc::new(
+                    ValidatorNetworkImpl::new(Arc::clone(&network))
+                        .with_addresses(
+                            config
+                                .network
+                                .listen_addresses
+                                .iter()
+                                .map(listen_address_to_validator_address)
+                                .collect(),
+                        )
+                        .with_signed_messages(validator_config.signed_messages),
+                );
 
-                let validator = Validator::new(
+                let mut mempool_config = config.mempool;
+                mempool_config.rng = rng.fork();
+                let mut validator = Validator::new(
                     &consensus,
                     validator_network,
                     validator_address,
                     signing_key,
                     voting_key,
                     fee_key,
-                    config.mempool,
+                    mempool_config,
                 );
+                validator.set_rng(rng.fork());
+                validator.set_reward_splits(validator_config.reward_splits);
+                validator.set_production_window(validator_config.production_window);
 
                 // Use the validator's mempool as TransactionVerificationCache in the blockchain.
                 consensus.blockchain.write().tx_verification_cache =
                     Arc::<Mempool>::clone(&validator.mempool);
 
+                let mempool_memory = validator.mempool.memory();
+                mempool_memory.set_cap(config.memory.mempool);
+                memory.register("mempool", mempool_memory);
+
                 let validator_proxy = validator.proxy();
                 (Some(validator), Some(validator_proxy))
             }
@@ -191,6 +341,10 @@ impl ClientInner {
                 consensus: consensus.proxy(),
                 #[cfg(feature = "validator")]
                 validator: validator_proxy,
+                mempool,
+                memory,
+                heap_profile_dir: config.storage.heap_profile_dir(),
+                executors,
                 #[cfg(feature = "wallet")]
                 wallet_store,
             }),
@@ -246,7 +400,7 @@ impl Client {
     }
 
     /// Returns a reference to the blockchain
-    pub fn blockchain(&self) -> Arc<RwLock<Blockchain>> {
+    pub fn blockchain(&self) -> Arc<BlockchainLock> {
         Arc::clone(&self.inner.consensus.blockchain)
     }
 
@@ -272,15 +426,50 @@ impl Client {
         self.inner.validator.clone()
     }
 
-    #[cfg(feature = "validator")]
+    /// Returns a reference to the *Mempool* or `None` if the node doesn't keep one.
     pub fn mempool(&self) -> Option<Arc<Mempool>> {
-        self.validator
-            .as_ref()
-            .map(|validator| Arc::clone(&validator.mempool))
+        #[cfg(feature = "validator")]
+        if let Some(validator) = self.validator.as_ref() {
+            return Some(Arc::clone(&validator.mempool));
+        }
+        self.inner.mempool.clone()
     }
 
     /// Returns the database environment.
     pub fn environment(&self) -> Environment {
         self.inner.environment.clone()
     }
+
+    /// Returns the memory gauges of the subsystems.
+    pub fn memory_accounting(&self) -> MemoryAccounting {
+        self.inner.memory.clone()
+    }
+
+    /// Returns the directory heap profiles are written to, or `None` if the storage is not
+    /// persistent.
+    pub fn heap_profile_dir(&self) -> Option<PathBuf> {
+        self.inner.heap_profile_dir.clone()
+    }
+
+    /// Returns the executors that the tasks of the subsystems should be spawned on.
+    pub fn executors(&self) -> Executors {
+        self.inner.executors.clone()
+    }
+}
+
+/// Returns the validator address for a listen address, with the transport it uses.
+#[cfg(feature = "validator")]
+fn listen_address_to_validator_address(address: &Multiaddr) -> ValidatorAddress<Multiaddr> {
+    let transport = if address.iter().any(|p| matches!(p, Protocol::Wss(_))) {
+        TransportHint::Wss
+    } else if address.iter().any(|p| matches!(p, Protocol::Quic)) {
+        TransportHint::Quic
+    } else {
+        TransportHint::Ws
+    };
+
+    ValidatorAddress {
+        transport,
+        address: address.clone(),
+    }
 }
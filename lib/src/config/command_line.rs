@@ -6,7 +6,7 @@ use thiserror::Error;
 
 use nimiq_primitives::networks::NetworkId;
 
-use crate::config::config_file::SyncMode;
+use crate::config::config_file::{NodeRole, SyncMode};
 
 /*lazy_static! {
     static ref VALID_LOG_LEVELS: [&'static str; 6] = ["off", "error", "warn", "info", "debug", "trace"];
@@ -53,15 +53,25 @@ pub struct CommandLine {
     #[structopt(long)]
     pub passive: bool,
 
-    /// Configure sync mode, one of history (default)
+    /// Configure sync mode, one of history (default), follower
     ///
     /// # Examples
     ///
     /// * `nimiq-client --mode history`
+    /// * `nimiq-client --mode follower`
     ///
     #[structopt(long = "mode", parse(try_from_str))]
     pub sync_mode: Option<SyncMode>,
 
+    /// Configure the role of the node, one of validator, full, history (default), light, seed
+    ///
+    /// # Examples
+    ///
+    /// * `nimiq-client --role full`
+    ///
+    #[structopt(long, parse(try_from_str))]
+    pub role: Option<NodeRole>,
+
     /// Configure the network to connect to, one of test-albatross, dev-albatross (default)
     ///
     /// # Examples
@@ -70,6 +80,76 @@ pub struct CommandLine {
     ///
     #[structopt(long)]
     pub network: Option<NetworkId>,
+
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Commands that are run instead of the client.
+#[derive(Debug, StructOpt)]
+#[structopt(rename_all = "kebab")]
+pub enum Command {
+    /// Maintain the database of the client.
+    Db(DbCommand),
+
+    /// Print the schema of all network messages and gossipsub topics as JSON, so other
+    /// implementations and debugging proxies can decode the traffic.
+    ///
+    /// # Examples
+    ///
+    /// * `nimiq-client network-schema > schema.json`
+    ///
+    NetworkSchema,
+
+    /// Capture the network traffic or decode a capture.
+    Sniff(SniffCommand),
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(rename_all = "kebab")]
+pub enum DbCommand {
+    /// Rebuild the address index of the history, e.g. after its format changed.
+    ///
+    /// # Notes
+    ///
+    /// * The address index is the only index derived from the history so far. There is no
+    ///   staking event log or reward index to rebuild yet.
+    /// * The client must not be running.
+    /// * An interrupted reindex resumes where it stopped.
+    /// * The client refuses to start until an interrupted reindex has finished.
+    ///
+    /// # Examples
+    ///
+    /// * `nimiq-client db reindex`
+    ///
+    Reindex,
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(rename_all = "kebab")]
+pub enum SniffCommand {
+    /// Run the client without a validator and record all inbound messages, including the ones of
+    /// the validator topics.
+    ///
+    /// # Examples
+    ///
+    /// * `nimiq-client sniff record capture.rec`
+    ///
+    Record {
+        #[structopt(parse(from_os_str))]
+        output: PathBuf,
+    },
+
+    /// Print the messages of a capture, decoded with the message types of this client.
+    ///
+    /// # Examples
+    ///
+    /// * `nimiq-client sniff decode capture.rec`
+    ///
+    Decode {
+        #[structopt(parse(from_os_str))]
+        capture: PathBuf,
+    },
 }
 
 impl CommandLine {
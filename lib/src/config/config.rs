@@ -1,28 +1,47 @@
 #[cfg(any(feature = "rpc-server", feature = "metrics-server"))]
 use std::net::IpAddr;
+#[cfg(feature = "rpc-server")]
+use std::net::SocketAddr;
 use std::{
     path::{Path, PathBuf},
     string::ToString,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use derive_builder::Builder;
+use ip_network::IpNetwork;
 use strum_macros::Display;
+#[cfg(feature = "webhooks")]
+use url::Url;
 
 use beserial::Deserialize;
+use nimiq_blockchain::migration;
 #[cfg(feature = "validator")]
 use nimiq_bls::{KeyPair as BlsKeyPair, SecretKey as BlsSecretKey};
+use nimiq_consensus::sync::block_queue::BlockQueueConfig;
+use nimiq_consensus::sync::history::HistorySyncConfig;
+use nimiq_consensus::{EstablishedPolicy, RequestServing};
 use nimiq_database::{
     lmdb::{open as LmdbFlags, LmdbEnvironment},
+    metrics::DEFAULT_SLOW_COMMIT_THRESHOLD,
     volatile::VolatileEnvironment,
     Environment,
 };
 use nimiq_keys::{Address, KeyPair, PrivateKey};
 use nimiq_mempool::{config::MempoolConfig, filter::MempoolRules};
-use nimiq_network_libp2p::{Keypair as IdentityKeypair, Multiaddr};
+use nimiq_network_libp2p::{
+    discovery::peer_contacts::Services, Keypair as IdentityKeypair, Multiaddr,
+};
 use nimiq_primitives::networks::NetworkId;
+#[cfg(feature = "rpc-server")]
+use nimiq_rpc_server::{public::PublicConfig, tenants::ApiKeyConfig};
 use nimiq_utils::file_store::FileStore;
 #[cfg(feature = "validator")]
 use nimiq_utils::key_rng::SecureGenerate;
+#[cfg(feature = "validator")]
+use nimiq_validator::reward::RewardSplit;
+#[cfg(feature = "rpc-server")]
+use nimiq_wallet::ResubmissionPolicy;
 
 #[cfg(any(feature = "rpc-server", feature = "metrics-server"))]
 use crate::config::consts;
@@ -33,6 +52,7 @@ use crate::{
         config_file::{self, ConfigFile, Seed},
         paths,
         user_agent::UserAgent,
+        validation::{self, Diagnostic},
     },
     error::Error,
 };
@@ -41,7 +61,8 @@ use crate::{
 ///
 /// # Notes
 ///
-/// core-rs / Albatross currently only supports history sync.
+/// core-rs / Albatross currently only supports history sync. Followers sync the same way, but don't
+/// keep the bodies of old micro blocks.
 ///
 /// # ToDo
 ///
@@ -50,6 +71,17 @@ use crate::{
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Display)]
 pub enum SyncMode {
     History,
+    /// Follows the macro blocks and the state, but prunes the bodies of micro blocks once their
+    /// batch is finalized. Meant for RPC servers that only need the current state and the recent
+    /// transactions, which remain available from the history store.
+    Follower,
+}
+
+impl SyncMode {
+    /// Whether the bodies of micro blocks are pruned once their batch is finalized.
+    pub fn prunes_micro_bodies(&self) -> bool {
+        matches!(self, Self::Follower)
+    }
 }
 
 impl Default for SyncMode {
@@ -58,6 +90,72 @@ impl Default for SyncMode {
     }
 }
 
+/// The role of the node
+///
+/// The role determines which subsystems the client runs and which services it advertises to the
+/// network.
+///
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Display)]
+pub enum NodeRole {
+    /// Produces blocks and takes part in the consensus. Requires a validator configuration.
+    Validator,
+    /// Follows the chain and keeps a mempool, but doesn't serve the block history.
+    Full,
+    /// Like a full node, but additionally serves the full block history to syncing peers.
+    History,
+    /// Follows the chain without keeping a mempool or serving any data to other peers.
+    Light,
+    /// Helps other peers to discover the network. Accepts many short-lived connections, but only
+    /// answers requests for the head and single blocks.
+    Seed,
+}
+
+impl Default for NodeRole {
+    fn default() -> Self {
+        Self::History
+    }
+}
+
+impl NodeRole {
+    /// Whether the node runs a validator and produces blocks.
+    pub fn runs_validator(&self) -> bool {
+        matches!(self, Self::Validator)
+    }
+
+    /// Whether the node keeps a mempool and relays transactions.
+    pub fn runs_mempool(&self) -> bool {
+        matches!(self, Self::Validator | Self::Full | Self::History)
+    }
+
+    /// Which requests of syncing peers the node answers.
+    pub fn request_serving(&self) -> RequestServing {
+        match self {
+            Self::Validator | Self::History => RequestServing::Full,
+            Self::Full | Self::Light => RequestServing::WithoutHistory,
+            Self::Seed => RequestServing::HeadOnly,
+        }
+    }
+
+    /// The services the node advertises in its peer contact.
+    pub fn services(&self) -> Services {
+        let full = Services::FULL_BLOCKS
+            | Services::BLOCK_PROOF
+            | Services::CHAIN_PROOF
+            | Services::ACCOUNTS_PROOF
+            | Services::ACCOUNTS_CHUNKS
+            | Services::MEMPOOL
+            | Services::BODY_PROOF;
+        let history = full | Services::BLOCK_HISTORY | Services::TRANSACTION_INDEX;
+
+        match self {
+            Self::Validator => history | Services::VALIDATOR,
+            Self::History => history,
+            Self::Full => full,
+            Self::Light | Self::Seed => Services::empty(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Builder)]
 #[builder(setter(into))]
 pub struct ConsensusConfig {
@@ -65,6 +163,64 @@ pub struct ConsensusConfig {
     pub sync_mode: SyncMode,
     #[builder(default = "3")]
     pub min_peers: usize,
+    /// If set, consensus is only established once the head block is at most this old.
+    #[builder(default)]
+    pub max_head_age: Option<Duration>,
+    /// If set, consensus is lost once the head block is older than this.
+    #[builder(default)]
+    pub lost_head_age: Option<Duration>,
+    /// Whether sync requests carry trace IDs. Traced requests of other nodes are always handled in
+    /// spans with their trace ID.
+    #[builder(default)]
+    pub trace_requests: bool,
+    /// Whether gossiped blocks are relayed as soon as their header is verified, before they are
+    /// pushed to the chain.
+    #[builder(default)]
+    pub cut_through_relay: bool,
+    /// How many epochs a sync cluster downloads at the same time.
+    #[builder(default = "HistorySyncConfig::default().num_pending_epochs")]
+    pub num_pending_epochs: usize,
+    /// How many history chunks a sync cluster downloads at the same time.
+    #[builder(default = "HistorySyncConfig::default().num_pending_chunks")]
+    pub num_pending_chunks: usize,
+    /// How many clusters of epoch ids the history sync keeps.
+    #[builder(default = "HistorySyncConfig::default().max_clusters")]
+    pub max_clusters: usize,
+    /// How many requests for missing blocks are sent to peers at the same time.
+    #[builder(default = "BlockQueueConfig::default().concurrent_block_requests")]
+    pub concurrent_block_requests: usize,
+}
+
+impl ConsensusConfig {
+    /// Returns the policy that determines when consensus is established.
+    pub fn established_policy(&self) -> EstablishedPolicy {
+        EstablishedPolicy {
+            min_peers: self.min_peers,
+            max_head_age: self.max_head_age,
+            lost_head_age: self.lost_head_age,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the configuration of the queue for blocks received via gossip.
+    pub fn block_queue_config(&self) -> BlockQueueConfig {
+        BlockQueueConfig {
+            cut_through_relay: self.cut_through_relay,
+            concurrent_block_requests: self.concurrent_block_requests,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the configuration of the history sync.
+    pub fn history_sync_config(&self) -> HistorySyncConfig {
+        HistorySyncConfig {
+            num_pending_epochs: self.num_pending_epochs,
+            num_pending_chunks: self.num_pending_chunks,
+            max_clusters: self.max_clusters,
+            trace_requests: self.trace_requests,
+            ..Default::default()
+        }
+    }
 }
 
 impl Default for ConsensusConfig {
@@ -72,6 +228,14 @@ impl Default for ConsensusConfig {
         ConsensusConfig {
             sync_mode: SyncMode::default(),
             min_peers: 3,
+            max_head_age: None,
+            lost_head_age: None,
+            trace_requests: false,
+            cut_through_relay: false,
+            num_pending_epochs: HistorySyncConfig::default().num_pending_epochs,
+            num_pending_chunks: HistorySyncConfig::default().num_pending_chunks,
+            max_clusters: HistorySyncConfig::default().max_clusters,
+            concurrent_block_requests: BlockQueueConfig::default().concurrent_block_requests,
         }
     }
 }
@@ -95,6 +259,71 @@ pub struct NetworkConfig {
 
     #[builder(default)]
     pub seeds: Vec<Seed>,
+
+    /// If set, all inbound network messages are recorded to this file. The recording can be
+    /// replayed against a fresh node with `nimiq-replay`.
+    ///
+    #[builder(default)]
+    pub record_messages: Option<PathBuf>,
+
+    /// Addresses of reverse proxies (e.g. nginx or haproxy) in front of the websocket listener.
+    /// For connections from these addresses, the client address is taken from the `Forwarded` or
+    /// `X-Forwarded-For` header, so that IP limits and bans apply to the actual client.
+    ///
+    #[builder(default)]
+    pub trusted_proxies: Vec<IpNetwork>,
+
+    /// Whether plain TCP addresses (without `/ws`) can be listened on and dialed. Links between
+    /// validators don't need the websocket framing, browsers can only use websocket addresses.
+    ///
+    #[builder(default)]
+    pub plain_tcp: bool,
+
+    /// Whether this node relays connections to peers that are not reachable from the outside.
+    ///
+    #[builder(default)]
+    pub relay_server: bool,
+
+    /// Whether the ports of private listen addresses are mapped on the local router.
+    ///
+    #[builder(default)]
+    pub port_mapping: bool,
+
+    /// Certificates for secure websocket (`/wss`) listen addresses.
+    ///
+    #[builder(default)]
+    pub tls: Option<TlsConfig>,
+}
+
+/// The certificate chain and private key files (PEM) that `/wss` listen addresses are served with.
+///
+#[derive(Debug, Clone)]
+pub struct TlsConfig {
+    pub certificate_file: PathBuf,
+    pub private_key_file: PathBuf,
+}
+
+impl TlsConfig {
+    /// Returns `None` if neither a certificate nor a private key file is configured.
+    fn from_settings(settings: &config_file::TlsSettings) -> Result<Option<Self>, Error> {
+        if settings.identity_file.is_some() || settings.identity_password.is_some() {
+            log::warn!(
+                "`identity_file` and `identity_password` in `[network.tls]` are no longer supported \
+                 and are ignored, set `certificate_file` and `private_key_file` (PEM) instead"
+            );
+        }
+
+        match (&settings.certificate_file, &settings.private_key_file) {
+            (Some(certificate_file), Some(private_key_file)) => Ok(Some(TlsConfig {
+                certificate_file: PathBuf::from(certificate_file),
+                private_key_file: PathBuf::from(private_key_file),
+            })),
+            (None, None) => Ok(None),
+            _ => Err(Error::config_error(
+                "Both `certificate_file` and `private_key_file` must be set in `[network.tls]`",
+            )),
+        }
+    }
 }
 
 /// Contains which protocol to use and the configuration needed for that protocol.
@@ -245,8 +474,8 @@ pub struct DatabaseConfig {
     #[builder(default = "1024 * 1024 * 1024 * 1024")]
     size: usize,
 
-    /// Max number of DBs. Recommended: 12
-    #[builder(default = "12")]
+    /// Max number of DBs. Recommended: 13
+    #[builder(default = "13")]
     max_dbs: u32,
 
     /// Max number of threads that can open read transactions.
@@ -259,6 +488,16 @@ pub struct DatabaseConfig {
     /// Additional LMDB flags
     #[builder(default = "LmdbFlags::NOMETASYNC | LmdbFlags::NOSYNC | LmdbFlags::NORDAHEAD")]
     flags: LmdbFlags::Flags,
+
+    /// Commits that take at least this long are logged as warnings. Zero disables the warnings.
+    /// Default: 500 ms
+    #[builder(default = "DEFAULT_SLOW_COMMIT_THRESHOLD")]
+    slow_commit_threshold: Duration,
+
+    /// Copy the database before migrating it to a new schema version.
+    /// Default: false
+    #[builder(default)]
+    backup_before_migration: bool,
 }
 
 impl Default for DatabaseConfig {
@@ -266,9 +505,11 @@ impl Default for DatabaseConfig {
         Self {
             // 1 TB
             size: 1024 * 1024 * 1024 * 1024,
-            max_dbs: 12,
+            max_dbs: 13,
             max_readers: 600,
             flags: LmdbFlags::NOMETASYNC | LmdbFlags::NOSYNC | LmdbFlags::NORDAHEAD,
+            slow_commit_threshold: DEFAULT_SLOW_COMMIT_THRESHOLD,
+            backup_before_migration: false,
         }
     }
 }
@@ -283,6 +524,13 @@ impl From<Option<config_file::DatabaseSettings>> for DatabaseConfig {
                 max_dbs: db_settings.max_dbs.unwrap_or(default.max_dbs),
                 max_readers: db_settings.max_readers.unwrap_or(default.max_readers),
                 flags: default.flags,
+                slow_commit_threshold: db_settings
+                    .slow_commit_threshold
+                    .map(Duration::from_millis)
+                    .unwrap_or(default.slow_commit_threshold),
+                backup_before_migration: db_settings
+                    .backup_before_migration
+                    .unwrap_or(default.backup_before_migration),
             }
         } else {
             default
@@ -290,6 +538,127 @@ impl From<Option<config_file::DatabaseSettings>> for DatabaseConfig {
     }
 }
 
+/// Caps on the memory held by the subsystems of the client, in bytes. A subsystem that is over its
+/// cap sheds load: The mempool rejects new transactions, the history sync pauses downloading
+/// epochs and the response cache evicts entries. `None` means no cap.
+#[derive(Debug, Clone, Default, Builder, PartialEq, Eq)]
+#[builder(setter(into))]
+pub struct MemoryConfig {
+    /// Cap on the transactions in the mempool.
+    #[builder(default)]
+    pub mempool: Option<usize>,
+
+    /// Cap on the epochs that were downloaded by the history sync but not applied yet.
+    #[builder(default)]
+    pub sync: Option<usize>,
+
+    /// Cap on the cached responses to sync requests of other peers.
+    #[builder(default)]
+    pub response_cache: Option<usize>,
+}
+
+impl From<Option<config_file::MemorySettings>> for MemoryConfig {
+    fn from(memory_settings: Option<config_file::MemorySettings>) -> Self {
+        const MB: usize = 1024 * 1024;
+
+        if let Some(memory_settings) = memory_settings {
+            Self {
+                mempool: memory_settings.mempool.map(|cap| cap * MB),
+                sync: memory_settings.sync.map(|cap| cap * MB),
+                response_cache: memory_settings.response_cache.map(|cap| cap * MB),
+            }
+        } else {
+            Self::default()
+        }
+    }
+}
+
+/// How the tasks of a subsystem are executed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecutorConfig {
+    /// On the runtime of the client, shared with the other subsystems.
+    Shared,
+    /// On a dedicated thread that runs a current-thread runtime.
+    CurrentThread,
+    /// On a dedicated multi-threaded runtime with the given number of worker threads.
+    MultiThread(usize),
+}
+
+impl From<&config_file::ExecutorSettings> for ExecutorConfig {
+    fn from(settings: &config_file::ExecutorSettings) -> Self {
+        match settings.kind {
+            config_file::ExecutorKind::Shared => ExecutorConfig::Shared,
+            config_file::ExecutorKind::CurrentThread => ExecutorConfig::CurrentThread,
+            config_file::ExecutorKind::MultiThread => {
+                ExecutorConfig::MultiThread(settings.threads.unwrap_or_else(num_cores))
+            }
+        }
+    }
+}
+
+/// The executors of the subsystems, so that e.g. heavy RPC queries can't starve the swarm task.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuntimeConfig {
+    /// Runs the swarm task and the connections.
+    pub network: ExecutorConfig,
+    /// Runs the consensus and the validator, which verify and produce blocks.
+    pub consensus: ExecutorConfig,
+    /// Runs the RPC servers.
+    pub rpc: ExecutorConfig,
+}
+
+impl RuntimeConfig {
+    /// Derives the executors from the number of cores: The network gets a dedicated runtime with a
+    /// quarter of the cores, but at least two, if there are at least 4 cores, so connections are
+    /// handled in parallel. The RPC server gets a quarter of the cores if there are at least 8.
+    /// Everything else shares the runtime of the client.
+    pub fn for_cores(cores: usize) -> Self {
+        RuntimeConfig {
+            network: if cores >= 4 {
+                ExecutorConfig::MultiThread((cores / 4).max(2))
+            } else {
+                ExecutorConfig::Shared
+            },
+            consensus: ExecutorConfig::Shared,
+            rpc: if cores >= 8 {
+                ExecutorConfig::MultiThread(cores / 4)
+            } else {
+                ExecutorConfig::Shared
+            },
+        }
+    }
+}
+
+impl Default for RuntimeConfig {
+    fn default() -> Self {
+        Self::for_cores(num_cores())
+    }
+}
+
+impl From<Option<config_file::RuntimeSettings>> for RuntimeConfig {
+    fn from(runtime_settings: Option<config_file::RuntimeSettings>) -> Self {
+        let mut config = Self::default();
+
+        if let Some(runtime_settings) = runtime_settings {
+            if let Some(network) = &runtime_settings.network {
+                config.network = network.into();
+            }
+            if let Some(consensus) = &runtime_settings.consensus {
+                config.consensus = consensus.into();
+            }
+            if let Some(rpc) = &runtime_settings.rpc {
+                config.rpc = rpc.into();
+            }
+        }
+
+        config
+    }
+}
+
+fn num_cores() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| n.get())
+}
+
 /// Determines where the database will be stored.
 ///
 /// # ToDo
@@ -340,7 +709,8 @@ impl StorageConfig {
         let db_name = format!("{}-{}-consensus", network_id, sync_mode).to_lowercase();
         log::info!("Opening database: {}", db_name);
 
-        Ok(match self {
+        let mut backup_path = None;
+        let env = match self {
             StorageConfig::Volatile => VolatileEnvironment::new_with_lmdb_flags(
                 db_config.max_dbs,
                 db_config.max_readers,
@@ -357,6 +727,13 @@ impl StorageConfig {
                         ))
                     })?
                     .to_string();
+                if db_config.backup_before_migration {
+                    let timestamp = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map(|duration| duration.as_secs())
+                        .unwrap_or_default();
+                    backup_path = Some(format!("{}-backup-{}", db_path, timestamp));
+                }
                 LmdbEnvironment::new_with_max_readers(
                     &db_path,
                     db_config.size,
@@ -366,7 +743,19 @@ impl StorageConfig {
                 )?
             }
             _ => return Err(self.not_available()),
-        })
+        };
+        env.set_slow_commit_threshold(db_config.slow_commit_threshold);
+
+        let version = migration::migrate(&env, backup_path.as_deref())?;
+        if version != migration::SCHEMA_VERSION {
+            log::info!(
+                "Migrated database from schema version {} to {}",
+                version,
+                migration::SCHEMA_VERSION
+            );
+        }
+
+        Ok(env)
     }
 
     #[cfg(feature = "validator")]
@@ -491,6 +880,39 @@ impl StorageConfig {
         }
     }
 
+    /// Returns the path of the file in which the IDs of the recently seen gossipsub messages are
+    /// kept across restarts, or `None` if the storage is not persistent.
+    pub(crate) fn seen_messages_path(&self) -> Option<PathBuf> {
+        match self {
+            StorageConfig::Filesystem(file_storage) => {
+                Some(file_storage.database_parent.join("seen_messages.dat"))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the directory that heap profiles are written to, or `None` if the storage is not
+    /// persistent.
+    pub(crate) fn heap_profile_dir(&self) -> Option<PathBuf> {
+        match self {
+            StorageConfig::Filesystem(file_storage) => Some(
+                file_storage
+                    .database_parent
+                    .join(nimiq_utils::memory::HEAP_PROFILE_DIR),
+            ),
+            _ => None,
+        }
+    }
+
+    /// Returns the directory in which the history sync stages large histories, or `None` if the
+    /// storage is not persistent.
+    pub(crate) fn staging_dir(&self) -> Option<PathBuf> {
+        match self {
+            StorageConfig::Filesystem(file_storage) => Some(file_storage.database_parent.clone()),
+            _ => None,
+        }
+    }
+
     fn not_available(&self) -> Error {
         Error::Config(format!("Storage backend not implemented: {:?}", self))
     }
@@ -513,6 +935,50 @@ impl Default for StorageConfig {
 pub struct ValidatorConfig {
     /// The validator address.
     pub validator_address: Address,
+
+    /// Recipients that receive a share of the validator rewards.
+    pub reward_splits: Vec<RewardSplit>,
+
+    /// Whether messages to other validators are signed with the voting key.
+    pub signed_messages: bool,
+
+    /// How long expensive sync requests of other peers are refused and the re-validation of
+    /// reverted transactions is deferred once it is our turn to produce a block. Zero disables it.
+    pub production_window: Duration,
+}
+
+#[cfg(feature = "validator")]
+impl ValidatorConfig {
+    fn reward_splits(
+        settings: &[config_file::RewardSplitSettings],
+    ) -> Result<Vec<RewardSplit>, Error> {
+        let mut total_basis_points = 0u32;
+        let mut splits = Vec::with_capacity(settings.len());
+
+        for split in settings {
+            if !(split.percentage > 0.0 && split.percentage <= 100.0) {
+                return Err(Error::config_error(format!(
+                    "Invalid percentage of reward split to {}: {}",
+                    split.recipient, split.percentage
+                )));
+            }
+
+            let basis_points = (split.percentage * 100.0).round() as u16;
+            total_basis_points += u32::from(basis_points);
+            splits.push(RewardSplit {
+                recipient: Address::from_any_str(&split.recipient)?,
+                basis_points,
+            });
+        }
+
+        if total_basis_points > u32::from(RewardSplit::MAX_BASIS_POINTS) {
+            return Err(Error::config_error(
+                "The percentages of the reward splits add up to more than 100",
+            ));
+        }
+
+        Ok(splits)
+    }
 }
 
 /// Credentials for JSON RPC server, metrics server or websocket RPC server
@@ -572,6 +1038,25 @@ pub struct RpcServerConfig {
     /// If specified, require HTTP basic auth with these credentials
     #[builder(setter(strip_option))]
     pub credentials: Option<Credentials>,
+
+    /// How basic transactions sent from unlocked wallets are handled when they are about to
+    /// expire without having been included.
+    #[builder(default)]
+    pub resubmission: ResubmissionPolicy,
+
+    /// API keys with their own method allow-lists and rate limits. Their usage is reported by the
+    /// `getApiKeyUsage` admin method.
+    #[builder(default)]
+    pub api_keys: Vec<ApiKeyConfig>,
+
+    /// If set, requests without an API key are rejected. Otherwise, they may call all methods
+    /// except the admin methods.
+    #[builder(default)]
+    pub require_api_key: bool,
+
+    /// If specified, a browser-safe endpoint offering only read-only methods is served as well.
+    #[builder(default)]
+    pub public: Option<PublicConfig>,
 }
 
 #[cfg(feature = "metrics-server")]
@@ -597,6 +1082,126 @@ pub struct MetricsServerConfig {
     pub credentials: Option<Credentials>,
 }
 
+/// A node event that can be reported to webhooks
+#[cfg(feature = "webhooks")]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Display)]
+#[strum(serialize_all = "kebab-case")]
+pub enum WebhookEvent {
+    /// A block was added to the main chain.
+    Block,
+    /// A block was removed from the main chain by a rebranch.
+    Revert,
+    /// The main chain was rebranched and at least `reorg_depth` blocks were reverted.
+    Reorg,
+    /// The validator of this node was parked.
+    ValidatorParked,
+    /// The number of connected peers dropped below `min_peers`.
+    LowPeerCount,
+    /// No block was added to the main chain for `stall_timeout`.
+    SyncStalled,
+}
+
+#[cfg(feature = "webhooks")]
+#[derive(Debug, Clone)]
+pub struct WebhookEndpointConfig {
+    /// The URL the events are posted to.
+    pub url: Url,
+
+    /// The events that are posted to this endpoint. All events are posted if this is empty.
+    pub events: Vec<WebhookEvent>,
+
+    /// If specified, the payloads are signed with HMAC-SHA512 using this secret. The signature is
+    /// sent in the `X-Nimiq-Signature` header.
+    pub secret: Option<String>,
+}
+
+#[cfg(feature = "webhooks")]
+impl WebhookEndpointConfig {
+    /// Whether the given event is posted to this endpoint.
+    pub fn accepts(&self, event: WebhookEvent) -> bool {
+        self.events.is_empty() || self.events.contains(&event)
+    }
+}
+
+#[cfg(feature = "webhooks")]
+#[derive(Debug, Clone, Builder)]
+#[builder(setter(into))]
+pub struct WebhookConfig {
+    /// The endpoints events are posted to.
+    pub endpoints: Vec<WebhookEndpointConfig>,
+
+    /// Minimum number of reverted blocks for a rebranch to be reported as a reorg.
+    ///
+    /// Default: `2`
+    ///
+    #[builder(default = "2")]
+    pub reorg_depth: usize,
+
+    /// A low peer count is reported once the number of peers drops below this.
+    ///
+    /// Default: `3`
+    ///
+    #[builder(default = "3")]
+    pub min_peers: usize,
+
+    /// The sync is reported as stalled if no block was added to the main chain for this long.
+    ///
+    /// Default: 5 minutes
+    ///
+    #[builder(default = "Duration::from_secs(300)")]
+    pub stall_timeout: Duration,
+
+    /// Number of times a failed delivery is retried, with exponential backoff.
+    ///
+    /// Default: `5`
+    ///
+    #[builder(default = "5")]
+    pub max_retries: u32,
+}
+
+/// The message broker chain events are published to
+#[cfg(feature = "event-sink")]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum EventSinkBackend {
+    /// Apache Kafka, requires the `kafka` feature.
+    Kafka,
+    /// NATS JetStream, requires the `nats` feature.
+    Nats,
+}
+
+#[cfg(feature = "event-sink")]
+#[derive(Debug, Clone, Builder)]
+#[builder(setter(into))]
+pub struct EventSinkConfig {
+    /// The message broker the events are published to.
+    pub backend: EventSinkBackend,
+
+    /// The addresses of the brokers (Kafka) or servers (NATS) to connect to.
+    pub brokers: Vec<String>,
+
+    /// Topic (Kafka) or subject (NATS) of the block events.
+    ///
+    /// Default: `nimiq.blocks`
+    ///
+    #[builder(default = r#""nimiq.blocks".to_string()"#)]
+    pub block_topic: String,
+
+    /// Topic (Kafka) or subject (NATS) of the transaction events.
+    ///
+    /// Default: `nimiq.transactions`
+    ///
+    #[builder(default = r#""nimiq.transactions".to_string()"#)]
+    pub transaction_topic: String,
+
+    /// Topic (Kafka) or subject (NATS) of the staking events.
+    ///
+    /// Default: `nimiq.staking`
+    ///
+    #[builder(default = r#""nimiq.staking".to_string()"#)]
+    pub staking_topic: String,
+}
+
 /// Client configuration
 ///
 /// # ToDo
@@ -613,6 +1218,13 @@ pub struct ClientConfig {
     #[builder(default)]
     pub consensus: ConsensusConfig,
 
+    /// The role of the node, which determines the subsystems it runs.
+    ///
+    /// Default is `Validator` if a validator configuration is given, `History` otherwise.
+    ///
+    #[builder(default)]
+    pub role: NodeRole,
+
     /// The `ProtocolConfig` that determines how the client accepts incoming connections. This
     /// will also determine how the client advertises itself to the network.
     ///
@@ -652,6 +1264,24 @@ pub struct ClientConfig {
     #[builder(default, setter(custom))]
     pub mempool: MempoolConfig,
 
+    /// The memory caps of the subsystems
+    ///
+    #[builder(default)]
+    pub memory: MemoryConfig,
+
+    /// The executors of the subsystems
+    ///
+    #[builder(default)]
+    pub runtime: RuntimeConfig,
+
+    /// Seeds the randomness of the network discovery, the mempool and the validator to make
+    /// runs reproducible. Only meant for tests, nodes should use the OS randomness.
+    ///
+    /// Default is `None`
+    ///
+    #[builder(default)]
+    pub rng_seed: Option<u64>,
+
     /// The optional validator configuration
     ///
     #[cfg(feature = "validator")]
@@ -669,6 +1299,18 @@ pub struct ClientConfig {
     #[cfg(feature = "metrics-server")]
     #[builder(default)]
     pub metrics_server: Option<MetricsServerConfig>,
+
+    /// The optional webhook configuration
+    ///
+    #[cfg(feature = "webhooks")]
+    #[builder(default)]
+    pub webhooks: Option<WebhookConfig>,
+
+    /// The optional event sink configuration
+    ///
+    #[cfg(feature = "event-sink")]
+    #[builder(default)]
+    pub event_sink: Option<EventSinkConfig>,
 }
 
 impl ClientConfig {
@@ -678,6 +1320,13 @@ impl ClientConfig {
         ClientConfigBuilder::default()
     }
 
+    /// Cross-checks the settings and returns all problems found. This is done by
+    /// `ClientConfigBuilder::build` as well.
+    ///
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        validation::validate(self)
+    }
+
     /// Instantiates the Nimiq client from this configuration
     ///
     pub async fn instantiate_client(self) -> Result<Client, Error> {
@@ -690,11 +1339,32 @@ impl ClientConfigBuilder {
     ///
     pub fn build(&self) -> Result<ClientConfig, Error> {
         // NOTE: We rename the generated builder and make it private to map the error from a plain
-        // `String` to an actual Error.
-        // We could also put some validation here.
+        // `String` to an actual Error and to validate the config.
 
-        self.build_internal()
-            .map_err(|e| Error::config_error(e.to_string()))
+        // Nodes with a validator configuration default to the validator role.
+        #[cfg(feature = "validator")]
+        if self.role.is_none() && matches!(self.validator, Some(Some(_))) {
+            let mut builder = self.clone();
+            builder.role(NodeRole::Validator);
+            return builder.build();
+        }
+
+        let config = self
+            .build_internal()
+            .map_err(|e| Error::config_error(e.to_string()))?;
+
+        let (errors, warnings): (Vec<Diagnostic>, Vec<Diagnostic>) = config
+            .validate()
+            .into_iter()
+            .partition(Diagnostic::is_error);
+        for warning in warnings {
+            log::warn!("Configuration {}", warning);
+        }
+        if !errors.is_empty() {
+            return Err(Error::InvalidConfig(errors));
+        }
+
+        Ok(config)
     }
 
     /// Short cut to build the config and instantiate the client
@@ -745,10 +1415,37 @@ impl ClientConfigBuilder {
 
     /// Sets the mempool filter rules
     pub fn mempool(&mut self, filter_rules: MempoolRules, filter_limit: usize) -> &mut Self {
-        self.mempool = Some(MempoolConfig {
-            filter_rules,
-            filter_limit,
-        });
+        let mempool = self.mempool.get_or_insert_with(MempoolConfig::default);
+        mempool.filter_rules = filter_rules;
+        mempool.filter_limit = filter_limit;
+        self
+    }
+
+    /// Enables or disables the sharded transaction topics. If enabled, the mempool subscribes to
+    /// the configured transaction shards instead of the unsharded topic and transactions sent by
+    /// this client are published to the shard of their sender.
+    pub fn sharded_transaction_topics(&mut self, enabled: bool) -> &mut Self {
+        self.mempool
+            .get_or_insert_with(MempoolConfig::default)
+            .sharded_topics = enabled;
+        self
+    }
+
+    /// Restricts the transaction shards the mempool subscribes to if sharded topics are enabled.
+    /// By default, all shards are subscribed.
+    pub fn transaction_shards(&mut self, shards: Vec<u8>) -> &mut Self {
+        self.mempool
+            .get_or_insert_with(MempoolConfig::default)
+            .transaction_shards = Some(shards);
+        self
+    }
+
+    /// Enables or disables the subscription to the unsharded transaction topic while sharded
+    /// topics are enabled, for the transition to sharded topics.
+    pub fn legacy_transaction_topic(&mut self, enabled: bool) -> &mut Self {
+        self.mempool
+            .get_or_insert_with(MempoolConfig::default)
+            .legacy_transaction_topic = enabled;
         self
     }
 
@@ -771,6 +1468,37 @@ impl ClientConfigBuilder {
                 .unwrap_or_default(),
 
             seeds: config_file.network.seed_nodes.clone(),
+
+            record_messages: config_file
+                .network
+                .record_messages
+                .as_ref()
+                .map(PathBuf::from),
+
+            trusted_proxies: config_file
+                .network
+                .trusted_proxies
+                .iter()
+                .map(|s| {
+                    s.parse::<IpNetwork>().map_err(|e| {
+                        Error::config_error(format!("Invalid trusted proxy {}: {}", s, e))
+                    })
+                })
+                .collect::<Result<Vec<IpNetwork>, Error>>()?,
+
+            plain_tcp: config_file.network.plain_tcp.unwrap_or_default(),
+
+            relay_server: config_file.network.relay_server.unwrap_or_default(),
+
+            port_mapping: config_file.network.port_mapping.unwrap_or_default(),
+
+            tls: config_file
+                .network
+                .tls
+                .as_ref()
+                .map(TlsConfig::from_settings)
+                .transpose()?
+                .flatten(),
         });
 
         // Configure consensus
@@ -781,11 +1509,37 @@ impl ClientConfigBuilder {
         if let Some(min_peers) = config_file.consensus.min_peers {
             consensus.min_peers = min_peers;
         }
+        consensus.max_head_age = config_file.consensus.max_head_age.map(Duration::from_secs);
+        consensus.lost_head_age = config_file.consensus.lost_head_age.map(Duration::from_secs);
+        consensus.trace_requests = config_file.consensus.trace_requests.unwrap_or_default();
+        consensus.cut_through_relay = config_file.consensus.cut_through_relay.unwrap_or_default();
+        if let Some(num_pending_epochs) = config_file.consensus.num_pending_epochs {
+            consensus.num_pending_epochs = num_pending_epochs;
+        }
+        if let Some(num_pending_chunks) = config_file.consensus.num_pending_chunks {
+            consensus.num_pending_chunks = num_pending_chunks;
+        }
+        if let Some(max_clusters) = config_file.consensus.max_clusters {
+            consensus.max_clusters = max_clusters;
+        }
+        if let Some(concurrent_block_requests) = config_file.consensus.concurrent_block_requests {
+            consensus.concurrent_block_requests = concurrent_block_requests;
+        }
         self.consensus(consensus);
 
         // Configure network
         self.network_id(config_file.consensus.network);
 
+        // Configure role
+        if let Some(role) = config_file.role {
+            self.role(role);
+        }
+
+        // Configure mempool
+        if let Some(mempool) = config_file.mempool.as_ref() {
+            self.mempool = Some(mempool.clone().into());
+        }
+
         // Configure storage config.
         let mut file_storage = FileStorageConfig::default();
         if let Some(db_config_file) = &config_file.database {
@@ -803,6 +1557,9 @@ impl ClientConfigBuilder {
         if let Some(validator_config) = config_file.validator.as_ref() {
             self.validator(ValidatorConfig {
                 validator_address: Address::from_any_str(&validator_config.validator_address)?,
+                reward_splits: ValidatorConfig::reward_splits(&validator_config.reward_splits)?,
+                signed_messages: validator_config.signed_messages,
+                production_window: Duration::from_millis(validator_config.production_window),
             });
 
             if let Some(key_path) = &validator_config.voting_key_file {
@@ -829,6 +1586,12 @@ impl ClientConfigBuilder {
         // Configure database
         self.database(config_file.database.clone());
 
+        // Configure memory caps
+        self.memory(config_file.memory.clone());
+
+        // Configure the executors of the subsystems
+        self.runtime(config_file.runtime.clone());
+
         // Configure RPC server
         #[cfg(feature = "rpc-server")]
         {
@@ -869,6 +1632,49 @@ impl ClientConfigBuilder {
                     allow_ips,
                     allowed_methods: Some(rpc_config.methods.clone()),
                     credentials,
+                    resubmission: rpc_config
+                        .resubmission
+                        .as_ref()
+                        .map(|settings| ResubmissionPolicy {
+                            blocks_before_expiry: settings.blocks_before_expiry,
+                            resubmit: settings.resubmit,
+                            fee_bump_percent: settings.fee_bump_percent,
+                            max_resubmissions: settings.max_resubmissions,
+                        })
+                        .unwrap_or_default(),
+                    api_keys: rpc_config
+                        .api_keys
+                        .iter()
+                        .map(|settings| ApiKeyConfig {
+                            name: settings.name.clone(),
+                            key: settings.key.clone(),
+                            allowed_methods: if settings.methods.is_empty() {
+                                None
+                            } else {
+                                Some(settings.methods.iter().cloned().collect())
+                            },
+                            requests_per_minute: settings.requests_per_minute,
+                            admin: settings.admin,
+                        })
+                        .collect(),
+                    require_api_key: rpc_config.require_api_key,
+                    public: rpc_config.public.as_ref().map(|settings| {
+                        let ip = settings
+                            .bind
+                            .as_ref()
+                            .and_then(|addr| addr.into_ip_address())
+                            .or(bind_to)
+                            .unwrap_or_else(consts::default_bind);
+                        PublicConfig {
+                            bind_to: SocketAddr::new(
+                                ip,
+                                settings.port.unwrap_or(consts::PUBLIC_RPC_DEFAULT_PORT),
+                            ),
+                            cors_origins: settings.corsdomain.clone(),
+                            max_response_size: settings.max_response_size,
+                            requests_per_minute: settings.requests_per_minute,
+                        }
+                    }),
                 }));
             }
         }
@@ -895,6 +1701,67 @@ impl ClientConfigBuilder {
             }
         }
 
+        // Configure webhooks
+        #[cfg(feature = "webhooks")]
+        {
+            if let Some(webhook_config) = &config_file.webhooks {
+                let endpoints = webhook_config
+                    .endpoints
+                    .iter()
+                    .map(|endpoint| {
+                        let url = endpoint.url.parse::<Url>().map_err(|e| {
+                            Error::config_error(format!(
+                                "Invalid webhook URL {}: {}",
+                                endpoint.url, e
+                            ))
+                        })?;
+                        Ok(WebhookEndpointConfig {
+                            url,
+                            events: endpoint.events.iter().map(|&e| e.into()).collect(),
+                            secret: endpoint.secret.clone(),
+                        })
+                    })
+                    .collect::<Result<Vec<WebhookEndpointConfig>, Error>>()?;
+
+                let mut webhooks = WebhookConfigBuilder::default();
+                webhooks.endpoints(endpoints);
+                if let Some(reorg_depth) = webhook_config.reorg_depth {
+                    webhooks.reorg_depth(reorg_depth);
+                }
+                if let Some(min_peers) = webhook_config.min_peers {
+                    webhooks.min_peers(min_peers);
+                }
+                if let Some(stall_timeout) = webhook_config.stall_timeout {
+                    webhooks.stall_timeout(Duration::from_secs(stall_timeout));
+                }
+                if let Some(max_retries) = webhook_config.max_retries {
+                    webhooks.max_retries(max_retries);
+                }
+                self.webhooks = Some(Some(webhooks.build().unwrap()));
+            }
+        }
+
+        // Configure event sink
+        #[cfg(feature = "event-sink")]
+        {
+            if let Some(event_sink_config) = &config_file.event_sink {
+                let mut event_sink = EventSinkConfigBuilder::default();
+                event_sink
+                    .backend(EventSinkBackend::from(event_sink_config.backend))
+                    .brokers(event_sink_config.brokers.clone());
+                if let Some(block_topic) = &event_sink_config.block_topic {
+                    event_sink.block_topic(block_topic.clone());
+                }
+                if let Some(transaction_topic) = &event_sink_config.transaction_topic {
+                    event_sink.transaction_topic(transaction_topic.clone());
+                }
+                if let Some(staking_topic) = &event_sink_config.staking_topic {
+                    event_sink.staking_topic(staking_topic.clone());
+                }
+                self.event_sink = Some(Some(event_sink.build().unwrap()));
+            }
+        }
+
         Ok(self)
     }
 
@@ -912,6 +1779,11 @@ impl ClientConfigBuilder {
             self.network_id(network_id);
         }
 
+        // Set role
+        if let Some(role) = command_line.role {
+            self.role(role);
+        }
+
         // NOTE: We're always return `Ok(_)`, but we might want to introduce errors later.
         Ok(self)
     }
@@ -2,6 +2,7 @@ use std::collections::HashMap;
 use std::fs::read_to_string;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::time::Duration;
 
 use log::LevelFilter;
 use serde_derive::Deserialize;
@@ -10,10 +11,12 @@ use thiserror::Error;
 use nimiq_mempool::{
     config::MempoolConfig,
     filter::{MempoolFilter, MempoolRules},
+    inclusion::InclusionPolicy,
 };
 use nimiq_network_libp2p::Multiaddr;
 use nimiq_peer_address::{address, protocol}; // TODO: probably not needed anymore
 use nimiq_primitives::{coin::Coin, networks::NetworkId};
+use nimiq_utils::shared_rng::SharedRng;
 
 use crate::{
     config::{command_line::CommandLine, config, config_file::serialization::*, paths},
@@ -31,15 +34,20 @@ mod serialization;
 pub struct ConfigFile {
     #[serde(default)]
     pub network: NetworkSettings,
+    pub role: Option<NodeRole>,
     #[serde(default)]
     pub consensus: ConsensusSettings,
     pub rpc_server: Option<RpcServerSettings>,
     pub metrics_server: Option<MetricsServerSettings>,
+    pub webhooks: Option<WebhookSettings>,
+    pub event_sink: Option<EventSinkSettings>,
     //pub reverse_proxy: Option<ReverseProxySettings>,
     #[serde(default)]
     pub log: LogSettings,
     pub database: Option<DatabaseSettings>,
+    pub memory: Option<MemorySettings>,
     pub mempool: Option<MempoolSettings>,
+    pub runtime: Option<RuntimeSettings>,
     #[serde(default)]
     pub validator: Option<ValidatorSettings>,
 }
@@ -132,6 +140,18 @@ pub struct NetworkSettings {
 
     pub tls: Option<TlsSettings>,
     pub instant_inbound: Option<bool>,
+
+    #[serde(default)]
+    pub record_messages: Option<String>,
+
+    #[serde(default)]
+    pub trusted_proxies: Vec<String>,
+
+    pub plain_tcp: Option<bool>,
+
+    pub relay_server: Option<bool>,
+
+    pub port_mapping: Option<bool>,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -169,8 +189,13 @@ impl From<Protocol> for protocol::Protocol {
 #[derive(Clone, Debug, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct TlsSettings {
-    pub identity_file: String,
-    pub identity_password: String,
+    pub certificate_file: Option<String>,
+    pub private_key_file: Option<String>,
+    /// Deprecated: PKCS#12 identities are no longer supported. Only accepted so that existing
+    /// configuration files still load.
+    pub identity_file: Option<String>,
+    /// Deprecated together with `identity_file`.
+    pub identity_password: Option<String>,
 }
 
 #[derive(Clone, Debug, Deserialize, Default)]
@@ -182,12 +207,25 @@ pub struct ConsensusSettings {
     #[serde(default)]
     pub network: Network,
     pub min_peers: Option<usize>,
+    /// Maximum age of the head block in seconds for consensus to be established.
+    pub max_head_age: Option<u64>,
+    /// Age of the head block in seconds after which consensus is lost.
+    pub lost_head_age: Option<u64>,
+    /// Attach trace IDs to sync requests.
+    pub trace_requests: Option<bool>,
+    /// Relay gossiped blocks once their header is verified, before they are pushed.
+    pub cut_through_relay: Option<bool>,
+    pub num_pending_epochs: Option<usize>,
+    pub num_pending_chunks: Option<usize>,
+    pub max_clusters: Option<usize>,
+    pub concurrent_block_requests: Option<usize>,
 }
 
 #[derive(Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum SyncMode {
     History,
+    Follower,
 }
 impl Default for SyncMode {
     fn default() -> Self {
@@ -205,6 +243,7 @@ impl FromStr for SyncMode {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         Ok(match s.to_lowercase().as_str() {
             "history" => Self::History,
+            "follower" => Self::Follower,
             _ => return Err(SyncModeParseError(s.to_string())),
         })
     }
@@ -214,6 +253,48 @@ impl From<SyncMode> for config::SyncMode {
     fn from(sync_mode: SyncMode) -> Self {
         match sync_mode {
             SyncMode::History => Self::History,
+            SyncMode::Follower => Self::Follower,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeRole {
+    Validator,
+    Full,
+    History,
+    Light,
+    Seed,
+}
+
+#[derive(Debug, Error)]
+#[error("Invalid node role: {0}")]
+pub struct NodeRoleParseError(String);
+
+impl FromStr for NodeRole {
+    type Err = NodeRoleParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_lowercase().as_str() {
+            "validator" => Self::Validator,
+            "full" => Self::Full,
+            "history" => Self::History,
+            "light" => Self::Light,
+            "seed" => Self::Seed,
+            _ => return Err(NodeRoleParseError(s.to_string())),
+        })
+    }
+}
+
+impl From<NodeRole> for config::NodeRole {
+    fn from(role: NodeRole) -> Self {
+        match role {
+            NodeRole::Validator => Self::Validator,
+            NodeRole::Full => Self::Full,
+            NodeRole::History => Self::History,
+            NodeRole::Light => Self::Light,
+            NodeRole::Seed => Self::Seed,
         }
     }
 }
@@ -280,6 +361,80 @@ pub struct RpcServerSettings {
     pub methods: Vec<String>,
     pub username: Option<String>,
     pub password: Option<String>,
+    pub resubmission: Option<ResubmissionSettings>,
+    #[serde(default)]
+    pub api_keys: Vec<ApiKeySettings>,
+    #[serde(default)]
+    pub require_api_key: bool,
+    pub public: Option<PublicRpcSettings>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PublicRpcSettings {
+    #[serde(deserialize_with = "deserialize_string_option")]
+    #[serde(default)]
+    pub bind: Option<address::NetAddress>,
+    pub port: Option<u16>,
+    #[serde(default = "PublicRpcSettings::default_corsdomain")]
+    pub corsdomain: Vec<String>,
+    #[serde(default = "PublicRpcSettings::default_max_response_size")]
+    pub max_response_size: usize,
+    #[serde(default = "PublicRpcSettings::default_requests_per_minute")]
+    pub requests_per_minute: u32,
+}
+
+impl PublicRpcSettings {
+    pub fn default_corsdomain() -> Vec<String> {
+        vec!["*".to_string()]
+    }
+
+    pub fn default_max_response_size() -> usize {
+        1024 * 1024
+    }
+
+    pub fn default_requests_per_minute() -> u32 {
+        120
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ApiKeySettings {
+    pub name: String,
+    pub key: String,
+    #[serde(default)]
+    pub methods: Vec<String>,
+    pub requests_per_minute: Option<u32>,
+    #[serde(default)]
+    pub admin: bool,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ResubmissionSettings {
+    #[serde(default = "ResubmissionSettings::default_blocks_before_expiry")]
+    pub blocks_before_expiry: u32,
+    #[serde(default = "ResubmissionSettings::default_resubmit")]
+    pub resubmit: bool,
+    #[serde(default)]
+    pub fee_bump_percent: u32,
+    #[serde(default = "ResubmissionSettings::default_max_resubmissions")]
+    pub max_resubmissions: u32,
+}
+
+impl ResubmissionSettings {
+    pub fn default_blocks_before_expiry() -> u32 {
+        600
+    }
+
+    pub fn default_resubmit() -> bool {
+        true
+    }
+
+    pub fn default_max_resubmissions() -> u32 {
+        3
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Default)]
@@ -292,6 +447,79 @@ pub struct MetricsServerSettings {
     pub password: Option<String>,
 }
 
+#[derive(Clone, Debug, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookSettings {
+    #[serde(default, rename = "endpoint")]
+    pub endpoints: Vec<WebhookEndpointSettings>,
+    pub reorg_depth: Option<usize>,
+    pub min_peers: Option<usize>,
+    /// Time in seconds without a new block after which the sync is reported as stalled.
+    pub stall_timeout: Option<u64>,
+    pub max_retries: Option<u32>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookEndpointSettings {
+    pub url: String,
+    #[serde(default)]
+    pub events: Vec<WebhookEvent>,
+    pub secret: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum WebhookEvent {
+    Block,
+    Revert,
+    Reorg,
+    ValidatorParked,
+    LowPeerCount,
+    SyncStalled,
+}
+
+#[cfg(feature = "webhooks")]
+impl From<WebhookEvent> for config::WebhookEvent {
+    fn from(event: WebhookEvent) -> Self {
+        match event {
+            WebhookEvent::Block => Self::Block,
+            WebhookEvent::Revert => Self::Revert,
+            WebhookEvent::Reorg => Self::Reorg,
+            WebhookEvent::ValidatorParked => Self::ValidatorParked,
+            WebhookEvent::LowPeerCount => Self::LowPeerCount,
+            WebhookEvent::SyncStalled => Self::SyncStalled,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EventSinkSettings {
+    pub backend: EventSinkBackend,
+    pub brokers: Vec<String>,
+    pub block_topic: Option<String>,
+    pub transaction_topic: Option<String>,
+    pub staking_topic: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum EventSinkBackend {
+    Kafka,
+    Nats,
+}
+
+#[cfg(feature = "event-sink")]
+impl From<EventSinkBackend> for config::EventSinkBackend {
+    fn from(backend: EventSinkBackend) -> Self {
+        match backend {
+            EventSinkBackend::Kafka => Self::Kafka,
+            EventSinkBackend::Nats => Self::Nats,
+        }
+    }
+}
+
 /*
 #[derive(Clone, Debug, Deserialize)]
 #[serde(deny_unknown_fields)]
@@ -387,6 +615,42 @@ pub struct DatabaseSettings {
     pub size: Option<usize>,
     pub max_dbs: Option<u32>,
     pub max_readers: Option<u32>,
+    pub slow_commit_threshold: Option<u64>,
+    pub backup_before_migration: Option<bool>,
+}
+
+/// Memory caps in MB.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MemorySettings {
+    pub mempool: Option<usize>,
+    pub sync: Option<usize>,
+    pub response_cache: Option<usize>,
+}
+
+/// The executors of the subsystems. Subsystems that are not configured use the default.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuntimeSettings {
+    pub network: Option<ExecutorSettings>,
+    pub consensus: Option<ExecutorSettings>,
+    pub rpc: Option<ExecutorSettings>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExecutorSettings {
+    pub kind: ExecutorKind,
+    /// The number of worker threads of a `multi-thread` executor.
+    pub threads: Option<usize>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ExecutorKind {
+    Shared,
+    CurrentThread,
+    MultiThread,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -394,6 +658,36 @@ pub struct DatabaseSettings {
 pub struct MempoolSettings {
     pub filter: Option<MempoolFilterSettings>,
     pub blacklist_limit: Option<usize>,
+    #[serde(default)]
+    pub sharded_topics: bool,
+    pub transaction_shards: Option<Vec<u8>>,
+    #[serde(default)]
+    pub legacy_transaction_topic: bool,
+    pub inclusion_list: Option<InclusionListSettings>,
+    #[serde(default = "MempoolSettings::default_sync_on_connect")]
+    pub sync_on_connect: bool,
+    #[serde(default = "MempoolSettings::default_reconciliation_interval")]
+    pub reconciliation_interval: u64,
+    #[serde(default)]
+    pub min_relay_fee_per_byte: f64,
+}
+
+impl MempoolSettings {
+    pub fn default_sync_on_connect() -> bool {
+        true
+    }
+
+    pub fn default_reconciliation_interval() -> u64 {
+        60
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InclusionListSettings {
+    #[serde(default)]
+    pub min_fee_per_byte: f64,
+    pub max_wait_batches: u32,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -441,6 +735,20 @@ impl From<MempoolSettings> for MempoolConfig {
                 .blacklist_limit
                 .unwrap_or(MempoolFilter::DEFAULT_BLACKLIST_SIZE),
             filter_rules: mempool.filter.map(MempoolRules::from).unwrap_or_default(),
+            sharded_topics: mempool.sharded_topics,
+            transaction_shards: mempool.transaction_shards,
+            legacy_transaction_topic: mempool.legacy_transaction_topic,
+            inclusion_policy: mempool.inclusion_list.map(|settings| InclusionPolicy {
+                min_fee_per_byte: settings.min_fee_per_byte,
+                max_wait_batches: settings.max_wait_batches,
+            }),
+            sync_on_connect: mempool.sync_on_connect,
+            reconciliation_interval: match mempool.reconciliation_interval {
+                0 => None,
+                secs => Some(Duration::from_secs(secs)),
+            },
+            min_relay_fee_per_byte: mempool.min_relay_fee_per_byte,
+            rng: SharedRng::os(),
         }
     }
 }
@@ -475,4 +783,17 @@ pub struct ValidatorSettings {
     pub voting_key: Option<String>,
     pub fee_key_file: Option<String>,
     pub fee_key: Option<String>,
+    #[serde(default)]
+    pub reward_splits: Vec<RewardSplitSettings>,
+    #[serde(default)]
+    pub signed_messages: bool,
+    #[serde(default)]
+    pub production_window: u64,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RewardSplitSettings {
+    pub recipient: String,
+    pub percentage: f64,
 }
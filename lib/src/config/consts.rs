@@ -9,6 +9,9 @@ pub const REVERSE_PROXY_DEFAULT_PORT: u16 = 8444;
 /// The default port for the RPC server
 pub const RPC_DEFAULT_PORT: u16 = 8648;
 
+/// The default port for the public RPC endpoint
+pub const PUBLIC_RPC_DEFAULT_PORT: u16 = 8650;
+
 /// The default port for the metrics server
 pub const METRICS_DEFAULT_PORT: u16 = 8649;
 
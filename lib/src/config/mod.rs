@@ -12,3 +12,4 @@ pub mod config_file;
 pub mod consts;
 pub mod paths;
 pub mod user_agent;
+pub mod validation;
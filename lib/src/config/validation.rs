@@ -0,0 +1,438 @@
+//! Cross-checks the settings of a client configuration.
+//!
+//! Subsystems only see their own part of the configuration, so combinations of settings that don't
+//! make sense together would otherwise only be noticed much later, or not at all. The validation
+//! produces a diagnostic with a hint on how to fix it for every problem it finds.
+
+use std::fmt;
+#[cfg(feature = "rpc-server")]
+use std::net::IpAddr;
+
+use beserial::Deserialize;
+#[cfg(feature = "validator")]
+use nimiq_bls::SecretKey as BlsSecretKey;
+#[cfg(feature = "validator")]
+use nimiq_keys::PrivateKey;
+use nimiq_mempool::config::InvalidRelayFee;
+use nimiq_network_libp2p::{
+    libp2p::core::multiaddr::Protocol, Keypair as IdentityKeypair, Multiaddr,
+};
+
+use crate::config::config::{ClientConfig, ExecutorConfig, StorageConfig};
+
+/// The severity of a configuration problem.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Severity {
+    /// The client can run, but probably not as intended.
+    Warning,
+    /// The client can't run with this configuration.
+    Error,
+}
+
+/// A problem found in the configuration.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// What is wrong.
+    pub message: String,
+    /// How to fix it.
+    pub hint: String,
+}
+
+impl Diagnostic {
+    fn warning<M: Into<String>, H: Into<String>>(message: M, hint: H) -> Self {
+        Self {
+            severity: Severity::Warning,
+            message: message.into(),
+            hint: hint.into(),
+        }
+    }
+
+    fn error<M: Into<String>, H: Into<String>>(message: M, hint: H) -> Self {
+        Self {
+            severity: Severity::Error,
+            message: message.into(),
+            hint: hint.into(),
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{}: {} (hint: {})", severity, self.message, self.hint)
+    }
+}
+
+/// Validates the configuration and returns all problems found.
+pub fn validate(config: &ClientConfig) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+
+    check_role(config, &mut diagnostics);
+    check_keys(config, &mut diagnostics);
+    check_listen_addresses(config, &mut diagnostics);
+    check_sync_concurrency(config, &mut diagnostics);
+    check_runtime(config, &mut diagnostics);
+    check_mempool(config, &mut diagnostics);
+    #[cfg(feature = "rpc-server")]
+    check_rpc_server(config, &mut diagnostics);
+    #[cfg(feature = "webhooks")]
+    check_webhooks(config, &mut diagnostics);
+    #[cfg(feature = "event-sink")]
+    check_event_sink(config, &mut diagnostics);
+
+    diagnostics
+}
+
+fn check_role(config: &ClientConfig, diagnostics: &mut Vec<Diagnostic>) {
+    #[cfg(feature = "validator")]
+    let has_validator = config.validator.is_some();
+    #[cfg(not(feature = "validator"))]
+    let has_validator = false;
+
+    if config.role.runs_validator() && !has_validator {
+        diagnostics.push(Diagnostic::error(
+            "The node role is `validator`, but no validator is configured",
+            "add a [validator] section with the validator address and keys, or choose another role",
+        ));
+    } else if !config.role.runs_validator() && has_validator {
+        diagnostics.push(Diagnostic::error(
+            format!(
+                "The node role is `{}`, but a validator is configured",
+                config.role.to_string().to_lowercase()
+            ),
+            "remove the [validator] section or set the role to `validator`",
+        ));
+    }
+}
+
+fn check_sync_concurrency(config: &ClientConfig, diagnostics: &mut Vec<Diagnostic>) {
+    let consensus = &config.consensus;
+    let settings = [
+        ("num_pending_epochs", consensus.num_pending_epochs),
+        ("num_pending_chunks", consensus.num_pending_chunks),
+        ("max_clusters", consensus.max_clusters),
+        (
+            "concurrent_block_requests",
+            consensus.concurrent_block_requests,
+        ),
+    ];
+
+    for (name, value) in settings {
+        if value == 0 {
+            diagnostics.push(Diagnostic::error(
+                format!("`{}` is 0, which stops the node from syncing", name),
+                format!("set `{}` in the [consensus] section to at least 1", name),
+            ));
+        }
+    }
+}
+
+fn check_runtime(config: &ClientConfig, diagnostics: &mut Vec<Diagnostic>) {
+    let runtime = &config.runtime;
+    let executors = [
+        ("network", runtime.network),
+        ("consensus", runtime.consensus),
+        ("rpc", runtime.rpc),
+    ];
+
+    for (name, executor) in executors {
+        if executor == ExecutorConfig::MultiThread(0) {
+            diagnostics.push(Diagnostic::error(
+                format!("The `{}` runtime has no worker threads", name),
+                format!(
+                    "set `threads` in the [runtime.{}] section to at least 1, or use `kind=\"current-thread\"`",
+                    name
+                ),
+            ));
+        }
+    }
+}
+
+fn check_mempool(config: &ClientConfig, diagnostics: &mut Vec<Diagnostic>) {
+    if let Err(e) = InvalidRelayFee::check(config.mempool.min_relay_fee_per_byte) {
+        diagnostics.push(Diagnostic::error(
+            e.to_string(),
+            "set `min_relay_fee_per_byte` in the [mempool] section to 0 or more",
+        ));
+    }
+}
+
+fn check_keys(config: &ClientConfig, diagnostics: &mut Vec<Diagnostic>) {
+    let file_storage = match &config.storage {
+        StorageConfig::Filesystem(file_storage) => file_storage,
+        _ => return,
+    };
+
+    if let Some(key) = &file_storage.peer_key {
+        let valid = hex::decode(key)
+            .ok()
+            .and_then(|bytes| IdentityKeypair::deserialize_from_vec(&bytes).ok())
+            .is_some();
+        if !valid {
+            diagnostics.push(Diagnostic::error(
+                "The peer key is not a valid hex encoded key",
+                "fix `peer_key` in the [network] section or remove it to generate a new key",
+            ));
+        }
+    }
+
+    #[cfg(feature = "validator")]
+    {
+        if config.validator.is_none() {
+            return;
+        }
+
+        let keys = [
+            (
+                "voting",
+                &file_storage.voting_key_path,
+                &file_storage.voting_key,
+                file_storage
+                    .voting_key
+                    .as_ref()
+                    .map(|key| is_valid_key::<BlsSecretKey>(key)),
+            ),
+            (
+                "signing",
+                &file_storage.signing_key_path,
+                &file_storage.signing_key,
+                file_storage
+                    .signing_key
+                    .as_ref()
+                    .map(|key| is_valid_key::<PrivateKey>(key)),
+            ),
+            (
+                "fee",
+                &file_storage.fee_key_path,
+                &file_storage.fee_key,
+                file_storage
+                    .fee_key
+                    .as_ref()
+                    .map(|key| is_valid_key::<PrivateKey>(key)),
+            ),
+        ];
+
+        for (name, path, key, valid) in keys {
+            match path {
+                None => diagnostics.push(Diagnostic::error(
+                    format!("No file is configured for the validator {} key", name),
+                    format!("set `{}_key_file` in the [validator] section", name),
+                )),
+                // A missing key file is created from the configured key, or from a new random key
+                // which is not registered in the staking contract.
+                Some(path) if !path.exists() && key.is_none() => {
+                    diagnostics.push(Diagnostic::warning(
+                        format!(
+                            "The validator {} key file {} doesn't exist, a new key will be generated",
+                            name,
+                            path.display()
+                        ),
+                        format!(
+                            "set `{}_key` in the [validator] section to the key registered in the staking contract",
+                            name
+                        ),
+                    ))
+                }
+                _ => {}
+            }
+
+            if valid == Some(false) {
+                diagnostics.push(Diagnostic::error(
+                    format!("The validator {} key is not a valid hex encoded key", name),
+                    format!("fix `{}_key` in the [validator] section", name),
+                ));
+            }
+        }
+    }
+}
+
+#[cfg(feature = "validator")]
+fn is_valid_key<T: Deserialize>(key: &str) -> bool {
+    hex::decode(key)
+        .ok()
+        .and_then(|bytes| T::deserialize_from_vec(&bytes).ok())
+        .is_some()
+}
+
+/// The listen addresses are also advertised to other peers, so they have to be reachable.
+fn check_listen_addresses(config: &ClientConfig, diagnostics: &mut Vec<Diagnostic>) {
+    let listen_addresses = &config.network.listen_addresses;
+
+    for (i, address) in listen_addresses.iter().enumerate() {
+        if listen_addresses[..i].contains(address) {
+            diagnostics.push(Diagnostic::error(
+                format!(
+                    "The listen address {} is configured more than once",
+                    address
+                ),
+                "remove the duplicate from `listen_addresses`",
+            ));
+            continue;
+        }
+
+        if !config.network.plain_tcp
+            && !address
+                .iter()
+                .any(|p| matches!(p, Protocol::Ws(_) | Protocol::Wss(_)))
+        {
+            diagnostics.push(Diagnostic::error(
+                format!("The listen address {} is not a websocket address", address),
+                "enable `plain_tcp` or add `/ws` to the address",
+            ));
+        }
+
+        match address.iter().next() {
+            Some(Protocol::Ip4(ip)) if ip.is_unspecified() => {
+                diagnostics.push(unspecified_address(address))
+            }
+            Some(Protocol::Ip6(ip)) if ip.is_unspecified() => {
+                diagnostics.push(unspecified_address(address))
+            }
+            Some(Protocol::Ip4(ip)) if ip.is_loopback() && !config.network.seeds.is_empty() => {
+                diagnostics.push(loopback_address(address))
+            }
+            Some(Protocol::Ip6(ip)) if ip.is_loopback() && !config.network.seeds.is_empty() => {
+                diagnostics.push(loopback_address(address))
+            }
+            _ => {}
+        }
+    }
+
+    let has_wss_address = listen_addresses
+        .iter()
+        .any(|address| address.iter().any(|p| matches!(p, Protocol::Wss(_))));
+    if has_wss_address && config.network.tls.is_none() {
+        diagnostics.push(Diagnostic::error(
+            "There is a secure websocket listen address, but no TLS certificate is configured",
+            "set `certificate_file` and `private_key_file` in `[network.tls]`",
+        ));
+    } else if !has_wss_address && config.network.tls.is_some() {
+        diagnostics.push(Diagnostic::warning(
+            "A TLS certificate is configured, but there is no secure websocket listen address",
+            "add a `/wss` listen address or remove `[network.tls]`",
+        ));
+    }
+
+    if config.network.port_mapping
+        && !listen_addresses.iter().any(|address| {
+            let mut protocols = address.iter();
+            matches!(
+                (protocols.next(), protocols.next()),
+                (Some(Protocol::Ip4(ip)), Some(Protocol::Tcp(_))) if ip.is_private() || ip.is_unspecified()
+            )
+        })
+    {
+        diagnostics.push(Diagnostic::warning(
+            "Port mapping is enabled, but there is no private IPv4 listen address",
+            "add a listen address with the IPv4 address of this machine in the local network",
+        ));
+    }
+
+    if !config.network.trusted_proxies.is_empty()
+        && !listen_addresses
+            .iter()
+            .any(|address| address.iter().any(|p| matches!(p, Protocol::Ws(_))))
+    {
+        diagnostics.push(Diagnostic::warning(
+            "Trusted proxies are configured, but there is no websocket listen address",
+            "forwarded client addresses are only read from websocket connections, add a `/ws` listen address or remove `trusted_proxies`",
+        ));
+    }
+}
+
+fn unspecified_address(address: &Multiaddr) -> Diagnostic {
+    Diagnostic::warning(
+        format!(
+            "The listen address {} is advertised to other peers, but it is not reachable",
+            address
+        ),
+        "use the public IP address or a `/dns4/` address of this machine in `listen_addresses`",
+    )
+}
+
+fn loopback_address(address: &Multiaddr) -> Diagnostic {
+    Diagnostic::warning(
+        format!(
+            "The listen address {} is a loopback address, other machines can't connect to it",
+            address
+        ),
+        "use the public IP address or a `/dns4/` address of this machine in `listen_addresses`",
+    )
+}
+
+#[cfg(feature = "rpc-server")]
+fn check_rpc_server(config: &ClientConfig, diagnostics: &mut Vec<Diagnostic>) {
+    let rpc_server = match &config.rpc_server {
+        Some(rpc_server) => rpc_server,
+        None => return,
+    };
+
+    let public = rpc_server
+        .bind_to
+        .map_or(false, |ip: IpAddr| !ip.is_loopback());
+    let restricted = rpc_server
+        .allow_ips
+        .as_ref()
+        .map_or(false, |ips| !ips.is_empty());
+
+    if public && rpc_server.credentials.is_none() && !restricted {
+        diagnostics.push(Diagnostic::warning(
+            format!(
+                "The RPC server is bound to {} without authentication, anyone who can reach it can control this node",
+                rpc_server.bind_to.unwrap()
+            ),
+            "set `username` and `password` or `allowip` in the [rpc-server] section, or bind it to 127.0.0.1",
+        ));
+    }
+}
+
+#[cfg(feature = "webhooks")]
+fn check_webhooks(config: &ClientConfig, diagnostics: &mut Vec<Diagnostic>) {
+    if let Some(webhooks) = &config.webhooks {
+        if webhooks.endpoints.is_empty() {
+            diagnostics.push(Diagnostic::warning(
+                "Webhooks are enabled, but no endpoint is configured",
+                "add a [[webhooks.endpoint]] section or remove the [webhooks] section",
+            ));
+        }
+    }
+}
+
+#[cfg(feature = "event-sink")]
+fn check_event_sink(config: &ClientConfig, diagnostics: &mut Vec<Diagnostic>) {
+    use crate::config::config::EventSinkBackend;
+
+    let event_sink = match &config.event_sink {
+        Some(event_sink) => event_sink,
+        None => return,
+    };
+
+    if event_sink.brokers.is_empty() {
+        diagnostics.push(Diagnostic::error(
+            "The event sink is enabled, but no broker is configured",
+            "set `brokers` in the [event-sink] section",
+        ));
+    }
+
+    let available = match event_sink.backend {
+        EventSinkBackend::Kafka => cfg!(feature = "kafka"),
+        EventSinkBackend::Nats => cfg!(feature = "nats"),
+    };
+    if !available {
+        diagnostics.push(Diagnostic::error(
+            format!(
+                "The event sink backend `{}` is not available in this build",
+                event_sink.backend
+            ),
+            format!("build the client with the `{}` feature", event_sink.backend),
+        ));
+    }
+}
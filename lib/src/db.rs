@@ -0,0 +1,31 @@
+use std::sync::Arc;
+
+use nimiq_blockchain::Blockchain;
+use nimiq_utils::time::OffsetTime;
+
+use crate::config::config::ClientConfig;
+use crate::error::Error;
+
+/// Rebuilds the address index of the history in the database of the client, reporting the
+/// progress after each epoch. See [`Blockchain::reindex`].
+pub fn reindex_database(config: ClientConfig) -> Result<(), Error> {
+    let environment = config.storage.database(
+        config.network_id,
+        config.consensus.sync_mode,
+        config.database,
+    )?;
+    let blockchain = Blockchain::new(environment, config.network_id, Arc::new(OffsetTime::new()))?;
+
+    log::info!("Reindexing the history");
+    let epochs = blockchain.reindex(|progress| {
+        log::info!(
+            "Reindexed epoch {}/{}: {} entries",
+            progress.epoch,
+            progress.last_epoch,
+            progress.entries
+        );
+    });
+    log::info!("Reindex finished: {} epochs", epochs);
+
+    Ok(())
+}
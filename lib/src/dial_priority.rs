@@ -0,0 +1,184 @@
+use std::collections::HashSet;
+use std::hash::Hash;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::{future::join_all, StreamExt};
+use parking_lot::RwLock;
+
+use beserial::{Deserialize, Serialize};
+use nimiq_blockchain::{AbstractBlockchain, BlockchainEvent, BlockchainLock};
+use nimiq_bls::CompressedPublicKey;
+use nimiq_network_interface::{network::Network as NetworkInterface, peer::Peer};
+use nimiq_network_libp2p::{libp2p::identity, DialPriority, Network, PeerId};
+use nimiq_validator_network::validator_record::SignedValidatorRecord;
+
+/// How long to wait before resolving the validators again if some of their records couldn't be
+/// found.
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Prefers the peers of the current validators.
+///
+/// Validators publish the peer ID they run with in a validator record signed by their voting key.
+/// Such records can only be created by validators, which need to lock up stake, so they are costly
+/// to forge in large numbers.
+///
+/// The peer IDs are resolved once per epoch in the background by [`StakeDialPriority::run`], so
+/// looking up a dial candidate is cheap.
+#[derive(Default)]
+pub struct StakeDialPriority {
+    preferred: RwLock<HashSet<PeerId>>,
+}
+
+impl StakeDialPriority {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resolves the peer IDs of the current validators whenever an epoch is finalized. Records
+    /// that couldn't be found are looked up again after [`RETRY_INTERVAL`].
+    pub async fn run(self: Arc<Self>, blockchain: Arc<BlockchainLock>, network: Arc<Network>) {
+        let mut blockchain_events = blockchain.write().notifier.as_stream();
+
+        loop {
+            let voting_keys = current_voting_keys(&blockchain);
+            let (peer_ids, complete) = resolve_validator_peers(&*network, &voting_keys).await;
+            log::debug!(
+                "Preferring {} of {} validators when dialing",
+                peer_ids.len(),
+                voting_keys.len()
+            );
+            *self.preferred.write() = peer_ids;
+
+            let retry = tokio::time::sleep(RETRY_INTERVAL);
+            tokio::pin!(retry);
+            loop {
+                tokio::select! {
+                    event = blockchain_events.next() => match event {
+                        Some(BlockchainEvent::EpochFinalized(_)) => break,
+                        Some(_) => {}
+                        None => return,
+                    },
+                    _ = &mut retry, if !complete => break,
+                }
+            }
+        }
+    }
+}
+
+impl DialPriority for StakeDialPriority {
+    fn is_preferred(&self, public_key: &identity::PublicKey) -> bool {
+        self.preferred
+            .read()
+            .contains(&public_key.clone().to_peer_id())
+    }
+}
+
+/// Returns the voting keys of the current validators.
+fn current_voting_keys(blockchain: &BlockchainLock) -> Vec<CompressedPublicKey> {
+    blockchain
+        .read()
+        .current_validators()
+        .map(|validators| {
+            validators
+                .iter()
+                .map(|validator| validator.voting_key.compressed().clone())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Looks up the validator records of the given voting keys and returns the peer IDs of the ones
+/// with a valid record, and whether all records were found.
+async fn resolve_validator_peers<N>(
+    network: &N,
+    voting_keys: &[CompressedPublicKey],
+) -> (HashSet<<N::PeerType as Peer>::Id>, bool)
+where
+    N: NetworkInterface,
+    <N::PeerType as Peer>::Id: Serialize + Deserialize + Hash + Eq + Send + Sync,
+    N::AddressType: Serialize + Deserialize + Send + Sync,
+{
+    let lookups = voting_keys.iter().map(|voting_key| async move {
+        let record = network
+            .dht_get::<_, SignedValidatorRecord<<N::PeerType as Peer>::Id, N::AddressType>>(
+                voting_key,
+            )
+            .await
+            .ok()
+            .flatten()?;
+        let public_key = voting_key.uncompress_cached().ok()?;
+        record.verify(&public_key).then(|| record.record.peer_id)
+    });
+    let peer_ids: Vec<_> = join_all(lookups).await;
+
+    let complete = peer_ids.iter().all(Option::is_some);
+    (peer_ids.into_iter().flatten().collect(), complete)
+}
+
+#[cfg(test)]
+mod tests {
+    use nimiq_bls::KeyPair;
+    use nimiq_network_mock::{MockAddress, MockHub};
+    use nimiq_utils::key_rng::SecureGenerate;
+    use nimiq_validator_network::validator_record::ValidatorRecord;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn it_resolves_validators_by_their_records() {
+        let mut hub = MockHub::default();
+        let net1 = hub.new_network();
+        let net2 = hub.new_network();
+        net1.dial_mock(&net2);
+
+        let validator = KeyPair::generate_default_csprng();
+        let impostor = KeyPair::generate_default_csprng();
+        let unresolved = KeyPair::generate_default_csprng();
+
+        // A validator publishes its record.
+        let record = ValidatorRecord::<_, MockAddress>::new(net2.get_local_peer_id(), vec![])
+            .sign(&validator.secret_key);
+        net2.dht_put(&validator.public_key.compress(), &record)
+            .await
+            .unwrap();
+
+        // A record that isn't signed by the validator is ignored.
+        let forged = ValidatorRecord::<_, MockAddress>::new(net1.get_local_peer_id(), vec![])
+            .sign(&validator.secret_key);
+        net2.dht_put(&impostor.public_key.compress(), &forged)
+            .await
+            .unwrap();
+
+        let voting_keys = vec![
+            validator.public_key.compress(),
+            impostor.public_key.compress(),
+        ];
+        let (peer_ids, complete) = resolve_validator_peers(&net1, &voting_keys).await;
+        assert_eq!(peer_ids, HashSet::from([net2.get_local_peer_id()]));
+        assert!(!complete);
+
+        let voting_keys = vec![validator.public_key.compress()];
+        let (_, complete) = resolve_validator_peers(&net1, &voting_keys).await;
+        assert!(complete);
+
+        let voting_keys = vec![unresolved.public_key.compress()];
+        let (peer_ids, complete) = resolve_validator_peers(&net1, &voting_keys).await;
+        assert!(peer_ids.is_empty());
+        assert!(!complete);
+    }
+
+    #[test]
+    fn it_prefers_resolved_peers() {
+        let priority = StakeDialPriority::new();
+        let validator = identity::Keypair::generate_ed25519().public();
+        let other = identity::Keypair::generate_ed25519().public();
+
+        priority
+            .preferred
+            .write()
+            .insert(validator.clone().to_peer_id());
+        assert!(priority.is_preferred(&validator));
+        assert!(!priority.is_preferred(&other));
+    }
+}
@@ -2,6 +2,8 @@ use thiserror::Error;
 
 use nimiq_database::volatile::VolatileDatabaseError;
 
+use crate::config::validation::Diagnostic;
+
 // #[cfg(feature = "validator")]
 // use validator::error::Error as ValidatorError;
 #[derive(Error, Debug)]
@@ -9,6 +11,12 @@ pub enum Error {
     #[error("Configuration error: {0}")]
     Config(String), // TODO
 
+    #[error(
+        "Invalid configuration: {}",
+        .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("; ")
+    )]
+    InvalidConfig(Vec<Diagnostic>),
+
     #[error("LMDB error: {0}")]
     Lmdb(#[from] nimiq_database::lmdb::LmdbError),
 
@@ -24,9 +32,18 @@ pub enum Error {
     #[error("Consensus error: {0}")]
     Consensus(#[from] nimiq_consensus::Error),
 
+    #[error("Blockchain error: {0}")]
+    Blockchain(#[from] nimiq_blockchain::BlockchainError),
+
+    #[error("Database migration error: {0}")]
+    Migration(#[from] nimiq_blockchain::migration::MigrationError),
+
     #[error("Config file parsing error: {0}")]
     Toml(#[from] toml::de::Error),
 
+    #[error("Serialization error: {0}")]
+    Serialization(#[from] beserial::SerializingError),
+
     // #[cfg(feature = "validator")]
     // #[error("Validator error: {0}")]
     // Validator(#[from] ValidatorError),
@@ -34,6 +51,14 @@ pub enum Error {
     #[error("RPC server error: {0}")]
     RpcServer(#[from] nimiq_rpc_server::Error),
 
+    #[cfg(feature = "webhooks")]
+    #[error("Webhook error: {0}")]
+    Webhook(#[from] reqwest::Error),
+
+    #[cfg(feature = "event-sink")]
+    #[error("Event sink error: {0}")]
+    EventSink(String),
+
     #[error("Logger error: {0}")]
     Logging(#[from] log::SetLoggerError),
 
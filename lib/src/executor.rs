@@ -0,0 +1,133 @@
+use std::future::Future;
+use std::sync::Arc;
+use std::thread;
+
+use tokio::runtime::{Builder, Handle};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+
+use nimiq_utils::memory::{AllocationTag, Tagged};
+
+use crate::config::config::{ExecutorConfig, RuntimeConfig};
+use crate::error::Error;
+
+/// Spawns the tasks of a subsystem, either on the runtime of the client or on a dedicated
+/// runtime, see [`ExecutorConfig`]. The allocations of the tasks are attributed to the
+/// subsystem's [`AllocationTag`].
+#[derive(Clone)]
+pub struct Executor {
+    tag: AllocationTag,
+    runtime: Option<Arc<DedicatedRuntime>>,
+}
+
+impl Executor {
+    /// Creates the executor. A dedicated runtime is driven by its own thread, which is named
+    /// after the subsystem.
+    pub fn new(name: &str, tag: AllocationTag, config: ExecutorConfig) -> Result<Self, Error> {
+        let mut builder = match config {
+            ExecutorConfig::Shared => return Ok(Executor { tag, runtime: None }),
+            ExecutorConfig::CurrentThread => Builder::new_current_thread(),
+            ExecutorConfig::MultiThread(threads) => {
+                let mut builder = Builder::new_multi_thread();
+                builder.worker_threads(threads);
+                builder
+            }
+        };
+        let runtime = builder
+            .enable_all()
+            .thread_name(format!("{}-worker", name))
+            .build()?;
+        let handle = runtime.handle().clone();
+
+        // The runtime is dropped on its own thread once the executor is dropped, since dropping a
+        // runtime from within another runtime panics.
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+        thread::Builder::new()
+            .name(name.to_string())
+            .spawn(move || {
+                runtime.block_on(async {
+                    let _ = shutdown_rx.await;
+                });
+            })?;
+
+        log::debug!("Started dedicated {:?} runtime for {}", config, name);
+        Ok(Executor {
+            tag,
+            runtime: Some(Arc::new(DedicatedRuntime {
+                handle,
+                shutdown: Some(shutdown_tx),
+            })),
+        })
+    }
+
+    /// Spawns the task on the runtime of this executor.
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let future = Tagged::new(self.tag, future);
+        match &self.runtime {
+            Some(runtime) => runtime.handle.spawn(future),
+            None => tokio::spawn(future),
+        }
+    }
+
+    /// Returns the handle of the dedicated runtime, or `None` if the executor uses the runtime of
+    /// the client.
+    pub fn handle(&self) -> Option<Handle> {
+        self.runtime.as_ref().map(|runtime| runtime.handle.clone())
+    }
+}
+
+struct DedicatedRuntime {
+    handle: Handle,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl Drop for DedicatedRuntime {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+/// The executors of the subsystems of the client.
+#[derive(Clone)]
+pub struct Executors {
+    pub network: Executor,
+    pub consensus: Executor,
+    pub rpc: Executor,
+}
+
+impl Executors {
+    pub fn new(config: &RuntimeConfig) -> Result<Self, Error> {
+        Ok(Executors {
+            network: Executor::new("network", AllocationTag::Network, config.network)?,
+            consensus: Executor::new("consensus", AllocationTag::Consensus, config.consensus)?,
+            rpc: Executor::new("rpc", AllocationTag::Rpc, config.rpc)?,
+        })
+    }
+
+    /// Returns the handles of the runtimes the tasks are spawned on, by name. The runtime of the
+    /// caller, i.e. the one shared executors spawn on, is called `main`.
+    pub fn runtime_handles(&self) -> Vec<(String, Handle)> {
+        let mut handles: Vec<_> = Handle::try_current()
+            .map(|handle| ("main".to_string(), handle))
+            .into_iter()
+            .collect();
+
+        for (name, executor) in [
+            ("network", &self.network),
+            ("consensus", &self.consensus),
+            ("rpc", &self.rpc),
+        ] {
+            if let Some(handle) = executor.handle() {
+                handles.push((name.to_string(), handle));
+            }
+        }
+
+        handles
+    }
+}
@@ -0,0 +1,557 @@
+use std::{sync::Arc, time::Duration};
+
+use futures::StreamExt;
+use serde_json::{json, Value};
+
+use beserial::{Deserialize, Serialize};
+use nimiq_block::Block;
+use nimiq_blockchain::{
+    AbstractBlockchain, Blockchain, BlockchainLock, EventSequencer, SequencedEvent,
+};
+use nimiq_database::{Database, Environment, ReadTransaction, WriteTransaction};
+use nimiq_hash::{Blake2bHash, Hash};
+use nimiq_primitives::account::AccountType;
+use nimiq_transaction::{
+    account::staking_contract::{IncomingStakingTransactionData, OutgoingStakingTransactionProof},
+    Transaction,
+};
+
+use crate::{
+    client::Client,
+    config::config::{EventSinkBackend, EventSinkConfig},
+    error::Error,
+};
+
+/// Delay before the first retry of a failed publication. The delay doubles with every retry, up to
+/// `MAX_RETRY_DELAY`.
+const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// The last block whose events were published and the state of the cursors. Blocks are published
+/// in order, so all blocks of the main chain up to this one have been published.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct Offset {
+    block_number: u32,
+    hash: Blake2bHash,
+    next_cursor: u64,
+    /// The blocks that can still be reverted and the cursors they were published with.
+    #[beserial(len_type(u16))]
+    announced: Vec<AnnouncedBlock>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct AnnouncedBlock {
+    hash: Blake2bHash,
+    cursor: u64,
+}
+
+impl Offset {
+    fn new(block_number: u32, hash: Blake2bHash, sequencer: &EventSequencer) -> Self {
+        Offset {
+            block_number,
+            hash,
+            next_cursor: sequencer.next_cursor(),
+            announced: sequencer
+                .announced()
+                .into_iter()
+                .map(|(hash, cursor)| AnnouncedBlock { hash, cursor })
+                .collect(),
+        }
+    }
+
+    /// Returns the hash of the block published before the block of this offset, if it can still
+    /// be reverted.
+    fn published_parent(&self) -> Option<Blake2bHash> {
+        match &self.announced[..] {
+            [.., parent, last] if last.hash == self.hash => Some(parent.hash.clone()),
+            _ => None,
+        }
+    }
+
+    fn sequencer(&self) -> EventSequencer {
+        EventSequencer::resume(
+            self.next_cursor,
+            self.block_number,
+            self.announced
+                .iter()
+                .map(|block| (block.hash.clone(), block.cursor))
+                .collect(),
+        )
+    }
+}
+
+/// Stores the offset of the event sink, so it can resume where it stopped after a restart.
+struct OffsetStore {
+    env: Environment,
+    db: Database,
+}
+
+impl OffsetStore {
+    const DB_NAME: &'static str = "EventSink";
+    const OFFSET_KEY: &'static str = "offset";
+
+    fn new(env: Environment) -> Self {
+        let db = env.open_database(Self::DB_NAME.to_string());
+        OffsetStore { env, db }
+    }
+
+    fn get(&self) -> Option<Offset> {
+        ReadTransaction::new(&self.env)
+            .get::<str, Vec<u8>>(&self.db, Self::OFFSET_KEY)
+            .and_then(|bytes| Deserialize::deserialize_from_vec(&bytes).ok())
+    }
+
+    fn put(&self, offset: &Offset) {
+        let mut txn = WriteTransaction::new(&self.env);
+        txn.put::<str, Vec<u8>>(&self.db, Self::OFFSET_KEY, &offset.serialize_to_vec());
+        txn.commit();
+    }
+}
+
+/// The next step of catching up with the main chain.
+#[derive(Debug, PartialEq)]
+enum Step {
+    /// Publish the next block of the main chain.
+    Publish(Block),
+    /// Revert the last published block, if it is still stored, and continue with its parent.
+    Revert {
+        block: Option<Block>,
+        parent_hash: Blake2bHash,
+    },
+}
+
+impl Step {
+    /// Returns the next step after the block of the given offset, or `None` if the sink is at the
+    /// head of the main chain.
+    fn next(blockchain: &Blockchain, offset: &Offset) -> Option<Step> {
+        let on_main_chain = blockchain
+            .get_block_at(offset.block_number, false, None)
+            .map_or(false, |block| block.hash() == offset.hash);
+
+        if on_main_chain {
+            return blockchain
+                .get_block_at(offset.block_number + 1, true, None)
+                .map(Step::Publish);
+        }
+
+        // The reverted block is still stored as a fork, unless it was pruned. Then we continue
+        // with the block published before it, which is its parent. Only if that isn't known
+        // either, the main chain is the best guess.
+        let block = blockchain.get_block(&offset.hash, true, None);
+        let parent_hash = match &block {
+            Some(block) => block.parent_hash().clone(),
+            None => offset.published_parent().unwrap_or_else(|| {
+                blockchain
+                    .get_block_at(offset.block_number - 1, false, None)
+                    .expect("Parent of a reverted block must be on the main chain")
+                    .hash()
+            }),
+        };
+
+        Some(Step::Revert { block, parent_hash })
+    }
+}
+
+/// A connection to the message broker.
+enum Producer {
+    #[cfg(feature = "kafka")]
+    Kafka(rdkafka::producer::FutureProducer),
+    #[cfg(feature = "nats")]
+    Nats(async_nats::jetstream::Context),
+}
+
+impl Producer {
+    #[allow(unused_variables)]
+    async fn connect(backend: EventSinkBackend, brokers: &[String]) -> Result<Self, Error> {
+        match backend {
+            #[cfg(feature = "kafka")]
+            EventSinkBackend::Kafka => {
+                // Idempotent delivery keeps retries from duplicating messages within a session.
+                let producer = rdkafka::ClientConfig::new()
+                    .set("bootstrap.servers", brokers.join(","))
+                    .set("enable.idempotence", "true")
+                    .set("acks", "all")
+                    .create()
+                    .map_err(|e| Error::EventSink(e.to_string()))?;
+                Ok(Producer::Kafka(producer))
+            }
+            #[cfg(feature = "nats")]
+            EventSinkBackend::Nats => {
+                // Messages are published to JetStream, which acknowledges them once they are stored.
+                let client = async_nats::connect(brokers.join(","))
+                    .await
+                    .map_err(|e| Error::EventSink(e.to_string()))?;
+                Ok(Producer::Nats(async_nats::jetstream::new(client)))
+            }
+            #[allow(unreachable_patterns)]
+            backend => Err(Error::config_error(format!(
+                "The event sink backend {} is not available in this build",
+                backend
+            ))),
+        }
+    }
+
+    /// Publishes the payload and waits until the broker acknowledged it.
+    #[allow(unused_variables)]
+    async fn publish(&self, topic: &str, key: &str, payload: String) -> Result<(), String> {
+        match *self {
+            #[cfg(feature = "kafka")]
+            Producer::Kafka(ref producer) => producer
+                .send(
+                    rdkafka::producer::FutureRecord::to(topic)
+                        .key(key)
+                        .payload(&payload),
+                    Duration::from_secs(0),
+                )
+                .await
+                .map(|_| ())
+                .map_err(|(e, _)| e.to_string()),
+            #[cfg(feature = "nats")]
+            Producer::Nats(ref jetstream) => {
+                let ack = jetstream
+                    .publish(topic.to_string(), payload.into())
+                    .await
+                    .map_err(|e| e.to_string())?;
+                ack.await.map(|_| ()).map_err(|e| e.to_string())
+            }
+        }
+    }
+}
+
+/// Publishes block, transaction and staking events of the main chain to a message broker.
+///
+/// Every block of the main chain is published in order, after the events of the previous block
+/// have been acknowledged by the broker. The last published block is stored in the database, so
+/// after a restart publishing resumes with the next block. Delivery is at-least-once: events that
+/// were published right before a crash can be published again. Blocks that are reverted by a
+/// rebranch are published again with `"reverted": true`.
+///
+/// All events of a block carry the cursor of the block, which increases with every published or
+/// reverted block. Events of reverted blocks additionally carry the `revertedCursor` the block was
+/// published with. Events that are published again after a restart keep their cursor, so
+/// consumers can use it to detect duplicates.
+pub struct EventSink {
+    config: EventSinkConfig,
+    producer: Producer,
+    blockchain: Arc<BlockchainLock>,
+    store: OffsetStore,
+}
+
+pub async fn initialize_event_sink(
+    client: &Client,
+    config: EventSinkConfig,
+) -> Result<EventSink, Error> {
+    log::info!(
+        "Initializing {} event sink: {}",
+        config.backend,
+        config.brokers.join(", ")
+    );
+
+    let producer = Producer::connect(config.backend, &config.brokers).await?;
+
+    Ok(EventSink {
+        config,
+        producer,
+        blockchain: client.blockchain(),
+        store: OffsetStore::new(client.environment()),
+    })
+}
+
+impl EventSink {
+    /// Runs the event sink. This never returns.
+    pub async fn run(self) {
+        let mut blockchain_events = self.blockchain.write().notifier.as_stream();
+
+        let mut offset = match self.store.get() {
+            Some(offset) => {
+                log::info!("Resuming event sink after block #{}", offset.block_number);
+                offset
+            }
+            None => {
+                // Nothing was published yet, start with the next block.
+                let head = self.blockchain.read().head();
+                let sequencer = EventSequencer::new(head.block_number());
+                let offset = Offset::new(head.block_number(), head.hash(), &sequencer);
+                self.store.put(&offset);
+                offset
+            }
+        };
+
+        loop {
+            self.catch_up(&mut offset).await;
+
+            // Any change of the main chain can add or revert blocks.
+            if blockchain_events.next().await.is_none() {
+                return;
+            }
+        }
+    }
+
+    /// Reverts the published blocks that aren't on the main chain anymore and publishes the new
+    /// blocks of the main chain.
+    async fn catch_up(&self, offset: &mut Offset) {
+        let mut sequencer = offset.sequencer();
+
+        loop {
+            let step = match Step::next(&self.blockchain.read(), offset) {
+                Some(step) => step,
+                None => return,
+            };
+
+            let (block_number, hash) = match step {
+                Step::Publish(block) => {
+                    let event = sequencer.announce(block.hash(), block.block_number());
+                    self.publish_block(&event, Some(&block)).await;
+                    (block.block_number(), block.hash())
+                }
+                Step::Revert { block, parent_hash } => {
+                    let event = sequencer.revert(offset.hash.clone(), offset.block_number);
+                    if block.is_none() {
+                        log::warn!(
+                            "Reverted block #{} {} is not stored anymore, can't publish the events of its transactions",
+                            offset.block_number,
+                            offset.hash
+                        );
+                    }
+                    self.publish_block(&event, block.as_ref()).await;
+                    (offset.block_number - 1, parent_hash)
+                }
+            };
+
+            *offset = Offset::new(block_number, hash, &sequencer);
+            self.store.put(offset);
+        }
+    }
+
+    /// Publishes the events of a published or reverted block, retrying until they are
+    /// acknowledged. Without the block, only the block event is published.
+    async fn publish_block(&self, event: &SequencedEvent, block: Option<&Block>) {
+        let (cursor, reverted_cursor, block_number, hash) = match event {
+            SequencedEvent::Block {
+                cursor,
+                block_number,
+                hash,
+            } => (*cursor, None, *block_number, hash),
+            SequencedEvent::Revert {
+                cursor,
+                reverted_cursor,
+                block_number,
+                hash,
+            } => (*cursor, *reverted_cursor, *block_number, hash),
+        };
+        let reverted = matches!(event, SequencedEvent::Revert { .. });
+
+        let mut block_event = json!({
+            "cursor": cursor,
+            "blockNumber": block_number,
+            "hash": hash.to_hex(),
+            "reverted": reverted,
+        });
+        if reverted {
+            block_event["revertedCursor"] = reverted_cursor.into();
+        }
+        if let Some(block) = block {
+            block_event["viewNumber"] = block.view_number().into();
+            block_event["parentHash"] = block.parent_hash().to_hex().into();
+            block_event["timestamp"] = block.timestamp().into();
+            block_event["type"] = if block.is_macro() { "macro" } else { "micro" }.into();
+            block_event["numTransactions"] = block.num_transactions().into();
+        }
+        self.publish(&self.config.block_topic, &hash.to_hex(), block_event)
+            .await;
+
+        let transactions = block.and_then(|block| block.transactions());
+        for transaction in transactions.into_iter().flatten() {
+            let tx_hash: Blake2bHash = transaction.hash();
+
+            let mut transaction_event = json!({
+                "cursor": cursor,
+                "hash": tx_hash.to_hex(),
+                "blockNumber": block_number,
+                "blockHash": hash.to_hex(),
+                "from": transaction.sender.to_user_friendly_address(),
+                "to": transaction.recipient.to_user_friendly_address(),
+                "value": u64::from(transaction.value),
+                "fee": u64::from(transaction.fee),
+                "data": hex::encode(&transaction.data),
+                "reverted": reverted,
+            });
+            if reverted {
+                transaction_event["revertedCursor"] = reverted_cursor.into();
+            }
+            self.publish(
+                &self.config.transaction_topic,
+                &tx_hash.to_hex(),
+                transaction_event,
+            )
+            .await;
+
+            if let Some(action) = staking_action(transaction) {
+                let mut staking_event = json!({
+                    "cursor": cursor,
+                    "action": action,
+                    "transactionHash": tx_hash.to_hex(),
+                    "blockNumber": block_number,
+                    "blockHash": hash.to_hex(),
+                    "sender": transaction.sender.to_user_friendly_address(),
+                    "recipient": transaction.recipient.to_user_friendly_address(),
+                    "value": u64::from(transaction.value),
+                    "reverted": reverted,
+                });
+                if reverted {
+                    staking_event["revertedCursor"] = reverted_cursor.into();
+                }
+                self.publish(&self.config.staking_topic, &tx_hash.to_hex(), staking_event)
+                    .await;
+            }
+        }
+    }
+
+    /// Publishes an event, retrying with exponential backoff until it is acknowledged. Events are
+    /// never dropped, so a broker outage pauses the sink until the broker is back.
+    async fn publish(&self, topic: &str, key: &str, event: Value) {
+        let payload = event.to_string();
+
+        let mut retry_delay = INITIAL_RETRY_DELAY;
+        loop {
+            match self.producer.publish(topic, key, payload.clone()).await {
+                Ok(()) => return,
+                Err(err) => log::warn!(
+                    "Failed to publish event to {}: {}, retrying in {:?}",
+                    topic,
+                    err,
+                    retry_delay
+                ),
+            }
+
+            tokio::time::sleep(retry_delay).await;
+            retry_delay = (retry_delay * 2).min(MAX_RETRY_DELAY);
+        }
+    }
+}
+
+/// Returns the name of the staking action of a transaction, or `None` if it doesn't interact with
+/// the staking contract.
+fn staking_action(transaction: &Transaction) -> Option<&'static str> {
+    if transaction.recipient_type == AccountType::Staking {
+        let action = match IncomingStakingTransactionData::parse(transaction).ok()? {
+            IncomingStakingTransactionData::CreateValidator { .. } => "create-validator",
+            IncomingStakingTransactionData::UpdateValidator { .. } => "update-validator",
+            IncomingStakingTransactionData::InactivateValidator { .. } => "inactivate-validator",
+            IncomingStakingTransactionData::ReactivateValidator { .. } => "reactivate-validator",
+            IncomingStakingTransactionData::UnparkValidator { .. } => "unpark-validator",
+            IncomingStakingTransactionData::CreateStaker { .. } => "create-staker",
+            IncomingStakingTransactionData::Stake { .. } => "stake",
+            IncomingStakingTransactionData::UpdateStaker { .. } => "update-staker",
+        };
+        return Some(action);
+    }
+
+    if transaction.sender_type == AccountType::Staking {
+        let action = match OutgoingStakingTransactionProof::parse(transaction).ok()? {
+            OutgoingStakingTransactionProof::DeleteValidator { .. } => "delete-validator",
+            OutgoingStakingTransactionProof::Unstake { .. } => "unstake",
+        };
+        return Some(action);
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use nimiq_block_production::test_utils::TemporaryBlockProducer;
+    use nimiq_hash::HashOutput;
+
+    use super::*;
+
+    fn offset(block: &Block) -> Offset {
+        let sequencer = EventSequencer::new(block.block_number());
+        Offset::new(block.block_number(), block.hash(), &sequencer)
+    }
+
+    #[test]
+    fn it_reverts_to_the_parent_of_the_reverted_block() {
+        let producer1 = TemporaryBlockProducer::new();
+        let producer2 = TemporaryBlockProducer::new();
+
+        // [1] - [2] - [3]
+        //    \- [2'] - [3']
+        let block1 = producer1.next_block(0, vec![]);
+        producer2.push(block1.clone()).unwrap();
+        let block2 = producer1.next_block(0, vec![]);
+        let block3 = producer1.next_block(0, vec![]);
+        let fork2 = producer2.next_block(1, vec![]);
+        let fork3 = producer2.next_block(1, vec![]);
+        producer1.push(fork2.clone()).unwrap();
+        producer1.push(fork3).unwrap();
+
+        let blockchain = producer1.blockchain.read();
+        assert_eq!(
+            Step::next(&blockchain, &offset(&block3)),
+            Some(Step::Revert {
+                block: Some(block3),
+                parent_hash: block2.hash(),
+            })
+        );
+        assert_eq!(
+            Step::next(&blockchain, &offset(&block2)),
+            Some(Step::Revert {
+                block: Some(block2),
+                parent_hash: block1.hash(),
+            })
+        );
+        assert_eq!(
+            Step::next(&blockchain, &offset(&block1)),
+            Some(Step::Publish(fork2))
+        );
+    }
+
+    #[test]
+    fn it_reverts_pruned_blocks_to_the_previously_published_block() {
+        let producer = TemporaryBlockProducer::new();
+        let block1 = producer.next_block(0, vec![]);
+
+        // The reverted block isn't stored, but the block published before it is known.
+        let mut sequencer = EventSequencer::new(0);
+        sequencer.announce(block1.hash(), 1);
+        let pruned: Blake2bHash = [1u8; 32].into();
+        sequencer.announce(pruned.clone(), 2);
+        let offset = Offset::new(2, pruned, &sequencer);
+
+        let blockchain = producer.blockchain.read();
+        assert_eq!(
+            Step::next(&blockchain, &offset),
+            Some(Step::Revert {
+                block: None,
+                parent_hash: block1.hash(),
+            })
+        );
+    }
+
+    #[test]
+    fn it_reads_offsets_migrated_from_the_legacy_layout() {
+        // The migration to schema version 2 appends the next cursor and an empty list of
+        // announced blocks to the block number and hash.
+        let hash: Blake2bHash = [7u8; 32].into();
+        let mut migrated = 42u32.to_be_bytes().to_vec();
+        migrated.extend_from_slice(hash.as_bytes());
+        migrated.extend_from_slice(&1u64.to_be_bytes());
+        migrated.extend_from_slice(&0u16.to_be_bytes());
+
+        let offset: Offset = Deserialize::deserialize_from_vec(&migrated).unwrap();
+        assert_eq!(offset, Offset::new(42, hash, &EventSequencer::new(42)));
+    }
+
+    #[test]
+    fn it_stops_at_the_head() {
+        let producer = TemporaryBlockProducer::new();
+        let block = producer.next_block(0, vec![]);
+
+        assert_eq!(
+            Step::next(&producer.blockchain.read(), &offset(&block)),
+            None
+        );
+    }
+}
@@ -8,17 +8,17 @@ use url::Url;
 /// A rocket-like launcher. We can use this to easily:
 ///
 /// * initialize logging
-/// * initialize dead-lock detection
+/// * initialize stall detection
 /// * handle panics either by logging or human-panic
 /// * load config file?
 /// * parse command line?
 /// * start tokio runtime and pass in the config struct
 ///
 
-#[cfg(feature = "deadlock")]
-use crate::extras::deadlock::initialize_deadlock_detection;
 #[cfg(feature = "logging")]
 use crate::extras::logging::initialize_logging;
+#[cfg(feature = "stall-detector")]
+use crate::extras::stall_detector::initialize_stall_detection;
 
 pub fn go() -> Launcher {
     Launcher::default()
@@ -44,17 +44,17 @@ pub struct PanicMode {
 
 #[derive(Debug, Default)]
 pub struct Launcher {
-    deadlock_detection: bool,
+    stall_detection: bool,
     logging: bool,
     _panic: PanicMode,
 }
 
 impl Launcher {
-    #[cfg(feature = "deadlock")]
+    #[cfg(feature = "stall-detector")]
     #[must_use]
-    pub fn deadlock_detection(mut self) -> Self {
-        self.deadlock_detection = true;
-        initialize_deadlock_detection();
+    pub fn stall_detection(mut self) -> Self {
+        self.stall_detection = true;
+        initialize_stall_detection();
         self
     }
 
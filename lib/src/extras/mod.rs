@@ -1,5 +1,5 @@
-#[cfg(feature = "deadlock")]
-pub mod deadlock;
+#[cfg(feature = "event-sink")]
+pub mod event_sink;
 #[cfg(feature = "logging")]
 pub mod logging;
 #[cfg(feature = "metrics-server")]
@@ -8,6 +8,10 @@ pub mod metrics_server;
 pub mod panic;
 #[cfg(feature = "rpc-server")]
 pub mod rpc_server;
+#[cfg(feature = "stall-detector")]
+pub mod stall_detector;
+#[cfg(feature = "webhooks")]
+pub mod webhooks;
 
 #[cfg(feature = "launcher")]
 pub mod launcher;
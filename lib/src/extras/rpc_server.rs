@@ -1,6 +1,9 @@
 use std::{collections::HashSet, iter::FromIterator, sync::Arc};
 
 use nimiq_rpc_server::dispatchers::*;
+use nimiq_rpc_server::public::{PublicConfig, PublicServer};
+use nimiq_rpc_server::resubmission::TransactionResubmitter;
+use nimiq_rpc_server::tenants::{TenantDispatcher, Tenants};
 
 use nimiq_jsonrpc_core::Credentials;
 use nimiq_jsonrpc_server::{AllowListDispatcher, Config, ModularDispatcher, Server as _Server};
@@ -13,7 +16,7 @@ use crate::config::config::RpcServerConfig;
 use crate::config::consts::default_bind;
 use crate::error::Error;
 
-pub type Server = _Server<AllowListDispatcher<ModularDispatcher>>;
+pub type Server = _Server<AllowListDispatcher<TenantDispatcher<ModularDispatcher>>>;
 
 #[cfg(feature = "rpc-server")]
 pub fn initialize_rpc_server(
@@ -45,12 +48,21 @@ pub fn initialize_rpc_server(
     let wallet_dispatcher = WalletDispatcher::new(wallet_store);
     let unlocked_wallets = Arc::clone(&wallet_dispatcher.unlocked_wallets);
 
-    dispatcher.add(BlockchainDispatcher::new(client.blockchain()));
-    dispatcher.add(ConsensusDispatcher::new(
+    let resubmitter = TransactionResubmitter::spawn(
         client.consensus_proxy(),
-        Some(unlocked_wallets),
-    ));
-    dispatcher.add(NetworkDispatcher::new(client.network()));
+        Arc::clone(&unlocked_wallets),
+        config.resubmission,
+    );
+
+    dispatcher.add(BlockchainDispatcher::new(client.blockchain()));
+    dispatcher.add(
+        ConsensusDispatcher::new(client.consensus_proxy(), Some(unlocked_wallets))
+            .with_resubmitter(resubmitter),
+    );
+    dispatcher.add(
+        NetworkDispatcher::new(client.network(), client.blockchain())
+            .with_registry(crate::registry::REGISTRY),
+    );
     if let Some(mempool) = client.mempool() {
         dispatcher.add(MempoolDispatcher::new(mempool));
     }
@@ -59,6 +71,15 @@ pub fn initialize_rpc_server(
     }
     dispatcher.add(wallet_dispatcher);
 
+    let tenants = Arc::new(Tenants::new(config.api_keys, config.require_api_key));
+    dispatcher.add(
+        AdminDispatcher::new(Arc::clone(&tenants))
+            .with_memory(client.memory_accounting())
+            .with_heap_profile_dir(client.heap_profile_dir())
+            .with_runtimes(client.executors().runtime_handles()),
+    );
+    dispatcher.add(DebugDispatcher::new(client.memory_accounting()));
+
     Ok(Server::new(
         Config {
             bind_to: (config.bind_to.unwrap_or_else(default_bind), config.port).into(),
@@ -66,6 +87,25 @@ pub fn initialize_rpc_server(
             ip_whitelist: None,
             basic_auth,
         },
-        AllowListDispatcher::new(dispatcher, allowed_methods),
+        AllowListDispatcher::new(TenantDispatcher::new(dispatcher, tenants), allowed_methods),
     ))
 }
+
+/// Creates the browser-safe endpoint. It only dispatches to the blockchain, consensus and mempool
+/// methods, none of which have access to the wallets of the node.
+#[cfg(feature = "rpc-server")]
+pub fn initialize_public_rpc_server(
+    client: &Client,
+    config: PublicConfig,
+) -> PublicServer<ModularDispatcher> {
+    log::info!("Initializing public RPC endpoint: {}", config.bind_to);
+
+    let mut dispatcher = ModularDispatcher::default();
+    dispatcher.add(BlockchainDispatcher::new(client.blockchain()));
+    dispatcher.add(ConsensusDispatcher::new(client.consensus_proxy(), None));
+    if let Some(mempool) = client.mempool() {
+        dispatcher.add(MempoolDispatcher::new(mempool));
+    }
+
+    PublicServer::new(config, dispatcher)
+}
@@ -0,0 +1,13 @@
+use std::time::Duration;
+
+use nimiq_utils::stall;
+
+/// How often the watchdog checks for stalled operations.
+const WATCHDOG_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Starts a background thread that warns about operations that stall, e.g. a poll of the swarm
+/// task or a block push that takes too long. This also catches tasks that block a runtime thread
+/// without holding a lock, which a lock-based deadlock detector can't see.
+pub fn initialize_stall_detection() {
+    stall::start_watchdog(WATCHDOG_INTERVAL);
+}
@@ -0,0 +1,420 @@
+use std::{sync::Arc, time::Duration};
+
+use futures::StreamExt;
+use serde_json::{json, Value};
+
+use nimiq_block::Block;
+use nimiq_blockchain::{
+    AbstractBlockchain, BlockchainEvent, BlockchainLock, EventSequencer, SequencedEvent,
+};
+use nimiq_hash::{hmac::compute_hmac_sha512, Blake2bHash};
+use nimiq_keys::Address;
+use nimiq_network_interface::network::{Network as NetworkInterface, NetworkEvent};
+use nimiq_network_libp2p::Network;
+
+use crate::{
+    client::Client,
+    config::config::{WebhookConfig, WebhookEndpointConfig, WebhookEvent},
+    error::Error,
+};
+
+/// Time after which a delivery attempt is aborted.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Delay before the first retry of a failed delivery. The delay doubles with every retry.
+const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Interval in which the age of the head block is checked.
+const STALL_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Header that contains the HMAC signature of the payload.
+const SIGNATURE_HEADER: &str = "X-Nimiq-Signature";
+
+/// Posts node events to the configured webhooks.
+///
+/// Conditions (low peer count, stalled sync, parked validator) are only reported once when they
+/// start and are reported again after they were resolved.
+///
+/// Block events carry a cursor that increases with every event. Blocks reverted by a rebranch are
+/// reported by revert events that reference the cursor of the reverted block's event.
+pub struct WebhookDispatcher {
+    config: WebhookConfig,
+    http: reqwest::Client,
+    blockchain: Arc<BlockchainLock>,
+    sequencer: EventSequencer,
+    network: Arc<Network>,
+    validator_address: Option<Address>,
+    low_peer_count: bool,
+    sync_stalled: bool,
+    validator_parked: bool,
+}
+
+pub fn initialize_webhooks(
+    client: &Client,
+    config: WebhookConfig,
+) -> Result<WebhookDispatcher, Error> {
+    log::info!(
+        "Initializing webhooks: {}",
+        config
+            .endpoints
+            .iter()
+            .map(|endpoint| endpoint.url.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+
+    let http = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()?;
+
+    #[cfg(feature = "validator")]
+    let validator_address = client
+        .validator_proxy()
+        .map(|proxy| proxy.validator_address.read().clone());
+    #[cfg(not(feature = "validator"))]
+    let validator_address = None;
+
+    let blockchain = client.blockchain();
+    let sequencer = EventSequencer::new(blockchain.read().block_number());
+
+    Ok(WebhookDispatcher {
+        config,
+        http,
+        blockchain,
+        sequencer,
+        network: client.network(),
+        validator_address,
+        low_peer_count: false,
+        sync_stalled: false,
+        validator_parked: false,
+    })
+}
+
+impl WebhookDispatcher {
+    /// Runs the dispatcher. This never returns.
+    pub async fn run(mut self) {
+        let mut blockchain_events = self.blockchain.write().notifier.as_stream();
+        let mut network_events = self.network.subscribe_events();
+        let mut stall_check = tokio::time::interval(STALL_CHECK_INTERVAL);
+
+        loop {
+            tokio::select! {
+                Some(event) = blockchain_events.next() => self.on_blockchain_event(event),
+                Some(event) = network_events.next() => {
+                    if let Ok(NetworkEvent::PeerJoined(_) | NetworkEvent::PeerLeft(_)) = event {
+                        self.check_peer_count();
+                    }
+                }
+                _ = stall_check.tick() => self.check_stall(),
+            }
+        }
+    }
+
+    fn on_blockchain_event(&mut self, event: BlockchainEvent) {
+        if let BlockchainEvent::Rebranched(reverted_blocks, adopted_blocks) = &event {
+            if reverted_blocks.len() >= self.config.reorg_depth {
+                let hashes = |blocks: &[(Blake2bHash, Block)]| {
+                    blocks
+                        .iter()
+                        .map(|(hash, _)| hash.to_hex())
+                        .collect::<Vec<_>>()
+                };
+                self.dispatch(
+                    WebhookEvent::Reorg,
+                    json!({
+                        "depth": reverted_blocks.len(),
+                        "revertedBlocks": hashes(reverted_blocks),
+                        "adoptedBlocks": hashes(adopted_blocks),
+                    }),
+                );
+            }
+        }
+
+        for sequenced_event in self.sequencer.sequence(&event) {
+            match sequenced_event {
+                SequencedEvent::Block {
+                    cursor,
+                    block_number,
+                    hash,
+                } => {
+                    let mut data = json!({
+                        "cursor": cursor,
+                        "blockNumber": block_number,
+                        "hash": hash.to_hex(),
+                    });
+                    if let Some(block) = self.blockchain.read().get_block(&hash, false, None) {
+                        data["viewNumber"] = block.view_number().into();
+                        data["timestamp"] = block.timestamp().into();
+                    }
+                    self.dispatch(WebhookEvent::Block, data);
+                }
+                SequencedEvent::Revert {
+                    cursor,
+                    reverted_cursor,
+                    block_number,
+                    hash,
+                } => self.dispatch(
+                    WebhookEvent::Revert,
+                    json!({
+                        "cursor": cursor,
+                        "revertedCursor": reverted_cursor,
+                        "blockNumber": block_number,
+                        "hash": hash.to_hex(),
+                    }),
+                ),
+            }
+        }
+
+        let head = self.blockchain.read().head();
+        if self.sync_stalled {
+            log::info!("Sync resumed at block #{}", head.block_number());
+            self.sync_stalled = false;
+        }
+
+        self.check_parked(head.block_number());
+    }
+
+    fn check_peer_count(&mut self) {
+        let num_peers = self.network.get_peers().len();
+        let low_peer_count = num_peers < self.config.min_peers;
+
+        if low_peer_count && !self.low_peer_count {
+            self.dispatch(
+                WebhookEvent::LowPeerCount,
+                json!({
+                    "numPeers": num_peers,
+                    "minPeers": self.config.min_peers,
+                }),
+            );
+        }
+        self.low_peer_count = low_peer_count;
+    }
+
+    fn check_stall(&mut self) {
+        let (head, now) = {
+            let blockchain = self.blockchain.read();
+            (blockchain.head(), blockchain.time.now())
+        };
+        let head_age = Duration::from_millis(now.saturating_sub(head.timestamp()));
+
+        if head_age > self.config.stall_timeout && !self.sync_stalled {
+            self.sync_stalled = true;
+            self.dispatch(
+                WebhookEvent::SyncStalled,
+                json!({
+                    "blockNumber": head.block_number(),
+                    "hash": head.hash().to_hex(),
+                    "headAge": head_age.as_secs(),
+                }),
+            );
+        }
+    }
+
+    fn check_parked(&mut self, block_number: u32) {
+        let validator_address = match &self.validator_address {
+            Some(address) => address,
+            None => return,
+        };
+
+        let parked = self
+            .blockchain
+            .read()
+            .get_staking_contract()
+            .parked_set
+            .contains(validator_address);
+
+        if parked && !self.validator_parked {
+            self.dispatch(
+                WebhookEvent::ValidatorParked,
+                json!({
+                    "validatorAddress": validator_address.to_user_friendly_address(),
+                    "blockNumber": block_number,
+                }),
+            );
+        }
+        self.validator_parked = parked;
+    }
+
+    /// Posts the event to all endpoints that accept it. Deliveries run in the background, so a slow
+    /// endpoint doesn't delay other events.
+    fn dispatch(&self, event: WebhookEvent, data: Value) {
+        let endpoints: Vec<WebhookEndpointConfig> = self
+            .config
+            .endpoints
+            .iter()
+            .filter(|endpoint| endpoint.accepts(event))
+            .cloned()
+            .collect();
+        if endpoints.is_empty() {
+            return;
+        }
+
+        let payload = json!({
+            "event": event.to_string(),
+            "timestamp": self.blockchain.read().time.now(),
+            "data": data,
+        })
+        .to_string();
+
+        for endpoint in endpoints {
+            tokio::spawn(deliver(
+                self.http.clone(),
+                endpoint,
+                payload.clone(),
+                self.config.max_retries,
+            ));
+        }
+    }
+}
+
+/// Posts the payload to the endpoint, retrying with exponential backoff if the request fails or
+/// the endpoint doesn't respond with a success status.
+async fn deliver(
+    http: reqwest::Client,
+    endpoint: WebhookEndpointConfig,
+    payload: String,
+    max_retries: u32,
+) {
+    let signature = endpoint
+        .secret
+        .as_ref()
+        .map(|secret| compute_hmac_sha512(secret.as_bytes(), payload.as_bytes()).to_string());
+
+    let mut retry_delay = INITIAL_RETRY_DELAY;
+    for attempt in 0..=max_retries {
+        if attempt > 0 {
+            tokio::time::sleep(retry_delay).await;
+            retry_delay *= 2;
+        }
+
+        let mut request = http
+            .post(endpoint.url.clone())
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(payload.clone());
+        if let Some(signature) = &signature {
+            request = request.header(SIGNATURE_HEADER, format!("sha512={}", signature));
+        }
+
+        match request.send().await {
+            Ok(response) if response.status().is_success() => return,
+            Ok(response) => log::debug!(
+                "Webhook {} responded with {} (attempt {})",
+                endpoint.url,
+                response.status(),
+                attempt + 1
+            ),
+            Err(err) => log::debug!(
+                "Failed to post to webhook {}: {} (attempt {})",
+                endpoint.url,
+                err,
+                attempt + 1
+            ),
+        }
+    }
+
+    log::warn!(
+        "Giving up delivering event to webhook {} after {} attempts",
+        endpoint.url,
+        max_retries + 1
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+        task::JoinHandle,
+    };
+    use url::Url;
+
+    use super::*;
+
+    /// Serves one request for each of the given response statuses and returns the requests that
+    /// were received.
+    async fn serve(statuses: Vec<u16>) -> (Url, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap())
+            .parse()
+            .unwrap();
+
+        let server = tokio::spawn(async move {
+            let mut requests = vec![];
+            for status in statuses {
+                let (mut socket, _) = listener.accept().await.unwrap();
+
+                // Read the headers and the body announced by them.
+                let mut request = vec![];
+                let mut buf = [0u8; 1024];
+                loop {
+                    let n = socket.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                    let text = String::from_utf8_lossy(&request);
+                    if let Some(end) = text.find("\r\n\r\n") {
+                        let content_length = text[..end]
+                            .lines()
+                            .find_map(|line| {
+                                let (name, value) = line.split_once(':')?;
+                                name.eq_ignore_ascii_case("content-length")
+                                    .then(|| value.trim().parse::<usize>().unwrap())
+                            })
+                            .unwrap_or(0);
+                        if request.len() >= end + 4 + content_length {
+                            break;
+                        }
+                    }
+                }
+                requests.push(String::from_utf8(request).unwrap());
+
+                let response = format!(
+                    "HTTP/1.1 {} Status\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+                    status
+                );
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+            requests
+        });
+
+        (url, server)
+    }
+
+    #[test]
+    fn it_accepts_only_the_configured_events() {
+        let mut endpoint = WebhookEndpointConfig {
+            url: "http://localhost/hook".parse().unwrap(),
+            events: vec![],
+            secret: None,
+        };
+        assert!(endpoint.accepts(WebhookEvent::Block));
+        assert!(endpoint.accepts(WebhookEvent::SyncStalled));
+
+        endpoint.events = vec![WebhookEvent::Reorg];
+        assert!(endpoint.accepts(WebhookEvent::Reorg));
+        assert!(!endpoint.accepts(WebhookEvent::Block));
+    }
+
+    #[tokio::test]
+    async fn it_retries_failed_deliveries_and_signs_the_payload() {
+        let (url, server) = serve(vec![500, 200]).await;
+        let endpoint = WebhookEndpointConfig {
+            url,
+            events: vec![],
+            secret: Some("secret".to_string()),
+        };
+        let payload = r#"{"event":"block"}"#.to_string();
+
+        deliver(reqwest::Client::new(), endpoint, payload.clone(), 3).await;
+
+        let requests = server.await.unwrap();
+        assert_eq!(requests.len(), 2);
+
+        let signature = compute_hmac_sha512(b"secret", payload.as_bytes()).to_string();
+        for request in requests {
+            assert!(request.starts_with("POST /hook "));
+            assert!(request
+                .to_lowercase()
+                .contains(&format!("x-nimiq-signature: sha512={}", signature).to_lowercase()));
+            assert!(request.ends_with(&payload));
+        }
+    }
+}
@@ -1,5 +1,10 @@
 pub mod client;
 pub mod config;
+pub mod db;
+pub mod dial_priority;
 pub mod error;
+pub mod executor;
 pub mod extras;
 pub mod prelude;
+pub mod registry;
+pub mod sniff;
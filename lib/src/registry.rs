@@ -0,0 +1,24 @@
+//! The registry of all network messages and gossipsub topics used by the client.
+//!
+//! Compilation fails if two crates use the same message type ID or topic name.
+
+#[cfg(not(feature = "validator"))]
+nimiq_network_interface::network_registry!(pub REGISTRY = {
+    messages: [nimiq_consensus::messages::MESSAGES, nimiq_mempool::sync::MESSAGES],
+    topics: [nimiq_consensus::TOPICS, nimiq_mempool::sharding::TOPICS],
+});
+
+#[cfg(feature = "validator")]
+nimiq_network_interface::network_registry!(pub REGISTRY = {
+    messages: [
+        nimiq_consensus::messages::MESSAGES,
+        nimiq_mempool::sync::MESSAGES,
+        nimiq_validator::aggregation::MESSAGES,
+        nimiq_validator_network::MESSAGES,
+    ],
+    topics: [
+        nimiq_consensus::TOPICS,
+        nimiq_mempool::sharding::TOPICS,
+        nimiq_validator::TOPICS,
+    ],
+});
@@ -0,0 +1,138 @@
+//! Capturing and decoding the network traffic of the client, see `nimiq-client sniff`.
+//!
+//! A capture is a recording of all inbound messages (see
+//! [`MessageRecorder`](nimiq_network_interface::recording::MessageRecorder)) made by a client that
+//! doesn't take part in the validation. It can be replayed with `nimiq-replay` or decoded with the
+//! message types of this client.
+
+use std::io::Write;
+use std::path::Path;
+#[cfg(feature = "validator")]
+use std::sync::Arc;
+
+#[cfg(feature = "validator")]
+use futures::StreamExt;
+
+#[cfg(feature = "validator")]
+use nimiq_network_interface::network::{MsgAcceptance, Network as NetworkInterface, Topic};
+use nimiq_network_interface::recording::{RecordedPayload, RecordingReader};
+#[cfg(feature = "validator")]
+use nimiq_network_libp2p::Network;
+#[cfg(feature = "validator")]
+use nimiq_validator::{
+    aggregation::gossip::{TendermintUpdateTopic, ViewChangeUpdateTopic},
+    validator::ProposalTopic,
+};
+
+use crate::error::Error;
+use crate::registry::REGISTRY;
+
+/// Subscribes to the topics that a client without a validator doesn't subscribe to by itself, so
+/// their messages are captured as well. The messages are neither processed nor propagated.
+#[cfg(feature = "validator")]
+pub async fn subscribe_validator_topics(network: &Arc<Network>) -> Result<(), Error> {
+    ignore_topic::<ProposalTopic>(network).await?;
+    ignore_topic::<ViewChangeUpdateTopic>(network).await?;
+    ignore_topic::<TendermintUpdateTopic>(network).await?;
+    Ok(())
+}
+
+#[cfg(feature = "validator")]
+async fn ignore_topic<T: Topic + Sync>(network: &Arc<Network>) -> Result<(), Error> {
+    let mut stream = network.subscribe::<T>().await?;
+    let network = Arc::clone(network);
+    tokio::spawn(async move {
+        while let Some((_, id)) = stream.next().await {
+            if T::VALIDATE {
+                network.validate_message::<T>(id, MsgAcceptance::Ignore);
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Writes the messages of a capture to `out`, one per line, decoded with the message types and
+/// topics of this client. Messages that can't be decoded are written as hex.
+pub fn decode_capture<P: AsRef<Path>, W: Write>(path: P, mut out: W) -> Result<(), Error> {
+    for message in RecordingReader::open(path)? {
+        let message = message?;
+        let timestamp = format!(
+            "{}.{:03}",
+            message.timestamp / 1000,
+            message.timestamp % 1000
+        );
+
+        let (kind, description) = match &message.payload {
+            RecordedPayload::Message { type_id, data } => match REGISTRY.message(*type_id) {
+                Some(schema) => (schema.name.to_string(), (schema.describe)(data)),
+                None => (format!("unknown message {}", type_id), None),
+            },
+            RecordedPayload::Gossip { topic, data } => match REGISTRY.topic(topic) {
+                Some(schema) => (
+                    format!("{} ({})", schema.name, schema.topic),
+                    (schema.describe_item)(data),
+                ),
+                None => (format!("unknown topic {}", topic), None),
+            },
+        };
+
+        let description = description.unwrap_or_else(|| match &message.payload {
+            RecordedPayload::Message { data, .. } | RecordedPayload::Gossip { data, .. } => {
+                hex::encode(data)
+            }
+        });
+
+        writeln!(
+            out,
+            "[{}s] {} from {}: {}",
+            timestamp, kind, message.peer, description
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use beserial::Serialize;
+    use nimiq_consensus::messages::BlockHashes;
+    use nimiq_network_interface::{message::Message, recording::MessageRecorder};
+
+    use super::*;
+
+    #[test]
+    fn it_decodes_captured_messages() {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let path = std::env::temp_dir().join(format!("nimiq-capture-{}.bin", nanos));
+
+        let block_hashes = BlockHashes {
+            hashes: None,
+            request_identifier: 7,
+        };
+        let recorder = MessageRecorder::create(&path).unwrap();
+        recorder.record_message(
+            &"peer-1",
+            BlockHashes::TYPE_ID,
+            &block_hashes.serialize_to_vec(),
+        );
+        recorder.record_gossip(&"peer-2", "unknown-topic", &[0xab, 0xcd]);
+        drop(recorder);
+
+        let mut out = vec![];
+        let result = decode_capture(&path, &mut out);
+        std::fs::remove_file(&path).ok();
+        result.unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains("BlockHashes"));
+        assert!(lines[0].contains("from peer-1"));
+        assert!(lines[1].ends_with("unknown topic unknown-topic from peer-2: abcd"));
+    }
+}
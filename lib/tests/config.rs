@@ -1,9 +1,15 @@
 use std::path::PathBuf;
 
+use nimiq_consensus::RequestServing;
 use nimiq_lib::config::{
-    config::{ClientConfigBuilder, DatabaseConfig, DatabaseConfigBuilder, FileStorageConfig},
+    config::{
+        ClientConfigBuilder, DatabaseConfig, DatabaseConfigBuilder, ExecutorConfig,
+        FileStorageConfig, NodeRole, RuntimeConfig,
+    },
     config_file::ConfigFile,
 };
+use nimiq_lib::error::Error;
+use nimiq_network_libp2p::discovery::peer_contacts::Services;
 
 #[test]
 fn config_file_no_db_entry() {
@@ -112,3 +118,359 @@ fn config_file_partial_db_entry() {
 
     assert_eq!(config.storage, db_config.into());
 }
+
+#[test]
+fn config_validator_role_without_validator() {
+    let config_file: ConfigFile = toml::from_str(
+        r#"
+    role = "validator"
+    "#,
+    )
+    .unwrap();
+
+    let mut config_builder = ClientConfigBuilder::default();
+    config_builder.config_file(&config_file).unwrap();
+
+    match config_builder.build() {
+        Err(Error::InvalidConfig(diagnostics)) => {
+            assert_eq!(diagnostics.len(), 1);
+            assert!(diagnostics[0].is_error());
+        }
+        other => panic!("Expected an invalid config, got {:?}", other),
+    }
+}
+
+#[test]
+fn config_seed_role() {
+    let config_file: ConfigFile = toml::from_str(
+        r#"
+    role = "seed"
+    "#,
+    )
+    .unwrap();
+
+    let mut config_builder = ClientConfigBuilder::default();
+    config_builder.config_file(&config_file).unwrap();
+    let config = config_builder.build().unwrap();
+
+    assert_eq!(config.role, NodeRole::Seed);
+    assert_eq!(config.role.request_serving(), RequestServing::HeadOnly);
+    assert!(!config.role.runs_mempool());
+}
+
+#[test]
+fn config_role_services() {
+    let history_services = Services::BLOCK_HISTORY | Services::TRANSACTION_INDEX;
+
+    for role in [
+        NodeRole::Validator,
+        NodeRole::Full,
+        NodeRole::History,
+        NodeRole::Light,
+        NodeRole::Seed,
+    ] {
+        let services = role.services();
+
+        assert_eq!(
+            services.contains(Services::VALIDATOR),
+            role == NodeRole::Validator,
+            "{}",
+            role
+        );
+        assert_eq!(
+            services.contains(history_services),
+            matches!(role, NodeRole::Validator | NodeRole::History),
+            "{}",
+            role
+        );
+        assert_eq!(
+            services.intersects(history_services),
+            services.contains(history_services),
+            "{}",
+            role
+        );
+        assert_eq!(
+            services.contains(Services::MEMPOOL),
+            role.runs_mempool(),
+            "{}",
+            role
+        );
+        assert_eq!(
+            services.contains(Services::FULL_BLOCKS | Services::ACCOUNTS_CHUNKS),
+            !matches!(role, NodeRole::Light | NodeRole::Seed),
+            "{}",
+            role
+        );
+    }
+}
+
+#[test]
+fn config_role_subsystems() {
+    let expected = [
+        (NodeRole::Validator, true, true, RequestServing::Full),
+        (NodeRole::Full, false, true, RequestServing::WithoutHistory),
+        (NodeRole::History, false, true, RequestServing::Full),
+        (
+            NodeRole::Light,
+            false,
+            false,
+            RequestServing::WithoutHistory,
+        ),
+        (NodeRole::Seed, false, false, RequestServing::HeadOnly),
+    ];
+
+    for (role, runs_validator, runs_mempool, request_serving) in expected {
+        assert_eq!(role.runs_validator(), runs_validator, "{}", role);
+        assert_eq!(role.runs_mempool(), runs_mempool, "{}", role);
+        assert_eq!(role.request_serving(), request_serving, "{}", role);
+    }
+}
+
+#[cfg(feature = "validator")]
+#[test]
+fn config_validator_with_incompatible_role() {
+    for role in ["full", "history", "light", "seed"] {
+        let config_file: ConfigFile = toml::from_str(&format!(
+            r#"
+    role = "{}"
+
+    [validator]
+    validator_address = "NQ07 0000 0000 0000 0000 0000 0000 0000 0000"
+    signing_key_file = "signing_key.dat"
+    voting_key_file = "voting_key.dat"
+    fee_key_file = "fee_key.dat"
+    "#,
+            role
+        ))
+        .unwrap();
+
+        let mut config_builder = ClientConfigBuilder::default();
+        config_builder.config_file(&config_file).unwrap();
+
+        match config_builder.build() {
+            Err(Error::InvalidConfig(diagnostics)) => {
+                assert_eq!(diagnostics.len(), 1, "{}", role);
+                assert!(diagnostics[0].message.contains(role));
+            }
+            other => panic!(
+                "Expected an invalid config for role {}, got {:?}",
+                role, other
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "validator")]
+#[test]
+fn config_validator_defaults_to_validator_role() {
+    let config_file: ConfigFile = toml::from_str(
+        r#"
+    [validator]
+    validator_address = "NQ07 0000 0000 0000 0000 0000 0000 0000 0000"
+    signing_key_file = "signing_key.dat"
+    voting_key_file = "voting_key.dat"
+    fee_key_file = "fee_key.dat"
+    "#,
+    )
+    .unwrap();
+
+    let mut config_builder = ClientConfigBuilder::default();
+    config_builder.config_file(&config_file).unwrap();
+    let config = config_builder.build().unwrap();
+
+    assert_eq!(config.role, NodeRole::Validator);
+}
+
+#[test]
+fn config_duplicate_listen_address() {
+    let config_file: ConfigFile = toml::from_str(
+        r#"
+    [network]
+    listen_addresses = ["/ip4/127.0.0.1/tcp/8443/ws", "/ip4/127.0.0.1/tcp/8443/ws"]
+    "#,
+    )
+    .unwrap();
+
+    let mut config_builder = ClientConfigBuilder::default();
+    config_builder.config_file(&config_file).unwrap();
+
+    assert!(matches!(
+        config_builder.build(),
+        Err(Error::InvalidConfig(_))
+    ));
+}
+
+#[test]
+fn config_plain_tcp_listen_address() {
+    let listen_on_tcp = |plain_tcp: bool| {
+        let config_file: ConfigFile = toml::from_str(&format!(
+            r#"
+    [network]
+    listen_addresses = ["/ip4/127.0.0.1/tcp/8443/ws", "/ip4/127.0.0.1/tcp/8444"]
+    plain_tcp = {}
+    "#,
+            plain_tcp
+        ))
+        .unwrap();
+
+        let mut config_builder = ClientConfigBuilder::default();
+        config_builder.config_file(&config_file).unwrap();
+        config_builder.build()
+    };
+
+    assert!(matches!(listen_on_tcp(false), Err(Error::InvalidConfig(_))));
+    assert!(listen_on_tcp(true).unwrap().network.plain_tcp);
+}
+
+#[test]
+fn config_sync_concurrency_must_not_be_zero() {
+    let with_setting = |setting: &str| {
+        let config_file: ConfigFile = toml::from_str(&format!(
+            r#"
+    [consensus]
+    {}
+    "#,
+            setting
+        ))
+        .unwrap();
+
+        let mut config_builder = ClientConfigBuilder::default();
+        config_builder.config_file(&config_file).unwrap();
+        config_builder.build()
+    };
+
+    for name in [
+        "num_pending_epochs",
+        "num_pending_chunks",
+        "max_clusters",
+        "concurrent_block_requests",
+    ] {
+        assert!(
+            matches!(
+                with_setting(&format!("{} = 0", name)),
+                Err(Error::InvalidConfig(_))
+            ),
+            "{}",
+            name
+        );
+        assert!(with_setting(&format!("{} = 1", name)).is_ok(), "{}", name);
+    }
+}
+
+#[test]
+fn config_wss_listen_address_requires_tls() {
+    let listen_on_wss = |tls: &str| {
+        let config_file: ConfigFile = toml::from_str(&format!(
+            r#"
+    [network]
+    listen_addresses = ["/ip4/127.0.0.1/tcp/8443/wss"]
+    {}
+    "#,
+            tls
+        ))
+        .unwrap();
+
+        let mut config_builder = ClientConfigBuilder::default();
+        config_builder.config_file(&config_file)?;
+        config_builder.build()
+    };
+
+    assert!(matches!(listen_on_wss(""), Err(Error::InvalidConfig(_))));
+    let config = listen_on_wss(
+        r#"
+    [network.tls]
+    certificate_file = "fullchain.pem"
+    private_key_file = "privkey.pem"
+    "#,
+    )
+    .unwrap();
+    assert_eq!(
+        config.network.tls.unwrap().certificate_file,
+        std::path::PathBuf::from("fullchain.pem")
+    );
+
+    // The PKCS#12 identity of older configuration files is still accepted, but ignored.
+    assert!(matches!(
+        listen_on_wss(
+            r#"
+    [network.tls]
+    identity_file = "./my.domain.p12"
+    identity_password = "secret"
+    "#
+        ),
+        Err(Error::InvalidConfig(_))
+    ));
+    assert!(matches!(
+        listen_on_wss(
+            r#"
+    [network.tls]
+    certificate_file = "fullchain.pem"
+    "#
+        ),
+        Err(Error::Config(_))
+    ));
+}
+
+#[test]
+fn runtime_scales_with_cores() {
+    // Few cores are shared by all subsystems.
+    for cores in 1..4 {
+        let config = RuntimeConfig::for_cores(cores);
+        assert_eq!(config.network, ExecutorConfig::Shared);
+        assert_eq!(config.rpc, ExecutorConfig::Shared);
+    }
+
+    // The network handles its connections on several threads.
+    let config = RuntimeConfig::for_cores(4);
+    assert_eq!(config.network, ExecutorConfig::MultiThread(2));
+    assert_eq!(config.rpc, ExecutorConfig::Shared);
+
+    let config = RuntimeConfig::for_cores(16);
+    assert_eq!(config.network, ExecutorConfig::MultiThread(4));
+    assert_eq!(config.consensus, ExecutorConfig::Shared);
+    assert_eq!(config.rpc, ExecutorConfig::MultiThread(4));
+}
+
+#[test]
+fn config_file_runtime_overrides_defaults() {
+    let config_file: ConfigFile = toml::from_str(
+        r#"
+    [runtime.network]
+    kind = "current-thread"
+
+    [runtime.rpc]
+    kind = "multi-thread"
+    threads = 3
+    "#,
+    )
+    .unwrap();
+    let mut config_builder = ClientConfigBuilder::default();
+    config_builder.config_file(&config_file).unwrap();
+    let config = config_builder.build().unwrap();
+
+    assert_eq!(config.runtime.network, ExecutorConfig::CurrentThread);
+    assert_eq!(config.runtime.rpc, ExecutorConfig::MultiThread(3));
+    assert_eq!(config.runtime.consensus, RuntimeConfig::default().consensus);
+}
+
+#[test]
+fn config_min_relay_fee_must_not_be_negative() {
+    let with_fee = |fee: f64| {
+        let config_file: ConfigFile = toml::from_str(&format!(
+            r#"
+    [mempool]
+    min_relay_fee_per_byte = {:?}
+    "#,
+            fee
+        ))
+        .unwrap();
+
+        let mut config_builder = ClientConfigBuilder::default();
+        config_builder.config_file(&config_file).unwrap();
+        config_builder.build()
+    };
+
+    assert!(matches!(with_fee(-1.0), Err(Error::InvalidConfig(_))));
+    assert!(with_fee(0.0).is_ok());
+    assert!(with_fee(1.5).is_ok());
+}
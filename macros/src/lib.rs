@@ -42,6 +42,18 @@ macro_rules! create_typed_array {
             }
         }
 
+        impl ::beserial::Schema for $name {
+            fn name() -> String {
+                stringify!($name).to_string()
+            }
+
+            fn schema() -> ::beserial::TypeSchema {
+                ::beserial::TypeSchema::Bytes {
+                    len: $len * ::std::mem::size_of::<$t>(),
+                }
+            }
+        }
+
         impl From<[$t; $len]> for $name {
             fn from(arr: [$t; $len]) -> Self {
                 $name(arr)
@@ -1,4 +1,27 @@
+use std::time::Duration;
+
+use thiserror::Error;
+
+use nimiq_utils::shared_rng::SharedRng;
+
 use crate::filter::{MempoolFilter, MempoolRules};
+use crate::inclusion::InclusionPolicy;
+
+/// Error returned for a minimum relay fee that is negative or not a number.
+#[derive(Clone, Copy, Debug, Error, PartialEq)]
+#[error("Invalid minimum relay fee per byte: {0}, it must be zero or positive")]
+pub struct InvalidRelayFee(pub f64);
+
+impl InvalidRelayFee {
+    /// Checks that the fee per byte is zero, positive or infinite.
+    pub fn check(fee_per_byte: f64) -> Result<f64, Self> {
+        if fee_per_byte >= 0.0 {
+            Ok(fee_per_byte)
+        } else {
+            Err(Self(fee_per_byte))
+        }
+    }
+}
 
 /// Struct defining a Mempool configuration
 #[derive(Debug, Clone)]
@@ -7,6 +30,27 @@ pub struct MempoolConfig {
     pub filter_rules: MempoolRules,
     /// Mempool filter limit or size
     pub filter_limit: usize,
+    /// Whether to subscribe to the sharded transaction topics instead of the unsharded one
+    pub sharded_topics: bool,
+    /// The transaction shards to subscribe to if sharded topics are enabled. If not set, all
+    /// shards are subscribed, as block producers need all transactions
+    pub transaction_shards: Option<Vec<u8>>,
+    /// Whether to subscribe to the unsharded transaction topic as well if sharded topics are
+    /// enabled. This is only meant for the transition, while some nodes still publish to it
+    pub legacy_transaction_topic: bool,
+    /// If set, transactions on the inclusion list are put first into produced blocks and the
+    /// inclusion list is accounted for all adopted blocks
+    pub inclusion_policy: Option<InclusionPolicy>,
+    /// Whether to reconcile the mempool with every newly connected peer
+    pub sync_on_connect: bool,
+    /// If set, the mempool is reconciled with a few random peers in this interval
+    pub reconciliation_interval: Option<Duration>,
+    /// Minimum fee per byte of transactions received via gossip. Transactions paying less are
+    /// rejected before they are verified and thus not relayed. Unlike the filter rules, this
+    /// doesn't apply to transactions added locally
+    pub min_relay_fee_per_byte: f64,
+    /// The randomness used to choose the peers the mempool is reconciled with
+    pub rng: SharedRng,
 }
 
 impl Default for MempoolConfig {
@@ -14,6 +58,14 @@ impl Default for MempoolConfig {
         MempoolConfig {
             filter_rules: MempoolRules::default(),
             filter_limit: MempoolFilter::DEFAULT_BLACKLIST_SIZE,
+            sharded_topics: false,
+            transaction_shards: None,
+            legacy_transaction_topic: false,
+            inclusion_policy: None,
+            sync_on_connect: true,
+            reconciliation_interval: Some(Duration::from_secs(60)),
+            min_relay_fee_per_byte: 0.0,
+            rng: SharedRng::os(),
         }
     }
 }
@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+
+use beserial::Deserialize;
+use nimiq_block::Block;
+use nimiq_hash::{Blake2bHash, Hash};
+use nimiq_keys::Address;
+use nimiq_primitives::account::AccountType;
+use nimiq_primitives::coin::Coin;
+use nimiq_transaction::account::htlc_contract::ProofType;
+use nimiq_transaction::Transaction;
+
+use crate::mempool::MempoolState;
+
+/// Index of the transactions that were recently mined, by sender.
+///
+/// It is used to tell a wallet which transaction its new transaction conflicts with, even after
+/// the conflicting transaction already left the mempool.
+#[derive(Default)]
+pub(crate) struct RecentlyMined {
+    // The last transaction of each sender, together with the number of the block it was mined in.
+    by_sender: HashMap<Address, (Blake2bHash, u32)>,
+}
+
+impl RecentlyMined {
+    /// Number of blocks a mined transaction is kept in the index.
+    pub const NUM_BLOCKS: u32 = 120;
+
+    /// Adds the transactions of an adopted block.
+    pub fn add_block(&mut self, block: &Block) {
+        if let Some(transactions) = block.transactions() {
+            for tx in transactions {
+                self.by_sender
+                    .insert(tx.sender.clone(), (tx.hash(), block.block_number()));
+            }
+        }
+    }
+
+    /// Removes the transactions of a reverted block.
+    pub fn revert_block(&mut self, block: &Block) {
+        if let Some(transactions) = block.transactions() {
+            for tx in transactions {
+                let tx_hash = tx.hash();
+                if matches!(self.by_sender.get(&tx.sender), Some((hash, _)) if *hash == tx_hash) {
+                    self.by_sender.remove(&tx.sender);
+                }
+            }
+        }
+    }
+
+    /// Removes all transactions that were mined more than `NUM_BLOCKS` blocks before the given
+    /// block number.
+    pub fn prune(&mut self, block_number: u32) {
+        let min_block_number = block_number.saturating_sub(Self::NUM_BLOCKS);
+        self.by_sender
+            .retain(|_, (_, mined_at)| *mined_at >= min_block_number);
+    }
+
+    /// Returns the hash of the last transaction of the given sender that was recently mined.
+    pub fn get(&self, sender: &Address) -> Option<&Blake2bHash> {
+        self.by_sender.get(sender).map(|(hash, _)| hash)
+    }
+}
+
+impl MempoolState {
+    /// Looks for the transaction that the given transaction conflicts with, either in the mempool
+    /// or among the recently mined transactions. `sender_balance` is the balance of the sender in
+    /// the blockchain, or `None` if the sender account doesn't exist.
+    ///
+    /// Two transactions conflict if:
+    ///  - both resolve the same HTLC the same way and the pending one drains the contract, or
+    ///  - the sender could pay for the transaction alone, but not together with the transactions of
+    ///    the same sender that are already in the mempool or were recently mined.
+    ///
+    /// An HTLC can be withdrawn from in several transactions, e.g. an early resolve followed by the
+    /// remainder, so these only conflict if they exceed the balance of the contract together.
+    pub(crate) fn find_conflict(
+        &self,
+        transaction: &Transaction,
+        sender_balance: Option<Coin>,
+    ) -> Option<Blake2bHash> {
+        let pending = self.state_by_sender.get(&transaction.sender);
+
+        let sender_balance = match sender_balance {
+            // The account was emptied and pruned, most likely by a recently mined transaction.
+            None => return self.recently_mined.get(&transaction.sender).cloned(),
+            Some(balance) => balance,
+        };
+
+        if let (AccountType::HTLC, Some(sender_state)) = (transaction.sender_type, pending) {
+            let proof_type = htlc_proof_type(transaction);
+            let draining = sender_state.txns.iter().find(|hash| {
+                self.get(hash).map_or(false, |tx| {
+                    htlc_proof_type(tx) == proof_type && tx.total_value() == sender_balance
+                })
+            });
+            if let Some(hash) = draining {
+                return Some(hash.clone());
+            }
+        }
+
+        if let Some(sender_state) = pending {
+            if transaction.total_value() <= sender_balance
+                && transaction.total_value() + sender_state.total > sender_balance
+            {
+                // Report the pending transaction that uses most of the balance, replacing it is the
+                // most likely way to make room for the new one.
+                return sender_state
+                    .txns
+                    .iter()
+                    .filter_map(|hash| self.get(hash).map(|tx| (tx.total_value(), hash)))
+                    .max()
+                    .map(|(_, hash)| hash.clone());
+            }
+        }
+
+        if transaction.total_value() > sender_balance {
+            return self.recently_mined.get(&transaction.sender).cloned();
+        }
+
+        None
+    }
+}
+
+/// Returns the way an outgoing HTLC transaction resolves the contract.
+fn htlc_proof_type(transaction: &Transaction) -> Option<ProofType> {
+    ProofType::deserialize(&mut &transaction.proof[..]).ok()
+}
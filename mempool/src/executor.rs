@@ -7,7 +7,7 @@ use futures::task::{Context, Poll};
 use futures::{stream::BoxStream, Future, StreamExt};
 use parking_lot::{RwLock, RwLockUpgradableReadGuard};
 
-use nimiq_blockchain::Blockchain;
+use nimiq_blockchain::BlockchainReader;
 use nimiq_network_interface::network::{MsgAcceptance, Network};
 use nimiq_primitives::networks::NetworkId;
 use nimiq_transaction::Transaction;
@@ -19,8 +19,8 @@ use crate::verify::{verify_tx, VerifyErr};
 const CONCURRENT_VERIF_TASKS: u32 = 1000;
 
 pub(crate) struct MempoolExecutor<N: Network> {
-    // Blockchain reference
-    blockchain: Arc<RwLock<Blockchain>>,
+    // Handle to read the blockchain state
+    blockchain: BlockchainReader,
 
     // The mempool state: the data structure where the transactions are stored
     state: Arc<RwLock<MempoolState>>,
@@ -28,6 +28,9 @@ pub(crate) struct MempoolExecutor<N: Network> {
     // Mempool filter
     filter: Arc<RwLock<MempoolFilter>>,
 
+    // Minimum fee per byte of relayed transactions
+    min_relay_fee_per_byte: Arc<RwLock<f64>>,
+
     // Ongoing verification tasks counter
     verification_tasks: Arc<AtomicU32>,
 
@@ -43,18 +46,20 @@ pub(crate) struct MempoolExecutor<N: Network> {
 
 impl<N: Network> MempoolExecutor<N> {
     pub fn new(
-        blockchain: Arc<RwLock<Blockchain>>,
+        blockchain: BlockchainReader,
         state: Arc<RwLock<MempoolState>>,
         filter: Arc<RwLock<MempoolFilter>>,
+        min_relay_fee_per_byte: Arc<RwLock<f64>>,
         network: Arc<N>,
         txn_stream: BoxStream<'static, (Transaction, <N as Network>::PubsubId)>,
     ) -> Self {
         Self {
-            blockchain: blockchain.clone(),
+            network_id: Arc::new(blockchain.network_id()),
+            blockchain,
             state,
             filter,
+            min_relay_fee_per_byte,
             network,
-            network_id: Arc::new(blockchain.read().network_id),
             verification_tasks: Arc::new(AtomicU32::new(0)),
             txn_stream,
         }
@@ -73,7 +78,16 @@ impl<N: Network> Future for MempoolExecutor<N> {
                 continue;
             }
 
-            let blockchain = Arc::clone(&self.blockchain);
+            // Transactions paying less than the relay fee are rejected without verifying them,
+            // so fee spam doesn't use up our gossip bandwidth.
+            if tx.fee_per_byte() < *self.min_relay_fee_per_byte.read() {
+                log::debug!("Rejecting transaction below the minimum relay fee");
+                self.network
+                    .validate_message::<TransactionTopic>(pubsub_id, MsgAcceptance::Reject);
+                continue;
+            }
+
+            let blockchain = self.blockchain.clone();
             let mempool_state = Arc::clone(&self.state);
             let filter = Arc::clone(&self.filter);
             let tasks_count = Arc::clone(&self.verification_tasks);
@@ -92,8 +106,11 @@ impl<N: Network> Future for MempoolExecutor<N> {
 
                     match verify_tx_ret {
                         Ok(mempool_state_lock) => {
-                            RwLockUpgradableReadGuard::upgrade(mempool_state_lock).put(&tx);
-                            MsgAcceptance::Accept
+                            if RwLockUpgradableReadGuard::upgrade(mempool_state_lock).put(&tx) {
+                                MsgAcceptance::Accept
+                            } else {
+                                MsgAcceptance::Ignore
+                            }
                         }
                         // Reject the message if signature verification fails or transaction is invalid
                         // for current validation window
@@ -0,0 +1,140 @@
+use beserial::{Deserialize, Serialize};
+
+/// Number of cells each key is added to.
+const NUM_HASHES: usize = 3;
+
+/// A cell of an [`Iblt`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, Schema)]
+pub struct IbltCell {
+    /// Number of keys added to the cell, negative after subtracting a table with more keys
+    pub count: i32,
+    /// XOR of the keys added to the cell
+    pub key_sum: u64,
+    /// XOR of the check hashes of the keys added to the cell
+    pub hash_sum: u64,
+}
+
+impl IbltCell {
+    fn toggle(&mut self, key: u64, count: i32) {
+        self.count += count;
+        self.key_sum ^= key;
+        self.hash_sum ^= check_hash(key);
+    }
+
+    fn is_pure(&self) -> bool {
+        (self.count == 1 || self.count == -1) && self.hash_sum == check_hash(self.key_sum)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.count == 0 && self.key_sum == 0 && self.hash_sum == 0
+    }
+}
+
+/// An invertible Bloom lookup table of 64 bit keys.
+///
+/// The difference of two tables can be decoded into the keys that are only in either of them, as
+/// long as the difference is small compared to the size of the tables. The size of the tables is
+/// independent of the number of keys, which makes them suitable to reconcile large sets that
+/// differ only slightly. Keys should be uniformly distributed, e.g. salted hashes.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Schema)]
+pub struct Iblt {
+    #[beserial(len_type(u16))]
+    cells: Vec<IbltCell>,
+}
+
+impl Iblt {
+    /// Creates an empty table. The number of cells is rounded up to a multiple of the number of
+    /// hash functions.
+    pub fn new(num_cells: usize) -> Self {
+        let num_cells = (num_cells.max(1) + NUM_HASHES - 1) / NUM_HASHES * NUM_HASHES;
+        Iblt {
+            cells: vec![IbltCell::default(); num_cells],
+        }
+    }
+
+    /// Returns the number of cells of the table.
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    /// Returns whether the table has no cells.
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    /// Adds a key to the table.
+    pub fn insert(&mut self, key: u64) {
+        self.toggle(key, 1);
+    }
+
+    /// Removes a key from the table.
+    pub fn remove(&mut self, key: u64) {
+        self.toggle(key, -1);
+    }
+
+    fn toggle(&mut self, key: u64, count: i32) {
+        // Every hash function has its own part of the table, so the cells of a key are distinct.
+        let part_len = self.cells.len() / NUM_HASHES;
+        if part_len == 0 {
+            return;
+        }
+        for i in 0..NUM_HASHES {
+            let index = i * part_len + (mix(key, i as u64) % part_len as u64) as usize;
+            self.cells[index].toggle(key, count);
+        }
+    }
+
+    /// Subtracts the other table from this one. Returns `None` if the tables differ in size.
+    pub fn subtract(mut self, other: &Iblt) -> Option<Iblt> {
+        if self.cells.len() != other.cells.len() {
+            return None;
+        }
+        for (cell, other) in self.cells.iter_mut().zip(&other.cells) {
+            cell.count -= other.count;
+            cell.key_sum ^= other.key_sum;
+            cell.hash_sum ^= other.hash_sum;
+        }
+        Some(self)
+    }
+
+    /// Decodes the difference of two tables created by [`Iblt::subtract`]. Returns the keys that
+    /// are only in the minuend and the keys that are only in the subtrahend, or `None` if the
+    /// difference is too large to be decoded.
+    pub fn decode(mut self) -> Option<(Vec<u64>, Vec<u64>)> {
+        let mut only_in_self = vec![];
+        let mut only_in_other = vec![];
+
+        // A table can't hold more keys than it has cells, this also stops the peeling of
+        // corrupted tables.
+        for _ in 0..self.cells.len() {
+            let (key, count) = match self.cells.iter().find(|cell| cell.is_pure()) {
+                Some(cell) => (cell.key_sum, cell.count),
+                None => break,
+            };
+            if count > 0 {
+                only_in_self.push(key);
+            } else {
+                only_in_other.push(key);
+            }
+            self.toggle(key, -count);
+        }
+
+        if self.cells.iter().all(IbltCell::is_empty) {
+            Some((only_in_self, only_in_other))
+        } else {
+            None
+        }
+    }
+}
+
+fn check_hash(key: u64) -> u64 {
+    mix(key, NUM_HASHES as u64)
+}
+
+/// The SplitMix64 finalizer, seeded differently for every hash function.
+fn mix(key: u64, seed: u64) -> u64 {
+    let mut z = key.wrapping_add(seed.wrapping_add(1).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
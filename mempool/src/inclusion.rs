@@ -0,0 +1,93 @@
+use std::collections::HashSet;
+
+use nimiq_block::Block;
+use nimiq_hash::{Blake2bHash, Hash};
+use nimiq_primitives::policy;
+
+use crate::mempool::MempoolState;
+
+/// Policy for the inclusion list.
+///
+/// Transactions that pay at least `min_fee_per_byte` and have been waiting in the mempool for
+/// `max_wait_batches` batches are put on the inclusion list. A validator that follows the policy
+/// includes them in its next micro block before any other transaction. Since every node keeps the
+/// same accounting for the blocks it sees, validators that keep omitting listed transactions
+/// become visible.
+#[derive(Debug, Clone)]
+pub struct InclusionPolicy {
+    /// Minimum fee per byte of a transaction to be put on the inclusion list
+    pub min_fee_per_byte: f64,
+    /// Number of batches a transaction has to wait in the mempool to be put on the inclusion list
+    pub max_wait_batches: u32,
+}
+
+impl InclusionPolicy {
+    /// Number of blocks a transaction has to wait to be put on the inclusion list.
+    pub fn max_wait_blocks(&self) -> u32 {
+        self.max_wait_batches.saturating_mul(policy::BATCH_LENGTH)
+    }
+}
+
+/// Inclusion list accounting of a block producer.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InclusionStats {
+    /// Number of micro blocks produced
+    pub blocks: u64,
+    /// Number of listed transactions that were included
+    pub included: u64,
+    /// Number of listed transactions that were omitted. A transaction that is omitted by several
+    /// blocks is counted for each of them.
+    pub omitted: u64,
+}
+
+impl InclusionStats {
+    /// Records a block that included `included` and omitted `omitted` listed transactions.
+    pub(crate) fn record_block(&mut self, included: usize, omitted: usize) {
+        self.blocks += 1;
+        self.included += included as u64;
+        self.omitted += omitted as u64;
+    }
+}
+
+impl MempoolState {
+    /// Returns the hashes of the transactions that are on the inclusion list of the block at the
+    /// given block number.
+    pub(crate) fn inclusion_list(
+        &self,
+        policy: &InclusionPolicy,
+        block_number: u32,
+    ) -> HashSet<Blake2bHash> {
+        self.received_at
+            .iter()
+            .filter(|(_, &received_at)| {
+                block_number.saturating_sub(received_at) >= policy.max_wait_blocks()
+            })
+            .filter(|(hash, _)| {
+                self.get(hash)
+                    .map_or(false, |tx| tx.fee_per_byte() >= policy.min_fee_per_byte)
+            })
+            .map(|(hash, _)| hash.clone())
+            .collect()
+    }
+
+    /// Returns the number of listed transactions that the block included and omitted.
+    pub(crate) fn check_inclusion_list(
+        &self,
+        policy: &InclusionPolicy,
+        block: &Block,
+    ) -> (usize, usize) {
+        let listed = self.inclusion_list(policy, block.block_number());
+
+        let included = block
+            .transactions()
+            .map(|transactions| {
+                transactions
+                    .iter()
+                    .filter(|tx| listed.contains(&tx.hash::<Blake2bHash>()))
+                    .count()
+            })
+            .unwrap_or(0);
+
+        (included, listed.len() - included)
+    }
+}
@@ -7,15 +7,31 @@
 //! processing transactions. The validator will use the mempool to collect
 //! transactions that should be included in a block.
 
+#[macro_use]
+extern crate beserial_derive;
 extern crate log;
 
 /// Mempool config module
 pub mod config;
+/// Transaction conflict detection module
+mod conflicts;
 /// Mempool executor module
 pub mod executor;
 /// Mempool filter module
 pub mod filter;
+/// Invertible Bloom lookup table module
+pub mod iblt;
+/// Inclusion list module
+pub mod inclusion;
 /// Main mempool module
 pub mod mempool;
+/// Window in which our validator produces a block
+pub mod production_window;
+/// Staged re-validation of the transactions of reverted blocks
+mod revalidation;
+/// Sharded transaction topics module
+pub mod sharding;
+/// Mempool reconciliation module
+pub mod sync;
 /// Verify transaction module
 pub mod verify;
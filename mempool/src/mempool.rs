@@ -1,31 +1,51 @@
-use futures::future::{AbortHandle, Abortable};
+use futures::future::{self, AbortHandle, Abortable};
 use futures::lock::Mutex;
 use futures::stream::BoxStream;
 use keyed_priority_queue::KeyedPriorityQueue;
 use parking_lot::{RwLock, RwLockUpgradableReadGuard};
-use std::cmp::Ordering;
+use std::cmp::{Ordering, Reverse};
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
+use std::time::Instant;
 
 use beserial::Serialize;
 use nimiq_account::{Account, BasicAccount};
 use nimiq_block::Block;
-use nimiq_blockchain::{AbstractBlockchain, Blockchain, TransactionVerificationCache};
+use nimiq_blockchain::{
+    AbstractBlockchain, BlockchainLock, BlockchainReader, TransactionVerificationCache,
+};
 use nimiq_hash::{Blake2bHash, Hash};
 use nimiq_keys::Address;
 use nimiq_network_interface::network::{Network, Topic};
 use nimiq_primitives::account::AccountType;
 use nimiq_primitives::coin::Coin;
+use nimiq_primitives::policy;
 use nimiq_transaction::account::staking_contract::{
     IncomingStakingTransactionData, OutgoingStakingTransactionProof,
 };
 use nimiq_transaction::Transaction;
+use nimiq_utils::memory::MemoryGauge;
 
-use crate::config::MempoolConfig;
+use crate::config::{InvalidRelayFee, MempoolConfig};
+use crate::conflicts::RecentlyMined;
 use crate::executor::MempoolExecutor;
 use crate::filter::{MempoolFilter, MempoolRules};
+use crate::inclusion::{InclusionPolicy, InclusionStats};
+use crate::production_window::ProductionWindow;
+use crate::revalidation::{self, RevalidationQueue};
+use crate::sharding::{all_transaction_shards, subscribe_shards, unsubscribe_shards};
+use crate::sync::{mempool_sync, SyncConfig};
 use crate::verify::{verify_tx, VerifyErr};
 
+/// The transactions selected for a block by [`Mempool::snapshot_for_block_until`].
+#[derive(Debug)]
+pub struct BlockSnapshot {
+    /// The selected transactions, ordered like `snapshot_for_block` orders them.
+    pub transactions: Vec<Transaction>,
+    /// Whether the deadline passed before all transactions that fit into the block were selected.
+    pub timed_out: bool,
+}
+
 /// Transaction topic for the Mempool to request transactions from the network
 #[derive(Clone, Debug, Default)]
 pub struct TransactionTopic;
@@ -41,7 +61,10 @@ impl Topic for TransactionTopic {
 /// Struct defining the Mempool
 pub struct Mempool {
     /// Blockchain reference
-    pub blockchain: Arc<RwLock<Blockchain>>,
+    pub blockchain: Arc<BlockchainLock>,
+
+    /// Handle to read the blockchain state without taking the blockchain lock
+    pub(crate) reader: BlockchainReader,
 
     /// The mempool state: the data structure where the transactions are stored
     pub(crate) state: Arc<RwLock<MempoolState>>,
@@ -51,25 +74,57 @@ pub struct Mempool {
 
     /// Mempool executor handle used to stop the executor
     pub(crate) executor_handle: Mutex<Option<AbortHandle>>,
+
+    /// The transaction shards the executor subscribes to, if sharded topics are enabled
+    pub(crate) transaction_shards: Option<Vec<u8>>,
+
+    /// Whether the executor subscribes to the unsharded transaction topic as well if sharded
+    /// topics are enabled
+    pub(crate) legacy_transaction_topic: bool,
+
+    /// The inclusion list policy, if enabled
+    pub(crate) inclusion_policy: Option<InclusionPolicy>,
+
+    /// Inclusion list accounting per block producer
+    pub(crate) inclusion_stats: RwLock<HashMap<Address, InclusionStats>>,
+
+    /// Settings of the mempool reconciliation with peers
+    pub(crate) sync_config: SyncConfig,
+
+    /// Minimum fee per byte of transactions received via gossip
+    pub(crate) min_relay_fee_per_byte: Arc<RwLock<f64>>,
+
+    /// Transactions of reverted blocks that still need to be re-validated
+    pub(crate) revalidation: Arc<parking_lot::Mutex<RevalidationQueue>>,
+
+    /// The window in which our validator produces a block
+    production_window: Arc<ProductionWindow>,
 }
 
 impl Mempool {
     /// Creates a new mempool
-    pub fn new(blockchain: Arc<RwLock<Blockchain>>, config: MempoolConfig) -> Self {
+    pub fn new(blockchain: Arc<BlockchainLock>, config: MempoolConfig) -> Self {
         let state = MempoolState {
             transactions: HashMap::new(),
             transactions_by_fee: KeyedPriorityQueue::new(),
+            transactions_by_lowest_fee: KeyedPriorityQueue::new(),
             transactions_by_age: KeyedPriorityQueue::new(),
             state_by_sender: HashMap::new(),
+            transactions_by_recipient: HashMap::new(),
             outgoing_validators: HashSet::new(),
             outgoing_stakers: HashSet::new(),
             creating_validators: HashSet::new(),
             creating_stakers: HashSet::new(),
+            recently_mined: RecentlyMined::default(),
+            received_at: HashMap::new(),
+            block_number: blockchain.read().block_number(),
+            memory: MemoryGauge::new(),
         };
 
         let state = Arc::new(RwLock::new(state));
 
         Self {
+            reader: blockchain.read().reader(),
             blockchain: Arc::clone(&blockchain),
             state: Arc::clone(&state),
             filter: Arc::new(RwLock::new(MempoolFilter::new(
@@ -77,6 +132,24 @@ impl Mempool {
                 config.filter_limit,
             ))),
             executor_handle: Mutex::new(None),
+            transaction_shards: config.sharded_topics.then(|| {
+                config
+                    .transaction_shards
+                    .unwrap_or_else(all_transaction_shards)
+            }),
+            legacy_transaction_topic: config.legacy_transaction_topic,
+            inclusion_policy: config.inclusion_policy,
+            inclusion_stats: RwLock::new(HashMap::new()),
+            sync_config: SyncConfig {
+                sync_on_connect: config.sync_on_connect,
+                reconciliation_interval: config.reconciliation_interval,
+                rng: config.rng,
+            },
+            min_relay_fee_per_byte: Arc::new(RwLock::new(config.min_relay_fee_per_byte)),
+            revalidation: Arc::new(parking_lot::Mutex::new(RevalidationQueue::new(
+                tokio::runtime::Handle::try_current().ok(),
+            ))),
+            production_window: Arc::new(ProductionWindow::default()),
         }
     }
 
@@ -84,6 +157,11 @@ impl Mempool {
     ///
     /// Once this function is called, the mempool executor is spawned.
     /// The executor will subscribe to the transaction topic from the the network.
+    /// If sharded topics are enabled, it subscribes to the configured transaction shard topics
+    /// instead, and only to the unsharded topic if the legacy topic is enabled.
+    /// The executor also answers the mempool reconciliation requests of peers and, if enabled,
+    /// reconciles the mempool with every newly connected peer and periodically with a few random
+    /// peers.
     pub async fn start_executor<N: Network>(&self, network: Arc<N>) {
         let mut executor_handle = self.executor_handle.lock().await;
 
@@ -92,20 +170,42 @@ impl Mempool {
             return;
         }
 
+        self.revalidation
+            .lock()
+            .set_runtime(tokio::runtime::Handle::current());
+
         // Suscribe to the network TX topic
-        let txn_stream = network.subscribe::<TransactionTopic>().await.unwrap();
+        let txn_stream = if let Some(shards) = &self.transaction_shards {
+            subscribe_shards(&*network, shards, self.legacy_transaction_topic)
+                .await
+                .unwrap()
+        } else {
+            network.subscribe::<TransactionTopic>().await.unwrap()
+        };
 
         let mempool_executor = MempoolExecutor::new(
-            Arc::clone(&self.blockchain),
+            self.reader.clone(),
             Arc::clone(&self.state),
             Arc::clone(&self.filter),
+            Arc::clone(&self.min_relay_fee_per_byte),
             Arc::clone(&network),
             txn_stream,
         );
 
+        let sync = mempool_sync(
+            network,
+            self.reader.clone(),
+            Arc::clone(&self.state),
+            Arc::clone(&self.filter),
+            self.sync_config.clone(),
+        );
+
         // Start the executor and obtain its handle
         let (abort_handle, abort_registration) = AbortHandle::new_pair();
-        tokio::spawn(Abortable::new(mempool_executor, abort_registration));
+        tokio::spawn(Abortable::new(
+            future::join(mempool_executor, sync),
+            abort_registration,
+        ));
 
         // Set the executor handle
         *executor_handle = Some(abort_handle);
@@ -128,10 +228,15 @@ impl Mempool {
             return;
         }
 
+        self.revalidation
+            .lock()
+            .set_runtime(tokio::runtime::Handle::current());
+
         let mempool_executor = MempoolExecutor::<N>::new(
             Arc::clone(&self.blockchain),
             Arc::clone(&self.state),
             Arc::clone(&self.filter),
+            Arc::clone(&self.min_relay_fee_per_byte),
             Arc::clone(&network),
             txn_stream,
         );
@@ -156,7 +261,13 @@ impl Mempool {
         }
 
         // Unsuscribe to the network TX topic before killing the executor
-        network.unsubscribe::<TransactionTopic>().await.unwrap();
+        if let Some(shards) = &self.transaction_shards {
+            unsubscribe_shards(&*network, shards, self.legacy_transaction_topic)
+                .await
+                .unwrap();
+        } else {
+            network.unsubscribe::<TransactionTopic>().await.unwrap();
+        }
 
         // Stop the executor
         handle.take().expect("Expected an executor handle").abort();
@@ -194,16 +305,39 @@ impl Mempool {
     /// 1.B and 2.A can be iterated over the txs in the adopted blocks, that is, it is not
     /// necessary to iterate all transactions in the mempool.
     ///
+    /// The mempool is updated against a view of the blockchain state, without taking the
+    /// blockchain lock. The transactions of reverted blocks are re-validated in batches, the first
+    /// one right away and the others by a background task (see `RevalidationQueue`).
+    ///
     pub fn mempool_update(
         &self,
         adopted_blocks: &[(Blake2bHash, Block)],
         reverted_blocks: &[(Blake2bHash, Block)],
     ) {
-        // Acquire the mempool and blockchain locks
-        let blockchain = self.blockchain.read();
+        // The producers of the adopted blocks are needed for the inclusion list accounting. They
+        // are looked up before acquiring the mempool state, since the blockchain lock must not be
+        // taken while holding it.
+        let producers: Vec<Option<Address>> = match &self.inclusion_policy {
+            Some(_) => {
+                let blockchain = self.blockchain.read();
+                adopted_blocks
+                    .iter()
+                    .map(|(_, block)| {
+                        blockchain
+                            .get_slot_owner_at(block.block_number(), block.view_number(), None)
+                            .map(|(producer, _)| producer.address)
+                    })
+                    .collect()
+            }
+            None => vec![],
+        };
+
+        // Acquire the mempool state and open a view of the blockchain state
         let mut mempool_state = self.state.write();
+        let blockchain = self.reader.view();
 
         let block_height = blockchain.block_number() + 1;
+        mempool_state.block_number = blockchain.block_number();
 
         // First remove the transactions that are no longer valid due to age.
         loop {
@@ -228,6 +362,18 @@ impl Mempool {
             }
         }
 
+        // Update the index of recently mined transactions. Reverted blocks are removed first, since
+        // the adopted blocks may contain the same transactions.
+        for (_, block) in reverted_blocks {
+            mempool_state.recently_mined.revert_block(block);
+        }
+        for (_, block) in adopted_blocks {
+            mempool_state.recently_mined.add_block(block);
+        }
+        mempool_state
+            .recently_mined
+            .prune(blockchain.block_number());
+
         // Now iterate over the transactions in the adopted blocks:
         //  if transaction was known:
         //    remove it from the mempool
@@ -237,7 +383,28 @@ impl Mempool {
         //    else
         //      we don't care, since it won't affect our senders balance
         //
-        for (_, block) in adopted_blocks {
+        for (i, (_, block)) in adopted_blocks.iter().enumerate() {
+            // Account for the inclusion list of the block, before its transactions are removed.
+            if let (Some(policy), Block::Micro(_)) = (&self.inclusion_policy, block) {
+                let (included, omitted) = mempool_state.check_inclusion_list(policy, block);
+                if let Some(producer) = &producers[i] {
+                    if omitted > 0 {
+                        log::debug!(
+                            "Block #{}.{} by {} omitted {} transactions from the inclusion list",
+                            block.block_number(),
+                            block.view_number(),
+                            producer,
+                            omitted
+                        );
+                    }
+                    self.inclusion_stats
+                        .write()
+                        .entry(producer.clone())
+                        .or_default()
+                        .record_block(included, omitted);
+                }
+            }
+
             if let Some(transactions) = block.transactions() {
                 for tx in transactions {
                     let tx_hash = tx.hash();
@@ -315,60 +482,41 @@ impl Mempool {
         // what we need to know is if we need to add back the transaction into the mempool
         // This is similar to an operation where we try to add a transaction,
         // the only difference is that we don't need to re-check signature
+        let mut revalidation = self.revalidation.lock();
         for (_, block) in reverted_blocks {
-            let block_height = blockchain.block_number() + 1;
-
-            if let Some(transactions) = block.transactions() {
-                for tx in transactions {
-                    let tx_hash = tx.hash();
-
-                    // Check if we already know this transaction. If yes, skip ahead.
-                    if mempool_state.contains(&tx_hash) {
-                        continue;
-                    }
-
-                    // Check if transaction is still valid.
-                    if !tx.is_valid_at(block_height)
-                        || blockchain.contains_tx_in_validity_window(&tx_hash, None)
-                    {
-                        // Tx has expired or is already included in the new chain, so skip it
-                        // (TX is lost...)
-                        continue;
-                    }
-
-                    // Get the sender's account balance.
-                    let sender_balance = match blockchain.get_account(&tx.sender) {
-                        None => {
-                            // No sender in the blockchain for this tx, no need to process.
-                            continue;
-                        }
-                        Some(sender_account) => sender_account.balance(),
-                    };
-
-                    // Get the sender's transaction total.
-                    let sender_total = match mempool_state.state_by_sender.get(&tx.sender) {
-                        None => Coin::ZERO,
-                        Some(sender_state) => sender_state.total,
-                    };
-
-                    // Calculate the new balance assuming we add this transaction to the mempool
-                    let in_fly_balance = tx.total_value() + sender_total;
-
-                    if in_fly_balance <= sender_balance {
-                        mempool_state.put(tx);
-                    } else {
-                        log::debug!(
-                            "Tx {} from reverted block #{}.{} was dropped because of insufficient funds",
-                            tx_hash,
-                            block.block_number(),
-                            block.view_number()
-                        );
-                    }
-                }
+            revalidation.push_block(block);
+        }
+        // While our validator produces a block, all of them are re-validated in the background,
+        // so the incoming transactions don't wait for the mempool lock.
+        let remaining = if self.production_window.is_open() {
+            revalidation.has_pending()
+        } else {
+            revalidation.process_batch(&blockchain, &mut mempool_state)
+        };
+        if remaining {
+            if let Some(runtime) = revalidation.start() {
+                log::debug!(
+                    "Re-validating the remaining transactions of reverted blocks in the background"
+                );
+                runtime.spawn(revalidation::process_queue(
+                    self.reader.clone(),
+                    Arc::clone(&self.state),
+                    Arc::clone(&self.revalidation),
+                    Arc::clone(&self.production_window),
+                ));
+            } else if !revalidation.is_running() {
+                // Without a runtime, the remaining transactions are re-validated right away.
+                while revalidation.process_batch(&blockchain, &mut mempool_state) {}
             }
         }
     }
 
+    /// The window in which our validator produces a block. The re-validation of the transactions
+    /// of reverted blocks is deferred while it is open.
+    pub fn production_window(&self) -> &ProductionWindow {
+        &self.production_window
+    }
+
     /// Returns a vector with accepted transactions from the mempool.
     ///
     /// Returns the highest fee per byte up to max_bytes transactions and removes them from the mempool
@@ -422,19 +570,114 @@ impl Mempool {
         tx_vec
     }
 
+    /// Returns the highest fee per byte transactions up to max_bytes, without removing them from the
+    /// mempool. Of the incoming staking transactions that affect the same validator or staker, only
+    /// the one with the highest priority is returned.
+    ///
+    /// The transactions stay in the mempool until a block containing them is adopted (see
+    /// `mempool_update`), so they are not lost if producing or pushing the block fails.
+    pub fn snapshot_for_block(&self, max_bytes: usize) -> Vec<Transaction> {
+        self.snapshot(max_bytes, None).transactions
+    }
+
+    /// Like `snapshot_for_block`, but stops selecting transactions once the deadline has passed,
+    /// including while waiting for the mempool lock. The transactions selected until then are
+    /// returned, they are still the highest paying ones.
+    pub fn snapshot_for_block_until(&self, max_bytes: usize, deadline: Instant) -> BlockSnapshot {
+        self.snapshot(max_bytes, Some(deadline))
+    }
+
+    fn snapshot(&self, max_bytes: usize, deadline: Option<Instant>) -> BlockSnapshot {
+        let mut snapshot = BlockSnapshot {
+            transactions: vec![],
+            timed_out: false,
+        };
+        let timed_out = || deadline.map_or(false, |deadline| Instant::now() >= deadline);
+
+        let state = match deadline {
+            Some(deadline) => match self.state.try_read_until(deadline) {
+                Some(state) => state,
+                None => {
+                    log::debug!("Timed out waiting for the mempool lock");
+                    snapshot.timed_out = true;
+                    return snapshot;
+                }
+            },
+            None => self.state.read(),
+        };
+
+        if state.transactions.is_empty() {
+            log::debug!("Requesting txns and there are no txns in the mempool ");
+            return snapshot;
+        }
+
+        // Order the transactions like popping them from the fee queue would. If the inclusion
+        // list is enabled, the listed transactions come first.
+        let listed = self
+            .inclusion_policy
+            .as_ref()
+            .map(|policy| state.inclusion_list(policy, state.block_number + 1))
+            .unwrap_or_default();
+        let deferred = state.staking_conflicts(|tx_hash| listed.contains(tx_hash));
+        let mut tx_hashes: Vec<(&Blake2bHash, &FeeWrapper)> = state
+            .transactions_by_fee
+            .iter()
+            .filter(|(tx_hash, _)| !deferred.contains(*tx_hash))
+            .collect();
+        tx_hashes.sort_unstable_by(|(hash_a, fee_a), (hash_b, fee_b)| {
+            (listed.contains(*hash_b), fee_b).cmp(&(listed.contains(*hash_a), fee_a))
+        });
+
+        let mut size = 0_usize;
+
+        for (tx_hash, _) in tx_hashes {
+            if timed_out() {
+                snapshot.timed_out = true;
+                break;
+            }
+
+            let tx = state.get(tx_hash).unwrap();
+
+            // Calculate size. If we can't fit the transaction in the block, then we stop here.
+            size += tx.serialized_size();
+
+            if size > max_bytes {
+                break;
+            }
+
+            snapshot.transactions.push(tx.clone());
+        }
+
+        log::debug!(
+            "Returning {} transactions from mempool snapshot ({} in total{})",
+            snapshot.transactions.len(),
+            state.transactions.len(),
+            if snapshot.timed_out {
+                ", timed out"
+            } else {
+                ""
+            }
+        );
+
+        snapshot
+    }
+
     /// Adds a transaction to the Mempool.
     pub async fn add_transaction(&self, transaction: Transaction) -> Result<(), VerifyErr> {
-        let blockchain = Arc::clone(&self.blockchain);
+        let blockchain = self.reader.clone();
         let mempool_state = Arc::clone(&self.state);
         let filter = Arc::clone(&self.filter);
-        let network_id = Arc::new(blockchain.read().network_id);
+        let network_id = Arc::new(blockchain.network_id());
         let verify_tx_ret =
             verify_tx(&transaction, blockchain, network_id, &mempool_state, filter).await;
 
         match verify_tx_ret {
             Ok(mempool_state_lock) => {
-                RwLockUpgradableReadGuard::upgrade(mempool_state_lock).put(&transaction);
-                Ok(())
+                if RwLockUpgradableReadGuard::upgrade(mempool_state_lock).put(&transaction) {
+                    Ok(())
+                } else {
+                    Err(VerifyErr::Full)
+                }
             }
             Err(e) => Err(e),
         }
@@ -445,11 +688,43 @@ impl Mempool {
         self.filter.read().blacklisted(hash)
     }
 
+    /// Returns the hashes of the transactions on the inclusion list of the next block. This is
+    /// empty if the inclusion list is disabled.
+    pub fn get_inclusion_list(&self) -> Vec<Blake2bHash> {
+        let state = self.state.read();
+        self.inclusion_policy
+            .as_ref()
+            .map(|policy| {
+                state
+                    .inclusion_list(policy, state.block_number + 1)
+                    .into_iter()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Returns the inclusion list accounting of all block producers seen so far.
+    pub fn get_inclusion_stats(&self) -> HashMap<Address, InclusionStats> {
+        self.inclusion_stats.read().clone()
+    }
+
     /// Returns the rules for the mempool.
     pub fn get_rules(&self) -> MempoolRules {
         self.filter.read().rules.clone()
     }
 
+    /// Returns the minimum fee per byte of transactions received via gossip.
+    pub fn get_min_relay_fee_per_byte(&self) -> f64 {
+        *self.min_relay_fee_per_byte.read()
+    }
+
+    /// Sets the minimum fee per byte of transactions received via gossip. This takes effect
+    /// immediately, also for a running executor. Negative and NaN fees are rejected.
+    pub fn set_min_relay_fee_per_byte(&self, fee_per_byte: f64) -> Result<(), InvalidRelayFee> {
+        *self.min_relay_fee_per_byte.write() = InvalidRelayFee::check(fee_per_byte)?;
+        Ok(())
+    }
+
     /// Checks if a transactions is in the mempool, by its hash.
     pub fn contains_transaction_by_hash(&self, hash: &Blake2bHash) -> bool {
         self.state.read().contains(hash)
@@ -460,6 +735,16 @@ impl Mempool {
         self.state.read().get(hash).cloned()
     }
 
+    /// Gets the pending transactions to the given recipient, e.g. all transactions to the staking
+    /// contract or all resolutions of an HTLC.
+    pub fn get_pending_by_recipient(&self, address: &Address) -> Vec<Transaction> {
+        self.state
+            .read()
+            .get_by_recipient(address)
+            .cloned()
+            .collect()
+    }
+
     /// Gets all transaction hashes in the mempool.
     pub fn get_transaction_hashes(&self) -> Vec<Blake2bHash> {
         self.state.read().transactions.keys().cloned().collect()
@@ -474,6 +759,12 @@ impl Mempool {
     pub fn get_transactions(&self) -> Vec<Transaction> {
         self.state.read().transactions.values().cloned().collect()
     }
+
+    /// Returns the gauge counting the serialized size of the transactions in the mempool. If its
+    /// cap is reached, no more transactions are accepted.
+    pub fn memory(&self) -> MemoryGauge {
+        self.state.read().memory.clone()
+    }
 }
 
 impl TransactionVerificationCache for Mempool {
@@ -493,12 +784,19 @@ pub(crate) struct MempoolState {
     // Transactions ordered by fee (higher fee transactions pop first)
     pub(crate) transactions_by_fee: KeyedPriorityQueue<Blake2bHash, FeeWrapper>,
 
+    // Transactions ordered by fee (lower fee transactions pop first), used to evict the cheapest
+    // transactions when the mempool is full
+    pub(crate) transactions_by_lowest_fee: KeyedPriorityQueue<Blake2bHash, Reverse<FeeWrapper>>,
+
     // Transactions ordered by age (older transactions pop first)
     pub(crate) transactions_by_age: KeyedPriorityQueue<Blake2bHash, u32>,
 
     // The in-fly balance per sender
     pub(crate) state_by_sender: HashMap<Address, SenderPendingState>,
 
+    // The hashes of the transactions per recipient
+    pub(crate) transactions_by_recipient: HashMap<Address, HashSet<Blake2bHash>>,
+
     // The sets of all senders of staking transactions. For simplicity, each validator/staker can
     // only have one outgoing staking transaction in the mempool. This makes sure that the outgoing
     // staking transaction can actually pay its fee.
@@ -510,6 +808,19 @@ pub(crate) struct MempoolState {
     // sure that the creation staking transactions do not interfere with one another.
     pub(crate) creating_validators: HashSet<Address>,
     pub(crate) creating_stakers: HashSet<Address>,
+
+    // The recently mined transactions, used to report conflicts with transactions that already
+    // left the mempool.
+    pub(crate) recently_mined: RecentlyMined,
+
+    // The block number at which each transaction was added to the mempool.
+    pub(crate) received_at: HashMap<Blake2bHash, u32>,
+
+    // The block number of the current head.
+    pub(crate) block_number: u32,
+
+    // The serialized size of the transactions.
+    pub(crate) memory: MemoryGauge,
 }
 
 impl MempoolState {
@@ -521,21 +832,165 @@ impl MempoolState {
         self.transactions.get(hash)
     }
 
+    /// Returns the transactions to the given recipient.
+    pub fn get_by_recipient<'a>(
+        &'a self,
+        address: &Address,
+    ) -> impl Iterator<Item = &'a Transaction> + 'a {
+        self.transactions_by_recipient
+            .get(address)
+            .into_iter()
+            .flatten()
+            .filter_map(|tx_hash| self.transactions.get(tx_hash))
+    }
+
+    /// Returns the incoming staking transactions that have to wait for a later block, because a
+    /// transaction with a higher priority affects the same validator or staker.
+    ///
+    /// The transactions of a block are applied in their canonical order (see the `Ord`
+    /// implementation of `Transaction`), not in the order they were sent. So if both were included,
+    /// an update could be applied before the validator it updates is created. `Stake` transactions
+    /// only add to the stake, so they never have to wait. `is_listed` tells whether a transaction
+    /// is on the inclusion list, those are preferred over the ones with a higher fee.
+    ///
+    /// Transactions to the staking contract whose data can't be parsed are returned as well, so
+    /// that they are never included in a block.
+    pub(crate) fn staking_conflicts<F: Fn(&Blake2bHash) -> bool>(
+        &self,
+        is_listed: F,
+    ) -> HashSet<Blake2bHash> {
+        let mut best: HashMap<Address, ((bool, FeeWrapper), Blake2bHash)> = HashMap::new();
+        let mut deferred = HashSet::new();
+
+        for tx in self.get_by_recipient(&policy::STAKING_CONTRACT_ADDRESS) {
+            if tx.recipient_type != AccountType::Staking {
+                continue;
+            }
+
+            let tx_hash = tx.hash();
+            let affected = match IncomingStakingTransactionData::parse(tx) {
+                Ok(IncomingStakingTransactionData::CreateValidator { proof, .. })
+                | Ok(IncomingStakingTransactionData::UpdateValidator { proof, .. })
+                | Ok(IncomingStakingTransactionData::CreateStaker { proof, .. })
+                | Ok(IncomingStakingTransactionData::UpdateStaker { proof, .. }) => {
+                    proof.compute_signer()
+                }
+                Ok(IncomingStakingTransactionData::InactivateValidator {
+                    validator_address,
+                    ..
+                })
+                | Ok(IncomingStakingTransactionData::ReactivateValidator {
+                    validator_address,
+                    ..
+                })
+                | Ok(IncomingStakingTransactionData::UnparkValidator {
+                    validator_address, ..
+                }) => validator_address,
+                Ok(IncomingStakingTransactionData::Stake { .. }) => continue,
+                Err(error) => {
+                    log::warn!(
+                        "Skipping staking transaction {} with invalid data: {}",
+                        tx_hash,
+                        error
+                    );
+                    deferred.insert(tx_hash);
+                    continue;
+                }
+            };
+
+            let priority = (is_listed(&tx_hash), FeeWrapper(tx.fee_per_byte()));
+            match best.get_mut(&affected) {
+                Some((best_priority, _)) if *best_priority >= priority => {
+                    deferred.insert(tx_hash);
+                }
+                Some(current) => {
+                    deferred.insert(current.1.clone());
+                    *current = (priority, tx_hash);
+                }
+                None => {
+                    best.insert(affected, (priority, tx_hash));
+                }
+            }
+        }
+
+        deferred
+    }
+
+    /// Returns whether the transaction pays more per byte than the cheapest transaction in the
+    /// mempool, so that it may replace it when the mempool is full.
+    pub(crate) fn outbids_cheapest(&self, tx: &Transaction) -> bool {
+        self.transactions_by_lowest_fee
+            .peek()
+            .map_or(false, |(_, Reverse(lowest))| {
+                *lowest < FeeWrapper(tx.fee_per_byte())
+            })
+    }
+
+    /// Evicts the cheapest transactions that pay less per byte than the given transaction until it
+    /// fits under the memory cap. Nothing is evicted if it doesn't fit even then.
+    fn make_room(&mut self, tx: &Transaction) -> bool {
+        let size = tx.serialized_size();
+        let fee = FeeWrapper(tx.fee_per_byte());
+
+        let mut freed = 0;
+        let mut evicted = vec![];
+        while self.memory.would_exceed_cap(size.saturating_sub(freed)) {
+            match self.transactions_by_lowest_fee.peek() {
+                Some((_, Reverse(lowest))) if *lowest < fee => {}
+                _ => break,
+            }
+            let (tx_hash, priority) = self.transactions_by_lowest_fee.pop().unwrap();
+            freed += self.transactions[&tx_hash].serialized_size();
+            evicted.push((tx_hash, priority));
+        }
+
+        if self.memory.would_exceed_cap(size.saturating_sub(freed)) {
+            for (tx_hash, priority) in evicted {
+                self.transactions_by_lowest_fee.push(tx_hash, priority);
+            }
+            return false;
+        }
+
+        if !evicted.is_empty() {
+            log::debug!(
+                "Evicting {} transactions to make room for {}",
+                evicted.len(),
+                tx.hash::<Blake2bHash>()
+            );
+        }
+        for (tx_hash, _) in evicted {
+            self.remove(&tx_hash);
+        }
+        true
+    }
+
+    /// Adds the transaction, evicting cheaper ones if the mempool is full. Returns whether the
+    /// transaction was added.
     pub(crate) fn put(&mut self, tx: &Transaction) -> bool {
         let tx_hash = tx.hash();
 
-        if self.transactions.contains_key(&tx_hash) {
+        if self.transactions.contains_key(&tx_hash) || !self.make_room(tx) {
             return false;
         }
 
         self.transactions.insert(tx_hash.clone(), tx.clone());
+        self.memory.add(tx.serialized_size());
 
         self.transactions_by_fee
             .push(tx_hash.clone(), FeeWrapper(tx.fee_per_byte()));
+        self.transactions_by_lowest_fee
+            .push(tx_hash.clone(), Reverse(FeeWrapper(tx.fee_per_byte())));
 
         self.transactions_by_age
             .push(tx_hash.clone(), tx.validity_start_height);
 
+        self.received_at.insert(tx_hash.clone(), self.block_number);
+
+        self.transactions_by_recipient
+            .entry(tx.recipient.clone())
+            .or_default()
+            .insert(tx_hash.clone());
+
         match self.state_by_sender.get_mut(&tx.sender) {
             None => {
                 let mut txns = HashSet::new();
@@ -595,9 +1050,12 @@ impl MempoolState {
 
     pub(crate) fn remove(&mut self, tx_hash: &Blake2bHash) -> Option<Transaction> {
         let tx = self.transactions.remove(tx_hash)?;
+        self.memory.sub(tx.serialized_size());
 
         self.transactions_by_age.remove(tx_hash);
         self.transactions_by_fee.remove(tx_hash);
+        self.transactions_by_lowest_fee.remove(tx_hash);
+        self.received_at.remove(tx_hash);
 
         let sender_state = self.state_by_sender.get_mut(&tx.sender).unwrap();
 
@@ -608,6 +1066,13 @@ impl MempoolState {
             self.state_by_sender.remove(&tx.sender);
         }
 
+        if let Some(txns) = self.transactions_by_recipient.get_mut(&tx.recipient) {
+            txns.remove(tx_hash);
+            if txns.is_empty() {
+                self.transactions_by_recipient.remove(&tx.recipient);
+            }
+        }
+
         // If it is an outgoing staking transaction then we have additional work.
         if tx.sender_type == AccountType::Staking {
             // Parse transaction data.
@@ -676,3 +1141,245 @@ impl Ord for FeeWrapper {
         self.0.total_cmp(&other.0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use nimiq_block::{MicroBlock, MicroBody, MicroHeader};
+    use nimiq_keys::{KeyPair, SecureGenerate};
+    use nimiq_primitives::networks::NetworkId;
+    use nimiq_transaction::account::htlc_contract::ProofType;
+    use nimiq_transaction_builder::TransactionBuilder;
+
+    use super::*;
+
+    fn empty_state() -> MempoolState {
+        MempoolState {
+            transactions: HashMap::new(),
+            transactions_by_fee: KeyedPriorityQueue::new(),
+            transactions_by_lowest_fee: KeyedPriorityQueue::new(),
+            transactions_by_age: KeyedPriorityQueue::new(),
+            state_by_sender: HashMap::new(),
+            transactions_by_recipient: HashMap::new(),
+            outgoing_validators: HashSet::new(),
+            outgoing_stakers: HashSet::new(),
+            creating_validators: HashSet::new(),
+            creating_stakers: HashSet::new(),
+            recently_mined: RecentlyMined::default(),
+            received_at: HashMap::new(),
+            block_number: 1,
+            memory: MemoryGauge::new(),
+        }
+    }
+
+    #[test]
+    fn it_skips_malformed_staking_transactions() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let key_pair = KeyPair::generate(&mut rng);
+
+        // A basic transaction to the staking contract with data that can't be parsed.
+        let tx = Transaction::new_extended(
+            Address::from(&key_pair),
+            AccountType::Basic,
+            policy::STAKING_CONTRACT_ADDRESS,
+            AccountType::Basic,
+            Coin::from_u64_unchecked(10),
+            Coin::from_u64_unchecked(10),
+            vec![0xff; 64],
+            1,
+            NetworkId::UnitAlbatross,
+        );
+
+        let mut state = empty_state();
+        assert!(state.put(&tx));
+        assert!(state.staking_conflicts(|_| false).is_empty());
+    }
+
+    #[test]
+    fn it_defers_conflicting_staking_transactions() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let key_pair = KeyPair::generate(&mut rng);
+        let validator = KeyPair::generate(&mut rng);
+
+        let update = |fee| {
+            TransactionBuilder::new_update_validator(
+                &key_pair,
+                &validator,
+                None,
+                None,
+                Some(Address::from(&key_pair)),
+                None,
+                Coin::from_u64_unchecked(fee),
+                1,
+                NetworkId::UnitAlbatross,
+            )
+        };
+        let cheap = update(100);
+        let expensive = update(200);
+        let stake = TransactionBuilder::new_stake(
+            &key_pair,
+            Address::from(&validator),
+            Coin::from_u64_unchecked(1000),
+            Coin::from_u64_unchecked(10),
+            1,
+            NetworkId::UnitAlbatross,
+        );
+
+        let mut state = empty_state();
+        for tx in [&cheap, &expensive, &stake] {
+            assert!(state.put(tx));
+        }
+
+        // The update with the higher fee goes first, the stake doesn't conflict with anything.
+        let deferred = state.staking_conflicts(|_| false);
+        assert_eq!(deferred, HashSet::from([cheap.hash()]));
+
+        // Transactions on the inclusion list are preferred over the ones with a higher fee.
+        let cheap_hash = cheap.hash();
+        let deferred = state.staking_conflicts(|tx_hash| *tx_hash == cheap_hash);
+        assert_eq!(deferred, HashSet::from([expensive.hash()]));
+    }
+
+    fn basic_transaction(key_pair: &KeyPair, value: u64, fee: u64) -> Transaction {
+        Transaction::new_basic(
+            Address::from(key_pair),
+            Address::from([2u8; Address::SIZE]),
+            Coin::from_u64_unchecked(value),
+            Coin::from_u64_unchecked(fee),
+            1,
+            NetworkId::UnitAlbatross,
+        )
+    }
+
+    fn micro_block(block_number: u32, transactions: Vec<Transaction>) -> Block {
+        Block::Micro(MicroBlock {
+            header: MicroHeader {
+                version: policy::VERSION,
+                block_number,
+                view_number: 0,
+                timestamp: 0,
+                parent_hash: Blake2bHash::default(),
+                seed: Default::default(),
+                extra_data: vec![],
+                state_root: Blake2bHash::default(),
+                body_root: Blake2bHash::default(),
+                history_root: Blake2bHash::default(),
+            },
+            justification: None,
+            body: Some(MicroBody {
+                fork_proofs: vec![],
+                transactions,
+            }),
+        })
+    }
+
+    #[test]
+    fn it_lists_transactions_that_waited_long_enough() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let key_pair = KeyPair::generate(&mut rng);
+        let policy = InclusionPolicy {
+            min_fee_per_byte: 1.0,
+            max_wait_batches: 1,
+        };
+
+        let mut state = empty_state();
+        let paying = basic_transaction(&key_pair, 10, 1000);
+        let cheap = basic_transaction(&key_pair, 20, 1);
+        assert!(state.put(&paying));
+        assert!(state.put(&cheap));
+
+        // Nothing is listed before the transactions waited for a batch.
+        let listed_at = state.block_number + policy.max_wait_blocks();
+        assert!(state.inclusion_list(&policy, listed_at - 1).is_empty());
+
+        // Only the transaction paying enough is listed afterwards.
+        assert_eq!(
+            state.inclusion_list(&policy, listed_at),
+            HashSet::from([paying.hash()])
+        );
+        assert_eq!(
+            state.check_inclusion_list(&policy, &micro_block(listed_at, vec![paying.clone()])),
+            (1, 0)
+        );
+        assert_eq!(
+            state.check_inclusion_list(&policy, &micro_block(listed_at, vec![cheap])),
+            (0, 1)
+        );
+    }
+
+    #[test]
+    fn it_accounts_the_inclusion_list_per_block() {
+        let mut stats = InclusionStats::default();
+        stats.record_block(2, 0);
+        stats.record_block(0, 3);
+
+        assert_eq!(
+            stats,
+            InclusionStats {
+                blocks: 2,
+                included: 2,
+                omitted: 3,
+            }
+        );
+    }
+
+    fn htlc_withdrawal(
+        htlc: &Address,
+        proof_type: ProofType,
+        value: u64,
+        validity_start_height: u32,
+    ) -> Transaction {
+        let mut tx = Transaction::new_extended(
+            htlc.clone(),
+            AccountType::HTLC,
+            Address::default(),
+            AccountType::Basic,
+            Coin::from_u64_unchecked(value),
+            Coin::ZERO,
+            vec![],
+            validity_start_height,
+            NetworkId::UnitAlbatross,
+        );
+        tx.proof = vec![proof_type as u8];
+        tx
+    }
+
+    #[test]
+    fn it_accepts_partial_htlc_withdrawals() {
+        let htlc = Address::from([1u8; Address::SIZE]);
+        let balance = Coin::from_u64_unchecked(100);
+
+        let mut state = empty_state();
+        let early = htlc_withdrawal(&htlc, ProofType::EarlyResolve, 40, 1);
+        assert!(state.put(&early));
+
+        // The remainder can be withdrawn in addition to the early resolve.
+        let remainder = htlc_withdrawal(&htlc, ProofType::RegularTransfer, 60, 1);
+        assert_eq!(state.find_conflict(&remainder, Some(balance)), None);
+
+        // More than the remainder can't.
+        let too_much = htlc_withdrawal(&htlc, ProofType::RegularTransfer, 61, 1);
+        assert_eq!(
+            state.find_conflict(&too_much, Some(balance)),
+            Some(early.hash())
+        );
+    }
+
+    #[test]
+    fn it_reports_htlc_withdrawals_that_resolve_a_drained_contract_again() {
+        let htlc = Address::from([1u8; Address::SIZE]);
+        let balance = Coin::from_u64_unchecked(100);
+
+        let mut state = empty_state();
+        let timeout = htlc_withdrawal(&htlc, ProofType::TimeoutResolve, 100, 1);
+        assert!(state.put(&timeout));
+
+        // Resolving the drained contract the same way again conflicts even without any value.
+        let again = htlc_withdrawal(&htlc, ProofType::TimeoutResolve, 0, 2);
+        assert_eq!(
+            state.find_conflict(&again, Some(balance)),
+            Some(timeout.hash())
+        );
+    }
+}
@@ -0,0 +1,70 @@
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+/// The time in which our validator produces a block.
+///
+/// While the window is open, the consensus answers requests for batch sets and history chunks with
+/// a busy response, and the mempool defers the re-validation of the transactions of reverted
+/// blocks, which holds the mempool lock that incoming transactions need. This leaves the disk and
+/// CPU to block production and to the ingestion of new transactions. The window closes by itself
+/// once its duration elapsed, even if the validator never closes it.
+#[derive(Debug, Default)]
+pub struct ProductionWindow {
+    closes_at: Mutex<Option<Instant>>,
+}
+
+impl ProductionWindow {
+    /// Opens the window for the given duration. A zero duration closes it instead.
+    pub fn open(&self, duration: Duration) {
+        *self.closes_at.lock() = if duration.is_zero() {
+            None
+        } else {
+            Some(Instant::now() + duration)
+        };
+    }
+
+    /// Closes the window before its duration elapsed.
+    pub fn close(&self) {
+        *self.closes_at.lock() = None;
+    }
+
+    /// Returns whether the window is open.
+    pub fn is_open(&self) -> bool {
+        self.remaining().is_some()
+    }
+
+    /// Returns the time until the window closes, or `None` if it is closed.
+    pub fn remaining(&self) -> Option<Duration> {
+        self.closes_at
+            .lock()
+            .and_then(|closes_at| closes_at.checked_duration_since(Instant::now()))
+            .filter(|remaining| !remaining.is_zero())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::ProductionWindow;
+
+    #[test]
+    fn it_closes_after_its_duration() {
+        let window = ProductionWindow::default();
+        assert!(!window.is_open());
+
+        window.open(Duration::from_secs(60));
+        assert!(window.is_open());
+        assert!(window.remaining().unwrap() <= Duration::from_secs(60));
+        window.close();
+        assert!(!window.is_open());
+
+        window.open(Duration::from_millis(10));
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(!window.is_open());
+
+        window.open(Duration::ZERO);
+        assert!(!window.is_open());
+    }
+}
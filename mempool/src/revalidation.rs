@@ -0,0 +1,195 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use parking_lot::{Mutex, RwLock};
+use tokio::runtime::Handle;
+
+use nimiq_block::Block;
+use nimiq_blockchain::{BlockchainReader, StateView};
+use nimiq_hash::{Blake2bHash, Hash};
+use nimiq_primitives::coin::Coin;
+use nimiq_transaction::Transaction;
+
+use crate::mempool::MempoolState;
+use crate::production_window::ProductionWindow;
+
+/// The transaction of a reverted block that is waiting to be added back to the mempool.
+struct RevertedTransaction {
+    tx: Transaction,
+    block_number: u32,
+    view_number: u32,
+}
+
+/// Queue of the transactions of reverted blocks that still need to be re-validated.
+///
+/// After a deep rebranch, there can be many of them. Re-validating all of them at once would hold
+/// the mempool lock for too long, so they are processed in batches of [`Self::BATCH_SIZE`]. The
+/// first batch is processed right away, the others by a background task. Without a runtime to
+/// spawn the task on, all of them are processed right away.
+pub(crate) struct RevalidationQueue {
+    pending: VecDeque<RevertedTransaction>,
+    // Whether a background task is processing the queue.
+    running: bool,
+    // The runtime the background task is spawned on. The queue is processed from synchronous code,
+    // which might not run within the runtime.
+    runtime: Option<Handle>,
+}
+
+impl RevalidationQueue {
+    /// Number of transactions that are re-validated while holding the mempool lock.
+    pub const BATCH_SIZE: usize = 500;
+
+    pub fn new(runtime: Option<Handle>) -> Self {
+        RevalidationQueue {
+            pending: VecDeque::new(),
+            running: false,
+            runtime,
+        }
+    }
+
+    /// Sets the runtime the background task is spawned on, if none is known yet.
+    pub fn set_runtime(&mut self, runtime: Handle) {
+        self.runtime.get_or_insert(runtime);
+    }
+
+    /// Queues the transactions of a reverted block.
+    pub fn push_block(&mut self, block: &Block) {
+        if let Some(transactions) = block.transactions() {
+            for tx in transactions {
+                self.pending.push_back(RevertedTransaction {
+                    tx: tx.clone(),
+                    block_number: block.block_number(),
+                    view_number: block.view_number(),
+                });
+            }
+        }
+    }
+
+    /// Re-validates the next batch of transactions against the given view of the blockchain and
+    /// adds the valid ones back to the mempool. Returns whether there are transactions left.
+    pub fn process_batch(&mut self, view: &StateView, mempool_state: &mut MempoolState) -> bool {
+        let block_height = view.block_number() + 1;
+
+        for _ in 0..Self::BATCH_SIZE {
+            let reverted = match self.pending.pop_front() {
+                Some(reverted) => reverted,
+                None => break,
+            };
+            let tx = &reverted.tx;
+            let tx_hash: Blake2bHash = tx.hash();
+
+            // Check if we already know this transaction. If yes, skip ahead.
+            if mempool_state.contains(&tx_hash) {
+                continue;
+            }
+
+            // Check if transaction is still valid.
+            if !tx.is_valid_at(block_height) || view.contains_tx_in_validity_window(&tx_hash) {
+                // Tx has expired or is already included in the new chain, so skip it
+                // (TX is lost...)
+                continue;
+            }
+
+            // Get the sender's account balance.
+            let sender_balance = match view.get_account(&tx.sender) {
+                None => {
+                    // No sender in the blockchain for this tx, no need to process.
+                    continue;
+                }
+                Some(sender_account) => sender_account.balance(),
+            };
+
+            // Get the sender's transaction total.
+            let sender_total = match mempool_state.state_by_sender.get(&tx.sender) {
+                None => Coin::ZERO,
+                Some(sender_state) => sender_state.total,
+            };
+
+            // Calculate the new balance assuming we add this transaction to the mempool
+            let in_fly_balance = tx.total_value() + sender_total;
+
+            if in_fly_balance <= sender_balance {
+                mempool_state.put(tx);
+            } else {
+                log::debug!(
+                    "Tx {} from reverted block #{}.{} was dropped because of insufficient funds",
+                    tx_hash,
+                    reverted.block_number,
+                    reverted.view_number
+                );
+            }
+        }
+
+        !self.pending.is_empty()
+    }
+
+    /// Marks the queue as being processed by a background task. Returns the runtime to spawn the
+    /// task on, or `None` if there is a task already or no runtime is known.
+    pub fn start(&mut self) -> Option<Handle> {
+        if self.running {
+            return None;
+        }
+        let runtime = self.runtime.clone()?;
+        self.running = true;
+        Some(runtime)
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+}
+
+/// Marks the queue as no longer being processed when the background task ends, even if it panics
+/// or is cancelled, so the next update starts a new one.
+struct RunningGuard {
+    queue: Arc<Mutex<RevalidationQueue>>,
+    // Set once the task reset the flag itself while holding the queue lock.
+    finished: bool,
+}
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.queue.lock().running = false;
+        }
+    }
+}
+
+/// Re-validates the remaining transactions of the queue in the background, one batch at a time.
+/// Each batch reads from a new view of the blockchain, so the mempool lock is released in between.
+/// No batch is processed while the production window is open.
+pub(crate) async fn process_queue(
+    reader: BlockchainReader,
+    state: Arc<RwLock<MempoolState>>,
+    queue: Arc<Mutex<RevalidationQueue>>,
+    production_window: Arc<ProductionWindow>,
+) {
+    let mut guard = RunningGuard {
+        queue: Arc::clone(&queue),
+        finished: false,
+    };
+    loop {
+        tokio::task::yield_now().await;
+        while let Some(remaining) = production_window.remaining() {
+            tokio::time::sleep(remaining).await;
+        }
+
+        // The view is opened after acquiring the mempool state, so that the mempool can't process a
+        // newer block while the transactions are validated against an older one.
+        let mut mempool_state = state.write();
+        let view = reader.view();
+        let mut queue = queue.lock();
+        if !queue.process_batch(&view, &mut mempool_state) {
+            // Reset while holding the lock, so an update queueing more transactions in the
+            // meantime starts a new task.
+            queue.running = false;
+            guard.finished = true;
+            log::debug!("Finished re-validating the transactions of reverted blocks");
+            return;
+        }
+    }
+}
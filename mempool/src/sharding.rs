@@ -0,0 +1,119 @@
+use futures::stream::{self, BoxStream, StreamExt};
+
+use nimiq_keys::Address;
+use nimiq_network_interface::network::{Network, Topic};
+use nimiq_transaction::Transaction;
+
+use crate::mempool::TransactionTopic;
+
+/// Number of transaction topic shards. Transactions are assigned to a shard by the first nibble
+/// of their sender address.
+pub const NUM_TRANSACTION_SHARDS: u8 = 16;
+
+/// Returns the shard (`0..NUM_TRANSACTION_SHARDS`) that transactions from the given sender are
+/// published to.
+pub fn transaction_shard(sender: &Address) -> u8 {
+    sender.as_bytes()[0] >> 4
+}
+
+/// Returns all transaction shards.
+pub fn all_transaction_shards() -> Vec<u8> {
+    (0..NUM_TRANSACTION_SHARDS).collect()
+}
+
+macro_rules! transaction_shard_topics {
+    ($($shard: literal => $topic: ident : $name: literal),* $(,)?) => {
+        $(
+            #[doc = concat!("Transaction topic for shard `", stringify!($shard), "`")]
+            #[derive(Clone, Debug, Default)]
+            pub struct $topic;
+
+            impl Topic for $topic {
+                type Item = Transaction;
+
+                const BUFFER_SIZE: usize = 1024;
+                const NAME: &'static str = $name;
+                const VALIDATE: bool = true;
+            }
+        )*
+
+        /// Subscribes to the topics of the given transaction shards and merges them into a single
+        /// stream. If `legacy_topic` is set, the unsharded transaction topic is subscribed as well,
+        /// which is only needed while some nodes still publish to it. Shards that don't exist are
+        /// ignored.
+        pub async fn subscribe_shards<N: Network>(
+            network: &N,
+            shards: &[u8],
+            legacy_topic: bool,
+        ) -> Result<BoxStream<'static, (Transaction, N::PubsubId)>, N::Error> {
+            let mut streams = vec![];
+            if legacy_topic {
+                streams.push(network.subscribe::<TransactionTopic>().await?);
+            }
+            for shard in shards {
+                streams.push(match shard {
+                    $($shard => network.subscribe::<$topic>().await?,)*
+                    _ => {
+                        log::warn!("Ignoring unknown transaction shard {}", shard);
+                        continue;
+                    }
+                });
+            }
+            Ok(stream::select_all(streams).boxed())
+        }
+
+        /// Unsubscribes from the topics of the given transaction shards and, if `legacy_topic` is
+        /// set, from the unsharded transaction topic.
+        pub async fn unsubscribe_shards<N: Network>(
+            network: &N,
+            shards: &[u8],
+            legacy_topic: bool,
+        ) -> Result<(), N::Error> {
+            if legacy_topic {
+                network.unsubscribe::<TransactionTopic>().await?;
+            }
+            for shard in shards {
+                match shard {
+                    $($shard => network.unsubscribe::<$topic>().await?,)*
+                    _ => {}
+                }
+            }
+            Ok(())
+        }
+
+        /// Publishes the transaction to the shard topic of its sender.
+        pub async fn publish_to_shard<N: Network>(
+            network: &N,
+            transaction: Transaction,
+        ) -> Result<(), N::Error> {
+            match transaction_shard(&transaction.sender) {
+                $($shard => network.publish::<$topic>(transaction).await,)*
+                _ => unreachable!(),
+            }
+        }
+
+        nimiq_network_interface::register_topics!(
+            /// The unsharded transaction topic and all transaction shard topics.
+            pub TOPICS = [TransactionTopic, $($topic),*]
+        );
+    };
+}
+
+transaction_shard_topics! {
+    0x0 => TransactionShard0Topic: "transactions/0",
+    0x1 => TransactionShard1Topic: "transactions/1",
+    0x2 => TransactionShard2Topic: "transactions/2",
+    0x3 => TransactionShard3Topic: "transactions/3",
+    0x4 => TransactionShard4Topic: "transactions/4",
+    0x5 => TransactionShard5Topic: "transactions/5",
+    0x6 => TransactionShard6Topic: "transactions/6",
+    0x7 => TransactionShard7Topic: "transactions/7",
+    0x8 => TransactionShard8Topic: "transactions/8",
+    0x9 => TransactionShard9Topic: "transactions/9",
+    0xa => TransactionShardATopic: "transactions/a",
+    0xb => TransactionShardBTopic: "transactions/b",
+    0xc => TransactionShardCTopic: "transactions/c",
+    0xd => TransactionShardDTopic: "transactions/d",
+    0xe => TransactionShardETopic: "transactions/e",
+    0xf => TransactionShardFTopic: "transactions/f",
+}
@@ -0,0 +1,670 @@
+//! Mempool reconciliation with newly connected peers.
+//!
+//! When a peer connects, we request the short IDs of the transactions in its mempool, compare them
+//! to our own transactions and request the ones we are missing. This lets restarted nodes
+//! repopulate their mempool without waiting for the transactions to be gossiped again. The short
+//! IDs are salted by the requester, so transactions can't be crafted to collide with the IDs of
+//! other transactions. Like all peer messages, the exchange runs over the encrypted connection to
+//! the peer.
+//!
+//! Transactions can also get lost later on, e.g. if gossip buffers overflow. To recover them, the
+//! mempool is periodically reconciled with a few random peers. Instead of all short IDs, an
+//! invertible Bloom lookup table of the short IDs is exchanged, whose size only depends on the
+//! expected number of differing transactions.
+
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::hash::Hash;
+use std::io::Write;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::{future, StreamExt};
+use parking_lot::{Mutex, RwLock, RwLockUpgradableReadGuard};
+use rand::seq::SliceRandom;
+
+use beserial::{Deserialize, Serialize};
+use nimiq_blockchain::BlockchainReader;
+use nimiq_hash::{Blake2bHash, Blake2bHasher, Hasher};
+use nimiq_network_interface::message::{Message, RequestMessage, ResponseMessage};
+use nimiq_network_interface::network::{Network, NetworkEvent};
+use nimiq_network_interface::peer::Peer;
+use nimiq_network_interface::request_response::{RequestError, RequestResponse};
+use nimiq_primitives::networks::NetworkId;
+use nimiq_transaction::Transaction;
+use nimiq_utils::rate_limit::RateLimit;
+use nimiq_utils::shared_rng::SharedRng;
+
+use crate::filter::MempoolFilter;
+use crate::iblt::Iblt;
+use crate::mempool::MempoolState;
+use crate::verify::verify_tx;
+
+/// Maximum number of short IDs sent in a sketch.
+pub const MAX_SKETCH_SIZE: usize = 10_000;
+
+/// Maximum number of transactions requested at once.
+pub const MAX_TRANSACTIONS_PER_REQUEST: usize = 500;
+
+/// Number of cells of the first lookup table requested in a periodic reconciliation. If the
+/// difference can't be decoded, tables twice as large are requested, up to `MAX_IBLT_CELLS`.
+pub const MIN_IBLT_CELLS: usize = 96;
+
+/// Maximum number of cells of a lookup table. If the difference can't be decoded with a table of
+/// this size, the short IDs of all transactions are requested.
+pub const MAX_IBLT_CELLS: usize = 3072;
+
+/// Number of random peers the mempool is reconciled with periodically.
+pub const RECONCILIATION_PEERS: usize = 3;
+
+/// Time to wait for the responses of a peer.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Maximum number of reconciliation requests that are answered per peer and minute. A full
+/// reconciliation needs one request for the sketch and one per `MAX_TRANSACTIONS_PER_REQUEST`
+/// missing transactions, a periodic one up to six requests for lookup tables.
+const MAX_REQUESTS_PER_MINUTE: usize = 30;
+
+/// Maximum number of sketch requests that are answered per peer and minute. A sketch lists the
+/// whole mempool and is only needed once per connection, so it is limited separately.
+const MAX_SKETCH_REQUESTS_PER_MINUTE: usize = 2;
+
+/// Returns the short ID of a transaction for the given salt.
+pub fn short_id(salt: u64, hash: &Blake2bHash) -> u64 {
+    let mut hasher = Blake2bHasher::default();
+    hasher.write_all(&salt.to_be_bytes()).unwrap();
+    hasher.write_all(hash.as_bytes()).unwrap();
+
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&hasher.finish().as_bytes()[..8]);
+    u64::from_be_bytes(bytes)
+}
+
+/// Requests the short IDs of the transactions in the mempool of a peer.
+#[derive(Clone, Debug, Serialize, Deserialize, Schema)]
+pub struct RequestMempoolSketch {
+    /// The salt of the short IDs
+    pub salt: u64,
+    /// The request identifier
+    pub request_identifier: u32,
+}
+
+/// The short IDs of the transactions in the mempool of a peer.
+#[derive(Clone, Debug, Serialize, Deserialize, Schema)]
+pub struct MempoolSketch {
+    /// The short IDs, at most `MAX_SKETCH_SIZE`
+    #[beserial(len_type(u16))]
+    pub short_ids: Vec<u64>,
+    /// The request identifier
+    pub request_identifier: u32,
+}
+
+/// Requests the transactions with the given short IDs from the mempool of a peer.
+#[derive(Clone, Debug, Serialize, Deserialize, Schema)]
+pub struct RequestMempoolTransactions {
+    /// The salt of the short IDs
+    pub salt: u64,
+    /// The short IDs, at most `MAX_TRANSACTIONS_PER_REQUEST`
+    #[beserial(len_type(u16))]
+    pub short_ids: Vec<u64>,
+    /// The request identifier
+    pub request_identifier: u32,
+}
+
+/// The requested transactions that are still in the mempool of the peer.
+#[derive(Clone, Debug, Serialize, Deserialize, Schema)]
+pub struct MempoolTransactions {
+    /// The transactions
+    #[beserial(len_type(u16))]
+    pub transactions: Vec<Transaction>,
+    /// The request identifier
+    pub request_identifier: u32,
+}
+
+/// Requests a lookup table of the short IDs of the transactions in the mempool of a peer.
+#[derive(Clone, Debug, Serialize, Deserialize, Schema)]
+pub struct RequestMempoolIblt {
+    /// The salt of the short IDs
+    pub salt: u64,
+    /// The number of cells of the table, at most `MAX_IBLT_CELLS`
+    pub num_cells: u16,
+    /// The request identifier
+    pub request_identifier: u32,
+}
+
+/// A lookup table of the short IDs of the transactions in the mempool of a peer.
+#[derive(Clone, Debug, Serialize, Deserialize, Schema)]
+pub struct MempoolIblt {
+    /// The lookup table
+    pub iblt: Iblt,
+    /// The request identifier
+    pub request_identifier: u32,
+}
+
+macro_rules! request_response {
+    ($msg:ty) => {
+        impl RequestMessage for $msg {
+            fn set_request_identifier(&mut self, request_identifier: u32) {
+                self.request_identifier = request_identifier;
+            }
+        }
+        impl ResponseMessage for $msg {
+            fn get_request_identifier(&self) -> u32 {
+                self.request_identifier
+            }
+        }
+    };
+}
+
+request_response!(RequestMempoolSketch);
+request_response!(MempoolSketch);
+request_response!(RequestMempoolTransactions);
+request_response!(MempoolTransactions);
+request_response!(RequestMempoolIblt);
+request_response!(MempoolIblt);
+
+impl Message for RequestMempoolSketch {
+    const TYPE_ID: u64 = 220;
+}
+
+impl Message for MempoolSketch {
+    const TYPE_ID: u64 = 221;
+}
+
+impl Message for RequestMempoolTransactions {
+    const TYPE_ID: u64 = 222;
+}
+
+impl Message for MempoolTransactions {
+    const TYPE_ID: u64 = 223;
+}
+
+impl Message for RequestMempoolIblt {
+    const TYPE_ID: u64 = 224;
+}
+
+impl Message for MempoolIblt {
+    const TYPE_ID: u64 = 225;
+}
+
+nimiq_network_interface::register_messages!(
+    /// The messages of the mempool reconciliation.
+    pub MESSAGES = [
+        RequestMempoolSketch => RequestMempoolSketch {
+            salt: 1,
+            request_identifier: 2,
+        },
+        MempoolSketch => MempoolSketch {
+            short_ids: vec![1, 2],
+            request_identifier: 3,
+        },
+        RequestMempoolTransactions => RequestMempoolTransactions {
+            salt: 1,
+            short_ids: vec![2, 3],
+            request_identifier: 4,
+        },
+        MempoolTransactions => MempoolTransactions {
+            transactions: vec![],
+            request_identifier: 1,
+        },
+        RequestMempoolIblt => RequestMempoolIblt {
+            salt: 1,
+            num_cells: 96,
+            request_identifier: 2,
+        },
+        MempoolIblt => MempoolIblt {
+            iblt: Iblt::new(3),
+            request_identifier: 1,
+        },
+    ]
+);
+
+/// Settings of the mempool reconciliation.
+#[derive(Clone, Debug)]
+pub(crate) struct SyncConfig {
+    pub sync_on_connect: bool,
+    pub reconciliation_interval: Option<Duration>,
+    pub rng: SharedRng,
+}
+
+/// The parts of the mempool needed to add the transactions received from peers.
+#[derive(Clone)]
+struct SyncContext {
+    blockchain: BlockchainReader,
+    state: Arc<RwLock<MempoolState>>,
+    filter: Arc<RwLock<MempoolFilter>>,
+    network_id: Arc<NetworkId>,
+}
+
+/// The request handles of a peer. A peer only accepts one receiver per message type, so they
+/// are created once per connected peer and shared by all reconciliations with it.
+struct PeerRequests<P: Peer> {
+    sketches: RequestResponse<P, RequestMempoolSketch, MempoolSketch>,
+    iblts: RequestResponse<P, RequestMempoolIblt, MempoolIblt>,
+    transactions: RequestResponse<P, RequestMempoolTransactions, MempoolTransactions>,
+}
+
+impl<P: Peer> PeerRequests<P> {
+    fn new(peer: &Arc<P>) -> Self {
+        Self {
+            sketches: RequestResponse::new(Arc::clone(peer), REQUEST_TIMEOUT),
+            iblts: RequestResponse::new(Arc::clone(peer), REQUEST_TIMEOUT),
+            transactions: RequestResponse::new(Arc::clone(peer), REQUEST_TIMEOUT),
+        }
+    }
+
+    fn peer(&self) -> &Arc<P> {
+        self.sketches.peer()
+    }
+}
+
+/// The request handles of the connected peers, see [`PeerRequests`].
+struct RequestHandles<P: Peer> {
+    peers: Mutex<HashMap<P::Id, Arc<PeerRequests<P>>>>,
+}
+
+impl<P: Peer> RequestHandles<P> {
+    fn new() -> Self {
+        Self {
+            peers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the request handles of the peer, creating them on first use.
+    fn get(&self, peer: &Arc<P>) -> Arc<PeerRequests<P>> {
+        let mut peers = self.peers.lock();
+        match peers.get(&peer.id()) {
+            // A peer that reconnected has a new connection and needs new handles.
+            Some(requests) if Arc::ptr_eq(requests.peer(), peer) => Arc::clone(requests),
+            _ => {
+                let requests = Arc::new(PeerRequests::new(peer));
+                peers.insert(peer.id(), Arc::clone(&requests));
+                requests
+            }
+        }
+    }
+
+    fn remove(&self, peer: &Arc<P>) {
+        let mut peers = self.peers.lock();
+        if matches!(peers.get(&peer.id()), Some(requests) if Arc::ptr_eq(requests.peer(), peer)) {
+            peers.remove(&peer.id());
+        }
+    }
+}
+
+/// Limits the reconciliation requests that are answered per peer.
+struct ServingLimits<Id> {
+    peers: Mutex<HashMap<Id, RateLimit>>,
+    per_minute: usize,
+}
+
+impl<Id: Hash + Eq> ServingLimits<Id> {
+    fn new(per_minute: usize) -> Self {
+        Self {
+            peers: Mutex::new(HashMap::new()),
+            per_minute,
+        }
+    }
+
+    /// Returns whether a request of the peer should be answered.
+    fn allow(&self, peer_id: Id) -> bool {
+        self.peers
+            .lock()
+            .entry(peer_id)
+            .or_insert_with(|| RateLimit::new_per_minute(self.per_minute))
+            .note_single()
+    }
+
+    fn remove(&self, peer_id: &Id) {
+        self.peers.lock().remove(peer_id);
+    }
+}
+
+/// Answers the reconciliation requests of peers, reconciles the mempool with every new peer and
+/// with all peers after the network reconnected if `sync_on_connect` is set and periodically with a few random peers if a
+/// `reconciliation_interval` is set.
+pub(crate) fn mempool_sync<N: Network>(
+    network: Arc<N>,
+    blockchain: BlockchainReader,
+    state: Arc<RwLock<MempoolState>>,
+    filter: Arc<RwLock<MempoolFilter>>,
+    config: SyncConfig,
+) -> impl Future<Output = ()> {
+    let limits = Arc::new(ServingLimits::new(MAX_REQUESTS_PER_MINUTE));
+    let sketch_limits = Arc::new(ServingLimits::new(MAX_SKETCH_REQUESTS_PER_MINUTE));
+
+    let serve_sketches = {
+        let state = Arc::clone(&state);
+        let limits = Arc::clone(&limits);
+        let sketch_limits = Arc::clone(&sketch_limits);
+        network
+            .receive_from_all::<RequestMempoolSketch>()
+            .filter(move |(_, peer)| {
+                future::ready(sketch_limits.allow(peer.id()) && limits.allow(peer.id()))
+            })
+            .for_each(move |(request, peer)| {
+                let response = MempoolSketch {
+                    short_ids: state
+                        .read()
+                        .transactions
+                        .keys()
+                        .take(MAX_SKETCH_SIZE)
+                        .map(|hash| short_id(request.salt, hash))
+                        .collect(),
+                    request_identifier: request.request_identifier,
+                };
+                send_response(peer, response)
+            })
+    };
+
+    let serve_iblts = {
+        let state = Arc::clone(&state);
+        let limits = Arc::clone(&limits);
+        network
+            .receive_from_all::<RequestMempoolIblt>()
+            .filter(move |(_, peer)| future::ready(limits.allow(peer.id())))
+            .for_each(move |(request, peer)| {
+                let num_cells = usize::from(request.num_cells).min(MAX_IBLT_CELLS);
+                let response = MempoolIblt {
+                    iblt: mempool_iblt(&state, request.salt, num_cells),
+                    request_identifier: request.request_identifier,
+                };
+                send_response(peer, response)
+            })
+    };
+
+    let serve_transactions = {
+        let state = Arc::clone(&state);
+        let limits = Arc::clone(&limits);
+        network
+            .receive_from_all::<RequestMempoolTransactions>()
+            .filter(move |(_, peer)| future::ready(limits.allow(peer.id())))
+            .for_each(move |(request, peer)| {
+                let requested: HashSet<u64> = request
+                    .short_ids
+                    .iter()
+                    .take(MAX_TRANSACTIONS_PER_REQUEST)
+                    .copied()
+                    .collect();
+                let response = MempoolTransactions {
+                    transactions: state
+                        .read()
+                        .transactions
+                        .iter()
+                        .filter(|(hash, _)| requested.contains(&short_id(request.salt, hash)))
+                        .map(|(_, tx)| tx.clone())
+                        .collect(),
+                    request_identifier: request.request_identifier,
+                };
+                send_response(peer, response)
+            })
+    };
+
+    let context = SyncContext {
+        network_id: Arc::new(blockchain.network_id()),
+        blockchain,
+        state,
+        filter,
+    };
+
+    let requests = Arc::new(RequestHandles::new());
+
+    let handle_peer_events = {
+        let network = Arc::clone(&network);
+        let context = context.clone();
+        let requests = Arc::clone(&requests);
+        let sync_on_connect = config.sync_on_connect;
+        async move {
+            let mut events = network.subscribe_events();
+            while let Some(event) = events.next().await {
+                match event {
+                    Ok(NetworkEvent::PeerJoined(peer)) if sync_on_connect => {
+                        let context = context.clone();
+                        let requests = requests.get(&peer);
+                        tokio::spawn(async move {
+                            let peer_id = peer.id();
+                            log_result(peer_id, sync_with_peer(&requests, &context).await);
+                        });
+                    }
+                    // Transactions may have been missed while the network was disconnected, so
+                    // the mempool is reconciled with all peers again.
+                    Ok(NetworkEvent::Reconnected) if sync_on_connect => {
+                        for peer in network.get_peers() {
+                            let context = context.clone();
+                            let requests = requests.get(&peer);
+                            tokio::spawn(async move {
+                                let peer_id = peer.id();
+                                log_result(peer_id, sync_with_peer(&requests, &context).await);
+                            });
+                        }
+                    }
+                    Ok(NetworkEvent::PeerLeft(peer)) => {
+                        requests.remove(&peer);
+                        limits.remove(&peer.id());
+                        sketch_limits.remove(&peer.id());
+                    }
+                    _ => {}
+                }
+            }
+        }
+    };
+
+    let reconcile_periodically = async move {
+        let mut rng = config.rng;
+        let interval = match config.reconciliation_interval {
+            Some(interval) => interval,
+            None => return,
+        };
+
+        let mut ticks = tokio::time::interval(interval);
+        // The first tick completes immediately.
+        ticks.tick().await;
+        loop {
+            ticks.tick().await;
+
+            let peers: Vec<_> = network
+                .get_peers()
+                .choose_multiple(&mut rng, RECONCILIATION_PEERS)
+                .cloned()
+                .collect();
+            for peer in peers {
+                let context = context.clone();
+                let requests = requests.get(&peer);
+                tokio::spawn(async move {
+                    let peer_id = peer.id();
+                    log_result(peer_id, reconcile_with_peer(&requests, &context).await);
+                });
+            }
+        }
+    };
+
+    async move {
+        future::join5(
+            serve_sketches,
+            serve_iblts,
+            serve_transactions,
+            handle_peer_events,
+            reconcile_periodically,
+        )
+        .await;
+    }
+}
+
+fn send_response<P: Peer, M: Message>(peer: Arc<P>, response: M) -> impl Future<Output = ()> {
+    async move {
+        if let Err(e) = peer.send(response).await {
+            log::debug!(
+                "Failed to send {} response: {:?}",
+                std::any::type_name::<M>(),
+                e
+            );
+        }
+    }
+}
+
+fn log_result<Id: std::fmt::Debug>(peer_id: Id, result: Result<usize, RequestError>) {
+    match result {
+        Ok(0) => {}
+        Ok(added) => log::debug!(
+            "Added {} transactions from the mempool of peer {:?}",
+            added,
+            peer_id
+        ),
+        Err(e) => log::debug!("Failed to reconcile mempool with peer {:?}: {}", peer_id, e),
+    }
+}
+
+/// Returns a lookup table of the short IDs of all transactions in the mempool.
+fn mempool_iblt(state: &RwLock<MempoolState>, salt: u64, num_cells: usize) -> Iblt {
+    let mut iblt = Iblt::new(num_cells);
+    for hash in state.read().transactions.keys() {
+        iblt.insert(short_id(salt, hash));
+    }
+    iblt
+}
+
+/// Requests the short IDs of all transactions of the peer and then the transactions we are
+/// missing. Returns the number of transactions that were added to our mempool.
+async fn sync_with_peer<P: Peer>(
+    requests: &PeerRequests<P>,
+    context: &SyncContext,
+) -> Result<usize, RequestError> {
+    let salt = rand::random();
+
+    let sketch = requests
+        .sketches
+        .request(RequestMempoolSketch {
+            salt,
+            request_identifier: 0,
+        })
+        .await?;
+
+    let missing: Vec<u64> = {
+        let state = context.state.read();
+        let known: HashSet<u64> = state
+            .transactions
+            .keys()
+            .map(|hash| short_id(salt, hash))
+            .collect();
+        sketch
+            .short_ids
+            .into_iter()
+            .filter(|id| !known.contains(id))
+            .collect()
+    };
+
+    request_transactions(requests, context, salt, &missing).await
+}
+
+/// Exchanges lookup tables with the peer to find the transactions we are missing and requests
+/// them. Falls back to `sync_with_peer` if the difference is too large for the lookup tables.
+async fn reconcile_with_peer<P: Peer>(
+    requests: &PeerRequests<P>,
+    context: &SyncContext,
+) -> Result<usize, RequestError> {
+    let mut num_cells = MIN_IBLT_CELLS;
+    while num_cells <= MAX_IBLT_CELLS {
+        let salt = rand::random();
+        let response = requests
+            .iblts
+            .request(RequestMempoolIblt {
+                salt,
+                num_cells: num_cells as u16,
+                request_identifier: 0,
+            })
+            .await?;
+
+        let own = mempool_iblt(&context.state, salt, num_cells);
+        if let Some((missing, _)) = response.iblt.subtract(&own).and_then(Iblt::decode) {
+            return request_transactions(requests, context, salt, &missing).await;
+        }
+
+        num_cells *= 2;
+    }
+
+    log::debug!(
+        "Mempool difference to peer {:?} is too large for lookup tables",
+        requests.peer().id()
+    );
+    sync_with_peer(requests, context).await
+}
+
+/// Requests the transactions with the given short IDs from the peer and adds them to the mempool.
+/// Returns the number of transactions that were added.
+async fn request_transactions<P: Peer>(
+    requests: &PeerRequests<P>,
+    context: &SyncContext,
+    salt: u64,
+    short_ids: &[u64],
+) -> Result<usize, RequestError> {
+    let mut added = 0;
+    for short_ids in short_ids.chunks(MAX_TRANSACTIONS_PER_REQUEST) {
+        let response = requests
+            .transactions
+            .request(RequestMempoolTransactions {
+                salt,
+                short_ids: short_ids.to_vec(),
+                request_identifier: 0,
+            })
+            .await?;
+
+        for tx in response.transactions {
+            // The transactions are verified like transactions received via gossip.
+            if let Ok(mempool_state) = verify_tx(
+                &tx,
+                context.blockchain.clone(),
+                Arc::clone(&context.network_id),
+                &context.state,
+                Arc::clone(&context.filter),
+            )
+            .await
+            {
+                if RwLockUpgradableReadGuard::upgrade(mempool_state).put(&tx) {
+                    added += 1;
+                }
+            }
+        }
+    }
+
+    Ok(added)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_limits_the_requests_served_per_peer() {
+        let limits = ServingLimits::new(MAX_REQUESTS_PER_MINUTE);
+
+        for _ in 0..MAX_REQUESTS_PER_MINUTE {
+            assert!(limits.allow(1u32));
+        }
+        assert!(!limits.allow(1));
+
+        // Other peers have their own limit.
+        assert!(limits.allow(2));
+
+        // The limit of a peer is reset once it disconnects.
+        limits.remove(&1);
+        assert!(limits.allow(1));
+    }
+
+    #[test]
+    fn it_limits_sketch_requests_separately() {
+        let limits = ServingLimits::new(MAX_REQUESTS_PER_MINUTE);
+        let sketch_limits = ServingLimits::new(MAX_SKETCH_REQUESTS_PER_MINUTE);
+        let allow_sketch = |peer_id| sketch_limits.allow(peer_id) && limits.allow(peer_id);
+
+        for _ in 0..MAX_SKETCH_REQUESTS_PER_MINUTE {
+            assert!(allow_sketch(1u32));
+        }
+        assert!(!allow_sketch(1));
+
+        // Refused sketch requests don't count against the other requests of the peer.
+        for _ in MAX_SKETCH_REQUESTS_PER_MINUTE..MAX_REQUESTS_PER_MINUTE {
+            assert!(limits.allow(1));
+        }
+        assert!(!limits.allow(1));
+    }
+}
@@ -5,9 +5,10 @@ use std::{
     sync::Arc,
 };
 
+use beserial::Serialize;
 use nimiq_account::{Account, BasicAccount, StakingContract};
-use nimiq_blockchain::{AbstractBlockchain, Blockchain};
-use nimiq_hash::Hash;
+use nimiq_blockchain::BlockchainReader;
+use nimiq_hash::{Blake2bHash, Hash};
 use nimiq_primitives::account::AccountType;
 use nimiq_primitives::coin::Coin;
 use nimiq_transaction::account::staking_contract::{
@@ -45,6 +46,14 @@ pub enum VerifyErr {
     Known,
     /// Transaction is filtered
     Filtered,
+    /// The mempool reached its memory cap and the transaction doesn't pay more than the cheapest
+    /// ones
+    Full,
+    /// Transaction conflicts with a transaction that is in the mempool or was recently mined
+    Conflict {
+        /// Hash of the conflicting transaction
+        existing_tx: Blake2bHash,
+    },
 }
 
 impl Display for VerifyErr {
@@ -71,6 +80,12 @@ impl Display for VerifyErr {
             VerifyErr::Filtered => {
                 write!(f, "Filtered")
             }
+            VerifyErr::Full => {
+                write!(f, "Mempool is full")
+            }
+            VerifyErr::Conflict { existing_tx } => {
+                write!(f, "Conflicts with transaction {}", existing_tx)
+            }
         }
     }
 }
@@ -83,7 +98,7 @@ impl Display for VerifyErr {
 /// caller can upgrade the lock and add the transaction to the mempool.
 pub(crate) async fn verify_tx<'a>(
     transaction: &Transaction,
-    blockchain: Arc<RwLock<Blockchain>>,
+    blockchain: BlockchainReader,
     network_id: Arc<NetworkId>,
     mempool_state: &'a Arc<RwLock<MempoolState>>,
     filter: Arc<RwLock<MempoolFilter>>,
@@ -112,9 +127,11 @@ pub(crate) async fn verify_tx<'a>(
         }
     };
 
-    // 2. Acquire the mempool state upgradable read lock
-    let blockchain = blockchain.read();
+    // 2. Acquire the mempool state upgradable read lock and open a view of the blockchain state.
+    //    The view doesn't block on blocks being pushed. It is opened after acquiring the mempool
+    //    state, so that the mempool can't process a newer block before this transaction is added.
     let mempool_state = mempool_state.upgradable_read();
+    let blockchain = blockchain.view();
 
     // 3. Check if we already know the transaction
     if mempool_state.contains(&transaction.hash()) {
@@ -122,6 +139,17 @@ pub(crate) async fn verify_tx<'a>(
         return Err(VerifyErr::Known);
     }
 
+    // Don't grow beyond the memory cap, unless the transaction pays more than the cheapest
+    // transactions, which are then evicted to make room for it.
+    if mempool_state
+        .memory
+        .would_exceed_cap(transaction.serialized_size())
+        && !mempool_state.outbids_cheapest(transaction)
+    {
+        log::debug!("Mempool is full");
+        return Err(VerifyErr::Full);
+    }
+
     // 4. Check if the transaction is going to be filtered.
     {
         let filter = filter.read();
@@ -131,9 +159,7 @@ pub(crate) async fn verify_tx<'a>(
         }
     }
 
-    // 5. Acquire Blockchain read lock
-
-    // 6. Check Validity Window and already included
+    // 5. Check Validity Window and already included
     let block_height = blockchain.block_number() + 1;
 
     if !transaction.is_valid_at(block_height) {
@@ -141,13 +167,12 @@ pub(crate) async fn verify_tx<'a>(
         return Err(VerifyErr::InvalidBlockHeight);
     }
 
-    if blockchain.contains_tx_in_validity_window(&transaction.hash(), None) {
+    if blockchain.contains_tx_in_validity_window(&transaction.hash()) {
         log::debug!("Transaction has already been mined");
         return Err(VerifyErr::InvalidTxWindow);
     }
 
-    // 7. Sequentialize per Sender to Check Balances and acquire the upgradable from the blockchain.
-    //    Perform all balances checks.
+    // 6. Sequentialize per Sender to Check Balances. Perform all balances checks.
     let sender_account = match blockchain.get_account(&transaction.sender).or_else(|| {
         if transaction.total_value() != Coin::ZERO {
             None
@@ -162,12 +187,15 @@ pub(crate) async fn verify_tx<'a>(
                 "There is no account for this sender in the blockchain {}",
                 transaction.sender.to_user_friendly_address()
             );
+            if let Some(existing_tx) = mempool_state.find_conflict(transaction, None) {
+                return Err(VerifyErr::Conflict { existing_tx });
+            }
             return Err(VerifyErr::InvalidSender);
         }
         Some(account) => account,
     };
 
-    // 8. Get recipient account to later check against filter rules.
+    // 7. Get recipient account to later check against filter rules.
     let recipient_account = match blockchain.get_account(&transaction.recipient) {
         None => Account::Basic(BasicAccount {
             balance: Coin::ZERO,
@@ -177,7 +205,7 @@ pub(crate) async fn verify_tx<'a>(
 
     // If it is an outgoing staking transaction then we have additional checks.
     if transaction.sender_type == AccountType::Staking {
-        let accounts_tree = &blockchain.state().accounts.tree;
+        let accounts_tree = blockchain.accounts_tree();
         let db_txn = blockchain.read_transaction();
 
         // Parse transaction data.
@@ -203,7 +231,7 @@ pub(crate) async fn verify_tx<'a>(
         // transaction.
         if !StakingContract::can_pay_tx(
             accounts_tree,
-            &db_txn,
+            db_txn,
             data,
             transaction.total_value(),
             block_height,
@@ -215,7 +243,7 @@ pub(crate) async fn verify_tx<'a>(
 
     // If it is an incoming staking transaction then we have additional checks.
     if transaction.recipient_type == AccountType::Staking {
-        let accounts_tree = &blockchain.state().accounts.tree;
+        let accounts_tree = blockchain.accounts_tree();
         let db_txn = blockchain.read_transaction();
 
         // Parse transaction data.
@@ -240,13 +268,13 @@ pub(crate) async fn verify_tx<'a>(
 
         // If the recipient is not already in the mempool, then we need to check if the transaction
         // can succeed.
-        if !StakingContract::can_create(accounts_tree, &db_txn, data) {
+        if !StakingContract::can_create(accounts_tree, db_txn, data) {
             log::debug!("Outgoing staking transaction cannot pay fee.");
             return Err(VerifyErr::NotEnoughFunds);
         }
     }
 
-    // 9. Drop the blockchain lock since it is no longer needed
+    // 8. Drop the view of the blockchain since it is no longer needed
     drop(blockchain);
 
     let blockchain_sender_balance = sender_account.balance();
@@ -290,6 +318,19 @@ pub(crate) async fn verify_tx<'a>(
         return Err(VerifyErr::Filtered);
     }
 
+    // Check for conflicts with pending or recently mined transactions, so that the sender learns
+    // which transaction prevents this one from being accepted.
+    if let Some(existing_tx) =
+        mempool_state.find_conflict(transaction, Some(blockchain_sender_balance))
+    {
+        log::debug!(
+            "Transaction {} conflicts with transaction {}",
+            transaction.hash::<Blake2bHash>(),
+            existing_tx
+        );
+        return Err(VerifyErr::Conflict { existing_tx });
+    }
+
     if sender_in_fly_balance > blockchain_sender_balance {
         log::debug!("Dropped because sum of txs in mempool is larger than the account balance");
         return Err(VerifyErr::NotEnoughFunds);
@@ -0,0 +1,37 @@
+use nimiq_mempool::iblt::Iblt;
+
+#[test]
+fn it_decodes_small_differences() {
+    let mut ours = Iblt::new(30);
+    let mut theirs = Iblt::new(30);
+
+    for key in 0..1000u64 {
+        let key = key.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        ours.insert(key);
+        theirs.insert(key);
+    }
+    ours.insert(1);
+    ours.insert(2);
+    theirs.insert(3);
+
+    let (mut only_ours, only_theirs) = ours.subtract(&theirs).unwrap().decode().unwrap();
+    only_ours.sort_unstable();
+    assert_eq!(only_ours, vec![1, 2]);
+    assert_eq!(only_theirs, vec![3]);
+}
+
+#[test]
+fn it_fails_to_decode_large_differences() {
+    let mut ours = Iblt::new(6);
+    for key in 1..=100u64 {
+        ours.insert(key.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+    }
+
+    assert_eq!(ours.subtract(&Iblt::new(6)).unwrap().decode(), None);
+}
+
+#[test]
+fn it_only_subtracts_tables_of_the_same_size() {
+    assert_eq!(Iblt::new(3).subtract(&Iblt::new(6)), None);
+    assert_eq!(Iblt::new(7).len(), 9);
+}
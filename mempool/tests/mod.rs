@@ -1,23 +1,24 @@
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use futures::{channel::mpsc, sink::SinkExt};
 use log::LevelFilter::Debug;
-use parking_lot::RwLock;
 use rand::prelude::StdRng;
 use rand::SeedableRng;
 
 use beserial::{Deserialize, Serialize};
 use nimiq_block::{Block, MicroBlock, MicroBody, MicroHeader};
-use nimiq_blockchain::Blockchain;
+use nimiq_blockchain::{Blockchain, BlockchainLock};
 use nimiq_bls::KeyPair as BlsKeyPair;
 use nimiq_build_tools::genesis::GenesisBuilder;
 use nimiq_database::volatile::VolatileEnvironment;
-use nimiq_hash::Blake2bHash;
+use nimiq_hash::{Blake2bHash, Hash};
 use nimiq_keys::{
     Address, KeyPair as SchnorrKeyPair, PublicKey as SchnorrPublicKey, SecureGenerate,
 };
 use nimiq_mempool::config::MempoolConfig;
 use nimiq_mempool::mempool::Mempool;
+use nimiq_mempool::verify::VerifyErr;
 use nimiq_network_mock::{MockHub, MockId, MockNetwork, MockPeerId};
 use nimiq_primitives::networks::NetworkId;
 use nimiq_test_utils::test_transaction::{
@@ -32,7 +33,7 @@ const ENABLE_LOG: bool = false;
 const NUM_TXNS_START_STOP: usize = 100;
 
 async fn send_get_mempool_txns(
-    blockchain: Arc<RwLock<Blockchain>>,
+    blockchain: Arc<BlockchainLock>,
     transactions: Vec<Transaction>,
     txn_len: usize,
 ) -> Vec<Transaction> {
@@ -79,10 +80,7 @@ async fn send_txn_to_mempool(
     mempool.stop_executor_without_unsuscribe().await;
 }
 
-async fn multiple_start_stop_send(
-    blockchain: Arc<RwLock<Blockchain>>,
-    transactions: Vec<Transaction>,
-) {
+async fn multiple_start_stop_send(blockchain: Arc<BlockchainLock>, transactions: Vec<Transaction>) {
     // Create a MPSC channel to directly send transactions to the mempool
     let (mut txn_stream_tx, txn_stream_rx) = mpsc::channel(64);
 
@@ -262,7 +260,7 @@ async fn push_same_tx_twice() {
 
     let genesis_info = genesis_builder.generate(env.clone()).unwrap();
 
-    let blockchain = Arc::new(RwLock::new(
+    let blockchain = Arc::new(BlockchainLock::new(
         Blockchain::with_genesis(
             env.clone(),
             time,
@@ -318,7 +316,7 @@ async fn valid_tx_not_in_blockchain() {
     let env = VolatileEnvironment::new(10).unwrap();
 
     // Create an empty blockchain
-    let blockchain = Arc::new(RwLock::new(
+    let blockchain = Arc::new(BlockchainLock::new(
         Blockchain::new(env, NetworkId::UnitAlbatross, time).unwrap(),
     ));
 
@@ -335,7 +333,7 @@ async fn push_tx_with_wrong_signature() {
     let env = VolatileEnvironment::new(10).unwrap();
 
     // Create an empty blockchain
-    let blockchain = Arc::new(RwLock::new(
+    let blockchain = Arc::new(BlockchainLock::new(
         Blockchain::new(env, NetworkId::UnitAlbatross, time).unwrap(),
     ));
 
@@ -401,7 +399,7 @@ async fn mempool_get_txn_max_size() {
 
     let genesis_info = genesis_builder.generate(env.clone()).unwrap();
 
-    let blockchain = Arc::new(RwLock::new(
+    let blockchain = Arc::new(BlockchainLock::new(
         Blockchain::with_genesis(
             env.clone(),
             time,
@@ -475,7 +473,7 @@ async fn mempool_get_txn_ordered() {
 
     let genesis_info = genesis_builder.generate(env.clone()).unwrap();
 
-    let blockchain = Arc::new(RwLock::new(
+    let blockchain = Arc::new(BlockchainLock::new(
         Blockchain::with_genesis(
             env.clone(),
             time,
@@ -502,6 +500,112 @@ async fn mempool_get_txn_ordered() {
     }
 }
 
+#[tokio::test]
+async fn mempool_snapshot_for_block() {
+    // Generate and sign transaction from an address
+    let mut rng = StdRng::seed_from_u64(0);
+    let balance = 40;
+    let num_txns = 4;
+    let mut mempool_transactions = vec![];
+    let sender_balances = vec![balance + num_txns * 3; 1];
+    let recipient_balances = vec![0; num_txns as usize];
+    let mut genesis_builder = GenesisBuilder::default();
+
+    // Generate recipient accounts
+    let recipient_accounts = generate_accounts(recipient_balances, &mut genesis_builder, false);
+    // Generate sender accounts
+    let sender_accounts = generate_accounts(sender_balances, &mut genesis_builder, true);
+
+    // Generate transactions
+    for i in 0..num_txns {
+        let mempool_transaction = TestTransaction {
+            fee: (i + 1) as u64,
+            value: balance / num_txns,
+            recipient: recipient_accounts[i as usize].clone(),
+            sender: sender_accounts[0].clone(),
+        };
+        mempool_transactions.push(mempool_transaction);
+    }
+    let (txns, txns_len) = generate_transactions(mempool_transactions, true);
+
+    let time = Arc::new(OffsetTime::new());
+    let env = VolatileEnvironment::new(10).unwrap();
+
+    // Add a validator to genesis
+    genesis_builder.with_genesis_validator(
+        Address::from(&SchnorrKeyPair::generate(&mut rng)),
+        SchnorrPublicKey::from([0u8; 32]),
+        BlsKeyPair::generate(&mut rng).public_key,
+        Address::default(),
+    );
+
+    let genesis_info = genesis_builder.generate(env.clone()).unwrap();
+
+    let blockchain = Arc::new(BlockchainLock::new(
+        Blockchain::with_genesis(
+            env.clone(),
+            time,
+            NetworkId::UnitAlbatross,
+            genesis_info.block,
+            genesis_info.accounts,
+        )
+        .unwrap(),
+    ));
+
+    // Send the transactions
+    let mempool = Mempool::new(Arc::clone(&blockchain), MempoolConfig::default());
+    let mut hub = MockHub::new();
+    let mock_id = MockId::new(hub.new_address().into());
+    let mock_network = Arc::new(hub.new_network());
+    send_txn_to_mempool(&mempool, mock_network, mock_id, txns.clone()).await;
+
+    // The transactions are indexed by their recipients
+    for txn in &txns {
+        assert_eq!(
+            mempool.get_pending_by_recipient(&txn.recipient),
+            vec![txn.clone()]
+        );
+    }
+
+    // Taking a snapshot doesn't remove the transactions
+    let snapshot = mempool.snapshot_for_block(txns_len);
+    assert_eq!(snapshot.len(), num_txns as usize);
+    assert_eq!(mempool.num_transactions(), num_txns as usize);
+    for pair in snapshot.windows(2) {
+        assert!(
+            pair[0].fee >= pair[1].fee,
+            "Transactions in snapshot are not ordered by fee"
+        );
+    }
+
+    // The snapshot respects the size limit
+    let first_len = snapshot[0].serialized_size();
+    assert_eq!(mempool.snapshot_for_block(first_len).len(), 1);
+
+    // A snapshot that meets its deadline selects the same transactions
+    let snapshot_until =
+        mempool.snapshot_for_block_until(txns_len, Instant::now() + Duration::from_secs(10));
+    assert!(!snapshot_until.timed_out);
+    assert_eq!(snapshot_until.transactions, snapshot);
+
+    // A snapshot whose deadline has passed stops selecting transactions
+    let snapshot_until = mempool.snapshot_for_block_until(txns_len, Instant::now());
+    assert!(snapshot_until.timed_out);
+    assert!(snapshot_until.transactions.len() < snapshot.len());
+    assert_eq!(
+        snapshot_until.transactions,
+        snapshot[..snapshot_until.transactions.len()]
+    );
+
+    // The snapshot matches the transactions that are handed out for a block
+    let obtained_txns = mempool.get_transactions_for_block(txns_len);
+    assert_eq!(obtained_txns, snapshot);
+    assert_eq!(mempool.num_transactions(), 0);
+    assert!(mempool
+        .get_pending_by_recipient(&txns[0].recipient)
+        .is_empty());
+}
+
 #[tokio::test]
 async fn push_tx_with_insufficient_balance() {
     if ENABLE_LOG {
@@ -552,7 +656,7 @@ async fn push_tx_with_insufficient_balance() {
 
     let genesis_info = genesis_builder.generate(env.clone()).unwrap();
 
-    let blockchain = Arc::new(RwLock::new(
+    let blockchain = Arc::new(BlockchainLock::new(
         Blockchain::with_genesis(
             env.clone(),
             time,
@@ -571,6 +675,294 @@ async fn push_tx_with_insufficient_balance() {
     assert_eq!(txns.len(), 1);
 }
 
+#[tokio::test]
+async fn push_tx_below_min_relay_fee() {
+    if ENABLE_LOG {
+        simple_logger::SimpleLogger::new()
+            .with_level(Debug)
+            .init()
+            .ok();
+    }
+
+    // Generate and sign transaction from an address
+    let mut rng = StdRng::seed_from_u64(0);
+    let fees = [0, 1000];
+    let mut mempool_transactions = vec![];
+    let sender_balances = vec![10000; fees.len()];
+    let recipient_balances = vec![0; fees.len()];
+    let mut genesis_builder = GenesisBuilder::default();
+
+    // Generate recipient accounts
+    let recipient_accounts = generate_accounts(recipient_balances, &mut genesis_builder, false);
+    // Generate sender accounts
+    let sender_accounts = generate_accounts(sender_balances, &mut genesis_builder, true);
+
+    // Generate one transaction per sender, only the second one pays a fee
+    for (i, fee) in fees.iter().enumerate() {
+        let mempool_transaction = TestTransaction {
+            fee: *fee,
+            value: 10,
+            recipient: recipient_accounts[i].clone(),
+            sender: sender_accounts[i].clone(),
+        };
+        mempool_transactions.push(mempool_transaction);
+    }
+    let (txns, txns_len) = generate_transactions(mempool_transactions, true);
+    log::debug!("Done generating transactions and accounts");
+
+    let time = Arc::new(OffsetTime::new());
+    let env = VolatileEnvironment::new(10).unwrap();
+
+    // Add a validator to genesis
+    genesis_builder.with_genesis_validator(
+        Address::from(&SchnorrKeyPair::generate(&mut rng)),
+        SchnorrPublicKey::from([0u8; 32]),
+        BlsKeyPair::generate(&mut rng).public_key,
+        Address::default(),
+    );
+
+    let genesis_info = genesis_builder.generate(env.clone()).unwrap();
+
+    let blockchain = Arc::new(BlockchainLock::new(
+        Blockchain::with_genesis(
+            env.clone(),
+            time,
+            NetworkId::UnitAlbatross,
+            genesis_info.block,
+            genesis_info.accounts,
+        )
+        .unwrap(),
+    ));
+
+    // Create a mempool that only relays transactions paying at least 1 luna per byte
+    let mempool = Mempool::new(Arc::clone(&blockchain), MempoolConfig::default());
+    mempool.set_min_relay_fee_per_byte(1.0).unwrap();
+    assert_eq!(mempool.get_min_relay_fee_per_byte(), 1.0);
+
+    // Negative and NaN fees are rejected and leave the fee unchanged
+    assert!(mempool.set_min_relay_fee_per_byte(-1.0).is_err());
+    assert!(mempool.set_min_relay_fee_per_byte(f64::NAN).is_err());
+    assert_eq!(mempool.get_min_relay_fee_per_byte(), 1.0);
+
+    let mut hub = MockHub::new();
+    let mock_id = MockId::new(hub.new_address().into());
+    let mock_network = Arc::new(hub.new_network());
+
+    send_txn_to_mempool(&mempool, mock_network, mock_id, txns).await;
+
+    // Expect only the transaction paying the relay fee in the mempool
+    let txns = mempool.get_transactions_for_block(txns_len);
+    assert_eq!(txns.len(), 1);
+    assert_eq!(u64::from(txns[0].fee), 1000);
+}
+
+#[tokio::test]
+async fn push_conflicting_tx() {
+    // Generate and sign transactions from an address
+    let mut rng = StdRng::seed_from_u64(0);
+    let balance = 30;
+    let txns_value: Vec<u64> = vec![20, 10];
+    let mut mempool_transactions = vec![];
+    let sender_balances = vec![balance; 1];
+    let recipient_balances = vec![0; txns_value.len()];
+    let mut genesis_builder = GenesisBuilder::default();
+
+    // Generate recipient accounts
+    let recipient_accounts = generate_accounts(recipient_balances, &mut genesis_builder, false);
+    // Generate sender accounts
+    let sender_accounts = generate_accounts(sender_balances, &mut genesis_builder, true);
+
+    // Generate transactions
+    for (i, value) in txns_value.iter().enumerate() {
+        let mempool_transaction = TestTransaction {
+            fee: (i + 1) as u64,
+            value: *value,
+            recipient: recipient_accounts[i].clone(),
+            sender: sender_accounts[0].clone(),
+        };
+        mempool_transactions.push(mempool_transaction);
+    }
+    let (txns, _) = generate_transactions(mempool_transactions, true);
+
+    let time = Arc::new(OffsetTime::new());
+    let env = VolatileEnvironment::new(10).unwrap();
+
+    // Add a validator to genesis
+    genesis_builder.with_genesis_validator(
+        Address::from(&SchnorrKeyPair::generate(&mut rng)),
+        SchnorrPublicKey::from([0u8; 32]),
+        BlsKeyPair::generate(&mut rng).public_key,
+        Address::default(),
+    );
+
+    let genesis_info = genesis_builder.generate(env.clone()).unwrap();
+
+    let blockchain = Arc::new(BlockchainLock::new(
+        Blockchain::with_genesis(
+            env.clone(),
+            time,
+            NetworkId::UnitAlbatross,
+            genesis_info.block,
+            genesis_info.accounts,
+        )
+        .unwrap(),
+    ));
+
+    let mempool = Mempool::new(Arc::clone(&blockchain), MempoolConfig::default());
+
+    // The first transaction is accepted
+    mempool.add_transaction(txns[0].clone()).await.unwrap();
+
+    // The sender can pay for the second transaction alone, but not together with the first one
+    assert_eq!(
+        mempool.add_transaction(txns[1].clone()).await,
+        Err(VerifyErr::Conflict {
+            existing_tx: txns[0].hash(),
+        })
+    );
+    assert_eq!(mempool.num_transactions(), 1);
+}
+
+#[tokio::test]
+async fn push_tx_when_full() {
+    // Generate and sign transactions from two addresses
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut mempool_transactions = vec![];
+    let sender_balances = vec![100; 2];
+    let recipient_balances = vec![0; 1];
+    let mut genesis_builder = GenesisBuilder::default();
+
+    // Generate recipient accounts
+    let recipient_accounts = generate_accounts(recipient_balances, &mut genesis_builder, false);
+    // Generate sender accounts
+    let sender_accounts = generate_accounts(sender_balances, &mut genesis_builder, true);
+
+    // Generate transactions
+    for sender in sender_accounts {
+        let mempool_transaction = TestTransaction {
+            fee: 1,
+            value: 10,
+            recipient: recipient_accounts[0].clone(),
+            sender,
+        };
+        mempool_transactions.push(mempool_transaction);
+    }
+    let (txns, _) = generate_transactions(mempool_transactions, true);
+
+    let time = Arc::new(OffsetTime::new());
+    let env = VolatileEnvironment::new(10).unwrap();
+
+    // Add a validator to genesis
+    genesis_builder.with_genesis_validator(
+        Address::from(&SchnorrKeyPair::generate(&mut rng)),
+        SchnorrPublicKey::from([0u8; 32]),
+        BlsKeyPair::generate(&mut rng).public_key,
+        Address::default(),
+    );
+
+    let genesis_info = genesis_builder.generate(env.clone()).unwrap();
+
+    let blockchain = Arc::new(BlockchainLock::new(
+        Blockchain::with_genesis(
+            env.clone(),
+            time,
+            NetworkId::UnitAlbatross,
+            genesis_info.block,
+            genesis_info.accounts,
+        )
+        .unwrap(),
+    ));
+
+    let mempool = Mempool::new(Arc::clone(&blockchain), MempoolConfig::default());
+
+    // Leave room for exactly one transaction
+    let memory = mempool.memory();
+    memory.set_cap(Some(txns[0].serialized_size()));
+
+    mempool.add_transaction(txns[0].clone()).await.unwrap();
+    assert_eq!(memory.used(), txns[0].serialized_size());
+
+    assert_eq!(
+        mempool.add_transaction(txns[1].clone()).await,
+        Err(VerifyErr::Full)
+    );
+    assert_eq!(mempool.num_transactions(), 1);
+}
+
+#[tokio::test]
+async fn push_tx_when_full_evicts_cheaper_transactions() {
+    // Generate and sign transactions with different fees from three addresses
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut mempool_transactions = vec![];
+    let sender_balances = vec![100; 3];
+    let recipient_balances = vec![0; 1];
+    let mut genesis_builder = GenesisBuilder::default();
+
+    // Generate recipient accounts
+    let recipient_accounts = generate_accounts(recipient_balances, &mut genesis_builder, false);
+    // Generate sender accounts
+    let sender_accounts = generate_accounts(sender_balances, &mut genesis_builder, true);
+
+    // Generate transactions
+    for (sender, fee) in sender_accounts.into_iter().zip([1, 10, 5]) {
+        let mempool_transaction = TestTransaction {
+            fee,
+            value: 10,
+            recipient: recipient_accounts[0].clone(),
+            sender,
+        };
+        mempool_transactions.push(mempool_transaction);
+    }
+    let (txns, _) = generate_transactions(mempool_transactions, true);
+
+    let time = Arc::new(OffsetTime::new());
+    let env = VolatileEnvironment::new(10).unwrap();
+
+    // Add a validator to genesis
+    genesis_builder.with_genesis_validator(
+        Address::from(&SchnorrKeyPair::generate(&mut rng)),
+        SchnorrPublicKey::from([0u8; 32]),
+        BlsKeyPair::generate(&mut rng).public_key,
+        Address::default(),
+    );
+
+    let genesis_info = genesis_builder.generate(env.clone()).unwrap();
+
+    let blockchain = Arc::new(BlockchainLock::new(
+        Blockchain::with_genesis(
+            env.clone(),
+            time,
+            NetworkId::UnitAlbatross,
+            genesis_info.block,
+            genesis_info.accounts,
+        )
+        .unwrap(),
+    ));
+
+    let mempool = Mempool::new(Arc::clone(&blockchain), MempoolConfig::default());
+
+    // Leave room for exactly one transaction
+    let memory = mempool.memory();
+    memory.set_cap(Some(txns[0].serialized_size()));
+
+    mempool.add_transaction(txns[0].clone()).await.unwrap();
+
+    // A transaction that pays more replaces the cheapest one
+    mempool.add_transaction(txns[1].clone()).await.unwrap();
+    assert_eq!(mempool.num_transactions(), 1);
+    assert!(!mempool.contains_transaction_by_hash(&txns[0].hash()));
+    assert!(mempool.contains_transaction_by_hash(&txns[1].hash()));
+    assert_eq!(memory.used(), txns[1].serialized_size());
+
+    // A transaction that pays less than the cheapest one is rejected
+    assert_eq!(
+        mempool.add_transaction(txns[2].clone()).await,
+        Err(VerifyErr::Full)
+    );
+    assert_eq!(mempool.num_transactions(), 1);
+    assert!(mempool.contains_transaction_by_hash(&txns[1].hash()));
+}
+
 #[tokio::test]
 async fn multiple_transactions_multiple_senders() {
     if ENABLE_LOG {
@@ -619,7 +1011,7 @@ async fn multiple_transactions_multiple_senders() {
 
     let genesis_info = genesis_builder.generate(env.clone()).unwrap();
 
-    let blockchain = Arc::new(RwLock::new(
+    let blockchain = Arc::new(BlockchainLock::new(
         Blockchain::with_genesis(
             env.clone(),
             time,
@@ -696,7 +1088,7 @@ async fn mempool_tps() {
     // Generate the genesis and blockchain
     let genesis_info = genesis_builder.generate(env.clone()).unwrap();
 
-    let blockchain = Arc::new(RwLock::new(
+    let blockchain = Arc::new(BlockchainLock::new(
         Blockchain::with_genesis(
             env.clone(),
             time,
@@ -779,7 +1171,7 @@ async fn multiple_start_stop() {
     // Generate the genesis and blockchain
     let genesis_info = genesis_builder.generate(env.clone()).unwrap();
 
-    let blockchain = Arc::new(RwLock::new(
+    let blockchain = Arc::new(BlockchainLock::new(
         Blockchain::with_genesis(
             env.clone(),
             time,
@@ -918,7 +1310,7 @@ async fn mempool_update() {
     // Generate the genesis and blockchain
     let genesis_info = genesis_builder.generate(env.clone()).unwrap();
 
-    let blockchain = Arc::new(RwLock::new(
+    let blockchain = Arc::new(BlockchainLock::new(
         Blockchain::with_genesis(
             env.clone(),
             time,
@@ -975,3 +1367,171 @@ async fn mempool_update() {
         );
     }
 }
+
+/// Number of transactions of reverted blocks that are re-validated at once, see
+/// `RevalidationQueue::BATCH_SIZE`.
+const REVALIDATION_BATCH_SIZE: usize = 500;
+
+/// Creates a blockchain and a reverted block with `num_txns` transactions that are valid on it.
+fn reverted_block(num_txns: usize) -> (Arc<BlockchainLock>, Vec<(Blake2bHash, Block)>) {
+    let mut rng = StdRng::seed_from_u64(0);
+    let time = Arc::new(OffsetTime::new());
+    let env = VolatileEnvironment::new(10).unwrap();
+    let mut genesis_builder = GenesisBuilder::default();
+
+    let recipient_accounts = generate_accounts(vec![0; num_txns], &mut genesis_builder, false);
+    let sender_accounts = generate_accounts(vec![1000; num_txns], &mut genesis_builder, true);
+    let reverted_transactions = (0..num_txns)
+        .map(|i| TestTransaction {
+            fee: (i + 1) as u64,
+            value: 100,
+            recipient: recipient_accounts[i].clone(),
+            sender: sender_accounts[i].clone(),
+        })
+        .collect();
+    let (txns, _) = generate_transactions(reverted_transactions, true);
+
+    genesis_builder.with_genesis_validator(
+        Address::from(&SchnorrKeyPair::generate(&mut rng)),
+        SchnorrPublicKey::from([0u8; 32]),
+        BlsKeyPair::generate(&mut rng).public_key,
+        Address::default(),
+    );
+    let genesis_info = genesis_builder.generate(env.clone()).unwrap();
+    let blockchain = Arc::new(BlockchainLock::new(
+        Blockchain::with_genesis(
+            env,
+            time,
+            NetworkId::UnitAlbatross,
+            genesis_info.block,
+            genesis_info.accounts,
+        )
+        .unwrap(),
+    ));
+
+    let reverted_blocks = vec![(Blake2bHash::default(), create_dummy_micro_block(Some(txns)))];
+    (blockchain, reverted_blocks)
+}
+
+#[tokio::test]
+async fn mempool_update_revalidates_reverted_transactions_in_batches() {
+    let num_txns = REVALIDATION_BATCH_SIZE + 100;
+    let (blockchain, reverted_blocks) = reverted_block(num_txns);
+    let mempool = Mempool::new(blockchain, MempoolConfig::default());
+
+    // The first batch is re-validated right away.
+    mempool.mempool_update(&[], &reverted_blocks);
+    assert_eq!(mempool.num_transactions(), REVALIDATION_BATCH_SIZE);
+
+    // The remaining transactions are re-validated by a background task.
+    for _ in 0..100 {
+        if mempool.num_transactions() == num_txns {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(mempool.num_transactions(), num_txns);
+}
+
+#[test]
+fn mempool_update_revalidates_reverted_transactions_without_runtime() {
+    let num_txns = REVALIDATION_BATCH_SIZE + 100;
+    let (blockchain, reverted_blocks) = reverted_block(num_txns);
+    let mempool = Mempool::new(blockchain, MempoolConfig::default());
+
+    // Without a runtime to process the queue in the background, all batches are processed at once.
+    mempool.mempool_update(&[], &reverted_blocks);
+    assert_eq!(mempool.num_transactions(), num_txns);
+}
+
+#[tokio::test]
+async fn mempool_update_defers_revalidation_during_production_window() {
+    let num_txns = 10;
+    let (blockchain, reverted_blocks) = reverted_block(num_txns);
+    let mempool = Mempool::new(blockchain, MempoolConfig::default());
+
+    // While our validator produces a block, none of the transactions are re-validated.
+    mempool.production_window().open(Duration::from_millis(200));
+    mempool.mempool_update(&[], &reverted_blocks);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(mempool.num_transactions(), 0);
+
+    // They are re-validated once the window closed.
+    for _ in 0..100 {
+        if mempool.num_transactions() == num_txns {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(mempool.num_transactions(), num_txns);
+}
+
+#[tokio::test]
+async fn mempool_resyncs_after_the_network_reconnected() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut genesis_builder = GenesisBuilder::default();
+    let recipient_accounts = generate_accounts(vec![0; 1], &mut genesis_builder, false);
+    let sender_accounts = generate_accounts(vec![10000; 1], &mut genesis_builder, true);
+    let (txns, _) = generate_transactions(
+        vec![TestTransaction {
+            fee: 0,
+            value: 10,
+            recipient: recipient_accounts[0].clone(),
+            sender: sender_accounts[0].clone(),
+        }],
+        true,
+    );
+
+    genesis_builder.with_genesis_validator(
+        Address::from(&SchnorrKeyPair::generate(&mut rng)),
+        SchnorrPublicKey::from([0u8; 32]),
+        BlsKeyPair::generate(&mut rng).public_key,
+        Address::default(),
+    );
+    let genesis_info = genesis_builder
+        .generate(VolatileEnvironment::new(10).unwrap())
+        .unwrap();
+
+    let new_mempool = || {
+        let blockchain = Arc::new(BlockchainLock::new(
+            Blockchain::with_genesis(
+                VolatileEnvironment::new(10).unwrap(),
+                Arc::new(OffsetTime::new()),
+                NetworkId::UnitAlbatross,
+                genesis_info.block.clone(),
+                genesis_info.accounts.clone(),
+            )
+            .unwrap(),
+        ));
+        let config = MempoolConfig {
+            reconciliation_interval: None,
+            ..Default::default()
+        };
+        Mempool::new(blockchain, config)
+    };
+    let mempool1 = new_mempool();
+    let mempool2 = new_mempool();
+
+    // The networks are connected before the executors start, so that there is no sync on connect.
+    let mut hub = MockHub::new();
+    let network1 = Arc::new(hub.new_network());
+    let network2 = Arc::new(hub.new_network());
+    network1.dial_mock(&network2);
+    mempool1.start_executor(Arc::clone(&network1)).await;
+    mempool2.start_executor(Arc::clone(&network2)).await;
+
+    // The transaction only gets into the first mempool and isn't gossiped.
+    mempool1.add_transaction(txns[0].clone()).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(mempool2.num_transactions(), 0);
+
+    // Once the network reconnected, the second mempool requests it.
+    network2.reconnected();
+    for _ in 0..100 {
+        if mempool2.num_transactions() == 1 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert!(mempool2.contains_transaction_by_hash(&txns[0].hash()));
+}
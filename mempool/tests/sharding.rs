@@ -0,0 +1,83 @@
+use std::time::Duration;
+
+use futures::{Stream, StreamExt};
+
+use nimiq_keys::Address;
+use nimiq_mempool::mempool::TransactionTopic;
+use nimiq_mempool::sharding::{
+    publish_to_shard, subscribe_shards, transaction_shard, NUM_TRANSACTION_SHARDS,
+};
+use nimiq_network_interface::network::Network;
+use nimiq_network_mock::MockHub;
+use nimiq_primitives::coin::Coin;
+use nimiq_primitives::networks::NetworkId;
+use nimiq_transaction::Transaction;
+
+#[test]
+fn it_assigns_transactions_to_shards_by_sender_prefix() {
+    let mut bytes = [0u8; Address::SIZE];
+
+    for shard in 0..NUM_TRANSACTION_SHARDS {
+        bytes[0] = shard << 4;
+        assert_eq!(transaction_shard(&Address::from(bytes)), shard);
+
+        bytes[0] = (shard << 4) | 0x0f;
+        assert_eq!(transaction_shard(&Address::from(bytes)), shard);
+    }
+}
+
+/// Returns whether the stream yields an item within a short time.
+async fn receives<S: Stream + Unpin>(stream: &mut S) -> bool {
+    tokio::time::timeout(Duration::from_millis(100), stream.next())
+        .await
+        .is_ok()
+}
+
+#[tokio::test]
+async fn it_delivers_transactions_only_to_subscribers_of_their_shard() {
+    let mut hub = MockHub::new();
+    let publisher = hub.new_network();
+    let own_shard = hub.new_network();
+    let other_shard = hub.new_network();
+    let legacy = hub.new_network();
+    for network in [&own_shard, &other_shard, &legacy] {
+        network.dial_mock(&publisher);
+    }
+
+    let mut sender = [0u8; Address::SIZE];
+    sender[0] = 0x3a;
+    let transaction = Transaction::new_basic(
+        Address::from(sender),
+        Address::default(),
+        Coin::from_u64_unchecked(10),
+        Coin::ZERO,
+        1,
+        NetworkId::UnitAlbatross,
+    );
+    assert_eq!(transaction_shard(&transaction.sender), 3);
+
+    let mut own_shard_txns = subscribe_shards(&own_shard, &[3], false).await.unwrap();
+    let mut other_shard_txns = subscribe_shards(&other_shard, &[4], false).await.unwrap();
+    let mut legacy_txns = subscribe_shards(&legacy, &[4], true).await.unwrap();
+
+    // Only the subscriber of the sender's shard receives the transaction.
+    publish_to_shard(&publisher, transaction.clone())
+        .await
+        .unwrap();
+    let (received, _) = tokio::time::timeout(Duration::from_secs(1), own_shard_txns.next())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(received, transaction);
+    assert!(!receives(&mut other_shard_txns).await);
+    assert!(!receives(&mut legacy_txns).await);
+
+    // Transactions on the unsharded topic only reach subscribers of the legacy topic.
+    publisher
+        .publish::<TransactionTopic>(transaction.clone())
+        .await
+        .unwrap();
+    assert!(receives(&mut legacy_txns).await);
+    assert!(!receives(&mut own_shard_txns).await);
+    assert!(!receives(&mut other_shard_txns).await);
+}
@@ -0,0 +1,13 @@
+use nimiq_hash::Blake2bHash;
+use nimiq_mempool::sync::short_id;
+
+#[test]
+fn short_ids_depend_on_the_salt() {
+    let hash = Blake2bHash::default();
+    assert_eq!(short_id(1, &hash), short_id(1, &hash));
+    assert_ne!(short_id(1, &hash), short_id(2, &hash));
+    assert_ne!(
+        short_id(1, &hash),
+        short_id(1, &Blake2bHash::from([1u8; 32]))
+    );
+}
@@ -1,7 +1,11 @@
+#[macro_use]
+extern crate beserial_derive;
+
 pub mod message;
 pub mod network;
 pub mod peer;
 pub mod peer_map;
+pub mod recording;
 pub mod request_response;
 
 pub mod prelude {
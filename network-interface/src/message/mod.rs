@@ -4,12 +4,17 @@ use std::io::{Cursor, Read, Seek, SeekFrom};
 use derive_more::{AsMut, AsRef, Display, From, Into};
 
 use beserial::{uvar, Deserialize, ReadBytesExt, Serialize, SerializingError, WriteBytesExt};
+use bytes::{Buf, Bytes};
 use futures::{AsyncRead, AsyncReadExt};
 use nimiq_utils::crc::Crc32Computer;
 
 use crate::message::crc::ReaderComputeCrc32;
 
 mod crc;
+pub mod registry;
+mod serialized;
+
+pub use self::serialized::SerializedMessage;
 
 #[derive(
     Copy, Clone, Debug, From, Into, AsRef, AsMut, Display, Hash, PartialEq, Eq, PartialOrd, Ord,
@@ -79,6 +84,17 @@ pub trait Message:
         serialized_size
     }
 
+    /// Deserializes the message body from a buffer that was received from the network. Messages
+    /// that are kept in serialized form take the buffer without copying it.
+    fn deserialize_from_bytes(data: Bytes) -> Result<Self, SerializingError> {
+        Deserialize::deserialize(&mut data.reader())
+    }
+
+    /// Serializes the message body into a buffer that can be shared, e.g. to remember it.
+    fn serialize_to_bytes(&self) -> Bytes {
+        Bytes::from(self.serialize_to_vec())
+    }
+
     fn deserialize_message<R: ReadBytesExt>(reader: &mut R) -> Result<Self, SerializingError> {
         // Initialize CRC reader.
         let mut crc32_reader = ReaderComputeCrc32::new(reader);
@@ -99,7 +115,22 @@ pub trait Message:
         let checksum: u32 = Deserialize::deserialize(&mut crc32_reader)?;
         crc32_reader.at_checksum = false;
 
-        let message: Self = Deserialize::deserialize(&mut crc32_reader)?;
+        // Only read up to the end of this message.
+        let body_length = (length as usize)
+            .checked_sub(crc32_reader.length)
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, "Incorrect message length")
+            })?;
+        let message: Self = {
+            let mut body = Read::take(&mut crc32_reader, body_length as u64);
+            let message = Deserialize::deserialize(&mut body)?;
+
+            // XXX Consume any leftover bytes in the message before computing the checksum.
+            // This is consistent with the JS implementation. Leftover bytes are fields that were
+            // added by a newer version of the message, which we don't know about.
+            body.read_to_end(&mut Vec::new())?;
+            message
+        };
 
         if length as usize != crc32_reader.length {
             return Err(
@@ -107,15 +138,6 @@ pub trait Message:
             );
         }
 
-        // XXX Consume any leftover bytes in the message before computing the checksum.
-        // This is consistent with the JS implementation.
-        let remaining_length = crc32_reader.read_to_end(&mut Vec::new()).unwrap();
-        if remaining_length > 0 {
-            return Err(
-                io::Error::new(io::ErrorKind::InvalidData, "Incorrect message length").into(),
-            );
-        }
-
         let crc_comp = crc32_reader.crc32.result();
         if crc_comp != checksum {
             return Err(io::Error::new(
@@ -129,6 +151,27 @@ pub trait Message:
     }
 }
 
+/// Adds the message header to an already serialized message body, i.e. the result is the same as
+/// calling `serialize_message` on the deserialized message.
+pub fn frame_message(type_id: u64, body: &[u8]) -> Vec<u8> {
+    let ty = uvar::from(type_id);
+    let serialized_size = (4 + 4 + 4 + ty.serialized_size() + body.len()) as u32;
+
+    let mut v = Vec::with_capacity(serialized_size as usize);
+    MAGIC.serialize(&mut v).unwrap();
+    ty.serialize(&mut v).unwrap();
+    serialized_size.serialize(&mut v).unwrap();
+    let checksum_start = v.len();
+    0u32.serialize(&mut v).unwrap(); // crc32 placeholder
+    v.extend_from_slice(body);
+
+    // Write checksum to placeholder.
+    let checksum = Crc32Computer::default().update(v.as_slice()).result();
+    v[checksum_start..(4 + checksum_start)].clone_from_slice(&checksum.to_be_bytes());
+
+    v
+}
+
 pub fn peek_type(buffer: &[u8]) -> Result<u64, SerializingError> {
     let mut c = Cursor::new(buffer);
 
@@ -185,8 +228,36 @@ pub async fn read_message<R: AsyncRead + Unpin>(
 
 pub trait RequestMessage: Message {
     fn set_request_identifier(&mut self, request_identifier: u32);
+
+    /// Attaches a trace ID to the request. Requests that can't carry a trace ID ignore it.
+    fn set_trace_id(&mut self, _trace_id: TraceId) {}
 }
 
 pub trait ResponseMessage: Message {
     fn get_request_identifier(&self) -> u32;
+
+    /// Returns the trace ID the request was sent with, if any.
+    fn get_trace_id(&self) -> Option<TraceId> {
+        None
+    }
+}
+
+/// Correlates the handling of a request on the serving node with the request on the requesting
+/// node, e.g. to find the logs of a slow sync request on both sides.
+///
+/// Requests carry it as an optional `#[beserial(trailing)]` field, so nodes that don't know the
+/// field ignore it as leftover bytes.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq, Serialize, Deserialize, Schema)]
+pub struct TraceId(pub u64);
+
+impl TraceId {
+    pub fn random() -> Self {
+        TraceId(rand::random())
+    }
+}
+
+impl std::fmt::Display for TraceId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
 }
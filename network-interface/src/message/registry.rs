@@ -0,0 +1,385 @@
+//! Registries of the network message types and gossipsub topics.
+//!
+//! Every crate that defines network messages registers them with [`register_messages!`], every
+//! crate that defines topics registers them with [`register_topics!`]. The registries are used to
+//! verify that message type IDs and topic names are unique and that the wire format of the
+//! messages doesn't change by accident.
+//!
+//! The registries of all crates are combined with [`network_registry!`] into the registry of the
+//! client, which fails to compile if type IDs or topic names collide between crates. The schema of
+//! the combined registry can be exported as JSON, so other implementations and debugging proxies
+//! can decode the messages.
+
+use std::fmt::{Debug, Write};
+
+use beserial::Deserialize;
+use serde::Serialize;
+
+pub use beserial::schema::Definitions;
+pub use beserial::{Schema, TypeSchema};
+
+use super::Message;
+
+/// Describes a registered message type.
+#[derive(Clone, Copy)]
+pub struct MessageSchema {
+    pub type_id: u64,
+    pub name: &'static str,
+    /// Deserializes a message body and serializes the message again. Returns `None` if the data
+    /// isn't a valid message body.
+    pub round_trip: fn(&[u8]) -> Option<Vec<u8>>,
+    /// Returns the serialization of a fixed sample message, if the registration provides one.
+    pub sample: Option<fn() -> Vec<u8>>,
+    /// The [`Schema::name`] of the message type.
+    pub type_name: fn() -> String,
+    /// Adds the schemas of the message type and of all types it refers to.
+    pub collect: fn(&mut Definitions),
+    /// Deserializes a message body and pretty prints it. Returns `None` if the data isn't a valid
+    /// message body.
+    pub describe: fn(&[u8]) -> Option<String>,
+}
+
+impl std::fmt::Debug for MessageSchema {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MessageSchema")
+            .field("type_id", &self.type_id)
+            .field("name", &self.name)
+            .finish()
+    }
+}
+
+pub fn round_trip<M: Message>(data: &[u8]) -> Option<Vec<u8>> {
+    let message = M::deserialize_from_vec(data).ok()?;
+    let serialized = message.serialize_to_vec();
+    debug_assert_eq!(message.serialized_size(), serialized.len());
+    Some(serialized)
+}
+
+pub fn serialize<M: Message>(message: M) -> Vec<u8> {
+    message.serialize_to_vec()
+}
+
+pub fn describe<T: Deserialize + Debug>(data: &[u8]) -> Option<String> {
+    let item = T::deserialize_from_vec(data).ok()?;
+    Some(format!("{:#?}", item))
+}
+
+/// Returns all type IDs that are used by more than one of the given message types, together with
+/// the names of those message types.
+pub fn duplicate_type_ids(schemas: &[MessageSchema]) -> Vec<(u64, Vec<&'static str>)> {
+    let mut duplicates: Vec<(u64, Vec<&'static str>)> = vec![];
+    for (i, schema) in schemas.iter().enumerate() {
+        if duplicates
+            .iter()
+            .any(|(type_id, _)| *type_id == schema.type_id)
+        {
+            continue;
+        }
+        let names: Vec<&'static str> = schemas[i..]
+            .iter()
+            .filter(|other| other.type_id == schema.type_id)
+            .map(|other| other.name)
+            .collect();
+        if names.len() > 1 {
+            duplicates.push((schema.type_id, names));
+        }
+    }
+    duplicates
+}
+
+/// Describes a registered gossipsub topic.
+#[derive(Clone, Copy, Debug)]
+pub struct TopicSchema {
+    pub name: &'static str,
+    /// Name of the type that implements the topic.
+    pub topic: &'static str,
+    pub buffer_size: usize,
+    pub validate: bool,
+    /// The [`Schema::name`] of the type of the items published on the topic.
+    pub item_type: fn() -> String,
+    /// Adds the schemas of the item type and of all types it refers to.
+    pub collect_item: fn(&mut Definitions),
+    /// Deserializes an item of the topic and pretty prints it. Returns `None` if the data isn't a
+    /// valid item.
+    pub describe_item: fn(&[u8]) -> Option<String>,
+}
+
+/// The message types and topics of all crates a client is made of.
+#[derive(Clone, Copy, Debug)]
+pub struct NetworkRegistry {
+    pub messages: &'static [&'static [MessageSchema]],
+    pub topics: &'static [&'static [TopicSchema]],
+}
+
+impl NetworkRegistry {
+    pub fn messages(&self) -> impl Iterator<Item = &'static MessageSchema> {
+        self.messages.iter().flat_map(|messages| messages.iter())
+    }
+
+    pub fn topics(&self) -> impl Iterator<Item = &'static TopicSchema> {
+        self.topics.iter().flat_map(|topics| topics.iter())
+    }
+
+    pub fn message(&self, type_id: u64) -> Option<&'static MessageSchema> {
+        self.messages().find(|schema| schema.type_id == type_id)
+    }
+
+    pub fn topic(&self, name: &str) -> Option<&'static TopicSchema> {
+        self.topics().find(|schema| schema.name == name)
+    }
+
+    /// Returns the schema of all message types and topics, sorted by type ID and topic name,
+    /// together with the schemas of all types they refer to.
+    pub fn schema(&self) -> NetworkSchema {
+        let mut definitions = Definitions::new();
+
+        let mut messages: Vec<MessageExport> = self
+            .messages()
+            .map(|schema| {
+                (schema.collect)(&mut definitions);
+                MessageExport {
+                    type_id: schema.type_id,
+                    name: schema.name,
+                    ty: (schema.type_name)(),
+                }
+            })
+            .collect();
+        messages.sort_by_key(|message| message.type_id);
+
+        let mut topics: Vec<TopicExport> = self
+            .topics()
+            .map(|schema| {
+                (schema.collect_item)(&mut definitions);
+                TopicExport {
+                    name: schema.name,
+                    topic: schema.topic,
+                    item: (schema.item_type)(),
+                }
+            })
+            .collect();
+        topics.sort_by_key(|topic| topic.name);
+
+        NetworkSchema {
+            messages,
+            topics,
+            definitions,
+        }
+    }
+
+    /// Returns the schema of all message types and topics as pretty printed JSON.
+    pub fn schema_json(&self) -> String {
+        serde_json::to_string_pretty(&self.schema()).expect("Failed to serialize network schema")
+    }
+}
+
+/// The exported schema of a [`NetworkRegistry`].
+#[derive(Clone, Debug, Serialize)]
+pub struct NetworkSchema {
+    pub messages: Vec<MessageExport>,
+    pub topics: Vec<TopicExport>,
+    /// The schemas of the message types, the item types and all types they refer to, by their
+    /// [`Schema::name`].
+    pub definitions: Definitions,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageExport {
+    pub type_id: u64,
+    pub name: &'static str,
+    /// The name of the message type in the definitions.
+    #[serde(rename = "type")]
+    pub ty: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct TopicExport {
+    pub name: &'static str,
+    pub topic: &'static str,
+    /// The name of the item type in the definitions.
+    pub item: String,
+}
+
+/// Panics if a message type ID is used more than once in the given registries. Used in constant
+/// evaluation to detect collisions at compile time.
+pub const fn assert_unique_type_ids(registries: &[&[MessageSchema]]) {
+    let mut i = 0;
+    while i < registries.len() {
+        let mut j = 0;
+        while j < registries[i].len() {
+            let type_id = registries[i][j].type_id;
+
+            // Compare with all following message types, in the same and the following registries.
+            let mut k = i;
+            let mut l = j + 1;
+            while k < registries.len() {
+                while l < registries[k].len() {
+                    if registries[k][l].type_id == type_id {
+                        panic!("Duplicate message type ID");
+                    }
+                    l += 1;
+                }
+                k += 1;
+                l = 0;
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+}
+
+/// Panics if a topic name is used more than once in the given registries. Used in constant
+/// evaluation to detect collisions at compile time.
+pub const fn assert_unique_topic_names(registries: &[&[TopicSchema]]) {
+    let mut i = 0;
+    while i < registries.len() {
+        let mut j = 0;
+        while j < registries[i].len() {
+            let name = registries[i][j].name;
+
+            let mut k = i;
+            let mut l = j + 1;
+            while k < registries.len() {
+                while l < registries[k].len() {
+                    if str_eq(registries[k][l].name, name) {
+                        panic!("Duplicate topic name");
+                    }
+                    l += 1;
+                }
+                k += 1;
+                l = 0;
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+}
+
+const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Renders the wire format snapshot of the given message types. Every line contains the type ID
+/// and the hex encoded sample of a message type (or `-` if it has no sample), sorted by type ID.
+pub fn snapshot(schemas: &[MessageSchema]) -> String {
+    let mut schemas = schemas.to_vec();
+    schemas.sort_by_key(|schema| schema.type_id);
+
+    let mut snapshot = String::new();
+    for schema in schemas {
+        let sample = match schema.sample {
+            Some(sample) => hex::encode(sample()),
+            None => "-".to_string(),
+        };
+        writeln!(snapshot, "{} {}", schema.type_id, sample).unwrap();
+    }
+    snapshot
+}
+
+/// Registers network message types in a constant list of [`MessageSchema`]s. Optionally, a sample
+/// message can be given for a type, which is part of the wire format snapshot. The types must
+/// implement [`Schema`].
+///
+/// Fails to compile if two of the registered types use the same type ID.
+///
+/// ```ignore
+/// register_messages!(pub MESSAGES = [
+///     RequestHead => RequestHead { request_identifier: 1 },
+///     HeadResponse,
+/// ]);
+/// ```
+#[macro_export]
+macro_rules! register_messages {
+    (@sample $ty:ty) => {
+        None
+    };
+    (@sample $ty:ty, $sample:expr) => {
+        Some(|| $crate::message::registry::serialize::<$ty>($sample))
+    };
+    ($(#[$attr:meta])* $vis:vis $name:ident = [$($ty:ty $(=> $sample:expr)?),* $(,)?]) => {
+        $(#[$attr])*
+        $vis const $name: &[$crate::message::registry::MessageSchema] = &[
+            $(
+                $crate::message::registry::MessageSchema {
+                    type_id: <$ty as $crate::message::Message>::TYPE_ID,
+                    name: stringify!($ty),
+                    round_trip: $crate::message::registry::round_trip::<$ty>,
+                    sample: $crate::register_messages!(@sample $ty $(, $sample)?),
+                    type_name: <$ty as $crate::message::registry::Schema>::name,
+                    collect: <$ty as $crate::message::registry::Schema>::collect,
+                    describe: $crate::message::registry::describe::<$ty>,
+                },
+            )*
+        ];
+
+        const _: () = $crate::message::registry::assert_unique_type_ids(&[$name]);
+    };
+}
+
+/// Registers gossipsub topics in a constant list of [`TopicSchema`]s. The items of the topics must
+/// implement [`Schema`].
+///
+/// Fails to compile if two of the registered topics use the same name.
+///
+/// ```ignore
+/// register_topics!(pub TOPICS = [BlockTopic, TransactionTopic]);
+/// ```
+#[macro_export]
+macro_rules! register_topics {
+    ($(#[$attr:meta])* $vis:vis $name:ident = [$($ty:ty),* $(,)?]) => {
+        $(#[$attr])*
+        $vis const $name: &[$crate::message::registry::TopicSchema] = &[
+            $(
+                $crate::message::registry::TopicSchema {
+                    name: <$ty as $crate::network::Topic>::NAME,
+                    topic: stringify!($ty),
+                    buffer_size: <$ty as $crate::network::Topic>::BUFFER_SIZE,
+                    validate: <$ty as $crate::network::Topic>::VALIDATE,
+                    item_type: <<$ty as $crate::network::Topic>::Item as $crate::message::registry::Schema>::name,
+                    collect_item: <<$ty as $crate::network::Topic>::Item as $crate::message::registry::Schema>::collect,
+                    describe_item: $crate::message::registry::describe::<<$ty as $crate::network::Topic>::Item>,
+                },
+            )*
+        ];
+
+        const _: () = $crate::message::registry::assert_unique_topic_names(&[$name]);
+    };
+}
+
+/// Combines the message and topic registries of several crates into a [`NetworkRegistry`].
+///
+/// Fails to compile if a message type ID or a topic name is used by more than one crate.
+///
+/// ```ignore
+/// network_registry!(pub REGISTRY = {
+///     messages: [nimiq_consensus::messages::MESSAGES],
+///     topics: [nimiq_consensus::TOPICS, nimiq_mempool::TOPICS],
+/// });
+/// ```
+#[macro_export]
+macro_rules! network_registry {
+    ($vis:vis $name:ident = {
+        messages: [$($messages:path),* $(,)?],
+        topics: [$($topics:path),* $(,)?] $(,)?
+    }) => {
+        $vis const $name: $crate::message::registry::NetworkRegistry =
+            $crate::message::registry::NetworkRegistry {
+                messages: &[$($messages),*],
+                topics: &[$($topics),*],
+            };
+
+        const _: () = $crate::message::registry::assert_unique_type_ids($name.messages);
+        const _: () = $crate::message::registry::assert_unique_topic_names($name.topics);
+    };
+}
@@ -0,0 +1,146 @@
+use std::fmt;
+use std::io::Read;
+use std::marker::PhantomData;
+
+use bytes::Bytes;
+
+use beserial::{Deserialize, ReadBytesExt, Serialize, SerializingError, WriteBytesExt};
+
+use crate::message::Message;
+
+/// A message whose body is already serialized.
+///
+/// Large responses, e.g. history chunks, are kept in this form, so that they can be remembered
+/// and sent to several peers without serializing them again. On the wire, it is the same as the
+/// message `M` itself. Received messages wrap the buffer of the network without copying it.
+pub struct SerializedMessage<M> {
+    body: Bytes,
+    _message: PhantomData<fn() -> M>,
+}
+
+impl<M: Message> SerializedMessage<M> {
+    pub fn new(message: &M) -> Self {
+        Self::from_bytes(message.serialize_to_bytes())
+    }
+
+    /// Wraps a serialized message body. The body isn't checked until it is deserialized.
+    pub fn from_bytes(body: Bytes) -> Self {
+        SerializedMessage {
+            body,
+            _message: PhantomData,
+        }
+    }
+
+    pub fn body(&self) -> &Bytes {
+        &self.body
+    }
+
+    /// Deserializes the message.
+    pub fn decode(&self) -> Result<M, SerializingError> {
+        M::deserialize_from_bytes(self.body.clone())
+    }
+}
+
+impl<M> Clone for SerializedMessage<M> {
+    fn clone(&self) -> Self {
+        SerializedMessage {
+            body: self.body.clone(),
+            _message: PhantomData,
+        }
+    }
+}
+
+impl<M> fmt::Debug for SerializedMessage<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SerializedMessage")
+            .field("message", &std::any::type_name::<M>())
+            .field("length", &self.body.len())
+            .finish()
+    }
+}
+
+impl<M> Serialize for SerializedMessage<M> {
+    fn serialize<W: WriteBytesExt>(&self, writer: &mut W) -> Result<usize, SerializingError> {
+        writer.write_all(&self.body)?;
+        Ok(self.body.len())
+    }
+
+    fn serialized_size(&self) -> usize {
+        self.body.len()
+    }
+}
+
+impl<M> Deserialize for SerializedMessage<M> {
+    /// Takes the remainder of the reader as the message body.
+    fn deserialize<R: ReadBytesExt>(reader: &mut R) -> Result<Self, SerializingError> {
+        let mut body = Vec::new();
+        reader.read_to_end(&mut body)?;
+        Ok(SerializedMessage {
+            body: Bytes::from(body),
+            _message: PhantomData,
+        })
+    }
+}
+
+impl<M: Message> Message for SerializedMessage<M> {
+    const TYPE_ID: u64 = M::TYPE_ID;
+
+    fn deserialize_from_bytes(data: Bytes) -> Result<Self, SerializingError> {
+        Ok(Self::from_bytes(data))
+    }
+
+    fn serialize_to_bytes(&self) -> Bytes {
+        self.body.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct TestMessage {
+        #[beserial(len_type(u32))]
+        data: Vec<u8>,
+        value: u64,
+    }
+
+    impl Message for TestMessage {
+        const TYPE_ID: u64 = 4243;
+    }
+
+    #[test]
+    fn it_is_the_same_as_the_message_on_the_wire() {
+        let message = TestMessage {
+            data: vec![42; 1000],
+            value: 7,
+        };
+        let serialized = SerializedMessage::new(&message);
+
+        let mut expected = vec![];
+        message.serialize_message(&mut expected).unwrap();
+        let mut buf = vec![];
+        serialized.serialize_message(&mut buf).unwrap();
+        assert_eq!(buf, expected);
+        assert_eq!(serialized.serialized_message_size(), expected.len());
+
+        let received =
+            SerializedMessage::<TestMessage>::deserialize_message(&mut &buf[..]).unwrap();
+        assert_eq!(received.decode().unwrap(), message);
+    }
+
+    #[test]
+    fn it_wraps_received_buffers_without_copying() {
+        let message = TestMessage {
+            data: vec![42; 1000],
+            value: 7,
+        };
+        let data = message.serialize_to_bytes();
+
+        let serialized =
+            SerializedMessage::<TestMessage>::deserialize_from_bytes(data.clone()).unwrap();
+        assert_eq!(serialized.body().as_ptr(), data.as_ptr());
+        assert_eq!(serialized.serialize_to_bytes().as_ptr(), data.as_ptr());
+        assert_eq!(serialized.decode().unwrap(), message);
+    }
+}
@@ -14,9 +14,37 @@ use beserial::{Deserialize, Serialize};
 use crate::message::Message;
 use crate::peer::*;
 
-pub enum NetworkEvent<P> {
+pub enum NetworkEvent<P: Peer> {
     PeerJoined(Arc<P>),
     PeerLeft(Arc<P>),
+    /// The network regained connectivity after losing all of its peers or dropping below the
+    /// minimum number of peers, and re-subscribed to its gossip topics. Dependent components
+    /// should resync their state.
+    Reconnected,
+    /// Dialing a peer failed. `attempts` is the number of consecutive failed attempts.
+    DialFailed {
+        peer_id: P::Id,
+        error: String,
+        attempts: usize,
+    },
+    /// A connection of a peer was refused because the peer is banned.
+    PeerBanned(P::Id),
+    /// A peer won't be dialed for a while, e.g. after too many failed dial attempts.
+    BackoffStarted(P::Id),
+    /// A peer that was backed off may be dialed again.
+    BackoffEnded(P::Id),
+}
+
+/// What happens to inbound messages of a topic when its subscription buffer is full.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// New messages are dropped until the subscriber catches up.
+    DropNew,
+    /// The oldest buffered messages are dropped to make room for new ones.
+    DropOld,
+    /// New messages are dropped and not forwarded to other peers. Gossipsub doesn't count them as
+    /// delivered, so peers sending us more than we can handle don't gain score for it.
+    Backpressure,
 }
 
 pub trait Topic {
@@ -25,26 +53,59 @@ pub trait Topic {
     const BUFFER_SIZE: usize;
     const NAME: &'static str;
     const VALIDATE: bool;
+    const OVERFLOW_POLICY: OverflowPolicy = OverflowPolicy::DropNew;
+    /// Whether the IDs of the messages seen on this topic are persisted, so messages that are
+    /// still circulating after a restart are ignored.
+    const PERSIST_SEEN: bool = false;
 }
 
 impl<P: Peer> std::fmt::Debug for NetworkEvent<P> {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        let (event_name, peer) = match self {
-            NetworkEvent::PeerJoined(peer) => ("PeerJoined", peer),
-            NetworkEvent::PeerLeft(peer) => ("PeerLeft", peer),
+        let (event_name, peer_id) = match self {
+            NetworkEvent::PeerJoined(peer) => ("PeerJoined", peer.id()),
+            NetworkEvent::PeerLeft(peer) => ("PeerLeft", peer.id()),
+            NetworkEvent::Reconnected => return f.debug_struct("Reconnected").finish(),
+            NetworkEvent::DialFailed {
+                peer_id,
+                error,
+                attempts,
+            } => {
+                return f
+                    .debug_struct("DialFailed")
+                    .field("peer_id", peer_id)
+                    .field("error", error)
+                    .field("attempts", attempts)
+                    .finish()
+            }
+            NetworkEvent::PeerBanned(peer_id) => ("PeerBanned", peer_id.clone()),
+            NetworkEvent::BackoffStarted(peer_id) => ("BackoffStarted", peer_id.clone()),
+            NetworkEvent::BackoffEnded(peer_id) => ("BackoffEnded", peer_id.clone()),
         };
 
         f.debug_struct(event_name)
-            .field("peer_id", &peer.id())
+            .field("peer_id", &peer_id)
             .finish()
     }
 }
 
-impl<P> Clone for NetworkEvent<P> {
+impl<P: Peer> Clone for NetworkEvent<P> {
     fn clone(&self) -> Self {
         match self {
             NetworkEvent::PeerJoined(peer) => NetworkEvent::PeerJoined(Arc::clone(peer)),
             NetworkEvent::PeerLeft(peer) => NetworkEvent::PeerLeft(Arc::clone(peer)),
+            NetworkEvent::Reconnected => NetworkEvent::Reconnected,
+            NetworkEvent::DialFailed {
+                peer_id,
+                error,
+                attempts,
+            } => NetworkEvent::DialFailed {
+                peer_id: peer_id.clone(),
+                error: error.clone(),
+                attempts: *attempts,
+            },
+            NetworkEvent::PeerBanned(peer_id) => NetworkEvent::PeerBanned(peer_id.clone()),
+            NetworkEvent::BackoffStarted(peer_id) => NetworkEvent::BackoffStarted(peer_id.clone()),
+            NetworkEvent::BackoffEnded(peer_id) => NetworkEvent::BackoffEnded(peer_id.clone()),
         }
     }
 }
@@ -123,6 +184,19 @@ pub trait Network: Send + Sync + 'static {
         K: AsRef<[u8]> + Send + Sync,
         V: Serialize + Send + Sync;
 
+    /// Announces in the DHT that this node can provide the value for the key.
+    async fn dht_provide<K>(&self, k: &K) -> Result<(), Self::Error>
+    where
+        K: AsRef<[u8]> + Send + Sync;
+
+    /// Returns the peers that announced in the DHT that they can provide the value for the key.
+    async fn dht_get_providers<K>(
+        &self,
+        k: &K,
+    ) -> Result<Vec<<Self::PeerType as Peer>::Id>, Self::Error>
+    where
+        K: AsRef<[u8]> + Send + Sync;
+
     async fn dial_peer(&self, peer_id: <Self::PeerType as Peer>::Id) -> Result<(), Self::Error>;
 
     async fn dial_address(&self, address: Self::AddressType) -> Result<(), Self::Error>;
@@ -133,7 +207,7 @@ pub trait Network: Send + Sync + 'static {
 // .next() To get next item of stream.
 
 /// A wrapper around `SelectAll` that automatically subscribes to new peers.
-pub struct ReceiveFromAll<T: Message, P> {
+pub struct ReceiveFromAll<T: Message, P: Peer> {
     inner: SelectAll<Pin<Box<dyn Stream<Item = (T, Arc<P>)> + Send>>>,
     event_stream:
         Pin<Box<dyn FusedStream<Item = Result<NetworkEvent<P>, BroadcastStreamRecvError>> + Send>>,
@@ -17,6 +17,8 @@ pub enum CloseReason {
     Other,
     RemoteClosed,
     Error,
+    /// The peer misbehaved. Its IP address is banned for a while.
+    MaliciousPeer,
 }
 
 #[derive(Debug, Error)]
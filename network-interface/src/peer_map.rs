@@ -97,6 +97,12 @@ where
         Self::from_peers(HashMap::new())
     }
 
+    /// Sends an event to the subscribers of the peer map, e.g. to emit network events that are not
+    /// caused by a peer joining or leaving.
+    pub fn notify(&self, event: NetworkEvent<P>) {
+        self.inner.read().notify(event);
+    }
+
     /// Adds a peer to the peer map. Returns `true` if the peer wasn't in the map yet, `false` otherwise.
     pub fn insert(&self, peer: impl Into<Arc<P>>) -> bool {
         let peer = peer.into();
@@ -146,6 +152,14 @@ where
         self.inner.read().peers.get(peer_id).map(Arc::clone)
     }
 
+    pub fn len(&self) -> usize {
+        self.inner.read().peers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.read().peers.is_empty()
+    }
+
     pub fn get_peers(&self) -> Vec<Arc<P>> {
         self.inner.read().peers.values().map(Arc::clone).collect()
     }
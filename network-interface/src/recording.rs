@@ -0,0 +1,248 @@
+//! Recording of inbound network messages.
+//!
+//! A recording starts with a short file header followed by a sequence of [`RecordedMessage`]s,
+//! each serialized with `beserial`. Recordings are written by the network implementation and can
+//! be replayed against a fresh node to reproduce consensus failures.
+
+use std::{
+    fmt::Display,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+    sync::atomic::{AtomicU64, Ordering},
+    sync::mpsc::{self, Receiver, SyncSender, TrySendError},
+    thread::{self, JoinHandle},
+    time::Instant,
+};
+
+use beserial::{Deserialize, Serialize, SerializingError};
+
+const RECORDING_MAGIC: u32 = 0x4e52_4543; // "NREC"
+const RECORDING_VERSION: u8 = 1;
+
+/// The content of a recorded message.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[repr(u8)]
+pub enum RecordedPayload {
+    /// A message received directly from a peer. `data` is the serialized message without its
+    /// header.
+    Message {
+        type_id: u64,
+        #[beserial(len_type(u32))]
+        data: Vec<u8>,
+    },
+    /// A message received on a gossipsub topic. `data` is the serialized topic item.
+    Gossip {
+        #[beserial(len_type(u8))]
+        topic: String,
+        #[beserial(len_type(u32))]
+        data: Vec<u8>,
+    },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecordedMessage {
+    /// Milliseconds since the recording was started.
+    pub timestamp: u64,
+    /// The peer the message was received from.
+    #[beserial(len_type(u8))]
+    pub peer: String,
+    pub payload: RecordedPayload,
+}
+
+/// The number of messages that can be queued for the writer thread. Messages that arrive while
+/// the queue is full are dropped, so that a slow disk can't stall the network.
+const RECORDER_QUEUE_SIZE: usize = 4096;
+
+/// Appends all inbound messages passed to it to a recording file.
+///
+/// The messages are serialized by the caller and written by a dedicated thread, which flushes
+/// them as soon as it has no more messages queued, such that the recording is usable even if the
+/// node crashes. Dropping the recorder waits until the queued messages are written.
+#[derive(Debug)]
+pub struct MessageRecorder {
+    queue: Option<SyncSender<Vec<u8>>>,
+    writer: Option<JoinHandle<()>>,
+    started: Instant,
+    dropped: AtomicU64,
+}
+
+impl MessageRecorder {
+    /// Creates a new recording at `path`, truncating any existing file.
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        RECORDING_MAGIC
+            .serialize(&mut writer)
+            .map_err(into_io_error)?;
+        RECORDING_VERSION
+            .serialize(&mut writer)
+            .map_err(into_io_error)?;
+        writer.flush()?;
+
+        let (queue, messages) = mpsc::sync_channel(RECORDER_QUEUE_SIZE);
+        let writer = thread::Builder::new()
+            .name("message-recorder".to_string())
+            .spawn(move || Self::write_messages(writer, messages))?;
+
+        Ok(Self {
+            queue: Some(queue),
+            writer: Some(writer),
+            started: Instant::now(),
+            dropped: AtomicU64::new(0),
+        })
+    }
+
+    fn write_messages(mut writer: BufWriter<File>, messages: Receiver<Vec<u8>>) {
+        while let Ok(message) = messages.recv() {
+            let mut result = writer.write_all(&message);
+            // Flush once the queue is drained.
+            while result.is_ok() {
+                match messages.try_recv() {
+                    Ok(message) => result = writer.write_all(&message),
+                    Err(_) => break,
+                }
+            }
+            if let Err(e) = result.and_then(|_| writer.flush()) {
+                log::warn!("Failed to record messages: {}", e);
+            }
+        }
+    }
+
+    pub fn record_message<P: Display>(&self, peer: &P, type_id: u64, data: &[u8]) {
+        self.record(
+            peer,
+            RecordedPayload::Message {
+                type_id,
+                data: data.to_vec(),
+            },
+        );
+    }
+
+    pub fn record_gossip<P: Display>(&self, peer: &P, topic: &str, data: &[u8]) {
+        self.record(
+            peer,
+            RecordedPayload::Gossip {
+                topic: topic.to_string(),
+                data: data.to_vec(),
+            },
+        );
+    }
+
+    fn record<P: Display>(&self, peer: &P, payload: RecordedPayload) {
+        let message = RecordedMessage {
+            timestamp: self.started.elapsed().as_millis() as u64,
+            peer: peer.to_string(),
+            payload,
+        };
+
+        let queue = self.queue.as_ref().expect("Recorder was dropped");
+        match queue.try_send(message.serialize_to_vec()) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                let dropped = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+                if dropped.is_power_of_two() {
+                    log::warn!(
+                        "Recording can't keep up, dropped {} messages so far",
+                        dropped
+                    );
+                }
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                log::warn!("Failed to record message: the writer thread stopped");
+            }
+        }
+    }
+}
+
+impl Drop for MessageRecorder {
+    fn drop(&mut self) {
+        // Closing the queue stops the writer thread once it wrote the queued messages.
+        self.queue.take();
+        if let Some(writer) = self.writer.take() {
+            writer.join().ok();
+        }
+    }
+}
+
+/// Reads the messages of a recording in the order they were recorded.
+pub struct RecordingReader<R: Read> {
+    reader: R,
+}
+
+impl RecordingReader<BufReader<File>> {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, SerializingError> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> RecordingReader<R> {
+    pub fn new(mut reader: R) -> Result<Self, SerializingError> {
+        let magic: u32 = Deserialize::deserialize(&mut reader)?;
+        if magic != RECORDING_MAGIC {
+            return Err(SerializingError::InvalidValue);
+        }
+        let version: u8 = Deserialize::deserialize(&mut reader)?;
+        if version != RECORDING_VERSION {
+            return Err(SerializingError::InvalidValue);
+        }
+
+        Ok(Self { reader })
+    }
+}
+
+impl<R: Read> Iterator for RecordingReader<R> {
+    type Item = Result<RecordedMessage, SerializingError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match RecordedMessage::deserialize(&mut self.reader) {
+            Ok(message) => Some(Ok(message)),
+            // The recording ends here. A truncated last message is expected if the node crashed
+            // while recording.
+            Err(SerializingError::IoError(e)) if e.kind() == io::ErrorKind::UnexpectedEof => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+fn into_io_error(e: SerializingError) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, e)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use super::*;
+
+    #[test]
+    fn recorded_messages_can_be_read_back() {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let path = std::env::temp_dir().join(format!("nimiq-recording-{}.bin", nanos));
+
+        let recorder = MessageRecorder::create(&path).unwrap();
+        recorder.record_message(&"peer-1", 42, &[1, 2, 3]);
+        recorder.record_gossip(&"peer-2", "blocks", &[4, 5]);
+        drop(recorder);
+
+        let messages: Vec<RecordedMessage> = RecordingReader::open(&path)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].peer, "peer-1");
+        assert!(matches!(
+            &messages[0].payload,
+            RecordedPayload::Message { type_id: 42, data } if data == &[1, 2, 3]
+        ));
+        assert_eq!(messages[1].peer, "peer-2");
+        assert!(matches!(
+            &messages[1].payload,
+            RecordedPayload::Gossip { topic, data } if topic == "blocks" && data == &[4, 5]
+        ));
+    }
+}
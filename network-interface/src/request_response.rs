@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::{Arc, Weak};
 use std::time::{Duration, Instant};
 
@@ -7,15 +8,31 @@ use futures::{
     channel::oneshot::{channel, Sender},
     future, StreamExt,
 };
+use lazy_static::lazy_static;
 use parking_lot::Mutex;
 use thiserror::Error;
 use tokio::{task::spawn, time::timeout};
+use tracing::Instrument;
 
 use crate::message::*;
 use crate::peer::*;
 
+lazy_static! {
+    /// The next request identifier of this session.
+    ///
+    /// Request identifiers are allocated from a single counter for all peers and connections,
+    /// starting at a random offset. This keeps them unique across reconnects (and most likely
+    /// across restarts), so a late response to a request of an earlier connection can't be
+    /// mistaken for the response to a new request.
+    static ref NEXT_REQUEST_IDENTIFIER: AtomicU32 = AtomicU32::new(rand::random());
+}
+
+/// Allocates a new request identifier that is unique within this session.
+pub fn next_request_identifier() -> u32 {
+    NEXT_REQUEST_IDENTIFIER.fetch_add(1, Ordering::Relaxed)
+}
+
 struct RequestResponseState<Res: ResponseMessage> {
-    current_request_identifier: u32,
     responses: HashMap<u32, Sender<Res>>,
 }
 
@@ -25,6 +42,10 @@ pub struct RequestResponse<P: Peer, Req: RequestMessage, Res: ResponseMessage> {
     peer: Arc<P>,
     state: Arc<Mutex<RequestResponseState<Res>>>,
     timeout: Duration,
+    /// Number of times a request is sent again, with the same identifier, if it times out.
+    max_retries: usize,
+    /// Whether requests carry a random trace ID, see [`RequestResponse::with_tracing`].
+    trace_requests: bool,
     _req_type: PhantomData<Req>,
 }
 
@@ -39,10 +60,11 @@ pub enum RequestError {
 }
 
 // Probably not really `Message` as types, but something that has a request identifier.
-impl<P: Peer, Req: RequestMessage, Res: ResponseMessage + 'static> RequestResponse<P, Req, Res> {
+impl<P: Peer, Req: RequestMessage + Clone, Res: ResponseMessage + 'static>
+    RequestResponse<P, Req, Res>
+{
     pub fn new(peer: Arc<P>, timeout: Duration) -> Self {
         let state = Arc::new(Mutex::new(RequestResponseState {
-            current_request_identifier: 0,
             responses: Default::default(),
         }));
 
@@ -60,6 +82,13 @@ impl<P: Peer, Req: RequestMessage, Res: ResponseMessage + 'static> RequestRespon
                         let mut state = state.lock();
                         if let Some(sender) = state.responses.remove(&request_identifier) {
                             sender.send(item).ok();
+                        } else {
+                            // The request timed out or was sent over an earlier connection.
+                            log::debug!(
+                                "Dropping stale {} response [{}]",
+                                std::any::type_name::<Res>(),
+                                request_identifier
+                            );
                         }
                     }
                     future::ready(())
@@ -70,16 +99,61 @@ impl<P: Peer, Req: RequestMessage, Res: ResponseMessage + 'static> RequestRespon
             peer,
             state,
             timeout,
+            max_retries: 0,
+            trace_requests: false,
             _req_type: PhantomData,
         }
     }
 
+    /// Returns the peer the requests are sent to.
+    pub fn peer(&self) -> &Arc<P> {
+        &self.peer
+    }
+
+    /// Sends requests that time out again up to `max_retries` times. The retries keep the request
+    /// identifier, so the peer can answer them without handling the request again.
+    pub fn with_max_retries(mut self, max_retries: usize) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Attaches a random trace ID to every request and sends it in a span with that ID. The
+    /// serving node handles the request in a span with the same ID, so the request can be
+    /// correlated across both nodes.
+    pub fn with_tracing(mut self, trace_requests: bool) -> Self {
+        self.trace_requests = trace_requests;
+        self
+    }
+
     pub async fn request(&self, mut request: Req) -> Result<Res, RequestError> {
-        // Lock state, set identifier and send out request. Also add channel to the state.
-        let (request_identifier, receiver) = {
+        // The trace ID is kept for retries, so they are correlated with the first attempt.
+        if self.trace_requests {
+            let trace_id = TraceId::random();
+            request.set_trace_id(trace_id);
+
+            let span = tracing::debug_span!(
+                "request",
+                trace_id = %trace_id,
+                request = std::any::type_name::<Req>(),
+                peer = ?self.peer.id(),
+            );
+            self.send_request(request, Some(trace_id))
+                .instrument(span)
+                .await
+        } else {
+            self.send_request(request, None).await
+        }
+    }
+
+    async fn send_request(
+        &self,
+        mut request: Req,
+        trace_id: Option<TraceId>,
+    ) -> Result<Res, RequestError> {
+        // Lock state, set identifier and add channel to the state.
+        let (request_identifier, mut receiver) = {
             let mut state = self.state.lock();
-            let request_identifier = state.current_request_identifier;
-            state.current_request_identifier += 1;
+            let request_identifier = next_request_identifier();
 
             request.set_request_identifier(request_identifier);
 
@@ -89,63 +163,175 @@ impl<P: Peer, Req: RequestMessage, Res: ResponseMessage + 'static> RequestRespon
             (request_identifier, receiver)
         };
 
-        log::trace!(
-            "-> [{}] {:?} {:#?}",
-            request_identifier,
-            self.peer.id(),
-            request
-        );
-
-        // TODO: CloseType
-        // If sending fails, remove channel and return error.
-        if let Err(e) = self
-            .peer
-            .send_or_close(request, |_| CloseReason::Other)
-            .await
-        {
-            let mut state = self.state.lock();
-            state.responses.remove(&request_identifier);
-            return Err(RequestError::SendError(e));
-        }
+        // Requests are logged with their trace ID, if any.
+        let tag = match trace_id {
+            Some(trace_id) => format!("{} {}", request_identifier, trace_id),
+            None => request_identifier.to_string(),
+        };
 
-        // Now we only have to wait for the response.
         let start = Instant::now();
-        match timeout(self.timeout, receiver).await {
-            Ok(Ok(response)) => {
-                log::trace!(
-                    "<- [{}] {:?} {:?} {:#?}",
-                    request_identifier,
-                    start.elapsed(),
-                    self.peer.id(),
-                    response
-                );
-
-                Ok(response)
-            }
-            Ok(Err(e)) => {
-                log::error!(
-                    "ReceiveError [{}] {:?} {:?} {} {}",
-                    request_identifier,
-                    start.elapsed(),
-                    self.peer.id(),
-                    std::any::type_name::<Req>(),
-                    e
-                );
-                Err(RequestError::ReceiveError)
-            }
-            Err(_) => {
-                log::error!(
-                    "Timeout [{}] {:?} {}",
-                    request_identifier,
-                    start.elapsed(),
-                    std::any::type_name::<Req>()
-                );
-
-                // Lock state and remove channel on timeout.
+        let mut retries = 0;
+        loop {
+            log::trace!("-> [{}] {:?} {:#?}", tag, self.peer.id(), request);
+
+            // TODO: CloseType
+            // If sending fails, remove channel and return error.
+            if let Err(e) = self
+                .peer
+                .send_or_close(request.clone(), |_| CloseReason::Other)
+                .await
+            {
                 let mut state = self.state.lock();
                 state.responses.remove(&request_identifier);
-                Err(RequestError::Timeout)
+                return Err(RequestError::SendError(e));
+            }
+
+            // Now we only have to wait for the response.
+            match timeout(self.timeout, &mut receiver).await {
+                Ok(Ok(response)) => {
+                    if trace_id.is_some() {
+                        log::debug!(
+                            "Traced {} [{}] answered by {:?} after {:?}",
+                            std::any::type_name::<Req>(),
+                            tag,
+                            self.peer.id(),
+                            start.elapsed()
+                        );
+                    }
+                    log::trace!(
+                        "<- [{}] {:?} {:?} {:#?}",
+                        tag,
+                        start.elapsed(),
+                        self.peer.id(),
+                        response
+                    );
+
+                    return Ok(response);
+                }
+                Ok(Err(e)) => {
+                    log::error!(
+                        "ReceiveError [{}] {:?} {:?} {} {}",
+                        tag,
+                        start.elapsed(),
+                        self.peer.id(),
+                        std::any::type_name::<Req>(),
+                        e
+                    );
+                    return Err(RequestError::ReceiveError);
+                }
+                Err(_) if retries < self.max_retries => {
+                    retries += 1;
+                    log::debug!(
+                        "Timeout [{}] {:?} {}, retrying ({}/{})",
+                        tag,
+                        start.elapsed(),
+                        std::any::type_name::<Req>(),
+                        retries,
+                        self.max_retries
+                    );
+                }
+                Err(_) => {
+                    log::error!(
+                        "Timeout [{}] {:?} {}",
+                        tag,
+                        start.elapsed(),
+                        std::any::type_name::<Req>()
+                    );
+
+                    // Lock state and remove channel on timeout.
+                    let mut state = self.state.lock();
+                    state.responses.remove(&request_identifier);
+                    return Err(RequestError::Timeout);
+                }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use beserial::{Deserialize, Serialize, SerializingError};
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct TestRequest {
+        value: u64,
+        request_identifier: u32,
+        #[beserial(trailing)]
+        trace_id: Option<TraceId>,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct OldTestRequest {
+        value: u64,
+        request_identifier: u32,
+    }
+
+    impl Message for TestRequest {
+        const TYPE_ID: u64 = 4243;
+    }
+
+    impl Message for OldTestRequest {
+        const TYPE_ID: u64 = 4243;
+    }
+
+    #[test]
+    fn it_allocates_unique_request_identifiers() {
+        let first = next_request_identifier();
+        let second = next_request_identifier();
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn it_encodes_optional_trace_ids() {
+        let old = OldTestRequest {
+            value: 42,
+            request_identifier: 7,
+        };
+        let untraced = TestRequest {
+            value: 42,
+            request_identifier: 7,
+            trace_id: None,
+        };
+        let traced = TestRequest {
+            value: 42,
+            request_identifier: 7,
+            trace_id: Some(TraceId(0x0123_4567_89ab_cdef)),
+        };
+
+        // Requests of nodes that don't know the trace ID are read as untraced.
+        assert_eq!(
+            TestRequest::deserialize_from_vec(&old.serialize_to_vec()).unwrap(),
+            untraced
+        );
+
+        let mut buf = vec![];
+        traced.serialize_message(&mut buf).unwrap();
+        assert_eq!(buf.len(), traced.serialized_message_size());
+        assert_eq!(
+            TestRequest::deserialize_message(&mut &buf[..]).unwrap(),
+            traced
+        );
+        // Nodes that don't know the trace ID skip it.
+        assert_eq!(
+            OldTestRequest::deserialize_message(&mut &buf[..]).unwrap(),
+            old
+        );
+
+        let mut buf = vec![];
+        untraced.serialize_message(&mut buf).unwrap();
+        assert_eq!(
+            OldTestRequest::deserialize_message(&mut &buf[..]).unwrap(),
+            old
+        );
+
+        // A truncated trace ID is rejected.
+        let mut truncated = traced.serialize_to_vec();
+        truncated.pop();
+        assert!(matches!(
+            TestRequest::deserialize_from_vec(&truncated),
+            Err(SerializingError::IoError(_))
+        ));
+    }
+}
@@ -1,19 +1,21 @@
 use std::collections::VecDeque;
 use std::sync::Arc;
 use std::task::{Context, Poll, Waker};
+use std::time::Duration;
 
 use libp2p::{
-    core::either::EitherError,
+    autonat, dcutr,
     gossipsub::{
-        error::GossipsubHandlerError, Gossipsub, GossipsubEvent, MessageAuthenticity,
-        PeerScoreParams, PeerScoreThresholds,
+        Gossipsub, GossipsubEvent, MessageAuthenticity, PeerScoreParams, PeerScoreThresholds,
     },
     identify::{Identify, IdentifyConfig, IdentifyEvent},
     kad::{store::MemoryStore, Kademlia, KademliaEvent},
     ping,
-    ping::{Failure, PingEvent},
+    ping::PingEvent,
+    relay::v2::{client, relay},
     swarm::{
-        NetworkBehaviour, NetworkBehaviourAction, NetworkBehaviourEventProcess, PollParameters,
+        toggle::Toggle, ConnectionHandler, IntoConnectionHandler, NetworkBehaviour,
+        NetworkBehaviourAction, NetworkBehaviourEventProcess, PollParameters,
     },
     Multiaddr, NetworkBehaviour, PeerId,
 };
@@ -24,38 +26,50 @@ use nimiq_network_interface::peer_map::ObservablePeerMap;
 use nimiq_utils::time::OffsetTime;
 
 use crate::{
-    connection_pool::{
-        behaviour::{ConnectionPoolBehaviour, ConnectionPoolEvent},
-        handler::HandlerError as ConnectionPoolError,
-    },
+    connection_pool::behaviour::{ConnectionPoolBehaviour, ConnectionPoolEvent},
     discovery::{
         behaviour::{DiscoveryBehaviour, DiscoveryEvent},
-        handler::HandlerError as DiscoveryError,
         peer_contacts::PeerContactBook,
     },
     peer::Peer,
     Config,
 };
 
-pub type NimiqNetworkBehaviourError = EitherError<
-    EitherError<
-        EitherError<
-            EitherError<EitherError<std::io::Error, DiscoveryError>, GossipsubHandlerError>,
-            std::io::Error,
-        >,
-        Failure,
-    >,
-    ConnectionPoolError,
->;
+/// How long a relayed connection may stay open. Relayed connections are only a fallback until
+/// hole punching succeeds, but they need to be able to carry sync and gossip in the meantime.
+pub(crate) const MAX_CIRCUIT_DURATION: Duration = Duration::from_secs(60 * 60);
+
+/// How many bytes may be relayed per direction of a relayed connection.
+pub(crate) const MAX_CIRCUIT_BYTES: u64 = 256 * 1024 * 1024;
+
+/// The error of the combined connection handlers of all behaviours.
+pub type NimiqNetworkBehaviourError =
+    <<<NimiqBehaviour as NetworkBehaviour>::ConnectionHandler as IntoConnectionHandler>::Handler as ConnectionHandler>::Error;
 
 #[derive(Debug)]
 pub enum NimiqEvent {
+    Autonat(autonat::Event),
+    Dcutr(dcutr::behaviour::Event),
     Dht(KademliaEvent),
     Discovery(DiscoveryEvent),
     Gossip(GossipsubEvent),
     Identify(IdentifyEvent),
     Ping(PingEvent),
     Pool(ConnectionPoolEvent),
+    Relay(relay::Event),
+    RelayClient(client::Event),
+}
+
+impl From<autonat::Event> for NimiqEvent {
+    fn from(event: autonat::Event) -> Self {
+        Self::Autonat(event)
+    }
+}
+
+impl From<dcutr::behaviour::Event> for NimiqEvent {
+    fn from(event: dcutr::behaviour::Event) -> Self {
+        Self::Dcutr(event)
+    }
 }
 
 impl From<KademliaEvent> for NimiqEvent {
@@ -94,15 +108,33 @@ impl From<ConnectionPoolEvent> for NimiqEvent {
     }
 }
 
+impl From<relay::Event> for NimiqEvent {
+    fn from(event: relay::Event) -> Self {
+        Self::Relay(event)
+    }
+}
+
+impl From<client::Event> for NimiqEvent {
+    fn from(event: client::Event) -> Self {
+        Self::RelayClient(event)
+    }
+}
+
 #[derive(NetworkBehaviour)]
 #[behaviour(out_event = "NimiqEvent", poll_method = "poll_event")]
 pub struct NimiqBehaviour {
+    pub autonat: autonat::Behaviour,
+    /// Upgrades relayed connections to direct ones by hole punching.
+    pub dcutr: dcutr::behaviour::Behaviour,
     pub dht: Kademlia<MemoryStore>,
     pub discovery: DiscoveryBehaviour,
     pub gossipsub: Gossipsub,
     pub identify: Identify,
     pub ping: ping::Behaviour,
     pub pool: ConnectionPoolBehaviour,
+    /// Only enabled if this node relays connections for other peers.
+    pub relay: Toggle<relay::Relay>,
+    pub relay_client: client::Client,
 
     #[behaviour(ignore)]
     contacts: Arc<RwLock<PeerContactBook>>,
@@ -118,7 +150,12 @@ pub struct NimiqBehaviour {
 }
 
 impl NimiqBehaviour {
-    pub fn new(config: Config, clock: Arc<OffsetTime>, peers: ObservablePeerMap<Peer>) -> Self {
+    pub fn new(
+        config: Config,
+        clock: Arc<OffsetTime>,
+        peers: ObservablePeerMap<Peer>,
+        relay_client: client::Client,
+    ) -> Self {
         let public_key = config.keypair.public();
         let peer_id = public_key.to_peer_id();
 
@@ -137,6 +174,7 @@ impl NimiqBehaviour {
             config.keypair.clone(),
             Arc::clone(&contacts),
             clock,
+            config.rng.fork(),
         );
 
         // Gossipsub behaviour
@@ -165,16 +203,42 @@ impl NimiqBehaviour {
                 .with_timeout(duration),
         );
 
+        // AutoNAT behaviour
+        // Asks the connected peers to dial us back to find out whether we are reachable.
+        let autonat = autonat::Behaviour::new(peer_id, autonat::Config::default());
+
+        // Relay behaviour
+        let relay = Toggle::from(
+            config
+                .relay_server
+                .then(|| relay::Relay::new(peer_id, relay_config())),
+        );
+
+        // Hole punching behaviour
+        let dcutr = dcutr::behaviour::Behaviour::new();
+
         // Connection pool behaviour
-        let pool = ConnectionPoolBehaviour::new(Arc::clone(&contacts), config.seeds, peers);
+        let pool = ConnectionPoolBehaviour::new(
+            Arc::clone(&contacts),
+            config.seeds,
+            peers,
+            config.recorder.clone(),
+            config.dial_priority.clone(),
+            config.rng.clone(),
+            config.seed,
+        );
 
         Self {
+            autonat,
+            dcutr,
             dht,
             discovery,
             gossipsub,
             identify,
             ping,
             pool,
+            relay,
+            relay_client,
             events: VecDeque::new(),
             contacts,
             update_scores,
@@ -231,6 +295,18 @@ impl NimiqBehaviour {
     }
 }
 
+impl NetworkBehaviourEventProcess<autonat::Event> for NimiqBehaviour {
+    fn inject_event(&mut self, event: autonat::Event) {
+        self.emit_event(event);
+    }
+}
+
+impl NetworkBehaviourEventProcess<dcutr::behaviour::Event> for NimiqBehaviour {
+    fn inject_event(&mut self, event: dcutr::behaviour::Event) {
+        self.emit_event(event);
+    }
+}
+
 impl NetworkBehaviourEventProcess<KademliaEvent> for NimiqBehaviour {
     fn inject_event(&mut self, event: KademliaEvent) {
         self.emit_event(event);
@@ -238,8 +314,9 @@ impl NetworkBehaviourEventProcess<KademliaEvent> for NimiqBehaviour {
 }
 
 impl NetworkBehaviourEventProcess<DiscoveryEvent> for NimiqBehaviour {
-    fn inject_event(&mut self, _event: DiscoveryEvent) {
+    fn inject_event(&mut self, event: DiscoveryEvent) {
         self.pool.maintain_peers();
+        self.emit_event(event);
     }
 }
 
@@ -266,3 +343,42 @@ impl NetworkBehaviourEventProcess<ConnectionPoolEvent> for NimiqBehaviour {
         self.emit_event(event);
     }
 }
+
+impl NetworkBehaviourEventProcess<relay::Event> for NimiqBehaviour {
+    fn inject_event(&mut self, event: relay::Event) {
+        self.emit_event(event);
+    }
+}
+
+impl NetworkBehaviourEventProcess<client::Event> for NimiqBehaviour {
+    fn inject_event(&mut self, event: client::Event) {
+        self.emit_event(event);
+    }
+}
+
+/// The limits of the connections we relay. The default limits (2 minutes, 128 KiB) are meant for
+/// hole punching only and are too tight for a connection that has to stay usable if hole punching
+/// fails.
+pub(crate) fn relay_config() -> relay::Config {
+    relay::Config {
+        max_circuit_duration: MAX_CIRCUIT_DURATION,
+        max_circuit_bytes: MAX_CIRCUIT_BYTES,
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relayed_connections_can_carry_sync() {
+        let config = relay_config();
+        let default = relay::Config::default();
+
+        assert_eq!(config.max_circuit_duration, MAX_CIRCUIT_DURATION);
+        assert_eq!(config.max_circuit_bytes, MAX_CIRCUIT_BYTES);
+        assert!(config.max_circuit_duration > default.max_circuit_duration);
+        assert!(config.max_circuit_bytes > default.max_circuit_bytes);
+    }
+}
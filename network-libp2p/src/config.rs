@@ -1,3 +1,4 @@
+use ip_network::IpNetwork;
 use libp2p::{
     gossipsub::{GossipsubConfig, GossipsubConfigBuilder, MessageId},
     identity::Keypair,
@@ -7,12 +8,22 @@ use libp2p::{
 use std::{
     collections::hash_map::DefaultHasher,
     hash::{Hash, Hasher},
+    path::PathBuf,
+    sync::Arc,
     time::Duration,
 };
 
+use tokio::runtime::Handle;
+
 use nimiq_hash::Blake2bHash;
+use nimiq_network_interface::recording::MessageRecorder;
+use nimiq_utils::shared_rng::SharedRng;
 
-use crate::discovery::{behaviour::DiscoveryConfig, peer_contacts::PeerContact};
+use crate::{
+    connection_pool::behaviour::DialPriority,
+    discovery::{behaviour::DiscoveryConfig, peer_contacts::PeerContact},
+    tls::TlsCertificates,
+};
 
 pub struct Config {
     pub keypair: Keypair,
@@ -21,6 +32,36 @@ pub struct Config {
     pub discovery: DiscoveryConfig,
     pub kademlia: KademliaConfig,
     pub gossipsub: GossipsubConfig,
+    /// If set, all inbound messages are recorded for later replay.
+    pub recorder: Option<Arc<MessageRecorder>>,
+    /// Reverse proxies whose forwarded headers are trusted. For inbound connections from these
+    /// addresses, the client address is taken from the forwarded headers of the websocket upgrade.
+    pub trusted_proxies: Vec<IpNetwork>,
+    /// If set, plain TCP addresses (without `/ws`) can be listened on and dialed besides
+    /// websocket addresses. This avoids the websocket framing on links between validators, browsers
+    /// still need a websocket address.
+    pub plain_tcp: bool,
+    /// If set, `/wss` addresses are listened on with these certificates.
+    pub tls: Option<Arc<TlsCertificates>>,
+    /// If set, this node relays connections to peers that are not reachable from the outside.
+    /// Only publicly reachable nodes should enable this.
+    pub relay_server: bool,
+    /// If set, the node runs as a seed node. It accepts many more connections to help peers
+    /// discover the network, but closes them after a few minutes.
+    pub seed: bool,
+    /// If set, the TCP ports of private listen addresses are mapped on the local router via
+    /// NAT-PMP or UPnP, and the resulting external addresses are advertised in our peer contact.
+    pub port_mapping: bool,
+    /// If set, decides which peers are dialed first.
+    pub dial_priority: Option<Arc<dyn DialPriority>>,
+    /// If set, the IDs of the recently seen gossipsub messages of the topics that opt in are stored
+    /// in this file, so that messages which were already seen before a restart are ignored.
+    pub seen_messages_file: Option<PathBuf>,
+    /// If set, the swarm task and the connection tasks are spawned on this runtime instead of the
+    /// runtime that creates the network, so that they can't be starved by other tasks.
+    pub runtime: Option<Handle>,
+    /// Chooses the peers and seeds that are dialed. Seeded in tests to make them reproducible.
+    pub rng: SharedRng,
 }
 
 impl Config {
@@ -64,6 +105,17 @@ impl Config {
             discovery: DiscoveryConfig::new(genesis_hash),
             kademlia,
             gossipsub,
+            recorder: None,
+            trusted_proxies: vec![],
+            plain_tcp: false,
+            tls: None,
+            relay_server: false,
+            seed: false,
+            port_mapping: false,
+            dial_priority: None,
+            seen_messages_file: None,
+            runtime: None,
+            rng: SharedRng::os(),
         }
     }
 }
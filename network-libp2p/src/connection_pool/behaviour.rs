@@ -13,6 +13,7 @@ use ip_network::IpNetwork;
 use libp2p::swarm::dial_opts::PeerCondition;
 use libp2p::{
     core::{connection::ConnectionId, multiaddr::Protocol, ConnectedPoint},
+    identity::PublicKey,
     swarm::{
         dial_opts::DialOpts, CloseConnection, ConnectionHandler, DialError, IntoConnectionHandler,
         NetworkBehaviour, NetworkBehaviourAction, NotifyHandler, PollParameters,
@@ -20,19 +21,32 @@ use libp2p::{
     Multiaddr, PeerId,
 };
 use parking_lot::RwLock;
-use rand::seq::IteratorRandom;
-use rand::thread_rng;
+use rand::{seq::IteratorRandom, Rng};
 use tokio::time::Interval;
 
 use nimiq_network_interface::{
     message::MessageType, peer::CloseReason, peer_map::ObservablePeerMap,
+    recording::MessageRecorder,
 };
+use nimiq_utils::memory::MemoryGauge;
+use nimiq_utils::rate_limit::RateLimit;
+use nimiq_utils::shared_rng::SharedRng;
 
-use crate::discovery::peer_contacts::{PeerContactBook, Services};
+use crate::discovery::peer_contacts::{PeerContactBook, PeerContactInfo, Services};
 use crate::peer::Peer;
 
 use super::handler::{ConnectionPoolHandler, HandlerInEvent, HandlerOutEvent};
 
+/// Decides which peers the connection pool prefers when choosing peers to dial.
+///
+/// Anyone can create any number of peer contacts, so the contact book alone gives no protection against a sybil
+/// attacker filling our connection slots. Implementations can use information from outside of the network, e.g. the
+/// staking contract, to prefer peers that are costly to impersonate.
+pub trait DialPriority: Send + Sync {
+    /// Returns whether the peer with the given public key is preferred.
+    fn is_preferred(&self, public_key: &PublicKey) -> bool;
+}
+
 #[derive(Clone, Debug)]
 struct ConnectionPoolLimits {
     ip_count: HashMap<IpNetwork, usize>,
@@ -49,8 +63,30 @@ struct ConnectionPoolConfig {
     ipv4_subnet_mask: u8,
     ipv6_subnet_mask: u8,
     dialing_count_max: usize,
+    /// Maximum number of dials per second, over all peers and seeds.
+    dials_per_second_max: usize,
+    /// Backoff after the first failed dial of a peer or address. It doubles with every further
+    /// failed dial, up to `dial_backoff_max`.
+    dial_backoff_base: Duration,
+    dial_backoff_max: Duration,
     retry_down_after: Duration,
     housekeeping_interval: Duration,
+    /// If set, inbound connections are closed after this time.
+    connection_lifetime: Option<Duration>,
+}
+
+impl ConnectionPoolConfig {
+    /// Seed nodes accept many more connections, but only keep them for a short time. They dial
+    /// just enough peers to follow the network themselves.
+    fn seed() -> Self {
+        Self {
+            peer_count_desired: 4,
+            peer_count_max: 20_000,
+            housekeeping_interval: Duration::from_secs(30),
+            connection_lifetime: Some(Duration::from_secs(60 * 5)), // 5 minutes
+            ..Default::default()
+        }
+    }
 }
 
 impl Default for ConnectionPoolConfig {
@@ -63,8 +99,12 @@ impl Default for ConnectionPoolConfig {
             ipv4_subnet_mask: 24,
             ipv6_subnet_mask: 96,
             dialing_count_max: 3,
+            dials_per_second_max: 5,
+            dial_backoff_base: Duration::from_secs(2),
+            dial_backoff_max: Duration::from_secs(60 * 5), // 5 minutes
             retry_down_after: Duration::from_secs(60 * 10), // 10 minutes
             housekeeping_interval: Duration::from_secs(60 * 2), // 2 minutes
+            connection_lifetime: None,
         }
     }
 }
@@ -73,20 +113,31 @@ struct ConnectionState<T> {
     dialing: BTreeSet<T>,
     connected: BTreeSet<T>,
     failed: BTreeMap<T, usize>,
+    /// Ids that failed to be dialed and the time at which they may be dialed again.
+    backoff: BTreeMap<T, Instant>,
     down: BTreeMap<T, Instant>,
+    /// Ids that are down because dialing them failed `max_failures` times, as opposed to the ones
+    /// that are down because their connection was closed.
+    failed_down: BTreeSet<T>,
     max_failures: usize,
+    backoff_base: Duration,
+    backoff_max: Duration,
     retry_down_after: Duration,
 }
 
 impl<T: Ord> ConnectionState<T> {
-    fn new(max_failures: usize, retry_down_after: Duration) -> Self {
+    fn new(max_failures: usize, config: &ConnectionPoolConfig) -> Self {
         Self {
             dialing: BTreeSet::new(),
             connected: BTreeSet::new(),
             failed: BTreeMap::new(),
+            backoff: BTreeMap::new(),
             down: BTreeMap::new(),
+            failed_down: BTreeSet::new(),
             max_failures,
-            retry_down_after,
+            backoff_base: config.dial_backoff_base,
+            backoff_max: config.dial_backoff_max,
+            retry_down_after: config.retry_down_after,
         }
     }
 
@@ -97,7 +148,9 @@ impl<T: Ord> ConnectionState<T> {
     fn mark_connected(&mut self, id: T) {
         self.dialing.remove(&id);
         self.failed.remove(&id);
+        self.backoff.remove(&id);
         self.down.remove(&id);
+        self.failed_down.remove(&id);
         self.connected.insert(id);
     }
 
@@ -105,28 +158,62 @@ impl<T: Ord> ConnectionState<T> {
         self.connected.remove(&id);
     }
 
-    fn mark_failed(&mut self, id: T) {
+    /// Marks a dial attempt as failed and returns the number of consecutive failed attempts.
+    /// The id isn't dialed again until its backoff elapsed, and it is marked as down once
+    /// `max_failures` is reached.
+    fn mark_failed<R: Rng>(&mut self, id: T, rng: &mut R) -> usize
+    where
+        T: Clone,
+    {
         self.dialing.remove(&id);
 
         // TODO Ignore failures if down?
 
-        if let Some(num_attempts) = self.failed.get_mut(&id) {
-            *num_attempts += 1;
-            if *num_attempts >= self.max_failures {
-                self.mark_down(id);
-            }
+        let num_attempts = self.failed.entry(id.clone()).or_insert(0);
+        *num_attempts += 1;
+        let num_attempts = *num_attempts;
+
+        if num_attempts >= self.max_failures {
+            self.failed_down.insert(id.clone());
+            self.mark_down(id);
         } else {
-            self.failed.insert(id, 1);
+            // Exponential backoff. The jitter keeps nodes that lost their connectivity at the
+            // same time from dialing in lockstep once it is back. It is applied before capping,
+            // so the backoff never exceeds the maximum.
+            let backoff = self
+                .backoff_base
+                .saturating_mul(1 << (num_attempts - 1).min(16))
+                .mul_f64(rng.gen_range(1.0..1.5))
+                .min(self.backoff_max);
+            self.backoff.insert(id, Instant::now() + backoff);
         }
+        num_attempts
     }
 
     fn mark_down(&mut self, id: T) {
         self.failed.remove(&id);
+        self.backoff.remove(&id);
         self.down.insert(id, Instant::now());
     }
 
     fn can_dial(&self, id: &T) -> bool {
-        !self.dialing.contains(id) && !self.connected.contains(id) && !self.down.contains_key(id)
+        !self.dialing.contains(id)
+            && !self.connected.contains(id)
+            && !self.down.contains_key(id)
+            && self
+                .backoff
+                .get(id)
+                .map_or(true, |until| *until <= Instant::now())
+    }
+
+    /// Returns when the next id is out of its backoff.
+    fn next_retry(&self) -> Option<Instant> {
+        let now = Instant::now();
+        self.backoff
+            .values()
+            .filter(|until| **until > now)
+            .min()
+            .copied()
     }
 
     fn num_dialing(&self) -> usize {
@@ -137,11 +224,31 @@ impl<T: Ord> ConnectionState<T> {
         self.connected.len()
     }
 
-    fn housekeeping(&mut self) {
+    /// Returns the ids that were down because dialing them failed and may be dialed again.
+    fn housekeeping(&mut self) -> Vec<T>
+    where
+        T: Clone,
+    {
+        // Forget the backoffs that elapsed, the number of failed attempts is kept until the id
+        // is connected.
+        let now = Instant::now();
+        self.backoff.retain(|_, until| *until > now);
+
         // Remove all down peers that we haven't dialed in a while from the `down` map to dial them again.
         let retry_down_after = self.retry_down_after;
-        self.down
-            .retain(|_, down_since| down_since.elapsed() < retry_down_after);
+        let expired: Vec<T> = self
+            .down
+            .iter()
+            .filter(|(_, down_since)| down_since.elapsed() >= retry_down_after)
+            .map(|(id, _)| id.clone())
+            .collect();
+        expired
+            .into_iter()
+            .filter(|id| {
+                self.down.remove(id);
+                self.failed_down.remove(id)
+            })
+            .collect()
     }
 }
 
@@ -149,10 +256,11 @@ impl<T> std::fmt::Display for ConnectionState<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "connected={}, dialing={}, failed={}, down={}",
+            "connected={}, dialing={}, failed={}, backoff={}, down={}",
             self.connected.len(),
             self.dialing.len(),
             self.failed.len(),
+            self.backoff.len(),
             self.down.len()
         )
     }
@@ -160,7 +268,32 @@ impl<T> std::fmt::Display for ConnectionState<T> {
 
 #[derive(Clone, Debug)]
 pub enum ConnectionPoolEvent {
-    PeerJoined { peer: Arc<Peer> },
+    PeerJoined {
+        peer: Arc<Peer>,
+    },
+    /// A peer was replaced by the peer of a direct connection that was established by hole
+    /// punching.
+    PeerLeft {
+        peer: Arc<Peer>,
+    },
+    /// Dialing a peer failed. `attempts` is the number of consecutive failed attempts.
+    DialFailed {
+        peer_id: PeerId,
+        error: String,
+        attempts: usize,
+    },
+    /// A connection of a peer was closed because its IP is banned.
+    PeerBanned {
+        peer_id: PeerId,
+    },
+    /// A peer is marked as down and won't be dialed until `retry_down_after` elapsed.
+    BackoffStarted {
+        peer_id: PeerId,
+    },
+    /// A peer that was marked as down may be dialed again.
+    BackoffEnded {
+        peer_id: PeerId,
+    },
 }
 
 type PoolNetworkBehaviourAction =
@@ -181,10 +314,36 @@ pub struct ConnectionPoolBehaviour {
     limits: ConnectionPoolLimits,
     config: ConnectionPoolConfig,
     banned: HashMap<IpNetwork, SystemTime>,
+    /// Peers that were closed for misbehaving. Their IP is banned once their connection is closed.
+    malicious_peers: HashSet<PeerId>,
+    /// The time the inbound connections were established, if they have a limited lifetime.
+    inbound_since: HashMap<PeerId, Instant>,
+    /// The connections of peers that are connected through a relay. A direct connection to such a
+    /// peer replaces the relayed one.
+    relayed: HashMap<PeerId, ConnectionId>,
     waker: Option<Waker>,
     housekeeping_timer: Interval,
 
+    /// Limits the number of dials per second.
+    dial_budget: RateLimit,
+    /// If set, peers are maintained again at this time, because dials were held back by the
+    /// budget or a backoff.
+    next_dial: Option<Instant>,
+    dial_timer: Interval,
+
     message_receivers: HashMap<MessageType, mpsc::Sender<(Bytes, Arc<Peer>)>>,
+
+    /// If set, inbound messages of all peers are recorded.
+    recorder: Option<Arc<MessageRecorder>>,
+
+    /// If set, preferred peers are dialed before any other peers.
+    dial_priority: Option<Arc<dyn DialPriority>>,
+
+    /// Counts the messages buffered by the connected peers.
+    memory: MemoryGauge,
+
+    /// Chooses the peers and seeds to dial.
+    rng: SharedRng,
 }
 
 impl ConnectionPoolBehaviour {
@@ -192,32 +351,56 @@ impl ConnectionPoolBehaviour {
         contacts: Arc<RwLock<PeerContactBook>>,
         seeds: Vec<Multiaddr>,
         peers: ObservablePeerMap<Peer>,
+        recorder: Option<Arc<MessageRecorder>>,
+        dial_priority: Option<Arc<dyn DialPriority>>,
+        rng: SharedRng,
+        seed: bool,
     ) -> Self {
         let limits = ConnectionPoolLimits {
             ip_count: HashMap::new(),
             ipv4_count: 0,
             ipv6_count: 0,
         };
-        let config = ConnectionPoolConfig::default();
+        let config = if seed {
+            ConnectionPoolConfig::seed()
+        } else {
+            ConnectionPoolConfig::default()
+        };
         let housekeeping_timer = tokio::time::interval(config.housekeeping_interval);
+        let dial_budget = RateLimit::new(config.dials_per_second_max, Duration::from_secs(1));
 
         Self {
             contacts,
             seeds,
             peers,
-            peer_ids: ConnectionState::new(2, config.retry_down_after),
-            addresses: ConnectionState::new(4, config.retry_down_after),
+            peer_ids: ConnectionState::new(2, &config),
+            addresses: ConnectionState::new(4, &config),
             actions: VecDeque::new(),
             active: false,
             limits,
             config,
             banned: HashMap::new(),
+            malicious_peers: HashSet::new(),
+            inbound_since: HashMap::new(),
+            relayed: HashMap::new(),
             waker: None,
             housekeeping_timer,
+            dial_budget,
+            next_dial: None,
+            dial_timer: tokio::time::interval(Duration::from_secs(1)),
             message_receivers: HashMap::new(),
+            recorder,
+            dial_priority,
+            memory: MemoryGauge::new(),
+            rng,
         }
     }
 
+    /// Returns the gauge that counts the messages buffered by the connected peers.
+    pub fn memory(&self) -> MemoryGauge {
+        self.memory.clone()
+    }
+
     pub fn maintain_peers(&mut self) {
         log::debug!(
             "Maintaining peers: {} | addresses: {}",
@@ -226,12 +409,15 @@ impl ConnectionPoolBehaviour {
         );
 
         // Try to maintain at least `peer_count_desired` connections.
+        self.next_dial = None;
         if self.active
             && self.peer_ids.num_connected() < self.config.peer_count_desired
             && self.peer_ids.num_dialing() < self.config.dialing_count_max
         {
             // Dial peers from the contact book.
-            for peer_id in self.choose_peers_to_dial() {
+            let peers_to_dial = self.choose_peers_to_dial(self.dial_budget.num_allowed());
+            self.dial_budget.note(peers_to_dial.len());
+            for peer_id in peers_to_dial {
                 log::debug!("Dialing peer {}", peer_id);
                 self.peer_ids.mark_dialing(peer_id);
                 let handler = self.new_handler();
@@ -244,7 +430,9 @@ impl ConnectionPoolBehaviour {
             }
 
             // Dial seeds.
-            for address in self.choose_seeds_to_dial() {
+            let seeds_to_dial = self.choose_seeds_to_dial(self.dial_budget.num_allowed());
+            self.dial_budget.note(seeds_to_dial.len());
+            for address in seeds_to_dial {
                 log::debug!("Dialing seed {}", address);
                 self.addresses.mark_dialing(address.clone());
                 let handler = self.new_handler();
@@ -253,6 +441,18 @@ impl ConnectionPoolBehaviour {
                     handler,
                 });
             }
+
+            // Try again once the budget is replenished or the next backoff elapsed.
+            let budget_exhausted = self.dial_budget.num_allowed() == 0;
+            let now = Instant::now();
+            self.next_dial = [
+                budget_exhausted.then(|| now + Duration::from_secs(1)),
+                self.peer_ids.next_retry(),
+                self.addresses.next_retry(),
+            ]
+            .into_iter()
+            .flatten()
+            .min();
         }
 
         if let Some(waker) = &self.waker {
@@ -265,44 +465,60 @@ impl ConnectionPoolBehaviour {
         self.maintain_peers();
     }
 
-    fn choose_peers_to_dial(&self) -> Vec<PeerId> {
+    fn choose_peers_to_dial(&self, budget: usize) -> Vec<PeerId> {
         let num_peers = usize::min(
             self.config.peer_count_desired - self.peer_ids.num_connected(),
             self.config.dialing_count_max - self.peer_ids.num_dialing(),
-        );
+        )
+        .min(budget);
         let contacts = self.contacts.read();
         let own_contact = contacts.get_own_contact();
         let own_peer_id = own_contact.peer_id();
 
         // TODO Services
-        contacts
+        let candidates = contacts
             .query(own_contact.protocols(), Services::all()) // TODO Services
-            .filter_map(|contact| {
+            .filter(|contact| {
                 let peer_id = contact.peer_id();
-                if peer_id != own_peer_id && self.peer_ids.can_dial(peer_id) {
-                    Some(*peer_id)
-                } else {
-                    None
-                }
-            })
-            .choose_multiple(&mut thread_rng(), num_peers)
+                peer_id != own_peer_id && self.peer_ids.can_dial(peer_id)
+            });
+
+        // Choose the preferred peers first and fill the remaining slots with random other peers.
+        let mut rng = self.rng.clone();
+        let (preferred, others): (Vec<_>, Vec<_>) =
+            candidates.partition(|contact| self.is_preferred(contact));
+
+        let mut chosen = preferred.into_iter().choose_multiple(&mut rng, num_peers);
+        let num_others = num_peers - chosen.len();
+        chosen.extend(others.into_iter().choose_multiple(&mut rng, num_others));
+
+        chosen
+            .into_iter()
+            .map(|contact| *contact.peer_id())
+            .collect()
+    }
+
+    fn is_preferred(&self, contact: &PeerContactInfo) -> bool {
+        self.dial_priority.as_ref().map_or(false, |priority| {
+            priority.is_preferred(contact.public_key())
+        })
     }
 
-    fn choose_seeds_to_dial(&self) -> Vec<Multiaddr> {
+    fn choose_seeds_to_dial(&self, budget: usize) -> Vec<Multiaddr> {
         // We prefer to connect to non-seed peers. Thus, we only choose any seeds here if we're
         // not already dialing any peers and at most one seed at a time.
         if self.peer_ids.num_dialing() > 0 || self.addresses.num_dialing() > 0 {
             return vec![];
         }
 
-        let num_seeds = 1;
+        let num_seeds = budget.min(1);
         let contacts = self.contacts.read();
         let own_addresses: HashSet<&Multiaddr> = contacts.get_own_contact().addresses().collect();
         self.seeds
             .iter()
             .filter(|address| !own_addresses.contains(address) && self.addresses.can_dial(*address))
             .cloned()
-            .choose_multiple(&mut thread_rng(), num_seeds)
+            .choose_multiple(&mut self.rng.clone(), num_seeds)
     }
 
     fn housekeeping(&mut self) {
@@ -320,9 +536,33 @@ impl ConnectionPoolBehaviour {
         }
         drop(contacts);
 
-        self.peer_ids.housekeeping();
+        for peer_id in self.peer_ids.housekeeping() {
+            self.actions
+                .push_back(NetworkBehaviourAction::GenerateEvent(
+                    ConnectionPoolEvent::BackoffEnded { peer_id },
+                ));
+        }
         self.addresses.housekeeping();
 
+        if let Some(lifetime) = self.config.connection_lifetime {
+            for peer_id in self.expired_connections(lifetime, Instant::now()) {
+                log::debug!(
+                    "Closing connection to peer {} after {:?}",
+                    peer_id,
+                    lifetime
+                );
+                self.inbound_since.remove(&peer_id);
+                self.actions
+                    .push_back(NetworkBehaviourAction::NotifyHandler {
+                        peer_id,
+                        handler: NotifyHandler::Any,
+                        event: HandlerInEvent::Close {
+                            reason: CloseReason::Other,
+                        },
+                    });
+            }
+        }
+
         for (ip, time) in self.banned.clone() {
             if time < SystemTime::now() {
                 self.banned.remove(&ip);
@@ -332,7 +572,16 @@ impl ConnectionPoolBehaviour {
         self.maintain_peers();
     }
 
-    pub fn _ban_ip(&mut self, ip: IpNetwork) {
+    /// Returns the peers whose inbound connection is older than `lifetime`.
+    fn expired_connections(&self, lifetime: Duration, now: Instant) -> Vec<PeerId> {
+        self.inbound_since
+            .iter()
+            .filter(|(_, since)| now.saturating_duration_since(**since) >= lifetime)
+            .map(|(peer_id, _)| *peer_id)
+            .collect()
+    }
+
+    pub fn ban_ip(&mut self, ip: IpNetwork) {
         if self
             .banned
             .insert(ip, SystemTime::now() + Duration::from_secs(60 * 10)) // 10 minutes
@@ -423,28 +672,50 @@ impl NetworkBehaviour for ConnectionPoolBehaviour {
         failed_addresses: Option<&Vec<Multiaddr>>,
         other_established: usize,
     ) {
+        let relayed = is_relayed(endpoint.get_remote_address());
+        // Besides the first connection, only a direct connection that replaces a relayed one is
+        // allowed.
+        let duplicate = other_established > 0 && (relayed || !self.relayed.contains_key(peer_id));
+
         if other_established == 0 {
             // This is the first connection to this peer
             self.peer_ids.mark_connected(*peer_id);
+            if self.config.connection_lifetime.is_some() && !endpoint.is_dialer() {
+                self.inbound_since.insert(*peer_id, Instant::now());
+            }
+            if relayed {
+                self.relayed.insert(*peer_id, *connection_id);
+            }
             self.maintain_peers();
         }
 
-        // Send an event to the handler that tells it if this is an inbound or outbound connection, and the registered
-        // messages handlers, that receive from all peers.
-        self.actions
-            .push_back(NetworkBehaviourAction::NotifyHandler {
-                peer_id: *peer_id,
-                handler: NotifyHandler::One(*connection_id),
-                event: HandlerInEvent::PeerConnected {
+        if duplicate {
+            log::debug!("Closing duplicate connection to peer {}", peer_id);
+            self.actions
+                .push_back(NetworkBehaviourAction::CloseConnection {
                     peer_id: *peer_id,
-                    outbound: endpoint.is_dialer(),
-                    receive_from_all: self.message_receivers.clone(),
-                },
-            });
+                    connection: CloseConnection::One(*connection_id),
+                });
+        } else {
+            // Send an event to the handler that tells it if this is an inbound or outbound connection, and the registered
+            // messages handlers, that receive from all peers.
+            self.actions
+                .push_back(NetworkBehaviourAction::NotifyHandler {
+                    peer_id: *peer_id,
+                    handler: NotifyHandler::One(*connection_id),
+                    event: HandlerInEvent::PeerConnected {
+                        peer_id: *peer_id,
+                        outbound: endpoint.is_dialer(),
+                        receive_from_all: self.message_receivers.clone(),
+                        recorder: self.recorder.clone(),
+                        memory: self.memory.clone(),
+                    },
+                });
+        }
 
         if let Some(addresses) = failed_addresses {
             for address in addresses {
-                self.addresses.mark_failed(address.clone());
+                self.addresses.mark_failed(address.clone(), &mut self.rng);
             }
         }
 
@@ -469,6 +740,10 @@ impl NetworkBehaviour for ConnectionPoolBehaviour {
 
         if self.banned.get(&ip).is_some() {
             log::debug!("IP is banned, {}", ip);
+            self.actions
+                .push_back(NetworkBehaviourAction::GenerateEvent(
+                    ConnectionPoolEvent::PeerBanned { peer_id: *peer_id },
+                ));
             close_connection = true;
         }
         if self.config.peer_count_per_ip_max
@@ -509,7 +784,7 @@ impl NetworkBehaviour for ConnectionPoolBehaviour {
             self.actions
                 .push_back(NetworkBehaviourAction::NotifyHandler {
                     peer_id: *peer_id,
-                    handler: NotifyHandler::Any,
+                    handler: NotifyHandler::One(*connection_id),
                     event: HandlerInEvent::Close {
                         reason: CloseReason::Other,
                     },
@@ -539,6 +814,12 @@ impl NetworkBehaviour for ConnectionPoolBehaviour {
         _handler: <Self::ConnectionHandler as IntoConnectionHandler>::Handler,
         remaining_established: usize,
     ) {
+        // The relayed connection is kept until a direct connection replaced it, even if it was
+        // closed before, so the peer of the relayed connection is replaced as well.
+        if remaining_established == 0 {
+            self.relayed.remove(peer_id);
+        }
+
         let address = endpoint.get_remote_address();
 
         let ip = match address.iter().next() {
@@ -551,6 +832,10 @@ impl NetworkBehaviour for ConnectionPoolBehaviour {
             _ => return, // TODO: Review if we need to handle additional protocols
         };
 
+        if self.malicious_peers.remove(peer_id) {
+            self.ban_ip(ip);
+        }
+
         // Decrement IP counters
         let value = self.limits.ip_count.entry(ip).or_insert(1);
         *value = value.saturating_sub(1);
@@ -564,19 +849,21 @@ impl NetworkBehaviour for ConnectionPoolBehaviour {
         };
 
         self.addresses.mark_closed(address.clone());
-        // Notify handler about the connection is going to be shut down
-        self.actions
-            .push_back(NetworkBehaviourAction::NotifyHandler {
-                peer_id: *peer_id,
-                handler: NotifyHandler::Any,
-                event: HandlerInEvent::Close {
-                    reason: CloseReason::RemoteClosed,
-                },
-            });
 
         if remaining_established == 0 {
+            // Notify handler about the connection is going to be shut down
+            self.actions
+                .push_back(NetworkBehaviourAction::NotifyHandler {
+                    peer_id: *peer_id,
+                    handler: NotifyHandler::Any,
+                    event: HandlerInEvent::Close {
+                        reason: CloseReason::RemoteClosed,
+                    },
+                });
+
             // There are no more remaining connections to this peer
             self.peer_ids.mark_closed(*peer_id);
+            self.inbound_since.remove(peer_id);
             // If the connection was closed for any reason, don't dial the peer again.
             // FIXME We want to be more selective here and only mark peers as down for specific CloseReasons.
             self.peer_ids.mark_down(*peer_id);
@@ -587,7 +874,7 @@ impl NetworkBehaviour for ConnectionPoolBehaviour {
     fn inject_event(
         &mut self,
         peer_id: PeerId,
-        _connection: ConnectionId,
+        connection: ConnectionId,
         event: <<Self::ConnectionHandler as IntoConnectionHandler>::Handler as ConnectionHandler>::OutEvent,
     ) {
         match event {
@@ -599,6 +886,29 @@ impl NetworkBehaviour for ConnectionPoolBehaviour {
                     dispatch.receive_multiple_raw(self.message_receivers.clone());
                 }
 
+                // A direct connection that was established by hole punching replaces the relayed
+                // connection to the peer.
+                let relayed = self
+                    .relayed
+                    .get(&peer_id)
+                    .copied()
+                    .filter(|relayed| *relayed != connection);
+                if let Some(relayed) = relayed {
+                    log::debug!("Replacing the relayed connection to peer {}", peer_id);
+                    self.relayed.remove(&peer_id);
+                    if let Some(peer) = self.peers.remove(&peer_id) {
+                        self.actions
+                            .push_back(NetworkBehaviourAction::GenerateEvent(
+                                ConnectionPoolEvent::PeerLeft { peer },
+                            ));
+                    }
+                    self.actions
+                        .push_back(NetworkBehaviourAction::CloseConnection {
+                            peer_id,
+                            connection: CloseConnection::One(relayed),
+                        });
+                }
+
                 if !self.peers.insert(Arc::clone(&peer)) {
                     log::error!("Peer joined but it already exists ");
                 }
@@ -607,11 +917,17 @@ impl NetworkBehaviour for ConnectionPoolBehaviour {
                         ConnectionPoolEvent::PeerJoined { peer },
                     ));
             }
-            HandlerOutEvent::PeerLeft { peer_id, .. } => {
+            HandlerOutEvent::PeerLeft { peer_id, reason } => {
+                if let CloseReason::MaliciousPeer = reason {
+                    log::debug!("Peer {:?} misbehaved, banning its IP", peer_id);
+                    self.malicious_peers.insert(peer_id);
+                }
+                // Only the connection of the handler is closed, a direct connection that replaced
+                // a relayed one stays open.
                 self.actions
                     .push_back(NetworkBehaviourAction::CloseConnection {
                         peer_id,
-                        connection: CloseConnection::All,
+                        connection: CloseConnection::One(connection),
                     });
             }
         }
@@ -635,12 +951,42 @@ impl NetworkBehaviour for ConnectionPoolBehaviour {
             | DialError::NoAddresses => {
                 let peer_id = match peer_id {
                     Some(id) => id,
-                    // Not interested in dial failures to unknown peers right now.
-                    None => return,
+                    // Seeds are dialed without knowing their peer ID. Back off from the
+                    // addresses that failed, so that unreachable seeds aren't dialed over and
+                    // over again.
+                    None => {
+                        let addresses: Vec<Multiaddr> = match error {
+                            DialError::Transport(errors) => {
+                                errors.iter().map(|(address, _)| address.clone()).collect()
+                            }
+                            // At most one seed is dialed at a time.
+                            _ => self.addresses.dialing.iter().cloned().collect(),
+                        };
+                        for address in addresses {
+                            log::debug!("Failed to dial seed {}: {:?}", address, error);
+                            self.addresses.mark_failed(address, &mut self.rng);
+                        }
+                        self.maintain_peers();
+                        return;
+                    }
                 };
 
                 log::debug!("Failed to dial peer {}: {:?}", peer_id, error);
-                self.peer_ids.mark_failed(peer_id);
+                let attempts = self.peer_ids.mark_failed(peer_id, &mut self.rng);
+                self.actions
+                    .push_back(NetworkBehaviourAction::GenerateEvent(
+                        ConnectionPoolEvent::DialFailed {
+                            peer_id,
+                            error: error.to_string(),
+                            attempts,
+                        },
+                    ));
+                if attempts == self.peer_ids.max_failures {
+                    self.actions
+                        .push_back(NetworkBehaviourAction::GenerateEvent(
+                            ConnectionPoolEvent::BackoffStarted { peer_id },
+                        ));
+                }
                 self.maintain_peers();
             }
             DialError::DialPeerConditionFalse(
@@ -670,8 +1016,184 @@ impl NetworkBehaviour for ConnectionPoolBehaviour {
             self.housekeeping();
         }
 
+        // Dial the peers that were held back by the dial budget or a backoff.
+        if self.dial_timer.poll_tick(cx).is_ready() {
+            if let Some(next_dial) = self.next_dial {
+                if next_dial <= Instant::now() {
+                    self.maintain_peers();
+                }
+            }
+        }
+
         store_waker!(self, waker, cx);
 
         Poll::Pending
     }
 }
+
+/// Returns whether the address is the address of a connection through a relay.
+fn is_relayed(address: &Multiaddr) -> bool {
+    address
+        .iter()
+        .any(|protocol| matches!(protocol, Protocol::P2pCircuit))
+}
+
+#[cfg(test)]
+mod tests {
+    use libp2p::{core::Endpoint, identity::Keypair};
+
+    use super::*;
+    use crate::discovery::peer_contacts::PeerContact;
+
+    fn test_pool(seed: bool) -> ConnectionPoolBehaviour {
+        let keypair = Keypair::generate_ed25519();
+        let contact = PeerContact::new(vec![], keypair.public(), Services::empty(), None);
+        let contacts = PeerContactBook::new(Default::default(), contact.sign(&keypair));
+        ConnectionPoolBehaviour::new(
+            Arc::new(RwLock::new(contacts)),
+            vec![],
+            ObservablePeerMap::new(),
+            None,
+            None,
+            SharedRng::seeded(0),
+            seed,
+        )
+    }
+
+    /// Returns the peers that the pool asked to close their connection.
+    fn closed_peers(pool: &ConnectionPoolBehaviour) -> Vec<PeerId> {
+        pool.actions
+            .iter()
+            .filter_map(|action| match action {
+                NetworkBehaviourAction::NotifyHandler {
+                    peer_id,
+                    event: HandlerInEvent::Close { .. },
+                    ..
+                } => Some(*peer_id),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn circuit_addresses_are_relayed() {
+        let relayed: Multiaddr = "/ip4/1.2.3.4/tcp/8443/ws/p2p/12D3KooWDpJ7As7BWAwRMfu1VU2WCqNjvq387JEYKDBj4kx6nXTN/p2p-circuit"
+            .parse()
+            .unwrap();
+        assert!(is_relayed(&relayed));
+
+        let direct: Multiaddr = "/ip4/1.2.3.4/tcp/8443/ws".parse().unwrap();
+        assert!(!is_relayed(&direct));
+    }
+
+    #[test]
+    fn failed_dials_back_off_exponentially() {
+        let config = ConnectionPoolConfig::default();
+        let mut state = ConnectionState::new(4, &config);
+        let mut rng = SharedRng::seeded(0);
+
+        for attempts in 1..4 {
+            state.mark_dialing(1);
+            let before = Instant::now();
+            assert_eq!(state.mark_failed(1, &mut rng), attempts);
+            assert!(!state.can_dial(&1));
+
+            let backoff = state.backoff[&1] - before;
+            let expected = config.dial_backoff_base * (1 << (attempts - 1));
+            assert!(backoff >= expected);
+            assert!(backoff <= expected.mul_f64(1.5) + Duration::from_secs(1));
+            assert_eq!(state.next_retry(), Some(state.backoff[&1]));
+        }
+
+        // The last failed attempt marks the id as down instead.
+        assert_eq!(state.mark_failed(1, &mut rng), 4);
+        assert!(state.down.contains_key(&1));
+        assert!(state.backoff.is_empty());
+        assert_eq!(state.next_retry(), None);
+
+        state.mark_connected(1);
+        assert!(state.failed.is_empty() && state.down.is_empty());
+    }
+
+    #[test]
+    fn only_failed_dials_end_a_backoff() {
+        let config = ConnectionPoolConfig {
+            retry_down_after: Duration::ZERO,
+            ..Default::default()
+        };
+        let mut state = ConnectionState::new(2, &config);
+        let mut rng = SharedRng::seeded(0);
+
+        // A closed connection marks the peer as down without starting a backoff.
+        state.mark_connected(1);
+        state.mark_closed(1);
+        state.mark_down(1);
+
+        // Only the last failed attempt starts a backoff.
+        assert_eq!(state.mark_failed(2, &mut rng), 1);
+        assert!(!state.down.contains_key(&2));
+        assert_eq!(state.mark_failed(2, &mut rng), 2);
+        assert!(state.down.contains_key(&2));
+
+        assert_eq!(state.housekeeping(), vec![2]);
+        assert!(state.down.is_empty() && state.failed_down.is_empty());
+        assert!(state.can_dial(&1) && state.can_dial(&2));
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        let config = ConnectionPoolConfig::default();
+        let mut state = ConnectionState::new(usize::MAX, &config);
+        let mut rng = SharedRng::seeded(0);
+
+        for _ in 0..20 {
+            state.mark_failed(1, &mut rng);
+        }
+        assert!(state.backoff[&1] - Instant::now() <= config.dial_backoff_max);
+    }
+
+    #[tokio::test]
+    async fn inbound_connections_are_closed_after_their_lifetime() {
+        let inbound = PeerId::random();
+        let outbound = PeerId::random();
+        let listener = ConnectedPoint::Listener {
+            local_addr: "/ip4/10.0.0.1/tcp/8443/ws".parse().unwrap(),
+            send_back_addr: "/ip4/1.2.3.4/tcp/50000/ws".parse().unwrap(),
+        };
+        let dialer = ConnectedPoint::Dialer {
+            address: "/ip4/5.6.7.8/tcp/8443/ws".parse().unwrap(),
+            role_override: Endpoint::Dialer,
+        };
+
+        let mut pool = test_pool(true);
+        pool.inject_connection_established(&inbound, &ConnectionId::new(0), &listener, None, 0);
+        pool.inject_connection_established(&outbound, &ConnectionId::new(1), &dialer, None, 0);
+
+        // Only the inbound connection expires, and only once its lifetime has passed.
+        let lifetime = pool.config.connection_lifetime.unwrap();
+        assert!(pool
+            .expired_connections(lifetime, Instant::now())
+            .is_empty());
+        assert_eq!(
+            pool.expired_connections(lifetime, Instant::now() + lifetime),
+            vec![inbound]
+        );
+
+        pool.config.connection_lifetime = Some(Duration::ZERO);
+        pool.actions.clear();
+        pool.housekeeping();
+        assert_eq!(closed_peers(&pool), vec![inbound]);
+        assert!(pool.inbound_since.is_empty());
+
+        // The connection is only closed once.
+        pool.actions.clear();
+        pool.housekeeping();
+        assert!(closed_peers(&pool).is_empty());
+
+        // Without a lifetime, inbound connections are kept.
+        let mut pool = test_pool(false);
+        pool.inject_connection_established(&inbound, &ConnectionId::new(0), &listener, None, 0);
+        pool.housekeeping();
+        assert!(closed_peers(&pool).is_empty());
+    }
+}
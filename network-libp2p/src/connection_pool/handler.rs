@@ -19,7 +19,10 @@ use libp2p::{
 use thiserror::Error;
 
 use beserial::SerializingError;
-use nimiq_network_interface::{message::MessageType, peer::CloseReason};
+use nimiq_network_interface::{
+    message::MessageType, peer::CloseReason, recording::MessageRecorder,
+};
+use nimiq_utils::memory::MemoryGauge;
 
 use crate::dispatch::message_dispatch::MessageDispatch;
 use crate::peer::Peer;
@@ -35,6 +38,8 @@ pub enum HandlerInEvent {
         peer_id: PeerId,
         outbound: bool,
         receive_from_all: HashMap<MessageType, mpsc::Sender<(Bytes, Arc<Peer>)>>,
+        recorder: Option<Arc<MessageRecorder>>,
+        memory: MemoryGauge,
     },
 }
 
@@ -79,6 +84,12 @@ pub struct ConnectionPoolHandler {
 
     // The global message receivers are stored here, until we create the MessageDispatch
     receive_from_all: Option<HashMap<MessageType, mpsc::Sender<(Bytes, Arc<Peer>)>>>,
+
+    // The message recorder is stored here, until we create the MessageDispatch
+    recorder: Option<Arc<MessageRecorder>>,
+
+    // The gauge of the peer buffers is stored here, until we create the MessageDispatch
+    memory: Option<MemoryGauge>,
 }
 
 impl ConnectionPoolHandler {
@@ -92,6 +103,8 @@ impl ConnectionPoolHandler {
             socket: None,
             closing: None,
             receive_from_all: None,
+            recorder: None,
+            memory: None,
         }
     }
 
@@ -164,6 +177,8 @@ impl ConnectionHandler for ConnectionPoolHandler {
                 peer_id,
                 outbound,
                 receive_from_all,
+                recorder,
+                memory,
             } => {
                 // Both peer_id and receive_from_all should not have been set yet.
                 assert!(self.peer_id.is_none());
@@ -171,6 +186,8 @@ impl ConnectionHandler for ConnectionPoolHandler {
 
                 self.peer_id = Some(peer_id);
                 self.receive_from_all = Some(receive_from_all);
+                self.recorder = recorder;
+                self.memory = Some(memory);
 
                 if outbound {
                     // Next open the outbound, but only if our connection is outbound
@@ -315,6 +332,10 @@ impl ConnectionHandler for ConnectionPoolHandler {
             // Register the global message receivers with this message dispatch.
             let receive_from_all = self.receive_from_all.take().expect("global receivers");
             socket.receive_multiple_raw(receive_from_all);
+            socket.set_recorder(self.recorder.take());
+            if let Some(memory) = self.memory.take() {
+                socket.set_memory(memory);
+            }
 
             let peer = Arc::new(Peer::new(peer_id, socket, close_tx));
             log::debug!("New peer: {:?}", peer);
@@ -21,7 +21,7 @@ use parking_lot::RwLock;
 use wasm_timer::Interval;
 
 use nimiq_hash::Blake2bHash;
-use nimiq_utils::time::OffsetTime;
+use nimiq_utils::{shared_rng::SharedRng, time::OffsetTime};
 
 use super::{
     handler::{DiscoveryHandler, HandlerInEvent, HandlerOutEvent},
@@ -40,9 +40,19 @@ pub struct DiscoveryConfig {
     /// be rejected.
     pub min_recv_update_interval: Duration,
 
-    /// How many updated peer contacts we want to receive per update.
+    /// How many updated peer contacts we want to receive per update. Larger updates are dropped.
     pub update_limit: u16,
 
+    /// Maximum number of peer contacts we send in one message, regardless of the limit the peer asked for.
+    pub max_send_limit: u16,
+
+    /// Number of peer contacts we send to a peer that didn't contribute any new peer contacts in its last message.
+    pub initial_send_limit: u16,
+
+    /// Number of additional peer contacts we send for every new peer contact the peer contributed in its last
+    /// message. This keeps peers from using us to amplify their discovery traffic.
+    pub amplification_factor: u16,
+
     /// Protocols for which we filter.
     pub protocols_filter: Protocols,
 
@@ -67,6 +77,9 @@ impl DiscoveryConfig {
             min_send_update_interval: Duration::from_secs(30),
             min_recv_update_interval: Duration::from_secs(30),
             update_limit: 64,
+            max_send_limit: 64,
+            initial_send_limit: 16,
+            amplification_factor: 2,
             protocols_filter: Protocols::all(),
             services_filter: Services::all(),
             house_keeping_interval: Duration::from_secs(60),
@@ -77,7 +90,10 @@ impl DiscoveryConfig {
 
 #[derive(Clone, Debug)]
 pub enum DiscoveryEvent {
-    Established { peer_id: PeerId },
+    Established {
+        peer_id: PeerId,
+        clock_offset: Option<i64>,
+    },
     Update,
 }
 
@@ -88,9 +104,8 @@ type DiscoveryNetworkBehaviourAction = NetworkBehaviourAction<DiscoveryEvent, Di
 /// When a connection to a peer is established, a handshake is done to exchange protocols and services filters, and
 /// subscription settings. The peers then send updates to each other in a configurable interval.
 ///
-/// # TODO
-///
-///  - Exchange clock time with other peers.
+/// The peers also exchange their wall-clock time in the handshake. The resulting clock offset is reported with
+/// [`DiscoveryEvent::Established`].
 ///
 pub struct DiscoveryBehaviour {
     /// Configuration for the discovery behaviour
@@ -105,7 +120,7 @@ pub struct DiscoveryBehaviour {
     /// Contains all known peer contacts.
     peer_contact_book: Arc<RwLock<PeerContactBook>>,
 
-    #[allow(dead_code)]
+    /// Clock that is used to exchange our time with other peers.
     clock: Arc<OffsetTime>,
 
     /// Queue with events to emit.
@@ -113,6 +128,9 @@ pub struct DiscoveryBehaviour {
 
     /// Timer to do house-keeping in the peer address book.
     house_keeping_timer: Interval,
+
+    /// Randomness used by the handlers to choose the peer contacts they send.
+    rng: SharedRng,
 }
 
 impl DiscoveryBehaviour {
@@ -121,6 +139,7 @@ impl DiscoveryBehaviour {
         keypair: Keypair,
         peer_contact_book: Arc<RwLock<PeerContactBook>>,
         clock: Arc<OffsetTime>,
+        rng: SharedRng,
     ) -> Self {
         let house_keeping_timer = Interval::new(config.house_keeping_interval);
         peer_contact_book.write().update_own_contact(&keypair);
@@ -133,12 +152,27 @@ impl DiscoveryBehaviour {
             clock,
             events: VecDeque::new(),
             house_keeping_timer,
+            rng,
         }
     }
 
     pub fn peer_contact_book(&self) -> Arc<RwLock<PeerContactBook>> {
         Arc::clone(&self.peer_contact_book)
     }
+
+    /// Advertises an additional address in our peer contact.
+    pub fn add_own_address(&mut self, address: Multiaddr) -> bool {
+        self.peer_contact_book
+            .write()
+            .add_own_address(address, &self.keypair)
+    }
+
+    /// Stops advertising an address in our peer contact.
+    pub fn remove_own_address(&mut self, address: &Multiaddr) -> bool {
+        self.peer_contact_book
+            .write()
+            .remove_own_address(address, &self.keypair)
+    }
 }
 
 impl NetworkBehaviour for DiscoveryBehaviour {
@@ -150,6 +184,8 @@ impl NetworkBehaviour for DiscoveryBehaviour {
             self.config.clone(),
             self.keypair.clone(),
             self.peer_contact_book(),
+            Arc::clone(&self.clock),
+            self.rng.clone(),
         )
     }
 
@@ -212,10 +248,14 @@ impl NetworkBehaviour for DiscoveryBehaviour {
         log::trace!("inject_event: peer_id={}: {:?}", peer_id, event);
 
         match event {
-            HandlerOutEvent::PeerExchangeEstablished { peer_contact } => {
+            HandlerOutEvent::PeerExchangeEstablished {
+                peer_contact,
+                clock_offset,
+            } => {
                 self.events.push_back(NetworkBehaviourAction::GenerateEvent(
                     DiscoveryEvent::Established {
                         peer_id: peer_contact.public_key().clone().to_peer_id(),
+                        clock_offset,
                     },
                 ));
             }
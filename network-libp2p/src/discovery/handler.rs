@@ -17,13 +17,13 @@ use libp2p::{
     Multiaddr,
 };
 use parking_lot::RwLock;
-use rand::{seq::IteratorRandom, thread_rng};
+use rand::seq::IteratorRandom;
 use thiserror::Error;
 use wasm_timer::Interval;
 
 use beserial::SerializingError;
 use nimiq_hash::Blake2bHash;
-use nimiq_utils::tagged_signing::TaggedKeypair;
+use nimiq_utils::{shared_rng::SharedRng, tagged_signing::TaggedKeypair, time::OffsetTime};
 
 use super::{
     behaviour::DiscoveryConfig,
@@ -32,6 +32,46 @@ use super::{
     protocol::{ChallengeNonce, DiscoveryMessage, DiscoveryProtocol},
 };
 
+/// Number of too frequent or too large updates from a peer that are dropped before the connection is closed.
+const MAX_UPDATE_VIOLATIONS: u8 = 3;
+
+/// Time after which one violation of a peer is forgiven.
+const UPDATE_VIOLATION_DECAY: Duration = Duration::from_secs(10 * 60);
+
+/// Counts the updates from a peer that were dropped. Violations decay over time, so only peers that keep misbehaving
+/// are disconnected, not long-lived peers that occasionally send an update too early.
+#[derive(Debug, Default)]
+struct UpdateViolations {
+    count: u8,
+
+    /// Time from which the next violation is forgiven.
+    decay_since: Option<Instant>,
+}
+
+impl UpdateViolations {
+    /// Records a violation. Returns `true` if the peer exceeded the number of tolerated violations.
+    fn record(&mut self, now: Instant) -> bool {
+        self.decay(now);
+        if self.count == 0 {
+            self.decay_since = Some(now);
+        }
+        self.count = self.count.saturating_add(1);
+        self.count > MAX_UPDATE_VIOLATIONS
+    }
+
+    /// Forgives one violation per elapsed decay period.
+    fn decay(&mut self, now: Instant) {
+        if let Some(decay_since) = self.decay_since {
+            let elapsed = now.saturating_duration_since(decay_since);
+            let periods = (elapsed.as_secs_f64() / UPDATE_VIOLATION_DECAY.as_secs_f64()) as u32;
+            if periods > 0 {
+                self.count = self.count.saturating_sub(periods.min(u8::MAX as u32) as u8);
+                self.decay_since = Some(decay_since + UPDATE_VIOLATION_DECAY * periods);
+            }
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum HandlerInEvent {
     ObservedAddress(Multiaddr),
@@ -39,8 +79,14 @@ pub enum HandlerInEvent {
 
 #[derive(Clone, Debug)]
 pub enum HandlerOutEvent {
-    ObservedAddresses { observed_addresses: Vec<Multiaddr> },
-    PeerExchangeEstablished { peer_contact: SignedPeerContact },
+    ObservedAddresses {
+        observed_addresses: Vec<Multiaddr>,
+    },
+    PeerExchangeEstablished {
+        peer_contact: SignedPeerContact,
+        /// Offset of the peer's clock to ours in milliseconds, if the peer sent its time.
+        clock_offset: Option<i64>,
+    },
     Update,
 }
 
@@ -118,6 +164,9 @@ pub struct DiscoveryHandler {
     /// The peer contact book
     peer_contact_book: Arc<RwLock<PeerContactBook>>,
 
+    /// The clock used to exchange our time with the peer.
+    clock: Arc<OffsetTime>,
+
     /// The peer contact of the peer we're connected to.
     _peer_contact: Option<SignedPeerContact>,
 
@@ -145,6 +194,13 @@ pub struct DiscoveryHandler {
     /// Time when we last received an update from the other peer.
     last_update_time: Option<Instant>,
 
+    /// Number of updates from the other peer we dropped because they were too frequent or too large.
+    update_violations: UpdateViolations,
+
+    /// Number of peer contacts we send to the other peer in the next message. This depends on the number of new peer
+    /// contacts the peer sent us in its last message.
+    send_allowance: usize,
+
     /// The inbound message stream.
     inbound: Option<MessageReader<NegotiatedSubstream, DiscoveryMessage>>,
 
@@ -153,6 +209,9 @@ pub struct DiscoveryHandler {
 
     /// Waker used when opening a substream.
     waker: Option<Waker>,
+
+    /// Randomness used to choose the peer contacts we send.
+    rng: SharedRng,
 }
 
 impl DiscoveryHandler {
@@ -160,11 +219,15 @@ impl DiscoveryHandler {
         config: DiscoveryConfig,
         keypair: Keypair,
         peer_contact_book: Arc<RwLock<PeerContactBook>>,
+        clock: Arc<OffsetTime>,
+        rng: SharedRng,
     ) -> Self {
         Self {
+            send_allowance: config.initial_send_limit as usize,
             config,
             keypair,
             peer_contact_book,
+            clock,
             _peer_contact: None,
             observed_addresses: vec![],
             challenge_nonce: ChallengeNonce::generate(),
@@ -174,9 +237,11 @@ impl DiscoveryHandler {
             peer_list_limit: None,
             periodic_update_interval: None,
             last_update_time: None,
+            update_violations: UpdateViolations::default(),
             inbound: None,
             outbound: None,
             waker: None,
+            rng,
         }
     }
 
@@ -195,20 +260,51 @@ impl DiscoveryHandler {
     }
 
     /// Get peer contacts from our contact book to send to this peer. The contacts are filtered according to the peer's
-    /// protocols and service filters, they are limited to the number of peers specified by the peer.
-    fn get_peer_contacts(&self, peer_contact_book: &PeerContactBook) -> Vec<SignedPeerContact> {
-        let n = self.peer_list_limit.unwrap() as usize;
-
-        let mut rng = thread_rng();
+    /// protocols and service filters, they are limited to the number of peers specified by the peer, our own send limit
+    /// and the allowance the peer earned by contributing peer contacts.
+    fn get_peer_contacts(&mut self, peer_contact_book: &PeerContactBook) -> Vec<SignedPeerContact> {
+        let n = (self.peer_list_limit.unwrap() as usize)
+            .min(self.config.max_send_limit as usize)
+            .min(self.send_allowance);
 
         peer_contact_book
             .query(self.protocols_filter, self.services_filter)
-            .choose_multiple(&mut rng, n)
+            .choose_multiple(&mut self.rng, n)
             .into_iter()
             .map(|c| c.signed().clone())
             .collect()
     }
 
+    /// Inserts the peer contacts received from this peer into the contact book and updates the number of peer contacts
+    /// we send to this peer in return. Only contacts that were new to us count as a contribution.
+    fn insert_peer_contacts(&mut self, peer_contacts: Vec<SignedPeerContact>) {
+        let mut peer_contact_book = self.peer_contact_book.write();
+
+        let mut contributed = 0;
+        for peer_contact in peer_contacts {
+            if peer_contact_book
+                .get(&peer_contact.inner.peer_id())
+                .is_none()
+            {
+                contributed += 1;
+            }
+            peer_contact_book.insert_filtered(
+                peer_contact,
+                self.config.protocols_filter,
+                self.config.services_filter,
+            );
+        }
+
+        self.send_allowance = self.config.initial_send_limit as usize
+            + self.config.amplification_factor as usize * contributed;
+    }
+
+    /// Records an update from this peer that was dropped. Returns `true` if the peer exceeded the number of tolerated
+    /// violations and the connection should be closed.
+    fn record_update_violation(&mut self) -> bool {
+        self.update_violations.record(Instant::now())
+    }
+
     /// Checks if both inbound and outbound are available and transitions to sending a handshake. This includes waking
     /// waker to continue polling.
     fn check_connected(&mut self) {
@@ -379,7 +475,8 @@ impl ConnectionHandler for DiscoveryHandler {
                                         ));
                                     }
 
-                                    let mut peer_contact_book = self.peer_contact_book.write();
+                                    let peer_contact_book = Arc::clone(&self.peer_contact_book);
+                                    let mut peer_contact_book = peer_contact_book.write();
 
                                     // Update our own peer contact given the observed addresses we received
                                     peer_contact_book.add_own_addresses(observed_addresses.clone());
@@ -403,6 +500,7 @@ impl ConnectionHandler for DiscoveryHandler {
                                             self.config.update_interval.as_secs(),
                                         ),
                                         peer_contacts: self.get_peer_contacts(&peer_contact_book),
+                                        timestamp: Some(self.clock.now()),
                                     };
 
                                     drop(peer_contact_book);
@@ -452,6 +550,7 @@ impl ConnectionHandler for DiscoveryHandler {
                                     response_signature,
                                     update_interval,
                                     peer_contacts,
+                                    timestamp,
                                 } => {
                                     // Check the peer contact for a valid signature.
                                     if !peer_contact.verify() {
@@ -485,9 +584,20 @@ impl ConnectionHandler for DiscoveryHandler {
                                         self.config.services_filter,
                                     );
 
-                                    // Insert the initial set of peer contacts into the peer contact book.
-                                    // TODO: This doesn't actually filter and just assumes the peer already filtered.
-                                    peer_contact_book.insert_all(peer_contacts);
+                                    drop(peer_contact_book);
+
+                                    // Insert the initial set of peer contacts into the peer contact book, unless the
+                                    // peer sent more than we asked for.
+                                    if peer_contacts.len() > self.config.update_limit as usize {
+                                        log::debug!(
+                                            "Dropping {} peer contacts from handshake of {:?}",
+                                            peer_contacts.len(),
+                                            peer_contact.inner.peer_id(),
+                                        );
+                                        self.record_update_violation();
+                                    } else {
+                                        self.insert_peer_contacts(peer_contacts);
+                                    }
 
                                     // Store peer contact in handler
                                     self._peer_contact = Some(peer_contact.clone());
@@ -504,12 +614,21 @@ impl ConnectionHandler for DiscoveryHandler {
                                         ));
                                     }
 
+                                    // The offset includes the one-way delay of the message, which is
+                                    // negligible compared to the clock drift we care about.
+                                    let clock_offset = timestamp.map(|timestamp| {
+                                        timestamp as i64 - self.clock.now() as i64
+                                    });
+
                                     // Switch to established state
                                     self.state = HandlerState::Established;
 
                                     // TODO: Return an event that we established PEX with a new peer.
                                     return Poll::Ready(ConnectionHandlerEvent::Custom(
-                                        HandlerOutEvent::PeerExchangeEstablished { peer_contact },
+                                        HandlerOutEvent::PeerExchangeEstablished {
+                                            peer_contact,
+                                            clock_offset,
+                                        },
                                     ));
                                 }
 
@@ -541,34 +660,45 @@ impl ConnectionHandler for DiscoveryHandler {
                         Poll::Ready(Some(Ok(message))) => {
                             match message {
                                 DiscoveryMessage::PeerAddresses { peer_contacts } => {
-                                    // Check if the update is actually not too frequent
+                                    // Drop the update if it is too frequent. Close the connection if the peer keeps
+                                    // sending them.
                                     let now = Instant::now();
                                     if let Some(last_update_time) = self.last_update_time {
                                         let interval = now - last_update_time;
                                         if interval < self.config.min_recv_update_interval {
-                                            // TODO: Should we just close, or ban?
-                                            return Poll::Ready(ConnectionHandlerEvent::Close(
-                                                HandlerError::TooFrequentUpdates { interval },
-                                            ));
+                                            if self.record_update_violation() {
+                                                return Poll::Ready(ConnectionHandlerEvent::Close(
+                                                    HandlerError::TooFrequentUpdates { interval },
+                                                ));
+                                            }
+                                            log::debug!(
+                                                "Dropping too frequent update after {:?}",
+                                                interval
+                                            );
+                                            continue;
                                         }
                                     }
-                                    self.last_update_time = Some(now);
 
-                                    // Check if the update is not too large.
+                                    // Drop the update if it is too large. Close the connection if the peer keeps
+                                    // sending them.
                                     if peer_contacts.len() > self.config.update_limit as usize {
-                                        return Poll::Ready(ConnectionHandlerEvent::Close(
-                                            HandlerError::UpdateLimitExceeded {
-                                                num_peer_contacts: peer_contacts.len(),
-                                            },
-                                        ));
+                                        if self.record_update_violation() {
+                                            return Poll::Ready(ConnectionHandlerEvent::Close(
+                                                HandlerError::UpdateLimitExceeded {
+                                                    num_peer_contacts: peer_contacts.len(),
+                                                },
+                                            ));
+                                        }
+                                        log::debug!(
+                                            "Dropping update with {} peer contacts",
+                                            peer_contacts.len()
+                                        );
+                                        continue;
                                     }
+                                    self.last_update_time = Some(now);
 
                                     // Insert the new peer contacts into the peer contact book.
-                                    self.peer_contact_book.write().insert_all_filtered(
-                                        peer_contacts,
-                                        self.config.protocols_filter,
-                                        self.config.services_filter,
-                                    );
+                                    self.insert_peer_contacts(peer_contacts);
 
                                     return Poll::Ready(ConnectionHandlerEvent::Custom(
                                         HandlerOutEvent::Update,
@@ -600,8 +730,9 @@ impl ConnectionHandler for DiscoveryHandler {
                     if let Some(timer) = self.periodic_update_interval.as_mut() {
                         match timer.poll_next_unpin(cx) {
                             Poll::Ready(Some(_instant)) => {
+                                let peer_contact_book = Arc::clone(&self.peer_contact_book);
                                 let peer_contacts =
-                                    self.get_peer_contacts(&self.peer_contact_book.read());
+                                    self.get_peer_contacts(&peer_contact_book.read());
 
                                 if !peer_contacts.is_empty() {
                                     let msg = DiscoveryMessage::PeerAddresses { peer_contacts };
@@ -625,3 +756,40 @@ impl ConnectionHandler for DiscoveryHandler {
         Poll::Pending
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_tolerates_a_limited_number_of_violations() {
+        let mut violations = UpdateViolations::default();
+        let now = Instant::now();
+
+        for _ in 0..MAX_UPDATE_VIOLATIONS {
+            assert!(!violations.record(now));
+        }
+        assert!(violations.record(now));
+    }
+
+    #[test]
+    fn it_forgives_violations_over_time() {
+        let mut violations = UpdateViolations::default();
+        let mut now = Instant::now();
+
+        // A peer that occasionally sends an update too early is never disconnected.
+        for _ in 0..100 {
+            assert!(!violations.record(now));
+            now += UPDATE_VIOLATION_DECAY;
+        }
+
+        // One violation is forgiven per decay period.
+        let mut violations = UpdateViolations::default();
+        let now = Instant::now();
+        for _ in 0..MAX_UPDATE_VIOLATIONS {
+            assert!(!violations.record(now));
+        }
+        assert!(!violations.record(now + UPDATE_VIOLATION_DECAY));
+        assert!(violations.record(now + UPDATE_VIOLATION_DECAY));
+    }
+}
@@ -526,6 +526,52 @@ impl PeerContactBook {
         self.insert(contact.sign(keypair));
     }
 
+    /// Adds an address to our own peer contact, e.g. one that was mapped on the router. Returns
+    /// whether the address was new.
+    pub fn add_own_address(&mut self, address: Multiaddr, keypair: &Keypair) -> bool {
+        let contact = self.own_peer_contact.contact();
+        if contact.addresses.contains(&address) {
+            return false;
+        }
+
+        let mut addresses = contact.addresses.clone();
+        addresses.push(address);
+        self.set_own_addresses(addresses, keypair);
+        true
+    }
+
+    /// Removes an address from our own peer contact. Returns whether it was part of it.
+    pub fn remove_own_address(&mut self, address: &Multiaddr, keypair: &Keypair) -> bool {
+        let contact = self.own_peer_contact.contact();
+        if !contact.addresses.contains(address) {
+            return false;
+        }
+
+        let addresses = contact
+            .addresses
+            .iter()
+            .filter(|a| *a != address)
+            .cloned()
+            .collect::<Vec<_>>();
+        self.set_own_addresses(addresses, keypair);
+        true
+    }
+
+    fn set_own_addresses(&mut self, addresses: Vec<Multiaddr>, keypair: &Keypair) {
+        let own_contact = self.own_peer_contact.contact();
+        let mut contact = PeerContact::new(
+            addresses,
+            own_contact.public_key.clone(),
+            own_contact.services,
+            None,
+        );
+        contact.set_current_time();
+
+        let signed = contact.sign(keypair);
+        self.own_peer_contact = signed.clone().into();
+        self.insert(signed);
+    }
+
     pub fn get_own_contact(&self) -> &PeerContactInfo {
         &self.own_peer_contact
     }
@@ -557,7 +603,9 @@ impl PeerContactBook {
 
 #[cfg(test)]
 mod tests {
-    use super::Protocols;
+    use libp2p::identity::Keypair;
+
+    use super::{PeerContact, PeerContactBook, Protocols, Services};
 
     #[test]
     fn protocols_from_multiaddr() {
@@ -595,6 +643,41 @@ mod tests {
             Protocols::WS | Protocols::WSS
         );
     }
+
+    #[test]
+    fn own_addresses_can_be_added_and_removed() {
+        let keypair = Keypair::generate_ed25519();
+        let local: libp2p::Multiaddr = "/ip4/192.168.1.10/tcp/8443/ws".parse().unwrap();
+        let mapped: libp2p::Multiaddr = "/ip4/1.2.3.4/tcp/8443/ws".parse().unwrap();
+
+        let contact = PeerContact::new(
+            vec![local.clone()],
+            keypair.public(),
+            Services::FULL_BLOCKS,
+            None,
+        );
+        let mut book = PeerContactBook::new(Default::default(), contact.sign(&keypair));
+
+        assert!(book.add_own_address(mapped.clone(), &keypair));
+        assert!(!book.add_own_address(mapped.clone(), &keypair));
+        let own_contact = book.get_own_contact();
+        assert!(own_contact.signed().verify());
+        assert_eq!(own_contact.services(), Services::FULL_BLOCKS);
+        assert_eq!(
+            own_contact.addresses().cloned().collect::<Vec<_>>(),
+            vec![mapped.clone(), local.clone()]
+        );
+
+        assert!(book.remove_own_address(&mapped, &keypair));
+        assert!(!book.remove_own_address(&mapped, &keypair));
+        assert_eq!(
+            book.get_own_contact()
+                .addresses()
+                .cloned()
+                .collect::<Vec<_>>(),
+            vec![local]
+        );
+    }
 }
 
 #[cfg(feature = "peer-contact-book-persistence")]
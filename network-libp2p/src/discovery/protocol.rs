@@ -77,6 +77,11 @@ pub enum DiscoveryMessage {
         /// Initial set of peer contacts.
         #[beserial(len_type(u16))]
         peer_contacts: Vec<SignedPeerContact>,
+
+        /// Wall-clock time of the sender in milliseconds since the Unix epoch. Older peers don't
+        /// send it.
+        #[beserial(trailing)]
+        timestamp: Option<u64>,
     },
 
     #[beserial(discriminant = 3)]
@@ -89,6 +89,9 @@ impl Header {
     /// - length: 4B
     /// - checksum: 4B
     pub const SIZE: usize = 20;
+    /// Maximum length of a message including the header. Frames that declare a larger length are
+    /// rejected before anything is allocated for them.
+    pub const MAX_MESSAGE_SIZE: usize = 64 * 1024 * 1024;
 
     fn new(type_id: u64) -> Self {
         Self {
@@ -102,7 +105,9 @@ impl Header {
     fn preliminary_check(&self) -> Result<(), Error> {
         if self.magic != Self::MAGIC {
             Err(Error::InvalidMagic(self.magic))
-        } else if (self.length as usize) < Self::SIZE {
+        } else if (self.length as usize) < Self::SIZE
+            || (self.length as usize) > Self::MAX_MESSAGE_SIZE
+        {
             Err(Error::InvalidLength(self.length))
         } else {
             Ok(())
@@ -110,6 +115,9 @@ impl Header {
     }
 }
 
+/// Minimum number of bytes that is reserved for the rest of a frame.
+const RESERVE_CHUNK_SIZE: usize = 64 * 1024;
+
 #[derive(Clone, Debug)]
 enum DecodeState {
     Head,
@@ -173,6 +181,13 @@ impl Decoder for MessageCodec {
                             // Preliminary header check (we can't verify the checksum yet)
                             header.preliminary_check()?;
 
+                            // Make room for more of the frame, so that the buffer of a large
+                            // message isn't reallocated and copied too often while it is received.
+                            // The buffer grows at most by what is already buffered, so a peer
+                            // can't make us allocate memory for data it never sends.
+                            let missing = (header.length as usize).saturating_sub(src.len());
+                            src.reserve(missing.min(src.len().max(RESERVE_CHUNK_SIZE)));
+
                             // Set decode state to reading the remaining data
                             self.state = DecodeState::Data {
                                 header,
@@ -273,3 +288,38 @@ impl<M: Message> Encoder<&M> for MessageCodec {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(length: u32) -> BytesMut {
+        let header = Header {
+            magic: Header::MAGIC,
+            type_id: 1,
+            length,
+            checksum: 0,
+        };
+        BytesMut::from(&header.serialize_to_vec()[..])
+    }
+
+    #[test]
+    fn it_rejects_oversized_frames() {
+        let mut codec = MessageCodec::default();
+        let mut src = header(u32::MAX);
+
+        assert!(matches!(
+            codec.decode(&mut src),
+            Err(Error::InvalidLength(u32::MAX))
+        ));
+    }
+
+    #[test]
+    fn it_only_reserves_what_is_buffered() {
+        let mut codec = MessageCodec::default();
+        let mut src = header(Header::MAX_MESSAGE_SIZE as u32);
+
+        assert!(matches!(codec.decode(&mut src), Ok(None)));
+        assert!(src.capacity() < 2 * (Header::SIZE + RESERVE_CHUNK_SIZE));
+    }
+}
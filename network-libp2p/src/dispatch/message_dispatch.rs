@@ -2,7 +2,7 @@ use std::collections::VecDeque;
 use std::task::Waker;
 use std::{collections::HashMap, pin::Pin, sync::Arc};
 
-use bytes::{Buf, Bytes};
+use bytes::Bytes;
 use futures::{
     channel::mpsc,
     io::{AsyncRead, AsyncWrite},
@@ -14,6 +14,8 @@ use futures::{
 use tokio_util::codec::Framed;
 
 use beserial::{Deserialize, Serialize};
+use nimiq_network_interface::recording::MessageRecorder;
+use nimiq_utils::memory::{MemoryGauge, MemoryReservation};
 
 use super::codecs::{
     tokio_adapter::TokioAdapter,
@@ -66,12 +68,18 @@ where
 
     /// A single buffer slot. This is needed because we can only find out if we have capacity for a message after
     /// receiving it.
-    buffer: Option<(MessageType, Bytes)>,
+    buffer: Option<(MessageType, Bytes, MemoryReservation)>,
 
     /// The buffer size for new channels.
     channel_size: usize,
 
-    outbound_messages: VecDeque<Box<dyn SendMessage<FramedStream<C>>>>,
+    outbound_messages: VecDeque<(Box<dyn SendMessage<FramedStream<C>>>, MemoryReservation)>,
+
+    /// If set, all inbound messages are recorded.
+    recorder: Option<Arc<MessageRecorder>>,
+
+    /// Counts the bytes of the buffered inbound and outbound messages.
+    memory: MemoryGauge,
 
     waker: Option<Waker>,
 }
@@ -96,15 +104,28 @@ where
             buffer: None,
             channel_size,
             outbound_messages: VecDeque::new(),
+            recorder: None,
+            memory: MemoryGauge::new(),
             waker: None,
         }
     }
 
+    pub fn set_recorder(&mut self, recorder: Option<Arc<MessageRecorder>>) {
+        self.recorder = recorder;
+    }
+
+    /// Sets the gauge that counts the buffered messages. This should be done before any messages
+    /// are sent or received.
+    pub fn set_memory(&mut self, memory: MemoryGauge) {
+        self.memory = memory;
+    }
+
     pub fn send<M: Message>(&mut self, message: M) -> Result<(), Error> {
-        self.outbound_messages
-            .push_back(Box::new(move |sink: Pin<&mut FramedStream<C>>| {
-                Sink::<&M>::start_send(sink, &message)
-            }));
+        let reservation = self.memory.reserve(message.serialized_size());
+        self.outbound_messages.push_back((
+            Box::new(move |sink: Pin<&mut FramedStream<C>>| Sink::<&M>::start_send(sink, &message)),
+            reservation,
+        ));
         if let Some(waker) = self.waker.take() {
             waker.wake();
         }
@@ -120,7 +141,7 @@ where
     ) -> Poll<Result<(), Error>> {
         loop {
             // Try to dispatch the buffered value. This will return Poll::Pending if the buffer can't be cleared.
-            if let Some((type_id, _)) = &self.buffer {
+            if let Some((type_id, ..)) = &self.buffer {
                 let type_id = *type_id;
 
                 if let Some(tx) = self.channels.get_mut(&type_id) {
@@ -145,7 +166,7 @@ where
                         // We have space, so send the message to the channel.
                         Poll::Ready(Ok(())) => {
                             // Take the buffered message. We know that there is one, from the outer `if let Some`-block
-                            let (_, data) = self.buffer.take().unwrap();
+                            let (_, data, _) = self.buffer.take().unwrap();
 
                             // Not sure why this still can fail, but if it does, we consider the receiver to be gone.
                             if let Err(e) = tx.start_send((data, Arc::clone(peer))) {
@@ -183,9 +204,14 @@ where
                     // receivers).
                     assert!(self.buffer.is_none());
 
+                    if let Some(recorder) = &self.recorder {
+                        recorder.record_message(&peer.id, type_id.into(), &data);
+                    }
+
                     // We 'freeze' the message, i.e. turning the `BytesMut` into a `Bytes`. We could use this to cheaply
                     // clone the reference to the data.
-                    self.buffer = Some((type_id, data.freeze()));
+                    let reservation = self.memory.reserve(data.len());
+                    self.buffer = Some((type_id, data.freeze(), reservation));
                 }
 
                 // Error while receiving a message. This could be an error from the underlying socket (i.e. an
@@ -225,7 +251,7 @@ where
         ))
         .is_ok()
         {
-            if let Some(send_message) = self.outbound_messages.pop_front() {
+            if let Some((send_message, _)) = self.outbound_messages.pop_front() {
                 if let Err(e) = send_message.send(self.framed.as_mut()) {
                     return Poll::Ready(Err(e));
                 }
@@ -288,7 +314,7 @@ where
         self.channels.insert(M::TYPE_ID.into(), tx);
 
         rx.filter_map(|(data, peer)| async move {
-            match Deserialize::deserialize(&mut data.reader()) {
+            match M::deserialize_from_bytes(data) {
                 Ok(message) => Some(message),
                 Err(e) => {
                     log::warn!(
@@ -28,6 +28,12 @@ pub enum NetworkError {
     #[error("DHT PutRecord error: {0:?}")]
     DhtPutRecord(libp2p::kad::PutRecordError),
 
+    #[error("DHT StartProviding error: {0:?}")]
+    DhtStartProviding(libp2p::kad::AddProviderError),
+
+    #[error("DHT GetProviders error: {0:?}")]
+    DhtGetProviders(libp2p::kad::GetProvidersError),
+
     #[error("Gossipsub Publish error: {0:?}")]
     GossipsubPublish(libp2p::gossipsub::error::PublishError),
 
@@ -65,6 +71,18 @@ impl From<libp2p::kad::PutRecordError> for NetworkError {
     }
 }
 
+impl From<libp2p::kad::AddProviderError> for NetworkError {
+    fn from(e: libp2p::kad::AddProviderError) -> Self {
+        Self::DhtStartProviding(e)
+    }
+}
+
+impl From<libp2p::kad::GetProvidersError> for NetworkError {
+    fn from(e: libp2p::kad::GetProvidersError) -> Self {
+        Self::DhtGetProviders(e)
+    }
+}
+
 impl From<libp2p::gossipsub::error::PublishError> for NetworkError {
     fn from(e: libp2p::gossipsub::error::PublishError) -> Self {
         Self::GossipsubPublish(e)
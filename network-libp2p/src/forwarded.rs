@@ -0,0 +1,510 @@
+//! Support for listening behind a reverse proxy.
+//!
+//! A node that is run behind a reverse proxy (e.g. nginx or haproxy) sees the address of the proxy
+//! for every inbound connection. Since IP limits and bans are applied to the remote address of a
+//! connection, all peers would share the limits of the proxy.
+//!
+//! For connections from a trusted proxy, the [`ForwardedTransport`] reads the HTTP request of the
+//! websocket upgrade and replaces the remote address with the client address from the
+//! `Forwarded` or `X-Forwarded-For` header. The request is then replayed to the websocket
+//! transport, which performs the actual upgrade.
+
+use std::{
+    io,
+    net::{IpAddr, SocketAddr},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::{
+    future::{self, BoxFuture, FutureExt, TryFutureExt},
+    io::{AsyncRead, AsyncReadExt, AsyncWrite},
+    stream::{BoxStream, FuturesUnordered, Stream, StreamExt},
+};
+use ip_network::IpNetwork;
+use libp2p::{
+    core::{
+        multiaddr::Protocol,
+        transport::{ListenerEvent, TransportError},
+    },
+    Multiaddr, Transport,
+};
+
+/// Maximum size of the HTTP request headers sent by a proxy.
+const MAX_HEADER_SIZE: usize = 8 * 1024;
+
+/// Time a proxied client has to send the HTTP request headers.
+const HEADER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Maximum number of proxied connections that are waiting for their request headers.
+const MAX_PENDING_HEADERS: usize = 64;
+
+type ForwardedUpgrade<S> = BoxFuture<'static, io::Result<PrefixedStream<S>>>;
+type ForwardedEvent<S> = ListenerEvent<ForwardedUpgrade<S>, io::Error>;
+
+/// Transport that rewrites the remote address of connections from trusted proxies to the address
+/// of the client behind the proxy.
+///
+/// This must be used below the websocket transport, since it relies on the HTTP request of the
+/// websocket upgrade. Connections from any other address are passed through unchanged.
+#[derive(Clone)]
+pub struct ForwardedTransport<T> {
+    inner: T,
+    trusted_proxies: Arc<Vec<IpNetwork>>,
+}
+
+impl<T> ForwardedTransport<T> {
+    pub fn new(inner: T, trusted_proxies: Vec<IpNetwork>) -> Self {
+        Self {
+            inner,
+            trusted_proxies: Arc::new(trusted_proxies),
+        }
+    }
+}
+
+impl<T> Transport for ForwardedTransport<T>
+where
+    T: Transport<Error = io::Error> + Send + 'static,
+    T::Output: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    T::Listener: Send + 'static,
+    T::ListenerUpgrade: Send + 'static,
+    T::Dial: Send + 'static,
+{
+    type Output = PrefixedStream<T::Output>;
+    type Error = io::Error;
+    type Listener = ForwardedListener<T::Output>;
+    type ListenerUpgrade = ForwardedUpgrade<T::Output>;
+    type Dial = ForwardedUpgrade<T::Output>;
+
+    fn listen_on(self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
+        let inner = self
+            .inner
+            .listen_on(addr)?
+            .map(|event| event.map(|event| event.map(|upgrade| upgrade.boxed())))
+            .boxed();
+
+        Ok(ForwardedListener {
+            inner: Some(inner),
+            trusted_proxies: self.trusted_proxies,
+            pending: FuturesUnordered::new(),
+        })
+    }
+
+    fn dial(self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
+        Ok(self.inner.dial(addr)?.map_ok(PrefixedStream::plain).boxed())
+    }
+
+    fn dial_as_listener(self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
+        Ok(self
+            .inner
+            .dial_as_listener(addr)?
+            .map_ok(PrefixedStream::plain)
+            .boxed())
+    }
+
+    fn address_translation(&self, listen: &Multiaddr, observed: &Multiaddr) -> Option<Multiaddr> {
+        self.inner.address_translation(listen, observed)
+    }
+}
+
+/// Listener of the [`ForwardedTransport`].
+///
+/// Inbound connections from trusted proxies are only reported once their request headers have been
+/// read, all other events are passed through immediately.
+pub struct ForwardedListener<S> {
+    inner: Option<
+        BoxStream<
+            'static,
+            Result<ListenerEvent<BoxFuture<'static, io::Result<S>>, io::Error>, io::Error>,
+        >,
+    >,
+    trusted_proxies: Arc<Vec<IpNetwork>>,
+    pending: FuturesUnordered<BoxFuture<'static, ForwardedEvent<S>>>,
+}
+
+impl<S> ForwardedListener<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    fn on_upgrade(
+        &mut self,
+        upgrade: BoxFuture<'static, io::Result<S>>,
+        local_addr: Multiaddr,
+        remote_addr: Multiaddr,
+    ) -> Option<ForwardedEvent<S>> {
+        let from_proxy = remote_ip(&remote_addr)
+            .map(|ip| is_trusted(&self.trusted_proxies, ip))
+            .unwrap_or(false);
+
+        if !from_proxy {
+            return Some(ListenerEvent::Upgrade {
+                upgrade: upgrade.map_ok(PrefixedStream::plain).boxed(),
+                local_addr,
+                remote_addr,
+            });
+        }
+
+        if self.pending.len() >= MAX_PENDING_HEADERS {
+            log::warn!(
+                "Too many proxied connections waiting for headers, dropping connection from {}",
+                remote_addr
+            );
+            return Some(ListenerEvent::Upgrade {
+                upgrade: future::err(io::Error::new(
+                    io::ErrorKind::Other,
+                    "Too many pending proxied connections",
+                ))
+                .boxed(),
+                local_addr,
+                remote_addr,
+            });
+        }
+
+        let trusted_proxies = Arc::clone(&self.trusted_proxies);
+        self.pending.push(
+            async move {
+                let result = async {
+                    let stream = upgrade.await?;
+                    tokio::time::timeout(HEADER_TIMEOUT, read_forwarded(stream, &trusted_proxies))
+                        .await
+                        .map_err(|_| {
+                            io::Error::new(
+                                io::ErrorKind::TimedOut,
+                                "Timeout reading request headers",
+                            )
+                        })?
+                }
+                .await;
+
+                match result {
+                    Ok((stream, client_ip)) => {
+                        let remote_addr = match client_ip {
+                            Some(ip) => {
+                                log::trace!("Connection from {} forwarded for {}", remote_addr, ip);
+                                replace_ip(&remote_addr, ip)
+                            }
+                            None => {
+                                log::debug!(
+                                    "Connection from trusted proxy {} without forwarded header",
+                                    remote_addr
+                                );
+                                remote_addr
+                            }
+                        };
+                        ListenerEvent::Upgrade {
+                            upgrade: future::ok(stream).boxed(),
+                            local_addr,
+                            remote_addr,
+                        }
+                    }
+                    Err(error) => ListenerEvent::Upgrade {
+                        upgrade: future::err(error).boxed(),
+                        local_addr,
+                        remote_addr,
+                    },
+                }
+            }
+            .boxed(),
+        );
+
+        None
+    }
+}
+
+impl<S> Stream for ForwardedListener<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    type Item = Result<ForwardedEvent<S>, io::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        loop {
+            if let Poll::Ready(Some(event)) = this.pending.poll_next_unpin(cx) {
+                return Poll::Ready(Some(Ok(event)));
+            }
+
+            let inner = match this.inner.as_mut() {
+                Some(inner) => inner,
+                None if this.pending.is_empty() => return Poll::Ready(None),
+                None => return Poll::Pending,
+            };
+
+            match inner.poll_next_unpin(cx) {
+                Poll::Ready(Some(Ok(ListenerEvent::Upgrade {
+                    upgrade,
+                    local_addr,
+                    remote_addr,
+                }))) => {
+                    if let Some(event) = this.on_upgrade(upgrade, local_addr, remote_addr) {
+                        return Poll::Ready(Some(Ok(event)));
+                    }
+                    // The connection is now pending, poll it once to register the waker.
+                }
+                Poll::Ready(Some(Ok(event))) => {
+                    return Poll::Ready(Some(Ok(
+                        event.map(|upgrade| upgrade.map_ok(PrefixedStream::plain).boxed())
+                    )));
+                }
+                Poll::Ready(Some(Err(error))) => return Poll::Ready(Some(Err(error))),
+                Poll::Ready(None) => this.inner = None,
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/// A stream that first returns the bytes that were already read from the inner stream.
+pub struct PrefixedStream<S> {
+    prefix: Vec<u8>,
+    offset: usize,
+    inner: S,
+}
+
+impl<S> PrefixedStream<S> {
+    fn new(prefix: Vec<u8>, inner: S) -> Self {
+        Self {
+            prefix,
+            offset: 0,
+            inner,
+        }
+    }
+
+    fn plain(inner: S) -> Self {
+        Self::new(Vec::new(), inner)
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for PrefixedStream<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        if this.offset < this.prefix.len() {
+            let remaining = &this.prefix[this.offset..];
+            let n = remaining.len().min(buf.len());
+            buf[..n].copy_from_slice(&remaining[..n]);
+            this.offset += n;
+
+            if this.offset == this.prefix.len() {
+                this.prefix = Vec::new();
+                this.offset = 0;
+            }
+            return Poll::Ready(Ok(n));
+        }
+
+        Pin::new(&mut this.inner).poll_read(cx, buf)
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for PrefixedStream<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_close(cx)
+    }
+}
+
+/// Reads the HTTP request headers from the stream and extracts the client address. The returned
+/// stream replays everything that was read.
+async fn read_forwarded<S: AsyncRead + Unpin>(
+    mut stream: S,
+    trusted_proxies: &[IpNetwork],
+) -> io::Result<(PrefixedStream<S>, Option<IpAddr>)> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 1024];
+
+    loop {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        buffer.extend_from_slice(&chunk[..n]);
+
+        if let Some(end) = buffer.windows(4).position(|window| window == b"\r\n\r\n") {
+            let client_ip = parse_client_ip(&buffer[..end], trusted_proxies);
+            return Ok((PrefixedStream::new(buffer, stream), client_ip));
+        }
+
+        if buffer.len() > MAX_HEADER_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Request headers too large",
+            ));
+        }
+    }
+}
+
+/// Extracts the client address from the `Forwarded` or `X-Forwarded-For` headers. `Forwarded` takes
+/// precedence if both are present.
+///
+/// Every proxy appends the address it received the request from, so only the last entries can be
+/// trusted. The client is the last address that doesn't belong to a trusted proxy.
+fn parse_client_ip(headers: &[u8], trusted_proxies: &[IpNetwork]) -> Option<IpAddr> {
+    let headers = String::from_utf8_lossy(headers);
+
+    let mut forwarded = vec![];
+    let mut x_forwarded_for = vec![];
+
+    // Skip the request line.
+    for line in headers.split("\r\n").skip(1) {
+        let (name, value) = match line.split_once(':') {
+            Some(header) => header,
+            None => continue,
+        };
+
+        if name.trim().eq_ignore_ascii_case("forwarded") {
+            for element in value.split(',') {
+                for pair in element.split(';') {
+                    if let Some((key, value)) = pair.split_once('=') {
+                        if key.trim().eq_ignore_ascii_case("for") {
+                            forwarded.push(parse_ip(value));
+                        }
+                    }
+                }
+            }
+        } else if name.trim().eq_ignore_ascii_case("x-forwarded-for") {
+            x_forwarded_for.extend(value.split(',').map(parse_ip));
+        }
+    }
+
+    let addresses = if forwarded.is_empty() {
+        x_forwarded_for
+    } else {
+        forwarded
+    };
+
+    let mut client_ip = None;
+    for address in addresses.into_iter().rev() {
+        // Stop at addresses we can't parse (e.g. obfuscated identifiers), we can't tell whether
+        // they belong to a proxy.
+        let ip = address?;
+        client_ip = Some(ip);
+        if !is_trusted(trusted_proxies, ip) {
+            break;
+        }
+    }
+    client_ip
+}
+
+/// Parses an IP address with an optional port, as found in forwarded headers.
+fn parse_ip(value: &str) -> Option<IpAddr> {
+    let value = value.trim().trim_matches('"');
+
+    if let Ok(ip) = value.parse::<IpAddr>() {
+        return Some(ip);
+    }
+    if let Ok(address) = value.parse::<SocketAddr>() {
+        return Some(address.ip());
+    }
+
+    // IPv6 address in brackets without port
+    value
+        .strip_prefix('[')
+        .and_then(|value| value.strip_suffix(']'))
+        .and_then(|value| value.parse().ok())
+}
+
+fn is_trusted(trusted_proxies: &[IpNetwork], ip: IpAddr) -> bool {
+    trusted_proxies.iter().any(|network| network.contains(ip))
+}
+
+fn remote_ip(address: &Multiaddr) -> Option<IpAddr> {
+    match address.iter().next() {
+        Some(Protocol::Ip4(ip)) => Some(ip.into()),
+        Some(Protocol::Ip6(ip)) => Some(ip.into()),
+        _ => None,
+    }
+}
+
+/// Replaces the IP address of the given address, keeping everything else.
+fn replace_ip(address: &Multiaddr, ip: IpAddr) -> Multiaddr {
+    address
+        .iter()
+        .enumerate()
+        .map(|(i, protocol)| match (i, protocol) {
+            (0, Protocol::Ip4(_)) | (0, Protocol::Ip6(_)) => Protocol::from(ip),
+            (_, protocol) => protocol,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proxies() -> Vec<IpNetwork> {
+        vec![
+            "10.0.0.0/8".parse().unwrap(),
+            "2001:db8::/32".parse().unwrap(),
+        ]
+    }
+
+    fn request(headers: &str) -> Vec<u8> {
+        format!("GET / HTTP/1.1\r\nHost: example.com\r\n{}", headers).into_bytes()
+    }
+
+    #[test]
+    fn it_uses_the_last_untrusted_address() {
+        let headers = request("X-Forwarded-For: 203.0.113.1, 198.51.100.7, 10.1.2.3");
+        assert_eq!(
+            parse_client_ip(&headers, &proxies()),
+            Some("198.51.100.7".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn it_prefers_the_forwarded_header() {
+        let headers = request(
+            "x-forwarded-for: 203.0.113.1\r\n\
+             Forwarded: for=\"[2001:db8::1]:4711\";proto=http, for=198.51.100.7:1234",
+        );
+        assert_eq!(
+            parse_client_ip(&headers, &proxies()),
+            Some("198.51.100.7".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn it_ignores_requests_without_forwarded_headers() {
+        assert_eq!(parse_client_ip(&request(""), &proxies()), None);
+        assert_eq!(
+            parse_client_ip(&request("Forwarded: for=unknown"), &proxies()),
+            None
+        );
+    }
+
+    #[test]
+    fn it_replaces_the_ip_of_an_address() {
+        let address: Multiaddr = "/ip4/10.0.0.1/tcp/8443".parse().unwrap();
+        assert_eq!(
+            replace_ip(&address, "2001:db9::1".parse().unwrap()),
+            "/ip6/2001:db9::1/tcp/8443".parse::<Multiaddr>().unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn it_replays_the_request() {
+        let data = request("X-Forwarded-For: 203.0.113.1\r\n\r\nbody");
+        let (mut stream, client_ip) = read_forwarded(&data[..], &proxies()).await.unwrap();
+        assert_eq!(client_ip, Some("203.0.113.1".parse().unwrap()));
+
+        let mut replayed = vec![];
+        stream.read_to_end(&mut replayed).await.unwrap();
+        assert_eq!(replayed, data);
+    }
+}
@@ -12,8 +12,14 @@ mod connection_pool;
 pub mod discovery;
 pub mod dispatch;
 mod error;
+mod forwarded;
 mod network;
 pub mod peer;
+mod peer_stats;
+mod port_mapping;
+mod seen_messages;
+mod tls;
+mod topic_buffer;
 
 pub const MESSAGE_PROTOCOL: &[u8] = b"/nimiq/message/0.0.1";
 pub const DISCOVERY_PROTOCOL: &[u8] = b"/nimiq/discovery/0.0.1";
@@ -21,5 +27,9 @@ pub const DISCOVERY_PROTOCOL: &[u8] = b"/nimiq/discovery/0.0.1";
 pub use libp2p::{self, identity::Keypair, swarm::NetworkInfo, Multiaddr, PeerId};
 
 pub use config::Config;
+pub use connection_pool::behaviour::DialPriority;
 pub use error::NetworkError;
-pub use network::Network;
+pub use network::{Network, SwarmHealth};
+pub use peer_stats::PeerStats;
+pub use tls::TlsCertificates;
+pub use topic_buffer::GossipTopicStats;
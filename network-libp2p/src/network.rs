@@ -1,9 +1,12 @@
 #![allow(dead_code)]
 
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    sync::Arc,
+};
 
 use async_trait::async_trait;
-use bytes::{Buf, Bytes};
+use bytes::Bytes;
 use futures::executor;
 use futures::{
     channel::{mpsc, oneshot},
@@ -13,9 +16,14 @@ use futures::{
 #[cfg(test)]
 use libp2p::core::transport::MemoryTransport;
 use libp2p::{
+    autonat::{self, NatStatus},
     core,
-    core::{muxing::StreamMuxerBox, transport::Boxed},
-    dns,
+    core::{
+        connection::{ConnectedPoint, ListenerId},
+        muxing::StreamMuxerBox,
+        transport::{Boxed, OptionalTransport},
+    },
+    dcutr, dns,
     gossipsub::{
         error::PublishError, GossipsubEvent, GossipsubMessage, IdentTopic, MessageAcceptance,
         MessageId, TopicHash, TopicScoreParams,
@@ -23,14 +31,19 @@ use libp2p::{
     identify::IdentifyEvent,
     identity::Keypair,
     kad::{
-        store::RecordStore, GetRecordOk, InboundRequest, KademliaEvent, QueryId, QueryResult,
-        Quorum, Record,
+        store::RecordStore, GetProvidersOk, GetRecordOk, InboundRequest, KademliaEvent, QueryId,
+        QueryResult, Quorum, Record,
     },
+    multiaddr::Protocol,
     noise,
     ping::Success,
-    swarm::{dial_opts::DialOpts, ConnectionLimits, NetworkInfo, SwarmBuilder, SwarmEvent},
+    relay::v2::client::{self, transport::ClientTransport},
+    swarm::{
+        dial_opts::DialOpts, AddressScore, ConnectionLimits, NetworkInfo, SwarmBuilder, SwarmEvent,
+    },
     tcp, websocket, yamux, Multiaddr, PeerId, Swarm, Transport,
 };
+use tokio::runtime::Handle;
 use tokio::sync::broadcast;
 use tokio_stream::wrappers::BroadcastStream;
 use tracing::Instrument;
@@ -39,22 +52,51 @@ use beserial::{Deserialize, Serialize};
 use nimiq_bls::CompressedPublicKey;
 use nimiq_network_interface::{
     message::{Message, MessageType},
-    network::{MsgAcceptance, Network as NetworkInterface, NetworkEvent, PubsubId, Topic},
+    network::{
+        MsgAcceptance, Network as NetworkInterface, NetworkEvent, OverflowPolicy, PubsubId, Topic,
+    },
     peer::Peer as PeerInterface,
     peer_map::ObservablePeerMap,
+    recording::MessageRecorder,
 };
+use nimiq_utils::memory::MemoryGauge;
+use nimiq_utils::stall;
 use nimiq_utils::time::OffsetTime;
 use nimiq_validator_network::validator_record::SignedValidatorRecord;
 
 use crate::{
     behaviour::{NimiqBehaviour, NimiqEvent, NimiqNetworkBehaviourError},
     connection_pool::behaviour::ConnectionPoolEvent,
+    discovery::behaviour::DiscoveryEvent,
+    forwarded::ForwardedTransport,
     peer::Peer,
+    peer_stats::PeerStats,
+    port_mapping::{self, PortMappingEvent},
+    seen_messages::SeenMessages,
+    tls::{TlsListeners, TlsTransport, WssTransport},
+    topic_buffer::{GossipItem, GossipTopicStats, TopicBuffer},
     Config, NetworkError,
 };
 
-/// Maximum simultaneous libp2p connections per peer
-const MAX_CONNECTIONS_PER_PEER: u32 = 1;
+/// Maximum simultaneous libp2p connections per peer. A second connection is only kept while a
+/// direct connection established by hole punching replaces a relayed one.
+const MAX_CONNECTIONS_PER_PEER: u32 = 2;
+
+/// Interval in which the swarm task checks for connectivity loss and empty gossipsub meshes.
+const SUPERVISION_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// How long the IDs of seen gossipsub messages are kept across restarts.
+const SEEN_MESSAGES_RETENTION: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+
+/// Protocol of the peers that relay connections, as announced via identify.
+const RELAY_HOP_PROTOCOL: &str = "/libp2p/circuit/relay/0.2.0/hop";
+
+/// A single poll of the swarm task that takes longer than this is reported as a stall, since it
+/// delays all network I/O.
+const SWARM_POLL_THRESHOLD: std::time::Duration = std::time::Duration::from_millis(250);
+
+/// How long the swarm task may take to answer a heartbeat before it is reported as unresponsive.
+const HEARTBEAT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
 
 type NimiqSwarm = Swarm<NimiqBehaviour>;
 #[derive(Debug)]
@@ -76,13 +118,21 @@ pub(crate) enum NetworkAction {
         value: Vec<u8>,
         output: oneshot::Sender<Result<(), NetworkError>>,
     },
+    DhtProvide {
+        key: Vec<u8>,
+        output: oneshot::Sender<Result<(), NetworkError>>,
+    },
+    DhtGetProviders {
+        key: Vec<u8>,
+        output: oneshot::Sender<Result<Vec<PeerId>, NetworkError>>,
+    },
     Subscribe {
         topic_name: &'static str,
         buffer_size: usize,
         validate: bool,
-        output: oneshot::Sender<
-            Result<mpsc::Receiver<(GossipsubMessage, MessageId, PeerId)>, NetworkError>,
-        >,
+        overflow_policy: OverflowPolicy,
+        persist_seen: bool,
+        output: oneshot::Sender<Result<BoxStream<'static, GossipItem>, NetworkError>>,
     },
     Unsubscribe {
         topic_name: &'static str,
@@ -96,6 +146,17 @@ pub(crate) enum NetworkAction {
     NetworkInfo {
         output: oneshot::Sender<NetworkInfo>,
     },
+    PeerAddresses {
+        peer_id: PeerId,
+        output: oneshot::Sender<Vec<Multiaddr>>,
+    },
+    PeerStats {
+        peer_id: PeerId,
+        output: oneshot::Sender<Option<PeerStats>>,
+    },
+    GossipStats {
+        output: oneshot::Sender<Vec<GossipTopicStats>>,
+    },
     Validate {
         message_id: MessageId,
         source: PeerId,
@@ -110,6 +171,9 @@ pub(crate) enum NetworkAction {
         listen_addresses: Vec<Multiaddr>,
     },
     StartConnecting,
+    Heartbeat {
+        output: oneshot::Sender<()>,
+    },
 }
 
 struct ValidateMessage<P: Clone> {
@@ -139,8 +203,117 @@ impl<P: Clone> ValidateMessage<P> {
 struct TaskState {
     dht_puts: HashMap<QueryId, oneshot::Sender<Result<(), NetworkError>>>,
     dht_gets: HashMap<QueryId, oneshot::Sender<Result<Option<Vec<u8>>, NetworkError>>>,
-    gossip_topics: HashMap<TopicHash, (mpsc::Sender<(GossipsubMessage, MessageId, PeerId)>, bool)>,
+    dht_provides: HashMap<QueryId, oneshot::Sender<Result<(), NetworkError>>>,
+    dht_get_providers: HashMap<QueryId, oneshot::Sender<Result<Vec<PeerId>, NetworkError>>>,
+    gossip_topics: HashMap<TopicHash, TopicBuffer>,
     is_bootstraped: bool,
+    /// Tracks whether the number of connected peers dropped below the minimum.
+    connectivity: Connectivity,
+    /// If set, all inbound gossipsub messages are recorded.
+    recorder: Option<Arc<MessageRecorder>>,
+    /// If set, the IDs of the seen gossipsub messages are persisted across restarts.
+    seen_messages: Option<SeenMessages>,
+    /// Latency and clock statistics of the connected peers.
+    peer_stats: HashMap<PeerId, PeerStats>,
+    /// Connected peers that relay connections, with an address to reach them.
+    relays: HashMap<PeerId, Multiaddr>,
+    /// The addresses we dialed the connected peers at. Unlike the listen addresses they announce,
+    /// these are known to be reachable.
+    dialed_addresses: HashMap<PeerId, Multiaddr>,
+    /// The listener on a relayed address while we aren't reachable from the outside, and the relay
+    /// it goes through.
+    relay_listener: Option<(ListenerId, PeerId)>,
+    /// If set, the listen ports are mapped on the router and the mapped addresses are sent here.
+    port_mapping: Option<mpsc::UnboundedSender<PortMappingEvent>>,
+    /// The ports that are being mapped, with the listener they belong to. Dropping the sender
+    /// removes the mapping from the router.
+    mapped_ports: HashMap<u16, (ListenerId, oneshot::Sender<()>)>,
+    /// Mapped addresses that AutoNAT didn't confirm to be reachable yet. They are only advertised
+    /// once confirmed.
+    unconfirmed_mappings: HashSet<Multiaddr>,
+}
+
+/// The state of the swarm task as determined by a heartbeat, see [`Network::swarm_health`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SwarmHealth {
+    /// The swarm task answered the heartbeat.
+    Alive,
+    /// The swarm task didn't answer the heartbeat in time, e.g. because it is stalled.
+    Unresponsive,
+    /// The swarm task terminated.
+    Dead,
+}
+
+/// A change of the connectivity, see [`Connectivity::update`].
+#[derive(Debug, PartialEq, Eq)]
+enum ConnectivityChange {
+    Unchanged,
+    /// All peers were lost.
+    Lost,
+    /// The number of connected peers dropped below the minimum.
+    Degraded(usize),
+    /// Enough peers are connected again after the connectivity was lost or degraded.
+    Recovered,
+}
+
+/// Tracks the number of connected peers against the minimum that is needed to keep the gossipsub
+/// meshes filled.
+#[derive(Default)]
+struct Connectivity {
+    min_peers: usize,
+    /// Set once the first peer connected.
+    had_peers: bool,
+    /// Set once the minimum number of peers was connected. Until then, only the loss of all peers
+    /// is detected, so that small networks don't count as degraded.
+    reached_min_peers: bool,
+    /// Set once the connectivity was lost or degraded. Reset once it recovered.
+    degraded: bool,
+    /// Set once all peers were lost. Reset once any peer connected, so that the loss of all peers
+    /// is also reported when the connectivity was already degraded.
+    lost: bool,
+}
+
+impl Connectivity {
+    fn new(min_peers: usize) -> Self {
+        Connectivity {
+            min_peers,
+            ..Default::default()
+        }
+    }
+
+    fn update(&mut self, num_peers: usize) -> ConnectivityChange {
+        if num_peers == 0 {
+            if self.had_peers && !self.lost {
+                self.lost = true;
+                self.degraded = true;
+                return ConnectivityChange::Lost;
+            }
+            return ConnectivityChange::Unchanged;
+        }
+        self.had_peers = true;
+        self.lost = false;
+
+        if num_peers < self.min_peers {
+            if self.reached_min_peers && !self.degraded {
+                self.degraded = true;
+                return ConnectivityChange::Degraded(num_peers);
+            }
+            // If the minimum was never reached, the connectivity was lost entirely and any peer
+            // counts as a recovery.
+            if !self.reached_min_peers && self.degraded {
+                self.degraded = false;
+                return ConnectivityChange::Recovered;
+            }
+            return ConnectivityChange::Unchanged;
+        }
+        self.reached_min_peers = true;
+
+        if self.degraded {
+            self.degraded = false;
+            return ConnectivityChange::Recovered;
+        }
+        ConnectivityChange::Unchanged
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -161,6 +334,15 @@ pub struct Network {
     action_tx: mpsc::Sender<NetworkAction>,
     peers: ObservablePeerMap<Peer>,
     validate_tx: mpsc::UnboundedSender<ValidateMessage<PeerId>>,
+    peer_buffers: MemoryGauge,
+    watchdog: tokio::task::JoinHandle<()>,
+}
+
+impl Drop for Network {
+    fn drop(&mut self) {
+        // The watchdog holds an action sender, which would keep the swarm task running.
+        self.watchdog.abort();
+    }
 }
 
 impl Network {
@@ -174,19 +356,37 @@ impl Network {
     ///
     pub async fn new(clock: Arc<OffsetTime>, config: Config) -> Self {
         let peers = ObservablePeerMap::new();
+        let recorder = config.recorder.clone();
+        let seen_messages = config
+            .seen_messages_file
+            .clone()
+            .map(|path| SeenMessages::load(path, SEEN_MESSAGES_RETENTION));
+        let runtime = config.runtime.clone().unwrap_or_else(Handle::current);
+        let port_mapping = config.port_mapping;
+        let min_peers = config.gossipsub.mesh_n_low();
         let swarm = Self::new_swarm(clock, config, peers.clone());
 
         let local_peer_id = *Swarm::local_peer_id(&swarm);
+        let peer_buffers = swarm.behaviour().pool.memory();
 
         let (events_tx, _) = broadcast::channel(64);
         let (action_tx, action_rx) = mpsc::channel(64);
         let (validate_tx, validate_rx) = mpsc::unbounded();
 
-        tokio::spawn(Self::swarm_task(
-            swarm,
-            events_tx.clone(),
-            action_rx,
-            validate_rx,
+        let watchdog = runtime.spawn(Self::swarm_watchdog(action_tx.clone()));
+        runtime.spawn(stall::instrument(
+            "swarm",
+            SWARM_POLL_THRESHOLD,
+            Self::swarm_task(
+                swarm,
+                events_tx.clone(),
+                action_rx,
+                validate_rx,
+                recorder,
+                seen_messages,
+                port_mapping,
+                min_peers,
+            ),
         ));
 
         Self {
@@ -195,26 +395,105 @@ impl Network {
             action_tx,
             peers,
             validate_tx,
+            peer_buffers,
+            watchdog,
         }
     }
 
-    fn new_transport(keypair: &Keypair) -> std::io::Result<Boxed<(PeerId, StreamMuxerBox)>> {
+    /// Sends a heartbeat to the swarm task in every supervision interval and reports if it doesn't
+    /// answer in time. Returns once the swarm task terminated.
+    async fn swarm_watchdog(action_tx: mpsc::Sender<NetworkAction>) {
+        let mut interval = tokio::time::interval(SUPERVISION_INTERVAL);
+        let mut unresponsive = false;
+        loop {
+            interval.tick().await;
+            match Self::heartbeat(&action_tx, HEARTBEAT_TIMEOUT).await {
+                SwarmHealth::Alive => {
+                    if unresponsive {
+                        tracing::info!("Swarm task is responsive again");
+                        unresponsive = false;
+                    }
+                }
+                SwarmHealth::Unresponsive => {
+                    if !unresponsive {
+                        tracing::warn!(timeout = ?HEARTBEAT_TIMEOUT, "Swarm task is unresponsive");
+                        unresponsive = true;
+                    }
+                }
+                SwarmHealth::Dead => {
+                    tracing::error!("Swarm task terminated, the network is down");
+                    return;
+                }
+            }
+        }
+    }
+
+    async fn heartbeat(
+        action_tx: &mpsc::Sender<NetworkAction>,
+        timeout: std::time::Duration,
+    ) -> SwarmHealth {
+        let (output_tx, output_rx) = oneshot::channel();
+        if action_tx
+            .clone()
+            .send(NetworkAction::Heartbeat { output: output_tx })
+            .await
+            .is_err()
+        {
+            return SwarmHealth::Dead;
+        }
+        match tokio::time::timeout(timeout, output_rx).await {
+            Ok(Ok(())) => SwarmHealth::Alive,
+            // The swarm task dropped the action without answering, i.e. it terminated.
+            Ok(Err(_)) => SwarmHealth::Dead,
+            Err(_) => SwarmHealth::Unresponsive,
+        }
+    }
+
+    fn new_transport(
+        config: &Config,
+        relay_transport: ClientTransport,
+    ) -> std::io::Result<Boxed<(PeerId, StreamMuxerBox)>> {
+        // Plain TCP/DNS, only used for addresses without `/ws`. The websocket transport is tried
+        // first, since it only accepts addresses with `/ws`.
+        let plain_tcp = if config.plain_tcp {
+            OptionalTransport::some(dns::TokioDnsConfig::system(
+                tcp::TokioTcpConfig::new().nodelay(true),
+            )?)
+        } else {
+            OptionalTransport::none()
+        };
+
         // Websocket over TCP/DNS
+        // TLS of `/wss` listen addresses is accepted below the websocket transport, so that the
+        // certificates can be reloaded.
+        let tls_listeners = TlsListeners::new(config.tls.clone());
+        let websocket = WssTransport::new(
+            websocket::WsConfig::new(dns::TokioDnsConfig::system(ForwardedTransport::new(
+                TlsTransport::new(
+                    tcp::TokioTcpConfig::new().nodelay(true),
+                    tls_listeners.clone(),
+                ),
+                config.trusted_proxies.clone(),
+            ))?),
+            tls_listeners,
+        );
+
+        // Relayed connections, only used for `/p2p-circuit` addresses.
         #[cfg(not(test))]
-        let transport = websocket::WsConfig::new(dns::TokioDnsConfig::system(
-            tcp::TokioTcpConfig::new().nodelay(true),
-        )?);
+        let transport = websocket
+            .or_transport(plain_tcp)
+            .or_transport(relay_transport);
 
         // Memory transport for testing
         // TODO: Use websocket over the memory transport
         #[cfg(test)]
-        let transport = websocket::WsConfig::new(dns::TokioDnsConfig::system(
-            tcp::TokioTcpConfig::new().nodelay(true),
-        )?)
-        .or_transport(MemoryTransport::default());
+        let transport = websocket
+            .or_transport(plain_tcp)
+            .or_transport(relay_transport)
+            .or_transport(MemoryTransport::default());
 
         let noise_keys = noise::Keypair::<noise::X25519Spec>::new()
-            .into_authentic(keypair)
+            .into_authentic(&config.keypair)
             .unwrap();
 
         let mut yamux = yamux::YamuxConfig::default();
@@ -234,23 +513,36 @@ impl Network {
         peers: ObservablePeerMap<Peer>,
     ) -> Swarm<NimiqBehaviour> {
         let local_peer_id = PeerId::from(config.keypair.public());
+        let runtime = config.runtime.clone().unwrap_or_else(Handle::current);
+
+        let (relay_transport, relay_client) =
+            client::Client::new_transport_and_behaviour(local_peer_id);
+        let transport = Self::new_transport(&config, relay_transport).unwrap();
+        if let Some(certificates) = &config.tls {
+            runtime.spawn(Arc::clone(certificates).watch());
+        }
 
-        let transport = Self::new_transport(&config.keypair).unwrap();
+        // Seed nodes accept many more, but short-lived connections.
+        let (max_pending_incoming, max_established_incoming) = if config.seed {
+            (128, 20_000)
+        } else {
+            (16, 4800)
+        };
 
-        let behaviour = NimiqBehaviour::new(config, clock, peers);
+        let behaviour = NimiqBehaviour::new(config, clock, peers, relay_client);
 
         let limits = ConnectionLimits::default()
-            .with_max_pending_incoming(Some(16))
+            .with_max_pending_incoming(Some(max_pending_incoming))
             .with_max_pending_outgoing(Some(16))
-            .with_max_established_incoming(Some(4800))
+            .with_max_established_incoming(Some(max_established_incoming))
             .with_max_established_outgoing(Some(4800))
             .with_max_established_per_peer(Some(MAX_CONNECTIONS_PER_PEER));
 
         // TODO add proper config
         SwarmBuilder::new(transport, behaviour, local_peer_id)
             .connection_limits(limits)
-            .executor(Box::new(|fut| {
-                tokio::spawn(fut);
+            .executor(Box::new(move |fut| {
+                runtime.spawn(fut);
             }))
             .build()
     }
@@ -259,17 +551,37 @@ impl Network {
         &self.local_peer_id
     }
 
+    /// Returns the gauge that counts the messages buffered by the connected peers.
+    pub fn peer_buffer_memory(&self) -> MemoryGauge {
+        self.peer_buffers.clone()
+    }
+
     async fn swarm_task(
         mut swarm: NimiqSwarm,
         events_tx: broadcast::Sender<NetworkEvent<Peer>>,
         mut action_rx: mpsc::Receiver<NetworkAction>,
         mut validate_rx: mpsc::UnboundedReceiver<ValidateMessage<PeerId>>,
+        recorder: Option<Arc<MessageRecorder>>,
+        seen_messages: Option<SeenMessages>,
+        port_mapping: bool,
+        min_peers: usize,
     ) {
-        let mut task_state = TaskState::default();
+        // Events of the tasks that keep the listen ports mapped on the router.
+        let (port_mapping_tx, mut port_mapping_rx) = mpsc::unbounded();
+
+        let mut task_state = TaskState {
+            recorder,
+            seen_messages,
+            port_mapping: port_mapping.then(|| port_mapping_tx),
+            connectivity: Connectivity::new(min_peers),
+            ..Default::default()
+        };
 
         let peer_id = Swarm::local_peer_id(&swarm);
         let task_span = tracing::trace_span!("swarm task", peer_id=?peer_id);
 
+        let mut supervision_interval = tokio::time::interval(SUPERVISION_INTERVAL);
+
         async move {
             loop {
                 tokio::select! {
@@ -297,6 +609,27 @@ impl Network {
                             Self::handle_event(event, &events_tx, &mut swarm, &mut task_state);
                         }
                     },
+                    Some(event) = port_mapping_rx.next() => {
+                        match event {
+                            PortMappingEvent::Mapped(address) => {
+                                swarm.behaviour_mut().autonat.probe_address(address.clone());
+                                task_state.unconfirmed_mappings.insert(address);
+                                Self::confirm_mappings(&mut swarm, &mut task_state);
+                            }
+                            PortMappingEvent::Expired(address) => {
+                                task_state.unconfirmed_mappings.remove(&address);
+                                swarm.remove_external_address(&address);
+                                swarm.behaviour_mut().discovery.remove_own_address(&address);
+                            }
+                        }
+                    },
+                    _ = supervision_interval.tick() => {
+                        Self::supervise(&events_tx, &mut swarm, &mut task_state);
+                        if let Some(seen_messages) = &mut task_state.seen_messages {
+                            // The file is written in the background.
+                            drop(seen_messages.save());
+                        }
+                    },
                     action = action_rx.next() => {
                         if let Some(action) = action {
                             Self::perform_action(action, &mut swarm, &mut task_state);
@@ -308,11 +641,88 @@ impl Network {
                     },
                 };
             }
+
+            if let Some(seen_messages) = &mut task_state.seen_messages {
+                seen_messages.save().await.ok();
+            }
         }
         .instrument(task_span)
         .await
     }
 
+    /// Detects a loss of connectivity, a number of peers that dropped below the minimum and
+    /// gossipsub meshes that went empty even though peers are subscribed to the topic. Once enough
+    /// peers are connected again, and for empty meshes, our topics are re-subscribed, which
+    /// re-announces the subscriptions and rebuilds the meshes. The existing subscription channels
+    /// are kept, so subscribers won't notice. After the connectivity recovered, a
+    /// `NetworkEvent::Reconnected` is emitted so that dependent components can resync.
+    fn supervise(
+        events_tx: &broadcast::Sender<NetworkEvent<Peer>>,
+        swarm: &mut NimiqSwarm,
+        state: &mut TaskState,
+    ) {
+        let num_peers = swarm.behaviour().pool.peers.len();
+        let recovered = match state.connectivity.update(num_peers) {
+            ConnectivityChange::Lost => {
+                tracing::warn!("Lost connection to all peers");
+                false
+            }
+            ConnectivityChange::Degraded(num_peers) => {
+                tracing::warn!(
+                    num_peers,
+                    min_peers = state.connectivity.min_peers,
+                    "Number of peers dropped below the minimum"
+                );
+                false
+            }
+            ConnectivityChange::Recovered => true,
+            ConnectivityChange::Unchanged => false,
+        };
+        if num_peers == 0 {
+            return;
+        }
+
+        let gossipsub = &swarm.behaviour().gossipsub;
+        let stale_topics: Vec<TopicHash> = if recovered {
+            state.gossip_topics.keys().cloned().collect()
+        } else {
+            state
+                .gossip_topics
+                .keys()
+                .filter(|topic| {
+                    gossipsub.mesh_peers(topic).next().is_none()
+                        && gossipsub
+                            .all_peers()
+                            .any(|(_, topics)| topics.contains(topic))
+                })
+                .cloned()
+                .collect()
+        };
+
+        for topic_hash in stale_topics {
+            tracing::debug!(topic = ?topic_hash, "Re-subscribing to topic");
+
+            let topic = IdentTopic::new(topic_hash.as_str());
+            let gossipsub = &mut swarm.behaviour_mut().gossipsub;
+            gossipsub.unsubscribe(&topic).ok();
+            if let Err(e) = gossipsub.subscribe(&topic) {
+                tracing::error!(topic = ?topic_hash, error = ?e, "Failed to re-subscribe to topic");
+                continue;
+            }
+            gossipsub
+                .set_topic_params(topic, TopicScoreParams::default())
+                .ok();
+        }
+
+        if recovered {
+            tracing::info!(
+                num_peers,
+                "Regained connectivity, re-subscribed to all topics"
+            );
+            events_tx.send(NetworkEvent::<Peer>::Reconnected).ok();
+        }
+    }
+
     fn handle_event(
         event: SwarmEvent<NimiqEvent, NimiqNetworkBehaviourError>,
         events_tx: &broadcast::Sender<NetworkEvent<Peer>>,
@@ -345,6 +755,10 @@ impl Network {
                     }
                 }
 
+                if let Some(address) = dialed_address(&endpoint) {
+                    state.dialed_addresses.insert(peer_id, address);
+                }
+
                 // Save dialed peer addresses
                 if endpoint.is_dialer() {
                     let listen_addr = endpoint.get_remote_address();
@@ -383,19 +797,28 @@ impl Network {
                     tracing::info!("Connection closed because: {:?}", cause);
                 }
 
-                let behavior = swarm.behaviour_mut();
-
-                // Remove Peer
-                if let Some(peer) = behavior.pool.peers.remove(&peer_id) {
-                    // Remove peer addresses from the DHT if they are present
-                    let mut addresses: Vec<Multiaddr> = vec![];
-                    if let Some(record) = behavior.pool.contacts.read().get(&peer_id) {
-                        addresses.extend::<Vec<Multiaddr>>(record.addresses().cloned().collect());
-                    }
-                    for address in addresses {
-                        behavior.remove_peer_address(peer_id, address);
+                // The peer is kept while another connection to it is open, e.g. a direct
+                // connection that replaced a relayed one.
+                if num_established == 0 {
+                    let behavior = swarm.behaviour_mut();
+
+                    // Remove Peer
+                    if let Some(peer) = behavior.pool.peers.remove(&peer_id) {
+                        // Remove peer addresses from the DHT if they are present
+                        let mut addresses: Vec<Multiaddr> = vec![];
+                        if let Some(record) = behavior.pool.contacts.read().get(&peer_id) {
+                            addresses
+                                .extend::<Vec<Multiaddr>>(record.addresses().cloned().collect());
+                        }
+                        for address in addresses {
+                            behavior.remove_peer_address(peer_id, address);
+                        }
+                        events_tx.send(NetworkEvent::<Peer>::PeerLeft(peer)).ok();
                     }
-                    events_tx.send(NetworkEvent::<Peer>::PeerLeft(peer)).ok();
+
+                    state.peer_stats.remove(&peer_id);
+                    state.relays.remove(&peer_id);
+                    state.dialed_addresses.remove(&peer_id);
                 }
             }
 
@@ -428,8 +851,56 @@ impl Network {
                 tracing::debug!("Dialing peer {}", peer_id);
             }
 
+            SwarmEvent::NewListenAddr {
+                listener_id,
+                address,
+            } => {
+                tracing::debug!("Listening on {}", address);
+
+                if let Some(port_mapping) = &state.port_mapping {
+                    if let Some((_, port)) = port_mapping::mappable_port(&address) {
+                        if let Entry::Vacant(entry) = state.mapped_ports.entry(port) {
+                            let (stop_tx, stop_rx) = oneshot::channel();
+                            entry.insert((listener_id, stop_tx));
+                            tokio::spawn(port_mapping::maintain(
+                                address,
+                                port_mapping.clone(),
+                                stop_rx,
+                            ));
+                        }
+                    }
+                }
+            }
+
+            SwarmEvent::ListenerClosed {
+                listener_id,
+                reason,
+                ..
+            } => {
+                // Stop mapping the ports of the listener.
+                state
+                    .mapped_ports
+                    .retain(|_, (mapped_listener_id, _)| *mapped_listener_id != listener_id);
+
+                if let Some((relay_listener_id, relay_peer_id)) = state.relay_listener {
+                    if listener_id == relay_listener_id {
+                        tracing::info!("Lost relayed address via {}: {:?}", relay_peer_id, reason);
+                        state.relays.remove(&relay_peer_id);
+                        state.relay_listener = None;
+                        Self::update_relay_listener(swarm, state);
+                    }
+                }
+            }
+
             SwarmEvent::Behaviour(event) => {
                 match event {
+                    NimiqEvent::Autonat(event) => {
+                        if let autonat::Event::StatusChanged { old, new } = event {
+                            tracing::info!("NAT status changed from {:?} to {:?}", old, new);
+                            Self::update_relay_listener(swarm, state);
+                            Self::confirm_mappings(swarm, state);
+                        }
+                    }
                     NimiqEvent::Dht(event) => {
                         match event {
                             KademliaEvent::OutboundQueryCompleted { id, result, .. } => {
@@ -457,6 +928,27 @@ impl Network {
                                             tracing::warn!(query_id = ?id, "PutRecord query result for unknown query ID");
                                         }
                                     }
+                                    QueryResult::StartProviding(result) => {
+                                        if let Some(output) = state.dht_provides.remove(&id) {
+                                            output
+                                                .send(result.map(|_| ()).map_err(Into::into))
+                                                .ok();
+                                        } else {
+                                            tracing::warn!(query_id = ?id, "StartProviding query result for unknown query ID");
+                                        }
+                                    }
+                                    QueryResult::GetProviders(result) => {
+                                        if let Some(output) = state.dht_get_providers.remove(&id) {
+                                            let result = result.map_err(Into::into).map(
+                                                |GetProvidersOk { providers, .. }| {
+                                                    providers.into_iter().collect()
+                                                },
+                                            );
+                                            output.send(result).ok();
+                                        } else {
+                                            tracing::warn!(query_id = ?id, "GetProviders query result for unknown query ID");
+                                        }
+                                    }
                                     QueryResult::Bootstrap(result) => match result {
                                         Ok(result) => {
                                             tracing::debug!(result = ?result, "DHT bootstrap successful")
@@ -480,13 +972,14 @@ impl Network {
                                     if let Ok(pk) = (CompressedPublicKey {
                                         public_key: compressed_pk,
                                     })
-                                    .uncompress()
+                                    .uncompress_cached()
                                     {
-                                        if let Ok(signed_record) =
-                                            SignedValidatorRecord::<PeerId>::deserialize_from_vec(
-                                                &record.value,
-                                            )
-                                        {
+                                        if let Ok(signed_record) = SignedValidatorRecord::<
+                                            PeerId,
+                                            Multiaddr,
+                                        >::deserialize_from_vec(
+                                            &record.value
+                                        ) {
                                             if signed_record.verify(&pk) {
                                                 if swarm
                                                     .behaviour_mut()
@@ -514,7 +1007,21 @@ impl Network {
                             _ => {}
                         }
                     }
-                    NimiqEvent::Discovery(_e) => {}
+                    NimiqEvent::Discovery(event) => {
+                        if let DiscoveryEvent::Established {
+                            peer_id,
+                            clock_offset: Some(clock_offset),
+                        } = event
+                        {
+                            tracing::trace!(
+                                "Clock offset of peer {}: {} ms",
+                                peer_id,
+                                clock_offset
+                            );
+                            state.peer_stats.entry(peer_id).or_default().clock_offset =
+                                Some(clock_offset);
+                        }
+                    }
                     NimiqEvent::Gossip(event) => match event {
                         GossipsubEvent::Message {
                             propagation_source,
@@ -522,29 +1029,76 @@ impl Network {
                             message,
                         } => {
                             if let Some(topic_info) = state.gossip_topics.get_mut(&message.topic) {
-                                let (output, validate) = topic_info;
-                                if !&*validate {
+                                if let Some(recorder) = &state.recorder {
+                                    recorder.record_gossip(
+                                        &propagation_source,
+                                        message.topic.as_str(),
+                                        &message.data,
+                                    );
+                                }
+
+                                if let Some(seen_messages) = state
+                                    .seen_messages
+                                    .as_mut()
+                                    .filter(|_| topic_info.persist_seen)
+                                {
+                                    let topic = message.topic.as_str();
+                                    if seen_messages.contains(topic, &message_id.0) {
+                                        tracing::debug!(
+                                            "Ignoring gossipsub message on topic '{}' that was seen before the restart",
+                                            topic
+                                        );
+                                        swarm
+                                            .behaviour_mut()
+                                            .gossipsub
+                                            .report_message_validation_result(
+                                                &message_id,
+                                                &propagation_source,
+                                                MessageAcceptance::Ignore,
+                                            )
+                                            .ok();
+                                        return;
+                                    }
+                                    seen_messages.insert(topic, &message_id.0);
+                                }
+
+                                let topic = message.topic.clone();
+                                let acceptance = match topic_info.dispatch((
+                                    message,
+                                    message_id.clone(),
+                                    propagation_source,
+                                )) {
+                                    Ok(()) if topic_info.validate => None,
+                                    Ok(()) => Some(MessageAcceptance::Accept),
+                                    Err(_) => {
+                                        tracing::debug!(
+                                            "Buffer of gossipsub topic '{}' is full, dropping message",
+                                            topic.as_str()
+                                        );
+                                        // Dropped messages never reach the validation of the
+                                        // subscriber.
+                                        if topic_info.validate
+                                            || topic_info.overflow_policy
+                                                == OverflowPolicy::Backpressure
+                                        {
+                                            Some(MessageAcceptance::Ignore)
+                                        } else {
+                                            Some(MessageAcceptance::Accept)
+                                        }
+                                    }
+                                };
+
+                                if let Some(acceptance) = acceptance {
                                     swarm
                                         .behaviour_mut()
                                         .gossipsub
                                         .report_message_validation_result(
                                             &message_id,
                                             &propagation_source,
-                                            MessageAcceptance::Accept,
+                                            acceptance,
                                         )
                                         .ok();
                                 }
-
-                                let topic = message.topic.clone();
-                                if let Err(e) =
-                                    output.try_send((message, message_id, propagation_source))
-                                {
-                                    tracing::error!(
-                                        "Failed to dispatch gossipsub '{}' message: {:?}",
-                                        topic.as_str(),
-                                        e
-                                    )
-                                }
                             } else {
                                 tracing::warn!(topic = ?message.topic, "unknown topic hash");
                             }
@@ -569,6 +1123,16 @@ impl Network {
                                     info
                                 );
 
+                                // Remember peers that can relay connections to us
+                                if info.protocols.iter().any(|p| p == RELAY_HOP_PROTOCOL) {
+                                    // The listen addresses announced by the relay are often
+                                    // private, so we use the address we reached it at.
+                                    if let Some(address) = state.dialed_addresses.get(&peer_id) {
+                                        state.relays.insert(peer_id, address.clone());
+                                        Self::update_relay_listener(swarm, state);
+                                    }
+                                }
+
                                 // Save identified peer listen addresses
                                 for listen_addr in info.listen_addrs {
                                     swarm.behaviour_mut().add_peer_address(peer_id, listen_addr);
@@ -620,6 +1184,11 @@ impl Network {
                                     event.peer,
                                     rtt
                                 );
+                                state
+                                    .peer_stats
+                                    .entry(event.peer)
+                                    .or_default()
+                                    .record_rtt(rtt);
                             }
                         };
                     }
@@ -628,14 +1197,117 @@ impl Network {
                             ConnectionPoolEvent::PeerJoined { peer } => {
                                 events_tx.send(NetworkEvent::<Peer>::PeerJoined(peer)).ok();
                             }
+                            ConnectionPoolEvent::PeerLeft { peer } => {
+                                events_tx.send(NetworkEvent::<Peer>::PeerLeft(peer)).ok();
+                            }
+                            ConnectionPoolEvent::DialFailed {
+                                peer_id,
+                                error,
+                                attempts,
+                            } => {
+                                events_tx
+                                    .send(NetworkEvent::<Peer>::DialFailed {
+                                        peer_id,
+                                        error,
+                                        attempts,
+                                    })
+                                    .ok();
+                            }
+                            ConnectionPoolEvent::PeerBanned { peer_id } => {
+                                events_tx
+                                    .send(NetworkEvent::<Peer>::PeerBanned(peer_id))
+                                    .ok();
+                            }
+                            ConnectionPoolEvent::BackoffStarted { peer_id } => {
+                                events_tx
+                                    .send(NetworkEvent::<Peer>::BackoffStarted(peer_id))
+                                    .ok();
+                            }
+                            ConnectionPoolEvent::BackoffEnded { peer_id } => {
+                                events_tx
+                                    .send(NetworkEvent::<Peer>::BackoffEnded(peer_id))
+                                    .ok();
+                            }
                         };
                     }
+                    NimiqEvent::Relay(event) => {
+                        tracing::debug!("Relay event: {:?}", event);
+                    }
+                    NimiqEvent::RelayClient(event) => match event {
+                        client::Event::ReservationReqAccepted { relay_peer_id, .. } => {
+                            tracing::debug!("Reservation accepted by relay {}", relay_peer_id);
+                        }
+                        event => tracing::debug!("Relay client event: {:?}", event),
+                    },
+                    NimiqEvent::Dcutr(event) => match event {
+                        dcutr::behaviour::Event::DirectConnectionUpgradeSucceeded {
+                            remote_peer_id,
+                        } => {
+                            tracing::debug!(
+                                "Established a direct connection to {} by hole punching",
+                                remote_peer_id
+                            );
+                        }
+                        event => tracing::debug!("Hole punching event: {:?}", event),
+                    },
                 }
             }
             _ => {}
         }
     }
 
+    /// Listens on a relayed address while we aren't reachable from the outside, so that peers can
+    /// still connect to us through a relay. The relayed address is given up once we are reachable.
+    fn update_relay_listener(swarm: &mut NimiqSwarm, state: &mut TaskState) {
+        match (swarm.behaviour().autonat.nat_status(), state.relay_listener) {
+            (NatStatus::Public(_), Some((listener_id, relay_peer_id))) => {
+                tracing::info!(
+                    "Reachable from the outside, no longer listening via relay {}",
+                    relay_peer_id
+                );
+                swarm.remove_listener(listener_id);
+                state.relay_listener = None;
+            }
+            (NatStatus::Private, None) => {
+                for (relay_peer_id, address) in &state.relays {
+                    let address = address
+                        .clone()
+                        .with(Protocol::P2p((*relay_peer_id).into()))
+                        .with(Protocol::P2pCircuit);
+                    match swarm.listen_on(address.clone()) {
+                        Ok(listener_id) => {
+                            tracing::info!(
+                                "Not reachable from the outside, listening on relayed address {}",
+                                address
+                            );
+                            state.relay_listener = Some((listener_id, *relay_peer_id));
+                            break;
+                        }
+                        Err(error) => {
+                            tracing::debug!(
+                                "Failed to listen on relayed address {}: {:?}",
+                                address,
+                                error
+                            );
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Advertises the mapped address that AutoNAT confirmed to be reachable from the outside.
+    fn confirm_mappings(swarm: &mut NimiqSwarm, state: &mut TaskState) {
+        if let Some(address) = swarm.behaviour().autonat.public_address().cloned() {
+            if state.unconfirmed_mappings.remove(&address) {
+                tracing::info!("Mapped address {} is reachable, advertising it", address);
+                swarm.add_external_address(address.clone(), AddressScore::Infinite);
+                swarm.behaviour_mut().discovery.add_own_address(address);
+            }
+        }
+    }
+
     fn perform_action(action: NetworkAction, swarm: &mut NimiqSwarm, state: &mut TaskState) {
         // FIXME implement compact debug format for NetworkAction
         // tracing::trace!(action = ?action, "performing action");
@@ -683,10 +1355,27 @@ impl Network {
                     }
                 }
             }
+            NetworkAction::DhtProvide { key, output } => {
+                match swarm.behaviour_mut().dht.start_providing(key.into()) {
+                    Ok(query_id) => {
+                        // Remember provide operation to resolve when we receive a `QueryResult::StartProviding`
+                        state.dht_provides.insert(query_id, output);
+                    }
+                    Err(e) => {
+                        output.send(Err(e.into())).ok();
+                    }
+                }
+            }
+            NetworkAction::DhtGetProviders { key, output } => {
+                let query_id = swarm.behaviour_mut().dht.get_providers(key.into());
+                state.dht_get_providers.insert(query_id, output);
+            }
             NetworkAction::Subscribe {
                 topic_name,
                 buffer_size,
                 validate,
+                overflow_policy,
+                persist_seen,
                 output,
             } => {
                 let topic = IdentTopic::new(topic_name);
@@ -694,9 +1383,10 @@ impl Network {
                 match swarm.behaviour_mut().gossipsub.subscribe(&topic) {
                     // New subscription. Insert the sender into our subscription table.
                     Ok(true) => {
-                        let (tx, rx) = mpsc::channel(buffer_size);
+                        let (buffer, rx) =
+                            TopicBuffer::new(buffer_size, validate, overflow_policy, persist_seen);
 
-                        state.gossip_topics.insert(topic.hash(), (tx, validate));
+                        state.gossip_topics.insert(topic.hash(), buffer);
 
                         match swarm
                             .behaviour_mut()
@@ -733,14 +1423,14 @@ impl Network {
                     match swarm.behaviour_mut().gossipsub.unsubscribe(&topic) {
                         // Unsubscription. Remove the topic from the subscription table.
                         Ok(true) => {
-                            drop(state.gossip_topics.remove(&topic.hash()).unwrap().0);
+                            drop(state.gossip_topics.remove(&topic.hash()));
 
                             output.send(Ok(())).ok();
                         }
 
                         // Apparently we're already unsubscribed.
                         Ok(false) => {
-                            drop(state.gossip_topics.remove(&topic.hash()).unwrap().0);
+                            drop(state.gossip_topics.remove(&topic.hash()));
 
                             output
                                 .send(Err(NetworkError::AlreadyUnsubscribed { topic_name }))
@@ -779,6 +1469,28 @@ impl Network {
             NetworkAction::NetworkInfo { output } => {
                 output.send(Swarm::network_info(swarm)).ok();
             }
+            NetworkAction::PeerAddresses { peer_id, output } => {
+                let addresses = swarm
+                    .behaviour()
+                    .pool
+                    .contacts
+                    .read()
+                    .get(&peer_id)
+                    .map(|contact| contact.addresses().cloned().collect())
+                    .unwrap_or_default();
+                output.send(addresses).ok();
+            }
+            NetworkAction::PeerStats { peer_id, output } => {
+                output.send(state.peer_stats.get(&peer_id).cloned()).ok();
+            }
+            NetworkAction::GossipStats { output } => {
+                let stats = state
+                    .gossip_topics
+                    .iter()
+                    .map(|(topic, buffer)| buffer.stats(topic.to_string()))
+                    .collect();
+                output.send(stats).ok();
+            }
             NetworkAction::Validate {
                 message_id,
                 source,
@@ -807,9 +1519,17 @@ impl Network {
             NetworkAction::StartConnecting => {
                 swarm.behaviour_mut().pool.start_connecting();
             }
+            NetworkAction::Heartbeat { output } => {
+                output.send(()).ok();
+            }
         }
     }
 
+    /// Checks whether the swarm task is still alive and answers actions.
+    pub async fn swarm_health(&self) -> SwarmHealth {
+        Self::heartbeat(&self.action_tx, HEARTBEAT_TIMEOUT).await
+    }
+
     pub async fn network_info(&self) -> Result<NetworkInfo, NetworkError> {
         let (output_tx, output_rx) = oneshot::channel();
 
@@ -820,6 +1540,45 @@ impl Network {
         Ok(output_rx.await?)
     }
 
+    /// Returns the addresses of a peer as advertised in its peer contact, if we know it.
+    pub async fn peer_addresses(&self, peer_id: PeerId) -> Result<Vec<Multiaddr>, NetworkError> {
+        let (output_tx, output_rx) = oneshot::channel();
+
+        self.action_tx
+            .clone()
+            .send(NetworkAction::PeerAddresses {
+                peer_id,
+                output: output_tx,
+            })
+            .await?;
+        Ok(output_rx.await?)
+    }
+
+    /// Returns the latency and clock statistics of a peer, if we are connected to it.
+    pub async fn peer_stats(&self, peer_id: PeerId) -> Result<Option<PeerStats>, NetworkError> {
+        let (output_tx, output_rx) = oneshot::channel();
+
+        self.action_tx
+            .clone()
+            .send(NetworkAction::PeerStats {
+                peer_id,
+                output: output_tx,
+            })
+            .await?;
+        Ok(output_rx.await?)
+    }
+
+    /// Returns the inbound message counters of the subscribed gossipsub topics.
+    pub async fn gossip_stats(&self) -> Result<Vec<GossipTopicStats>, NetworkError> {
+        let (output_tx, output_rx) = oneshot::channel();
+
+        self.action_tx
+            .clone()
+            .send(NetworkAction::GossipStats { output: output_tx })
+            .await?;
+        Ok(output_rx.await?)
+    }
+
     pub async fn listen_on(&self, listen_addresses: Vec<Multiaddr>) {
         self.action_tx
             .clone()
@@ -896,7 +1655,7 @@ impl NetworkInterface for Network {
         receive_stream
             .filter_map(|(data, peer)| async move {
                 // Map the (data, peer) stream to (message, peer) by deserializing the messages.
-                match <T as Deserialize>::deserialize(&mut data.reader()) {
+                match T::deserialize_from_bytes(data) {
                     Ok(message) => Some((message, peer)),
                     Err(e) => {
                         tracing::error!(
@@ -926,6 +1685,8 @@ impl NetworkInterface for Network {
                 topic_name: <T as Topic>::NAME,
                 buffer_size: <T as Topic>::BUFFER_SIZE,
                 validate: <T as Topic>::VALIDATE,
+                overflow_policy: <T as Topic>::OVERFLOW_POLICY,
+                persist_seen: <T as Topic>::PERSIST_SEEN,
                 output: tx,
             })
             .await?;
@@ -1034,6 +1795,36 @@ impl NetworkInterface for Network {
         output_rx.await?
     }
 
+    async fn dht_provide<K>(&self, k: &K) -> Result<(), Self::Error>
+    where
+        K: AsRef<[u8]> + Send + Sync,
+    {
+        let (output_tx, output_rx) = oneshot::channel();
+        self.action_tx
+            .clone()
+            .send(NetworkAction::DhtProvide {
+                key: k.as_ref().to_owned(),
+                output: output_tx,
+            })
+            .await?;
+        output_rx.await?
+    }
+
+    async fn dht_get_providers<K>(&self, k: &K) -> Result<Vec<PeerId>, Self::Error>
+    where
+        K: AsRef<[u8]> + Send + Sync,
+    {
+        let (output_tx, output_rx) = oneshot::channel();
+        self.action_tx
+            .clone()
+            .send(NetworkAction::DhtGetProviders {
+                key: k.as_ref().to_owned(),
+                output: output_tx,
+            })
+            .await?;
+        output_rx.await?
+    }
+
     async fn dial_peer(&self, peer_id: PeerId) -> Result<(), NetworkError> {
         let (output_tx, output_rx) = oneshot::channel();
         self.action_tx
@@ -1063,15 +1854,37 @@ impl NetworkInterface for Network {
     }
 }
 
+/// Returns the address we dialed a peer at, without the trailing peer ID. Addresses of
+/// connections through a relay aren't returned.
+fn dialed_address(endpoint: &ConnectedPoint) -> Option<Multiaddr> {
+    let address = match endpoint {
+        ConnectedPoint::Dialer { address, .. } => address,
+        ConnectedPoint::Listener { .. } => return None,
+    };
+    if address
+        .iter()
+        .any(|protocol| matches!(protocol, Protocol::P2pCircuit))
+    {
+        return None;
+    }
+
+    let mut address = address.clone();
+    if let Some(Protocol::P2p(_)) = address.iter().last() {
+        address.pop();
+    }
+    Some(address)
+}
+
 #[cfg(test)]
 mod tests {
     use std::{sync::Arc, time::Duration};
 
-    use futures::{Stream, StreamExt};
+    use futures::{channel::mpsc, Stream, StreamExt};
     use libp2p::{
+        core::{ConnectedPoint, Endpoint},
         gossipsub::GossipsubConfigBuilder,
         identity::Keypair,
-        multiaddr::{multiaddr, Multiaddr},
+        multiaddr::{multiaddr, Multiaddr, Protocol},
         swarm::KeepAlive,
         PeerId,
     };
@@ -1084,6 +1897,7 @@ mod tests {
         network::Network as NetworkInterface,
         peer::{CloseReason, Peer as PeerInterface},
     };
+    use nimiq_utils::shared_rng::SharedRng;
     use nimiq_utils::time::OffsetTime;
 
     use crate::{
@@ -1094,7 +1908,7 @@ mod tests {
         peer::Peer,
     };
 
-    use super::{Config, Network};
+    use super::{dialed_address, Config, Connectivity, ConnectivityChange, Network, SwarmHealth};
 
     #[derive(Clone, Debug, Deserialize, Serialize)]
     struct TestMessage {
@@ -1140,6 +1954,9 @@ mod tests {
                 update_interval: Duration::from_secs(60),
                 min_recv_update_interval: Duration::from_secs(30),
                 update_limit: 64,
+                max_send_limit: 64,
+                initial_send_limit: 16,
+                amplification_factor: 2,
                 protocols_filter: Protocols::all(),
                 services_filter: Services::all(),
                 min_send_update_interval: Duration::from_secs(30),
@@ -1148,6 +1965,17 @@ mod tests {
             },
             kademlia: Default::default(),
             gossipsub,
+            recorder: None,
+            trusted_proxies: vec![],
+            plain_tcp: false,
+            tls: None,
+            relay_server: false,
+            seed: false,
+            port_mapping: false,
+            dial_priority: None,
+            seen_messages_file: None,
+            runtime: None,
+            rng: SharedRng::seeded(42),
         }
     }
 
@@ -1383,6 +2211,62 @@ mod tests {
         networks
     }
 
+    #[test]
+    fn connectivity_detects_the_loss_of_all_peers() {
+        let mut connectivity = Connectivity::new(3);
+        assert_eq!(connectivity.update(0), ConnectivityChange::Unchanged);
+
+        // Fewer peers than the minimum were ever connected, only losing all of them counts.
+        assert_eq!(connectivity.update(1), ConnectivityChange::Unchanged);
+        assert_eq!(connectivity.update(0), ConnectivityChange::Lost);
+        assert_eq!(connectivity.update(0), ConnectivityChange::Unchanged);
+        assert_eq!(connectivity.update(1), ConnectivityChange::Recovered);
+        assert_eq!(connectivity.update(2), ConnectivityChange::Unchanged);
+    }
+
+    #[test]
+    fn connectivity_detects_a_peer_count_below_the_minimum() {
+        let mut connectivity = Connectivity::new(3);
+        assert_eq!(connectivity.update(5), ConnectivityChange::Unchanged);
+        assert_eq!(connectivity.update(2), ConnectivityChange::Degraded(2));
+        assert_eq!(connectivity.update(1), ConnectivityChange::Unchanged);
+
+        // Losing all peers is reported even though the connectivity already degraded.
+        assert_eq!(connectivity.update(0), ConnectivityChange::Lost);
+        assert_eq!(connectivity.update(0), ConnectivityChange::Unchanged);
+        assert_eq!(connectivity.update(1), ConnectivityChange::Unchanged);
+
+        // Recovering requires the minimum number of peers.
+        assert_eq!(connectivity.update(2), ConnectivityChange::Unchanged);
+        assert_eq!(connectivity.update(3), ConnectivityChange::Recovered);
+        assert_eq!(connectivity.update(4), ConnectivityChange::Unchanged);
+
+        // Losing all peers after reaching the minimum is reported once.
+        assert_eq!(connectivity.update(0), ConnectivityChange::Lost);
+        assert_eq!(connectivity.update(1), ConnectivityChange::Unchanged);
+        assert_eq!(connectivity.update(3), ConnectivityChange::Recovered);
+    }
+
+    #[tokio::test]
+    async fn heartbeat_detects_a_dead_swarm_task() {
+        let (net1, _net2) = create_connected_networks().await;
+        assert_eq!(net1.swarm_health().await, SwarmHealth::Alive);
+
+        // A swarm task that doesn't answer.
+        let (action_tx, action_rx) = mpsc::channel(1);
+        assert_eq!(
+            Network::heartbeat(&action_tx, Duration::from_millis(100)).await,
+            SwarmHealth::Unresponsive
+        );
+
+        // A swarm task that terminated.
+        drop(action_rx);
+        assert_eq!(
+            Network::heartbeat(&action_tx, Duration::from_millis(100)).await,
+            SwarmHealth::Dead
+        );
+    }
+
     #[tokio::test]
     async fn connections_stress_and_reconnect() {
         // pretty_env_logger::init();
@@ -1406,6 +2290,38 @@ mod tests {
         assert_eq!(peer1.id(), net1.local_peer_id);
     }
 
+    #[tokio::test]
+    async fn two_networks_can_connect_over_plain_tcp() {
+        let free_port = || {
+            std::net::TcpListener::bind("127.0.0.1:0")
+                .unwrap()
+                .local_addr()
+                .unwrap()
+                .port()
+        };
+        let addr1 = multiaddr![Ip4([127, 0, 0, 1]), Tcp(free_port())];
+        let addr2 = multiaddr![Ip4([127, 0, 0, 1]), Tcp(free_port())];
+
+        let mut config1 = network_config(addr1.clone());
+        config1.plain_tcp = true;
+        let net1 = Network::new(Arc::new(OffsetTime::new()), config1).await;
+        net1.listen_on(vec![addr1.clone()]).await;
+
+        let mut config2 = network_config(addr2.clone());
+        config2.plain_tcp = true;
+        let net2 = Network::new(Arc::new(OffsetTime::new()), config2).await;
+        net2.listen_on(vec![addr2]).await;
+
+        let mut events1 = net1.subscribe_events();
+        let mut events2 = net2.subscribe_events();
+        net2.dial_address(addr1).await.unwrap();
+
+        let event1 = events1.next().await.unwrap().unwrap();
+        assert_peer_joined(&event1, &net2.local_peer_id);
+        let event2 = events2.next().await.unwrap().unwrap();
+        assert_peer_joined(&event2, &net1.local_peer_id);
+    }
+
     #[tokio::test]
     async fn one_peer_can_talk_to_another() {
         let (net1, net2) = create_connected_networks().await;
@@ -1586,4 +2502,34 @@ mod tests {
         }
         net1.network_info().await.unwrap();
     }
+
+    #[test]
+    fn dialed_addresses_are_used_for_relays() {
+        let peer_id = PeerId::random();
+        let address = multiaddr![Ip4([1, 2, 3, 4]), Tcp(8443u16), Ws("/".into())];
+        let dialer = |address: Multiaddr| ConnectedPoint::Dialer {
+            address,
+            role_override: Endpoint::Dialer,
+        };
+
+        // The trailing peer ID is stripped.
+        let with_peer_id = address.clone().with(Protocol::P2p(peer_id.into()));
+        assert_eq!(
+            dialed_address(&dialer(with_peer_id.clone())),
+            Some(address.clone())
+        );
+        assert_eq!(
+            dialed_address(&dialer(address.clone())),
+            Some(address.clone())
+        );
+
+        // Relayed and inbound connections don't tell us where the peer can be reached.
+        let relayed = with_peer_id.with(Protocol::P2pCircuit);
+        assert_eq!(dialed_address(&dialer(relayed)), None);
+        let listener = ConnectedPoint::Listener {
+            local_addr: address.clone(),
+            send_back_addr: address,
+        };
+        assert_eq!(dialed_address(&listener), None);
+    }
 }
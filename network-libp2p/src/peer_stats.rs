@@ -0,0 +1,52 @@
+use std::time::Duration;
+
+/// Latency and clock statistics of a connected peer.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PeerStats {
+    /// Round trip time of the last successful ping.
+    pub rtt: Option<Duration>,
+
+    /// Smoothed round trip time, see [`PeerStats::record_rtt`].
+    pub average_rtt: Option<Duration>,
+
+    /// Lowest round trip time measured so far.
+    pub min_rtt: Option<Duration>,
+
+    /// Offset of the peer's clock to ours in milliseconds, as observed during the discovery
+    /// handshake. A positive value means that the peer's clock is ahead of ours. Not set for peers
+    /// that don't send their time.
+    pub clock_offset: Option<i64>,
+}
+
+impl PeerStats {
+    /// Records a ping round trip time. The average is an exponentially weighted moving average
+    /// with a weight of 1/8 for new samples, like the smoothed RTT of TCP.
+    pub(crate) fn record_rtt(&mut self, rtt: Duration) {
+        self.rtt = Some(rtt);
+        self.average_rtt = Some(match self.average_rtt {
+            Some(average) => (average * 7 + rtt) / 8,
+            None => rtt,
+        });
+        self.min_rtt = Some(self.min_rtt.map_or(rtt, |min_rtt| min_rtt.min(rtt)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_records_round_trip_times() {
+        let mut stats = PeerStats::default();
+
+        stats.record_rtt(Duration::from_millis(80));
+        assert_eq!(stats.rtt, Some(Duration::from_millis(80)));
+        assert_eq!(stats.average_rtt, Some(Duration::from_millis(80)));
+        assert_eq!(stats.min_rtt, Some(Duration::from_millis(80)));
+
+        stats.record_rtt(Duration::from_millis(160));
+        assert_eq!(stats.rtt, Some(Duration::from_millis(160)));
+        assert_eq!(stats.average_rtt, Some(Duration::from_millis(90)));
+        assert_eq!(stats.min_rtt, Some(Duration::from_millis(80)));
+    }
+}
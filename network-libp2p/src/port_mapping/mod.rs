@@ -0,0 +1,328 @@
+//! Maps the listen ports on the local router, so that nodes behind a NAT are reachable without
+//! configuring port forwarding manually.
+//!
+//! NAT-PMP is tried first, since it is cheap and needs no discovery. If the router doesn't support
+//! it, the port is mapped via UPnP IGD.
+
+mod natpmp;
+mod upnp;
+
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr},
+    time::{Duration, Instant},
+};
+
+use futures::channel::{mpsc, oneshot};
+use libp2p::{multiaddr::Protocol, Multiaddr};
+use thiserror::Error;
+
+/// Lifetime that is requested for the mappings. They are renewed after half of it.
+const MAPPING_LIFETIME: Duration = Duration::from_secs(60 * 60);
+
+/// Time to wait before trying again if no port could be mapped.
+const RETRY_INTERVAL: Duration = Duration::from_secs(60 * 10);
+
+/// Minimum time between two requests to the router, even if it grants very short leases.
+const MIN_RENEW_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Error)]
+pub enum PortMappingError {
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("No response from the router")]
+    Timeout,
+
+    #[error("No default gateway found")]
+    NoGateway,
+
+    #[error("The router rejected the request with code {0}")]
+    Rejected(u16),
+
+    #[error("Invalid response from the router: {0}")]
+    InvalidResponse(String),
+}
+
+/// How a port was mapped on the router.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Mapper {
+    /// Via NAT-PMP on the given gateway.
+    NatPmp(Ipv4Addr),
+    Upnp,
+}
+
+/// A port that was mapped on the router.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Mapping {
+    pub external_ip: Ipv4Addr,
+    pub external_port: u16,
+    pub lifetime: Duration,
+    pub mapper: Mapper,
+}
+
+/// Emitted by [`maintain`] when the external address of a listen address changes.
+#[derive(Clone, Debug)]
+pub(crate) enum PortMappingEvent {
+    Mapped(Multiaddr),
+    Expired(Multiaddr),
+}
+
+/// Returns the TCP port of a listen address that can be mapped, i.e. an unspecified or private
+/// IPv4 address.
+pub(crate) fn mappable_port(address: &Multiaddr) -> Option<(Ipv4Addr, u16)> {
+    let mut protocols = address.iter();
+    match (protocols.next(), protocols.next()) {
+        (Some(Protocol::Ip4(ip)), Some(Protocol::Tcp(port)))
+            if ip.is_unspecified() || ip.is_private() =>
+        {
+            Some((ip, port))
+        }
+        _ => None,
+    }
+}
+
+/// Returns whether an IP is reachable from the internet. A router behind another NAT, e.g. a
+/// carrier-grade NAT, reports a private or shared IP as its external IP.
+fn is_public(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        // "This network" (0.0.0.0/8)
+        || a == 0
+        // Shared address space of carrier-grade NATs (100.64.0.0/10)
+        || (a == 100 && b & 0xc0 == 64)
+        // Benchmarking (198.18.0.0/15)
+        || (a == 198 && b & 0xfe == 18)
+        // Reserved, including the broadcast address (240.0.0.0/4)
+        || a >= 240)
+}
+
+/// Returns the time after which a mapping with the given lifetime is renewed.
+fn renew_after(lifetime: Duration) -> Duration {
+    (lifetime / 2).max(MIN_RENEW_INTERVAL)
+}
+
+/// Returns the time after which a failed renewal is retried. The mapping is only given up once
+/// its lease ran out, so it is retried before that.
+fn retry_after(expires_at: Option<Instant>, now: Instant) -> Duration {
+    match expires_at {
+        Some(expires_at) if expires_at > now => (expires_at - now)
+            .min(RETRY_INTERVAL)
+            .max(MIN_RENEW_INTERVAL),
+        _ => RETRY_INTERVAL,
+    }
+}
+
+/// Replaces the IP and the TCP port of a listen address with the ones of its mapping.
+fn external_address(address: &Multiaddr, mapping: &Mapping) -> Multiaddr {
+    address
+        .iter()
+        .map(|protocol| match protocol {
+            Protocol::Ip4(_) => Protocol::Ip4(mapping.external_ip),
+            Protocol::Tcp(_) => Protocol::Tcp(mapping.external_port),
+            protocol => protocol,
+        })
+        .collect()
+}
+
+/// Maps a port via NAT-PMP or, if that fails, via UPnP.
+async fn map_port(
+    local_ip: Ipv4Addr,
+    port: u16,
+    lifetime: Duration,
+) -> Result<Mapping, PortMappingError> {
+    let natpmp_error = match natpmp::gateway() {
+        Some(gateway) => match natpmp::map_port(gateway, port, lifetime).await {
+            Ok(mapping) => return Ok(mapping),
+            Err(error) => error,
+        },
+        None => PortMappingError::NoGateway,
+    };
+    log::debug!("Failed to map port {} via NAT-PMP: {}", port, natpmp_error);
+
+    upnp::map_port(local_ip, port, lifetime).await
+}
+
+/// Removes a mapping from the router.
+async fn unmap_port(mapper: Mapper, port: u16) -> Result<(), PortMappingError> {
+    match mapper {
+        Mapper::NatPmp(gateway) => natpmp::unmap_port(gateway, port).await,
+        Mapper::Upnp => upnp::unmap_port(port).await,
+    }
+}
+
+/// Keeps the port of a listen address mapped on the router and reports its external address.
+/// Once `stop` fires or the receiver of the events is dropped, the mapping is removed from the
+/// router and its external address is reported as expired.
+pub(crate) async fn maintain(
+    address: Multiaddr,
+    events: mpsc::UnboundedSender<PortMappingEvent>,
+    mut stop: oneshot::Receiver<()>,
+) {
+    let (local_ip, port) = match mappable_port(&address) {
+        Some(port) => port,
+        None => return,
+    };
+
+    // The external address and the time its lease runs out.
+    let mut external: Option<(Multiaddr, Instant)> = None;
+    let mut mapper = None;
+    loop {
+        let now = Instant::now();
+        let wait = match map_port(local_ip, port, MAPPING_LIFETIME).await {
+            Ok(mapping) if !is_public(mapping.external_ip) => {
+                log::warn!(
+                    "Not mapping port {}, the router is behind another NAT: its external IP is {}",
+                    port,
+                    mapping.external_ip
+                );
+                mapper = Some(mapping.mapper);
+                break;
+            }
+            Ok(mapping) => {
+                mapper = Some(mapping.mapper);
+                let address = external_address(&address, &mapping);
+                if external.as_ref().map(|(external, _)| external) != Some(&address) {
+                    log::info!("Mapped port {} on the router to {}", port, address);
+                    if let Some((previous, _)) = external.take() {
+                        events
+                            .unbounded_send(PortMappingEvent::Expired(previous))
+                            .ok();
+                    }
+                    events
+                        .unbounded_send(PortMappingEvent::Mapped(address.clone()))
+                        .ok();
+                }
+                external = Some((address, now + mapping.lifetime));
+                renew_after(mapping.lifetime)
+            }
+            Err(error) => {
+                log::warn!("Failed to map port {} on the router: {}", port, error);
+                let expires_at = external.as_ref().map(|(_, expires_at)| *expires_at);
+                if expires_at.map_or(false, |expires_at| expires_at <= now) {
+                    if let Some((previous, _)) = external.take() {
+                        events
+                            .unbounded_send(PortMappingEvent::Expired(previous))
+                            .ok();
+                    }
+                }
+                retry_after(expires_at, now)
+            }
+        };
+
+        if events.is_closed() {
+            break;
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(wait) => {}
+            _ = &mut stop => break,
+        }
+        if events.is_closed() {
+            break;
+        }
+    }
+
+    if let Some(mapper) = mapper {
+        match unmap_port(mapper, port).await {
+            Ok(()) => log::info!("Removed the mapping of port {} from the router", port),
+            Err(error) => log::debug!("Failed to remove the mapping of port {}: {}", port, error),
+        }
+    }
+    if let Some((previous, _)) = external {
+        events
+            .unbounded_send(PortMappingEvent::Expired(previous))
+            .ok();
+    }
+}
+
+/// Returns the local IP that is used to reach the given gateway.
+async fn local_ip_towards(gateway: IpAddr) -> io::Result<Ipv4Addr> {
+    let socket = tokio::net::UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await?;
+    socket.connect((gateway, 9)).await?;
+    match socket.local_addr()?.ip() {
+        IpAddr::V4(ip) => Ok(ip),
+        IpAddr::V6(_) => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "The gateway isn't reachable via IPv4",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_only_maps_private_tcp_addresses() {
+        let port = |address: &str| mappable_port(&address.parse().unwrap());
+
+        assert_eq!(
+            port("/ip4/192.168.1.10/tcp/8443/ws"),
+            Some((Ipv4Addr::new(192, 168, 1, 10), 8443))
+        );
+        assert_eq!(
+            port("/ip4/0.0.0.0/tcp/8443"),
+            Some((Ipv4Addr::UNSPECIFIED, 8443))
+        );
+        assert_eq!(port("/ip4/1.2.3.4/tcp/8443/ws"), None);
+        assert_eq!(port("/ip6/::1/tcp/8443/ws"), None);
+        assert_eq!(port("/dns4/localhost/tcp/8443/ws"), None);
+    }
+
+    #[test]
+    fn it_replaces_the_ip_and_port() {
+        let mapping = Mapping {
+            external_ip: Ipv4Addr::new(1, 2, 3, 4),
+            external_port: 10443,
+            lifetime: MAPPING_LIFETIME,
+            mapper: Mapper::Upnp,
+        };
+        let address: Multiaddr = "/ip4/192.168.1.10/tcp/8443/ws".parse().unwrap();
+
+        assert_eq!(
+            external_address(&address, &mapping),
+            "/ip4/1.2.3.4/tcp/10443/ws".parse().unwrap()
+        );
+    }
+
+    #[test]
+    fn it_only_advertises_public_ips() {
+        assert!(is_public(Ipv4Addr::new(1, 2, 3, 4)));
+        assert!(is_public(Ipv4Addr::new(100, 128, 0, 1)));
+
+        assert!(!is_public(Ipv4Addr::new(192, 168, 1, 1)));
+        assert!(!is_public(Ipv4Addr::new(10, 0, 0, 1)));
+        assert!(!is_public(Ipv4Addr::new(100, 64, 0, 1)));
+        assert!(!is_public(Ipv4Addr::new(100, 127, 255, 254)));
+        assert!(!is_public(Ipv4Addr::new(198, 19, 0, 1)));
+        assert!(!is_public(Ipv4Addr::UNSPECIFIED));
+        assert!(!is_public(Ipv4Addr::BROADCAST));
+    }
+
+    #[test]
+    fn it_renews_in_time_but_not_too_often() {
+        assert_eq!(renew_after(MAPPING_LIFETIME), MAPPING_LIFETIME / 2);
+        assert_eq!(renew_after(Duration::ZERO), MIN_RENEW_INTERVAL);
+
+        let now = Instant::now();
+        // A mapping that is still valid is retried before its lease runs out.
+        assert_eq!(
+            retry_after(Some(now + Duration::from_secs(60 * 5)), now),
+            Duration::from_secs(60 * 5)
+        );
+        assert_eq!(
+            retry_after(Some(now + MAPPING_LIFETIME), now),
+            RETRY_INTERVAL
+        );
+        assert_eq!(
+            retry_after(Some(now + Duration::from_secs(1)), now),
+            MIN_RENEW_INTERVAL
+        );
+        assert_eq!(retry_after(Some(now), now), RETRY_INTERVAL);
+        assert_eq!(retry_after(None, now), RETRY_INTERVAL);
+    }
+}
@@ -0,0 +1,173 @@
+//! Minimal NAT-PMP client (RFC 6886).
+
+use std::{
+    fs,
+    net::{Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+use tokio::net::UdpSocket;
+
+use super::{Mapper, Mapping, PortMappingError};
+
+const NATPMP_PORT: u16 = 5351;
+
+/// Timeout of the first request. It is doubled for every retry.
+const INITIAL_TIMEOUT: Duration = Duration::from_millis(250);
+const MAX_ATTEMPTS: usize = 4;
+
+const OPCODE_EXTERNAL_ADDRESS: u8 = 0;
+const OPCODE_MAP_TCP: u8 = 2;
+/// Responses have the opcode of their request plus 128.
+const OPCODE_RESPONSE: u8 = 128;
+
+/// Returns the default gateway. Only supported on Linux.
+pub(super) fn gateway() -> Option<Ipv4Addr> {
+    parse_gateway(&fs::read_to_string("/proc/net/route").ok()?)
+}
+
+/// Parses the default gateway from the routing table in `/proc/net/route`.
+fn parse_gateway(routes: &str) -> Option<Ipv4Addr> {
+    routes.lines().skip(1).find_map(|line| {
+        let columns: Vec<&str> = line.split_whitespace().collect();
+        match columns.as_slice() {
+            [_, "00000000", gateway, ..] => {
+                // The addresses are printed in host byte order.
+                let gateway = u32::from_str_radix(gateway, 16).ok()?;
+                Some(Ipv4Addr::from(gateway.to_ne_bytes())).filter(|ip| !ip.is_unspecified())
+            }
+            _ => None,
+        }
+    })
+}
+
+pub(super) async fn map_port(
+    gateway: Ipv4Addr,
+    port: u16,
+    lifetime: Duration,
+) -> Result<Mapping, PortMappingError> {
+    let socket = connect(gateway).await?;
+
+    let response = request(&socket, &[0, OPCODE_EXTERNAL_ADDRESS], 12).await?;
+    let external_ip = Ipv4Addr::new(response[8], response[9], response[10], response[11]);
+
+    let response = request(&socket, &map_request(port, port, lifetime), 16).await?;
+
+    Ok(Mapping {
+        external_ip,
+        external_port: u16::from_be_bytes([response[10], response[11]]),
+        lifetime: Duration::from_secs(u32::from_be_bytes([
+            response[12],
+            response[13],
+            response[14],
+            response[15],
+        ]) as u64),
+        mapper: Mapper::NatPmp(gateway),
+    })
+}
+
+/// Removes the mapping of a port. A mapping is removed by requesting it with a lifetime and an
+/// external port of 0.
+pub(super) async fn unmap_port(gateway: Ipv4Addr, port: u16) -> Result<(), PortMappingError> {
+    let socket = connect(gateway).await?;
+    request(&socket, &map_request(port, 0, Duration::ZERO), 16).await?;
+    Ok(())
+}
+
+async fn connect(gateway: Ipv4Addr) -> Result<UdpSocket, PortMappingError> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await?;
+    socket
+        .connect(SocketAddr::from((gateway, NATPMP_PORT)))
+        .await?;
+    Ok(socket)
+}
+
+fn map_request(port: u16, external_port: u16, lifetime: Duration) -> Vec<u8> {
+    let mut request = vec![0, OPCODE_MAP_TCP, 0, 0];
+    request.extend_from_slice(&port.to_be_bytes());
+    request.extend_from_slice(&external_port.to_be_bytes());
+    request.extend_from_slice(&(lifetime.as_secs() as u32).to_be_bytes());
+    request
+}
+
+/// Sends a request until the gateway responds and checks the result code of the response.
+async fn request(
+    socket: &UdpSocket,
+    request: &[u8],
+    response_len: usize,
+) -> Result<Vec<u8>, PortMappingError> {
+    let mut timeout = INITIAL_TIMEOUT;
+    let mut buf = [0u8; 16];
+
+    for _ in 0..MAX_ATTEMPTS {
+        socket.send(request).await?;
+
+        if let Ok(received) = tokio::time::timeout(timeout, socket.recv(&mut buf)).await {
+            let response = &buf[..received?];
+            return parse_response(response, request[1], response_len).map(|_| response.to_vec());
+        }
+        timeout *= 2;
+    }
+
+    Err(PortMappingError::Timeout)
+}
+
+fn parse_response(
+    response: &[u8],
+    opcode: u8,
+    response_len: usize,
+) -> Result<(), PortMappingError> {
+    if response.len() < response_len || response[0] != 0 || response[1] != opcode + OPCODE_RESPONSE
+    {
+        return Err(PortMappingError::InvalidResponse(format!(
+            "{:?} for opcode {}",
+            response, opcode
+        )));
+    }
+
+    match u16::from_be_bytes([response[2], response[3]]) {
+        0 => Ok(()),
+        code => Err(PortMappingError::Rejected(code)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses_the_default_gateway() {
+        let routes =
+            "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT\n\
+                      eth0\t0001A8C0\t00000000\t0001\t0\t0\t0\t00FFFFFF\t0\t0\t0\n\
+                      eth0\t00000000\t0101A8C0\t0003\t0\t0\t0\t00000000\t0\t0\t0\n";
+
+        assert_eq!(parse_gateway(routes), Some(Ipv4Addr::new(192, 168, 1, 1)));
+        assert_eq!(parse_gateway("Iface\tDestination\tGateway\n"), None);
+    }
+
+    #[test]
+    fn it_checks_the_result_code() {
+        let response = [
+            0, 130, 0, 0, 0, 0, 0, 1, 0x20, 0xfb, 0x20, 0xfb, 0, 0, 0x0e, 0x10,
+        ];
+        assert!(parse_response(&response, OPCODE_MAP_TCP, 16).is_ok());
+
+        let rejected = [0, 130, 0, 3, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0];
+        assert!(matches!(
+            parse_response(&rejected, OPCODE_MAP_TCP, 16),
+            Err(PortMappingError::Rejected(3))
+        ));
+
+        assert!(parse_response(&response[..12], OPCODE_MAP_TCP, 16).is_err());
+        assert!(parse_response(&response, OPCODE_EXTERNAL_ADDRESS, 12).is_err());
+    }
+
+    #[test]
+    fn it_removes_mappings_with_a_zero_lifetime() {
+        assert_eq!(
+            map_request(8443, 0, Duration::ZERO),
+            [0, OPCODE_MAP_TCP, 0, 0, 0x20, 0xfb, 0, 0, 0, 0, 0, 0]
+        );
+    }
+}
@@ -0,0 +1,383 @@
+//! Minimal UPnP IGD client. The router is discovered via SSDP and the port is mapped with the
+//! `AddPortMapping` action of its WAN connection service.
+
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpStream, UdpSocket},
+};
+
+use super::{local_ip_towards, Mapper, Mapping, PortMappingError, MAPPING_LIFETIME};
+
+const SSDP_ADDRESS: (Ipv4Addr, u16) = (Ipv4Addr::new(239, 255, 255, 250), 1900);
+const SSDP_TIMEOUT: Duration = Duration::from_secs(3);
+const HTTP_TIMEOUT: Duration = Duration::from_secs(5);
+
+const SEARCH_REQUEST: &str = "M-SEARCH * HTTP/1.1\r\n\
+    HOST: 239.255.255.250:1900\r\n\
+    ST: urn:schemas-upnp-org:device:InternetGatewayDevice:1\r\n\
+    MAN: \"ssdp:discover\"\r\n\
+    MX: 2\r\n\r\n";
+
+/// The services that can map ports, in order of preference.
+const WAN_SERVICES: [&str; 3] = [
+    "urn:schemas-upnp-org:service:WANIPConnection:2",
+    "urn:schemas-upnp-org:service:WANIPConnection:1",
+    "urn:schemas-upnp-org:service:WANPPPConnection:1",
+];
+
+/// Error code of routers that only support mappings without a lease duration.
+const ONLY_PERMANENT_LEASES_SUPPORTED: &str = "<errorCode>725</errorCode>";
+
+/// The WAN connection service of a router.
+#[derive(Debug, PartialEq, Eq)]
+struct Gateway {
+    /// Host and port of the HTTP server of the router.
+    host: SocketAddr,
+    control_path: String,
+    service_type: &'static str,
+}
+
+pub(super) async fn map_port(
+    local_ip: Ipv4Addr,
+    port: u16,
+    lifetime: Duration,
+) -> Result<Mapping, PortMappingError> {
+    let gateway = discover().await?;
+    let local_ip = if local_ip.is_unspecified() {
+        local_ip_towards(gateway.host.ip()).await?
+    } else {
+        local_ip
+    };
+
+    let mut lifetime = lifetime;
+    match gateway
+        .call(
+            "AddPortMapping",
+            &add_port_mapping(port, local_ip, lifetime),
+        )
+        .await
+    {
+        Err(PortMappingError::InvalidResponse(body))
+            if body.contains(ONLY_PERMANENT_LEASES_SUPPORTED) =>
+        {
+            lifetime = Duration::ZERO;
+            gateway
+                .call(
+                    "AddPortMapping",
+                    &add_port_mapping(port, local_ip, lifetime),
+                )
+                .await?;
+        }
+        response => {
+            response?;
+        }
+    }
+
+    let response = gateway.call("GetExternalIPAddress", "").await?;
+    let external_ip = tag_value(&response, "NewExternalIPAddress")
+        .and_then(|ip| ip.parse().ok())
+        .ok_or_else(|| PortMappingError::InvalidResponse(response.clone()))?;
+
+    Ok(Mapping {
+        external_ip,
+        external_port: port,
+        // Permanent mappings are still renewed from time to time, in case the router restarted.
+        lifetime: if lifetime.is_zero() {
+            MAPPING_LIFETIME
+        } else {
+            lifetime
+        },
+        mapper: Mapper::Upnp,
+    })
+}
+
+/// Removes the mapping of a port.
+pub(super) async fn unmap_port(port: u16) -> Result<(), PortMappingError> {
+    let gateway = discover().await?;
+    gateway
+        .call("DeletePortMapping", &delete_port_mapping(port))
+        .await?;
+    Ok(())
+}
+
+fn add_port_mapping(port: u16, local_ip: Ipv4Addr, lifetime: Duration) -> String {
+    format!(
+        "<NewRemoteHost></NewRemoteHost>\
+         <NewExternalPort>{port}</NewExternalPort>\
+         <NewProtocol>TCP</NewProtocol>\
+         <NewInternalPort>{port}</NewInternalPort>\
+         <NewInternalClient>{local_ip}</NewInternalClient>\
+         <NewEnabled>1</NewEnabled>\
+         <NewPortMappingDescription>Nimiq</NewPortMappingDescription>\
+         <NewLeaseDuration>{lifetime}</NewLeaseDuration>",
+        port = port,
+        local_ip = local_ip,
+        lifetime = lifetime.as_secs(),
+    )
+}
+
+fn delete_port_mapping(port: u16) -> String {
+    format!(
+        "<NewRemoteHost></NewRemoteHost>\
+         <NewExternalPort>{port}</NewExternalPort>\
+         <NewProtocol>TCP</NewProtocol>",
+        port = port,
+    )
+}
+
+/// Finds the router via SSDP and reads its WAN connection service from its description.
+async fn discover() -> Result<Gateway, PortMappingError> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await?;
+    socket
+        .send_to(SEARCH_REQUEST.as_bytes(), SSDP_ADDRESS)
+        .await?;
+
+    let mut buf = [0u8; 2048];
+    let location = tokio::time::timeout(SSDP_TIMEOUT, async {
+        loop {
+            let (len, _) = socket.recv_from(&mut buf).await?;
+            if let Some(location) = header_value(&String::from_utf8_lossy(&buf[..len]), "location")
+            {
+                return Ok::<_, PortMappingError>(location);
+            }
+        }
+    })
+    .await
+    .map_err(|_| PortMappingError::Timeout)??;
+
+    let (host, path) = parse_url(&location)
+        .ok_or_else(|| PortMappingError::InvalidResponse(format!("Location {}", location)))?;
+    let description = http_request(host, &format!("GET {} HTTP/1.1\r\n", path), &[], "").await?;
+
+    parse_description(host, &description)
+        .ok_or_else(|| PortMappingError::InvalidResponse(description))
+}
+
+impl Gateway {
+    /// Calls an action of the service and returns the response body.
+    async fn call(&self, action: &str, arguments: &str) -> Result<String, PortMappingError> {
+        let body = format!(
+            "<?xml version=\"1.0\"?>\
+             <s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\" \
+             s:encodingStyle=\"http://schemas.xmlsoap.org/soap/encoding/\">\
+             <s:Body><u:{action} xmlns:u=\"{service}\">{arguments}</u:{action}></s:Body>\
+             </s:Envelope>",
+            action = action,
+            service = self.service_type,
+            arguments = arguments,
+        );
+        let soap_action = format!("\"{}#{}\"", self.service_type, action);
+
+        http_request(
+            self.host,
+            &format!("POST {} HTTP/1.1\r\n", self.control_path),
+            &[
+                ("Content-Type", "text/xml; charset=\"utf-8\""),
+                ("SOAPAction", &soap_action),
+            ],
+            &body,
+        )
+        .await
+    }
+}
+
+/// Sends an HTTP request and returns the body of a successful response. The body of an
+/// unsuccessful response is returned as [`PortMappingError::InvalidResponse`].
+async fn http_request(
+    host: SocketAddr,
+    request_line: &str,
+    headers: &[(&str, &str)],
+    body: &str,
+) -> Result<String, PortMappingError> {
+    let mut request = String::from(request_line);
+    request.push_str(&format!("Host: {}\r\n", host));
+    for (name, value) in headers {
+        request.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    request.push_str(&format!(
+        "Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body
+    ));
+
+    let response = tokio::time::timeout(HTTP_TIMEOUT, async {
+        let mut stream = TcpStream::connect(host).await?;
+        stream.write_all(request.as_bytes()).await?;
+        let mut response = vec![];
+        stream.read_to_end(&mut response).await?;
+        Ok::<_, std::io::Error>(response)
+    })
+    .await
+    .map_err(|_| PortMappingError::Timeout)??;
+
+    parse_response(&String::from_utf8_lossy(&response))
+}
+
+fn parse_response(response: &str) -> Result<String, PortMappingError> {
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .ok_or_else(|| PortMappingError::InvalidResponse(response.to_string()))?;
+
+    let body = if header_value(head, "transfer-encoding")
+        .map_or(false, |encoding| encoding.eq_ignore_ascii_case("chunked"))
+    {
+        decode_chunked(body).ok_or_else(|| PortMappingError::InvalidResponse(body.to_string()))?
+    } else {
+        body.to_string()
+    };
+
+    let status = head.split_whitespace().nth(1);
+    if status == Some("200") {
+        Ok(body)
+    } else {
+        Err(PortMappingError::InvalidResponse(body))
+    }
+}
+
+fn decode_chunked(mut body: &str) -> Option<String> {
+    let mut decoded = String::new();
+    loop {
+        let (size, rest) = body.split_once("\r\n")?;
+        let size = usize::from_str_radix(size.split(';').next()?.trim(), 16).ok()?;
+        if size == 0 {
+            return Some(decoded);
+        }
+        decoded.push_str(rest.get(..size)?);
+        body = rest.get(size..)?.strip_prefix("\r\n")?;
+    }
+}
+
+/// Returns the value of a header, ignoring the case of its name.
+fn header_value(head: &str, name: &str) -> Option<String> {
+    head.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.trim()
+            .eq_ignore_ascii_case(name)
+            .then(|| value.trim().to_string())
+    })
+}
+
+/// Splits an `http://` URL into the socket address of the host and the path.
+fn parse_url(url: &str) -> Option<(SocketAddr, String)> {
+    let url = url.strip_prefix("http://")?;
+    let (host, path) = match url.find('/') {
+        Some(i) => url.split_at(i),
+        None => (url, "/"),
+    };
+    let host = match host.parse() {
+        Ok(host) => host,
+        Err(_) => SocketAddr::new(host.parse::<IpAddr>().ok()?, 80),
+    };
+    Some((host, path.to_string()))
+}
+
+/// Finds the preferred WAN connection service in the description of the router.
+fn parse_description(host: SocketAddr, description: &str) -> Option<Gateway> {
+    let services: Vec<(String, String)> = description
+        .split("<service>")
+        .skip(1)
+        .filter_map(|service| {
+            Some((
+                tag_value(service, "serviceType")?,
+                tag_value(service, "controlURL")?,
+            ))
+        })
+        .collect();
+
+    WAN_SERVICES.iter().find_map(|&service_type| {
+        let (_, control_url) = services.iter().find(|(ty, _)| ty == service_type)?;
+        let control_path = match parse_url(control_url) {
+            Some((_, path)) => path,
+            None if control_url.starts_with('/') => control_url.clone(),
+            None => format!("/{}", control_url),
+        };
+        Some(Gateway {
+            host,
+            control_path,
+            service_type,
+        })
+    })
+}
+
+/// Returns the text of the first element with the given name.
+fn tag_value(xml: &str, name: &str) -> Option<String> {
+    let start = xml.find(&format!("<{}>", name))? + name.len() + 2;
+    let end = start + xml[start..].find(&format!("</{}>", name))?;
+    Some(xml[start..end].trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses_the_location() {
+        let response = "HTTP/1.1 200 OK\r\n\
+                        CACHE-CONTROL: max-age=120\r\n\
+                        Location: http://192.168.1.1:5000/rootDesc.xml\r\n\
+                        ST: urn:schemas-upnp-org:device:InternetGatewayDevice:1\r\n\r\n";
+
+        let location = header_value(response, "location").unwrap();
+        assert_eq!(
+            parse_url(&location),
+            Some((
+                "192.168.1.1:5000".parse().unwrap(),
+                "/rootDesc.xml".to_string()
+            ))
+        );
+        assert_eq!(
+            parse_url("http://192.168.1.1"),
+            Some(("192.168.1.1:80".parse().unwrap(), "/".to_string()))
+        );
+        assert_eq!(parse_url("https://192.168.1.1/"), None);
+    }
+
+    #[test]
+    fn it_finds_the_wan_connection_service() {
+        let host: SocketAddr = "192.168.1.1:5000".parse().unwrap();
+        let description = "<root><device><serviceList>\
+             <service><serviceType>urn:schemas-upnp-org:service:Layer3Forwarding:1</serviceType>\
+             <controlURL>/ctl/L3F</controlURL></service>\
+             <service><serviceType>urn:schemas-upnp-org:service:WANPPPConnection:1</serviceType>\
+             <controlURL>ctl/PPPConn</controlURL></service>\
+             <service><serviceType>urn:schemas-upnp-org:service:WANIPConnection:1</serviceType>\
+             <controlURL>/ctl/IPConn</controlURL></service>\
+             </serviceList></device></root>";
+
+        assert_eq!(
+            parse_description(host, description),
+            Some(Gateway {
+                host,
+                control_path: "/ctl/IPConn".to_string(),
+                service_type: "urn:schemas-upnp-org:service:WANIPConnection:1",
+            })
+        );
+        assert_eq!(parse_description(host, "<root></root>"), None);
+    }
+
+    #[test]
+    fn it_parses_responses() {
+        let response = "HTTP/1.1 200 OK\r\nContent-Type: text/xml\r\n\r\n\
+                        <NewExternalIPAddress>1.2.3.4</NewExternalIPAddress>";
+        let body = parse_response(response).unwrap();
+        assert_eq!(
+            tag_value(&body, "NewExternalIPAddress"),
+            Some("1.2.3.4".to_string())
+        );
+
+        let chunked = "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+                       5\r\nHello\r\n6\r\n World\r\n0\r\n\r\n";
+        assert_eq!(parse_response(chunked).unwrap(), "Hello World");
+
+        let error = "HTTP/1.1 500 Internal Server Error\r\n\r\n<errorCode>725</errorCode>";
+        assert!(matches!(
+            parse_response(error),
+            Err(PortMappingError::InvalidResponse(body)) if body.contains(ONLY_PERMANENT_LEASES_SUPPORTED)
+        ));
+    }
+}
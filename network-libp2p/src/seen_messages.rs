@@ -0,0 +1,228 @@
+//! Persistence of the recently seen gossipsub message IDs.
+//!
+//! Gossipsub drops duplicate messages by their ID, but its duplicate cache is lost when the node
+//! restarts. A validator that restarts quickly would receive and process the messages that are
+//! still circulating once more. Therefore the IDs of the messages seen within the retention time
+//! are written to a file regularly and loaded on startup. Messages with a known ID are ignored.
+//!
+//! Only the topics that opt in via [`Topic::PERSIST_SEEN`](nimiq_network_interface::network::Topic)
+//! are recorded, and at most [`MAX_SEEN_MESSAGES`] IDs are kept.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use parking_lot::Mutex;
+use tokio::task::JoinHandle;
+
+use beserial::{Deserialize, Serialize, SerializingError};
+
+/// The maximum number of message IDs kept. The oldest IDs are dropped first.
+const MAX_SEEN_MESSAGES: usize = 16 * 1024;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct SeenMessage {
+    #[beserial(len_type(u8))]
+    topic: String,
+    #[beserial(len_type(u8))]
+    id: Vec<u8>,
+    /// Unix time in milliseconds at which the message was first seen.
+    timestamp: u64,
+}
+
+pub(crate) struct SeenMessages {
+    path: PathBuf,
+    retention: Duration,
+    /// The seen messages, oldest first.
+    messages: VecDeque<SeenMessage>,
+    /// The IDs of the seen messages by topic.
+    ids: HashMap<String, HashSet<Vec<u8>>>,
+    /// Held while the file is written, so writes on the blocking thread pool don't overlap.
+    write_lock: Arc<Mutex<()>>,
+}
+
+impl SeenMessages {
+    /// Loads the message IDs stored at `path` that were seen within the retention time. Starts
+    /// with an empty set if the file doesn't exist or can't be read.
+    pub fn load(path: PathBuf, retention: Duration) -> Self {
+        let mut seen_messages = SeenMessages {
+            path,
+            retention,
+            messages: VecDeque::new(),
+            ids: HashMap::new(),
+            write_lock: Arc::new(Mutex::new(())),
+        };
+
+        match seen_messages.read() {
+            Ok(messages) => {
+                for message in messages {
+                    seen_messages.push(message);
+                }
+                seen_messages.prune(now());
+                log::debug!(
+                    "Loaded {} seen gossipsub messages from {}",
+                    seen_messages.messages.len(),
+                    seen_messages.path.display()
+                );
+            }
+            Err(SerializingError::IoError(e)) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => log::warn!(
+                "Failed to load the seen gossipsub messages from {}: {}",
+                seen_messages.path.display(),
+                e
+            ),
+        }
+
+        seen_messages
+    }
+
+    pub fn contains(&self, topic: &str, id: &[u8]) -> bool {
+        self.ids.get(topic).map_or(false, |ids| ids.contains(id))
+    }
+
+    pub fn insert(&mut self, topic: &str, id: &[u8]) {
+        if !self.contains(topic, id) {
+            self.push(SeenMessage {
+                topic: topic.to_string(),
+                id: id.to_vec(),
+                timestamp: now(),
+            });
+        }
+    }
+
+    /// Drops the messages that are older than the retention time and writes the others to the
+    /// file on the blocking thread pool. The file is replaced atomically, so it stays intact if
+    /// the node crashes meanwhile.
+    pub fn save(&mut self) -> JoinHandle<()> {
+        self.prune(now());
+
+        let data = self.serialize();
+        let path = self.path.clone();
+        let write_lock = Arc::clone(&self.write_lock);
+        tokio::task::spawn_blocking(move || {
+            let _guard = write_lock.lock();
+            if let Err(e) = write(&path, data) {
+                log::warn!(
+                    "Failed to save the seen gossipsub messages to {}: {}",
+                    path.display(),
+                    e
+                );
+            }
+        })
+    }
+
+    fn push(&mut self, message: SeenMessage) {
+        if self.messages.len() >= MAX_SEEN_MESSAGES {
+            self.pop_oldest();
+        }
+        self.ids
+            .entry(message.topic.clone())
+            .or_default()
+            .insert(message.id.clone());
+        self.messages.push_back(message);
+    }
+
+    fn prune(&mut self, now: u64) {
+        let retention = self.retention.as_millis() as u64;
+        while let Some(message) = self.messages.front() {
+            if message.timestamp.saturating_add(retention) > now {
+                break;
+            }
+            self.pop_oldest();
+        }
+    }
+
+    fn pop_oldest(&mut self) {
+        if let Some(message) = self.messages.pop_front() {
+            if let Some(ids) = self.ids.get_mut(&message.topic) {
+                ids.remove(&message.id);
+                if ids.is_empty() {
+                    self.ids.remove(&message.topic);
+                }
+            }
+        }
+    }
+
+    fn read(&self) -> Result<Vec<SeenMessage>, SerializingError> {
+        let data = fs::read(&self.path)?;
+        let reader = &mut &data[..];
+
+        let num_messages: u32 = Deserialize::deserialize(reader)?;
+        (0..num_messages)
+            .map(|_| Deserialize::deserialize(reader))
+            .collect()
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        let mut data = vec![];
+        (self.messages.len() as u32)
+            .serialize(&mut data)
+            .expect("Failed to serialize the number of seen messages");
+        for message in &self.messages {
+            message
+                .serialize(&mut data)
+                .expect("Failed to serialize a seen message");
+        }
+        data
+    }
+}
+
+fn write(path: &Path, data: Vec<u8>) -> io::Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, data)?;
+    fs::rename(&tmp_path, path)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("System time is before the unix epoch")
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{SeenMessages, MAX_SEEN_MESSAGES};
+
+    #[tokio::test]
+    async fn it_restores_the_recent_messages() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("seen_messages");
+
+        let mut seen_messages = SeenMessages::load(path.clone(), Duration::from_secs(60));
+        seen_messages.insert("blocks", b"a");
+        seen_messages.insert("transactions", b"b");
+        seen_messages.save().await.unwrap();
+
+        let seen_messages = SeenMessages::load(path.clone(), Duration::from_secs(60));
+        assert!(seen_messages.contains("blocks", b"a"));
+        assert!(seen_messages.contains("transactions", b"b"));
+        assert!(!seen_messages.contains("blocks", b"b"));
+
+        // Messages that are older than the retention time are dropped.
+        let seen_messages = SeenMessages::load(path, Duration::ZERO);
+        assert!(!seen_messages.contains("blocks", b"a"));
+    }
+
+    #[test]
+    fn it_keeps_a_bounded_number_of_messages() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("seen_messages");
+
+        let mut seen_messages = SeenMessages::load(path, Duration::from_secs(60));
+        for i in 0..=MAX_SEEN_MESSAGES as u32 {
+            seen_messages.insert("blocks", &i.to_be_bytes());
+        }
+
+        // The oldest message was dropped to stay within the limit.
+        assert_eq!(seen_messages.messages.len(), MAX_SEEN_MESSAGES);
+        assert!(!seen_messages.contains("blocks", &0u32.to_be_bytes()));
+        assert!(seen_messages.contains("blocks", &1u32.to_be_bytes()));
+        assert!(seen_messages.contains("blocks", &(MAX_SEEN_MESSAGES as u32).to_be_bytes()));
+    }
+}
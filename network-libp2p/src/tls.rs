@@ -0,0 +1,535 @@
+//! Support for secure websocket (`/wss`) listen addresses.
+//!
+//! The websocket transport of libp2p only takes a fixed TLS configuration, so renewed certificates
+//! would only be used after a restart. Instead, the [`TlsTransport`] terminates TLS below the
+//! websocket transport with the current certificates, and the [`WssTransport`] above it listens on
+//! the corresponding plain websocket address. Dialing `/wss` addresses is still done by the
+//! websocket transport.
+
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use futures::{
+    future::{BoxFuture, Either, FutureExt, TryFutureExt},
+    io::{AsyncRead, AsyncWrite},
+    stream::{BoxStream, StreamExt, TryStreamExt},
+};
+use futures_rustls::{
+    rustls::{Certificate, PrivateKey, ServerConfig},
+    server::TlsStream,
+    TlsAcceptor,
+};
+use libp2p::{
+    core::{
+        multiaddr::Protocol,
+        transport::{ListenerEvent, TransportError},
+    },
+    Multiaddr, Transport,
+};
+use parking_lot::{Mutex, RwLock};
+use rustls_pemfile::Item;
+
+/// The certificate chain and private key that secure websocket connections are accepted with.
+///
+/// The files are read in PEM format. They are checked for changes periodically, so renewed
+/// certificates are used for new connections without restarting the node.
+pub struct TlsCertificates {
+    certificate_file: PathBuf,
+    private_key_file: PathBuf,
+    /// The time the files were last modified when they were loaded.
+    modified: Mutex<SystemTime>,
+    server_config: RwLock<Arc<ServerConfig>>,
+}
+
+impl TlsCertificates {
+    /// Interval at which the files are checked for changes.
+    pub const RELOAD_INTERVAL: Duration = Duration::from_secs(60);
+
+    pub fn load<P: Into<PathBuf>>(certificate_file: P, private_key_file: P) -> io::Result<Self> {
+        let certificate_file = certificate_file.into();
+        let private_key_file = private_key_file.into();
+
+        let modified = modified(&certificate_file, &private_key_file)?;
+        let server_config = server_config(&certificate_file, &private_key_file)?;
+
+        Ok(TlsCertificates {
+            certificate_file,
+            private_key_file,
+            modified: Mutex::new(modified),
+            server_config: RwLock::new(Arc::new(server_config)),
+        })
+    }
+
+    /// Loads the files again. The previous certificates are kept if they can't be loaded.
+    pub fn reload(&self) -> io::Result<()> {
+        let modified = modified(&self.certificate_file, &self.private_key_file)?;
+        let server_config = server_config(&self.certificate_file, &self.private_key_file)?;
+
+        *self.modified.lock() = modified;
+        *self.server_config.write() = Arc::new(server_config);
+        Ok(())
+    }
+
+    /// Loads the files again if they were modified since they were last loaded.
+    fn reload_if_modified(&self) -> io::Result<bool> {
+        let modified = modified(&self.certificate_file, &self.private_key_file)?;
+        if modified == *self.modified.lock() {
+            return Ok(false);
+        }
+        self.reload()?;
+        Ok(true)
+    }
+
+    /// Checks the files for changes every [`Self::RELOAD_INTERVAL`].
+    pub async fn watch(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(Self::RELOAD_INTERVAL);
+        // The first tick completes immediately.
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            match self.reload_if_modified() {
+                Ok(true) => log::info!(
+                    "Reloaded TLS certificate from {}",
+                    self.certificate_file.display()
+                ),
+                Ok(false) => {}
+                Err(error) => log::warn!(
+                    "Failed to reload TLS certificate from {}, keeping the previous one: {}",
+                    self.certificate_file.display(),
+                    error
+                ),
+            }
+        }
+    }
+
+    fn acceptor(&self) -> TlsAcceptor {
+        TlsAcceptor::from(Arc::clone(&self.server_config.read()))
+    }
+}
+
+/// Returns when either of the files was last modified.
+fn modified(certificate_file: &Path, private_key_file: &Path) -> io::Result<SystemTime> {
+    Ok(fs::metadata(certificate_file)?
+        .modified()?
+        .max(fs::metadata(private_key_file)?.modified()?))
+}
+
+fn server_config(certificate_file: &Path, private_key_file: &Path) -> io::Result<ServerConfig> {
+    ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(
+            read_certificates(certificate_file)?,
+            read_private_key(private_key_file)?,
+        )
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+/// Reads the certificate chain from a PEM file.
+fn read_certificates(path: &Path) -> io::Result<Vec<Certificate>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let certificates: Vec<Certificate> = rustls_pemfile::certs(&mut reader)?
+        .into_iter()
+        .map(Certificate)
+        .collect();
+    if certificates.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("No certificate found in {}", path.display()),
+        ));
+    }
+    Ok(certificates)
+}
+
+/// Reads the first PKCS#8, PKCS#1 (RSA) or SEC1 (EC) private key from a PEM file.
+fn read_private_key(path: &Path) -> io::Result<PrivateKey> {
+    let mut reader = BufReader::new(File::open(path)?);
+    loop {
+        match rustls_pemfile::read_one(&mut reader)? {
+            Some(Item::PKCS8Key(key) | Item::RSAKey(key) | Item::ECKey(key)) => {
+                return Ok(PrivateKey(key))
+            }
+            Some(_) => {}
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("No private key found in {}", path.display()),
+                ))
+            }
+        }
+    }
+}
+
+/// The state shared by a [`WssTransport`] and the [`TlsTransport`] below it.
+#[derive(Clone, Default)]
+pub struct TlsListeners {
+    certificates: Option<Arc<TlsCertificates>>,
+    /// The addresses the TLS transport is about to listen on for the websocket transport.
+    addresses: Arc<Mutex<HashSet<Multiaddr>>>,
+}
+
+impl TlsListeners {
+    pub fn new(certificates: Option<Arc<TlsCertificates>>) -> Self {
+        TlsListeners {
+            certificates,
+            addresses: Arc::default(),
+        }
+    }
+}
+
+type TlsUpgrade<S> = BoxFuture<'static, io::Result<Either<S, TlsStream<S>>>>;
+
+/// Transport that accepts TLS connections on the addresses the [`WssTransport`] listens on.
+/// Connections on any other address and dialed connections are passed through unchanged.
+#[derive(Clone)]
+pub struct TlsTransport<T> {
+    inner: T,
+    listeners: TlsListeners,
+}
+
+impl<T> TlsTransport<T> {
+    pub fn new(inner: T, listeners: TlsListeners) -> Self {
+        Self { inner, listeners }
+    }
+}
+
+impl<T> Transport for TlsTransport<T>
+where
+    T: Transport<Error = io::Error> + Send + 'static,
+    T::Output: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    T::Listener: Send + 'static,
+    T::ListenerUpgrade: Send + 'static,
+    T::Dial: Send + 'static,
+{
+    type Output = Either<T::Output, TlsStream<T::Output>>;
+    type Error = io::Error;
+    type Listener =
+        BoxStream<'static, Result<ListenerEvent<TlsUpgrade<T::Output>, io::Error>, io::Error>>;
+    type ListenerUpgrade = TlsUpgrade<T::Output>;
+    type Dial = TlsUpgrade<T::Output>;
+
+    fn listen_on(self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
+        let certificates = match &self.listeners.certificates {
+            Some(certificates) if self.listeners.addresses.lock().contains(&addr) => {
+                Arc::clone(certificates)
+            }
+            _ => {
+                return Ok(self
+                    .inner
+                    .listen_on(addr)?
+                    .map_ok(|event| event.map(|upgrade| upgrade.map_ok(Either::Left).boxed()))
+                    .boxed())
+            }
+        };
+
+        Ok(self
+            .inner
+            .listen_on(addr)?
+            .map_ok(move |event| {
+                let certificates = Arc::clone(&certificates);
+                event.map(|upgrade| {
+                    async move {
+                        let stream = upgrade.await?;
+                        // New connections always use the current certificates.
+                        let stream = certificates.acceptor().accept(stream).await?;
+                        Ok(Either::Right(stream))
+                    }
+                    .boxed()
+                })
+            })
+            .boxed())
+    }
+
+    fn dial(self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
+        Ok(self.inner.dial(addr)?.map_ok(Either::Left).boxed())
+    }
+
+    fn dial_as_listener(self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
+        Ok(self
+            .inner
+            .dial_as_listener(addr)?
+            .map_ok(Either::Left)
+            .boxed())
+    }
+
+    fn address_translation(&self, listen: &Multiaddr, observed: &Multiaddr) -> Option<Multiaddr> {
+        self.inner.address_translation(listen, observed)
+    }
+}
+
+/// Transport that listens on `/wss` addresses by listening on the corresponding `/ws` address of
+/// the websocket transport, with TLS accepted by the [`TlsTransport`] below it. It must wrap the
+/// websocket transport.
+#[derive(Clone)]
+pub struct WssTransport<T> {
+    inner: T,
+    listeners: TlsListeners,
+}
+
+impl<T> WssTransport<T> {
+    pub fn new(inner: T, listeners: TlsListeners) -> Self {
+        Self { inner, listeners }
+    }
+}
+
+impl<T> Transport for WssTransport<T>
+where
+    T: Transport + Send + 'static,
+    T::Error: Send + 'static,
+    T::Listener: Send + 'static,
+    T::ListenerUpgrade: Send + 'static,
+{
+    type Output = T::Output;
+    type Error = T::Error;
+    type Listener =
+        BoxStream<'static, Result<ListenerEvent<T::ListenerUpgrade, T::Error>, T::Error>>;
+    type ListenerUpgrade = T::ListenerUpgrade;
+    type Dial = T::Dial;
+
+    fn listen_on(self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
+        // Without certificates, the websocket transport rejects the address.
+        let ws_addr = match wss_to_ws(&addr) {
+            Some(ws_addr) if self.listeners.certificates.is_some() => ws_addr,
+            _ => return Ok(self.inner.listen_on(addr)?.boxed()),
+        };
+
+        // The TLS transport gets the address without the websocket protocol.
+        let mut tls_addr = ws_addr.clone();
+        tls_addr.pop();
+
+        self.listeners.addresses.lock().insert(tls_addr.clone());
+        let listener = self.inner.listen_on(ws_addr);
+        self.listeners.addresses.lock().remove(&tls_addr);
+
+        let listener = listener.map_err(|error| match error {
+            TransportError::MultiaddrNotSupported(_) => TransportError::MultiaddrNotSupported(addr),
+            error => error,
+        })?;
+
+        Ok(listener
+            .map_ok(|event| match event {
+                ListenerEvent::NewAddress(addr) => ListenerEvent::NewAddress(ws_to_wss(addr)),
+                ListenerEvent::Upgrade {
+                    upgrade,
+                    local_addr,
+                    remote_addr,
+                } => ListenerEvent::Upgrade {
+                    upgrade,
+                    local_addr: ws_to_wss(local_addr),
+                    remote_addr,
+                },
+                ListenerEvent::AddressExpired(addr) => {
+                    ListenerEvent::AddressExpired(ws_to_wss(addr))
+                }
+                ListenerEvent::Error(error) => ListenerEvent::Error(error),
+            })
+            .boxed())
+    }
+
+    fn dial(self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
+        self.inner.dial(addr)
+    }
+
+    fn dial_as_listener(self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
+        self.inner.dial_as_listener(addr)
+    }
+
+    fn address_translation(&self, listen: &Multiaddr, observed: &Multiaddr) -> Option<Multiaddr> {
+        self.inner.address_translation(listen, observed)
+    }
+}
+
+/// Replaces a trailing `/wss` with `/ws`. Returns `None` for any other address.
+fn wss_to_ws(addr: &Multiaddr) -> Option<Multiaddr> {
+    let mut ws_addr = addr.clone();
+    match ws_addr.pop() {
+        Some(Protocol::Wss(path)) => Some(ws_addr.with(Protocol::Ws(path))),
+        _ => None,
+    }
+}
+
+/// Replaces a trailing `/ws` with `/wss`. Any other address is returned unchanged.
+fn ws_to_wss(addr: Multiaddr) -> Multiaddr {
+    let mut wss_addr = addr.clone();
+    match wss_addr.pop() {
+        Some(Protocol::Ws(path)) => wss_addr.with(Protocol::Wss(path)),
+        _ => addr,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::io::{AsyncReadExt, AsyncWriteExt};
+    use futures_rustls::{
+        rustls::{ClientConfig, RootCertStore, ServerName},
+        TlsConnector,
+    };
+    use libp2p::{tcp::TokioTcpConfig, websocket::WsConfig};
+
+    use super::*;
+
+    type TestTransport = WssTransport<WsConfig<TlsTransport<TokioTcpConfig>>>;
+
+    /// Returns the path of a test certificate. `localhost-1.pem` and `localhost-2.pem` are
+    /// certificates for `localhost` issued by `ca.pem`.
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/certificates")
+            .join(name)
+    }
+
+    async fn listen_wss(
+        certificates: Arc<TlsCertificates>,
+    ) -> (<TestTransport as Transport>::Listener, Multiaddr) {
+        let listeners = TlsListeners::new(Some(certificates));
+        let transport = WssTransport::new(
+            WsConfig::new(TlsTransport::new(TokioTcpConfig::new(), listeners.clone())),
+            listeners,
+        );
+
+        let mut listener = transport
+            .listen_on("/ip4/127.0.0.1/tcp/0/wss".parse().unwrap())
+            .unwrap();
+        match listener.next().await {
+            Some(Ok(ListenerEvent::NewAddress(addr))) => {
+                assert!(matches!(addr.iter().last(), Some(Protocol::Wss(_))));
+                (listener, addr)
+            }
+            _ => panic!("Expected a listen address"),
+        }
+    }
+
+    /// Opens a websocket connection to a `/wss` address that the listener accepts, and returns
+    /// the certificate the connection was secured with.
+    async fn connect(
+        listener: &mut <TestTransport as Transport>::Listener,
+        addr: &Multiaddr,
+    ) -> Certificate {
+        let mut tcp_addr = addr.clone();
+        tcp_addr.pop();
+
+        let mut roots = RootCertStore::empty();
+        for certificate in read_certificates(&fixture("ca.pem")).unwrap() {
+            roots.add(&certificate).unwrap();
+        }
+        let connector = TlsConnector::from(Arc::new(
+            ClientConfig::builder()
+                .with_safe_defaults()
+                .with_root_certificates(roots)
+                .with_no_client_auth(),
+        ));
+
+        let client = async {
+            let stream = TokioTcpConfig::new().dial(tcp_addr).unwrap().await.unwrap();
+            let mut stream = connector
+                .connect(ServerName::try_from("localhost").unwrap(), stream)
+                .await
+                .unwrap();
+
+            stream
+                .write_all(
+                    b"GET / HTTP/1.1\r\n\
+                      Host: localhost\r\n\
+                      Upgrade: websocket\r\n\
+                      Connection: Upgrade\r\n\
+                      Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+                      Sec-WebSocket-Version: 13\r\n\r\n",
+                )
+                .await
+                .unwrap();
+            stream.flush().await.unwrap();
+
+            let mut response = vec![];
+            while !response.ends_with(b"\r\n\r\n") {
+                let mut byte = [0u8];
+                stream.read_exact(&mut byte).await.unwrap();
+                response.push(byte[0]);
+            }
+            assert!(response.starts_with(b"HTTP/1.1 101"));
+
+            stream.get_ref().1.peer_certificates().unwrap()[0].clone()
+        };
+
+        let server = async {
+            match listener.next().await {
+                Some(Ok(ListenerEvent::Upgrade { upgrade, .. })) => upgrade.await.unwrap(),
+                _ => panic!("Expected an incoming connection"),
+            }
+        };
+
+        let (certificate, _connection) = futures::join!(client, server);
+        certificate
+    }
+
+    #[test]
+    fn it_reads_sec1_and_pkcs8_keys() {
+        // The key of the first certificate is in SEC1 format, the second one in PKCS#8.
+        for name in ["localhost-1", "localhost-2"] {
+            assert!(server_config(
+                &fixture(&format!("{}.pem", name)),
+                &fixture(&format!("{}-key.pem", name))
+            )
+            .is_ok());
+        }
+
+        assert!(read_certificates(&fixture("localhost-1-key.pem")).is_err());
+        assert!(read_private_key(&fixture("ca.pem")).is_err());
+    }
+
+    #[tokio::test]
+    async fn it_accepts_wss_connections() {
+        let certificates =
+            TlsCertificates::load(fixture("localhost-1.pem"), fixture("localhost-1-key.pem"))
+                .unwrap();
+        let (mut listener, addr) = listen_wss(Arc::new(certificates)).await;
+
+        assert_eq!(
+            connect(&mut listener, &addr).await,
+            read_certificates(&fixture("localhost-1.pem")).unwrap()[0]
+        );
+    }
+
+    #[tokio::test]
+    async fn it_uses_reloaded_certificates() {
+        let dir = tempfile::tempdir().unwrap();
+        let certificate_file = dir.path().join("fullchain.pem");
+        let private_key_file = dir.path().join("privkey.pem");
+        let install = |name: &str| {
+            fs::copy(fixture(&format!("{}.pem", name)), &certificate_file).unwrap();
+            fs::copy(fixture(&format!("{}-key.pem", name)), &private_key_file).unwrap();
+        };
+
+        install("localhost-1");
+        let certificates =
+            Arc::new(TlsCertificates::load(&certificate_file, &private_key_file).unwrap());
+        let (mut listener, addr) = listen_wss(Arc::clone(&certificates)).await;
+        assert!(!certificates.reload_if_modified().unwrap());
+
+        install("localhost-2");
+        assert!(certificates.reload_if_modified().unwrap());
+        let renewed = read_certificates(&fixture("localhost-2.pem")).unwrap();
+        assert_eq!(connect(&mut listener, &addr).await, renewed[0]);
+
+        // Invalid files don't replace the current certificates.
+        fs::write(&private_key_file, "").unwrap();
+        assert!(certificates.reload_if_modified().is_err());
+        assert_eq!(connect(&mut listener, &addr).await, renewed[0]);
+    }
+
+    #[test]
+    fn it_maps_wss_addresses() {
+        let wss: Multiaddr = "/ip4/127.0.0.1/tcp/8443/wss".parse().unwrap();
+        let ws: Multiaddr = "/ip4/127.0.0.1/tcp/8443/ws".parse().unwrap();
+
+        assert_eq!(wss_to_ws(&wss), Some(ws.clone()));
+        assert_eq!(wss_to_ws(&ws), None);
+        assert_eq!(ws_to_wss(ws), wss);
+        assert_eq!(ws_to_wss(wss.clone()), wss);
+    }
+}
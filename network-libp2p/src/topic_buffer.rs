@@ -0,0 +1,167 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use futures::{
+    channel::mpsc,
+    future,
+    stream::{BoxStream, StreamExt},
+};
+use libp2p::{
+    gossipsub::{GossipsubMessage, MessageId},
+    PeerId,
+};
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
+
+use nimiq_network_interface::network::OverflowPolicy;
+
+pub(crate) type GossipItem = (GossipsubMessage, MessageId, PeerId);
+
+/// Counters of the inbound messages of a subscribed gossipsub topic.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GossipTopicStats {
+    pub topic_name: String,
+    pub overflow_policy: OverflowPolicy,
+    /// Number of messages received on the topic.
+    pub received: u64,
+    /// Number of messages that were dropped because the subscriber didn't keep up.
+    pub dropped: u64,
+}
+
+#[derive(Default)]
+struct Counters {
+    received: AtomicU64,
+    dropped: AtomicU64,
+}
+
+enum Sender {
+    Bounded(mpsc::Sender<GossipItem>),
+    Ring(broadcast::Sender<GossipItem>),
+}
+
+/// The buffer between the swarm and the subscriber of a gossipsub topic.
+pub(crate) struct TopicBuffer {
+    sender: Sender,
+    pub validate: bool,
+    pub overflow_policy: OverflowPolicy,
+    pub persist_seen: bool,
+    counters: Arc<Counters>,
+}
+
+impl TopicBuffer {
+    /// Creates a buffer and the stream of messages for the subscriber.
+    pub fn new(
+        buffer_size: usize,
+        validate: bool,
+        overflow_policy: OverflowPolicy,
+        persist_seen: bool,
+    ) -> (Self, BoxStream<'static, GossipItem>) {
+        let counters = Arc::new(Counters::default());
+
+        let (sender, stream) = match overflow_policy {
+            OverflowPolicy::DropNew | OverflowPolicy::Backpressure => {
+                let (tx, rx) = mpsc::channel(buffer_size);
+                (Sender::Bounded(tx), rx.boxed())
+            }
+            OverflowPolicy::DropOld => {
+                // A lagging broadcast receiver skips the oldest messages.
+                let (tx, rx) = broadcast::channel(buffer_size.max(1));
+                let counters = Arc::clone(&counters);
+                let stream = BroadcastStream::new(rx).filter_map(move |item| {
+                    future::ready(match item {
+                        Ok(item) => Some(item),
+                        Err(BroadcastStreamRecvError::Lagged(skipped)) => {
+                            counters.dropped.fetch_add(skipped, Ordering::Relaxed);
+                            None
+                        }
+                    })
+                });
+                (Sender::Ring(tx), stream.boxed())
+            }
+        };
+
+        let buffer = TopicBuffer {
+            sender,
+            validate,
+            overflow_policy,
+            persist_seen,
+            counters,
+        };
+        (buffer, stream)
+    }
+
+    /// Passes a message on to the subscriber. Returns the message if it was dropped.
+    pub fn dispatch(&mut self, item: GossipItem) -> Result<(), GossipItem> {
+        self.counters.received.fetch_add(1, Ordering::Relaxed);
+
+        let result = match &mut self.sender {
+            Sender::Bounded(sender) => sender.try_send(item).map_err(|e| e.into_inner()),
+            Sender::Ring(sender) => sender.send(item).map(|_| ()).map_err(|e| e.0),
+        };
+        if result.is_err() {
+            self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        result
+    }
+
+    pub fn stats(&self, topic_name: String) -> GossipTopicStats {
+        GossipTopicStats {
+            topic_name,
+            overflow_policy: self.overflow_policy,
+            received: self.counters.received.load(Ordering::Relaxed),
+            dropped: self.counters.dropped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libp2p::gossipsub::TopicHash;
+
+    use super::*;
+
+    fn item(data: u8) -> GossipItem {
+        let message = GossipsubMessage {
+            source: None,
+            data: vec![data],
+            sequence_number: None,
+            topic: TopicHash::from_raw("test"),
+        };
+        (message, MessageId::new(&[data]), PeerId::random())
+    }
+
+    async fn received(stream: &mut BoxStream<'static, GossipItem>, n: usize) -> Vec<u8> {
+        stream
+            .take(n)
+            .map(|(message, _, _)| message.data[0])
+            .collect()
+            .await
+    }
+
+    #[tokio::test]
+    async fn it_drops_new_messages() {
+        let (mut buffer, mut stream) = TopicBuffer::new(1, false, OverflowPolicy::DropNew, false);
+
+        // The bounded channel has one slot per sender in addition to the buffer.
+        assert!(buffer.dispatch(item(1)).is_ok());
+        assert!(buffer.dispatch(item(2)).is_ok());
+        assert!(buffer.dispatch(item(3)).is_err());
+
+        assert_eq!(received(&mut stream, 2).await, vec![1, 2]);
+        let stats = buffer.stats("test".to_string());
+        assert_eq!((stats.received, stats.dropped), (3, 1));
+    }
+
+    #[tokio::test]
+    async fn it_drops_old_messages() {
+        let (mut buffer, mut stream) = TopicBuffer::new(2, false, OverflowPolicy::DropOld, false);
+
+        for i in 1..=4 {
+            assert!(buffer.dispatch(item(i)).is_ok());
+        }
+
+        assert_eq!(received(&mut stream, 2).await, vec![3, 4]);
+        let stats = buffer.stats("test".to_string());
+        assert_eq!((stats.received, stats.dropped), (4, 2));
+    }
+}
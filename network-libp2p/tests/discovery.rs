@@ -24,6 +24,7 @@ use nimiq_network_libp2p::discovery::{
     behaviour::{DiscoveryBehaviour, DiscoveryConfig, DiscoveryEvent},
     peer_contacts::{PeerContact, Protocols, Services},
 };
+use nimiq_utils::shared_rng::SharedRng;
 use nimiq_utils::time::OffsetTime;
 
 struct TestNode {
@@ -59,6 +60,9 @@ impl TestNode {
             update_interval: Duration::from_secs(10),
             min_send_update_interval: Duration::from_secs(5),
             update_limit: 64,
+            max_send_limit: 64,
+            initial_send_limit: 16,
+            amplification_factor: 2,
             protocols_filter: Protocols::all(),
             services_filter: Services::all(),
             min_recv_update_interval: Duration::from_secs(1),
@@ -80,8 +84,13 @@ impl TestNode {
         )));
 
         let clock = Arc::new(OffsetTime::new());
-        let behaviour =
-            DiscoveryBehaviour::new(config, keypair, Arc::clone(&peer_contact_book), clock);
+        let behaviour = DiscoveryBehaviour::new(
+            config,
+            keypair,
+            Arc::clone(&peer_contact_book),
+            clock,
+            SharedRng::seeded(42),
+        );
 
         let mut swarm = Swarm::new(transport, behaviour, peer_id);
 
@@ -43,6 +43,9 @@ pub(crate) struct MockHubInner {
     /// DHT
     pub dht: HashMap<Vec<u8>, Vec<u8>>,
 
+    /// Providers of DHT keys
+    pub dht_providers: HashMap<Vec<u8>, HashSet<MockPeerId>>,
+
     /// Arcs to `AtomicBool`s for each network if they're connected.
     pub is_connected: HashMap<MockAddress, Arc<AtomicBool>>,
 }
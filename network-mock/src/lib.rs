@@ -4,6 +4,7 @@ extern crate beserial_derive;
 mod hub;
 mod network;
 mod peer;
+pub mod replay;
 
 use beserial::{Deserialize, Serialize};
 use derive_more::{Display, From, Into};
@@ -14,7 +15,7 @@ pub use peer::MockPeer;
 
 /// The address of a MockNetwork or a peer thereof. Peer IDs are always equal to their respective address, thus these
 /// can be converted between each other.
-#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq, Display, From, Into)]
+#[derive(Copy, Clone, Debug, Deserialize, Serialize, Hash, PartialEq, Eq, Display, From, Into)]
 pub struct MockAddress(u64);
 
 /// The peer ID of a MockNetwork or a peer thereof. Peer IDs are always equal to their respective address, thus these
@@ -75,9 +76,11 @@ pub mod tests {
         message::Message,
         network::{Network, NetworkEvent, Topic},
         peer::Peer,
+        recording::{RecordedMessage, RecordedPayload},
     };
 
     use super::network::MockNetworkError;
+    use super::replay::replay;
     use super::{MockHub, MockPeer, MockPeerId};
 
     pub async fn assert_peer_joined(
@@ -166,6 +169,23 @@ pub mod tests {
         assert_eq!(fetched_record, Some(put_record));
     }
 
+    #[tokio::test]
+    async fn dht_provide_and_get_providers() {
+        let mut hub = MockHub::new();
+        let net1 = hub.new_network();
+        let net2 = hub.new_network();
+        net1.dial_mock(&net2);
+
+        assert_eq!(net2.dht_get_providers(b"foo").await.unwrap(), vec![]);
+
+        net1.dht_provide(b"foo").await.unwrap();
+
+        assert_eq!(
+            net2.dht_get_providers(b"foo").await.unwrap(),
+            vec![net1.peer_id()]
+        );
+    }
+
     pub struct TestTopic;
 
     impl Topic for TestTopic {
@@ -242,4 +262,45 @@ pub mod tests {
         assert_eq!(msg1.id, 1337);
         assert_eq!(msg2.id, 420);
     }
+
+    #[tokio::test]
+    async fn replay_recorded_messages() {
+        let mut hub = MockHub::new();
+        let target = hub.new_network();
+
+        let mut gossip = target.subscribe::<TestTopic>().await.unwrap();
+        let (_, mut events) = target.get_peer_updates();
+        let direct = tokio::spawn(async move {
+            if let Some(Ok(NetworkEvent::PeerJoined(peer))) = events.next().await {
+                peer.receive::<TestMessage>().next().await
+            } else {
+                None
+            }
+        });
+
+        let messages = vec![
+            RecordedMessage {
+                timestamp: 0,
+                peer: "a".to_string(),
+                payload: RecordedPayload::Gossip {
+                    topic: TestTopic::NAME.to_string(),
+                    data: TestRecord { x: 42 }.serialize_to_vec(),
+                },
+            },
+            RecordedMessage {
+                timestamp: 10,
+                peer: "a".to_string(),
+                payload: RecordedPayload::Message {
+                    type_id: TestMessage::TYPE_ID,
+                    data: TestMessage { id: 1337 }.serialize_to_vec(),
+                },
+            },
+        ];
+
+        assert_eq!(replay(&mut hub, &target, messages, 10).await, Ok(2));
+
+        let (record, _) = gossip.next().await.unwrap();
+        assert_eq!(record, TestRecord { x: 42 });
+        assert_eq!(direct.await.unwrap().unwrap().id, 1337);
+    }
 }
@@ -4,18 +4,26 @@ use std::sync::{
 };
 
 use async_trait::async_trait;
-use futures::stream::{BoxStream, StreamExt};
+use futures::{
+    sink::SinkExt,
+    stream::{BoxStream, StreamExt},
+};
 use parking_lot::Mutex;
 use thiserror::Error;
 use tokio::sync::broadcast::Sender;
 use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
 
 use beserial::{Deserialize, Serialize};
+use nimiq_network_interface::message::frame_message;
 use nimiq_network_interface::network::{MsgAcceptance, NetworkEvent, PubsubId, Topic};
 use nimiq_network_interface::peer::Peer;
 use nimiq_network_interface::{network::Network, peer_map::ObservablePeerMap};
 
-use crate::{hub::MockHubInner, peer::MockPeer, MockAddress, MockPeerId};
+use crate::{
+    hub::{MockHubInner, SenderKey},
+    peer::MockPeer,
+    MockAddress, MockPeerId,
+};
 
 #[derive(Debug, Error, PartialEq)]
 pub enum MockNetworkError {
@@ -58,6 +66,8 @@ pub struct MockNetwork {
     peers: ObservablePeerMap<MockPeer>,
     hub: Arc<Mutex<MockHubInner>>,
     is_connected: Arc<AtomicBool>,
+    /// The validation results reported for pubsub messages - `(topic, acceptance)`.
+    validations: Mutex<Vec<(&'static str, MsgAcceptance)>>,
 }
 
 impl MockNetwork {
@@ -87,6 +97,7 @@ impl MockNetwork {
             peers,
             hub,
             is_connected,
+            validations: Mutex::new(Vec::new()),
         }
     }
 
@@ -98,6 +109,12 @@ impl MockNetwork {
         self.address.into()
     }
 
+    /// Returns the validation results reported for pubsub messages, in the order they were
+    /// reported.
+    pub fn validations(&self) -> Vec<(&'static str, MsgAcceptance)> {
+        self.validations.lock().clone()
+    }
+
     fn dial_mock_address(&self, address: MockAddress) -> Result<(), MockNetworkError> {
         let hub = self.hub.lock();
 
@@ -160,12 +177,67 @@ impl MockNetwork {
         self.is_connected.store(false, Ordering::SeqCst);
     }
 
+    /// Emits a `NetworkEvent::Reconnected`, as the libp2p network does once it regained
+    /// connectivity.
+    pub fn reconnected(&self) {
+        self.peers.notify(NetworkEvent::Reconnected);
+    }
+
     /// Disconnects from all peers and deletes this peer from the hub to prevent future connections
     /// to or from it.
     pub fn shutdown(&self) {
         self.disconnect();
         self.hub.lock().peer_maps.remove(&self.address);
     }
+
+    /// Publishes already serialized data on a topic. This is used to replay recorded messages.
+    pub fn publish_raw(&self, topic_name: &str, data: Vec<u8>) -> Result<(), MockNetworkError> {
+        if !self.is_connected.load(Ordering::SeqCst) {
+            return Err(MockNetworkError::NotConnected);
+        }
+
+        let hub = self.hub.lock();
+        if let Some(topic) = hub.gossipsub_topics.get(topic_name) {
+            // Sending only fails if there are no receivers, which is fine.
+            topic
+                .sender
+                .send((Arc::new(data), self.address.into()))
+                .ok();
+        } else {
+            log::debug!("No peer is subscribed to topic: '{}'", topic_name);
+        }
+        Ok(())
+    }
+
+    /// Sends an already serialized message body to a peer. This is used to replay recorded
+    /// messages.
+    pub async fn send_raw(
+        &self,
+        peer_id: MockPeerId,
+        type_id: u64,
+        data: &[u8],
+    ) -> Result<(), MockNetworkError> {
+        let k = SenderKey {
+            network_recipient: peer_id.into(),
+            sender_peer: self.address.into(),
+            message_type: type_id,
+        };
+
+        let mut sender = {
+            let hub = self.hub.lock();
+            if let Some(sender) = hub.network_senders.get(&k) {
+                sender.clone()
+            } else {
+                log::debug!("No such sender: {:?}", k);
+                return Ok(());
+            }
+        };
+
+        sender
+            .send(frame_message(type_id, data))
+            .await
+            .map_err(|_| MockNetworkError::CantConnect(peer_id.into()))
+    }
 }
 
 #[async_trait]
@@ -305,11 +377,12 @@ impl Network for MockNetwork {
         }
     }
 
-    fn validate_message<TTopic>(&self, _id: Self::PubsubId, _acceptance: MsgAcceptance)
+    fn validate_message<TTopic>(&self, _id: Self::PubsubId, acceptance: MsgAcceptance)
     where
         TTopic: Topic + Sync,
     {
-        // TODO implement
+        // TODO implement relaying of accepted messages
+        self.validations.lock().push((TTopic::NAME, acceptance));
     }
 
     async fn dht_get<K, V>(&self, k: &K) -> Result<Option<V>, Self::Error>
@@ -346,6 +419,40 @@ impl Network for MockNetwork {
         }
     }
 
+    async fn dht_provide<K>(&self, k: &K) -> Result<(), Self::Error>
+    where
+        K: AsRef<[u8]> + Send + Sync,
+    {
+        if self.is_connected.load(Ordering::SeqCst) {
+            let mut hub = self.hub.lock();
+
+            hub.dht_providers
+                .entry(k.as_ref().to_owned())
+                .or_default()
+                .insert(self.address.into());
+            Ok(())
+        } else {
+            Err(MockNetworkError::NotConnected)
+        }
+    }
+
+    async fn dht_get_providers<K>(&self, k: &K) -> Result<Vec<MockPeerId>, Self::Error>
+    where
+        K: AsRef<[u8]> + Send + Sync,
+    {
+        if self.is_connected.load(Ordering::SeqCst) {
+            let hub = self.hub.lock();
+
+            Ok(hub
+                .dht_providers
+                .get(k.as_ref())
+                .map(|providers| providers.iter().cloned().collect())
+                .unwrap_or_default())
+        } else {
+            Err(MockNetworkError::NotConnected)
+        }
+    }
+
     async fn dial_peer(&self, peer_id: MockPeerId) -> Result<(), Self::Error> {
         self.dial_mock_address(peer_id.into())
     }
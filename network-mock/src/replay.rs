@@ -0,0 +1,59 @@
+use std::{collections::HashMap, time::Duration};
+
+use nimiq_network_interface::recording::{RecordedMessage, RecordedPayload};
+
+use crate::{network::MockNetworkError, MockHub, MockNetwork};
+
+/// Time to give the target to register its message handlers for a newly connected peer.
+const CONNECT_DELAY: Duration = Duration::from_millis(100);
+
+/// Replays recorded messages against `target`.
+///
+/// Every peer found in the recording is represented by a new network in `hub` that connects to
+/// `target` when its first message is replayed. The delays between the messages are divided by
+/// `speedup`, i.e. a `speedup` of 10 replays a recording ten times faster than it was recorded.
+///
+/// Returns the number of replayed messages.
+pub async fn replay<I>(
+    hub: &mut MockHub,
+    target: &MockNetwork,
+    messages: I,
+    speedup: u32,
+) -> Result<usize, MockNetworkError>
+where
+    I: IntoIterator<Item = RecordedMessage>,
+{
+    let speedup = u64::from(speedup.max(1));
+    let mut sources: HashMap<String, MockNetwork> = HashMap::new();
+    let mut last_timestamp = None;
+    let mut num_replayed = 0;
+
+    for message in messages {
+        if let Some(last_timestamp) = last_timestamp {
+            let delay = message.timestamp.saturating_sub(last_timestamp) / speedup;
+            if delay > 0 {
+                tokio::time::sleep(Duration::from_millis(delay)).await;
+            }
+        }
+        last_timestamp = Some(message.timestamp);
+
+        if !sources.contains_key(&message.peer) {
+            log::debug!("Connecting replay source for peer {}", message.peer);
+            let source = hub.new_network();
+            source.dial_mock(target);
+            tokio::time::sleep(CONNECT_DELAY).await;
+            sources.insert(message.peer.clone(), source);
+        }
+        let source = &sources[&message.peer];
+
+        match message.payload {
+            RecordedPayload::Message { type_id, data } => {
+                source.send_raw(target.peer_id(), type_id, &data).await?
+            }
+            RecordedPayload::Gossip { topic, data } => source.publish_raw(&topic, data)?,
+        }
+        num_replayed += 1;
+    }
+
+    Ok(num_replayed)
+}
@@ -1,11 +1,15 @@
 use std::io;
 
-use beserial::{Deserialize, ReadBytesExt, Serialize, SerializingError, WriteBytesExt};
+use beserial::schema::{define, Definitions};
+use beserial::{
+    Deserialize, FieldSchema, ReadBytesExt, Schema, Serialize, SerializingError, TypeSchema,
+    WriteBytesExt,
+};
 use nimiq_hash::{Hash, SerializeContent};
 use nimiq_keys::Address;
 use nimiq_primitives::coin::Coin;
 
-#[derive(Clone, Debug, Eq, PartialEq, Copy, Serialize, Deserialize)]
+#[derive(Clone, Debug, Eq, PartialEq, Copy, Serialize, Deserialize, Schema)]
 #[repr(u8)]
 pub enum InherentType {
     Reward,
@@ -104,3 +108,30 @@ impl Deserialize for Inherent {
         })
     }
 }
+
+/// The data is prefixed by its length as a `u32`.
+impl Schema for Inherent {
+    fn name() -> String {
+        "Inherent".to_string()
+    }
+
+    fn schema() -> TypeSchema {
+        TypeSchema::Struct {
+            fields: vec![
+                FieldSchema::new::<InherentType>(Some("ty")),
+                FieldSchema::new::<Address>(Some("target")),
+                FieldSchema::new::<Coin>(Some("value")),
+                FieldSchema::new::<Vec<u8>>(Some("data")).with_len_type("u32", None),
+            ],
+        }
+    }
+
+    fn collect(definitions: &mut Definitions) {
+        if define::<Self>(definitions) {
+            InherentType::collect(definitions);
+            Address::collect(definitions);
+            Coin::collect(definitions);
+            <Vec<u8>>::collect(definitions);
+        }
+    }
+}
@@ -243,15 +243,9 @@ impl StakingContract {
             validator_stakes.push(u64::from(*coin));
         }
 
-        let mut rng = seed.rng(VrfUseCase::ValidatorSlotSelection);
-
-        let lookup = AliasMethod::new(validator_stakes);
-
         let mut slots_builder = ValidatorsBuilder::default();
 
-        for _ in 0..policy::SLOTS {
-            let index = lookup.sample(&mut rng);
-
+        for index in StakingContract::assign_slots(validator_stakes, seed) {
             let chosen_validator =
                 StakingContract::get_validator(accounts_tree, db_txn, validator_addresses[index]).expect("Couldn't find in the accounts tree a validator that was in the active validators list!");
 
@@ -265,6 +259,19 @@ impl StakingContract {
         slots_builder.build()
     }
 
+    /// Randomly distributes the validator slots among validators with the given stakes, like the
+    /// election does. Returns the index of the validator chosen for each slot. To get the same
+    /// result as the election, the stakes have to be ordered by validator address.
+    pub fn assign_slots(stakes: Vec<u64>, seed: &VrfSeed) -> Vec<usize> {
+        let mut rng = seed.rng(VrfUseCase::ValidatorSlotSelection);
+
+        let lookup = AliasMethod::new(stakes);
+
+        (0..policy::SLOTS)
+            .map(|_| lookup.sample(&mut rng))
+            .collect()
+    }
+
     /// Returns a BitSet of slots that lost its rewards in the previous batch.
     pub fn previous_lost_rewards(&self) -> BitSet {
         self.previous_lost_rewards.clone()
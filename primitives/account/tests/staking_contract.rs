@@ -24,6 +24,7 @@ use nimiq_transaction::account::staking_contract::{
 };
 use nimiq_transaction::{SignatureProof, Transaction};
 use nimiq_utils::key_rng::SecureGenerate;
+use nimiq_vrf::VrfSeed;
 
 const CONTRACT_1: &str = "00000000000000000000000000000000000000000000";
 const CONTRACT_2: &str =
@@ -2191,3 +2192,17 @@ fn bls_key_pair(sk: &str) -> BlsKeyPair {
 fn ed25519_key_pair(sk: &str) -> KeyPair {
     KeyPair::from(PrivateKey::deserialize_from_vec(&hex::decode(sk).unwrap()).unwrap())
 }
+
+#[test]
+fn assign_slots_is_deterministic() {
+    let seed = VrfSeed::default();
+
+    let slots = StakingContract::assign_slots(vec![100], &seed);
+    assert_eq!(slots, vec![0; policy::SLOTS as usize]);
+
+    let stakes = vec![300, 100, 200];
+    let slots = StakingContract::assign_slots(stakes.clone(), &seed);
+    assert_eq!(slots.len(), policy::SLOTS as usize);
+    assert!(slots.iter().all(|index| *index < stakes.len()));
+    assert_eq!(slots, StakingContract::assign_slots(stakes, &seed));
+}
@@ -3,7 +3,11 @@ use std::{fmt, io};
 
 use bitflags::bitflags;
 
-use beserial::{Deserialize, ReadBytesExt, Serialize, SerializingError, WriteBytesExt};
+use beserial::schema::{define, Definitions};
+use beserial::{
+    Deserialize, FieldSchema, ReadBytesExt, Schema, Serialize, SerializingError, TypeSchema,
+    VariantSchema, WriteBytesExt,
+};
 use nimiq_database::{FromDatabaseValue, IntoDatabaseValue};
 use nimiq_hash::{Blake2bHash, Blake2sHash, Hash, SerializeContent};
 use nimiq_hash_derive::SerializeContent;
@@ -329,6 +333,37 @@ impl Deserialize for Block {
     }
 }
 
+impl Schema for Block {
+    fn name() -> String {
+        "Block".to_string()
+    }
+
+    fn schema() -> TypeSchema {
+        TypeSchema::Enum {
+            discriminant: "u8",
+            variants: vec![
+                VariantSchema {
+                    name: "Macro",
+                    discriminant: BlockType::Macro as u64,
+                    fields: vec![FieldSchema::new::<MacroBlock>(None)],
+                },
+                VariantSchema {
+                    name: "Micro",
+                    discriminant: BlockType::Micro as u64,
+                    fields: vec![FieldSchema::new::<MicroBlock>(None)],
+                },
+            ],
+        }
+    }
+
+    fn collect(definitions: &mut Definitions) {
+        if define::<Self>(definitions) {
+            MacroBlock::collect(definitions);
+            MicroBlock::collect(definitions);
+        }
+    }
+}
+
 impl fmt::Display for Block {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         match self {
@@ -457,6 +492,14 @@ impl BlockHeader {
         }
     }
 
+    /// Returns the history root of the block.
+    pub fn history_root(&self) -> &Blake2bHash {
+        match self {
+            BlockHeader::Macro(ref header) => &header.history_root,
+            BlockHeader::Micro(ref header) => &header.history_root,
+        }
+    }
+
     /// Returns the next view number, assuming that there was no view change. This will return 0, if
     /// the next block is the first of the batch (i.e. the current one is a macro block), or the
     /// view number of the current block.
@@ -12,7 +12,7 @@ use crate::MicroHeader;
 /// Struct representing a fork proof. A fork proof proves that a given validator created or
 /// continued a fork. For this it is enough to provide two different headers, with the same block
 /// number and view number, signed by the same validator.
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, Schema)]
 pub struct ForkProof {
     /// Header number 1.
     pub header1: MicroHeader,
@@ -16,7 +16,7 @@ use crate::ViewChangeProof;
 /// The struct representing a Micro block.
 /// A Micro block, unlike a Macro block, doesn't contain any inherents (data that can be calculated
 /// by full nodes but for syncing and for nano nodes some needs to be explicitly included).
-#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, Schema)]
 pub struct MicroBlock {
     /// The header, contains some basic information and commitments to the body and the state.
     pub header: MicroHeader,
@@ -28,7 +28,7 @@ pub struct MicroBlock {
 }
 
 /// The struct representing the header of a Micro block.
-#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, SerializeContent)]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, Schema, SerializeContent)]
 pub struct MicroHeader {
     /// The version number of the block. Changing this always results in a hard fork.
     pub version: u16,
@@ -58,7 +58,7 @@ pub struct MicroHeader {
 }
 
 /// The struct representing the justification for a Micro block.
-#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, Schema)]
 pub struct MicroJustification {
     /// The signature of the block producer.
     pub signature: Signature,
@@ -68,7 +68,7 @@ pub struct MicroJustification {
 }
 
 /// The struct representing the body of a Micro block.
-#[derive(Clone, Eq, PartialEq, Serialize, Deserialize, SerializeContent)]
+#[derive(Clone, Eq, PartialEq, Serialize, Deserialize, Schema, SerializeContent)]
 pub struct MicroBody {
     /// A vector containing the fork proofs for this block. It might be empty.
     #[beserial(len_type(u16))]
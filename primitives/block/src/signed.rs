@@ -4,7 +4,7 @@ use beserial::{Deserialize, Serialize, WriteBytesExt};
 use nimiq_bls::{PublicKey, SecretKey, SigHash, Signature};
 use nimiq_hash::{Blake2sHasher, Hasher, SerializeContent};
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, Schema)]
 pub struct SignedMessage<M: Message> {
     // The signed message. Note that the actual message doesn't contain the prefix. This is added
     // only during signing
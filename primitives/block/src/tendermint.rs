@@ -15,7 +15,7 @@ use crate::signed::{
 use crate::{MacroBlock, MacroHeader, MultiSignature};
 
 /// The proposal message sent by the Tendermint leader.
-#[derive(Clone, Debug, Serialize, Deserialize, SerializeContent, PartialEq, Eq)]
+#[derive(Clone, Debug, Serialize, Deserialize, Schema, SerializeContent, PartialEq, Eq)]
 pub struct TendermintProposal {
     /// The header of the macro block, which is effectively the proposal.
     pub value: MacroHeader,
@@ -32,7 +32,7 @@ impl Message for TendermintProposal {
 pub type SignedTendermintProposal = SignedMessage<TendermintProposal>;
 
 /// The proof for a block produced by Tendermint.
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Clone, Debug, Serialize, Deserialize, Schema, PartialEq, Eq)]
 #[cfg_attr(feature = "serde-derive", derive(serde::Serialize, serde::Deserialize))]
 pub struct TendermintProof {
     // The round when the block was completed. This is necessary to verify the signature.
@@ -96,7 +96,9 @@ impl TendermintProof {
 
 /// Internal representation of nimiq_tendermint::Step struct. It needs to be Serializable and must not contain Proposal
 /// thus the additional type.
-#[derive(Serialize, Deserialize, Debug, Clone, Ord, PartialOrd, PartialEq, Eq, Hash, Copy)]
+#[derive(
+    Serialize, Deserialize, Schema, Debug, Clone, Ord, PartialOrd, PartialEq, Eq, Hash, Copy,
+)]
 #[repr(u8)]
 pub enum TendermintStep {
     PreVote = PREFIX_TENDERMINT_PREPARE,
@@ -105,7 +107,7 @@ pub enum TendermintStep {
 }
 
 /// Unique identifier for a single instance of TendermintAggregation
-#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Schema, Debug, Clone, Eq, PartialEq)]
 pub struct TendermintIdentifier {
     /// block_number of the to-be-decided-upon macro block.
     pub block_number: u32,
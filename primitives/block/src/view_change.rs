@@ -14,7 +14,7 @@ use crate::{Message, MultiSignature, SignedMessage, PREFIX_VIEW_CHANGE};
 /// produced in time by its intended producer. It allows the next slot owner to take over and
 /// produce the block. A proof is necessary but it exists as the ViewChangeProof struct.
 #[derive(
-    Clone, Debug, Ord, PartialOrd, Eq, PartialEq, Deserialize, Serialize, SerializeContent,
+    Clone, Debug, Ord, PartialOrd, Eq, PartialEq, Deserialize, Schema, Serialize, SerializeContent,
 )]
 pub struct ViewChange {
     /// The number of the block for which the view change is constructed (i.e. the block number
@@ -83,7 +83,7 @@ impl ViewChanges {
     }
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, Schema)]
 #[cfg_attr(feature = "serde-derive", derive(serde::Serialize, serde::Deserialize))]
 pub struct ViewChangeProof {
     // The aggregated signature of the validator's signatures for the view change.
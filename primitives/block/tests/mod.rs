@@ -1,13 +1,23 @@
 use std::str::FromStr;
 
-use beserial::{Deserialize, Serialize};
-use nimiq_block::{IndividualSignature, MacroBlock, MacroBody, MacroHeader, MultiSignature};
+use std::convert::TryFrom;
+
+use beserial::schema::{skip, Definitions};
+use beserial::{Deserialize, Schema, Serialize};
+use nimiq_block::{
+    Block, IndividualSignature, MacroBlock, MacroBody, MacroHeader, MicroBlock, MicroBody,
+    MicroHeader, MicroJustification, MultiSignature, TendermintProof, ViewChangeProof,
+};
 use nimiq_bls::{CompressedPublicKey, KeyPair};
 use nimiq_collections::bitset::BitSet;
 use nimiq_handel::update::LevelUpdate;
 use nimiq_hash::{Blake2bHasher, Hasher};
-use nimiq_keys::{Address, PublicKey};
+use nimiq_keys::{Address, PublicKey, Signature};
+use nimiq_primitives::account::AccountType;
+use nimiq_primitives::coin::Coin;
+use nimiq_primitives::networks::NetworkId;
 use nimiq_primitives::slots::ValidatorsBuilder;
+use nimiq_transaction::Transaction;
 use nimiq_vrf::VrfSeed;
 
 #[test]
@@ -140,3 +150,99 @@ fn test_serialize_deserialize_with_message() {
     let update = LevelUpdate::new(create_multisig(), None, 2, 3).with_tag(42u64);
     assert_eq!(update.serialized_size(), 108 + 8);
 }
+
+#[test]
+fn schema_matches_the_serialization() {
+    let mut definitions = Definitions::new();
+    Block::collect(&mut definitions);
+
+    let hash = Blake2bHasher::default().digest(&[]);
+    let voting_key = KeyPair::deserialize_from_vec(
+        &hex::decode(
+            "1b9e470e0deb06fe55774bb2cf499b411f55265c10d8d78742078381803451e058c88\
+            391431799462edde4c7872649964137d8e03cd618dd4a25690c56ffd7f42fb7ae8049d29f38d569598b38d4\
+            39f69107cc0b6f4ecd00a250c74409510100",
+        )
+        .unwrap(),
+    )
+    .unwrap()
+    .public_key;
+    let mut builder = ValidatorsBuilder::new();
+    for _ in 0..3 {
+        builder.push(
+            Address::from([1u8; 20]),
+            voting_key.compress(),
+            PublicKey::from([2u8; 32]),
+        );
+    }
+    let mut lost_reward_set = BitSet::new();
+    lost_reward_set.insert(130);
+
+    let macro_block = Block::Macro(MacroBlock {
+        header: MacroHeader {
+            version: 1,
+            block_number: 42,
+            view_number: 1,
+            timestamp: 2,
+            parent_hash: hash.clone(),
+            parent_election_hash: hash.clone(),
+            seed: VrfSeed::default(),
+            extra_data: vec![1, 2, 3],
+            state_root: hash.clone(),
+            body_root: hash.clone(),
+            history_root: hash.clone(),
+        },
+        justification: Some(TendermintProof {
+            round: 1,
+            sig: create_multisig(),
+        }),
+        body: Some(MacroBody {
+            validators: Some(builder.build()),
+            pk_tree_root: Some(vec![4; 96]),
+            lost_reward_set,
+            disabled_set: BitSet::new(),
+        }),
+    });
+
+    let micro_block = Block::Micro(MicroBlock {
+        header: MicroHeader {
+            version: 1,
+            block_number: 43,
+            view_number: 0,
+            timestamp: 3,
+            parent_hash: hash.clone(),
+            seed: VrfSeed::default(),
+            extra_data: vec![],
+            state_root: hash.clone(),
+            body_root: hash.clone(),
+            history_root: hash,
+        },
+        justification: Some(MicroJustification {
+            signature: Signature::from([5u8; 64]),
+            view_change_proof: Some(ViewChangeProof {
+                sig: create_multisig(),
+            }),
+        }),
+        body: Some(MicroBody {
+            fork_proofs: vec![],
+            transactions: vec![Transaction::new_extended(
+                Address::from([1u8; 20]),
+                AccountType::Basic,
+                Address::from([2u8; 20]),
+                AccountType::Staking,
+                Coin::try_from(100u64).unwrap(),
+                Coin::ZERO,
+                vec![6; 10],
+                42,
+                NetworkId::UnitAlbatross,
+            )],
+        }),
+    });
+
+    for block in [macro_block, micro_block] {
+        let data = block.serialize_to_vec();
+        let reader = &mut &data[..];
+        skip(&definitions, &Block::name(), reader).unwrap();
+        assert!(reader.is_empty());
+    }
+}
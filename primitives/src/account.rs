@@ -5,7 +5,9 @@ use thiserror::Error;
 
 use beserial::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, PartialEq, PartialOrd, Eq, Ord, Debug, Serialize, Deserialize, Display)]
+#[derive(
+    Clone, Copy, PartialEq, PartialOrd, Eq, Ord, Debug, Serialize, Deserialize, Schema, Display,
+)]
 #[repr(u8)]
 #[cfg_attr(
     feature = "serde-derive",
@@ -11,7 +11,9 @@ use num_traits::{SaturatingAdd, SaturatingSub};
 use regex::Regex;
 use thiserror::Error;
 
-use beserial::{Deserialize, ReadBytesExt, Serialize, SerializingError, WriteBytesExt};
+use beserial::{
+    Deserialize, ReadBytesExt, Schema, Serialize, SerializingError, TypeSchema, WriteBytesExt,
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Eq, Ord, Default)]
 #[cfg_attr(
@@ -213,6 +215,16 @@ impl Serialize for Coin {
     }
 }
 
+impl Schema for Coin {
+    fn name() -> String {
+        "Coin".to_string()
+    }
+
+    fn schema() -> TypeSchema {
+        u64::schema()
+    }
+}
+
 #[derive(Debug, Error)]
 #[error("Can't parse Coin value: '{0}'")]
 pub struct CoinParseError(String);
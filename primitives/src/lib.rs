@@ -8,6 +8,8 @@ pub mod account;
 pub mod coin;
 #[cfg(feature = "networks")]
 pub mod networks;
+#[cfg(feature = "payment-uri")]
+pub mod payment_uri;
 #[cfg(feature = "policy")]
 pub mod policy;
 #[cfg(feature = "slots")]
@@ -5,7 +5,9 @@ use thiserror::Error;
 
 use beserial::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+#[derive(
+    Serialize, Deserialize, Schema, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash,
+)]
 #[cfg_attr(feature = "serde-derive", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum NetworkId {
@@ -0,0 +1,146 @@
+use std::fmt;
+use std::str::FromStr;
+
+use percent_encoding::{percent_decode_str, utf8_percent_encode, NON_ALPHANUMERIC};
+use thiserror::Error;
+
+use nimiq_keys::{Address, AddressParseError};
+
+use crate::coin::{Coin, CoinParseError};
+
+#[derive(Debug, Error)]
+pub enum PaymentUriError {
+    #[error("Not a Nimiq payment URI")]
+    InvalidScheme,
+    #[error("Invalid address: {0}")]
+    InvalidAddress(#[from] AddressParseError),
+    #[error("Invalid amount: {0}")]
+    InvalidAmount(#[from] CoinParseError),
+    #[error("Invalid expiry: {0}")]
+    InvalidExpiry(String),
+    #[error("Invalid encoding of parameter {0}")]
+    InvalidEncoding(String),
+}
+
+/// A request to pay to an address, encoded as URI, e.g. to be shown as QR code.
+///
+/// The URI has the form `nimiq:<address>?amount=<NIM>&message=<text>&expires=<timestamp>`, where
+/// the address is the user-friendly address without spaces, the amount is given in NIM, the
+/// message is percent encoded and the expiry is a Unix timestamp in seconds. All parameters are
+/// optional. Unknown parameters are ignored when parsing.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PaymentUri {
+    pub recipient: Address,
+    pub amount: Option<Coin>,
+    pub message: Option<String>,
+    /// Unix timestamp in seconds after which the request shouldn't be paid anymore.
+    pub expires: Option<u64>,
+}
+
+impl PaymentUri {
+    pub const SCHEME: &'static str = "nimiq";
+
+    pub fn new(recipient: Address) -> Self {
+        Self {
+            recipient,
+            amount: None,
+            message: None,
+            expires: None,
+        }
+    }
+
+    pub fn with_amount(mut self, amount: Coin) -> Self {
+        self.amount = Some(amount);
+        self
+    }
+
+    pub fn with_message<S: Into<String>>(mut self, message: S) -> Self {
+        self.message = Some(message.into());
+        self
+    }
+
+    pub fn with_expiry(mut self, expires: u64) -> Self {
+        self.expires = Some(expires);
+        self
+    }
+
+    /// Returns whether the request is expired at the given Unix timestamp in seconds.
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires.map_or(false, |expires| now > expires)
+    }
+}
+
+impl fmt::Display for PaymentUri {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}",
+            Self::SCHEME,
+            self.recipient.to_user_friendly_address().replace(' ', "")
+        )?;
+
+        let mut separator = '?';
+        if let Some(amount) = &self.amount {
+            write!(f, "{}amount={}", separator, amount)?;
+            separator = '&';
+        }
+        if let Some(message) = &self.message {
+            write!(
+                f,
+                "{}message={}",
+                separator,
+                utf8_percent_encode(message, NON_ALPHANUMERIC)
+            )?;
+            separator = '&';
+        }
+        if let Some(expires) = &self.expires {
+            write!(f, "{}expires={}", separator, expires)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for PaymentUri {
+    type Err = PaymentUriError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (scheme, rest) = s.split_once(':').ok_or(PaymentUriError::InvalidScheme)?;
+        if !scheme.eq_ignore_ascii_case(Self::SCHEME) {
+            return Err(PaymentUriError::InvalidScheme);
+        }
+
+        // Some applications put slashes between the scheme and the address.
+        let rest = rest.trim_start_matches('/');
+        let (address, query) = rest.split_once('?').unwrap_or((rest, ""));
+        let address = percent_decode_str(address)
+            .decode_utf8()
+            .map_err(|_| PaymentUriError::InvalidEncoding("address".to_string()))?;
+
+        let mut uri = PaymentUri::new(Address::from_user_friendly_address(&address)?);
+
+        for param in query.split('&').filter(|param| !param.is_empty()) {
+            let (key, value) = param.split_once('=').unwrap_or((param, ""));
+            let value = percent_decode_str(&value.replace('+', " "))
+                .decode_utf8()
+                .map_err(|_| PaymentUriError::InvalidEncoding(key.to_string()))?
+                .into_owned();
+
+            match key {
+                "amount" => uri.amount = Some(value.parse()?),
+                "message" => uri.message = Some(value),
+                "expires" => {
+                    uri.expires = Some(
+                        value
+                            .parse()
+                            .map_err(|_| PaymentUriError::InvalidExpiry(value))?,
+                    )
+                }
+                _ => {}
+            }
+        }
+
+        Ok(uri)
+    }
+}
@@ -22,8 +22,9 @@ use std::collections::BTreeMap;
 use std::slice::Iter;
 
 use beserial::{
-    Deserialize, DeserializeWithLength, ReadBytesExt, Serialize, SerializeWithLength,
-    SerializingError, WriteBytesExt,
+    schema::{define, Definitions},
+    Deserialize, DeserializeWithLength, FieldSchema, ReadBytesExt, Schema, Serialize,
+    SerializeWithLength, SerializingError, TypeSchema, WriteBytesExt,
 };
 use nimiq_bls::lazy::LazyPublicKey as LazyBlsPublicKey;
 use nimiq_bls::PublicKey as BlsPublicKey;
@@ -32,7 +33,7 @@ use nimiq_keys::{Address, PublicKey as SchnorrPublicKey};
 use crate::policy::SLOTS;
 
 /// A validator that owns some slots.
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Schema)]
 pub struct Validator {
     pub address: Address,
     pub voting_key: LazyBlsPublicKey,
@@ -186,6 +187,27 @@ impl Deserialize for Validators {
     }
 }
 
+/// Only the validators are serialized, the mapping is rebuilt from them.
+impl Schema for Validators {
+    fn name() -> String {
+        "Validators".to_string()
+    }
+
+    fn schema() -> TypeSchema {
+        TypeSchema::Struct {
+            fields: vec![
+                FieldSchema::new::<Vec<Validator>>(Some("validators")).with_len_type("u16", None)
+            ],
+        }
+    }
+
+    fn collect(definitions: &mut Definitions) {
+        if define::<Self>(definitions) {
+            <Vec<Validator>>::collect(definitions);
+        }
+    }
+}
+
 /// Builder for slot collection. You can push individual slots into it and it'll compress them
 /// into a Validators struct.
 #[derive(Clone, Debug, Default)]
@@ -2,3 +2,5 @@ extern crate nimiq_primitives as primitives;
 
 #[cfg(feature = "coin")]
 mod coin;
+#[cfg(feature = "payment-uri")]
+mod payment_uri;
@@ -0,0 +1,63 @@
+use nimiq_keys::Address;
+use primitives::coin::Coin;
+use primitives::payment_uri::{PaymentUri, PaymentUriError};
+
+fn address() -> Address {
+    Address::from_user_friendly_address("NQ05 563U 530Y XDRT L7GQ M6HE YRNU 20FE 4PNR").unwrap()
+}
+
+#[test]
+fn it_creates_payment_uris() {
+    assert_eq!(
+        PaymentUri::new(address()).to_string(),
+        "nimiq:NQ05563U530YXDRTL7GQM6HEYRNU20FE4PNR"
+    );
+    assert_eq!(
+        PaymentUri::new(address())
+            .with_amount(Coin::from_u64_unchecked(150_000))
+            .with_message("Coffee & cake")
+            .to_string(),
+        "nimiq:NQ05563U530YXDRTL7GQM6HEYRNU20FE4PNR?amount=1.5&message=Coffee%20%26%20cake"
+    );
+    assert_eq!(
+        PaymentUri::new(address())
+            .with_expiry(1650000000)
+            .to_string(),
+        "nimiq:NQ05563U530YXDRTL7GQM6HEYRNU20FE4PNR?expires=1650000000"
+    );
+}
+
+#[test]
+fn it_parses_payment_uris() {
+    let uri: PaymentUri =
+        "nimiq:NQ05563U530YXDRTL7GQM6HEYRNU20FE4PNR?amount=1.5&message=Coffee%20%26+cake&foo=bar"
+            .parse()
+            .unwrap();
+    assert_eq!(uri.recipient, address());
+    assert_eq!(uri.amount, Some(Coin::from_u64_unchecked(150_000)));
+    assert_eq!(uri.message.as_deref(), Some("Coffee & cake"));
+
+    let uri = PaymentUri::new(address())
+        .with_message("Grüße")
+        .with_expiry(1650000000);
+    assert_eq!(uri.to_string().parse::<PaymentUri>().unwrap(), uri);
+    assert!(!uri.is_expired(1650000000));
+    assert!(uri.is_expired(1650000001));
+
+    assert!(matches!(
+        "bitcoin:NQ05563U530YXDRTL7GQM6HEYRNU20FE4PNR".parse::<PaymentUri>(),
+        Err(PaymentUriError::InvalidScheme)
+    ));
+    assert!(matches!(
+        "nimiq:NQ05563U530YXDRTL7GQM6HEYRNU20FE4PNS".parse::<PaymentUri>(),
+        Err(PaymentUriError::InvalidAddress(_))
+    ));
+    assert!(matches!(
+        "nimiq:NQ05563U530YXDRTL7GQM6HEYRNU20FE4PNR?amount=-1".parse::<PaymentUri>(),
+        Err(PaymentUriError::InvalidAmount(_))
+    ));
+    assert!(matches!(
+        "nimiq:NQ05563U530YXDRTL7GQM6HEYRNU20FE4PNR?expires=soon".parse::<PaymentUri>(),
+        Err(PaymentUriError::InvalidExpiry(_))
+    ));
+}
@@ -18,9 +18,10 @@ use std::sync::Arc;
 use bitflags::bitflags;
 use thiserror::Error;
 
+use beserial::schema::{define, Definitions};
 use beserial::{
-    Deserialize, DeserializeWithLength, ReadBytesExt, Serialize, SerializeWithLength,
-    SerializingError, WriteBytesExt,
+    Deserialize, DeserializeWithLength, FieldSchema, ReadBytesExt, Schema, Serialize,
+    SerializeWithLength, SerializingError, TypeSchema, VariantSchema, WriteBytesExt,
 };
 use nimiq_hash::{Blake2bHash, Hash, SerializeContent};
 use nimiq_keys::Address;
@@ -92,6 +93,16 @@ impl Deserialize for TransactionFlags {
     }
 }
 
+impl Schema for TransactionFlags {
+    fn name() -> String {
+        "TransactionFlags".to_string()
+    }
+
+    fn schema() -> TypeSchema {
+        u8::schema()
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SignatureProof {
     pub public_key: PublicKey,
@@ -432,6 +443,64 @@ impl Serialize for Transaction {
     }
 }
 
+impl Schema for Transaction {
+    fn name() -> String {
+        "Transaction".to_string()
+    }
+
+    fn schema() -> TypeSchema {
+        TypeSchema::Enum {
+            discriminant: "u8",
+            variants: vec![
+                VariantSchema {
+                    name: "Basic",
+                    discriminant: TransactionFormat::Basic as u64,
+                    fields: vec![
+                        FieldSchema::new::<PublicKey>(Some("sender_public_key")),
+                        FieldSchema::new::<Address>(Some("recipient")),
+                        FieldSchema::new::<Coin>(Some("value")),
+                        FieldSchema::new::<Coin>(Some("fee")),
+                        FieldSchema::new::<u32>(Some("validity_start_height")),
+                        FieldSchema::new::<NetworkId>(Some("network_id")),
+                        FieldSchema::new::<Signature>(Some("signature")),
+                    ],
+                },
+                VariantSchema {
+                    name: "Extended",
+                    discriminant: TransactionFormat::Extended as u64,
+                    fields: vec![
+                        FieldSchema::new::<Vec<u8>>(Some("data")).with_len_type("u16", None),
+                        FieldSchema::new::<Address>(Some("sender")),
+                        FieldSchema::new::<AccountType>(Some("sender_type")),
+                        FieldSchema::new::<Address>(Some("recipient")),
+                        FieldSchema::new::<AccountType>(Some("recipient_type")),
+                        FieldSchema::new::<Coin>(Some("value")),
+                        FieldSchema::new::<Coin>(Some("fee")),
+                        FieldSchema::new::<u32>(Some("validity_start_height")),
+                        FieldSchema::new::<NetworkId>(Some("network_id")),
+                        FieldSchema::new::<TransactionFlags>(Some("flags")),
+                        FieldSchema::new::<Vec<u8>>(Some("proof")).with_len_type("u16", None),
+                    ],
+                },
+            ],
+        }
+    }
+
+    fn collect(definitions: &mut Definitions) {
+        if define::<Self>(definitions) {
+            PublicKey::collect(definitions);
+            Address::collect(definitions);
+            AccountType::collect(definitions);
+            Coin::collect(definitions);
+            u32::collect(definitions);
+            NetworkId::collect(definitions);
+            TransactionFlags::collect(definitions);
+            Signature::collect(definitions);
+            <Vec<u8>>::collect(definitions);
+        }
+    }
+}
+
 impl Deserialize for Transaction {
     fn deserialize<R: ReadBytesExt>(reader: &mut R) -> Result<Self, SerializingError> {
         let transaction_type: TransactionFormat = Deserialize::deserialize(reader)?;
@@ -1,6 +1,7 @@
 use std::convert::{TryFrom, TryInto};
 
-use beserial::{Deserialize, Serialize, SerializingError};
+use beserial::schema::{skip, Definitions};
+use beserial::{Deserialize, Schema, Serialize, SerializingError};
 use nimiq_keys::Address;
 use nimiq_primitives::account::AccountType;
 use nimiq_primitives::coin::Coin;
@@ -83,3 +84,35 @@ fn it_can_serialize_basic_transaction() {
     assert_eq!(size, t.serialized_size());
     assert_eq!(hex::encode(v2), BASIC_TRANSACTION);
 }
+
+#[test]
+fn schema_matches_the_serialization() {
+    let mut definitions = Definitions::new();
+    Transaction::collect(&mut definitions);
+
+    let extended = Transaction::new_extended(
+        Address::from([1u8; 20]),
+        AccountType::Vesting,
+        Address::from([2u8; 20]),
+        AccountType::HTLC,
+        Coin::try_from(100u64).unwrap(),
+        Coin::try_from(1u64).unwrap(),
+        vec![1, 2, 3],
+        42,
+        NetworkId::Dummy,
+    );
+
+    for data in [
+        hex::decode(BASIC_TRANSACTION).unwrap(),
+        hex::decode(EXTENDED_TRANSACTION).unwrap(),
+        extended.serialize_to_vec(),
+    ] {
+        let reader = &mut &data[..];
+        skip(&definitions, &Transaction::name(), reader).unwrap();
+        assert!(reader.is_empty());
+    }
+
+    let data = hex::decode(EXTENDED_TRANSACTION).unwrap();
+    let reader = &mut &data[..data.len() - 1];
+    assert!(skip(&definitions, &Transaction::name(), reader).is_err());
+}
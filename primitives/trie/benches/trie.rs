@@ -0,0 +1,47 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+
+use nimiq_database::volatile::VolatileEnvironment;
+use nimiq_database::WriteTransaction;
+use nimiq_hash::{Blake2bHasher, HashOutput, Hasher};
+use nimiq_trie::key_nibbles::KeyNibbles;
+use nimiq_trie::trie::MerkleRadixTrie;
+
+const NUM_ACCOUNTS: u64 = 10_000;
+const TOUCHED_ACCOUNTS: u64 = 1_000;
+
+fn key(i: u64) -> KeyNibbles {
+    let hash = Blake2bHasher::default().digest(&i.to_be_bytes());
+    KeyNibbles::from(&hash.as_bytes()[..20])
+}
+
+/// Applies a block touching many accounts to a populated trie and recomputes the root hash.
+fn apply_block(c: &mut Criterion) {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let trie = MerkleRadixTrie::new(env.clone(), "accounts");
+
+    let mut txn = WriteTransaction::new(&env);
+    for i in 0..NUM_ACCOUNTS {
+        trie.put(&mut txn, &key(i), i);
+    }
+    trie.update_root(&mut txn);
+    txn.commit();
+
+    let keys: Vec<KeyNibbles> = (0..TOUCHED_ACCOUNTS).map(key).collect();
+
+    c.bench_function("apply_block", |b| {
+        b.iter_batched(
+            || WriteTransaction::new(&env),
+            |mut txn| {
+                for (i, key) in keys.iter().enumerate() {
+                    trie.put(&mut txn, key, i as u64);
+                }
+                trie.update_root(&mut txn);
+                txn.abort();
+            },
+            BatchSize::PerIteration,
+        )
+    });
+}
+
+criterion_group!(benches, apply_block);
+criterion_main!(benches);
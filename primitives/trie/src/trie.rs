@@ -428,12 +428,24 @@ impl<A: Serialize + Deserialize + Clone> MerkleRadixTrie<A> {
 
     /// Updates the keys for a chain of nodes and marks those nodes as dirty. It assumes that the
     /// path starts at the root node and that each consecutive node is a child of the previous node.
+    ///
+    /// A node is only marked as dirty together with all of its ancestors, and the hashes are only
+    /// recomputed for the whole trie at once. So once we reach a parent that already marks the
+    /// child as dirty, the rest of the path is dirty too and we can stop.
     fn update_keys(&self, txn: &mut WriteTransaction, mut root_path: Vec<TrieNode<A>>) {
         // Get the first node in the path.
         let mut child_node = root_path.pop().expect("Root path must not be empty!");
 
+        let default_hash = Blake2bHash::default();
+
         // Go up the root path until you get to the root.
         while let Some(mut parent_node) = root_path.pop() {
+            if parent_node.get_child_key(child_node.key()).as_ref() == Ok(child_node.key())
+                && parent_node.get_child_hash(child_node.key()) == Ok(&default_hash)
+            {
+                return;
+            }
+
             // Update and store the parent node.
             parent_node = parent_node
                 // Mark this node as dirty by storing the default hash.
@@ -452,15 +464,20 @@ impl<A: Serialize + Deserialize + Clone> MerkleRadixTrie<A> {
             return node.hash();
         }
 
-        // Compute sub hashes if necessary.
+        // Compute sub hashes if necessary. The hashes of clean subtrees are cached in their parent
+        // nodes, so only the dirty paths are visited.
         let default_hash = Blake2bHash::default();
+        let mut changed = false;
         for mut child in node.iter_children_mut() {
             if child.hash == default_hash {
                 // TODO This could be parallelized.
                 child.hash = self.update_hashes(txn, &(key + &child.suffix));
+                changed = true;
             }
         }
-        txn.put_reserve(&self.db, key, &node);
+        if changed {
+            txn.put_reserve(&self.db, key, &node);
+        }
         node.hash()
     }
 
@@ -557,6 +574,45 @@ mod tests {
         assert_eq!(trie.get(&txn, &key_3), None);
     }
 
+    #[test]
+    fn incremental_hashing_works() {
+        let keys: Vec<KeyNibbles> = [
+            "413f22b3e",
+            "413b39931",
+            "413b397fa",
+            "cfb986f5a",
+            "cfb98e0f6",
+        ]
+        .iter()
+        .map(|key| key.parse().unwrap())
+        .collect();
+
+        let env = nimiq_database::volatile::VolatileEnvironment::new(10).unwrap();
+        let trie = MerkleRadixTrie::new(env.clone(), "incremental");
+        let mut txn = WriteTransaction::new(&env);
+
+        // Build the trie in several batches, updating the root in between.
+        for (i, key) in keys.iter().enumerate() {
+            trie.put(&mut txn, key, i as u32);
+        }
+        trie.update_root(&mut txn);
+        trie.put(&mut txn, &keys[1], 10);
+        trie.put(&mut txn, &keys[3], 30);
+        trie.remove(&mut txn, &keys[4]);
+        trie.update_root(&mut txn);
+        trie.put(&mut txn, &keys[1], 11);
+        trie.update_root(&mut txn);
+
+        // Build the same trie at once.
+        let full_trie = MerkleRadixTrie::new(env.clone(), "full");
+        for (key, value) in keys.iter().zip([0u32, 11, 2, 30]) {
+            full_trie.put(&mut txn, key, value);
+        }
+        full_trie.update_root(&mut txn);
+
+        assert_eq!(trie.root_hash(&txn), full_trie.root_hash(&txn));
+    }
+
     #[test]
     fn get_proof_works() {
         let key_1 = "cfb986f5a".parse().unwrap();
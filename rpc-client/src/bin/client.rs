@@ -1,3 +1,8 @@
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+
 use anyhow::{bail, Error};
 use futures::stream::StreamExt;
 use structopt::StructOpt;
@@ -9,7 +14,7 @@ use nimiq_rpc_client::Client;
 use nimiq_rpc_interface::{
     blockchain::BlockchainInterface,
     consensus::ConsensusInterface,
-    types::{BlockNumberOrHash, ValidityStartHeight},
+    types::{BlockNumberOrHash, HistoryEntry, ValidityStartHeight},
     wallet::WalletInterface,
 };
 
@@ -50,6 +55,23 @@ enum Command {
         block: bool,
     },
 
+    /// Export the transaction history of an address, e.g. for accounting purposes.
+    History {
+        address: Address,
+
+        /// Maximum number of transactions to export, counted from the most recent one.
+        #[structopt(short, long)]
+        max: Option<u16>,
+
+        /// Output format, either `csv` or `json`.
+        #[structopt(short, long, default_value = "csv")]
+        format: ExportFormat,
+
+        /// Write the export to this file instead of the standard output.
+        #[structopt(short, long)]
+        output: Option<PathBuf>,
+    },
+
     /// Show wallet accounts and their balances.
     Account(AccountCommand),
 
@@ -58,6 +80,58 @@ enum Command {
     Transaction(TransactionCommand),
 }
 
+#[derive(Debug)]
+enum ExportFormat {
+    Csv,
+    Json,
+}
+
+impl FromStr for ExportFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(ExportFormat::Csv),
+            "json" => Ok(ExportFormat::Json),
+            _ => bail!("Unknown export format: {}", s),
+        }
+    }
+}
+
+/// Formats an amount of Luna as NIM, with a sign for negative amounts.
+fn format_luna(luna: i64) -> String {
+    let coin = Coin::from_u64_unchecked(luna.unsigned_abs());
+    if luna < 0 {
+        format!("-{}", coin)
+    } else {
+        coin.to_string()
+    }
+}
+
+fn write_history_csv<W: Write>(mut out: W, entries: &[HistoryEntry]) -> Result<(), Error> {
+    writeln!(
+        out,
+        "hash,block_number,timestamp,from,to,value,fee,is_reward,balance_change,balance"
+    )?;
+    for entry in entries {
+        writeln!(
+            out,
+            "{},{},{},{},{},{},{},{},{},{}",
+            entry.hash,
+            entry.block_number,
+            entry.timestamp,
+            entry.from.to_user_friendly_address(),
+            entry.to.to_user_friendly_address(),
+            entry.value,
+            entry.fee,
+            entry.is_reward,
+            format_luna(entry.balance_change),
+            entry.balance,
+        )?;
+    }
+    Ok(())
+}
+
 #[derive(Debug, StructOpt)]
 enum AccountCommand {
     List {
@@ -113,6 +187,29 @@ enum TransactionCommand {
         dry: bool,
     },
 
+    /// Pays a payment request URI (`nimiq:<address>?amount=...`) from the wallet `wallet`.
+    Pay {
+        /// Transaction will be sent from this address. An wallet with this address must be unlocked.
+        wallet: Address,
+
+        /// The payment request URI.
+        uri: String,
+
+        /// The amount of NIM to send. Defaults to the amount of the payment request.
+        #[structopt(short = "a", long)]
+        value: Option<Coin>,
+
+        #[structopt(short, long, default_value = "0")]
+        fee: Coin,
+
+        #[structopt(short, long, default_value)]
+        validity_start_height: ValidityStartHeight,
+
+        /// Don't actually send the transaction, but output the transaction as hex string.
+        #[structopt(long = "dry")]
+        dry: bool,
+    },
+
     /// Sends a staking transaction from the address of a given `key_pair` to a given `staker_address`.
     Stake {
         /// The stake will be sent from this wallet.
@@ -207,6 +304,28 @@ impl Command {
                 }
             }
 
+            Command::History {
+                address,
+                max,
+                format,
+                output,
+            } => {
+                let entries = client
+                    .blockchain
+                    .get_transaction_history(address, max)
+                    .await?;
+
+                let out: Box<dyn Write> = match output {
+                    Some(path) => Box::new(File::create(path)?),
+                    None => Box::new(io::stdout()),
+                };
+
+                match format {
+                    ExportFormat::Csv => write_history_csv(out, &entries)?,
+                    ExportFormat::Json => serde_json::to_writer_pretty(out, &entries)?,
+                }
+            }
+
             Command::Account(command) => {
                 match command {
                     AccountCommand::List { short } => {
@@ -291,6 +410,41 @@ impl Command {
                     }
                 }
 
+                TransactionCommand::Pay {
+                    wallet,
+                    uri,
+                    value,
+                    fee,
+                    validity_start_height,
+                    dry,
+                } => {
+                    if dry {
+                        let tx = client
+                            .consensus
+                            .create_payment_request_transaction(
+                                wallet,
+                                uri,
+                                value,
+                                fee,
+                                validity_start_height,
+                            )
+                            .await?;
+                        println!("{}", tx);
+                    } else {
+                        let txid = client
+                            .consensus
+                            .send_payment_request_transaction(
+                                wallet,
+                                uri,
+                                value,
+                                fee,
+                                validity_start_height,
+                            )
+                            .await?;
+                        println!("{}", txid);
+                    }
+                }
+
                 TransactionCommand::Stake {
                     wallet,
                     staker_address,
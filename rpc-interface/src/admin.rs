@@ -0,0 +1,21 @@
+use async_trait::async_trait;
+
+use crate::types::{ApiKeyUsage, LockStats, ResourceUsage, RuntimeStats, TaskStats};
+
+#[nimiq_jsonrpc_derive::proxy(name = "AdminProxy", rename_all = "camelCase")]
+#[async_trait]
+pub trait AdminInterface {
+    type Error;
+
+    async fn get_api_key_usage(&mut self) -> Result<Vec<ApiKeyUsage>, Self::Error>;
+
+    async fn get_resource_usage(&mut self) -> Result<ResourceUsage, Self::Error>;
+
+    async fn dump_heap_profile(&mut self) -> Result<String, Self::Error>;
+
+    async fn get_task_stats(&mut self) -> Result<Vec<TaskStats>, Self::Error>;
+
+    async fn get_runtime_stats(&mut self) -> Result<Vec<RuntimeStats>, Self::Error>;
+
+    async fn get_lock_stats(&mut self) -> Result<Vec<LockStats>, Self::Error>;
+}
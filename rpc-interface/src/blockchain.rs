@@ -6,9 +6,13 @@ use futures::stream::BoxStream;
 use nimiq_hash::Blake2bHash;
 use nimiq_keys::Address;
 use nimiq_primitives::coin::Coin;
+use nimiq_vrf::VrfSeed;
 
 use crate::types::{
-    Account, Block, Inherent, ParkedSet, SlashedSlots, Slot, Staker, Transaction, Validator,
+    Account, Block, BlockJustification, ChainEvent, ChainSchedule, DatabaseMetrics, EpochStats,
+    ExtendedTransaction, HistoryCursor, HistoryEntry, Inherent, ParkedSet, PublicKeyCacheStats,
+    SimulatedSlots, SlashedSlots, Slot, SlotAssignment, Staker, Transaction, TransactionPage,
+    Validator,
 };
 
 #[nimiq_jsonrpc_derive::proxy(name = "BlockchainProxy", rename_all = "camelCase")]
@@ -39,12 +43,23 @@ pub trait BlockchainInterface {
         include_transactions: Option<bool>,
     ) -> Result<Block, Self::Error>;
 
+    async fn get_block_justification(
+        &mut self,
+        hash: Blake2bHash,
+    ) -> Result<BlockJustification, Self::Error>;
+
     async fn get_slot_at(
         &mut self,
         block_number: u32,
         view_number: Option<u32>,
     ) -> Result<Slot, Self::Error>;
 
+    async fn get_slot_assignment_at(
+        &mut self,
+        block_number: u32,
+        view_number: Option<u32>,
+    ) -> Result<SlotAssignment, Self::Error>;
+
     async fn get_transactions_by_block_number(
         &mut self,
         block_number: u32,
@@ -76,7 +91,14 @@ pub trait BlockchainInterface {
         &mut self,
         address: Address,
         max: Option<u16>,
-    ) -> Result<Vec<Transaction>, Self::Error>;
+        cursor: Option<HistoryCursor>,
+    ) -> Result<TransactionPage, Self::Error>;
+
+    async fn get_transaction_history(
+        &mut self,
+        address: Address,
+        max: Option<u16>,
+    ) -> Result<Vec<HistoryEntry>, Self::Error>;
 
     async fn get_account_by_address(&mut self, address: Address) -> Result<Account, Self::Error>;
 
@@ -96,6 +118,37 @@ pub trait BlockchainInterface {
 
     async fn get_staker_by_address(&mut self, address: Address) -> Result<Staker, Self::Error>;
 
+    async fn get_epoch_stats(&mut self, epoch: u32) -> Result<EpochStats, Self::Error>;
+
+    async fn get_chain_schedule(&mut self) -> Result<ChainSchedule, Self::Error>;
+
+    async fn simulate_election(
+        &mut self,
+        seed: Option<VrfSeed>,
+        validators: HashMap<Address, Coin>,
+    ) -> Result<Vec<SimulatedSlots>, Self::Error>;
+
+    async fn get_payment_receipt(&mut self, hash: Blake2bHash) -> Result<String, Self::Error>;
+
+    async fn verify_payment_receipt(
+        &mut self,
+        hash: Blake2bHash,
+        receipt: String,
+    ) -> Result<Transaction, Self::Error>;
+
+    async fn get_database_metrics(&mut self) -> Result<DatabaseMetrics, Self::Error>;
+
+    async fn get_public_key_cache_stats(&mut self) -> Result<PublicKeyCacheStats, Self::Error>;
+
     #[stream]
     async fn head_subscribe(&mut self) -> Result<BoxStream<'static, Blake2bHash>, Self::Error>;
+
+    #[stream]
+    async fn chain_subscribe(&mut self) -> Result<BoxStream<'static, ChainEvent>, Self::Error>;
+
+    #[stream]
+    async fn follow_history(
+        &mut self,
+        from_epoch: u32,
+    ) -> Result<BoxStream<'static, ExtendedTransaction>, Self::Error>;
 }
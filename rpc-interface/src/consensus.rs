@@ -1,11 +1,16 @@
 use async_trait::async_trait;
 
 use nimiq_hash::Blake2bHash;
-use nimiq_keys::Address;
+use nimiq_keys::{Address, PublicKey, Signature};
 use nimiq_primitives::coin::Coin;
 use nimiq_transaction::account::htlc_contract::{AnyHash, HashAlgorithm};
 
-use crate::types::{Transaction, ValidityStartHeight};
+use futures::stream::BoxStream;
+
+use crate::types::{
+    ColdStakingTransaction, ConsensusState, Transaction, ValidityStartHeight,
+    WalletTransactionEvent,
+};
 
 #[nimiq_jsonrpc_derive::proxy(name = "ConsensusProxy", rename_all = "camelCase")]
 #[async_trait]
@@ -14,6 +19,13 @@ pub trait ConsensusInterface {
 
     async fn is_consensus_established(&mut self) -> Result<bool, Self::Error>;
 
+    async fn get_consensus_state(&mut self) -> Result<ConsensusState, Self::Error>;
+
+    #[stream]
+    async fn consensus_subscribe(
+        &mut self,
+    ) -> Result<BoxStream<'static, ConsensusState>, Self::Error>;
+
     async fn get_raw_transaction_info(
         &mut self,
         raw_tx: String,
@@ -21,6 +33,11 @@ pub trait ConsensusInterface {
 
     async fn send_raw_transaction(&mut self, raw_tx: String) -> Result<Blake2bHash, Self::Error>;
 
+    #[stream]
+    async fn wallet_transactions_subscribe(
+        &mut self,
+    ) -> Result<BoxStream<'static, WalletTransactionEvent>, Self::Error>;
+
     async fn create_basic_transaction(
         &mut self,
         wallet: Address,
@@ -59,6 +76,24 @@ pub trait ConsensusInterface {
         validity_start_height: ValidityStartHeight,
     ) -> Result<Blake2bHash, Self::Error>;
 
+    async fn create_payment_request_transaction(
+        &mut self,
+        wallet: Address,
+        uri: String,
+        value: Option<Coin>,
+        fee: Coin,
+        validity_start_height: ValidityStartHeight,
+    ) -> Result<String, Self::Error>;
+
+    async fn send_payment_request_transaction(
+        &mut self,
+        wallet: Address,
+        uri: String,
+        value: Option<Coin>,
+        fee: Coin,
+        validity_start_height: ValidityStartHeight,
+    ) -> Result<Blake2bHash, Self::Error>;
+
     async fn create_new_vesting_transaction(
         &mut self,
         wallet: Address,
@@ -285,6 +320,46 @@ pub trait ConsensusInterface {
         validity_start_height: ValidityStartHeight,
     ) -> Result<Blake2bHash, Self::Error>;
 
+    async fn create_cold_new_staker_transaction(
+        &mut self,
+        sender_wallet: Address,
+        staker_address: Address,
+        delegation: Option<Address>,
+        value: Coin,
+        fee: Coin,
+        validity_start_height: ValidityStartHeight,
+    ) -> Result<ColdStakingTransaction, Self::Error>;
+
+    async fn create_cold_update_transaction(
+        &mut self,
+        sender_wallet: Address,
+        staker_address: Address,
+        new_delegation: Option<Address>,
+        fee: Coin,
+        validity_start_height: ValidityStartHeight,
+    ) -> Result<ColdStakingTransaction, Self::Error>;
+
+    async fn create_cold_unstake_transaction(
+        &mut self,
+        staker_address: Address,
+        recipient: Address,
+        value: Coin,
+        fee: Coin,
+        validity_start_height: ValidityStartHeight,
+    ) -> Result<ColdStakingTransaction, Self::Error>;
+
+    async fn send_cold_staking_transaction(
+        &mut self,
+        id: Blake2bHash,
+        staker_public_key: PublicKey,
+        staker_signature: Signature,
+    ) -> Result<Blake2bHash, Self::Error>;
+
+    async fn get_cold_staking_transactions(
+        &mut self,
+        staker_address: Option<Address>,
+    ) -> Result<Vec<ColdStakingTransaction>, Self::Error>;
+
     async fn create_new_validator_transaction(
         &mut self,
         sender_wallet: Address,
@@ -1,8 +1,10 @@
+pub mod admin;
 pub mod blockchain;
 pub mod consensus;
 pub mod error;
 pub mod mempool;
 pub mod network;
+pub mod rpc_types;
 mod serde_helpers;
 pub mod types;
 pub mod validator;
@@ -1,6 +1,6 @@
 use async_trait::async_trait;
 
-use crate::types::{HashOrTx, MempoolInfo, Transaction};
+use crate::types::{HashOrTx, InclusionStats, MempoolInfo, Transaction};
 use nimiq_hash::Blake2bHash;
 
 #[nimiq_jsonrpc_derive::proxy(name = "MempoolProxy", rename_all = "camelCase")]
@@ -24,4 +24,12 @@ pub trait MempoolInterface {
     async fn mempool(&mut self) -> Result<MempoolInfo, Self::Error>;
 
     async fn get_min_fee_per_byte(&mut self) -> Result<f64, Self::Error>;
+
+    async fn get_min_relay_fee_per_byte(&mut self) -> Result<f64, Self::Error>;
+
+    async fn set_min_relay_fee_per_byte(&mut self, fee_per_byte: f64) -> Result<(), Self::Error>;
+
+    async fn get_inclusion_list(&mut self) -> Result<Vec<Blake2bHash>, Self::Error>;
+
+    async fn get_inclusion_stats(&mut self) -> Result<Vec<InclusionStats>, Self::Error>;
 }
@@ -1,5 +1,9 @@
 use async_trait::async_trait;
 
+use nimiq_keys::Address;
+
+use crate::types::{GossipTopicStats, NetworkRegistry, PeerStats, ValidatorResolution};
+
 #[nimiq_jsonrpc_derive::proxy(name = "NetworkProxy", rename_all = "camelCase")]
 #[async_trait]
 pub trait NetworkInterface {
@@ -10,4 +14,15 @@ pub trait NetworkInterface {
     async fn get_peer_count(&mut self) -> Result<usize, Self::Error>;
 
     async fn get_peer_list(&mut self) -> Result<Vec<String>, Self::Error>;
+
+    async fn get_peer_stats(&mut self, peer_id: String) -> Result<PeerStats, Self::Error>;
+
+    async fn resolve_validator(
+        &mut self,
+        validator_address: Address,
+    ) -> Result<ValidatorResolution, Self::Error>;
+
+    async fn get_network_registry(&mut self) -> Result<NetworkRegistry, Self::Error>;
+
+    async fn get_gossip_stats(&mut self) -> Result<Vec<GossipTopicStats>, Self::Error>;
 }
@@ -0,0 +1,494 @@
+//! Structured JSON representations of the data and proofs of transactions and of the data of
+//! inherents.
+//!
+//! The data and proof of a transaction are serialized with beserial, their layout depends on the
+//! sender and recipient of the transaction. The same holds for the data of the inherents of a
+//! block. The types in this module decode them, so clients don't have to reimplement the binary
+//! formats. Keys, signatures and hashes are hex-encoded, field names
+//! are camelCase and enums are tagged with a `type` field.
+
+use serde::{Deserialize, Serialize};
+
+use beserial::{Deserialize as BeDeserialize, SerializingError};
+use nimiq_account::InherentType;
+use nimiq_bls::{CompressedPublicKey, CompressedSignature};
+use nimiq_hash::Blake2bHash;
+use nimiq_keys::{Address, PublicKey, Signature};
+use nimiq_primitives::account::AccountType;
+use nimiq_primitives::coin::Coin;
+use nimiq_primitives::slots::SlashedSlot;
+use nimiq_transaction::account::htlc_contract::{AnyHash, HashAlgorithm, ProofType};
+use nimiq_transaction::account::staking_contract::{
+    IncomingStakingTransactionData, OutgoingStakingTransactionProof,
+};
+use nimiq_transaction::account::{htlc_contract, vesting_contract};
+use nimiq_transaction::TransactionFlags;
+
+/// A signature over a transaction. For multisig wallets, `signer` is the address of the wallet
+/// and `publicKey` the aggregated key of the signers.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignatureProof {
+    pub signer: Address,
+    pub public_key: PublicKey,
+    pub signature: Signature,
+}
+
+impl From<nimiq_transaction::SignatureProof> for SignatureProof {
+    fn from(proof: nimiq_transaction::SignatureProof) -> Self {
+        SignatureProof {
+            signer: proof.compute_signer(),
+            public_key: proof.public_key,
+            signature: proof.signature,
+        }
+    }
+}
+
+/// The decoded proof of a transaction.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum TransactionProof {
+    /// A transaction sent from a basic account or a vesting contract.
+    Signature(SignatureProof),
+
+    /// A transfer from an HTLC to its recipient by revealing the pre-image.
+    #[serde(rename_all = "camelCase")]
+    HtlcRegularTransfer {
+        hash_algorithm: HashAlgorithm,
+        hash_depth: u8,
+        hash_root: AnyHash,
+        pre_image: AnyHash,
+        signature: SignatureProof,
+    },
+
+    /// A transfer from an HTLC signed by both its recipient and its sender.
+    #[serde(rename_all = "camelCase")]
+    HtlcEarlyResolve {
+        htlc_recipient: SignatureProof,
+        htlc_sender: SignatureProof,
+    },
+
+    /// A transfer from an HTLC back to its sender after the timeout.
+    #[serde(rename_all = "camelCase")]
+    HtlcTimeoutResolve { signature: SignatureProof },
+
+    /// The deposit of a deleted validator being paid out.
+    #[serde(rename_all = "camelCase")]
+    DeleteValidator { signature: SignatureProof },
+
+    /// Stake being withdrawn from the staking contract.
+    #[serde(rename_all = "camelCase")]
+    Unstake { signature: SignatureProof },
+}
+
+impl TransactionProof {
+    /// Decodes the proof of the transaction according to the type of its sender. Returns `None` if
+    /// the proof is malformed.
+    pub fn decode(transaction: &nimiq_transaction::Transaction) -> Option<Self> {
+        match transaction.sender_type {
+            AccountType::Basic | AccountType::Vesting => {
+                nimiq_transaction::SignatureProof::deserialize_from_vec(&transaction.proof)
+                    .ok()
+                    .map(|proof| TransactionProof::Signature(proof.into()))
+            }
+            AccountType::HTLC => Self::decode_htlc(&transaction.proof).ok(),
+            AccountType::Staking => match OutgoingStakingTransactionProof::parse(transaction) {
+                Ok(OutgoingStakingTransactionProof::DeleteValidator { proof }) => {
+                    Some(TransactionProof::DeleteValidator {
+                        signature: proof.into(),
+                    })
+                }
+                Ok(OutgoingStakingTransactionProof::Unstake { proof }) => {
+                    Some(TransactionProof::Unstake {
+                        signature: proof.into(),
+                    })
+                }
+                Err(_) => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn decode_htlc(proof: &[u8]) -> Result<Self, SerializingError> {
+        let reader = &mut &proof[..];
+        let proof_type: ProofType = BeDeserialize::deserialize(reader)?;
+
+        let proof = match proof_type {
+            ProofType::RegularTransfer => {
+                let hash_algorithm = BeDeserialize::deserialize(reader)?;
+                let hash_depth = BeDeserialize::deserialize(reader)?;
+                let hash_root = BeDeserialize::deserialize(reader)?;
+                let pre_image = BeDeserialize::deserialize(reader)?;
+                let signature: nimiq_transaction::SignatureProof =
+                    BeDeserialize::deserialize(reader)?;
+                TransactionProof::HtlcRegularTransfer {
+                    hash_algorithm,
+                    hash_depth,
+                    hash_root,
+                    pre_image,
+                    signature: signature.into(),
+                }
+            }
+            ProofType::EarlyResolve => {
+                let htlc_recipient: nimiq_transaction::SignatureProof =
+                    BeDeserialize::deserialize(reader)?;
+                let htlc_sender: nimiq_transaction::SignatureProof =
+                    BeDeserialize::deserialize(reader)?;
+                TransactionProof::HtlcEarlyResolve {
+                    htlc_recipient: htlc_recipient.into(),
+                    htlc_sender: htlc_sender.into(),
+                }
+            }
+            ProofType::TimeoutResolve => {
+                let signature: nimiq_transaction::SignatureProof =
+                    BeDeserialize::deserialize(reader)?;
+                TransactionProof::HtlcTimeoutResolve {
+                    signature: signature.into(),
+                }
+            }
+        };
+
+        Ok(proof)
+    }
+}
+
+/// The decoded data of a transaction that creates a contract or interacts with the staking
+/// contract. The data of other transactions is arbitrary and isn't decoded.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum TransactionData {
+    #[serde(rename_all = "camelCase")]
+    VestingCreation {
+        owner: Address,
+        start_time: u64,
+        time_step: u64,
+        step_amount: Coin,
+        total_amount: Coin,
+    },
+
+    #[serde(rename_all = "camelCase")]
+    HtlcCreation {
+        sender: Address,
+        recipient: Address,
+        hash_algorithm: HashAlgorithm,
+        hash_root: AnyHash,
+        hash_count: u8,
+        timeout: u64,
+    },
+
+    #[serde(rename_all = "camelCase")]
+    CreateValidator {
+        signing_key: PublicKey,
+        voting_key: CompressedPublicKey,
+        reward_address: Address,
+        signal_data: Option<Blake2bHash>,
+        proof_of_knowledge: CompressedSignature,
+        /// Signed with the cold key of the validator, the signer is the validator address.
+        signature: SignatureProof,
+    },
+
+    #[serde(rename_all = "camelCase")]
+    UpdateValidator {
+        new_signing_key: Option<PublicKey>,
+        new_voting_key: Option<CompressedPublicKey>,
+        new_reward_address: Option<Address>,
+        /// `null` if the signal data isn't changed. Signal data can also be removed, which is
+        /// encoded the same way.
+        new_signal_data: Option<Option<Blake2bHash>>,
+        new_proof_of_knowledge: Option<CompressedSignature>,
+        signature: SignatureProof,
+    },
+
+    #[serde(rename_all = "camelCase")]
+    InactivateValidator {
+        validator_address: Address,
+        signature: SignatureProof,
+    },
+
+    #[serde(rename_all = "camelCase")]
+    ReactivateValidator {
+        validator_address: Address,
+        signature: SignatureProof,
+    },
+
+    #[serde(rename_all = "camelCase")]
+    UnparkValidator {
+        validator_address: Address,
+        signature: SignatureProof,
+    },
+
+    #[serde(rename_all = "camelCase")]
+    CreateStaker {
+        delegation: Option<Address>,
+        signature: SignatureProof,
+    },
+
+    #[serde(rename_all = "camelCase")]
+    Stake { staker_address: Address },
+
+    #[serde(rename_all = "camelCase")]
+    UpdateStaker {
+        new_delegation: Option<Address>,
+        signature: SignatureProof,
+    },
+}
+
+impl TransactionData {
+    /// Decodes the data of the transaction according to the type of its recipient. Returns `None`
+    /// if the data is arbitrary or malformed.
+    pub fn decode(transaction: &nimiq_transaction::Transaction) -> Option<Self> {
+        let contract_creation = transaction
+            .flags
+            .contains(TransactionFlags::CONTRACT_CREATION);
+
+        match transaction.recipient_type {
+            AccountType::Vesting if contract_creation => {
+                vesting_contract::CreationTransactionData::parse(transaction)
+                    .ok()
+                    .map(|data| TransactionData::VestingCreation {
+                        owner: data.owner,
+                        start_time: data.start_time,
+                        time_step: data.time_step,
+                        step_amount: data.step_amount,
+                        total_amount: data.total_amount,
+                    })
+            }
+            AccountType::HTLC if contract_creation => {
+                htlc_contract::CreationTransactionData::parse(transaction)
+                    .ok()
+                    .map(|data| TransactionData::HtlcCreation {
+                        sender: data.sender,
+                        recipient: data.recipient,
+                        hash_algorithm: data.hash_algorithm,
+                        hash_root: data.hash_root,
+                        hash_count: data.hash_count,
+                        timeout: data.timeout,
+                    })
+            }
+            AccountType::Staking => IncomingStakingTransactionData::parse(transaction)
+                .ok()
+                .map(TransactionData::from),
+            _ => None,
+        }
+    }
+}
+
+impl From<IncomingStakingTransactionData> for TransactionData {
+    fn from(data: IncomingStakingTransactionData) -> Self {
+        match data {
+            IncomingStakingTransactionData::CreateValidator {
+                signing_key,
+                voting_key,
+                reward_address,
+                signal_data,
+                proof_of_knowledge,
+                proof,
+            } => TransactionData::CreateValidator {
+                signing_key,
+                voting_key,
+                reward_address,
+                signal_data,
+                proof_of_knowledge,
+                signature: proof.into(),
+            },
+            IncomingStakingTransactionData::UpdateValidator {
+                new_signing_key,
+                new_voting_key,
+                new_reward_address,
+                new_signal_data,
+                new_proof_of_knowledge,
+                proof,
+            } => TransactionData::UpdateValidator {
+                new_signing_key,
+                new_voting_key,
+                new_reward_address,
+                new_signal_data,
+                new_proof_of_knowledge,
+                signature: proof.into(),
+            },
+            IncomingStakingTransactionData::InactivateValidator {
+                validator_address,
+                proof,
+            } => TransactionData::InactivateValidator {
+                validator_address,
+                signature: proof.into(),
+            },
+            IncomingStakingTransactionData::ReactivateValidator {
+                validator_address,
+                proof,
+            } => TransactionData::ReactivateValidator {
+                validator_address,
+                signature: proof.into(),
+            },
+            IncomingStakingTransactionData::UnparkValidator {
+                validator_address,
+                proof,
+            } => TransactionData::UnparkValidator {
+                validator_address,
+                signature: proof.into(),
+            },
+            IncomingStakingTransactionData::CreateStaker { delegation, proof } => {
+                TransactionData::CreateStaker {
+                    delegation,
+                    signature: proof.into(),
+                }
+            }
+            IncomingStakingTransactionData::Stake { staker_address } => {
+                TransactionData::Stake { staker_address }
+            }
+            IncomingStakingTransactionData::UpdateStaker {
+                new_delegation,
+                proof,
+            } => TransactionData::UpdateStaker {
+                new_delegation,
+                signature: proof.into(),
+            },
+        }
+    }
+}
+
+/// The decoded data of an inherent. Only slash inherents carry data.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum InherentData {
+    /// A slot whose validator produced a fork or didn't produce its block in time.
+    #[serde(rename_all = "camelCase")]
+    Slash {
+        slot: u16,
+        validator_address: Address,
+        /// The block at which the slashable action occurred.
+        event_block: u32,
+    },
+}
+
+impl InherentData {
+    /// Decodes the data of the inherent according to its type. Returns `None` if the inherent
+    /// carries no data or if it is malformed.
+    pub fn decode(inherent: &nimiq_account::Inherent) -> Option<Self> {
+        match inherent.ty {
+            InherentType::Slash => {
+                SlashedSlot::deserialize_from_vec(&inherent.data)
+                    .ok()
+                    .map(|slot| InherentData::Slash {
+                        slot: slot.slot,
+                        validator_address: slot.validator_address,
+                        event_block: slot.event_block,
+                    })
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use beserial::Serialize as BeSerialize;
+    use nimiq_keys::{KeyPair, PrivateKey};
+    use nimiq_primitives::networks::NetworkId;
+    use nimiq_transaction::Transaction;
+
+    use super::*;
+
+    const KEY: &str = "9d5bd02379e7e45cf515c788048f5cf3c454ffabd3e83bd1d7667716c325c3c0";
+
+    fn key_pair() -> KeyPair {
+        KeyPair::from(PrivateKey::from_str(KEY).unwrap())
+    }
+
+    fn signature_proof(key_pair: &KeyPair, tx: &Transaction) -> nimiq_transaction::SignatureProof {
+        let signature = key_pair.sign(&tx.serialize_content());
+        nimiq_transaction::SignatureProof::from(key_pair.public, signature)
+    }
+
+    #[test]
+    fn it_decodes_signature_proofs() {
+        let key_pair = key_pair();
+        let mut tx = Transaction::new_basic(
+            Address::from(&key_pair),
+            Address::burn_address(),
+            Coin::from_u64_unchecked(10),
+            Coin::ZERO,
+            1,
+            NetworkId::UnitAlbatross,
+        );
+        tx.proof = signature_proof(&key_pair, &tx).serialize_to_vec();
+
+        match TransactionProof::decode(&tx) {
+            Some(TransactionProof::Signature(proof)) => {
+                assert_eq!(proof.signer, Address::from(&key_pair));
+                assert_eq!(proof.public_key, key_pair.public);
+            }
+            proof => panic!("Unexpected proof {:?}", proof),
+        }
+        // Basic transactions carry arbitrary data.
+        assert!(TransactionData::decode(&tx).is_none());
+
+        // A malformed proof isn't decoded.
+        tx.proof.truncate(10);
+        assert!(TransactionProof::decode(&tx).is_none());
+    }
+
+    #[test]
+    fn it_decodes_htlc_proofs() {
+        let key_pair = key_pair();
+        let mut tx = Transaction::new_extended(
+            Address::burn_address(),
+            AccountType::HTLC,
+            Address::from(&key_pair),
+            AccountType::Basic,
+            Coin::from_u64_unchecked(10),
+            Coin::ZERO,
+            vec![],
+            1,
+            NetworkId::UnitAlbatross,
+        );
+        let mut proof = ProofType::TimeoutResolve.serialize_to_vec();
+        signature_proof(&key_pair, &tx)
+            .serialize(&mut proof)
+            .unwrap();
+        tx.proof = proof;
+
+        match TransactionProof::decode(&tx) {
+            Some(TransactionProof::HtlcTimeoutResolve { signature }) => {
+                assert_eq!(signature.signer, Address::from(&key_pair));
+            }
+            proof => panic!("Unexpected proof {:?}", proof),
+        }
+    }
+
+    #[test]
+    fn it_decodes_slash_inherents() {
+        let slot = SlashedSlot {
+            slot: 42,
+            validator_address: Address::burn_address(),
+            event_block: 1000,
+        };
+        let inherent = nimiq_account::Inherent {
+            ty: InherentType::Slash,
+            target: Address::burn_address(),
+            value: Coin::ZERO,
+            data: slot.serialize_to_vec(),
+        };
+
+        let json = serde_json::to_value(InherentData::decode(&inherent).unwrap()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "type": "slash",
+                "slot": 42,
+                "validatorAddress": Address::burn_address(),
+                "eventBlock": 1000,
+            })
+        );
+
+        // Other inherents carry no data.
+        let inherent = nimiq_account::Inherent {
+            ty: InherentType::Reward,
+            data: vec![],
+            ..inherent
+        };
+        assert!(InherentData::decode(&inherent).is_none());
+    }
+}
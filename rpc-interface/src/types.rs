@@ -24,6 +24,7 @@ use nimiq_transaction::account::htlc_contract::AnyHash;
 use nimiq_vrf::VrfSeed;
 
 use crate::error::Error;
+use crate::rpc_types::{InherentData, TransactionData, TransactionProof};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -358,6 +359,108 @@ impl From<nimiq_block::MicroJustification> for MicroJustification {
     }
 }
 
+/// The justification of a block, with the signer bitmaps mapped to the validators that own the
+/// signing slots.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum BlockJustification {
+    #[serde(rename_all = "camelCase")]
+    Macro {
+        round: u32,
+        sig: MultiSignature,
+        signers: Vec<SignerSlots>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Micro {
+        producer: Slot,
+        signature: Signature,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        view_change_proof: Option<ViewChangeJustification>,
+    },
+}
+
+impl BlockJustification {
+    /// Decodes the justification of the given block. Returns `None` if the block has no
+    /// justification or if the validators of its epoch are unknown.
+    pub fn from_block(blockchain: &Blockchain, block: nimiq_block::Block) -> Option<Self> {
+        let block_number = block.block_number();
+
+        match block {
+            nimiq_block::Block::Macro(macro_block) => {
+                let proof = macro_block.justification?;
+                let validators =
+                    blockchain.get_validators_for_epoch(policy::epoch_at(block_number), None)?;
+
+                Some(BlockJustification::Macro {
+                    round: proof.round,
+                    signers: SignerSlots::from_signers(&proof.sig.signers, &validators),
+                    sig: proof.sig,
+                })
+            }
+            nimiq_block::Block::Micro(micro_block) => {
+                let justification = micro_block.justification?;
+
+                let view_change_proof = match justification.view_change_proof {
+                    Some(proof) => {
+                        let validators = blockchain
+                            .get_validators_for_epoch(policy::epoch_at(block_number), None)?;
+                        Some(ViewChangeJustification {
+                            signers: SignerSlots::from_signers(&proof.sig.signers, &validators),
+                            sig: proof.sig,
+                        })
+                    }
+                    None => None,
+                };
+
+                Some(BlockJustification::Micro {
+                    producer: Slot::from(blockchain, block_number, micro_block.header.view_number),
+                    signature: justification.signature,
+                    view_change_proof,
+                })
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ViewChangeJustification {
+    pub sig: MultiSignature,
+    pub signers: Vec<SignerSlots>,
+}
+
+/// The slots of a single validator that contributed to an aggregate signature.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignerSlots {
+    pub validator: Address,
+    pub slots: Vec<u16>,
+}
+
+impl SignerSlots {
+    /// Groups the slots set in the given signer bitmap by the validators that own them.
+    pub fn from_signers(signers: &BitSet, validators: &Validators) -> Vec<SignerSlots> {
+        let mut signer_slots: Vec<SignerSlots> = vec![];
+
+        // The slots of a validator are contiguous and the bitset is iterated in ascending order,
+        // so it is enough to compare against the last validator.
+        for slot in signers.iter().filter(|slot| *slot < policy::SLOTS as usize) {
+            let slot = slot as u16;
+            let validator = validators.get_validator_by_slot_number(slot);
+
+            match signer_slots.last_mut() {
+                Some(last) if last.validator == validator.address => last.slots.push(slot),
+                _ => signer_slots.push(SignerSlots {
+                    validator: validator.address.clone(),
+                    slots: vec![slot],
+                }),
+            }
+        }
+
+        signer_slots
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Slot {
@@ -380,6 +483,45 @@ impl Slot {
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SlotAssignment {
+    pub block_number: u32,
+    pub view_number: u32,
+    pub slot_number: u16,
+    pub validator: Address,
+    pub public_key: CompressedPublicKey,
+    pub signing_key: PublicKey,
+    /// The seed of the preceding block, its entropy is the input to the slot selection.
+    pub prev_seed: VrfSeed,
+    /// The hex encoded VRF entropy extracted from `prev_seed`.
+    pub vrf_entropy: String,
+    pub disabled_slots: BitSet,
+    /// The seed of the main chain block at this height, if it was produced in this view.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<VrfSeed>,
+}
+
+impl SlotAssignment {
+    pub fn from_slot_assignment(
+        assignment: nimiq_blockchain::SlotAssignment,
+        seed: Option<VrfSeed>,
+    ) -> Self {
+        SlotAssignment {
+            block_number: assignment.block_number,
+            view_number: assignment.view_number,
+            slot_number: assignment.slot.number,
+            vrf_entropy: hex::encode(&assignment.vrf_entropy().0),
+            validator: assignment.slot.validator.address,
+            public_key: assignment.slot.validator.voting_key.compressed().clone(),
+            signing_key: assignment.slot.validator.signing_key,
+            prev_seed: assignment.prev_seed,
+            disabled_slots: assignment.disabled_slots,
+            seed,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Slots {
@@ -462,6 +604,13 @@ pub struct Transaction {
     pub validity_start_height: u32,
     #[serde(with = "crate::serde_helpers::hex")]
     pub proof: Vec<u8>,
+    /// The decoded data, if the transaction creates a contract or interacts with the staking
+    /// contract.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decoded_data: Option<TransactionData>,
+    /// The decoded proof, if it is well-formed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decoded_proof: Option<TransactionProof>,
 }
 
 impl Transaction {
@@ -491,6 +640,8 @@ impl Transaction {
     ) -> Self {
         Transaction {
             hash: transaction.hash(),
+            decoded_data: TransactionData::decode(&transaction),
+            decoded_proof: TransactionProof::decode(&transaction),
             block_number,
             timestamp,
             confirmations: match head_height {
@@ -520,6 +671,9 @@ pub struct Inherent {
     pub value: Coin,
     #[serde(with = "crate::serde_helpers::hex")]
     pub data: Vec<u8>,
+    /// The decoded data, if the inherent carries any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decoded_data: Option<InherentData>,
     pub hash: Blake2bHash,
 }
 
@@ -530,6 +684,7 @@ impl Inherent {
         timestamp: u64,
     ) -> Self {
         let hash = inherent.hash();
+        let decoded_data = InherentData::decode(&inherent);
 
         Inherent {
             ty: inherent.ty as u8,
@@ -538,11 +693,266 @@ impl Inherent {
             target: inherent.target,
             value: inherent.value,
             data: inherent.data,
+            decoded_data,
             hash,
         }
     }
 }
 
+/// A change of the main chain. Cursors increase monotonically within a subscription.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum ChainEvent {
+    /// The block was added to the main chain.
+    #[serde(rename_all = "camelCase")]
+    Block {
+        cursor: u64,
+        block_number: u32,
+        hash: Blake2bHash,
+    },
+    /// The block was removed from the main chain by a rebranch. Consumers should roll back the
+    /// event with `revertedCursor`, which is `null` if the block was added before the subscription.
+    #[serde(rename_all = "camelCase")]
+    Revert {
+        cursor: u64,
+        reverted_cursor: Option<u64>,
+        block_number: u32,
+        hash: Blake2bHash,
+    },
+}
+
+impl From<nimiq_blockchain::SequencedEvent> for ChainEvent {
+    fn from(event: nimiq_blockchain::SequencedEvent) -> Self {
+        match event {
+            nimiq_blockchain::SequencedEvent::Block {
+                cursor,
+                block_number,
+                hash,
+            } => ChainEvent::Block {
+                cursor,
+                block_number,
+                hash,
+            },
+            nimiq_blockchain::SequencedEvent::Revert {
+                cursor,
+                reverted_cursor,
+                block_number,
+                hash,
+            } => ChainEvent::Revert {
+                cursor,
+                reverted_cursor,
+                block_number,
+                hash,
+            },
+        }
+    }
+}
+
+/// The requests made with an API key since the RPC server was started.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiKeyUsage {
+    /// The name of the API key. The key itself is never reported.
+    pub name: String,
+    /// The number of requests that were dispatched.
+    pub requests: u64,
+    /// The number of requests that were rejected because the method is not allowed for the key.
+    pub rejected_methods: u64,
+    /// The number of requests that were rejected because the rate limit was exceeded.
+    pub rate_limited: u64,
+    /// The number of dispatched requests per method.
+    pub methods: HashMap<String, u64>,
+}
+
+/// The memory held by the subsystems of the node.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceUsage {
+    /// The subsystems, ordered by name.
+    pub subsystems: Vec<SubsystemMemoryUsage>,
+    /// The number of bytes held by all subsystems together.
+    pub total: u64,
+}
+
+/// The memory held by a subsystem, e.g. the mempool or the history sync.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubsystemMemoryUsage {
+    pub name: String,
+    /// An estimate of the bytes held, usually the serialized size of the buffered objects.
+    pub used: u64,
+    /// The subsystem sheds load while it holds more bytes than this.
+    pub cap: Option<u64>,
+}
+
+/// The memory used by the node, as seen by the allocator and by its subsystems.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoryStats {
+    /// The statistics of the allocator, if the node was built with the `jemalloc` feature.
+    pub allocator: Option<AllocatorStats>,
+    /// The memory held by the subsystems, ordered by name.
+    pub subsystems: Vec<SubsystemMemoryUsage>,
+    /// The allocations of the subsystems, if the node was built with the `jemalloc` feature.
+    pub allocations: Vec<TaggedAllocations>,
+}
+
+/// The allocations of a subsystem since the node was started. Memory is counted as freed by the
+/// subsystem that frees it, so `allocated - freed` only approximates the memory it holds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaggedAllocations {
+    pub subsystem: String,
+    pub allocations: u64,
+    pub allocated: u64,
+    pub freed: u64,
+}
+
+/// The statistics of jemalloc in bytes, see its documentation of `stats.*`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AllocatorStats {
+    /// Bytes allocated by the node.
+    pub allocated: u64,
+    /// Bytes in the pages that hold allocations.
+    pub active: u64,
+    /// Bytes used by the allocator itself.
+    pub metadata: u64,
+    /// Bytes in physically resident pages.
+    pub resident: u64,
+    /// Bytes in the extents mapped by the allocator.
+    pub mapped: u64,
+    /// Bytes in mappings that were retained instead of being returned to the OS.
+    pub retained: u64,
+}
+
+/// The statistics of the long-running tasks of the node with the same name, e.g. the swarm task.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskStats {
+    pub name: String,
+    /// The number of tasks that were started.
+    pub spawned: u64,
+    /// The number of tasks that are still running.
+    pub alive: u64,
+    pub polls: u64,
+    /// The number of polls that took long enough to be reported as a stall.
+    pub slow_polls: u64,
+    /// The time spent in all polls, in microseconds.
+    pub poll_time: u64,
+    /// The longest time spent in a single poll, in microseconds.
+    pub max_poll_time: u64,
+}
+
+/// The metrics tokio collects for one of the runtimes of the node, e.g. the dedicated runtime of
+/// the network.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuntimeStats {
+    pub name: String,
+    /// The number of worker threads.
+    pub workers: u64,
+    /// The number of tasks waiting in the queues of the runtime to be polled.
+    pub queued_tasks: u64,
+    /// The number of tasks that were scheduled from outside of the runtime.
+    pub remote_schedules: u64,
+    /// The number of task polls by all workers.
+    pub polls: u64,
+    /// The time the workers spent polling tasks, in microseconds.
+    pub busy_time: u64,
+    /// The mean time spent in a poll, in microseconds.
+    pub mean_poll_time: u64,
+    /// The number of tasks the workers stole from each other.
+    pub steals: u64,
+    /// The number of times the workers went to sleep because there was no work.
+    pub parks: u64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LockMode {
+    Read,
+    UpgradableRead,
+    Write,
+    /// The upgrade of an upgradable read to a write.
+    Upgrade,
+}
+
+/// The contention on the blockchain lock caused by one call site and lock mode.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LockStats {
+    /// The source location of the call site, as `file:line:column`.
+    pub location: String,
+    pub mode: LockMode,
+    pub acquisitions: u64,
+    /// The number of acquisitions that had to wait for the lock.
+    pub contended: u64,
+    /// The time spent waiting for the lock, in microseconds.
+    pub wait_time: u64,
+    /// The longest wait for the lock, in microseconds.
+    pub max_wait_time: u64,
+    /// The time the lock was held, in microseconds. Not recorded for reads.
+    pub hold_time: u64,
+    /// The longest time the lock was held, in microseconds.
+    pub max_hold_time: u64,
+    /// The time other call sites waited while this one held the lock, in microseconds.
+    pub caused_wait_time: u64,
+}
+
+/// What happened to a transaction sent from an unlocked wallet.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum WalletTransactionEvent {
+    /// The transaction was included in a block.
+    #[serde(rename_all = "camelCase")]
+    Confirmed { hash: Blake2bHash },
+    /// The block that included the transaction was reverted, it is pending again.
+    #[serde(rename_all = "camelCase")]
+    Reverted { hash: Blake2bHash },
+    /// The transaction hasn't been included yet and can't be included from `expiresAt` on.
+    #[serde(rename_all = "camelCase")]
+    Expiring { hash: Blake2bHash, expires_at: u32 },
+    /// The transaction expired and was replaced by a re-signed transaction.
+    #[serde(rename_all = "camelCase")]
+    Resubmitted {
+        old_hash: Blake2bHash,
+        new_hash: Blake2bHash,
+        validity_start_height: u32,
+        fee: Coin,
+    },
+    /// The transaction expired without being included.
+    #[serde(rename_all = "camelCase")]
+    Expired { hash: Blake2bHash },
+}
+
+/// An entry of the history of the chain.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub enum ExtendedTransaction {
+    Transaction(Transaction),
+    Inherent(Inherent),
+}
+
+impl ExtendedTransaction {
+    pub fn from_blockchain(
+        ext_tx: nimiq_blockchain::ExtendedTransaction,
+        head_height: u32,
+    ) -> Self {
+        let block_number = ext_tx.block_number;
+        let timestamp = ext_tx.block_time;
+
+        match ext_tx.data {
+            nimiq_blockchain::ExtTxData::Basic(tx) => ExtendedTransaction::Transaction(
+                Transaction::from_blockchain(tx, block_number, timestamp, head_height),
+            ),
+            nimiq_blockchain::ExtTxData::Inherent(inherent) => ExtendedTransaction::Inherent(
+                Inherent::from_transaction(inherent, block_number, timestamp),
+            ),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Account {
     pub address: Address,
@@ -591,6 +1001,22 @@ pub enum AccountAdditionalFields {
         /// The total amount (in smallest unit) that was provided at the contract creation.
         total_amount: Coin,
     },
+
+    /// Additional account information for the staking contract.
+    #[serde(rename_all = "camelCase")]
+    Staking {
+        /// The validators that are eligible for slots in the next epoch and their stake.
+        active_validators: Vec<ActiveValidator>,
+        /// The validators that were parked during the current epoch.
+        parked_validators: Vec<Address>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActiveValidator {
+    pub address: Address,
+    pub balance: Coin,
 }
 
 impl Account {
@@ -624,6 +1050,18 @@ impl Account {
                     total_amount: htlc.total_amount,
                 },
             },
+            nimiq_account::Account::Staking(staking) => Account {
+                address,
+                balance: staking.balance,
+                account_additional_fields: AccountAdditionalFields::Staking {
+                    active_validators: staking
+                        .active_validators
+                        .into_iter()
+                        .map(|(address, balance)| ActiveValidator { address, balance })
+                        .collect(),
+                    parked_validators: staking.parked_set.into_iter().collect(),
+                },
+            },
             _ => unreachable!(),
         }
     }
@@ -637,6 +1075,110 @@ impl Account {
     }
 }
 
+/// The result of resolving a validator to its peer in the network.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidatorResolution {
+    pub validator_address: Address,
+    pub voting_key: CompressedPublicKey,
+    /// Whether a validator record was found in the DHT.
+    pub record_found: bool,
+    /// Whether the signature of the record is valid for the validator's voting key.
+    pub signature_valid: bool,
+    /// The peer ID of the validator, only present if the record was verified.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peer_id: Option<String>,
+    /// The addresses published in the validator record, the preferred ones first.
+    pub record_addresses: Vec<String>,
+    /// The addresses the peer advertises in its peer contact, if we know it.
+    pub addresses: Vec<String>,
+    pub connectivity: ConnectivityCheck,
+}
+
+/// Latency and clock statistics of a connected peer.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerStats {
+    pub peer_id: String,
+    /// Round trip time of the last ping in milliseconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rtt: Option<u64>,
+    /// Smoothed round trip time in milliseconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub average_rtt: Option<u64>,
+    /// Lowest round trip time measured in milliseconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_rtt: Option<u64>,
+    /// Offset of the peer's clock to ours in milliseconds. Positive if the peer's clock is ahead.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clock_offset: Option<i64>,
+}
+
+/// Inbound message counters of a subscribed gossipsub topic.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GossipTopicStats {
+    pub topic_name: String,
+    /// What happens to messages if the subscriber doesn't keep up: `dropNew`, `dropOld` or
+    /// `backpressure`.
+    pub overflow_policy: String,
+    pub received: u64,
+    /// Number of messages dropped because the subscription buffer was full.
+    pub dropped: u64,
+}
+
+/// The network messages and gossipsub topics known to the node.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NetworkRegistry {
+    pub messages: Vec<RegisteredMessage>,
+    pub topics: Vec<RegisteredTopic>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RegisteredMessage {
+    pub type_id: u64,
+    pub name: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RegisteredTopic {
+    /// The gossipsub topic name.
+    pub name: String,
+    /// The type implementing the topic.
+    pub topic: String,
+    pub buffer_size: usize,
+    pub validate: bool,
+}
+
+/// The consensus state of the node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ConsensusState {
+    /// Less than the minimum number of peers are connected.
+    WaitingForPeers,
+    /// Enough peers are connected, but the node didn't catch up with them yet.
+    Syncing,
+    /// The node is in sync with the network.
+    Established,
+}
+
+/// The result of checking whether we can connect to a peer.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "status", content = "error")]
+pub enum ConnectivityCheck {
+    /// We are already connected to the peer.
+    Connected,
+    /// We weren't connected to the peer, but dialing it succeeded.
+    Dialed,
+    /// Dialing the peer failed.
+    Unreachable(String),
+    /// There is no verified peer ID to check.
+    Skipped,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Staker {
@@ -870,3 +1412,381 @@ impl MempoolInfo {
         info
     }
 }
+
+/// Inclusion list accounting of a validator.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InclusionStats {
+    pub validator: Address,
+    /// Number of micro blocks produced by the validator.
+    pub blocks: u64,
+    /// Number of transactions from the inclusion list that the validator included.
+    pub included: u64,
+    /// Number of transactions from the inclusion list that the validator omitted.
+    pub omitted: u64,
+}
+
+/// The expected block numbers and timestamps of the next batch and epoch boundaries, extrapolated
+/// from the recent block times.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChainSchedule {
+    pub block_number: u32,
+    pub timestamp: u64,
+    /// The average time between the recent blocks in milliseconds.
+    pub average_block_time: u64,
+    pub next_macro_block: u32,
+    pub next_macro_block_eta: u64,
+    pub next_election_block: u32,
+    pub next_election_block_eta: u64,
+}
+
+impl From<nimiq_blockchain::ChainSchedule> for ChainSchedule {
+    fn from(schedule: nimiq_blockchain::ChainSchedule) -> Self {
+        ChainSchedule {
+            block_number: schedule.block_number,
+            timestamp: schedule.timestamp,
+            average_block_time: schedule.average_block_time,
+            next_macro_block: schedule.next_macro_block,
+            next_macro_block_eta: schedule.next_macro_block_eta,
+            next_election_block: schedule.next_election_block,
+            next_election_block_eta: schedule.next_election_block_eta,
+        }
+    }
+}
+
+/// The slots a validator gets in a simulated election.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulatedSlots {
+    pub address: Address,
+    pub stake: Coin,
+    /// The number of slots the validator got with the given seed.
+    pub num_slots: u16,
+    /// The number of slots the validator gets on average, in proportion to its stake.
+    pub expected_slots: f64,
+}
+
+/// Statistics of a finalized epoch.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EpochStats {
+    pub epoch: u32,
+    pub micro_blocks: u32,
+    pub macro_blocks: u32,
+    /// Number of transactions, not including inherents.
+    pub transactions: u64,
+    pub total_fees: Coin,
+    pub view_changes: u32,
+    /// Number of slash inherents.
+    pub slashes: u32,
+    pub validators: Vec<ValidatorParticipation>,
+}
+
+/// The participation of a validator in an epoch.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidatorParticipation {
+    pub address: Address,
+    pub num_slots: u16,
+    /// Number of micro blocks produced by the validator.
+    pub blocks_produced: u32,
+    /// Fraction of the validator's slots that signed the macro blocks of the epoch.
+    pub macro_participation: f64,
+}
+
+/// A staker transaction that waits for the signature of an offline staker key.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ColdStakingTransaction {
+    /// Identifies the transaction. It is the hash of the unsigned transaction.
+    pub id: Blake2bHash,
+    pub staker_address: Address,
+    /// The wallet that pays the transaction, if it isn't paid by the staker.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sender_wallet: Option<Address>,
+    /// The delegation that is set by the transaction, if it creates or updates a staker.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delegation: Option<Address>,
+    /// The serialized unsigned transaction.
+    pub transaction: String,
+    /// The hex encoded content that has to be signed with the staker key.
+    pub signing_payload: String,
+    pub validity_start_height: u32,
+    pub status: ColdStakingStatus,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum ColdStakingStatus {
+    AwaitingSignature,
+    #[serde(rename_all = "camelCase")]
+    Sent {
+        hash: Blake2bHash,
+    },
+}
+
+/// Accounting of the distribution of the validator rewards.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RewardDistribution {
+    /// Total rewards received at the reward address since the validator started.
+    pub rewards_received: Coin,
+    /// Number of the last block that paid out rewards.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_payout_block: Option<u32>,
+    pub recipients: Vec<RewardRecipient>,
+}
+
+/// A recipient of a share of the validator rewards.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RewardRecipient {
+    pub address: Address,
+    /// The configured share in percent.
+    pub percentage: f64,
+    /// Total value that was sent to the recipient.
+    pub distributed: Coin,
+    pub num_transactions: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_transaction: Option<Blake2bHash>,
+}
+
+/// An entry of the transaction history of an address, e.g. for accounting purposes.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryEntry {
+    pub hash: Blake2bHash,
+    pub block_number: u32,
+    /// Timestamp of the block in milliseconds.
+    pub timestamp: u64,
+    pub from: Address,
+    pub to: Address,
+    pub value: Coin,
+    pub fee: Coin,
+    /// Whether the entry is a reward inherent.
+    pub is_reward: bool,
+    /// Change of the balance of the address in Luna, negative for outgoing transactions.
+    pub balance_change: i64,
+    /// Balance of the address after the transaction.
+    pub balance: Coin,
+}
+
+/// The position of an entry in the transaction history of an address. Passing it back continues
+/// the query after that entry.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryCursor {
+    pub block_number: u32,
+    pub leaf_index: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address_index: Option<u32>,
+}
+
+impl From<nimiq_blockchain::HistoryCursor> for HistoryCursor {
+    fn from(cursor: nimiq_blockchain::HistoryCursor) -> Self {
+        HistoryCursor {
+            block_number: cursor.block_number,
+            leaf_index: cursor.leaf_index,
+            address_index: cursor.address_index,
+        }
+    }
+}
+
+impl From<HistoryCursor> for nimiq_blockchain::HistoryCursor {
+    fn from(cursor: HistoryCursor) -> Self {
+        nimiq_blockchain::HistoryCursor {
+            block_number: cursor.block_number,
+            leaf_index: cursor.leaf_index,
+            address_index: cursor.address_index,
+        }
+    }
+}
+
+/// A page of the transactions of an address.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionPage {
+    pub transactions: Vec<Transaction>,
+    /// The cursor to fetch the next page with, if the page is full.
+    pub next: Option<HistoryCursor>,
+}
+
+/// Metrics of the database since the client was started.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DatabaseMetrics {
+    /// Time read transactions were open.
+    pub read_transactions: DurationHistogram,
+    /// Time write transactions were open, including the commit.
+    pub write_transactions: DurationHistogram,
+    /// Time it took to commit write transactions.
+    pub commits: DurationHistogram,
+    /// Total size in bytes of the keys and values written by committed transactions.
+    pub bytes_committed: u64,
+    /// Size in bytes of the keys and values written by the largest commit.
+    pub largest_commit: u64,
+    /// Number of commits that exceeded the configured slow commit threshold.
+    pub slow_commits: u64,
+    /// Size of the memory map in bytes.
+    pub map_size: u64,
+    /// Size of the memory map in use in bytes.
+    pub map_used: u64,
+}
+
+/// The usage of the cache of uncompressed BLS public keys since the client was started.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublicKeyCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// The number of cached public keys.
+    pub size: u64,
+    pub capacity: u64,
+}
+
+/// A histogram of durations.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DurationHistogram {
+    /// Upper bounds of the buckets in milliseconds. The last bucket counts all longer durations.
+    pub bucket_bounds_ms: Vec<u64>,
+    pub buckets: Vec<u64>,
+    pub count: u64,
+    /// Sum of all durations in microseconds.
+    pub total_us: u64,
+    /// Longest duration in microseconds.
+    pub max_us: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use nimiq_block_production::BlockProducer;
+    use nimiq_blockchain::BlockchainLock;
+    use nimiq_database::volatile::VolatileEnvironment;
+    use nimiq_primitives::{networks::NetworkId, slots::Validator};
+    use nimiq_test_utils::blockchain::{
+        produce_macro_blocks, sign_view_change, signing_key, voting_key,
+    };
+    use nimiq_utils::time::OffsetTime;
+
+    use super::*;
+
+    fn test_blockchain() -> Arc<BlockchainLock> {
+        let env = VolatileEnvironment::new(10).unwrap();
+        let time = Arc::new(OffsetTime::new());
+        Arc::new(BlockchainLock::new(
+            Blockchain::new(env, NetworkId::UnitAlbatross, time).unwrap(),
+        ))
+    }
+
+    /// The genesis validator owns all slots, so it is the only signer.
+    fn genesis_signers(blockchain: &Blockchain) -> Vec<(Address, Vec<u16>)> {
+        let validators = blockchain.current_validators().unwrap();
+        vec![(
+            validators.get_validator_by_slot_number(0).address.clone(),
+            (0..policy::TWO_F_PLUS_ONE).collect(),
+        )]
+    }
+
+    fn slots(signers: &[SignerSlots]) -> Vec<(Address, Vec<u16>)> {
+        signers
+            .iter()
+            .map(|signer| (signer.validator.clone(), signer.slots.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn signer_slots_are_grouped_by_validator() {
+        let voting_key = voting_key().public_key;
+        let signing_key = signing_key().public;
+        let validators = Validators::new(vec![
+            Validator::new(Address::from([1u8; 20]), voting_key, signing_key, (0, 10)),
+            Validator::new(Address::from([2u8; 20]), voting_key, signing_key, (10, 20)),
+            Validator::new(
+                Address::from([3u8; 20]),
+                voting_key,
+                signing_key,
+                (20, policy::SLOTS),
+            ),
+        ]);
+
+        let mut signers = BitSet::new();
+        for slot in [1, 2, 9, 20, 21, policy::SLOTS - 1] {
+            signers.insert(slot as usize);
+        }
+        // Slots beyond the slot count are ignored.
+        signers.insert(policy::SLOTS as usize + 3);
+
+        // The second validator didn't sign at all.
+        assert_eq!(
+            slots(&SignerSlots::from_signers(&signers, &validators)),
+            vec![
+                (Address::from([1u8; 20]), vec![1, 2, 9]),
+                (Address::from([3u8; 20]), vec![20, 21, policy::SLOTS - 1]),
+            ]
+        );
+        assert!(SignerSlots::from_signers(&BitSet::new(), &validators).is_empty());
+    }
+
+    #[test]
+    fn micro_block_justification_maps_the_view_change_signers() {
+        let producer = BlockProducer::new(signing_key(), voting_key());
+        let blockchain = test_blockchain();
+        let blockchain = blockchain.read();
+
+        let view_change_proof = sign_view_change(blockchain.head().seed().clone(), 1, 1);
+        let micro_block = producer.next_micro_block(
+            &blockchain,
+            blockchain.time.now() + 1000,
+            1,
+            Some(view_change_proof),
+            vec![],
+            vec![],
+            vec![0x42],
+        );
+
+        match BlockJustification::from_block(&blockchain, nimiq_block::Block::Micro(micro_block)) {
+            Some(BlockJustification::Micro {
+                producer,
+                view_change_proof: Some(view_change_proof),
+                ..
+            }) => {
+                assert_eq!(
+                    slots(&view_change_proof.signers),
+                    genesis_signers(&blockchain)
+                );
+                let expected_producer = Slot::from(&blockchain, 1, 1);
+                assert_eq!(producer.slot_number, expected_producer.slot_number);
+                assert_eq!(producer.validator, expected_producer.validator);
+            }
+            other => panic!(
+                "Expected a micro justification with a view change, got {:?}",
+                other
+            ),
+        }
+    }
+
+    #[test]
+    fn macro_block_justification_maps_the_tendermint_signers() {
+        let producer = BlockProducer::new(signing_key(), voting_key());
+        let blockchain = test_blockchain();
+        produce_macro_blocks(&producer, &blockchain, 1);
+        let blockchain = blockchain.read();
+
+        let macro_block = blockchain
+            .get_block_at(policy::macro_block_of(1), true, None)
+            .unwrap();
+        assert!(macro_block.is_macro());
+
+        match BlockJustification::from_block(&blockchain, macro_block) {
+            Some(BlockJustification::Macro { round, signers, .. }) => {
+                assert_eq!(round, 0);
+                assert_eq!(slots(&signers), genesis_signers(&blockchain));
+            }
+            other => panic!("Expected a macro justification, got {:?}", other),
+        }
+    }
+}
@@ -2,6 +2,8 @@ use async_trait::async_trait;
 
 use nimiq_keys::Address;
 
+use crate::types::RewardDistribution;
+
 #[nimiq_jsonrpc_derive::proxy(name = "ValidatorProxy", rename_all = "camelCase")]
 #[async_trait]
 pub trait ValidatorInterface {
@@ -12,4 +14,6 @@ pub trait ValidatorInterface {
     async fn get_signing_key(&mut self) -> Result<String, Self::Error>;
 
     async fn get_voting_key(&mut self) -> Result<String, Self::Error>;
+
+    async fn get_reward_distribution(&mut self) -> Result<RewardDistribution, Self::Error>;
 }
@@ -1,6 +1,7 @@
 use async_trait::async_trait;
 
 use nimiq_keys::{Address, PrivateKey, PublicKey, Signature};
+use nimiq_primitives::{coin::Coin, payment_uri::PaymentUri};
 
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -17,6 +18,29 @@ pub struct ReturnAccount {
     pub private_key: PrivateKey,
 }
 
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PaymentRequest {
+    pub uri: String,
+    pub recipient: Address,
+    pub amount: Option<Coin>,
+    pub message: Option<String>,
+    /// Unix timestamp in seconds after which the request shouldn't be paid anymore.
+    pub expires: Option<u64>,
+}
+
+impl From<PaymentUri> for PaymentRequest {
+    fn from(uri: PaymentUri) -> Self {
+        PaymentRequest {
+            uri: uri.to_string(),
+            recipient: uri.recipient,
+            amount: uri.amount,
+            message: uri.message,
+            expires: uri.expires,
+        }
+    }
+}
+
 #[nimiq_jsonrpc_derive::proxy(name = "WalletProxy", rename_all = "camelCase")]
 #[async_trait]
 pub trait WalletInterface {
@@ -63,4 +87,14 @@ pub trait WalletInterface {
         signature: Signature,
         is_hex: bool,
     ) -> Result<bool, Self::Error>;
+
+    async fn create_payment_request(
+        &mut self,
+        recipient: Address,
+        amount: Option<Coin>,
+        message: Option<String>,
+        expires: Option<u64>,
+    ) -> Result<PaymentRequest, Self::Error>;
+
+    async fn decode_payment_request(&mut self, uri: String) -> Result<PaymentRequest, Self::Error>;
 }
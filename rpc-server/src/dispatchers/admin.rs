@@ -0,0 +1,216 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use tokio::runtime::Handle;
+
+use nimiq_rpc_interface::admin::AdminInterface;
+use nimiq_rpc_interface::types::{
+    ApiKeyUsage, LockMode, LockStats, ResourceUsage, RuntimeStats, SubsystemMemoryUsage, TaskStats,
+};
+use nimiq_utils::lock_metrics;
+use nimiq_utils::memory::{self, MemoryAccounting};
+use nimiq_utils::stall;
+
+use crate::error::Error;
+use crate::tenants::Tenants;
+
+pub struct AdminDispatcher {
+    tenants: Arc<Tenants>,
+    memory: MemoryAccounting,
+    heap_profile_dir: Option<PathBuf>,
+    runtimes: Vec<(String, Handle)>,
+}
+
+impl AdminDispatcher {
+    pub fn new(tenants: Arc<Tenants>) -> Self {
+        AdminDispatcher {
+            tenants,
+            memory: MemoryAccounting::new(),
+            heap_profile_dir: None,
+            runtimes: vec![],
+        }
+    }
+
+    /// Writes heap profiles to `dir`. Without it, heap profiles can't be written.
+    pub fn with_heap_profile_dir(mut self, dir: Option<PathBuf>) -> Self {
+        self.heap_profile_dir = dir;
+        self
+    }
+
+    /// Reports the memory usage of the subsystems registered with `memory`.
+    pub fn with_memory(mut self, memory: MemoryAccounting) -> Self {
+        self.memory = memory;
+        self
+    }
+
+    /// Reports the metrics of the given runtimes, by name.
+    pub fn with_runtimes(mut self, runtimes: Vec<(String, Handle)>) -> Self {
+        self.runtimes = runtimes;
+        self
+    }
+}
+
+/// Reads the metrics of the runtime. Tokio only collects them if the node was built with
+/// `--cfg tokio_unstable`, which `.cargo/config.toml` sets.
+#[cfg(tokio_unstable)]
+fn runtime_stats(name: &str, handle: &Handle) -> Option<RuntimeStats> {
+    let metrics = handle.metrics();
+    let workers = 0..metrics.num_workers();
+
+    let queued_tasks = metrics.injection_queue_depth()
+        + workers
+            .clone()
+            .map(|worker| metrics.worker_local_queue_depth(worker))
+            .sum::<usize>();
+    let polls: u64 = workers
+        .clone()
+        .map(|worker| metrics.worker_poll_count(worker))
+        .sum();
+    let busy_time: std::time::Duration = workers
+        .clone()
+        .map(|worker| metrics.worker_total_busy_duration(worker))
+        .sum();
+
+    Some(RuntimeStats {
+        name: name.to_string(),
+        workers: metrics.num_workers() as u64,
+        queued_tasks: queued_tasks as u64,
+        remote_schedules: metrics.remote_schedule_count(),
+        polls,
+        busy_time: busy_time.as_micros() as u64,
+        mean_poll_time: busy_time
+            .as_micros()
+            .checked_div(polls as u128)
+            .unwrap_or_default() as u64,
+        steals: workers
+            .clone()
+            .map(|worker| metrics.worker_steal_count(worker))
+            .sum(),
+        parks: workers
+            .map(|worker| metrics.worker_park_count(worker))
+            .sum(),
+    })
+}
+
+#[cfg(not(tokio_unstable))]
+fn runtime_stats(_name: &str, _handle: &Handle) -> Option<RuntimeStats> {
+    None
+}
+
+/// Returns the memory held by the subsystems registered with `memory` and their caps.
+pub(super) fn subsystem_usage(memory: &MemoryAccounting) -> Vec<SubsystemMemoryUsage> {
+    memory
+        .usage()
+        .into_iter()
+        .map(|usage| SubsystemMemoryUsage {
+            name: usage.subsystem,
+            used: usage.used as u64,
+            cap: usage.cap.map(|cap| cap as u64),
+        })
+        .collect()
+}
+
+#[nimiq_jsonrpc_derive::service(rename_all = "camelCase")]
+#[async_trait]
+impl AdminInterface for AdminDispatcher {
+    type Error = Error;
+
+    /// Returns the number of requests made with each API key since the node was started, and how
+    /// many of them were rejected.
+    async fn get_api_key_usage(&mut self) -> Result<Vec<ApiKeyUsage>, Self::Error> {
+        Ok(self.tenants.usage())
+    }
+
+    /// Returns the memory held by the subsystems of the node and their caps, in bytes.
+    async fn get_resource_usage(&mut self) -> Result<ResourceUsage, Self::Error> {
+        Ok(ResourceUsage {
+            subsystems: subsystem_usage(&self.memory),
+            total: self.memory.total() as u64,
+        })
+    }
+
+    /// Writes a heap profile to a new file in the heap profile directory under the data
+    /// directory of the node and returns its path. Requires a node built with the
+    /// `jemalloc-profiling` feature and started with `_RJEM_MALLOC_CONF=prof:true`.
+    async fn dump_heap_profile(&mut self) -> Result<String, Self::Error> {
+        let dir = self.heap_profile_dir.as_ref().ok_or_else(|| {
+            Error::HeapProfile("Heap profiles require a persistent data directory".to_string())
+        })?;
+        let path = memory::dump_heap_profile(dir).map_err(Error::HeapProfile)?;
+        Ok(path.display().to_string())
+    }
+
+    /// Returns the poll statistics of the instrumented tasks of the node, e.g. the swarm task.
+    async fn get_task_stats(&mut self) -> Result<Vec<TaskStats>, Self::Error> {
+        Ok(stall::task_stats()
+            .into_iter()
+            .map(|stats| TaskStats {
+                name: stats.name.to_string(),
+                spawned: stats.spawned,
+                alive: stats.alive,
+                polls: stats.polls,
+                slow_polls: stats.slow_polls,
+                poll_time: stats.poll_time.as_micros() as u64,
+                max_poll_time: stats.max_poll_time.as_micros() as u64,
+            })
+            .collect())
+    }
+
+    /// Returns the metrics that tokio collects for the runtimes of the node, e.g. the number of
+    /// queued tasks and the time spent polling them.
+    async fn get_runtime_stats(&mut self) -> Result<Vec<RuntimeStats>, Self::Error> {
+        self.runtimes
+            .iter()
+            .map(|(name, handle)| runtime_stats(name, handle))
+            .collect::<Option<Vec<_>>>()
+            .ok_or(Error::RuntimeMetricsUnavailable)
+    }
+
+    /// Returns the wait and hold times of the call sites of the blockchain lock. They are only
+    /// recorded if the client was built with the `metrics` feature.
+    async fn get_lock_stats(&mut self) -> Result<Vec<LockStats>, Self::Error> {
+        Ok(lock_metrics::lock_stats()
+            .into_iter()
+            .map(|stats| LockStats {
+                location: stats.location.to_string(),
+                mode: match stats.mode {
+                    lock_metrics::LockMode::Read => LockMode::Read,
+                    lock_metrics::LockMode::UpgradableRead => LockMode::UpgradableRead,
+                    lock_metrics::LockMode::Write => LockMode::Write,
+                    lock_metrics::LockMode::Upgrade => LockMode::Upgrade,
+                },
+                acquisitions: stats.acquisitions,
+                contended: stats.contended,
+                wait_time: stats.wait_time.as_micros() as u64,
+                max_wait_time: stats.max_wait_time.as_micros() as u64,
+                hold_time: stats.hold_time.as_micros() as u64,
+                max_hold_time: stats.max_hold_time.as_micros() as u64,
+                caused_wait_time: stats.caused_wait_time.as_micros() as u64,
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn it_reports_the_runtime_stats() {
+        let mut dispatcher = AdminDispatcher::new(Arc::new(Tenants::new(vec![], false)))
+            .with_runtimes(vec![("main".to_string(), Handle::current())]);
+
+        let result = dispatcher.get_runtime_stats().await;
+
+        if cfg!(tokio_unstable) {
+            let stats = result.unwrap();
+            assert_eq!(stats.len(), 1);
+            assert_eq!(stats[0].name, "main");
+            // The test runs on a current thread runtime.
+            assert_eq!(stats[0].workers, 1);
+        } else {
+            assert!(matches!(result, Err(Error::RuntimeMetricsUnavailable)));
+        }
+    }
+}
@@ -1,29 +1,56 @@
-use std::{collections::HashMap, ops::Deref, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    ops::Deref,
+    sync::Arc,
+};
 
 use async_trait::async_trait;
-use futures::stream::{BoxStream, StreamExt};
-use parking_lot::RwLock;
-
-use nimiq_account::StakingContract;
-use nimiq_blockchain::{AbstractBlockchain, Blockchain, BlockchainEvent};
+use futures::stream::{self, BoxStream, StreamExt};
+
+use beserial::{Deserialize, Serialize};
+use nimiq_account::{InherentType, StakingContract};
+use nimiq_block::Block as BlockchainBlock;
+use nimiq_blockchain::{
+    receipt::PaymentReceipt, AbstractBlockchain, BlockchainEvent, BlockchainLock, BlockchainReader,
+    EventSequencer, HistoryFilter,
+};
+use nimiq_bls::cache::PublicKeyCache;
+use nimiq_database::metrics::{DurationHistogramSnapshot, DURATION_BUCKETS_MS};
 use nimiq_hash::Blake2bHash;
 use nimiq_keys::Address;
 use nimiq_primitives::{coin::Coin, policy};
 use nimiq_rpc_interface::types::{ParkedSet, Validator};
 use nimiq_rpc_interface::{
     blockchain::BlockchainInterface,
-    types::{Account, Block, Inherent, SlashedSlots, Slot, Staker, Transaction},
+    types::{
+        Account, Block, BlockJustification, ChainEvent, ChainSchedule, DatabaseMetrics,
+        DurationHistogram, EpochStats, ExtendedTransaction, HistoryCursor, HistoryEntry, Inherent,
+        PublicKeyCacheStats, SimulatedSlots, SlashedSlots, Slot, SlotAssignment, Staker,
+        Transaction, TransactionPage, ValidatorParticipation,
+    },
 };
+use nimiq_vrf::VrfSeed;
 
 use crate::error::Error;
 
 pub struct BlockchainDispatcher {
-    blockchain: Arc<RwLock<Blockchain>>,
+    blockchain: Arc<BlockchainLock>,
+    // Answers the queries of the head and the accounts without waiting for blocks being pushed.
+    reader: BlockchainReader,
+    // Statistics of finalized epochs. They can't change anymore, so they are only computed once.
+    epoch_stats: HashMap<u32, EpochStats>,
 }
 
 impl BlockchainDispatcher {
-    pub fn new(blockchain: Arc<RwLock<Blockchain>>) -> Self {
-        Self { blockchain }
+    /// The number of recent blocks whose average block time is used to estimate the schedule.
+    const SCHEDULE_SAMPLE_BLOCKS: u32 = 1_000;
+
+    pub fn new(blockchain: Arc<BlockchainLock>) -> Self {
+        Self {
+            reader: blockchain.read().reader(),
+            blockchain,
+            epoch_stats: HashMap::new(),
+        }
     }
 }
 
@@ -34,17 +61,17 @@ impl BlockchainInterface for BlockchainDispatcher {
 
     /// Returns the block number for the current head.
     async fn get_block_number(&mut self) -> Result<u32, Error> {
-        Ok(self.blockchain.read().block_number())
+        Ok(self.reader.block_number())
     }
 
     /// Returns the batch number for the current head.
     async fn get_batch_number(&mut self) -> Result<u32, Error> {
-        Ok(policy::batch_at(self.blockchain.read().block_number()))
+        Ok(policy::batch_at(self.reader.block_number()))
     }
 
     /// Returns the epoch number for the current head.
     async fn get_epoch_number(&mut self) -> Result<u32, Error> {
-        Ok(policy::epoch_at(self.blockchain.read().block_number()))
+        Ok(policy::epoch_at(self.reader.block_number()))
     }
 
     /// Tries to fetch a block given its hash. It has an option to include the transactions in the
@@ -105,6 +132,23 @@ impl BlockchainInterface for BlockchainDispatcher {
         ))
     }
 
+    /// Returns the justification of the block with the given hash. The signer bitmaps of the
+    /// Tendermint proof or view change proof are mapped to the addresses of the validators that
+    /// own the signing slots.
+    async fn get_block_justification(
+        &mut self,
+        hash: Blake2bHash,
+    ) -> Result<BlockJustification, Error> {
+        let blockchain = self.blockchain.read();
+
+        let block = blockchain
+            .get_block(&hash, false, None)
+            .ok_or_else(|| Error::BlockNotFound(hash.clone().into()))?;
+
+        BlockJustification::from_block(blockchain.deref(), block)
+            .ok_or(Error::JustificationNotFound(hash))
+    }
+
     /// Returns the information for the slot owner at the given block height and view number. The
     /// view number is optional, it will default to getting the view number for the existing block
     /// at the given height.
@@ -128,6 +172,39 @@ impl BlockchainInterface for BlockchainDispatcher {
         Ok(Slot::from(blockchain.deref(), block_number, view_number))
     }
 
+    /// Returns the slot assignment at the given block height and view number, together with the
+    /// VRF seed, entropy and disabled slots used to compute it. This allows anyone to independently
+    /// verify which validator was supposed to produce a block. The view number is optional, it
+    /// defaults to the view number of the existing block at the given height.
+    async fn get_slot_assignment_at(
+        &mut self,
+        block_number: u32,
+        view_number_opt: Option<u32>,
+    ) -> Result<SlotAssignment, Error> {
+        let blockchain = self.blockchain.read();
+
+        let block = blockchain
+            .chain_store
+            .get_block_at(block_number, false, None);
+
+        let view_number = match (view_number_opt, &block) {
+            (Some(view_number), _) => view_number,
+            (None, Some(block)) => block.view_number(),
+            (None, None) => return Err(Error::BlockNotFound(block_number.into())),
+        };
+
+        let assignment = blockchain
+            .get_slot_assignment_at(block_number, view_number, None)
+            .ok_or_else(|| Error::BlockNotFound(block_number.into()))?;
+
+        // Only include the block's seed if the block was actually produced in the requested view.
+        let seed = block
+            .filter(|block| block.view_number() == view_number)
+            .map(|block| block.seed().clone());
+
+        Ok(SlotAssignment::from_slot_assignment(assignment, seed))
+    }
+
     /// Returns all the transactions (including reward transactions) for the given block number. Note
     /// that this only considers blocks in the main chain.
     async fn get_transactions_by_block_number(
@@ -310,37 +387,34 @@ impl BlockchainInterface for BlockchainDispatcher {
     /// Returns the latest transactions for a given address. All the transactions
     /// where the given address is listed as a recipient or as a sender are considered. Reward
     /// transactions are also returned. It has an option to specify the maximum number of transactions
-    /// to fetch, it defaults to 500.
+    /// to fetch, it defaults to 500. If the page is full, the next page is fetched by passing
+    /// the returned cursor.
     async fn get_transactions_by_address(
         &mut self,
         address: Address,
         max: Option<u16>,
-    ) -> Result<Vec<Transaction>, Error> {
+        cursor: Option<HistoryCursor>,
+    ) -> Result<TransactionPage, Error> {
         let blockchain = self.blockchain.read();
+        let db_txn = blockchain.read_transaction();
+        let max = max.unwrap_or(500) as usize;
 
-        // Get the transaction hashes for this address.
-        let tx_hashes =
-            blockchain
-                .history_store
-                .get_tx_hashes_by_address(&address, max.unwrap_or(500), None);
+        // Read a page of the extended transactions of this address, from the most recent one.
+        let mut filter = HistoryFilter::new().address(address).newest_first();
+        if let Some(cursor) = cursor {
+            filter = filter.after(cursor.into());
+        }
+        let mut query = blockchain.history_store.query(filter, &db_txn);
+        let extended_txs: Vec<_> = query.by_ref().take(max).collect();
+        let next = if extended_txs.len() == max {
+            query.cursor().map(HistoryCursor::from)
+        } else {
+            None
+        };
 
         let mut txs = vec![];
 
-        for hash in tx_hashes {
-            // Get all the extended transactions that correspond to this hash.
-            let mut extended_tx_vec = blockchain.history_store.get_ext_tx_by_hash(&hash, None);
-
-            // Unpack the transaction or raise an error.
-            let extended_tx = match extended_tx_vec.len() {
-                0 => {
-                    return Err(Error::TransactionNotFound(hash));
-                }
-                1 => extended_tx_vec.pop().unwrap(),
-                _ => {
-                    return Err(Error::MultipleTransactionsFound(hash));
-                }
-            };
-
+        for extended_tx in extended_txs {
             // Convert the extended transaction into a regular transaction. This will also convert
             // reward inherents.
             let block_number = extended_tx.block_number;
@@ -356,12 +430,76 @@ impl BlockchainInterface for BlockchainDispatcher {
             }
         }
 
-        Ok(txs)
+        Ok(TransactionPage {
+            transactions: txs,
+            next,
+        })
+    }
+
+    /// Returns the transaction history of the given address, from the oldest to the most recent
+    /// transaction. Each entry contains the balance of the address after the transaction, which is
+    /// computed backwards from the current balance. It has an option to specify the maximum number
+    /// of transactions to fetch, it defaults to 500.
+    async fn get_transaction_history(
+        &mut self,
+        address: Address,
+        max: Option<u16>,
+    ) -> Result<Vec<HistoryEntry>, Error> {
+        let blockchain = self.blockchain.read();
+
+        let mut balance = blockchain
+            .get_account(&address)
+            .map_or(Coin::ZERO, |account| account.balance());
+
+        // The extended transactions are streamed from the most recent to the oldest.
+        let db_txn = blockchain.read_transaction();
+        let filter = HistoryFilter::new().address(address.clone()).newest_first();
+        let extended_txs = blockchain
+            .history_store
+            .query(filter, &db_txn)
+            .take(max.unwrap_or(500) as usize);
+
+        let mut entries = vec![];
+
+        for extended_tx in extended_txs {
+            let block_number = extended_tx.block_number;
+            let timestamp = extended_tx.block_time;
+            let is_reward = extended_tx.is_inherent();
+            let hash = extended_tx.tx_hash();
+
+            let tx = match extended_tx.into_transaction() {
+                Ok(tx) => tx,
+                Err(_) => continue,
+            };
+
+            let balance_change = balance_change(&tx, &address);
+
+            entries.push(HistoryEntry {
+                hash,
+                block_number,
+                timestamp,
+                from: tx.sender,
+                to: tx.recipient,
+                value: tx.value,
+                fee: tx.fee,
+                is_reward,
+                balance_change,
+                balance,
+            });
+
+            // Go back to the balance before this transaction.
+            balance = Coin::from_u64_unchecked(
+                (u64::from(balance) as i64 - balance_change).max(0) as u64
+            );
+        }
+
+        entries.reverse();
+        Ok(entries)
     }
 
     /// Tries to fetch the account at the given address.
     async fn get_account_by_address(&mut self, address: Address) -> Result<Account, Error> {
-        let result = self.blockchain.read().get_account(&address);
+        let result = self.reader.view().get_account(&address);
 
         match result {
             Some(account) => Ok(Account::from_account(address, account)),
@@ -468,11 +606,9 @@ impl BlockchainInterface for BlockchainDispatcher {
 
     /// Tries to fetch a staker information given its address.
     async fn get_staker_by_address(&mut self, address: Address) -> Result<Staker, Error> {
-        let blockchain = self.blockchain.read();
-
-        let accounts_tree = &blockchain.state().accounts.tree;
-        let db_txn = blockchain.read_transaction();
-        let staker = StakingContract::get_staker(accounts_tree, &db_txn, &address);
+        let view = self.reader.view();
+        let staker =
+            StakingContract::get_staker(view.accounts_tree(), view.read_transaction(), &address);
 
         match staker {
             Some(s) => Ok(Staker::from_staker(&s)),
@@ -480,6 +616,224 @@ impl BlockchainInterface for BlockchainDispatcher {
         }
     }
 
+    /// Returns statistics of the given epoch: the number of blocks, transactions and view changes,
+    /// the fees paid, the number of slashes and the participation of each validator. Only finalized
+    /// epochs are supported.
+    async fn get_epoch_stats(&mut self, epoch: u32) -> Result<EpochStats, Error> {
+        if let Some(stats) = self.epoch_stats.get(&epoch) {
+            return Ok(stats.clone());
+        }
+
+        let blockchain = self.blockchain.read();
+
+        if epoch == 0
+            || blockchain.election_head().block_number() < policy::election_block_of(epoch)
+        {
+            return Err(Error::EpochNotFinalized(epoch));
+        }
+
+        let validators = blockchain
+            .get_validators_for_epoch(epoch, None)
+            .ok_or(Error::EpochNotFinalized(epoch))?;
+
+        let mut stats = EpochStats {
+            epoch,
+            micro_blocks: 0,
+            macro_blocks: 0,
+            transactions: 0,
+            total_fees: Coin::ZERO,
+            view_changes: 0,
+            slashes: 0,
+            validators: vec![],
+        };
+        let mut blocks_produced = vec![0u32; validators.num_validators()];
+        let mut signed_slots = vec![0u64; validators.num_validators()];
+
+        let first_block = policy::first_block_of(epoch);
+        let mut prev_block = blockchain
+            .get_block_at(first_block - 1, false, None)
+            .ok_or_else(|| Error::BlockNotFound((first_block - 1).into()))?;
+
+        for block_number in first_block..=policy::election_block_of(epoch) {
+            let block = blockchain
+                .get_block_at(block_number, true, None)
+                .ok_or_else(|| Error::BlockNotFound(block_number.into()))?;
+
+            match &block {
+                BlockchainBlock::Micro(_) => {
+                    stats.micro_blocks += 1;
+                    stats.view_changes += block
+                        .view_number()
+                        .saturating_sub(prev_block.next_view_number());
+
+                    if let Some((_, slot)) =
+                        blockchain.get_slot_owner_at(block_number, block.view_number(), None)
+                    {
+                        blocks_produced[validators.get_band_from_slot(slot) as usize] += 1;
+                    }
+                }
+                BlockchainBlock::Macro(macro_block) => {
+                    stats.macro_blocks += 1;
+
+                    if let Some(justification) = &macro_block.justification {
+                        for slot in justification.sig.signers.iter() {
+                            signed_slots[validators.get_band_from_slot(slot as u16) as usize] += 1;
+                        }
+                    }
+                }
+            }
+
+            prev_block = block;
+        }
+
+        let db_txn = blockchain.read_transaction();
+        let filter = HistoryFilter::new().epochs(epoch..=epoch);
+        for ext_tx in blockchain.history_store.query(filter, &db_txn) {
+            if ext_tx.is_inherent() {
+                if ext_tx.unwrap_inherent().ty == InherentType::Slash {
+                    stats.slashes += 1;
+                }
+            } else {
+                stats.transactions += 1;
+                stats.total_fees += ext_tx.unwrap_basic().fee;
+            }
+        }
+
+        stats.validators = validators
+            .validators
+            .iter()
+            .enumerate()
+            .map(|(band, validator)| {
+                let expected_signatures = validator.num_slots() as u64 * stats.macro_blocks as u64;
+                ValidatorParticipation {
+                    address: validator.address.clone(),
+                    num_slots: validator.num_slots(),
+                    blocks_produced: blocks_produced[band],
+                    macro_participation: if expected_signatures > 0 {
+                        signed_slots[band] as f64 / expected_signatures as f64
+                    } else {
+                        0.0
+                    },
+                }
+            })
+            .collect();
+
+        drop(blockchain);
+        self.epoch_stats.insert(epoch, stats.clone());
+
+        Ok(stats)
+    }
+
+    /// Returns the expected block numbers and timestamps of the next macro block and the next
+    /// election block. The timestamps are extrapolated from the average time between the recent
+    /// blocks.
+    async fn get_chain_schedule(&mut self) -> Result<ChainSchedule, Error> {
+        self.blockchain
+            .read()
+            .chain_schedule(Self::SCHEDULE_SAMPLE_BLOCKS)
+            .map(ChainSchedule::from)
+            .map_err(Error::from)
+    }
+
+    /// Simulates the slot assignment of an election with the given validators and stakes, so
+    /// potential validators can estimate how many slots they would get. If no seed is given, the
+    /// seed of the head block is used.
+    async fn simulate_election(
+        &mut self,
+        seed: Option<VrfSeed>,
+        validators: HashMap<Address, Coin>,
+    ) -> Result<Vec<SimulatedSlots>, Error> {
+        // The election orders the validators by their addresses.
+        let validators: BTreeMap<Address, Coin> = validators.into_iter().collect();
+        let stakes: Vec<u64> = validators.values().map(|stake| u64::from(*stake)).collect();
+
+        let total_stake = stakes
+            .iter()
+            .try_fold(0u64, |total, stake| total.checked_add(*stake))
+            .filter(|total| *total > 0 && *total <= policy::TOTAL_SUPPLY)
+            .ok_or(Error::InvalidValidatorSet)?;
+
+        let seed = seed.unwrap_or_else(|| self.blockchain.read().head().seed().clone());
+
+        let mut num_slots = vec![0u16; validators.len()];
+        for index in StakingContract::assign_slots(stakes, &seed) {
+            num_slots[index] += 1;
+        }
+
+        Ok(validators
+            .into_iter()
+            .zip(num_slots)
+            .map(|((address, stake), num_slots)| SimulatedSlots {
+                address,
+                stake,
+                num_slots,
+                expected_slots: u64::from(stake) as f64 / total_stake as f64
+                    * f64::from(policy::SLOTS),
+            })
+            .collect())
+    }
+
+    /// Returns a payment receipt for the transaction with the given hash, serialized as hex. The
+    /// receipt proves that the transaction was included in a block and can be verified offline.
+    async fn get_payment_receipt(&mut self, hash: Blake2bHash) -> Result<String, Error> {
+        let receipt = self.blockchain.read().create_payment_receipt(&hash)?;
+        Ok(hex::encode(receipt.serialize_to_vec()))
+    }
+
+    /// Verifies a serialized payment receipt for the transaction with the given hash and returns
+    /// the transaction. This doesn't check that the block of the receipt is part of our chain.
+    async fn verify_payment_receipt(
+        &mut self,
+        hash: Blake2bHash,
+        receipt: String,
+    ) -> Result<Transaction, Error> {
+        let receipt: PaymentReceipt = Deserialize::deserialize_from_vec(&hex::decode(&receipt)?)?;
+        let ext_tx = receipt.verify(&hash)?.clone();
+
+        let block_number = ext_tx.block_number;
+        let timestamp = ext_tx.block_time;
+        let tx = ext_tx
+            .into_transaction()
+            .map_err(|_| Error::TransactionNotFound(hash))?;
+
+        Ok(Transaction::from_blockchain(
+            tx,
+            block_number,
+            timestamp,
+            self.blockchain.read().block_number(),
+        ))
+    }
+
+    /// Returns the durations of database transactions and commits, the amount of data committed and
+    /// the usage of the memory map since the client was started.
+    async fn get_database_metrics(&mut self) -> Result<DatabaseMetrics, Error> {
+        let metrics = self.blockchain.read().database_metrics();
+
+        Ok(DatabaseMetrics {
+            read_transactions: duration_histogram(metrics.read_transactions),
+            write_transactions: duration_histogram(metrics.write_transactions),
+            commits: duration_histogram(metrics.commits),
+            bytes_committed: metrics.bytes_committed,
+            largest_commit: metrics.largest_commit,
+            slow_commits: metrics.slow_commits,
+            map_size: metrics.map_size as u64,
+            map_used: metrics.map_used as u64,
+        })
+    }
+
+    /// Returns the hits and misses of the cache of uncompressed BLS public keys since the client
+    /// was started.
+    async fn get_public_key_cache_stats(&mut self) -> Result<PublicKeyCacheStats, Error> {
+        let stats = PublicKeyCache::global().stats();
+
+        Ok(PublicKeyCacheStats {
+            hits: stats.hits as u64,
+            misses: stats.misses as u64,
+            size: stats.len as u64,
+            capacity: stats.capacity as u64,
+        })
+    }
+
     /// Subscribes to blockchain events.
     #[stream]
     async fn head_subscribe(&mut self) -> Result<BoxStream<'static, Blake2bHash>, Error> {
@@ -495,4 +849,256 @@ impl BlockchainInterface for BlockchainDispatcher {
             })
             .boxed())
     }
+
+    /// Subscribes to the changes of the main chain. Every block added to the main chain is assigned
+    /// a cursor. Blocks reverted by a rebranch are announced by revert events that reference the
+    /// cursor the block was added with.
+    #[stream]
+    async fn chain_subscribe(&mut self) -> Result<BoxStream<'static, ChainEvent>, Error> {
+        let (stream, mut sequencer) = {
+            let mut blockchain = self.blockchain.write();
+            let sequencer = EventSequencer::new(blockchain.block_number());
+            (blockchain.notifier.as_stream(), sequencer)
+        };
+
+        Ok(stream
+            .flat_map(move |event| {
+                let events = sequencer.sequence(&event);
+                stream::iter(events.into_iter().map(ChainEvent::from))
+            })
+            .boxed())
+    }
+
+    /// Streams the transactions and inherents of the finalized blocks, starting with the first
+    /// block of the given epoch. Once the existing history has been sent, the entries of each
+    /// batch are sent when the batch is finalized.
+    #[stream]
+    async fn follow_history(
+        &mut self,
+        from_epoch: u32,
+    ) -> Result<BoxStream<'static, ExtendedTransaction>, Error> {
+        // Subscribe before reading the history, so that no finalized batch is missed.
+        let events = self.blockchain.write().notifier.as_stream().boxed();
+
+        let follower = HistoryFollower {
+            blockchain: Arc::clone(&self.blockchain),
+            events,
+            next_block: policy::first_block_of(from_epoch.max(1)),
+            pending: VecDeque::new(),
+        };
+        Ok(stream::unfold(follower, HistoryFollower::next).boxed())
+    }
+}
+
+/// The state of a `followHistory` stream.
+struct HistoryFollower {
+    blockchain: Arc<BlockchainLock>,
+    events: BoxStream<'static, BlockchainEvent>,
+    /// The first block whose entries haven't been read yet.
+    next_block: u32,
+    pending: VecDeque<ExtendedTransaction>,
+}
+
+impl HistoryFollower {
+    async fn next(mut self) -> Option<(ExtendedTransaction, Self)> {
+        loop {
+            if let Some(entry) = self.pending.pop_front() {
+                return Some((entry, self));
+            }
+
+            if !self.read_finalized_batch() {
+                // Wait for the blockchain to change, one of the events finalizes the next batch.
+                self.events.next().await?;
+            }
+        }
+    }
+
+    /// Reads the entries of the batch of `next_block` into `pending`, one batch at a time so
+    /// that the whole history is never loaded at once. Returns false if the batch isn't
+    /// finalized yet.
+    fn read_finalized_batch(&mut self) -> bool {
+        let blockchain = self.blockchain.read();
+        if self.next_block > blockchain.macro_head().header.block_number {
+            return false;
+        }
+
+        let last_block = policy::macro_block_of(policy::batch_at(self.next_block));
+        let head_height = blockchain.block_number();
+        let db_txn = blockchain.read_transaction();
+        for block_number in self.next_block..=last_block {
+            for ext_tx in blockchain
+                .history_store
+                .get_block_transactions(block_number, Some(&db_txn))
+            {
+                self.pending
+                    .push_back(ExtendedTransaction::from_blockchain(ext_tx, head_height));
+            }
+        }
+
+        self.next_block = last_block + 1;
+        true
+    }
+}
+
+/// Returns the change of the balance of the address caused by the transaction, in Luna. The fee is
+/// charged to the sender independently of the value, so transactions that don't transfer any value
+/// still reduce the balance of the sender.
+fn balance_change(tx: &nimiq_transaction::Transaction, address: &Address) -> i64 {
+    let mut change = 0i64;
+    if &tx.recipient == address {
+        change += u64::from(tx.value) as i64;
+    }
+    if &tx.sender == address {
+        change -= u64::from(tx.value) as i64;
+        change -= u64::from(tx.fee) as i64;
+    }
+    change
+}
+
+fn duration_histogram(histogram: DurationHistogramSnapshot) -> DurationHistogram {
+    DurationHistogram {
+        bucket_bounds_ms: DURATION_BUCKETS_MS.to_vec(),
+        buckets: histogram.buckets,
+        count: histogram.count,
+        total_us: histogram.total.as_micros() as u64,
+        max_us: histogram.max.as_micros() as u64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nimiq_block_production::BlockProducer;
+    use nimiq_blockchain::{Blockchain, PushResult, ScheduleError};
+    use nimiq_database::volatile::VolatileEnvironment;
+    use nimiq_primitives::networks::NetworkId;
+    use nimiq_test_utils::blockchain::{produce_macro_blocks, signing_key, voting_key};
+    use nimiq_utils::time::OffsetTime;
+
+    use super::*;
+
+    fn test_blockchain() -> Arc<BlockchainLock> {
+        let env = VolatileEnvironment::new(10).unwrap();
+        let time = Arc::new(OffsetTime::new());
+        Arc::new(BlockchainLock::new(
+            Blockchain::new(env, NetworkId::UnitAlbatross, time).unwrap(),
+        ))
+    }
+
+    #[test]
+    fn balance_change_includes_the_fee() {
+        let address = Address::from([1u8; 20]);
+        let other = Address::from([2u8; 20]);
+        let tx = |sender: &Address, recipient: &Address, value: u64, fee: u64| {
+            nimiq_transaction::Transaction::new_basic(
+                sender.clone(),
+                recipient.clone(),
+                Coin::from_u64_unchecked(value),
+                Coin::from_u64_unchecked(fee),
+                1,
+                NetworkId::UnitAlbatross,
+            )
+        };
+
+        assert_eq!(balance_change(&tx(&other, &address, 100, 1), &address), 100);
+        assert_eq!(
+            balance_change(&tx(&address, &other, 100, 1), &address),
+            -101
+        );
+        // Transactions without value still pay the fee.
+        assert_eq!(balance_change(&tx(&address, &other, 0, 1), &address), -1);
+        // Transactions to oneself only pay the fee.
+        assert_eq!(
+            balance_change(&tx(&address, &address, 100, 1), &address),
+            -1
+        );
+    }
+
+    #[tokio::test]
+    async fn follow_history_streams_finalized_batches() {
+        let producer = BlockProducer::new(signing_key(), voting_key());
+        let blockchain = test_blockchain();
+        let batch_len = |batch: u32| -> usize {
+            let blockchain = blockchain.read();
+            (policy::first_block_of_batch(batch)..=policy::macro_block_of(batch))
+                .map(|block_number| {
+                    blockchain
+                        .history_store
+                        .get_block_transactions(block_number, None)
+                        .len()
+                })
+                .sum()
+        };
+        let block_number = |entry: &ExtendedTransaction| match entry {
+            ExtendedTransaction::Transaction(tx) => tx.block_number.unwrap(),
+            ExtendedTransaction::Inherent(inherent) => inherent.block_number,
+        };
+
+        // The first batch has no rewards, so its history is empty.
+        produce_macro_blocks(&producer, &blockchain, 2);
+        let mut dispatcher = BlockchainDispatcher::new(Arc::clone(&blockchain));
+        let mut history = dispatcher.follow_history(1).await.unwrap();
+
+        // The history that is already finalized is sent right away.
+        let len = batch_len(1) + batch_len(2);
+        assert!(len > 0);
+        for _ in 0..len {
+            let entry = history.next().await.unwrap();
+            assert!(policy::batch_at(block_number(&entry)) <= 2);
+        }
+
+        // The entries of the next batch are sent once it is finalized.
+        produce_macro_blocks(&producer, &blockchain, 1);
+        let len = batch_len(3);
+        assert!(len > 0);
+        for _ in 0..len {
+            let entry = history.next().await.unwrap();
+            assert_eq!(policy::batch_at(block_number(&entry)), 3);
+        }
+    }
+
+    #[tokio::test]
+    async fn chain_schedule_fails_without_the_sample_blocks() {
+        let num_epochs = BlockchainDispatcher::SCHEDULE_SAMPLE_BLOCKS / policy::EPOCH_LENGTH + 1;
+        let producer = BlockProducer::new(signing_key(), voting_key());
+        let blockchain = test_blockchain();
+        produce_macro_blocks(
+            &producer,
+            &blockchain,
+            (num_epochs * policy::BATCHES_PER_EPOCH as u32) as usize,
+        );
+
+        // A node that synced the history only stores the election blocks.
+        let history_synced = test_blockchain();
+        for epoch in 1..=num_epochs {
+            let (block, transactions) = {
+                let blockchain = blockchain.read();
+                let block = blockchain
+                    .chain_store
+                    .get_block_at(epoch * policy::EPOCH_LENGTH, true, None)
+                    .unwrap();
+                let transactions = blockchain.history_store.get_epoch_transactions(epoch, None);
+                (block, transactions)
+            };
+            assert_eq!(
+                Blockchain::push_history_sync(
+                    history_synced.upgradable_read(),
+                    block,
+                    &transactions
+                ),
+                Ok(PushResult::Extended)
+            );
+        }
+
+        let mut dispatcher = BlockchainDispatcher::new(blockchain);
+        assert!(dispatcher.get_chain_schedule().await.is_ok());
+
+        let first_block_number =
+            num_epochs * policy::EPOCH_LENGTH - BlockchainDispatcher::SCHEDULE_SAMPLE_BLOCKS;
+        let mut dispatcher = BlockchainDispatcher::new(history_synced);
+        assert!(matches!(
+            dispatcher.get_chain_schedule().await,
+            Err(Error::Schedule(ScheduleError::MissingBlock(block_number)))
+                if block_number == first_block_number
+        ));
+    }
 }
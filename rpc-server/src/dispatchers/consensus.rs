@@ -1,29 +1,38 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use async_trait::async_trait;
+use futures::stream::{BoxStream, StreamExt};
 use parking_lot::RwLock;
 
 use beserial::{Deserialize, Serialize};
 use nimiq_blockchain::AbstractBlockchain;
 use nimiq_bls::{KeyPair as BlsKeyPair, SecretKey as BlsSecretKey};
-use nimiq_consensus::ConsensusProxy;
+use nimiq_consensus::{ConsensusProxy, ConsensusState};
 use nimiq_hash::{Blake2bHash, Hash};
-use nimiq_keys::{Address, KeyPair, PrivateKey, PublicKey};
+use nimiq_keys::{Address, KeyPair, PrivateKey, PublicKey, Signature};
 use nimiq_network_libp2p::Network;
-use nimiq_primitives::{coin::Coin, networks::NetworkId};
+use nimiq_primitives::{coin::Coin, networks::NetworkId, payment_uri::PaymentUri, policy};
 use nimiq_rpc_interface::{
     consensus::ConsensusInterface,
-    types::{Transaction as RPCTransaction, ValidityStartHeight},
+    types::{
+        ColdStakingStatus, ColdStakingTransaction, ConsensusState as RPCConsensusState,
+        Transaction as RPCTransaction, ValidityStartHeight, WalletTransactionEvent,
+    },
 };
 use nimiq_transaction::account::htlc_contract::{AnyHash, HashAlgorithm};
 use nimiq_transaction::{SignatureProof, Transaction};
 use nimiq_transaction_builder::TransactionBuilder;
 
-use crate::{error::Error, wallets::UnlockedWallets};
+use crate::{error::Error, resubmission::TransactionResubmitter, wallets::UnlockedWallets};
 
 pub struct ConsensusDispatcher {
     consensus: ConsensusProxy<Network>,
     unlocked_wallets: Option<Arc<RwLock<UnlockedWallets>>>,
+    /// Staker transactions that wait for the signature of an offline staker key.
+    cold_staking: HashMap<Blake2bHash, ColdStakingTransaction>,
+    /// Tracks the basic transactions sent from unlocked wallets until they are included.
+    resubmitter: Option<Arc<TransactionResubmitter>>,
 }
 
 impl ConsensusDispatcher {
@@ -34,9 +43,29 @@ impl ConsensusDispatcher {
         Self {
             consensus,
             unlocked_wallets,
+            cold_staking: HashMap::new(),
+            resubmitter: None,
         }
     }
 
+    /// Tracks the basic transactions sent from unlocked wallets with the given resubmitter.
+    pub fn with_resubmitter(mut self, resubmitter: Arc<TransactionResubmitter>) -> Self {
+        self.resubmitter = Some(resubmitter);
+        self
+    }
+
+    /// Sends a transaction signed by an unlocked wallet and tracks it for resubmission.
+    async fn send_wallet_transaction(&mut self, raw_tx: String) -> Result<Blake2bHash, Error> {
+        let txid = self.send_raw_transaction(raw_tx.clone()).await?;
+
+        if let Some(resubmitter) = &self.resubmitter {
+            let tx: Transaction = Deserialize::deserialize_from_vec(&hex::decode(&raw_tx)?)?;
+            resubmitter.track(tx);
+        }
+
+        Ok(txid)
+    }
+
     /// Tries to fetch the key pair for the wallet with the given address.
     fn get_wallet_keypair(&self, address: &Address) -> Result<KeyPair, Error> {
         Ok(self
@@ -60,6 +89,47 @@ impl ConsensusDispatcher {
     fn validity_start_height(&self, validity_start_height: ValidityStartHeight) -> u32 {
         validity_start_height.block_number(self.consensus.blockchain.read().block_number())
     }
+
+    /// Removes the cold staking transactions that can't be included in the chain anymore.
+    fn prune_cold_staking_transactions(&mut self) {
+        let block_number = self.consensus.blockchain.read().block_number();
+        self.cold_staking.retain(|_, cold_tx| {
+            block_number < cold_tx.validity_start_height + policy::TRANSACTION_VALIDITY_WINDOW
+        });
+    }
+
+    /// Stores an unsigned staker transaction until the staker signature is provided.
+    fn add_cold_staking_transaction(
+        &mut self,
+        transaction: Transaction,
+        staker_address: Address,
+        sender_wallet: Option<Address>,
+        delegation: Option<Address>,
+    ) -> ColdStakingTransaction {
+        self.prune_cold_staking_transactions();
+
+        let cold_tx = ColdStakingTransaction {
+            id: transaction.hash(),
+            staker_address,
+            sender_wallet,
+            delegation,
+            transaction: transaction_to_hex_string(&transaction),
+            signing_payload: hex::encode(transaction.serialize_content()),
+            validity_start_height: transaction.validity_start_height,
+            status: ColdStakingStatus::AwaitingSignature,
+        };
+        self.cold_staking
+            .insert(cold_tx.id.clone(), cold_tx.clone());
+        cold_tx
+    }
+}
+
+fn consensus_state_to_rpc(state: ConsensusState) -> RPCConsensusState {
+    match state {
+        ConsensusState::WaitingForPeers => RPCConsensusState::WaitingForPeers,
+        ConsensusState::Syncing => RPCConsensusState::Syncing,
+        ConsensusState::Established => RPCConsensusState::Established,
+    }
 }
 
 fn transaction_to_hex_string(transaction: &Transaction) -> String {
@@ -76,6 +146,29 @@ impl ConsensusInterface for ConsensusDispatcher {
         Ok(self.consensus.is_established())
     }
 
+    /// Returns the consensus state of the node.
+    async fn get_consensus_state(&mut self) -> Result<RPCConsensusState, Self::Error> {
+        Ok(consensus_state_to_rpc(self.consensus.state()))
+    }
+
+    /// Subscribes to consensus state changes. The new state is sent whenever consensus is
+    /// established or lost.
+    #[stream]
+    async fn consensus_subscribe(
+        &mut self,
+    ) -> Result<BoxStream<'static, RPCConsensusState>, Self::Error> {
+        let consensus = self.consensus.clone();
+        Ok(consensus
+            .subscribe_events()
+            .filter_map(move |event| {
+                let state = event
+                    .ok()
+                    .map(|_| consensus_state_to_rpc(consensus.state()));
+                async move { state }
+            })
+            .boxed())
+    }
+
     /// Given a serialized transaction, it will return the corresponding transaction struct.
     async fn get_raw_transaction_info(&mut self, raw_tx: String) -> Result<RPCTransaction, Error> {
         let transaction: Transaction = Deserialize::deserialize_from_vec(&hex::decode(&raw_tx)?)?;
@@ -93,6 +186,18 @@ impl ConsensusInterface for ConsensusDispatcher {
         }
     }
 
+    /// Subscribes to the events of the basic transactions sent from unlocked wallets: whether they
+    /// were included, are about to expire, or were resubmitted after expiring.
+    #[stream]
+    async fn wallet_transactions_subscribe(
+        &mut self,
+    ) -> Result<BoxStream<'static, WalletTransactionEvent>, Self::Error> {
+        Ok(match &self.resubmitter {
+            Some(resubmitter) => resubmitter.subscribe(),
+            None => futures::stream::empty().boxed(),
+        })
+    }
+
     /// Returns a serialized basic transaction.
     async fn create_basic_transaction(
         &mut self,
@@ -126,7 +231,7 @@ impl ConsensusInterface for ConsensusDispatcher {
         let raw_tx = self
             .create_basic_transaction(wallet, recipient, value, fee, validity_start_height)
             .await?;
-        self.send_raw_transaction(raw_tx).await
+        self.send_wallet_transaction(raw_tx).await
     }
 
     /// Returns a serialized basic transaction with an arbitrary data field.
@@ -172,7 +277,71 @@ impl ConsensusInterface for ConsensusDispatcher {
                 validity_start_height,
             )
             .await?;
-        self.send_raw_transaction(raw_tx).await
+        self.send_wallet_transaction(raw_tx).await
+    }
+
+    /// Returns a serialized transaction paying the given payment request URI. The message of the
+    /// request is put into the data field of the transaction. The value defaults to the amount of
+    /// the request. Expired requests are rejected.
+    async fn create_payment_request_transaction(
+        &mut self,
+        wallet: Address,
+        uri: String,
+        value: Option<Coin>,
+        fee: Coin,
+        validity_start_height: ValidityStartHeight,
+    ) -> Result<String, Self::Error> {
+        let request: PaymentUri = uri.parse()?;
+
+        if let Some(expires) = request.expires {
+            let now = self.consensus.blockchain.read().time.now() / 1000;
+            if request.is_expired(now) {
+                return Err(Error::PaymentRequestExpired(expires));
+            }
+        }
+
+        let value = value
+            .or(request.amount)
+            .ok_or(Error::PaymentRequestWithoutAmount)?;
+
+        match request.message {
+            Some(message) => {
+                self.create_basic_transaction_with_data(
+                    wallet,
+                    request.recipient,
+                    message.into_bytes(),
+                    value,
+                    fee,
+                    validity_start_height,
+                )
+                .await
+            }
+            None => {
+                self.create_basic_transaction(
+                    wallet,
+                    request.recipient,
+                    value,
+                    fee,
+                    validity_start_height,
+                )
+                .await
+            }
+        }
+    }
+
+    /// Pays the given payment request URI, see `create_payment_request_transaction`.
+    async fn send_payment_request_transaction(
+        &mut self,
+        wallet: Address,
+        uri: String,
+        value: Option<Coin>,
+        fee: Coin,
+        validity_start_height: ValidityStartHeight,
+    ) -> Result<Blake2bHash, Self::Error> {
+        let raw_tx = self
+            .create_payment_request_transaction(wallet, uri, value, fee, validity_start_height)
+            .await?;
+        self.send_wallet_transaction(raw_tx).await
     }
 
     /// Returns a serialized transaction creating a new vesting contract.
@@ -713,6 +882,152 @@ impl ConsensusInterface for ConsensusDispatcher {
         self.send_raw_transaction(raw_tx).await
     }
 
+    /// Creates a `new_staker` transaction for a staker whose key is kept offline. The transaction
+    /// has to be signed with the staker key and then sent using `send_cold_staking_transaction`.
+    /// The initial stake and the transaction fee are paid from the `sender_wallet`.
+    async fn create_cold_new_staker_transaction(
+        &mut self,
+        sender_wallet: Address,
+        staker_address: Address,
+        delegation: Option<Address>,
+        value: Coin,
+        fee: Coin,
+        validity_start_height: ValidityStartHeight,
+    ) -> Result<ColdStakingTransaction, Error> {
+        // Make sure the sender wallet can sign the transaction later.
+        self.get_wallet_keypair(&sender_wallet)?;
+
+        let transaction = TransactionBuilder::new_unsigned_create_staker(
+            sender_wallet.clone(),
+            delegation.clone(),
+            value,
+            fee,
+            self.validity_start_height(validity_start_height),
+            self.get_network_id(),
+        );
+
+        Ok(self.add_cold_staking_transaction(
+            transaction,
+            staker_address,
+            Some(sender_wallet),
+            delegation,
+        ))
+    }
+
+    /// Creates an `update_staker` transaction for a staker whose key is kept offline. The
+    /// transaction has to be signed with the staker key and then sent using
+    /// `send_cold_staking_transaction`. The transaction fee is paid from the `sender_wallet`.
+    async fn create_cold_update_transaction(
+        &mut self,
+        sender_wallet: Address,
+        staker_address: Address,
+        new_delegation: Option<Address>,
+        fee: Coin,
+        validity_start_height: ValidityStartHeight,
+    ) -> Result<ColdStakingTransaction, Error> {
+        self.get_wallet_keypair(&sender_wallet)?;
+
+        let transaction = TransactionBuilder::new_unsigned_update_staker(
+            sender_wallet.clone(),
+            new_delegation.clone(),
+            fee,
+            self.validity_start_height(validity_start_height),
+            self.get_network_id(),
+        );
+
+        Ok(self.add_cold_staking_transaction(
+            transaction,
+            staker_address,
+            Some(sender_wallet),
+            new_delegation,
+        ))
+    }
+
+    /// Creates an `unstake` transaction for a staker whose key is kept offline. The transaction
+    /// has to be signed with the staker key and then sent using `send_cold_staking_transaction`.
+    /// The transaction fee will be paid from the funds being unstaked.
+    async fn create_cold_unstake_transaction(
+        &mut self,
+        staker_address: Address,
+        recipient: Address,
+        value: Coin,
+        fee: Coin,
+        validity_start_height: ValidityStartHeight,
+    ) -> Result<ColdStakingTransaction, Error> {
+        let transaction = TransactionBuilder::new_unsigned_unstake(
+            recipient,
+            value,
+            fee,
+            self.validity_start_height(validity_start_height),
+            self.get_network_id(),
+        );
+
+        Ok(self.add_cold_staking_transaction(transaction, staker_address, None, None))
+    }
+
+    /// Adds the signature of the offline staker key to a cold staking transaction and sends it to
+    /// the network. The signature has to be over the `signingPayload` of the transaction.
+    async fn send_cold_staking_transaction(
+        &mut self,
+        id: Blake2bHash,
+        staker_public_key: PublicKey,
+        staker_signature: Signature,
+    ) -> Result<Blake2bHash, Error> {
+        let cold_tx = self
+            .cold_staking
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| Error::ColdStakingTransactionNotFound(id.clone()))?;
+
+        let staker_proof = SignatureProof::from(staker_public_key, staker_signature);
+        if staker_proof.compute_signer() != cold_tx.staker_address {
+            return Err(Error::WrongStakerKey(cold_tx.staker_address));
+        }
+
+        let sender_key = match &cold_tx.sender_wallet {
+            None => None,
+            Some(address) => Some(self.get_wallet_keypair(address)?),
+        };
+
+        let transaction: Transaction =
+            Deserialize::deserialize_from_vec(&hex::decode(&cold_tx.transaction)?)?;
+        let transaction = TransactionBuilder::sign_staker_transaction(
+            transaction,
+            staker_proof,
+            sender_key.as_ref(),
+        )?;
+
+        let hash = self
+            .send_raw_transaction(transaction_to_hex_string(&transaction))
+            .await?;
+
+        if let Some(cold_tx) = self.cold_staking.get_mut(&id) {
+            cold_tx.status = ColdStakingStatus::Sent { hash: hash.clone() };
+        }
+
+        Ok(hash)
+    }
+
+    /// Returns the cold staking transactions that are still valid, optionally only those of the
+    /// given staker.
+    async fn get_cold_staking_transactions(
+        &mut self,
+        staker_address: Option<Address>,
+    ) -> Result<Vec<ColdStakingTransaction>, Error> {
+        self.prune_cold_staking_transactions();
+
+        Ok(self
+            .cold_staking
+            .values()
+            .filter(|cold_tx| {
+                staker_address
+                    .as_ref()
+                    .map_or(true, |address| cold_tx.staker_address == *address)
+            })
+            .cloned()
+            .collect())
+    }
+
     /// Returns a serialized `new_validator` transaction. You need to provide the address of a basic
     /// account (the sender wallet) to pay the transaction fee and the validator deposit.
     ///  Since JSON doesn't have a primitive for Option (it just has the null primitive), we can't
@@ -0,0 +1,148 @@
+use async_trait::async_trait;
+use serde_json::Value;
+use tokio::sync::mpsc;
+
+use nimiq_jsonrpc_core::{Request, Response, RpcError};
+use nimiq_jsonrpc_server::{Dispatcher, Message};
+use nimiq_rpc_interface::types::{AllocatorStats, MemoryStats, TaggedAllocations};
+use nimiq_utils::memory::{self, MemoryAccounting};
+
+use crate::dispatchers::admin::subsystem_usage;
+
+/// Dispatches the `debug_*` methods. Their names don't follow the camel case naming of the other
+/// methods, so they are dispatched by hand instead of with `nimiq_jsonrpc_derive::service`.
+pub struct DebugDispatcher {
+    memory: MemoryAccounting,
+}
+
+impl DebugDispatcher {
+    /// Methods served by this dispatcher.
+    const METHODS: &'static [&'static str] = &["debug_memoryStats"];
+
+    pub fn new(memory: MemoryAccounting) -> Self {
+        DebugDispatcher { memory }
+    }
+
+    /// Returns the statistics of the allocator, if the node was built with the `jemalloc`
+    /// feature, and the memory held by the subsystems of the node, in bytes.
+    fn memory_stats(&self) -> MemoryStats {
+        let allocator = memory::AllocatorStats::read().map(|stats| AllocatorStats {
+            allocated: stats.allocated as u64,
+            active: stats.active as u64,
+            metadata: stats.metadata as u64,
+            resident: stats.resident as u64,
+            mapped: stats.mapped as u64,
+            retained: stats.retained as u64,
+        });
+
+        // The counters are only maintained if the tagging allocator is installed, which
+        // comes with the allocator statistics.
+        let allocations = if allocator.is_some() {
+            memory::allocation_counters()
+                .into_iter()
+                .map(|counters| TaggedAllocations {
+                    subsystem: counters.tag.name().to_string(),
+                    allocations: counters.allocations,
+                    allocated: counters.allocated,
+                    freed: counters.freed,
+                })
+                .collect()
+        } else {
+            vec![]
+        };
+
+        MemoryStats {
+            allocator,
+            subsystems: subsystem_usage(&self.memory),
+            allocations,
+        }
+    }
+}
+
+#[async_trait]
+impl Dispatcher for DebugDispatcher {
+    async fn dispatch(
+        &mut self,
+        request: Request,
+        _tx: Option<&mpsc::Sender<Message>>,
+        _id: u64,
+    ) -> Option<Response> {
+        // Requests without an ID are notifications and don't get a response.
+        let id = request.id?;
+
+        let result = match request.method.as_str() {
+            "debug_memoryStats" => serde_json::to_value(self.memory_stats()),
+            _ => return Some(Response::new_error(id, RpcError::method_not_found(None))),
+        };
+
+        Some(match result {
+            Ok(value) => Response::new_success(id, value),
+            Err(e) => Response::new_error(
+                id,
+                RpcError::internal_error(Some(Value::String(e.to_string()))),
+            ),
+        })
+    }
+
+    fn match_method(&self, name: &str) -> bool {
+        Self::METHODS.contains(&name)
+    }
+
+    fn method_names(&self) -> Vec<&str> {
+        Self::METHODS.to_vec()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use nimiq_utils::memory::MemoryGauge;
+
+    use super::*;
+
+    fn request(method: &str) -> Request {
+        serde_json::from_value(json!({
+            "jsonrpc": "2.0",
+            "method": method,
+            "id": 1,
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn it_reports_the_memory_stats() {
+        let memory = MemoryAccounting::new();
+        let gauge = MemoryGauge::new();
+        gauge.add(42);
+        memory.register("mempool", gauge);
+        let mut dispatcher = DebugDispatcher::new(memory);
+
+        assert!(dispatcher.match_method("debug_memoryStats"));
+        let response = dispatcher
+            .dispatch(request("debug_memoryStats"), None, 1)
+            .await
+            .unwrap();
+        let response = serde_json::to_value(response).unwrap();
+
+        assert_eq!(
+            response["result"]["subsystems"],
+            json!([{ "name": "mempool", "used": 42, "cap": null }])
+        );
+    }
+
+    #[tokio::test]
+    async fn it_only_serves_the_debug_methods() {
+        let mut dispatcher = DebugDispatcher::new(MemoryAccounting::new());
+
+        assert!(!dispatcher.match_method("getMemoryStats"));
+        let response = dispatcher
+            .dispatch(request("getMemoryStats"), None, 1)
+            .await
+            .unwrap();
+        let response = serde_json::to_value(response).unwrap();
+
+        assert!(response.get("result").map_or(true, Value::is_null));
+        assert!(!response["error"].is_null());
+    }
+}
@@ -8,7 +8,7 @@ use nimiq_hash::{Blake2bHash, Hash};
 use nimiq_mempool::mempool::Mempool;
 
 use nimiq_rpc_interface::mempool::MempoolInterface;
-use nimiq_rpc_interface::types::{HashOrTx, MempoolInfo, Transaction};
+use nimiq_rpc_interface::types::{HashOrTx, InclusionStats, MempoolInfo, Transaction};
 
 use crate::error::Error;
 
@@ -114,4 +114,37 @@ impl MempoolInterface for MempoolDispatcher {
     async fn get_min_fee_per_byte(&mut self) -> Result<f64, Self::Error> {
         Ok(self.mempool.get_rules().tx_fee_per_byte)
     }
+
+    /// Returns the minimum fee per byte of transactions this node relays.
+    async fn get_min_relay_fee_per_byte(&mut self) -> Result<f64, Self::Error> {
+        Ok(self.mempool.get_min_relay_fee_per_byte())
+    }
+
+    /// Sets the minimum fee per byte of transactions this node relays. Transactions received via
+    /// gossip that pay less are rejected.
+    async fn set_min_relay_fee_per_byte(&mut self, fee_per_byte: f64) -> Result<(), Self::Error> {
+        Ok(self.mempool.set_min_relay_fee_per_byte(fee_per_byte)?)
+    }
+
+    /// Returns the hashes of the transactions that the next block is expected to include. This is
+    /// empty if the inclusion list is disabled.
+    async fn get_inclusion_list(&mut self) -> Result<Vec<Blake2bHash>, Self::Error> {
+        Ok(self.mempool.get_inclusion_list())
+    }
+
+    /// Returns for every validator how many transactions from the inclusion list it included and
+    /// omitted in the blocks it produced.
+    async fn get_inclusion_stats(&mut self) -> Result<Vec<InclusionStats>, Self::Error> {
+        Ok(self
+            .mempool
+            .get_inclusion_stats()
+            .into_iter()
+            .map(|(validator, stats)| InclusionStats {
+                validator,
+                blocks: stats.blocks,
+                included: stats.included,
+                omitted: stats.omitted,
+            })
+            .collect())
+    }
 }
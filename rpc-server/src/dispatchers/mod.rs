@@ -1,12 +1,16 @@
+pub use admin::AdminDispatcher;
 pub use blockchain::BlockchainDispatcher;
 pub use consensus::ConsensusDispatcher;
+pub use debug::DebugDispatcher;
 pub use mempool::MempoolDispatcher;
 pub use network::NetworkDispatcher;
 pub use validator::ValidatorDispatcher;
 pub use wallet::WalletDispatcher;
 
+mod admin;
 mod blockchain;
 mod consensus;
+mod debug;
 mod mempool;
 mod network;
 mod validator;
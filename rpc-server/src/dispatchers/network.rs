@@ -1,20 +1,77 @@
 use std::sync::Arc;
+use std::time::Duration;
 
 use async_trait::async_trait;
+use futures::StreamExt;
 
-use nimiq_network_interface::network::Network as InterfaceNetwork;
-use nimiq_network_libp2p::Network;
-use nimiq_rpc_interface::network::NetworkInterface;
+use nimiq_account::StakingContract;
+use nimiq_blockchain::BlockchainLock;
+use nimiq_keys::Address;
+use nimiq_network_interface::message::registry;
+use nimiq_network_interface::network::{Network as InterfaceNetwork, NetworkEvent, OverflowPolicy};
+use nimiq_network_libp2p::{Multiaddr, Network, PeerId};
+use nimiq_rpc_interface::{
+    network::NetworkInterface,
+    types::{
+        ConnectivityCheck, GossipTopicStats, NetworkRegistry, PeerStats, RegisteredMessage,
+        RegisteredTopic, ValidatorResolution,
+    },
+};
+use nimiq_validator_network::validator_record::SignedValidatorRecord;
 
 use crate::error::Error;
 
 pub struct NetworkDispatcher {
     network: Arc<Network>,
+    blockchain: Arc<BlockchainLock>,
+    registry: Option<registry::NetworkRegistry>,
 }
 
 impl NetworkDispatcher {
-    pub fn new(network: Arc<Network>) -> Self {
-        NetworkDispatcher { network }
+    /// Time to wait for a dialed peer to join when checking its connectivity.
+    const DIAL_TIMEOUT: Duration = Duration::from_secs(5);
+
+    pub fn new(network: Arc<Network>, blockchain: Arc<BlockchainLock>) -> Self {
+        NetworkDispatcher {
+            network,
+            blockchain,
+            registry: None,
+        }
+    }
+
+    /// Sets the registry of network messages and topics returned by `getNetworkRegistry`.
+    pub fn with_registry(mut self, registry: registry::NetworkRegistry) -> Self {
+        self.registry = Some(registry);
+        self
+    }
+
+    /// Checks whether we are connected to the peer, dialing it if we aren't.
+    async fn check_connectivity(&self, peer_id: PeerId) -> ConnectivityCheck {
+        let (peers, mut events) = self.network.get_peer_updates();
+        if peers.iter().any(|peer| peer.id == peer_id) {
+            return ConnectivityCheck::Connected;
+        }
+
+        if let Err(error) = self.network.dial_peer(peer_id).await {
+            return ConnectivityCheck::Unreachable(error.to_string());
+        }
+
+        let joined = async {
+            while let Some(event) = events.next().await {
+                if let Ok(NetworkEvent::PeerJoined(peer)) = event {
+                    if peer.id == peer_id {
+                        return true;
+                    }
+                }
+            }
+            false
+        };
+
+        match tokio::time::timeout(Self::DIAL_TIMEOUT, joined).await {
+            Ok(true) => ConnectivityCheck::Dialed,
+            Ok(false) => ConnectivityCheck::Unreachable("Network is offline".to_string()),
+            Err(_) => ConnectivityCheck::Unreachable("Timed out".to_string()),
+        }
     }
 }
 
@@ -42,4 +99,275 @@ impl NetworkInterface for NetworkDispatcher {
             .map(|peer| peer.id.to_string())
             .collect())
     }
+
+    /// Returns the latency and clock statistics of a connected peer.
+    async fn get_peer_stats(&mut self, peer_id: String) -> Result<PeerStats, Self::Error> {
+        let parsed_peer_id = peer_id
+            .parse::<PeerId>()
+            .map_err(|_| Error::InvalidPeerId(peer_id.clone()))?;
+
+        let stats = self
+            .network
+            .peer_stats(parsed_peer_id)
+            .await?
+            .ok_or_else(|| Error::PeerNotConnected(peer_id.clone()))?;
+
+        Ok(PeerStats {
+            peer_id,
+            rtt: stats.rtt.map(|rtt| rtt.as_millis() as u64),
+            average_rtt: stats.average_rtt.map(|rtt| rtt.as_millis() as u64),
+            min_rtt: stats.min_rtt.map(|rtt| rtt.as_millis() as u64),
+            clock_offset: stats.clock_offset,
+        })
+    }
+
+    /// Looks up the signed record of a validator in the DHT and verifies it against the
+    /// validator's voting key. If the record is valid, the peer's addresses are returned and
+    /// we check whether we can connect to it.
+    async fn resolve_validator(
+        &mut self,
+        validator_address: Address,
+    ) -> Result<ValidatorResolution, Self::Error> {
+        let voting_key = {
+            let blockchain = self.blockchain.read();
+            let accounts_tree = &blockchain.state().accounts.tree;
+            let db_txn = blockchain.read_transaction();
+            StakingContract::get_validator(accounts_tree, &db_txn, &validator_address)
+                .ok_or_else(|| Error::ValidatorNotFound(validator_address.clone()))?
+                .voting_key
+        };
+
+        let record = self
+            .network
+            .dht_get::<_, SignedValidatorRecord<PeerId, Multiaddr>>(&voting_key)
+            .await?;
+        let record_found = record.is_some();
+
+        let signature_valid = match (&record, voting_key.uncompress_cached()) {
+            (Some(record), Ok(public_key)) => record.verify(&public_key),
+            _ => false,
+        };
+        let record = record
+            .filter(|_| signature_valid)
+            .map(|record| record.record);
+        let peer_id = record.as_ref().map(|record| record.peer_id);
+        let record_addresses = record
+            .map(|record| {
+                record
+                    .addresses
+                    .iter()
+                    .map(|address| address.address.to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let (addresses, connectivity) = if let Some(peer_id) = peer_id {
+            let addresses = self
+                .network
+                .peer_addresses(peer_id)
+                .await?
+                .iter()
+                .map(ToString::to_string)
+                .collect();
+            (addresses, self.check_connectivity(peer_id).await)
+        } else {
+            (vec![], ConnectivityCheck::Skipped)
+        };
+
+        Ok(ValidatorResolution {
+            validator_address,
+            voting_key,
+            record_found,
+            signature_valid,
+            peer_id: peer_id.map(|peer_id| peer_id.to_string()),
+            record_addresses,
+            addresses,
+            connectivity,
+        })
+    }
+
+    /// Returns the network message types and gossipsub topics known to the node.
+    async fn get_network_registry(&mut self) -> Result<NetworkRegistry, Self::Error> {
+        let registry = self
+            .registry
+            .as_ref()
+            .ok_or(Error::NetworkRegistryUnavailable)?;
+
+        Ok(NetworkRegistry {
+            messages: registry
+                .messages()
+                .map(|message| RegisteredMessage {
+                    type_id: message.type_id,
+                    name: message.name.to_string(),
+                })
+                .collect(),
+            topics: registry
+                .topics()
+                .map(|topic| RegisteredTopic {
+                    name: topic.name.to_string(),
+                    topic: topic.topic.to_string(),
+                    buffer_size: topic.buffer_size,
+                    validate: topic.validate,
+                })
+                .collect(),
+        })
+    }
+
+    /// Returns the number of received and dropped messages of the subscribed gossipsub topics.
+    async fn get_gossip_stats(&mut self) -> Result<Vec<GossipTopicStats>, Self::Error> {
+        Ok(self
+            .network
+            .gossip_stats()
+            .await?
+            .into_iter()
+            .map(|stats| GossipTopicStats {
+                topic_name: stats.topic_name,
+                overflow_policy: match stats.overflow_policy {
+                    OverflowPolicy::DropNew => "dropNew",
+                    OverflowPolicy::DropOld => "dropOld",
+                    OverflowPolicy::Backpressure => "backpressure",
+                }
+                .to_string(),
+                received: stats.received,
+                dropped: stats.dropped,
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nimiq_blockchain::Blockchain;
+    use nimiq_database::volatile::VolatileEnvironment;
+    use nimiq_network_libp2p::{libp2p::core::multiaddr::multiaddr, Keypair};
+    use nimiq_primitives::networks::NetworkId;
+    use nimiq_test_utils::{blockchain::voting_key, test_network::TestNetwork};
+    use nimiq_utils::time::OffsetTime;
+    use nimiq_validator_network::validator_record::{
+        TransportHint, ValidatorAddress, ValidatorRecord,
+    };
+
+    use super::*;
+
+    /// The validator of the unit test genesis block.
+    const VALIDATOR_ADDRESS: &str = "NQ20 TSB0 DFSM UH9C 15GQ GAGJ TTE4 D3MA 859E";
+
+    fn validator_address() -> Address {
+        Address::from_any_str(VALIDATOR_ADDRESS).unwrap()
+    }
+
+    /// Creates a dispatcher and a connected validator network listening on the given memory
+    /// addresses.
+    async fn dispatcher_and_validator(
+        dispatcher_port: u64,
+        validator_port: u64,
+    ) -> (NetworkDispatcher, Arc<Network>) {
+        let env = VolatileEnvironment::new(10).unwrap();
+        let time = Arc::new(OffsetTime::new());
+        let blockchain = Arc::new(BlockchainLock::new(
+            Blockchain::new(env, NetworkId::UnitAlbatross, time).unwrap(),
+        ));
+        let genesis_hash = blockchain.read().head_hash();
+
+        let validator =
+            Network::build_network(validator_port, genesis_hash.clone(), &mut None).await;
+        let network = Network::build_network(dispatcher_port, genesis_hash, &mut None).await;
+        network
+            .dial_address(multiaddr![Memory(validator_port)])
+            .await
+            .unwrap();
+
+        // FIXME: Add delay while networks share their addresses
+        tokio::time::sleep(Duration::from_secs(2)).await;
+
+        (NetworkDispatcher::new(network, blockchain), validator)
+    }
+
+    /// Publishes a validator record for the given peer signed by the genesis validator.
+    async fn publish_record(network: &Network, peer_id: PeerId, addresses: Vec<Multiaddr>) {
+        let voting_key = voting_key();
+        let record = ValidatorRecord::new(
+            peer_id,
+            addresses
+                .into_iter()
+                .map(|address| ValidatorAddress {
+                    transport: TransportHint::Ws,
+                    address,
+                })
+                .collect(),
+        )
+        .sign(&voting_key.secret_key);
+
+        network
+            .dht_put(&voting_key.public_key.compress(), &record)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn resolve_validator_finds_a_connected_validator() {
+        let (mut dispatcher, validator) = dispatcher_and_validator(26671, 26672).await;
+        let validator_address = multiaddr![Memory(26672u64)];
+        publish_record(
+            &validator,
+            *validator.local_peer_id(),
+            vec![validator_address.clone()],
+        )
+        .await;
+
+        let resolution = dispatcher
+            .resolve_validator(self::validator_address())
+            .await
+            .unwrap();
+
+        assert!(resolution.record_found);
+        assert!(resolution.signature_valid);
+        assert_eq!(
+            resolution.peer_id,
+            Some(validator.local_peer_id().to_string())
+        );
+        assert_eq!(
+            resolution.record_addresses,
+            vec![validator_address.to_string()]
+        );
+        assert!(matches!(
+            resolution.connectivity,
+            ConnectivityCheck::Connected
+        ));
+    }
+
+    #[tokio::test]
+    async fn resolve_validator_rejects_an_unknown_address() {
+        let (mut dispatcher, _validator) = dispatcher_and_validator(26673, 26674).await;
+        let unknown_address = Address::from([1u8; 20]);
+
+        let result = dispatcher.resolve_validator(unknown_address.clone()).await;
+
+        assert!(matches!(
+            result,
+            Err(Error::ValidatorNotFound(address)) if address == unknown_address
+        ));
+    }
+
+    #[tokio::test]
+    async fn resolve_validator_reports_an_unreachable_peer() {
+        let (mut dispatcher, validator) = dispatcher_and_validator(26675, 26676).await;
+        // The record points to a peer that isn't connected to anyone and has no known address.
+        let offline_peer_id = Keypair::generate_ed25519().public().to_peer_id();
+        publish_record(&validator, offline_peer_id, vec![]).await;
+
+        let resolution = dispatcher
+            .resolve_validator(validator_address())
+            .await
+            .unwrap();
+
+        assert!(resolution.record_found);
+        assert!(resolution.signature_valid);
+        assert_eq!(resolution.peer_id, Some(offline_peer_id.to_string()));
+        assert!(resolution.addresses.is_empty());
+        assert!(matches!(
+            resolution.connectivity,
+            ConnectivityCheck::Unreachable(_)
+        ));
+    }
 }
@@ -2,7 +2,10 @@ use async_trait::async_trait;
 use beserial::Serialize;
 
 use nimiq_keys::Address;
-use nimiq_rpc_interface::validator::ValidatorInterface;
+use nimiq_rpc_interface::{
+    types::{RewardDistribution, RewardRecipient},
+    validator::ValidatorInterface,
+};
 use nimiq_validator::validator::ValidatorProxy;
 
 use crate::error::Error;
@@ -44,4 +47,34 @@ impl ValidatorInterface for ValidatorDispatcher {
                 .serialize_to_vec(),
         ))
     }
+
+    /// Returns the accounting of the distribution of our rewards to the configured recipients.
+    async fn get_reward_distribution(&mut self) -> Result<RewardDistribution, Self::Error> {
+        let distribution = self.validator.reward_distribution.read();
+
+        let recipients = distribution
+            .splits
+            .iter()
+            .map(|split| {
+                let stats = distribution
+                    .recipients
+                    .get(&split.recipient)
+                    .cloned()
+                    .unwrap_or_default();
+                RewardRecipient {
+                    address: split.recipient.clone(),
+                    percentage: f64::from(split.basis_points) / 100.0,
+                    distributed: stats.distributed,
+                    num_transactions: stats.num_transactions,
+                    last_transaction: stats.last_transaction,
+                }
+            })
+            .collect();
+
+        Ok(RewardDistribution {
+            rewards_received: distribution.rewards_received,
+            last_payout_block: distribution.last_payout_block,
+            recipients,
+        })
+    }
 }
@@ -5,7 +5,10 @@ use parking_lot::RwLock;
 
 use beserial::Deserialize;
 use nimiq_keys::{Address, KeyPair, PrivateKey, PublicKey, Signature};
-use nimiq_rpc_interface::wallet::{ReturnAccount, ReturnSignature, WalletInterface};
+use nimiq_primitives::{coin::Coin, payment_uri::PaymentUri};
+use nimiq_rpc_interface::wallet::{
+    PaymentRequest, ReturnAccount, ReturnSignature, WalletInterface,
+};
 use nimiq_utils::otp::Locked;
 use nimiq_wallet::{WalletAccount, WalletStore};
 
@@ -175,4 +178,27 @@ impl WalletInterface for WalletDispatcher {
             &signature,
         ))
     }
+
+    /// Creates a payment request URI for the given recipient, e.g. to be shown as QR code. The
+    /// expiry is a Unix timestamp in seconds.
+    async fn create_payment_request(
+        &mut self,
+        recipient: Address,
+        amount: Option<Coin>,
+        message: Option<String>,
+        expires: Option<u64>,
+    ) -> Result<PaymentRequest, Error> {
+        Ok(PaymentUri {
+            recipient,
+            amount,
+            message,
+            expires,
+        }
+        .into())
+    }
+
+    /// Decodes a payment request URI.
+    async fn decode_payment_request(&mut self, uri: String) -> Result<PaymentRequest, Error> {
+        Ok(uri.parse::<PaymentUri>()?.into())
+    }
 }
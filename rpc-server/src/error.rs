@@ -17,9 +17,24 @@ pub enum Error {
     #[error("Mempool rejected transaction: {0}")]
     MempoolError(VerifyErr),
 
+    #[error("{0}")]
+    InvalidRelayFee(#[from] nimiq_mempool::config::InvalidRelayFee),
+
     #[error("Block not found: {0}")]
     BlockNotFound(BlockNumberOrHash),
 
+    #[error("No justification for block: {0}")]
+    JustificationNotFound(Blake2bHash),
+
+    #[error("Epoch is not finalized: {0}")]
+    EpochNotFinalized(u32),
+
+    #[error("{0}")]
+    Schedule(#[from] nimiq_blockchain::ScheduleError),
+
+    #[error("The stakes of the validator set must add up to more than zero and at most the total supply")]
+    InvalidValidatorSet,
+
     #[error("Unexpected macro block: {0}")]
     UnexpectedMacroBlock(BlockNumberOrHash),
 
@@ -29,6 +44,15 @@ pub enum Error {
     #[error("Invalid combination of transaction parameters")]
     InvalidTransactionParameters,
 
+    #[error("Invalid payment request: {0}")]
+    InvalidPaymentRequest(#[from] nimiq_primitives::payment_uri::PaymentUriError),
+
+    #[error("Payment request expired at {0}")]
+    PaymentRequestExpired(u64),
+
+    #[error("Payment request doesn't specify an amount")]
+    PaymentRequestWithoutAmount,
+
     #[error("Failed to build a transaction: {0}")]
     TransactionBuilder(#[from] nimiq_transaction_builder::TransactionBuilderError),
 
@@ -38,6 +62,12 @@ pub enum Error {
     #[error("No validator with address: {0}")]
     ValidatorNotFound(Address),
 
+    #[error("Invalid peer ID: {0}")]
+    InvalidPeerId(String),
+
+    #[error("Not connected to peer: {0}")]
+    PeerNotConnected(String),
+
     #[error("No staker with address: {0}")]
     StakerNotFound(Address),
 
@@ -47,6 +77,9 @@ pub enum Error {
     #[error("No unlocked wallet with address: {0}")]
     UnlockedWalletNotFound(Address),
 
+    #[error("{0}")]
+    HeapProfile(String),
+
     #[error("Invalid hex: {0}")]
     HexError(#[from] hex::FromHexError),
 
@@ -62,8 +95,26 @@ pub enum Error {
     #[error("Multiple transactions found: {0}")]
     MultipleTransactionsFound(Blake2bHash),
 
+    #[error("No cold staking transaction with ID: {0}")]
+    ColdStakingTransactionNotFound(Blake2bHash),
+
+    #[error("Signature isn't from the staker key of {0}")]
+    WrongStakerKey(Address),
+
+    #[error("Network registry not available")]
+    NetworkRegistryUnavailable,
+
+    #[error("Runtime metrics not available, the node must be built with `--cfg tokio_unstable`")]
+    RuntimeMetricsUnavailable,
+
+    #[error("Invalid payment receipt: {0}")]
+    PaymentReceipt(#[from] nimiq_blockchain::receipt::ReceiptError),
+
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
+
+    #[error("Failed to bind the public endpoint: {0}")]
+    Bind(#[from] warp::Error),
 }
 
 impl From<Error> for nimiq_jsonrpc_core::RpcError {
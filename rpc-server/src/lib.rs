@@ -4,4 +4,7 @@ pub use error::Error;
 
 pub mod dispatchers;
 pub mod error;
+pub mod public;
+pub mod resubmission;
+pub mod tenants;
 pub mod wallets;
@@ -0,0 +1,291 @@
+use std::collections::hash_map::DefaultHasher;
+use std::convert::Infallible;
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Instant;
+
+use lru::LruCache;
+use parking_lot::Mutex;
+use serde_json::Value;
+use warp::http::StatusCode;
+use warp::hyper::body::Bytes;
+use warp::reply::{self, Reply};
+use warp::Filter;
+
+use nimiq_jsonrpc_core::{Request, Response, RpcError};
+use nimiq_jsonrpc_server::Dispatcher;
+
+use crate::error::Error;
+use crate::tenants::TokenBucket;
+
+/// The methods that are enabled on the public endpoint. They only read the chain and the mempool
+/// and don't reveal anything about the node or its wallets.
+pub const READ_ONLY_METHODS: &[&str] = &[
+    "getBlockNumber",
+    "getBatchNumber",
+    "getEpochNumber",
+    "getBlockByHash",
+    "getBlockByNumber",
+    "getLatestBlock",
+    "getBlockJustification",
+    "getSlotAt",
+    "getTransactionsByBlockNumber",
+    "getInherentsByBlockNumber",
+    "getTransactionsByBatchNumber",
+    "getInherentsByBatchNumber",
+    "getTransactionHashesByAddress",
+    "getTransactionsByAddress",
+    "getTransactionHistory",
+    "getAccountByAddress",
+    "getActiveValidators",
+    "getCurrentSlashedSlots",
+    "getPreviousSlashedSlots",
+    "getParkedValidators",
+    "getValidatorByAddress",
+    "getStakerByAddress",
+    "getEpochStats",
+    "getChainSchedule",
+    "verifyPaymentReceipt",
+    "isConsensusEstablished",
+    "getRawTransactionInfo",
+    "getTransactionByHash",
+    "getMinFeePerByte",
+];
+
+/// The settings of the public RPC endpoint.
+#[derive(Clone, Debug)]
+pub struct PublicConfig {
+    /// The address the endpoint listens on.
+    pub bind_to: SocketAddr,
+    /// The origins that browsers may send requests from. `*` allows all origins.
+    pub cors_origins: Vec<String>,
+    /// Responses larger than this many bytes are replaced by an error.
+    pub max_response_size: usize,
+    /// The maximum number of requests per minute from a single IP address.
+    pub requests_per_minute: u32,
+}
+
+/// The rate limits of the clients, keyed by [`client_key`].
+///
+/// The limits are split into shards with a lock each, so that requests from different clients
+/// rarely wait for each other. Each shard forgets the clients that were least recently seen once
+/// it is full.
+struct RateLimits {
+    shards: Vec<Mutex<LruCache<Option<IpAddr>, TokenBucket>>>,
+    requests_per_minute: u32,
+}
+
+impl RateLimits {
+    const NUM_SHARDS: usize = 16;
+
+    fn new(capacity: usize, requests_per_minute: u32) -> Self {
+        let shard_capacity = (capacity / Self::NUM_SHARDS).max(1);
+        Self {
+            shards: (0..Self::NUM_SHARDS)
+                .map(|_| Mutex::new(LruCache::new(shard_capacity)))
+                .collect(),
+            requests_per_minute,
+        }
+    }
+
+    /// Takes a token from the bucket of the client, creating it if necessary.
+    fn try_take(&self, key: Option<IpAddr>, now: Instant) -> bool {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        let mut shard = self.shards[hasher.finish() as usize % self.shards.len()].lock();
+
+        if let Some(bucket) = shard.get_mut(&key) {
+            return bucket.try_take(now);
+        }
+
+        let mut bucket = TokenBucket::new(self.requests_per_minute, now);
+        let taken = bucket.try_take(now);
+        shard.put(key, bucket);
+        taken
+    }
+}
+
+/// Returns the key a client is rate limited by. IPv6 clients usually get a whole /64 network, so
+/// they are limited per /64 network. Requests without a remote address share a single limit.
+fn client_key(addr: Option<SocketAddr>) -> Option<IpAddr> {
+    addr.map(|addr| match addr.ip() {
+        IpAddr::V6(ip) => IpAddr::V6(Ipv6Addr::from(u128::from(ip) & !(u128::MAX >> 64))),
+        ip => ip,
+    })
+}
+
+/// A JSON-RPC endpoint that is safe to expose to browsers.
+///
+/// Only the [`READ_ONLY_METHODS`] are dispatched, and only single requests over HTTP POST are
+/// accepted. Each IP address is rate limited, responses are capped in size and CORS headers are set
+/// for the configured origins.
+pub struct PublicServer<D: Dispatcher> {
+    config: PublicConfig,
+    dispatcher: tokio::sync::Mutex<D>,
+    limits: RateLimits,
+}
+
+impl<D: Dispatcher> PublicServer<D> {
+    /// The maximum size of a request in bytes.
+    const MAX_REQUEST_SIZE: u64 = 16 * 1024;
+
+    /// The number of clients that are tracked before the least recently seen ones are forgotten.
+    const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+    pub fn new(config: PublicConfig, dispatcher: D) -> Self {
+        let limits = RateLimits::new(Self::MAX_TRACKED_CLIENTS, config.requests_per_minute);
+        Self {
+            config,
+            dispatcher: tokio::sync::Mutex::new(dispatcher),
+            limits,
+        }
+    }
+
+    /// Binds the endpoint to the configured address and returns the future that serves it.
+    pub fn bind(self) -> Result<impl Future<Output = ()>, Error> {
+        let bind_to = self.config.bind_to;
+
+        let mut cors = warp::cors()
+            .allow_methods(vec!["POST"])
+            .allow_header("content-type");
+        cors = if self.config.cors_origins.iter().any(|origin| origin == "*") {
+            cors.allow_any_origin()
+        } else {
+            cors.allow_origins(self.config.cors_origins.iter().map(String::as_str))
+        };
+
+        let this = Arc::new(self);
+        let route = warp::post()
+            .and(warp::path::end())
+            .and(warp::addr::remote())
+            .and(warp::body::content_length_limit(Self::MAX_REQUEST_SIZE))
+            .and(warp::body::bytes())
+            .and_then(move |addr: Option<SocketAddr>, body: Bytes| {
+                let this = Arc::clone(&this);
+                async move { Ok::<_, Infallible>(this.handle(addr, body).await) }
+            })
+            .with(cors.build());
+
+        let (addr, server) = warp::serve(route).try_bind_ephemeral(bind_to)?;
+        log::info!("Public RPC endpoint listening on {}", addr);
+        Ok(server)
+    }
+
+    async fn handle(&self, addr: Option<SocketAddr>, body: Bytes) -> reply::Response {
+        if !self.limits.try_take(client_key(addr), Instant::now()) {
+            return reply::with_status(
+                Self::json(Self::error_body(
+                    Value::Null,
+                    RpcError::invalid_request(Some(Value::from("Rate limit exceeded"))),
+                )),
+                StatusCode::TOO_MANY_REQUESTS,
+            )
+            .into_response();
+        }
+
+        let request: Request = match serde_json::from_slice(&body) {
+            Ok(request) => request,
+            Err(_) => {
+                return Self::json(Self::error_body(Value::Null, RpcError::parse_error(None)))
+            }
+        };
+
+        let id = match &request.id {
+            Some(id) => id.clone(),
+            // Notifications are not dispatched, they could only be used to modify the node.
+            None => return StatusCode::NO_CONTENT.into_response(),
+        };
+
+        if !READ_ONLY_METHODS.contains(&request.method.as_str()) {
+            return Self::json(Self::error_body(id, RpcError::method_not_found(None)));
+        }
+
+        let response = self
+            .dispatcher
+            .lock()
+            .await
+            .dispatch(request, None, 0)
+            .await;
+        let body = match response.map(|response| serde_json::to_string(&response)) {
+            Some(Ok(body)) if body.len() <= self.config.max_response_size => body,
+            Some(Ok(_)) => Self::error_body(
+                id,
+                RpcError::internal_error(Some(Value::from("Response too large"))),
+            ),
+            Some(Err(e)) => {
+                log::error!("Failed to serialize response: {}", e);
+                Self::error_body(id, RpcError::internal_error(None))
+            }
+            None => return StatusCode::NO_CONTENT.into_response(),
+        };
+        Self::json(body)
+    }
+
+    fn error_body(id: Value, error: RpcError) -> String {
+        serde_json::to_string(&Response::new_error(id, error))
+            .expect("Error responses should serialize")
+    }
+
+    fn json(body: String) -> reply::Response {
+        reply::with_header(body, "content-type", "application/json").into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(addr: &str) -> Option<SocketAddr> {
+        Some(addr.parse().unwrap())
+    }
+
+    #[test]
+    fn ipv6_clients_are_limited_per_network() {
+        assert_eq!(
+            client_key(addr("[2001:db8:1:2:3:4:5:6]:1234")),
+            client_key(addr("[2001:db8:1:2:ffff::1]:80"))
+        );
+        assert_ne!(
+            client_key(addr("[2001:db8:1:2::1]:80")),
+            client_key(addr("[2001:db8:1:3::1]:80"))
+        );
+        assert_ne!(
+            client_key(addr("192.0.2.1:80")),
+            client_key(addr("192.0.2.2:80"))
+        );
+    }
+
+    #[test]
+    fn it_limits_each_client() {
+        let limits = RateLimits::new(100, 2);
+        let now = Instant::now();
+
+        for key in [client_key(addr("192.0.2.1:80")), None] {
+            assert!(limits.try_take(key, now));
+            assert!(limits.try_take(key, now));
+            assert!(!limits.try_take(key, now));
+        }
+        assert!(limits.try_take(client_key(addr("192.0.2.2:80")), now));
+    }
+
+    #[test]
+    fn it_forgets_the_least_recently_seen_clients() {
+        let limits = RateLimits::new(RateLimits::NUM_SHARDS, 1);
+        let now = Instant::now();
+
+        let first = client_key(addr("192.0.2.1:80"));
+        assert!(limits.try_take(first, now));
+        assert!(!limits.try_take(first, now));
+
+        // Each shard holds a single client, so many other clients push out the first one.
+        for i in 0..=255 {
+            let ip = IpAddr::from([198, 51, 100, i]);
+            limits.try_take(Some(ip), now);
+        }
+        let tracked: usize = limits.shards.iter().map(|shard| shard.lock().len()).sum();
+        assert!(tracked <= RateLimits::NUM_SHARDS);
+        assert!(limits.try_take(first, now));
+    }
+}
@@ -0,0 +1,124 @@
+use std::sync::Arc;
+
+use futures::future;
+use futures::stream::{BoxStream, StreamExt};
+use parking_lot::{Mutex, RwLock};
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::BroadcastStream;
+
+use nimiq_blockchain::AbstractBlockchain;
+use nimiq_consensus::ConsensusProxy;
+use nimiq_hash::{Blake2bHash, Hash};
+use nimiq_network_libp2p::Network;
+use nimiq_rpc_interface::types::WalletTransactionEvent;
+use nimiq_transaction::Transaction;
+use nimiq_wallet::{PendingTransactions, ResubmissionEvent, ResubmissionPolicy};
+
+use crate::wallets::UnlockedWallets;
+
+/// Tracks the transactions sent from unlocked wallets until they are included in a block.
+///
+/// Whenever the head of the chain changes, including rebranches, the pending transactions are
+/// checked against the resubmission policy. Transactions that expired before the last macro block
+/// are re-signed and sent again, if the policy allows it and the wallet is still unlocked.
+/// Everything that happens to a pending transaction is announced to the subscribers.
+pub struct TransactionResubmitter {
+    pending: Mutex<PendingTransactions>,
+    events: broadcast::Sender<WalletTransactionEvent>,
+}
+
+impl TransactionResubmitter {
+    const EVENT_BUFFER_SIZE: usize = 64;
+
+    /// Creates the resubmitter and spawns the task following the head of the chain.
+    pub fn spawn(
+        consensus: ConsensusProxy<Network>,
+        unlocked_wallets: Arc<RwLock<UnlockedWallets>>,
+        policy: ResubmissionPolicy,
+    ) -> Arc<Self> {
+        let (events, _) = broadcast::channel(Self::EVENT_BUFFER_SIZE);
+        let this = Arc::new(Self {
+            pending: Mutex::new(PendingTransactions::new(policy)),
+            events,
+        });
+
+        let mut blockchain_events = consensus.blockchain.write().notifier.as_stream();
+        let weak = Arc::downgrade(&this);
+        tokio::spawn(async move {
+            while blockchain_events.next().await.is_some() {
+                match weak.upgrade() {
+                    Some(this) => this.on_head(&consensus, &unlocked_wallets).await,
+                    None => break,
+                }
+            }
+        });
+
+        this
+    }
+
+    /// Starts tracking a transaction sent from an unlocked wallet.
+    pub fn track(&self, transaction: Transaction) {
+        self.pending.lock().track(transaction);
+    }
+
+    /// Returns a stream of the events of all tracked transactions.
+    pub fn subscribe(&self) -> BoxStream<'static, WalletTransactionEvent> {
+        BroadcastStream::new(self.events.subscribe())
+            .filter_map(|event| future::ready(event.ok()))
+            .boxed()
+    }
+
+    async fn on_head(
+        &self,
+        consensus: &ConsensusProxy<Network>,
+        unlocked_wallets: &RwLock<UnlockedWallets>,
+    ) {
+        let events = {
+            let blockchain = consensus.blockchain.read();
+            let mut pending = self.pending.lock();
+            if pending.is_empty() {
+                return;
+            }
+
+            // A transaction can't be included twice, so any block that contains it counts. Its
+            // inclusion might date back further than the validity window of the head.
+            pending.on_head(
+                blockchain.block_number(),
+                blockchain.macro_head().header.block_number,
+                |hash| blockchain.tx_in_validity_window(hash, 0, None),
+                |address| unlocked_wallets.read().get(address).cloned(),
+            )
+        };
+
+        for event in events {
+            if let ResubmissionEvent::Resubmitted { transaction, .. } = &event {
+                if let Err(e) = consensus.send_transaction(transaction.clone()).await {
+                    log::warn!("Failed to resubmit transaction: {}", e);
+                }
+            }
+
+            // There might be no subscriber.
+            let _ = self.events.send(wallet_transaction_event(event));
+        }
+    }
+}
+
+fn wallet_transaction_event(event: ResubmissionEvent) -> WalletTransactionEvent {
+    match event {
+        ResubmissionEvent::Confirmed { hash } => WalletTransactionEvent::Confirmed { hash },
+        ResubmissionEvent::Reverted { hash } => WalletTransactionEvent::Reverted { hash },
+        ResubmissionEvent::Expiring { hash, expires_at } => {
+            WalletTransactionEvent::Expiring { hash, expires_at }
+        }
+        ResubmissionEvent::Resubmitted {
+            old_hash,
+            transaction,
+        } => WalletTransactionEvent::Resubmitted {
+            old_hash,
+            new_hash: transaction.hash::<Blake2bHash>(),
+            validity_start_height: transaction.validity_start_height,
+            fee: transaction.fee,
+        },
+        ResubmissionEvent::Expired { hash } => WalletTransactionEvent::Expired { hash },
+    }
+}
@@ -0,0 +1,387 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Instant;
+
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use serde_json::Value;
+use tokio::sync::mpsc;
+
+use nimiq_jsonrpc_core::{Request, Response, RpcError};
+use nimiq_jsonrpc_server::{Dispatcher, Message};
+use nimiq_rpc_interface::types::ApiKeyUsage;
+
+/// The settings of an API key.
+#[derive(Clone, Debug)]
+pub struct ApiKeyConfig {
+    /// A name for the key, used to report its usage.
+    pub name: String,
+    /// The key that is passed with the requests.
+    pub key: String,
+    /// The methods the key may call. If `None`, all methods except the admin methods are allowed.
+    pub allowed_methods: Option<HashSet<String>>,
+    /// The maximum number of requests per minute. Unlimited if `None`.
+    pub requests_per_minute: Option<u32>,
+    /// Whether the key may call the admin methods.
+    pub admin: bool,
+}
+
+/// Why a request was not dispatched.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Denied {
+    /// The request has no API key, but one is required.
+    MissingKey,
+    /// The API key is not known.
+    UnknownKey,
+    /// The method is not allowed for the API key.
+    MethodNotAllowed,
+    /// The API key exceeded its rate limit.
+    RateLimited,
+}
+
+/// Limits requests to a steady rate, allowing bursts of up to a minute's worth of requests.
+pub(crate) struct TokenBucket {
+    capacity: f64,
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    pub(crate) fn new(requests_per_minute: u32, now: Instant) -> Self {
+        let capacity = f64::from(requests_per_minute);
+        Self {
+            capacity,
+            tokens: capacity,
+            refilled_at: now,
+        }
+    }
+
+    pub(crate) fn try_take(&mut self, now: Instant) -> bool {
+        self.refill(now);
+
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.refilled_at);
+        self.tokens =
+            (self.tokens + elapsed.as_secs_f64() * self.capacity / 60.0).min(self.capacity);
+        self.refilled_at = now;
+    }
+}
+
+struct Tenant {
+    config: ApiKeyConfig,
+    bucket: Option<TokenBucket>,
+    usage: ApiKeyUsage,
+}
+
+impl Tenant {
+    fn new(config: ApiKeyConfig, now: Instant) -> Self {
+        Self {
+            bucket: config
+                .requests_per_minute
+                .map(|limit| TokenBucket::new(limit, now)),
+            usage: ApiKeyUsage {
+                name: config.name.clone(),
+                requests: 0,
+                rejected_methods: 0,
+                rate_limited: 0,
+                methods: HashMap::new(),
+            },
+            config,
+        }
+    }
+
+    fn is_allowed(&self, method: &str) -> bool {
+        if Tenants::ADMIN_METHODS.contains(&method) && !self.config.admin {
+            return false;
+        }
+        self.config
+            .allowed_methods
+            .as_ref()
+            .map_or(true, |allowed| allowed.contains(method))
+    }
+}
+
+/// The API keys of the RPC server, with their method allow-lists, rate limits and usage.
+pub struct Tenants {
+    tenants: Mutex<HashMap<String, Tenant>>,
+    require_api_key: bool,
+}
+
+impl Tenants {
+    /// Methods that are only allowed for admin keys.
+    pub const ADMIN_METHODS: &'static [&'static str] = &[
+        "getApiKeyUsage",
+        "getResourceUsage",
+        "debug_memoryStats",
+        "dumpHeapProfile",
+        "getTaskStats",
+        "getRuntimeStats",
+        "getLockStats",
+    ];
+
+    /// Creates the API keys. If `require_api_key` is set, requests without a key are rejected.
+    /// Otherwise, they may call all methods except the admin methods.
+    pub fn new(keys: Vec<ApiKeyConfig>, require_api_key: bool) -> Self {
+        let now = Instant::now();
+        let tenants = keys
+            .into_iter()
+            .map(|config| (config.key.clone(), Tenant::new(config, now)))
+            .collect();
+
+        Self {
+            tenants: Mutex::new(tenants),
+            require_api_key,
+        }
+    }
+
+    /// Checks whether a request for `method` with the given API key may be dispatched, and
+    /// accounts for it. The method must be registered, the usage is kept per method.
+    pub fn authorize(&self, key: Option<&str>, method: &str) -> Result<(), Denied> {
+        let key = match key {
+            Some(key) => key,
+            None if self.require_api_key => return Err(Denied::MissingKey),
+            None if Self::ADMIN_METHODS.contains(&method) => return Err(Denied::MethodNotAllowed),
+            None => return Ok(()),
+        };
+
+        let mut tenants = self.tenants.lock();
+        let tenant = tenants.get_mut(key).ok_or(Denied::UnknownKey)?;
+
+        if !tenant.is_allowed(method) {
+            tenant.usage.rejected_methods += 1;
+            return Err(Denied::MethodNotAllowed);
+        }
+
+        if let Some(bucket) = &mut tenant.bucket {
+            if !bucket.try_take(Instant::now()) {
+                tenant.usage.rate_limited += 1;
+                return Err(Denied::RateLimited);
+            }
+        }
+
+        tenant.usage.requests += 1;
+        *tenant.usage.methods.entry(method.to_string()).or_insert(0) += 1;
+        Ok(())
+    }
+
+    /// Returns the usage of all API keys, ordered by name.
+    pub fn usage(&self) -> Vec<ApiKeyUsage> {
+        let mut usage: Vec<ApiKeyUsage> = self
+            .tenants
+            .lock()
+            .values()
+            .map(|tenant| tenant.usage.clone())
+            .collect();
+        usage.sort_by(|a, b| a.name.cmp(&b.name));
+        usage
+    }
+}
+
+/// Dispatches requests only if their API key allows it.
+///
+/// The key is passed as the `apiKey` member of the named parameters of a request. Methods with
+/// positional parameters are called with `{"apiKey": "...", "params": [...]}` instead.
+pub struct TenantDispatcher<D: Dispatcher> {
+    inner: D,
+    tenants: Arc<Tenants>,
+}
+
+impl<D: Dispatcher> TenantDispatcher<D> {
+    pub fn new(inner: D, tenants: Arc<Tenants>) -> Self {
+        Self { inner, tenants }
+    }
+
+    /// Removes the API key from the parameters of the request.
+    fn take_api_key(request: &mut Request) -> Option<String> {
+        let params = request.params.as_mut()?.as_object_mut()?;
+        let key = params.remove("apiKey")?;
+
+        if params.is_empty() {
+            request.params = None;
+        } else if params.len() == 1 && params.contains_key("params") {
+            request.params = params.remove("params");
+        }
+
+        match key {
+            Value::String(key) => Some(key),
+            _ => None,
+        }
+    }
+}
+
+#[async_trait]
+impl<D: Dispatcher> Dispatcher for TenantDispatcher<D> {
+    async fn dispatch(
+        &mut self,
+        mut request: Request,
+        tx: Option<&mpsc::Sender<Message>>,
+        id: u64,
+    ) -> Option<Response> {
+        let key = Self::take_api_key(&mut request);
+
+        // Unknown methods aren't accounted for, the usage per method would grow without bound.
+        if !self.inner.match_method(&request.method) {
+            return self.inner.dispatch(request, tx, id).await;
+        }
+
+        match self.tenants.authorize(key.as_deref(), &request.method) {
+            Ok(()) => self.inner.dispatch(request, tx, id).await,
+            Err(denied) => {
+                log::debug!("Rejecting call to {}: {:?}", request.method, denied);
+
+                let error = match denied {
+                    // Pretend the method doesn't exist, like the method allow-list does.
+                    Denied::MethodNotAllowed => RpcError::method_not_found(None),
+                    Denied::MissingKey => {
+                        RpcError::invalid_request(Some(Value::from("API key required")))
+                    }
+                    Denied::UnknownKey => {
+                        RpcError::invalid_request(Some(Value::from("Unknown API key")))
+                    }
+                    Denied::RateLimited => {
+                        RpcError::invalid_request(Some(Value::from("Rate limit exceeded")))
+                    }
+                };
+                // Requests without an ID are notifications and don't get a response.
+                request.id.map(|id| Response::new_error(id, error))
+            }
+        }
+    }
+
+    fn match_method(&self, name: &str) -> bool {
+        self.inner.match_method(name)
+    }
+
+    fn method_names(&self) -> Vec<&str> {
+        self.inner.method_names()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn api_key(key: &str, admin: bool) -> ApiKeyConfig {
+        ApiKeyConfig {
+            name: key.to_string(),
+            key: key.to_string(),
+            allowed_methods: None,
+            requests_per_minute: None,
+            admin,
+        }
+    }
+
+    /// Answers every call to `getBlockNumber` and rejects other methods.
+    struct Methods;
+
+    #[async_trait]
+    impl Dispatcher for Methods {
+        async fn dispatch(
+            &mut self,
+            request: Request,
+            _tx: Option<&mpsc::Sender<Message>>,
+            _id: u64,
+        ) -> Option<Response> {
+            let id = request.id?;
+            Some(if self.match_method(&request.method) {
+                Response::new_success(id, Value::from(1))
+            } else {
+                Response::new_error(id, RpcError::method_not_found(None))
+            })
+        }
+
+        fn match_method(&self, name: &str) -> bool {
+            name == "getBlockNumber"
+        }
+
+        fn method_names(&self) -> Vec<&str> {
+            vec!["getBlockNumber"]
+        }
+    }
+
+    #[test]
+    fn keyless_requests_cant_call_admin_methods() {
+        let tenants = Tenants::new(vec![api_key("admin", true), api_key("user", false)], false);
+
+        assert_eq!(tenants.authorize(None, "getBlockNumber"), Ok(()));
+        assert_eq!(
+            tenants.authorize(None, "getApiKeyUsage"),
+            Err(Denied::MethodNotAllowed)
+        );
+        assert_eq!(
+            tenants.authorize(Some("user"), "getApiKeyUsage"),
+            Err(Denied::MethodNotAllowed)
+        );
+        assert_eq!(tenants.authorize(Some("admin"), "getApiKeyUsage"), Ok(()));
+    }
+
+    #[test]
+    fn it_requires_known_keys() {
+        let tenants = Tenants::new(vec![api_key("user", false)], true);
+
+        assert_eq!(
+            tenants.authorize(None, "getBlockNumber"),
+            Err(Denied::MissingKey)
+        );
+        assert_eq!(
+            tenants.authorize(Some("other"), "getBlockNumber"),
+            Err(Denied::UnknownKey)
+        );
+        assert_eq!(tenants.authorize(Some("user"), "getBlockNumber"), Ok(()));
+    }
+
+    #[test]
+    fn it_limits_methods_and_rate() {
+        let mut config = api_key("user", false);
+        config.allowed_methods = Some(HashSet::from(["getBlockNumber".to_string()]));
+        config.requests_per_minute = Some(1);
+        let tenants = Tenants::new(vec![config], true);
+
+        assert_eq!(
+            tenants.authorize(Some("user"), "getAccount"),
+            Err(Denied::MethodNotAllowed)
+        );
+        assert_eq!(tenants.authorize(Some("user"), "getBlockNumber"), Ok(()));
+        assert_eq!(
+            tenants.authorize(Some("user"), "getBlockNumber"),
+            Err(Denied::RateLimited)
+        );
+
+        let usage = &tenants.usage()[0];
+        assert_eq!(usage.requests, 1);
+        assert_eq!(usage.rejected_methods, 1);
+        assert_eq!(usage.rate_limited, 1);
+        assert_eq!(usage.methods.get("getBlockNumber"), Some(&1));
+    }
+
+    #[tokio::test]
+    async fn it_only_accounts_for_registered_methods() {
+        let tenants = Arc::new(Tenants::new(vec![api_key("user", false)], true));
+        let mut dispatcher = TenantDispatcher::new(Methods, Arc::clone(&tenants));
+
+        for method in ["getBlockNumber", "doesNotExist1", "doesNotExist2"] {
+            let request: Request = serde_json::from_value(json!({
+                "jsonrpc": "2.0",
+                "method": method,
+                "params": { "apiKey": "user" },
+                "id": 1,
+            }))
+            .unwrap();
+            assert!(dispatcher.dispatch(request, None, 1).await.is_some());
+        }
+
+        let usage = &tenants.usage()[0];
+        assert_eq!(usage.requests, 1);
+        assert_eq!(usage.methods.len(), 1);
+        assert_eq!(usage.methods.get("getBlockNumber"), Some(&1));
+    }
+}
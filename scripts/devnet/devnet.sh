@@ -65,8 +65,8 @@ function check_failures() {
             echo "SLOW_LOCK_ACQUISITION" >> temp-state/RESULT.TXT
             break
         fi
-        # Search for deadlocks
-        if grep -wrin "deadlock" $logsdir/*.log
+        # Search for deadlocks and stalled tasks
+        if grep -wrin -e "deadlock" -e "probably stalled" $logsdir/*.log
         then
             # Only report deadlock once
             if [ -f "temp-state/RESULT.TXT" ] && [ $(grep "DEADLOCK" temp-state/RESULT.TXT) ]
@@ -20,9 +20,9 @@ pub use nimiq::{
     config::config_file::ConfigFile,
     error::Error,
     extras::{
-        deadlock::initialize_deadlock_detection,
         logging::{initialize_logging, log_error_cause_chain},
         panic::initialize_panic_reporting,
+        stall_detector::initialize_stall_detection,
     },
 };
 use nimiq_block::BlockType;
@@ -147,8 +147,8 @@ async fn update_metric_counters(block_number: i64, block_time: i64, spammer_tps:
 }
 
 async fn main_inner() -> Result<(), Error> {
-    // Initialize deadlock detection
-    initialize_deadlock_detection();
+    // Initialize stall detection
+    initialize_stall_detection();
 
     // Parse command line.
     let spammer_command_line = SpammerCommandLine::from_args();
@@ -212,12 +212,14 @@ async fn main_inner() -> Result<(), Error> {
     let mut client: Client = Client::from_config(config).await?;
     log::info!("Client initialized");
 
+    let executors = client.executors();
+
     // Initialize RPC server
     if let Some(rpc_config) = rpc_config {
         use nimiq::extras::rpc_server::initialize_rpc_server;
         let rpc_server = initialize_rpc_server(&client, rpc_config, client.wallet_store())
             .expect("Failed to initialize RPC server");
-        tokio::spawn(async move { rpc_server.run().await });
+        executors.rpc.spawn(async move { rpc_server.run().await });
     }
 
     // Start consensus.
@@ -229,14 +231,14 @@ async fn main_inner() -> Result<(), Error> {
     };
 
     log::info!("Spawning consensus");
-    tokio::spawn(consensus);
+    executors.consensus.spawn(consensus);
     let consensus = client.consensus_proxy();
 
     // Start Spammer
     let mempool = if let Some(validator) = client.validator() {
         log::info!("Spawning spammer");
         let mempool = std::sync::Arc::clone(&validator.mempool);
-        tokio::spawn(validator);
+        executors.consensus.spawn(validator);
         mempool
     } else {
         panic!("Could not start spammer");
@@ -2,7 +2,6 @@ use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Instant;
 
-use parking_lot::RwLock;
 use rand::{prelude::StdRng, RngCore, SeedableRng};
 
 use beserial::Deserialize;
@@ -12,7 +11,7 @@ use nimiq_block::{
     ViewChangeProof,
 };
 use nimiq_block_production::BlockProducer;
-use nimiq_blockchain::{AbstractBlockchain, Blockchain, PushResult};
+use nimiq_blockchain::{AbstractBlockchain, Blockchain, BlockchainLock, PushResult};
 use nimiq_bls::{AggregateSignature, KeyPair as BlsKeyPair, SecretKey as BlsSecretKey};
 use nimiq_collections::BitSet;
 use nimiq_genesis::NetworkId;
@@ -61,7 +60,7 @@ pub fn generate_transactions(
 /// Produces a series of macro blocks (and the corresponding batches).
 pub fn produce_macro_blocks(
     producer: &BlockProducer,
-    blockchain: &Arc<RwLock<Blockchain>>,
+    blockchain: &Arc<BlockchainLock>,
     num_blocks: usize,
 ) {
     for _ in 0..num_blocks {
@@ -93,7 +92,7 @@ pub fn produce_macro_blocks(
 /// Produces a series of macro blocks (and the corresponding batches).
 pub fn produce_macro_blocks_with_txns(
     producer: &BlockProducer,
-    blockchain: &Arc<RwLock<Blockchain>>,
+    blockchain: &Arc<BlockchainLock>,
     num_blocks: usize,
     num_txns: usize,
     rng_seed: u64,
@@ -125,7 +124,7 @@ pub fn produce_macro_blocks_with_txns(
 }
 
 /// Fill batch with micro blocks.
-pub fn fill_micro_blocks(producer: &BlockProducer, blockchain: &Arc<RwLock<Blockchain>>) {
+pub fn fill_micro_blocks(producer: &BlockProducer, blockchain: &Arc<BlockchainLock>) {
     let init_height = blockchain.read().block_number();
 
     assert!(policy::is_macro_block_at(init_height));
@@ -156,7 +155,7 @@ pub fn fill_micro_blocks(producer: &BlockProducer, blockchain: &Arc<RwLock<Block
 /// Fill batch with simple transactions to random recipients
 pub fn fill_micro_blocks_with_txns(
     producer: &BlockProducer,
-    blockchain: &Arc<RwLock<Blockchain>>,
+    blockchain: &Arc<BlockchainLock>,
     num_transactions: usize,
     rng_seed: u64,
 ) {
@@ -3,4 +3,5 @@ pub mod consensus;
 pub mod node;
 pub mod test_network;
 pub mod test_transaction;
+pub mod test_vectors;
 pub mod validator;
@@ -1,11 +1,9 @@
 use std::sync::Arc;
 
-use parking_lot::RwLock;
-
-use nimiq_blockchain::Blockchain;
+use nimiq_blockchain::{Blockchain, BlockchainLock};
 use nimiq_build_tools::genesis::GenesisInfo;
 use nimiq_consensus::sync::history::HistorySync;
-use nimiq_consensus::Consensus as AbstractConsensus;
+use nimiq_consensus::{Consensus as AbstractConsensus, RequestServing};
 use nimiq_database::volatile::VolatileEnvironment;
 use nimiq_network_interface::network::Network as NetworkInterface;
 use nimiq_network_mock::MockHub;
@@ -16,7 +14,7 @@ use crate::test_network::TestNetwork;
 
 pub struct Node<N: NetworkInterface + TestNetwork> {
     pub network: Arc<N>,
-    pub blockchain: Arc<RwLock<Blockchain>>,
+    pub blockchain: Arc<BlockchainLock>,
     pub consensus: Option<AbstractConsensus<N>>,
 }
 
@@ -24,7 +22,7 @@ impl<N: NetworkInterface + TestNetwork> Node<N> {
     pub async fn new(peer_id: u64, genesis_info: GenesisInfo, hub: &mut Option<MockHub>) -> Self {
         let env = VolatileEnvironment::new(12).unwrap();
         let clock = Arc::new(OffsetTime::new());
-        let blockchain = Arc::new(RwLock::new(
+        let blockchain = Arc::new(BlockchainLock::new(
             Blockchain::with_genesis(
                 env.clone(),
                 Arc::clone(&clock),
@@ -45,6 +43,7 @@ impl<N: NetworkInterface + TestNetwork> Node<N> {
             Arc::clone(&network),
             Box::pin(sync_protocol),
             1,
+            RequestServing::Full,
         )
         .await;
 
@@ -7,6 +7,7 @@ use nimiq_network_libp2p::discovery::peer_contacts::{PeerContact, Services};
 use nimiq_network_libp2p::libp2p::core::multiaddr::multiaddr;
 use nimiq_network_libp2p::{Config, Keypair, Network};
 use nimiq_network_mock::{MockHub, MockNetwork};
+use nimiq_utils::shared_rng::SharedRng;
 use nimiq_utils::time::OffsetTime;
 
 #[async_trait]
@@ -63,7 +64,8 @@ impl TestNetwork for Network {
             None,
         );
         peer_contact.set_current_time();
-        let config = Config::new(peer_key, peer_contact, Vec::new(), genesis_hash.clone());
+        let mut config = Config::new(peer_key, peer_contact, Vec::new(), genesis_hash.clone());
+        config.rng = SharedRng::seeded(peer_id);
         let network = Arc::new(Network::new(clock, config).await);
         network.listen_on(vec![peer_address]).await;
         network
@@ -0,0 +1,375 @@
+//! Golden test vectors of the consensus objects.
+//!
+//! The vectors are serializations of fixed blocks, justifications, staking transactions and the
+//! public inputs of the nano ZK proofs. They are built from the keys in [`crate::blockchain`] and
+//! contain neither timestamps nor randomness, so they are the same on every run. The objects are
+//! not valid on any chain, they only pin down the wire format.
+//!
+//! The vectors are rendered as one line per vector, `<name> <hex>`, and are checked against the
+//! golden file `test-utils/tests/consensus.vectors`. Alternative client implementations can use
+//! that file to check that they serialize and deserialize these objects the same way.
+
+use std::fmt::Write;
+use std::str::FromStr;
+
+use beserial::{Deserialize, Serialize};
+use nimiq_block::{
+    Block, MacroBlock, MacroBody, MacroHeader, MicroBlock, MicroBody, MicroHeader,
+    MicroJustification,
+};
+use nimiq_bls::lazy::LazyPublicKey;
+use nimiq_collections::BitSet;
+use nimiq_genesis::NetworkId;
+use nimiq_hash::{Blake2bHash, Hash};
+use nimiq_keys::{Address, KeyPair, PrivateKey};
+use nimiq_nano_primitives::state_commitment;
+use nimiq_primitives::coin::Coin;
+use nimiq_primitives::policy;
+use nimiq_primitives::slots::{Validator, Validators};
+use nimiq_transaction::Transaction;
+use nimiq_transaction_builder::TransactionBuilder;
+use nimiq_vrf::VrfSeed;
+
+use crate::blockchain::{sign_macro_block, sign_view_change, signing_key, voting_key, UNIT_KEY};
+
+/// A named serialization of a consensus object.
+#[derive(Clone, Debug)]
+pub struct TestVector {
+    pub name: &'static str,
+    pub data: Vec<u8>,
+    /// Deserializes the data as the type of the object and serializes it again. Returns `None` if
+    /// the data isn't a valid object of that type.
+    pub round_trip: fn(&[u8]) -> Option<Vec<u8>>,
+}
+
+impl TestVector {
+    fn new<T: Serialize + Deserialize>(name: &'static str, object: &T) -> Self {
+        TestVector {
+            name,
+            data: object.serialize_to_vec(),
+            round_trip: round_trip::<T>,
+        }
+    }
+
+    /// Returns the public inputs of a ZK proof, which are plain bytes without a beserial type.
+    fn raw(name: &'static str, data: Vec<u8>) -> Self {
+        TestVector {
+            name,
+            data,
+            round_trip: |data| Some(data.to_vec()),
+        }
+    }
+}
+
+fn round_trip<T: Serialize + Deserialize>(data: &[u8]) -> Option<Vec<u8>> {
+    let object = T::deserialize_from_vec(data).ok()?;
+    Some(object.serialize_to_vec())
+}
+
+/// Generates the test vectors, in the order in which they appear in the golden file.
+pub fn generate() -> Vec<TestVector> {
+    let mut vectors = vec![];
+
+    let micro_block = micro_block();
+    vectors.push(TestVector::new("micro_header", &micro_block.header));
+    vectors.push(TestVector::new(
+        "micro_justification",
+        micro_block.justification.as_ref().unwrap(),
+    ));
+    vectors.push(TestVector::new(
+        "micro_body",
+        micro_block.body.as_ref().unwrap(),
+    ));
+    vectors.push(TestVector::new("micro_block", &Block::Micro(micro_block)));
+
+    let macro_block = election_block();
+    vectors.push(TestVector::new("macro_header", &macro_block.header));
+    vectors.push(TestVector::new(
+        "macro_justification",
+        macro_block.justification.as_ref().unwrap(),
+    ));
+    vectors.push(TestVector::new(
+        "macro_body",
+        macro_block.body.as_ref().unwrap(),
+    ));
+
+    // The public inputs of a nano ZK proof are the state commitments of its initial and final
+    // election blocks, which are computed from the block number, the header hash and the voting
+    // keys of the elected slots.
+    let voting_keys = vec![voting_key().public_key.public_key; policy::SLOTS as usize];
+    vectors.push(TestVector::raw(
+        "zkp_state_commitment",
+        state_commitment(
+            macro_block.header.block_number,
+            macro_block.hash().into(),
+            voting_keys,
+        ),
+    ));
+    vectors.push(TestVector::new("macro_block", &Block::Macro(macro_block)));
+
+    for (name, transaction) in staking_transactions() {
+        vectors.push(TestVector::new(name, &transaction));
+    }
+
+    vectors
+}
+
+/// Renders the test vectors in the format of the golden file.
+pub fn render(vectors: &[TestVector]) -> String {
+    let mut rendered = String::new();
+    for vector in vectors {
+        writeln!(rendered, "{} {}", vector.name, hex::encode(&vector.data)).unwrap();
+    }
+    rendered
+}
+
+/// Parses a golden file into the names and data of its vectors. Empty lines and lines starting
+/// with `#` are skipped.
+pub fn parse(golden: &str) -> Result<Vec<(String, Vec<u8>)>, String> {
+    golden
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let (name, data) = line
+                .split_once(' ')
+                .ok_or_else(|| format!("Malformed line: {}", line))?;
+            let data = hex::decode(data).map_err(|e| format!("Invalid hex in {}: {}", name, e))?;
+            Ok((name.to_string(), data))
+        })
+        .collect()
+}
+
+/// Verifies a golden file against the given test vectors. Every vector must be in the golden file
+/// with the same data, and the data must deserialize and serialize again to the same bytes.
+/// Returns a description of every mismatch.
+pub fn verify(vectors: &[TestVector], golden: &str) -> Vec<String> {
+    let golden = match parse(golden) {
+        Ok(golden) => golden,
+        Err(e) => return vec![e],
+    };
+
+    let mut errors = vec![];
+    for vector in vectors {
+        match golden.iter().find(|(name, _)| name == vector.name) {
+            None => errors.push(format!("{} is missing", vector.name)),
+            Some((_, data)) => {
+                if *data != vector.data {
+                    errors.push(format!("{} differs", vector.name));
+                }
+                if (vector.round_trip)(data).as_ref() != Some(data) {
+                    errors.push(format!("{} doesn't round trip", vector.name));
+                }
+            }
+        }
+    }
+
+    for (name, _) in &golden {
+        if !vectors.iter().any(|vector| vector.name == name) {
+            errors.push(format!("{} is unknown", name));
+        }
+    }
+
+    errors
+}
+
+fn micro_block() -> MicroBlock {
+    let block_number = 1;
+
+    let body = MicroBody {
+        fork_proofs: vec![],
+        transactions: staking_transactions()
+            .into_iter()
+            .map(|(_, transaction)| transaction)
+            .collect(),
+    };
+
+    let header = MicroHeader {
+        version: policy::VERSION,
+        block_number,
+        view_number: 1,
+        timestamp: 1_000,
+        parent_hash: Blake2bHash::from([1u8; 32]),
+        seed: VrfSeed::default(),
+        extra_data: vec![0x42],
+        state_root: Blake2bHash::from([2u8; 32]),
+        body_root: body.hash(),
+        history_root: Blake2bHash::from([3u8; 32]),
+    };
+
+    let hash = header.hash::<Blake2bHash>();
+    let signature = signing_key().sign(hash.as_slice());
+    let view_change_proof = sign_view_change(VrfSeed::default(), block_number, 1);
+
+    MicroBlock {
+        header,
+        justification: Some(MicroJustification {
+            signature,
+            view_change_proof: Some(view_change_proof),
+        }),
+        body: Some(body),
+    }
+}
+
+fn election_block() -> MacroBlock {
+    let validators = Validators::new(vec![Validator::new(
+        Address::from(&signing_key().public),
+        LazyPublicKey::from(voting_key().public_key),
+        signing_key().public,
+        (0, policy::SLOTS),
+    )]);
+
+    let mut disabled_set = BitSet::new();
+    disabled_set.insert(1);
+
+    let body = MacroBody {
+        pk_tree_root: Some(MacroBlock::pk_tree_root(&validators)),
+        validators: Some(validators),
+        lost_reward_set: disabled_set.clone(),
+        disabled_set,
+    };
+
+    let header = MacroHeader {
+        version: policy::VERSION,
+        block_number: policy::EPOCH_LENGTH,
+        view_number: 0,
+        timestamp: 2_000,
+        parent_hash: Blake2bHash::from([4u8; 32]),
+        parent_election_hash: Blake2bHash::from([5u8; 32]),
+        seed: VrfSeed::default(),
+        extra_data: vec![],
+        state_root: Blake2bHash::from([6u8; 32]),
+        body_root: body.hash(),
+        history_root: Blake2bHash::from([7u8; 32]),
+    };
+
+    sign_macro_block(&voting_key(), header, Some(body))
+}
+
+fn staking_transactions() -> Vec<(&'static str, Transaction)> {
+    let key_pair = KeyPair::from(PrivateKey::from_str(UNIT_KEY).unwrap());
+    let signing_key = signing_key();
+    let voting_key = voting_key();
+    let validator_address = Address::from(&key_pair.public);
+    let staker_address = Address::from(&signing_key.public);
+    let network_id = NetworkId::UnitAlbatross;
+
+    vec![
+        (
+            "create_validator",
+            TransactionBuilder::new_create_validator(
+                &key_pair,
+                &key_pair,
+                signing_key.public,
+                &voting_key,
+                validator_address.clone(),
+                Some(Blake2bHash::from([8u8; 32])),
+                Coin::from_u64_unchecked(100),
+                1,
+                network_id,
+            ),
+        ),
+        (
+            "update_validator",
+            TransactionBuilder::new_update_validator(
+                &key_pair,
+                &key_pair,
+                Some(signing_key.public),
+                Some(&voting_key),
+                Some(staker_address.clone()),
+                Some(None),
+                Coin::from_u64_unchecked(100),
+                1,
+                network_id,
+            ),
+        ),
+        (
+            "inactivate_validator",
+            TransactionBuilder::new_inactivate_validator(
+                &key_pair,
+                validator_address.clone(),
+                &signing_key,
+                Coin::from_u64_unchecked(100),
+                1,
+                network_id,
+            ),
+        ),
+        (
+            "reactivate_validator",
+            TransactionBuilder::new_reactivate_validator(
+                &key_pair,
+                validator_address.clone(),
+                &signing_key,
+                Coin::from_u64_unchecked(100),
+                1,
+                network_id,
+            ),
+        ),
+        (
+            "unpark_validator",
+            TransactionBuilder::new_unpark_validator(
+                &key_pair,
+                validator_address.clone(),
+                &signing_key,
+                Coin::from_u64_unchecked(100),
+                1,
+                network_id,
+            ),
+        ),
+        (
+            "delete_validator",
+            TransactionBuilder::new_delete_validator(
+                validator_address.clone(),
+                &key_pair,
+                Coin::from_u64_unchecked(100),
+                1,
+                network_id,
+            ),
+        ),
+        (
+            "create_staker",
+            TransactionBuilder::new_create_staker(
+                &key_pair,
+                &signing_key,
+                Some(validator_address.clone()),
+                Coin::from_u64_unchecked(10_000),
+                Coin::from_u64_unchecked(100),
+                1,
+                network_id,
+            ),
+        ),
+        (
+            "stake",
+            TransactionBuilder::new_stake(
+                &key_pair,
+                staker_address.clone(),
+                Coin::from_u64_unchecked(10_000),
+                Coin::from_u64_unchecked(100),
+                1,
+                network_id,
+            ),
+        ),
+        (
+            "update_staker",
+            TransactionBuilder::new_update_staker(
+                Some(&key_pair),
+                &signing_key,
+                None,
+                Coin::from_u64_unchecked(100),
+                1,
+                network_id,
+            ),
+        ),
+        (
+            "unstake",
+            TransactionBuilder::new_unstake(
+                &signing_key,
+                staker_address,
+                Coin::from_u64_unchecked(10_000),
+                Coin::from_u64_unchecked(100),
+                1,
+                network_id,
+            ),
+        ),
+    ]
+}
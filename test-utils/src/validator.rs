@@ -18,6 +18,7 @@ use nimiq_keys::{Address, KeyPair as SchnorrKeyPair, SecureGenerate};
 use nimiq_mempool::config::MempoolConfig;
 use nimiq_network_interface::{network::Network as NetworkInterface, peer::Peer as PeerInterface};
 use nimiq_network_mock::MockHub;
+use nimiq_utils::shared_rng::SharedRng;
 use nimiq_validator::validator::Validator as AbstractValidator;
 use nimiq_validator_network::network_impl::ValidatorNetworkImpl;
 
@@ -39,22 +40,30 @@ pub async fn build_validator<N: TestNetwork + NetworkInterface>(
 )
 where
     N::Error: Send,
+    N::AddressType: Send + Sync + Serialize + Deserialize + Clone,
     <N::PeerType as PeerInterface>::Id: Serialize + Deserialize + Clone,
 {
     let consensus = consensus(peer_id, genesis_info, hub).await;
     let validator_network = Arc::new(ValidatorNetworkImpl::new(Arc::clone(&consensus.network)));
-    (
-        AbstractValidator::<N, ValidatorNetworkImpl<N>>::new(
-            &consensus,
-            validator_network,
-            validator_address,
-            signing_key,
-            voting_key,
-            fee_key,
-            MempoolConfig::default(),
-        ),
-        consensus,
-    )
+
+    // Seed the randomness with the peer id, so that test runs are reproducible.
+    let rng = SharedRng::seeded(peer_id);
+    let mempool_config = MempoolConfig {
+        rng: rng.fork(),
+        ..Default::default()
+    };
+    let mut validator = AbstractValidator::<N, ValidatorNetworkImpl<N>>::new(
+        &consensus,
+        validator_network,
+        validator_address,
+        signing_key,
+        voting_key,
+        fee_key,
+        mempool_config,
+    );
+    validator.set_rng(rng.fork());
+
+    (validator, consensus)
 }
 
 pub async fn build_validators<N: TestNetwork + NetworkInterface>(
@@ -64,6 +73,7 @@ pub async fn build_validators<N: TestNetwork + NetworkInterface>(
 ) -> Vec<AbstractValidator<N, ValidatorNetworkImpl<N>>>
 where
     N::Error: Send,
+    N::AddressType: Send + Sync + Serialize + Deserialize + Clone,
     <N::PeerType as PeerInterface>::Id: Serialize + Deserialize + Clone + Display,
 {
     // Generate validator key pairs.
@@ -143,6 +153,7 @@ pub fn validator_for_slot<N: TestNetwork + NetworkInterface>(
 ) -> &AbstractValidator<N, ValidatorNetworkImpl<N>>
 where
     N::Error: Send,
+    N::AddressType: Send + Sync + Serialize + Deserialize + Clone,
     <N::PeerType as PeerInterface>::Id: Serialize + Deserialize + Clone + Send,
 {
     let consensus = &validators.first().unwrap().consensus;
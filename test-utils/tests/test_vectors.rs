@@ -0,0 +1,42 @@
+use std::path::PathBuf;
+
+use nimiq_test_utils::test_vectors::{generate, render, verify};
+
+fn golden_file() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/consensus.vectors")
+}
+
+#[test]
+fn test_vectors_match_golden_file() {
+    let vectors = generate();
+
+    // Run with UPDATE_TEST_VECTORS=1 to write the current vectors to the golden file.
+    if std::env::var_os("UPDATE_TEST_VECTORS").is_some() {
+        let header = std::fs::read_to_string(golden_file())
+            .unwrap_or_default()
+            .lines()
+            .take_while(|line| line.starts_with('#'))
+            .map(|line| format!("{}\n", line))
+            .collect::<String>();
+        std::fs::write(golden_file(), header + &render(&vectors)).unwrap();
+    }
+
+    let golden = std::fs::read_to_string(golden_file()).unwrap();
+    assert_eq!(
+        verify(&vectors, &golden),
+        Vec::<String>::new(),
+        "The serialization of a consensus object changed. If this is intended, update tests/consensus.vectors with UPDATE_TEST_VECTORS=1."
+    );
+}
+
+#[test]
+fn test_vectors_round_trip() {
+    for vector in generate() {
+        assert_eq!(
+            (vector.round_trip)(&vector.data),
+            Some(vector.data.clone()),
+            "{}",
+            vector.name
+        );
+    }
+}
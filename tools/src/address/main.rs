@@ -1,14 +1,187 @@
 extern crate nimiq_keys as keys;
+extern crate nimiq_primitives as primitives;
 
-use keys::{Address, PrivateKey, PublicKey, SecureGenerate};
+use std::process::exit;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 
-fn main() {
-    let private_key = PrivateKey::generate_default_csprng();
-    let public_key = PublicKey::from(&private_key);
-    let address = Address::from(&public_key);
+use anyhow::Error;
+use clap::{crate_authors, crate_description, crate_version, Arg, ArgMatches, Command};
+
+use keys::vanity::{generate_vanity_key_pair, VanityPattern, VanityPosition};
+use keys::{Address, KeyPair, PrivateKey, PublicKey, SecureGenerate};
+use primitives::coin::Coin;
+use primitives::payment_uri::PaymentUri;
+
+fn print_key_pair(key_pair: &KeyPair) {
+    let address = Address::from(key_pair);
 
     println!("Address:       {}", address.to_user_friendly_address());
     println!("Address (raw): {}", address);
-    println!("Public Key:    {}", public_key.to_hex());
-    println!("Private Key:   {}", hex::encode(private_key.as_bytes()));
+    println!("Public Key:    {}", key_pair.public.to_hex());
+    println!(
+        "Private Key:   {}",
+        hex::encode(key_pair.private.as_bytes())
+    );
+}
+
+fn generate() {
+    let private_key = PrivateKey::generate_default_csprng();
+    let public_key = PublicKey::from(&private_key);
+    print_key_pair(&KeyPair {
+        public: public_key,
+        private: private_key,
+    });
+}
+
+fn validate(matches: &ArgMatches) -> Result<(), Error> {
+    let address = matches.value_of("address").unwrap();
+
+    match Address::from_user_friendly_address(address) {
+        Ok(address) => {
+            println!("Valid address: {}", address.to_user_friendly_address());
+            println!("Address (raw): {}", address.to_hex());
+            Ok(())
+        }
+        Err(e) => Err(anyhow::anyhow!("Invalid address: {}", e)),
+    }
+}
+
+fn payment_uri(matches: &ArgMatches) -> Result<(), Error> {
+    let mut uri = PaymentUri::new(Address::from_any_str(matches.value_of("address").unwrap())?);
+    if let Some(amount) = matches.value_of("amount") {
+        uri = uri.with_amount(amount.parse::<Coin>()?);
+    }
+    if let Some(message) = matches.value_of("message") {
+        uri = uri.with_message(message);
+    }
+
+    println!("{}", uri);
+    Ok(())
+}
+
+fn vanity(matches: &ArgMatches) -> Result<(), Error> {
+    let position = if matches.is_present("suffix") {
+        VanityPosition::Suffix
+    } else if matches.is_present("anywhere") {
+        VanityPosition::Anywhere
+    } else {
+        VanityPosition::Prefix
+    };
+    let pattern = VanityPattern::new(matches.value_of("pattern").unwrap(), position)?;
+    let num_threads = match matches.value_of("threads") {
+        Some(threads) => threads.parse()?,
+        None => thread::available_parallelism().map_or(1, |n| n.get()),
+    };
+
+    eprintln!(
+        "Searching with {} threads, about {:.0} key pairs have to be generated",
+        num_threads,
+        pattern.difficulty()
+    );
+
+    // Report the progress until the search finishes.
+    let attempts = Arc::new(AtomicU64::new(0));
+    let progress_attempts = Arc::clone(&attempts);
+    let start = Instant::now();
+    thread::spawn(move || loop {
+        thread::sleep(Duration::from_secs(10));
+        let attempts = progress_attempts.load(Ordering::Relaxed);
+        eprintln!(
+            "{} key pairs generated ({:.0}/s)",
+            attempts,
+            attempts as f64 / start.elapsed().as_secs_f64()
+        );
+    });
+
+    let (key_pair, attempts) = generate_vanity_key_pair(&pattern, num_threads, attempts);
+    eprintln!(
+        "Found a match after {} key pairs in {}s",
+        attempts,
+        start.elapsed().as_secs()
+    );
+
+    print_key_pair(&key_pair);
+    Ok(())
+}
+
+fn run_app() -> Result<(), Error> {
+    let matches = Command::new("Nimiq address tool")
+        .version(crate_version!())
+        .author(crate_authors!())
+        .about(crate_description!())
+        .subcommand(Command::new("generate").about("Generate a new random key pair (default)"))
+        .subcommand(
+            Command::new("validate")
+                .about("Validate a user-friendly address")
+                .arg(Arg::new("address").value_name("ADDRESS").required(true)),
+        )
+        .subcommand(
+            Command::new("uri")
+                .about("Create a payment URI, e.g. to be shown as QR code")
+                .arg(Arg::new("address").value_name("ADDRESS").required(true))
+                .arg(
+                    Arg::new("amount")
+                        .short('a')
+                        .long("amount")
+                        .value_name("NIM")
+                        .help("Amount to request in NIM.")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("message")
+                        .short('m')
+                        .long("message")
+                        .value_name("MESSAGE")
+                        .help("Message to include in the request.")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            Command::new("vanity")
+                .about("Generate a key pair whose address contains a pattern")
+                .arg(Arg::new("pattern").value_name("PATTERN").required(true))
+                .arg(
+                    Arg::new("suffix")
+                        .long("suffix")
+                        .help("Match the pattern at the end of the address.")
+                        .conflicts_with("anywhere"),
+                )
+                .arg(
+                    Arg::new("anywhere")
+                        .long("anywhere")
+                        .help("Match the pattern anywhere in the address."),
+                )
+                .arg(
+                    Arg::new("threads")
+                        .short('t')
+                        .long("threads")
+                        .value_name("THREADS")
+                        .help("Number of threads to use. Defaults to the number of CPUs.")
+                        .takes_value(true),
+                ),
+        )
+        .get_matches();
+
+    match matches.subcommand() {
+        Some(("validate", matches)) => validate(matches),
+        Some(("uri", matches)) => payment_uri(matches),
+        Some(("vanity", matches)) => vanity(matches),
+        _ => {
+            generate();
+            Ok(())
+        }
+    }
+}
+
+fn main() {
+    exit(match run_app() {
+        Ok(_) => 0,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        }
+    });
 }
@@ -0,0 +1,202 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+
+use anyhow::{anyhow, Error};
+use clap::{crate_authors, crate_description, crate_version, Arg, ArgMatches, Command};
+use serde::{Deserialize, Serialize};
+
+/// Where criterion writes its results by default.
+const CRITERION_DIR: &str = "target/criterion";
+
+/// The timings of a benchmark, in nanoseconds per iteration.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Timing {
+    mean_ns: f64,
+    median_ns: f64,
+    std_dev_ns: f64,
+}
+
+/// A machine-readable summary of a benchmark run.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Baseline {
+    benchmarks: BTreeMap<String, Timing>,
+}
+
+#[derive(Deserialize)]
+struct Estimate {
+    point_estimate: f64,
+}
+
+/// The parts of criterion's `estimates.json` we are interested in.
+#[derive(Deserialize)]
+struct Estimates {
+    mean: Estimate,
+    median: Estimate,
+    std_dev: Estimate,
+}
+
+/// The parts of criterion's `benchmark.json` we are interested in.
+#[derive(Deserialize)]
+struct BenchmarkId {
+    full_id: String,
+}
+
+/// Collects the results of the last run of every benchmark in the criterion directory.
+fn collect(dir: &Path, baseline: &mut Baseline) -> Result<(), Error> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if !path.is_dir() {
+            continue;
+        }
+
+        // Criterion keeps the latest results of a benchmark in its `new` directory.
+        if path.file_name().map_or(false, |name| name == "new") {
+            let estimates: Estimates =
+                serde_json::from_str(&fs::read_to_string(path.join("estimates.json"))?)?;
+            let id: BenchmarkId =
+                serde_json::from_str(&fs::read_to_string(path.join("benchmark.json"))?)?;
+
+            baseline.benchmarks.insert(
+                id.full_id,
+                Timing {
+                    mean_ns: estimates.mean.point_estimate,
+                    median_ns: estimates.median.point_estimate,
+                    std_dev_ns: estimates.std_dev.point_estimate,
+                },
+            );
+        } else {
+            collect(&path, baseline)?;
+        }
+    }
+    Ok(())
+}
+
+fn current(matches: &ArgMatches) -> Result<Baseline, Error> {
+    let dir = PathBuf::from(matches.value_of("criterion_dir").unwrap_or(CRITERION_DIR));
+    let mut baseline = Baseline::default();
+    collect(&dir, &mut baseline)
+        .map_err(|e| anyhow!("Failed to read results from {}: {}", dir.display(), e))?;
+
+    if baseline.benchmarks.is_empty() {
+        return Err(anyhow!(
+            "No benchmark results in {}, run the benchmarks first",
+            dir.display()
+        ));
+    }
+    Ok(baseline)
+}
+
+fn save(matches: &ArgMatches) -> Result<(), Error> {
+    let baseline = current(matches)?;
+    let file = matches.value_of("baseline").unwrap();
+    fs::write(file, serde_json::to_string_pretty(&baseline)?)?;
+
+    println!("Saved {} benchmarks to {}", baseline.benchmarks.len(), file);
+    Ok(())
+}
+
+/// Compares the current results to the baseline. Returns whether any benchmark regressed.
+fn compare(matches: &ArgMatches) -> Result<bool, Error> {
+    let file = matches.value_of("baseline").unwrap();
+    let baseline: Baseline = serde_json::from_str(&fs::read_to_string(file)?)?;
+    let current = current(matches)?;
+    let threshold = match matches.value_of("threshold") {
+        Some(s) => s.parse::<f64>()?,
+        None => 10.0,
+    };
+
+    let mut regressed = false;
+    for (name, timing) in &current.benchmarks {
+        let base = match baseline.benchmarks.get(name) {
+            Some(base) => base,
+            None => {
+                println!("{:<48} {:>14.0} ns  (new)", name, timing.mean_ns);
+                continue;
+            }
+        };
+
+        let change = (timing.mean_ns - base.mean_ns) / base.mean_ns * 100.0;
+        let verdict = if change > threshold {
+            regressed = true;
+            "REGRESSED"
+        } else if change < -threshold {
+            "improved"
+        } else {
+            ""
+        };
+        println!(
+            "{:<48} {:>14.0} ns  {:>+8.2}%  {}",
+            name, timing.mean_ns, change, verdict
+        );
+    }
+
+    for name in baseline.benchmarks.keys() {
+        if !current.benchmarks.contains_key(name) {
+            println!("{:<48} {:>14}     (missing)", name, "-");
+        }
+    }
+
+    Ok(regressed)
+}
+
+fn run_app() -> Result<bool, Error> {
+    let criterion_dir = Arg::new("criterion_dir")
+        .long("criterion-dir")
+        .value_name("DIR")
+        .help("The directory criterion wrote its results to. Defaults to target/criterion.")
+        .takes_value(true);
+    let baseline = Arg::new("baseline")
+        .value_name("FILE")
+        .help("The baseline file.")
+        .required(true)
+        .takes_value(true);
+
+    let matches = Command::new("Benchmark baselines")
+        .version(crate_version!())
+        .author(crate_authors!())
+        .about(crate_description!())
+        .subcommand_required(true)
+        .subcommand(
+            Command::new("save")
+                .about("Saves the results of the last benchmark run as a baseline.")
+                .arg(baseline.clone())
+                .arg(criterion_dir.clone()),
+        )
+        .subcommand(
+            Command::new("compare")
+                .about("Compares the results of the last benchmark run to a baseline. Exits with an error if a benchmark got slower by more than the threshold.")
+                .arg(baseline)
+                .arg(criterion_dir)
+                .arg(
+                    Arg::new("threshold")
+                        .short('t')
+                        .long("threshold")
+                        .value_name("PERCENT")
+                        .help("The slowdown in percent of the mean that counts as a regression. Defaults to 10.")
+                        .takes_value(true),
+                ),
+        )
+        .get_matches();
+
+    match matches.subcommand() {
+        Some(("save", matches)) => save(matches).map(|_| false),
+        Some(("compare", matches)) => compare(matches),
+        _ => unreachable!(),
+    }
+}
+
+fn main() {
+    match run_app() {
+        Ok(false) => {}
+        Ok(true) => {
+            eprintln!("Benchmarks regressed");
+            exit(2);
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            exit(1);
+        }
+    }
+}
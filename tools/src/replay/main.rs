@@ -0,0 +1,105 @@
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Error;
+use clap::{crate_authors, crate_description, crate_version, Arg, Command};
+
+use nimiq_blockchain::{AbstractBlockchain, Blockchain, BlockchainLock};
+use nimiq_consensus::sync::history::HistorySync;
+use nimiq_consensus::{Consensus, RequestServing};
+use nimiq_database::volatile::VolatileEnvironment;
+use nimiq_network_interface::network::Network;
+use nimiq_network_interface::recording::RecordingReader;
+use nimiq_network_mock::{replay::replay, MockHub, MockNetwork};
+use nimiq_primitives::networks::NetworkId;
+use nimiq_utils::time::OffsetTime;
+
+/// Time to let the node process the last replayed messages before printing its state.
+const SETTLE_TIME: Duration = Duration::from_secs(2);
+
+async fn run_app() -> Result<(), Error> {
+    let matches = Command::new("Replay recorded network messages")
+        .version(crate_version!())
+        .author(crate_authors!())
+        .about(crate_description!())
+        .arg(
+            Arg::new("recording")
+                .value_name("FILE")
+                .help("The recording to replay.")
+                .required(true)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("network_id")
+                .short('N')
+                .long("network")
+                .value_name("NETWORK")
+                .help("The network the recording was made on.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("speedup")
+                .short('s')
+                .long("speedup")
+                .value_name("FACTOR")
+                .help("Replay FACTOR times faster than recorded.")
+                .takes_value(true),
+        )
+        .get_matches();
+
+    let network_id = match matches.value_of("network_id") {
+        Some(s) => NetworkId::from_str(s)?,
+        None => NetworkId::DevAlbatross,
+    };
+    let speedup = match matches.value_of("speedup") {
+        Some(s) => u32::from_str(s)?,
+        None => 1,
+    };
+    let messages = RecordingReader::open(matches.value_of("recording").unwrap())?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Set up a fresh node on a mock network. Addresses handed out by the hub start at 1, so the
+    // node's address doesn't collide with the replay sources.
+    let mut hub = MockHub::new();
+    let network = Arc::new(hub.new_network_with_address(0));
+    let env = VolatileEnvironment::new(12)?;
+    let blockchain = Arc::new(BlockchainLock::new(Blockchain::new(
+        env.clone(),
+        network_id,
+        Arc::new(OffsetTime::new()),
+    )?));
+    let sync = HistorySync::<MockNetwork>::new(Arc::clone(&blockchain), network.subscribe_events());
+    let consensus = Consensus::with_min_peers(
+        env,
+        Arc::clone(&blockchain),
+        Arc::clone(&network),
+        Box::pin(sync),
+        1,
+        RequestServing::Full,
+    )
+    .await;
+    tokio::spawn(consensus);
+
+    println!("Replaying {} messages", messages.len());
+    let num_replayed = replay(&mut hub, &network, messages, speedup).await?;
+    tokio::time::sleep(SETTLE_TIME).await;
+
+    let blockchain = blockchain.read();
+    println!("Replayed {} messages", num_replayed);
+    println!(
+        "Head: #{} {}",
+        blockchain.block_number(),
+        blockchain.head_hash()
+    );
+
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() {
+    if let Err(e) = run_app().await {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
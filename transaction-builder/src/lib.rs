@@ -79,6 +79,12 @@ pub enum TransactionBuilderError {
     /// [`signalling transaction`]: struct.TransactionBuilder.html#method.with_value
     #[error("The value must be zero for signalling transactions and cannot be zero for others.")]
     InvalidValue,
+    /// The signature of an offline staker key doesn't match the transaction.
+    #[error("The staker signature is invalid for this transaction.")]
+    InvalidStakerSignature,
+    /// The transaction also requires the signature of the sender, but no key pair was given.
+    #[error("The transaction requires a sender key pair.")]
+    NoSenderKeyPair,
 }
 
 /// A helper to build arbitrary transactions.
@@ -1492,3 +1498,173 @@ impl TransactionBuilder {
         }
     }
 }
+
+// Cold staking. The staker key is kept offline, so staker transactions are created unsigned. The
+// serialized content of the unsigned transaction is signed with the staker key elsewhere and the
+// signature is then added using `sign_staker_transaction`.
+impl TransactionBuilder {
+    /// Creates an unsigned transaction that creates a new staker with a given initial stake and
+    /// delegation. The staker address is derived from the key that signs the transaction later.
+    ///
+    /// # Arguments
+    ///
+    ///  - `sender`:                The basic account that sends the initial stake. Its key pair
+    ///                             is needed again to finalize the transaction.
+    ///  - `delegation`:            The (optional) delegation to a validator.
+    ///  - `value`:                 The value for the initial stake.
+    ///  - `fee`:                   Transaction fee.
+    ///  - `validity_start_height`: Block height from which this transaction is valid.
+    ///  - `network_id`:            ID of network for which the transaction is meant.
+    ///
+    /// # Returns
+    ///
+    /// The unsigned transaction.
+    ///
+    pub fn new_unsigned_create_staker(
+        sender: Address,
+        delegation: Option<Address>,
+        value: Coin,
+        fee: Coin,
+        validity_start_height: u32,
+        network_id: NetworkId,
+    ) -> Transaction {
+        let mut recipient = Recipient::new_staking_builder();
+        recipient.create_staker(delegation);
+
+        let mut builder = Self::new();
+        builder
+            .with_sender(sender)
+            .with_recipient(recipient.generate().unwrap())
+            .with_value(value)
+            .with_fee(fee)
+            .with_validity_start_height(validity_start_height)
+            .with_network_id(network_id);
+
+        builder
+            .generate()
+            .unwrap()
+            .preliminary_transaction()
+            .clone()
+    }
+
+    /// Creates an unsigned update staker transaction that changes the delegation. The fee is paid
+    /// from the basic account `sender`.
+    ///
+    /// # Arguments
+    ///
+    ///  - `sender`:                The basic account that pays the fee. Its key pair is needed
+    ///                             again to finalize the transaction.
+    ///  - `new_delegation`:        The new delegation.
+    ///  - `fee`:                   Transaction fee.
+    ///  - `validity_start_height`: Block height from which this transaction is valid.
+    ///  - `network_id`:            ID of network for which the transaction is meant.
+    ///
+    /// # Returns
+    ///
+    /// The unsigned transaction.
+    ///
+    pub fn new_unsigned_update_staker(
+        sender: Address,
+        new_delegation: Option<Address>,
+        fee: Coin,
+        validity_start_height: u32,
+        network_id: NetworkId,
+    ) -> Transaction {
+        let mut recipient = Recipient::new_staking_builder();
+        recipient.update_staker(new_delegation);
+
+        let mut builder = Self::new();
+        builder
+            .with_sender(sender)
+            .with_recipient(recipient.generate().unwrap())
+            .with_value(Coin::ZERO)
+            .with_fee(fee)
+            .with_validity_start_height(validity_start_height)
+            .with_network_id(network_id);
+
+        builder
+            .generate()
+            .unwrap()
+            .preliminary_transaction()
+            .clone()
+    }
+
+    /// Creates an unsigned transaction to move stake from the staking contract to a basic
+    /// `recipient` address. The staker is derived from the key that signs the transaction later.
+    ///
+    /// # Arguments
+    ///
+    ///  - `recipient`:             The basic address that will receive the unstaked funds.
+    ///  - `value`:                 The value to be moved from the staker.
+    ///  - `fee`:                   Transaction fee.
+    ///  - `validity_start_height`: Block height from which this transaction is valid.
+    ///  - `network_id`:            ID of network for which the transaction is meant.
+    ///
+    /// # Returns
+    ///
+    /// The unsigned transaction.
+    ///
+    pub fn new_unsigned_unstake(
+        recipient: Address,
+        value: Coin,
+        fee: Coin,
+        validity_start_height: u32,
+        network_id: NetworkId,
+    ) -> Transaction {
+        let mut builder = Self::new();
+        builder
+            .with_sender(STAKING_CONTRACT_ADDRESS)
+            .with_sender_type(AccountType::Staking)
+            .with_recipient(Recipient::new_basic(recipient))
+            .with_value(value)
+            .with_fee(fee)
+            .with_validity_start_height(validity_start_height)
+            .with_network_id(network_id);
+
+        builder
+            .generate()
+            .unwrap()
+            .preliminary_transaction()
+            .clone()
+    }
+
+    /// Adds the signature of the staker to an unsigned staker transaction.
+    ///
+    /// # Arguments
+    ///
+    ///  - `transaction`:  The unsigned transaction.
+    ///  - `staker_proof`: The staker's signature over the serialized content of the unsigned
+    ///                    transaction.
+    ///  - `key_pair`:     The key pair of the sender. It is required for incoming staking
+    ///                    transactions, which are also signed by the sender.
+    ///
+    /// # Returns
+    ///
+    /// The finalized transaction or an error if the signature doesn't match the transaction or
+    /// the sender key pair is missing.
+    ///
+    pub fn sign_staker_transaction(
+        transaction: Transaction,
+        staker_proof: SignatureProof,
+        key_pair: Option<&KeyPair>,
+    ) -> Result<Transaction, TransactionBuilderError> {
+        if !staker_proof.verify(transaction.serialize_content().as_slice()) {
+            return Err(TransactionBuilderError::InvalidStakerSignature);
+        }
+
+        match TransactionProofBuilder::new(transaction) {
+            TransactionProofBuilder::InStaking(mut builder) => {
+                let key_pair = key_pair.ok_or(TransactionBuilderError::NoSenderKeyPair)?;
+                builder.set_signature(staker_proof);
+                let mut builder = builder.generate().unwrap().unwrap_basic();
+                builder.sign_with_key_pair(key_pair);
+                Ok(builder.generate().unwrap())
+            }
+            TransactionProofBuilder::OutStaking(mut builder) => {
+                builder.unstake_with_signature(staker_proof);
+                Ok(builder.generate().unwrap())
+            }
+            _ => Err(TransactionBuilderError::InvalidSender),
+        }
+    }
+}
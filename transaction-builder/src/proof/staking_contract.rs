@@ -48,6 +48,22 @@ impl StakingDataBuilder {
         self
     }
 
+    /// This method sets the required `signature` proof from a signature that was created
+    /// externally, e.g. by an offline key. The signature must be over the serialized content of
+    /// the preliminary transaction.
+    pub fn set_signature(&mut self, proof: SignatureProof) -> &mut Self {
+        let mut data: IncomingStakingTransactionData =
+            Deserialize::deserialize_from_vec(&self.transaction.data[..]).unwrap();
+
+        match data {
+            IncomingStakingTransactionData::Stake { .. } => {}
+            _ => data.set_signature(proof),
+        }
+
+        self.data = Some(data);
+        self
+    }
+
     /// This method returns the next proof builder to be used if the staking data signature
     /// has been set correctly.
     /// Otherwise, it returns `None`.
@@ -97,6 +113,14 @@ impl StakingProofBuilder {
         self
     }
 
+    /// This methods sets the action to unstake from a staker's signature that was created
+    /// externally, e.g. by an offline key. The signature must be over the serialized content of
+    /// the preliminary transaction.
+    pub fn unstake_with_signature(&mut self, proof: SignatureProof) -> &mut Self {
+        self.proof = Some(OutgoingStakingTransactionProof::Unstake { proof });
+        self
+    }
+
     /// This method generates the final transaction if the proof has been set correctly.
     /// Otherwise, it returns `None`.
     pub fn generate(self) -> Option<Transaction> {
@@ -3,7 +3,7 @@ use std::convert::TryInto;
 use beserial::{Deserialize, Serialize};
 use nimiq_bls::KeyPair as BlsKeyPair;
 use nimiq_hash::Blake2bHash;
-use nimiq_keys::{Address, KeyPair, PrivateKey};
+use nimiq_keys::{Address, KeyPair, PrivateKey, SecureGenerate};
 use nimiq_primitives::account::AccountType;
 use nimiq_primitives::networks::NetworkId;
 use nimiq_primitives::policy::{STAKING_CONTRACT_ADDRESS, VALIDATOR_DEPOSIT};
@@ -11,7 +11,7 @@ use nimiq_transaction::account::staking_contract::{
     IncomingStakingTransactionData, OutgoingStakingTransactionProof,
 };
 use nimiq_transaction::{SignatureProof, Transaction};
-use nimiq_transaction_builder::TransactionBuilder;
+use nimiq_transaction_builder::{TransactionBuilder, TransactionBuilderError};
 
 const ADDRESS: &str = "9cd82948650d902d95d52ea2ec91eae6deb0c9fe";
 const PRIVATE_KEY: &str = "b410a7a583cbc13ef4f1cbddace30928bcb4f9c13722414bc4a2faaba3f4e187";
@@ -122,6 +122,91 @@ fn it_can_create_staker_transactions() {
     assert_eq!(tx, tx2);
 }
 
+#[test]
+fn it_can_create_cold_staker_transactions() {
+    let key_pair = ed25519_key_pair();
+    let staker_key_pair = KeyPair::generate_default_csprng();
+    let address = Address::from_any_str(ADDRESS).unwrap();
+    let sign = |tx: &Transaction, key_pair: &KeyPair| {
+        SignatureProof::from(key_pair.public, key_pair.sign(&tx.serialize_content()))
+    };
+
+    // Create staker
+    let unsigned = TransactionBuilder::new_unsigned_create_staker(
+        Address::from(&key_pair),
+        Some(address.clone()),
+        100_000_000.try_into().unwrap(),
+        100.try_into().unwrap(),
+        1,
+        NetworkId::Dummy,
+    );
+    let proof = sign(&unsigned, &staker_key_pair);
+
+    let tx = TransactionBuilder::sign_staker_transaction(unsigned.clone(), proof.clone(), None);
+    assert!(matches!(tx, Err(TransactionBuilderError::NoSenderKeyPair)));
+
+    let tx = TransactionBuilder::sign_staker_transaction(unsigned, proof, Some(&key_pair)).unwrap();
+    let tx2 = TransactionBuilder::new_create_staker(
+        &key_pair,
+        &staker_key_pair,
+        Some(address.clone()),
+        100_000_000.try_into().unwrap(),
+        100.try_into().unwrap(),
+        1,
+        NetworkId::Dummy,
+    );
+    assert_eq!(tx, tx2);
+
+    // Update
+    let unsigned = TransactionBuilder::new_unsigned_update_staker(
+        Address::from(&key_pair),
+        None,
+        100.try_into().unwrap(),
+        1,
+        NetworkId::Dummy,
+    );
+    let proof = sign(&unsigned, &staker_key_pair);
+
+    let tx = TransactionBuilder::sign_staker_transaction(unsigned, proof, Some(&key_pair)).unwrap();
+    let tx2 = TransactionBuilder::new_update_staker(
+        Some(&key_pair),
+        &staker_key_pair,
+        None,
+        100.try_into().unwrap(),
+        1,
+        NetworkId::Dummy,
+    );
+    assert_eq!(tx, tx2);
+
+    // Unstake
+    let unsigned = TransactionBuilder::new_unsigned_unstake(
+        address.clone(),
+        150_000_000.try_into().unwrap(),
+        100.try_into().unwrap(),
+        1,
+        NetworkId::Dummy,
+    );
+
+    // A signature of another transaction is rejected.
+    let wrong_proof = sign(&tx2, &staker_key_pair);
+    assert!(matches!(
+        TransactionBuilder::sign_staker_transaction(unsigned.clone(), wrong_proof, None),
+        Err(TransactionBuilderError::InvalidStakerSignature)
+    ));
+
+    let proof = sign(&unsigned, &staker_key_pair);
+    let tx = TransactionBuilder::sign_staker_transaction(unsigned, proof, None).unwrap();
+    let tx2 = TransactionBuilder::new_unstake(
+        &staker_key_pair,
+        address,
+        150_000_000.try_into().unwrap(),
+        100.try_into().unwrap(),
+        1,
+        NetworkId::Dummy,
+    );
+    assert_eq!(tx, tx2);
+}
+
 #[test]
 fn it_can_create_validator_transactions() {
     let bls_pair = bls_key_pair();
@@ -0,0 +1,211 @@
+use std::fmt;
+
+use rand::{CryptoRng, Rng};
+
+use nimiq_keys::{Address, KeyPair, SecureGenerate};
+use nimiq_primitives::coin::Coin;
+use nimiq_primitives::networks::NetworkId;
+use nimiq_transaction::Transaction;
+use nimiq_transaction_builder::TransactionBuilder;
+
+/// The kind of a generated transaction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TransactionKind {
+    /// Funds one of the senders from the funding account.
+    Funding,
+    /// A basic transaction to a random address.
+    Basic,
+    /// Creates a staker with a random key.
+    Staking,
+}
+
+impl fmt::Display for TransactionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TransactionKind::Funding => "funding",
+            TransactionKind::Basic => "basic",
+            TransactionKind::Staking => "staking",
+        })
+    }
+}
+
+/// The shape of the generated load.
+#[derive(Clone, Debug)]
+pub struct LoadProfile {
+    /// The number of bytes of random data attached to basic transactions.
+    pub data_size: usize,
+    /// The share of staking transactions, between 0 and 1.
+    pub staking_ratio: f64,
+    /// The value of every transaction.
+    pub value: Coin,
+    /// The fee of every transaction.
+    pub fee: Coin,
+}
+
+/// Generates the transactions of the load, sending from the senders in turn.
+pub struct Generator {
+    senders: Vec<KeyPair>,
+    next_sender: usize,
+    profile: LoadProfile,
+    network_id: NetworkId,
+}
+
+impl Generator {
+    pub fn new(senders: Vec<KeyPair>, profile: LoadProfile, network_id: NetworkId) -> Self {
+        assert!(!senders.is_empty(), "At least one sender is required");
+        Generator {
+            senders,
+            next_sender: 0,
+            profile,
+            network_id,
+        }
+    }
+
+    pub fn senders(&self) -> &[KeyPair] {
+        &self.senders
+    }
+
+    /// Returns the transactions that fund the senders from the funding account, such that every
+    /// sender can send `num_transactions` transactions.
+    pub fn funding_transactions(
+        &self,
+        funding_key: &KeyPair,
+        num_transactions: u64,
+        validity_start_height: u32,
+    ) -> Vec<Transaction> {
+        let amount = (self.profile.value + self.profile.fee)
+            .checked_mul(num_transactions)
+            .expect("Funding amount overflows");
+        self.senders
+            .iter()
+            .map(|sender| {
+                TransactionBuilder::new_basic(
+                    funding_key,
+                    Address::from(sender),
+                    amount,
+                    self.profile.fee,
+                    validity_start_height,
+                    self.network_id,
+                )
+            })
+            .collect()
+    }
+
+    /// Generates the next transaction of the load.
+    pub fn next_transaction<R: Rng + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        validity_start_height: u32,
+    ) -> (TransactionKind, Transaction) {
+        let sender = &self.senders[self.next_sender];
+        self.next_sender = (self.next_sender + 1) % self.senders.len();
+
+        if rng.gen_bool(self.profile.staking_ratio.clamp(0.0, 1.0)) {
+            let staker = KeyPair::generate(rng);
+            let transaction = TransactionBuilder::new_create_staker(
+                sender,
+                &staker,
+                None,
+                self.profile.value,
+                self.profile.fee,
+                validity_start_height,
+                self.network_id,
+            );
+            return (TransactionKind::Staking, transaction);
+        }
+
+        let mut recipient = [0u8; 20];
+        rng.fill_bytes(&mut recipient);
+        let recipient = Address::from(recipient);
+
+        let transaction = if self.profile.data_size > 0 {
+            let mut data = vec![0u8; self.profile.data_size];
+            rng.fill_bytes(&mut data);
+            TransactionBuilder::new_basic_with_data(
+                sender,
+                recipient,
+                data,
+                self.profile.value,
+                self.profile.fee,
+                validity_start_height,
+                self.network_id,
+            )
+        } else {
+            TransactionBuilder::new_basic(
+                sender,
+                recipient,
+                self.profile.value,
+                self.profile.fee,
+                validity_start_height,
+                self.network_id,
+            )
+        };
+        (TransactionKind::Basic, transaction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use nimiq_keys::{Address, KeyPair, SecureGenerate};
+    use nimiq_primitives::coin::Coin;
+    use nimiq_primitives::networks::NetworkId;
+
+    use super::{Generator, LoadProfile, TransactionKind};
+
+    fn generator(senders: Vec<KeyPair>, staking_ratio: f64) -> Generator {
+        Generator::new(
+            senders,
+            LoadProfile {
+                data_size: 32,
+                staking_ratio,
+                value: Coin::from_u64_unchecked(1),
+                fee: Coin::from_u64_unchecked(200),
+            },
+            NetworkId::UnitAlbatross,
+        )
+    }
+
+    #[test]
+    fn it_rotates_the_senders() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let senders = vec![KeyPair::generate(&mut rng), KeyPair::generate(&mut rng)];
+        let mut generator = generator(senders.clone(), 0.0);
+
+        for i in 0..4 {
+            let (kind, transaction) = generator.next_transaction(&mut rng, 1);
+            assert_eq!(kind, TransactionKind::Basic);
+            assert_eq!(transaction.sender, Address::from(&senders[i % 2]));
+            assert_eq!(transaction.data.len(), 32);
+        }
+    }
+
+    #[test]
+    fn it_generates_staking_transactions() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut generator = generator(vec![KeyPair::generate(&mut rng)], 1.0);
+
+        let (kind, _) = generator.next_transaction(&mut rng, 1);
+        assert_eq!(kind, TransactionKind::Staking);
+    }
+
+    #[test]
+    fn it_funds_every_sender() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let funding_key = KeyPair::generate(&mut rng);
+        let generator = generator(
+            vec![KeyPair::generate(&mut rng), KeyPair::generate(&mut rng)],
+            0.0,
+        );
+
+        let transactions = generator.funding_transactions(&funding_key, 10, 1);
+        assert_eq!(transactions.len(), 2);
+        for (transaction, sender) in transactions.iter().zip(generator.senders()) {
+            assert_eq!(transaction.sender, Address::from(&funding_key));
+            assert_eq!(transaction.recipient, Address::from(sender));
+            assert_eq!(transaction.value, Coin::from_u64_unchecked(201 * 10));
+        }
+    }
+}
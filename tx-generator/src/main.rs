@@ -0,0 +1,331 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail};
+use futures::stream::{BoxStream, StreamExt};
+use rand::thread_rng;
+use structopt::StructOpt;
+use tokio::time::{interval, timeout_at, Instant};
+
+use nimiq_hash::Blake2bHash;
+use nimiq_keys::{KeyPair, PrivateKey, SecureGenerate};
+use nimiq_primitives::coin::Coin;
+use nimiq_primitives::networks::NetworkId;
+use nimiq_rpc_client::{Credentials, Url};
+use nimiq_transaction::Transaction;
+
+use crate::generator::{Generator, LoadProfile, TransactionKind};
+use crate::stats::LoadStats;
+use crate::target::{GossipTarget, RpcTarget, Target};
+
+mod generator;
+mod stats;
+mod target;
+
+/// How often the progress is logged.
+const REPORT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The lowest rate in transactions per second, i.e. at most 1000 seconds between transactions.
+const MIN_RATE: f64 = 0.001;
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "nimiq-tx-generator",
+    about = "Generates a transaction load against a Nimiq node and measures its inclusion",
+    rename_all = "kebab"
+)]
+struct Opt {
+    /// The private key of the account that pays for the load, as hex.
+    #[structopt(long, short = "k")]
+    private_key: String,
+
+    /// The network to create the transactions for. Only used if it can't be obtained from the node.
+    #[structopt(long, default_value = "devalbatross")]
+    network: NetworkId,
+
+    /// Transactions per second to send.
+    #[structopt(long, short = "r", default_value = "10", parse(try_from_str = parse_rate))]
+    rate: f64,
+
+    /// For how many seconds to send transactions.
+    #[structopt(long, short = "d", default_value = "60")]
+    duration: u64,
+
+    /// How many seconds to wait for the inclusion of the pending transactions after sending.
+    #[structopt(long, default_value = "30")]
+    drain: u64,
+
+    /// The number of accounts sending the transactions. If greater than 1, new accounts are
+    /// created and funded before the load starts.
+    #[structopt(long, short = "s", default_value = "1")]
+    senders: usize,
+
+    /// The number of bytes of random data to attach to basic transactions.
+    #[structopt(long, default_value = "0")]
+    data_size: usize,
+
+    /// The share of staking transactions, between 0 and 1.
+    #[structopt(long, default_value = "0", parse(try_from_str = parse_ratio))]
+    staking_ratio: f64,
+
+    /// The value of every transaction in NIM.
+    #[structopt(long, default_value = "0.00001")]
+    value: Coin,
+
+    /// The fee of every transaction in NIM.
+    #[structopt(long, default_value = "0.002")]
+    fee: Coin,
+
+    /// Write the inclusion latency of every transaction to this CSV file.
+    #[structopt(long, short = "o")]
+    output: Option<PathBuf>,
+
+    #[structopt(subcommand)]
+    command: Command,
+}
+
+fn parse_rate(s: &str) -> Result<f64, String> {
+    let rate = f64::from_str(s).map_err(|e| e.to_string())?;
+    // Also rejects NaN, which would panic when the interval between transactions is computed.
+    if !(MIN_RATE..=f64::MAX).contains(&rate) {
+        return Err(format!("The rate must be at least {} tx/s", MIN_RATE));
+    }
+    Ok(rate)
+}
+
+fn parse_ratio(s: &str) -> Result<f64, String> {
+    let ratio = f64::from_str(s).map_err(|e| e.to_string())?;
+    if !(0.0..=1.0).contains(&ratio) {
+        return Err("The ratio must be between 0 and 1".to_string());
+    }
+    Ok(ratio)
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Send the transactions over the JSON RPC interface of a node.
+    Rpc {
+        #[structopt(short, long, default_value = "ws://127.0.0.1:8648/ws")]
+        url: Url,
+
+        #[structopt(short = "U")]
+        username: Option<String>,
+
+        #[structopt(short = "P")]
+        password: Option<String>,
+    },
+
+    /// Run a client and publish the transactions directly on the network.
+    Gossip {
+        /// Use a custom configuration file for the client.
+        #[structopt(long, short = "c")]
+        config: Option<PathBuf>,
+    },
+}
+
+/// Follows the chain and records the inclusion of the sent transactions.
+struct Chain {
+    heads: BoxStream<'static, Blake2bHash>,
+    block_number: u32,
+}
+
+impl Chain {
+    async fn on_head(
+        &mut self,
+        target: &mut dyn Target,
+        stats: &mut LoadStats,
+        hash: Blake2bHash,
+    ) -> anyhow::Result<()> {
+        let (block_number, transactions) = target.block_transactions(hash).await?;
+        let included = stats.on_block(block_number, &transactions, Instant::now().into_std());
+        log::debug!(
+            "Block #{} includes {} of {} transactions of the load",
+            block_number,
+            included,
+            transactions.len()
+        );
+        self.block_number = self.block_number.max(block_number);
+        Ok(())
+    }
+
+    /// Processes the new blocks until no transactions of the given kind are pending or the
+    /// deadline is reached.
+    async fn wait_for_inclusion(
+        &mut self,
+        target: &mut dyn Target,
+        stats: &mut LoadStats,
+        kind: Option<TransactionKind>,
+        deadline: Instant,
+    ) -> anyhow::Result<()> {
+        let pending = |stats: &LoadStats| match kind {
+            Some(kind) => stats.num_pending(kind),
+            None => stats.summary().pending,
+        };
+
+        while pending(stats) > 0 {
+            match timeout_at(deadline, self.heads.next()).await {
+                Ok(Some(hash)) => self.on_head(target, stats, hash).await?,
+                Ok(None) => bail!("Head subscription ended"),
+                Err(_) => break,
+            }
+        }
+        Ok(())
+    }
+}
+
+async fn send(
+    target: &mut dyn Target,
+    stats: &mut LoadStats,
+    kind: TransactionKind,
+    transaction: Transaction,
+) {
+    match target.send_transaction(transaction).await {
+        Ok(hash) => stats.on_sent(hash, kind, Instant::now().into_std()),
+        Err(e) => {
+            log::debug!("Failed to send {} transaction: {}", kind, e);
+            stats.on_rejected();
+        }
+    }
+}
+
+async fn run(opt: Opt) -> anyhow::Result<()> {
+    let funding_key = KeyPair::from(
+        PrivateKey::from_str(&opt.private_key)
+            .map_err(|e| anyhow!("Invalid private key: {:?}", e))?,
+    );
+
+    let mut target: Box<dyn Target> = match opt.command {
+        Command::Rpc {
+            url,
+            username,
+            password,
+        } => {
+            let credentials = match (username, password) {
+                (Some(username), Some(password)) => Some(Credentials { username, password }),
+                (None, None) => None,
+                _ => bail!("Both username and password needs to be specified."),
+            };
+            Box::new(RpcTarget::connect(url, credentials).await?)
+        }
+        Command::Gossip { config } => Box::new(GossipTarget::start(config.as_deref()).await?),
+    };
+    let target = target.as_mut();
+
+    let network_id = target.network_id().unwrap_or(opt.network);
+    let mut chain = Chain {
+        heads: target.head_subscribe().await?,
+        block_number: target.block_number().await?,
+    };
+    let mut stats = LoadStats::default();
+    let mut rng = thread_rng();
+
+    let profile = LoadProfile {
+        data_size: opt.data_size,
+        staking_ratio: opt.staking_ratio,
+        value: opt.value,
+        fee: opt.fee,
+    };
+
+    let mut generator = if opt.senders > 1 {
+        let senders = (0..opt.senders)
+            .map(|_| KeyPair::generate(&mut rng))
+            .collect();
+        let generator = Generator::new(senders, profile, network_id);
+
+        // Fund the senders with enough for their share of the load.
+        let num_transactions = opt.rate * opt.duration as f64 / opt.senders as f64;
+        let funding = generator.funding_transactions(
+            &funding_key,
+            num_transactions.ceil() as u64 + 1,
+            chain.block_number,
+        );
+        log::info!("Funding {} senders", funding.len());
+        for transaction in funding {
+            send(target, &mut stats, TransactionKind::Funding, transaction).await;
+        }
+
+        let deadline = Instant::now() + Duration::from_secs(opt.drain);
+        chain
+            .wait_for_inclusion(target, &mut stats, Some(TransactionKind::Funding), deadline)
+            .await?;
+        if stats.num_pending(TransactionKind::Funding) > 0 {
+            bail!("Not all senders were funded");
+        }
+        stats = LoadStats::default();
+
+        generator
+    } else {
+        Generator::new(vec![funding_key], profile, network_id)
+    };
+
+    log::info!(
+        "Sending {} tx/s for {}s from {} senders",
+        opt.rate,
+        opt.duration,
+        generator.senders().len()
+    );
+
+    let deadline = Instant::now() + Duration::from_secs(opt.duration);
+    let mut ticker = interval(Duration::from_secs_f64(1.0 / opt.rate));
+    let mut report = interval(REPORT_INTERVAL);
+
+    loop {
+        tokio::select! {
+            now = ticker.tick() => {
+                if now >= deadline {
+                    break;
+                }
+                let (kind, transaction) = generator.next_transaction(&mut rng, chain.block_number);
+                send(target, &mut stats, kind, transaction).await;
+            }
+            hash = chain.heads.next() => match hash {
+                Some(hash) => chain.on_head(target, &mut stats, hash).await?,
+                None => bail!("Head subscription ended"),
+            },
+            _ = report.tick() => log::info!("{}", stats.summary()),
+        }
+    }
+
+    log::info!("Waiting for the pending transactions to be included");
+    let deadline = Instant::now() + Duration::from_secs(opt.drain);
+    chain
+        .wait_for_inclusion(target, &mut stats, None, deadline)
+        .await?;
+
+    println!("{}", stats.summary());
+
+    if let Some(path) = opt.output {
+        stats.write_csv(BufWriter::new(File::create(&path)?))?;
+        log::info!("Wrote the inclusions to {}", path.display());
+    }
+
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    pretty_env_logger::init();
+    run(Opt::from_args()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_rejects_invalid_rates() {
+        assert_eq!(parse_rate("2.5"), Ok(2.5));
+        assert_eq!(parse_rate("0.001"), Ok(0.001));
+        for rate in ["0", "-1", "NaN", "inf", "1e-30", "fast"] {
+            assert!(parse_rate(rate).is_err(), "{}", rate);
+        }
+
+        assert_eq!(parse_ratio("0.25"), Ok(0.25));
+        for ratio in ["-0.1", "1.5", "NaN"] {
+            assert!(parse_ratio(ratio).is_err(), "{}", ratio);
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use nimiq_hash::Blake2bHash;
+
+use crate::generator::TransactionKind;
+
+/// A transaction that was included in a block.
+#[derive(Clone, Debug)]
+pub struct Inclusion {
+    pub hash: Blake2bHash,
+    pub kind: TransactionKind,
+    pub block_number: u32,
+    /// The time between sending the transaction and seeing the block that includes it.
+    pub latency: Duration,
+}
+
+/// Tracks the sent transactions until they are included in a block.
+#[derive(Default)]
+pub struct LoadStats {
+    pending: HashMap<Blake2bHash, (TransactionKind, Instant)>,
+    inclusions: Vec<Inclusion>,
+    sent: usize,
+    rejected: usize,
+}
+
+impl LoadStats {
+    pub fn on_sent(&mut self, hash: Blake2bHash, kind: TransactionKind, now: Instant) {
+        self.sent += 1;
+        self.pending.insert(hash, (kind, now));
+    }
+
+    pub fn on_rejected(&mut self) {
+        self.sent += 1;
+        self.rejected += 1;
+    }
+
+    /// Records the inclusion of the pending transactions among the given ones. Returns the number
+    /// of transactions of the load that were included.
+    pub fn on_block(
+        &mut self,
+        block_number: u32,
+        transactions: &[Blake2bHash],
+        now: Instant,
+    ) -> usize {
+        let mut included = 0;
+        for hash in transactions {
+            if let Some((kind, sent_at)) = self.pending.remove(hash) {
+                self.inclusions.push(Inclusion {
+                    hash: hash.clone(),
+                    kind,
+                    block_number,
+                    latency: now.saturating_duration_since(sent_at),
+                });
+                included += 1;
+            }
+        }
+        included
+    }
+
+    /// Returns the number of pending transactions of the given kind.
+    pub fn num_pending(&self, kind: TransactionKind) -> usize {
+        self.pending
+            .values()
+            .filter(|(pending_kind, _)| *pending_kind == kind)
+            .count()
+    }
+
+    pub fn summary(&self) -> Summary {
+        let mut latencies: Vec<Duration> = self
+            .inclusions
+            .iter()
+            .filter(|inclusion| inclusion.kind != TransactionKind::Funding)
+            .map(|inclusion| inclusion.latency)
+            .collect();
+        latencies.sort_unstable();
+
+        let percentile = |p: usize| {
+            latencies
+                .get((latencies.len() * p / 100).min(latencies.len().saturating_sub(1)))
+                .copied()
+                .unwrap_or_default()
+        };
+
+        Summary {
+            sent: self.sent,
+            rejected: self.rejected,
+            included: self.inclusions.len(),
+            pending: self.pending.len(),
+            latency_p50: percentile(50),
+            latency_p90: percentile(90),
+            latency_p99: percentile(99),
+            latency_max: latencies.last().copied().unwrap_or_default(),
+        }
+    }
+
+    /// Writes the included transactions as CSV.
+    pub fn write_csv<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "hash,kind,block_number,latency_ms")?;
+        for inclusion in &self.inclusions {
+            writeln!(
+                writer,
+                "{},{},{},{}",
+                inclusion.hash,
+                inclusion.kind,
+                inclusion.block_number,
+                inclusion.latency.as_millis()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The number of transactions in each state and the inclusion latencies of the load.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Summary {
+    pub sent: usize,
+    pub rejected: usize,
+    pub included: usize,
+    pub pending: usize,
+    pub latency_p50: Duration,
+    pub latency_p90: Duration,
+    pub latency_p99: Duration,
+    pub latency_max: Duration,
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "sent: {}, rejected: {}, included: {}, pending: {}, latency p50: {:?}, p90: {:?}, p99: {:?}, max: {:?}",
+            self.sent,
+            self.rejected,
+            self.included,
+            self.pending,
+            self.latency_p50,
+            self.latency_p90,
+            self.latency_p99,
+            self.latency_max
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use nimiq_hash::Blake2bHash;
+
+    use super::LoadStats;
+    use crate::generator::TransactionKind;
+
+    #[test]
+    fn it_measures_the_inclusion_latency() {
+        let start = Instant::now();
+        let mut stats = LoadStats::default();
+
+        let hashes: Vec<Blake2bHash> = (1..=4u8).map(|i| Blake2bHash::from([i; 32])).collect();
+        for (i, hash) in hashes.iter().enumerate() {
+            stats.on_sent(
+                hash.clone(),
+                TransactionKind::Basic,
+                start + Duration::from_secs(i as u64),
+            );
+        }
+        stats.on_rejected();
+
+        let unknown = Blake2bHash::from([9u8; 32]);
+        let included = stats.on_block(
+            1,
+            &[hashes[0].clone(), hashes[1].clone(), unknown],
+            start + Duration::from_secs(5),
+        );
+        assert_eq!(included, 2);
+        assert_eq!(stats.num_pending(TransactionKind::Basic), 2);
+
+        let summary = stats.summary();
+        assert_eq!(summary.sent, 5);
+        assert_eq!(summary.rejected, 1);
+        assert_eq!(summary.included, 2);
+        assert_eq!(summary.pending, 2);
+        assert_eq!(summary.latency_p50, Duration::from_secs(5));
+        assert_eq!(summary.latency_max, Duration::from_secs(5));
+
+        let mut csv = vec![];
+        stats.write_csv(&mut csv).unwrap();
+        assert_eq!(String::from_utf8(csv).unwrap().lines().count(), 3);
+    }
+}
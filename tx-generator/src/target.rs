@@ -0,0 +1,190 @@
+use std::path::Path;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use futures::stream::{self, BoxStream, StreamExt};
+
+use beserial::Serialize;
+use nimiq::client::{Client, ConsensusProxy};
+use nimiq::config::config::ClientConfig;
+use nimiq::config::config_file::ConfigFile;
+use nimiq_blockchain::{AbstractBlockchain, BlockchainEvent};
+use nimiq_hash::{Blake2bHash, Hash};
+use nimiq_primitives::networks::NetworkId;
+use nimiq_rpc_client::{Client as RpcClient, Credentials, Url};
+use nimiq_rpc_interface::{blockchain::BlockchainInterface, consensus::ConsensusInterface};
+use nimiq_transaction::Transaction;
+
+/// The node that the load is generated against.
+#[async_trait]
+pub trait Target: Send {
+    /// The network of the node, if it can be determined.
+    fn network_id(&self) -> Option<NetworkId>;
+
+    async fn block_number(&mut self) -> anyhow::Result<u32>;
+
+    /// Submits the transaction and returns its hash.
+    async fn send_transaction(&mut self, transaction: Transaction) -> anyhow::Result<Blake2bHash>;
+
+    /// Returns a stream of the hashes of the blocks that are added to the main chain.
+    async fn head_subscribe(&mut self) -> anyhow::Result<BoxStream<'static, Blake2bHash>>;
+
+    /// Returns the block number of the given block and the hashes of its transactions.
+    async fn block_transactions(
+        &mut self,
+        hash: Blake2bHash,
+    ) -> anyhow::Result<(u32, Vec<Blake2bHash>)>;
+}
+
+/// Submits the transactions over the JSON RPC interface of a node.
+pub struct RpcTarget {
+    client: RpcClient,
+}
+
+impl RpcTarget {
+    pub async fn connect(url: Url, credentials: Option<Credentials>) -> anyhow::Result<Self> {
+        Ok(RpcTarget {
+            client: RpcClient::new(url, credentials).await?,
+        })
+    }
+}
+
+#[async_trait]
+impl Target for RpcTarget {
+    fn network_id(&self) -> Option<NetworkId> {
+        None
+    }
+
+    async fn block_number(&mut self) -> anyhow::Result<u32> {
+        Ok(self.client.blockchain.get_block_number().await?)
+    }
+
+    async fn send_transaction(&mut self, transaction: Transaction) -> anyhow::Result<Blake2bHash> {
+        let raw_tx = hex::encode(transaction.serialize_to_vec());
+        Ok(self.client.consensus.send_raw_transaction(raw_tx).await?)
+    }
+
+    async fn head_subscribe(&mut self) -> anyhow::Result<BoxStream<'static, Blake2bHash>> {
+        Ok(self.client.blockchain.head_subscribe().await?)
+    }
+
+    async fn block_transactions(
+        &mut self,
+        hash: Blake2bHash,
+    ) -> anyhow::Result<(u32, Vec<Blake2bHash>)> {
+        let block_number = self
+            .client
+            .blockchain
+            .get_block_by_hash(hash, Some(false))
+            .await?
+            .number;
+        let transactions = self
+            .client
+            .blockchain
+            .get_transactions_by_block_number(block_number)
+            .await?;
+        Ok((
+            block_number,
+            transactions.into_iter().map(|tx| tx.hash).collect(),
+        ))
+    }
+}
+
+/// Runs a client that publishes the transactions directly on the gossipsub network.
+pub struct GossipTarget {
+    // Keeps the client running.
+    _client: Client,
+    consensus: ConsensusProxy,
+}
+
+impl GossipTarget {
+    /// Starts a client with the given configuration file, or the default one, and waits until it
+    /// has established consensus.
+    pub async fn start(config: Option<&Path>) -> anyhow::Result<Self> {
+        let config_file = match config {
+            Some(path) => ConfigFile::from_file(path)?,
+            None => ConfigFile::find(None)?,
+        };
+
+        let mut builder = ClientConfig::builder();
+        builder.config_file(&config_file)?;
+        let config = builder.build()?;
+
+        log::info!("Initializing client");
+        let mut client = Client::from_config(config).await?;
+
+        let consensus = client
+            .consensus()
+            .ok_or_else(|| anyhow!("Client has no consensus"))?;
+        let mut events = consensus.subscribe_events();
+        tokio::spawn(consensus);
+
+        let consensus = client.consensus_proxy();
+        log::info!("Waiting for consensus");
+        while !consensus.is_established() {
+            if events.next().await.is_none() {
+                return Err(anyhow!("Consensus stopped"));
+            }
+        }
+        log::info!("Consensus established");
+
+        Ok(GossipTarget {
+            _client: client,
+            consensus,
+        })
+    }
+}
+
+#[async_trait]
+impl Target for GossipTarget {
+    fn network_id(&self) -> Option<NetworkId> {
+        Some(self.consensus.blockchain.read().network_id())
+    }
+
+    async fn block_number(&mut self) -> anyhow::Result<u32> {
+        Ok(self.consensus.blockchain.read().block_number())
+    }
+
+    async fn send_transaction(&mut self, transaction: Transaction) -> anyhow::Result<Blake2bHash> {
+        let hash = transaction.hash::<Blake2bHash>();
+        self.consensus
+            .send_transaction(transaction)
+            .await
+            .map_err(|e| anyhow!("Failed to publish transaction: {:?}", e))?;
+        Ok(hash)
+    }
+
+    async fn head_subscribe(&mut self) -> anyhow::Result<BoxStream<'static, Blake2bHash>> {
+        let events = self.consensus.blockchain.write().notifier.as_stream();
+        Ok(events
+            .flat_map(|event| {
+                let hashes = match event {
+                    BlockchainEvent::Extended(hash)
+                    | BlockchainEvent::Finalized(hash)
+                    | BlockchainEvent::EpochFinalized(hash) => vec![hash],
+                    BlockchainEvent::Rebranched(_, new_blocks) => {
+                        new_blocks.into_iter().map(|(hash, _)| hash).collect()
+                    }
+                };
+                stream::iter(hashes)
+            })
+            .boxed())
+    }
+
+    async fn block_transactions(
+        &mut self,
+        hash: Blake2bHash,
+    ) -> anyhow::Result<(u32, Vec<Blake2bHash>)> {
+        let block = self
+            .consensus
+            .blockchain
+            .read()
+            .get_block(&hash, true, None)
+            .ok_or_else(|| anyhow!("Unknown block {}", hash))?;
+        let transactions = block
+            .transactions()
+            .map(|txs| txs.iter().map(|tx| tx.hash::<Blake2bHash>()).collect())
+            .unwrap_or_default();
+        Ok((block.block_number(), transactions))
+    }
+}
@@ -18,10 +18,14 @@ pub mod hash_rng;
 pub mod iterators;
 #[cfg(feature = "key-rng")]
 pub mod key_rng;
+#[cfg(feature = "lock-metrics")]
+pub mod lock_metrics;
 // #[cfg(feature = "locking")]
 // pub mod locking;
 #[cfg(feature = "math")]
 pub mod math;
+#[cfg(feature = "memory")]
+pub mod memory;
 #[cfg(feature = "merkle")]
 pub mod merkle;
 #[cfg(feature = "mutable-once")]
@@ -32,6 +36,10 @@ pub mod observer;
 pub mod otp;
 #[cfg(feature = "rate-limit")]
 pub mod rate_limit;
+#[cfg(feature = "shared-rng")]
+pub mod shared_rng;
+#[cfg(feature = "stall")]
+pub mod stall;
 #[cfg(feature = "tagged-signing")]
 pub mod tagged_signing;
 #[cfg(feature = "throttled-queue")]
@@ -0,0 +1,604 @@
+//! A read-write lock that records how long its call sites wait for and hold it.
+//!
+//! The statistics are kept per call site and lock mode, see [`LockStats`]. An acquisition that
+//! can't take the lock immediately is counted as contended, and the time it waited is attributed
+//! to the call sites that held the lock at that moment, readers included.
+//!
+//! The statistics are only recorded with the `lock-stats` feature. Without it,
+//! [`InstrumentedRwLock`] is a plain `parking_lot::RwLock` and [`lock_stats`] returns nothing.
+
+use std::panic::Location;
+use std::time::Duration;
+
+#[cfg(not(feature = "lock-stats"))]
+pub use parking_lot::{
+    RwLock as InstrumentedRwLock, RwLockReadGuard, RwLockUpgradableReadGuard, RwLockWriteGuard,
+};
+
+#[cfg(feature = "lock-stats")]
+pub use self::instrumented::{
+    InstrumentedRwLock, RawInstrumentedRwLock, RwLockReadGuard, RwLockUpgradableReadGuard,
+    RwLockWriteGuard,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum LockMode {
+    Read,
+    UpgradableRead,
+    Write,
+    /// The upgrade of an upgradable read guard to a write guard.
+    Upgrade,
+}
+
+/// The statistics of a call site for one lock mode.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LockStats {
+    pub location: &'static Location<'static>,
+    pub mode: LockMode,
+    pub acquisitions: u64,
+    /// The number of acquisitions that had to wait for the lock.
+    pub contended: u64,
+    pub wait_time: Duration,
+    pub max_wait_time: Duration,
+    /// The time the lock was held.
+    pub hold_time: Duration,
+    pub max_hold_time: Duration,
+    /// The time other call sites waited for the lock while this one held it.
+    pub caused_wait_time: Duration,
+}
+
+/// Returns the statistics of all call sites, ordered by location and mode. Empty unless the
+/// `lock-stats` feature is enabled.
+pub fn lock_stats() -> Vec<LockStats> {
+    #[cfg(feature = "lock-stats")]
+    return instrumented::lock_stats();
+    #[cfg(not(feature = "lock-stats"))]
+    return vec![];
+}
+
+#[cfg(feature = "lock-stats")]
+mod instrumented {
+    use std::cell::Cell;
+    use std::collections::BTreeMap;
+    use std::panic::Location;
+    use std::ptr;
+    use std::sync::atomic::{AtomicPtr, AtomicU64, Ordering};
+    use std::thread::{self, ThreadId};
+    use std::time::{Duration, Instant};
+
+    use parking_lot::lock_api::{
+        self, RawRwLock as _, RawRwLockDowngrade, RawRwLockUpgrade, RawRwLockUpgradeDowngrade,
+    };
+    use parking_lot::Mutex;
+
+    use super::{LockMode, LockStats};
+
+    /// The maximum number of call sites that are recorded. Further call sites are ignored.
+    const MAX_CALL_SITES: usize = 512;
+
+    /// The number of lock modes, see [`LockMode`].
+    const NUM_MODES: usize = 4;
+
+    struct Counters {
+        acquisitions: AtomicU64,
+        contended: AtomicU64,
+        wait_time_us: AtomicU64,
+        max_wait_time_us: AtomicU64,
+        hold_time_us: AtomicU64,
+        max_hold_time_us: AtomicU64,
+        caused_wait_time_us: AtomicU64,
+    }
+
+    #[allow(clippy::declare_interior_mutable_const)]
+    const EMPTY_COUNTERS: Counters = Counters {
+        acquisitions: AtomicU64::new(0),
+        contended: AtomicU64::new(0),
+        wait_time_us: AtomicU64::new(0),
+        max_wait_time_us: AtomicU64::new(0),
+        hold_time_us: AtomicU64::new(0),
+        max_hold_time_us: AtomicU64::new(0),
+        caused_wait_time_us: AtomicU64::new(0),
+    };
+
+    /// The counters of a call site, one per lock mode.
+    struct CallSite {
+        location: AtomicPtr<Location<'static>>,
+        counters: [Counters; NUM_MODES],
+    }
+
+    #[allow(clippy::declare_interior_mutable_const)]
+    const EMPTY_CALL_SITE: CallSite = CallSite {
+        location: AtomicPtr::new(ptr::null_mut()),
+        counters: [EMPTY_COUNTERS; NUM_MODES],
+    };
+
+    /// The call sites, in an open addressing hash table keyed by their location. A slot is
+    /// claimed by the first acquisition of its call site and never released, so looking up the
+    /// counters of a call site doesn't take a lock.
+    static CALL_SITES: [CallSite; MAX_CALL_SITES] = [EMPTY_CALL_SITE; MAX_CALL_SITES];
+
+    fn counters_of(
+        location: &'static Location<'static>,
+        mode: LockMode,
+    ) -> Option<&'static Counters> {
+        let key = location as *const Location<'static> as *mut Location<'static>;
+        let start = (key as usize >> 3) % MAX_CALL_SITES;
+        for i in 0..MAX_CALL_SITES {
+            let call_site = &CALL_SITES[(start + i) % MAX_CALL_SITES];
+            let mut current = call_site.location.load(Ordering::Acquire);
+            if current.is_null() {
+                current = match call_site.location.compare_exchange(
+                    ptr::null_mut(),
+                    key,
+                    Ordering::AcqRel,
+                    Ordering::Acquire,
+                ) {
+                    Ok(_) => key,
+                    Err(current) => current,
+                };
+            }
+            if current == key {
+                return Some(&call_site.counters[mode as usize]);
+            }
+        }
+        None
+    }
+
+    fn record_wait(
+        location: &'static Location<'static>,
+        mode: LockMode,
+        wait_time: Duration,
+        blockers: &[Holder],
+    ) {
+        let wait_time_us = wait_time.as_micros() as u64;
+        if let Some(counters) = counters_of(location, mode) {
+            counters.contended.fetch_add(1, Ordering::Relaxed);
+            counters
+                .wait_time_us
+                .fetch_add(wait_time_us, Ordering::Relaxed);
+            counters
+                .max_wait_time_us
+                .fetch_max(wait_time_us, Ordering::Relaxed);
+        }
+
+        // A call site that holds the lock more than once is only charged once.
+        let mut charged: Vec<(&'static Location<'static>, LockMode)> = vec![];
+        for blocker in blockers {
+            let call_site = (blocker.location, blocker.mode);
+            if charged.contains(&call_site) {
+                continue;
+            }
+            charged.push(call_site);
+            if let Some(counters) = counters_of(blocker.location, blocker.mode) {
+                counters
+                    .caused_wait_time_us
+                    .fetch_add(wait_time_us, Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn record_hold(holder: &Holder) {
+        let hold_time_us = holder.since.elapsed().as_micros() as u64;
+        if let Some(counters) = counters_of(holder.location, holder.mode) {
+            counters
+                .hold_time_us
+                .fetch_add(hold_time_us, Ordering::Relaxed);
+            counters
+                .max_hold_time_us
+                .fetch_max(hold_time_us, Ordering::Relaxed);
+        }
+    }
+
+    pub(super) fn lock_stats() -> Vec<LockStats> {
+        // The same call site might have several locations if its function was instantiated more
+        // than once, those are merged.
+        let mut stats: BTreeMap<(&'static str, u32, u32, LockMode), LockStats> = BTreeMap::new();
+        for call_site in CALL_SITES.iter() {
+            let location = call_site.location.load(Ordering::Acquire);
+            if location.is_null() {
+                continue;
+            }
+            // Only `&'static Location` references are stored in the table.
+            let location: &'static Location<'static> = unsafe { &*location };
+
+            for (mode, counters) in [
+                LockMode::Read,
+                LockMode::UpgradableRead,
+                LockMode::Write,
+                LockMode::Upgrade,
+            ]
+            .into_iter()
+            .zip(&call_site.counters)
+            {
+                let acquisitions = counters.acquisitions.load(Ordering::Relaxed);
+                if acquisitions == 0 {
+                    continue;
+                }
+                let load =
+                    |counter: &AtomicU64| Duration::from_micros(counter.load(Ordering::Relaxed));
+
+                let entry = stats
+                    .entry((location.file(), location.line(), location.column(), mode))
+                    .or_insert(LockStats {
+                        location,
+                        mode,
+                        acquisitions: 0,
+                        contended: 0,
+                        wait_time: Duration::ZERO,
+                        max_wait_time: Duration::ZERO,
+                        hold_time: Duration::ZERO,
+                        max_hold_time: Duration::ZERO,
+                        caused_wait_time: Duration::ZERO,
+                    });
+                entry.acquisitions += acquisitions;
+                entry.contended += counters.contended.load(Ordering::Relaxed);
+                entry.wait_time += load(&counters.wait_time_us);
+                entry.max_wait_time = entry.max_wait_time.max(load(&counters.max_wait_time_us));
+                entry.hold_time += load(&counters.hold_time_us);
+                entry.max_hold_time = entry.max_hold_time.max(load(&counters.max_hold_time_us));
+                entry.caused_wait_time += load(&counters.caused_wait_time_us);
+            }
+        }
+        stats.into_values().collect()
+    }
+
+    thread_local! {
+        /// The call site of the lock that is currently being acquired on this thread. The raw lock
+        /// can't be `#[track_caller]`, so [`InstrumentedRwLock`] passes the location through here.
+        static CALLER: Cell<Option<&'static Location<'static>>> = Cell::new(None);
+    }
+
+    fn take_caller() -> &'static Location<'static> {
+        match CALLER.with(Cell::take) {
+            Some(location) => location,
+            None => Location::caller(),
+        }
+    }
+
+    #[derive(Clone, Copy)]
+    struct Holder {
+        location: &'static Location<'static>,
+        mode: LockMode,
+        since: Instant,
+        thread: ThreadId,
+    }
+
+    /// The raw lock of [`InstrumentedRwLock`], a `parking_lot::RawRwLock` that keeps track of the
+    /// call sites that hold it.
+    pub struct RawInstrumentedRwLock {
+        inner: parking_lot::RawRwLock,
+        holders: Mutex<Vec<Holder>>,
+    }
+
+    impl RawInstrumentedRwLock {
+        fn acquire<T, L>(
+            &self,
+            location: &'static Location<'static>,
+            mode: LockMode,
+            try_lock: T,
+            lock: L,
+        ) where
+            T: FnOnce(&parking_lot::RawRwLock) -> bool,
+            L: FnOnce(&parking_lot::RawRwLock),
+        {
+            if let Some(counters) = counters_of(location, mode) {
+                counters.acquisitions.fetch_add(1, Ordering::Relaxed);
+            }
+
+            if try_lock(&self.inner) {
+                return;
+            }
+
+            // The holders might release the lock before we look at them, so the attribution of
+            // the wait is only approximate.
+            let blockers = self.holders.lock().clone();
+            let start = Instant::now();
+            lock(&self.inner);
+            record_wait(location, mode, start.elapsed(), &blockers);
+        }
+
+        fn add_holder(&self, location: &'static Location<'static>, mode: LockMode) {
+            self.holders.lock().push(Holder {
+                location,
+                mode,
+                since: Instant::now(),
+                thread: thread::current().id(),
+            });
+        }
+
+        /// Removes the exclusive or upgradable holder and records its hold time.
+        fn remove_exclusive_holder(&self) -> Option<Holder> {
+            let mut holders = self.holders.lock();
+            let position = holders
+                .iter()
+                .position(|holder| holder.mode != LockMode::Read)?;
+            let holder = holders.swap_remove(position);
+            drop(holders);
+
+            record_hold(&holder);
+            Some(holder)
+        }
+
+        /// Removes the latest shared holder of the current thread and records its hold time.
+        /// Guards are released on the thread that acquired them, unless they were sent to another
+        /// thread, in which case any shared holder is removed.
+        fn remove_shared_holder(&self) {
+            let thread = thread::current().id();
+            let mut holders = self.holders.lock();
+            let position = holders
+                .iter()
+                .rposition(|holder| holder.mode == LockMode::Read && holder.thread == thread)
+                .or_else(|| {
+                    holders
+                        .iter()
+                        .position(|holder| holder.mode == LockMode::Read)
+                });
+            if let Some(position) = position {
+                let holder = holders.remove(position);
+                drop(holders);
+                record_hold(&holder);
+            }
+        }
+
+        /// Turns the exclusive or upgradable holder into a shared one.
+        fn downgrade_holder(&self) {
+            if let Some(holder) = self.remove_exclusive_holder() {
+                self.add_holder(holder.location, LockMode::Read);
+            }
+        }
+    }
+
+    unsafe impl lock_api::RawRwLock for RawInstrumentedRwLock {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const INIT: Self = RawInstrumentedRwLock {
+            inner: parking_lot::RawRwLock::INIT,
+            holders: parking_lot::const_mutex(Vec::new()),
+        };
+
+        type GuardMarker = <parking_lot::RawRwLock as lock_api::RawRwLock>::GuardMarker;
+
+        fn lock_shared(&self) {
+            let location = take_caller();
+            self.acquire(
+                location,
+                LockMode::Read,
+                |inner| inner.try_lock_shared(),
+                |inner| inner.lock_shared(),
+            );
+            self.add_holder(location, LockMode::Read);
+        }
+
+        fn try_lock_shared(&self) -> bool {
+            let locked = self.inner.try_lock_shared();
+            if locked {
+                self.add_holder(take_caller(), LockMode::Read);
+            }
+            locked
+        }
+
+        unsafe fn unlock_shared(&self) {
+            self.remove_shared_holder();
+            self.inner.unlock_shared();
+        }
+
+        fn lock_exclusive(&self) {
+            let location = take_caller();
+            self.acquire(
+                location,
+                LockMode::Write,
+                |inner| inner.try_lock_exclusive(),
+                |inner| inner.lock_exclusive(),
+            );
+            self.add_holder(location, LockMode::Write);
+        }
+
+        fn try_lock_exclusive(&self) -> bool {
+            let locked = self.inner.try_lock_exclusive();
+            if locked {
+                self.add_holder(take_caller(), LockMode::Write);
+            }
+            locked
+        }
+
+        unsafe fn unlock_exclusive(&self) {
+            self.remove_exclusive_holder();
+            self.inner.unlock_exclusive();
+        }
+    }
+
+    unsafe impl RawRwLockUpgrade for RawInstrumentedRwLock {
+        fn lock_upgradable(&self) {
+            let location = take_caller();
+            self.acquire(
+                location,
+                LockMode::UpgradableRead,
+                |inner| inner.try_lock_upgradable(),
+                |inner| inner.lock_upgradable(),
+            );
+            self.add_holder(location, LockMode::UpgradableRead);
+        }
+
+        fn try_lock_upgradable(&self) -> bool {
+            let locked = self.inner.try_lock_upgradable();
+            if locked {
+                self.add_holder(take_caller(), LockMode::UpgradableRead);
+            }
+            locked
+        }
+
+        unsafe fn unlock_upgradable(&self) {
+            self.remove_exclusive_holder();
+            self.inner.unlock_upgradable();
+        }
+
+        unsafe fn upgrade(&self) {
+            // Upgrades don't go through `InstrumentedRwLock`, they are attributed to the call site
+            // that acquired the upgradable guard. The hold time keeps counting for that call site.
+            let location = self
+                .holders
+                .lock()
+                .iter()
+                .find(|holder| holder.mode == LockMode::UpgradableRead)
+                .map_or_else(Location::caller, |holder| holder.location);
+            self.acquire(
+                location,
+                LockMode::Upgrade,
+                |inner| inner.try_upgrade(),
+                |inner| inner.upgrade(),
+            );
+        }
+
+        unsafe fn try_upgrade(&self) -> bool {
+            self.inner.try_upgrade()
+        }
+    }
+
+    unsafe impl RawRwLockDowngrade for RawInstrumentedRwLock {
+        unsafe fn downgrade(&self) {
+            self.downgrade_holder();
+            self.inner.downgrade();
+        }
+    }
+
+    unsafe impl RawRwLockUpgradeDowngrade for RawInstrumentedRwLock {
+        unsafe fn downgrade_upgradable(&self) {
+            self.downgrade_holder();
+            self.inner.downgrade_upgradable();
+        }
+
+        unsafe fn downgrade_to_upgradable(&self) {
+            // The guard is still held by the same call site.
+            self.inner.downgrade_to_upgradable();
+        }
+    }
+
+    pub type RwLockReadGuard<'a, T> = lock_api::RwLockReadGuard<'a, RawInstrumentedRwLock, T>;
+    pub type RwLockUpgradableReadGuard<'a, T> =
+        lock_api::RwLockUpgradableReadGuard<'a, RawInstrumentedRwLock, T>;
+    pub type RwLockWriteGuard<'a, T> = lock_api::RwLockWriteGuard<'a, RawInstrumentedRwLock, T>;
+
+    /// A read-write lock with the same semantics as `parking_lot::RwLock`, that records the
+    /// [`LockStats`] of the call sites that acquire it.
+    #[derive(Default)]
+    pub struct InstrumentedRwLock<T> {
+        lock: lock_api::RwLock<RawInstrumentedRwLock, T>,
+    }
+
+    impl<T> InstrumentedRwLock<T> {
+        pub fn new(value: T) -> Self {
+            InstrumentedRwLock {
+                lock: lock_api::RwLock::new(value),
+            }
+        }
+
+        #[track_caller]
+        pub fn read(&self) -> RwLockReadGuard<T> {
+            CALLER.with(|caller| caller.set(Some(Location::caller())));
+            self.lock.read()
+        }
+
+        #[track_caller]
+        pub fn upgradable_read(&self) -> RwLockUpgradableReadGuard<T> {
+            CALLER.with(|caller| caller.set(Some(Location::caller())));
+            self.lock.upgradable_read()
+        }
+
+        #[track_caller]
+        pub fn write(&self) -> RwLockWriteGuard<T> {
+            CALLER.with(|caller| caller.set(Some(Location::caller())));
+            self.lock.write()
+        }
+
+        pub fn get_mut(&mut self) -> &mut T {
+            self.lock.get_mut()
+        }
+
+        pub fn into_inner(self) -> T {
+            self.lock.into_inner()
+        }
+    }
+}
+
+#[cfg(all(test, feature = "lock-stats"))]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    use super::{lock_stats, InstrumentedRwLock, LockMode, RwLockUpgradableReadGuard};
+
+    #[test]
+    fn it_counts_acquisitions_per_call_site() {
+        let lock = InstrumentedRwLock::new(0);
+        for _ in 0..3 {
+            let _guard = lock.read();
+        }
+        *lock.write() += 1;
+        let guard = lock.upgradable_read();
+        let mut guard = RwLockUpgradableReadGuard::upgrade(guard);
+        *guard += 1;
+        drop(guard);
+        assert_eq!(*lock.read(), 2);
+
+        let stats = lock_stats();
+        let reads = stats
+            .iter()
+            .find(|stats| stats.mode == LockMode::Read && stats.location.file() == file!())
+            .unwrap();
+        assert!(reads.acquisitions >= 3);
+        assert!(stats
+            .iter()
+            .any(|stats| stats.mode == LockMode::Write && stats.location.file() == file!()));
+        assert!(stats
+            .iter()
+            .any(|stats| stats.mode == LockMode::Upgrade && stats.location.file() == file!()));
+    }
+
+    #[test]
+    fn it_attributes_waits_to_the_holder() {
+        let lock = Arc::new(InstrumentedRwLock::new(()));
+        let guard = lock.write();
+
+        let lock2 = Arc::clone(&lock);
+        let reader = thread::spawn(move || {
+            let _guard = lock2.read();
+        });
+        thread::sleep(Duration::from_millis(50));
+        drop(guard);
+        reader.join().unwrap();
+
+        let stats = lock_stats();
+        let writer = stats
+            .iter()
+            .find(|stats| stats.mode == LockMode::Write && stats.caused_wait_time > Duration::ZERO)
+            .unwrap();
+        assert_eq!(writer.location.file(), file!());
+        assert!(writer.hold_time >= Duration::from_millis(50));
+        assert!(stats
+            .iter()
+            .any(|stats| stats.mode == LockMode::Read && stats.contended > 0));
+    }
+
+    #[test]
+    fn it_attributes_waits_to_readers() {
+        let lock = Arc::new(InstrumentedRwLock::new(()));
+        let guard = lock.read();
+
+        let lock2 = Arc::clone(&lock);
+        let writer = thread::spawn(move || {
+            let _guard = lock2.write();
+        });
+        thread::sleep(Duration::from_millis(50));
+        drop(guard);
+        writer.join().unwrap();
+
+        let stats = lock_stats();
+        let reader = stats
+            .iter()
+            .find(|stats| stats.mode == LockMode::Read && stats.caused_wait_time > Duration::ZERO)
+            .unwrap();
+        assert_eq!(reader.location.file(), file!());
+        assert!(reader.hold_time >= Duration::from_millis(50));
+        assert!(reader.max_hold_time >= Duration::from_millis(50));
+    }
+}
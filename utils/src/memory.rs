@@ -0,0 +1,478 @@
+use std::alloc::{GlobalAlloc, Layout};
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use parking_lot::RwLock;
+
+#[derive(Default)]
+struct GaugeInner {
+    used: AtomicUsize,
+    /// Zero means no cap.
+    cap: AtomicUsize,
+}
+
+/// Counts the bytes of memory held by a subsystem.
+///
+/// The count is an estimate maintained by the subsystem itself, usually the serialized size of the
+/// buffered objects. A gauge can have a cap. Subsystems that are over their cap shed load, e.g. by
+/// pausing work or evicting cached entries. Clones share the same count.
+#[derive(Clone, Default)]
+pub struct MemoryGauge {
+    inner: Arc<GaugeInner>,
+}
+
+impl MemoryGauge {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&self, bytes: usize) {
+        self.inner.used.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn sub(&self, bytes: usize) {
+        // Never wrap around, even if a subsystem subtracts more than it added.
+        let _ = self
+            .inner
+            .used
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                Some(used.saturating_sub(bytes))
+            });
+    }
+
+    /// The number of bytes currently held.
+    pub fn used(&self) -> usize {
+        self.inner.used.load(Ordering::Relaxed)
+    }
+
+    pub fn cap(&self) -> Option<usize> {
+        match self.inner.cap.load(Ordering::Relaxed) {
+            0 => None,
+            cap => Some(cap),
+        }
+    }
+
+    pub fn set_cap(&self, cap: Option<usize>) {
+        self.inner.cap.store(cap.unwrap_or(0), Ordering::Relaxed);
+    }
+
+    /// Returns whether more bytes are held than the cap allows.
+    pub fn is_over_cap(&self) -> bool {
+        self.cap().map_or(false, |cap| self.used() > cap)
+    }
+
+    /// Returns whether `bytes` more bytes would exceed the cap.
+    pub fn would_exceed_cap(&self, bytes: usize) -> bool {
+        self.cap()
+            .map_or(false, |cap| self.used().saturating_add(bytes) > cap)
+    }
+
+    /// Adds `bytes` until the returned reservation is dropped.
+    pub fn reserve(&self, bytes: usize) -> MemoryReservation {
+        self.add(bytes);
+        MemoryReservation {
+            gauge: self.clone(),
+            bytes,
+        }
+    }
+}
+
+/// Bytes added to a [`MemoryGauge`] that are subtracted again when the reservation is dropped.
+///
+/// This is useful for objects that can be dropped on several paths, e.g. futures that are
+/// either completed or cancelled.
+#[must_use]
+pub struct MemoryReservation {
+    gauge: MemoryGauge,
+    bytes: usize,
+}
+
+impl MemoryReservation {
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+}
+
+impl Drop for MemoryReservation {
+    fn drop(&mut self) {
+        self.gauge.sub(self.bytes);
+    }
+}
+
+impl std::fmt::Debug for MemoryGauge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MemoryGauge")
+            .field("used", &self.used())
+            .field("cap", &self.cap())
+            .finish()
+    }
+}
+
+/// The memory usage of a subsystem at some point in time.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemoryUsage {
+    pub subsystem: String,
+    pub used: usize,
+    pub cap: Option<usize>,
+}
+
+/// The memory gauges of all subsystems of a node, by subsystem name.
+#[derive(Clone, Default)]
+pub struct MemoryAccounting {
+    gauges: Arc<RwLock<BTreeMap<String, MemoryGauge>>>,
+}
+
+impl MemoryAccounting {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the gauge of a subsystem, replacing any gauge registered under the same name.
+    pub fn register<S: Into<String>>(&self, subsystem: S, gauge: MemoryGauge) {
+        self.gauges.write().insert(subsystem.into(), gauge);
+    }
+
+    pub fn gauge(&self, subsystem: &str) -> Option<MemoryGauge> {
+        self.gauges.read().get(subsystem).cloned()
+    }
+
+    /// Returns the usage of all subsystems, ordered by name.
+    pub fn usage(&self) -> Vec<MemoryUsage> {
+        self.gauges
+            .read()
+            .iter()
+            .map(|(subsystem, gauge)| MemoryUsage {
+                subsystem: subsystem.clone(),
+                used: gauge.used(),
+                cap: gauge.cap(),
+            })
+            .collect()
+    }
+
+    /// The number of bytes held by all subsystems together.
+    pub fn total(&self) -> usize {
+        self.gauges.read().values().map(MemoryGauge::used).sum()
+    }
+}
+
+/// Statistics of the memory allocator, in bytes.
+///
+/// Only available if the node was built with the `jemalloc` feature, which makes jemalloc the
+/// global allocator. See the jemalloc documentation of `stats.*` for the meaning of the values.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AllocatorStats {
+    /// Bytes allocated by the application.
+    pub allocated: usize,
+    /// Bytes in active pages, a multiple of the page size and at least `allocated`.
+    pub active: usize,
+    /// Bytes dedicated to the metadata of the allocator.
+    pub metadata: usize,
+    /// Bytes in physically resident data pages mapped by the allocator.
+    pub resident: usize,
+    /// Bytes in active extents mapped by the allocator.
+    pub mapped: usize,
+    /// Bytes in virtual memory mappings that were retained instead of being returned to the OS.
+    pub retained: usize,
+}
+
+impl AllocatorStats {
+    /// Reads the current statistics of the allocator. Returns `None` if jemalloc isn't used.
+    #[cfg(feature = "jemalloc")]
+    pub fn read() -> Option<Self> {
+        use tikv_jemalloc_ctl::{epoch, stats};
+
+        // The statistics are cached and only refreshed when the epoch is advanced.
+        let read = || -> Result<Self, tikv_jemalloc_ctl::Error> {
+            epoch::advance()?;
+            Ok(AllocatorStats {
+                allocated: stats::allocated::read()?,
+                active: stats::active::read()?,
+                metadata: stats::metadata::read()?,
+                resident: stats::resident::read()?,
+                mapped: stats::mapped::read()?,
+                retained: stats::retained::read()?,
+            })
+        };
+
+        match read() {
+            Ok(stats) => Some(stats),
+            Err(e) => {
+                log::warn!("Failed to read the allocator statistics: {}", e);
+                None
+            }
+        }
+    }
+
+    #[cfg(not(feature = "jemalloc"))]
+    pub fn read() -> Option<Self> {
+        None
+    }
+}
+
+/// The name of the directory under the data directory that heap profiles are written to.
+pub const HEAP_PROFILE_DIR: &str = "heap-profiles";
+
+/// Writes a heap profile to a new file in `dir`, which can be analyzed with `jeprof`, and returns
+/// the path of the file. The file is named after the current time, so existing files are never
+/// overwritten.
+///
+/// This requires a node built with the `jemalloc-profiling` feature and started with profiling
+/// enabled, e.g. with `_RJEM_MALLOC_CONF=prof:true`.
+#[cfg(feature = "jemalloc")]
+pub fn dump_heap_profile(dir: &Path) -> Result<PathBuf, String> {
+    std::fs::create_dir_all(dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let path = heap_profile_path(dir);
+    let c_path = path
+        .to_str()
+        .and_then(|path| std::ffi::CString::new(path).ok())
+        .ok_or_else(|| format!("Invalid heap profile path: {}", path.display()))?;
+    // Safety: `prof.dump` expects a pointer to a nul-terminated string, which outlives the call.
+    unsafe { tikv_jemalloc_ctl::raw::write(b"prof.dump\0", c_path.as_ptr()) }
+        .map_err(|e| format!("Failed to dump the heap profile: {}", e))?;
+    Ok(path)
+}
+
+#[cfg(not(feature = "jemalloc"))]
+pub fn dump_heap_profile(_dir: &Path) -> Result<PathBuf, String> {
+    Err("Heap profiles require a node built with the jemalloc feature".to_string())
+}
+
+fn heap_profile_path(dir: &Path) -> PathBuf {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_millis());
+    dir.join(format!("heap-{}.prof", millis))
+}
+
+/// The subsystems that allocations are attributed to by the [`TaggingAllocator`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum AllocationTag {
+    /// Allocations outside of any tagged subsystem.
+    Other,
+    Blockchain,
+    Consensus,
+    Network,
+    Rpc,
+}
+
+impl AllocationTag {
+    pub const ALL: [AllocationTag; 5] = [
+        AllocationTag::Other,
+        AllocationTag::Blockchain,
+        AllocationTag::Consensus,
+        AllocationTag::Network,
+        AllocationTag::Rpc,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            AllocationTag::Other => "other",
+            AllocationTag::Blockchain => "blockchain",
+            AllocationTag::Consensus => "consensus",
+            AllocationTag::Network => "network",
+            AllocationTag::Rpc => "rpc",
+        }
+    }
+}
+
+struct TagCounters {
+    allocations: AtomicU64,
+    allocated: AtomicU64,
+    freed: AtomicU64,
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const EMPTY_TAG_COUNTERS: TagCounters = TagCounters {
+    allocations: AtomicU64::new(0),
+    allocated: AtomicU64::new(0),
+    freed: AtomicU64::new(0),
+};
+
+static TAG_COUNTERS: [TagCounters; AllocationTag::ALL.len()] =
+    [EMPTY_TAG_COUNTERS; AllocationTag::ALL.len()];
+
+thread_local! {
+    static CURRENT_TAG: Cell<AllocationTag> = Cell::new(AllocationTag::Other);
+}
+
+fn current_tag() -> AllocationTag {
+    // The thread local is unavailable while the thread is being torn down.
+    CURRENT_TAG
+        .try_with(Cell::get)
+        .unwrap_or(AllocationTag::Other)
+}
+
+fn record_allocation(bytes: usize) {
+    let counters = &TAG_COUNTERS[current_tag() as usize];
+    counters.allocations.fetch_add(1, Ordering::Relaxed);
+    counters
+        .allocated
+        .fetch_add(bytes as u64, Ordering::Relaxed);
+}
+
+fn record_free(bytes: usize) {
+    let counters = &TAG_COUNTERS[current_tag() as usize];
+    counters.freed.fetch_add(bytes as u64, Ordering::Relaxed);
+}
+
+/// A global allocator that counts the allocations of each subsystem, see [`AllocationTag`].
+///
+/// Allocations are attributed to the tag of the current thread, which is set by
+/// [`with_allocation_tag`] and [`Tagged`]. Memory is counted as freed by the subsystem that frees
+/// it, which isn't necessarily the one that allocated it, so the difference of the counters of a
+/// subsystem is only an approximation of the memory it holds.
+pub struct TaggingAllocator<A>(pub A);
+
+unsafe impl<A: GlobalAlloc> GlobalAlloc for TaggingAllocator<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = self.0.alloc(layout);
+        if !ptr.is_null() {
+            record_allocation(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.0.dealloc(ptr, layout);
+        record_free(layout.size());
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = self.0.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            record_allocation(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = self.0.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            record_free(layout.size());
+            record_allocation(new_size);
+        }
+        new_ptr
+    }
+}
+
+/// Attributes the allocations of `f` to `tag`, see [`TaggingAllocator`].
+pub fn with_allocation_tag<R>(tag: AllocationTag, f: impl FnOnce() -> R) -> R {
+    struct Restore(AllocationTag);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let _ = CURRENT_TAG.try_with(|current| current.set(self.0));
+        }
+    }
+
+    let _restore = Restore(current_tag());
+    let _ = CURRENT_TAG.try_with(|current| current.set(tag));
+    f()
+}
+
+/// A future whose polls are attributed to a subsystem, see [`TaggingAllocator`].
+pub struct Tagged<F> {
+    tag: AllocationTag,
+    future: Pin<Box<F>>,
+}
+
+impl<F> Tagged<F> {
+    pub fn new(tag: AllocationTag, future: F) -> Self {
+        Tagged {
+            tag,
+            future: Box::pin(future),
+        }
+    }
+}
+
+impl<F: Future> Future for Tagged<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let tag = self.tag;
+        with_allocation_tag(tag, || self.future.as_mut().poll(cx))
+    }
+}
+
+/// The allocations of a subsystem since the node was started, in bytes. Only counted if the
+/// [`TaggingAllocator`] is the global allocator.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AllocationCounters {
+    pub tag: AllocationTag,
+    pub allocations: u64,
+    pub allocated: u64,
+    pub freed: u64,
+}
+
+/// Returns the allocation counters of all subsystems.
+pub fn allocation_counters() -> Vec<AllocationCounters> {
+    AllocationTag::ALL
+        .iter()
+        .map(|&tag| {
+            let counters = &TAG_COUNTERS[tag as usize];
+            AllocationCounters {
+                tag,
+                allocations: counters.allocations.load(Ordering::Relaxed),
+                allocated: counters.allocated.load(Ordering::Relaxed),
+                freed: counters.freed.load(Ordering::Relaxed),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::alloc::System;
+
+    use super::*;
+
+    fn counters(tag: AllocationTag) -> AllocationCounters {
+        allocation_counters()
+            .into_iter()
+            .find(|counters| counters.tag == tag)
+            .unwrap()
+    }
+
+    #[test]
+    fn it_attributes_allocations_to_the_current_tag() {
+        let allocator = TaggingAllocator(System);
+        let layout = Layout::from_size_align(1000, 8).unwrap();
+        let before = counters(AllocationTag::Rpc);
+
+        let ptr = with_allocation_tag(AllocationTag::Rpc, || unsafe {
+            let ptr = allocator.alloc(layout);
+            // The tag is restored after a nested tag ends.
+            with_allocation_tag(AllocationTag::Network, || {});
+            allocator.realloc(ptr, layout, 2000)
+        });
+        assert_eq!(current_tag(), AllocationTag::Other);
+
+        let after = counters(AllocationTag::Rpc);
+        assert_eq!(after.allocations - before.allocations, 2);
+        assert_eq!(after.allocated - before.allocated, 3000);
+        assert_eq!(after.freed - before.freed, 1000);
+
+        unsafe { allocator.dealloc(ptr, Layout::from_size_align(2000, 8).unwrap()) };
+    }
+
+    #[test]
+    fn heap_profiles_are_written_to_new_files_in_the_directory() {
+        let dir = Path::new("/data/heap-profiles");
+        let path = heap_profile_path(dir);
+        assert_eq!(path.parent(), Some(dir));
+        assert!(path
+            .file_name()
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .starts_with("heap-"));
+    }
+}
@@ -0,0 +1,110 @@
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use rand::rngs::{OsRng, StdRng};
+use rand::{Error, RngCore, SeedableRng};
+
+/// A random number generator that is injected into the components whose behaviour depends on
+/// randomness, e.g. which peers are dialed or when a view change starts.
+///
+/// By default, it draws from the randomness of the OS. Tests and fuzzers seed it instead, so that
+/// the choices of the components are reproducible. Clones of a seeded generator share its state,
+/// use [`SharedRng::fork`] to give a component its own stream that doesn't depend on the order in
+/// which the other components draw.
+///
+/// It deliberately doesn't implement `CryptoRng`: a seeded generator is predictable, so keys and
+/// nonces must not be drawn from it.
+#[derive(Clone, Debug, Default)]
+pub struct SharedRng {
+    seeded: Option<Arc<Mutex<StdRng>>>,
+}
+
+impl SharedRng {
+    /// Draws from the randomness of the OS.
+    pub fn os() -> Self {
+        SharedRng::default()
+    }
+
+    /// A reproducible generator for tests and fuzzing. This must not be used in production.
+    pub fn seeded(seed: u64) -> Self {
+        SharedRng {
+            seeded: Some(Arc::new(Mutex::new(StdRng::seed_from_u64(seed)))),
+        }
+    }
+
+    pub fn is_seeded(&self) -> bool {
+        self.seeded.is_some()
+    }
+
+    /// Returns an independent generator that is seeded from this one, or one that draws from the
+    /// randomness of the OS if this one does.
+    pub fn fork(&self) -> Self {
+        match &self.seeded {
+            Some(rng) => SharedRng::seeded(rng.lock().next_u64()),
+            None => SharedRng::os(),
+        }
+    }
+}
+
+impl RngCore for SharedRng {
+    fn next_u32(&mut self) -> u32 {
+        match &self.seeded {
+            Some(rng) => rng.lock().next_u32(),
+            None => OsRng.next_u32(),
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        match &self.seeded {
+            Some(rng) => rng.lock().next_u64(),
+            None => OsRng.next_u64(),
+        }
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        match &self.seeded {
+            Some(rng) => rng.lock().fill_bytes(dest),
+            None => OsRng.fill_bytes(dest),
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        match &self.seeded {
+            Some(rng) => rng.lock().try_fill_bytes(dest),
+            None => OsRng.try_fill_bytes(dest),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::SharedRng;
+
+    #[test]
+    fn seeded_rngs_are_reproducible() {
+        let mut rng1 = SharedRng::seeded(42);
+        let mut rng2 = SharedRng::seeded(42);
+        let values1: Vec<u64> = (0..10).map(|_| rng1.gen()).collect();
+        let values2: Vec<u64> = (0..10).map(|_| rng2.gen()).collect();
+        assert_eq!(values1, values2);
+
+        let mut forked1 = SharedRng::seeded(7).fork();
+        let mut forked2 = SharedRng::seeded(7).fork();
+        assert!(forked1.is_seeded());
+        assert_eq!(forked1.gen::<u64>(), forked2.gen::<u64>());
+    }
+
+    #[test]
+    fn clones_share_the_state() {
+        let mut rng = SharedRng::seeded(42);
+        let mut clone = rng.clone();
+        let first: u64 = rng.gen();
+        let second: u64 = clone.gen();
+
+        let mut reference = SharedRng::seeded(42);
+        assert_eq!(reference.gen::<u64>(), first);
+        assert_eq!(reference.gen::<u64>(), second);
+    }
+}
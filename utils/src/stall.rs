@@ -0,0 +1,390 @@
+//! Detection of stalled tasks and slow operations.
+//!
+//! A future that blocks in `poll`, e.g. on a lock or a long computation, stalls the worker thread
+//! of the runtime and all tasks queued on it. Such stalls can't be reported by the stalled code
+//! itself, so the operations that must not take long are marked with a [`Span`] while they
+//! run, and a watchdog thread (see [`start_watchdog`]) warns about the spans that exceed their
+//! threshold. Long-running tasks can be wrapped with [`instrument`] to mark every poll and to
+//! collect their [`TaskStats`].
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+use std::task::{Context, Poll};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+
+/// The state of an operation that is watched for stalls. Entering and leaving the operation only
+/// touches its atomics, the registry is locked once when the operation is registered.
+struct SpanState {
+    name: &'static str,
+    threshold: Duration,
+    /// When the running operation started, in microseconds since [`EPOCH`] plus one, or zero while
+    /// the operation isn't running.
+    started: AtomicU64,
+    /// Whether the watchdog already warned about the running operation.
+    reported: AtomicBool,
+}
+
+impl SpanState {
+    const fn new(name: &'static str, threshold: Duration) -> Self {
+        SpanState {
+            name,
+            threshold,
+            started: AtomicU64::new(0),
+            reported: AtomicBool::new(false),
+        }
+    }
+
+    fn enter(&self) -> Instant {
+        let start = Instant::now();
+        self.reported.store(false, Ordering::Relaxed);
+        self.started
+            .store(micros_since_epoch(start) + 1, Ordering::Release);
+        start
+    }
+
+    /// Leaves the operation that was entered at `start`, warns if it took longer than the threshold
+    /// and returns how long it took.
+    fn exit(&self, start: Instant) -> Duration {
+        self.started.store(0, Ordering::Release);
+        let elapsed = start.elapsed();
+        if elapsed > self.threshold {
+            log::warn!(
+                "{} took {:?}, more than the threshold of {:?}",
+                self.name,
+                elapsed,
+                self.threshold
+            );
+        }
+        elapsed
+    }
+
+    /// Warns once if the running operation has exceeded its threshold at `now`.
+    fn check(&self, now: Instant) {
+        let started = self.started.load(Ordering::Acquire);
+        if started == 0 {
+            return;
+        }
+
+        let elapsed = Duration::from_micros(micros_since_epoch(now).saturating_sub(started - 1));
+        if elapsed > self.threshold && !self.reported.swap(true, Ordering::Relaxed) {
+            log::warn!(
+                "{} has been running for {:?}, it is probably stalled",
+                self.name,
+                elapsed
+            );
+        }
+    }
+}
+
+/// A span that is watched by the watchdog.
+enum RegisteredSpan {
+    Static(&'static SpanState),
+    /// The span of an instrumented task, which is removed once the task is dropped.
+    Task(Weak<SpanState>),
+}
+
+#[derive(Default)]
+struct Registry {
+    spans: Mutex<Vec<RegisteredSpan>>,
+    tasks: Mutex<BTreeMap<&'static str, Arc<TaskCounters>>>,
+    watchdog_started: AtomicBool,
+}
+
+lazy_static! {
+    static ref REGISTRY: Registry = Registry::default();
+    /// The reference point of the start times of the spans, which are stored as integers.
+    static ref EPOCH: Instant = Instant::now();
+}
+
+fn micros_since_epoch(instant: Instant) -> u64 {
+    instant.saturating_duration_since(*EPOCH).as_micros() as u64
+}
+
+/// An operation that should finish within a threshold. Spans are declared as statics, e.g.
+///
+/// ```ignore
+/// static PUSH_SPAN: Span = Span::new("Blockchain::push", Duration::from_secs(2));
+/// ```
+///
+/// and are registered with the watchdog when they are first entered. A span watches one
+/// operation at a time, so the operation must not run concurrently with itself, e.g. because
+/// it is serialized by a lock.
+pub struct Span {
+    state: SpanState,
+    registered: AtomicBool,
+}
+
+impl Span {
+    pub const fn new(name: &'static str, threshold: Duration) -> Self {
+        Span {
+            state: SpanState::new(name, threshold),
+            registered: AtomicBool::new(false),
+        }
+    }
+
+    /// Marks the operation as running until the guard is dropped.
+    pub fn enter(&'static self) -> SpanGuard<'static> {
+        if !self.registered.swap(true, Ordering::Relaxed) {
+            REGISTRY
+                .spans
+                .lock()
+                .push(RegisteredSpan::Static(&self.state));
+        }
+
+        SpanGuard {
+            state: &self.state,
+            start: self.state.enter(),
+        }
+    }
+}
+
+/// A running operation, see [`Span::enter`]. Warns on drop if the operation took longer than its
+/// threshold.
+#[must_use]
+pub struct SpanGuard<'a> {
+    state: &'a SpanState,
+    start: Instant,
+}
+
+impl<'a> SpanGuard<'a> {
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+impl<'a> Drop for SpanGuard<'a> {
+    fn drop(&mut self) {
+        self.state.exit(self.start);
+    }
+}
+
+/// Warns about the operations that have been running longer than their threshold and forgets the
+/// spans of dropped tasks.
+fn check_spans() {
+    let now = Instant::now();
+    REGISTRY.spans.lock().retain(|span| match span {
+        RegisteredSpan::Static(state) => {
+            state.check(now);
+            true
+        }
+        RegisteredSpan::Task(state) => match state.upgrade() {
+            Some(state) => {
+                state.check(now);
+                true
+            }
+            None => false,
+        },
+    });
+}
+
+/// Starts a thread that checks every `interval` for operations that have been running longer than
+/// their threshold and warns about them once. Only the first call starts a thread.
+pub fn start_watchdog(interval: Duration) {
+    if REGISTRY.watchdog_started.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    thread::Builder::new()
+        .name("stall-watchdog".to_string())
+        .spawn(move || loop {
+            thread::sleep(interval);
+            check_spans();
+        })
+        .expect("Failed to spawn the stall watchdog thread");
+}
+
+#[derive(Default)]
+struct TaskCounters {
+    spawned: AtomicU64,
+    alive: AtomicU64,
+    polls: AtomicU64,
+    slow_polls: AtomicU64,
+    poll_time_us: AtomicU64,
+    max_poll_time_us: AtomicU64,
+}
+
+/// The statistics of the instrumented tasks with the same name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TaskStats {
+    pub name: &'static str,
+    /// The number of tasks that were instrumented.
+    pub spawned: u64,
+    /// The number of tasks that didn't complete and weren't dropped yet.
+    pub alive: u64,
+    pub polls: u64,
+    /// The number of polls that took longer than the threshold.
+    pub slow_polls: u64,
+    /// The time spent in all polls.
+    pub poll_time: Duration,
+    /// The longest time spent in a single poll.
+    pub max_poll_time: Duration,
+}
+
+/// Returns the statistics of the instrumented tasks, ordered by name.
+pub fn task_stats() -> Vec<TaskStats> {
+    REGISTRY
+        .tasks
+        .lock()
+        .iter()
+        .map(|(&name, counters)| TaskStats {
+            name,
+            spawned: counters.spawned.load(Ordering::Relaxed),
+            alive: counters.alive.load(Ordering::Relaxed),
+            polls: counters.polls.load(Ordering::Relaxed),
+            slow_polls: counters.slow_polls.load(Ordering::Relaxed),
+            poll_time: Duration::from_micros(counters.poll_time_us.load(Ordering::Relaxed)),
+            max_poll_time: Duration::from_micros(counters.max_poll_time_us.load(Ordering::Relaxed)),
+        })
+        .collect()
+}
+
+/// Wraps a task so that its polls are watched for stalls and counted in the [`TaskStats`] of
+/// `name`. A poll that takes longer than `poll_threshold` is reported.
+pub fn instrument<F: Future>(
+    name: &'static str,
+    poll_threshold: Duration,
+    future: F,
+) -> Instrumented<F> {
+    let counters = Arc::clone(REGISTRY.tasks.lock().entry(name).or_default());
+    counters.spawned.fetch_add(1, Ordering::Relaxed);
+    counters.alive.fetch_add(1, Ordering::Relaxed);
+
+    let span = Arc::new(SpanState::new(name, poll_threshold));
+    REGISTRY
+        .spans
+        .lock()
+        .push(RegisteredSpan::Task(Arc::downgrade(&span)));
+
+    Instrumented {
+        future: Box::pin(future),
+        span,
+        counters: Alive(counters),
+    }
+}
+
+/// Decrements the number of alive tasks when dropped.
+struct Alive(Arc<TaskCounters>);
+
+impl Drop for Alive {
+    fn drop(&mut self) {
+        self.0.alive.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// A task instrumented with [`instrument`].
+pub struct Instrumented<F> {
+    future: Pin<Box<F>>,
+    span: Arc<SpanState>,
+    counters: Alive,
+}
+
+impl<F: Future> Future for Instrumented<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let start = self.span.enter();
+        let result = self.future.as_mut().poll(cx);
+        let elapsed = self.span.exit(start);
+
+        let counters = &self.counters.0;
+        let elapsed_us = elapsed.as_micros() as u64;
+        counters.polls.fetch_add(1, Ordering::Relaxed);
+        counters
+            .poll_time_us
+            .fetch_add(elapsed_us, Ordering::Relaxed);
+        counters
+            .max_poll_time_us
+            .fetch_max(elapsed_us, Ordering::Relaxed);
+        if elapsed > self.span.threshold {
+            counters.slow_polls.fetch_add(1, Ordering::Relaxed);
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+    use std::thread;
+    use std::time::Duration;
+
+    use super::{check_spans, instrument, task_stats, RegisteredSpan, Span, REGISTRY};
+
+    fn is_registered(name: &str) -> bool {
+        REGISTRY.spans.lock().iter().any(|span| match span {
+            RegisteredSpan::Static(state) => state.name == name,
+            RegisteredSpan::Task(state) => {
+                state.upgrade().map_or(false, |state| state.name == name)
+            }
+        })
+    }
+
+    #[test]
+    fn it_tracks_running_spans() {
+        static SPAN: Span = Span::new("test span", Duration::from_secs(60));
+
+        let guard = SPAN.enter();
+        assert_ne!(SPAN.state.started.load(Ordering::Relaxed), 0);
+        drop(guard);
+        assert_eq!(SPAN.state.started.load(Ordering::Relaxed), 0);
+
+        // The span is registered only once.
+        drop(SPAN.enter());
+        let registered = REGISTRY
+            .spans
+            .lock()
+            .iter()
+            .filter(
+                |span| matches!(span, RegisteredSpan::Static(state) if state.name == "test span"),
+            )
+            .count();
+        assert_eq!(registered, 1);
+    }
+
+    #[test]
+    fn it_reports_stalled_spans_once() {
+        static SPAN: Span = Span::new("stalled test span", Duration::ZERO);
+
+        let guard = SPAN.enter();
+        thread::sleep(Duration::from_millis(1));
+        check_spans();
+        assert!(SPAN.state.reported.load(Ordering::Relaxed));
+        drop(guard);
+
+        // Entering the span again resets the report.
+        let _guard = SPAN.enter();
+        assert!(!SPAN.state.reported.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn it_counts_the_polls_of_instrumented_tasks() {
+        let task = instrument("test task", Duration::ZERO, async { 42 });
+        let stats = task_stats()
+            .into_iter()
+            .find(|stats| stats.name == "test task")
+            .unwrap();
+        assert_eq!(stats.spawned, 1);
+        assert_eq!(stats.alive, 1);
+        assert!(is_registered("test task"));
+
+        assert_eq!(futures::executor::block_on(task), 42);
+        let stats = task_stats()
+            .into_iter()
+            .find(|stats| stats.name == "test task")
+            .unwrap();
+        assert_eq!(stats.alive, 0);
+        assert_eq!(stats.polls, 1);
+
+        // The span of the task is forgotten once the task is dropped.
+        check_spans();
+        assert!(!is_registered("test task"));
+    }
+}
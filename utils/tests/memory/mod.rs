@@ -0,0 +1,79 @@
+use nimiq_utils::memory::*;
+
+#[test]
+fn it_counts_bytes() {
+    let gauge = MemoryGauge::new();
+    let clone = gauge.clone();
+
+    gauge.add(100);
+    clone.add(50);
+    assert_eq!(gauge.used(), 150);
+
+    gauge.sub(120);
+    assert_eq!(clone.used(), 30);
+
+    // The count never wraps around.
+    gauge.sub(100);
+    assert_eq!(gauge.used(), 0);
+}
+
+#[test]
+fn it_checks_the_cap() {
+    let gauge = MemoryGauge::new();
+    gauge.add(100);
+    assert_eq!(gauge.cap(), None);
+    assert!(!gauge.is_over_cap());
+    assert!(!gauge.would_exceed_cap(usize::MAX));
+
+    gauge.set_cap(Some(100));
+    assert!(!gauge.is_over_cap());
+    assert!(gauge.would_exceed_cap(1));
+
+    gauge.add(1);
+    assert!(gauge.is_over_cap());
+
+    gauge.set_cap(None);
+    assert!(!gauge.is_over_cap());
+}
+
+#[test]
+fn it_reports_usage_per_subsystem() {
+    let accounting = MemoryAccounting::new();
+    let mempool = MemoryGauge::new();
+    let sync = MemoryGauge::new();
+    sync.set_cap(Some(1000));
+    accounting.register("sync", sync.clone());
+    accounting.register("mempool", mempool.clone());
+
+    mempool.add(10);
+    sync.add(20);
+
+    assert_eq!(
+        accounting.usage(),
+        vec![
+            MemoryUsage {
+                subsystem: "mempool".to_string(),
+                used: 10,
+                cap: None,
+            },
+            MemoryUsage {
+                subsystem: "sync".to_string(),
+                used: 20,
+                cap: Some(1000),
+            },
+        ]
+    );
+    assert_eq!(accounting.total(), 30);
+}
+
+#[test]
+fn it_releases_reservations() {
+    let gauge = MemoryGauge::new();
+
+    let reservation = gauge.reserve(64);
+    assert_eq!(reservation.bytes(), 64);
+    assert_eq!(gauge.used(), 64);
+
+    drop(reservation);
+    assert_eq!(gauge.used(), 0);
+}
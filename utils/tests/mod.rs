@@ -6,6 +6,8 @@ extern crate beserial_derive;
 pub mod crc;
 #[cfg(feature = "iterators")]
 pub mod iterators;
+#[cfg(feature = "memory")]
+pub mod memory;
 #[cfg(feature = "merkle")]
 pub mod merkle;
 #[cfg(feature = "observer")]
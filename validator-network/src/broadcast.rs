@@ -0,0 +1,106 @@
+//! Sending a message to many validators.
+//!
+//! [`ValidatorNetwork::send_to`] sends the message to all given validators at once and reports
+//! the result of every send. [`broadcast`] limits the number of concurrent sends and sends the
+//! message again to the validators it could not be delivered to, e.g. because the connection had
+//! to be established first or the validator's peer ID changed.
+
+use std::time::Duration;
+
+use nimiq_network_interface::message::Message;
+
+use crate::{SendResult, ValidatorNetwork};
+
+/// Configures how a message is broadcast to validators.
+#[derive(Clone, Debug)]
+pub struct BroadcastConfig {
+    /// Maximum number of validators the message is sent to concurrently.
+    pub fanout: usize,
+    /// Number of times the message is sent again to validators it could not be delivered to.
+    pub max_retries: usize,
+    /// Delay before the message is sent again. The delay grows linearly with the number of
+    /// retries.
+    pub retry_delay: Duration,
+}
+
+impl Default for BroadcastConfig {
+    fn default() -> Self {
+        BroadcastConfig {
+            fanout: 16,
+            max_retries: 2,
+            retry_delay: Duration::from_millis(200),
+        }
+    }
+}
+
+/// The outcome of a broadcast.
+#[derive(Debug)]
+pub struct BroadcastResult<E> {
+    /// The validators the message was delivered to.
+    pub delivered: Vec<usize>,
+    /// The validators the message could not be delivered to, with the outcome of the last
+    /// attempt.
+    pub failed: Vec<SendResult<E>>,
+}
+
+impl<E> BroadcastResult<E> {
+    /// Returns whether the message was delivered to all validators.
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+/// Sends the message to the given validators, to at most `config.fanout` of them at a time.
+/// Validators the message could not be delivered to are retried up to `config.max_retries` times,
+/// unless the failure is not retryable, see [`crate::SendOutcome::is_retryable`].
+pub async fn broadcast<N, M>(
+    network: &N,
+    validator_ids: &[usize],
+    msg: M,
+    config: &BroadcastConfig,
+) -> BroadcastResult<N::Error>
+where
+    N: ValidatorNetwork + ?Sized,
+    M: Message + Clone,
+{
+    let mut result = BroadcastResult {
+        delivered: Vec::with_capacity(validator_ids.len()),
+        failed: vec![],
+    };
+
+    let mut pending = validator_ids.to_vec();
+    let mut retries = 0;
+    loop {
+        let mut failed = vec![];
+        for validator_ids in pending.chunks(config.fanout.max(1)) {
+            for send_result in network.send_to(validator_ids, msg.clone()).await {
+                if send_result.is_sent() {
+                    result.delivered.push(send_result.validator_id);
+                } else if send_result.outcome.is_retryable() {
+                    failed.push(send_result);
+                } else {
+                    result.failed.push(send_result);
+                }
+            }
+        }
+
+        if failed.is_empty() || retries >= config.max_retries {
+            result.failed.extend(failed);
+            return result;
+        }
+
+        retries += 1;
+        log::debug!(
+            "Failed to send {} to {} validators, retrying ({}/{})",
+            std::any::type_name::<M>(),
+            failed.len(),
+            retries,
+            config.max_retries
+        );
+        tokio::time::sleep(config.retry_delay * retries as u32).await;
+        pending = failed
+            .into_iter()
+            .map(|send_result| send_result.validator_id)
+            .collect();
+    }
+}
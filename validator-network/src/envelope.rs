@@ -0,0 +1,65 @@
+//! Signed envelopes for validator network messages.
+//!
+//! If signed messages are enabled, every message that is sent to another validator is wrapped in a
+//! [`SignedEnvelope`]. The envelope carries the validator ID of the sender and a BLS signature of
+//! the payload by the sender's voting key. Receivers verify the signature against the voting key of
+//! that validator before the payload is delivered, so the sender of a message is known even if the
+//! envelope was relayed by other peers.
+
+use beserial::{Deserialize, Serialize};
+use nimiq_bls::{PublicKey, SecretKey, Signature};
+use nimiq_network_interface::message::Message;
+
+/// The prefix of the signed data of an envelope. See
+/// [`TaggedSignable`](nimiq_utils::tagged_signing::TaggedSignable) for the other tags in use.
+const ENVELOPE_TAG: u8 = 0x04;
+
+/// Added to the type ID of a message to get the type ID of its envelope.
+pub const ENVELOPE_TYPE_ID_OFFSET: u64 = 1 << 16;
+
+/// A message signed by the voting key of the validator that sent it.
+#[derive(Clone, Debug, Serialize, Deserialize, Schema)]
+pub struct SignedEnvelope<M: Serialize + Deserialize> {
+    /// The validator ID of the sender in the current validator set.
+    pub validator_id: u16,
+    pub payload: M,
+    pub signature: Signature,
+}
+
+impl<M: Message> SignedEnvelope<M> {
+    /// Signs the message as the validator with the given ID.
+    pub fn sign(payload: M, validator_id: u16, secret_key: &SecretKey) -> Self {
+        let signature = secret_key.sign(&Self::signed_data(validator_id, &payload));
+        Self {
+            validator_id,
+            payload,
+            signature,
+        }
+    }
+
+    /// Verifies the signature with the voting key of the validator the envelope claims to be sent
+    /// by.
+    pub fn verify(&self, public_key: &PublicKey) -> bool {
+        public_key.verify(
+            &Self::signed_data(self.validator_id, &self.payload),
+            &self.signature,
+        )
+    }
+
+    /// The type ID of the payload is signed as well, so a signature can't be reused for a message
+    /// of another type with the same serialization.
+    fn signed_data(validator_id: u16, payload: &M) -> Vec<u8> {
+        let mut data = Vec::with_capacity(1 + 8 + 2 + payload.serialized_size());
+        data.push(ENVELOPE_TAG);
+        data.extend_from_slice(&M::TYPE_ID.to_be_bytes());
+        data.extend_from_slice(&validator_id.to_be_bytes());
+        payload
+            .serialize(&mut data)
+            .expect("Failed to serialize message");
+        data
+    }
+}
+
+impl<M: Message> Message for SignedEnvelope<M> {
+    const TYPE_ID: u64 = M::TYPE_ID + ENVELOPE_TYPE_ID_OFFSET;
+}
@@ -27,6 +27,10 @@ where
     #[error("Unknown validator: {0}")]
     UnknownValidator(usize),
 
+    /// Messages are signed, but our voting key is not one of the current validators.
+    #[error("Not a current validator, can't sign messages")]
+    NotSigning,
+
     #[error("Network error: {0}")]
     Network(#[from] TNetworkError),
 
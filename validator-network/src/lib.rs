@@ -1,7 +1,10 @@
 #[macro_use]
 extern crate beserial_derive;
 
+pub mod broadcast;
+pub mod envelope;
 pub mod error;
+pub mod messages;
 pub mod network_impl;
 pub mod validator_record;
 
@@ -17,11 +20,80 @@ use nimiq_network_interface::{
     peer::Peer,
 };
 
+pub use crate::broadcast::{broadcast, BroadcastConfig, BroadcastResult};
+pub use crate::envelope::SignedEnvelope;
 pub use crate::error::NetworkError;
+pub use crate::messages::MESSAGES;
 
 pub type MessageStream<TMessage, TPeerId> =
     Pin<Box<dyn Stream<Item = (TMessage, TPeerId)> + Send + 'static>>;
 
+/// What happened when a message was sent to a validator.
+#[derive(Debug)]
+pub enum SendOutcome<E> {
+    /// The message was queued on the connection to the validator.
+    Sent,
+    /// The peer ID of the validator is unknown, e.g. because it didn't publish a validator record.
+    NotResolved(E),
+    /// The validator's peer ID is known, but there is no connection and dialing it failed.
+    DialFailed(E),
+    /// The connection to the validator was closed before the message could be queued on it.
+    ConnectionClosed(E),
+    /// The message could not be serialized.
+    SerializationFailed(E),
+    /// Messages are signed, but this node is not one of the current validators.
+    NotSigned(E),
+}
+
+impl<E> SendOutcome<E> {
+    /// Whether sending the message again might succeed. Messages that can't be serialized or
+    /// signed fail the same way every time.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            SendOutcome::NotResolved(_)
+                | SendOutcome::DialFailed(_)
+                | SendOutcome::ConnectionClosed(_)
+        )
+    }
+}
+
+/// The outcome of sending a message to the validator with the given ID.
+#[derive(Debug)]
+pub struct SendResult<E> {
+    pub validator_id: usize,
+    pub outcome: SendOutcome<E>,
+}
+
+impl<E> SendResult<E> {
+    pub fn is_sent(&self) -> bool {
+        matches!(self.outcome, SendOutcome::Sent)
+    }
+
+    /// Returns the cause the message could not be sent, if any.
+    pub fn error(&self) -> Option<&E> {
+        match &self.outcome {
+            SendOutcome::Sent => None,
+            SendOutcome::NotResolved(e)
+            | SendOutcome::DialFailed(e)
+            | SendOutcome::ConnectionClosed(e)
+            | SendOutcome::SerializationFailed(e)
+            | SendOutcome::NotSigned(e) => Some(e),
+        }
+    }
+
+    pub fn into_result(self) -> Result<(), E> {
+        match self.outcome {
+            SendOutcome::Sent => Ok(()),
+            SendOutcome::NotResolved(e)
+            | SendOutcome::DialFailed(e)
+            | SendOutcome::ConnectionClosed(e)
+            | SendOutcome::SerializationFailed(e)
+            | SendOutcome::NotSigned(e) => Err(e),
+        }
+    }
+}
+
 /// Fixed upper bound network.
 /// Peers are denoted by a usize identifier which deterministically identifies them.
 #[async_trait]
@@ -34,6 +106,11 @@ pub trait ValidatorNetwork: Send + Sync {
     /// ordered, such that the k-th entry is the validator with ID k.
     async fn set_validators(&self, validator_keys: Vec<CompressedPublicKey>);
 
+    /// Resolves the given validators and connects to them, without changing the current set of active validators.
+    /// This is used to connect to the validators of the next epoch as soon as they are elected, so the first rounds
+    /// of the epoch don't wait for connections to be established.
+    async fn preconnect(&self, validator_keys: Vec<CompressedPublicKey>);
+
     async fn get_validator_peer(
         &self,
         validator_id: usize,
@@ -41,15 +118,21 @@ pub trait ValidatorNetwork: Send + Sync {
 
     /// must make a reasonable effort to establish a connection to the peer denoted with `validator_address`
     /// before returning a connection not established error.
+    ///
+    /// Returns the outcome for every validator, in the order of `validator_ids`.
     async fn send_to<M: Message + Clone>(
         &self,
         validator_ids: &[usize],
         msg: M,
-    ) -> Vec<Result<(), Self::Error>>;
+    ) -> Vec<SendResult<Self::Error>>;
 
     /// Will receive from all connected peers
     fn receive<M: Message>(&self) -> MessageStream<M, <Self::PeerType as Peer>::Id>;
 
+    /// Receives messages from the current validators, along with the validator ID of the sender.
+    /// Messages from peers that aren't known to be one of the current validators are dropped.
+    fn receive_from_validators<M: Message>(&self) -> MessageStream<M, usize>;
+
     async fn publish<TTopic: Topic + Sync>(&self, item: TTopic::Item) -> Result<(), Self::Error>;
 
     async fn subscribe<'a, TTopic: Topic + Sync>(
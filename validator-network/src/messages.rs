@@ -0,0 +1,65 @@
+use beserial::{Deserialize, Serialize};
+use nimiq_bls::CompressedPublicKey;
+use nimiq_network_interface::message::{Message, RequestMessage, ResponseMessage};
+
+/// The prefix of the DHT keys validators provide their validator record under.
+const PROVIDER_KEY_PREFIX: &[u8] = b"validator-record/";
+
+/// Returns the DHT key the validator with the given public key registers as provider for.
+///
+/// Validators announce themselves as providers for this key in addition to putting their record
+/// into the DHT. If the record can't be found, e.g. because the nodes storing it left the network,
+/// the providers are asked for the record directly.
+pub fn provider_key(public_key: &CompressedPublicKey) -> Vec<u8> {
+    let mut key = PROVIDER_KEY_PREFIX.to_vec();
+    key.extend_from_slice(public_key.as_ref());
+    key
+}
+
+/// Requests the signed validator record of the validator with the given public key from a peer
+/// that provides it.
+#[derive(Clone, Debug, Serialize, Deserialize, Schema)]
+pub struct RequestValidatorRecord {
+    pub public_key: CompressedPublicKey,
+    pub request_identifier: u32,
+}
+
+/// The signed validator record, serialized as it is stored in the DHT, or `None` if the peer
+/// doesn't provide the record.
+#[derive(Clone, Debug, Serialize, Deserialize, Schema)]
+pub struct ValidatorRecordResponse {
+    #[beserial(len_type(u16))]
+    pub record: Option<Vec<u8>>,
+    pub request_identifier: u32,
+}
+
+impl Message for RequestValidatorRecord {
+    const TYPE_ID: u64 = 125;
+}
+
+impl Message for ValidatorRecordResponse {
+    const TYPE_ID: u64 = 126;
+}
+
+impl RequestMessage for RequestValidatorRecord {
+    fn set_request_identifier(&mut self, request_identifier: u32) {
+        self.request_identifier = request_identifier;
+    }
+}
+
+impl ResponseMessage for ValidatorRecordResponse {
+    fn get_request_identifier(&self) -> u32 {
+        self.request_identifier
+    }
+}
+
+nimiq_network_interface::register_messages!(
+    /// The messages of the validator network.
+    pub MESSAGES = [
+        RequestValidatorRecord,
+        ValidatorRecordResponse => ValidatorRecordResponse {
+            record: None,
+            request_identifier: 1,
+        },
+    ]
+);
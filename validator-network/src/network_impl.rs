@@ -1,28 +1,102 @@
 use std::{
-    collections::{btree_map::Entry, BTreeMap},
-    sync::Arc,
+    collections::{hash_map::RandomState, BTreeMap, HashMap, HashSet, VecDeque},
+    future::{self, Future},
+    hash::{BuildHasher, Hash, Hasher},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
     time::Duration,
 };
 
 use async_trait::async_trait;
 use futures::{future::join_all, lock::Mutex, stream::BoxStream, StreamExt};
+use parking_lot::RwLock;
+use tokio::sync::Notify;
+use tokio::time::Instant;
 
 use beserial::{Deserialize, Serialize};
-use nimiq_bls::{CompressedPublicKey, SecretKey};
+use nimiq_bls::{CompressedPublicKey, PublicKey, SecretKey};
 use nimiq_network_interface::network::{MsgAcceptance, Network, Topic};
 use nimiq_network_interface::prelude::NetworkEvent;
-use nimiq_network_interface::{message::Message, peer::Peer};
+use nimiq_network_interface::request_response::RequestResponse;
+use nimiq_network_interface::{
+    message::Message,
+    peer::{Peer, SendError},
+};
+
+use super::{MessageStream, NetworkError, SendOutcome, SendResult, ValidatorNetwork};
+use crate::envelope::SignedEnvelope;
+use crate::messages::{provider_key, RequestValidatorRecord, ValidatorRecordResponse};
+use crate::validator_record::{SignedValidatorRecord, ValidatorAddress, ValidatorRecord};
+
+/// The maximum number of providers asked for a validator record that isn't in the DHT.
+const MAX_RECORD_PROVIDERS: usize = 4;
+
+/// How long to wait for a provider to answer a validator record request.
+const RECORD_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long to wait for a connection to a peer after dialing it.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
 
-use super::{MessageStream, NetworkError, ValidatorNetwork};
-use crate::validator_record::{SignedValidatorRecord, ValidatorRecord};
+/// How long dialing a validator may take in total, for all of its addresses together.
+const DIAL_VALIDATOR_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a message from a peer that isn't mapped to a validator is held back. Validators are
+/// resolved while we send to them, which at the start of an epoch often happens only after they
+/// sent us their first messages.
+const UNMAPPED_SENDER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The maximum number of messages from unmapped peers held back per stream. Further messages from
+/// unmapped peers are dropped right away.
+const MAX_UNMAPPED_MESSAGES: usize = 256;
+
+/// The maximum number of envelope signatures verified concurrently per message stream.
+const MAX_PENDING_VERIFICATIONS: usize = 16;
+
+/// The number of recently received envelopes remembered per message stream to drop duplicates
+/// before their signature is verified.
+const RECENT_ENVELOPES: usize = 1024;
 
 // Helper to get PeerId type from a network
 type PeerId<N> = <<N as Network>::PeerType as Peer>::Id;
 
+// Helper to get the address type from a network
+type Address<N> = <N as Network>::AddressType;
+
+/// A validator whose peer ID and addresses were looked up in the DHT.
 #[derive(Clone, Debug)]
-pub struct State<TPeerId> {
+pub struct ResolvedValidator<TPeerId, TAddress>
+where
+    TAddress: Serialize + Deserialize,
+{
+    peer_id: TPeerId,
+    addresses: Vec<ValidatorAddress<TAddress>>,
+    /// The index of the address the validator was last reached at.
+    working_address: Option<usize>,
+}
+
+impl<TPeerId, TAddress> ResolvedValidator<TPeerId, TAddress>
+where
+    TPeerId: Serialize + Deserialize,
+    TAddress: Serialize + Deserialize,
+{
+    fn from_record(record: SignedValidatorRecord<TPeerId, TAddress>) -> Self {
+        ResolvedValidator {
+            peer_id: record.record.peer_id,
+            addresses: record.record.addresses,
+            working_address: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct State<TPeerId, TAddress>
+where
+    TAddress: Serialize + Deserialize,
+{
     validator_keys: Vec<CompressedPublicKey>,
-    validator_peer_id_cache: BTreeMap<CompressedPublicKey, TPeerId>,
+    validator_peer_id_cache: BTreeMap<CompressedPublicKey, ResolvedValidator<TPeerId, TAddress>>,
 }
 
 #[derive(Debug)]
@@ -30,15 +104,39 @@ pub struct ValidatorNetworkImpl<N>
 where
     N: Network,
     <N::PeerType as Peer>::Id: Send + Sync + Serialize + Deserialize,
+    N::AddressType: Serialize + Deserialize,
 {
     network: Arc<N>,
-    state: Mutex<State<PeerId<N>>>,
+    state: Mutex<State<PeerId<N>, Address<N>>>,
+    /// The validator IDs of the resolved validators of the current set, by their peer ID.
+    validator_ids: Arc<RwLock<HashMap<PeerId<N>, usize>>>,
+    /// Notified whenever peer IDs are mapped to validator IDs.
+    validator_ids_changed: Arc<Notify>,
+    /// The keys of the current validators, used to verify signed messages.
+    validator_keys: Arc<RwLock<Vec<CompressedPublicKey>>>,
+    /// Whether messages are sent and received in signed envelopes.
+    signed_messages: bool,
+    /// The voting key messages are signed with.
+    signing_key: RwLock<Option<(CompressedPublicKey, SecretKey)>>,
+    /// The addresses this node publishes in its validator record.
+    own_addresses: Vec<ValidatorAddress<Address<N>>>,
+    /// The public key and serialized signed record of this node, served to validators that find
+    /// us as a provider of our record.
+    own_record: Arc<Mutex<Option<(CompressedPublicKey, Vec<u8>)>>>,
+    /// The handles for requesting validator records from providers. A peer only accepts one
+    /// receiver per message type, so they are created once per provider connection.
+    record_requests: RwLock<HashMap<PeerId<N>, Arc<RecordRequests<N>>>>,
 }
 
+/// Handle for requesting validator records from a provider.
+type RecordRequests<N> =
+    RequestResponse<<N as Network>::PeerType, RequestValidatorRecord, ValidatorRecordResponse>;
+
 impl<N> ValidatorNetworkImpl<N>
 where
     N: Network,
     <N::PeerType as Peer>::Id: Send + Sync + Serialize + Deserialize + Clone,
+    N::AddressType: Send + Sync + Serialize + Deserialize + Clone,
 {
     pub fn new(network: Arc<N>) -> Self {
         Self {
@@ -47,22 +145,179 @@ where
                 validator_keys: vec![],
                 validator_peer_id_cache: BTreeMap::new(),
             }),
+            validator_ids: Arc::new(RwLock::new(HashMap::new())),
+            validator_ids_changed: Arc::new(Notify::new()),
+            validator_keys: Arc::new(RwLock::new(vec![])),
+            signed_messages: false,
+            signing_key: RwLock::new(None),
+            own_addresses: vec![],
+            own_record: Arc::new(Mutex::new(None)),
+            record_requests: RwLock::new(HashMap::new()),
         }
     }
 
-    async fn dial_peer(
+    /// Publishes the given addresses in the validator record of this node, the preferred ones
+    /// first. Other validators try them in order before falling back to their regular peer
+    /// discovery.
+    pub fn with_addresses(mut self, addresses: Vec<ValidatorAddress<Address<N>>>) -> Self {
+        self.own_addresses = addresses;
+        self
+    }
+
+    /// Sends all messages in [`SignedEnvelope`]s and only delivers received messages that are
+    /// signed by one of the current validators. All validators need to use the same setting.
+    pub fn with_signed_messages(mut self, signed_messages: bool) -> Self {
+        self.signed_messages = signed_messages;
+        self
+    }
+
+    /// Wraps the message in an envelope signed by our voting key. Returns `None` if we are not one
+    /// of the current validators.
+    fn seal<M: Message>(&self, msg: M) -> Option<SignedEnvelope<M>> {
+        let signing_key = self.signing_key.read();
+        let (public_key, secret_key) = signing_key.as_ref()?;
+        let validator_id = self
+            .validator_keys
+            .read()
+            .iter()
+            .position(|key| key == public_key)?;
+
+        Some(SignedEnvelope::sign(msg, validator_id as u16, secret_key))
+    }
+
+    /// Receives the envelopes of messages of type `M` and yields the payloads that are signed by one
+    /// of the current validators, together with the validator ID of the signer and the peer that
+    /// relayed the envelope.
+    ///
+    /// Envelopes of unknown validators and envelopes that were already received are dropped before
+    /// their signature is checked. Signatures are verified on the blocking thread pool, at most
+    /// [`MAX_PENDING_VERIFICATIONS`] at a time per stream, so a flood of forged envelopes neither
+    /// stalls the runtime nor queues up unbounded work.
+    fn receive_signed<M: Message>(&self) -> BoxStream<'static, (M, usize, Arc<N::PeerType>)> {
+        let validator_keys = Arc::clone(&self.validator_keys);
+        let mut recent = RecentEnvelopes::new(RECENT_ENVELOPES);
+        self.network
+            .receive_from_all::<SignedEnvelope<M>>()
+            .filter_map(move |(envelope, peer)| {
+                let validator_id = envelope.validator_id as usize;
+                let public_key = validator_keys.read().get(validator_id).cloned();
+                let pending = match public_key {
+                    Some(public_key) if recent.insert(&envelope) => {
+                        Some((envelope, public_key, peer))
+                    }
+                    Some(_) => {
+                        log::trace!(
+                            "Dropping duplicate message of validator {} from {:?}",
+                            validator_id,
+                            peer.id()
+                        );
+                        None
+                    }
+                    None => {
+                        log::debug!(
+                            "Dropping message of unknown validator {} from {:?}",
+                            validator_id,
+                            peer.id()
+                        );
+                        None
+                    }
+                };
+                future::ready(pending)
+            })
+            .map(|(envelope, public_key, peer)| async move {
+                let validator_id = envelope.validator_id as usize;
+                let verified = tokio::task::spawn_blocking(move || {
+                    let verified = public_key
+                        .uncompress_cached()
+                        .map_or(false, |public_key| envelope.verify(&public_key));
+                    verified.then(|| envelope.payload)
+                })
+                .await
+                .ok()
+                .flatten();
+
+                if verified.is_none() {
+                    log::debug!(
+                        "Dropping message with invalid signature of validator {} from {:?}",
+                        validator_id,
+                        peer.id()
+                    );
+                }
+                verified.map(|payload| (payload, validator_id, peer))
+            })
+            .buffered(MAX_PENDING_VERIFICATIONS)
+            .filter_map(future::ready)
+            .boxed()
+    }
+
+    async fn send_to_all<M: Message + Clone>(
+        &self,
+        validator_ids: &[usize],
+        msg: M,
+    ) -> Vec<SendResult<NetworkError<N::Error>>>
+    where
+        N::Error: Send,
+    {
+        let futures = validator_ids
+            .iter()
+            .copied()
+            .map(|validator_id| (validator_id, msg.clone()))
+            .map(|(validator_id, msg)| async move {
+                SendResult {
+                    validator_id,
+                    outcome: self.send_to_validator(validator_id, msg).await,
+                }
+            });
+
+        join_all(futures).await
+    }
+
+    /// Caches the resolved validator. If it is one of the current validators, its peer ID is mapped
+    /// to its validator ID.
+    fn cache_validator(
+        &self,
+        state: &mut State<PeerId<N>, Address<N>>,
+        public_key: CompressedPublicKey,
+        validator: ResolvedValidator<PeerId<N>, Address<N>>,
+    ) {
+        let validator_id = state
+            .validator_keys
+            .iter()
+            .position(|key| *key == public_key);
+        let peer_id = validator.peer_id.clone();
+
+        let previous = state.validator_peer_id_cache.insert(public_key, validator);
+
+        let mut validator_ids = self.validator_ids.write();
+        if let Some(previous) = previous {
+            if previous.peer_id != peer_id {
+                validator_ids.remove(&previous.peer_id);
+            }
+        }
+        if let Some(validator_id) = validator_id {
+            validator_ids.insert(peer_id, validator_id);
+            self.validator_ids_changed.notify_waiters();
+        }
+    }
+
+    /// Waits until a connection to the peer is established after `dial` succeeded.
+    async fn connect<F>(
         &self,
         peer_id: PeerId<N>,
-    ) -> Result<Arc<N::PeerType>, NetworkError<N::Error>> {
+        dial: F,
+        timeout: Duration,
+    ) -> Result<Arc<N::PeerType>, NetworkError<N::Error>>
+    where
+        F: Future<Output = Result<(), N::Error>>,
+    {
         let (peers, mut event_stream) = self.network.get_peer_updates();
 
         if let Some(peer) = peers.into_iter().find(|peer| peer.id() == peer_id) {
             return Ok(peer);
         }
 
-        self.network.dial_peer(peer_id.clone()).await?;
-
         let future = async move {
+            dial.await?;
             loop {
                 match event_stream.next().await {
                     Some(Ok(NetworkEvent::PeerJoined(peer))) if peer.id() == peer_id => {
@@ -74,27 +329,219 @@ where
             }
         };
 
-        tokio::time::timeout(Duration::from_secs(5), future)
+        tokio::time::timeout(timeout, future)
             .await
             .map_err(|_| NetworkError::Unreachable)?
     }
 
-    /// Looks up the peer ID for a validator public key in the DHT.
-    async fn resolve_peer_id(
-        network: &N,
+    /// Dials the validator at the addresses of its record, starting with the one that worked
+    /// last. If none of them works, the peer is dialed by its ID. Returns the peer and the index of
+    /// the address it was reached at.
+    ///
+    /// All attempts share [`DIAL_VALIDATOR_TIMEOUT`]: each one gets an equal part of the time that
+    /// is left, so time not used by failed attempts goes to the following ones.
+    async fn dial_validator(
+        &self,
+        validator: &ResolvedValidator<PeerId<N>, Address<N>>,
+    ) -> Result<(Arc<N::PeerType>, Option<usize>), NetworkError<N::Error>> {
+        let mut order: Vec<usize> = (0..validator.addresses.len()).collect();
+        if let Some(working) = validator.working_address {
+            order.retain(|&i| i != working);
+            order.insert(0, working);
+        }
+
+        let deadline = Instant::now() + DIAL_VALIDATOR_TIMEOUT;
+        let mut remaining_attempts = order.len() as u32 + 1;
+        let mut next_timeout = || {
+            let timeout = deadline.saturating_duration_since(Instant::now()) / remaining_attempts;
+            remaining_attempts -= 1;
+            timeout
+        };
+
+        for i in order {
+            let address = &validator.addresses[i];
+            let dial = self.network.dial_address(address.address.clone());
+            let timeout = next_timeout();
+            match self.connect(validator.peer_id.clone(), dial, timeout).await {
+                Ok(peer) => return Ok((peer, Some(i))),
+                Err(e) => log::debug!(
+                    "Failed to dial validator {:?} at {} ({:?}): {}",
+                    validator.peer_id,
+                    address.address,
+                    address.transport,
+                    e
+                ),
+            }
+        }
+
+        let dial = self.network.dial_peer(validator.peer_id.clone());
+        let peer = self
+            .connect(validator.peer_id.clone(), dial, next_timeout())
+            .await?;
+        Ok((peer, None))
+    }
+
+    /// Looks up the peer ID and addresses for a validator public key in the DHT. If the record
+    /// can't be found there, the providers of the record are asked for it directly.
+    async fn resolve_validator(
+        &self,
         public_key: &CompressedPublicKey,
-    ) -> Result<Option<PeerId<N>>, NetworkError<N::Error>> {
-        if let Some(record) = network
-            .dht_get::<_, SignedValidatorRecord<PeerId<N>>>(&public_key)
-            .await?
+    ) -> Result<Option<ResolvedValidator<PeerId<N>, Address<N>>>, NetworkError<N::Error>> {
+        let uncompressed_key = public_key.uncompress_cached().unwrap();
+
+        let lookup_error = match self
+            .network
+            .dht_get::<_, SignedValidatorRecord<PeerId<N>, Address<N>>>(&public_key)
+            .await
         {
-            if record.verify(&public_key.uncompress().unwrap()) {
-                Ok(Some(record.record.peer_id))
-            } else {
-                Ok(None)
+            Ok(Some(record)) if record.verify(&uncompressed_key) => {
+                return Ok(Some(ResolvedValidator::from_record(record)))
+            }
+            Ok(_) => None,
+            Err(e) => {
+                log::debug!(
+                    "Failed to look up validator record in DHT: public_key = {:?}, error = {}",
+                    public_key,
+                    e
+                );
+                Some(e)
+            }
+        };
+
+        match self
+            .request_record_from_providers(public_key, &uncompressed_key)
+            .await
+        {
+            Some(record) => Ok(Some(ResolvedValidator::from_record(record))),
+            None => match lookup_error {
+                Some(e) => Err(e.into()),
+                None => Ok(None),
+            },
+        }
+    }
+
+    /// Returns the handle for requesting validator records from the peer. Handles of peers that
+    /// are no longer connected are dropped.
+    fn record_requests(&self, peer: Arc<N::PeerType>) -> Arc<RecordRequests<N>> {
+        let mut record_requests = self.record_requests.write();
+        if let Some(requests) = record_requests.get(&peer.id()) {
+            if Arc::ptr_eq(requests.peer(), &peer) {
+                return Arc::clone(requests);
+            }
+        }
+
+        record_requests.retain(|peer_id, requests| {
+            self.network
+                .get_peer(peer_id.clone())
+                .map_or(false, |connected| Arc::ptr_eq(requests.peer(), &connected))
+        });
+
+        let requests = Arc::new(RequestResponse::new(
+            Arc::clone(&peer),
+            RECORD_REQUEST_TIMEOUT,
+        ));
+        record_requests.insert(peer.id(), Arc::clone(&requests));
+        requests
+    }
+
+    /// Asks the peers that provide the record of the validator for it.
+    async fn request_record_from_providers(
+        &self,
+        public_key: &CompressedPublicKey,
+        uncompressed_key: &PublicKey,
+    ) -> Option<SignedValidatorRecord<PeerId<N>, Address<N>>> {
+        let providers = match self
+            .network
+            .dht_get_providers(&provider_key(public_key))
+            .await
+        {
+            Ok(providers) => providers,
+            Err(e) => {
+                log::debug!(
+                    "Failed to look up providers of validator record: public_key = {:?}, error = {}",
+                    public_key,
+                    e
+                );
+                return None;
+            }
+        };
+
+        for provider in providers.into_iter().take(MAX_RECORD_PROVIDERS) {
+            let dial = self.network.dial_peer(provider.clone());
+            let peer = match self.connect(provider.clone(), dial, CONNECT_TIMEOUT).await {
+                Ok(peer) => peer,
+                Err(e) => {
+                    log::debug!(
+                        "Failed to connect to provider {:?} of validator record: {}",
+                        provider,
+                        e
+                    );
+                    continue;
+                }
+            };
+
+            let response = self
+                .record_requests(peer)
+                .request(RequestValidatorRecord {
+                    public_key: public_key.clone(),
+                    request_identifier: 0,
+                })
+                .await;
+
+            match response {
+                Ok(ValidatorRecordResponse {
+                    record: Some(data), ..
+                }) => match SignedValidatorRecord::<PeerId<N>, Address<N>>::deserialize_from_vec(
+                    &data,
+                ) {
+                    Ok(record) if record.verify(uncompressed_key) => {
+                        log::debug!(
+                            "Got validator record from provider {:?}: public_key = {:?}",
+                            provider,
+                            public_key
+                        );
+                        return Some(record);
+                    }
+                    _ => log::warn!(
+                        "Provider {:?} sent an invalid validator record: public_key = {:?}",
+                        provider,
+                        public_key
+                    ),
+                },
+                Ok(_) => {}
+                Err(e) => log::debug!(
+                    "Failed to request validator record from provider {:?}: {}",
+                    provider,
+                    e
+                ),
+            }
+        }
+
+        None
+    }
+
+    /// Answers the validator record requests of other validators with the record of this node.
+    async fn serve_record(
+        network: Arc<N>,
+        own_record: Arc<Mutex<Option<(CompressedPublicKey, Vec<u8>)>>>,
+    ) {
+        let mut requests = network.receive_from_all::<RequestValidatorRecord>();
+
+        while let Some((request, peer)) = requests.next().await {
+            let record = match &*own_record.lock().await {
+                Some((public_key, record)) if *public_key == request.public_key => {
+                    Some(record.clone())
+                }
+                _ => None,
+            };
+
+            let response = ValidatorRecordResponse {
+                record,
+                request_identifier: request.request_identifier,
+            };
+            if let Err(e) = peer.send(response).await {
+                log::debug!("Failed to send validator record response: {:?}", e);
             }
-        } else {
-            Ok(None)
         }
     }
 
@@ -111,25 +558,193 @@ where
             .ok_or(NetworkError::UnknownValidator(validator_id))?
             .clone();
 
-        let entry = state.validator_peer_id_cache.entry(public_key.clone());
+        if let Some(validator) = state.validator_peer_id_cache.get(&public_key) {
+            return Ok(validator.peer_id.clone());
+        }
+
+        if let Some(validator) = self.resolve_validator(&public_key).await? {
+            let peer_id = validator.peer_id.clone();
+            self.cache_validator(&mut state, public_key, validator);
+            Ok(peer_id)
+        } else {
+            log::error!(
+                "Could not find peer ID for validator in DHT: public_key = {:?}",
+                public_key
+            );
+            Err(NetworkError::UnknownValidator(validator_id))
+        }
+    }
+
+    /// Resolves the validator and dials it if there is no connection yet. The resolved validator is
+    /// cached, so it doesn't need to be looked up again once it becomes active.
+    async fn preconnect_validator(&self, public_key: CompressedPublicKey) {
+        let cached = self
+            .state
+            .lock()
+            .await
+            .validator_peer_id_cache
+            .get(&public_key)
+            .cloned();
+
+        let mut validator = match cached {
+            Some(validator) => validator,
+            None => match self.resolve_validator(&public_key).await {
+                Ok(Some(validator)) => validator,
+                Ok(None) => {
+                    log::debug!(
+                        "Can't preconnect, could not find peer ID for validator in DHT: public_key = {:?}",
+                        public_key
+                    );
+                    return;
+                }
+                Err(e) => {
+                    log::debug!(
+                        "Can't preconnect, failed to resolve validator: public_key = {:?}, error = {}",
+                        public_key,
+                        e
+                    );
+                    return;
+                }
+            },
+        };
+
+        if validator.peer_id != self.network.get_local_peer_id()
+            && self.network.get_peer(validator.peer_id.clone()).is_none()
+        {
+            match self.dial_validator(&validator).await {
+                Ok((_, working_address)) => validator.working_address = working_address,
+                Err(e) => log::debug!(
+                    "Failed to preconnect to validator {:?}: {}",
+                    validator.peer_id,
+                    e
+                ),
+            }
+        }
+
+        let mut state = self.state.lock().await;
+        self.cache_validator(&mut state, public_key, validator);
+    }
+
+    /// Sends the message to the validator, dialing it if there is no connection yet.
+    async fn send_to_validator<M: Message>(
+        &self,
+        validator_id: usize,
+        msg: M,
+    ) -> SendOutcome<NetworkError<N::Error>>
+    where
+        N::Error: Send,
+    {
+        let peer = if let Ok(Some(peer)) = self.get_validator_peer(validator_id).await {
+            // The peer was cached so the send is fast tracked
+            peer
+        } else {
+            // The peer could not be retrieved so we update the cache with a fresh lookup
+            let mut state = self.state.lock().await;
+
+            // get the public key for the validator_id
+            let public_key = match state.validator_keys.get(validator_id) {
+                Some(public_key) => public_key.clone(),
+                None => {
+                    return SendOutcome::NotResolved(NetworkError::UnknownValidator(validator_id))
+                }
+            };
 
-        match entry {
-            Entry::Occupied(occupied) => Ok(occupied.get().clone()),
-            Entry::Vacant(vacant) => {
-                if let Some(peer_id) = Self::resolve_peer_id(&self.network, &public_key).await? {
-                    Ok(vacant.insert(peer_id).clone())
-                } else {
+            // resolve the public key to the peer_id using the DHT record
+            let mut validator = match self.resolve_validator(&public_key).await {
+                Ok(Some(validator)) => validator,
+                Ok(None) => {
                     log::error!(
-                        "Could not find peer ID for validator in DHT: public_key = {:?}",
+                        "send_to failed; Could not find peer ID for validator in DHT: public_key = {:?}",
                         public_key
                     );
-                    Err(NetworkError::UnknownValidator(validator_id))
+                    return SendOutcome::NotResolved(NetworkError::UnknownValidator(validator_id));
+                }
+                Err(e) => return SendOutcome::NotResolved(e),
+            };
+
+            // Keep the address that worked before if the validator is still the same peer.
+            if let Some(cached) = state.validator_peer_id_cache.get(&public_key) {
+                if cached.peer_id == validator.peer_id {
+                    validator.working_address = cached
+                        .working_address
+                        .filter(|&i| i < validator.addresses.len());
                 }
             }
+
+            // try to get the peer for the peer_id. If it does not exist it should be dialed
+            let peer = if let Some(peer) = self.network.get_peer(validator.peer_id.clone()) {
+                peer
+            } else {
+                log::debug!(
+                    "Not connected to validator {} @ {:?}, dialing...",
+                    validator_id,
+                    validator.peer_id
+                );
+                match self.dial_validator(&validator).await {
+                    Ok((peer, working_address)) => {
+                        validator.working_address = working_address;
+                        peer
+                    }
+                    Err(e) => {
+                        self.cache_validator(&mut state, public_key, validator);
+                        return SendOutcome::DialFailed(e);
+                    }
+                }
+            };
+
+            // set the cache with the new record for this public key
+            self.cache_validator(&mut state, public_key, validator);
+            peer
+        };
+
+        send_outcome(peer.send(msg).await)
+    }
+}
+
+/// Maps the result of sending a message to a peer to the outcome of sending it to the validator.
+fn send_outcome(result: Result<(), SendError>) -> SendOutcome<NetworkError> {
+    match result {
+        Ok(()) => SendOutcome::Sent,
+        Err(e @ SendError::AlreadyClosed) => SendOutcome::ConnectionClosed(NetworkError::Send(e)),
+        Err(e @ SendError::Serialization(_)) => {
+            SendOutcome::SerializationFailed(NetworkError::Send(e))
         }
     }
 }
 
+/// Returns the validator ID of the peer. If the peer isn't mapped to a validator, waits for it to be
+/// mapped for up to [`UNMAPPED_SENDER_TIMEOUT`], unless too many messages are held back already.
+async fn wait_for_validator_id<TPeerId: Eq + std::hash::Hash>(
+    validator_ids: &RwLock<HashMap<TPeerId, usize>>,
+    validator_ids_changed: &Notify,
+    unmapped: &AtomicUsize,
+    peer_id: &TPeerId,
+) -> Option<usize> {
+    if let Some(validator_id) = validator_ids.read().get(peer_id) {
+        return Some(*validator_id);
+    }
+
+    if unmapped.fetch_add(1, Ordering::SeqCst) >= MAX_UNMAPPED_MESSAGES {
+        unmapped.fetch_sub(1, Ordering::SeqCst);
+        return None;
+    }
+
+    let deadline = Instant::now() + UNMAPPED_SENDER_TIMEOUT;
+    let validator_id = loop {
+        // Register for the notification before checking, so no change is missed.
+        let changed = validator_ids_changed.notified();
+        if let Some(validator_id) = validator_ids.read().get(peer_id) {
+            break Some(*validator_id);
+        }
+        if tokio::time::timeout_at(deadline, changed).await.is_err() {
+            break None;
+        }
+    };
+
+    unmapped.fetch_sub(1, Ordering::SeqCst);
+    validator_id
+}
+
 // Proposal - gossip
 // LevelUpdate - multicast
 // StateEx - request/response
@@ -139,6 +754,7 @@ impl<N> ValidatorNetwork for ValidatorNetworkImpl<N>
 where
     N: Network,
     <N::PeerType as Peer>::Id: Send + Sync + Serialize + Deserialize + Clone,
+    N::AddressType: Send + Sync + Serialize + Deserialize + Clone,
     N::Error: Send,
 {
     type Error = NetworkError<N::Error>;
@@ -157,15 +773,39 @@ where
 
         let mut keep_cached = BTreeMap::new();
         for validator_key in &validator_keys {
-            if let Some(peer_id) = state.validator_peer_id_cache.remove(validator_key) {
-                keep_cached.insert(validator_key.clone(), peer_id);
+            if let Some(validator) = state.validator_peer_id_cache.remove(validator_key) {
+                keep_cached.insert(validator_key.clone(), validator);
             }
         }
 
+        // Map the peer IDs of the validators we already know to their new validator IDs.
+        let mut validator_ids = self.validator_ids.write();
+        validator_ids.clear();
+        for (validator_id, validator_key) in validator_keys.iter().enumerate() {
+            if let Some(validator) = keep_cached.get(validator_key) {
+                validator_ids.insert(validator.peer_id.clone(), validator_id);
+            }
+        }
+
+        drop(validator_ids);
+        self.validator_ids_changed.notify_waiters();
+
+        *self.validator_keys.write() = validator_keys.clone();
         state.validator_keys = validator_keys;
         state.validator_peer_id_cache = keep_cached;
     }
 
+    async fn preconnect(&self, validator_keys: Vec<CompressedPublicKey>) {
+        log::debug!("Preconnecting to {} validators", validator_keys.len());
+
+        join_all(
+            validator_keys
+                .into_iter()
+                .map(|public_key| self.preconnect_validator(public_key)),
+        )
+        .await;
+    }
+
     async fn get_validator_peer(
         &self,
         validator_id: usize,
@@ -178,64 +818,31 @@ where
         &self,
         validator_ids: &[usize],
         msg: M,
-    ) -> Vec<Result<(), Self::Error>> {
-        let futures = validator_ids
-            .iter()
-            .copied()
-            .map(|validator_id| (validator_id, msg.clone()))
-            .map(|(validator_id, msg)| async move {
-                let peer = if let Ok(Some(peer)) = self.get_validator_peer(validator_id).await {
-                    // The peer was cached so the send is fast tracked
-                    peer
-                } else {
-                    // The peer could not be retrieved so we update the cache with a fresh lookup
-                    let mut state = self.state.lock().await;
-
-                    // get the public key for the validator_id, return NetworkError::UnknownValidator if it does not exist
-                    let public_key = state
-                        .validator_keys
-                        .get(validator_id)
-                        .ok_or(NetworkError::UnknownValidator(validator_id))?
-                        .clone();
-
-                    // resolve the public key to the peer_id using the DHT record
-                    if let Some(peer_id) = Self::resolve_peer_id(&self.network, &public_key).await? {
-                        // set the cache with he new peer_id for this public key
-                        state
-                            .validator_peer_id_cache
-                            .entry(public_key.clone())
-                            .and_modify(|id| *id = peer_id.clone())
-                            .or_insert_with(|| peer_id.clone());
-
-                        // try to get the peer for the peer_id. If it does not exist it should be dialed
-                        if let Some(peer) = self.network.get_peer(peer_id.clone()) {
-                            peer
-                        } else {
-                            log::debug!("Not connected to validator {} @ {:?}, dialing...", validator_id, peer_id);
-                            self.dial_peer(peer_id).await?
-                        }
-                    } else {
-                        log::error!(
-                            "send_to failed; Could not find peer ID for validator in DHT: public_key = {:?}",
-                            public_key
-                        );
-                        return Err(NetworkError::UnknownValidator(validator_id));
-                    }
-                };
-                peer
-                    .send(msg.clone())
-                    .await
-                    .map_err(NetworkError::Send)?;
-                Ok(())
-            });
+    ) -> Vec<SendResult<Self::Error>> {
+        if !self.signed_messages {
+            return self.send_to_all(validator_ids, msg).await;
+        }
 
-        join_all(futures)
-            .await
-            .into_iter()
-            .collect::<Vec<Result<(), Self::Error>>>()
+        match self.seal(msg) {
+            Some(envelope) => self.send_to_all(validator_ids, envelope).await,
+            None => validator_ids
+                .iter()
+                .map(|&validator_id| SendResult {
+                    validator_id,
+                    outcome: SendOutcome::NotSigned(NetworkError::NotSigning),
+                })
+                .collect(),
+        }
     }
 
     fn receive<M: Message>(&self) -> MessageStream<M, PeerId<N>> {
+        if self.signed_messages {
+            return Box::pin(
+                self.receive_signed()
+                    .map(|(message, _, peer)| (message, peer.id())),
+            );
+        }
+
         Box::pin(
             self.network
                 .receive_from_all()
@@ -243,6 +850,50 @@ where
         )
     }
 
+    fn receive_from_validators<M: Message>(&self) -> MessageStream<M, usize> {
+        // Signed messages carry the validator ID of their sender.
+        if self.signed_messages {
+            return Box::pin(
+                self.receive_signed()
+                    .map(|(message, validator_id, _)| (message, validator_id)),
+            );
+        }
+
+        // Messages from peers that aren't mapped to a validator yet are held back until the
+        // sender is resolved, instead of being dropped.
+        let validator_ids = Arc::clone(&self.validator_ids);
+        let validator_ids_changed = Arc::clone(&self.validator_ids_changed);
+        let unmapped = Arc::new(AtomicUsize::new(0));
+        Box::pin(
+            self.network
+                .receive_from_all()
+                .map(move |(message, peer): (M, Arc<N::PeerType>)| {
+                    let validator_ids = Arc::clone(&validator_ids);
+                    let validator_ids_changed = Arc::clone(&validator_ids_changed);
+                    let unmapped = Arc::clone(&unmapped);
+                    async move {
+                        let peer_id = peer.id();
+                        let validator_id = wait_for_validator_id(
+                            &validator_ids,
+                            &validator_ids_changed,
+                            &unmapped,
+                            &peer_id,
+                        )
+                        .await;
+                        if validator_id.is_none() {
+                            log::trace!(
+                                "Dropping message from {:?}, it is not a known validator",
+                                peer_id
+                            );
+                        }
+                        validator_id.map(|validator_id| (message, validator_id))
+                    }
+                })
+                .buffer_unordered(MAX_UNMAPPED_MESSAGES + 1)
+                .filter_map(future::ready),
+        )
+    }
+
     async fn publish<TTopic>(&self, item: TTopic::Item) -> Result<(), Self::Error>
     where
         TTopic: Topic + Sync,
@@ -269,11 +920,28 @@ where
         public_key: &CompressedPublicKey,
         secret_key: &SecretKey,
     ) -> Result<(), Self::Error> {
+        *self.signing_key.write() = Some((public_key.clone(), *secret_key));
+
         let peer_id = self.network.get_local_peer_id();
-        let record = ValidatorRecord::new(peer_id);
-        self.network
-            .dht_put(public_key, &record.sign(secret_key))
-            .await?;
+        let record = ValidatorRecord::new(peer_id, self.own_addresses.clone()).sign(secret_key);
+        self.network.dht_put(public_key, &record).await?;
+
+        // Serve the record to validators that can't find it in the DHT.
+        let previous = self
+            .own_record
+            .lock()
+            .await
+            .replace((public_key.clone(), record.serialize_to_vec()));
+        if previous.is_none() {
+            tokio::spawn(Self::serve_record(
+                Arc::clone(&self.network),
+                Arc::clone(&self.own_record),
+            ));
+        }
+
+        if let Err(e) = self.network.dht_provide(&provider_key(public_key)).await {
+            log::warn!("Failed to register as provider of validator record: {}", e);
+        }
 
         Ok(())
     }
@@ -285,3 +953,326 @@ where
         self.network.validate_message::<TTopic>(id, acceptance);
     }
 }
+
+/// The hashes of the most recently received envelopes.
+///
+/// The whole envelope is hashed, not only its signature, so a forged envelope that reuses the
+/// signature of a genuine one can't cause the genuine one to be dropped.
+struct RecentEnvelopes {
+    hasher: RandomState,
+    order: VecDeque<u64>,
+    hashes: HashSet<u64>,
+    capacity: usize,
+}
+
+impl RecentEnvelopes {
+    fn new(capacity: usize) -> Self {
+        Self {
+            hasher: RandomState::new(),
+            order: VecDeque::with_capacity(capacity),
+            hashes: HashSet::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Remembers the envelope. Returns `false` if it was received recently.
+    fn insert<M: Message>(&mut self, envelope: &SignedEnvelope<M>) -> bool {
+        let mut hasher = self.hasher.build_hasher();
+        envelope.serialize_to_vec().hash(&mut hasher);
+        let hash = hasher.finish();
+
+        if !self.hashes.insert(hash) {
+            return false;
+        }
+        if self.order.len() == self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.hashes.remove(&oldest);
+            }
+        }
+        self.order.push_back(hash);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nimiq_bls::KeyPair;
+    use nimiq_network_mock::{MockAddress, MockHub, MockNetwork, MockPeerId};
+    use nimiq_utils::key_rng::SecureGenerate;
+
+    use super::*;
+    use crate::validator_record::TransportHint;
+
+    fn request(request_identifier: u32) -> RequestValidatorRecord {
+        RequestValidatorRecord {
+            public_key: CompressedPublicKey::default(),
+            request_identifier,
+        }
+    }
+
+    /// Maps the peer of the given network to the validator with the given key.
+    async fn resolve(
+        validator_network: &ValidatorNetworkImpl<MockNetwork>,
+        public_key: CompressedPublicKey,
+        network: &MockNetwork,
+    ) {
+        let mut state = validator_network.state.lock().await;
+        let validator = ResolvedValidator {
+            peer_id: network.get_local_peer_id(),
+            addresses: vec![],
+            working_address: None,
+        };
+        validator_network.cache_validator(&mut state, public_key, validator);
+    }
+
+    fn setup() -> (
+        ValidatorNetworkImpl<MockNetwork>,
+        Arc<MockNetwork>,
+        Arc<MockNetwork>,
+    ) {
+        let mut hub = MockHub::default();
+        let net1 = Arc::new(hub.new_network());
+        let net2 = Arc::new(hub.new_network());
+        net1.dial_mock(&net2);
+
+        (ValidatorNetworkImpl::new(Arc::clone(&net1)), net1, net2)
+    }
+
+    #[tokio::test]
+    async fn it_holds_back_messages_until_the_sender_is_resolved() {
+        let (validator_network, net1, net2) = setup();
+        let public_key = CompressedPublicKey::default();
+        validator_network
+            .set_validators(vec![public_key.clone()])
+            .await;
+
+        let mut messages = validator_network.receive_from_validators::<RequestValidatorRecord>();
+        let received = tokio::spawn(async move { messages.next().await });
+
+        let peer = net2.get_peer(net1.get_local_peer_id()).unwrap();
+        peer.send(request(1)).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // The validator is resolved only after its message arrived.
+        resolve(&validator_network, public_key, &net2).await;
+
+        let (message, validator_id) = received.await.unwrap().unwrap();
+        assert_eq!(message.request_identifier, 1);
+        assert_eq!(validator_id, 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn it_drops_messages_of_senders_that_are_not_resolved_in_time() {
+        let (validator_network, net1, net2) = setup();
+        let public_key = CompressedPublicKey::default();
+        validator_network
+            .set_validators(vec![public_key.clone()])
+            .await;
+
+        let mut messages = validator_network.receive_from_validators::<RequestValidatorRecord>();
+        let received = tokio::spawn(async move { messages.next().await });
+
+        let peer = net2.get_peer(net1.get_local_peer_id()).unwrap();
+        peer.send(request(1)).await.unwrap();
+        tokio::time::sleep(UNMAPPED_SENDER_TIMEOUT + Duration::from_secs(1)).await;
+
+        resolve(&validator_network, public_key, &net2).await;
+        peer.send(request(2)).await.unwrap();
+
+        let (message, _) = received.await.unwrap().unwrap();
+        assert_eq!(message.request_identifier, 2);
+    }
+
+    fn setup_signed(
+        validator_keys: &[CompressedPublicKey],
+    ) -> (
+        ValidatorNetworkImpl<MockNetwork>,
+        Arc<MockNetwork>,
+        Arc<MockNetwork>,
+    ) {
+        let (validator_network, net1, net2) = setup();
+        let validator_network = validator_network.with_signed_messages(true);
+        *validator_network.validator_keys.write() = validator_keys.to_vec();
+        (validator_network, net1, net2)
+    }
+
+    #[tokio::test]
+    async fn it_delivers_signed_messages_of_validators_once() {
+        let key_pair = KeyPair::generate_default_csprng();
+        let (validator_network, net1, net2) = setup_signed(&[key_pair.public_key.compress()]);
+
+        let mut messages = validator_network.receive_from_validators::<RequestValidatorRecord>();
+        let peer = net2.get_peer(net1.get_local_peer_id()).unwrap();
+
+        let envelope = SignedEnvelope::sign(request(1), 0, &key_pair.secret_key);
+        peer.send(envelope.clone()).await.unwrap();
+        peer.send(envelope).await.unwrap();
+        peer.send(SignedEnvelope::sign(request(2), 0, &key_pair.secret_key))
+            .await
+            .unwrap();
+
+        let (message, validator_id) = messages.next().await.unwrap();
+        assert_eq!(message.request_identifier, 1);
+        assert_eq!(validator_id, 0);
+
+        // The duplicate of the first envelope is dropped.
+        let (message, _) = messages.next().await.unwrap();
+        assert_eq!(message.request_identifier, 2);
+    }
+
+    #[tokio::test]
+    async fn it_drops_forged_messages_and_messages_of_unknown_validators() {
+        let key_pair = KeyPair::generate_default_csprng();
+        let forger = KeyPair::generate_default_csprng();
+        let (validator_network, net1, net2) = setup_signed(&[key_pair.public_key.compress()]);
+
+        let mut messages = validator_network.receive_from_validators::<RequestValidatorRecord>();
+        let peer = net2.get_peer(net1.get_local_peer_id()).unwrap();
+
+        // Signed by a key that isn't the validator's.
+        peer.send(SignedEnvelope::sign(request(1), 0, &forger.secret_key))
+            .await
+            .unwrap();
+        // Claims to be sent by a validator that doesn't exist.
+        peer.send(SignedEnvelope::sign(request(2), 1, &key_pair.secret_key))
+            .await
+            .unwrap();
+        // Reuses the signature of a genuine envelope for another payload.
+        let mut forged = SignedEnvelope::sign(request(4), 0, &key_pair.secret_key);
+        forged.payload = request(3);
+        peer.send(forged).await.unwrap();
+        peer.send(SignedEnvelope::sign(request(4), 0, &key_pair.secret_key))
+            .await
+            .unwrap();
+
+        let (message, validator_id) = messages.next().await.unwrap();
+        assert_eq!(message.request_identifier, 4);
+        assert_eq!(validator_id, 0);
+    }
+
+    #[test]
+    fn it_keeps_the_cause_of_send_errors() {
+        assert!(matches!(send_outcome(Ok(())), SendOutcome::Sent));
+
+        let closed = send_outcome(Err(SendError::AlreadyClosed));
+        assert!(matches!(closed, SendOutcome::ConnectionClosed(_)));
+        assert!(closed.is_retryable());
+
+        let unserializable = send_outcome(Err(SendError::Serialization(
+            beserial::SerializingError::Overflow,
+        )));
+        assert!(matches!(
+            unserializable,
+            SendOutcome::SerializationFailed(_)
+        ));
+        assert!(!unserializable.is_retryable());
+    }
+
+    #[test]
+    fn it_forgets_the_oldest_envelopes() {
+        let key_pair = KeyPair::generate_default_csprng();
+        let envelopes: Vec<_> = (0..3)
+            .map(|i| SignedEnvelope::sign(request(i), 0, &key_pair.secret_key))
+            .collect();
+
+        let mut recent = RecentEnvelopes::new(2);
+        assert!(recent.insert(&envelopes[0]));
+        assert!(recent.insert(&envelopes[1]));
+        assert!(!recent.insert(&envelopes[0]));
+        assert!(recent.insert(&envelopes[2]));
+        assert!(recent.insert(&envelopes[0]));
+        assert!(!recent.insert(&envelopes[2]));
+    }
+
+    fn validator(
+        peer_id: MockPeerId,
+        addresses: Vec<MockAddress>,
+    ) -> ResolvedValidator<MockPeerId, MockAddress> {
+        ResolvedValidator {
+            peer_id,
+            addresses: addresses
+                .into_iter()
+                .map(|address| ValidatorAddress {
+                    transport: TransportHint::Ws,
+                    address,
+                })
+                .collect(),
+            working_address: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn it_dials_validators_at_the_next_address() {
+        let mut hub = MockHub::default();
+        let net1 = Arc::new(hub.new_network());
+        let net2 = hub.new_network();
+        let validator_network = ValidatorNetworkImpl::new(Arc::clone(&net1));
+
+        // The first address doesn't exist.
+        let addresses = vec![hub.new_address(), net2.get_local_peer_id().into()];
+        let validator = validator(net2.get_local_peer_id(), addresses);
+
+        let (peer, working_address) = validator_network.dial_validator(&validator).await.unwrap();
+        assert_eq!(peer.id(), net2.get_local_peer_id());
+        assert_eq!(working_address, Some(1));
+    }
+
+    #[tokio::test]
+    async fn it_preconnects_without_changing_the_active_validators() {
+        let mut hub = MockHub::default();
+        let net1 = Arc::new(hub.new_network());
+        let net2 = hub.new_network();
+        let validator_network = ValidatorNetworkImpl::new(Arc::clone(&net1));
+
+        let active_key = KeyPair::generate_default_csprng().public_key.compress();
+        let next_key = KeyPair::generate_default_csprng().public_key.compress();
+        validator_network
+            .set_validators(vec![active_key.clone()])
+            .await;
+
+        // The next validator is known, but not connected yet.
+        {
+            let mut state = validator_network.state.lock().await;
+            let next_validator = validator(
+                net2.get_local_peer_id(),
+                vec![net2.get_local_peer_id().into()],
+            );
+            validator_network.cache_validator(&mut state, next_key.clone(), next_validator);
+        }
+        assert!(net1.get_peer(net2.get_local_peer_id()).is_none());
+
+        validator_network.preconnect(vec![next_key.clone()]).await;
+
+        assert!(net1.get_peer(net2.get_local_peer_id()).is_some());
+        let state = validator_network.state.lock().await;
+        assert_eq!(state.validator_keys, vec![active_key]);
+        assert_eq!(
+            state.validator_peer_id_cache[&next_key].working_address,
+            Some(0)
+        );
+        assert!(!validator_network
+            .validator_ids
+            .read()
+            .contains_key(&net2.get_local_peer_id()));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn it_dials_validators_within_one_deadline() {
+        let mut hub = MockHub::default();
+        let net1 = Arc::new(hub.new_network());
+        let validator_network = ValidatorNetworkImpl::new(Arc::clone(&net1));
+
+        // All addresses are reachable, but belong to other peers, so every attempt runs into its
+        // timeout.
+        let others: Vec<_> = (0..4).map(|_| hub.new_network()).collect();
+        let addresses = others
+            .iter()
+            .map(|net| net.get_local_peer_id().into())
+            .collect();
+        let validator = validator(hub.new_address().into(), addresses);
+
+        let start = Instant::now();
+        assert!(validator_network.dial_validator(&validator).await.is_err());
+        assert!(start.elapsed() <= DIAL_VALIDATOR_TIMEOUT);
+    }
+}
@@ -1,60 +1,243 @@
-use beserial::{Deserialize, Serialize};
+use beserial::{
+    deserialize_trailing, Deserialize, ReadBytesExt, Serialize, SerializeWithLength,
+    SerializingError, WriteBytesExt,
+};
 use nimiq_bls::{PublicKey, SecretKey, Signature};
 use nimiq_utils::tagged_signing::TaggedSignable;
 
 //struct ValidatorPeerId<TPeerId: Serialize>(TPeerId);
 
 // TODO: Use a tagged signature for validator records
-impl<TPeerId> TaggedSignable for ValidatorRecord<TPeerId>
+impl<TPeerId, TAddress> TaggedSignable for ValidatorRecord<TPeerId, TAddress>
 where
     TPeerId: Serialize + Deserialize,
+    TAddress: Serialize + Deserialize,
 {
     const TAG: u8 = 0x03;
 }
 
+/// The transport a validator address is reachable with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[repr(u8)]
+pub enum TransportHint {
+    Ws = 0,
+    Wss = 1,
+    Quic = 2,
+}
+
+/// An address a validator can be dialed at.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValidatorAddress<TAddress>
+where
+    TAddress: Serialize + Deserialize,
+{
+    pub transport: TransportHint,
+    pub address: TAddress,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct ValidatorRecord<TPeerId>
+pub struct ValidatorRecord<TPeerId, TAddress>
 where
     TPeerId: Serialize + Deserialize,
+    TAddress: Serialize + Deserialize,
 {
     pub peer_id: TPeerId,
+    /// The addresses the validator can be dialed at, the preferred ones first. They are only
+    /// part of the signed encoding, see [`SignedValidatorRecord`].
+    #[beserial(len_type(u8, limit = 16))]
+    pub addresses: Vec<ValidatorAddress<TAddress>>,
     //public_key: PublicKey,
     // TODO: other info?
 }
 
-impl<TPeerId> ValidatorRecord<TPeerId>
+impl<TPeerId, TAddress> ValidatorRecord<TPeerId, TAddress>
 where
     TPeerId: Serialize + Deserialize,
+    TAddress: Serialize + Deserialize,
 {
-    pub fn new(peer_id: TPeerId) -> Self {
-        Self { peer_id }
+    /// The maximum number of addresses in a record.
+    pub const MAX_ADDRESSES: usize = 16;
+
+    pub fn new(peer_id: TPeerId, addresses: Vec<ValidatorAddress<TAddress>>) -> Self {
+        Self { peer_id, addresses }
     }
 
-    pub fn sign(self, secret_key: &SecretKey) -> SignedValidatorRecord<TPeerId> {
-        let data = self.serialize_to_vec();
-        let signature = secret_key.sign(&data);
+    pub fn sign(self, secret_key: &SecretKey) -> SignedValidatorRecord<TPeerId, TAddress> {
+        let signature = secret_key.sign(&self.peer_id.serialize_to_vec());
+        let addresses_signature =
+            (!self.addresses.is_empty()).then(|| secret_key.sign(&self.serialize_to_vec()));
 
         SignedValidatorRecord {
             record: self,
             signature,
+            addresses_signature,
         }
     }
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct SignedValidatorRecord<TPeerId>
+/// A validator record signed by the voting key of the validator.
+///
+/// Records without addresses are encoded as before the addresses were introduced: the peer ID and
+/// its signature. The addresses and a second signature over the whole record are appended, so
+/// older nodes still decode and verify the peer ID of records that contain addresses.
+#[derive(Clone, Debug)]
+pub struct SignedValidatorRecord<TPeerId, TAddress>
 where
     TPeerId: Serialize + Deserialize,
+    TAddress: Serialize + Deserialize,
 {
-    pub record: ValidatorRecord<TPeerId>,
+    pub record: ValidatorRecord<TPeerId, TAddress>,
+    /// The signature of the peer ID.
     pub signature: Signature,
+    /// The signature of the whole record, if it contains addresses.
+    pub addresses_signature: Option<Signature>,
 }
 
-impl<TPeerId> SignedValidatorRecord<TPeerId>
+impl<TPeerId, TAddress> SignedValidatorRecord<TPeerId, TAddress>
 where
     TPeerId: Serialize + Deserialize,
+    TAddress: Serialize + Deserialize,
 {
     pub fn verify(&self, public_key: &PublicKey) -> bool {
-        public_key.verify(&self.record.serialize_to_vec(), &self.signature)
+        if !public_key.verify(&self.record.peer_id.serialize_to_vec(), &self.signature) {
+            return false;
+        }
+
+        match &self.addresses_signature {
+            Some(signature) => public_key.verify(&self.record.serialize_to_vec(), signature),
+            None => self.record.addresses.is_empty(),
+        }
+    }
+}
+
+impl<TPeerId, TAddress> Serialize for SignedValidatorRecord<TPeerId, TAddress>
+where
+    TPeerId: Serialize + Deserialize,
+    TAddress: Serialize + Deserialize,
+{
+    fn serialize<W: WriteBytesExt>(&self, writer: &mut W) -> Result<usize, SerializingError> {
+        let mut size = self.record.peer_id.serialize(writer)?;
+        size += self.signature.serialize(writer)?;
+        if let Some(addresses_signature) = &self.addresses_signature {
+            size += SerializeWithLength::serialize::<u8, _>(&self.record.addresses, writer)?;
+            size += addresses_signature.serialize(writer)?;
+        }
+        Ok(size)
+    }
+
+    fn serialized_size(&self) -> usize {
+        let mut size = self.record.peer_id.serialized_size() + self.signature.serialized_size();
+        if let Some(addresses_signature) = &self.addresses_signature {
+            size += SerializeWithLength::serialized_size::<u8>(&self.record.addresses);
+            size += addresses_signature.serialized_size();
+        }
+        size
+    }
+}
+
+impl<TPeerId, TAddress> Deserialize for SignedValidatorRecord<TPeerId, TAddress>
+where
+    TPeerId: Serialize + Deserialize,
+    TAddress: Serialize + Deserialize,
+{
+    fn deserialize<R: ReadBytesExt>(reader: &mut R) -> Result<Self, SerializingError> {
+        let peer_id = Deserialize::deserialize(reader)?;
+        let signature = Deserialize::deserialize(reader)?;
+
+        // Records of older validators end here.
+        let num_addresses: u8 = deserialize_trailing(reader)?;
+        let (addresses, addresses_signature) = if num_addresses == 0 {
+            (vec![], None)
+        } else {
+            if num_addresses as usize > ValidatorRecord::<TPeerId, TAddress>::MAX_ADDRESSES {
+                return Err(SerializingError::LimitExceeded);
+            }
+            let addresses = (0..num_addresses)
+                .map(|_| Deserialize::deserialize(reader))
+                .collect::<Result<Vec<_>, _>>()?;
+            (addresses, Some(Deserialize::deserialize(reader)?))
+        };
+
+        Ok(SignedValidatorRecord {
+            record: ValidatorRecord { peer_id, addresses },
+            signature,
+            addresses_signature,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nimiq_bls::KeyPair;
+    use nimiq_utils::key_rng::SecureGenerate;
+
+    use super::*;
+
+    type Record = ValidatorRecord<u64, u32>;
+    type SignedRecord = SignedValidatorRecord<u64, u32>;
+
+    fn addresses() -> Vec<ValidatorAddress<u32>> {
+        vec![
+            ValidatorAddress {
+                transport: TransportHint::Quic,
+                address: 1,
+            },
+            ValidatorAddress {
+                transport: TransportHint::Ws,
+                address: 2,
+            },
+        ]
+    }
+
+    #[test]
+    fn it_decodes_records_of_older_validators() {
+        let key_pair = KeyPair::generate_default_csprng();
+
+        // Older validators sign and send only the peer ID.
+        let peer_id = 42u64;
+        let signature = key_pair.secret_key.sign(&peer_id.serialize_to_vec());
+        let mut bin = peer_id.serialize_to_vec();
+        bin.extend(signature.serialize_to_vec());
+
+        let record = SignedRecord::deserialize_from_vec(&bin).unwrap();
+        assert_eq!(record.record.peer_id, peer_id);
+        assert!(record.record.addresses.is_empty());
+        assert!(record.verify(&key_pair.public_key));
+
+        // Records without addresses are still encoded the same way.
+        let own = Record::new(peer_id, vec![]).sign(&key_pair.secret_key);
+        assert_eq!(own.serialize_to_vec(), bin);
+    }
+
+    #[test]
+    fn it_keeps_the_peer_id_readable_for_older_validators() {
+        let key_pair = KeyPair::generate_default_csprng();
+        let peer_id = 42u64;
+        let bin = Record::new(peer_id, addresses())
+            .sign(&key_pair.secret_key)
+            .serialize_to_vec();
+
+        // The prefix is what older validators decode and verify.
+        let signature = Signature::deserialize_from_vec(&bin[peer_id.serialized_size()..]).unwrap();
+        assert!(key_pair
+            .public_key
+            .verify(&peer_id.serialize_to_vec(), &signature));
+
+        let record = SignedRecord::deserialize_from_vec(&bin).unwrap();
+        assert_eq!(record.record.addresses, addresses());
+        assert!(record.verify(&key_pair.public_key));
+    }
+
+    #[test]
+    fn it_rejects_records_with_tampered_addresses() {
+        let key_pair = KeyPair::generate_default_csprng();
+        let mut record = Record::new(42, addresses()).sign(&key_pair.secret_key);
+
+        record.record.addresses.swap(0, 1);
+        assert!(!record.verify(&key_pair.public_key));
+
+        // Addresses can't be passed off as unsigned either.
+        record.addresses_signature = None;
+        assert!(!record.verify(&key_pair.public_key));
     }
 }
@@ -0,0 +1,511 @@
+//! Gossip fallback for Handel level updates.
+//!
+//! Handel sends level updates directly to the peers of each level, which requires connections to
+//! most of the other validators. With large validator sets this is not always possible. Whenever a
+//! direct send fails, the update is published on a gossipsub topic instead. All validators
+//! subscribe to these topics and feed the received updates into their running aggregations.
+//!
+//! There is one topic per aggregation type, the aggregation instance is identified by the tag of
+//! the level update. Gossipsub only forwards an update once its signature was verified against the
+//! validators of its epoch and if it adds weight to what was verified for its instance and level
+//! before. Duplicates and updates without new contributors are ignored, forged updates are
+//! rejected.
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::io::Write;
+use std::sync::Arc;
+
+use futures::future::{self, BoxFuture, FutureExt};
+use futures::stream::{BoxStream, StreamExt};
+use linked_hash_map::LinkedHashMap;
+use parking_lot::Mutex;
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::BroadcastStream;
+
+use beserial::{Deserialize, Serialize};
+use block::{Message, TendermintIdentifier, ViewChange};
+use blockchain::BlockchainLock;
+use collections::BitSet;
+use handel::contribution::AggregatableContribution;
+use handel::update::LevelUpdateMessage;
+use handel::verifier::{VerificationResult, Verifier};
+use hash::{Blake2bHash, Blake2bHasher, Hasher};
+use network_interface::network::{MsgAcceptance, OverflowPolicy, Topic};
+use primitives::policy;
+use validator_network::ValidatorNetwork;
+
+use super::registry::ValidatorRegistry;
+use super::tendermint::{TendermintContribution, TendermintVerifier};
+use super::verifier::MultithreadedVerifier;
+use super::view_change::SignedViewChangeMessage;
+
+/// The topic for view change level updates.
+pub struct ViewChangeUpdateTopic;
+
+impl Topic for ViewChangeUpdateTopic {
+    type Item = LevelUpdateMessage<SignedViewChangeMessage, ViewChange>;
+
+    const BUFFER_SIZE: usize = 256;
+    const NAME: &'static str = "handel-view-change";
+    const VALIDATE: bool = true;
+    /// Newer level updates supersede the older ones of the same level.
+    const OVERFLOW_POLICY: OverflowPolicy = OverflowPolicy::DropOld;
+    const PERSIST_SEEN: bool = true;
+}
+
+/// The topic for Tendermint prevote and precommit level updates.
+pub struct TendermintUpdateTopic;
+
+impl Topic for TendermintUpdateTopic {
+    type Item = LevelUpdateMessage<TendermintContribution, TendermintIdentifier>;
+
+    const BUFFER_SIZE: usize = 256;
+    const NAME: &'static str = "handel-tendermint";
+    const VALIDATE: bool = true;
+    /// Newer level updates supersede the older ones of the same level.
+    const OVERFLOW_POLICY: OverflowPolicy = OverflowPolicy::DropOld;
+    const PERSIST_SEEN: bool = true;
+}
+
+/// Verifies the level updates received via gossip, so that only valid updates are relayed.
+pub trait UpdateVerifier<M>: Send + Sync {
+    /// Verifies the aggregate signature of the update. Returns `None` if the validators of its
+    /// aggregation instance are unknown.
+    fn verify<'a>(&'a self, message: &'a M) -> BoxFuture<'a, Option<VerificationResult>>;
+}
+
+/// Verifies level updates against the validators of the epoch their block belongs to.
+pub struct EpochUpdateVerifier {
+    blockchain: Arc<BlockchainLock>,
+    /// The registry of the epoch that was verified against last. Level updates almost always
+    /// belong to the current epoch.
+    registry: Mutex<Option<(u32, Arc<ValidatorRegistry>)>>,
+}
+
+impl EpochUpdateVerifier {
+    pub fn new(blockchain: Arc<BlockchainLock>) -> Self {
+        Self {
+            blockchain,
+            registry: Mutex::new(None),
+        }
+    }
+
+    fn registry(&self, block_number: u32) -> Option<Arc<ValidatorRegistry>> {
+        let epoch = policy::epoch_at(block_number);
+
+        let mut registry = self.registry.lock();
+        match &*registry {
+            Some((registry_epoch, registry)) if *registry_epoch == epoch => {
+                Some(Arc::clone(registry))
+            }
+            _ => {
+                let validators = self
+                    .blockchain
+                    .read()
+                    .get_validators_for_epoch(epoch, None)?;
+                let epoch_registry = Arc::new(ValidatorRegistry::new(validators));
+                *registry = Some((epoch, Arc::clone(&epoch_registry)));
+                Some(epoch_registry)
+            }
+        }
+    }
+}
+
+impl UpdateVerifier<LevelUpdateMessage<SignedViewChangeMessage, ViewChange>>
+    for EpochUpdateVerifier
+{
+    fn verify<'a>(
+        &'a self,
+        message: &'a LevelUpdateMessage<SignedViewChangeMessage, ViewChange>,
+    ) -> BoxFuture<'a, Option<VerificationResult>> {
+        async move {
+            let registry = self.registry(message.tag.block_number)?;
+            let verifier = MultithreadedVerifier::new(message.tag.hash_with_prefix(), registry);
+            Some(verifier.verify(&message.update.aggregate).await)
+        }
+        .boxed()
+    }
+}
+
+impl UpdateVerifier<LevelUpdateMessage<TendermintContribution, TendermintIdentifier>>
+    for EpochUpdateVerifier
+{
+    fn verify<'a>(
+        &'a self,
+        message: &'a LevelUpdateMessage<TendermintContribution, TendermintIdentifier>,
+    ) -> BoxFuture<'a, Option<VerificationResult>> {
+        async move {
+            let registry = self.registry(message.tag.block_number)?;
+            let verifier = TendermintVerifier::new(registry, message.tag.clone());
+            Some(verifier.verify(&message.update.aggregate).await)
+        }
+        .boxed()
+    }
+}
+
+/// Keeps track of the verified contributors for each aggregation instance and level, and of the
+/// signatures that were checked already.
+#[derive(Default)]
+struct GossipFilter {
+    /// Maps the serialized tag of an aggregation instance to the verified contributors per level.
+    instances: LinkedHashMap<Vec<u8>, HashMap<usize, BitSet>>,
+    /// The hashes of the signatures that were verified already, valid or not.
+    signatures: LinkedHashMap<Blake2bHash, ()>,
+}
+
+impl GossipFilter {
+    /// Maximum number of aggregation instances to keep track of. The oldest instance is dropped
+    /// first, as those aggregations are most likely finished already.
+    const MAX_INSTANCES: usize = 64;
+    /// Maximum number of signatures to remember.
+    const MAX_SIGNATURES: usize = 4096;
+
+    /// Identifies the signature of an update within its aggregation instance and level.
+    fn signature_key<C, T>(message: &LevelUpdateMessage<C, T>) -> Blake2bHash
+    where
+        C: AggregatableContribution,
+        T: Clone + Debug + Serialize + Deserialize + Send + Unpin,
+    {
+        let mut hasher = Blake2bHasher::default();
+        hasher.write_all(&message.tag.serialize_to_vec()).unwrap();
+        hasher.write_all(&[message.update.level() as u8]).unwrap();
+        hasher
+            .write_all(&message.update.aggregate.serialize_to_vec())
+            .unwrap();
+        hasher.finish()
+    }
+
+    /// Returns true if the signature of the update wasn't checked before and the update might add
+    /// weight to what was verified for its instance and level.
+    fn is_new<C, T>(&self, key: &Blake2bHash, message: &LevelUpdateMessage<C, T>) -> bool
+    where
+        C: AggregatableContribution,
+        T: Clone + Debug + Serialize + Deserialize + Send + Unpin,
+    {
+        if self.signatures.contains_key(key) {
+            return false;
+        }
+
+        let contributors = message.update.aggregate.contributors();
+        let known = self
+            .instances
+            .get(&message.tag.serialize_to_vec())
+            .and_then(|levels| levels.get(&message.update.level()));
+        !contributors.is_empty() && known.map_or(true, |known| !contributors.is_subset(known))
+    }
+
+    /// Remembers that the signature with the given key was checked.
+    fn insert_signature(&mut self, key: Blake2bHash) {
+        if self.signatures.len() >= Self::MAX_SIGNATURES {
+            self.signatures.pop_front();
+        }
+        self.signatures.insert(key, ());
+    }
+
+    /// Records the contributors of a verified update. Returns true if the update adds weight to
+    /// what was verified for its instance and level before. Every slot has the same weight, so
+    /// this is the case if the update contains at least one new contributor.
+    fn insert<C, T>(&mut self, key: Blake2bHash, message: &LevelUpdateMessage<C, T>) -> bool
+    where
+        C: AggregatableContribution,
+        T: Clone + Debug + Serialize + Deserialize + Send + Unpin,
+    {
+        self.insert_signature(key);
+
+        let tag = message.tag.serialize_to_vec();
+        if !self.instances.contains_key(&tag) && self.instances.len() >= Self::MAX_INSTANCES {
+            self.instances.pop_front();
+        }
+
+        let known = self
+            .instances
+            .entry(tag)
+            .or_insert_with(HashMap::new)
+            .entry(message.update.level())
+            .or_insert_with(BitSet::new);
+
+        let contributors = message.update.aggregate.contributors();
+        if contributors.is_empty() || contributors.is_subset(known) {
+            return false;
+        }
+
+        *known |= contributors;
+        true
+    }
+}
+
+/// Decides whether a level update received via gossip is relayed. The signature is verified
+/// before the update is recorded, so that forged updates can't pre-empt the real contributions of
+/// the validators they claim.
+async fn check_update<C, T>(
+    filter: &Mutex<GossipFilter>,
+    verifier: &dyn UpdateVerifier<LevelUpdateMessage<C, T>>,
+    message: &LevelUpdateMessage<C, T>,
+) -> MsgAcceptance
+where
+    C: AggregatableContribution,
+    T: Clone + Debug + Serialize + Deserialize + Send + Unpin,
+{
+    let key = GossipFilter::signature_key(message);
+    if !filter.lock().is_new(&key, message) {
+        return MsgAcceptance::Ignore;
+    }
+
+    match verifier.verify(message).await {
+        Some(VerificationResult::Ok) => {}
+        Some(result) => {
+            debug!("Rejecting level update for {:?}: {:?}", message.tag, result);
+            filter.lock().insert_signature(key);
+            return MsgAcceptance::Reject;
+        }
+        // We don't know the validators of this instance (yet), so we can't tell if it is valid.
+        None => return MsgAcceptance::Ignore,
+    }
+
+    if filter.lock().insert(key, message) {
+        MsgAcceptance::Accept
+    } else {
+        MsgAcceptance::Ignore
+    }
+}
+
+/// Fallback for level updates that could not be delivered to a peer directly.
+pub trait GossipFallback<M>: Send + Sync {
+    fn publish(self: Arc<Self>, message: M) -> BoxFuture<'static, ()>;
+}
+
+/// The gossip channel for the level updates of one aggregation type.
+///
+/// Subscribes to the topic on creation and hands out the updates that passed the filter to all
+/// aggregations of this type.
+pub struct LevelUpdateGossip<N: ValidatorNetwork, TTopic: Topic> {
+    network: Arc<N>,
+    verifier: Arc<dyn UpdateVerifier<TTopic::Item>>,
+    filter: Mutex<GossipFilter>,
+    sender: broadcast::Sender<TTopic::Item>,
+}
+
+impl<N, TTopic, C, T> LevelUpdateGossip<N, TTopic>
+where
+    N: ValidatorNetwork + 'static,
+    TTopic: Topic<Item = LevelUpdateMessage<C, T>> + Send + Sync + 'static,
+    C: AggregatableContribution + 'static,
+    T: Clone + Debug + Serialize + Deserialize + Send + Sync + Unpin + 'static,
+{
+    pub fn new(network: Arc<N>, verifier: Arc<dyn UpdateVerifier<TTopic::Item>>) -> Arc<Self> {
+        let (sender, _) = broadcast::channel(TTopic::BUFFER_SIZE);
+        let this = Arc::new(Self {
+            network: Arc::clone(&network),
+            verifier,
+            filter: Mutex::new(GossipFilter::default()),
+            sender,
+        });
+
+        let weak = Arc::downgrade(&this);
+        tokio::spawn(async move {
+            let mut stream = match network.subscribe::<TTopic>().await {
+                Ok(stream) => stream,
+                Err(err) => {
+                    error!("Failed to subscribe to {}: {:?}", TTopic::NAME, err);
+                    return;
+                }
+            };
+
+            while let Some((message, pubsub_id)) = stream.next().await {
+                match weak.upgrade() {
+                    Some(this) => {
+                        let acceptance = this.on_message(message).await;
+                        this.network
+                            .validate_message::<TTopic>(pubsub_id, acceptance);
+                    }
+                    None => break,
+                }
+            }
+        });
+
+        this
+    }
+
+    async fn on_message(&self, message: LevelUpdateMessage<C, T>) -> MsgAcceptance {
+        let acceptance = check_update(&self.filter, &*self.verifier, &message).await;
+        if matches!(acceptance, MsgAcceptance::Accept) {
+            // There might be no running aggregation which is interested in this update,
+            // in which case sending fails. It is still relayed to the other validators.
+            let _ = self.sender.send(message);
+        }
+        acceptance
+    }
+
+    /// Returns a stream of all level updates received via gossip.
+    pub fn receive(&self) -> BoxStream<'static, LevelUpdateMessage<C, T>> {
+        BroadcastStream::new(self.sender.subscribe())
+            .filter_map(|result| future::ready(result.ok()))
+            .boxed()
+    }
+
+    /// Publishes the update, unless it does not add weight to what was already seen. Our own
+    /// aggregations only contain verified contributions, so the update isn't verified again.
+    pub async fn publish_update(&self, message: LevelUpdateMessage<C, T>) {
+        let key = GossipFilter::signature_key(&message);
+        if !self.filter.lock().insert(key, &message) {
+            return;
+        }
+
+        if let Err(err) = self.network.publish::<TTopic>(message).await {
+            debug!(
+                "Failed to publish level update to {}: {:?}",
+                TTopic::NAME,
+                err
+            );
+        }
+    }
+}
+
+impl<N, TTopic, C, T> GossipFallback<LevelUpdateMessage<C, T>> for LevelUpdateGossip<N, TTopic>
+where
+    N: ValidatorNetwork + 'static,
+    TTopic: Topic<Item = LevelUpdateMessage<C, T>> + Send + Sync + 'static,
+    C: AggregatableContribution + 'static,
+    T: Clone + Debug + Serialize + Deserialize + Send + Sync + Unpin + 'static,
+{
+    fn publish(self: Arc<Self>, message: LevelUpdateMessage<C, T>) -> BoxFuture<'static, ()> {
+        async move { self.publish_update(message).await }.boxed()
+    }
+}
+
+/// The gossip channels of all aggregation types.
+pub struct AggregationGossip<N: ValidatorNetwork> {
+    pub view_changes: Arc<LevelUpdateGossip<N, ViewChangeUpdateTopic>>,
+    pub tendermint: Arc<LevelUpdateGossip<N, TendermintUpdateTopic>>,
+}
+
+impl<N: ValidatorNetwork + 'static> AggregationGossip<N> {
+    pub fn new(network: Arc<N>, blockchain: Arc<BlockchainLock>) -> Self {
+        let verifier = Arc::new(EpochUpdateVerifier::new(blockchain));
+        Self {
+            view_changes: LevelUpdateGossip::new(Arc::clone(&network), verifier.clone()),
+            tendermint: LevelUpdateGossip::new(network, verifier),
+        }
+    }
+}
+
+impl<N: ValidatorNetwork> Clone for AggregationGossip<N> {
+    fn clone(&self) -> Self {
+        Self {
+            view_changes: Arc::clone(&self.view_changes),
+            tendermint: Arc::clone(&self.tendermint),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use block::MultiSignature;
+    use bls::AggregateSignature;
+    use handel::update::LevelUpdate;
+    use vrf::VrfSeed;
+
+    use super::*;
+
+    type ViewChangeUpdate = LevelUpdateMessage<SignedViewChangeMessage, ViewChange>;
+
+    /// Treats updates that carry a previous proof as forged and updates for block 0 as belonging
+    /// to an unknown epoch. Counts the verifications.
+    #[derive(Default)]
+    struct TestVerifier {
+        verifications: AtomicUsize,
+    }
+
+    impl UpdateVerifier<ViewChangeUpdate> for TestVerifier {
+        fn verify<'a>(
+            &'a self,
+            message: &'a ViewChangeUpdate,
+        ) -> BoxFuture<'a, Option<VerificationResult>> {
+            self.verifications.fetch_add(1, Ordering::SeqCst);
+            let result = if message.tag.block_number == 0 {
+                None
+            } else if message.update.aggregate.previous_proof.is_some() {
+                Some(VerificationResult::Forged)
+            } else {
+                Some(VerificationResult::Ok)
+            };
+            future::ready(result).boxed()
+        }
+    }
+
+    impl TestVerifier {
+        fn verifications(&self) -> usize {
+            self.verifications.load(Ordering::SeqCst)
+        }
+    }
+
+    fn update(block_number: u32, contributors: &[usize], forged: bool) -> ViewChangeUpdate {
+        let mut signers = BitSet::new();
+        for contributor in contributors {
+            signers.insert(*contributor);
+        }
+        let signature = MultiSignature::new(AggregateSignature::new(), signers);
+
+        let aggregate = SignedViewChangeMessage {
+            view_change: signature.clone(),
+            previous_proof: if forged { Some(signature) } else { None },
+        };
+        LevelUpdate::new(aggregate, None, 1, 0).with_tag(ViewChange {
+            block_number,
+            new_view_number: 1,
+            vrf_entropy: VrfSeed::default().entropy(),
+        })
+    }
+
+    #[tokio::test]
+    async fn forged_updates_do_not_censor_valid_ones() {
+        let filter = Mutex::new(GossipFilter::default());
+        let verifier = TestVerifier::default();
+
+        let acceptance = check_update(&filter, &verifier, &update(1, &[1, 2], true)).await;
+        assert!(matches!(acceptance, MsgAcceptance::Reject));
+
+        // The forged update claimed the same contributors, the valid one is still relayed.
+        let acceptance = check_update(&filter, &verifier, &update(1, &[1, 2], false)).await;
+        assert!(matches!(acceptance, MsgAcceptance::Accept));
+    }
+
+    #[tokio::test]
+    async fn it_verifies_each_signature_once() {
+        let filter = Mutex::new(GossipFilter::default());
+        let verifier = TestVerifier::default();
+
+        check_update(&filter, &verifier, &update(1, &[1, 2], true)).await;
+        let acceptance = check_update(&filter, &verifier, &update(1, &[1, 2], true)).await;
+        assert!(matches!(acceptance, MsgAcceptance::Ignore));
+        assert_eq!(verifier.verifications(), 1);
+
+        check_update(&filter, &verifier, &update(1, &[1, 2], false)).await;
+        assert_eq!(verifier.verifications(), 2);
+
+        // Updates without new verified contributors are ignored without verifying them.
+        let acceptance = check_update(&filter, &verifier, &update(1, &[1], false)).await;
+        assert!(matches!(acceptance, MsgAcceptance::Ignore));
+        assert_eq!(verifier.verifications(), 2);
+
+        // Other aggregation instances are tracked separately.
+        let acceptance = check_update(&filter, &verifier, &update(2, &[1], false)).await;
+        assert!(matches!(acceptance, MsgAcceptance::Accept));
+    }
+
+    #[tokio::test]
+    async fn updates_of_unknown_epochs_are_not_relayed() {
+        let filter = Mutex::new(GossipFilter::default());
+        let verifier = TestVerifier::default();
+
+        let acceptance = check_update(&filter, &verifier, &update(0, &[1], false)).await;
+        assert!(matches!(acceptance, MsgAcceptance::Ignore));
+
+        // The update is checked again once the epoch might be known.
+        check_update(&filter, &verifier, &update(0, &[1], false)).await;
+        assert_eq!(verifier.verifications(), 2);
+    }
+}
@@ -1,3 +1,4 @@
+pub mod gossip;
 pub mod network_sink;
 mod registry;
 pub mod tendermint;
@@ -5,3 +6,53 @@ pub mod tendermint;
 /// the Handel protocol. The Handel protocol itself is implemented in the nimiq-handel crate.
 mod verifier;
 pub mod view_change;
+
+use std::fmt::Debug;
+
+use futures::future;
+use futures::stream::{BoxStream, StreamExt};
+
+use beserial::{Deserialize, Serialize};
+use block::{TendermintIdentifier, ViewChange};
+use handel::contribution::AggregatableContribution;
+use handel::update::LevelUpdateMessage;
+use nimiq_validator_network::{SignedEnvelope, ValidatorNetwork};
+
+use self::tendermint::TendermintContribution;
+use self::view_change::{SignedViewChangeMessage, ViewChangeProofMessage};
+
+network_interface::register_messages!(pub MESSAGES = [
+    LevelUpdateMessage<SignedViewChangeMessage, ViewChange>,
+    ViewChangeProofMessage,
+    LevelUpdateMessage<TendermintContribution, TendermintIdentifier>,
+    SignedEnvelope<LevelUpdateMessage<SignedViewChangeMessage, ViewChange>>,
+    SignedEnvelope<ViewChangeProofMessage>,
+    SignedEnvelope<LevelUpdateMessage<TendermintContribution, TendermintIdentifier>>,
+]);
+
+/// Receives the level updates that other validators send to us directly. Updates that claim to
+/// originate from another validator than the one that sent them are dropped.
+pub(crate) fn receive_level_updates<N, C, T>(
+    network: &N,
+) -> BoxStream<'static, LevelUpdateMessage<C, T>>
+where
+    N: ValidatorNetwork,
+    C: AggregatableContribution + 'static,
+    T: Clone + Debug + Serialize + Deserialize + Send + Sync + Unpin + 'static,
+{
+    network
+        .receive_from_validators::<LevelUpdateMessage<C, T>>()
+        .filter_map(|(message, validator_id)| {
+            future::ready(if message.update.origin() == validator_id {
+                Some(message)
+            } else {
+                debug!(
+                    "Dropping level update of validator {} sent by validator {}",
+                    message.update.origin(),
+                    validator_id
+                );
+                None
+            })
+        })
+        .boxed()
+}
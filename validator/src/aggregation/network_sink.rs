@@ -1,37 +1,100 @@
+use std::collections::HashMap;
 use std::marker::PhantomData;
 use std::pin::Pin;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use futures::future::{BoxFuture, FutureExt};
 use futures::sink::Sink;
+use futures::stream::{FuturesUnordered, StreamExt};
 use futures::task::{Context, Poll};
+use parking_lot::Mutex;
 
 use nimiq_network_interface::message::Message;
-use nimiq_validator_network::ValidatorNetwork;
+use nimiq_validator_network::{broadcast, BroadcastConfig, SendOutcome, ValidatorNetwork};
 
-// TODO:
-// * future per peer.
-// * one message to multiple peers
+use super::gossip::GossipFallback;
 
-struct SendingFuture<N: ValidatorNetwork> {
+/// Validators whose peer ID could not be resolved, with the time it was last tried.
+///
+/// Resolving a peer ID requires a DHT lookup, which is unlikely to succeed again right away. If
+/// there is a gossip fallback, messages to these validators are gossiped directly for a while.
+#[derive(Default)]
+struct UnresolvedValidators(Mutex<HashMap<usize, Instant>>);
+
+impl UnresolvedValidators {
+    /// Time after which sending directly to an unresolved validator is tried again.
+    const RETRY_AFTER: Duration = Duration::from_secs(10);
+
+    fn contains(&self, validator_id: usize) -> bool {
+        let mut validators = self.0.lock();
+        match validators.get(&validator_id) {
+            Some(since) if since.elapsed() < Self::RETRY_AFTER => true,
+            Some(_) => {
+                validators.remove(&validator_id);
+                false
+            }
+            None => false,
+        }
+    }
+
+    fn insert(&self, validator_id: usize) {
+        self.0.lock().insert(validator_id, Instant::now());
+    }
+}
+
+struct SendingFuture<M, N: ValidatorNetwork> {
     network: Arc<N>,
+    gossip: Option<Arc<dyn GossipFallback<M>>>,
+    config: BroadcastConfig,
+    unresolved: Arc<UnresolvedValidators>,
 }
 
-impl<N: ValidatorNetwork> SendingFuture<N> {
-    pub async fn send<M: Message + Clone + Unpin + std::fmt::Debug>(self, msg: (M, usize)) {
-        let result = self.network.send_to(&[msg.1], msg.0).await;
-        if let Some(Err(err)) = result.get(0) {
-            debug!("Sending msg to validator #{} failed: {:?}", &msg.1, err);
+impl<M: Message + Clone + Unpin + std::fmt::Debug, N: ValidatorNetwork> SendingFuture<M, N> {
+    pub async fn send(self, msg: (M, usize)) {
+        let (msg, validator_id) = msg;
+
+        if let Some(gossip) = &self.gossip {
+            if self.unresolved.contains(validator_id) {
+                gossip.publish(msg).await;
+                return;
+            }
+        }
+
+        let result = broadcast(&*self.network, &[validator_id], msg.clone(), &self.config).await;
+        if let Some(send_result) = result.failed.first() {
+            debug!(
+                "Sending msg to validator #{} failed: {:?}",
+                validator_id, send_result.outcome
+            );
+            if let SendOutcome::NotResolved(_) = send_result.outcome {
+                self.unresolved.insert(validator_id);
+            }
+
+            // The validator could not be reached directly, relay the message over gossip instead.
+            if let Some(gossip) = self.gossip {
+                gossip.publish(msg).await;
+            }
         }
     }
 }
 
 /// Implementation of a simple Sink Wrapper for the NetworkInterface's Network trait
+///
+/// Up to `fanout` messages of the [`BroadcastConfig`] are sent concurrently, so that a message
+/// which is retried doesn't hold back the messages after it.
 pub struct NetworkSink<M: Message + Unpin, N: ValidatorNetwork> {
     /// The network this sink is sending its messages over
     network: Arc<N>,
-    /// The currently executed future of sending an item.
-    current_future: Option<BoxFuture<'static, ()>>,
+    /// The messages that are currently being sent.
+    sending: FuturesUnordered<BoxFuture<'static, ()>>,
+    /// Used to relay messages which could not be sent directly.
+    gossip: Option<Arc<dyn GossipFallback<M>>>,
+    /// How many messages are sent concurrently and how often messages which could not be sent
+    /// directly are retried.
+    config: BroadcastConfig,
+    /// Validators that are currently only reached via gossip.
+    unresolved: Arc<UnresolvedValidators>,
 
     phantom: PhantomData<M>,
 }
@@ -40,10 +103,37 @@ impl<M: Message + Unpin, N: ValidatorNetwork> NetworkSink<M, N> {
     pub fn new(network: Arc<N>) -> Self {
         Self {
             network,
-            current_future: None,
+            sending: FuturesUnordered::new(),
+            gossip: None,
+            config: BroadcastConfig {
+                max_retries: 1,
+                retry_delay: Duration::from_millis(50),
+                ..Default::default()
+            },
+            unresolved: Default::default(),
             phantom: PhantomData,
         }
     }
+
+    /// Sets how many messages are sent concurrently and how messages which could not be sent
+    /// directly are retried before falling back to gossip. By default, they are retried once after
+    /// a short delay.
+    pub fn with_broadcast_config(mut self, config: BroadcastConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Publishes messages which could not be sent directly using the given gossip fallback.
+    pub fn with_gossip(mut self, gossip: Arc<dyn GossipFallback<M>>) -> Self {
+        self.gossip = Some(gossip);
+        self
+    }
+
+    /// Polls the messages that are being sent until all of them completed or all remaining ones
+    /// are pending.
+    fn poll_sending(&mut self, cx: &mut Context<'_>) {
+        while let Poll::Ready(Some(())) = self.sending.poll_next_unpin(cx) {}
+    }
 }
 
 impl<M: Message + Clone + Unpin + std::fmt::Debug, N: ValidatorNetwork + 'static> Sink<(M, usize)>
@@ -51,48 +141,120 @@ impl<M: Message + Clone + Unpin + std::fmt::Debug, N: ValidatorNetwork + 'static
 {
     type Error = ();
 
-    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        // As this Sink only bufferes a single message poll_ready is the same as poll_flush
-        self.poll_flush(cx)
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        // A new message can be accepted as long as fewer than `fanout` messages are being sent.
+        self.poll_sending(cx);
+        if self.sending.len() < self.config.fanout.max(1) {
+            Poll::Ready(Ok(()))
+        } else {
+            Poll::Pending
+        }
     }
 
     fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        // As this Sink only bufferes a single message poll_close is the same as poll_flush
+        // Nothing needs to be closed, only the messages being sent need to be completed.
         self.poll_flush(cx)
     }
 
     fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        // If there is a future being processed
-        if let Some(mut fut) = self.current_future.take() {
-            // Poll it to check its state
-            if fut.as_mut().poll(cx).is_pending() {
-                // If it is still being processed reset self.current_future and return Pending as no new item can be accepted (and the buffer is occupied).
-                self.current_future = Some(fut);
-                Poll::Pending
-            } else {
-                // If it has completed a new item can be accepted (and the buffer is also empty).
-                Poll::Ready(Ok(()))
-            }
-        } else {
-            // when there is no future the buffer is empty and a new item can be accepted.
+        self.poll_sending(cx);
+        if self.sending.is_empty() {
             Poll::Ready(Ok(()))
+        } else {
+            Poll::Pending
         }
     }
 
     fn start_send(mut self: Pin<&mut Self>, item: (M, usize)) -> Result<(), Self::Error> {
-        // If there is future poll_ready didnot return Ready(Ok(())) or poll_ready was not called resulting in an error
-        if self.current_future.is_some() {
-            Err(())
-        } else {
-            // Otherwise, create the future and store it.
-            // Note: This future does not get polled. Only once poll_* is called it will actually be polled.
-            let fut = (SendingFuture {
-                network: self.network.clone(),
+        // If poll_ready didn't return Ready(Ok(())) or wasn't called, there is no room for the
+        // message.
+        if self.sending.len() >= self.config.fanout.max(1) {
+            return Err(());
+        }
+
+        // Note: This future does not get polled. Only once poll_* is called it will actually be
+        // polled.
+        let fut = (SendingFuture {
+            network: self.network.clone(),
+            gossip: self.gossip.clone(),
+            config: self.config.clone(),
+            unresolved: Arc::clone(&self.unresolved),
+        })
+        .send(item)
+        .boxed();
+        self.sending.push(fut);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use futures::future;
+    use futures::SinkExt;
+
+    use nimiq_network_mock::MockHub;
+    use nimiq_validator_network::network_impl::ValidatorNetworkImpl;
+
+    use super::*;
+
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    struct TestMessage(u32);
+
+    impl Message for TestMessage {
+        const TYPE_ID: u64 = 4242;
+    }
+
+    #[derive(Default)]
+    struct CountingGossip(AtomicUsize);
+
+    impl GossipFallback<TestMessage> for CountingGossip {
+        fn publish(self: Arc<Self>, _message: TestMessage) -> BoxFuture<'static, ()> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            future::ready(()).boxed()
+        }
+    }
+
+    /// Sends a message to each of four validators that can't be resolved, so every message is
+    /// retried once before it is gossiped. Returns how long that took.
+    async fn send_unresolvable(fanout: usize) -> Duration {
+        let hub = MockHub::default();
+        let network = Arc::new(ValidatorNetworkImpl::new(Arc::new(hub.new_network())));
+        let gossip = Arc::new(CountingGossip::default());
+        let mut sink = NetworkSink::new(network)
+            .with_broadcast_config(BroadcastConfig {
+                fanout,
+                max_retries: 1,
+                retry_delay: Duration::from_secs(10),
             })
-            .send(item)
-            .boxed();
-            self.current_future = Some(fut);
-            Ok(())
+            .with_gossip(Arc::clone(&gossip) as Arc<dyn GossipFallback<TestMessage>>);
+
+        let start = tokio::time::Instant::now();
+        for validator_id in 0..4 {
+            sink.feed((TestMessage(validator_id as u32), validator_id))
+                .await
+                .unwrap();
         }
+        sink.flush().await.unwrap();
+
+        assert_eq!(gossip.0.load(Ordering::SeqCst), 4);
+        start.elapsed()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retries_dont_hold_back_other_messages() {
+        let elapsed = send_unresolvable(4).await;
+        assert!(elapsed < Duration::from_secs(20), "took {:?}", elapsed);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn at_most_fanout_messages_are_sent_concurrently() {
+        let elapsed = send_unresolvable(1).await;
+        assert!(elapsed >= Duration::from_secs(40), "took {:?}", elapsed);
+
+        let elapsed = send_unresolvable(2).await;
+        assert!(elapsed >= Duration::from_secs(20), "took {:?}", elapsed);
+        assert!(elapsed < Duration::from_secs(30), "took {:?}", elapsed);
     }
 }
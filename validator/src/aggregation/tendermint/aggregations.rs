@@ -1,15 +1,12 @@
 use std::{
     collections::{btree_map::Entry, BTreeMap},
     pin::Pin,
-    sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc,
-    },
+    sync::Arc,
     task::{Poll, Waker},
 };
 
 use futures::{
-    future,
+    future::{AbortHandle, Abortable},
     stream::{BoxStream, SelectAll},
     Sink, Stream, StreamExt,
 };
@@ -35,6 +32,14 @@ use super::{
     utils::{AggregationDescriptor, AggregationEvent, TendermintAggregationEvent},
 };
 
+/// Maximum number of aggregations kept at the same time. Once exceeded, the aggregations of the
+/// lowest rounds are dropped. This bounds the memory used if the chain stalls and many rounds are
+/// started.
+const MAX_AGGREGATIONS: usize = 8;
+
+/// Maximum number of future rounds for which contributors are tracked.
+const MAX_FUTURE_ROUNDS: usize = 16;
+
 /// Maintains various aggregations for different rounds and steps of Tendermint.
 ///
 /// Note that `TendermintAggregations::broadcast_and_aggregate` needs to have been called at least once before the stream can meaningfully be awaited.
@@ -118,34 +123,29 @@ impl<N: ValidatorNetwork> TendermintAggregations<N> {
                 output_sink,
             );
 
-            // create the stream closer and wrap in Arc so it can be shared borrow
-            let stream_closer = Arc::new(AtomicBool::new(true));
+            // create the handle used to stop the aggregation
+            let (abort_handle, abort_registration) = AbortHandle::new_pair();
 
             // Create and store AggregationDescriptor
             entry.insert(AggregationDescriptor {
                 input: sender,
-                is_running: stream_closer.clone(),
+                abort_handle,
             });
 
-            let tmp_desc: Vec<((&u32, &TendermintStep), bool)> = self
-                .aggregation_descriptors
-                .iter()
-                .map(|((r, s), v)| ((r, s), v.is_running.load(Ordering::Relaxed)))
-                .collect();
-
-            trace!("Aggregation_descriptors: {:?}", &tmp_desc,);
-
             // copy round_number for use in drain_filter couple of lines down so that id can be moved into closure.
             let round_number = id.round_number;
 
             // wrap the aggregation stream
-            let aggregation = Box::pin(
-                aggregation
-                    .map(move |x| ((id.round_number, id.step), x))
-                    .take_while(move |_x| {
-                        future::ready(stream_closer.clone().load(Ordering::Relaxed))
-                        // Todo: Check Ordering
-                    }),
+            let aggregation = Box::pin(Abortable::new(
+                aggregation.map(move |x| ((id.round_number, id.step), x)),
+                abort_registration,
+            ));
+
+            drop_lowest_aggregations(&mut self.aggregation_descriptors);
+
+            trace!(
+                "Aggregation_descriptors: {:?}",
+                self.aggregation_descriptors.keys().collect::<Vec<_>>(),
             );
 
             // Since this instance of Aggregation now becomes the current aggregation all bitsets containing contributors
@@ -162,10 +162,11 @@ impl<N: ValidatorNetwork> TendermintAggregations<N> {
         }
     }
 
-    pub fn cancel_aggregation(&self, round: u32, step: TendermintStep) {
-        if let Some(descriptor) = self.aggregation_descriptors.get(&(round, step)) {
+    pub fn cancel_aggregation(&mut self, round: u32, step: TendermintStep) {
+        // Removing the descriptor also drops the input channel of the aggregation.
+        if let Some(descriptor) = self.aggregation_descriptors.remove(&(round, step)) {
             trace!("canceling aggregation for {}-{:?}", &round, &step);
-            descriptor.is_running.store(false, Ordering::Relaxed);
+            descriptor.abort_handle.abort();
         }
     }
 }
@@ -208,12 +209,11 @@ impl<N: ValidatorNetwork + 'static> Stream for TendermintAggregations<N> {
                     // Also note that PreVote and PreCommit are tracked in the same bitset as the protocol requires.
                     if highest_round < &message.tag.round_number {
                         trace!("New contribution for future round: {:?}", &message);
-                        let future_contributors = self
-                            .future_aggregations
-                            .entry(message.tag.round_number)
-                            .and_modify(|bitset| *bitset |= message.update.aggregate.contributors())
-                            .or_insert_with(|| message.update.aggregate.contributors())
-                            .clone();
+                        let future_contributors = track_future_contributors(
+                            &mut self.future_aggregations,
+                            message.tag.round_number,
+                            message.update.aggregate.contributors(),
+                        );
                         // now check if that suffices for a f+1 contributor weight
                         if let Some(weight) =
                             self.validator_registry.signers_weight(&future_contributors)
@@ -254,3 +254,122 @@ impl<N: ValidatorNetwork + 'static> Stream for TendermintAggregations<N> {
         }
     }
 }
+
+/// Drops the aggregations of the lowest rounds if there are more than `MAX_AGGREGATIONS`. Dropping
+/// the descriptor closes the input of the aggregation and aborting it terminates its stream.
+fn drop_lowest_aggregations(descriptors: &mut BTreeMap<RoundAndStep, AggregationDescriptor>) {
+    while descriptors.len() > MAX_AGGREGATIONS {
+        if let Some(((round, step), descriptor)) = descriptors.pop_first() {
+            debug!("Dropping aggregation for {}-{:?}", round, step);
+            descriptor.abort_handle.abort();
+        }
+    }
+}
+
+/// Adds the contributors of a future round and returns all contributors known for it. Only the
+/// nearest `MAX_FUTURE_ROUNDS` future rounds are tracked.
+fn track_future_contributors(
+    future_aggregations: &mut BTreeMap<u32, BitSet>,
+    round: u32,
+    contributors: BitSet,
+) -> BitSet {
+    let future_contributors = future_aggregations
+        .entry(round)
+        .and_modify(|bitset| *bitset |= contributors.clone())
+        .or_insert(contributors)
+        .clone();
+    while future_aggregations.len() > MAX_FUTURE_ROUNDS {
+        future_aggregations.pop_last();
+    }
+    future_contributors
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{
+        future::{self, Aborted},
+        FutureExt,
+    };
+
+    use super::*;
+
+    /// Creates the descriptor of an aggregation along with the aggregation task it controls and
+    /// the receiving end of its input.
+    fn aggregation() -> (
+        AggregationDescriptor,
+        Abortable<future::Pending<()>>,
+        mpsc::UnboundedReceiver<LevelUpdate<TendermintContribution>>,
+    ) {
+        let (abort_handle, abort_registration) = AbortHandle::new_pair();
+        let (input, receiver) = mpsc::unbounded_channel();
+        (
+            AggregationDescriptor {
+                abort_handle,
+                input,
+            },
+            Abortable::new(future::pending(), abort_registration),
+            receiver,
+        )
+    }
+
+    #[test]
+    fn it_drops_the_aggregations_of_the_lowest_rounds() {
+        let num_dropped = 3;
+        let mut descriptors = BTreeMap::new();
+        let mut aggregations = vec![];
+        for round in 0..(MAX_AGGREGATIONS + num_dropped) as u32 {
+            let (descriptor, task, input) = aggregation();
+            descriptors.insert((round, TendermintStep::PreVote), descriptor);
+            drop_lowest_aggregations(&mut descriptors);
+            aggregations.push((round, task, input));
+        }
+
+        assert_eq!(descriptors.len(), MAX_AGGREGATIONS);
+        assert_eq!(
+            descriptors.keys().next(),
+            Some(&(num_dropped as u32, TendermintStep::PreVote))
+        );
+
+        // The dropped aggregations are aborted and their inputs are closed, the others keep
+        // running.
+        for (round, task, mut input) in aggregations {
+            let dropped = round < num_dropped as u32;
+            assert_eq!(task.now_or_never() == Some(Err(Aborted)), dropped);
+            assert_eq!(input.recv().now_or_never() == Some(None), dropped);
+        }
+    }
+
+    #[test]
+    fn it_drops_the_furthest_future_rounds() {
+        let mut future_aggregations = BTreeMap::new();
+        for round in (1..=(MAX_FUTURE_ROUNDS + 4) as u32).rev() {
+            let mut contributors = BitSet::new();
+            contributors.insert(round as usize);
+            track_future_contributors(&mut future_aggregations, round, contributors);
+        }
+
+        // Only the nearest rounds are kept, even though they were added last.
+        assert_eq!(future_aggregations.len(), MAX_FUTURE_ROUNDS);
+        assert_eq!(
+            future_aggregations.keys().copied().collect::<Vec<_>>(),
+            (1..=MAX_FUTURE_ROUNDS as u32).collect::<Vec<_>>()
+        );
+
+        // The contributors of a round accumulate.
+        let mut contributors = BitSet::new();
+        contributors.insert(0);
+        let future_contributors =
+            track_future_contributors(&mut future_aggregations, 1, contributors);
+        assert!(future_contributors.contains(0));
+        assert!(future_contributors.contains(1));
+
+        // A round beyond the tracked ones is dropped right away.
+        track_future_contributors(
+            &mut future_aggregations,
+            MAX_FUTURE_ROUNDS as u32 + 10,
+            BitSet::new(),
+        );
+        assert_eq!(future_aggregations.len(), MAX_FUTURE_ROUNDS);
+        assert!(!future_aggregations.contains_key(&(MAX_FUTURE_ROUNDS as u32 + 10)));
+    }
+}
@@ -8,7 +8,7 @@ use nimiq_collections::bitset::BitSet;
 
 use nimiq_handel::contribution::{AggregatableContribution, ContributionError};
 
-#[derive(Serialize, Deserialize, std::fmt::Debug, Clone)]
+#[derive(Serialize, Deserialize, Schema, std::fmt::Debug, Clone)]
 pub struct TendermintContribution {
     #[beserial(len_type(u16))]
     pub contributions: BTreeMap<Option<Blake2sHash>, MultiSignature>,
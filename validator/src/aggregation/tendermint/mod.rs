@@ -7,4 +7,6 @@ mod tendermint;
 mod utils;
 mod verifier;
 
+pub(crate) use self::contribution::TendermintContribution;
+pub(crate) use self::verifier::TendermintVerifier;
 pub use self::tendermint::HandelTendermintAdapter;
@@ -16,7 +16,10 @@ use nimiq_tendermint::{AggregationResult, TendermintError};
 use nimiq_validator_network::ValidatorNetwork;
 
 use crate::aggregation::{
-    network_sink::NetworkSink, registry::ValidatorRegistry,
+    gossip::{LevelUpdateGossip, TendermintUpdateTopic},
+    network_sink::NetworkSink,
+    receive_level_updates,
+    registry::ValidatorRegistry,
     tendermint::aggregations::TendermintAggregations,
 };
 
@@ -37,6 +40,7 @@ pub struct HandelTendermintAdapter<N: ValidatorNetwork> {
     validator_slot_band: u16,
     validator_registry: Arc<ValidatorRegistry>,
     network: Arc<N>,
+    gossip: Arc<LevelUpdateGossip<N, TendermintUpdateTopic>>,
     event_sender: mpsc::Sender<AggregationEvent<N>>,
     background_task: Option<BackgroundTask<N>>,
 }
@@ -50,25 +54,32 @@ where
         active_validators: Validators,
         block_height: u32,
         network: Arc<N>,
+        gossip: Arc<LevelUpdateGossip<N, TendermintUpdateTopic>>,
         secret_key: SecretKey,
     ) -> Self {
-        // the input stream is all levelUpdateMessages concerning a TendermintContribution and TendermintIdentifier.
-        // We get rid of the sender, but while processing these messages they need to be dispatched to the appropriate Aggregation.
+        // the input stream is all levelUpdateMessages concerning a TendermintContribution and TendermintIdentifier,
+        // received either directly or via gossip.
+        // Direct updates are only accepted from the validator they originate from. While processing these messages they
+        // need to be dispatched to the appropriate Aggregation.
         let input = Box::pin(
-            network
-                .receive::<LevelUpdateMessage<TendermintContribution, TendermintIdentifier>>()
-                .filter_map(move |msg| {
-                    future::ready(if msg.0.tag.block_number == block_height {
-                        Some(msg.0)
-                    } else {
-                        log::debug!(
-                            "Received message for different block_height: msg.0.tag.block_number: {} - actual block_height: {}",
-                            msg.0.tag.block_number,
-                            block_height
-                        );
-                        None
-                    })
-                }),
+            futures::stream::select(
+                receive_level_updates::<_, TendermintContribution, TendermintIdentifier>(
+                    &*network,
+                ),
+                gossip.receive(),
+            )
+            .filter_map(move |msg| {
+                future::ready(if msg.tag.block_number == block_height {
+                    Some(msg)
+                } else {
+                    log::debug!(
+                        "Received message for different block_height: msg.tag.block_number: {} - actual block_height: {}",
+                        msg.tag.block_number,
+                        block_height
+                    );
+                    None
+                })
+            }),
         );
 
         let validator_registry = Arc::new(ValidatorRegistry::new(active_validators));
@@ -102,6 +113,7 @@ where
             validator_slot_band,
             validator_registry,
             network,
+            gossip,
             event_sender,
             background_task,
         }
@@ -170,10 +182,12 @@ where
             self.validator_registry.get_slots(self.validator_slot_band),
         );
 
-        let output_sink = Box::new(NetworkSink::<
-            LevelUpdateMessage<TendermintContribution, TendermintIdentifier>,
-            N,
-        >::new(self.network.clone()));
+        let output_sink = Box::new(
+            NetworkSink::<LevelUpdateMessage<TendermintContribution, TendermintIdentifier>, N>::new(
+                self.network.clone(),
+            )
+            .with_gossip(self.gossip.clone()),
+        );
 
         // Relay the AggregationEvent to TendermintAggregations
         self.event_sender
@@ -1,6 +1,4 @@
-use std::sync::atomic::AtomicBool;
-use std::sync::Arc;
-
+use futures::future::AbortHandle;
 use handel::update::LevelUpdateMessage;
 use hash::Blake2sHash;
 use nimiq_validator_network::ValidatorNetwork;
@@ -27,10 +25,9 @@ pub(super) struct CurrentAggregation {
 /// Struct to describe the different ongoing aggregations
 #[derive(std::fmt::Debug)]
 pub(super) struct AggregationDescriptor {
-    /// Atomic bool keeping track whether the aggregation should continue or not.
-    /// once set to false the aggregations next poll call will return Poll::Ready(None)
-    /// terminating this aggregation.
-    pub(super) is_running: Arc<AtomicBool>,
+    /// Handle to stop the aggregation. Once aborted the aggregation stream returns Poll::Ready(None),
+    /// terminating this aggregation and freeing its resources.
+    pub(super) abort_handle: AbortHandle,
     /// The sender used for LevelUpdateMessages for this aggregation
     pub(super) input: mpsc::UnboundedSender<LevelUpdate<TendermintContribution>>,
 }
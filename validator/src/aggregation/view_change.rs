@@ -2,11 +2,12 @@ use std::fmt;
 use std::pin::Pin;
 use std::sync::Arc;
 
-use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use futures::channel::mpsc::{channel, Receiver, Sender};
 use futures::ready;
 use futures::stream::{BoxStream, Stream, StreamExt};
 use futures::task::{Context, Poll};
-use parking_lot::RwLock;
+use linked_hash_map::LinkedHashMap;
+use parking_lot::{Mutex, RwLock};
 
 use beserial::{Deserialize, Serialize};
 use block::{Message, MultiSignature, SignedViewChange, ViewChange, ViewChangeProof};
@@ -22,17 +23,86 @@ use handel::protocol::Protocol;
 use handel::store::ReplaceStore;
 use handel::update::{LevelUpdate, LevelUpdateMessage};
 use hash::Blake2sHash;
+use network_interface::message::Message as NetworkMessage;
 use nimiq_validator_network::ValidatorNetwork;
 use primitives::policy;
 use primitives::slots::Validators;
+use utils::shared_rng::SharedRng;
 
+use super::gossip::{LevelUpdateGossip, ViewChangeUpdateTopic};
 use super::network_sink::NetworkSink;
+use super::receive_level_updates;
 use super::registry::ValidatorRegistry;
 use super::verifier::MultithreadedVerifier;
 
 enum ViewChangeResult {
     FutureViewChange(SignedViewChangeMessage, ViewChange),
     ViewChange(SignedViewChangeMessage),
+    /// The validator with the given ID is still aggregating a past view change.
+    PastViewChange(usize),
+    /// A completed view change was received from another validator.
+    CompletedViewChange(ViewChangeProofMessage),
+}
+
+/// A completed view change and its proof.
+///
+/// Validators that were offline during an aggregation keep sending level updates for a view change
+/// that was completed meanwhile. Instead of replaying the Handel exchange for them, they are sent
+/// the proof of the latest completed view change directly.
+#[derive(Clone, Debug, Deserialize, Serialize, Schema)]
+pub struct ViewChangeProofMessage {
+    pub view_change: ViewChange,
+    pub proof: ViewChangeProof,
+}
+
+impl NetworkMessage for ViewChangeProofMessage {
+    const TYPE_ID: u64 = 127;
+}
+
+/// The proofs of the latest completed view changes, one per block number.
+#[derive(Default)]
+pub struct ViewChangeProofCache {
+    proofs: Mutex<LinkedHashMap<u32, ViewChangeProofMessage>>,
+}
+
+impl ViewChangeProofCache {
+    /// Maximum number of block numbers to keep a proof for. The lowest block number is dropped
+    /// first, as its view changes are most likely persisted on chain already.
+    const MAX_ENTRIES: usize = 16;
+
+    /// Stores the proof, unless a proof for a higher view on the same block is known already.
+    pub fn insert(&self, view_change: ViewChange, proof: ViewChangeProof) {
+        let mut proofs = self.proofs.lock();
+
+        if let Some(known) = proofs.get(&view_change.block_number) {
+            if known.view_change.vrf_entropy == view_change.vrf_entropy
+                && known.view_change.new_view_number >= view_change.new_view_number
+            {
+                return;
+            }
+        }
+
+        proofs.insert(
+            view_change.block_number,
+            ViewChangeProofMessage { view_change, proof },
+        );
+        while proofs.len() > Self::MAX_ENTRIES {
+            proofs.pop_front();
+        }
+    }
+
+    /// Returns the proof of the latest completed view change which is at least as recent as the
+    /// given one.
+    pub fn get(&self, view_change: &ViewChange) -> Option<ViewChangeProofMessage> {
+        self.proofs
+            .lock()
+            .get(&view_change.block_number)
+            .filter(|known| {
+                known.view_change.vrf_entropy == view_change.vrf_entropy
+                    && known.view_change.new_view_number >= view_change.new_view_number
+            })
+            .cloned()
+    }
 }
 
 /// Switch for incoming ViewChanges.
@@ -40,24 +110,33 @@ enum ViewChangeResult {
 /// in case it recognizes it is behind.
 struct InputStreamSwitch {
     input: BoxStream<'static, LevelUpdateMessage<SignedViewChangeMessage, ViewChange>>,
-    sender: UnboundedSender<ViewChangeResult>,
+    sender: Sender<ViewChangeResult>,
     future_view_changes: BitSet,
+    /// The validators which were reported to be aggregating a past view change.
+    past_view_changes: BitSet,
     current_view_change: ViewChange,
     identity_registry: Arc<ValidatorRegistry>,
 }
 
 impl InputStreamSwitch {
+    /// Maximum number of future view changes waiting to be processed. Further future view
+    /// changes are dropped until there is room again, so a stalled chain can't pile them up.
+    const MAX_PENDING_FUTURE_VIEW_CHANGES: usize = 16;
+
     fn new(
         input: BoxStream<'static, LevelUpdateMessage<SignedViewChangeMessage, ViewChange>>,
         current_view_change: ViewChange,
         identity_registry: Arc<ValidatorRegistry>,
-    ) -> (Self, UnboundedReceiver<ViewChangeResult>) {
-        let (sender, receiver) = unbounded::<ViewChangeResult>();
+    ) -> (Self, Receiver<ViewChangeResult>) {
+        // The capacity of the channel is its buffer size plus one slot for the sender.
+        let (sender, receiver) =
+            channel::<ViewChangeResult>(Self::MAX_PENDING_FUTURE_VIEW_CHANGES - 1);
 
         let this = Self {
             input,
             sender,
             future_view_changes: BitSet::new(),
+            past_view_changes: BitSet::new(),
             current_view_change,
             identity_registry,
         };
@@ -82,11 +161,30 @@ impl Stream for InputStreamSwitch {
                 return Poll::Ready(Some(message.update));
             }
 
-            if message.tag.new_view_number > self.current_view_change.new_view_number {
+            // Messages for past view changes are superseded and dropped. Their sender is reported
+            // once, so it can be sent the proof it is missing.
+            if message.tag.new_view_number < self.current_view_change.new_view_number {
+                let origin = message.update.origin();
+                if !self.past_view_changes.contains(origin) {
+                    self.past_view_changes.insert(origin);
+                    let _ = self
+                        .sender
+                        .try_send(ViewChangeResult::PastViewChange(origin));
+                }
+                continue;
+            }
+
+            // Future view changes can only be acted upon if they carry a proof for the previous
+            // view change.
+            if message.update.aggregate.previous_proof.is_some() {
                 let result =
                     ViewChangeResult::FutureViewChange(message.update.aggregate, message.tag);
-                if let Err(err) = self.sender.unbounded_send(result) {
-                    error!("Failed to send FutureViewChange result: {:?}", err);
+                if let Err(err) = self.sender.try_send(result) {
+                    if err.is_full() {
+                        debug!("Too many pending future view changes, dropping one");
+                    } else {
+                        error!("Failed to send FutureViewChange result: {:?}", err);
+                    }
                 }
             }
         }
@@ -100,7 +198,7 @@ impl Stream for InputStreamSwitch {
 // TODO once actual state sync is implemented this can be removed again as it serves the same purpose.
 /// The ViewChangeMessage containing the current view change and an optional previous proof if applicable.
 /// Contains the actual information of block_height, new_view_number and prev_seed as tag.
-#[derive(Clone, Deserialize, Serialize, std::fmt::Debug)]
+#[derive(Clone, Deserialize, Serialize, Schema, std::fmt::Debug)]
 pub struct SignedViewChangeMessage {
     /// The currently aggregated view change.
     pub view_change: MultiSignature,
@@ -207,6 +305,12 @@ impl Protocol for ViewChangeAggregationProtocol {
 pub struct ViewChangeAggregation {}
 
 impl ViewChangeAggregation {
+    /// Aggregates the view change. Completed view changes are stored in `proofs`. If a proof for
+    /// this or a later view on the same block is known or received from another validator, it is
+    /// returned right away.
+    // Ignoring clippy warning because there wouldn't be much to be gained by refactoring this,
+    // except making clippy happy
+    #[allow(clippy::too_many_arguments)]
     pub async fn start<N: ValidatorNetwork + 'static>(
         mut view_change: ViewChange,
         mut previous_proof: Option<MultiSignature>,
@@ -215,6 +319,9 @@ impl ViewChangeAggregation {
         validator_id: u16,
         active_validators: Validators,
         network: Arc<N>,
+        gossip: Arc<LevelUpdateGossip<N, ViewChangeUpdateTopic>>,
+        proofs: Arc<ViewChangeProofCache>,
+        rng: SharedRng,
     ) -> (ViewChange, ViewChangeProof) {
         // TODO expose this somewehere else so we don't need to clone here.
         let weights = Arc::new(ValidatorRegistry::new(active_validators.clone()));
@@ -226,6 +333,14 @@ impl ViewChangeAggregation {
         trace!("Previous view_change proof: {:?}", &previous_proof);
 
         loop {
+            if let Some(completed) = proofs.get(&view_change) {
+                debug!(
+                    "View change {}.{} already completed",
+                    completed.view_change.block_number, completed.view_change.new_view_number,
+                );
+                return (completed.view_change, completed.proof);
+            }
+
             let message_hash = view_change.hash_with_prefix();
             trace!(
                 "message: {:?}, message_hash: {:?}",
@@ -264,12 +379,12 @@ impl ViewChangeAggregation {
                 message_hash,
             );
 
+            // Level updates are received directly from other validators as well as via gossip.
             let (input_switch, receiver) = InputStreamSwitch::new(
-                Box::pin(
-                    network
-                        .receive::<LevelUpdateMessage<SignedViewChangeMessage, ViewChange>>()
-                        .map(move |msg| msg.0),
-                ),
+                Box::pin(futures::stream::select(
+                    receive_level_updates::<_, SignedViewChangeMessage, ViewChange>(&*network),
+                    gossip.receive(),
+                )),
                 view_change.clone(),
                 weights.clone(),
             );
@@ -277,17 +392,28 @@ impl ViewChangeAggregation {
             let aggregation = Aggregation::new(
                 protocol,
                 view_change.clone(),
-                Config::default(),
+                Config {
+                    rng: rng.clone(),
+                    ..Config::default()
+                },
                 own_contribution,
                 Box::pin(input_switch),
-                Box::new(NetworkSink::<
-                    LevelUpdateMessage<SignedViewChangeMessage, ViewChange>,
-                    N,
-                >::new(network.clone())),
+                Box::new(
+                    NetworkSink::<LevelUpdateMessage<SignedViewChangeMessage, ViewChange>, N>::new(
+                        network.clone(),
+                    )
+                    .with_gossip(gossip.clone()),
+                ),
             );
 
-            let mut stream =
-                futures::stream::select(aggregation.map(ViewChangeResult::ViewChange), receiver);
+            let completed_view_changes = network
+                .receive::<ViewChangeProofMessage>()
+                .map(|msg| ViewChangeResult::CompletedViewChange(msg.0));
+
+            let mut stream = futures::stream::select(
+                futures::stream::select(aggregation.map(ViewChangeResult::ViewChange), receiver),
+                completed_view_changes,
+            );
             while let Some(msg) = stream.next().await {
                 match msg {
                     ViewChangeResult::FutureViewChange(vc, tag) => {
@@ -316,6 +442,9 @@ impl ViewChangeAggregation {
                             if aggregated_public_key
                                 .verify_hash(past_view_change.hash_with_prefix(), &sig.signature)
                             {
+                                proofs
+                                    .insert(past_view_change, ViewChangeProof { sig: sig.clone() });
+
                                 // set the proof and exit the while loop to create a new Aggregtion for the correct new view
                                 view_change = tag;
                                 previous_proof = Some(sig);
@@ -324,6 +453,50 @@ impl ViewChangeAggregation {
                         }
                         error!("Did not receive necessary past proof!");
                     }
+                    ViewChangeResult::PastViewChange(validator_id) => {
+                        // The proof for the previous view is known if this view change was started
+                        // after completing it.
+                        let completed = ViewChange {
+                            block_number: view_change.block_number,
+                            new_view_number: view_change.new_view_number - 1,
+                            vrf_entropy: view_change.vrf_entropy.clone(),
+                        };
+                        if let Some(completed) = proofs.get(&completed) {
+                            debug!(
+                                "Sending proof for view change {}.{} to validator {}",
+                                completed.view_change.block_number,
+                                completed.view_change.new_view_number,
+                                validator_id,
+                            );
+                            let network = Arc::clone(&network);
+                            tokio::spawn(async move {
+                                network.send_to(&[validator_id], completed).await;
+                            });
+                        }
+                    }
+                    ViewChangeResult::CompletedViewChange(completed) => {
+                        if completed.view_change.block_number != view_change.block_number
+                            || completed.view_change.vrf_entropy != view_change.vrf_entropy
+                            || completed.view_change.new_view_number < view_change.new_view_number
+                            || proofs.get(&completed.view_change).is_some()
+                        {
+                            continue;
+                        }
+
+                        if completed
+                            .proof
+                            .verify(&completed.view_change, &active_validators)
+                        {
+                            debug!(
+                                "Received proof for view change {}.{}",
+                                completed.view_change.block_number,
+                                completed.view_change.new_view_number,
+                            );
+                            proofs.insert(completed.view_change.clone(), completed.proof.clone());
+                            return (completed.view_change, completed.proof);
+                        }
+                        debug!("Received invalid view change proof");
+                    }
                     ViewChangeResult::ViewChange(vc) => {
                         if let Some(aggregate_weight) = weights.signature_weight(&vc.view_change) {
                             trace!(
@@ -340,6 +513,7 @@ impl ViewChangeAggregation {
                                     sig: vc.view_change,
                                 };
                                 trace!("View Change complete: {:?}", &view_change_proof);
+                                proofs.insert(view_change.clone(), view_change_proof.clone());
 
                                 // return the ViewChangeProof
                                 return (view_change, view_change_proof);
@@ -357,3 +531,107 @@ impl fmt::Debug for ViewChangeAggregationProtocol {
         write!(f, "ViewChangeAggregation {{ node_id: {} }}", self.node_id(),)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bls::AggregateSignature;
+    use vrf::{VrfEntropy, VrfSeed};
+
+    use super::*;
+
+    fn view_change(block_number: u32, new_view_number: u32) -> ViewChange {
+        ViewChange {
+            block_number,
+            new_view_number,
+            vrf_entropy: VrfSeed::default().entropy(),
+        }
+    }
+
+    /// A proof that can be told apart by its signers.
+    fn proof(signer: usize) -> ViewChangeProof {
+        let mut signers = BitSet::new();
+        signers.insert(signer);
+        ViewChangeProof {
+            sig: MultiSignature::new(AggregateSignature::new(), signers),
+        }
+    }
+
+    /// A level update for a view change that carries the proof of the previous one.
+    fn future_view_change_update(
+        new_view_number: u32,
+    ) -> LevelUpdateMessage<SignedViewChangeMessage, ViewChange> {
+        let message = SignedViewChangeMessage {
+            view_change: proof(1).sig,
+            previous_proof: Some(proof(1).sig),
+        };
+        LevelUpdateMessage {
+            update: LevelUpdate::new(message, None, 0, 1),
+            tag: view_change(10, new_view_number),
+        }
+    }
+
+    #[test]
+    fn input_switch_bounds_the_pending_future_view_changes() {
+        let max_pending = InputStreamSwitch::MAX_PENDING_FUTURE_VIEW_CHANGES as u32;
+        let updates: Vec<_> = (2..max_pending + 10)
+            .map(future_view_change_update)
+            .collect();
+        let (input_switch, mut receiver) = InputStreamSwitch::new(
+            Box::pin(futures::stream::iter(updates)),
+            view_change(10, 1),
+            Arc::new(ValidatorRegistry::new(Validators::new(vec![]))),
+        );
+
+        // None of the updates are for the current view change.
+        let current_updates = futures::executor::block_on(input_switch.collect::<Vec<_>>());
+        assert!(current_updates.is_empty());
+
+        // Only the first future view changes are kept, the rest is dropped.
+        let mut pending = vec![];
+        while let Ok(Some(result)) = receiver.try_next() {
+            match result {
+                ViewChangeResult::FutureViewChange(_, tag) => pending.push(tag.new_view_number),
+                _ => panic!("Unexpected view change result"),
+            }
+        }
+        assert_eq!(pending, (2..max_pending + 2).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn proof_cache_returns_the_latest_view() {
+        let cache = ViewChangeProofCache::default();
+        cache.insert(view_change(10, 1), proof(1));
+        cache.insert(view_change(10, 2), proof(2));
+
+        // A lagging validator gets the proof of the latest view.
+        let cached = cache.get(&view_change(10, 1)).unwrap();
+        assert_eq!(cached.view_change, view_change(10, 2));
+        assert_eq!(cached.proof, proof(2));
+
+        // An older proof doesn't replace a newer one.
+        cache.insert(view_change(10, 1), proof(1));
+        assert_eq!(cache.get(&view_change(10, 2)).unwrap().proof, proof(2));
+
+        // There is no proof for views that weren't completed yet, or for other blocks.
+        assert!(cache.get(&view_change(10, 3)).is_none());
+        assert!(cache.get(&view_change(11, 1)).is_none());
+
+        // View changes on another fork don't match.
+        let mut other_fork = view_change(10, 1);
+        other_fork.vrf_entropy = VrfEntropy::from(&[1u8; 32][..]);
+        assert!(cache.get(&other_fork).is_none());
+    }
+
+    #[test]
+    fn proof_cache_drops_the_lowest_block_numbers() {
+        let cache = ViewChangeProofCache::default();
+        let max_entries = ViewChangeProofCache::MAX_ENTRIES as u32;
+        for block_number in 1..=max_entries + 1 {
+            cache.insert(view_change(block_number, 1), proof(1));
+        }
+
+        assert!(cache.get(&view_change(1, 1)).is_none());
+        assert!(cache.get(&view_change(2, 1)).is_some());
+        assert!(cache.get(&view_change(max_entries + 1, 1)).is_some());
+    }
+}
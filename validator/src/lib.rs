@@ -23,6 +23,7 @@ extern crate nimiq_mempool as mempool;
 extern crate nimiq_network_interface as network_interface;
 extern crate nimiq_primitives as primitives;
 extern crate nimiq_tendermint as tendermint_protocol;
+extern crate nimiq_transaction as transaction;
 extern crate nimiq_transaction_builder as transaction_builder;
 extern crate nimiq_utils as utils;
 extern crate nimiq_validator_network as validator_network;
@@ -31,6 +32,14 @@ extern crate nimiq_vrf as vrf;
 pub mod aggregation;
 mod r#macro;
 mod micro;
+mod proposal;
+pub mod reward;
 mod slash;
 mod tendermint;
 pub mod validator;
+
+network_interface::register_topics!(pub TOPICS = [
+    validator::ProposalTopic,
+    aggregation::gossip::ViewChangeUpdateTopic,
+    aggregation::gossip::TendermintUpdateTopic,
+]);
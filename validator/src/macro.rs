@@ -4,15 +4,16 @@ use std::sync::Arc;
 
 use futures::stream::{BoxStream, Stream, StreamExt};
 use futures::task::{Context, Poll};
-use parking_lot::RwLock;
 
 use beserial::{Deserialize, Serialize};
 use nimiq_block::{
-    MacroBlock, MacroHeader, MultiSignature, SignedTendermintProposal, TendermintStep,
+    Block, MacroBlock, MacroHeader, MultiSignature, SignedTendermintProposal, TendermintStep,
 };
 use nimiq_block_production::BlockProducer;
-use nimiq_blockchain::Blockchain;
-use nimiq_database::{FromDatabaseValue, IntoDatabaseValue};
+use nimiq_blockchain::BlockchainLock;
+use nimiq_database::{
+    Database, Environment, FromDatabaseValue, IntoDatabaseValue, ReadTransaction, WriteTransaction,
+};
 use nimiq_primitives::slots::Validators;
 use nimiq_tendermint::{
     Checkpoint, Step, TendermintOutsideDeps, TendermintReturn, TendermintState,
@@ -20,6 +21,7 @@ use nimiq_tendermint::{
 use nimiq_validator_network::ValidatorNetwork;
 use nimiq_vrf::VrfSeed;
 
+use crate::aggregation::gossip::{LevelUpdateGossip, TendermintUpdateTopic};
 use crate::tendermint::TendermintInterface;
 
 #[derive(Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -59,14 +61,86 @@ impl<TValidatorNetwork: ValidatorNetwork> FromDatabaseValue
     }
 }
 
+/// Persists the Tendermint state of the macro block that is being produced, so that a restarted
+/// validator can resume without equivocating.
+pub(crate) struct MacroStateStore {
+    env: Environment,
+    database: Database,
+}
+
+impl MacroStateStore {
+    const DB_NAME: &'static str = "ValidatorState";
+    const KEY: &'static str = "validatorState";
+
+    pub fn new(env: Environment) -> Self {
+        let database = env.open_database(Self::DB_NAME.to_string());
+        Self { env, database }
+    }
+
+    pub fn load<TValidatorNetwork: ValidatorNetwork>(
+        &self,
+    ) -> Option<PersistedMacroState<TValidatorNetwork>> {
+        let read_transaction = ReadTransaction::new(&self.env);
+        read_transaction.get(&self.database, Self::KEY)
+    }
+
+    pub fn store<TValidatorNetwork: ValidatorNetwork>(
+        &self,
+        state: &PersistedMacroState<TValidatorNetwork>,
+    ) {
+        let mut write_transaction = WriteTransaction::new(&self.env);
+        write_transaction.put::<str, Vec<u8>>(
+            &self.database,
+            Self::KEY,
+            &Serialize::serialize_to_vec(state),
+        );
+        write_transaction.commit();
+    }
+
+    /// Clears the persisted state if the accepted block is a macro block, since the state only
+    /// applies to the production of that block. Returns whether the block was a macro block.
+    pub fn on_block_accepted(&self, block: &Block) -> bool {
+        if !block.is_macro() {
+            return false;
+        }
+
+        let mut write_transaction = WriteTransaction::new(&self.env);
+        write_transaction.remove(&self.database, Self::KEY);
+        write_transaction.commit();
+        true
+    }
+}
+
+/// Returns the round in which Tendermint resumes from a persisted state.
+///
+/// If we might have proposed or voted in the persisted round already before going down, we must
+/// not do so again in that round, since we don't know for what and would risk equivocating.
+/// Instead, we resume at the start of the next round, keeping our locked and valid values. A
+/// state persisted in the propose step is safe to resume in the same round, even if it is our
+/// turn: Our proposal and our prevote for it are made in the same step transition, so the state
+/// persisted after it is already in the prevote step.
+fn resume_round(round: u32, step: TendermintStep) -> u32 {
+    if step == TendermintStep::Propose {
+        round
+    } else {
+        log::debug!(
+            "Resuming Tendermint in round {} instead of {} to avoid equivocation",
+            round + 1,
+            round
+        );
+        round + 1
+    }
+}
+
 pub(crate) struct ProduceMacroBlock {
     tendermint: BoxStream<'static, TendermintReturn<MacroHeader, MultiSignature, MacroBlock>>,
 }
 
 impl ProduceMacroBlock {
     pub fn new<TValidatorNetwork: ValidatorNetwork + 'static>(
-        blockchain: Arc<RwLock<Blockchain>>,
+        blockchain: Arc<BlockchainLock>,
         network: Arc<TValidatorNetwork>,
+        gossip: Arc<LevelUpdateGossip<TValidatorNetwork, TendermintUpdateTopic>>,
         block_producer: BlockProducer,
         validator_slot_band: u16,
         active_validators: Validators,
@@ -89,6 +163,7 @@ impl ProduceMacroBlock {
             prev_seed,
             block_height,
             network,
+            gossip,
             blockchain,
             block_producer,
             proposal_stream,
@@ -96,12 +171,8 @@ impl ProduceMacroBlock {
         );
 
         let state_opt = state.map(|s| TendermintState {
-            step: match s.step {
-                TendermintStep::PreVote => Step::Prevote,
-                TendermintStep::PreCommit => Step::Precommit,
-                TendermintStep::Propose => Step::Propose,
-            },
-            round: s.round,
+            step: Step::Propose,
+            round: resume_round(s.round, s.step),
             locked_value: s.locked_value,
             locked_round: s.locked_round,
             valid_value: s.valid_value,
@@ -141,3 +212,82 @@ impl Stream for ProduceMacroBlock {
         self.tendermint.poll_next_unpin(cx)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use nimiq_block::{MicroBlock, MicroHeader};
+    use nimiq_database::volatile::VolatileEnvironment;
+    use nimiq_hash::Blake2bHash;
+    use nimiq_network_mock::MockNetwork;
+    use nimiq_validator_network::network_impl::ValidatorNetworkImpl;
+
+    use super::*;
+
+    type TestState = PersistedMacroState<ValidatorNetworkImpl<MockNetwork>>;
+
+    fn persisted_state(round: u32, step: TendermintStep) -> TestState {
+        PersistedMacroState {
+            height: 32,
+            round,
+            step,
+            locked_value: None,
+            locked_round: None,
+            valid_value: None,
+            valid_round: None,
+        }
+    }
+
+    fn micro_block() -> Block {
+        Block::Micro(MicroBlock {
+            header: MicroHeader {
+                version: 0,
+                block_number: 31,
+                view_number: 0,
+                timestamp: 0,
+                parent_hash: Blake2bHash::default(),
+                seed: VrfSeed::default(),
+                extra_data: vec![],
+                state_root: Blake2bHash::default(),
+                body_root: Blake2bHash::default(),
+                history_root: Blake2bHash::default(),
+            },
+            justification: None,
+            body: None,
+        })
+    }
+
+    #[test]
+    fn it_resumes_in_the_next_round_after_voting() {
+        assert_eq!(resume_round(3, TendermintStep::PreVote), 4);
+        assert_eq!(resume_round(3, TendermintStep::PreCommit), 4);
+    }
+
+    #[test]
+    fn it_resumes_in_the_same_round_before_proposing() {
+        // Whether it is our turn or not, nothing was proposed or voted for in the propose step.
+        assert_eq!(resume_round(0, TendermintStep::Propose), 0);
+        assert_eq!(resume_round(3, TendermintStep::Propose), 3);
+    }
+
+    #[test]
+    fn it_clears_the_persisted_state_once_a_macro_block_is_accepted() {
+        let store = MacroStateStore::new(VolatileEnvironment::new(10).unwrap());
+        assert!(store.load::<ValidatorNetworkImpl<MockNetwork>>().is_none());
+
+        let persisted = |store: &MacroStateStore| {
+            store
+                .load::<ValidatorNetworkImpl<MockNetwork>>()
+                .map(|state| (state.height, state.round, state.step))
+        };
+
+        store.store(&persisted_state(2, TendermintStep::PreCommit));
+        assert_eq!(persisted(&store), Some((32, 2, TendermintStep::PreCommit)));
+
+        // Micro blocks don't affect the state.
+        assert!(!store.on_block_accepted(&micro_block()));
+        assert_eq!(persisted(&store), Some((32, 2, TendermintStep::PreCommit)));
+
+        assert!(store.on_block_accepted(&Block::Macro(MacroBlock::default())));
+        assert_eq!(persisted(&store), None);
+    }
+}
@@ -1,39 +1,76 @@
+use std::fmt;
 use std::pin::Pin;
 use std::sync::Arc;
-use std::time::{Duration, SystemTime};
+use std::time::{Duration, Instant, SystemTime};
 
 use futures::future::BoxFuture;
 use futures::task::{Context, Poll};
 use futures::{ready, FutureExt, Stream};
-use parking_lot::RwLock;
+use rand::Rng;
 use tokio::time;
 
 use block::{Block, ForkProof, MicroBlock, ViewChange, ViewChangeProof};
-use block_production::BlockProducer;
-use blockchain::{AbstractBlockchain, Blockchain, PushResult};
+use block_production::{BlockProducer, BlockProductionError};
+use blockchain::{AbstractBlockchain, Blockchain, BlockchainLock, PushError, PushResult};
 use mempool::mempool::Mempool;
 
 use nimiq_primitives::slots::Validators;
+use transaction::Transaction;
 
 use nimiq_validator_network::ValidatorNetwork;
+use utils::shared_rng::SharedRng;
 use utils::time::systemtime_to_timestamp;
 use vrf::VrfSeed;
 
-use crate::aggregation::view_change::ViewChangeAggregation;
+use crate::aggregation::gossip::{LevelUpdateGossip, ViewChangeUpdateTopic};
+use crate::aggregation::view_change::{ViewChangeAggregation, ViewChangeProofCache};
+
+/// The view change delay is extended by a random share of up to `1 / VIEW_CHANGE_JITTER_DIVISOR`
+/// of it, so that the validators don't all start the view change at the same time.
+const VIEW_CHANGE_JITTER_DIVISOR: u32 = 10;
 
 // Ignoring this clippy warning since size difference is not that much (320
 // bytes) and we probably don't want the performance penalty of the allocation.
 #[allow(clippy::large_enum_variant)]
 pub(crate) enum ProduceMicroBlockEvent {
-    MicroBlock(MicroBlock, PushResult),
+    MicroBlock(MicroBlock, PushResult, Option<ProductionDegradation>),
     ViewChange(ViewChange, ViewChangeProof),
 }
 
+/// The reason why a micro block was produced without all the transactions of the mempool that fit
+/// into it.
+#[derive(Debug)]
+pub(crate) enum ProductionDegradation {
+    /// Selecting the transactions from the mempool hit the deadline. The block contains the given
+    /// number of transactions that were selected until then.
+    SelectionTimeout(usize),
+    /// The selected transactions or fork proofs couldn't be applied to the accounts.
+    ProductionFailed(BlockProductionError),
+    /// The blockchain rejected the block with the selected transactions.
+    PushFailed(PushError),
+}
+
+impl fmt::Display for ProductionDegradation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProductionDegradation::SelectionTimeout(selected) => write!(
+                f,
+                "selecting the transactions timed out after {} transactions",
+                selected
+            ),
+            ProductionDegradation::ProductionFailed(e) => write!(f, "{}", e),
+            ProductionDegradation::PushFailed(e) => write!(f, "the block was rejected: {}", e),
+        }
+    }
+}
+
 #[derive(Clone)]
 struct NextProduceMicroBlockEvent<TValidatorNetwork> {
-    blockchain: Arc<RwLock<Blockchain>>,
+    blockchain: Arc<BlockchainLock>,
     mempool: Arc<Mempool>,
     network: Arc<TValidatorNetwork>,
+    gossip: Arc<LevelUpdateGossip<TValidatorNetwork, ViewChangeUpdateTopic>>,
+    view_change_proofs: Arc<ViewChangeProofCache>,
     block_producer: BlockProducer,
     validator_slot_band: u16,
     fork_proofs: Vec<ForkProof>,
@@ -43,6 +80,7 @@ struct NextProduceMicroBlockEvent<TValidatorNetwork> {
     view_change_proof: Option<ViewChangeProof>,
     view_change: Option<ViewChange>,
     view_change_delay: Duration,
+    rng: SharedRng,
 }
 
 impl<TValidatorNetwork: ValidatorNetwork + 'static> NextProduceMicroBlockEvent<TValidatorNetwork> {
@@ -50,9 +88,11 @@ impl<TValidatorNetwork: ValidatorNetwork + 'static> NextProduceMicroBlockEvent<T
     // except making clippy happy
     #[allow(clippy::too_many_arguments)]
     fn new(
-        blockchain: Arc<RwLock<Blockchain>>,
+        blockchain: Arc<BlockchainLock>,
         mempool: Arc<Mempool>,
         network: Arc<TValidatorNetwork>,
+        gossip: Arc<LevelUpdateGossip<TValidatorNetwork, ViewChangeUpdateTopic>>,
+        view_change_proofs: Arc<ViewChangeProofCache>,
         block_producer: BlockProducer,
         validator_slot_band: u16,
         fork_proofs: Vec<ForkProof>,
@@ -62,11 +102,14 @@ impl<TValidatorNetwork: ValidatorNetwork + 'static> NextProduceMicroBlockEvent<T
         view_change_proof: Option<ViewChangeProof>,
         view_change: Option<ViewChange>,
         view_change_delay: Duration,
+        rng: SharedRng,
     ) -> Self {
         Self {
             blockchain,
             mempool,
             network,
+            gossip,
+            view_change_proofs,
             block_producer,
             validator_slot_band,
             fork_proofs,
@@ -76,6 +119,7 @@ impl<TValidatorNetwork: ValidatorNetwork + 'static> NextProduceMicroBlockEvent<T
             view_change_proof,
             view_change,
             view_change_delay,
+            rng,
         }
     }
 
@@ -85,17 +129,11 @@ impl<TValidatorNetwork: ValidatorNetwork + 'static> NextProduceMicroBlockEvent<T
         Option<ProduceMicroBlockEvent>,
         NextProduceMicroBlockEvent<TValidatorNetwork>,
     ) {
-        let in_current_state = |head: &Block| {
-            self.prev_seed == *head.seed()
-                && self.block_number == head.block_number() + 1
-                && self.view_number >= head.next_view_number()
-        };
-
         // Acquire blockchain.upgradable_read() to prevent further changes to the blockchain while
         // we're constructing the block. Check if we're still in the correct state, abort otherwise.
         let return_value = {
             let blockchain = self.blockchain.upgradable_read();
-            if !in_current_state(&blockchain.head()) {
+            if !self.in_current_state(&blockchain.head()) {
                 Some(None)
             } else if self.is_our_turn(&*blockchain) {
                 info!(
@@ -103,7 +141,16 @@ impl<TValidatorNetwork: ValidatorNetwork + 'static> NextProduceMicroBlockEvent<T
                     self.validator_slot_band, self.block_number, self.view_number
                 );
 
-                let block = self.produce_micro_block(&*blockchain);
+                let (block, degradation) = self.produce_micro_block(&*blockchain);
+                let has_transactions = block
+                    .body
+                    .as_ref()
+                    .map_or(false, |body| !body.transactions.is_empty());
+                let is_empty = !has_transactions
+                    && block
+                        .body
+                        .as_ref()
+                        .map_or(true, |body| body.fork_proofs.is_empty());
 
                 debug!(
                     "Produced micro block #{}.{} with {} transactions",
@@ -125,14 +172,27 @@ impl<TValidatorNetwork: ValidatorNetwork + 'static> NextProduceMicroBlockEvent<T
                     Blockchain::push(blockchain, Block::Micro(block))
                 };
 
-                if let Err(e) = &result {
-                    error!("Failed to push our own block onto the chain: {:?}", e);
+                match result {
+                    Ok(result) => Some(Some(ProduceMicroBlockEvent::MicroBlock(
+                        block1,
+                        result,
+                        degradation,
+                    ))),
+                    Err(e) if !is_empty => {
+                        error!(
+                            "Failed to push our own block onto the chain: {:?}, falling back to a block without transactions",
+                            e
+                        );
+                        Some(self.push_fallback_micro_block(
+                            ProductionDegradation::PushFailed(e),
+                            has_transactions,
+                        ))
+                    }
+                    Err(e) => {
+                        error!("Failed to push our own block onto the chain: {:?}", e);
+                        Some(None)
+                    }
                 }
-
-                let event = result
-                    .map(move |result| ProduceMicroBlockEvent::MicroBlock(block1, result))
-                    .ok();
-                Some(event)
             } else {
                 None
             }
@@ -145,7 +205,7 @@ impl<TValidatorNetwork: ValidatorNetwork + 'static> NextProduceMicroBlockEvent<T
             "[{}] Not our turn at #{}:{}, waiting for micro block",
             self.validator_slot_band, self.block_number, self.view_number
         );
-        time::sleep(self.view_change_delay).await;
+        time::sleep(self.view_change_delay + self.view_change_jitter()).await;
         info!(
             "No micro block received within timeout at #{}:{}, starting view change",
             self.block_number, self.view_number
@@ -154,7 +214,7 @@ impl<TValidatorNetwork: ValidatorNetwork + 'static> NextProduceMicroBlockEvent<T
         // Acquire a blockchain read lock and check if the state still matches to fetch active validators.
         let active_validators = {
             let blockchain = self.blockchain.read();
-            if in_current_state(&blockchain.head()) {
+            if self.in_current_state(&blockchain.head()) {
                 Some(blockchain.current_validators().unwrap())
             } else {
                 None
@@ -173,6 +233,17 @@ impl<TValidatorNetwork: ValidatorNetwork + 'static> NextProduceMicroBlockEvent<T
         (Some(event), self)
     }
 
+    fn view_change_jitter(&mut self) -> Duration {
+        let max_jitter = self.view_change_delay / VIEW_CHANGE_JITTER_DIVISOR;
+        self.rng.gen_range(Duration::ZERO..=max_jitter)
+    }
+
+    fn in_current_state(&self, head: &Block) -> bool {
+        self.prev_seed == *head.seed()
+            && self.block_number == head.block_number() + 1
+            && self.view_number >= head.next_view_number()
+    }
+
     fn is_our_turn(&self, blockchain: &Blockchain) -> bool {
         let proposer_slot = blockchain.get_proposer_at(
             self.block_number,
@@ -192,17 +263,45 @@ impl<TValidatorNetwork: ValidatorNetwork + 'static> NextProduceMicroBlockEvent<T
         }
     }
 
-    fn produce_micro_block(&self, blockchain: &Blockchain) -> MicroBlock {
+    /// Produces a micro block with the transactions of the mempool. If selecting the transactions
+    /// hits the deadline, the block contains the transactions selected until then. If the block
+    /// can't be produced with them, a block without transactions is produced instead, so that we
+    /// don't miss our slot. Returns the reason in both cases.
+    fn produce_micro_block(
+        &self,
+        blockchain: &Blockchain,
+    ) -> (MicroBlock, Option<ProductionDegradation>) {
         let timestamp = u64::max(
             blockchain.timestamp(),
             systemtime_to_timestamp(SystemTime::now()),
         );
 
-        let transactions = self
-            .mempool
-            .get_transactions_for_block(MicroBlock::get_available_bytes(self.fork_proofs.len()));
+        // Leave enough time to produce and propagate the block before the other validators start
+        // a view change.
+        let deadline = Instant::now() + self.view_change_delay / 4;
+        let snapshot = self.mempool.snapshot_for_block_until(
+            MicroBlock::get_available_bytes(self.fork_proofs.len()),
+            deadline,
+        );
+        let timeout = snapshot
+            .timed_out
+            .then(|| ProductionDegradation::SelectionTimeout(snapshot.transactions.len()));
 
-        self.block_producer.next_micro_block(
+        self.produce_micro_block_with(blockchain, timestamp, snapshot.transactions, timeout)
+    }
+
+    /// Produces a micro block with the given transactions and the fork proofs. If that fails, the
+    /// fork proofs are retried without the transactions, so that a bad transaction doesn't keep
+    /// them off the chain, before falling back to an empty block.
+    fn produce_micro_block_with(
+        &self,
+        blockchain: &Blockchain,
+        timestamp: u64,
+        transactions: Vec<Transaction>,
+        timeout: Option<ProductionDegradation>,
+    ) -> (MicroBlock, Option<ProductionDegradation>) {
+        let has_transactions = !transactions.is_empty();
+        let degradation = match self.block_producer.try_next_micro_block(
             blockchain,
             timestamp,
             self.view_number,
@@ -210,9 +309,130 @@ impl<TValidatorNetwork: ValidatorNetwork + 'static> NextProduceMicroBlockEvent<T
             self.fork_proofs.clone(),
             transactions,
             vec![], // TODO: Allow validators to set extra data field.
+        ) {
+            Ok(block) => return (block, timeout),
+            Err(e) => ProductionDegradation::ProductionFailed(e),
+        };
+
+        warn!(
+            "Failed to produce micro block #{}.{} ({}), producing a block without transactions instead",
+            self.block_number, self.view_number, degradation
+        );
+        let fork_proof_block = if has_transactions {
+            self.produce_fork_proof_micro_block(blockchain, timestamp)
+        } else {
+            None
+        };
+        let block = fork_proof_block
+            .unwrap_or_else(|| self.produce_empty_micro_block(blockchain, timestamp));
+        (block, Some(degradation))
+    }
+
+    /// Produces a micro block with the fork proofs, but without any transactions. Returns `None`
+    /// if there are no fork proofs or if they can't be applied either.
+    fn produce_fork_proof_micro_block(
+        &self,
+        blockchain: &Blockchain,
+        timestamp: u64,
+    ) -> Option<MicroBlock> {
+        if self.fork_proofs.is_empty() {
+            return None;
+        }
+
+        match self.block_producer.try_next_micro_block(
+            blockchain,
+            timestamp,
+            self.view_number,
+            self.view_change_proof.clone(),
+            self.fork_proofs.clone(),
+            vec![],
+            vec![],
+        ) {
+            Ok(block) => Some(block),
+            Err(e) => {
+                warn!(
+                    "Failed to produce micro block #{}.{} with the fork proofs ({}), producing an empty block instead",
+                    self.block_number, self.view_number, e
+                );
+                None
+            }
+        }
+    }
+
+    fn produce_empty_micro_block(&self, blockchain: &Blockchain, timestamp: u64) -> MicroBlock {
+        self.block_producer.next_micro_block(
+            blockchain,
+            timestamp,
+            self.view_number,
+            self.view_change_proof.clone(),
+            vec![],
+            vec![],
+            vec![],
         )
     }
 
+    /// Pushes a block without transactions, after the block with the transactions of the mempool
+    /// was rejected. If the rejected block had transactions, the fork proofs are retried first.
+    /// If the block with the fork proofs is rejected as well, an empty block is pushed.
+    fn push_fallback_micro_block(
+        &self,
+        degradation: ProductionDegradation,
+        retry_fork_proofs: bool,
+    ) -> Option<ProduceMicroBlockEvent> {
+        let mut with_fork_proofs = retry_fork_proofs;
+        loop {
+            let blockchain = self.blockchain.upgradable_read();
+            if !self.in_current_state(&blockchain.head()) {
+                return None;
+            }
+
+            let timestamp = u64::max(
+                blockchain.timestamp(),
+                systemtime_to_timestamp(SystemTime::now()),
+            );
+            let fork_proof_block = if with_fork_proofs {
+                self.produce_fork_proof_micro_block(&*blockchain, timestamp)
+            } else {
+                None
+            };
+            let block = match fork_proof_block {
+                Some(block) => block,
+                None => {
+                    with_fork_proofs = false;
+                    self.produce_empty_micro_block(&*blockchain, timestamp)
+                }
+            };
+            let block1 = block.clone();
+
+            let result = if cfg!(feature = "trusted_push") {
+                Blockchain::trusted_push(blockchain, Block::Micro(block))
+            } else {
+                Blockchain::push(blockchain, Block::Micro(block))
+            };
+
+            match result {
+                Ok(result) => {
+                    return Some(ProduceMicroBlockEvent::MicroBlock(
+                        block1,
+                        result,
+                        Some(degradation),
+                    ))
+                }
+                Err(e) if with_fork_proofs => {
+                    error!(
+                        "Failed to push our own block with the fork proofs onto the chain: {:?}, falling back to an empty block",
+                        e
+                    );
+                    with_fork_proofs = false;
+                }
+                Err(e) => {
+                    error!("Failed to push our own empty block onto the chain: {:?}", e);
+                    return None;
+                }
+            }
+        }
+    }
+
     async fn change_view(
         &mut self,
         active_validators: Validators,
@@ -240,6 +460,9 @@ impl<TValidatorNetwork: ValidatorNetwork + 'static> NextProduceMicroBlockEvent<T
             self.validator_slot_band,
             active_validators,
             Arc::clone(&self.network),
+            Arc::clone(&self.gossip),
+            Arc::clone(&self.view_change_proofs),
+            self.rng.clone(),
         )
         .await;
 
@@ -269,9 +492,11 @@ impl<TValidatorNetwork: ValidatorNetwork + 'static> ProduceMicroBlock<TValidator
     // except making clippy happy
     #[allow(clippy::too_many_arguments)]
     pub fn new(
-        blockchain: Arc<RwLock<Blockchain>>,
+        blockchain: Arc<BlockchainLock>,
         mempool: Arc<Mempool>,
         network: Arc<TValidatorNetwork>,
+        gossip: Arc<LevelUpdateGossip<TValidatorNetwork, ViewChangeUpdateTopic>>,
+        view_change_proofs: Arc<ViewChangeProofCache>,
         block_producer: BlockProducer,
         validator_slot_band: u16,
         fork_proofs: Vec<ForkProof>,
@@ -281,11 +506,14 @@ impl<TValidatorNetwork: ValidatorNetwork + 'static> ProduceMicroBlock<TValidator
         view_change_proof: Option<ViewChangeProof>,
         view_change: Option<ViewChange>,
         view_change_delay: Duration,
+        rng: SharedRng,
     ) -> Self {
         let next_event = NextProduceMicroBlockEvent::new(
             blockchain,
             mempool,
             network,
+            gossip,
+            view_change_proofs,
             block_producer,
             validator_slot_band,
             fork_proofs,
@@ -295,6 +523,7 @@ impl<TValidatorNetwork: ValidatorNetwork + 'static> ProduceMicroBlock<TValidator
             view_change_proof,
             view_change,
             view_change_delay,
+            rng,
         )
         .next()
         .boxed();
@@ -331,3 +560,204 @@ impl<TValidatorNetwork: ValidatorNetwork + 'static> Stream
         Poll::Ready(Some(event))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use database::volatile::VolatileEnvironment;
+    use hash::{Blake2bHash, Hash};
+    use keys::Address;
+    use mempool::config::MempoolConfig;
+    use nimiq_network_mock::MockHub;
+    use nimiq_test_utils::blockchain::{signing_key, voting_key};
+    use nimiq_validator_network::network_impl::ValidatorNetworkImpl;
+    use primitives::{coin::Coin, networks::NetworkId};
+    use utils::time::OffsetTime;
+
+    use crate::aggregation::gossip::AggregationGossip;
+    use crate::aggregation::view_change::ViewChangeProofCache;
+
+    use super::*;
+
+    type TestNetwork = ValidatorNetworkImpl<nimiq_network_mock::MockNetwork>;
+
+    fn test_blockchain() -> Arc<BlockchainLock> {
+        let env = VolatileEnvironment::new(10).unwrap();
+        let time = Arc::new(OffsetTime::new());
+        Arc::new(BlockchainLock::new(
+            Blockchain::new(env, NetworkId::UnitAlbatross, time).unwrap(),
+        ))
+    }
+
+    fn timestamp() -> u64 {
+        systemtime_to_timestamp(SystemTime::now())
+    }
+
+    /// Pushes micro block #1 and returns a proof of a fork at it. If `valid` is false, the second
+    /// header isn't signed by the block producer, so the proof is rejected by the blockchain.
+    fn push_block_and_fork_proof(blockchain: &Arc<BlockchainLock>, valid: bool) -> ForkProof {
+        let producer = BlockProducer::new(signing_key(), voting_key());
+        let blockchain = blockchain.upgradable_read();
+        let prev_vrf_seed = blockchain.head().seed().clone();
+        let block =
+            producer.next_micro_block(&blockchain, timestamp(), 0, None, vec![], vec![], vec![]);
+        assert_eq!(
+            Blockchain::push(blockchain, Block::Micro(block.clone())),
+            Ok(PushResult::Extended)
+        );
+
+        let header1 = block.header.clone();
+        let justification1 = block.justification.unwrap().signature;
+        let mut header2 = header1.clone();
+        header2.timestamp += 1;
+        let justification2 = if valid {
+            signing_key().sign(header2.hash::<Blake2bHash>().as_slice())
+        } else {
+            justification1.clone()
+        };
+        ForkProof {
+            header1,
+            header2,
+            justification1,
+            justification2,
+            prev_vrf_seed,
+        }
+    }
+
+    /// A transaction that can't be applied because the sender has no funds.
+    fn unfunded_transaction() -> Transaction {
+        Transaction::new_basic(
+            Address::from([1u8; 20]),
+            Address::from([2u8; 20]),
+            Coin::from_u64_unchecked(100),
+            Coin::from_u64_unchecked(1),
+            1,
+            NetworkId::UnitAlbatross,
+        )
+    }
+
+    fn next_event(
+        blockchain: &Arc<BlockchainLock>,
+        fork_proofs: Vec<ForkProof>,
+    ) -> NextProduceMicroBlockEvent<TestNetwork> {
+        let network = Arc::new(ValidatorNetworkImpl::new(Arc::new(
+            MockHub::default().new_network(),
+        )));
+        let gossip = AggregationGossip::new(Arc::clone(&network), Arc::clone(blockchain));
+        let (prev_seed, block_number) = {
+            let blockchain = blockchain.read();
+            (
+                blockchain.head().seed().clone(),
+                blockchain.block_number() + 1,
+            )
+        };
+
+        NextProduceMicroBlockEvent::new(
+            Arc::clone(blockchain),
+            Arc::new(Mempool::new(
+                Arc::clone(blockchain),
+                MempoolConfig::default(),
+            )),
+            network,
+            gossip.view_changes,
+            Arc::new(ViewChangeProofCache::default()),
+            BlockProducer::new(signing_key(), voting_key()),
+            0,
+            fork_proofs,
+            prev_seed,
+            block_number,
+            0,
+            None,
+            None,
+            Duration::from_secs(1),
+            SharedRng::seeded(0),
+        )
+    }
+
+    /// Returns the number of transactions and fork proofs of the block.
+    fn contents(block: &MicroBlock) -> (usize, usize) {
+        let body = block.body.as_ref().unwrap();
+        (body.transactions.len(), body.fork_proofs.len())
+    }
+
+    #[tokio::test]
+    async fn production_failure_keeps_the_fork_proofs() {
+        let blockchain = test_blockchain();
+        let fork_proof = push_block_and_fork_proof(&blockchain, true);
+        let event = next_event(&blockchain, vec![fork_proof]);
+
+        let (block, degradation) = event.produce_micro_block_with(
+            &blockchain.read(),
+            timestamp(),
+            vec![unfunded_transaction()],
+            None,
+        );
+
+        assert!(matches!(
+            degradation,
+            Some(ProductionDegradation::ProductionFailed(_))
+        ));
+        assert_eq!(contents(&block), (0, 1));
+        assert_eq!(
+            Blockchain::push(blockchain.upgradable_read(), Block::Micro(block)),
+            Ok(PushResult::Extended)
+        );
+    }
+
+    #[tokio::test]
+    async fn production_failure_without_fork_proofs_produces_an_empty_block() {
+        let blockchain = test_blockchain();
+        let event = next_event(&blockchain, vec![]);
+
+        let (block, degradation) = event.produce_micro_block_with(
+            &blockchain.read(),
+            timestamp(),
+            vec![unfunded_transaction()],
+            None,
+        );
+
+        assert!(matches!(
+            degradation,
+            Some(ProductionDegradation::ProductionFailed(_))
+        ));
+        assert_eq!(contents(&block), (0, 0));
+    }
+
+    #[tokio::test]
+    async fn push_failure_retries_the_fork_proofs() {
+        let blockchain = test_blockchain();
+        let fork_proof = push_block_and_fork_proof(&blockchain, true);
+        let event = next_event(&blockchain, vec![fork_proof]);
+
+        match event
+            .push_fallback_micro_block(ProductionDegradation::PushFailed(PushError::Orphan), true)
+        {
+            Some(ProduceMicroBlockEvent::MicroBlock(
+                block,
+                PushResult::Extended,
+                Some(ProductionDegradation::PushFailed(_)),
+            )) => assert_eq!(contents(&block), (0, 1)),
+            _ => panic!("Expected the block with the fork proofs to be pushed"),
+        }
+        assert_eq!(blockchain.read().block_number(), 2);
+    }
+
+    #[tokio::test]
+    async fn push_failure_falls_back_to_an_empty_block() {
+        let blockchain = test_blockchain();
+        let fork_proof = push_block_and_fork_proof(&blockchain, false);
+        let event = next_event(&blockchain, vec![fork_proof]);
+
+        // The block with the fork proofs is rejected as well, so an empty block is pushed.
+        match event
+            .push_fallback_micro_block(ProductionDegradation::PushFailed(PushError::Orphan), true)
+        {
+            Some(ProduceMicroBlockEvent::MicroBlock(
+                block,
+                PushResult::Extended,
+                Some(ProductionDegradation::PushFailed(_)),
+            )) => assert_eq!(contents(&block), (0, 0)),
+            _ => panic!("Expected an empty block to be pushed"),
+        }
+        assert_eq!(blockchain.read().block_number(), 2);
+    }
+}
@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::sync::Arc;
+
+use futures::future::{BoxFuture, FutureExt, Shared};
+use parking_lot::Mutex;
+
+use block::{Block, BlockHeader, MacroBlock, MacroBody, MacroHeader};
+use blockchain::{Blockchain, BlockchainLock};
+use hash::{Blake2bHash, Hash};
+use keys::PublicKey as SchnorrPublicKey;
+use vrf::VrfSeed;
+
+/// The result of validating a macro block proposal.
+#[derive(Clone, Debug)]
+pub(crate) enum ProposalValidity {
+    /// The proposal is valid. Contains the body matching the proposed header.
+    Valid(Option<MacroBody>),
+    /// The header is invalid. Nothing that depends on the blockchain state was checked.
+    InvalidHeader,
+    /// The header is valid, but the proposal can't be applied to our state (e.g. wrong state root
+    /// or punished slots).
+    InvalidState,
+}
+
+type ValidationFuture = Shared<BoxFuture<'static, ProposalValidity>>;
+
+/// Validates the macro block proposals of one block height.
+///
+/// Validation runs on the blocking thread pool, in parallel to the aggregations running on the
+/// executor. The results are cached, so a proposal that is repeated in a later round (with a valid
+/// round) is not validated again.
+pub(crate) struct ProposalValidator {
+    blockchain: Arc<BlockchainLock>,
+    prev_seed: VrfSeed,
+    results: Mutex<HashMap<(Blake2bHash, SchnorrPublicKey), ValidationFuture>>,
+}
+
+impl ProposalValidator {
+    /// Maximum number of cached results. There is at most one proposal per round, so this is only
+    /// reached if many rounds fail.
+    const MAX_CACHED_RESULTS: usize = 32;
+
+    pub fn new(blockchain: Arc<BlockchainLock>, prev_seed: VrfSeed) -> Self {
+        Self {
+            blockchain,
+            prev_seed,
+            results: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Starts validating the given header unless it was already validated using the same VRF key.
+    /// The returned future resolves once the validation is finished.
+    pub fn validate(&self, header: MacroHeader, vrf_key: SchnorrPublicKey) -> ValidationFuture {
+        let key = (header.hash::<Blake2bHash>(), vrf_key);
+
+        let mut results = self.results.lock();
+        if let Some(result) = results.get(&key) {
+            trace!("Proposal {} was validated before", key.0);
+            return result.clone();
+        }
+
+        // Start the validation right away, even if the result is not awaited yet.
+        let blockchain = Arc::clone(&self.blockchain);
+        let prev_seed = self.prev_seed.clone();
+        let handle = tokio::task::spawn_blocking(move || {
+            Self::validate_blocking(&blockchain, &prev_seed, header, &vrf_key)
+        });
+
+        let result = async move {
+            handle.await.unwrap_or_else(|err| {
+                error!("Proposal validation failed: {:?}", err);
+                ProposalValidity::InvalidState
+            })
+        }
+        .boxed()
+        .shared();
+
+        if results.len() >= Self::MAX_CACHED_RESULTS {
+            results.clear();
+        }
+        results.insert(key, result.clone());
+
+        result
+    }
+
+    fn validate_blocking(
+        blockchain: &BlockchainLock,
+        prev_seed: &VrfSeed,
+        header: MacroHeader,
+        vrf_key: &SchnorrPublicKey,
+    ) -> ProposalValidity {
+        let blockchain = blockchain.read();
+
+        // Check the validity of the block header. This doesn't check anything that depends on the
+        // blockchain state.
+        if Blockchain::verify_block_header(
+            blockchain.deref(),
+            &BlockHeader::Macro(header.clone()),
+            vrf_key,
+            None,
+            true,
+        )
+        .is_err()
+        {
+            debug!("Proposal validation: Invalid block header");
+            return ProposalValidity::InvalidHeader;
+        }
+
+        // Get a write transaction to the database.
+        let mut txn = blockchain.write_transaction();
+
+        // Get the blockchain state.
+        let state = blockchain.state();
+
+        // Create a block with just our header.
+        let block = Block::Macro(MacroBlock {
+            header,
+            body: None,
+            justification: None,
+        });
+
+        // Update our blockchain state using the received proposal.
+        // FIXME Is first_view_number = 0 correct here? Does it matter?
+        let result = if blockchain
+            .commit_accounts(state, &block, prev_seed.entropy(), 0, &mut txn)
+            .is_err()
+        {
+            debug!("Proposal validation: Can't update state");
+            ProposalValidity::InvalidState
+        } else {
+            // Check the validity of the block against our state. This also returns the block body
+            // that matches the block header (assuming that the block is valid).
+            match blockchain.verify_block_state(state, &block, Some(&txn)) {
+                Ok(body) => ProposalValidity::Valid(body),
+                Err(err) => {
+                    debug!("Proposal validation: Invalid block state: {:?}", err);
+                    ProposalValidity::InvalidState
+                }
+            }
+        };
+
+        // Abort the transaction so that we don't commit the changes we made to the blockchain state.
+        txn.abort();
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use block_production::BlockProducer;
+    use blockchain::AbstractBlockchain;
+    use database::volatile::VolatileEnvironment;
+    use keys::{KeyPair as SchnorrKeyPair, SecureGenerate};
+    use nimiq_test_utils::blockchain::{fill_micro_blocks, signing_key, voting_key};
+    use primitives::networks::NetworkId;
+    use utils::time::OffsetTime;
+
+    use super::*;
+
+    /// Returns a validator for the macro block following the given batch and a proposal for it.
+    fn setup() -> (ProposalValidator, BlockProducer, MacroBlock) {
+        let time = Arc::new(OffsetTime::new());
+        let env = VolatileEnvironment::new(10).unwrap();
+        let blockchain = Arc::new(BlockchainLock::new(
+            Blockchain::new(env, NetworkId::UnitAlbatross, time).unwrap(),
+        ));
+        let producer = BlockProducer::new(signing_key(), voting_key());
+        fill_micro_blocks(&producer, &blockchain);
+
+        let (proposal, prev_seed) = {
+            let blockchain = blockchain.read();
+            let proposal = producer.next_macro_block_proposal(
+                &blockchain,
+                blockchain.time.now() + 1000,
+                0,
+                vec![],
+            );
+            (proposal, blockchain.head().seed().clone())
+        };
+
+        (
+            ProposalValidator::new(blockchain, prev_seed),
+            producer,
+            proposal,
+        )
+    }
+
+    #[tokio::test]
+    async fn it_returns_the_body_of_valid_proposals_and_caches_the_result() {
+        let (validator, producer, proposal) = setup();
+        let vrf_key = producer.signing_key.public;
+
+        match validator.validate(proposal.header.clone(), vrf_key).await {
+            ProposalValidity::Valid(body) => assert_eq!(body, proposal.body),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+
+        // The repeated proposal is answered from the cache.
+        assert!(matches!(
+            validator.validate(proposal.header, vrf_key).await,
+            ProposalValidity::Valid(_)
+        ));
+        assert_eq!(validator.results.lock().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn it_rejects_proposals_with_a_seed_of_another_key() {
+        let (validator, _, proposal) = setup();
+        let other_key = SchnorrKeyPair::generate_default_csprng().public;
+
+        assert!(matches!(
+            validator.validate(proposal.header, other_key).await,
+            ProposalValidity::InvalidHeader
+        ));
+    }
+}
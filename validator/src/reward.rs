@@ -0,0 +1,260 @@
+//! Distribution of the validator rewards to several recipients.
+//!
+//! The rewards are paid to the reward address of the validator at the end of every batch. If
+//! reward splits are configured, the validator forwards the configured shares of each payout to
+//! the recipients. The distribution transactions are signed with the fee key, so the reward address
+//! of the validator has to be the address of the fee key. Their fees are deducted from the shares,
+//! so that the distributions never exceed the reward.
+
+use std::collections::HashMap;
+
+use account::{Inherent, InherentType, StakingContract};
+use beserial::Serialize;
+use block::Block;
+use blockchain::{AbstractBlockchain, Blockchain, ExtTxData};
+use hash::{Blake2bHash, Hash};
+use keys::{Address, KeyPair};
+use primitives::coin::Coin;
+use primitives::networks::NetworkId;
+use transaction::Transaction;
+use transaction_builder::TransactionBuilder;
+
+/// The minimum fee per byte of the distribution transactions, so that they aren't crowded out of
+/// busy mempools. The minimum relay fee of our mempool is used if it is higher.
+pub(crate) const DISTRIBUTION_FEE_PER_BYTE: f64 = 2.0;
+
+/// Share of the validator rewards that is forwarded to a recipient.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RewardSplit {
+    pub recipient: Address,
+    /// The share in basis points, i.e. hundredths of a percent.
+    pub basis_points: u16,
+}
+
+impl RewardSplit {
+    pub const MAX_BASIS_POINTS: u16 = 10_000;
+
+    /// Returns the share of the recipient of the given reward, rounded down.
+    pub fn share_of(&self, reward: Coin) -> Coin {
+        let share = u128::from(u64::from(reward)) * u128::from(self.basis_points)
+            / u128::from(Self::MAX_BASIS_POINTS);
+        Coin::from_u64_unchecked(share as u64)
+    }
+}
+
+/// Accounting of the distributions to a recipient.
+#[derive(Clone, Debug, Default)]
+pub struct RecipientStats {
+    /// Total value that was sent to the recipient.
+    pub distributed: Coin,
+    /// Number of distribution transactions sent to the recipient.
+    pub num_transactions: u64,
+    /// Hash of the last distribution transaction sent to the recipient.
+    pub last_transaction: Option<Blake2bHash>,
+}
+
+/// Accounting of the reward distribution.
+#[derive(Clone, Debug, Default)]
+pub struct RewardDistributionStats {
+    /// The configured splits.
+    pub splits: Vec<RewardSplit>,
+    /// Total rewards received at the reward address since the validator started.
+    pub rewards_received: Coin,
+    /// Number of the last block that paid out rewards.
+    pub last_payout_block: Option<u32>,
+    pub recipients: HashMap<Address, RecipientStats>,
+}
+
+/// Creates the transactions that distribute the reward paid out in the given macro block.
+///
+/// Returns the received reward and the distribution transactions, together with the split each
+/// of them belongs to. Nothing is returned if the block didn't pay out rewards to the reward
+/// address of the validator.
+pub(crate) fn distribution_transactions(
+    blockchain: &Blockchain,
+    block: &Block,
+    validator_address: &Address,
+    fee_key: &KeyPair,
+    splits: &[RewardSplit],
+    fee_per_byte: f64,
+) -> Option<(Coin, Vec<(RewardSplit, Transaction)>)> {
+    let accounts_tree = &blockchain.state().accounts.tree;
+    let db_txn = blockchain.read_transaction();
+    let validator = StakingContract::get_validator(accounts_tree, &db_txn, validator_address)?;
+
+    let reward_address = Address::from(fee_key);
+    if validator.reward_address != reward_address {
+        warn!(
+            "Can't distribute rewards, the reward address {} isn't the address of the fee key",
+            validator.reward_address
+        );
+        return None;
+    }
+
+    let reward: Coin = blockchain
+        .history_store
+        .get_block_transactions(block.block_number(), Some(&db_txn))
+        .into_iter()
+        .filter_map(|ext_tx| match ext_tx.data {
+            ExtTxData::Inherent(Inherent {
+                ty: InherentType::Reward,
+                target,
+                value,
+                ..
+            }) if target == reward_address => Some(value),
+            _ => None,
+        })
+        .sum();
+
+    if reward.is_zero() {
+        return None;
+    }
+
+    let transactions = splits
+        .iter()
+        .filter_map(|split| {
+            let transaction = distribution_transaction(
+                fee_key,
+                split,
+                reward,
+                fee_per_byte,
+                block.block_number(),
+                blockchain.network_id(),
+            )?;
+            Some((split.clone(), transaction))
+        })
+        .collect();
+
+    Some((reward, transactions))
+}
+
+/// Creates the transaction that forwards the share of the split of the given reward, paying
+/// `fee_per_byte` out of the share. Returns `None` if the share doesn't cover the fee.
+fn distribution_transaction(
+    fee_key: &KeyPair,
+    split: &RewardSplit,
+    reward: Coin,
+    fee_per_byte: f64,
+    validity_start_height: u32,
+    network_id: NetworkId,
+) -> Option<Transaction> {
+    let share = split.share_of(reward);
+
+    // The size of a basic transaction doesn't depend on its value and fee.
+    let size = TransactionBuilder::new_basic(
+        fee_key,
+        split.recipient.clone(),
+        share,
+        Coin::ZERO,
+        validity_start_height,
+        network_id,
+    )
+    .serialized_size();
+    let fee = Coin::from_u64_unchecked((size as f64 * fee_per_byte).ceil() as u64);
+    if share <= fee {
+        return None;
+    }
+
+    Some(TransactionBuilder::new_basic(
+        fee_key,
+        split.recipient.clone(),
+        share - fee,
+        fee,
+        validity_start_height,
+        network_id,
+    ))
+}
+
+impl RewardDistributionStats {
+    /// Records a payout, so that it is distributed only once.
+    pub(crate) fn record_payout(&mut self, block_number: u32, reward: Coin) {
+        self.rewards_received += reward;
+        self.last_payout_block = Some(block_number);
+    }
+
+    /// Records a distribution transaction once it was sent successfully.
+    pub(crate) fn record_distribution(&mut self, split: &RewardSplit, transaction: &Transaction) {
+        let stats = self.recipients.entry(split.recipient.clone()).or_default();
+        stats.distributed += transaction.value;
+        stats.num_transactions += 1;
+        stats.last_transaction = Some(transaction.hash());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use keys::SecureGenerate;
+
+    use super::*;
+
+    fn split(basis_points: u16) -> RewardSplit {
+        RewardSplit {
+            recipient: Address::from([1u8; Address::SIZE]),
+            basis_points,
+        }
+    }
+
+    #[test]
+    fn distributions_pay_their_fee_out_of_the_share() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let fee_key = KeyPair::generate(&mut rng);
+        let reward = Coin::from_u64_unchecked(100_000);
+
+        let transaction = distribution_transaction(
+            &fee_key,
+            &split(2_500),
+            reward,
+            DISTRIBUTION_FEE_PER_BYTE,
+            1,
+            NetworkId::UnitAlbatross,
+        )
+        .unwrap();
+        assert!(!transaction.fee.is_zero());
+        assert!(transaction.fee_per_byte() >= DISTRIBUTION_FEE_PER_BYTE);
+        assert_eq!(
+            transaction.value + transaction.fee,
+            Coin::from_u64_unchecked(25_000)
+        );
+        assert!(transaction.verify(NetworkId::UnitAlbatross).is_ok());
+
+        // A share that doesn't cover the fee isn't distributed.
+        assert!(distribution_transaction(
+            &fee_key,
+            &split(1),
+            reward,
+            DISTRIBUTION_FEE_PER_BYTE,
+            1,
+            NetworkId::UnitAlbatross,
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn distributions_are_recorded_separately_from_the_payout() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let fee_key = KeyPair::generate(&mut rng);
+        let split = split(5_000);
+        let transaction = distribution_transaction(
+            &fee_key,
+            &split,
+            Coin::from_u64_unchecked(100_000),
+            DISTRIBUTION_FEE_PER_BYTE,
+            1,
+            NetworkId::UnitAlbatross,
+        )
+        .unwrap();
+
+        let mut stats = RewardDistributionStats::default();
+        stats.record_payout(32, Coin::from_u64_unchecked(100_000));
+        assert_eq!(stats.last_payout_block, Some(32));
+        assert!(stats.recipients.is_empty());
+
+        stats.record_distribution(&split, &transaction);
+        let recipient = &stats.recipients[&split.recipient];
+        assert_eq!(recipient.distributed, transaction.value);
+        assert_eq!(recipient.num_transactions, 1);
+        assert_eq!(recipient.last_transaction, Some(transaction.hash()));
+    }
+}
@@ -1,4 +1,3 @@
-use std::ops::Deref;
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -8,15 +7,14 @@ use futures::{
     future::{BoxFuture, FutureExt},
     stream::{BoxStream, StreamExt},
 };
-use parking_lot::RwLock;
 
 use block::{
-    Block, BlockHeader, MacroBlock, MacroBody, MacroHeader, MultiSignature,
-    SignedTendermintProposal, TendermintProof, TendermintProposal,
+    MacroBlock, MacroBody, MacroHeader, MultiSignature, SignedTendermintProposal, TendermintProof,
+    TendermintProposal,
 };
 use block_production::BlockProducer;
-use blockchain::{AbstractBlockchain, Blockchain};
-use bls::PublicKey;
+use blockchain::{AbstractBlockchain, BlockchainLock};
+use bls::{CompressedPublicKey, PublicKey};
 use hash::{Blake2bHash, Blake2sHash, Hash};
 use nimiq_network_interface::network::MsgAcceptance;
 use nimiq_validator_network::ValidatorNetwork;
@@ -31,7 +29,9 @@ use tendermint_protocol::{
 use utils::time::OffsetTime;
 use vrf::VrfSeed;
 
+use crate::aggregation::gossip::{LevelUpdateGossip, TendermintUpdateTopic};
 use crate::aggregation::tendermint::HandelTendermintAdapter;
+use crate::proposal::{ProposalValidator, ProposalValidity};
 use crate::validator::ProposalTopic;
 
 /// The struct that interfaces with the Tendermint crate. It only has to implement the
@@ -52,15 +52,21 @@ pub struct TendermintInterface<TValidatorNetwork: ValidatorNetwork> {
     // The validators for the current epoch.
     pub current_validators: Validators,
     // The main blockchain struct. Contains all of this validator information about the current chain.
-    pub blockchain: Arc<RwLock<Blockchain>>,
+    pub blockchain: Arc<BlockchainLock>,
     // The aggregation adapter allows Tendermint to use Handel functions and networking.
     pub aggregation_adapter: HandelTendermintAdapter<TValidatorNetwork>,
+    // Validates received proposals and caches the results, so proposals repeated in later rounds
+    // are not validated again.
+    proposal_validator: ProposalValidator,
     // Just a field to temporarily store a block body. Since the body of a macro block is completely
     // deterministic, our Tendermint proposal only contains the block header. If the validator needs
     // the body, it is supposed for him to calculate it from the header and his current state.
     // However, calculating the body is an expensive operation. To avoid having to calculate the
     // body several times, we can cache it here.
     pub cache_body: Option<MacroBody>,
+    // Whether we already connected to the validators elected by the block. The election block is
+    // proposed in every round until it is decided, but we only need to connect once.
+    next_validators_preconnected: bool,
 
     proposal_stream: BoxStream<
         'static,
@@ -128,6 +134,7 @@ impl<TValidatorNetwork: ValidatorNetwork + 'static> TendermintOutsideDeps
 
         // Cache the block body and hash for future use.
         self.cache_body = block.body;
+        self.preconnect_next_validators();
 
         // Return the block header as the proposal.
         Ok(block.header)
@@ -264,91 +271,48 @@ impl<TValidatorNetwork: ValidatorNetwork + 'static> TendermintOutsideDeps
             }
         };
 
-        let (acceptance, valid_round, header) = {
-            let blockchain = self.blockchain.read();
+        // Get the header and valid round from the proposal.
+        let header = proposal.value;
+        let valid_round = proposal.valid_round;
 
-            // Get the header and valid round from the proposal.
-            let header = proposal.value;
-            let valid_round = proposal.valid_round;
-
-            // In case the proposal has a valid round, the original proposer signed the VRF Seed,
-            // so the original slot owners key must be retrieved for header verification.
-            // View numbers in macro blocks denote the original proposers round.
-            let vrf_key = if valid_round.is_some() {
-                let proposer_slot = blockchain
-                    .get_proposer_at(
-                        self.block_height,
-                        header.view_number,
-                        self.prev_seed.entropy(),
-                        None,
-                    )
-                    .expect("Couldn't find slot owner!");
-
-                proposer_slot.validator.signing_key
-            } else {
-                proposer_signing_key
-            };
-
-            // Check the validity of the block header. If it is invalid, we return a proposal timeout
-            // right here. This doesn't check anything that depends on the blockchain state.
-            if Blockchain::verify_block_header(
-                blockchain.deref(),
-                &BlockHeader::Macro(header.clone()),
-                &vrf_key,
-                None,
-                true,
-            )
-            .is_err()
-            {
-                debug!("Tendermint - await_proposal: Invalid block header");
-                (MsgAcceptance::Reject, valid_round, None)
-            } else {
-                let mut acceptance = MsgAcceptance::Accept;
-
-                // Get a write transaction to the database.
-                let mut txn = blockchain.write_transaction();
-
-                // Get the blockchain state.
-                let state = blockchain.state();
-
-                // Create a block with just our header.
-                let block = Block::Macro(MacroBlock {
-                    header: header.clone(),
-                    body: None,
-                    justification: None,
-                });
-
-                // Update our blockchain state using the received proposal. If we can't update the state, we
-                // return a proposal timeout.
-                // FIXME Is first_view_number = 0 correct here? Does it matter?
-                if blockchain
-                    .commit_accounts(state, &block, self.prev_seed.entropy(), 0, &mut txn)
-                    .is_err()
-                {
-                    debug!("Tendermint - await_proposal: Can't update state");
-                    acceptance = MsgAcceptance::Reject;
-                } else {
-                    // Check the validity of the block against our state. If it is invalid, we return a proposal
-                    // timeout. This also returns the block body that matches the block header
-                    // (assuming that the block is valid).
-                    let block_state = blockchain.verify_block_state(state, &block, Some(&txn));
-
-                    if let Ok(body) = block_state {
-                        // Cache the body that we calculated.
-                        self.cache_body = body;
-                    } else if let Err(err) = block_state {
-                        debug!(
-                            "Tendermint - await_proposal: Invalid block state: {:?}",
-                            err
-                        );
-                        acceptance = MsgAcceptance::Reject;
-                    }
-                }
+        // In case the proposal has a valid round, the original proposer signed the VRF Seed,
+        // so the original slot owners key must be retrieved for header verification.
+        // View numbers in macro blocks denote the original proposers round.
+        let vrf_key = if valid_round.is_some() {
+            let blockchain = self.blockchain.read();
+            let proposer_slot = blockchain
+                .get_proposer_at(
+                    self.block_height,
+                    header.view_number,
+                    self.prev_seed.entropy(),
+                    None,
+                )
+                .expect("Couldn't find slot owner!");
 
-                // Abort the transaction so that we don't commit the changes we made to the blockchain state.
-                txn.abort();
+            proposer_slot.validator.signing_key
+        } else {
+            proposer_signing_key
+        };
 
-                (acceptance, valid_round, Some(header))
+        // Validate the proposal. If it was proposed in an earlier round already, the cached result is used.
+        let (acceptance, header) = match self
+            .proposal_validator
+            .validate(header.clone(), vrf_key)
+            .await
+        {
+            ProposalValidity::Valid(body) => {
+                // Cache the body that we calculated.
+                self.cache_body = body;
+                self.preconnect_next_validators();
+                (MsgAcceptance::Accept, Some(header))
+            }
+            ProposalValidity::InvalidHeader => {
+                debug!("Tendermint - await_proposal: Invalid block header");
+                (MsgAcceptance::Reject, None)
+            }
+            ProposalValidity::InvalidState => {
+                debug!("Tendermint - await_proposal: Invalid block state");
+                (MsgAcceptance::Reject, Some(header))
             }
         };
 
@@ -453,13 +417,41 @@ impl<TValidatorNetwork: ValidatorNetwork + 'static> TendermintInterface<TValidat
         unreachable!()
     }
 
+    /// If the cached body is the body of an election block, connects to the validators it elects.
+    /// This way, the connections are established before the next epoch starts and its first
+    /// rounds don't stall.
+    fn preconnect_next_validators(&mut self) {
+        if self.next_validators_preconnected {
+            return;
+        }
+
+        let voting_keys: Vec<CompressedPublicKey> = match self
+            .cache_body
+            .as_ref()
+            .and_then(|body| body.validators.as_ref())
+        {
+            Some(validators) => validators
+                .iter()
+                .map(|validator| validator.voting_key.compressed().clone())
+                .collect(),
+            None => return,
+        };
+        self.next_validators_preconnected = true;
+
+        let network = Arc::clone(&self.network);
+        tokio::spawn(async move {
+            network.preconnect(voting_keys).await;
+        });
+    }
+
     pub fn new(
         validator_slot_band: u16,
         active_validators: Validators,
         prev_seed: VrfSeed,
         block_height: u32,
         network: Arc<TValidatorNetwork>,
-        blockchain: Arc<RwLock<Blockchain>>,
+        gossip: Arc<LevelUpdateGossip<TValidatorNetwork, TendermintUpdateTopic>>,
+        blockchain: Arc<BlockchainLock>,
         block_producer: BlockProducer,
         proposal_stream: BoxStream<
             'static,
@@ -476,9 +468,12 @@ impl<TValidatorNetwork: ValidatorNetwork + 'static> TendermintInterface<TValidat
             active_validators.clone(),
             block_height,
             network.clone(),
+            gossip,
             block_producer.voting_key.secret_key,
         );
 
+        let proposal_validator = ProposalValidator::new(Arc::clone(&blockchain), prev_seed.clone());
+
         // Create the instance and return it.
         Self {
             network,
@@ -490,7 +485,9 @@ impl<TValidatorNetwork: ValidatorNetwork + 'static> TendermintInterface<TValidat
             current_validators: active_validators,
             blockchain,
             aggregation_adapter,
+            proposal_validator,
             cache_body: None,
+            next_validators_preconnected: false,
             proposal_stream,
             initial_round,
         }
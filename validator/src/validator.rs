@@ -16,12 +16,11 @@ use block_production::BlockProducer;
 use blockchain::{AbstractBlockchain, Blockchain, BlockchainEvent, ForkEvent, PushResult};
 use bls::{CompressedPublicKey, KeyPair as BlsKeyPair};
 use consensus::{sync::block_queue::BlockTopic, Consensus, ConsensusEvent, ConsensusProxy};
-use database::{Database, Environment, ReadTransaction, WriteTransaction};
 use hash::{Blake2bHash, Hash};
 use keys::{Address, KeyPair as SchnorrKeyPair};
 use mempool::{config::MempoolConfig, mempool::Mempool};
 use network_interface::{
-    network::{Network, PubsubId, Topic},
+    network::{Network, OverflowPolicy, PubsubId, Topic},
     peer::Peer,
 };
 use primitives::coin::Coin;
@@ -29,10 +28,16 @@ use primitives::policy;
 use tendermint_protocol::TendermintReturn;
 use transaction_builder::TransactionBuilder;
 use utils::observer::NotifierStream;
+use utils::shared_rng::SharedRng;
 use validator_network::ValidatorNetwork;
 
+use crate::aggregation::gossip::AggregationGossip;
+use crate::aggregation::view_change::ViewChangeProofCache;
 use crate::micro::{ProduceMicroBlock, ProduceMicroBlockEvent};
-use crate::r#macro::{PersistedMacroState, ProduceMacroBlock};
+use crate::r#macro::{MacroStateStore, PersistedMacroState, ProduceMacroBlock};
+use crate::reward::{
+    distribution_transactions, RewardDistributionStats, RewardSplit, DISTRIBUTION_FEE_PER_BYTE,
+};
 use crate::slash::ForkProofPool;
 
 pub struct ProposalTopic;
@@ -43,6 +48,9 @@ impl Topic for ProposalTopic {
     const BUFFER_SIZE: usize = 8;
     const NAME: &'static str = "tendermint-proposal";
     const VALIDATE: bool = true;
+    /// Proposals of past rounds are useless once a newer one arrived.
+    const OVERFLOW_POLICY: OverflowPolicy = OverflowPolicy::DropOld;
+    const PERSIST_SEEN: bool = true;
 }
 
 #[derive(PartialEq)]
@@ -83,6 +91,7 @@ pub struct ValidatorProxy {
     pub signing_key: Arc<RwLock<SchnorrKeyPair>>,
     pub voting_key: Arc<RwLock<BlsKeyPair>>,
     pub fee_key: Arc<RwLock<SchnorrKeyPair>>,
+    pub reward_distribution: Arc<RwLock<RewardDistributionStats>>,
 }
 
 impl Clone for ValidatorProxy {
@@ -92,6 +101,7 @@ impl Clone for ValidatorProxy {
             signing_key: Arc::clone(&self.signing_key),
             voting_key: Arc::clone(&self.voting_key),
             fee_key: Arc::clone(&self.fee_key),
+            reward_distribution: Arc::clone(&self.reward_distribution),
         }
     }
 }
@@ -99,14 +109,16 @@ impl Clone for ValidatorProxy {
 pub struct Validator<TNetwork: Network, TValidatorNetwork: ValidatorNetwork + 'static> {
     pub consensus: ConsensusProxy<TNetwork>,
     network: Arc<TValidatorNetwork>,
+    aggregation_gossip: AggregationGossip<TValidatorNetwork>,
+    view_change_proofs: Arc<ViewChangeProofCache>,
 
-    database: Database,
-    env: Environment,
+    macro_state_store: MacroStateStore,
 
     validator_address: Arc<RwLock<Address>>,
     signing_key: Arc<RwLock<SchnorrKeyPair>>,
     voting_key: Arc<RwLock<BlsKeyPair>>,
     fee_key: Arc<RwLock<SchnorrKeyPair>>,
+    reward_distribution: Arc<RwLock<RewardDistributionStats>>,
 
     proposal_receiver: ProposalReceiver<TValidatorNetwork>,
 
@@ -126,13 +138,19 @@ pub struct Validator<TNetwork: Network, TValidatorNetwork: ValidatorNetwork + 's
 
     pub mempool: Arc<Mempool>,
     mempool_state: MempoolState,
+
+    /// How long expensive sync requests are refused and the mempool is prioritized once it is our
+    /// turn to produce a block.
+    production_window: Duration,
+
+    /// Draws the view change jitter and the order in which the peers of an aggregation are
+    /// contacted.
+    rng: SharedRng,
 }
 
 impl<TNetwork: Network, TValidatorNetwork: ValidatorNetwork>
     Validator<TNetwork, TValidatorNetwork>
 {
-    const MACRO_STATE_DB_NAME: &'static str = "ValidatorState";
-    const MACRO_STATE_KEY: &'static str = "validatorState";
     const VIEW_CHANGE_DELAY: Duration = Duration::from_secs(10);
     const FORK_PROOFS_MAX_SIZE: usize = 1_000; // bytes
 
@@ -162,31 +180,32 @@ impl<TNetwork: Network, TValidatorNetwork: ValidatorNetwork>
             fork_proofs: ForkProofPool::new(),
         };
 
-        let env = consensus.env.clone();
-        let database = env.open_database(Self::MACRO_STATE_DB_NAME.to_string());
-
-        let macro_state: Option<PersistedMacroState<TValidatorNetwork>> = {
-            let read_transaction = ReadTransaction::new(&env);
-            read_transaction.get(&database, Self::MACRO_STATE_KEY)
-        };
+        let macro_state_store = MacroStateStore::new(consensus.env.clone());
+        let macro_state = macro_state_store.load();
 
         let network1 = Arc::clone(&network);
         let (proposal_sender, proposal_receiver) = ProposalBuffer::new();
 
+        // Level updates are relayed over gossip if validators can't reach each other directly.
+        let aggregation_gossip =
+            AggregationGossip::new(Arc::clone(&network), consensus.blockchain.clone());
+
         let mempool = Arc::new(Mempool::new(consensus.blockchain.clone(), mempool_config));
         let mempool_state = MempoolState::Inactive;
 
         let mut this = Self {
             consensus: consensus.proxy(),
             network,
+            aggregation_gossip,
+            view_change_proofs: Arc::new(ViewChangeProofCache::default()),
 
-            database,
-            env,
+            macro_state_store,
 
             validator_address: Arc::new(RwLock::new(validator_address)),
             signing_key: Arc::new(RwLock::new(signing_key)),
             voting_key: Arc::new(RwLock::new(voting_key)),
             fee_key: Arc::new(RwLock::new(fee_key)),
+            reward_distribution: Arc::new(RwLock::new(RewardDistributionStats::default())),
 
             proposal_receiver,
 
@@ -206,6 +225,10 @@ impl<TNetwork: Network, TValidatorNetwork: ValidatorNetwork>
 
             mempool: Arc::clone(&mempool),
             mempool_state,
+
+            production_window: Duration::ZERO,
+
+            rng: SharedRng::os(),
         };
         this.init();
 
@@ -299,6 +322,7 @@ impl<TNetwork: Network, TValidatorNetwork: ValidatorNetwork>
 
     fn init_block_producer(&mut self) {
         if !self.is_active() {
+            self.close_production_window();
             return;
         }
 
@@ -306,6 +330,7 @@ impl<TNetwork: Network, TValidatorNetwork: ValidatorNetwork>
         let head = blockchain.head();
         let next_block_number = head.block_number() + 1;
         let next_view_number = head.next_view_number();
+        self.update_production_window(&blockchain, next_block_number, next_view_number);
         let block_producer = BlockProducer::new(self.signing_key(), self.voting_key());
 
         log::debug!(
@@ -337,6 +362,7 @@ impl<TNetwork: Network, TValidatorNetwork: ValidatorNetwork>
                 self.macro_producer = Some(ProduceMacroBlock::new(
                     Arc::clone(&self.consensus.blockchain),
                     Arc::clone(&self.network),
+                    Arc::clone(&self.aggregation_gossip.tendermint),
                     block_producer,
                     self.validator_slot_band(),
                     active_validators,
@@ -366,6 +392,8 @@ impl<TNetwork: Network, TValidatorNetwork: ValidatorNetwork>
                     Arc::clone(&self.consensus.blockchain),
                     Arc::clone(&self.mempool),
                     Arc::clone(&self.network),
+                    Arc::clone(&self.aggregation_gossip.view_changes),
+                    Arc::clone(&self.view_change_proofs),
                     block_producer,
                     self.validator_slot_band(),
                     fork_proofs,
@@ -375,11 +403,56 @@ impl<TNetwork: Network, TValidatorNetwork: ValidatorNetwork>
                     self.micro_state.view_change_proof.clone(),
                     self.micro_state.view_change.clone(),
                     Self::VIEW_CHANGE_DELAY,
+                    self.rng.clone(),
                 ));
             }
         }
     }
 
+    /// Opens the production window if we produce the block at the given view, closes it
+    /// otherwise.
+    fn update_production_window(
+        &self,
+        blockchain: &Blockchain,
+        block_number: u32,
+        view_number: u32,
+    ) {
+        if self.production_window.is_zero() {
+            return;
+        }
+
+        let is_our_turn = blockchain
+            .get_proposer_at(
+                block_number,
+                view_number,
+                blockchain.head().seed().entropy(),
+                None,
+            )
+            .map_or(false, |slot| slot.band == self.validator_slot_band());
+
+        if is_our_turn {
+            log::debug!(
+                "Producing #{}.{}, prioritizing the mempool over expensive sync requests for {:?}",
+                block_number,
+                view_number,
+                self.production_window
+            );
+            self.consensus
+                .production_window()
+                .open(self.production_window);
+            self.mempool
+                .production_window()
+                .open(self.production_window);
+        } else {
+            self.close_production_window();
+        }
+    }
+
+    fn close_production_window(&self) {
+        self.consensus.production_window().close();
+        self.mempool.production_window().close();
+    }
+
     fn on_blockchain_event(&mut self, event: BlockchainEvent) {
         match event {
             BlockchainEvent::Extended(ref hash) => self.on_blockchain_extended(hash),
@@ -402,6 +475,12 @@ impl<TNetwork: Network, TValidatorNetwork: ValidatorNetwork>
             .get_block(hash, true, None)
             .expect("Head block not found");
 
+        // Once the macro block is on the chain, the persisted Tendermint state is obsolete.
+        if self.macro_state_store.on_block_accepted(&block) {
+            self.macro_state = None;
+            self.distribute_rewards(&block);
+        }
+
         // Update mempool and blockchain state
         self.blockchain_state.fork_proofs.apply_block(&block);
         self.mempool
@@ -491,7 +570,6 @@ impl<TNetwork: Network, TValidatorNetwork: ValidatorNetwork>
                 // In case of a new state update we need to store the new version of it disregarding
                 // any old state which potentially still lingers.
                 TendermintReturn::StateUpdate(update) => {
-                    let mut write_transaction = WriteTransaction::new(&self.env);
                     let persistable_state = PersistedMacroState::<TValidatorNetwork> {
                         height: self.consensus.blockchain.read().block_number() + 1,
                         step: update.step.into(),
@@ -502,14 +580,7 @@ impl<TNetwork: Network, TValidatorNetwork: ValidatorNetwork>
                         valid_value: update.valid_value,
                     };
 
-                    write_transaction.put::<str, Vec<u8>>(
-                        &self.database,
-                        Self::MACRO_STATE_KEY,
-                        &beserial::Serialize::serialize_to_vec(&persistable_state),
-                    );
-
-                    write_transaction.commit();
-
+                    self.macro_state_store.store(&persistable_state);
                     self.macro_state = Some(persistable_state);
                 }
             }
@@ -517,10 +588,18 @@ impl<TNetwork: Network, TValidatorNetwork: ValidatorNetwork>
     }
 
     fn poll_micro(&mut self, cx: &mut Context<'_>) {
+        let mut view_changed = false;
         let micro_producer = self.micro_producer.as_mut().unwrap();
         while let Poll::Ready(Some(event)) = micro_producer.poll_next_unpin(cx) {
             match event {
-                ProduceMicroBlockEvent::MicroBlock(block, result) => {
+                ProduceMicroBlockEvent::MicroBlock(block, result, degradation) => {
+                    if let Some(degradation) = degradation {
+                        warn!(
+                            "Produced micro block #{}.{} without all transactions of the mempool: {}",
+                            block.header.block_number, block.header.view_number, degradation
+                        );
+                    }
+
                     if result == PushResult::Extended || result == PushResult::Rebranched {
                         // Todo get rid of spawn
                         let network = self.network.clone();
@@ -539,9 +618,20 @@ impl<TNetwork: Network, TValidatorNetwork: ValidatorNetwork>
                     self.micro_state.view_number = view_change.new_view_number; // needed?
                     self.micro_state.view_change_proof = Some(view_change_proof);
                     self.micro_state.view_change = Some(view_change);
+                    view_changed = true;
                 }
             }
         }
+
+        // The producer of the new view might be us.
+        if view_changed {
+            let blockchain = self.consensus.blockchain.read();
+            self.update_production_window(
+                &blockchain,
+                blockchain.block_number() + 1,
+                self.micro_state.view_number,
+            );
+        }
     }
 
     fn is_active(&self) -> bool {
@@ -607,6 +697,81 @@ impl<TNetwork: Network, TValidatorNetwork: ValidatorNetwork>
         }
     }
 
+    /// Forwards the configured shares of the rewards paid out in the given macro block.
+    fn distribute_rewards(&self, block: &Block) {
+        let splits = self.reward_distribution.read().splits.clone();
+        if splits.is_empty() {
+            return;
+        }
+
+        // Macro blocks can be reported several times, e.g. as finalized and as epoch finalized.
+        if let Some(last_payout_block) = self.reward_distribution.read().last_payout_block {
+            if block.block_number() <= last_payout_block {
+                return;
+            }
+        }
+
+        let blockchain = self.consensus.blockchain.read();
+        let (reward, transactions) = match distribution_transactions(
+            &*blockchain,
+            block,
+            &self.validator_address(),
+            &self.fee_key(),
+            &splits,
+            f64::max(
+                self.mempool.get_min_relay_fee_per_byte(),
+                DISTRIBUTION_FEE_PER_BYTE,
+            ),
+        ) {
+            Some(distribution) => distribution,
+            None => return,
+        };
+        drop(blockchain);
+
+        // The payout is recorded right away so that it isn't distributed twice, the distributions
+        // only once they were sent.
+        self.reward_distribution
+            .write()
+            .record_payout(block.block_number(), reward);
+
+        let cn = self.consensus.clone();
+        let reward_distribution = Arc::clone(&self.reward_distribution);
+        tokio::spawn(async move {
+            for (split, transaction) in transactions {
+                debug!(
+                    "Sending reward distribution of {} to {}",
+                    transaction.value, split.recipient
+                );
+                match cn.send_transaction(transaction.clone()).await {
+                    Ok(_) => reward_distribution
+                        .write()
+                        .record_distribution(&split, &transaction),
+                    Err(_) => error!(
+                        "Failed to send reward distribution transaction to {}",
+                        split.recipient
+                    ),
+                }
+            }
+        });
+    }
+
+    /// Sets the recipients that receive a share of the validator rewards.
+    pub fn set_reward_splits(&mut self, splits: Vec<RewardSplit>) {
+        self.reward_distribution.write().splits = splits;
+    }
+
+    /// Sets how long requests for batch sets and history chunks are answered with a busy response
+    /// once it is our turn to produce a block. Zero disables it.
+    pub fn set_production_window(&mut self, production_window: Duration) {
+        self.production_window = production_window;
+    }
+
+    /// Replaces the randomness of the validator, e.g. with a seeded generator to make tests
+    /// reproducible.
+    pub fn set_rng(&mut self, rng: SharedRng) {
+        self.rng = rng;
+    }
+
     pub fn validator_slot_band(&self) -> u16 {
         self.epoch_state
             .as_ref()
@@ -636,6 +801,7 @@ impl<TNetwork: Network, TValidatorNetwork: ValidatorNetwork>
             signing_key: Arc::clone(&self.signing_key),
             voting_key: Arc::clone(&self.voting_key),
             fee_key: Arc::clone(&self.fee_key),
+            reward_distribution: Arc::clone(&self.reward_distribution),
         }
     }
 }
@@ -661,6 +827,14 @@ impl<TNetwork: Network, TValidatorNetwork: ValidatorNetwork> Future
                     }
                 }
                 Ok(ConsensusEvent::Lost) => {
+                    // Pause block production until consensus is established again. The producers
+                    // are recreated by `init` then, a running Tendermint instance resumes from its
+                    // persisted state.
+                    info!("Consensus lost, pausing block production");
+                    self.macro_producer = None;
+                    self.micro_producer = None;
+                    self.close_production_window();
+
                     if let MempoolState::Active = self.mempool_state {
                         let mempool = Arc::clone(&self.mempool);
                         let network = Arc::clone(&self.consensus.network);
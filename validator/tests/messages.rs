@@ -0,0 +1,100 @@
+use proptest::prelude::*;
+
+use beserial::schema::skip;
+use nimiq_network_interface::message::registry::{
+    duplicate_type_ids, snapshot, Definitions, MessageSchema,
+};
+
+/// The message types of all crates, like in the network registry of the client.
+fn all_messages() -> Vec<MessageSchema> {
+    nimiq_consensus::messages::MESSAGES
+        .iter()
+        .chain(nimiq_validator::aggregation::MESSAGES)
+        .chain(nimiq_validator_network::MESSAGES)
+        .chain(nimiq_mempool::sync::MESSAGES)
+        .copied()
+        .collect()
+}
+
+#[test]
+fn message_type_ids_are_unique() {
+    assert_eq!(duplicate_type_ids(&all_messages()), vec![]);
+}
+
+#[test]
+fn wire_format_matches_snapshot() {
+    assert_eq!(
+        snapshot(&all_messages()),
+        include_str!("messages.snapshot"),
+        "The wire format of a network message changed. If this is intended, update tests/messages.snapshot."
+    );
+}
+
+fn definitions() -> Definitions {
+    let mut definitions = Definitions::new();
+    for schema in all_messages() {
+        (schema.collect)(&mut definitions);
+    }
+    definitions
+}
+
+/// Reads the message as described by its schema and checks that all of it was read.
+fn skips_exactly(definitions: &Definitions, schema: &MessageSchema, data: &[u8]) -> bool {
+    let reader = &mut &data[..];
+    skip(definitions, &(schema.type_name)(), reader).is_ok() && reader.is_empty()
+}
+
+#[test]
+fn schemas_match_the_samples() {
+    let definitions = definitions();
+    for schema in all_messages() {
+        if let Some(sample) = schema.sample {
+            assert!(
+                skips_exactly(&definitions, &schema, &sample()),
+                "{}",
+                schema.name
+            );
+        }
+    }
+}
+
+#[test]
+fn samples_round_trip() {
+    for schema in all_messages() {
+        if let Some(sample) = schema.sample {
+            let data = sample();
+            assert_eq!((schema.round_trip)(&data), Some(data), "{}", schema.name);
+        }
+    }
+}
+
+proptest! {
+    // Random data rarely is a valid message, but the simple messages are hit regularly.
+    #[test]
+    fn random_messages_round_trip(data in proptest::collection::vec(any::<u8>(), 0..256)) {
+        let definitions = definitions();
+        for schema in all_messages() {
+            if let Some(serialized) = (schema.round_trip)(&data) {
+                prop_assert!(skips_exactly(&definitions, &schema, &serialized), "{}", schema.name);
+                prop_assert_eq!((schema.round_trip)(&serialized), Some(serialized), "{}", schema.name);
+            }
+        }
+    }
+
+    #[test]
+    fn mutated_samples_round_trip(mutations in proptest::collection::vec((any::<usize>(), any::<u8>()), 1..4)) {
+        for schema in all_messages() {
+            if let Some(sample) = schema.sample {
+                let mut data = sample();
+                for (index, value) in &mutations {
+                    let len = data.len();
+                    data[index % len] = *value;
+                }
+
+                if let Some(serialized) = (schema.round_trip)(&data) {
+                    prop_assert_eq!((schema.round_trip)(&serialized), Some(serialized), "{}", schema.name);
+                }
+            }
+        }
+    }
+}
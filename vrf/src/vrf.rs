@@ -16,7 +16,7 @@ use rand::prelude::*;
 use serde_big_array::big_array;
 use sha2::{Digest, Sha256, Sha512};
 
-use beserial::{Deserialize, Serialize, SerializingError};
+use beserial::{Deserialize, Schema, Serialize, SerializingError, TypeSchema};
 use nimiq_hash::{Blake2bHash, Blake2bHasher, HashOutput, Hasher};
 use nimiq_keys::{KeyPair, PublicKey};
 
@@ -262,6 +262,16 @@ impl Deserialize for VrfSeed {
     }
 }
 
+impl Schema for VrfSeed {
+    fn name() -> String {
+        "VrfSeed".to_string()
+    }
+
+    fn schema() -> TypeSchema {
+        TypeSchema::Bytes { len: VrfSeed::SIZE }
+    }
+}
+
 pub struct VrfRng {
     entropy: VrfEntropy,
     use_case: VrfUseCase,
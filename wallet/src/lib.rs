@@ -6,8 +6,10 @@ extern crate nimiq_keys as keys;
 extern crate nimiq_primitives as primitives;
 extern crate nimiq_transaction as transaction;
 
+pub use resubmission::{PendingTransactions, ResubmissionEvent, ResubmissionPolicy};
 pub use wallet_account::WalletAccount;
 pub use wallet_store::WalletStore;
 
+mod resubmission;
 mod wallet_account;
 mod wallet_store;
@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+use keys::Address;
+use nimiq_hash::{Blake2bHash, Hash};
+use primitives::coin::Coin;
+use primitives::policy;
+use transaction::Transaction;
+
+use crate::WalletAccount;
+
+/// Decides how transactions sent from a wallet are handled when they are about to expire without
+/// having been included in a block.
+///
+/// A transaction can only be replaced safely once it can't be included anymore, otherwise both the
+/// old and the new transaction might end up in the chain. Micro blocks can still be reverted, so
+/// expiring transactions are only announced while they are still valid and resubmitted once a
+/// final macro block is past their validity window.
+#[derive(Clone, Debug)]
+pub struct ResubmissionPolicy {
+    /// A pending transaction is announced as expiring once fewer than this many blocks of its
+    /// validity window are left.
+    pub blocks_before_expiry: u32,
+    /// Whether expired transactions are re-signed with a new validity start height and sent again.
+    pub resubmit: bool,
+    /// The fee of a resubmitted transaction is increased by this many percent of its previous fee.
+    pub fee_bump_percent: u32,
+    /// The number of times a transaction is resubmitted before it is given up.
+    pub max_resubmissions: u32,
+}
+
+impl Default for ResubmissionPolicy {
+    fn default() -> Self {
+        Self {
+            blocks_before_expiry: 600,
+            resubmit: true,
+            fee_bump_percent: 0,
+            max_resubmissions: 3,
+        }
+    }
+}
+
+/// What happened to a pending transaction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ResubmissionEvent {
+    /// The transaction was included in a block. It is tracked until its inclusion is final.
+    Confirmed { hash: Blake2bHash },
+    /// The block that included the transaction was reverted, it is pending again.
+    Reverted { hash: Blake2bHash },
+    /// The transaction hasn't been included yet and expires at the given block number.
+    Expiring { hash: Blake2bHash, expires_at: u32 },
+    /// The transaction expired and was replaced by the given transaction, which must be sent to
+    /// the network.
+    Resubmitted {
+        old_hash: Blake2bHash,
+        transaction: Transaction,
+    },
+    /// The transaction expired without being included and is not tracked anymore.
+    Expired { hash: Blake2bHash },
+}
+
+struct PendingTransaction {
+    transaction: Transaction,
+    resubmissions: u32,
+    expiring_announced: bool,
+    confirmed: bool,
+}
+
+impl PendingTransaction {
+    /// The first block number at which the transaction can't be included anymore.
+    fn expires_at(&self) -> u32 {
+        self.transaction.validity_start_height + policy::TRANSACTION_VALIDITY_WINDOW
+    }
+}
+
+/// The transactions sent from a wallet that haven't been included in a block yet.
+pub struct PendingTransactions {
+    policy: ResubmissionPolicy,
+    transactions: HashMap<Blake2bHash, PendingTransaction>,
+}
+
+impl PendingTransactions {
+    pub fn new(policy: ResubmissionPolicy) -> Self {
+        Self {
+            policy,
+            transactions: HashMap::new(),
+        }
+    }
+
+    pub fn policy(&self) -> &ResubmissionPolicy {
+        &self.policy
+    }
+
+    /// Starts tracking a transaction that was sent to the network.
+    pub fn track(&mut self, transaction: Transaction) {
+        self.insert(transaction, 0);
+    }
+
+    pub fn len(&self) -> usize {
+        self.transactions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transactions.is_empty()
+    }
+
+    /// Checks all pending transactions against the new head of the chain.
+    ///
+    /// `block_number` is the number of the head and `final_block_number` the number of the last
+    /// macro block. `is_included` tells whether a transaction was included in the chain. `wallet`
+    /// returns the unlocked wallet of a sender, if any. Expired transactions whose sender is locked
+    /// can't be re-signed and are given up.
+    ///
+    /// Included transactions are tracked until a macro block is past their validity window, so
+    /// transactions whose block was reverted by a rebranch are pending again.
+    pub fn on_head<I, W>(
+        &mut self,
+        block_number: u32,
+        final_block_number: u32,
+        is_included: I,
+        wallet: W,
+    ) -> Vec<ResubmissionEvent>
+    where
+        I: Fn(&Blake2bHash) -> bool,
+        W: Fn(&Address) -> Option<WalletAccount>,
+    {
+        let mut events = vec![];
+        let mut resubmitted = vec![];
+
+        let policy = &self.policy;
+        self.transactions.retain(|hash, pending| {
+            let expires_at = pending.expires_at();
+            // The last block that can include the transaction is final, so whether it was
+            // included can't change anymore.
+            let is_final = final_block_number + 1 >= expires_at;
+
+            if is_included(hash) {
+                if !pending.confirmed {
+                    pending.confirmed = true;
+                    events.push(ResubmissionEvent::Confirmed { hash: hash.clone() });
+                }
+                return !is_final;
+            }
+
+            if pending.confirmed {
+                pending.confirmed = false;
+                events.push(ResubmissionEvent::Reverted { hash: hash.clone() });
+            }
+
+            // The next block is the last one that can include the transaction.
+            if block_number + 1 < expires_at
+                && !pending.expiring_announced
+                && block_number + policy.blocks_before_expiry >= expires_at
+            {
+                pending.expiring_announced = true;
+                events.push(ResubmissionEvent::Expiring {
+                    hash: hash.clone(),
+                    expires_at,
+                });
+            }
+
+            if !is_final {
+                return true;
+            }
+
+            let account = wallet(&pending.transaction.sender);
+            match account {
+                Some(account)
+                    if policy.resubmit && pending.resubmissions < policy.max_resubmissions =>
+                {
+                    let transaction =
+                        Self::resign(&pending.transaction, &account, block_number, policy);
+                    events.push(ResubmissionEvent::Resubmitted {
+                        old_hash: hash.clone(),
+                        transaction: transaction.clone(),
+                    });
+                    resubmitted.push((transaction, pending.resubmissions + 1));
+                }
+                _ => events.push(ResubmissionEvent::Expired { hash: hash.clone() }),
+            }
+            false
+        });
+
+        for (transaction, resubmissions) in resubmitted {
+            self.insert(transaction, resubmissions);
+        }
+
+        events
+    }
+
+    fn insert(&mut self, transaction: Transaction, resubmissions: u32) {
+        self.transactions.insert(
+            transaction.hash(),
+            PendingTransaction {
+                transaction,
+                resubmissions,
+                expiring_announced: false,
+                confirmed: false,
+            },
+        );
+    }
+
+    /// Creates a copy of the transaction that is valid starting at the given block number, with
+    /// the fee bumped according to the policy.
+    fn resign(
+        transaction: &Transaction,
+        account: &WalletAccount,
+        block_number: u32,
+        policy: &ResubmissionPolicy,
+    ) -> Transaction {
+        let mut transaction = transaction.clone();
+        transaction.validity_start_height = block_number;
+
+        let fee = u64::from(transaction.fee);
+        let bump = fee.saturating_mul(u64::from(policy.fee_bump_percent)) / 100;
+        transaction.fee = Coin::try_from(fee.saturating_add(bump)).unwrap_or(transaction.fee);
+
+        account.sign_transaction(&mut transaction);
+        transaction
+    }
+}
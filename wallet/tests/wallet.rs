@@ -7,9 +7,11 @@ use lazy_static::lazy_static;
 
 use beserial::{Deserialize, Serialize};
 use keys::{Address, KeyPair, PrivateKey};
+use nimiq_hash::{Blake2bHash, Hash};
 use primitives::coin::Coin;
 use primitives::networks::NetworkId;
-use wallet::WalletAccount;
+use primitives::policy;
+use wallet::{PendingTransactions, ResubmissionEvent, ResubmissionPolicy, WalletAccount};
 
 lazy_static! {
     /// This is an example for using doc comment attributes
@@ -52,3 +54,128 @@ fn test_serialize_deserialize() {
         }
     }
 }
+
+#[test]
+fn test_resubmit_expired_transaction() {
+    let wallet = WALLET.clone();
+    let transaction = wallet.create_transaction(
+        Address::from_user_friendly_address("NQ16 C3HR 85U8 P7MK F52R E9RG SA3Y Q69C X563")
+            .unwrap(),
+        Coin::from_u64_unchecked(42),
+        Coin::from_u64_unchecked(100),
+        1,
+        NetworkId::Main,
+    );
+    let hash: Blake2bHash = transaction.hash();
+    let expires_at = 1 + policy::TRANSACTION_VALIDITY_WINDOW;
+
+    let mut pending = PendingTransactions::new(ResubmissionPolicy {
+        blocks_before_expiry: 10,
+        resubmit: true,
+        fee_bump_percent: 50,
+        max_resubmissions: 1,
+    });
+    pending.track(transaction);
+
+    let not_included = |_: &Blake2bHash| false;
+    let unlocked = |_: &Address| Some(WALLET.clone());
+
+    // Nothing happens while the transaction is far from expiring.
+    assert!(pending.on_head(100, 0, not_included, unlocked).is_empty());
+
+    // The transaction is announced once when it is about to expire.
+    let events = pending.on_head(expires_at - 10, 0, not_included, unlocked);
+    assert_eq!(
+        events,
+        vec![ResubmissionEvent::Expiring {
+            hash: hash.clone(),
+            expires_at,
+        }]
+    );
+    assert!(pending
+        .on_head(expires_at - 5, 0, not_included, unlocked)
+        .is_empty());
+
+    // It isn't replaced while the blocks that could include it might still be reverted.
+    assert!(pending
+        .on_head(expires_at + 5, 0, not_included, unlocked)
+        .is_empty());
+
+    // Once a macro block is past its validity window, it is re-signed with a new validity start
+    // height and a bumped fee.
+    let events = pending.on_head(expires_at + 5, expires_at - 1, not_included, unlocked);
+    let resubmitted = match &events[..] {
+        [ResubmissionEvent::Resubmitted {
+            old_hash,
+            transaction,
+        }] => {
+            assert_eq!(*old_hash, hash);
+            transaction.clone()
+        }
+        _ => panic!("Unexpected events: {:?}", events),
+    };
+    assert_eq!(resubmitted.validity_start_height, expires_at + 5);
+    assert_eq!(resubmitted.fee, Coin::from_u64_unchecked(150));
+    assert_eq!(Ok(()), resubmitted.verify(NetworkId::Main));
+    assert_eq!(pending.len(), 1);
+
+    // The resubmitted transaction is given up after the maximum number of resubmissions.
+    let resubmitted_hash: Blake2bHash = resubmitted.hash();
+    let expires_at = expires_at + 5 + policy::TRANSACTION_VALIDITY_WINDOW;
+    let events = pending.on_head(expires_at, expires_at, not_included, unlocked);
+    assert_eq!(
+        events,
+        vec![ResubmissionEvent::Expired {
+            hash: resubmitted_hash,
+        }]
+    );
+    assert!(pending.is_empty());
+}
+
+#[test]
+fn test_confirmed_transaction_is_not_resubmitted() {
+    let wallet = WALLET.clone();
+    let transaction = wallet.create_transaction(
+        Address::from_user_friendly_address("NQ16 C3HR 85U8 P7MK F52R E9RG SA3Y Q69C X563")
+            .unwrap(),
+        Coin::from_u64_unchecked(42),
+        Coin::ZERO,
+        1,
+        NetworkId::Main,
+    );
+    let hash: Blake2bHash = transaction.hash();
+    let expires_at = 1 + policy::TRANSACTION_VALIDITY_WINDOW;
+
+    let mut pending = PendingTransactions::new(ResubmissionPolicy::default());
+    pending.track(transaction);
+
+    let included = |included: &Blake2bHash| *included == hash;
+    let not_included = |_: &Blake2bHash| false;
+    let unlocked = |_: &Address| Some(WALLET.clone());
+
+    let events = pending.on_head(policy::TRANSACTION_VALIDITY_WINDOW, 0, included, unlocked);
+    assert_eq!(
+        events,
+        vec![ResubmissionEvent::Confirmed { hash: hash.clone() }]
+    );
+    assert_eq!(pending.len(), 1);
+
+    // A rebranch reverted the block that included the transaction, so it is pending again.
+    let events = pending.on_head(expires_at, 0, not_included, unlocked);
+    assert_eq!(
+        events,
+        vec![ResubmissionEvent::Reverted { hash: hash.clone() }]
+    );
+
+    let events = pending.on_head(expires_at, 0, included, unlocked);
+    assert_eq!(
+        events,
+        vec![ResubmissionEvent::Confirmed { hash: hash.clone() }]
+    );
+
+    // The transaction isn't tracked anymore once its inclusion is final.
+    assert!(pending
+        .on_head(expires_at, expires_at, included, unlocked)
+        .is_empty());
+    assert!(pending.is_empty());
+}